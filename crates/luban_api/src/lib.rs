@@ -6,6 +6,10 @@ pub const PROTOCOL_VERSION: u32 = 1;
 #[serde(transparent)]
 pub struct ProjectId(pub String);
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ProjectGroupId(pub u64);
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 #[serde(transparent)]
 pub struct WorkspaceId(pub u64);
@@ -18,6 +22,9 @@ pub struct WorkspaceThreadId(pub u64);
 pub struct AppSnapshot {
     pub rev: u64,
     pub projects: Vec<ProjectSnapshot>,
+    /// Sidebar folders. Projects not listed in any group are shown ungrouped.
+    #[serde(default)]
+    pub project_groups: Vec<ProjectGroupSnapshot>,
     pub appearance: AppearanceSnapshot,
     #[serde(default)]
     pub agent: AgentSettingsSnapshot,
@@ -27,12 +34,91 @@ pub struct AppSnapshot {
     pub ui: UiSnapshot,
     #[serde(default)]
     pub integrations: IntegrationsSnapshot,
+    /// Number of agent turns currently running across all tasks.
+    #[serde(default)]
+    pub running_turns: u64,
+    /// Number of started turns waiting for a free slot under `max_concurrent_turns`.
+    #[serde(default)]
+    pub waiting_turns: u64,
+    /// Agent turns allowed to run at once; `0` means unlimited.
+    #[serde(default)]
+    pub max_concurrent_turns: u64,
+    /// Server-configured limits on stored conversation history; read-only.
+    #[serde(default)]
+    pub conversation_retention: ConversationRetentionSnapshot,
+    /// Idle days after which workspaces without an open PR or running turn are archived.
+    #[serde(default)]
+    pub auto_archive_after_days: Option<u32>,
+    /// Number of queued prompts waiting across all tasks.
+    #[serde(default)]
+    pub queued_prompts_total: u64,
+    #[serde(default)]
+    pub main_pane: MainPaneSnapshot,
+    /// Recent errors, oldest first. Dismiss individual entries with `ClientAction::DismissError`.
+    #[serde(default)]
+    pub errors: Vec<ErrorLogEntrySnapshot>,
+}
+
+/// Unset limits are not enforced; with both unset no history is pruned.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ConversationRetentionSnapshot {
+    #[serde(default)]
+    pub keep_last_entries: Option<u64>,
+    #[serde(default)]
+    pub max_age_days: Option<u64>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ProjectGroupSnapshot {
+    pub id: ProjectGroupId,
+    pub name: String,
+    pub project_ids: Vec<ProjectId>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ErrorLogEntrySnapshot {
+    pub message: String,
+    pub created_at_unix_ms: u64,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MainPaneSnapshot {
+    #[default]
+    None,
+    Dashboard,
+    ProjectSettings {
+        project_id: ProjectId,
+    },
+    #[serde(rename = "workdir", alias = "workspace")]
+    Workspace {
+        #[serde(rename = "workdir_id", alias = "workspace_id")]
+        workspace_id: WorkspaceId,
+    },
 }
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct IntegrationsSnapshot {
     #[serde(default)]
     pub telegram: TelegramIntegrationSnapshot,
+    #[serde(default)]
+    pub webhook: WebhookIntegrationSnapshot,
+}
+
+/// HTTP webhook that receives turn-completion and PR-ready notifications. The secret is never sent.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct WebhookIntegrationSnapshot {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub has_secret: bool,
+    #[serde(default)]
+    pub config_rev: u64,
+    /// Error of the last failed delivery; cleared by the next successful one.
+    #[serde(default)]
+    pub last_error: Option<String>,
 }
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
@@ -43,14 +129,37 @@ pub struct TelegramIntegrationSnapshot {
     pub has_token: bool,
     #[serde(default)]
     pub bot_username: Option<String>,
-    #[serde(default)]
-    pub paired_chat_id: Option<i64>,
+    /// Chats that receive notifications, in pairing order. Also accepts the legacy single
+    /// `paired_chat_id` field.
+    #[serde(
+        default,
+        alias = "paired_chat_id",
+        deserialize_with = "deserialize_paired_chat_ids"
+    )]
+    pub paired_chat_ids: Vec<i64>,
     #[serde(default)]
     pub config_rev: u64,
     #[serde(default)]
     pub last_error: Option<String>,
 }
 
+fn deserialize_paired_chat_ids<'de, D>(deserializer: D) -> Result<Vec<i64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum PairedChatIds {
+        Many(Vec<i64>),
+        One(Option<i64>),
+    }
+
+    Ok(match PairedChatIds::deserialize(deserializer)? {
+        PairedChatIds::Many(ids) => ids,
+        PairedChatIds::One(id) => id.into_iter().collect(),
+    })
+}
+
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct UiSnapshot {
     #[serde(default)]
@@ -88,12 +197,36 @@ pub struct AgentSettingsSnapshot {
     pub default_model_id: Option<String>,
     #[serde(default)]
     pub runner_default_models: std::collections::HashMap<String, String>,
+    /// Text placed before the user prompt for a runner, keyed by runner name.
+    #[serde(default)]
+    pub runner_prompt_prefixes: std::collections::HashMap<String, String>,
+    /// Text placed after the user prompt for a runner, keyed by runner name.
+    #[serde(default)]
+    pub runner_prompt_suffixes: std::collections::HashMap<String, String>,
+    /// Highest thinking effort each model honours, keyed by model id.
+    #[serde(default)]
+    pub model_thinking_effort_caps: std::collections::HashMap<String, ThinkingEffort>,
     #[serde(default)]
     pub default_thinking_effort: Option<ThinkingEffort>,
     #[serde(default)]
     pub default_runner: Option<AgentRunnerKind>,
     #[serde(default)]
-    pub amp_mode: Option<String>,
+    pub amp_mode: Option<AmpMode>,
+    #[serde(default)]
+    pub merge_reasoning_entries: bool,
+    #[serde(default)]
+    pub pause_queue_on_patch_failure: bool,
+    #[serde(default)]
+    pub item_dedup_window: u64,
+    #[serde(default)]
+    pub auto_retry_on_failure: u8,
+    /// Byte cap on command output kept per command execution; `0` means unlimited.
+    #[serde(default = "default_command_output_max_bytes")]
+    pub command_output_max_bytes: u64,
+}
+
+fn default_command_output_max_bytes() -> u64 {
+    256 * 1024
 }
 
 fn default_true() -> bool {
@@ -109,9 +242,17 @@ impl Default for AgentSettingsSnapshot {
             droid_enabled: true,
             default_model_id: None,
             runner_default_models: std::collections::HashMap::new(),
+            runner_prompt_prefixes: std::collections::HashMap::new(),
+            runner_prompt_suffixes: std::collections::HashMap::new(),
+            model_thinking_effort_caps: std::collections::HashMap::new(),
             default_thinking_effort: None,
             default_runner: None,
             amp_mode: None,
+            merge_reasoning_entries: false,
+            pause_queue_on_patch_failure: false,
+            item_dedup_window: 0,
+            auto_retry_on_failure: 0,
+            command_output_max_bytes: default_command_output_max_bytes(),
         }
     }
 }
@@ -125,6 +266,77 @@ pub enum AgentRunnerKind {
     Droid,
 }
 
+/// Amp agent mode, serialized as its plain string.
+///
+/// Strings outside the supported modes deserialize into `Other` so stored values keep loading;
+/// the engine refuses to start an Amp turn with one of them.
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum AmpMode {
+    Smart,
+    Rush,
+    Other(String),
+}
+
+impl AmpMode {
+    pub fn parse(value: &str) -> Self {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("smart") {
+            Self::Smart
+        } else if value.eq_ignore_ascii_case("rush") {
+            Self::Rush
+        } else {
+            Self::Other(value.to_owned())
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Smart => "smart",
+            Self::Rush => "rush",
+            Self::Other(value) => value,
+        }
+    }
+
+    pub fn is_known(&self) -> bool {
+        !matches!(self, Self::Other(_))
+    }
+}
+
+impl From<String> for AmpMode {
+    fn from(value: String) -> Self {
+        Self::parse(&value)
+    }
+}
+
+impl From<AmpMode> for String {
+    fn from(mode: AmpMode) -> Self {
+        mode.as_str().to_owned()
+    }
+}
+
+#[cfg(test)]
+mod amp_mode_tests {
+    use super::AmpMode;
+
+    #[test]
+    fn amp_mode_roundtrips_as_string_and_keeps_unknown_values() {
+        let json = serde_json::to_string(&AmpMode::Rush).expect("serialize");
+        assert_eq!(json, "\"rush\"");
+
+        let parsed: AmpMode = serde_json::from_str("\"Smart\"").expect("deserialize");
+        assert_eq!(parsed, AmpMode::Smart);
+
+        let parsed: AmpMode = serde_json::from_str("\"turbo\"").expect("deserialize");
+        assert_eq!(parsed, AmpMode::Other("turbo".to_owned()));
+        assert!(!parsed.is_known());
+        assert_eq!(
+            serde_json::to_string(&parsed).expect("serialize"),
+            "\"turbo\""
+        );
+    }
+}
+
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct TaskSettingsSnapshot {
     #[serde(default)]
@@ -150,6 +362,8 @@ pub enum SystemTaskKind {
     RenameBranch,
     AutoTitleThread,
     AutoUpdateTaskStatus,
+    PrDescription,
+    ConversationSummary,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -247,12 +461,113 @@ pub struct ProjectSnapshot {
     #[serde(default)]
     pub is_git: bool,
     pub expanded: bool,
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+    /// Directory new worktrees are created under; `None` uses the default location.
+    #[serde(default)]
+    pub worktree_root: Option<String>,
+    /// Template new branch names are rendered from (`{name}` = workdir, `{slug}` = project,
+    /// `{user}`); `None` uses `luban/{name}`.
+    #[serde(default)]
+    pub branch_name_template: Option<String>,
+    /// Git remote pull requests are looked up in; `None` lets `gh` resolve it.
+    #[serde(default)]
+    pub pr_remote: Option<String>,
+    /// Base branch pull requests must target; `None` accepts any base.
+    #[serde(default)]
+    pub pr_base_branch: Option<String>,
+    /// Archived projects are omitted from `AppSnapshot.projects` unless explicitly requested.
+    #[serde(default)]
+    pub status: ProjectStatus,
+    #[serde(default)]
+    pub agent_defaults: ProjectAgentDefaultsSnapshot,
+    /// Named command snippets for the terminal palette, launched via `TerminalCommandStart`.
+    #[serde(default)]
+    pub saved_commands: Vec<ProjectSavedCommandSnapshot>,
+    /// Environment variables set on the project's agent runs. Values are never sent.
+    #[serde(default)]
+    pub agent_env: Vec<ProjectAgentEnvVarSnapshot>,
     #[serde(rename = "create_workdir_status", alias = "create_workspace_status")]
     pub create_workspace_status: OperationStatus,
     #[serde(rename = "workdirs", alias = "workspaces")]
     pub workspaces: Vec<WorkspaceSnapshot>,
 }
 
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ProjectSavedCommandSnapshot {
+    pub id: u64,
+    pub label: String,
+    pub command: String,
+}
+
+/// A project agent environment variable with its value masked.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ProjectAgentEnvVarSnapshot {
+    pub key: String,
+    /// Whether the stored value is non-empty.
+    pub has_value: bool,
+}
+
+/// An entry of `ClientAction::SetProjectAgentEnv`; a `null` value keeps the stored value.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ProjectAgentEnvVar {
+    pub key: String,
+    #[serde(default)]
+    pub value: Option<String>,
+}
+
+/// Run config new tasks in a project start with; `None` fields fall back to `AgentSettingsSnapshot`.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ProjectAgentDefaultsSnapshot {
+    #[serde(default)]
+    pub runner: Option<AgentRunnerKind>,
+    #[serde(default)]
+    pub model_id: Option<String>,
+    #[serde(default)]
+    pub thinking_effort: Option<ThinkingEffort>,
+    #[serde(default)]
+    pub amp_mode: Option<String>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TaskStatusSetResult {
+    #[serde(rename = "workdir_id", alias = "workspace_id")]
+    pub workspace_id: WorkspaceId,
+    #[serde(rename = "task_id", alias = "thread_id")]
+    pub thread_id: WorkspaceThreadId,
+    pub ok: bool,
+    /// Why the status was not applied; `None` when `ok`.
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+/// A conversation entry matching a workdir-wide search.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ConversationSearchMatch {
+    #[serde(rename = "task_id", alias = "thread_id")]
+    pub thread_id: WorkspaceThreadId,
+    pub entry_id: String,
+    /// Zero-based index of the entry in its task, usable as a page `before` cursor.
+    pub entry_index: u64,
+    pub snippet: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JsonlImportLineError {
+    /// 1-based line number within the imported text.
+    pub line: u64,
+    pub message: String,
+}
+
+/// Drift between `git worktree list` and the workdirs Luban tracks for a project.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct WorktreeAuditSnapshot {
+    /// Worktree paths git knows about that no workdir tracks.
+    pub orphaned: Vec<String>,
+    /// Tracked workdir worktree paths that are missing on disk.
+    pub dangling: Vec<String>,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct WorkspaceSnapshot {
     pub id: WorkspaceId,
@@ -260,14 +575,38 @@ pub struct WorkspaceSnapshot {
     #[serde(rename = "workdir_name", alias = "workspace_name")]
     pub workspace_name: String,
     pub branch_name: String,
+    /// True when the worktree HEAD is not on a branch; branch rename is rejected.
+    #[serde(default)]
+    pub detached_head: bool,
     #[serde(rename = "workdir_path", alias = "worktree_path")]
     pub worktree_path: String,
     pub status: WorkspaceStatus,
     pub archive_status: OperationStatus,
     pub branch_rename_status: OperationStatus,
+    /// Running while a stash push/pop is in flight for the workdir.
+    #[serde(default)]
+    pub stash_status: OperationStatus,
+    /// Running while a `WorkspaceCommit` is in flight for the workdir.
+    #[serde(default)]
+    pub commit_status: OperationStatus,
     pub agent_run_status: OperationStatus,
     pub has_unread_completion: bool,
     pub pull_request: Option<PullRequestSnapshot>,
+    /// Why the last pull request lookup failed, e.g. a configured base branch that does not
+    /// exist. `pull_request` keeps the last known value meanwhile.
+    #[serde(default)]
+    pub pull_request_error: Option<String>,
+    /// Tip commit of the workdir branch; `None` until loaded or when the repo has no commits.
+    #[serde(default)]
+    pub last_commit: Option<LastCommitSnapshot>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct LastCommitSnapshot {
+    pub sha: String,
+    pub subject: String,
+    pub author: String,
+    pub unix_time: u64,
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
@@ -317,6 +656,21 @@ pub struct WorkspaceDiffFileSnapshot {
     pub file: ChangedFileSnapshot,
     pub old_file: DiffFileContents,
     pub new_file: DiffFileContents,
+    #[serde(default)]
+    pub hunks: Vec<DiffHunkSnapshot>,
+    /// Set for files git treats as binary; their contents are empty and they have no hunks.
+    #[serde(default)]
+    pub binary: bool,
+}
+
+/// One unified-diff hunk; `lines` keep their leading ` `, `+` or `-` marker.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DiffHunkSnapshot {
+    pub old_start: u64,
+    pub old_lines: u64,
+    pub new_start: u64,
+    pub new_lines: u64,
+    pub lines: Vec<String>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -358,6 +712,14 @@ pub enum WorkspaceStatus {
     Archived,
 }
 
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProjectStatus {
+    #[default]
+    Active,
+    Archived,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ConversationSnapshot {
     pub rev: u64,
@@ -370,10 +732,23 @@ pub struct ConversationSnapshot {
     pub agent_runner: AgentRunnerKind,
     pub agent_model_id: String,
     pub thinking_effort: ThinkingEffort,
+    /// Effort the current or last turn actually ran at, after model caps were applied.
     #[serde(default)]
-    pub amp_mode: Option<String>,
+    pub run_thinking_effort: Option<ThinkingEffort>,
+    #[serde(default)]
+    pub amp_mode: Option<AmpMode>,
+    /// Claude-only run options; `None` unless `agent_runner` is Claude.
+    #[serde(default)]
+    pub claude_max_output_tokens: Option<u32>,
+    #[serde(default)]
+    pub claude_system_prompt: Option<String>,
+    /// Whether the worktree is committed after each successfully completed turn.
+    #[serde(default)]
+    pub auto_commit: bool,
     pub run_status: OperationStatus,
     #[serde(default)]
+    pub turn_status: TurnStatus,
+    #[serde(default)]
     pub run_started_at_unix_ms: Option<u64>,
     #[serde(default)]
     pub run_finished_at_unix_ms: Option<u64>,
@@ -390,6 +765,68 @@ pub struct ConversationSnapshot {
     pub queue_paused: bool,
     pub remote_thread_id: Option<String>,
     pub title: String,
+    #[serde(default)]
+    pub notes: String,
+    #[serde(default)]
+    pub cwd_subpath: Option<String>,
+    #[serde(default)]
+    pub hunk_comments: Vec<HunkCommentSnapshot>,
+    /// Ranges of earlier entries replaced by a `history_summarized` entry, oldest first.
+    #[serde(default)]
+    pub collapsed_ranges: Vec<CollapsedRangeSnapshot>,
+    /// Persisted composer text, used to rehydrate the input box after a restart.
+    #[serde(default)]
+    pub draft: String,
+    /// Entry id of a file change in the current or last turn that failed to apply.
+    #[serde(default)]
+    pub patch_apply_failed_entry_id: Option<String>,
+    /// Whether the last finished turn ran a command that exited non-zero.
+    #[serde(default)]
+    pub had_failed_commands: bool,
+    /// Item ids of those failed command executions, in order.
+    #[serde(default)]
+    pub failed_command_ids: Vec<String>,
+    /// Whether the workdir has uncommitted changes (always `false` for non-git projects).
+    #[serde(default)]
+    pub worktree_dirty: bool,
+    /// Token usage summed over every turn of the thread.
+    #[serde(default)]
+    pub total_usage: TokenUsageSnapshot,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TokenUsageSnapshot {
+    pub input_tokens: u64,
+    pub cached_input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HunkLineSide {
+    Old,
+    New,
+}
+
+/// Comment anchored to one line of a file changed by a `file_change` entry.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HunkCommentSnapshot {
+    pub entry_id: String,
+    pub path: String,
+    pub side: HunkLineSide,
+    pub line: u64,
+    pub text: String,
+    #[serde(default)]
+    pub created_at_unix_ms: u64,
+}
+
+/// Inclusive range of entries summarized by the system entry `summary_entry_id`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CollapsedRangeSnapshot {
+    pub start_entry_id: String,
+    pub end_entry_id: String,
+    pub entry_count: u64,
+    pub summary_entry_id: String,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -407,11 +844,18 @@ pub struct AgentRunConfigSnapshot {
     pub thinking_effort: ThinkingEffort,
     #[serde(default)]
     pub amp_mode: Option<String>,
+    /// Only set when `runner` is Claude.
+    #[serde(default)]
+    pub claude_max_output_tokens: Option<u32>,
+    /// Only set when `runner` is Claude.
+    #[serde(default)]
+    pub claude_system_prompt: Option<String>,
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum OperationStatus {
+    #[default]
     Idle,
     Running,
 }
@@ -432,7 +876,7 @@ pub enum TaskStatus {
 
 #[cfg(test)]
 mod task_status_tests {
-    use super::TaskStatus;
+    use super::{ClientAction, TaskStatus, WorkspaceId, WorkspaceThreadId};
 
     #[test]
     fn task_status_roundtrips_with_current_values() {
@@ -451,6 +895,31 @@ mod task_status_tests {
         let parsed: TaskStatus = serde_json::from_str("\"in_review\"").expect("deserialize");
         assert_eq!(parsed, TaskStatus::Validating);
     }
+
+    #[test]
+    fn bulk_set_task_status_accepts_task_status_set_bulk_spelling() {
+        let parsed: ClientAction = serde_json::from_value(serde_json::json!({
+            "type": "task_status_set_bulk",
+            "items": [[1, 2], [3, 4]],
+            "task_status": "done",
+        }))
+        .expect("deserialize");
+        let ClientAction::BulkSetTaskStatus {
+            targets,
+            task_status,
+        } = parsed
+        else {
+            panic!("expected BulkSetTaskStatus");
+        };
+        assert_eq!(
+            targets,
+            vec![
+                (WorkspaceId(1), WorkspaceThreadId(2)),
+                (WorkspaceId(3), WorkspaceThreadId(4)),
+            ]
+        );
+        assert_eq!(task_status, TaskStatus::Done);
+    }
 }
 
 #[cfg(test)]
@@ -503,10 +972,33 @@ pub enum OpenTarget {
     Vscode,
     Cursor,
     Zed,
+    Idea,
+    Sublime,
+    /// Terminal Neovim, opened in a new terminal window.
+    Neovim,
     Ghostty,
     Finder,
 }
 
+#[cfg(test)]
+mod open_target_tests {
+    use super::OpenTarget;
+
+    #[test]
+    fn open_target_roundtrips_as_snake_case() {
+        for (target, wire) in [
+            (OpenTarget::Vscode, "\"vscode\""),
+            (OpenTarget::Idea, "\"idea\""),
+            (OpenTarget::Sublime, "\"sublime\""),
+            (OpenTarget::Neovim, "\"neovim\""),
+        ] {
+            assert_eq!(serde_json::to_string(&target).expect("serialize"), wire);
+            let parsed: OpenTarget = serde_json::from_str(wire).expect("deserialize");
+            assert_eq!(parsed, target);
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum TaskIntentKind {
@@ -626,6 +1118,13 @@ impl Default for WorkspaceTabsSnapshot {
     }
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TabDirection {
+    Next,
+    Prev,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ConversationEntry {
@@ -659,6 +1158,17 @@ pub enum ConversationSystemEvent {
         #[serde(default)]
         explanation_markdown: String,
     },
+    TurnRetriedWithRunner {
+        runner: AgentRunnerKind,
+        model_id: String,
+    },
+    HistorySummarized {
+        summary_markdown: String,
+        collapsed_entries: u64,
+    },
+    HistoryPruned {
+        pruned_entries: u64,
+    },
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -702,6 +1212,14 @@ pub struct TerminalCommandFinished {
     pub output_byte_len: u64,
 }
 
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TerminalHistoryEntry {
+    pub command: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    pub started_at_unix_ms: u64,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AgentEventEntry {
     #[serde(default)]
@@ -721,12 +1239,17 @@ pub enum AgentEvent {
     TurnUsage {
         usage_json: Option<serde_json::Value>,
     },
+    TurnStarted {
+        at_unix_ms: u64,
+    },
     TurnDuration {
         duration_ms: u64,
     },
     TurnCanceled,
     TurnError {
         message: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        code: Option<String>,
     },
 }
 
@@ -744,6 +1267,10 @@ pub struct AttachmentRef {
     pub extension: String,
     pub mime: Option<String>,
     pub byte_len: u64,
+    /// Base64-encoded PNG preview (at most 128px per side), filled in for image attachments in
+    /// `ConversationSnapshot`s. `None` for other kinds and for images that could not be decoded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thumbnail_base64: Option<String>,
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
@@ -861,6 +1388,9 @@ pub enum WsServerMessage {
     Hello {
         protocol_version: u32,
         current_rev: u64,
+        /// Optional server features enabled for this connection (e.g. `auth`).
+        #[serde(default)]
+        capabilities: Vec<String>,
     },
     Ack {
         request_id: String,
@@ -900,7 +1430,18 @@ pub enum ClientAction {
     },
     TelegramBotTokenClear,
     TelegramPairStart,
-    TelegramUnpair,
+    /// Unpairs `chat_id`, or every paired chat when it is omitted.
+    TelegramUnpair {
+        #[serde(default)]
+        chat_id: Option<i64>,
+    },
+    /// Enables the webhook sink. A `null` secret keeps the stored one; a blank secret removes it.
+    WebhookConfigSet {
+        url: String,
+        #[serde(default)]
+        secret: Option<String>,
+    },
+    WebhookConfigClear,
     TaskStarSet {
         #[serde(rename = "workdir_id", alias = "workspace_id")]
         workspace_id: WorkspaceId,
@@ -908,6 +1449,8 @@ pub enum ClientAction {
         thread_id: WorkspaceThreadId,
         starred: bool,
     },
+    /// Requests every starred task across all workdirs, most recently updated first.
+    ListStarredTasks,
     TaskStatusSet {
         #[serde(rename = "workdir_id", alias = "workspace_id")]
         workspace_id: WorkspaceId,
@@ -915,6 +1458,21 @@ pub enum ClientAction {
         thread_id: WorkspaceThreadId,
         task_status: TaskStatus,
     },
+    /// Sets the task title by hand. Blank titles are rejected.
+    RenameThread {
+        #[serde(rename = "workdir_id", alias = "workspace_id")]
+        workspace_id: WorkspaceId,
+        #[serde(rename = "task_id", alias = "thread_id")]
+        thread_id: WorkspaceThreadId,
+        title: String,
+    },
+    /// Applies `task_status` to every target; answered with `ServerEvent::TaskStatusBulkSet`.
+    #[serde(alias = "task_status_set_bulk")]
+    BulkSetTaskStatus {
+        #[serde(alias = "items")]
+        targets: Vec<(WorkspaceId, WorkspaceThreadId)>,
+        task_status: TaskStatus,
+    },
     FeedbackSubmit {
         title: String,
         body: String,
@@ -926,18 +1484,131 @@ pub enum ClientAction {
     DeleteProject {
         project_id: ProjectId,
     },
+    /// Hides the project from the sidebar while keeping its workdirs and task history.
+    ArchiveProject {
+        project_id: ProjectId,
+    },
+    RestoreProject {
+        project_id: ProjectId,
+    },
     ToggleProjectExpanded {
         project_id: ProjectId,
     },
+    ProjectSystemPromptChanged {
+        project_id: ProjectId,
+        system_prompt: Option<String>,
+    },
+    /// Sets the directory new worktrees of the project are created under (`~` is expanded).
+    /// `null` or a blank path restores the default location; existing worktrees are not moved.
+    SetProjectWorktreeRoot {
+        project_id: ProjectId,
+        path: Option<String>,
+    },
+    /// Sets the template new workdir branches (and AI branch renames) of the project use.
+    /// `null` or a blank template restores `luban/{slug}`.
+    SetProjectBranchNameTemplate {
+        project_id: ProjectId,
+        template: Option<String>,
+    },
+    /// Replaces the environment variables set on the project's agent runs. They override
+    /// variables inherited from the server process. An entry with a `null` value keeps the
+    /// currently stored value for its key, so clients can edit the list without knowing values.
+    SetProjectAgentEnv {
+        project_id: ProjectId,
+        env: Vec<ProjectAgentEnvVar>,
+    },
+    /// Sets the git remote and base branch the project's pull requests are looked up with.
+    /// `null` or blank values restore `gh`'s default resolution.
+    SetProjectPullRequestTarget {
+        project_id: ProjectId,
+        #[serde(default)]
+        remote: Option<String>,
+        #[serde(default)]
+        base_branch: Option<String>,
+    },
+    /// Replaces the project's agent defaults for new tasks; `null` fields use the global
+    /// defaults. Existing tasks keep their run config.
+    SetProjectAgentDefaults {
+        project_id: ProjectId,
+        #[serde(default)]
+        runner: Option<AgentRunnerKind>,
+        #[serde(default)]
+        model_id: Option<String>,
+        #[serde(default)]
+        thinking_effort: Option<ThinkingEffort>,
+        #[serde(default)]
+        amp_mode: Option<String>,
+    },
+    /// Saves a named command for the project. A label already in use gets a numeric suffix
+    /// (`run tests-2`).
+    AddProjectSavedCommand {
+        project_id: ProjectId,
+        label: String,
+        command: String,
+    },
+    RemoveProjectSavedCommand {
+        project_id: ProjectId,
+        id: u64,
+    },
+    /// Requests the diff of `limit` changed files starting at `offset` (ordered by path); replied
+    /// to with `WorkspaceDiffPageReady`. `context_lines` defaults to 3.
+    GetWorkspaceDiffPage {
+        #[serde(rename = "workdir_id", alias = "workspace_id")]
+        workspace_id: WorkspaceId,
+        offset: u64,
+        limit: u64,
+        #[serde(default)]
+        context_lines: Option<u32>,
+    },
+    AuditWorktrees {
+        project_id: ProjectId,
+    },
+    PruneOrphanedWorktrees {
+        project_id: ProjectId,
+        #[serde(default)]
+        dry_run: bool,
+    },
     #[serde(rename = "create_workdir", alias = "create_workspace")]
     CreateWorkspace {
         project_id: ProjectId,
+        /// Preferred workdir name; `luban/` and `refs/heads/` prefixes are stripped and the name
+        /// is sanitized. A `-vN` suffix is added when it is taken.
+        #[serde(default)]
+        branch_name_hint: Option<String>,
+    },
+    /// Replies with `ServerEvent::WorkspacePreview` describing what `CreateWorkspace` with the
+    /// same hint would create, without fetching or creating anything.
+    #[serde(rename = "preview_workdir", alias = "preview_workspace")]
+    PreviewWorkspace {
+        project_id: ProjectId,
+        #[serde(default)]
+        branch_name_hint: Option<String>,
+    },
+    #[serde(
+        rename = "create_workdir_from_branch",
+        alias = "create_workspace_from_branch"
+    )]
+    CreateWorkspaceFromBranch {
+        project_id: ProjectId,
+        branch_name: String,
     },
     #[serde(rename = "open_workdir", alias = "open_workspace")]
     OpenWorkspace {
         #[serde(rename = "workdir_id", alias = "workspace_id")]
         workspace_id: WorkspaceId,
     },
+    /// Clears the workdir's unread turn completion badge without opening it.
+    #[serde(rename = "mark_workdir_read", alias = "mark_workspace_read")]
+    MarkWorkspaceRead {
+        #[serde(rename = "workdir_id", alias = "workspace_id")]
+        workspace_id: WorkspaceId,
+    },
+    /// Clears the unread turn completion badges of every workdir.
+    MarkAllRead,
+    /// Switches the main pane directly; referenced projects/workdirs must exist.
+    SetMainPane {
+        pane: MainPaneSnapshot,
+    },
     #[serde(rename = "open_workdir_in_ide", alias = "open_workspace_in_ide")]
     OpenWorkspaceInIde {
         #[serde(rename = "workdir_id", alias = "workspace_id")]
@@ -974,6 +1645,11 @@ pub enum ClientAction {
     EnsureMainWorkspace {
         project_id: ProjectId,
     },
+    /// Ensures the project's main workdir and at least one task in it, activates that task and
+    /// opens the workdir. Safe to repeat; replies with `ServerEvent::ProjectMainOpened`.
+    OpenProjectMain {
+        project_id: ProjectId,
+    },
     ChatModelChanged {
         #[serde(rename = "workdir_id", alias = "workspace_id")]
         workspace_id: WorkspaceId,
@@ -993,7 +1669,26 @@ pub enum ClientAction {
         workspace_id: WorkspaceId,
         #[serde(rename = "task_id", alias = "thread_id")]
         thread_id: WorkspaceThreadId,
-        amp_mode: String,
+        amp_mode: AmpMode,
+    },
+    /// Sets the Claude-only run options of a task; ignored by other runners.
+    ChatClaudeOptionsChanged {
+        #[serde(rename = "workdir_id", alias = "workspace_id")]
+        workspace_id: WorkspaceId,
+        #[serde(rename = "task_id", alias = "thread_id")]
+        thread_id: WorkspaceThreadId,
+        #[serde(default)]
+        max_output_tokens: Option<u32>,
+        #[serde(default)]
+        system_prompt: Option<String>,
+    },
+    /// Toggles committing the task's worktree after each successfully completed turn.
+    ChatAutoCommitChanged {
+        #[serde(rename = "workdir_id", alias = "workspace_id")]
+        workspace_id: WorkspaceId,
+        #[serde(rename = "task_id", alias = "thread_id")]
+        thread_id: WorkspaceThreadId,
+        enabled: bool,
     },
     ThinkingEffortChanged {
         #[serde(rename = "workdir_id", alias = "workspace_id")]
@@ -1002,6 +1697,38 @@ pub enum ClientAction {
         thread_id: WorkspaceThreadId,
         thinking_effort: ThinkingEffort,
     },
+    ChatDraftChanged {
+        #[serde(rename = "workdir_id", alias = "workspace_id")]
+        workspace_id: WorkspaceId,
+        #[serde(rename = "task_id", alias = "thread_id")]
+        thread_id: WorkspaceThreadId,
+        text: String,
+    },
+    ThreadNotesChanged {
+        #[serde(rename = "workdir_id", alias = "workspace_id")]
+        workspace_id: WorkspaceId,
+        #[serde(rename = "task_id", alias = "thread_id")]
+        thread_id: WorkspaceThreadId,
+        notes: String,
+    },
+    ThreadCwdSubpathChanged {
+        #[serde(rename = "workdir_id", alias = "workspace_id")]
+        workspace_id: WorkspaceId,
+        #[serde(rename = "task_id", alias = "thread_id")]
+        thread_id: WorkspaceThreadId,
+        cwd_subpath: Option<String>,
+    },
+    AddHunkComment {
+        #[serde(rename = "workdir_id", alias = "workspace_id")]
+        workspace_id: WorkspaceId,
+        #[serde(rename = "task_id", alias = "thread_id")]
+        thread_id: WorkspaceThreadId,
+        entry_id: String,
+        path: String,
+        side: HunkLineSide,
+        line: u64,
+        text: String,
+    },
     TerminalCommandStart {
         #[serde(rename = "workdir_id", alias = "workspace_id")]
         workspace_id: WorkspaceId,
@@ -1009,6 +1736,14 @@ pub enum ClientAction {
         thread_id: WorkspaceThreadId,
         command: String,
     },
+    /// Lists terminal commands recently run in the workdir, newest first, with consecutive
+    /// repeats collapsed. `limit` defaults to 50. Replies with `ServerEvent::TerminalHistory`.
+    ListTerminalHistory {
+        #[serde(rename = "workdir_id", alias = "workspace_id")]
+        workspace_id: WorkspaceId,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        limit: Option<u64>,
+    },
     SendAgentMessage {
         #[serde(rename = "workdir_id", alias = "workspace_id")]
         workspace_id: WorkspaceId,
@@ -1020,6 +1755,10 @@ pub enum ClientAction {
         runner: Option<AgentRunnerKind>,
         #[serde(default)]
         amp_mode: Option<String>,
+        /// `ConversationSnapshot.rev` the client last saw; the action is rejected when the
+        /// conversation has changed since.
+        #[serde(default)]
+        expected_rev: Option<u64>,
     },
     CancelAndSendAgentMessage {
         #[serde(rename = "workdir_id", alias = "workspace_id")]
@@ -1032,6 +1771,10 @@ pub enum ClientAction {
         runner: Option<AgentRunnerKind>,
         #[serde(default)]
         amp_mode: Option<String>,
+        /// `ConversationSnapshot.rev` the client last saw; the action is rejected when the
+        /// conversation has changed since.
+        #[serde(default)]
+        expected_rev: Option<u64>,
     },
     QueueAgentMessage {
         #[serde(rename = "workdir_id", alias = "workspace_id")]
@@ -1044,6 +1787,10 @@ pub enum ClientAction {
         runner: Option<AgentRunnerKind>,
         #[serde(default)]
         amp_mode: Option<String>,
+        /// `ConversationSnapshot.rev` the client last saw; the action is rejected when the
+        /// conversation has changed since.
+        #[serde(default)]
+        expected_rev: Option<u64>,
     },
     RemoveQueuedPrompt {
         #[serde(rename = "workdir_id", alias = "workspace_id")]
@@ -1051,6 +1798,10 @@ pub enum ClientAction {
         #[serde(rename = "task_id", alias = "thread_id")]
         thread_id: WorkspaceThreadId,
         prompt_id: u64,
+        /// `ConversationSnapshot.rev` the client last saw; the action is rejected when the
+        /// conversation has changed since.
+        #[serde(default)]
+        expected_rev: Option<u64>,
     },
     ReorderQueuedPrompt {
         #[serde(rename = "workdir_id", alias = "workspace_id")]
@@ -1059,6 +1810,10 @@ pub enum ClientAction {
         thread_id: WorkspaceThreadId,
         active_id: u64,
         over_id: u64,
+        /// `ConversationSnapshot.rev` the client last saw; the action is rejected when the
+        /// conversation has changed since.
+        #[serde(default)]
+        expected_rev: Option<u64>,
     },
     UpdateQueuedPrompt {
         #[serde(rename = "workdir_id", alias = "workspace_id")]
@@ -1070,6 +1825,16 @@ pub enum ClientAction {
         attachments: Vec<AttachmentRef>,
         model_id: String,
         thinking_effort: ThinkingEffort,
+        /// Runner to switch the queued prompt to; `None` keeps its current runner.
+        #[serde(default)]
+        runner: Option<AgentRunnerKind>,
+        /// Only applied when the resulting runner is `Amp`; cleared otherwise.
+        #[serde(default)]
+        amp_mode: Option<String>,
+        /// `ConversationSnapshot.rev` the client last saw; the action is rejected when the
+        /// conversation has changed since.
+        #[serde(default)]
+        expected_rev: Option<u64>,
     },
     #[serde(rename = "workdir_rename_branch", alias = "workspace_rename_branch")]
     WorkspaceRenameBranch {
@@ -1077,6 +1842,25 @@ pub enum ClientAction {
         workspace_id: WorkspaceId,
         branch_name: String,
     },
+    #[serde(rename = "workdir_stash_changes", alias = "workspace_stash_changes")]
+    WorkspaceStashChanges {
+        #[serde(rename = "workdir_id", alias = "workspace_id")]
+        workspace_id: WorkspaceId,
+    },
+    #[serde(rename = "workdir_stash_pop", alias = "workspace_stash_pop")]
+    WorkspaceStashPop {
+        #[serde(rename = "workdir_id", alias = "workspace_id")]
+        workspace_id: WorkspaceId,
+    },
+    /// Stages `paths` (every change when omitted) and commits them in the workdir.
+    #[serde(rename = "workdir_commit", alias = "workspace_commit")]
+    WorkspaceCommit {
+        #[serde(rename = "workdir_id", alias = "workspace_id")]
+        workspace_id: WorkspaceId,
+        message: String,
+        #[serde(default)]
+        paths: Option<Vec<String>>,
+    },
     #[serde(
         rename = "workdir_ai_rename_branch",
         alias = "workspace_ai_rename_branch"
@@ -1093,11 +1877,83 @@ pub enum ClientAction {
         #[serde(rename = "task_id", alias = "thread_id")]
         thread_id: WorkspaceThreadId,
     },
+    /// Approves or denies an `mcp_tool_call` item whose status is `awaiting_approval`; the
+    /// task's `turn_status` returns from `awaiting` to `running`.
+    RespondToToolApproval {
+        #[serde(rename = "workdir_id", alias = "workspace_id")]
+        workspace_id: WorkspaceId,
+        #[serde(rename = "task_id", alias = "thread_id")]
+        thread_id: WorkspaceThreadId,
+        call_id: String,
+        approved: bool,
+    },
+    CancelAndClearQueue {
+        #[serde(rename = "workdir_id", alias = "workspace_id")]
+        workspace_id: WorkspaceId,
+        #[serde(rename = "task_id", alias = "thread_id")]
+        thread_id: WorkspaceThreadId,
+        #[serde(default)]
+        expected_rev: Option<u64>,
+    },
+    RetryLastTurnWithRunner {
+        #[serde(rename = "workdir_id", alias = "workspace_id")]
+        workspace_id: WorkspaceId,
+        #[serde(rename = "task_id", alias = "thread_id")]
+        thread_id: WorkspaceThreadId,
+        runner: AgentRunnerKind,
+        model_id: String,
+        #[serde(default)]
+        amp_mode: Option<String>,
+        #[serde(default)]
+        cancel_first: bool,
+    },
     #[serde(rename = "create_task", alias = "create_workspace_thread")]
     CreateWorkspaceThread {
         #[serde(rename = "workdir_id", alias = "workspace_id")]
         workspace_id: WorkspaceId,
     },
+    /// Forks a task into a new one seeded with its history. Without `up_to_entry_id` only the user
+    /// messages are copied; otherwise every entry up to and including that entry is copied.
+    #[serde(rename = "duplicate_task", alias = "duplicate_workspace_thread")]
+    DuplicateWorkspaceThread {
+        #[serde(rename = "workdir_id", alias = "workspace_id")]
+        workspace_id: WorkspaceId,
+        #[serde(rename = "task_id", alias = "thread_id")]
+        thread_id: WorkspaceThreadId,
+        #[serde(default)]
+        up_to_entry_id: Option<String>,
+    },
+    /// Creates a new task holding the conversation entries of a JSONL export (one entry per line).
+    ImportThreadJsonl {
+        #[serde(rename = "workdir_id", alias = "workspace_id")]
+        workspace_id: WorkspaceId,
+        jsonl: String,
+    },
+    /// Case-insensitive search over message text and command output of the workdir's tasks,
+    /// or of a single task when `thread_id` is set. An empty query yields no matches.
+    SearchConversations {
+        #[serde(rename = "workdir_id", alias = "workspace_id")]
+        workspace_id: WorkspaceId,
+        query: String,
+        #[serde(default, rename = "task_id", alias = "thread_id")]
+        thread_id: Option<WorkspaceThreadId>,
+    },
+    /// Summarizes the workdir changes and the task's agent messages into a markdown PR body.
+    GeneratePrDescription {
+        #[serde(rename = "workdir_id", alias = "workspace_id")]
+        workspace_id: WorkspaceId,
+        #[serde(rename = "task_id", alias = "thread_id")]
+        thread_id: WorkspaceThreadId,
+    },
+    /// Asks the task's agent runner to summarize the entries before `before_entry_id` (starting
+    /// after the last collapsed range) and collapses them behind one summary entry.
+    CompactConversation {
+        #[serde(rename = "workdir_id", alias = "workspace_id")]
+        workspace_id: WorkspaceId,
+        #[serde(rename = "task_id", alias = "thread_id")]
+        thread_id: WorkspaceThreadId,
+        before_entry_id: String,
+    },
     #[serde(rename = "activate_task", alias = "activate_workspace_thread")]
     ActivateWorkspaceThread {
         #[serde(rename = "workdir_id", alias = "workspace_id")]
@@ -1119,6 +1975,16 @@ pub enum ClientAction {
         #[serde(rename = "task_id", alias = "thread_id")]
         thread_id: WorkspaceThreadId,
     },
+    /// Moves a task, including its conversation history, queue and attachments, into another
+    /// workdir of the same project. The task must not be running.
+    MoveThread {
+        #[serde(rename = "from_workdir_id", alias = "from_workspace_id")]
+        from_workspace_id: WorkspaceId,
+        #[serde(rename = "task_id", alias = "thread_id")]
+        thread_id: WorkspaceThreadId,
+        #[serde(rename = "to_workdir_id", alias = "to_workspace_id")]
+        to_workspace_id: WorkspaceId,
+    },
     #[serde(rename = "restore_task_tab", alias = "restore_workspace_thread_tab")]
     RestoreWorkspaceThreadTab {
         #[serde(rename = "workdir_id", alias = "workspace_id")]
@@ -1134,6 +2000,14 @@ pub enum ClientAction {
         thread_id: WorkspaceThreadId,
         to_index: usize,
     },
+    /// Moves the active tab to the previous or next open tab, wrapping around at either end.
+    /// A workdir with a single open tab is left unchanged.
+    #[serde(rename = "activate_adjacent_task", alias = "activate_adjacent_thread")]
+    ActivateAdjacentThread {
+        #[serde(rename = "workdir_id", alias = "workspace_id")]
+        workspace_id: WorkspaceId,
+        direction: TabDirection,
+    },
     OpenButtonSelectionChanged {
         selection: String,
     },
@@ -1141,6 +2015,23 @@ pub enum ClientAction {
         #[serde(default)]
         project_ids: Vec<ProjectId>,
     },
+    CreateProjectGroup {
+        name: String,
+    },
+    RenameProjectGroup {
+        group_id: ProjectGroupId,
+        name: String,
+    },
+    /// Removes the group; its projects become ungrouped.
+    DeleteProjectGroup {
+        group_id: ProjectGroupId,
+    },
+    /// Moves the project into `group_id`, or out of every group when `None`.
+    AssignProjectToGroup {
+        project_id: ProjectId,
+        #[serde(default)]
+        group_id: Option<ProjectGroupId>,
+    },
     AppearanceThemeChanged {
         theme: AppearanceTheme,
     },
@@ -1162,11 +2053,44 @@ pub enum ClientAction {
     DroidEnabledChanged {
         enabled: bool,
     },
+    MergeReasoningEntriesChanged {
+        enabled: bool,
+    },
+    PauseQueueOnPatchFailureChanged {
+        enabled: bool,
+    },
+    ItemDedupWindowChanged {
+        window: u64,
+    },
+    AutoRetryOnFailureChanged {
+        retries: u8,
+    },
+    CommandOutputMaxBytesChanged {
+        max_bytes: u64,
+    },
+    /// `None` or `Some(0)` disables inactivity auto-archive.
+    AutoArchiveConfigChanged {
+        days: Option<u32>,
+    },
+    RunnerPromptPrefixChanged {
+        runner: AgentRunnerKind,
+        prefix: String,
+    },
+    RunnerPromptSuffixChanged {
+        runner: AgentRunnerKind,
+        suffix: String,
+    },
+    /// `cap: None` removes the cap for `model_id`.
+    ModelThinkingEffortCapChanged {
+        model_id: String,
+        #[serde(default)]
+        cap: Option<ThinkingEffort>,
+    },
     AgentRunnerChanged {
         runner: AgentRunnerKind,
     },
     AgentAmpModeChanged {
-        mode: String,
+        mode: AmpMode,
     },
     TaskPromptTemplateChanged {
         intent_kind: TaskIntentKind,
@@ -1176,6 +2100,17 @@ pub enum ClientAction {
         kind: SystemTaskKind,
         template: String,
     },
+    /// Writes every task and system prompt template to `<dir>/<key>.md`.
+    ExportPromptTemplates {
+        dir: String,
+    },
+    /// Reads `<dir>/<key>.md` templates back; customized templates that differ are reported as
+    /// conflicts instead of being overwritten unless `force` is set.
+    ImportPromptTemplatesFromDir {
+        dir: String,
+        #[serde(default)]
+        force: bool,
+    },
     CodexCheck,
     CodexConfigTree,
     CodexConfigListDir {
@@ -1224,6 +2159,15 @@ pub enum ClientAction {
         path: String,
         contents: String,
     },
+    /// Cancel an in-flight request-scoped operation (config reads/writes, checks) started by the
+    /// action with the given `request_id`; its result events are not emitted.
+    CancelRequest {
+        request_id: String,
+    },
+    /// Dismiss one entry of `AppSnapshot.errors` by its index.
+    DismissError {
+        index: u64,
+    },
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -1254,6 +2198,10 @@ pub enum ServerEvent {
     },
     ConversationChanged {
         snapshot: Box<ConversationSnapshot>,
+        /// Set when the change comes from a streamed agent item: `true` when the newest item was
+        /// appended or updated (clients may auto-follow), `false` when an older item changed.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        follow: Option<bool>,
     },
     Toast {
         message: String,
@@ -1268,6 +2216,20 @@ pub enum ServerEvent {
         #[serde(rename = "workdir_id", alias = "workspace_id")]
         workspace_id: WorkspaceId,
     },
+    ProjectMainOpened {
+        request_id: String,
+        project_id: ProjectId,
+        #[serde(rename = "workdir_id", alias = "workspace_id")]
+        workspace_id: WorkspaceId,
+        #[serde(rename = "task_id", alias = "thread_id")]
+        thread_id: WorkspaceThreadId,
+    },
+    TerminalHistory {
+        request_id: String,
+        #[serde(rename = "workdir_id", alias = "workspace_id")]
+        workspace_id: WorkspaceId,
+        commands: Vec<TerminalHistoryEntry>,
+    },
     TaskExecuted {
         request_id: String,
         result: TaskExecuteResult,
@@ -1281,6 +2243,87 @@ pub enum ServerEvent {
         ok: bool,
         message: Option<String>,
     },
+    /// Reply to `GetWorkspaceDiffPage`; `total` counts every changed file of the workdir.
+    WorkspaceDiffPageReady {
+        request_id: String,
+        offset: u64,
+        total: u64,
+        diff: WorkspaceDiffSnapshot,
+    },
+    WorktreeAuditReady {
+        request_id: String,
+        project_id: ProjectId,
+        audit: WorktreeAuditSnapshot,
+    },
+    OrphanedWorktreesPruned {
+        request_id: String,
+        project_id: ProjectId,
+        dry_run: bool,
+        /// Orphaned worktree paths that were removed (or would be, for a dry run).
+        pruned: Vec<String>,
+    },
+    ThreadJsonlImported {
+        request_id: String,
+        #[serde(rename = "workdir_id", alias = "workspace_id")]
+        workspace_id: WorkspaceId,
+        #[serde(rename = "task_id", alias = "thread_id")]
+        thread_id: WorkspaceThreadId,
+        imported: u64,
+        /// Lines that were skipped because they could not be parsed.
+        errors: Vec<JsonlImportLineError>,
+    },
+    #[serde(rename = "workdir_preview", alias = "workspace_preview")]
+    WorkspacePreview {
+        request_id: String,
+        /// Send back as `CreateWorkspace.branch_name_hint` to create exactly this workdir.
+        #[serde(rename = "workdir_name", alias = "workspace_name")]
+        workspace_name: String,
+        branch_name: String,
+        worktree_path: String,
+    },
+    PrDescriptionReady {
+        request_id: String,
+        #[serde(rename = "workdir_id", alias = "workspace_id")]
+        workspace_id: WorkspaceId,
+        #[serde(rename = "task_id", alias = "thread_id")]
+        thread_id: WorkspaceThreadId,
+        /// Markdown PR body, or an explanatory message when `has_changes` is false.
+        body: String,
+        has_changes: bool,
+    },
+    ConversationCompacted {
+        request_id: String,
+        #[serde(rename = "workdir_id", alias = "workspace_id")]
+        workspace_id: WorkspaceId,
+        #[serde(rename = "task_id", alias = "thread_id")]
+        thread_id: WorkspaceThreadId,
+        collapsed_entries: u64,
+    },
+    ConversationSearchResults {
+        request_id: String,
+        /// Oldest task and entry first, capped by the server.
+        matches: Vec<ConversationSearchMatch>,
+    },
+    PromptTemplatesExported {
+        request_id: String,
+        dir: String,
+        /// Template keys written, one `<key>.md` file each.
+        written: Vec<String>,
+    },
+    PromptTemplatesImported {
+        request_id: String,
+        imported: Vec<String>,
+        conflicts: Vec<String>,
+    },
+    StarredTasksListed {
+        request_id: String,
+        tasks: Vec<TaskSummarySnapshot>,
+    },
+    TaskStatusBulkSet {
+        request_id: String,
+        /// One result per requested target, in request order.
+        results: Vec<TaskStatusSetResult>,
+    },
     AmpCheckReady {
         request_id: String,
         ok: bool,
@@ -1385,4 +2428,7 @@ pub struct ThreadMeta {
     pub turn_status: TurnStatus,
     #[serde(default)]
     pub last_turn_result: Option<TurnResult>,
+    /// Input plus output tokens used by the thread's turns so far.
+    #[serde(default)]
+    pub total_tokens: u64,
 }
@@ -0,0 +1,2 @@
+ALTER TABLE conversations
+  ADD COLUMN notes TEXT NOT NULL DEFAULT '';
@@ -0,0 +1,2 @@
+ALTER TABLE conversations
+  ADD COLUMN cwd_subpath TEXT;
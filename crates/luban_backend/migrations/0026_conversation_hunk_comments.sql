@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS conversation_hunk_comments (
+    id INTEGER PRIMARY KEY,
+    project_slug TEXT NOT NULL,
+    workspace_name TEXT NOT NULL,
+    thread_local_id INTEGER NOT NULL,
+    entry_id TEXT NOT NULL,
+    path TEXT NOT NULL,
+    side TEXT NOT NULL,
+    line INTEGER NOT NULL,
+    text TEXT NOT NULL,
+    created_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS conversation_hunk_comments_by_thread
+    ON conversation_hunk_comments (project_slug, workspace_name, thread_local_id, id);
@@ -0,0 +1,5 @@
+-- Intentionally a no-op: v27 rewrites JSON keys inside `app_settings_text`, which plain SQL cannot
+-- do, so the work is done by `migrate_scope_keys_v27` right after this file runs in
+-- `apply_migrations`. The file still exists because every schema version needs an entry in
+-- `MIGRATIONS` for `user_version` to advance through it.
+SELECT 1;
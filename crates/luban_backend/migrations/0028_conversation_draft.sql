@@ -0,0 +1,2 @@
+ALTER TABLE conversations
+  ADD COLUMN draft TEXT NOT NULL DEFAULT '';
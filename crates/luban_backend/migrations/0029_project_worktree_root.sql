@@ -0,0 +1,2 @@
+ALTER TABLE projects
+  ADD COLUMN worktree_root TEXT;
@@ -0,0 +1,2 @@
+ALTER TABLE projects
+  ADD COLUMN status INTEGER NOT NULL DEFAULT 0;
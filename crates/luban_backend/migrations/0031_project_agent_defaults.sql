@@ -0,0 +1,11 @@
+ALTER TABLE projects
+  ADD COLUMN agent_runner TEXT;
+
+ALTER TABLE projects
+  ADD COLUMN agent_model_id TEXT;
+
+ALTER TABLE projects
+  ADD COLUMN agent_thinking_effort TEXT;
+
+ALTER TABLE projects
+  ADD COLUMN agent_amp_mode TEXT;
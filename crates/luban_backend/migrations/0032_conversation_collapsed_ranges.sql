@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS conversation_collapsed_ranges (
+    id INTEGER PRIMARY KEY,
+    project_slug TEXT NOT NULL,
+    workspace_name TEXT NOT NULL,
+    thread_local_id INTEGER NOT NULL,
+    start_entry_id TEXT NOT NULL,
+    end_entry_id TEXT NOT NULL,
+    entry_count INTEGER NOT NULL,
+    summary_entry_id TEXT NOT NULL,
+    created_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS conversation_collapsed_ranges_by_thread
+    ON conversation_collapsed_ranges (project_slug, workspace_name, thread_local_id, id);
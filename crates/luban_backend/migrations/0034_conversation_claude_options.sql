@@ -0,0 +1,5 @@
+ALTER TABLE conversations
+  ADD COLUMN claude_max_output_tokens INTEGER;
+
+ALTER TABLE conversations
+  ADD COLUMN claude_system_prompt TEXT;
@@ -0,0 +1,2 @@
+ALTER TABLE projects
+  ADD COLUMN branch_name_template TEXT;
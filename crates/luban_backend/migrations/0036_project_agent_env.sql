@@ -0,0 +1,2 @@
+ALTER TABLE projects
+  ADD COLUMN agent_env TEXT;
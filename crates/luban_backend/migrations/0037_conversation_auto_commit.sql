@@ -0,0 +1,2 @@
+ALTER TABLE conversations
+  ADD COLUMN auto_commit INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,5 @@
+ALTER TABLE projects
+  ADD COLUMN pr_remote TEXT;
+
+ALTER TABLE projects
+  ADD COLUMN pr_base_branch TEXT;
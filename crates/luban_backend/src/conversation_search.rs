@@ -0,0 +1,101 @@
+use luban_domain::{AgentEvent, CodexThreadItem, ConversationEntry, UserEvent};
+
+/// Characters of context kept on each side of a match in a search snippet.
+const SNIPPET_CONTEXT_CHARS: usize = 60;
+
+/// `LIKE` pattern that pre-filters stored entries by their JSON payload. The query is
+/// JSON-escaped the same way the payload is, so quotes and backslashes still match.
+pub(crate) fn payload_like_pattern(query: &str) -> String {
+    let json = serde_json::to_string(query).unwrap_or_default();
+    let escaped_json = json
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .unwrap_or(&json);
+    let mut pattern = String::with_capacity(escaped_json.len() + 2);
+    pattern.push('%');
+    for ch in escaped_json.chars() {
+        if matches!(ch, '%' | '_' | '!') {
+            pattern.push('!');
+        }
+        pattern.push(ch);
+    }
+    pattern.push('%');
+    pattern
+}
+
+/// Texts a conversation search matches against: message bodies, commands and their output.
+pub(crate) fn searchable_texts(entry: &ConversationEntry) -> Vec<&str> {
+    match entry {
+        ConversationEntry::SystemEvent { .. } => Vec::new(),
+        ConversationEntry::UserEvent { event, .. } => match event {
+            UserEvent::Message { text, .. } => vec![text.as_str()],
+            UserEvent::TerminalCommandStarted { command, .. }
+            | UserEvent::TerminalCommandFinished { command, .. } => vec![command.as_str()],
+        },
+        ConversationEntry::AgentEvent { event, .. } => match event {
+            AgentEvent::Message { text, .. } => vec![text.as_str()],
+            AgentEvent::Item { item } => match item.as_ref() {
+                CodexThreadItem::AgentMessage { text, .. } => vec![text.as_str()],
+                CodexThreadItem::CommandExecution {
+                    command,
+                    aggregated_output,
+                    ..
+                } => vec![command.as_str(), aggregated_output.as_str()],
+                _ => Vec::new(),
+            },
+            _ => Vec::new(),
+        },
+    }
+}
+
+/// Snippet around the first case-insensitive occurrence of `query` in `texts`, if any.
+pub(crate) fn match_snippet(texts: &[&str], query: &str) -> Option<String> {
+    let needle = query
+        .chars()
+        .flat_map(char::to_lowercase)
+        .collect::<Vec<_>>();
+    texts.iter().find_map(|text| {
+        let (start, end) = find_case_insensitive(text, &needle)?;
+        Some(snippet(text, start, end))
+    })
+}
+
+fn find_case_insensitive(haystack: &str, needle: &[char]) -> Option<(usize, usize)> {
+    if needle.is_empty() {
+        return None;
+    }
+    let folded = haystack
+        .char_indices()
+        .flat_map(|(idx, ch)| {
+            ch.to_lowercase()
+                .map(move |lower| (idx, idx + ch.len_utf8(), lower))
+        })
+        .collect::<Vec<_>>();
+    folded
+        .windows(needle.len())
+        .find(|window| window.iter().map(|(_, _, ch)| ch).eq(needle.iter()))
+        .map(|window| (window[0].0, window[window.len() - 1].1))
+}
+
+fn snippet(text: &str, start: usize, end: usize) -> String {
+    let before = text[..start]
+        .char_indices()
+        .rev()
+        .nth(SNIPPET_CONTEXT_CHARS.saturating_sub(1))
+        .map(|(idx, _)| idx);
+    let after = text[end..]
+        .char_indices()
+        .nth(SNIPPET_CONTEXT_CHARS)
+        .map(|(idx, _)| end + idx);
+
+    let mut out = String::new();
+    if before.is_some_and(|idx| idx > 0) {
+        out.push('…');
+    }
+    let slice = &text[before.unwrap_or(0)..after.unwrap_or(text.len())];
+    out.push_str(&slice.split_whitespace().collect::<Vec<_>>().join(" "));
+    if after.is_some() {
+        out.push('…');
+    }
+    out
+}
@@ -1,3 +1,4 @@
+mod conversation_search;
 mod env;
 mod services;
 mod sqlite_store;
@@ -1,14 +1,15 @@
 use anyhow::{Context as _, anyhow};
 use luban_domain::paths;
 use luban_domain::{
-    AgentThreadEvent, AttachmentKind, AttachmentRef, ClaudeConfigEntry, CodexConfigEntry,
-    CodexThreadEvent, CodexThreadItem, ContextImage, ConversationEntry, ConversationSnapshot,
-    CreatedWorkspace, DroidConfigEntry, OpenTarget, PersistedAppState, ProjectWorkspaceService,
-    PullRequestCiState, PullRequestInfo, PullRequestState, RunAgentTurnRequest, SystemTaskKind,
-    TaskIntentKind,
+    AgentThreadEvent, AttachmentKind, AttachmentRef, BranchNameTemplate, ClaudeConfigEntry,
+    CodexConfigEntry, CodexThreadEvent, CodexThreadItem, ContextImage, ConversationEntry,
+    ConversationSearchMatch, ConversationSnapshot, CreatedWorkspace, DroidConfigEntry,
+    LastCommitInfo, OpenTarget, PersistedAppState, ProjectWorkspaceService, PullRequestCiState,
+    PullRequestInfo, PullRequestState, PullRequestTarget, RunAgentTurnRequest, SystemTaskKind,
+    TaskIntentKind, WorktreeAudit,
 };
 use std::{
-    collections::{HashMap, HashSet},
+    collections::HashMap,
     path::{Path, PathBuf},
     process::Command,
     sync::atomic::{AtomicBool, Ordering},
@@ -21,6 +22,7 @@ use claude_process::{ClaudeProcessKey, ClaudeThreadProcess};
 use crate::sqlite_store::{SqliteStore, SqliteStoreOptions};
 use crate::time::unix_epoch_nanos_now;
 
+mod agent_cwd;
 mod amp_cli;
 mod amp_mode;
 mod ansi;
@@ -36,6 +38,8 @@ mod config_file_io;
 mod config_path;
 mod config_tree;
 mod context_blobs;
+mod context_dir_import;
+mod conversation_import;
 mod conversations;
 mod droid_cli;
 mod feedback;
@@ -45,33 +49,46 @@ mod git_branch;
 mod github_url;
 mod open_command;
 mod prompt;
+mod prompt_template_files;
 mod pull_request;
 mod reconnect_notice;
 mod roots;
+mod runner_spawn;
 mod stream_json;
 mod task;
 #[cfg(test)]
 mod test_support;
 mod thread_io;
+mod workspace_from_branch;
 mod workspace_name;
+mod worktree_audit;
 use amp_cli::AmpTurnParams;
 use amp_mode::detect_amp_mode_from_config_root;
-use claude_cli::ClaudeTurnParams;
+use claude_cli::{ClaudeRunOptions, ClaudeTurnParams};
 use codex_cli::CodexTurnParams;
-use codex_thread::{codex_item_id, generate_turn_scope_id, qualify_codex_item, qualify_event};
+use codex_thread::{
+    TurnItemLog, TurnItemWrite, codex_item_id, generate_turn_scope_id, qualify_codex_item,
+    qualify_event,
+};
 use config_entries::{
     amp_entries_from_shallow, claude_entries_from_shallow, codex_entries_from_shallow,
     droid_entries_from_shallow,
 };
 use droid_cli::DroidTurnParams;
-use git_branch::{branch_exists, normalize_branch_suffix};
-use prompt::{format_amp_prompt, format_codex_prompt, resolve_prompt_attachments};
+use git_branch::{
+    branch_exists, branch_template_user, is_detached_head, normalize_branch_suffix,
+    remote_branch_exists, render_branch_name,
+};
+use prompt::{
+    format_amp_prompt, format_codex_prompt, prepend_system_prompt, resolve_prompt_attachments,
+};
 use pull_request::pull_request_ci_state_from_check_buckets;
 use reconnect_notice::is_transient_reconnect_notice;
 use roots::{
     resolve_amp_root, resolve_claude_root, resolve_codex_root, resolve_droid_root,
     resolve_luban_root,
 };
+use runner_spawn::runner_spawn_error_code;
 
 fn anyhow_error_to_string(e: anyhow::Error) -> String {
     format!("{e:#}")
@@ -129,11 +146,64 @@ impl GitWorkspaceService {
         workspace_name::generate_workspace_name()
     }
 
-    fn worktree_path(&self, project_slug: &str, workspace_name: &str) -> PathBuf {
-        let mut path = self.worktrees_root.clone();
-        path.push(project_slug);
-        path.push(workspace_name);
-        path
+    /// Directory holding a project's worktrees: `<root>/<project_slug>`, where `root` is the
+    /// project's configured worktree root or the default worktrees directory.
+    fn project_worktrees_dir(&self, worktree_root: Option<&Path>, project_slug: &str) -> PathBuf {
+        worktree_root
+            .unwrap_or(&self.worktrees_root)
+            .join(project_slug)
+    }
+
+    /// Picks the first free workspace name (the normalized hint, then `<hint>-vN`, then random
+    /// names) whose worktree path and branch (`luban/<name>` or the rendered
+    /// `branch_name_template`) do not exist yet. Does not write anything, so it backs both
+    /// `create_workspace` and `preview_workspace`.
+    fn propose_new_workspace(
+        &self,
+        project_path: &Path,
+        worktrees_dir: &Path,
+        branch_name_hint: Option<&str>,
+        branch_name_template: Option<&BranchNameTemplate>,
+    ) -> anyhow::Result<CreatedWorkspace> {
+        let user = branch_name_template
+            .filter(|template| template.template.contains("{user}"))
+            .map(|_| branch_template_user(project_path))
+            .unwrap_or_default();
+        let propose = |workspace_name: String| -> anyhow::Result<Option<CreatedWorkspace>> {
+            let branch_name = render_branch_name(branch_name_template, &workspace_name, &user)?;
+            let worktree_path = worktrees_dir.join(&workspace_name);
+            if worktree_path.exists() || branch_exists(project_path, &branch_name) {
+                return Ok(None);
+            }
+            Ok(Some(CreatedWorkspace {
+                workspace_name,
+                branch_name,
+                worktree_path,
+            }))
+        };
+
+        if let Some(hint) = branch_name_hint.and_then(normalize_branch_suffix) {
+            for attempt in 0..64 {
+                let workspace_name = if attempt == 0 {
+                    hint.clone()
+                } else {
+                    format!("{hint}-v{}", attempt + 1)
+                };
+                if let Some(proposed) = propose(workspace_name)? {
+                    return Ok(proposed);
+                }
+            }
+        }
+
+        for _ in 0..64 {
+            if let Some(proposed) = propose(self.generate_workspace_name()?)? {
+                return Ok(proposed);
+            }
+        }
+
+        Err(anyhow!(
+            "failed to generate a unique workspace name after retries"
+        ))
     }
 
     fn task_prompt_template_path(&self, kind: TaskIntentKind) -> PathBuf {
@@ -154,13 +224,15 @@ impl GitWorkspaceService {
 
     /// Get an existing Claude process for the given thread, or create a new one.
     ///
-    /// If the existing process is not alive, it will be cleaned up and a new one created.
+    /// If the existing process is not alive, or was spawned with different run options, it will
+    /// be cleaned up and a new one created.
     fn get_or_create_claude_process(
         &self,
         key: ClaudeProcessKey,
         worktree_path: &Path,
         thread_id: Option<&str>,
         add_dirs: &[PathBuf],
+        options: &ClaudeRunOptions,
     ) -> anyhow::Result<()> {
         let mut processes = self
             .claude_processes
@@ -169,16 +241,19 @@ impl GitWorkspaceService {
 
         // Check if we have an existing process
         if let Some(process) = processes.get(&key) {
-            if process.is_alive() {
+            if process.is_alive() && process.options() == options {
                 // Existing process is alive, nothing to do
                 return Ok(());
             }
-            // Process is dead, remove it
-            processes.remove(&key);
+            // Process is dead or stale, remove it
+            if let Some(mut process) = processes.remove(&key) {
+                process.shutdown();
+            }
         }
 
         // Create a new process
-        let process = ClaudeThreadProcess::spawn_and_warmup(worktree_path, thread_id, add_dirs)?;
+        let process =
+            ClaudeThreadProcess::spawn_and_warmup(worktree_path, thread_id, add_dirs, options)?;
         processes.insert(key, process);
 
         Ok(())
@@ -231,6 +306,58 @@ impl GitWorkspaceService {
         }
     }
 
+    /// Persists the token usage reported when a turn completes; empty usage is not recorded.
+    fn append_turn_usage(
+        &self,
+        project_slug: &str,
+        workspace_name: &str,
+        thread_local_id: u64,
+        usage: &luban_domain::CodexUsage,
+    ) -> anyhow::Result<()> {
+        if usage.is_empty() {
+            return Ok(());
+        }
+        self.sqlite.append_conversation_entries(
+            project_slug.to_owned(),
+            workspace_name.to_owned(),
+            thread_local_id,
+            vec![ConversationEntry::AgentEvent {
+                entry_id: String::new(),
+                created_at_unix_ms: 0,
+                runner: None,
+                event: luban_domain::AgentEvent::TurnUsage {
+                    usage: Some(usage.clone()),
+                },
+            }],
+        )
+    }
+
+    fn write_turn_item(
+        &self,
+        project_slug: &str,
+        workspace_name: &str,
+        thread_local_id: u64,
+        write: TurnItemWrite,
+    ) -> anyhow::Result<()> {
+        match write {
+            TurnItemWrite::Append(entry) => self.sqlite.append_conversation_entries(
+                project_slug.to_owned(),
+                workspace_name.to_owned(),
+                thread_local_id,
+                vec![entry],
+            ),
+            TurnItemWrite::Update(item) => self
+                .sqlite
+                .update_conversation_item(
+                    project_slug.to_owned(),
+                    workspace_name.to_owned(),
+                    thread_local_id,
+                    item,
+                )
+                .map(|_| ()),
+        }
+    }
+
     fn run_codex_turn_streamed_via_cli(
         &self,
         params: CodexTurnParams,
@@ -293,6 +420,7 @@ impl GitWorkspaceService {
             &params.worktree_path,
             params.thread_id.as_deref(),
             &params.add_dirs,
+            &params.options,
         )?;
 
         // Send the prompt via stdin
@@ -315,6 +443,7 @@ impl GitWorkspaceService {
                     &params.worktree_path,
                     params.thread_id.as_deref(),
                     &params.add_dirs,
+                    &params.options,
                 )?;
 
                 // Try again with the new process
@@ -395,119 +524,142 @@ impl ProjectWorkspaceService for GitWorkspaceService {
             .map_err(anyhow_error_to_string)
     }
 
+    fn detect_default_branch(&self, project_path: PathBuf) -> Result<Option<String>, String> {
+        if !project_path.exists() {
+            return Err(format!(
+                "project path does not exist: {}",
+                project_path.display()
+            ));
+        }
+        Ok(self.default_branch(&project_path))
+    }
+
     fn create_workspace(
         &self,
         project_path: PathBuf,
         project_slug: String,
         branch_name_hint: Option<String>,
+        worktree_root: Option<PathBuf>,
+        branch_name_template: Option<BranchNameTemplate>,
     ) -> Result<CreatedWorkspace, String> {
         let result: anyhow::Result<CreatedWorkspace> = (|| {
             let remote = "origin";
             self.run_git(&project_path, ["remote", "get-url", remote])
                 .with_context(|| format!("remote '{remote}' not found"))?;
 
-            self.run_git(&project_path, ["fetch", "--prune", remote, "main"])
-                .with_context(|| format!("failed to fetch '{remote}/main'"))?;
+            let base_branch = self
+                .default_branch(&project_path)
+                .unwrap_or_else(|| luban_domain::FALLBACK_DEFAULT_BRANCH_NAME.to_owned());
+            self.run_git(&project_path, ["fetch", "--prune", remote, &base_branch])
+                .with_context(|| format!("failed to fetch '{remote}/{base_branch}'"))?;
 
             let upstream_commit = self
                 .run_git(
                     &project_path,
-                    ["rev-parse", "--verify", "origin/main^{commit}"],
+                    [
+                        "rev-parse",
+                        "--verify",
+                        &format!("{remote}/{base_branch}^{{commit}}"),
+                    ],
                 )
-                .context("failed to resolve origin/main commit")?;
-
-            std::fs::create_dir_all(self.worktrees_root.join(&project_slug))
-                .context("failed to create worktrees root")?;
-
-            if let Some(hint) = branch_name_hint
-                .as_deref()
-                .and_then(normalize_branch_suffix)
-            {
-                for attempt in 0..64 {
-                    let workspace_name = if attempt == 0 {
-                        hint.clone()
-                    } else {
-                        format!("{hint}-v{}", attempt + 1)
-                    };
+                .with_context(|| format!("failed to resolve {remote}/{base_branch} commit"))?;
 
-                    let branch_name = format!("luban/{workspace_name}");
-                    let worktree_path = self.worktree_path(&project_slug, &workspace_name);
+            let worktrees_dir = self.project_worktrees_dir(worktree_root.as_deref(), &project_slug);
+            std::fs::create_dir_all(&worktrees_dir).context("failed to create worktrees root")?;
 
-                    if worktree_path.exists() {
-                        continue;
-                    }
-                    if branch_exists(&project_path, &branch_name) {
-                        continue;
-                    }
-
-                    self.run_git(
-                        &project_path,
-                        [
-                            "worktree",
-                            "add",
-                            "-b",
-                            &branch_name,
-                            worktree_path
-                                .to_str()
-                                .ok_or_else(|| anyhow!("invalid worktree path"))?,
-                            upstream_commit.trim(),
-                        ],
-                    )
-                    .with_context(|| {
-                        format!("failed to create worktree at {}", worktree_path.display())
-                    })?;
-
-                    return Ok(CreatedWorkspace {
-                        workspace_name,
-                        branch_name,
-                        worktree_path,
-                    });
-                }
-            }
+            let proposed = self.propose_new_workspace(
+                &project_path,
+                &worktrees_dir,
+                branch_name_hint.as_deref(),
+                branch_name_template.as_ref(),
+            )?;
 
-            for _ in 0..64 {
-                let workspace_name = self.generate_workspace_name()?;
-                let branch_name = format!("luban/{workspace_name}");
-                let worktree_path = self.worktree_path(&project_slug, &workspace_name);
+            self.run_git(
+                &project_path,
+                [
+                    "worktree",
+                    "add",
+                    "-b",
+                    &proposed.branch_name,
+                    proposed
+                        .worktree_path
+                        .to_str()
+                        .ok_or_else(|| anyhow!("invalid worktree path"))?,
+                    upstream_commit.trim(),
+                ],
+            )
+            .with_context(|| {
+                format!(
+                    "failed to create worktree at {}",
+                    proposed.worktree_path.display()
+                )
+            })?;
 
-                if worktree_path.exists() {
-                    continue;
-                }
+            Ok(proposed)
+        })();
 
-                if branch_exists(&project_path, &branch_name) {
-                    continue;
-                }
+        result.map_err(anyhow_error_to_string)
+    }
 
-                self.run_git(
-                    &project_path,
-                    [
-                        "worktree",
-                        "add",
-                        "-b",
-                        &branch_name,
-                        worktree_path
-                            .to_str()
-                            .ok_or_else(|| anyhow!("invalid worktree path"))?,
-                        upstream_commit.trim(),
-                    ],
-                )
-                .with_context(|| {
-                    format!("failed to create worktree at {}", worktree_path.display())
-                })?;
+    fn preview_workspace(
+        &self,
+        project_path: PathBuf,
+        project_slug: String,
+        branch_name_hint: Option<String>,
+        worktree_root: Option<PathBuf>,
+        branch_name_template: Option<BranchNameTemplate>,
+    ) -> Result<CreatedWorkspace, String> {
+        let worktrees_dir = self.project_worktrees_dir(worktree_root.as_deref(), &project_slug);
+        self.propose_new_workspace(
+            &project_path,
+            &worktrees_dir,
+            branch_name_hint.as_deref(),
+            branch_name_template.as_ref(),
+        )
+        .map_err(anyhow_error_to_string)
+    }
 
-                return Ok(CreatedWorkspace {
-                    workspace_name,
-                    branch_name,
-                    worktree_path,
-                });
-            }
+    fn create_workspace_from_branch(
+        &self,
+        project_path: PathBuf,
+        project_slug: String,
+        branch_name: String,
+        worktree_root: Option<PathBuf>,
+    ) -> Result<CreatedWorkspace, String> {
+        self.create_workspace_from_branch_internal(
+            &project_path,
+            &project_slug,
+            &branch_name,
+            worktree_root.as_deref(),
+        )
+        .map_err(anyhow_error_to_string)
+    }
 
-            Err(anyhow!(
-                "failed to generate a unique workspace name after retries"
-            ))
-        })();
+    fn audit_worktrees(
+        &self,
+        project_path: PathBuf,
+        tracked_worktree_paths: Vec<PathBuf>,
+    ) -> Result<WorktreeAudit, String> {
+        self.audit_worktrees_internal(&project_path, &tracked_worktree_paths)
+            .map_err(anyhow_error_to_string)
+    }
 
-        result.map_err(anyhow_error_to_string)
+    fn prune_orphaned_worktrees(
+        &self,
+        project_path: PathBuf,
+        project_slug: String,
+        worktree_root: Option<PathBuf>,
+        tracked_worktree_paths: Vec<PathBuf>,
+        dry_run: bool,
+    ) -> Result<Vec<PathBuf>, String> {
+        self.prune_orphaned_worktrees_internal(
+            &project_path,
+            &project_slug,
+            worktree_root.as_deref(),
+            &tracked_worktree_paths,
+            dry_run,
+        )
+        .map_err(anyhow_error_to_string)
     }
 
     fn open_workspace_in_ide(&self, worktree_path: PathBuf) -> Result<(), String> {
@@ -529,11 +681,17 @@ impl ProjectWorkspaceService for GitWorkspaceService {
 
             #[cfg(target_os = "macos")]
             {
-                let mut cmd = Command::new("open");
+                let mut cmd = Command::new(match target {
+                    OpenTarget::Neovim => "osascript",
+                    _ => "open",
+                });
                 let cmd_label: &'static str = match target {
                     OpenTarget::Vscode => "open -a Visual Studio Code",
                     OpenTarget::Cursor => "open -a Cursor",
                     OpenTarget::Zed => "open -a Zed",
+                    OpenTarget::Idea => "open -a IntelliJ IDEA",
+                    OpenTarget::Sublime => "open -a Sublime Text",
+                    OpenTarget::Neovim => "osascript (Terminal: nvim)",
                     OpenTarget::Ghostty => "open -a Ghostty",
                     OpenTarget::Finder => "open -R",
                 };
@@ -548,6 +706,16 @@ impl ProjectWorkspaceService for GitWorkspaceService {
                     OpenTarget::Zed => {
                         cmd.args(["-a", "Zed"]).arg(&worktree_path);
                     }
+                    OpenTarget::Idea => {
+                        cmd.args(["-a", "IntelliJ IDEA"]).arg(&worktree_path);
+                    }
+                    OpenTarget::Sublime => {
+                        cmd.args(["-a", "Sublime Text"]).arg(&worktree_path);
+                    }
+                    OpenTarget::Neovim => {
+                        cmd.arg("-e")
+                            .arg(open_command::terminal_nvim_applescript(&worktree_path));
+                    }
                     OpenTarget::Ghostty => {
                         cmd.args(["-a", "Ghostty"]);
                     }
@@ -568,10 +736,19 @@ impl ProjectWorkspaceService for GitWorkspaceService {
             #[cfg(target_os = "linux")]
             {
                 let command = open_command::linux_open_command(target, &worktree_path)?;
-                let status = Command::new(command.program)
-                    .args(&command.args)
-                    .status()
-                    .with_context(|| format!("failed to spawn '{}'", command.label))?;
+                let status = match Command::new(command.program).args(&command.args).status() {
+                    Ok(status) => status,
+                    Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                        return Err(anyhow!(
+                            "'{}' was not found on PATH; install its command-line launcher",
+                            command.program
+                        ));
+                    }
+                    Err(err) => {
+                        return Err(err)
+                            .with_context(|| format!("failed to spawn '{}'", command.label));
+                    }
+                };
                 if !status.success() {
                     return Err(anyhow!("'{}' exited with status: {status}", command.label));
                 }
@@ -647,6 +824,7 @@ impl ProjectWorkspaceService for GitWorkspaceService {
         &self,
         worktree_path: PathBuf,
         requested_branch_name: String,
+        branch_name_template: Option<BranchNameTemplate>,
     ) -> Result<String, String> {
         let result: anyhow::Result<String> = (|| {
             if !worktree_path.exists() {
@@ -656,6 +834,12 @@ impl ProjectWorkspaceService for GitWorkspaceService {
                 ));
             }
 
+            if is_detached_head(&worktree_path) {
+                return Err(anyhow!(
+                    "workspace is in detached HEAD state; check out a branch before renaming"
+                ));
+            }
+
             let current_branch = self
                 .run_git(&worktree_path, ["rev-parse", "--abbrev-ref", "HEAD"])
                 .context("failed to resolve current branch")?;
@@ -666,13 +850,20 @@ impl ProjectWorkspaceService for GitWorkspaceService {
                     current_branch
                 ));
             }
-            if current_branch == "main" {
+            if current_branch == "main"
+                || self.default_branch(&worktree_path).as_deref() == Some(current_branch)
+            {
                 return Err(anyhow!("refusing to rename main branch"));
             }
 
             let suffix = normalize_branch_suffix(&requested_branch_name)
                 .ok_or_else(|| anyhow!("invalid branch name"))?;
-            let normalized = format!("luban/{suffix}");
+            let user = branch_name_template
+                .as_ref()
+                .filter(|template| template.template.contains("{user}"))
+                .map(|_| branch_template_user(&worktree_path))
+                .unwrap_or_default();
+            let normalized = render_branch_name(branch_name_template.as_ref(), &suffix, &user)?;
             if normalized == current_branch {
                 return Ok(normalized);
             }
@@ -887,6 +1078,90 @@ impl ProjectWorkspaceService for GitWorkspaceService {
             .map_err(anyhow_error_to_string)
     }
 
+    fn import_conversation_entries(
+        &self,
+        project_slug: String,
+        workspace_name: String,
+        thread_id: u64,
+        entries: Vec<ConversationEntry>,
+    ) -> Result<(), String> {
+        self.import_conversation_entries_internal(project_slug, workspace_name, thread_id, entries)
+            .map_err(anyhow_error_to_string)
+    }
+
+    fn duplicate_conversation(
+        &self,
+        project_slug: String,
+        workspace_name: String,
+        source_thread_id: u64,
+        thread_id: u64,
+        up_to_entry_id: Option<String>,
+    ) -> Result<(), String> {
+        self.duplicate_conversation_internal(
+            project_slug,
+            workspace_name,
+            source_thread_id,
+            thread_id,
+            up_to_entry_id.as_deref(),
+        )
+        .map_err(anyhow_error_to_string)
+    }
+
+    fn conversation_entries_total(
+        &self,
+        project_slug: String,
+        workspace_name: String,
+        thread_id: u64,
+    ) -> Result<u64, String> {
+        self.sqlite
+            .count_conversation_entries(project_slug, workspace_name, thread_id)
+            .map_err(anyhow_error_to_string)
+    }
+
+    fn prune_conversation_entries(
+        &self,
+        project_slug: String,
+        workspace_name: String,
+        thread_id: u64,
+        policy: luban_domain::ConversationRetentionPolicy,
+        now_unix_seconds: u64,
+    ) -> Result<u64, String> {
+        self.sqlite
+            .prune_conversation_entries(
+                project_slug,
+                workspace_name,
+                thread_id,
+                policy,
+                now_unix_seconds,
+            )
+            .map_err(anyhow_error_to_string)
+    }
+
+    fn load_prompt_history(
+        &self,
+        project_slug: String,
+        workspace_name: String,
+        thread_id: u64,
+        limit: usize,
+    ) -> Result<Vec<String>, String> {
+        self.sqlite
+            .load_prompt_history(project_slug, workspace_name, thread_id, limit)
+            .map_err(anyhow_error_to_string)
+    }
+
+    fn search_conversations(
+        &self,
+        project_slug: String,
+        workspace_name: String,
+        query: String,
+        thread_id: Option<u64>,
+        limit: usize,
+    ) -> Result<Vec<ConversationSearchMatch>, String> {
+        self.sqlite
+            .search_conversations(project_slug, workspace_name, query, thread_id, limit)
+            .map_err(anyhow_error_to_string)
+    }
+
     fn delete_conversation_thread(
         &self,
         project_slug: String,
@@ -898,6 +1173,24 @@ impl ProjectWorkspaceService for GitWorkspaceService {
             .map_err(anyhow_error_to_string)
     }
 
+    fn move_conversation_thread(
+        &self,
+        project_slug: String,
+        workspace_name: String,
+        thread_id: u64,
+        to_workspace_name: String,
+        to_thread_id: u64,
+    ) -> Result<(), String> {
+        self.move_conversation_internal(
+            project_slug,
+            workspace_name,
+            thread_id,
+            to_workspace_name,
+            to_thread_id,
+        )
+        .map_err(anyhow_error_to_string)
+    }
+
     fn save_conversation_queue_state(
         &self,
         project_slug: String,
@@ -930,6 +1223,9 @@ impl ProjectWorkspaceService for GitWorkspaceService {
         model_id: String,
         thinking_effort: luban_domain::ThinkingEffort,
         amp_mode: Option<String>,
+        claude_max_output_tokens: Option<u32>,
+        claude_system_prompt: Option<String>,
+        auto_commit: bool,
     ) -> Result<(), String> {
         self.sqlite
             .save_conversation_run_config(
@@ -940,6 +1236,9 @@ impl ProjectWorkspaceService for GitWorkspaceService {
                 model_id,
                 thinking_effort,
                 amp_mode,
+                claude_max_output_tokens,
+                claude_system_prompt,
+                auto_commit,
             )
             .map_err(anyhow_error_to_string)
     }
@@ -956,6 +1255,66 @@ impl ProjectWorkspaceService for GitWorkspaceService {
             .map_err(anyhow_error_to_string)
     }
 
+    fn save_conversation_notes(
+        &self,
+        project_slug: String,
+        workspace_name: String,
+        thread_id: u64,
+        notes: String,
+    ) -> Result<(), String> {
+        self.sqlite
+            .save_conversation_notes(project_slug, workspace_name, thread_id, notes)
+            .map_err(anyhow_error_to_string)
+    }
+
+    fn save_conversation_draft(
+        &self,
+        project_slug: String,
+        workspace_name: String,
+        thread_id: u64,
+        draft: String,
+    ) -> Result<(), String> {
+        self.sqlite
+            .save_conversation_draft(project_slug, workspace_name, thread_id, draft)
+            .map_err(anyhow_error_to_string)
+    }
+
+    fn save_conversation_cwd_subpath(
+        &self,
+        project_slug: String,
+        workspace_name: String,
+        thread_id: u64,
+        cwd_subpath: Option<String>,
+    ) -> Result<(), String> {
+        self.sqlite
+            .save_conversation_cwd_subpath(project_slug, workspace_name, thread_id, cwd_subpath)
+            .map_err(anyhow_error_to_string)
+    }
+
+    fn save_conversation_hunk_comment(
+        &self,
+        project_slug: String,
+        workspace_name: String,
+        thread_id: u64,
+        comment: luban_domain::HunkComment,
+    ) -> Result<(), String> {
+        self.sqlite
+            .save_conversation_hunk_comment(project_slug, workspace_name, thread_id, comment)
+            .map_err(anyhow_error_to_string)
+    }
+
+    fn save_conversation_collapsed_range(
+        &self,
+        project_slug: String,
+        workspace_name: String,
+        thread_id: u64,
+        range: luban_domain::CollapsedRange,
+    ) -> Result<(), String> {
+        self.sqlite
+            .save_conversation_collapsed_range(project_slug, workspace_name, thread_id, range)
+            .map_err(anyhow_error_to_string)
+    }
+
     fn save_conversation_task_status_last_analyzed(
         &self,
         project_slug: String,
@@ -1032,6 +1391,23 @@ impl ProjectWorkspaceService for GitWorkspaceService {
         })
     }
 
+    fn context_image_preview_png(
+        &self,
+        project_slug: String,
+        workspace_name: String,
+        attachment_id: String,
+        extension: String,
+    ) -> Result<Option<Vec<u8>>, String> {
+        GitWorkspaceService::context_image_preview_png(
+            self,
+            &project_slug,
+            &workspace_name,
+            &attachment_id,
+            &extension,
+        )
+        .map_err(anyhow_error_to_string)
+    }
+
     fn store_context_text(
         &self,
         project_slug: String,
@@ -1116,7 +1492,27 @@ impl ProjectWorkspaceService for GitWorkspaceService {
             .map_err(anyhow_error_to_string)
     }
 
-    fn list_new_task_drafts(&self) -> Result<Vec<luban_domain::NewTaskDraft>, String> {
+    fn import_context_dir(
+        &self,
+        project_slug: String,
+        workspace_name: String,
+        dir: PathBuf,
+        include: Vec<String>,
+        exclude: Vec<String>,
+        max_file_bytes: u64,
+    ) -> Result<luban_domain::ContextDirImport, String> {
+        self.import_context_dir_internal(
+            &project_slug,
+            &workspace_name,
+            &dir,
+            &include,
+            &exclude,
+            max_file_bytes,
+        )
+        .map_err(anyhow_error_to_string)
+    }
+
+    fn list_new_task_drafts(&self) -> Result<Vec<luban_domain::NewTaskDraft>, String> {
         self.sqlite
             .list_new_task_drafts()
             .map_err(anyhow_error_to_string)
@@ -1185,18 +1581,31 @@ impl ProjectWorkspaceService for GitWorkspaceService {
             attachments,
             runner,
             amp_mode,
+            claude_max_output_tokens,
+            claude_system_prompt,
+            agent_env,
             model,
             model_reasoning_effort,
+            cwd_subpath,
+            system_prompt,
+            prompt_prefix,
+            prompt_suffix,
+            retry,
+            command_output_max_bytes,
+            merge_reasoning_entries,
         } = request;
 
         let turn_started_at = Instant::now();
         let turn_scope_id = generate_turn_scope_id();
         let duration_appended = Arc::new(AtomicBool::new(false));
-        let mut appended_item_ids = HashSet::<String>::new();
+        let mut item_log = TurnItemLog::new(command_output_max_bytes, merge_reasoning_entries);
         let mut saw_agent_message = false;
         let mut latest_visible_agent_message: Option<(String, String)> = None;
 
         let result: anyhow::Result<()> = (|| {
+            // Reason: reject a bad cwd before the prompt lands in history as if it had run.
+            let worktree_path =
+                agent_cwd::resolve_agent_cwd(&worktree_path, cwd_subpath.as_deref())?;
             self.ensure_conversation_internal(
                 project_slug.clone(),
                 workspace_name.clone(),
@@ -1224,18 +1633,31 @@ impl ProjectWorkspaceService for GitWorkspaceService {
                 existing_thread_id = Some(legacy_thread_id);
             }
 
-            self.sqlite.append_conversation_entries(
-                project_slug.clone(),
-                workspace_name.clone(),
-                thread_local_id,
-                vec![ConversationEntry::UserEvent {
+            let turn_started_at_unix_ms = Self::now_unix_millis();
+            let mut turn_entries = Vec::with_capacity(2);
+            if !retry {
+                turn_entries.push(ConversationEntry::UserEvent {
                     entry_id: String::new(),
                     created_at_unix_ms: 0,
                     event: luban_domain::UserEvent::Message {
                         text: prompt.clone(),
                         attachments: attachments.clone(),
                     },
-                }],
+                });
+            }
+            turn_entries.push(ConversationEntry::AgentEvent {
+                entry_id: String::new(),
+                created_at_unix_ms: turn_started_at_unix_ms,
+                runner: None,
+                event: luban_domain::AgentEvent::TurnStarted {
+                    at_unix_ms: turn_started_at_unix_ms,
+                },
+            });
+            self.sqlite.append_conversation_entries(
+                project_slug.clone(),
+                workspace_name.clone(),
+                thread_local_id,
+                turn_entries,
             )?;
 
             let resolved_thread_id = thread_id.or(existing_thread_id);
@@ -1253,12 +1675,20 @@ impl ProjectWorkspaceService for GitWorkspaceService {
                 .and_then(luban_domain::parse_agent_runner_kind)
                 .unwrap_or(runner);
             let use_amp = runner == luban_domain::AgentRunnerKind::Amp;
+            let agent_prompt = prepend_system_prompt(
+                system_prompt.as_deref(),
+                &luban_domain::apply_runner_prompt_affixes(
+                    prompt_prefix.as_deref(),
+                    &prompt,
+                    prompt_suffix.as_deref(),
+                ),
+            );
             let amp_prompt = if use_amp {
-                format_amp_prompt(&prompt, &prompt_attachments)
+                format_amp_prompt(&agent_prompt, &prompt_attachments)
             } else {
-                prompt.clone()
+                agent_prompt.clone()
             };
-            let codex_prompt = format_codex_prompt(&prompt, &prompt_attachments);
+            let codex_prompt = format_codex_prompt(&agent_prompt, &prompt_attachments);
 
             let env_amp_mode = std::env::var("LUBAN_AMP_MODE")
                 .ok()
@@ -1286,6 +1716,7 @@ impl ProjectWorkspaceService for GitWorkspaceService {
                         worktree_path: worktree_path.clone(),
                         prompt: amp_prompt,
                         mode: resolved_amp_mode.clone(),
+                        env: agent_env.clone(),
                     },
                     cancel.clone(),
                     |event| {
@@ -1332,39 +1763,22 @@ impl ProjectWorkspaceService for GitWorkspaceService {
                                     )?;
                                 }
                                 CodexThreadEvent::ItemCompleted { item } => {
-                                    let id = codex_item_id(item).to_owned();
-                                    if appended_item_ids.insert(id) {
-                                        let entry = match item {
-                                            CodexThreadItem::AgentMessage { id, text } => {
-                                                ConversationEntry::AgentEvent {
-                                                    entry_id: String::new(),
-                                                    created_at_unix_ms: 0,
-                                                    runner: None,
-                                                    event: luban_domain::AgentEvent::Message {
-                                                        id: id.clone(),
-                                                        text: text.clone(),
-                                                    },
-                                                }
-                                            }
-                                            _ => ConversationEntry::AgentEvent {
-                                                entry_id: String::new(),
-                                                created_at_unix_ms: 0,
-                                                runner: None,
-                                                event: luban_domain::AgentEvent::Item {
-                                                    item: Box::new(item.clone()),
-                                                },
-                                            },
-                                        };
-                                        self.sqlite.append_conversation_entries(
-                                            project_slug.clone(),
-                                            workspace_name.clone(),
+                                    if let Some(write) = item_log.completed(item) {
+                                        self.write_turn_item(
+                                            &project_slug,
+                                            &workspace_name,
                                             thread_local_id,
-                                            vec![entry],
+                                            write,
                                         )?;
                                     }
                                 }
                                 CodexThreadEvent::TurnCompleted { usage } => {
-                                    let _ = usage;
+                                    self.append_turn_usage(
+                                        &project_slug,
+                                        &workspace_name,
+                                        thread_local_id,
+                                        usage,
+                                    )?;
                                     if duration_appended_for_events
                                         .compare_exchange(
                                             false,
@@ -1406,6 +1820,7 @@ impl ProjectWorkspaceService for GitWorkspaceService {
                                             runner: None,
                                             event: luban_domain::AgentEvent::TurnError {
                                                 message: error.message.clone(),
+                                                code: error.code.clone(),
                                             },
                                         }],
                                     )?;
@@ -1450,6 +1865,7 @@ impl ProjectWorkspaceService for GitWorkspaceService {
                                             runner: None,
                                             event: luban_domain::AgentEvent::TurnError {
                                                 message: message.clone(),
+                                                code: None,
                                             },
                                         }],
                                     )?;
@@ -1481,9 +1897,11 @@ impl ProjectWorkspaceService for GitWorkspaceService {
                                     }
                                 }
                                 CodexThreadEvent::TurnStarted
-                                | CodexThreadEvent::TurnDuration { .. }
-                                | CodexThreadEvent::ItemStarted { .. }
-                                | CodexThreadEvent::ItemUpdated { .. } => {}
+                                | CodexThreadEvent::TurnDuration { .. } => {}
+                                CodexThreadEvent::ItemStarted { item }
+                                | CodexThreadEvent::ItemUpdated { item } => {
+                                    item_log.observe(item);
+                                }
                             }
                         }
 
@@ -1504,6 +1922,11 @@ impl ProjectWorkspaceService for GitWorkspaceService {
                         } else {
                             vec![blobs_dir.clone()]
                         },
+                        options: ClaudeRunOptions {
+                            max_output_tokens: claude_max_output_tokens,
+                            system_prompt: claude_system_prompt.clone(),
+                            env: agent_env.clone(),
+                        },
                     },
                     cancel.clone(),
                     |event| {
@@ -1550,39 +1973,22 @@ impl ProjectWorkspaceService for GitWorkspaceService {
                                     )?;
                                 }
                                 CodexThreadEvent::ItemCompleted { item } => {
-                                    let id = codex_item_id(item).to_owned();
-                                    if appended_item_ids.insert(id) {
-                                        let entry = match item {
-                                            CodexThreadItem::AgentMessage { id, text } => {
-                                                ConversationEntry::AgentEvent {
-                                                    entry_id: String::new(),
-                                                    created_at_unix_ms: 0,
-                                                    runner: None,
-                                                    event: luban_domain::AgentEvent::Message {
-                                                        id: id.clone(),
-                                                        text: text.clone(),
-                                                    },
-                                                }
-                                            }
-                                            _ => ConversationEntry::AgentEvent {
-                                                entry_id: String::new(),
-                                                created_at_unix_ms: 0,
-                                                runner: None,
-                                                event: luban_domain::AgentEvent::Item {
-                                                    item: Box::new(item.clone()),
-                                                },
-                                            },
-                                        };
-                                        self.sqlite.append_conversation_entries(
-                                            project_slug.clone(),
-                                            workspace_name.clone(),
+                                    if let Some(write) = item_log.completed(item) {
+                                        self.write_turn_item(
+                                            &project_slug,
+                                            &workspace_name,
                                             thread_local_id,
-                                            vec![entry],
+                                            write,
                                         )?;
                                     }
                                 }
                                 CodexThreadEvent::TurnCompleted { usage } => {
-                                    let _ = usage;
+                                    self.append_turn_usage(
+                                        &project_slug,
+                                        &workspace_name,
+                                        thread_local_id,
+                                        usage,
+                                    )?;
                                     if duration_appended_for_events
                                         .compare_exchange(
                                             false,
@@ -1624,6 +2030,7 @@ impl ProjectWorkspaceService for GitWorkspaceService {
                                             runner: None,
                                             event: luban_domain::AgentEvent::TurnError {
                                                 message: error.message.clone(),
+                                                code: error.code.clone(),
                                             },
                                         }],
                                     )?;
@@ -1668,6 +2075,7 @@ impl ProjectWorkspaceService for GitWorkspaceService {
                                             runner: None,
                                             event: luban_domain::AgentEvent::TurnError {
                                                 message: message.clone(),
+                                                code: None,
                                             },
                                         }],
                                     )?;
@@ -1699,9 +2107,11 @@ impl ProjectWorkspaceService for GitWorkspaceService {
                                     }
                                 }
                                 CodexThreadEvent::TurnStarted
-                                | CodexThreadEvent::TurnDuration { .. }
-                                | CodexThreadEvent::ItemStarted { .. }
-                                | CodexThreadEvent::ItemUpdated { .. } => {}
+                                | CodexThreadEvent::TurnDuration { .. } => {}
+                                CodexThreadEvent::ItemStarted { item }
+                                | CodexThreadEvent::ItemUpdated { item } => {
+                                    item_log.observe(item);
+                                }
                             }
                         }
 
@@ -1717,6 +2127,7 @@ impl ProjectWorkspaceService for GitWorkspaceService {
                         model: model.clone(),
                         reasoning_effort: model_reasoning_effort.clone(),
                         auto_level: None,
+                        env: agent_env.clone(),
                     },
                     cancel.clone(),
                     |event| {
@@ -1741,39 +2152,22 @@ impl ProjectWorkspaceService for GitWorkspaceService {
                                 )?;
                             }
                             CodexThreadEvent::ItemCompleted { item } => {
-                                let id = codex_item_id(item).to_owned();
-                                if appended_item_ids.insert(id) {
-                                    let entry = match item {
-                                        CodexThreadItem::AgentMessage { id, text } => {
-                                            ConversationEntry::AgentEvent {
-                                                entry_id: String::new(),
-                                                created_at_unix_ms: 0,
-                                                runner: None,
-                                                event: luban_domain::AgentEvent::Message {
-                                                    id: id.clone(),
-                                                    text: text.clone(),
-                                                },
-                                            }
-                                        }
-                                        _ => ConversationEntry::AgentEvent {
-                                            entry_id: String::new(),
-                                            created_at_unix_ms: 0,
-                                            runner: None,
-                                            event: luban_domain::AgentEvent::Item {
-                                                item: Box::new(item.clone()),
-                                            },
-                                        },
-                                    };
-                                    self.sqlite.append_conversation_entries(
-                                        project_slug.clone(),
-                                        workspace_name.clone(),
+                                if let Some(write) = item_log.completed(item) {
+                                    self.write_turn_item(
+                                        &project_slug,
+                                        &workspace_name,
                                         thread_local_id,
-                                        vec![entry],
+                                        write,
                                     )?;
                                 }
                             }
                             CodexThreadEvent::TurnCompleted { usage } => {
-                                let _ = usage;
+                                self.append_turn_usage(
+                                    &project_slug,
+                                    &workspace_name,
+                                    thread_local_id,
+                                    usage,
+                                )?;
                                 if duration_appended_for_events
                                     .compare_exchange(
                                         false,
@@ -1814,6 +2208,7 @@ impl ProjectWorkspaceService for GitWorkspaceService {
                                         runner: None,
                                         event: luban_domain::AgentEvent::TurnError {
                                             message: error.message.clone(),
+                                            code: error.code.clone(),
                                         },
                                     }],
                                 )?;
@@ -1857,6 +2252,7 @@ impl ProjectWorkspaceService for GitWorkspaceService {
                                         runner: None,
                                         event: luban_domain::AgentEvent::TurnError {
                                             message: message.clone(),
+                                            code: None,
                                         },
                                     }],
                                 )?;
@@ -1887,9 +2283,11 @@ impl ProjectWorkspaceService for GitWorkspaceService {
                                 }
                             }
                             CodexThreadEvent::TurnStarted
-                            | CodexThreadEvent::TurnDuration { .. }
-                            | CodexThreadEvent::ItemStarted { .. }
-                            | CodexThreadEvent::ItemUpdated { .. } => {}
+                            | CodexThreadEvent::TurnDuration { .. } => {}
+                            CodexThreadEvent::ItemStarted { item }
+                            | CodexThreadEvent::ItemUpdated { item } => {
+                                item_log.observe(item);
+                            }
                         }
 
                         Ok(())
@@ -1905,6 +2303,7 @@ impl ProjectWorkspaceService for GitWorkspaceService {
                         model: model.clone(),
                         model_reasoning_effort: model_reasoning_effort.clone(),
                         sandbox_mode: None,
+                        env: agent_env.clone(),
                     },
                     cancel.clone(),
                     |event| {
@@ -1951,39 +2350,22 @@ impl ProjectWorkspaceService for GitWorkspaceService {
                                     )?;
                                 }
                                 CodexThreadEvent::ItemCompleted { item } => {
-                                    let id = codex_item_id(item).to_owned();
-                                    if appended_item_ids.insert(id) {
-                                        let entry = match item {
-                                            CodexThreadItem::AgentMessage { id, text } => {
-                                                ConversationEntry::AgentEvent {
-                                                    entry_id: String::new(),
-                                                    created_at_unix_ms: 0,
-                                                    runner: None,
-                                                    event: luban_domain::AgentEvent::Message {
-                                                        id: id.clone(),
-                                                        text: text.clone(),
-                                                    },
-                                                }
-                                            }
-                                            _ => ConversationEntry::AgentEvent {
-                                                entry_id: String::new(),
-                                                created_at_unix_ms: 0,
-                                                runner: None,
-                                                event: luban_domain::AgentEvent::Item {
-                                                    item: Box::new(item.clone()),
-                                                },
-                                            },
-                                        };
-                                        self.sqlite.append_conversation_entries(
-                                            project_slug.clone(),
-                                            workspace_name.clone(),
+                                    if let Some(write) = item_log.completed(item) {
+                                        self.write_turn_item(
+                                            &project_slug,
+                                            &workspace_name,
                                             thread_local_id,
-                                            vec![entry],
+                                            write,
                                         )?;
                                     }
                                 }
                                 CodexThreadEvent::TurnCompleted { usage } => {
-                                    let _ = usage;
+                                    self.append_turn_usage(
+                                        &project_slug,
+                                        &workspace_name,
+                                        thread_local_id,
+                                        usage,
+                                    )?;
                                     if duration_appended_for_events
                                         .compare_exchange(
                                             false,
@@ -2025,6 +2407,7 @@ impl ProjectWorkspaceService for GitWorkspaceService {
                                             runner: None,
                                             event: luban_domain::AgentEvent::TurnError {
                                                 message: error.message.clone(),
+                                                code: error.code.clone(),
                                             },
                                         }],
                                     )?;
@@ -2069,6 +2452,7 @@ impl ProjectWorkspaceService for GitWorkspaceService {
                                             runner: None,
                                             event: luban_domain::AgentEvent::TurnError {
                                                 message: message.clone(),
+                                                code: None,
                                             },
                                         }],
                                     )?;
@@ -2100,9 +2484,11 @@ impl ProjectWorkspaceService for GitWorkspaceService {
                                     }
                                 }
                                 CodexThreadEvent::TurnStarted
-                                | CodexThreadEvent::TurnDuration { .. }
-                                | CodexThreadEvent::ItemStarted { .. }
-                                | CodexThreadEvent::ItemUpdated { .. } => {}
+                                | CodexThreadEvent::TurnDuration { .. } => {}
+                                CodexThreadEvent::ItemStarted { item }
+                                | CodexThreadEvent::ItemUpdated { item } => {
+                                    item_log.observe(item);
+                                }
                             }
                         }
 
@@ -2147,7 +2533,7 @@ impl ProjectWorkspaceService for GitWorkspaceService {
             }
 
             if let Some((id, text)) = latest_visible_agent_message.as_ref()
-                && !appended_item_ids.contains(id)
+                && !item_log.contains(id)
             {
                 self.sqlite.append_conversation_entries(
                     project_slug.clone(),
@@ -2163,7 +2549,7 @@ impl ProjectWorkspaceService for GitWorkspaceService {
                         },
                     }],
                 )?;
-                appended_item_ids.insert(id.clone());
+                item_log.insert(id.clone());
             }
 
             if let Some(message) = turn_error {
@@ -2196,6 +2582,8 @@ impl ProjectWorkspaceService for GitWorkspaceService {
                 );
                 on_event(CodexThreadEvent::TurnDuration { duration_ms });
             }
+            let message = format!("{err:#}");
+            let code = runner_spawn_error_code(err).map(str::to_owned);
             let _ = self.sqlite.append_conversation_entries(
                 project_slug.clone(),
                 workspace_name.clone(),
@@ -2205,15 +2593,52 @@ impl ProjectWorkspaceService for GitWorkspaceService {
                     created_at_unix_ms: 0,
                     runner: None,
                     event: luban_domain::AgentEvent::TurnError {
-                        message: format!("{err:#}"),
+                        message: message.clone(),
+                        code: code.clone(),
                     },
                 }],
             );
+            if code.is_some() {
+                on_event(CodexThreadEvent::TurnFailed {
+                    error: luban_domain::CodexThreadError { message, code },
+                });
+            }
         }
 
         result.map_err(anyhow_error_to_string)
     }
 
+    fn git_fetch(&self, worktree_path: PathBuf) -> Result<(), String> {
+        self.run_git(&worktree_path, ["fetch", "--prune", "origin"])
+            .map(|_| ())
+            .map_err(anyhow_error_to_string)
+    }
+
+    fn git_last_commit(&self, worktree_path: PathBuf) -> Result<Option<LastCommitInfo>, String> {
+        self.last_commit(&worktree_path)
+            .map_err(anyhow_error_to_string)
+    }
+
+    fn git_stash_push(&self, worktree_path: PathBuf) -> Result<bool, String> {
+        self.stash_push(&worktree_path)
+            .map_err(anyhow_error_to_string)
+    }
+
+    fn git_stash_pop(&self, worktree_path: PathBuf) -> Result<(), String> {
+        self.stash_pop(&worktree_path)
+            .map_err(anyhow_error_to_string)
+    }
+
+    fn git_commit(
+        &self,
+        worktree_path: PathBuf,
+        message: String,
+        paths: Option<Vec<String>>,
+    ) -> Result<String, String> {
+        self.commit(&worktree_path, &message, paths.as_deref())
+            .map_err(anyhow_error_to_string)
+    }
+
     fn gh_is_authorized(&self) -> Result<bool, String> {
         let output = Command::new("gh")
             .args(["auth", "status", "-h", "github.com"])
@@ -2225,6 +2650,7 @@ impl ProjectWorkspaceService for GitWorkspaceService {
     fn gh_pull_request_info(
         &self,
         worktree_path: PathBuf,
+        target: PullRequestTarget,
     ) -> Result<Option<PullRequestInfo>, String> {
         #[derive(Clone, serde::Deserialize)]
         struct GhPullRequestCheck {
@@ -2245,26 +2671,98 @@ impl ProjectWorkspaceService for GitWorkspaceService {
             review_decision: String,
         }
 
+        #[derive(serde::Deserialize)]
+        struct GhPullRequestListItem {
+            number: u64,
+        }
+
+        let repo = match target.remote.as_deref() {
+            Some(remote) => {
+                let url = self
+                    .run_git(&worktree_path, ["remote", "get-url", remote])
+                    .map_err(|_| format!("Git remote '{remote}' does not exist"))?;
+                let repo = Self::github_repo_id_from_remote_url(&url).ok_or_else(|| {
+                    format!("Git remote '{remote}' does not point at a GitHub repository")
+                })?;
+                Some(repo)
+            }
+            None => None,
+        };
+
+        let selector = if target == PullRequestTarget::default() {
+            None
+        } else {
+            let branch = self
+                .run_git(
+                    &worktree_path,
+                    ["symbolic-ref", "--quiet", "--short", "HEAD"],
+                )
+                .map_err(|_| "Workdir is not on a branch".to_owned())?;
+            match target.base_branch.as_deref() {
+                Some(base) => {
+                    let remote = target.remote.as_deref().unwrap_or("origin");
+                    if !remote_branch_exists(&worktree_path, remote, base)
+                        && !branch_exists(&worktree_path, base)
+                    {
+                        return Err(format!("Base branch '{base}' does not exist"));
+                    }
+                    let output = Command::new("gh")
+                        .args(pull_request::gh_pr_args(
+                            &[
+                                "list", "--head", &branch, "--base", base, "--state", "all",
+                                "--json", "number", "--limit", "1",
+                            ],
+                            None,
+                            repo.as_deref(),
+                        ))
+                        .current_dir(&worktree_path)
+                        .output();
+                    let Ok(output) = output else {
+                        return Ok(None);
+                    };
+                    if !output.status.success() {
+                        let stderr = String::from_utf8_lossy(&output.stderr);
+                        return Err(format!("gh pr list failed: {}", stderr.trim()));
+                    }
+                    let items =
+                        serde_json::from_slice::<Vec<GhPullRequestListItem>>(&output.stdout)
+                            .map_err(|err| format!("failed to parse gh pr list output: {err}"))?;
+                    let Some(item) = items.first() else {
+                        return Ok(None);
+                    };
+                    Some(item.number.to_string())
+                }
+                None => Some(branch),
+            }
+        };
+        let gh_pr_args = |subcommand: &[&str]| {
+            pull_request::gh_pr_args(subcommand, selector.as_deref(), repo.as_deref())
+        };
+
         let output = Command::new("gh")
-            .args([
-                "pr",
+            .args(gh_pr_args(&[
                 "view",
                 "--json",
                 "number,isDraft,state,mergeStateStatus,reviewDecision",
-            ])
+            ]))
             .current_dir(&worktree_path)
             .output();
 
+        // Reason: a missing `gh` binary is a setup issue, not a transient failure; report it
+        // as "no pull request" so it does not trigger fast retries.
         let Ok(output) = output else {
             return Ok(None);
         };
         if !output.status.success() {
-            return Ok(None);
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if pull_request::is_no_pull_request_error(&stderr) {
+                return Ok(None);
+            }
+            return Err(format!("gh pr view failed: {}", stderr.trim()));
         }
 
-        let Ok(value) = serde_json::from_slice::<GhPullRequestView>(&output.stdout) else {
-            return Ok(None);
-        };
+        let value = serde_json::from_slice::<GhPullRequestView>(&output.stdout)
+            .map_err(|err| format!("failed to parse gh pr view output: {err}"))?;
 
         let state = match value.state.as_str() {
             "OPEN" => PullRequestState::Open,
@@ -2288,7 +2786,7 @@ impl ProjectWorkspaceService for GitWorkspaceService {
         }
 
         let required_checks_output = Command::new("gh")
-            .args(["pr", "checks", "--required", "--json", "bucket"])
+            .args(gh_pr_args(&["checks", "--required", "--json", "bucket"]))
             .current_dir(&worktree_path)
             .output();
         let required_checks_parsed = required_checks_output.as_ref().ok().and_then(parse_checks);
@@ -2301,7 +2799,7 @@ impl ProjectWorkspaceService for GitWorkspaceService {
             required_checks_parsed.clone().unwrap_or_default()
         } else {
             let all_checks_output = Command::new("gh")
-                .args(["pr", "checks", "--json", "bucket"])
+                .args(gh_pr_args(&["checks", "--json", "bucket"]))
                 .current_dir(&worktree_path)
                 .output();
             all_checks_parsed = all_checks_output.as_ref().ok().and_then(parse_checks);
@@ -2532,6 +3030,20 @@ impl ProjectWorkspaceService for GitWorkspaceService {
         }
     }
 
+    fn prompt_templates_export(&self, dir: PathBuf) -> Result<Vec<String>, String> {
+        self.export_prompt_templates_internal(&dir)
+            .map_err(anyhow_error_to_string)
+    }
+
+    fn prompt_templates_import(
+        &self,
+        dir: PathBuf,
+        force: bool,
+    ) -> Result<luban_domain::PromptTemplatesImportReport, String> {
+        self.import_prompt_templates_internal(&dir, force)
+            .map_err(anyhow_error_to_string)
+    }
+
     fn system_prompt_templates_load(
         &self,
     ) -> Result<std::collections::HashMap<SystemTaskKind, String>, String> {
@@ -2650,6 +3162,30 @@ impl ProjectWorkspaceService for GitWorkspaceService {
             .map_err(anyhow_error_to_string)
     }
 
+    fn task_generate_pr_description(
+        &self,
+        input: String,
+        runner: luban_domain::AgentRunnerKind,
+        model_id: String,
+        thinking_effort: luban_domain::ThinkingEffort,
+        amp_mode: Option<String>,
+    ) -> Result<String, String> {
+        task::task_generate_pr_description(self, input, runner, model_id, thinking_effort, amp_mode)
+            .map_err(anyhow_error_to_string)
+    }
+
+    fn task_summarize_conversation(
+        &self,
+        input: String,
+        runner: luban_domain::AgentRunnerKind,
+        model_id: String,
+        thinking_effort: luban_domain::ThinkingEffort,
+        amp_mode: Option<String>,
+    ) -> Result<String, String> {
+        task::task_summarize_conversation(self, input, runner, model_id, thinking_effort, amp_mode)
+            .map_err(anyhow_error_to_string)
+    }
+
     fn task_suggest_task_status(
         &self,
         input: String,
@@ -3098,13 +3634,13 @@ impl GitWorkspaceService {
 mod tests {
     use super::codex_thread::qualify_codex_item;
     use super::prompt::PromptAttachment;
-    use super::pull_request::is_merge_ready;
+    use super::pull_request::{gh_pr_args, is_merge_ready, is_no_pull_request_error};
     use super::test_support::{
         EnvVarGuard, assert_git_success, git_rev_parse, lock_env, run_git, stored_blob_path,
         temp_services_dir,
     };
     use super::*;
-    use luban_domain::{PersistedProject, PersistedWorkspace, WorkspaceStatus};
+    use luban_domain::{PersistedProject, PersistedWorkspace, ProjectStatus, WorkspaceStatus};
     use std::path::PathBuf;
 
     #[test]
@@ -3160,6 +3696,16 @@ mod tests {
         assert!(!formatted.contains("@/tmp/image.png"));
     }
 
+    #[test]
+    fn system_prompt_is_prepended_ahead_of_user_prompt() {
+        assert_eq!(prepend_system_prompt(None, "Hello"), "Hello");
+        assert_eq!(prepend_system_prompt(Some("  \n"), "Hello"), "Hello");
+        assert_eq!(
+            prepend_system_prompt(Some("Use tabs.\n"), "Hello"),
+            "<system_instructions>\nUse tabs.\n</system_instructions>\n\nHello"
+        );
+    }
+
     #[test]
     fn gh_pr_check_bucket_ci_state_mapping() {
         assert_eq!(
@@ -3185,6 +3731,22 @@ mod tests {
         assert_eq!(pull_request_ci_state_from_check_buckets(["unknown"]), None);
     }
 
+    #[test]
+    fn gh_no_pull_request_errors_are_distinguished_from_failures() {
+        assert!(is_no_pull_request_error(
+            "no pull requests found for branch \"luban/feature\"\n"
+        ));
+        assert!(is_no_pull_request_error(
+            "none of the git remotes configured for this repository point to a known GitHub host"
+        ));
+        assert!(!is_no_pull_request_error(
+            "error connecting to api.github.com\ncheck your internet connection"
+        ));
+        assert!(!is_no_pull_request_error(
+            "GraphQL: API rate limit exceeded for user ID 1."
+        ));
+    }
+
     #[test]
     fn gh_pr_merge_ready_logic_is_stable() {
         assert!(is_merge_ready(
@@ -3240,85 +3802,333 @@ mod tests {
     }
 
     #[test]
-    fn task_prompt_templates_roundtrip_via_files() {
+    fn duplicate_conversation_copies_user_messages_or_entries_up_to_an_id() {
         let _guard = lock_env();
 
         let unique = unix_epoch_nanos_now();
         let root = std::env::temp_dir().join(format!(
-            "luban-task-prompts-{}-{}",
+            "luban-duplicate-thread-{}-{}",
             std::process::id(),
             unique
         ));
         std::fs::create_dir_all(&root).expect("temp dir should be created");
         {
             let _env = EnvVarGuard::set(paths::LUBAN_ROOT_ENV, root.as_os_str());
-
             let service = GitWorkspaceService::new().expect("service should init");
+            let (slug, name) = ("p".to_owned(), "w".to_owned());
+
+            let user = |entry_id: &str, text: &str| ConversationEntry::UserEvent {
+                entry_id: entry_id.to_owned(),
+                created_at_unix_ms: 1,
+                event: luban_domain::UserEvent::Message {
+                    text: text.to_owned(),
+                    attachments: Vec::new(),
+                },
+            };
+            let agent = |entry_id: &str, text: &str| ConversationEntry::AgentEvent {
+                entry_id: entry_id.to_owned(),
+                created_at_unix_ms: 1,
+                runner: None,
+                event: luban_domain::AgentEvent::Message {
+                    id: entry_id.to_owned(),
+                    text: text.to_owned(),
+                },
+            };
             service
-                .task_prompt_template_store(TaskIntentKind::Fix, "hello".to_owned())
-                .expect("store should succeed");
+                .append_conversation_entries(
+                    slug.clone(),
+                    name.clone(),
+                    1,
+                    vec![
+                        user("u1", "Fix the parser"),
+                        agent("a1", "Done"),
+                        user("u2", "Add tests"),
+                        agent("a2", "Added"),
+                    ],
+                )
+                .expect("append should succeed");
 
-            let loaded = service
-                .task_prompt_templates_load()
-                .expect("load should succeed");
+            service
+                .duplicate_conversation(slug.clone(), name.clone(), 1, 2, None)
+                .expect("duplicate should succeed");
+            let copy = service
+                .load_conversation(slug.clone(), name.clone(), 2)
+                .expect("copy should load");
+            assert_eq!(copy.title.as_deref(), Some("Copy of Fix the parser"));
+            let texts = |entries: &[ConversationEntry]| {
+                entries
+                    .iter()
+                    .filter_map(|entry| match entry {
+                        ConversationEntry::UserEvent {
+                            event: luban_domain::UserEvent::Message { text, .. },
+                            ..
+                        } => Some(format!("user:{text}")),
+                        ConversationEntry::AgentEvent {
+                            event: luban_domain::AgentEvent::Message { text, .. },
+                            ..
+                        } => Some(format!("agent:{text}")),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+            };
             assert_eq!(
-                loaded.get(&TaskIntentKind::Fix).map(String::as_str),
-                Some("hello")
+                texts(&copy.entries),
+                vec!["user:Fix the parser", "user:Add tests"]
             );
 
             service
-                .task_prompt_template_delete(TaskIntentKind::Fix)
-                .expect("delete should succeed");
-            let loaded = service
-                .task_prompt_templates_load()
-                .expect("load should succeed");
-            assert!(!loaded.contains_key(&TaskIntentKind::Fix));
+                .duplicate_conversation(slug.clone(), name.clone(), 1, 3, Some("a1".to_owned()))
+                .expect("duplicate should succeed");
+            let copy = service
+                .load_conversation(slug.clone(), name.clone(), 3)
+                .expect("copy should load");
+            assert_eq!(
+                texts(&copy.entries),
+                vec!["user:Fix the parser", "agent:Done"]
+            );
+
+            let err = service
+                .duplicate_conversation(slug, name, 1, 4, Some("missing".to_owned()))
+                .expect_err("unknown entry id should fail");
+            assert!(err.contains("missing"), "{err}");
         }
         let _ = std::fs::remove_dir_all(&root);
     }
 
     #[test]
-    fn codex_config_tree_is_shallow_and_codex_config_list_dir_pages() {
+    fn moved_threads_keep_their_attachment_blobs() {
         let _guard = lock_env();
 
         let unique = unix_epoch_nanos_now();
         let root = std::env::temp_dir().join(format!(
-            "luban-codex-config-tree-{}-{}",
+            "luban-move-thread-{}-{}",
             std::process::id(),
             unique
         ));
         std::fs::create_dir_all(&root).expect("temp dir should be created");
+        {
+            let _env = EnvVarGuard::set(paths::LUBAN_ROOT_ENV, root.as_os_str());
+            let service = GitWorkspaceService::new().expect("service should init");
+            let slug = "p".to_owned();
 
-        let cache_dir = root.join("cache");
-        std::fs::create_dir_all(&cache_dir).expect("cache dir should be created");
-        for i in 0..3000 {
-            std::fs::write(cache_dir.join(format!("blob-{i}.bin")), b"x")
-                .expect("write cache file");
+            let from_dir = service.context_blobs_dir(&slug, "w");
+            std::fs::create_dir_all(&from_dir).expect("blobs dir should be created");
+            std::fs::write(from_dir.join("img.png"), b"image").expect("blob should be written");
+            std::fs::write(from_dir.join("img-thumb.png"), b"thumb")
+                .expect("thumbnail should be written");
+            service
+                .append_conversation_entries(
+                    slug.clone(),
+                    "w".to_owned(),
+                    1,
+                    vec![ConversationEntry::UserEvent {
+                        entry_id: "u1".to_owned(),
+                        created_at_unix_ms: 1,
+                        event: luban_domain::UserEvent::Message {
+                            text: "Look".to_owned(),
+                            attachments: vec![AttachmentRef {
+                                id: "img".to_owned(),
+                                kind: AttachmentKind::Image,
+                                name: "img.png".to_owned(),
+                                extension: "png".to_owned(),
+                                mime: None,
+                                byte_len: 5,
+                            }],
+                        },
+                    }],
+                )
+                .expect("append should succeed");
+
+            service
+                .move_conversation_thread(slug.clone(), "w".to_owned(), 1, "other".to_owned(), 2)
+                .expect("move should succeed");
+
+            let to_dir = service.context_blobs_dir(&slug, "other");
+            assert_eq!(
+                std::fs::read(to_dir.join("img.png")).expect("blob should be copied"),
+                b"image"
+            );
+            assert_eq!(
+                std::fs::read(to_dir.join("img-thumb.png")).expect("thumbnail should be copied"),
+                b"thumb"
+            );
+            let moved = service
+                .load_conversation(slug, "other".to_owned(), 2)
+                .expect("moved thread should load");
+            assert!(moved.entries.iter().any(|entry| matches!(
+                entry,
+                ConversationEntry::UserEvent {
+                    event: luban_domain::UserEvent::Message { attachments, .. },
+                    ..
+                } if attachments.iter().any(|a| a.id == "img")
+            )));
         }
+        let _ = std::fs::remove_dir_all(&root);
+    }
 
-        std::fs::write(root.join("config.toml"), "model = \"gpt-5.3-codex\"")
-            .expect("write config.toml");
-        let prompts_dir = root.join("prompts");
-        std::fs::create_dir_all(&prompts_dir).expect("prompts dir should be created");
-        std::fs::write(prompts_dir.join("hello.md"), "# Hello\n").expect("write prompt");
+    #[test]
+    fn prompt_templates_export_then_import_into_fresh_settings_matches() {
+        let _guard = lock_env();
 
-        let base_dir = temp_services_dir(unique);
-        std::fs::create_dir_all(&base_dir).expect("luban root should exist");
-        let sqlite =
-            SqliteStore::new(paths::sqlite_path(&base_dir)).expect("sqlite init should work");
-        let service = GitWorkspaceService {
-            worktrees_root: paths::worktrees_root(&base_dir),
-            conversations_root: paths::conversations_root(&base_dir),
-            task_prompts_root: paths::task_prompts_root(&base_dir),
-            sqlite,
-            claude_processes: Mutex::new(HashMap::new()),
+        let unique = unix_epoch_nanos_now();
+        let base = std::env::temp_dir().join(format!(
+            "luban-prompt-export-{}-{}",
+            std::process::id(),
+            unique
+        ));
+        let export_dir = base.join("export");
+        let load_all = |service: &GitWorkspaceService| {
+            (
+                service
+                    .task_prompt_templates_load()
+                    .expect("load should succeed"),
+                service
+                    .system_prompt_templates_load()
+                    .expect("load should succeed"),
+            )
         };
 
-        let tree = {
-            let _env = EnvVarGuard::set(paths::LUBAN_CODEX_ROOT_ENV, &root);
+        let source_templates = {
+            let _env = EnvVarGuard::set(paths::LUBAN_ROOT_ENV, base.join("source").as_os_str());
+            let service = GitWorkspaceService::new().expect("service should init");
+            service
+                .task_prompt_template_store(TaskIntentKind::Fix, "custom fix".to_owned())
+                .expect("store should succeed");
+            service
+                .system_prompt_template_store(
+                    SystemTaskKind::RenameBranch,
+                    "custom rename".to_owned(),
+                )
+                .expect("store should succeed");
+            let written = service
+                .prompt_templates_export(export_dir.clone())
+                .expect("export should succeed");
+            assert_eq!(
+                written.len(),
+                TaskIntentKind::ALL.len() + SystemTaskKind::ALL.len()
+            );
+            assert_eq!(
+                std::fs::read_to_string(export_dir.join("fix.md")).expect("fix.md exists"),
+                "custom fix\n"
+            );
+            load_all(&service)
+        };
 
-            let tree = ProjectWorkspaceService::codex_config_tree(&service)
-                .expect("codex_config_tree should succeed");
+        {
+            let _env = EnvVarGuard::set(paths::LUBAN_ROOT_ENV, base.join("fresh").as_os_str());
+            let service = GitWorkspaceService::new().expect("service should init");
+            let report = service
+                .prompt_templates_import(export_dir.clone(), false)
+                .expect("import should succeed");
+            assert!(report.conflicts.is_empty(), "{report:?}");
+            assert_eq!(report.imported, vec!["fix", "rename-branch"]);
+            assert_eq!(load_all(&service), source_templates);
+
+            service
+                .task_prompt_template_store(TaskIntentKind::Fix, "local fix".to_owned())
+                .expect("store should succeed");
+            let report = service
+                .prompt_templates_import(export_dir.clone(), false)
+                .expect("import should succeed");
+            assert_eq!(report.conflicts, vec!["fix"]);
+            assert!(report.imported.is_empty());
+            assert_eq!(
+                load_all(&service)
+                    .0
+                    .get(&TaskIntentKind::Fix)
+                    .map(String::as_str),
+                Some("local fix")
+            );
+
+            let report = service
+                .prompt_templates_import(export_dir.clone(), true)
+                .expect("forced import should succeed");
+            assert_eq!(report.imported, vec!["fix"]);
+            assert_eq!(load_all(&service), source_templates);
+        }
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn task_prompt_templates_roundtrip_via_files() {
+        let _guard = lock_env();
+
+        let unique = unix_epoch_nanos_now();
+        let root = std::env::temp_dir().join(format!(
+            "luban-task-prompts-{}-{}",
+            std::process::id(),
+            unique
+        ));
+        std::fs::create_dir_all(&root).expect("temp dir should be created");
+        {
+            let _env = EnvVarGuard::set(paths::LUBAN_ROOT_ENV, root.as_os_str());
+
+            let service = GitWorkspaceService::new().expect("service should init");
+            service
+                .task_prompt_template_store(TaskIntentKind::Fix, "hello".to_owned())
+                .expect("store should succeed");
+
+            let loaded = service
+                .task_prompt_templates_load()
+                .expect("load should succeed");
+            assert_eq!(
+                loaded.get(&TaskIntentKind::Fix).map(String::as_str),
+                Some("hello")
+            );
+
+            service
+                .task_prompt_template_delete(TaskIntentKind::Fix)
+                .expect("delete should succeed");
+            let loaded = service
+                .task_prompt_templates_load()
+                .expect("load should succeed");
+            assert!(!loaded.contains_key(&TaskIntentKind::Fix));
+        }
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn codex_config_tree_is_shallow_and_codex_config_list_dir_pages() {
+        let _guard = lock_env();
+
+        let unique = unix_epoch_nanos_now();
+        let root = std::env::temp_dir().join(format!(
+            "luban-codex-config-tree-{}-{}",
+            std::process::id(),
+            unique
+        ));
+        std::fs::create_dir_all(&root).expect("temp dir should be created");
+
+        let cache_dir = root.join("cache");
+        std::fs::create_dir_all(&cache_dir).expect("cache dir should be created");
+        for i in 0..3000 {
+            std::fs::write(cache_dir.join(format!("blob-{i}.bin")), b"x")
+                .expect("write cache file");
+        }
+
+        std::fs::write(root.join("config.toml"), "model = \"gpt-5.3-codex\"")
+            .expect("write config.toml");
+        let prompts_dir = root.join("prompts");
+        std::fs::create_dir_all(&prompts_dir).expect("prompts dir should be created");
+        std::fs::write(prompts_dir.join("hello.md"), "# Hello\n").expect("write prompt");
+
+        let base_dir = temp_services_dir(unique);
+        std::fs::create_dir_all(&base_dir).expect("luban root should exist");
+        let sqlite =
+            SqliteStore::new(paths::sqlite_path(&base_dir)).expect("sqlite init should work");
+        let service = GitWorkspaceService {
+            worktrees_root: paths::worktrees_root(&base_dir),
+            conversations_root: paths::conversations_root(&base_dir),
+            task_prompts_root: paths::task_prompts_root(&base_dir),
+            sqlite,
+            claude_processes: Mutex::new(HashMap::new()),
+        };
+
+        let tree = {
+            let _env = EnvVarGuard::set(paths::LUBAN_CODEX_ROOT_ENV, &root);
+
+            let tree = ProjectWorkspaceService::codex_config_tree(&service)
+                .expect("codex_config_tree should succeed");
 
             let entries =
                 ProjectWorkspaceService::codex_config_list_dir(&service, "cache".to_owned())
@@ -3883,6 +4693,7 @@ mod tests {
                     model: None,
                     model_reasoning_effort: None,
                     sandbox_mode: None,
+                    env: Vec::new(),
                 },
                 Arc::new(AtomicBool::new(false)),
                 |_event| Ok(()),
@@ -3981,8 +4792,18 @@ mod tests {
                     attachments: Vec::new(),
                     runner: luban_domain::AgentRunnerKind::Codex,
                     amp_mode: None,
+                    claude_max_output_tokens: None,
+                    claude_system_prompt: None,
+                    agent_env: Vec::new(),
                     model: None,
                     model_reasoning_effort: None,
+                    cwd_subpath: None,
+                    system_prompt: None,
+                    prompt_prefix: None,
+                    prompt_suffix: None,
+                    retry: false,
+                    command_output_max_bytes: 0,
+                    merge_reasoning_entries: false,
                 },
                 Arc::new(AtomicBool::new(false)),
                 Arc::new(|_event| {}),
@@ -4099,8 +4920,18 @@ mod tests {
                     attachments: Vec::new(),
                     runner: luban_domain::AgentRunnerKind::Codex,
                     amp_mode: None,
+                    claude_max_output_tokens: None,
+                    claude_system_prompt: None,
+                    agent_env: Vec::new(),
                     model: None,
                     model_reasoning_effort: None,
+                    cwd_subpath: None,
+                    system_prompt: None,
+                    prompt_prefix: None,
+                    prompt_suffix: None,
+                    retry: false,
+                    command_output_max_bytes: 0,
+                    merge_reasoning_entries: false,
                 },
                 Arc::new(AtomicBool::new(false)),
                 Arc::new(|_event| {}),
@@ -4135,120 +4966,46 @@ mod tests {
     }
 
     #[test]
-    fn tests_do_not_use_production_db_by_default() {
-        let _guard = lock_env();
-
-        let _env = EnvVarGuard::remove(paths::LUBAN_ROOT_ENV);
-
-        let root = resolve_luban_root().expect("test root should resolve");
-        assert!(
-            root.to_string_lossy().contains("luban-test-"),
-            "expected test root under temp dir, got {}",
-            root.display()
-        );
-        drop(_env);
-    }
-
-    #[test]
-    fn luban_root_env_overrides_default_root() {
+    #[cfg(unix)]
+    fn persisted_command_output_is_capped() {
         let _guard = lock_env();
 
-        let unique = unix_epoch_nanos_now();
-        let base_dir =
-            std::env::temp_dir().join(format!("luban-root-env-{}-{}", std::process::id(), unique));
-        std::fs::create_dir_all(&base_dir).expect("temp dir should be created");
-
-        {
-            let _env = EnvVarGuard::set(paths::LUBAN_ROOT_ENV, base_dir.as_os_str());
-
-            let service = GitWorkspaceService::new_with_options(SqliteStoreOptions::default())
-                .expect("service should init");
-            service
-                .sqlite
-                .load_app_state()
-                .expect("sqlite queries should work");
-
-            let expected_db = paths::sqlite_path(&base_dir);
-            assert!(
-                expected_db.exists(),
-                "expected sqlite db at {}, but it was not created",
-                expected_db.display()
-            );
-
-            drop(service);
-        }
-        let _ = std::fs::remove_dir_all(&base_dir);
-    }
-
-    #[test]
-    fn codex_item_ids_are_scoped_per_turn() {
-        let item = CodexThreadItem::AgentMessage {
-            id: "item_0".to_owned(),
-            text: "Hi".to_owned(),
-        };
-        let a = qualify_codex_item("turn-a", item.clone());
-        let b = qualify_codex_item("turn-b", item);
-        assert_eq!(codex_item_id(&a), "turn-a/item_0");
-        assert_eq!(codex_item_id(&b), "turn-b/item_0");
-        let a2 = qualify_codex_item("turn-a", a);
-        assert_eq!(codex_item_id(&a2), "turn-a/item_0");
-    }
-
-    #[test]
-    fn worktree_remove_force_allows_dirty_worktree() {
         let unique = unix_epoch_nanos_now();
         let base_dir = std::env::temp_dir().join(format!(
-            "luban-worktree-remove-force-{}-{}",
+            "luban-agent-output-cap-{}-{}",
             std::process::id(),
             unique
         ));
+        let worktree = base_dir.join("worktree");
+        std::fs::create_dir_all(&worktree).expect("worktree should be created");
 
-        std::fs::create_dir_all(&base_dir).expect("temp dir should be created");
-
-        let repo_path = base_dir.join("repo");
-        std::fs::create_dir_all(&repo_path).expect("repo dir should be created");
-
-        assert_git_success(&repo_path, &["init"]);
-        assert_git_success(&repo_path, &["config", "user.name", "Test User"]);
-        assert_git_success(&repo_path, &["config", "user.email", "test@example.com"]);
-
-        let tracked_file = repo_path.join("tracked.txt");
-        std::fs::write(&tracked_file, "hello\n").expect("write should succeed");
-        assert_git_success(&repo_path, &["add", "."]);
-        assert_git_success(&repo_path, &["commit", "-m", "init"]);
-
-        let worktree_path = base_dir.join("worktree");
-        let branch_name = format!("luban-test-branch-{unique}");
-        assert_git_success(
-            &repo_path,
-            &[
-                "worktree",
-                "add",
-                "-b",
-                &branch_name,
-                worktree_path
-                    .to_str()
-                    .expect("worktree path should be utf-8"),
-            ],
-        );
-
-        let dirty_file = worktree_path.join("tracked.txt");
-        std::fs::write(&dirty_file, "hello\ndirty\n").expect("write should succeed");
+        let output = "x".repeat(200);
+        let fake_codex = base_dir.join("fake-codex");
+        std::fs::write(
+            &fake_codex,
+            [
+                "#!/bin/sh".to_owned(),
+                "cat >/dev/null".to_owned(),
+                "echo '{\"type\":\"turn.started\"}'".to_owned(),
+                format!("echo '{{\"type\":\"item.completed\",\"item\":{{\"type\":\"command_execution\",\"id\":\"item_0\",\"command\":\"make\",\"aggregated_output\":\"{output}\",\"exit_code\":0,\"status\":\"completed\"}}}}'"),
+                "echo '{\"type\":\"item.completed\",\"item\":{\"type\":\"agent_message\",\"id\":\"item_1\",\"text\":\"done\"}}'".to_owned(),
+                "echo '{\"type\":\"turn.completed\",\"usage\":{\"input_tokens\":0,\"cached_input_tokens\":0,\"output_tokens\":0}}'".to_owned(),
+                "exit 0".to_owned(),
+                String::new(),
+            ]
+            .join("\n"),
+        )
+        .expect("fake codex should be written");
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mut perms = std::fs::metadata(&fake_codex)
+                .expect("fake codex should exist")
+                .permissions();
+            perms.set_mode(0o755);
+            std::fs::set_permissions(&fake_codex, perms).expect("fake codex should be executable");
+        }
 
-        let no_force = run_git(
-            &repo_path,
-            &[
-                "worktree",
-                "remove",
-                worktree_path
-                    .to_str()
-                    .expect("worktree path should be utf-8"),
-            ],
-        );
-        assert!(
-            !no_force.status.success(),
-            "worktree remove without --force should fail for dirty worktree"
-        );
+        let _env = EnvVarGuard::set(paths::LUBAN_CODEX_BIN_ENV, fake_codex.as_os_str());
 
         let sqlite =
             SqliteStore::new(paths::sqlite_path(&base_dir)).expect("sqlite init should work");
@@ -4260,61 +5017,1388 @@ mod tests {
             claude_processes: Mutex::new(HashMap::new()),
         };
 
-        ProjectWorkspaceService::archive_workspace(
-            &service,
-            repo_path.clone(),
-            worktree_path.clone(),
-            branch_name.clone(),
-        )
-        .expect("archive_workspace should remove dirty worktree with --force");
-        assert!(!worktree_path.exists(), "worktree path should be removed");
+        service
+            .run_agent_turn_streamed(
+                RunAgentTurnRequest {
+                    project_slug: "p".to_owned(),
+                    workspace_name: "w".to_owned(),
+                    worktree_path: worktree.clone(),
+                    thread_local_id: 1,
+                    thread_id: None,
+                    prompt: "Build".to_owned(),
+                    attachments: Vec::new(),
+                    runner: luban_domain::AgentRunnerKind::Codex,
+                    amp_mode: None,
+                    claude_max_output_tokens: None,
+                    claude_system_prompt: None,
+                    agent_env: Vec::new(),
+                    model: None,
+                    model_reasoning_effort: None,
+                    cwd_subpath: None,
+                    system_prompt: None,
+                    prompt_prefix: None,
+                    prompt_suffix: None,
+                    retry: false,
+                    command_output_max_bytes: 64,
+                    merge_reasoning_entries: false,
+                },
+                Arc::new(AtomicBool::new(false)),
+                Arc::new(|_event| {}),
+            )
+            .expect("turn should succeed");
+
+        let snapshot = service
+            .load_conversation("p".to_owned(), "w".to_owned(), 1)
+            .expect("conversation should load");
+        let (aggregated_output, truncated) = snapshot
+            .entries
+            .iter()
+            .find_map(|entry| match entry {
+                ConversationEntry::AgentEvent {
+                    event: luban_domain::AgentEvent::Item { item },
+                    ..
+                } => match item.as_ref() {
+                    CodexThreadItem::CommandExecution {
+                        aggregated_output,
+                        truncated,
+                        ..
+                    } => Some((aggregated_output.clone(), *truncated)),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .expect("command item should be persisted");
+        assert!(truncated);
+        assert!(
+            aggregated_output.len() < output.len(),
+            "output should be capped: {aggregated_output}"
+        );
 
+        drop(_env);
         drop(service);
         let _ = std::fs::remove_dir_all(&base_dir);
     }
 
     #[test]
-    fn archive_workspace_deletes_luban_branch_after_removing_worktree() {
+    #[cfg(unix)]
+    fn merged_reasoning_entries_reload_from_the_store() {
+        let _guard = lock_env();
+
         let unique = unix_epoch_nanos_now();
         let base_dir = std::env::temp_dir().join(format!(
-            "luban-archive-workspace-branch-delete-{}-{}",
+            "luban-agent-merged-reasoning-{}-{}",
             std::process::id(),
             unique
         ));
+        let worktree = base_dir.join("worktree");
+        std::fs::create_dir_all(&worktree).expect("worktree should be created");
 
-        std::fs::create_dir_all(&base_dir).expect("temp dir should be created");
-
-        let repo_path = base_dir.join("repo");
-        std::fs::create_dir_all(&repo_path).expect("repo dir should be created");
-
-        assert_git_success(&repo_path, &["init"]);
-        assert_git_success(&repo_path, &["config", "user.name", "Test User"]);
+        let fake_codex = base_dir.join("fake-codex");
+        std::fs::write(
+            &fake_codex,
+            [
+                "#!/bin/sh".to_owned(),
+                "cat >/dev/null".to_owned(),
+                "echo '{\"type\":\"turn.started\"}'".to_owned(),
+                "echo '{\"type\":\"item.completed\",\"item\":{\"type\":\"reasoning\",\"id\":\"item_r1\",\"text\":\"Read the code\"}}'".to_owned(),
+                "echo '{\"type\":\"item.completed\",\"item\":{\"type\":\"reasoning\",\"id\":\"item_r2\",\"text\":\"Plan the fix\"}}'".to_owned(),
+                "echo '{\"type\":\"item.completed\",\"item\":{\"type\":\"agent_message\",\"id\":\"item_1\",\"text\":\"done\"}}'".to_owned(),
+                "echo '{\"type\":\"turn.completed\",\"usage\":{\"input_tokens\":0,\"cached_input_tokens\":0,\"output_tokens\":0}}'".to_owned(),
+                "exit 0".to_owned(),
+                String::new(),
+            ]
+            .join("\n"),
+        )
+        .expect("fake codex should be written");
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mut perms = std::fs::metadata(&fake_codex)
+                .expect("fake codex should exist")
+                .permissions();
+            perms.set_mode(0o755);
+            std::fs::set_permissions(&fake_codex, perms).expect("fake codex should be executable");
+        }
+
+        let _env = EnvVarGuard::set(paths::LUBAN_CODEX_BIN_ENV, fake_codex.as_os_str());
+
+        let sqlite =
+            SqliteStore::new(paths::sqlite_path(&base_dir)).expect("sqlite init should work");
+        let service = GitWorkspaceService {
+            worktrees_root: paths::worktrees_root(&base_dir),
+            conversations_root: paths::conversations_root(&base_dir),
+            task_prompts_root: paths::task_prompts_root(&base_dir),
+            sqlite,
+            claude_processes: Mutex::new(HashMap::new()),
+        };
+
+        service
+            .run_agent_turn_streamed(
+                RunAgentTurnRequest {
+                    project_slug: "p".to_owned(),
+                    workspace_name: "w".to_owned(),
+                    worktree_path: worktree.clone(),
+                    thread_local_id: 1,
+                    thread_id: None,
+                    prompt: "Think".to_owned(),
+                    attachments: Vec::new(),
+                    runner: luban_domain::AgentRunnerKind::Codex,
+                    amp_mode: None,
+                    claude_max_output_tokens: None,
+                    claude_system_prompt: None,
+                    agent_env: Vec::new(),
+                    model: None,
+                    model_reasoning_effort: None,
+                    cwd_subpath: None,
+                    system_prompt: None,
+                    prompt_prefix: None,
+                    prompt_suffix: None,
+                    retry: false,
+                    command_output_max_bytes: 0,
+                    merge_reasoning_entries: true,
+                },
+                Arc::new(AtomicBool::new(false)),
+                Arc::new(|_event| {}),
+            )
+            .expect("turn should succeed");
+
+        let snapshot = service
+            .load_conversation("p".to_owned(), "w".to_owned(), 1)
+            .expect("conversation should load");
+        let reasoning = snapshot
+            .entries
+            .iter()
+            .filter_map(|entry| match entry {
+                ConversationEntry::AgentEvent {
+                    event: luban_domain::AgentEvent::Item { item },
+                    ..
+                } => match item.as_ref() {
+                    CodexThreadItem::Reasoning { text, .. } => Some(text.as_str()),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(reasoning, vec!["Read the code\n\nPlan the fix"]);
+
+        drop(_env);
+        drop(service);
+        let _ = std::fs::remove_dir_all(&base_dir);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn web_search_results_accumulate_in_the_stored_item() {
+        let _guard = lock_env();
+
+        let unique = unix_epoch_nanos_now();
+        let base_dir = std::env::temp_dir().join(format!(
+            "luban-agent-web-search-{}-{}",
+            std::process::id(),
+            unique
+        ));
+        let worktree = base_dir.join("worktree");
+        std::fs::create_dir_all(&worktree).expect("worktree should be created");
+
+        let fake_codex = base_dir.join("fake-codex");
+        std::fs::write(
+            &fake_codex,
+            [
+                "#!/bin/sh".to_owned(),
+                "cat >/dev/null".to_owned(),
+                "echo '{\"type\":\"turn.started\"}'".to_owned(),
+                "echo '{\"type\":\"item.started\",\"item\":{\"type\":\"web_search\",\"id\":\"item_s\",\"query\":\"rust\",\"results\":[{\"title\":\"Rust\",\"url\":\"https://www.rust-lang.org/\"}]}}'".to_owned(),
+                "echo '{\"type\":\"item.updated\",\"item\":{\"type\":\"web_search\",\"id\":\"item_s\",\"query\":\"rust\",\"results\":[{\"title\":\"Docs\",\"url\":\"https://doc.rust-lang.org/\"}]}}'".to_owned(),
+                "echo '{\"type\":\"item.completed\",\"item\":{\"type\":\"web_search\",\"id\":\"item_s\",\"query\":\"rust\"}}'".to_owned(),
+                "echo '{\"type\":\"item.completed\",\"item\":{\"type\":\"agent_message\",\"id\":\"item_1\",\"text\":\"done\"}}'".to_owned(),
+                "echo '{\"type\":\"turn.completed\",\"usage\":{\"input_tokens\":0,\"cached_input_tokens\":0,\"output_tokens\":0}}'".to_owned(),
+                "exit 0".to_owned(),
+                String::new(),
+            ]
+            .join("\n"),
+        )
+        .expect("fake codex should be written");
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mut perms = std::fs::metadata(&fake_codex)
+                .expect("fake codex should exist")
+                .permissions();
+            perms.set_mode(0o755);
+            std::fs::set_permissions(&fake_codex, perms).expect("fake codex should be executable");
+        }
+
+        let _env = EnvVarGuard::set(paths::LUBAN_CODEX_BIN_ENV, fake_codex.as_os_str());
+
+        let sqlite =
+            SqliteStore::new(paths::sqlite_path(&base_dir)).expect("sqlite init should work");
+        let service = GitWorkspaceService {
+            worktrees_root: paths::worktrees_root(&base_dir),
+            conversations_root: paths::conversations_root(&base_dir),
+            task_prompts_root: paths::task_prompts_root(&base_dir),
+            sqlite,
+            claude_processes: Mutex::new(HashMap::new()),
+        };
+
+        service
+            .run_agent_turn_streamed(
+                RunAgentTurnRequest {
+                    project_slug: "p".to_owned(),
+                    workspace_name: "w".to_owned(),
+                    worktree_path: worktree.clone(),
+                    thread_local_id: 1,
+                    thread_id: None,
+                    prompt: "Search".to_owned(),
+                    attachments: Vec::new(),
+                    runner: luban_domain::AgentRunnerKind::Codex,
+                    amp_mode: None,
+                    claude_max_output_tokens: None,
+                    claude_system_prompt: None,
+                    agent_env: Vec::new(),
+                    model: None,
+                    model_reasoning_effort: None,
+                    cwd_subpath: None,
+                    system_prompt: None,
+                    prompt_prefix: None,
+                    prompt_suffix: None,
+                    retry: false,
+                    command_output_max_bytes: 0,
+                    merge_reasoning_entries: false,
+                },
+                Arc::new(AtomicBool::new(false)),
+                Arc::new(|_event| {}),
+            )
+            .expect("turn should succeed");
+
+        let snapshot = service
+            .load_conversation("p".to_owned(), "w".to_owned(), 1)
+            .expect("conversation should load");
+        let urls = snapshot
+            .entries
+            .iter()
+            .find_map(|entry| match entry {
+                ConversationEntry::AgentEvent {
+                    event: luban_domain::AgentEvent::Item { item },
+                    ..
+                } => match item.as_ref() {
+                    CodexThreadItem::WebSearch { results, .. } => Some(
+                        results
+                            .iter()
+                            .map(|result| result.url.as_str())
+                            .collect::<Vec<_>>(),
+                    ),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .expect("web search item should be persisted");
+        assert_eq!(
+            urls,
+            vec!["https://www.rust-lang.org/", "https://doc.rust-lang.org/"]
+        );
+
+        drop(_env);
+        drop(service);
+        let _ = std::fs::remove_dir_all(&base_dir);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn agent_turn_runs_in_cwd_subpath_and_rejects_traversal() {
+        let _guard = lock_env();
+
+        let unique = unix_epoch_nanos_now();
+        let base_dir = std::env::temp_dir().join(format!(
+            "luban-agent-cwd-subpath-{}-{}",
+            std::process::id(),
+            unique
+        ));
+        let worktree = base_dir.join("worktree");
+        let subdir = worktree.join("packages").join("app");
+        std::fs::create_dir_all(&subdir).expect("subdir should be created");
+        std::fs::create_dir_all(base_dir.join("outside")).expect("outside dir should be created");
+
+        let marker = base_dir.join("cwd.txt");
+        let fake_codex = base_dir.join("fake-codex");
+        std::fs::write(
+            &fake_codex,
+            [
+                "#!/bin/sh".to_owned(),
+                "cat >/dev/null &".to_owned(),
+                "stdin_pid=$!".to_owned(),
+                format!("pwd -P > '{}'", marker.display()),
+                "echo '{\"type\":\"turn.started\"}'".to_owned(),
+                "echo '{\"type\":\"item.completed\",\"item\":{\"type\":\"agent_message\",\"id\":\"item_1\",\"text\":\"done\"}}'".to_owned(),
+                "echo '{\"type\":\"turn.completed\",\"usage\":{\"input_tokens\":0,\"cached_input_tokens\":0,\"output_tokens\":0}}'".to_owned(),
+                "wait \"$stdin_pid\"".to_owned(),
+                "exit 0".to_owned(),
+                String::new(),
+            ]
+            .join("\n"),
+        )
+        .expect("fake codex should be written");
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mut perms = std::fs::metadata(&fake_codex)
+                .expect("fake codex should exist")
+                .permissions();
+            perms.set_mode(0o755);
+            std::fs::set_permissions(&fake_codex, perms).expect("fake codex should be executable");
+        }
+
+        let _env = EnvVarGuard::set(paths::LUBAN_CODEX_BIN_ENV, fake_codex.as_os_str());
+
+        let sqlite =
+            SqliteStore::new(paths::sqlite_path(&base_dir)).expect("sqlite init should work");
+        let service = GitWorkspaceService {
+            worktrees_root: paths::worktrees_root(&base_dir),
+            conversations_root: paths::conversations_root(&base_dir),
+            task_prompts_root: paths::task_prompts_root(&base_dir),
+            sqlite,
+            claude_processes: Mutex::new(HashMap::new()),
+        };
+
+        let request = |cwd_subpath: &str| RunAgentTurnRequest {
+            project_slug: "p".to_owned(),
+            workspace_name: "w".to_owned(),
+            worktree_path: worktree.clone(),
+            thread_local_id: 1,
+            thread_id: None,
+            prompt: "Hello".to_owned(),
+            attachments: Vec::new(),
+            runner: luban_domain::AgentRunnerKind::Codex,
+            amp_mode: None,
+            claude_max_output_tokens: None,
+            claude_system_prompt: None,
+            agent_env: Vec::new(),
+            model: None,
+            model_reasoning_effort: None,
+            cwd_subpath: Some(cwd_subpath.to_owned()),
+            system_prompt: None,
+            prompt_prefix: None,
+            prompt_suffix: None,
+            retry: false,
+            command_output_max_bytes: 0,
+            merge_reasoning_entries: false,
+        };
+
+        service
+            .run_agent_turn_streamed(
+                request("packages/app"),
+                Arc::new(AtomicBool::new(false)),
+                Arc::new(|_event| {}),
+            )
+            .expect("turn in subdirectory should succeed");
+        let recorded = std::fs::read_to_string(&marker).expect("marker should be written");
+        assert_eq!(
+            PathBuf::from(recorded.trim()),
+            subdir.canonicalize().expect("subdir should canonicalize")
+        );
+
+        std::fs::remove_file(&marker).expect("marker should be removable");
+        let err = service
+            .run_agent_turn_streamed(
+                request("../outside"),
+                Arc::new(AtomicBool::new(false)),
+                Arc::new(|_event| {}),
+            )
+            .expect_err("traversal subpath should be rejected");
+        assert!(
+            err.contains("within the worktree"),
+            "unexpected error: {err}"
+        );
+        assert!(
+            !marker.exists(),
+            "agent should not run outside the worktree"
+        );
+        let prompts = service
+            .load_conversation("p".to_owned(), "w".to_owned(), 1)
+            .expect("conversation should load")
+            .entries
+            .iter()
+            .filter(|entry| {
+                matches!(
+                    entry,
+                    ConversationEntry::UserEvent {
+                        event: luban_domain::UserEvent::Message { .. },
+                        ..
+                    }
+                )
+            })
+            .count();
+        assert_eq!(prompts, 1, "the rejected prompt should not be stored");
+
+        drop(_env);
+        drop(service);
+        let _ = std::fs::remove_dir_all(&base_dir);
+    }
+
+    #[test]
+    fn tests_do_not_use_production_db_by_default() {
+        let _guard = lock_env();
+
+        let _env = EnvVarGuard::remove(paths::LUBAN_ROOT_ENV);
+
+        let root = resolve_luban_root().expect("test root should resolve");
+        assert!(
+            root.to_string_lossy().contains("luban-test-"),
+            "expected test root under temp dir, got {}",
+            root.display()
+        );
+        drop(_env);
+    }
+
+    #[test]
+    fn luban_root_env_overrides_default_root() {
+        let _guard = lock_env();
+
+        let unique = unix_epoch_nanos_now();
+        let base_dir =
+            std::env::temp_dir().join(format!("luban-root-env-{}-{}", std::process::id(), unique));
+        std::fs::create_dir_all(&base_dir).expect("temp dir should be created");
+
+        {
+            let _env = EnvVarGuard::set(paths::LUBAN_ROOT_ENV, base_dir.as_os_str());
+
+            let service = GitWorkspaceService::new_with_options(SqliteStoreOptions::default())
+                .expect("service should init");
+            service
+                .sqlite
+                .load_app_state()
+                .expect("sqlite queries should work");
+
+            let expected_db = paths::sqlite_path(&base_dir);
+            assert!(
+                expected_db.exists(),
+                "expected sqlite db at {}, but it was not created",
+                expected_db.display()
+            );
+
+            drop(service);
+        }
+        let _ = std::fs::remove_dir_all(&base_dir);
+    }
+
+    #[test]
+    fn codex_item_ids_are_scoped_per_turn() {
+        let item = CodexThreadItem::AgentMessage {
+            id: "item_0".to_owned(),
+            text: "Hi".to_owned(),
+        };
+        let a = qualify_codex_item("turn-a", item.clone());
+        let b = qualify_codex_item("turn-b", item);
+        assert_eq!(codex_item_id(&a), "turn-a/item_0");
+        assert_eq!(codex_item_id(&b), "turn-b/item_0");
+        let a2 = qualify_codex_item("turn-a", a);
+        assert_eq!(codex_item_id(&a2), "turn-a/item_0");
+    }
+
+    #[test]
+    fn worktree_remove_force_allows_dirty_worktree() {
+        let unique = unix_epoch_nanos_now();
+        let base_dir = std::env::temp_dir().join(format!(
+            "luban-worktree-remove-force-{}-{}",
+            std::process::id(),
+            unique
+        ));
+
+        std::fs::create_dir_all(&base_dir).expect("temp dir should be created");
+
+        let repo_path = base_dir.join("repo");
+        std::fs::create_dir_all(&repo_path).expect("repo dir should be created");
+
+        assert_git_success(&repo_path, &["init"]);
+        assert_git_success(&repo_path, &["config", "user.name", "Test User"]);
+        assert_git_success(&repo_path, &["config", "user.email", "test@example.com"]);
+
+        let tracked_file = repo_path.join("tracked.txt");
+        std::fs::write(&tracked_file, "hello\n").expect("write should succeed");
+        assert_git_success(&repo_path, &["add", "."]);
+        assert_git_success(&repo_path, &["commit", "-m", "init"]);
+
+        let worktree_path = base_dir.join("worktree");
+        let branch_name = format!("luban-test-branch-{unique}");
+        assert_git_success(
+            &repo_path,
+            &[
+                "worktree",
+                "add",
+                "-b",
+                &branch_name,
+                worktree_path
+                    .to_str()
+                    .expect("worktree path should be utf-8"),
+            ],
+        );
+
+        let dirty_file = worktree_path.join("tracked.txt");
+        std::fs::write(&dirty_file, "hello\ndirty\n").expect("write should succeed");
+
+        let no_force = run_git(
+            &repo_path,
+            &[
+                "worktree",
+                "remove",
+                worktree_path
+                    .to_str()
+                    .expect("worktree path should be utf-8"),
+            ],
+        );
+        assert!(
+            !no_force.status.success(),
+            "worktree remove without --force should fail for dirty worktree"
+        );
+
+        let sqlite =
+            SqliteStore::new(paths::sqlite_path(&base_dir)).expect("sqlite init should work");
+        let service = GitWorkspaceService {
+            worktrees_root: paths::worktrees_root(&base_dir),
+            conversations_root: paths::conversations_root(&base_dir),
+            task_prompts_root: paths::task_prompts_root(&base_dir),
+            sqlite,
+            claude_processes: Mutex::new(HashMap::new()),
+        };
+
+        ProjectWorkspaceService::archive_workspace(
+            &service,
+            repo_path.clone(),
+            worktree_path.clone(),
+            branch_name.clone(),
+        )
+        .expect("archive_workspace should remove dirty worktree with --force");
+        assert!(!worktree_path.exists(), "worktree path should be removed");
+
+        drop(service);
+        let _ = std::fs::remove_dir_all(&base_dir);
+    }
+
+    #[test]
+    fn git_stash_push_and_pop_round_trip_and_report_empty_stash() {
+        let unique = unix_epoch_nanos_now();
+        let base_dir =
+            std::env::temp_dir().join(format!("luban-git-stash-{}-{}", std::process::id(), unique));
+        let repo_path = base_dir.join("repo");
+        std::fs::create_dir_all(&repo_path).expect("repo dir should be created");
+
+        assert_git_success(&repo_path, &["init"]);
+        assert_git_success(&repo_path, &["config", "user.name", "Test User"]);
+        assert_git_success(&repo_path, &["config", "user.email", "test@example.com"]);
+        std::fs::write(repo_path.join("tracked.txt"), "hello\n").expect("write should succeed");
+        assert_git_success(&repo_path, &["add", "."]);
+        assert_git_success(&repo_path, &["commit", "-m", "init"]);
+
+        let sqlite =
+            SqliteStore::new(paths::sqlite_path(&base_dir)).expect("sqlite init should work");
+        let service = GitWorkspaceService {
+            worktrees_root: paths::worktrees_root(&base_dir),
+            conversations_root: paths::conversations_root(&base_dir),
+            task_prompts_root: paths::task_prompts_root(&base_dir),
+            sqlite,
+            claude_processes: Mutex::new(HashMap::new()),
+        };
+
+        assert_eq!(
+            ProjectWorkspaceService::git_stash_push(&service, repo_path.clone()),
+            Ok(false)
+        );
+        let err = ProjectWorkspaceService::git_stash_pop(&service, repo_path.clone())
+            .expect_err("popping an empty stash should fail");
+        assert_eq!(err, "No stashed changes to restore in this workdir");
+
+        std::fs::write(repo_path.join("tracked.txt"), "hello\nedited\n")
+            .expect("write should succeed");
+        std::fs::write(repo_path.join("untracked.txt"), "new\n").expect("write should succeed");
+        assert_eq!(
+            ProjectWorkspaceService::git_stash_push(&service, repo_path.clone()),
+            Ok(true)
+        );
+        assert!(!repo_path.join("untracked.txt").exists());
+        assert_eq!(
+            std::fs::read_to_string(repo_path.join("tracked.txt")).unwrap(),
+            "hello\n"
+        );
+
+        ProjectWorkspaceService::git_stash_pop(&service, repo_path.clone())
+            .expect("stash pop should succeed");
+        assert_eq!(
+            std::fs::read_to_string(repo_path.join("tracked.txt")).unwrap(),
+            "hello\nedited\n"
+        );
+        assert!(repo_path.join("untracked.txt").exists());
+
+        drop(service);
+        let _ = std::fs::remove_dir_all(&base_dir);
+    }
+
+    #[test]
+    fn gh_pull_request_info_rejects_unknown_remote_and_base_branch() {
+        let unique = unix_epoch_nanos_now();
+        let base_dir =
+            std::env::temp_dir().join(format!("luban-pr-target-{}-{}", std::process::id(), unique));
+        let repo_path = base_dir.join("repo");
+        std::fs::create_dir_all(&repo_path).expect("repo dir should be created");
+
+        assert_git_success(&repo_path, &["init", "-b", "feature"]);
+        assert_git_success(&repo_path, &["config", "user.name", "Test User"]);
+        assert_git_success(&repo_path, &["config", "user.email", "test@example.com"]);
+        assert_git_success(&repo_path, &["commit", "--allow-empty", "-m", "init"]);
+        assert_git_success(
+            &repo_path,
+            &[
+                "remote",
+                "add",
+                "upstream",
+                "git@github.com:acme/widgets.git",
+            ],
+        );
+
+        let sqlite =
+            SqliteStore::new(paths::sqlite_path(&base_dir)).expect("sqlite init should work");
+        let service = GitWorkspaceService {
+            worktrees_root: paths::worktrees_root(&base_dir),
+            conversations_root: paths::conversations_root(&base_dir),
+            task_prompts_root: paths::task_prompts_root(&base_dir),
+            sqlite,
+            claude_processes: Mutex::new(HashMap::new()),
+        };
+
+        let err = ProjectWorkspaceService::gh_pull_request_info(
+            &service,
+            repo_path.clone(),
+            PullRequestTarget {
+                remote: Some("fork".to_owned()),
+                base_branch: None,
+            },
+        )
+        .expect_err("an unknown remote should fail");
+        assert_eq!(err, "Git remote 'fork' does not exist");
+
+        let err = ProjectWorkspaceService::gh_pull_request_info(
+            &service,
+            repo_path.clone(),
+            PullRequestTarget {
+                remote: Some("upstream".to_owned()),
+                base_branch: Some("develop".to_owned()),
+            },
+        )
+        .expect_err("a missing base branch should fail");
+        assert_eq!(err, "Base branch 'develop' does not exist");
+
+        drop(service);
+        let _ = std::fs::remove_dir_all(&base_dir);
+    }
+
+    #[test]
+    fn gh_pr_args_add_selector_and_repo_only_when_targeted() {
+        assert_eq!(
+            gh_pr_args(&["view", "--json", "number"], None, None),
+            ["pr", "view", "--json", "number"]
+        );
+        assert_eq!(
+            gh_pr_args(
+                &["checks", "--json", "bucket"],
+                Some("42"),
+                Some("acme/widgets")
+            ),
+            [
+                "pr",
+                "checks",
+                "42",
+                "--json",
+                "bucket",
+                "--repo",
+                "acme/widgets"
+            ]
+        );
+    }
+
+    #[test]
+    fn git_commit_commits_selected_paths_and_rejects_noop_commits() {
+        let unique = unix_epoch_nanos_now();
+        let base_dir = std::env::temp_dir().join(format!(
+            "luban-git-commit-{}-{}",
+            std::process::id(),
+            unique
+        ));
+        let repo_path = base_dir.join("repo");
+        std::fs::create_dir_all(&repo_path).expect("repo dir should be created");
+
+        assert_git_success(&repo_path, &["init"]);
+        assert_git_success(&repo_path, &["config", "user.name", "Test User"]);
+        assert_git_success(&repo_path, &["config", "user.email", "test@example.com"]);
+        std::fs::write(repo_path.join("tracked.txt"), "hello\n").expect("write should succeed");
+        assert_git_success(&repo_path, &["add", "."]);
+        assert_git_success(&repo_path, &["commit", "-m", "init"]);
+
+        let sqlite =
+            SqliteStore::new(paths::sqlite_path(&base_dir)).expect("sqlite init should work");
+        let service = GitWorkspaceService {
+            worktrees_root: paths::worktrees_root(&base_dir),
+            conversations_root: paths::conversations_root(&base_dir),
+            task_prompts_root: paths::task_prompts_root(&base_dir),
+            sqlite,
+            claude_processes: Mutex::new(HashMap::new()),
+        };
+
+        let err = ProjectWorkspaceService::git_commit(
+            &service,
+            repo_path.clone(),
+            "noop".to_owned(),
+            None,
+        )
+        .expect_err("committing a clean worktree should fail");
+        assert_eq!(err, "Nothing to commit in this workdir");
+
+        std::fs::write(repo_path.join("tracked.txt"), "hello\nedited\n")
+            .expect("write should succeed");
+        std::fs::write(repo_path.join("new.txt"), "new\n").expect("write should succeed");
+        let short = ProjectWorkspaceService::git_commit(
+            &service,
+            repo_path.clone(),
+            "Add new file".to_owned(),
+            Some(vec!["new.txt".to_owned()]),
+        )
+        .expect("commit should succeed");
+        let head = git_rev_parse(&repo_path, "HEAD");
+        assert!(head.starts_with(&short), "{short} vs {head}");
+
+        let status = run_git(&repo_path, &["status", "--porcelain"]);
+        assert_eq!(
+            String::from_utf8_lossy(&status.stdout).trim(),
+            "M tracked.txt",
+            "only the selected path should be committed"
+        );
+
+        ProjectWorkspaceService::git_commit(
+            &service,
+            repo_path.clone(),
+            "Edit tracked".to_owned(),
+            None,
+        )
+        .expect("committing everything should succeed");
+        let status = run_git(&repo_path, &["status", "--porcelain"]);
+        assert!(status.stdout.is_empty());
+
+        drop(service);
+        let _ = std::fs::remove_dir_all(&base_dir);
+    }
+
+    #[test]
+    fn git_last_commit_reports_head_commit_and_none_for_empty_repo() {
+        let unique = unix_epoch_nanos_now();
+        let base_dir = std::env::temp_dir().join(format!(
+            "luban-git-last-commit-{}-{}",
+            std::process::id(),
+            unique
+        ));
+        let repo_path = base_dir.join("repo");
+        std::fs::create_dir_all(&repo_path).expect("repo dir should be created");
+
+        assert_git_success(&repo_path, &["init"]);
+        assert_git_success(&repo_path, &["config", "user.name", "Test User"]);
+        assert_git_success(&repo_path, &["config", "user.email", "test@example.com"]);
+
+        let sqlite =
+            SqliteStore::new(paths::sqlite_path(&base_dir)).expect("sqlite init should work");
+        let service = GitWorkspaceService {
+            worktrees_root: paths::worktrees_root(&base_dir),
+            conversations_root: paths::conversations_root(&base_dir),
+            task_prompts_root: paths::task_prompts_root(&base_dir),
+            sqlite,
+            claude_processes: Mutex::new(HashMap::new()),
+        };
+
+        assert_eq!(
+            ProjectWorkspaceService::git_last_commit(&service, repo_path.clone()),
+            Ok(None)
+        );
+
+        std::fs::write(repo_path.join("a.txt"), "a\n").expect("write should succeed");
+        assert_git_success(&repo_path, &["add", "."]);
+        assert_git_success(
+            &repo_path,
+            &["commit", "-m", "Add a.txt", "--date", "@1700000000 +0000"],
+        );
+        let head = git_rev_parse(&repo_path, "HEAD");
+
+        let last = ProjectWorkspaceService::git_last_commit(&service, repo_path.clone())
+            .expect("git_last_commit should succeed")
+            .expect("repo should have a last commit");
+        assert_eq!(last.sha, head);
+        assert_eq!(last.subject, "Add a.txt");
+        assert_eq!(last.author, "Test User");
+        assert_eq!(last.unix_time, 1_700_000_000);
+
+        drop(service);
+        let _ = std::fs::remove_dir_all(&base_dir);
+    }
+
+    #[test]
+    fn archive_workspace_deletes_luban_branch_after_removing_worktree() {
+        let unique = unix_epoch_nanos_now();
+        let base_dir = std::env::temp_dir().join(format!(
+            "luban-archive-workspace-branch-delete-{}-{}",
+            std::process::id(),
+            unique
+        ));
+
+        std::fs::create_dir_all(&base_dir).expect("temp dir should be created");
+
+        let repo_path = base_dir.join("repo");
+        std::fs::create_dir_all(&repo_path).expect("repo dir should be created");
+
+        assert_git_success(&repo_path, &["init"]);
+        assert_git_success(&repo_path, &["config", "user.name", "Test User"]);
+        assert_git_success(&repo_path, &["config", "user.email", "test@example.com"]);
+
+        let tracked_file = repo_path.join("tracked.txt");
+        std::fs::write(&tracked_file, "hello\n").expect("write should succeed");
+        assert_git_success(&repo_path, &["add", "."]);
+        assert_git_success(&repo_path, &["commit", "-m", "init"]);
+
+        let worktree_path = base_dir.join("worktree");
+        let branch_name = format!("luban/test-branch-{unique}");
+        assert_git_success(
+            &repo_path,
+            &[
+                "worktree",
+                "add",
+                "-b",
+                &branch_name,
+                worktree_path
+                    .to_str()
+                    .expect("worktree path should be utf-8"),
+            ],
+        );
+
+        assert!(
+            branch_exists(&repo_path, &branch_name),
+            "expected local branch to exist before archive"
+        );
+
+        let sqlite =
+            SqliteStore::new(paths::sqlite_path(&base_dir)).expect("sqlite init should work");
+        let service = GitWorkspaceService {
+            worktrees_root: paths::worktrees_root(&base_dir),
+            conversations_root: paths::conversations_root(&base_dir),
+            task_prompts_root: paths::task_prompts_root(&base_dir),
+            sqlite,
+            claude_processes: Mutex::new(HashMap::new()),
+        };
+
+        ProjectWorkspaceService::archive_workspace(
+            &service,
+            repo_path.clone(),
+            worktree_path.clone(),
+            branch_name.clone(),
+        )
+        .expect("archive_workspace should remove worktree and delete local luban branch");
+
+        assert!(!worktree_path.exists(), "worktree path should be removed");
+        assert!(
+            !branch_exists(&repo_path, &branch_name),
+            "expected local branch to be deleted after archive"
+        );
+
+        drop(service);
+        let _ = std::fs::remove_dir_all(&base_dir);
+    }
+
+    #[test]
+    fn load_app_state_archives_missing_worktrees() {
+        let unique = unix_epoch_nanos_now();
+        let base_dir = std::env::temp_dir().join(format!(
+            "luban-load-archives-missing-worktree-{}-{}",
+            std::process::id(),
+            unique
+        ));
+
+        std::fs::create_dir_all(&base_dir).expect("temp dir should be created");
+
+        let repo_path = base_dir.join("repo");
+        std::fs::create_dir_all(&repo_path).expect("repo dir should be created");
+
+        assert_git_success(&repo_path, &["init"]);
+        assert_git_success(&repo_path, &["config", "user.name", "Test User"]);
         assert_git_success(&repo_path, &["config", "user.email", "test@example.com"]);
+        assert_git_success(&repo_path, &["checkout", "-b", "main"]);
+
+        std::fs::write(repo_path.join("README.md"), "init\n").expect("write should succeed");
+        assert_git_success(&repo_path, &["add", "."]);
+        assert_git_success(&repo_path, &["commit", "-m", "init"]);
+
+        let worktree_path = base_dir.join("worktree");
+        let branch_name = format!("luban/review-lance-{}", unique % 10_000);
+        assert_git_success(
+            &repo_path,
+            &[
+                "worktree",
+                "add",
+                "-b",
+                &branch_name,
+                worktree_path
+                    .to_str()
+                    .expect("worktree path should be utf-8"),
+            ],
+        );
+        assert!(worktree_path.exists(), "worktree path should exist");
+
+        assert_git_success(
+            &repo_path,
+            &[
+                "worktree",
+                "remove",
+                "--force",
+                worktree_path
+                    .to_str()
+                    .expect("worktree path should be utf-8"),
+            ],
+        );
+        assert!(!worktree_path.exists(), "worktree path should be removed");
+
+        let sqlite =
+            SqliteStore::new(paths::sqlite_path(&base_dir)).expect("sqlite init should work");
+        let service = GitWorkspaceService {
+            worktrees_root: paths::worktrees_root(&base_dir),
+            conversations_root: paths::conversations_root(&base_dir),
+            task_prompts_root: paths::task_prompts_root(&base_dir),
+            sqlite,
+            claude_processes: Mutex::new(HashMap::new()),
+        };
+
+        let snapshot = PersistedAppState {
+            projects: vec![PersistedProject {
+                id: 1,
+                name: "repo".to_owned(),
+                path: repo_path.clone(),
+                slug: "repo".to_owned(),
+                is_git: true,
+                expanded: true,
+                system_prompt: None,
+                worktree_root: None,
+                branch_name_template: None,
+                status: ProjectStatus::Active,
+                agent_defaults: Default::default(),
+                saved_commands: Vec::new(),
+                agent_env: Vec::new(),
+                pr_remote: None,
+                pr_base_branch: None,
+                workspaces: vec![PersistedWorkspace {
+                    id: 1,
+                    workspace_name: "review-lance-5713".to_owned(),
+                    branch_name: branch_name.clone(),
+                    worktree_path: worktree_path.clone(),
+                    status: WorkspaceStatus::Active,
+                    last_activity_at_unix_seconds: None,
+                }],
+            }],
+            sidebar_width: None,
+            terminal_pane_width: None,
+            global_zoom_percent: None,
+            appearance_theme: None,
+            appearance_ui_font: None,
+            appearance_chat_font: None,
+            appearance_code_font: None,
+            appearance_terminal_font: None,
+            agent_default_model_id: None,
+            agent_runner_default_models: HashMap::new(),
+            agent_runner_prompt_prefixes: HashMap::new(),
+            agent_runner_prompt_suffixes: HashMap::new(),
+            model_thinking_effort_caps: HashMap::new(),
+            agent_default_thinking_effort: None,
+            agent_default_runner: None,
+            agent_amp_mode: None,
+            agent_codex_enabled: Some(true),
+            agent_amp_enabled: Some(true),
+            agent_claude_enabled: Some(true),
+            agent_droid_enabled: Some(true),
+            last_open_workspace_id: None,
+            open_button_selection: None,
+            sidebar_project_order: Vec::new(),
+            project_groups: Vec::new(),
+            workspace_active_thread_id: std::collections::HashMap::new(),
+            workspace_open_tabs: std::collections::HashMap::new(),
+            workspace_archived_tabs: std::collections::HashMap::new(),
+            workspace_next_thread_id: std::collections::HashMap::new(),
+            workspace_chat_scroll_y10: std::collections::HashMap::new(),
+            workspace_chat_scroll_anchor: std::collections::HashMap::new(),
+            workspace_unread_completions: std::collections::HashMap::new(),
+            workspace_thread_run_config_overrides: std::collections::HashMap::new(),
+            starred_tasks: std::collections::HashMap::new(),
+            task_prompt_templates: std::collections::HashMap::new(),
+            telegram_enabled: None,
+            telegram_bot_token: None,
+            telegram_bot_username: None,
+            telegram_paired_chat_ids: Vec::new(),
+            telegram_topic_bindings: None,
+            webhook_url: None,
+            webhook_secret: None,
+            agent_merge_reasoning_entries: None,
+            agent_pause_queue_on_patch_failure: None,
+            agent_item_dedup_window: None,
+            agent_auto_retry_on_failure: None,
+            agent_command_output_max_bytes: None,
+            auto_archive_after_days: None,
+        };
+
+        service
+            .sqlite
+            .save_app_state(snapshot)
+            .expect("sqlite save should succeed");
+
+        let loaded = service
+            .load_app_state_internal()
+            .expect("load_app_state_internal should succeed");
+        assert_eq!(
+            loaded.projects[0].workspaces[0].status,
+            WorkspaceStatus::Archived
+        );
+
+        let persisted = service
+            .sqlite
+            .load_app_state()
+            .expect("sqlite load should succeed");
+        assert_eq!(
+            persisted.projects[0].workspaces[0].status,
+            WorkspaceStatus::Archived
+        );
+
+        drop(service);
+        let _ = std::fs::remove_dir_all(&base_dir);
+    }
+
+    #[test]
+    fn create_workspace_bases_on_origin_main_and_does_not_track_upstream() {
+        let unique = unix_epoch_nanos_now();
+        let base_dir = std::env::temp_dir().join(format!(
+            "luban-create-workspace-origin-main-{}-{}",
+            std::process::id(),
+            unique
+        ));
+
+        std::fs::create_dir_all(&base_dir).expect("temp dir should be created");
+
+        let remote_dir = base_dir.join("remote.git");
+        std::fs::create_dir_all(&remote_dir).expect("remote dir should be created");
+        assert_git_success(&remote_dir, &["init", "--bare"]);
+        assert_git_success(&remote_dir, &["symbolic-ref", "HEAD", "refs/heads/main"]);
+
+        let project_dir = base_dir.join("repo");
+        std::fs::create_dir_all(&project_dir).expect("repo dir should be created");
+        assert_git_success(&project_dir, &["init"]);
+        assert_git_success(&project_dir, &["config", "user.name", "Test User"]);
+        assert_git_success(&project_dir, &["config", "user.email", "test@example.com"]);
+        assert_git_success(&project_dir, &["checkout", "-b", "main"]);
+
+        std::fs::write(project_dir.join("README.md"), "init\n").expect("write should succeed");
+        assert_git_success(&project_dir, &["add", "."]);
+        assert_git_success(&project_dir, &["commit", "-m", "init"]);
+        assert_git_success(
+            &project_dir,
+            &[
+                "remote",
+                "add",
+                "origin",
+                remote_dir.to_str().expect("remote path should be utf-8"),
+            ],
+        );
+        assert_git_success(&project_dir, &["push", "-u", "origin", "main"]);
+        assert_git_success(&project_dir, &["fetch", "--prune", "origin", "main"]);
+
+        let upstream_clone = base_dir.join("upstream");
+        assert_git_success(
+            &base_dir,
+            &[
+                "clone",
+                remote_dir.to_str().expect("remote path should be utf-8"),
+                upstream_clone
+                    .to_str()
+                    .expect("upstream clone path should be utf-8"),
+            ],
+        );
+        assert_git_success(&upstream_clone, &["config", "user.name", "Upstream User"]);
+        assert_git_success(
+            &upstream_clone,
+            &["config", "user.email", "upstream@example.com"],
+        );
+        std::fs::write(upstream_clone.join("CHANGELOG.md"), "upstream\n")
+            .expect("write should succeed");
+        assert_git_success(&upstream_clone, &["add", "."]);
+        assert_git_success(&upstream_clone, &["commit", "-m", "upstream"]);
+        assert_git_success(&upstream_clone, &["push", "origin", "main"]);
+        let upstream_head = git_rev_parse(&upstream_clone, "HEAD^{commit}");
+
+        std::fs::write(project_dir.join("LOCAL.md"), "local only\n").expect("write should succeed");
+        assert_git_success(&project_dir, &["add", "."]);
+        assert_git_success(&project_dir, &["commit", "-m", "local"]);
+
+        let sqlite =
+            SqliteStore::new(paths::sqlite_path(&base_dir)).expect("sqlite init should work");
+        let service = GitWorkspaceService {
+            worktrees_root: paths::worktrees_root(&base_dir),
+            conversations_root: paths::conversations_root(&base_dir),
+            task_prompts_root: paths::task_prompts_root(&base_dir),
+            sqlite,
+            claude_processes: Mutex::new(HashMap::new()),
+        };
+
+        let created = ProjectWorkspaceService::create_workspace(
+            &service,
+            project_dir.clone(),
+            "proj".to_owned(),
+            None,
+            None,
+            None,
+        )
+        .expect("create_workspace should succeed");
+
+        let head = git_rev_parse(&created.worktree_path, "HEAD^{commit}");
+        assert_eq!(
+            head, upstream_head,
+            "expected workspace to be created from origin/main (after fetch)"
+        );
+
+        let upstream_config_key = format!("branch.{}.remote", created.branch_name);
+        let config = Command::new("git")
+            .args(["config", "--get", &upstream_config_key])
+            .current_dir(&project_dir)
+            .output()
+            .expect("git config should spawn");
+        assert!(
+            !config.status.success(),
+            "expected branch to not track upstream, but {} is set to {:?}",
+            upstream_config_key,
+            String::from_utf8_lossy(&config.stdout).trim()
+        );
+        assert_eq!(
+            created.worktree_path,
+            paths::worktrees_root(&base_dir)
+                .join("proj")
+                .join(&created.workspace_name)
+        );
+
+        let custom_root = base_dir.join("big-volume").join("worktrees");
+        let preview = |hint: &str| {
+            ProjectWorkspaceService::preview_workspace(
+                &service,
+                project_dir.clone(),
+                "proj".to_owned(),
+                Some(hint.to_owned()),
+                Some(custom_root.clone()),
+                None,
+            )
+            .expect("preview_workspace should succeed")
+        };
+        let previewed = preview("Custom Root");
+        assert!(!previewed.worktree_path.exists());
+        let custom = ProjectWorkspaceService::create_workspace(
+            &service,
+            project_dir.clone(),
+            "proj".to_owned(),
+            Some("custom-root".to_owned()),
+            Some(custom_root.clone()),
+            None,
+        )
+        .expect("create_workspace under a custom root should succeed");
+        assert_eq!(
+            custom.worktree_path,
+            custom_root.join("proj").join("custom-root")
+        );
+        assert_eq!(previewed.workspace_name, custom.workspace_name);
+        assert_eq!(previewed.branch_name, custom.branch_name);
+        assert_eq!(previewed.worktree_path, custom.worktree_path);
+        assert_eq!(preview("custom-root").branch_name, "luban/custom-root-v2");
+
+        let random = ProjectWorkspaceService::preview_workspace(
+            &service,
+            project_dir.clone(),
+            "proj".to_owned(),
+            None,
+            Some(custom_root.clone()),
+            None,
+        )
+        .expect("preview_workspace without a hint should succeed");
+        let from_random = ProjectWorkspaceService::create_workspace(
+            &service,
+            project_dir.clone(),
+            "proj".to_owned(),
+            Some(random.workspace_name.clone()),
+            Some(custom_root.clone()),
+            None,
+        )
+        .expect("create_workspace from a previewed name should succeed");
+        assert_eq!(from_random.workspace_name, random.workspace_name);
+        assert_eq!(from_random.branch_name, random.branch_name);
+        assert_eq!(from_random.worktree_path, random.worktree_path);
+
+        let templated = ProjectWorkspaceService::create_workspace(
+            &service,
+            project_dir.clone(),
+            "proj".to_owned(),
+            Some("Templated".to_owned()),
+            None,
+            Some(BranchNameTemplate {
+                template: "{slug}/{name}".to_owned(),
+                project_slug: "my-proj".to_owned(),
+            }),
+        )
+        .expect("create_workspace with a branch name template should succeed");
+        assert_eq!(templated.workspace_name, "templated");
+        assert_eq!(templated.branch_name, "my-proj/templated");
+        assert!(branch_exists(&project_dir, "my-proj/templated"));
+        assert_eq!(
+            git_rev_parse(&custom.worktree_path, "HEAD^{commit}"),
+            upstream_head
+        );
+
+        drop(service);
+        let _ = std::fs::remove_dir_all(&base_dir);
+    }
+
+    #[test]
+    fn detect_default_branch_reports_master_and_create_workspace_bases_on_it() {
+        let unique = unix_epoch_nanos_now();
+        let base_dir = std::env::temp_dir().join(format!(
+            "luban-default-branch-master-{}-{}",
+            std::process::id(),
+            unique
+        ));
+        std::fs::create_dir_all(&base_dir).expect("temp dir should be created");
+
+        let remote_dir = base_dir.join("remote.git");
+        std::fs::create_dir_all(&remote_dir).expect("remote dir should be created");
+        assert_git_success(&remote_dir, &["init", "--bare"]);
+        assert_git_success(&remote_dir, &["symbolic-ref", "HEAD", "refs/heads/master"]);
+
+        let seed_dir = base_dir.join("seed");
+        std::fs::create_dir_all(&seed_dir).expect("seed dir should be created");
+        assert_git_success(&seed_dir, &["init"]);
+        assert_git_success(&seed_dir, &["config", "user.name", "Test User"]);
+        assert_git_success(&seed_dir, &["config", "user.email", "test@example.com"]);
+        assert_git_success(&seed_dir, &["checkout", "-b", "master"]);
+        std::fs::write(seed_dir.join("README.md"), "init\n").expect("write should succeed");
+        assert_git_success(&seed_dir, &["add", "."]);
+        assert_git_success(&seed_dir, &["commit", "-m", "init"]);
+        assert_git_success(
+            &seed_dir,
+            &[
+                "remote",
+                "add",
+                "origin",
+                remote_dir.to_str().expect("remote path should be utf-8"),
+            ],
+        );
+        assert_git_success(&seed_dir, &["push", "-u", "origin", "master"]);
+        assert_git_success(&seed_dir, &["checkout", "-b", "feature"]);
+        let master_head = git_rev_parse(&seed_dir, "master^{commit}");
+
+        let project_dir = base_dir.join("repo");
+        assert_git_success(
+            &base_dir,
+            &[
+                "clone",
+                remote_dir.to_str().expect("remote path should be utf-8"),
+                project_dir.to_str().expect("repo path should be utf-8"),
+            ],
+        );
+
+        let sqlite =
+            SqliteStore::new(paths::sqlite_path(&base_dir)).expect("sqlite init should work");
+        let service = GitWorkspaceService {
+            worktrees_root: paths::worktrees_root(&base_dir),
+            conversations_root: paths::conversations_root(&base_dir),
+            task_prompts_root: paths::task_prompts_root(&base_dir),
+            sqlite,
+            claude_processes: Mutex::new(HashMap::new()),
+        };
+
+        // The clone records `origin/HEAD`; the seed repo has no such ref and is on `feature`, so
+        // it falls back to the `master` branch it pushed to origin.
+        for repo in [&project_dir, &seed_dir] {
+            assert_eq!(
+                ProjectWorkspaceService::detect_default_branch(&service, repo.clone()),
+                Ok(Some("master".to_owned())),
+                "default branch of {}",
+                repo.display()
+            );
+        }
+
+        let created = ProjectWorkspaceService::create_workspace(
+            &service,
+            project_dir.clone(),
+            "proj".to_owned(),
+            None,
+            None,
+            None,
+        )
+        .expect("create_workspace should succeed without a main branch");
+        assert_eq!(
+            git_rev_parse(&created.worktree_path, "HEAD^{commit}"),
+            master_head
+        );
+
+        drop(service);
+        let _ = std::fs::remove_dir_all(&base_dir);
+    }
+
+    #[test]
+    fn create_workspace_from_branch_checks_out_existing_local_and_remote_branches() {
+        let unique = unix_epoch_nanos_now();
+        let base_dir = std::env::temp_dir().join(format!(
+            "luban-create-workspace-from-branch-{}-{}",
+            std::process::id(),
+            unique
+        ));
+        std::fs::create_dir_all(&base_dir).expect("temp dir should be created");
 
-        let tracked_file = repo_path.join("tracked.txt");
-        std::fs::write(&tracked_file, "hello\n").expect("write should succeed");
-        assert_git_success(&repo_path, &["add", "."]);
-        assert_git_success(&repo_path, &["commit", "-m", "init"]);
+        let remote_dir = base_dir.join("remote.git");
+        std::fs::create_dir_all(&remote_dir).expect("remote dir should be created");
+        assert_git_success(&remote_dir, &["init", "--bare"]);
+        assert_git_success(&remote_dir, &["symbolic-ref", "HEAD", "refs/heads/main"]);
 
-        let worktree_path = base_dir.join("worktree");
-        let branch_name = format!("luban/test-branch-{unique}");
+        let project_dir = base_dir.join("repo");
+        std::fs::create_dir_all(&project_dir).expect("repo dir should be created");
+        assert_git_success(&project_dir, &["init"]);
+        assert_git_success(&project_dir, &["config", "user.name", "Test User"]);
+        assert_git_success(&project_dir, &["config", "user.email", "test@example.com"]);
+        assert_git_success(&project_dir, &["checkout", "-b", "main"]);
+        std::fs::write(project_dir.join("README.md"), "init\n").expect("write should succeed");
+        assert_git_success(&project_dir, &["add", "."]);
+        assert_git_success(&project_dir, &["commit", "-m", "init"]);
         assert_git_success(
-            &repo_path,
+            &project_dir,
             &[
-                "worktree",
+                "remote",
                 "add",
-                "-b",
-                &branch_name,
-                worktree_path
-                    .to_str()
-                    .expect("worktree path should be utf-8"),
+                "origin",
+                remote_dir.to_str().expect("remote path should be utf-8"),
             ],
         );
+        assert_git_success(&project_dir, &["push", "-u", "origin", "main"]);
 
-        assert!(
-            branch_exists(&repo_path, &branch_name),
-            "expected local branch to exist before archive"
+        assert_git_success(&project_dir, &["branch", "feature/local"]);
+        let local_head = git_rev_parse(&project_dir, "feature/local^{commit}");
+
+        let teammate_clone = base_dir.join("teammate");
+        assert_git_success(
+            &base_dir,
+            &[
+                "clone",
+                remote_dir.to_str().expect("remote path should be utf-8"),
+                teammate_clone
+                    .to_str()
+                    .expect("teammate clone path should be utf-8"),
+            ],
+        );
+        assert_git_success(&teammate_clone, &["config", "user.name", "Teammate"]);
+        assert_git_success(
+            &teammate_clone,
+            &["config", "user.email", "teammate@example.com"],
         );
+        assert_git_success(&teammate_clone, &["checkout", "-b", "ci/fix-build"]);
+        std::fs::write(teammate_clone.join("FIX.md"), "fix\n").expect("write should succeed");
+        assert_git_success(&teammate_clone, &["add", "."]);
+        assert_git_success(&teammate_clone, &["commit", "-m", "fix"]);
+        assert_git_success(&teammate_clone, &["push", "origin", "ci/fix-build"]);
+        let remote_head = git_rev_parse(&teammate_clone, "HEAD^{commit}");
 
         let sqlite =
             SqliteStore::new(paths::sqlite_path(&base_dir)).expect("sqlite init should work");
@@ -4326,75 +6410,183 @@ mod tests {
             claude_processes: Mutex::new(HashMap::new()),
         };
 
-        ProjectWorkspaceService::archive_workspace(
+        let local = ProjectWorkspaceService::create_workspace_from_branch(
             &service,
-            repo_path.clone(),
-            worktree_path.clone(),
-            branch_name.clone(),
+            project_dir.clone(),
+            "proj".to_owned(),
+            "feature/local".to_owned(),
+            None,
         )
-        .expect("archive_workspace should remove worktree and delete local luban branch");
+        .expect("creating from a local branch should succeed");
+        assert_eq!(local.branch_name, "feature/local");
+        assert_eq!(local.workspace_name, "feature-local");
+        assert_eq!(
+            git_rev_parse(&local.worktree_path, "HEAD^{commit}"),
+            local_head
+        );
+        let head_branch = Command::new("git")
+            .args(["symbolic-ref", "--short", "HEAD"])
+            .current_dir(&local.worktree_path)
+            .output()
+            .expect("git symbolic-ref should spawn");
+        assert_eq!(
+            String::from_utf8_lossy(&head_branch.stdout).trim(),
+            "feature/local"
+        );
 
-        assert!(!worktree_path.exists(), "worktree path should be removed");
+        let remote = ProjectWorkspaceService::create_workspace_from_branch(
+            &service,
+            project_dir.clone(),
+            "proj".to_owned(),
+            "ci/fix-build".to_owned(),
+            None,
+        )
+        .expect("creating from a remote-only branch should succeed");
+        assert_eq!(remote.branch_name, "ci/fix-build");
+        assert_eq!(
+            git_rev_parse(&remote.worktree_path, "HEAD^{commit}"),
+            remote_head
+        );
+
+        let err = ProjectWorkspaceService::create_workspace_from_branch(
+            &service,
+            project_dir.clone(),
+            "proj".to_owned(),
+            "feature/local".to_owned(),
+            None,
+        )
+        .expect_err("a branch checked out elsewhere should be rejected");
         assert!(
-            !branch_exists(&repo_path, &branch_name),
-            "expected local branch to be deleted after archive"
+            err.contains("already checked out"),
+            "unexpected error: {err}"
         );
 
+        let err = ProjectWorkspaceService::create_workspace_from_branch(
+            &service,
+            project_dir.clone(),
+            "proj".to_owned(),
+            "does-not-exist".to_owned(),
+            None,
+        )
+        .expect_err("a missing branch should be rejected");
+        assert!(err.contains("does not exist"), "unexpected error: {err}");
+
         drop(service);
         let _ = std::fs::remove_dir_all(&base_dir);
     }
 
     #[test]
-    fn load_app_state_archives_missing_worktrees() {
+    fn audit_worktrees_reports_untracked_worktrees_and_missing_tracked_paths() {
         let unique = unix_epoch_nanos_now();
         let base_dir = std::env::temp_dir().join(format!(
-            "luban-load-archives-missing-worktree-{}-{}",
+            "luban-audit-worktrees-{}-{}",
             std::process::id(),
             unique
         ));
-
         std::fs::create_dir_all(&base_dir).expect("temp dir should be created");
 
-        let repo_path = base_dir.join("repo");
-        std::fs::create_dir_all(&repo_path).expect("repo dir should be created");
+        let project_dir = base_dir.join("repo");
+        std::fs::create_dir_all(&project_dir).expect("repo dir should be created");
+        assert_git_success(&project_dir, &["init"]);
+        assert_git_success(&project_dir, &["config", "user.name", "Test User"]);
+        assert_git_success(&project_dir, &["config", "user.email", "test@example.com"]);
+        assert_git_success(&project_dir, &["checkout", "-b", "main"]);
+        std::fs::write(project_dir.join("README.md"), "init\n").expect("write should succeed");
+        assert_git_success(&project_dir, &["add", "."]);
+        assert_git_success(&project_dir, &["commit", "-m", "init"]);
 
-        assert_git_success(&repo_path, &["init"]);
-        assert_git_success(&repo_path, &["config", "user.name", "Test User"]);
-        assert_git_success(&repo_path, &["config", "user.email", "test@example.com"]);
-        assert_git_success(&repo_path, &["checkout", "-b", "main"]);
+        let tracked_worktree = base_dir.join("tracked");
+        let manual_worktree = base_dir.join("manual");
+        for (path, branch) in [
+            (&tracked_worktree, "luban/tracked"),
+            (&manual_worktree, "manual"),
+        ] {
+            assert_git_success(
+                &project_dir,
+                &[
+                    "worktree",
+                    "add",
+                    "-b",
+                    branch,
+                    path.to_str().expect("worktree path should be utf-8"),
+                ],
+            );
+        }
+        let missing_worktree = base_dir.join("missing");
 
-        std::fs::write(repo_path.join("README.md"), "init\n").expect("write should succeed");
-        assert_git_success(&repo_path, &["add", "."]);
-        assert_git_success(&repo_path, &["commit", "-m", "init"]);
+        let sqlite =
+            SqliteStore::new(paths::sqlite_path(&base_dir)).expect("sqlite init should work");
+        let service = GitWorkspaceService {
+            worktrees_root: paths::worktrees_root(&base_dir),
+            conversations_root: paths::conversations_root(&base_dir),
+            task_prompts_root: paths::task_prompts_root(&base_dir),
+            sqlite,
+            claude_processes: Mutex::new(HashMap::new()),
+        };
 
-        let worktree_path = base_dir.join("worktree");
-        let branch_name = format!("luban/review-lance-{}", unique % 10_000);
-        assert_git_success(
-            &repo_path,
-            &[
-                "worktree",
-                "add",
-                "-b",
-                &branch_name,
-                worktree_path
-                    .to_str()
-                    .expect("worktree path should be utf-8"),
+        let audit = ProjectWorkspaceService::audit_worktrees(
+            &service,
+            project_dir.clone(),
+            vec![
+                project_dir.clone(),
+                tracked_worktree.clone(),
+                missing_worktree.clone(),
             ],
+        )
+        .expect("audit should succeed");
+        assert_eq!(
+            audit.orphaned,
+            vec![std::fs::canonicalize(&manual_worktree).expect("manual worktree should exist")]
         );
-        assert!(worktree_path.exists(), "worktree path should exist");
+        assert_eq!(audit.dangling, vec![missing_worktree]);
 
-        assert_git_success(
-            &repo_path,
-            &[
-                "worktree",
-                "remove",
-                "--force",
-                worktree_path
-                    .to_str()
-                    .expect("worktree path should be utf-8"),
-            ],
-        );
-        assert!(!worktree_path.exists(), "worktree path should be removed");
+        drop(service);
+        let _ = std::fs::remove_dir_all(&base_dir);
+    }
+
+    #[test]
+    fn prune_orphaned_worktrees_reports_on_dry_run_and_keeps_tracked_worktrees() {
+        let unique = unix_epoch_nanos_now();
+        let base_dir = std::env::temp_dir().join(format!(
+            "luban-prune-worktrees-{}-{}",
+            std::process::id(),
+            unique
+        ));
+        std::fs::create_dir_all(&base_dir).expect("temp dir should be created");
+
+        let project_dir = base_dir.join("repo");
+        std::fs::create_dir_all(&project_dir).expect("repo dir should be created");
+        assert_git_success(&project_dir, &["init"]);
+        assert_git_success(&project_dir, &["config", "user.name", "Test User"]);
+        assert_git_success(&project_dir, &["config", "user.email", "test@example.com"]);
+        assert_git_success(&project_dir, &["checkout", "-b", "main"]);
+        std::fs::write(project_dir.join("README.md"), "init\n").expect("write should succeed");
+        assert_git_success(&project_dir, &["add", "."]);
+        assert_git_success(&project_dir, &["commit", "-m", "init"]);
+
+        let managed_dir = paths::worktrees_root(&base_dir).join("repo");
+        std::fs::create_dir_all(&managed_dir).expect("worktrees dir should be created");
+        let tracked_worktree = managed_dir.join("tracked");
+        let orphaned_worktree = managed_dir.join("orphaned");
+        for (path, branch) in [
+            (&tracked_worktree, "luban/tracked"),
+            (&orphaned_worktree, "luban/crashed"),
+        ] {
+            assert_git_success(
+                &project_dir,
+                &[
+                    "worktree",
+                    "add",
+                    "-b",
+                    branch,
+                    path.to_str().expect("worktree path should be utf-8"),
+                ],
+            );
+        }
+        std::fs::write(orphaned_worktree.join("scratch.txt"), "dirty\n")
+            .expect("write should succeed");
+        let orphaned_canonical =
+            std::fs::canonicalize(&orphaned_worktree).expect("orphaned worktree should exist");
 
         let sqlite =
             SqliteStore::new(paths::sqlite_path(&base_dir)).expect("sqlite init should work");
@@ -4405,151 +6597,90 @@ mod tests {
             sqlite,
             claude_processes: Mutex::new(HashMap::new()),
         };
+        let tracked = vec![project_dir.clone(), tracked_worktree.clone()];
 
-        let snapshot = PersistedAppState {
-            projects: vec![PersistedProject {
-                id: 1,
-                name: "repo".to_owned(),
-                path: repo_path.clone(),
-                slug: "repo".to_owned(),
-                is_git: true,
-                expanded: true,
-                workspaces: vec![PersistedWorkspace {
-                    id: 1,
-                    workspace_name: "review-lance-5713".to_owned(),
-                    branch_name: branch_name.clone(),
-                    worktree_path: worktree_path.clone(),
-                    status: WorkspaceStatus::Active,
-                    last_activity_at_unix_seconds: None,
-                }],
-            }],
-            sidebar_width: None,
-            terminal_pane_width: None,
-            global_zoom_percent: None,
-            appearance_theme: None,
-            appearance_ui_font: None,
-            appearance_chat_font: None,
-            appearance_code_font: None,
-            appearance_terminal_font: None,
-            agent_default_model_id: None,
-            agent_runner_default_models: HashMap::new(),
-            agent_default_thinking_effort: None,
-            agent_default_runner: None,
-            agent_amp_mode: None,
-            agent_codex_enabled: Some(true),
-            agent_amp_enabled: Some(true),
-            agent_claude_enabled: Some(true),
-            agent_droid_enabled: Some(true),
-            last_open_workspace_id: None,
-            open_button_selection: None,
-            sidebar_project_order: Vec::new(),
-            workspace_active_thread_id: std::collections::HashMap::new(),
-            workspace_open_tabs: std::collections::HashMap::new(),
-            workspace_archived_tabs: std::collections::HashMap::new(),
-            workspace_next_thread_id: std::collections::HashMap::new(),
-            workspace_chat_scroll_y10: std::collections::HashMap::new(),
-            workspace_chat_scroll_anchor: std::collections::HashMap::new(),
-            workspace_unread_completions: std::collections::HashMap::new(),
-            workspace_thread_run_config_overrides: std::collections::HashMap::new(),
-            starred_tasks: std::collections::HashMap::new(),
-            task_prompt_templates: std::collections::HashMap::new(),
-            telegram_enabled: None,
-            telegram_bot_token: None,
-            telegram_bot_username: None,
-            telegram_paired_chat_id: None,
-            telegram_topic_bindings: None,
-        };
-
-        service
-            .sqlite
-            .save_app_state(snapshot)
-            .expect("sqlite save should succeed");
+        let planned = ProjectWorkspaceService::prune_orphaned_worktrees(
+            &service,
+            project_dir.clone(),
+            "repo".to_owned(),
+            None,
+            tracked.clone(),
+            true,
+        )
+        .expect("dry run should succeed");
+        assert_eq!(planned, vec![orphaned_canonical.clone()]);
+        assert!(orphaned_worktree.exists(), "dry run must not delete");
 
-        let loaded = service
-            .load_app_state_internal()
-            .expect("load_app_state_internal should succeed");
-        assert_eq!(
-            loaded.projects[0].workspaces[0].status,
-            WorkspaceStatus::Archived
+        let pruned = ProjectWorkspaceService::prune_orphaned_worktrees(
+            &service,
+            project_dir.clone(),
+            "repo".to_owned(),
+            None,
+            tracked.clone(),
+            false,
+        )
+        .expect("prune should succeed");
+        assert_eq!(pruned, vec![orphaned_canonical]);
+        assert!(
+            !orphaned_worktree.exists(),
+            "orphaned worktree should be removed"
         );
-
-        let persisted = service
-            .sqlite
-            .load_app_state()
-            .expect("sqlite load should succeed");
-        assert_eq!(
-            persisted.projects[0].workspaces[0].status,
-            WorkspaceStatus::Archived
+        assert!(
+            tracked_worktree.join("README.md").exists(),
+            "tracked worktree must be untouched"
         );
 
+        let audit =
+            ProjectWorkspaceService::audit_worktrees(&service, project_dir.clone(), tracked)
+                .expect("audit should succeed");
+        assert!(audit.orphaned.is_empty(), "unexpected orphans: {audit:?}");
+        let listed = Command::new("git")
+            .args(["worktree", "list", "--porcelain"])
+            .current_dir(&project_dir)
+            .output()
+            .expect("git worktree list should spawn");
+        assert!(!String::from_utf8_lossy(&listed.stdout).contains("orphaned"));
+
         drop(service);
         let _ = std::fs::remove_dir_all(&base_dir);
     }
 
     #[test]
-    fn create_workspace_bases_on_origin_main_and_does_not_track_upstream() {
+    fn prune_orphaned_worktrees_leaves_worktrees_outside_the_project_root_alone() {
         let unique = unix_epoch_nanos_now();
         let base_dir = std::env::temp_dir().join(format!(
-            "luban-create-workspace-origin-main-{}-{}",
+            "luban-prune-worktrees-outside-{}-{}",
             std::process::id(),
             unique
         ));
-
-        std::fs::create_dir_all(&base_dir).expect("temp dir should be created");
-
-        let remote_dir = base_dir.join("remote.git");
-        std::fs::create_dir_all(&remote_dir).expect("remote dir should be created");
-        assert_git_success(&remote_dir, &["init", "--bare"]);
-        assert_git_success(&remote_dir, &["symbolic-ref", "HEAD", "refs/heads/main"]);
-
-        let project_dir = base_dir.join("repo");
-        std::fs::create_dir_all(&project_dir).expect("repo dir should be created");
-        assert_git_success(&project_dir, &["init"]);
-        assert_git_success(&project_dir, &["config", "user.name", "Test User"]);
-        assert_git_success(&project_dir, &["config", "user.email", "test@example.com"]);
-        assert_git_success(&project_dir, &["checkout", "-b", "main"]);
-
-        std::fs::write(project_dir.join("README.md"), "init\n").expect("write should succeed");
-        assert_git_success(&project_dir, &["add", "."]);
-        assert_git_success(&project_dir, &["commit", "-m", "init"]);
-        assert_git_success(
-            &project_dir,
-            &[
-                "remote",
-                "add",
-                "origin",
-                remote_dir.to_str().expect("remote path should be utf-8"),
-            ],
-        );
-        assert_git_success(&project_dir, &["push", "-u", "origin", "main"]);
-        assert_git_success(&project_dir, &["fetch", "--prune", "origin", "main"]);
-
-        let upstream_clone = base_dir.join("upstream");
-        assert_git_success(
-            &base_dir,
-            &[
-                "clone",
-                remote_dir.to_str().expect("remote path should be utf-8"),
-                upstream_clone
-                    .to_str()
-                    .expect("upstream clone path should be utf-8"),
-            ],
-        );
-        assert_git_success(&upstream_clone, &["config", "user.name", "Upstream User"]);
+        let main_checkout = base_dir.join("main-checkout");
+        std::fs::create_dir_all(&main_checkout).expect("repo dir should be created");
+        assert_git_success(&main_checkout, &["init"]);
+        assert_git_success(&main_checkout, &["config", "user.name", "Test User"]);
         assert_git_success(
-            &upstream_clone,
-            &["config", "user.email", "upstream@example.com"],
+            &main_checkout,
+            &["config", "user.email", "test@example.com"],
         );
-        std::fs::write(upstream_clone.join("CHANGELOG.md"), "upstream\n")
-            .expect("write should succeed");
-        assert_git_success(&upstream_clone, &["add", "."]);
-        assert_git_success(&upstream_clone, &["commit", "-m", "upstream"]);
-        assert_git_success(&upstream_clone, &["push", "origin", "main"]);
-        let upstream_head = git_rev_parse(&upstream_clone, "HEAD^{commit}");
+        assert_git_success(&main_checkout, &["checkout", "-b", "main"]);
+        std::fs::write(main_checkout.join("README.md"), "init\n").expect("write should succeed");
+        assert_git_success(&main_checkout, &["add", "."]);
+        assert_git_success(&main_checkout, &["commit", "-m", "init"]);
 
-        std::fs::write(project_dir.join("LOCAL.md"), "local only\n").expect("write should succeed");
-        assert_git_success(&project_dir, &["add", "."]);
-        assert_git_success(&project_dir, &["commit", "-m", "local"]);
+        // The project is itself a linked worktree; a sibling was made by hand.
+        let project_dir = base_dir.join("repo");
+        let manual_worktree = base_dir.join("manual");
+        for (path, branch) in [(&project_dir, "project"), (&manual_worktree, "manual")] {
+            assert_git_success(
+                &main_checkout,
+                &[
+                    "worktree",
+                    "add",
+                    "-b",
+                    branch,
+                    path.to_str().expect("worktree path should be utf-8"),
+                ],
+            );
+        }
 
         let sqlite =
             SqliteStore::new(paths::sqlite_path(&base_dir)).expect("sqlite init should work");
@@ -4561,31 +6692,68 @@ mod tests {
             claude_processes: Mutex::new(HashMap::new()),
         };
 
-        let created = ProjectWorkspaceService::create_workspace(
+        let pruned = ProjectWorkspaceService::prune_orphaned_worktrees(
             &service,
             project_dir.clone(),
-            "proj".to_owned(),
+            "repo".to_owned(),
             None,
+            vec![project_dir.clone()],
+            false,
         )
-        .expect("create_workspace should succeed");
+        .expect("prune should succeed");
+        assert!(pruned.is_empty(), "unexpected prune: {pruned:?}");
+        assert!(main_checkout.join("README.md").exists());
+        assert!(manual_worktree.join("README.md").exists());
 
-        let head = git_rev_parse(&created.worktree_path, "HEAD^{commit}");
-        assert_eq!(
-            head, upstream_head,
-            "expected workspace to be created from origin/main (after fetch)"
-        );
+        drop(service);
+        let _ = std::fs::remove_dir_all(&base_dir);
+    }
 
-        let upstream_config_key = format!("branch.{}.remote", created.branch_name);
-        let config = Command::new("git")
-            .args(["config", "--get", &upstream_config_key])
-            .current_dir(&project_dir)
-            .output()
-            .expect("git config should spawn");
+    #[test]
+    fn rename_workspace_branch_rejects_detached_head() {
+        let unique = unix_epoch_nanos_now();
+        let base_dir = std::env::temp_dir().join(format!(
+            "luban-rename-detached-head-{}-{}",
+            std::process::id(),
+            unique
+        ));
+
+        let repo_dir = base_dir.join("repo");
+        std::fs::create_dir_all(&repo_dir).expect("repo dir should be created");
+        assert_git_success(&repo_dir, &["init"]);
+        assert_git_success(&repo_dir, &["config", "user.name", "Test User"]);
+        assert_git_success(&repo_dir, &["config", "user.email", "test@example.com"]);
+        assert_git_success(&repo_dir, &["checkout", "-b", "luban/feature"]);
+        std::fs::write(repo_dir.join("README.md"), "init\n").expect("write should succeed");
+        assert_git_success(&repo_dir, &["add", "."]);
+        assert_git_success(&repo_dir, &["commit", "-m", "init"]);
+        assert!(!is_detached_head(&repo_dir));
+
+        let head = git_rev_parse(&repo_dir, "HEAD^{commit}");
+        assert_git_success(&repo_dir, &["checkout", "--detach", &head]);
+        assert!(is_detached_head(&repo_dir));
+
+        let sqlite =
+            SqliteStore::new(paths::sqlite_path(&base_dir)).expect("sqlite init should work");
+        let service = GitWorkspaceService {
+            worktrees_root: paths::worktrees_root(&base_dir),
+            conversations_root: paths::conversations_root(&base_dir),
+            task_prompts_root: paths::task_prompts_root(&base_dir),
+            sqlite,
+            claude_processes: Mutex::new(HashMap::new()),
+        };
+
+        let err = ProjectWorkspaceService::rename_workspace_branch(
+            &service,
+            repo_dir.clone(),
+            "renamed".to_owned(),
+            None,
+        )
+        .expect_err("rename should be rejected on detached HEAD");
+        assert!(err.contains("detached HEAD"), "unexpected error: {err}");
         assert!(
-            !config.status.success(),
-            "expected branch to not track upstream, but {} is set to {:?}",
-            upstream_config_key,
-            String::from_utf8_lossy(&config.stdout).trim()
+            !branch_exists(&repo_dir, "luban/renamed"),
+            "no branch should be created on a detached HEAD"
         );
 
         drop(service);
@@ -4637,6 +6805,87 @@ mod tests {
         let _ = std::fs::remove_dir_all(&base_dir);
     }
 
+    #[test]
+    fn context_dir_import_walks_tree_and_applies_exclude_globs() {
+        let unique = unix_epoch_nanos_now();
+        let base_dir = std::env::temp_dir().join(format!(
+            "luban-context-dir-import-{}-{}",
+            std::process::id(),
+            unique
+        ));
+
+        std::fs::create_dir_all(&base_dir).expect("temp dir should be created");
+
+        let sqlite =
+            SqliteStore::new(paths::sqlite_path(&base_dir)).expect("sqlite init should work");
+        let service = GitWorkspaceService {
+            worktrees_root: paths::worktrees_root(&base_dir),
+            conversations_root: paths::conversations_root(&base_dir),
+            task_prompts_root: paths::task_prompts_root(&base_dir),
+            sqlite,
+            claude_processes: Mutex::new(HashMap::new()),
+        };
+
+        let tree = base_dir.join("tree");
+        std::fs::create_dir_all(tree.join("src/nested")).expect("create tree");
+        std::fs::create_dir_all(tree.join("logs")).expect("create tree");
+        std::fs::write(tree.join("README.md"), b"readme").expect("write file");
+        std::fs::write(tree.join("src/main.rs"), b"fn main() {}").expect("write file");
+        std::fs::write(tree.join("src/nested/lib.rs"), b"pub fn f() {}").expect("write file");
+        std::fs::write(tree.join("logs/run.log"), b"log line").expect("write file");
+
+        let imported = ProjectWorkspaceService::import_context_dir(
+            &service,
+            "proj".to_owned(),
+            "main".to_owned(),
+            tree.clone(),
+            Vec::new(),
+            vec!["logs/**".to_owned()],
+            0,
+        )
+        .expect("import_context_dir should succeed");
+
+        let names = imported
+            .attachments
+            .iter()
+            .map(|a| a.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["README.md", "src/main.rs", "src/nested/lib.rs"]);
+        assert!(imported.skipped.is_empty(), "{:?}", imported.skipped);
+        for attachment in &imported.attachments {
+            assert_eq!(attachment.kind, AttachmentKind::File);
+            assert!(
+                stored_blob_path(&service, "proj", "main", attachment).exists(),
+                "stored blob should exist for {}",
+                attachment.name
+            );
+        }
+
+        let capped = ProjectWorkspaceService::import_context_dir(
+            &service,
+            "proj".to_owned(),
+            "main".to_owned(),
+            tree,
+            vec!["*.md".to_owned(), "src/main.rs".to_owned()],
+            Vec::new(),
+            8,
+        )
+        .expect("import_context_dir should succeed");
+        let names = capped
+            .attachments
+            .iter()
+            .map(|a| a.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["README.md"]);
+        assert_eq!(
+            capped.skipped,
+            vec!["src/main.rs: skipped text file of 12 bytes (limit 8)"]
+        );
+
+        drop(service);
+        let _ = std::fs::remove_dir_all(&base_dir);
+    }
+
     #[test]
     fn context_images_are_content_addressed() {
         let unique = unix_epoch_nanos_now();
@@ -4750,5 +6999,186 @@ mod tests {
         let _ = std::fs::remove_dir_all(&base_dir);
     }
 
+    #[test]
+    fn context_image_preview_is_bounded_and_skips_undecodable_blobs() {
+        let unique = unix_epoch_nanos_now();
+        let base_dir = std::env::temp_dir().join(format!(
+            "luban-context-image-preview-{}-{}",
+            std::process::id(),
+            unique
+        ));
+
+        std::fs::create_dir_all(&base_dir).expect("temp dir should be created");
+
+        let sqlite =
+            SqliteStore::new(paths::sqlite_path(&base_dir)).expect("sqlite init should work");
+        let service = GitWorkspaceService {
+            worktrees_root: paths::worktrees_root(&base_dir),
+            conversations_root: paths::conversations_root(&base_dir),
+            task_prompts_root: paths::task_prompts_root(&base_dir),
+            sqlite,
+            claude_processes: Mutex::new(HashMap::new()),
+        };
+
+        let img = image::RgbImage::from_fn(600, 300, |x, y| {
+            image::Rgb([(x % 256) as u8, (y % 256) as u8, 0])
+        });
+        let mut png = Vec::new();
+        image::DynamicImage::ImageRgb8(img)
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .expect("encode png");
+        let image_ref = ProjectWorkspaceService::store_context_image(
+            &service,
+            "proj".to_owned(),
+            "main".to_owned(),
+            ContextImage {
+                extension: "png".to_owned(),
+                bytes: png,
+            },
+        )
+        .expect("store_context_image should succeed");
+        let broken_ref = ProjectWorkspaceService::store_context_image(
+            &service,
+            "proj".to_owned(),
+            "main".to_owned(),
+            ContextImage {
+                extension: "png".to_owned(),
+                bytes: b"not-a-real-png".to_vec(),
+            },
+        )
+        .expect("store_context_image should succeed");
+
+        let preview = |attachment: &AttachmentRef| {
+            ProjectWorkspaceService::context_image_preview_png(
+                &service,
+                "proj".to_owned(),
+                "main".to_owned(),
+                attachment.id.clone(),
+                attachment.extension.clone(),
+            )
+        };
+
+        let bytes = preview(&image_ref)
+            .expect("preview should succeed")
+            .expect("decodable image should have a preview");
+        let decoded = image::load_from_memory(&bytes).expect("decode preview");
+        assert_eq!((decoded.width(), decoded.height()), (128, 64));
+
+        assert_eq!(preview(&broken_ref).expect("preview should succeed"), None);
+
+        let err = ProjectWorkspaceService::context_image_preview_png(
+            &service,
+            "proj".to_owned(),
+            "main".to_owned(),
+            "../escape".to_owned(),
+            "png".to_owned(),
+        )
+        .expect_err("non-hash ids should be rejected");
+        assert!(err.contains("invalid attachment id"), "{err}");
+
+        drop(service);
+        let _ = std::fs::remove_dir_all(&base_dir);
+    }
+
+    #[test]
+    fn exported_thread_entries_round_trip_through_import() {
+        let unique = unix_epoch_nanos_now();
+        let base_dir = std::env::temp_dir().join(format!(
+            "luban-import-thread-jsonl-{}-{}",
+            std::process::id(),
+            unique
+        ));
+        std::fs::create_dir_all(&base_dir).expect("temp dir should be created");
+
+        let sqlite =
+            SqliteStore::new(paths::sqlite_path(&base_dir)).expect("sqlite init should work");
+        let service = GitWorkspaceService {
+            worktrees_root: paths::worktrees_root(&base_dir),
+            conversations_root: paths::conversations_root(&base_dir),
+            task_prompts_root: paths::task_prompts_root(&base_dir),
+            sqlite,
+            claude_processes: Mutex::new(HashMap::new()),
+        };
+
+        let project_slug = "p".to_owned();
+        let workspace_name = "w".to_owned();
+        service
+            .ensure_conversation(project_slug.clone(), workspace_name.clone(), 1)
+            .expect("ensure conversation should succeed");
+        service
+            .append_conversation_entries(
+                project_slug.clone(),
+                workspace_name.clone(),
+                1,
+                vec![
+                    ConversationEntry::UserEvent {
+                        entry_id: String::new(),
+                        created_at_unix_ms: 0,
+                        event: luban_domain::UserEvent::Message {
+                            text: "hello".to_owned(),
+                            attachments: Vec::new(),
+                        },
+                    },
+                    ConversationEntry::AgentEvent {
+                        entry_id: String::new(),
+                        created_at_unix_ms: 0,
+                        runner: Some(luban_domain::AgentRunnerKind::Codex),
+                        event: luban_domain::AgentEvent::Message {
+                            id: "msg_1".to_owned(),
+                            text: "hi there".to_owned(),
+                        },
+                    },
+                    ConversationEntry::AgentEvent {
+                        entry_id: String::new(),
+                        created_at_unix_ms: 0,
+                        runner: None,
+                        event: luban_domain::AgentEvent::TurnDuration { duration_ms: 42 },
+                    },
+                ],
+            )
+            .expect("append should succeed");
+
+        let exported = service
+            .load_conversation(project_slug.clone(), workspace_name.clone(), 1)
+            .expect("load should succeed")
+            .entries;
+        assert_eq!(
+            exported.len(),
+            4,
+            "task creation plus three appended entries"
+        );
+
+        service
+            .import_conversation_entries(
+                project_slug.clone(),
+                workspace_name.clone(),
+                2,
+                exported[1..].to_vec(),
+            )
+            .expect("import should succeed");
+
+        let imported = service
+            .load_conversation(project_slug.clone(), workspace_name.clone(), 2)
+            .expect("imported thread should load");
+        assert_eq!(imported.thread_id, None);
+        assert!(matches!(
+            imported.entries.first(),
+            Some(ConversationEntry::SystemEvent {
+                event: luban_domain::ConversationSystemEvent::TaskCreated,
+                ..
+            })
+        ));
+        let serialize = |entries: &[ConversationEntry]| {
+            entries
+                .iter()
+                .map(|entry| serde_json::to_string(entry).expect("entry should serialize"))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(serialize(&imported.entries[1..]), serialize(&exported[1..]));
+
+        drop(service);
+        let _ = std::fs::remove_dir_all(&base_dir);
+    }
+
     // Linux open-command tests live in services/open_command.rs.
 }
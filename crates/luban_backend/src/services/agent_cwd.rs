@@ -0,0 +1,35 @@
+use anyhow::{Context as _, anyhow};
+use std::path::{Path, PathBuf};
+
+/// Resolves the directory an agent turn runs in. The subpath is interpreted relative to the
+/// worktree and must resolve (after following symlinks) to a directory inside it.
+pub(super) fn resolve_agent_cwd(
+    worktree_path: &Path,
+    cwd_subpath: Option<&str>,
+) -> anyhow::Result<PathBuf> {
+    let Some(subpath) = cwd_subpath else {
+        return Ok(worktree_path.to_path_buf());
+    };
+    let Some(subpath) =
+        luban_domain::paths::normalize_cwd_subpath(subpath).map_err(|e| anyhow!(e))?
+    else {
+        return Ok(worktree_path.to_path_buf());
+    };
+
+    let candidate = worktree_path.join(&subpath);
+    let root = worktree_path
+        .canonicalize()
+        .with_context(|| format!("failed to resolve worktree {}", worktree_path.display()))?;
+    let resolved = candidate
+        .canonicalize()
+        .with_context(|| format!("working directory does not exist: {subpath}"))?;
+    if !resolved.starts_with(&root) {
+        return Err(anyhow!(
+            "working directory must stay within the worktree: {subpath}"
+        ));
+    }
+    if !resolved.is_dir() {
+        return Err(anyhow!("working directory is not a directory: {subpath}"));
+    }
+    Ok(candidate)
+}
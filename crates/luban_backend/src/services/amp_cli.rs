@@ -14,9 +14,10 @@ use std::sync::atomic::{AtomicBool, Ordering};
 
 use super::ansi::strip_ansi_control_sequences;
 use super::cancel_killer::spawn_cancel_killer;
+use super::runner_spawn::RunnerBinary;
 use super::stream_json::{
     extract_content_array, extract_string_field, parse_tool_result_content, tool_name_key,
-    value_as_string,
+    value_as_string, web_search_results,
 };
 use super::thread_io::spawn_read_to_string;
 
@@ -25,6 +26,7 @@ pub(super) struct AmpTurnParams {
     pub(super) worktree_path: PathBuf,
     pub(super) prompt: String,
     pub(super) mode: Option<String>,
+    pub(super) env: Vec<(String, String)>,
 }
 
 fn resolve_amp_exec() -> PathBuf {
@@ -37,18 +39,25 @@ fn run_amp_threads_new(
     amp: &Path,
     worktree_path: &Path,
     mode: Option<&str>,
+    env: &[(String, String)],
 ) -> anyhow::Result<String> {
     let mut command = Command::new(amp);
     command.current_dir(worktree_path);
+    command.envs(env.iter().map(|(key, value)| (key, value)));
     command.args(["--no-notifications", "--no-ide", "--no-jetbrains"]);
     if let Some(mode) = mode {
         command.args(["--mode", mode]);
     }
     command.args(["threads", "new"]);
 
-    let output = command
-        .output()
-        .context("failed to spawn amp threads new")?;
+    let output = command.output().map_err(|err| {
+        RunnerBinary {
+            runner: "amp",
+            path: amp,
+            bin_env: "LUBAN_AMP_BIN",
+        }
+        .spawn_error(err)
+    })?;
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
         return Err(anyhow!("amp threads new failed: {}", stderr.trim()));
@@ -266,6 +275,7 @@ fn parse_amp_stream_json_line(
                                         aggregated_output: String::new(),
                                         exit_code: None,
                                         status: AgentCommandExecutionStatus::InProgress,
+                                        truncated: false,
                                     },
                                 });
                             }
@@ -275,7 +285,11 @@ fn parse_amp_stream_json_line(
                                     _ => String::new(),
                                 };
                                 out.push(AgentThreadEvent::ItemStarted {
-                                    item: AgentThreadItem::WebSearch { id, query },
+                                    item: AgentThreadItem::WebSearch {
+                                        id,
+                                        query,
+                                        results: Vec::new(),
+                                    },
                                 });
                             }
                             AmpToolKind::FileChange => {
@@ -406,6 +420,7 @@ fn parse_amp_stream_json_line(
                                 } else {
                                     AgentCommandExecutionStatus::Completed
                                 },
+                                truncated: false,
                             },
                         });
                     }
@@ -418,6 +433,11 @@ fn parse_amp_stream_json_line(
                             item: AgentThreadItem::WebSearch {
                                 id: tool_use_id,
                                 query,
+                                results: if is_error {
+                                    Vec::new()
+                                } else {
+                                    web_search_results(&result)
+                                },
                             },
                         });
                     }
@@ -527,7 +547,10 @@ fn parse_amp_stream_json_line(
             .to_owned();
 
         out.push(AgentThreadEvent::TurnFailed {
-            error: AgentThreadError { message },
+            error: AgentThreadError {
+                message,
+                code: None,
+            },
         });
         return Ok(out);
     }
@@ -555,6 +578,7 @@ pub(super) fn run_amp_turn_streamed_via_cli(
         worktree_path,
         prompt,
         mode,
+        env,
     } = params;
 
     let amp = resolve_amp_exec();
@@ -562,7 +586,7 @@ pub(super) fn run_amp_turn_streamed_via_cli(
 
     let thread_id = match thread_id {
         Some(id) => id,
-        None => run_amp_threads_new(&amp, &worktree_path, mode)?,
+        None => run_amp_threads_new(&amp, &worktree_path, mode, &env)?,
     };
 
     on_event(AgentThreadEvent::ThreadStarted {
@@ -572,6 +596,7 @@ pub(super) fn run_amp_turn_streamed_via_cli(
 
     let mut command = Command::new(&amp);
     command.current_dir(&worktree_path);
+    command.envs(env);
     command.args([
         "--no-notifications",
         "--no-ide",
@@ -596,14 +621,12 @@ pub(super) fn run_amp_turn_streamed_via_cli(
         .stderr(std::process::Stdio::piped())
         .spawn()
         .map_err(|err| {
-            if err.kind() == std::io::ErrorKind::NotFound {
-                anyhow!(
-                    "missing amp executable ({}): install Amp CLI and ensure it is available on PATH (or set LUBAN_AMP_BIN to an absolute path)",
-                    amp.display()
-                )
-            } else {
-                anyhow!(err).context("failed to spawn amp")
+            RunnerBinary {
+                runner: "amp",
+                path: &amp,
+                bin_env: "LUBAN_AMP_BIN",
             }
+            .spawn_error(err)
         })?;
 
     if let Some(stdin) = child.stdin.as_mut() {
@@ -15,9 +15,10 @@ use std::sync::atomic::{AtomicBool, Ordering};
 
 use super::ansi::strip_ansi_control_sequences;
 use super::cancel_killer::spawn_cancel_killer;
+use super::runner_spawn::RunnerBinary;
 use super::stream_json::{
     extract_content_array, extract_string_field, parse_tool_result_content, tool_name_key,
-    value_as_string,
+    value_as_string, web_search_results,
 };
 use super::thread_io::spawn_read_to_string;
 
@@ -26,8 +27,33 @@ pub(super) struct ClaudeTurnParams {
     pub(super) worktree_path: PathBuf,
     pub(super) prompt: String,
     pub(super) add_dirs: Vec<PathBuf>,
+    pub(super) options: ClaudeRunOptions,
 }
 
+/// Per-task overrides passed to every `claude` invocation of a thread.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ClaudeRunOptions {
+    pub max_output_tokens: Option<u32>,
+    /// Replaces the CLI's default system prompt.
+    pub system_prompt: Option<String>,
+    /// Project environment variables; applied before the task options so those win.
+    pub env: Vec<(String, String)>,
+}
+
+impl ClaudeRunOptions {
+    pub(super) fn apply(&self, command: &mut Command) {
+        command.envs(self.env.iter().map(|(key, value)| (key, value)));
+        if let Some(max_output_tokens) = self.max_output_tokens {
+            command.env(CLAUDE_MAX_OUTPUT_TOKENS_ENV, max_output_tokens.to_string());
+        }
+        if let Some(system_prompt) = self.system_prompt.as_deref() {
+            command.arg("--system-prompt").arg(system_prompt);
+        }
+    }
+}
+
+const CLAUDE_MAX_OUTPUT_TOKENS_ENV: &str = "CLAUDE_CODE_MAX_OUTPUT_TOKENS";
+
 fn resolve_claude_exec() -> PathBuf {
     std::env::var_os(paths::LUBAN_CLAUDE_BIN_ENV)
         .map(PathBuf::from)
@@ -303,6 +329,7 @@ fn parse_claude_stream_json_line(
                                     aggregated_output: String::new(),
                                     exit_code: None,
                                     status: AgentCommandExecutionStatus::InProgress,
+                                    truncated: false,
                                 },
                             });
                         }
@@ -312,7 +339,11 @@ fn parse_claude_stream_json_line(
                                 _ => String::new(),
                             };
                             out.push(AgentThreadEvent::ItemStarted {
-                                item: AgentThreadItem::WebSearch { id, query },
+                                item: AgentThreadItem::WebSearch {
+                                    id,
+                                    query,
+                                    results: Vec::new(),
+                                },
                             });
                         }
                         ClaudeToolKind::FileChange => {
@@ -441,6 +472,7 @@ fn parse_claude_stream_json_line(
                                     } else {
                                         AgentCommandExecutionStatus::Completed
                                     },
+                                    truncated: false,
                                 },
                             });
                         }
@@ -452,6 +484,11 @@ fn parse_claude_stream_json_line(
                                         ClaudeToolSummary::WebSearch { query } => query,
                                         _ => tool.name,
                                     },
+                                    results: if is_error {
+                                        Vec::new()
+                                    } else {
+                                        web_search_results(&result)
+                                    },
                                 },
                             });
                         }
@@ -562,7 +599,10 @@ fn parse_claude_stream_json_line(
             .to_owned();
 
         out.push(AgentThreadEvent::TurnFailed {
-            error: AgentThreadError { message },
+            error: AgentThreadError {
+                message,
+                code: None,
+            },
         });
         return Ok(out);
     }
@@ -590,6 +630,7 @@ pub(super) fn run_claude_turn_streamed_via_cli(
         worktree_path,
         prompt,
         add_dirs,
+        options,
     } = params;
 
     let claude = resolve_claude_exec();
@@ -614,6 +655,7 @@ pub(super) fn run_claude_turn_streamed_via_cli(
     if let Some(thread_id) = thread_id.as_deref() {
         command.arg("--resume").arg(thread_id);
     }
+    options.apply(&mut command);
     command.arg(prompt);
 
     let mut child = command
@@ -622,14 +664,12 @@ pub(super) fn run_claude_turn_streamed_via_cli(
         .stderr(std::process::Stdio::piped())
         .spawn()
         .map_err(|err| {
-            if err.kind() == std::io::ErrorKind::NotFound {
-                anyhow!(
-                    "missing claude executable ({}): install Claude Code and ensure it is available on PATH (or set LUBAN_CLAUDE_BIN to an absolute path)",
-                    claude.display()
-                )
-            } else {
-                anyhow!(err).context("failed to spawn claude")
+            RunnerBinary {
+                runner: "claude",
+                path: &claude,
+                bin_env: paths::LUBAN_CLAUDE_BIN_ENV,
             }
+            .spawn_error(err)
         })?;
 
     if let Some(stdin) = child.stdin.as_mut() {
@@ -720,6 +760,40 @@ pub(super) fn run_claude_turn_streamed_via_cli(
 mod tests {
     use super::*;
 
+    #[test]
+    fn run_options_add_system_prompt_and_output_cap() {
+        let mut command = Command::new("claude");
+        ClaudeRunOptions::default().apply(&mut command);
+        assert_eq!(command.get_args().count(), 0);
+        assert_eq!(command.get_envs().count(), 0);
+
+        ClaudeRunOptions {
+            max_output_tokens: Some(8000),
+            system_prompt: Some("Be terse.".to_owned()),
+            env: vec![
+                (CLAUDE_MAX_OUTPUT_TOKENS_ENV.to_owned(), "1".to_owned()),
+                ("API_TOKEN".to_owned(), "secret".to_owned()),
+            ],
+        }
+        .apply(&mut command);
+        let args = command.get_args().collect::<Vec<_>>();
+        assert_eq!(args, ["--system-prompt", "Be terse."]);
+        let envs = command.get_envs().collect::<Vec<_>>();
+        assert_eq!(
+            envs,
+            [
+                (
+                    std::ffi::OsStr::new("API_TOKEN"),
+                    Some(std::ffi::OsStr::new("secret"))
+                ),
+                (
+                    std::ffi::OsStr::new(CLAUDE_MAX_OUTPUT_TOKENS_ENV),
+                    Some(std::ffi::OsStr::new("8000"))
+                ),
+            ]
+        );
+    }
+
     #[test]
     fn parses_system_init_as_thread_started() {
         let mut state = ClaudeStreamState::new();
@@ -758,6 +832,38 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn parses_web_search_links_into_results() {
+        let mut state = ClaudeStreamState::new();
+        let _ = parse_claude_stream_json_line(
+            &mut state,
+            r#"{"type":"assistant","message":{"content":[{"type":"tool_use","id":"t1","name":"WebSearch","input":{"query":"rust"}}]}}"#,
+        )
+        .expect("parse ok");
+
+        let events = parse_claude_stream_json_line(
+            &mut state,
+            r#"{"type":"user","message":{"content":[{"type":"tool_result","tool_use_id":"t1","content":"Web search results for query: \"rust\"\n\nLinks: [{\"title\":\"Rust\",\"url\":\"https://www.rust-lang.org/\"}]\n\nRust is a language.","is_error":false}]}}"#,
+        )
+        .expect("parse ok");
+        let [
+            AgentThreadEvent::ItemCompleted {
+                item: AgentThreadItem::WebSearch { query, results, .. },
+            },
+        ] = events.as_slice()
+        else {
+            panic!("unexpected events: {events:?}");
+        };
+        assert_eq!(query, "rust");
+        assert_eq!(
+            results
+                .iter()
+                .map(|result| (result.title.as_str(), result.url.as_str()))
+                .collect::<Vec<_>>(),
+            vec![("Rust", "https://www.rust-lang.org/")]
+        );
+    }
+
     #[test]
     fn parses_result_success_as_turn_completed() {
         let mut state = ClaudeStreamState::new();
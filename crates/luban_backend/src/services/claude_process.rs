@@ -11,7 +11,10 @@ use std::sync::{Arc, Mutex};
 use std::thread::JoinHandle;
 use std::time::{Duration, Instant};
 
-use super::claude_cli::{ClaudeStreamState, parse_claude_stream_json_line_public};
+use super::claude_cli::{
+    ClaudeRunOptions, ClaudeStreamState, parse_claude_stream_json_line_public,
+};
+use super::runner_spawn::RunnerBinary;
 
 /// A persistent Claude process that maintains MCP connections across multiple turns.
 ///
@@ -26,6 +29,8 @@ pub struct ClaudeThreadProcess {
     stdin: Arc<Mutex<BufWriter<ChildStdin>>>,
     session_id: Option<String>,
     worktree_path: PathBuf,
+    /// Options the process was spawned with; a turn with different options needs a new process.
+    options: ClaudeRunOptions,
     ready: AtomicBool,
     shutdown: AtomicBool,
 
@@ -56,6 +61,7 @@ impl ClaudeThreadProcess {
         worktree_path: &Path,
         thread_id: Option<&str>,
         add_dirs: &[PathBuf],
+        options: &ClaudeRunOptions,
     ) -> anyhow::Result<Self> {
         let claude = resolve_claude_exec();
 
@@ -85,6 +91,7 @@ impl ClaudeThreadProcess {
         if let Some(tid) = thread_id {
             command.arg("--resume").arg(tid);
         }
+        options.apply(&mut command);
 
         let mut child = command
             .stdin(Stdio::piped())
@@ -92,14 +99,12 @@ impl ClaudeThreadProcess {
             .stderr(Stdio::piped())
             .spawn()
             .map_err(|err| {
-                if err.kind() == std::io::ErrorKind::NotFound {
-                    anyhow!(
-                        "missing claude executable ({}): install Claude Code and ensure it is available on PATH (or set LUBAN_CLAUDE_BIN to an absolute path)",
-                        claude.display()
-                    )
-                } else {
-                    anyhow!(err).context("failed to spawn claude")
+                RunnerBinary {
+                    runner: "claude",
+                    path: &claude,
+                    bin_env: paths::LUBAN_CLAUDE_BIN_ENV,
                 }
+                .spawn_error(err)
             })?;
 
         let stdin = child
@@ -123,6 +128,7 @@ impl ClaudeThreadProcess {
             stdin: Arc::new(Mutex::new(BufWriter::new(stdin))),
             session_id: None,
             worktree_path: worktree_path.to_path_buf(),
+            options: options.clone(),
             ready: AtomicBool::new(false),
             shutdown: AtomicBool::new(false),
             event_queue,
@@ -183,6 +189,11 @@ impl ClaudeThreadProcess {
         }
     }
 
+    /// Options the process was spawned with.
+    pub fn options(&self) -> &ClaudeRunOptions {
+        &self.options
+    }
+
     /// Check if MCP warmup has completed
     pub fn is_ready(&self) -> bool {
         self.ready.load(Ordering::SeqCst)
@@ -1,6 +1,7 @@
 use super::thread_io::spawn_read_to_string;
 use anyhow::{Context as _, anyhow};
 use luban_domain::CodexThreadEvent;
+use luban_domain::paths;
 use std::{
     ffi::OsString,
     io::{BufRead as _, BufReader, Write as _},
@@ -11,6 +12,7 @@ use std::{
 };
 
 use super::cancel_killer::spawn_cancel_killer;
+use super::runner_spawn::RunnerBinary;
 
 fn should_skip_git_repo_check(worktree_path: &Path) -> bool {
     !worktree_path.join(".git").exists()
@@ -89,6 +91,7 @@ pub(super) struct CodexTurnParams {
     pub(super) model: Option<String>,
     pub(super) model_reasoning_effort: Option<String>,
     pub(super) sandbox_mode: Option<String>,
+    pub(super) env: Vec<(String, String)>,
 }
 
 enum CodexStdoutLine {
@@ -152,12 +155,15 @@ pub(super) fn run_codex_turn_streamed_via_cli(
         model,
         model_reasoning_effort,
         sandbox_mode,
+        env,
     } = params;
 
     let _ = sandbox_mode.as_deref();
     let sandbox_mode = CODEX_SANDBOX_MODE_DANGER_FULL_ACCESS;
 
     let mut command = Command::new(codex);
+    command.current_dir(&worktree_path);
+    command.envs(env);
     command.args(build_codex_exec_args(
         sandbox_mode,
         &worktree_path,
@@ -174,14 +180,12 @@ pub(super) fn run_codex_turn_streamed_via_cli(
         .stderr(std::process::Stdio::piped())
         .spawn()
         .map_err(|err| {
-            if err.kind() == std::io::ErrorKind::NotFound {
-                anyhow!(
-                    "missing codex executable ({}): install Codex CLI and ensure it is available on PATH (note: macOS apps launched from Finder/Dock may not inherit your shell PATH; set LUBAN_CODEX_BIN to an absolute path if needed)",
-                    codex.display()
-                )
-            } else {
-                anyhow!(err).context("failed to spawn codex")
+            RunnerBinary {
+                runner: "codex",
+                path: codex,
+                bin_env: paths::LUBAN_CODEX_BIN_ENV,
             }
+            .spawn_error(err)
         })?;
 
     child
@@ -209,6 +213,7 @@ pub(super) fn run_codex_turn_streamed_via_cli(
 
     let stdout_reader = BufReader::new(stdout);
     let mut stdout_noise: Vec<String> = Vec::new();
+    let mut saw_event = false;
     for line in stdout_reader.lines() {
         let line = match line {
             Ok(line) => line,
@@ -228,7 +233,10 @@ pub(super) fn run_codex_turn_streamed_via_cli(
         }
 
         match parse_codex_stdout_line(trimmed) {
-            Ok(CodexStdoutLine::Event(event)) => on_event(*event)?,
+            Ok(CodexStdoutLine::Event(event)) => {
+                saw_event = true;
+                on_event(*event)?
+            }
             Ok(CodexStdoutLine::Ignored { message } | CodexStdoutLine::Noise { message }) => {
                 if message.is_empty() {
                     continue;
@@ -259,6 +267,15 @@ pub(super) fn run_codex_turn_streamed_via_cli(
         return Ok(());
     }
 
+    if !status.success() && !saw_event && stdout_noise.is_empty() {
+        return Err(RunnerBinary {
+            runner: "codex",
+            path: codex,
+            bin_env: paths::LUBAN_CODEX_BIN_ENV,
+        }
+        .exited_immediately(status, &stderr_text));
+    }
+
     if !status.success() {
         let codex_noise = if stdout_noise.is_empty() {
             String::new()
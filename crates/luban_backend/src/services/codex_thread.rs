@@ -1,5 +1,8 @@
-use luban_domain::{CodexThreadEvent, CodexThreadItem};
+use luban_domain::{
+    AgentEvent, CodexThreadEvent, CodexThreadItem, CodexWebSearchResult, ConversationEntry,
+};
 use rand::{Rng as _, rngs::OsRng};
+use std::collections::{HashMap, HashSet};
 
 use crate::time::unix_epoch_micros_now;
 
@@ -66,3 +69,113 @@ pub(super) fn qualify_event(turn_scope_id: &str, event: CodexThreadEvent) -> Cod
         other => other,
     }
 }
+
+/// Copy of `item` as stored in the conversation, with command output capped the same way the
+/// reducer caps it in memory.
+fn persisted_item(item: &CodexThreadItem, command_output_max_bytes: usize) -> CodexThreadItem {
+    let mut item = item.clone();
+    luban_domain::cap_command_output(&mut item, command_output_max_bytes);
+    item
+}
+
+/// How a completed item reaches the store.
+pub(super) enum TurnItemWrite {
+    Append(ConversationEntry),
+    /// Rewrites the stored entry of the item with the same id.
+    Update(CodexThreadItem),
+}
+
+/// Tracks the items a turn has stored so completed items are persisted the way the reducer
+/// shows them: command output capped, web search results accumulated across updates and, when
+/// enabled, consecutive reasoning items folded into the first one.
+pub(super) struct TurnItemLog {
+    command_output_max_bytes: usize,
+    merge_reasoning_entries: bool,
+    appended_item_ids: HashSet<String>,
+    /// First item id and per-item segments of the reasoning entry later items fold into.
+    reasoning_merge: Option<(String, Vec<(String, String)>)>,
+    /// Results streamed so far for web search items that have not completed yet.
+    web_search_results: HashMap<String, Vec<CodexWebSearchResult>>,
+}
+
+impl TurnItemLog {
+    pub(super) fn new(command_output_max_bytes: usize, merge_reasoning_entries: bool) -> Self {
+        Self {
+            command_output_max_bytes,
+            merge_reasoning_entries,
+            appended_item_ids: HashSet::new(),
+            reasoning_merge: None,
+            web_search_results: HashMap::new(),
+        }
+    }
+
+    pub(super) fn contains(&self, item_id: &str) -> bool {
+        self.appended_item_ids.contains(item_id)
+    }
+
+    pub(super) fn insert(&mut self, item_id: String) {
+        self.appended_item_ids.insert(item_id);
+    }
+
+    /// Notes a streamed item; any other kind of item ends a run of merged reasoning items.
+    pub(super) fn observe(&mut self, item: &CodexThreadItem) {
+        if !matches!(item, CodexThreadItem::Reasoning { .. }) {
+            self.reasoning_merge = None;
+        }
+        if let CodexThreadItem::WebSearch { id, results, .. } = item {
+            let previous = self.web_search_results.entry(id.clone()).or_default();
+            *previous = luban_domain::merge_web_search_results(previous, results.clone());
+        }
+    }
+
+    /// The write for a completed item, or `None` when the item was already stored.
+    pub(super) fn completed(&mut self, item: &CodexThreadItem) -> Option<TurnItemWrite> {
+        self.observe(item);
+        let mut item = item.clone();
+        if let CodexThreadItem::WebSearch { id, results, .. } = &mut item
+            && let Some(accumulated) = self.web_search_results.remove(id.as_str())
+        {
+            *results = accumulated;
+        }
+        let item = &item;
+        if !self
+            .appended_item_ids
+            .insert(codex_item_id(item).to_owned())
+        {
+            return None;
+        }
+        if self.merge_reasoning_entries
+            && let CodexThreadItem::Reasoning { id, text } = item
+        {
+            match &mut self.reasoning_merge {
+                Some((first_id, segments)) => {
+                    let text =
+                        luban_domain::merge_reasoning_segment(segments, id.clone(), text.clone());
+                    return Some(TurnItemWrite::Update(CodexThreadItem::Reasoning {
+                        id: first_id.clone(),
+                        text,
+                    }));
+                }
+                None => {
+                    self.reasoning_merge = Some((id.clone(), vec![(id.clone(), text.clone())]));
+                }
+            }
+        }
+
+        let event = match item {
+            CodexThreadItem::AgentMessage { id, text } => AgentEvent::Message {
+                id: id.clone(),
+                text: text.clone(),
+            },
+            _ => AgentEvent::Item {
+                item: Box::new(persisted_item(item, self.command_output_max_bytes)),
+            },
+        };
+        Some(TurnItemWrite::Append(ConversationEntry::AgentEvent {
+            entry_id: String::new(),
+            created_at_unix_ms: 0,
+            runner: None,
+            event,
+        }))
+    }
+}
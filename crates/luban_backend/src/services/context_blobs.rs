@@ -2,12 +2,13 @@ use super::GitWorkspaceService;
 use anyhow::{Context as _, anyhow};
 use image::ImageFormat;
 use std::{
-    io::{Read as _, Write as _},
+    io::{Cursor, Read as _, Write as _},
     path::{Path, PathBuf},
 };
 
 const CONTEXT_IMAGE_THUMB_MAX_WIDTH: u32 = 360;
 const CONTEXT_IMAGE_THUMB_MAX_HEIGHT: u32 = 220;
+const CONTEXT_IMAGE_PREVIEW_MAX_SIZE: u32 = 128;
 
 fn finalize_atomic_rename(tmp: &Path, dest: &Path) -> std::io::Result<()> {
     match std::fs::rename(tmp, dest) {
@@ -138,6 +139,38 @@ impl GitWorkspaceService {
         Ok(Some(thumbnail_path))
     }
 
+    pub(super) fn context_image_preview_png(
+        &self,
+        project_slug: &str,
+        workspace_name: &str,
+        attachment_id: &str,
+        extension: &str,
+    ) -> anyhow::Result<Option<Vec<u8>>> {
+        if attachment_id.is_empty() || !attachment_id.chars().all(|ch| ch.is_ascii_hexdigit()) {
+            return Err(anyhow!("invalid attachment id"));
+        }
+        let extension = Self::normalize_extension(extension)?;
+        let path = self
+            .context_blobs_dir(project_slug, workspace_name)
+            .join(format!("{attachment_id}.{extension}"));
+        let bytes =
+            std::fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+
+        let decoded = match image::load_from_memory(&bytes) {
+            Ok(image) => image,
+            Err(_) => return Ok(None),
+        };
+        let preview = decoded.thumbnail(
+            CONTEXT_IMAGE_PREVIEW_MAX_SIZE,
+            CONTEXT_IMAGE_PREVIEW_MAX_SIZE,
+        );
+        let mut out = Cursor::new(Vec::new());
+        preview
+            .write_to(&mut out, ImageFormat::Png)
+            .context("failed to encode image preview")?;
+        Ok(Some(out.into_inner()))
+    }
+
     fn context_image_thumbnail_path(original_path: &Path) -> PathBuf {
         let stem = original_path
             .file_stem()
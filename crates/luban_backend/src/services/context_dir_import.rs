@@ -0,0 +1,124 @@
+use super::GitWorkspaceService;
+use anyhow::{Context as _, anyhow};
+use luban_domain::{AttachmentKind, AttachmentRef, ContextDirImport};
+use std::path::{Path, PathBuf};
+
+const BINARY_SNIFF_BYTES: usize = 8 * 1024;
+
+fn compile_patterns(patterns: &[String]) -> anyhow::Result<Vec<glob::Pattern>> {
+    patterns
+        .iter()
+        .map(|p| p.trim())
+        .filter(|p| !p.is_empty())
+        .map(|p| glob::Pattern::new(p).map_err(|err| anyhow!("invalid glob '{p}': {err}")))
+        .collect()
+}
+
+fn relative_display_path(rel: &Path) -> String {
+    rel.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn looks_binary(path: &Path) -> bool {
+    use std::io::Read as _;
+
+    let Ok(file) = std::fs::File::open(path) else {
+        return false;
+    };
+    let mut head = Vec::with_capacity(BINARY_SNIFF_BYTES);
+    if file
+        .take(BINARY_SNIFF_BYTES as u64)
+        .read_to_end(&mut head)
+        .is_err()
+    {
+        return false;
+    }
+    head.contains(&0)
+}
+
+/// Lists regular files under `dir` (recursively, sorted, symlinks not followed) as paths
+/// relative to `dir`.
+fn collect_files(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut pending = vec![PathBuf::new()];
+    let mut out = Vec::new();
+
+    while let Some(rel_dir) = pending.pop() {
+        let abs_dir = dir.join(&rel_dir);
+        let mut entries = std::fs::read_dir(&abs_dir)
+            .with_context(|| format!("failed to read directory {}", abs_dir.display()))?
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| format!("failed to read directory {}", abs_dir.display()))?;
+        entries.sort_by_key(|entry| entry.file_name());
+
+        for entry in entries {
+            let file_type = entry
+                .file_type()
+                .with_context(|| format!("failed to stat {}", entry.path().display()))?;
+            let rel = rel_dir.join(entry.file_name());
+            if file_type.is_dir() {
+                pending.push(rel);
+            } else if file_type.is_file() {
+                out.push(rel);
+            }
+        }
+    }
+
+    out.sort();
+    Ok(out)
+}
+
+impl GitWorkspaceService {
+    pub(super) fn import_context_dir_internal(
+        &self,
+        project_slug: &str,
+        workspace_name: &str,
+        dir: &Path,
+        include: &[String],
+        exclude: &[String],
+        max_file_bytes: u64,
+    ) -> anyhow::Result<ContextDirImport> {
+        if !dir.is_dir() {
+            return Err(anyhow!("not a directory: {}", dir.display()));
+        }
+        let include = compile_patterns(include)?;
+        let exclude = compile_patterns(exclude)?;
+
+        let mut out = ContextDirImport::default();
+        for rel in collect_files(dir)? {
+            let display = relative_display_path(&rel);
+            if !include.is_empty() && !include.iter().any(|p| p.matches(&display)) {
+                continue;
+            }
+            if exclude.iter().any(|p| p.matches(&display)) {
+                continue;
+            }
+
+            let abs = dir.join(&rel);
+            let byte_len = std::fs::metadata(&abs)
+                .with_context(|| format!("failed to stat {}", abs.display()))?
+                .len();
+            if max_file_bytes > 0 && byte_len > max_file_bytes {
+                let kind = if looks_binary(&abs) { "binary" } else { "text" };
+                out.skipped.push(format!(
+                    "{display}: skipped {kind} file of {byte_len} bytes (limit {max_file_bytes})"
+                ));
+                continue;
+            }
+
+            match self.store_context_file_internal(project_slug, workspace_name, &abs) {
+                Ok((id, extension, byte_len, _path)) => out.attachments.push(AttachmentRef {
+                    id,
+                    kind: AttachmentKind::File,
+                    name: display,
+                    extension,
+                    mime: None,
+                    byte_len,
+                }),
+                Err(err) => out.skipped.push(format!("{display}: {err:#}")),
+            }
+        }
+        Ok(out)
+    }
+}
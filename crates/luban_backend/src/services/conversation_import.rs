@@ -0,0 +1,162 @@
+use super::GitWorkspaceService;
+use anyhow::{Context as _, anyhow};
+use luban_domain::{ConversationEntry, ConversationSystemEvent, UserEvent};
+
+fn entry_id_mut(entry: &mut ConversationEntry) -> &mut String {
+    match entry {
+        ConversationEntry::SystemEvent { entry_id, .. }
+        | ConversationEntry::UserEvent { entry_id, .. }
+        | ConversationEntry::AgentEvent { entry_id, .. } => entry_id,
+    }
+}
+
+/// Picks the entries a duplicated task starts with: only user messages when `up_to_entry_id` is
+/// `None`, otherwise every entry up to and including that one.
+fn entries_to_duplicate(
+    mut entries: Vec<ConversationEntry>,
+    up_to_entry_id: Option<&str>,
+) -> anyhow::Result<Vec<ConversationEntry>> {
+    match up_to_entry_id {
+        None => entries.retain(|entry| {
+            matches!(
+                entry,
+                ConversationEntry::UserEvent {
+                    event: UserEvent::Message { .. },
+                    ..
+                }
+            )
+        }),
+        Some(up_to) => {
+            let end = entries
+                .iter_mut()
+                .position(|entry| entry_id_mut(entry) == up_to)
+                .ok_or_else(|| anyhow!("entry not found: {up_to}"))?;
+            entries.truncate(end + 1);
+            entries.retain(|entry| {
+                !matches!(
+                    entry,
+                    ConversationEntry::SystemEvent {
+                        event: ConversationSystemEvent::TaskCreated,
+                        ..
+                    }
+                )
+            });
+        }
+    }
+    // The copies get fresh ids in the new task.
+    for entry in &mut entries {
+        entry_id_mut(entry).clear();
+    }
+    Ok(entries)
+}
+
+impl GitWorkspaceService {
+    pub(super) fn duplicate_conversation_internal(
+        &self,
+        project_slug: String,
+        workspace_name: String,
+        source_thread_id: u64,
+        thread_id: u64,
+        up_to_entry_id: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let source = self.load_conversation_internal(
+            project_slug.clone(),
+            workspace_name.clone(),
+            source_thread_id,
+        )?;
+        let original_title = source
+            .title
+            .clone()
+            .unwrap_or_else(|| format!("Thread {source_thread_id}"));
+        let entries = entries_to_duplicate(source.entries, up_to_entry_id)?;
+
+        self.sqlite
+            .ensure_conversation(project_slug.clone(), workspace_name.clone(), thread_id)?;
+        // Reason: set the title before appending so the first copied message does not replace it.
+        self.sqlite.update_conversation_title_if_matches(
+            project_slug.clone(),
+            workspace_name.clone(),
+            thread_id,
+            format!("Thread {thread_id}"),
+            format!("Copy of {original_title}"),
+        )?;
+        if !entries.is_empty() {
+            self.sqlite
+                .append_conversation_entries(project_slug, workspace_name, thread_id, entries)
+                .context("failed to store duplicated entries")?;
+        }
+        Ok(())
+    }
+
+    /// Moves a thread to `to_thread_id` in another workspace of the same project, copying the
+    /// context blobs its messages and queued prompts attach so they keep resolving there.
+    pub(super) fn move_conversation_internal(
+        &self,
+        project_slug: String,
+        workspace_name: String,
+        thread_id: u64,
+        to_workspace_name: String,
+        to_thread_id: u64,
+    ) -> anyhow::Result<()> {
+        let source = self.load_conversation_internal(
+            project_slug.clone(),
+            workspace_name.clone(),
+            thread_id,
+        )?;
+        let message_attachments = source.entries.iter().flat_map(|entry| match entry {
+            ConversationEntry::UserEvent {
+                event: UserEvent::Message { attachments, .. },
+                ..
+            } => attachments.as_slice(),
+            _ => &[],
+        });
+        let queued_attachments = source
+            .pending_prompts
+            .iter()
+            .flat_map(|prompt| prompt.attachments.iter());
+
+        let from_dir = self.context_blobs_dir(&project_slug, &workspace_name);
+        let to_dir = self.context_blobs_dir(&project_slug, &to_workspace_name);
+        for attachment in message_attachments.chain(queued_attachments) {
+            let file_name = format!("{}.{}", attachment.id, attachment.extension);
+            let thumb_name = format!("{}-thumb.png", attachment.id);
+            for name in [file_name, thumb_name] {
+                let from = from_dir.join(&name);
+                let to = to_dir.join(&name);
+                if !from.exists() || to.exists() {
+                    continue;
+                }
+                std::fs::create_dir_all(&to_dir)
+                    .with_context(|| format!("failed to create {}", to_dir.display()))?;
+                std::fs::copy(&from, &to).with_context(|| {
+                    format!("failed to copy {} -> {}", from.display(), to.display())
+                })?;
+            }
+        }
+
+        self.sqlite.move_conversation_thread(
+            project_slug,
+            workspace_name,
+            thread_id,
+            to_workspace_name,
+            to_thread_id,
+        )
+    }
+
+    pub(super) fn import_conversation_entries_internal(
+        &self,
+        project_slug: String,
+        workspace_name: String,
+        thread_id: u64,
+        entries: Vec<ConversationEntry>,
+    ) -> anyhow::Result<()> {
+        self.sqlite
+            .ensure_conversation(project_slug.clone(), workspace_name.clone(), thread_id)?;
+        if !entries.is_empty() {
+            self.sqlite
+                .append_conversation_entries(project_slug, workspace_name, thread_id, entries)
+                .context("failed to store imported entries")?;
+        }
+        Ok(())
+    }
+}
@@ -125,7 +125,10 @@ fn migrate_legacy_entry(entry: LegacyConversationEntry) -> Option<ConversationEn
             entry_id: String::new(),
             created_at_unix_ms: 0,
             runner: None,
-            event: AgentEvent::TurnError { message },
+            event: AgentEvent::TurnError {
+                message,
+                code: None,
+            },
         }),
     }
 }
@@ -171,6 +174,13 @@ impl GitWorkspaceService {
             .as_secs()
     }
 
+    pub(super) fn now_unix_millis() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64
+    }
+
     fn read_conversation_meta_legacy(
         &self,
         project_slug: &str,
@@ -213,6 +223,9 @@ impl GitWorkspaceService {
                 agent_model_id: None,
                 thinking_effort: None,
                 amp_mode: None,
+                claude_max_output_tokens: None,
+                claude_system_prompt: None,
+                auto_commit: false,
                 entries: Vec::new(),
                 entries_total: 0,
                 entries_start: 0,
@@ -220,6 +233,12 @@ impl GitWorkspaceService {
                 queue_paused: false,
                 run_started_at_unix_ms: None,
                 run_finished_at_unix_ms: None,
+                notes: String::new(),
+                cwd_subpath: None,
+                hunk_comments: Vec::new(),
+                collapsed_ranges: Vec::new(),
+                draft: String::new(),
+                total_usage: Default::default(),
             }));
         }
 
@@ -270,6 +289,9 @@ impl GitWorkspaceService {
             agent_model_id: None,
             thinking_effort: None,
             amp_mode: None,
+            claude_max_output_tokens: None,
+            claude_system_prompt: None,
+            auto_commit: false,
             entries,
             entries_total,
             entries_start: 0,
@@ -277,6 +299,12 @@ impl GitWorkspaceService {
             queue_paused: false,
             run_started_at_unix_ms: None,
             run_finished_at_unix_ms: None,
+            notes: String::new(),
+            cwd_subpath: None,
+            hunk_comments: Vec::new(),
+            collapsed_ranges: Vec::new(),
+            draft: String::new(),
+            total_usage: Default::default(),
         }))
     }
 
@@ -14,6 +14,7 @@ use std::sync::atomic::{AtomicBool, Ordering};
 
 use super::ansi::strip_ansi_control_sequences;
 use super::cancel_killer::spawn_cancel_killer;
+use super::runner_spawn::RunnerBinary;
 use super::stream_json::{extract_string_field, tool_name_key, value_as_string};
 use super::thread_io::spawn_read_to_string;
 
@@ -24,6 +25,7 @@ pub(super) struct DroidTurnParams {
     pub(super) model: Option<String>,
     pub(super) reasoning_effort: Option<String>,
     pub(super) auto_level: Option<String>,
+    pub(super) env: Vec<(String, String)>,
 }
 
 fn resolve_droid_exec() -> PathBuf {
@@ -262,6 +264,7 @@ pub fn parse_droid_stream_json_line(
                         aggregated_output: String::new(),
                         exit_code: None,
                         status: AgentCommandExecutionStatus::InProgress,
+                        truncated: false,
                     },
                 });
             }
@@ -341,6 +344,7 @@ pub fn parse_droid_stream_json_line(
                             } else {
                                 AgentCommandExecutionStatus::Completed
                             },
+                            truncated: false,
                         },
                     });
                 }
@@ -464,6 +468,7 @@ pub(super) fn run_droid_turn_streamed_via_cli(
         model,
         reasoning_effort,
         auto_level,
+        env,
     } = params;
 
     let droid = resolve_droid_exec();
@@ -471,6 +476,7 @@ pub(super) fn run_droid_turn_streamed_via_cli(
     on_event(AgentThreadEvent::TurnStarted)?;
 
     let mut command = Command::new(&droid);
+    command.envs(env);
     command.args(["exec", "--output-format", "stream-json"]);
     command.arg("--cwd").arg(&worktree_path);
 
@@ -504,15 +510,12 @@ pub(super) fn run_droid_turn_streamed_via_cli(
         .stderr(std::process::Stdio::piped())
         .spawn()
         .map_err(|err| {
-            if err.kind() == std::io::ErrorKind::NotFound {
-                anyhow!(
-                    "missing droid executable ({}): install the Droid CLI and ensure \
-                     it is available on PATH (or set LUBAN_DROID_BIN to an absolute path)",
-                    droid.display()
-                )
-            } else {
-                anyhow!(err).context("failed to spawn droid")
+            RunnerBinary {
+                runner: "droid",
+                path: &droid,
+                bin_env: paths::LUBAN_DROID_BIN_ENV,
             }
+            .spawn_error(err)
         })?;
 
     // Pipe the prompt into stdin, then close it.
@@ -1,7 +1,12 @@
 use super::GitWorkspaceService;
+use super::git_branch::{branch_exists, remote_branch_exists};
 use anyhow::{Context as _, anyhow};
+use luban_domain::LastCommitInfo;
 use std::{ffi::OsStr, path::Path, path::PathBuf, process::Command};
 
+const NO_STASH_ENTRIES_MESSAGE: &str = "No stashed changes to restore in this workdir";
+const NOTHING_TO_COMMIT_MESSAGE: &str = "Nothing to commit in this workdir";
+
 fn push_ascii_lowercase(dst: &mut String, s: &str) {
     for ch in s.chars() {
         if ch.is_ascii_uppercase() {
@@ -38,6 +43,71 @@ impl GitWorkspaceService {
         Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
     }
 
+    /// `git log -1` of the worktree's HEAD, or `None` when the repository has no commits yet.
+    pub(super) fn last_commit(
+        &self,
+        worktree_path: &Path,
+    ) -> anyhow::Result<Option<LastCommitInfo>> {
+        if self
+            .run_git(worktree_path, ["rev-parse", "--verify", "--quiet", "HEAD"])
+            .is_err()
+        {
+            return Ok(None);
+        }
+        let out = self.run_git(
+            worktree_path,
+            ["log", "-1", "--format=%H%x00%s%x00%an%x00%at"],
+        )?;
+        parse_last_commit(&out).map(Some)
+    }
+
+    /// Runs `git stash push -u`; returns `false` when there was nothing to stash.
+    pub(super) fn stash_push(&self, worktree_path: &Path) -> anyhow::Result<bool> {
+        let status = self.run_git(worktree_path, ["status", "--porcelain"])?;
+        if status.is_empty() {
+            return Ok(false);
+        }
+        self.run_git(worktree_path, ["stash", "push", "-u"])?;
+        Ok(true)
+    }
+
+    pub(super) fn stash_pop(&self, worktree_path: &Path) -> anyhow::Result<()> {
+        let entries = self.run_git(worktree_path, ["stash", "list"])?;
+        if entries.is_empty() {
+            return Err(anyhow!(NO_STASH_ENTRIES_MESSAGE));
+        }
+        self.run_git(worktree_path, ["stash", "pop"])?;
+        Ok(())
+    }
+
+    /// Stages `paths` (everything when `None`), commits only those paths and returns the short
+    /// hash of the new commit.
+    pub(super) fn commit(
+        &self,
+        worktree_path: &Path,
+        message: &str,
+        paths: Option<&[String]>,
+    ) -> anyhow::Result<String> {
+        let pathspec = paths.unwrap_or_default();
+        let mut add = vec!["add", "-A"];
+        let mut diff = vec!["diff", "--cached", "--quiet"];
+        let mut commit = vec!["commit", "-m", message];
+        if !pathspec.is_empty() {
+            for args in [&mut add, &mut diff, &mut commit] {
+                args.push("--");
+                args.extend(pathspec.iter().map(String::as_str));
+            }
+        }
+
+        self.run_git(worktree_path, &add)?;
+        // `--quiet` exits non-zero when something is staged.
+        if self.run_git(worktree_path, &diff).is_ok() {
+            return Err(anyhow!(NOTHING_TO_COMMIT_MESSAGE));
+        }
+        self.run_git(worktree_path, &commit)?;
+        self.run_git(worktree_path, ["rev-parse", "--short", "HEAD"])
+    }
+
     pub(super) fn repo_root(&self, repo_path: &Path) -> anyhow::Result<PathBuf> {
         let root = self
             .run_git(repo_path, ["rev-parse", "--show-toplevel"])
@@ -69,6 +139,44 @@ impl GitWorkspaceService {
         Ok(first_remote.map(ToOwned::to_owned))
     }
 
+    /// Best-effort default branch of `repo_path`: `origin/HEAD` when the clone recorded it, then
+    /// a `main`/`master` branch on `origin`, then a local one, then the branch checked out at
+    /// the repository root.
+    pub(super) fn default_branch(&self, repo_path: &Path) -> Option<String> {
+        const REMOTE: &str = "origin";
+        const CONVENTIONAL: [&str; 2] = ["main", "master"];
+
+        if let Ok(head) = self.run_git(
+            repo_path,
+            [
+                "symbolic-ref",
+                "--quiet",
+                "--short",
+                "refs/remotes/origin/HEAD",
+            ],
+        ) && let Some(branch) = head.strip_prefix("origin/")
+            && !branch.is_empty()
+        {
+            return Some(branch.to_owned());
+        }
+
+        if let Some(branch) = CONVENTIONAL
+            .into_iter()
+            .find(|b| remote_branch_exists(repo_path, REMOTE, b))
+            .or_else(|| {
+                CONVENTIONAL
+                    .into_iter()
+                    .find(|b| branch_exists(repo_path, b))
+            })
+        {
+            return Some(branch.to_owned());
+        }
+
+        self.run_git(repo_path, ["symbolic-ref", "--quiet", "--short", "HEAD"])
+            .ok()
+            .filter(|branch| !branch.is_empty())
+    }
+
     pub(super) fn github_repo_id_from_remote_url(url: &str) -> Option<String> {
         let trimmed = url.trim().trim_end_matches('/');
         if trimmed.is_empty() {
@@ -114,6 +222,24 @@ impl GitWorkspaceService {
     }
 }
 
+fn parse_last_commit(out: &str) -> anyhow::Result<LastCommitInfo> {
+    let mut fields = out.split('\0');
+    let (Some(sha), Some(subject), Some(author), Some(unix_time)) =
+        (fields.next(), fields.next(), fields.next(), fields.next())
+    else {
+        return Err(anyhow!("unexpected git log output: {out:?}"));
+    };
+    Ok(LastCommitInfo {
+        sha: sha.to_owned(),
+        subject: subject.to_owned(),
+        author: author.to_owned(),
+        unix_time: unix_time
+            .trim()
+            .parse()
+            .with_context(|| format!("invalid commit time: {unix_time:?}"))?,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::GitWorkspaceService;
@@ -1,4 +1,8 @@
-use std::{path::Path, process::Command};
+use luban_domain::BranchNameTemplate;
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
 
 pub(crate) fn normalize_branch_suffix(raw: &str) -> Option<String> {
     let trimmed = raw.trim();
@@ -48,6 +52,104 @@ pub(crate) fn normalize_branch_suffix(raw: &str) -> Option<String> {
     Some(limited)
 }
 
+/// Returns the branch name for a workdir whose normalized name is `name`: `luban/<name>`, or
+/// the rendered project template.
+///
+/// In a template, `{name}` expands to `name`, `{slug}` to the project slug and `{user}` to
+/// `user`. A template without `{name}` is treated as a prefix, so `alice` yields `alice/<name>`.
+/// Each `/`-separated component is then sanitized like a project slug and empty components are
+/// dropped.
+pub(crate) fn render_branch_name(
+    template: Option<&BranchNameTemplate>,
+    name: &str,
+    user: &str,
+) -> anyhow::Result<String> {
+    let Some(template) = template else {
+        return Ok(format!("luban/{name}"));
+    };
+
+    let mut raw = template.template.clone();
+    if !raw.contains("{name}") {
+        raw.push_str("/{name}");
+    }
+    let raw = raw
+        .replace("{name}", name)
+        .replace("{slug}", &template.project_slug)
+        .replace("{user}", user);
+    let branch_name = raw
+        .split('/')
+        .map(sanitize_branch_component)
+        .filter(|component| !component.is_empty())
+        .collect::<Vec<_>>()
+        .join("/");
+
+    if !is_git_legal_branch_name(&branch_name) {
+        return Err(anyhow::anyhow!(
+            "branch name template '{}' renders an invalid branch name '{branch_name}'",
+            template.template
+        ));
+    }
+    Ok(branch_name)
+}
+
+/// Name the `{user}` placeholder expands to: git's `user.name`, then `$USER`.
+pub(crate) fn branch_template_user(repo_path: &Path) -> String {
+    Command::new("git")
+        .args(["config", "user.name"])
+        .current_dir(repo_path)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_owned())
+        .filter(|name| !name.is_empty())
+        .or_else(|| std::env::var("USER").ok())
+        .unwrap_or_default()
+}
+
+/// Lowercases ASCII letters and digits and collapses every other run into a single `-`, the
+/// same mapping `sanitize_slug` uses for project slugs.
+fn sanitize_branch_component(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len());
+    for ch in raw.chars() {
+        if ch.is_ascii_alphanumeric() {
+            out.push(ch.to_ascii_lowercase());
+        } else if !out.is_empty() && !out.ends_with('-') {
+            out.push('-');
+        }
+    }
+    while out.ends_with('-') {
+        out.pop();
+    }
+    out
+}
+
+/// Checks the rules of `git check-ref-format --branch`.
+fn is_git_legal_branch_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != "HEAD"
+        && !name.starts_with('-')
+        && !name.ends_with('.')
+        && !name.ends_with(".lock")
+        && !name.contains("..")
+        && !name.contains("@{")
+        && name
+            .split('/')
+            .all(|component| !component.is_empty() && !component.starts_with('.'))
+        && !name.chars().any(|ch| {
+            ch.is_ascii_control() || matches!(ch, ' ' | '~' | '^' | ':' | '?' | '*' | '[' | '\\')
+        })
+}
+
+/// Returns true when `HEAD` in `repo_path` points at a commit rather than a branch.
+pub(crate) fn is_detached_head(repo_path: &Path) -> bool {
+    Command::new("git")
+        .args(["symbolic-ref", "--quiet", "HEAD"])
+        .current_dir(repo_path)
+        .status()
+        .ok()
+        .is_some_and(|s| s.code() == Some(1))
+}
+
 pub(crate) fn branch_exists(repo_path: &Path, branch_name: &str) -> bool {
     let branch_ref = format!("refs/heads/{branch_name}");
     Command::new("git")
@@ -59,9 +161,44 @@ pub(crate) fn branch_exists(repo_path: &Path, branch_name: &str) -> bool {
         .unwrap_or(false)
 }
 
+pub(crate) fn remote_branch_exists(repo_path: &Path, remote: &str, branch_name: &str) -> bool {
+    let branch_ref = format!("refs/remotes/{remote}/{branch_name}");
+    Command::new("git")
+        .args(["show-ref", "--verify", "--quiet", &branch_ref])
+        .current_dir(repo_path)
+        .status()
+        .ok()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+/// Returns the worktree that currently has `branch_name` checked out, if any.
+pub(crate) fn branch_worktree_path(repo_path: &Path, branch_name: &str) -> Option<PathBuf> {
+    let output = Command::new("git")
+        .args(["worktree", "list", "--porcelain"])
+        .current_dir(repo_path)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let branch_ref = format!("refs/heads/{branch_name}");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut current: Option<&str> = None;
+    for line in stdout.lines() {
+        if let Some(path) = line.strip_prefix("worktree ") {
+            current = Some(path);
+        } else if line.strip_prefix("branch ") == Some(branch_ref.as_str()) {
+            return current.map(PathBuf::from);
+        }
+    }
+    None
+}
+
 #[cfg(test)]
 mod tests {
-    use super::normalize_branch_suffix;
+    use super::*;
 
     #[test]
     fn normalize_branch_suffix_strips_prefixes_and_sanitizes() {
@@ -88,4 +225,50 @@ mod tests {
         assert_eq!(suffix.len(), 24);
         assert_eq!(suffix, "abcdefghijklmnopqrstuvwx");
     }
+
+    #[test]
+    fn branch_name_template_expands_placeholders_and_sanitizes() {
+        let template = |raw: &str| BranchNameTemplate {
+            template: raw.to_owned(),
+            project_slug: "my-project".to_owned(),
+        };
+
+        assert_eq!(
+            render_branch_name(None, "fix-login", "alice").unwrap(),
+            "luban/fix-login"
+        );
+        assert_eq!(
+            render_branch_name(Some(&template("{user}/{name}")), "fix-login", "Alice Smith")
+                .unwrap(),
+            "alice-smith/fix-login"
+        );
+        assert_eq!(
+            render_branch_name(Some(&template("feat/{slug}_{name}")), "x", "").unwrap(),
+            "feat/my-project-x"
+        );
+        assert_eq!(
+            render_branch_name(Some(&template("{slug}/{name}")), "fix", "").unwrap(),
+            "my-project/fix"
+        );
+        assert_eq!(
+            render_branch_name(Some(&template("feature/{name}")), "fix", "").unwrap(),
+            "feature/fix"
+        );
+        assert_eq!(
+            render_branch_name(Some(&template("{slug}")), "fix", "").unwrap(),
+            "my-project/fix"
+        );
+        assert_eq!(
+            render_branch_name(Some(&template("Team..A/")), "fix", "").unwrap(),
+            "team-a/fix"
+        );
+        assert_eq!(
+            render_branch_name(Some(&template("{user}/{name}")), "fix", "").unwrap(),
+            "fix"
+        );
+        assert!(is_git_legal_branch_name("team-a/fix"));
+        assert!(!is_git_legal_branch_name("a..b"));
+        assert!(!is_git_legal_branch_name("a/.b"));
+        assert!(!is_git_legal_branch_name("ref.lock"));
+    }
 }
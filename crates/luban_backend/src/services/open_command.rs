@@ -28,6 +28,20 @@ pub(crate) fn linux_open_command(
             args.push(worktree_path.as_os_str().to_os_string());
             ("zed", "zed")
         }
+        OpenTarget::Idea => {
+            args.push(worktree_path.as_os_str().to_os_string());
+            ("idea", "idea")
+        }
+        OpenTarget::Sublime => {
+            args.push(worktree_path.as_os_str().to_os_string());
+            ("subl", "subl")
+        }
+        OpenTarget::Neovim => {
+            args.push("-e".into());
+            args.push("nvim".into());
+            args.push(worktree_path.as_os_str().to_os_string());
+            ("x-terminal-emulator", "x-terminal-emulator -e nvim")
+        }
         OpenTarget::Finder => {
             args.push(worktree_path.as_os_str().to_os_string());
             ("xdg-open", "xdg-open")
@@ -47,6 +61,33 @@ pub(crate) fn linux_open_command(
     })
 }
 
+/// AppleScript that opens a Terminal window in `worktree_path` running `nvim .`.
+#[cfg(any(target_os = "macos", test))]
+pub(crate) fn terminal_nvim_applescript(worktree_path: &std::path::Path) -> String {
+    let quoted_path = format!(
+        "'{}'",
+        worktree_path.to_string_lossy().replace('\'', "'\\''")
+    );
+    let shell_command = format!("cd {quoted_path} && nvim .");
+    let escaped = shell_command.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("tell application \"Terminal\"\n  activate\n  do script \"{escaped}\"\nend tell")
+}
+
+#[cfg(test)]
+mod applescript_tests {
+    use super::terminal_nvim_applescript;
+    use std::path::Path;
+
+    #[test]
+    fn terminal_nvim_applescript_quotes_the_worktree_path() {
+        let script = terminal_nvim_applescript(Path::new("/tmp/it's \"here\""));
+        assert_eq!(
+            script,
+            "tell application \"Terminal\"\n  activate\n  do script \"cd '/tmp/it'\\\\''s \\\"here\\\"' && nvim .\"\nend tell"
+        );
+    }
+}
+
 #[cfg(all(test, target_os = "linux"))]
 mod tests {
     use super::linux_open_command;
@@ -77,4 +118,26 @@ mod tests {
             vec![std::ffi::OsString::from("/tmp/luban-worktree")]
         );
     }
+
+    #[test]
+    fn linux_open_command_supports_idea_sublime_and_neovim() {
+        let worktree_path = Path::new("/tmp/luban-worktree");
+        let idea = linux_open_command(OpenTarget::Idea, worktree_path).expect("idea open command");
+        assert_eq!(idea.program, "idea");
+        let sublime =
+            linux_open_command(OpenTarget::Sublime, worktree_path).expect("subl open command");
+        assert_eq!(sublime.program, "subl");
+
+        let neovim =
+            linux_open_command(OpenTarget::Neovim, worktree_path).expect("nvim open command");
+        assert_eq!(neovim.program, "x-terminal-emulator");
+        assert_eq!(
+            neovim.args,
+            vec![
+                std::ffi::OsString::from("-e"),
+                std::ffi::OsString::from("nvim"),
+                std::ffi::OsString::from("/tmp/luban-worktree"),
+            ]
+        );
+    }
 }
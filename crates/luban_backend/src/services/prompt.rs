@@ -54,6 +54,16 @@ fn format_prompt(
     out
 }
 
+/// Prepends the turn's system prompt so every runner receives it ahead of the user message.
+pub(super) fn prepend_system_prompt(system_prompt: Option<&str>, prompt: &str) -> String {
+    match system_prompt.map(str::trim).filter(|s| !s.is_empty()) {
+        Some(system_prompt) => {
+            format!("<system_instructions>\n{system_prompt}\n</system_instructions>\n\n{prompt}")
+        }
+        None => prompt.to_owned(),
+    }
+}
+
 pub(super) fn format_amp_prompt(prompt: &str, attachments: &[PromptAttachment]) -> String {
     format_prompt(prompt, attachments, "@")
 }
@@ -0,0 +1,148 @@
+use super::GitWorkspaceService;
+use anyhow::{Context as _, anyhow};
+use luban_domain::{
+    ProjectWorkspaceService as _, PromptTemplatesImportReport, SystemTaskKind, TaskIntentKind,
+    default_system_prompt_template, default_task_prompt_template,
+};
+use std::path::Path;
+
+fn template_file_name(key: &str) -> String {
+    format!("{key}.md")
+}
+
+/// Reads `<dir>/<key>.md`, treating a missing or blank file as "no template".
+fn read_template(dir: &Path, key: &str) -> anyhow::Result<Option<String>> {
+    let path = dir.join(template_file_name(key));
+    match std::fs::read_to_string(&path) {
+        Ok(contents) => {
+            let trimmed = contents.trim();
+            Ok((!trimmed.is_empty()).then(|| trimmed.to_owned()))
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(anyhow!(err).context(format!("failed to read {}", path.display()))),
+    }
+}
+
+fn write_template(dir: &Path, key: &str, template: &str) -> anyhow::Result<()> {
+    let path = dir.join(template_file_name(key));
+    std::fs::write(&path, format!("{}\n", template.trim()))
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+enum ImportDecision {
+    Unchanged,
+    Conflict,
+    Store,
+    ResetToDefault,
+}
+
+/// A stored template is a user customization; replacing one with different contents is a
+/// conflict unless `force` is set.
+fn decide(incoming: &str, stored: Option<&str>, default: &str, force: bool) -> ImportDecision {
+    let current = stored.unwrap_or(default).trim();
+    if incoming == current {
+        return ImportDecision::Unchanged;
+    }
+    if stored.is_some() && !force {
+        return ImportDecision::Conflict;
+    }
+    if incoming == default.trim() {
+        ImportDecision::ResetToDefault
+    } else {
+        ImportDecision::Store
+    }
+}
+
+impl GitWorkspaceService {
+    /// Writes the effective template of every task and system prompt kind to `<dir>/<key>.md`
+    /// and returns the written keys.
+    pub(super) fn export_prompt_templates_internal(
+        &self,
+        dir: &Path,
+    ) -> anyhow::Result<Vec<String>> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create {}", dir.display()))?;
+        let task_templates = self
+            .task_prompt_templates_load()
+            .map_err(|err| anyhow!(err))?;
+        let system_templates = self
+            .system_prompt_templates_load()
+            .map_err(|err| anyhow!(err))?;
+
+        let mut written = Vec::new();
+        for kind in TaskIntentKind::ALL {
+            let template = task_templates
+                .get(&kind)
+                .cloned()
+                .unwrap_or_else(|| default_task_prompt_template(kind));
+            write_template(dir, kind.as_key(), &template)?;
+            written.push(kind.as_key().to_owned());
+        }
+        for kind in SystemTaskKind::ALL {
+            let template = system_templates
+                .get(&kind)
+                .cloned()
+                .unwrap_or_else(|| default_system_prompt_template(kind));
+            write_template(dir, kind.as_key(), &template)?;
+            written.push(kind.as_key().to_owned());
+        }
+        Ok(written)
+    }
+
+    /// Reads templates exported by `export_prompt_templates_internal` back from `dir`. Files for
+    /// unknown kinds are ignored and missing files leave the current template in place.
+    pub(super) fn import_prompt_templates_internal(
+        &self,
+        dir: &Path,
+        force: bool,
+    ) -> anyhow::Result<PromptTemplatesImportReport> {
+        if !dir.is_dir() {
+            return Err(anyhow!("not a directory: {}", dir.display()));
+        }
+        let task_templates = self
+            .task_prompt_templates_load()
+            .map_err(|err| anyhow!(err))?;
+        let system_templates = self
+            .system_prompt_templates_load()
+            .map_err(|err| anyhow!(err))?;
+
+        let mut report = PromptTemplatesImportReport::default();
+        for kind in TaskIntentKind::ALL {
+            let Some(incoming) = read_template(dir, kind.as_key())? else {
+                continue;
+            };
+            let default = default_task_prompt_template(kind);
+            let stored = task_templates.get(&kind).map(String::as_str);
+            match decide(&incoming, stored, &default, force) {
+                ImportDecision::Unchanged => continue,
+                ImportDecision::Conflict => {
+                    report.conflicts.push(kind.as_key().to_owned());
+                    continue;
+                }
+                ImportDecision::Store => self.task_prompt_template_store(kind, incoming),
+                ImportDecision::ResetToDefault => self.task_prompt_template_delete(kind),
+            }
+            .map_err(|err| anyhow!(err))?;
+            report.imported.push(kind.as_key().to_owned());
+        }
+        for kind in SystemTaskKind::ALL {
+            let Some(incoming) = read_template(dir, kind.as_key())? else {
+                continue;
+            };
+            let default = default_system_prompt_template(kind);
+            let stored = system_templates.get(&kind).map(String::as_str);
+            match decide(&incoming, stored, &default, force) {
+                ImportDecision::Unchanged => continue,
+                ImportDecision::Conflict => {
+                    report.conflicts.push(kind.as_key().to_owned());
+                    continue;
+                }
+                ImportDecision::Store => self.system_prompt_template_store(kind, incoming),
+                ImportDecision::ResetToDefault => self.system_prompt_template_delete(kind),
+            }
+            .map_err(|err| anyhow!(err))?;
+            report.imported.push(kind.as_key().to_owned());
+        }
+        Ok(report)
+    }
+}
@@ -47,3 +47,31 @@ pub(super) fn is_merge_ready(
     }
     matches!(merge_state_status, "CLEAN" | "HAS_HOOKS")
 }
+
+/// Whether a failed `gh pr view` only means there is no pull request to show for the branch, as
+/// opposed to a failure (network, auth, rate limit) that is worth retrying soon.
+pub(super) fn is_no_pull_request_error(stderr: &str) -> bool {
+    let stderr = stderr.to_ascii_lowercase();
+    stderr.contains("no pull requests found")
+        || stderr.contains("no git remotes found")
+        || stderr.contains("none of the git remotes configured")
+}
+
+/// `gh pr <subcommand>` arguments, narrowed to `selector` (a pull request number or branch) and
+/// to `repo` (`owner/repo`) when given. Without either, `gh` resolves both from the worktree.
+pub(super) fn gh_pr_args(
+    subcommand: &[&str],
+    selector: Option<&str>,
+    repo: Option<&str>,
+) -> Vec<String> {
+    let mut args = vec!["pr".to_owned()];
+    args.extend(subcommand.iter().map(|arg| (*arg).to_owned()));
+    if let Some(selector) = selector {
+        args.insert(2, selector.to_owned());
+    }
+    if let Some(repo) = repo {
+        args.push("--repo".to_owned());
+        args.push(repo.to_owned());
+    }
+    args
+}
@@ -0,0 +1,147 @@
+use anyhow::anyhow;
+use std::{fmt, path::Path, process::ExitStatus};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(super) enum RunnerSpawnErrorKind {
+    NotFound,
+    PermissionDenied,
+    ExitedImmediately,
+}
+
+impl RunnerSpawnErrorKind {
+    pub(super) fn code(self) -> &'static str {
+        match self {
+            Self::NotFound => "runner_not_found",
+            Self::PermissionDenied => "runner_permission_denied",
+            Self::ExitedImmediately => "runner_exited_immediately",
+        }
+    }
+}
+
+/// An agent binary that could not be started, with a message telling the user how to fix it.
+#[derive(Debug)]
+pub(super) struct RunnerSpawnError {
+    pub(super) kind: RunnerSpawnErrorKind,
+    message: String,
+}
+
+impl fmt::Display for RunnerSpawnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for RunnerSpawnError {}
+
+/// Which agent binary was being launched and how the user can point Luban at another one.
+#[derive(Clone, Copy)]
+pub(super) struct RunnerBinary<'a> {
+    pub(super) runner: &'static str,
+    pub(super) path: &'a Path,
+    pub(super) bin_env: &'static str,
+}
+
+impl RunnerBinary<'_> {
+    pub(super) fn spawn_error(self, err: std::io::Error) -> anyhow::Error {
+        let Self {
+            runner,
+            path,
+            bin_env,
+        } = self;
+        let (kind, message) = match err.kind() {
+            std::io::ErrorKind::NotFound => (
+                RunnerSpawnErrorKind::NotFound,
+                format!(
+                    "missing {runner} executable ({}): {runner} not found on PATH; install it or set {bin_env} to an absolute path (macOS apps launched from Finder/Dock may not inherit your shell PATH)",
+                    path.display()
+                ),
+            ),
+            std::io::ErrorKind::PermissionDenied => (
+                RunnerSpawnErrorKind::PermissionDenied,
+                format!(
+                    "{runner} executable is not runnable ({}): permission denied; make sure it is executable or set {bin_env} to a working binary",
+                    path.display()
+                ),
+            ),
+            _ => return anyhow!(err).context(format!("failed to spawn {runner}")),
+        };
+        RunnerSpawnError { kind, message }.into()
+    }
+
+    /// Used when the process fails before emitting a single protocol event, which usually
+    /// means a broken install rather than a failed turn.
+    pub(super) fn exited_immediately(self, status: ExitStatus, stderr: &str) -> anyhow::Error {
+        let Self {
+            runner,
+            path,
+            bin_env,
+        } = self;
+        let mut message = format!(
+            "{runner} exited immediately ({status}) without producing output; run `{} --version` to check the install or set {bin_env} to a working binary",
+            path.display()
+        );
+        let stderr = stderr.trim();
+        if !stderr.is_empty() {
+            message.push_str("\nstderr:\n");
+            message.push_str(stderr);
+        }
+        RunnerSpawnError {
+            kind: RunnerSpawnErrorKind::ExitedImmediately,
+            message,
+        }
+        .into()
+    }
+}
+
+pub(super) fn runner_spawn_error_code(err: &anyhow::Error) -> Option<&'static str> {
+    err.chain()
+        .find_map(|cause| cause.downcast_ref::<RunnerSpawnError>())
+        .map(|err| err.kind.code())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn spawning_a_nonexistent_binary_is_classified_as_not_found() {
+        let missing = std::env::temp_dir().join(format!(
+            "luban-missing-runner-{}-{}",
+            std::process::id(),
+            crate::time::unix_epoch_nanos_now()
+        ));
+        let err = Command::new(&missing)
+            .spawn()
+            .map_err(|err| {
+                RunnerBinary {
+                    runner: "codex",
+                    path: &missing,
+                    bin_env: "LUBAN_CODEX_BIN",
+                }
+                .spawn_error(err)
+            })
+            .expect_err("spawning a missing binary should fail");
+
+        assert_eq!(runner_spawn_error_code(&err), Some("runner_not_found"));
+        let message = format!("{err:#}");
+        assert!(message.contains("codex not found on PATH"), "{message}");
+        assert!(message.contains("LUBAN_CODEX_BIN"), "{message}");
+
+        let wrapped = err.context("failed to start turn");
+        assert_eq!(runner_spawn_error_code(&wrapped), Some("runner_not_found"));
+    }
+
+    #[test]
+    fn other_spawn_failures_are_left_unclassified() {
+        let err = RunnerBinary {
+            runner: "amp",
+            path: Path::new("amp"),
+            bin_env: "LUBAN_AMP_BIN",
+        }
+        .spawn_error(std::io::Error::other("boom"));
+
+        assert_eq!(runner_spawn_error_code(&err), None);
+        assert!(format!("{err:#}").starts_with("failed to spawn amp"));
+    }
+}
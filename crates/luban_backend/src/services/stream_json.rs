@@ -1,3 +1,4 @@
+use luban_domain::AgentWebSearchResult;
 use serde_json::Value;
 
 pub(super) fn value_as_string(value: &Value) -> Option<String> {
@@ -41,3 +42,45 @@ pub(super) fn extract_string_field(value: &Value, keys: &[&str]) -> Option<Strin
     }
     None
 }
+
+/// Results of a web search tool call, read from its tool result. Accepts a list of
+/// `{ title, url, snippet }` objects, an object wrapping one in `results`/`links`, text blocks
+/// holding such JSON, and text with a `Links: [...]` line as Claude reports it.
+pub(super) fn web_search_results(result: &Value) -> Vec<AgentWebSearchResult> {
+    match result {
+        Value::String(text) => {
+            if let Ok(value) = serde_json::from_str::<Value>(text)
+                && !value.is_string()
+            {
+                return web_search_results(&value);
+            }
+            let Some(start) = text.find("Links:") else {
+                return Vec::new();
+            };
+            serde_json::Deserializer::from_str(text[start + "Links:".len()..].trim_start())
+                .into_iter::<Value>()
+                .next()
+                .and_then(Result::ok)
+                .map(|links| web_search_results(&links))
+                .unwrap_or_default()
+        }
+        Value::Array(items) => items
+            .iter()
+            .flat_map(|item| match item.get("url").and_then(Value::as_str) {
+                Some(url) => vec![AgentWebSearchResult {
+                    title: extract_string_field(item, &["title"]).unwrap_or_default(),
+                    url: url.to_owned(),
+                    snippet: extract_string_field(item, &["snippet", "description"])
+                        .unwrap_or_default(),
+                }],
+                None => item.get("text").map(web_search_results).unwrap_or_default(),
+            })
+            .collect(),
+        Value::Object(object) => object
+            .get("results")
+            .or_else(|| object.get("links"))
+            .map(web_search_results)
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
@@ -150,6 +150,7 @@ fn run_system_task_and_collect_messages(
                     },
                     model_reasoning_effort: Some(thinking_effort.as_str().to_owned()),
                     sandbox_mode: Some("read-only".to_owned()),
+                    env: Vec::new(),
                 },
                 cancel,
                 |event| {
@@ -170,6 +171,7 @@ fn run_system_task_and_collect_messages(
                     worktree_path,
                     prompt,
                     mode: amp_mode,
+                    env: Vec::new(),
                 },
                 cancel,
                 |event| {
@@ -190,6 +192,7 @@ fn run_system_task_and_collect_messages(
                     worktree_path,
                     prompt,
                     add_dirs: Vec::new(),
+                    options: Default::default(),
                 },
                 cancel,
                 |event| {
@@ -212,6 +215,7 @@ fn run_system_task_and_collect_messages(
                     model: Some(model_id.to_owned()),
                     reasoning_effort: Some(thinking_effort.as_str().to_owned()),
                     auto_level: None,
+                    env: Vec::new(),
                 },
                 cancel,
                 |event| {
@@ -367,6 +371,85 @@ fn extract_json_object(raw: &str) -> Option<&str> {
     Some(&trimmed[start..=end])
 }
 
+pub(super) fn task_generate_pr_description(
+    service: &GitWorkspaceService,
+    input: String,
+    runner: AgentRunnerKind,
+    model_id: String,
+    thinking_effort: ThinkingEffort,
+    amp_mode: Option<String>,
+) -> anyhow::Result<String> {
+    let context_json = serde_json::json!({ "format": "github_markdown" }).to_string();
+    let prompt = system_prompt_for_task(
+        service,
+        SystemTaskKind::PrDescription,
+        input.trim(),
+        &context_json,
+    );
+
+    let raw = run_system_task_and_find_last_message(
+        service,
+        runner,
+        model_id,
+        thinking_effort,
+        amp_mode,
+        prompt,
+    )?;
+    parse_pr_description_output(&raw)
+}
+
+fn parse_pr_description_output(raw: &str) -> anyhow::Result<String> {
+    let body = strip_markdown_fence(raw);
+    if body.is_empty() {
+        return Err(anyhow!("agent returned an empty pull request description"));
+    }
+    Ok(body.to_owned())
+}
+
+pub(super) fn task_summarize_conversation(
+    service: &GitWorkspaceService,
+    input: String,
+    runner: AgentRunnerKind,
+    model_id: String,
+    thinking_effort: ThinkingEffort,
+    amp_mode: Option<String>,
+) -> anyhow::Result<String> {
+    let context_json = serde_json::json!({ "format": "markdown" }).to_string();
+    let prompt = system_prompt_for_task(
+        service,
+        SystemTaskKind::ConversationSummary,
+        input.trim(),
+        &context_json,
+    );
+
+    let raw = run_system_task_and_find_last_message(
+        service,
+        runner,
+        model_id,
+        thinking_effort,
+        amp_mode,
+        prompt,
+    )?;
+    let summary = strip_markdown_fence(&raw);
+    if summary.is_empty() {
+        return Err(anyhow!("agent returned an empty conversation summary"));
+    }
+    Ok(summary.to_owned())
+}
+
+/// Strips a single Markdown code fence the agent may have wrapped its answer in.
+fn strip_markdown_fence(raw: &str) -> &str {
+    let trimmed = raw.trim();
+    let without_prefix = trimmed
+        .strip_prefix("```markdown")
+        .or_else(|| trimmed.strip_prefix("```md"))
+        .or_else(|| trimmed.strip_prefix("```"));
+    match without_prefix {
+        Some(rest) => rest.strip_suffix("```").unwrap_or(rest).trim(),
+        None => trimmed,
+    }
+}
+
 pub(super) fn task_suggest_task_status(
     service: &GitWorkspaceService,
     input: String,
@@ -536,4 +619,11 @@ mod tests {
             Some("- Still implementing\n- No PR yet")
         );
     }
+
+    #[test]
+    fn pr_description_output_strips_markdown_fences() {
+        let body = parse_pr_description_output("```markdown\n## Summary\n\nFix it\n```").unwrap();
+        assert_eq!(body, "## Summary\n\nFix it");
+        assert!(parse_pr_description_output("```\n```").is_err());
+    }
 }
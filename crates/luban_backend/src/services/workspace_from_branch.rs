@@ -0,0 +1,94 @@
+use super::GitWorkspaceService;
+use super::git_branch::{
+    branch_exists, branch_worktree_path, normalize_branch_suffix, remote_branch_exists,
+};
+use anyhow::{Context as _, anyhow};
+use luban_domain::CreatedWorkspace;
+use std::path::Path;
+
+impl GitWorkspaceService {
+    pub(super) fn create_workspace_from_branch_internal(
+        &self,
+        project_path: &Path,
+        project_slug: &str,
+        branch_name: &str,
+        worktree_root: Option<&Path>,
+    ) -> anyhow::Result<CreatedWorkspace> {
+        let branch_name = branch_name.trim();
+        let branch_name = branch_name
+            .strip_prefix("refs/heads/")
+            .unwrap_or(branch_name);
+        if branch_name.is_empty() {
+            return Err(anyhow!("branch name is required"));
+        }
+        self.run_git(project_path, ["check-ref-format", "--branch", branch_name])
+            .map_err(|_| anyhow!("invalid branch name '{branch_name}'"))?;
+
+        let remote = "origin";
+        let is_local = branch_exists(project_path, branch_name);
+        if !is_local {
+            // The branch may only exist on the remote (pushed by CI or a teammate); a failed
+            // fetch is reported below as a missing branch.
+            let refspec = format!("refs/heads/{branch_name}:refs/remotes/{remote}/{branch_name}");
+            let _ = self.run_git(project_path, ["fetch", remote, &refspec]);
+            if !remote_branch_exists(project_path, remote, branch_name) {
+                return Err(anyhow!(
+                    "branch '{branch_name}' does not exist locally or on '{remote}'"
+                ));
+            }
+        }
+        if let Some(path) = branch_worktree_path(project_path, branch_name) {
+            return Err(anyhow!(
+                "branch '{branch_name}' is already checked out at {}",
+                path.display()
+            ));
+        }
+
+        let worktrees_dir = self.project_worktrees_dir(worktree_root, project_slug);
+        std::fs::create_dir_all(&worktrees_dir).context("failed to create worktrees root")?;
+
+        let name_base = normalize_branch_suffix(branch_name);
+        for attempt in 0..64 {
+            let workspace_name = match &name_base {
+                Some(base) if attempt == 0 => base.clone(),
+                Some(base) => format!("{base}-v{}", attempt + 1),
+                None => self.generate_workspace_name()?,
+            };
+            let worktree_path = worktrees_dir.join(&workspace_name);
+            if worktree_path.exists() {
+                continue;
+            }
+            let worktree_arg = worktree_path
+                .to_str()
+                .ok_or_else(|| anyhow!("invalid worktree path"))?;
+
+            let remote_ref = format!("{remote}/{branch_name}");
+            let add_args: Vec<&str> = if is_local {
+                vec!["worktree", "add", worktree_arg, branch_name]
+            } else {
+                vec![
+                    "worktree",
+                    "add",
+                    "--track",
+                    "-b",
+                    branch_name,
+                    worktree_arg,
+                    &remote_ref,
+                ]
+            };
+            self.run_git(project_path, add_args).with_context(|| {
+                format!("failed to create worktree at {}", worktree_path.display())
+            })?;
+
+            return Ok(CreatedWorkspace {
+                workspace_name,
+                branch_name: branch_name.to_owned(),
+                worktree_path,
+            });
+        }
+
+        Err(anyhow!(
+            "failed to generate a unique workspace name after retries"
+        ))
+    }
+}
@@ -0,0 +1,97 @@
+use super::GitWorkspaceService;
+use anyhow::Context as _;
+use luban_domain::WorktreeAudit;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Resolves symlinks (e.g. `/tmp` -> `/private/tmp`) so git's paths compare equal to ours.
+fn comparable_path(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Worktree paths from `git worktree list --porcelain`, main worktree first.
+fn parse_worktree_list(raw: &str) -> Vec<PathBuf> {
+    raw.lines()
+        .filter_map(|line| line.strip_prefix("worktree "))
+        .map(PathBuf::from)
+        .collect()
+}
+
+impl GitWorkspaceService {
+    pub(super) fn audit_worktrees_internal(
+        &self,
+        project_path: &Path,
+        tracked_worktree_paths: &[PathBuf],
+    ) -> anyhow::Result<WorktreeAudit> {
+        let raw = self
+            .run_git(project_path, ["worktree", "list", "--porcelain"])
+            .context("failed to list worktrees")?;
+        let project_path = comparable_path(project_path);
+        let tracked = tracked_worktree_paths
+            .iter()
+            .map(|path| comparable_path(path))
+            .collect::<HashSet<_>>();
+
+        // The first entry is the main checkout, which differs from `project_path` when the
+        // project itself is a linked worktree.
+        let mut out = WorktreeAudit::default();
+        for worktree in parse_worktree_list(&raw).iter().skip(1) {
+            let worktree = comparable_path(worktree);
+            if worktree == project_path || tracked.contains(&worktree) || !worktree.exists() {
+                continue;
+            }
+            out.orphaned.push(worktree);
+        }
+        out.dangling = tracked_worktree_paths
+            .iter()
+            .filter(|path| !path.exists())
+            .cloned()
+            .collect();
+        Ok(out)
+    }
+
+    /// Only orphans inside the project's worktrees directory are considered; worktrees elsewhere
+    /// on disk may have been created by hand and are left alone.
+    pub(super) fn prune_orphaned_worktrees_internal(
+        &self,
+        project_path: &Path,
+        project_slug: &str,
+        worktree_root: Option<&Path>,
+        tracked_worktree_paths: &[PathBuf],
+        dry_run: bool,
+    ) -> anyhow::Result<Vec<PathBuf>> {
+        if !dry_run {
+            // Drop administrative entries for worktrees whose directories are already gone so
+            // they are not reported below.
+            self.run_git(project_path, ["worktree", "prune"])
+                .context("failed to prune worktrees")?;
+        }
+        let managed_dir = comparable_path(&self.project_worktrees_dir(worktree_root, project_slug));
+        let orphaned = self
+            .audit_worktrees_internal(project_path, tracked_worktree_paths)?
+            .orphaned
+            .into_iter()
+            .filter(|worktree| worktree.starts_with(&managed_dir))
+            .collect::<Vec<_>>();
+        if dry_run {
+            return Ok(orphaned);
+        }
+
+        let mut failures = Vec::new();
+        for worktree in &orphaned {
+            let path = worktree.to_string_lossy();
+            if let Err(err) = self.run_git(
+                project_path,
+                ["worktree", "remove", "--force", path.as_ref()],
+            ) {
+                failures.push(format!("{path}: {err:#}"));
+            }
+        }
+        self.run_git(project_path, ["worktree", "prune"])
+            .context("failed to prune worktrees")?;
+        if !failures.is_empty() {
+            anyhow::bail!("failed to remove worktrees: {}", failures.join("; "));
+        }
+        Ok(orphaned)
+    }
+}
@@ -1,8 +1,10 @@
+use crate::conversation_search;
 use anyhow::{Context as _, anyhow};
 use luban_domain::{
-    AttachmentKind, AttachmentRef, ChatScrollAnchor, ContextItem, ConversationEntry,
-    ConversationSnapshot, ConversationThreadMeta, PersistedAppState, QueuedPrompt, ThinkingEffort,
-    WorkspaceStatus, WorkspaceThreadId,
+    AttachmentKind, AttachmentRef, ChatScrollAnchor, CollapsedRange, ContextItem,
+    ConversationEntry, ConversationRetentionPolicy, ConversationSearchMatch, ConversationSnapshot,
+    ConversationThreadMeta, HunkComment, HunkLineSide, PersistedAppState, PersistedProjectGroup,
+    ProjectStatus, QueuedPrompt, ThinkingEffort, WorkspaceStatus, WorkspaceThreadId,
 };
 use rand::{RngCore as _, rngs::OsRng};
 use rusqlite::{Connection, OptionalExtension as _, params, params_from_iter};
@@ -25,7 +27,7 @@ impl std::fmt::Display for SqliteStoreError {
 
 impl std::error::Error for SqliteStoreError {}
 
-const LATEST_SCHEMA_VERSION: u32 = 22;
+const LATEST_SCHEMA_VERSION: u32 = 38;
 const WORKSPACE_CHAT_SCROLL_PREFIX: &str = "workspace_chat_scroll_y10_";
 const WORKSPACE_CHAT_SCROLL_ANCHOR_PREFIX: &str = "workspace_chat_scroll_anchor_";
 const WORKSPACE_ACTIVE_THREAD_PREFIX: &str = "workspace_active_thread_id_";
@@ -38,9 +40,13 @@ const TASK_STARRED_PREFIX: &str = "task_starred_";
 const LAST_OPEN_WORKSPACE_ID_KEY: &str = "last_open_workspace_id";
 const OPEN_BUTTON_SELECTION_KEY: &str = "open_button_selection";
 const SIDEBAR_PROJECT_ORDER_KEY: &str = "sidebar_project_order";
+const PROJECT_GROUPS_KEY: &str = "project_groups";
 const GLOBAL_ZOOM_PERCENT_KEY: &str = "global_zoom_percent";
 const AGENT_DEFAULT_MODEL_ID_KEY: &str = "agent_default_model_id";
 const AGENT_RUNNER_DEFAULT_MODELS_KEY: &str = "agent_runner_default_models";
+const AGENT_RUNNER_PROMPT_PREFIXES_KEY: &str = "agent_runner_prompt_prefixes";
+const AGENT_RUNNER_PROMPT_SUFFIXES_KEY: &str = "agent_runner_prompt_suffixes";
+const MODEL_THINKING_EFFORT_CAPS_KEY: &str = "model_thinking_effort_caps";
 const AGENT_DEFAULT_THINKING_EFFORT_KEY: &str = "agent_default_thinking_effort";
 const AGENT_DEFAULT_RUNNER_KEY: &str = "agent_default_runner";
 const AGENT_AMP_MODE_KEY: &str = "agent_amp_mode";
@@ -48,6 +54,12 @@ const AGENT_CODEX_ENABLED_KEY: &str = "agent_codex_enabled";
 const AGENT_AMP_ENABLED_KEY: &str = "agent_amp_enabled";
 const AGENT_CLAUDE_ENABLED_KEY: &str = "agent_claude_enabled";
 const AGENT_DROID_ENABLED_KEY: &str = "agent_droid_enabled";
+const AGENT_MERGE_REASONING_ENTRIES_KEY: &str = "agent_merge_reasoning_entries";
+const AGENT_PAUSE_QUEUE_ON_PATCH_FAILURE_KEY: &str = "agent_pause_queue_on_patch_failure";
+const AGENT_ITEM_DEDUP_WINDOW_KEY: &str = "agent_item_dedup_window";
+const AGENT_AUTO_RETRY_ON_FAILURE_KEY: &str = "agent_auto_retry_on_failure";
+const AGENT_COMMAND_OUTPUT_MAX_BYTES_KEY: &str = "agent_command_output_max_bytes";
+const AUTO_ARCHIVE_AFTER_DAYS_KEY: &str = "auto_archive_after_days";
 const TASK_PROMPT_TEMPLATE_PREFIX: &str = "task_prompt_template_";
 const APPEARANCE_THEME_KEY: &str = "appearance_theme";
 const APPEARANCE_UI_FONT_KEY: &str = "appearance_ui_font";
@@ -57,8 +69,12 @@ const APPEARANCE_TERMINAL_FONT_KEY: &str = "appearance_terminal_font";
 const TELEGRAM_ENABLED_KEY: &str = "telegram_enabled";
 const TELEGRAM_BOT_TOKEN_KEY: &str = "telegram_bot_token";
 const TELEGRAM_BOT_USERNAME_KEY: &str = "telegram_bot_username";
+/// Legacy single-chat pairing, read only to migrate into `TELEGRAM_PAIRED_CHAT_IDS_KEY`.
 const TELEGRAM_PAIRED_CHAT_ID_KEY: &str = "telegram_paired_chat_id";
+const TELEGRAM_PAIRED_CHAT_IDS_KEY: &str = "telegram_paired_chat_ids";
 const TELEGRAM_TOPIC_BINDINGS_KEY: &str = "telegram_topic_bindings";
+const WEBHOOK_URL_KEY: &str = "webhook_url";
+const WEBHOOK_SECRET_KEY: &str = "webhook_secret";
 
 const MIGRATIONS: &[(u32, &str)] = &[
     (
@@ -215,6 +231,118 @@ const MIGRATIONS: &[(u32, &str)] = &[
             "/migrations/0022_new_task_drafts.sql"
         )),
     ),
+    (
+        23,
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/migrations/0023_conversation_notes.sql"
+        )),
+    ),
+    (
+        24,
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/migrations/0024_conversation_cwd_subpath.sql"
+        )),
+    ),
+    (
+        25,
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/migrations/0025_project_system_prompt.sql"
+        )),
+    ),
+    (
+        26,
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/migrations/0026_conversation_hunk_comments.sql"
+        )),
+    ),
+    (
+        27,
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/migrations/0027_canonical_scope_keys.sql"
+        )),
+    ),
+    (
+        28,
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/migrations/0028_conversation_draft.sql"
+        )),
+    ),
+    (
+        29,
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/migrations/0029_project_worktree_root.sql"
+        )),
+    ),
+    (
+        30,
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/migrations/0030_project_status.sql"
+        )),
+    ),
+    (
+        31,
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/migrations/0031_project_agent_defaults.sql"
+        )),
+    ),
+    (
+        32,
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/migrations/0032_conversation_collapsed_ranges.sql"
+        )),
+    ),
+    (
+        33,
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/migrations/0033_project_saved_commands.sql"
+        )),
+    ),
+    (
+        34,
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/migrations/0034_conversation_claude_options.sql"
+        )),
+    ),
+    (
+        35,
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/migrations/0035_project_branch_name_template.sql"
+        )),
+    ),
+    (
+        36,
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/migrations/0036_project_agent_env.sql"
+        )),
+    ),
+    (
+        37,
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/migrations/0037_conversation_auto_commit.sql"
+        )),
+    ),
+    (
+        38,
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/migrations/0038_project_pull_request_target.sql"
+        )),
+    ),
 ];
 
 #[derive(Clone)]
@@ -290,6 +418,13 @@ enum DbCommand {
         entries: Vec<ConversationEntry>,
         reply: mpsc::Sender<anyhow::Result<()>>,
     },
+    UpdateConversationItem {
+        project_slug: String,
+        workspace_name: String,
+        thread_local_id: u64,
+        item: Box<luban_domain::CodexThreadItem>,
+        reply: mpsc::Sender<anyhow::Result<bool>>,
+    },
     UpdateConversationTitleIfMatches {
         project_slug: String,
         workspace_name: String,
@@ -312,12 +447,49 @@ enum DbCommand {
         limit: u64,
         reply: mpsc::Sender<anyhow::Result<ConversationSnapshot>>,
     },
+    CountConversationEntries {
+        project_slug: String,
+        workspace_name: String,
+        thread_local_id: u64,
+        reply: mpsc::Sender<anyhow::Result<u64>>,
+    },
+    PruneConversationEntries {
+        project_slug: String,
+        workspace_name: String,
+        thread_local_id: u64,
+        policy: ConversationRetentionPolicy,
+        now_unix_seconds: u64,
+        reply: mpsc::Sender<anyhow::Result<u64>>,
+    },
+    LoadPromptHistory {
+        project_slug: String,
+        workspace_name: String,
+        thread_local_id: u64,
+        limit: usize,
+        reply: mpsc::Sender<anyhow::Result<Vec<String>>>,
+    },
+    SearchConversations {
+        project_slug: String,
+        workspace_name: String,
+        query: String,
+        thread_local_id: Option<u64>,
+        limit: usize,
+        reply: mpsc::Sender<anyhow::Result<Vec<ConversationSearchMatch>>>,
+    },
     DeleteConversationThread {
         project_slug: String,
         workspace_name: String,
         thread_local_id: u64,
         reply: mpsc::Sender<anyhow::Result<()>>,
     },
+    MoveConversationThread {
+        project_slug: String,
+        workspace_name: String,
+        thread_local_id: u64,
+        to_workspace_name: String,
+        to_thread_local_id: u64,
+        reply: mpsc::Sender<anyhow::Result<()>>,
+    },
     SaveConversationQueueState {
         project_slug: String,
         workspace_name: String,
@@ -336,6 +508,9 @@ enum DbCommand {
         model_id: String,
         thinking_effort: ThinkingEffort,
         amp_mode: Option<String>,
+        claude_max_output_tokens: Option<u32>,
+        claude_system_prompt: Option<String>,
+        auto_commit: bool,
         reply: mpsc::Sender<anyhow::Result<()>>,
     },
     SaveConversationTaskStatus {
@@ -345,6 +520,41 @@ enum DbCommand {
         task_status: luban_domain::TaskStatus,
         reply: mpsc::Sender<anyhow::Result<()>>,
     },
+    SaveConversationNotes {
+        project_slug: String,
+        workspace_name: String,
+        thread_local_id: u64,
+        notes: String,
+        reply: mpsc::Sender<anyhow::Result<()>>,
+    },
+    SaveConversationDraft {
+        project_slug: String,
+        workspace_name: String,
+        thread_local_id: u64,
+        draft: String,
+        reply: mpsc::Sender<anyhow::Result<()>>,
+    },
+    SaveConversationCwdSubpath {
+        project_slug: String,
+        workspace_name: String,
+        thread_local_id: u64,
+        cwd_subpath: Option<String>,
+        reply: mpsc::Sender<anyhow::Result<()>>,
+    },
+    SaveConversationHunkComment {
+        project_slug: String,
+        workspace_name: String,
+        thread_local_id: u64,
+        comment: HunkComment,
+        reply: mpsc::Sender<anyhow::Result<()>>,
+    },
+    SaveConversationCollapsedRange {
+        project_slug: String,
+        workspace_name: String,
+        thread_local_id: u64,
+        range: CollapsedRange,
+        reply: mpsc::Sender<anyhow::Result<()>>,
+    },
     SaveConversationTaskStatusLastAnalyzed {
         project_slug: String,
         workspace_name: String,
@@ -533,6 +743,23 @@ impl SqliteStore {
                                 &entries,
                             ));
                         }
+                        (
+                            Ok(db),
+                            DbCommand::UpdateConversationItem {
+                                project_slug,
+                                workspace_name,
+                                thread_local_id,
+                                item,
+                                reply,
+                            },
+                        ) => {
+                            let _ = reply.send(db.update_conversation_item(
+                                &project_slug,
+                                &workspace_name,
+                                thread_local_id,
+                                &item,
+                            ));
+                        }
                         (
                             Ok(db),
                             DbCommand::UpdateConversationTitleIfMatches {
@@ -567,6 +794,42 @@ impl SqliteStore {
                                 thread_local_id,
                             ));
                         }
+                        (
+                            Ok(db),
+                            DbCommand::LoadPromptHistory {
+                                project_slug,
+                                workspace_name,
+                                thread_local_id,
+                                limit,
+                                reply,
+                            },
+                        ) => {
+                            let _ = reply.send(db.load_prompt_history(
+                                &project_slug,
+                                &workspace_name,
+                                thread_local_id,
+                                limit,
+                            ));
+                        }
+                        (
+                            Ok(db),
+                            DbCommand::SearchConversations {
+                                project_slug,
+                                workspace_name,
+                                query,
+                                thread_local_id,
+                                limit,
+                                reply,
+                            },
+                        ) => {
+                            let _ = reply.send(db.search_conversations(
+                                &project_slug,
+                                &workspace_name,
+                                &query,
+                                thread_local_id,
+                                limit,
+                            ));
+                        }
                         (
                             Ok(db),
                             DbCommand::LoadConversationPage {
@@ -586,6 +849,40 @@ impl SqliteStore {
                                 limit,
                             ));
                         }
+                        (
+                            Ok(db),
+                            DbCommand::CountConversationEntries {
+                                project_slug,
+                                workspace_name,
+                                thread_local_id,
+                                reply,
+                            },
+                        ) => {
+                            let _ = reply.send(db.count_conversation_entries(
+                                &project_slug,
+                                &workspace_name,
+                                thread_local_id,
+                            ));
+                        }
+                        (
+                            Ok(db),
+                            DbCommand::PruneConversationEntries {
+                                project_slug,
+                                workspace_name,
+                                thread_local_id,
+                                policy,
+                                now_unix_seconds,
+                                reply,
+                            },
+                        ) => {
+                            let _ = reply.send(db.prune_conversation_entries(
+                                &project_slug,
+                                &workspace_name,
+                                thread_local_id,
+                                policy,
+                                now_unix_seconds,
+                            ));
+                        }
                         (
                             Ok(db),
                             DbCommand::DeleteConversationThread {
@@ -601,6 +898,25 @@ impl SqliteStore {
                                 thread_local_id,
                             ));
                         }
+                        (
+                            Ok(db),
+                            DbCommand::MoveConversationThread {
+                                project_slug,
+                                workspace_name,
+                                thread_local_id,
+                                to_workspace_name,
+                                to_thread_local_id,
+                                reply,
+                            },
+                        ) => {
+                            let _ = reply.send(db.move_conversation_thread(
+                                &project_slug,
+                                &workspace_name,
+                                thread_local_id,
+                                &to_workspace_name,
+                                to_thread_local_id,
+                            ));
+                        }
                         (
                             Ok(db),
                             DbCommand::SaveConversationQueueState {
@@ -634,6 +950,9 @@ impl SqliteStore {
                                 model_id,
                                 thinking_effort,
                                 amp_mode,
+                                claude_max_output_tokens,
+                                claude_system_prompt,
+                                auto_commit,
                                 reply,
                             },
                         ) => {
@@ -645,6 +964,9 @@ impl SqliteStore {
                                 &model_id,
                                 thinking_effort,
                                 amp_mode.as_deref(),
+                                claude_max_output_tokens,
+                                claude_system_prompt.as_deref(),
+                                auto_commit,
                             ));
                         }
                         (
@@ -666,102 +988,187 @@ impl SqliteStore {
                         }
                         (
                             Ok(db),
-                            DbCommand::SaveConversationTaskStatusLastAnalyzed {
+                            DbCommand::SaveConversationNotes {
                                 project_slug,
                                 workspace_name,
                                 thread_local_id,
+                                notes,
                                 reply,
                             },
                         ) => {
-                            let _ = reply.send(db.save_conversation_task_status_last_analyzed(
+                            let _ = reply.send(db.save_conversation_notes(
                                 &project_slug,
                                 &workspace_name,
                                 thread_local_id,
+                                &notes,
                             ));
                         }
                         (
                             Ok(db),
-                            DbCommand::SaveConversationTaskValidationPr {
+                            DbCommand::SaveConversationDraft {
                                 project_slug,
                                 workspace_name,
                                 thread_local_id,
-                                pr_number,
-                                pr_url,
+                                draft,
                                 reply,
                             },
                         ) => {
-                            let _ = reply.send(db.save_conversation_task_validation_pr(
+                            let _ = reply.send(db.save_conversation_draft(
                                 &project_slug,
                                 &workspace_name,
                                 thread_local_id,
-                                pr_number,
-                                pr_url.as_deref(),
+                                &draft,
                             ));
                         }
                         (
                             Ok(db),
-                            DbCommand::ListConversationTasksForMergedPr {
+                            DbCommand::SaveConversationCwdSubpath {
                                 project_slug,
                                 workspace_name,
-                                pr_number,
+                                thread_local_id,
+                                cwd_subpath,
                                 reply,
                             },
                         ) => {
-                            let _ = reply.send(db.list_conversation_tasks_for_merged_pr(
+                            let _ = reply.send(db.save_conversation_cwd_subpath(
                                 &project_slug,
                                 &workspace_name,
-                                pr_number,
+                                thread_local_id,
+                                cwd_subpath.as_deref(),
                             ));
                         }
                         (
                             Ok(db),
-                            DbCommand::InsertContextItem {
+                            DbCommand::SaveConversationHunkComment {
                                 project_slug,
                                 workspace_name,
-                                attachment,
-                                created_at_unix_ms,
+                                thread_local_id,
+                                comment,
                                 reply,
                             },
                         ) => {
-                            let _ = reply.send(db.insert_context_item(
+                            let _ = reply.send(db.save_conversation_hunk_comment(
                                 &project_slug,
                                 &workspace_name,
-                                &attachment,
-                                created_at_unix_ms,
+                                thread_local_id,
+                                &comment,
                             ));
                         }
                         (
                             Ok(db),
-                            DbCommand::ListContextItems {
+                            DbCommand::SaveConversationCollapsedRange {
                                 project_slug,
                                 workspace_name,
+                                thread_local_id,
+                                range,
                                 reply,
                             },
                         ) => {
-                            let _ =
-                                reply.send(db.list_context_items(&project_slug, &workspace_name));
+                            let _ = reply.send(db.save_conversation_collapsed_range(
+                                &project_slug,
+                                &workspace_name,
+                                thread_local_id,
+                                &range,
+                            ));
                         }
                         (
                             Ok(db),
-                            DbCommand::DeleteContextItem {
+                            DbCommand::SaveConversationTaskStatusLastAnalyzed {
                                 project_slug,
                                 workspace_name,
-                                context_id,
+                                thread_local_id,
                                 reply,
                             },
                         ) => {
-                            let _ = reply.send(db.delete_context_item(
+                            let _ = reply.send(db.save_conversation_task_status_last_analyzed(
                                 &project_slug,
                                 &workspace_name,
-                                context_id,
+                                thread_local_id,
                             ));
                         }
-                        (Ok(db), DbCommand::ListNewTaskDrafts { reply }) => {
-                            let _ = reply.send(db.list_new_task_drafts());
-                        }
                         (
                             Ok(db),
-                            DbCommand::CreateNewTaskDraft {
+                            DbCommand::SaveConversationTaskValidationPr {
+                                project_slug,
+                                workspace_name,
+                                thread_local_id,
+                                pr_number,
+                                pr_url,
+                                reply,
+                            },
+                        ) => {
+                            let _ = reply.send(db.save_conversation_task_validation_pr(
+                                &project_slug,
+                                &workspace_name,
+                                thread_local_id,
+                                pr_number,
+                                pr_url.as_deref(),
+                            ));
+                        }
+                        (
+                            Ok(db),
+                            DbCommand::ListConversationTasksForMergedPr {
+                                project_slug,
+                                workspace_name,
+                                pr_number,
+                                reply,
+                            },
+                        ) => {
+                            let _ = reply.send(db.list_conversation_tasks_for_merged_pr(
+                                &project_slug,
+                                &workspace_name,
+                                pr_number,
+                            ));
+                        }
+                        (
+                            Ok(db),
+                            DbCommand::InsertContextItem {
+                                project_slug,
+                                workspace_name,
+                                attachment,
+                                created_at_unix_ms,
+                                reply,
+                            },
+                        ) => {
+                            let _ = reply.send(db.insert_context_item(
+                                &project_slug,
+                                &workspace_name,
+                                &attachment,
+                                created_at_unix_ms,
+                            ));
+                        }
+                        (
+                            Ok(db),
+                            DbCommand::ListContextItems {
+                                project_slug,
+                                workspace_name,
+                                reply,
+                            },
+                        ) => {
+                            let _ =
+                                reply.send(db.list_context_items(&project_slug, &workspace_name));
+                        }
+                        (
+                            Ok(db),
+                            DbCommand::DeleteContextItem {
+                                project_slug,
+                                workspace_name,
+                                context_id,
+                                reply,
+                            },
+                        ) => {
+                            let _ = reply.send(db.delete_context_item(
+                                &project_slug,
+                                &workspace_name,
+                                context_id,
+                            ));
+                        }
+                        (Ok(db), DbCommand::ListNewTaskDrafts { reply }) => {
+                            let _ = reply.send(db.list_new_task_drafts());
+                        }
+                        (
+                            Ok(db),
+                            DbCommand::CreateNewTaskDraft {
                                 text,
                                 project_id,
                                 workspace_id,
@@ -972,6 +1379,28 @@ impl SqliteStore {
         reply_rx.recv().context("sqlite worker terminated")?
     }
 
+    /// Rewrites the stored item entry with the same item id, keeping its entry id and position.
+    /// Returns `false` when no such entry exists.
+    pub fn update_conversation_item(
+        &self,
+        project_slug: String,
+        workspace_name: String,
+        thread_local_id: u64,
+        item: luban_domain::CodexThreadItem,
+    ) -> anyhow::Result<bool> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+        self.tx
+            .send(DbCommand::UpdateConversationItem {
+                project_slug,
+                workspace_name,
+                thread_local_id,
+                item: Box::new(item),
+                reply: reply_tx,
+            })
+            .context("sqlite worker is not running")?;
+        reply_rx.recv().context("sqlite worker terminated")?
+    }
+
     pub fn update_conversation_title_if_matches(
         &self,
         project_slug: String,
@@ -1034,6 +1463,90 @@ impl SqliteStore {
         reply_rx.recv().context("sqlite worker terminated")?
     }
 
+    /// Number of entries stored for the conversation, including those trimmed from memory.
+    pub fn count_conversation_entries(
+        &self,
+        project_slug: String,
+        workspace_name: String,
+        thread_local_id: u64,
+    ) -> anyhow::Result<u64> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+        self.tx
+            .send(DbCommand::CountConversationEntries {
+                project_slug,
+                workspace_name,
+                thread_local_id,
+                reply: reply_tx,
+            })
+            .context("sqlite worker is not running")?;
+        reply_rx.recv().context("sqlite worker terminated")?
+    }
+
+    /// Applies `policy` to one conversation; see `Db::prune_conversation_entries`.
+    pub fn prune_conversation_entries(
+        &self,
+        project_slug: String,
+        workspace_name: String,
+        thread_local_id: u64,
+        policy: ConversationRetentionPolicy,
+        now_unix_seconds: u64,
+    ) -> anyhow::Result<u64> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+        self.tx
+            .send(DbCommand::PruneConversationEntries {
+                project_slug,
+                workspace_name,
+                thread_local_id,
+                policy,
+                now_unix_seconds,
+                reply: reply_tx,
+            })
+            .context("sqlite worker is not running")?;
+        reply_rx.recv().context("sqlite worker terminated")?
+    }
+
+    pub fn load_prompt_history(
+        &self,
+        project_slug: String,
+        workspace_name: String,
+        thread_local_id: u64,
+        limit: usize,
+    ) -> anyhow::Result<Vec<String>> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+        self.tx
+            .send(DbCommand::LoadPromptHistory {
+                project_slug,
+                workspace_name,
+                thread_local_id,
+                limit,
+                reply: reply_tx,
+            })
+            .context("sqlite worker is not running")?;
+        reply_rx.recv().context("sqlite worker terminated")?
+    }
+
+    pub fn search_conversations(
+        &self,
+        project_slug: String,
+        workspace_name: String,
+        query: String,
+        thread_local_id: Option<u64>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<ConversationSearchMatch>> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+        self.tx
+            .send(DbCommand::SearchConversations {
+                project_slug,
+                workspace_name,
+                query,
+                thread_local_id,
+                limit,
+                reply: reply_tx,
+            })
+            .context("sqlite worker is not running")?;
+        reply_rx.recv().context("sqlite worker terminated")?
+    }
+
     pub fn delete_conversation_thread(
         &self,
         project_slug: String,
@@ -1052,6 +1565,31 @@ impl SqliteStore {
         reply_rx.recv().context("sqlite worker terminated")?
     }
 
+    /// Re-keys a thread's rows to `to_thread_local_id` in another workspace of the same project,
+    /// failing when that key is taken. On-disk context blobs are copied beforehand by
+    /// `GitWorkspaceService::move_conversation_thread`.
+    pub fn move_conversation_thread(
+        &self,
+        project_slug: String,
+        workspace_name: String,
+        thread_local_id: u64,
+        to_workspace_name: String,
+        to_thread_local_id: u64,
+    ) -> anyhow::Result<()> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+        self.tx
+            .send(DbCommand::MoveConversationThread {
+                project_slug,
+                workspace_name,
+                thread_local_id,
+                to_workspace_name,
+                to_thread_local_id,
+                reply: reply_tx,
+            })
+            .context("sqlite worker is not running")?;
+        reply_rx.recv().context("sqlite worker terminated")?
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub fn save_conversation_queue_state(
         &self,
@@ -1089,6 +1627,9 @@ impl SqliteStore {
         model_id: String,
         thinking_effort: ThinkingEffort,
         amp_mode: Option<String>,
+        claude_max_output_tokens: Option<u32>,
+        claude_system_prompt: Option<String>,
+        auto_commit: bool,
     ) -> anyhow::Result<()> {
         let (reply_tx, reply_rx) = mpsc::channel();
         self.tx
@@ -1100,6 +1641,9 @@ impl SqliteStore {
                 model_id,
                 thinking_effort,
                 amp_mode,
+                claude_max_output_tokens,
+                claude_system_prompt,
+                auto_commit,
                 reply: reply_tx,
             })
             .context("sqlite worker is not running")?;
@@ -1126,6 +1670,106 @@ impl SqliteStore {
         reply_rx.recv().context("sqlite worker terminated")?
     }
 
+    pub fn save_conversation_notes(
+        &self,
+        project_slug: String,
+        workspace_name: String,
+        thread_local_id: u64,
+        notes: String,
+    ) -> anyhow::Result<()> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+        self.tx
+            .send(DbCommand::SaveConversationNotes {
+                project_slug,
+                workspace_name,
+                thread_local_id,
+                notes,
+                reply: reply_tx,
+            })
+            .context("sqlite worker is not running")?;
+        reply_rx.recv().context("sqlite worker terminated")?
+    }
+
+    pub fn save_conversation_draft(
+        &self,
+        project_slug: String,
+        workspace_name: String,
+        thread_local_id: u64,
+        draft: String,
+    ) -> anyhow::Result<()> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+        self.tx
+            .send(DbCommand::SaveConversationDraft {
+                project_slug,
+                workspace_name,
+                thread_local_id,
+                draft,
+                reply: reply_tx,
+            })
+            .context("sqlite worker is not running")?;
+        reply_rx.recv().context("sqlite worker terminated")?
+    }
+
+    pub fn save_conversation_cwd_subpath(
+        &self,
+        project_slug: String,
+        workspace_name: String,
+        thread_local_id: u64,
+        cwd_subpath: Option<String>,
+    ) -> anyhow::Result<()> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+        self.tx
+            .send(DbCommand::SaveConversationCwdSubpath {
+                project_slug,
+                workspace_name,
+                thread_local_id,
+                cwd_subpath,
+                reply: reply_tx,
+            })
+            .context("sqlite worker is not running")?;
+        reply_rx.recv().context("sqlite worker terminated")?
+    }
+
+    pub fn save_conversation_hunk_comment(
+        &self,
+        project_slug: String,
+        workspace_name: String,
+        thread_local_id: u64,
+        comment: HunkComment,
+    ) -> anyhow::Result<()> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+        self.tx
+            .send(DbCommand::SaveConversationHunkComment {
+                project_slug,
+                workspace_name,
+                thread_local_id,
+                comment,
+                reply: reply_tx,
+            })
+            .context("sqlite worker is not running")?;
+        reply_rx.recv().context("sqlite worker terminated")?
+    }
+
+    pub fn save_conversation_collapsed_range(
+        &self,
+        project_slug: String,
+        workspace_name: String,
+        thread_local_id: u64,
+        range: CollapsedRange,
+    ) -> anyhow::Result<()> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+        self.tx
+            .send(DbCommand::SaveConversationCollapsedRange {
+                project_slug,
+                workspace_name,
+                thread_local_id,
+                range,
+                reply: reply_tx,
+            })
+            .context("sqlite worker is not running")?;
+        reply_rx.recv().context("sqlite worker terminated")?
+    }
+
     pub fn save_conversation_task_status_last_analyzed(
         &self,
         project_slug: String,
@@ -1356,6 +2000,9 @@ fn respond_db_open_error(err: &anyhow::Error, cmd: DbCommand) {
         DbCommand::ReplaceConversationEntries { reply, .. } => {
             let _ = reply.send(Err(anyhow!(message)));
         }
+        DbCommand::UpdateConversationItem { reply, .. } => {
+            let _ = reply.send(Err(anyhow!(message)));
+        }
         DbCommand::UpdateConversationTitleIfMatches { reply, .. } => {
             let _ = reply.send(Err(anyhow!(message)));
         }
@@ -1365,9 +2012,24 @@ fn respond_db_open_error(err: &anyhow::Error, cmd: DbCommand) {
         DbCommand::LoadConversationPage { reply, .. } => {
             let _ = reply.send(Err(anyhow!(message)));
         }
+        DbCommand::CountConversationEntries { reply, .. } => {
+            let _ = reply.send(Err(anyhow!(message)));
+        }
+        DbCommand::PruneConversationEntries { reply, .. } => {
+            let _ = reply.send(Err(anyhow!(message)));
+        }
+        DbCommand::LoadPromptHistory { reply, .. } => {
+            let _ = reply.send(Err(anyhow!(message)));
+        }
+        DbCommand::SearchConversations { reply, .. } => {
+            let _ = reply.send(Err(anyhow!(message)));
+        }
         DbCommand::DeleteConversationThread { reply, .. } => {
             let _ = reply.send(Err(anyhow!(message)));
         }
+        DbCommand::MoveConversationThread { reply, .. } => {
+            let _ = reply.send(Err(anyhow!(message)));
+        }
         DbCommand::SaveConversationQueueState { reply, .. } => {
             let _ = reply.send(Err(anyhow!(message)));
         }
@@ -1377,6 +2039,21 @@ fn respond_db_open_error(err: &anyhow::Error, cmd: DbCommand) {
         DbCommand::SaveConversationTaskStatus { reply, .. } => {
             let _ = reply.send(Err(anyhow!(message)));
         }
+        DbCommand::SaveConversationNotes { reply, .. } => {
+            let _ = reply.send(Err(anyhow!(message)));
+        }
+        DbCommand::SaveConversationDraft { reply, .. } => {
+            let _ = reply.send(Err(anyhow!(message)));
+        }
+        DbCommand::SaveConversationCwdSubpath { reply, .. } => {
+            let _ = reply.send(Err(anyhow!(message)));
+        }
+        DbCommand::SaveConversationHunkComment { reply, .. } => {
+            let _ = reply.send(Err(anyhow!(message)));
+        }
+        DbCommand::SaveConversationCollapsedRange { reply, .. } => {
+            let _ = reply.send(Err(anyhow!(message)));
+        }
         DbCommand::SaveConversationTaskStatusLastAnalyzed { reply, .. } => {
             let _ = reply.send(Err(anyhow!(message)));
         }
@@ -1447,9 +2124,34 @@ impl SqliteDatabase {
         let mut projects = Vec::new();
         {
             let mut stmt = self.conn.prepare(
-                "SELECT id, slug, name, path, expanded, is_git FROM projects ORDER BY id ASC",
+                "SELECT id, slug, name, path, expanded, is_git, system_prompt, worktree_root, status,
+                        agent_runner, agent_model_id, agent_thinking_effort, agent_amp_mode,
+                        saved_commands, branch_name_template, agent_env, pr_remote, pr_base_branch
+                 FROM projects ORDER BY id ASC",
             )?;
             let rows = stmt.query_map([], |row| {
+                let agent_defaults = luban_domain::ProjectAgentDefaults {
+                    runner: row
+                        .get::<_, Option<String>>(9)?
+                        .as_deref()
+                        .and_then(luban_domain::parse_agent_runner_kind),
+                    model_id: row.get::<_, Option<String>>(10)?,
+                    thinking_effort: row
+                        .get::<_, Option<String>>(11)?
+                        .as_deref()
+                        .and_then(luban_domain::parse_thinking_effort),
+                    amp_mode: row.get::<_, Option<String>>(12)?,
+                };
+                let saved_commands = row
+                    .get::<_, Option<String>>(13)?
+                    .and_then(|raw| {
+                        serde_json::from_str::<Vec<luban_domain::ProjectSavedCommand>>(&raw).ok()
+                    })
+                    .unwrap_or_default();
+                let agent_env = row
+                    .get::<_, Option<String>>(15)?
+                    .and_then(|raw| serde_json::from_str::<Vec<(String, String)>>(&raw).ok())
+                    .unwrap_or_default();
                 Ok((
                     row.get::<_, i64>(0)? as u64,
                     row.get::<_, String>(1)?,
@@ -1457,10 +2159,35 @@ impl SqliteDatabase {
                     row.get::<_, String>(3)?,
                     row.get::<_, i64>(4)?,
                     row.get::<_, i64>(5)?,
+                    row.get::<_, Option<String>>(6)?,
+                    row.get::<_, Option<String>>(7)?,
+                    row.get::<_, i64>(8)?,
+                    agent_defaults,
+                    saved_commands,
+                    row.get::<_, Option<String>>(14)?,
+                    agent_env,
+                    row.get::<_, Option<String>>(16)?,
+                    row.get::<_, Option<String>>(17)?,
                 ))
             })?;
             for row in rows {
-                let (id, slug, name, path, expanded, is_git) = row?;
+                let (
+                    id,
+                    slug,
+                    name,
+                    path,
+                    expanded,
+                    is_git,
+                    system_prompt,
+                    worktree_root,
+                    status,
+                    agent_defaults,
+                    saved_commands,
+                    branch_name_template,
+                    agent_env,
+                    pr_remote,
+                    pr_base_branch,
+                ) = row?;
                 projects.push(luban_domain::PersistedProject {
                     id,
                     slug,
@@ -1468,6 +2195,15 @@ impl SqliteDatabase {
                     path: PathBuf::from(path),
                     is_git: is_git != 0,
                     expanded: expanded != 0,
+                    system_prompt,
+                    worktree_root: worktree_root.map(PathBuf::from),
+                    branch_name_template,
+                    status: project_status_from_i64(status)?,
+                    agent_defaults,
+                    saved_commands,
+                    agent_env,
+                    pr_remote,
+                    pr_base_branch,
                     workspaces: Vec::new(),
                 });
             }
@@ -1530,6 +2266,42 @@ impl SqliteDatabase {
             .and_then(|json| serde_json::from_str(&json).ok())
             .unwrap_or_default();
 
+        let agent_runner_prompt_prefixes: HashMap<String, String> = self
+            .conn
+            .query_row(
+                "SELECT value FROM app_settings_text WHERE key = ?1",
+                params![AGENT_RUNNER_PROMPT_PREFIXES_KEY],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()
+            .context("failed to load agent runner prompt prefixes")?
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+
+        let agent_runner_prompt_suffixes: HashMap<String, String> = self
+            .conn
+            .query_row(
+                "SELECT value FROM app_settings_text WHERE key = ?1",
+                params![AGENT_RUNNER_PROMPT_SUFFIXES_KEY],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()
+            .context("failed to load agent runner prompt suffixes")?
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+
+        let model_thinking_effort_caps: HashMap<String, String> = self
+            .conn
+            .query_row(
+                "SELECT value FROM app_settings_text WHERE key = ?1",
+                params![MODEL_THINKING_EFFORT_CAPS_KEY],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()
+            .context("failed to load model thinking effort caps")?
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+
         let agent_default_thinking_effort = self
             .conn
             .query_row(
@@ -1604,6 +2376,72 @@ impl SqliteDatabase {
             .context("failed to load agent droid enabled flag")?
             .map(|value| value != 0);
 
+        let agent_merge_reasoning_entries = self
+            .conn
+            .query_row(
+                "SELECT value FROM app_settings WHERE key = ?1",
+                params![AGENT_MERGE_REASONING_ENTRIES_KEY],
+                |row| row.get::<_, i64>(0),
+            )
+            .optional()
+            .context("failed to load agent merge reasoning entries flag")?
+            .map(|value| value != 0);
+
+        let agent_pause_queue_on_patch_failure = self
+            .conn
+            .query_row(
+                "SELECT value FROM app_settings WHERE key = ?1",
+                params![AGENT_PAUSE_QUEUE_ON_PATCH_FAILURE_KEY],
+                |row| row.get::<_, i64>(0),
+            )
+            .optional()
+            .context("failed to load agent pause queue on patch failure flag")?
+            .map(|value| value != 0);
+
+        let agent_item_dedup_window = self
+            .conn
+            .query_row(
+                "SELECT value FROM app_settings WHERE key = ?1",
+                params![AGENT_ITEM_DEDUP_WINDOW_KEY],
+                |row| row.get::<_, i64>(0),
+            )
+            .optional()
+            .context("failed to load agent item dedup window")?
+            .and_then(|value| u64::try_from(value).ok());
+
+        let agent_auto_retry_on_failure = self
+            .conn
+            .query_row(
+                "SELECT value FROM app_settings WHERE key = ?1",
+                params![AGENT_AUTO_RETRY_ON_FAILURE_KEY],
+                |row| row.get::<_, i64>(0),
+            )
+            .optional()
+            .context("failed to load agent auto retry on failure count")?
+            .and_then(|value| u64::try_from(value).ok());
+
+        let agent_command_output_max_bytes = self
+            .conn
+            .query_row(
+                "SELECT value FROM app_settings WHERE key = ?1",
+                params![AGENT_COMMAND_OUTPUT_MAX_BYTES_KEY],
+                |row| row.get::<_, i64>(0),
+            )
+            .optional()
+            .context("failed to load agent command output max bytes")?
+            .and_then(|value| u64::try_from(value).ok());
+
+        let auto_archive_after_days = self
+            .conn
+            .query_row(
+                "SELECT value FROM app_settings WHERE key = ?1",
+                params![AUTO_ARCHIVE_AFTER_DAYS_KEY],
+                |row| row.get::<_, i64>(0),
+            )
+            .optional()
+            .context("failed to load auto archive after days")?
+            .and_then(|value| u64::try_from(value).ok());
+
         let telegram_enabled = self
             .conn
             .query_row(
@@ -1635,15 +2473,30 @@ impl SqliteDatabase {
             .optional()
             .context("failed to load telegram bot username")?;
 
-        let telegram_paired_chat_id = self
+        let telegram_paired_chat_ids = self
             .conn
             .query_row(
-                "SELECT value FROM app_settings WHERE key = ?1",
-                params![TELEGRAM_PAIRED_CHAT_ID_KEY],
-                |row| row.get::<_, i64>(0),
+                "SELECT value FROM app_settings_text WHERE key = ?1",
+                params![TELEGRAM_PAIRED_CHAT_IDS_KEY],
+                |row| row.get::<_, String>(0),
             )
             .optional()
-            .context("failed to load telegram paired chat id")?;
+            .context("failed to load telegram paired chat ids")?
+            .and_then(|raw| serde_json::from_str::<Vec<i64>>(&raw).ok());
+        let telegram_paired_chat_ids = match telegram_paired_chat_ids {
+            Some(ids) => ids,
+            None => self
+                .conn
+                .query_row(
+                    "SELECT value FROM app_settings WHERE key = ?1",
+                    params![TELEGRAM_PAIRED_CHAT_ID_KEY],
+                    |row| row.get::<_, i64>(0),
+                )
+                .optional()
+                .context("failed to load telegram paired chat id")?
+                .into_iter()
+                .collect(),
+        };
 
         let telegram_topic_bindings = self
             .conn
@@ -1655,6 +2508,26 @@ impl SqliteDatabase {
             .optional()
             .context("failed to load telegram topic bindings")?;
 
+        let webhook_url = self
+            .conn
+            .query_row(
+                "SELECT value FROM app_settings_text WHERE key = ?1",
+                params![WEBHOOK_URL_KEY],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()
+            .context("failed to load webhook url")?;
+
+        let webhook_secret = self
+            .conn
+            .query_row(
+                "SELECT value FROM app_settings_text WHERE key = ?1",
+                params![WEBHOOK_SECRET_KEY],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()
+            .context("failed to load webhook secret")?;
+
         let mut task_prompt_templates = HashMap::new();
         let mut stmt = self.conn.prepare(
             "SELECT key, value FROM app_settings_text WHERE key LIKE 'task_prompt_template_%'",
@@ -1724,6 +2597,9 @@ impl SqliteDatabase {
                 appearance_terminal_font: None,
                 agent_default_model_id,
                 agent_runner_default_models,
+                agent_runner_prompt_prefixes,
+                agent_runner_prompt_suffixes,
+                model_thinking_effort_caps,
                 agent_default_thinking_effort,
                 agent_default_runner,
                 agent_amp_mode,
@@ -1734,6 +2610,7 @@ impl SqliteDatabase {
                 last_open_workspace_id: None,
                 open_button_selection: None,
                 sidebar_project_order: Vec::new(),
+                project_groups: Vec::new(),
                 workspace_active_thread_id: HashMap::new(),
                 workspace_open_tabs: HashMap::new(),
                 workspace_archived_tabs: HashMap::new(),
@@ -1747,8 +2624,16 @@ impl SqliteDatabase {
                 telegram_enabled,
                 telegram_bot_token,
                 telegram_bot_username,
-                telegram_paired_chat_id,
+                telegram_paired_chat_ids,
                 telegram_topic_bindings,
+                webhook_url,
+                webhook_secret,
+                agent_merge_reasoning_entries,
+                agent_pause_queue_on_patch_failure,
+                agent_item_dedup_window,
+                agent_auto_retry_on_failure,
+                agent_command_output_max_bytes,
+                auto_archive_after_days,
             });
         }
 
@@ -1868,6 +2753,18 @@ impl SqliteDatabase {
             .and_then(|raw| serde_json::from_str::<Vec<String>>(&raw).ok())
             .unwrap_or_default();
 
+        let project_groups = self
+            .conn
+            .query_row(
+                "SELECT value FROM app_settings_text WHERE key = ?1",
+                params![PROJECT_GROUPS_KEY],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()
+            .context("failed to load project groups")?
+            .and_then(|raw| serde_json::from_str::<Vec<PersistedProjectGroup>>(&raw).ok())
+            .unwrap_or_default();
+
         let mut workspace_active_thread_id = HashMap::new();
         let mut stmt = self.conn.prepare(
             "SELECT key, value FROM app_settings WHERE key LIKE 'workspace_active_thread_id_%'",
@@ -2139,6 +3036,9 @@ impl SqliteDatabase {
             appearance_terminal_font,
             agent_default_model_id,
             agent_runner_default_models,
+            agent_runner_prompt_prefixes,
+            agent_runner_prompt_suffixes,
+            model_thinking_effort_caps,
             agent_default_thinking_effort,
             agent_default_runner,
             agent_amp_mode,
@@ -2149,6 +3049,7 @@ impl SqliteDatabase {
             last_open_workspace_id,
             open_button_selection,
             sidebar_project_order,
+            project_groups,
             workspace_active_thread_id,
             workspace_open_tabs,
             workspace_archived_tabs,
@@ -2162,8 +3063,16 @@ impl SqliteDatabase {
             telegram_enabled,
             telegram_bot_token,
             telegram_bot_username,
-            telegram_paired_chat_id,
+            telegram_paired_chat_ids,
             telegram_topic_bindings,
+            webhook_url,
+            webhook_secret,
+            agent_merge_reasoning_entries,
+            agent_pause_queue_on_patch_failure,
+            agent_item_dedup_window,
+            agent_auto_retry_on_failure,
+            agent_command_output_max_bytes,
+            auto_archive_after_days,
         })
     }
 
@@ -2223,15 +3132,35 @@ impl SqliteDatabase {
 
         for project in &snapshot.projects {
             let path = project.path.to_string_lossy().into_owned();
+            let saved_commands = (!project.saved_commands.is_empty())
+                .then(|| serde_json::to_string(&project.saved_commands).ok())
+                .flatten();
+            let agent_env = (!project.agent_env.is_empty())
+                .then(|| serde_json::to_string(&project.agent_env).ok())
+                .flatten();
             tx.execute(
-                "INSERT INTO projects (id, slug, name, path, expanded, is_git, created_at, updated_at)
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, COALESCE((SELECT created_at FROM projects WHERE id = ?1), ?7), ?7)
+                "INSERT INTO projects (id, slug, name, path, expanded, is_git, system_prompt, worktree_root, status,
+                                       agent_runner, agent_model_id, agent_thinking_effort, agent_amp_mode, saved_commands,
+                                       branch_name_template, agent_env, pr_remote, pr_base_branch, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, COALESCE((SELECT created_at FROM projects WHERE id = ?1), ?7), ?7)
                  ON CONFLICT(id) DO UPDATE SET
                    slug = excluded.slug,
                    name = excluded.name,
                    path = excluded.path,
                    expanded = excluded.expanded,
                    is_git = excluded.is_git,
+                   system_prompt = excluded.system_prompt,
+                   worktree_root = excluded.worktree_root,
+                   status = excluded.status,
+                   agent_runner = excluded.agent_runner,
+                   agent_model_id = excluded.agent_model_id,
+                   agent_thinking_effort = excluded.agent_thinking_effort,
+                   agent_amp_mode = excluded.agent_amp_mode,
+                   saved_commands = excluded.saved_commands,
+                   branch_name_template = excluded.branch_name_template,
+                   agent_env = excluded.agent_env,
+                   pr_remote = excluded.pr_remote,
+                   pr_base_branch = excluded.pr_base_branch,
                    updated_at = excluded.updated_at",
                 params![
                     project.id as i64,
@@ -2241,6 +3170,24 @@ impl SqliteDatabase {
                     if project.expanded { 1i64 } else { 0i64 },
                     if project.is_git { 1i64 } else { 0i64 },
                     now,
+                    project.system_prompt,
+                    project
+                        .worktree_root
+                        .as_ref()
+                        .map(|root| root.to_string_lossy().into_owned()),
+                    project_status_to_i64(project.status),
+                    project.agent_defaults.runner.map(|runner| runner.as_str()),
+                    project.agent_defaults.model_id,
+                    project
+                        .agent_defaults
+                        .thinking_effort
+                        .map(|effort| effort.as_str()),
+                    project.agent_defaults.amp_mode,
+                    saved_commands,
+                    project.branch_name_template,
+                    agent_env,
+                    project.pr_remote,
+                    project.pr_base_branch,
                 ],
             )?;
         }
@@ -2431,6 +3378,10 @@ impl SqliteDatabase {
                 SIDEBAR_PROJECT_ORDER_KEY,
                 sidebar_project_order.as_deref(),
             )?;
+            let project_groups = (!snapshot.project_groups.is_empty())
+                .then(|| serde_json::to_string(&snapshot.project_groups).ok())
+                .flatten();
+            upsert_text(&tx, PROJECT_GROUPS_KEY, project_groups.as_deref())?;
 
             if let Some(value) = snapshot.global_zoom_percent {
                 tx.execute(
@@ -2483,6 +3434,35 @@ impl SqliteDatabase {
             )?;
         }
 
+        for (key, affixes) in [
+            (
+                AGENT_RUNNER_PROMPT_PREFIXES_KEY,
+                &snapshot.agent_runner_prompt_prefixes,
+            ),
+            (
+                AGENT_RUNNER_PROMPT_SUFFIXES_KEY,
+                &snapshot.agent_runner_prompt_suffixes,
+            ),
+            (
+                MODEL_THINKING_EFFORT_CAPS_KEY,
+                &snapshot.model_thinking_effort_caps,
+            ),
+        ] {
+            if !affixes.is_empty() {
+                let json = serde_json::to_string(affixes).unwrap_or_default();
+                tx.execute(
+                    "INSERT INTO app_settings_text (key, value, created_at, updated_at)
+                     VALUES (?1, ?2, COALESCE((SELECT created_at FROM app_settings_text WHERE key = ?1), ?3), ?3)
+                     ON CONFLICT(key) DO UPDATE SET
+                       value = excluded.value,
+                       updated_at = excluded.updated_at",
+                    params![key, json, now],
+                )?;
+            } else {
+                tx.execute("DELETE FROM app_settings_text WHERE key = ?1", params![key])?;
+            }
+        }
+
         if let Some(value) = snapshot.agent_default_thinking_effort.as_deref() {
             tx.execute(
                 "INSERT INTO app_settings_text (key, value, created_at, updated_at)
@@ -2611,7 +3591,7 @@ impl SqliteDatabase {
             )?;
         }
 
-        if let Some(enabled) = snapshot.telegram_enabled {
+        if let Some(enabled) = snapshot.agent_merge_reasoning_entries {
             tx.execute(
                 "INSERT INTO app_settings (key, value, created_at, updated_at)
                  VALUES (?1, ?2, COALESCE((SELECT created_at FROM app_settings WHERE key = ?1), ?3), ?3)
@@ -2619,7 +3599,7 @@ impl SqliteDatabase {
                    value = excluded.value,
                    updated_at = excluded.updated_at",
                 params![
-                    TELEGRAM_ENABLED_KEY,
+                    AGENT_MERGE_REASONING_ENTRIES_KEY,
                     if enabled { 1i64 } else { 0i64 },
                     now
                 ],
@@ -2627,127 +3607,270 @@ impl SqliteDatabase {
         } else {
             tx.execute(
                 "DELETE FROM app_settings WHERE key = ?1",
-                params![TELEGRAM_ENABLED_KEY],
+                params![AGENT_MERGE_REASONING_ENTRIES_KEY],
             )?;
         }
 
-        if let Some(value) = snapshot.telegram_bot_token.as_deref() {
+        if let Some(enabled) = snapshot.agent_pause_queue_on_patch_failure {
             tx.execute(
-                "INSERT INTO app_settings_text (key, value, created_at, updated_at)
-                 VALUES (?1, ?2, COALESCE((SELECT created_at FROM app_settings_text WHERE key = ?1), ?3), ?3)
+                "INSERT INTO app_settings (key, value, created_at, updated_at)
+                 VALUES (?1, ?2, COALESCE((SELECT created_at FROM app_settings WHERE key = ?1), ?3), ?3)
                  ON CONFLICT(key) DO UPDATE SET
                    value = excluded.value,
                    updated_at = excluded.updated_at",
-                params![TELEGRAM_BOT_TOKEN_KEY, value, now],
+                params![
+                    AGENT_PAUSE_QUEUE_ON_PATCH_FAILURE_KEY,
+                    if enabled { 1i64 } else { 0i64 },
+                    now
+                ],
             )?;
         } else {
             tx.execute(
-                "DELETE FROM app_settings_text WHERE key = ?1",
-                params![TELEGRAM_BOT_TOKEN_KEY],
+                "DELETE FROM app_settings WHERE key = ?1",
+                params![AGENT_PAUSE_QUEUE_ON_PATCH_FAILURE_KEY],
             )?;
         }
 
-        if let Some(value) = snapshot.telegram_bot_username.as_deref() {
+        if let Some(window) = snapshot.agent_item_dedup_window {
             tx.execute(
-                "INSERT INTO app_settings_text (key, value, created_at, updated_at)
-                 VALUES (?1, ?2, COALESCE((SELECT created_at FROM app_settings_text WHERE key = ?1), ?3), ?3)
+                "INSERT INTO app_settings (key, value, created_at, updated_at)
+                 VALUES (?1, ?2, COALESCE((SELECT created_at FROM app_settings WHERE key = ?1), ?3), ?3)
                  ON CONFLICT(key) DO UPDATE SET
                    value = excluded.value,
                    updated_at = excluded.updated_at",
-                params![TELEGRAM_BOT_USERNAME_KEY, value, now],
+                params![
+                    AGENT_ITEM_DEDUP_WINDOW_KEY,
+                    i64::try_from(window).unwrap_or(i64::MAX),
+                    now
+                ],
             )?;
         } else {
             tx.execute(
-                "DELETE FROM app_settings_text WHERE key = ?1",
-                params![TELEGRAM_BOT_USERNAME_KEY],
+                "DELETE FROM app_settings WHERE key = ?1",
+                params![AGENT_ITEM_DEDUP_WINDOW_KEY],
             )?;
         }
 
-        if let Some(chat_id) = snapshot.telegram_paired_chat_id {
+        if let Some(retries) = snapshot.agent_auto_retry_on_failure {
             tx.execute(
                 "INSERT INTO app_settings (key, value, created_at, updated_at)
                  VALUES (?1, ?2, COALESCE((SELECT created_at FROM app_settings WHERE key = ?1), ?3), ?3)
                  ON CONFLICT(key) DO UPDATE SET
                    value = excluded.value,
                    updated_at = excluded.updated_at",
-                params![TELEGRAM_PAIRED_CHAT_ID_KEY, chat_id, now],
+                params![
+                    AGENT_AUTO_RETRY_ON_FAILURE_KEY,
+                    i64::try_from(retries).unwrap_or(i64::MAX),
+                    now
+                ],
             )?;
         } else {
             tx.execute(
                 "DELETE FROM app_settings WHERE key = ?1",
-                params![TELEGRAM_PAIRED_CHAT_ID_KEY],
+                params![AGENT_AUTO_RETRY_ON_FAILURE_KEY],
             )?;
         }
 
-        if let Some(value) = snapshot.telegram_topic_bindings.as_deref() {
+        if let Some(max_bytes) = snapshot.agent_command_output_max_bytes {
             tx.execute(
-                "INSERT INTO app_settings_text (key, value, created_at, updated_at)
-                 VALUES (?1, ?2, COALESCE((SELECT created_at FROM app_settings_text WHERE key = ?1), ?3), ?3)
+                "INSERT INTO app_settings (key, value, created_at, updated_at)
+                 VALUES (?1, ?2, COALESCE((SELECT created_at FROM app_settings WHERE key = ?1), ?3), ?3)
                  ON CONFLICT(key) DO UPDATE SET
                    value = excluded.value,
                    updated_at = excluded.updated_at",
-                params![TELEGRAM_TOPIC_BINDINGS_KEY, value, now],
+                params![
+                    AGENT_COMMAND_OUTPUT_MAX_BYTES_KEY,
+                    i64::try_from(max_bytes).unwrap_or(i64::MAX),
+                    now
+                ],
             )?;
         } else {
             tx.execute(
-                "DELETE FROM app_settings_text WHERE key = ?1",
-                params![TELEGRAM_TOPIC_BINDINGS_KEY],
+                "DELETE FROM app_settings WHERE key = ?1",
+                params![AGENT_COMMAND_OUTPUT_MAX_BYTES_KEY],
             )?;
         }
 
-        tx.execute(
-            "DELETE FROM app_settings_text WHERE key LIKE 'task_prompt_template_%'",
-            [],
-        )?;
-        for (kind, template) in &snapshot.task_prompt_templates {
-            let key = format!("{TASK_PROMPT_TEMPLATE_PREFIX}{kind}");
-            let trimmed = template.trim();
-            if trimmed.is_empty() {
-                continue;
-            }
+        if let Some(days) = snapshot.auto_archive_after_days {
             tx.execute(
-                "INSERT INTO app_settings_text (key, value, created_at, updated_at)
-                 VALUES (?1, ?2, COALESCE((SELECT created_at FROM app_settings_text WHERE key = ?1), ?3), ?3)
+                "INSERT INTO app_settings (key, value, created_at, updated_at)
+                 VALUES (?1, ?2, COALESCE((SELECT created_at FROM app_settings WHERE key = ?1), ?3), ?3)
                  ON CONFLICT(key) DO UPDATE SET
                    value = excluded.value,
                    updated_at = excluded.updated_at",
-                params![key, trimmed, now],
+                params![
+                    AUTO_ARCHIVE_AFTER_DAYS_KEY,
+                    i64::try_from(days).unwrap_or(i64::MAX),
+                    now
+                ],
+            )?;
+        } else {
+            tx.execute(
+                "DELETE FROM app_settings WHERE key = ?1",
+                params![AUTO_ARCHIVE_AFTER_DAYS_KEY],
             )?;
         }
 
-        tx.execute(
-            "DELETE FROM app_settings_text WHERE key LIKE 'workspace_thread_run_config_%'",
-            [],
-        )?;
-        for ((workspace_id, thread_id), run_config) in
-            &snapshot.workspace_thread_run_config_overrides
-        {
-            let key = format!("{WORKSPACE_THREAD_RUN_CONFIG_PREFIX}{workspace_id}_{thread_id}");
-            let model_id = run_config.model_id.trim();
-            let effort = run_config.thinking_effort.trim();
-            if model_id.is_empty() || effort.is_empty() {
-                continue;
-            }
-            let value = serde_json::to_string(run_config).unwrap_or_default();
-            if value.trim().is_empty() {
-                continue;
-            }
+        if let Some(enabled) = snapshot.telegram_enabled {
             tx.execute(
-                "INSERT INTO app_settings_text (key, value, created_at, updated_at)
-                 VALUES (?1, ?2, COALESCE((SELECT created_at FROM app_settings_text WHERE key = ?1), ?3), ?3)
+                "INSERT INTO app_settings (key, value, created_at, updated_at)
+                 VALUES (?1, ?2, COALESCE((SELECT created_at FROM app_settings WHERE key = ?1), ?3), ?3)
                  ON CONFLICT(key) DO UPDATE SET
                    value = excluded.value,
                    updated_at = excluded.updated_at",
-                params![key, value, now],
+                params![
+                    TELEGRAM_ENABLED_KEY,
+                    if enabled { 1i64 } else { 0i64 },
+                    now
+                ],
+            )?;
+        } else {
+            tx.execute(
+                "DELETE FROM app_settings WHERE key = ?1",
+                params![TELEGRAM_ENABLED_KEY],
             )?;
         }
 
-        if self.persist_ui_state {
-            if let Some(value) = snapshot.last_open_workspace_id {
-                tx.execute(
-                    "INSERT INTO app_settings (key, value, created_at, updated_at)
-                     VALUES (?1, ?2, COALESCE((SELECT created_at FROM app_settings WHERE key = ?1), ?3), ?3)
-                     ON CONFLICT(key) DO UPDATE SET
+        if let Some(value) = snapshot.telegram_bot_token.as_deref() {
+            tx.execute(
+                "INSERT INTO app_settings_text (key, value, created_at, updated_at)
+                 VALUES (?1, ?2, COALESCE((SELECT created_at FROM app_settings_text WHERE key = ?1), ?3), ?3)
+                 ON CONFLICT(key) DO UPDATE SET
+                   value = excluded.value,
+                   updated_at = excluded.updated_at",
+                params![TELEGRAM_BOT_TOKEN_KEY, value, now],
+            )?;
+        } else {
+            tx.execute(
+                "DELETE FROM app_settings_text WHERE key = ?1",
+                params![TELEGRAM_BOT_TOKEN_KEY],
+            )?;
+        }
+
+        if let Some(value) = snapshot.telegram_bot_username.as_deref() {
+            tx.execute(
+                "INSERT INTO app_settings_text (key, value, created_at, updated_at)
+                 VALUES (?1, ?2, COALESCE((SELECT created_at FROM app_settings_text WHERE key = ?1), ?3), ?3)
+                 ON CONFLICT(key) DO UPDATE SET
+                   value = excluded.value,
+                   updated_at = excluded.updated_at",
+                params![TELEGRAM_BOT_USERNAME_KEY, value, now],
+            )?;
+        } else {
+            tx.execute(
+                "DELETE FROM app_settings_text WHERE key = ?1",
+                params![TELEGRAM_BOT_USERNAME_KEY],
+            )?;
+        }
+
+        if snapshot.telegram_paired_chat_ids.is_empty() {
+            tx.execute(
+                "DELETE FROM app_settings_text WHERE key = ?1",
+                params![TELEGRAM_PAIRED_CHAT_IDS_KEY],
+            )?;
+        } else {
+            let value = serde_json::to_string(&snapshot.telegram_paired_chat_ids)?;
+            tx.execute(
+                "INSERT INTO app_settings_text (key, value, created_at, updated_at)
+                 VALUES (?1, ?2, COALESCE((SELECT created_at FROM app_settings_text WHERE key = ?1), ?3), ?3)
+                 ON CONFLICT(key) DO UPDATE SET
+                   value = excluded.value,
+                   updated_at = excluded.updated_at",
+                params![TELEGRAM_PAIRED_CHAT_IDS_KEY, value, now],
+            )?;
+        }
+        tx.execute(
+            "DELETE FROM app_settings WHERE key = ?1",
+            params![TELEGRAM_PAIRED_CHAT_ID_KEY],
+        )?;
+
+        if let Some(value) = snapshot.telegram_topic_bindings.as_deref() {
+            tx.execute(
+                "INSERT INTO app_settings_text (key, value, created_at, updated_at)
+                 VALUES (?1, ?2, COALESCE((SELECT created_at FROM app_settings_text WHERE key = ?1), ?3), ?3)
+                 ON CONFLICT(key) DO UPDATE SET
+                   value = excluded.value,
+                   updated_at = excluded.updated_at",
+                params![TELEGRAM_TOPIC_BINDINGS_KEY, value, now],
+            )?;
+        } else {
+            tx.execute(
+                "DELETE FROM app_settings_text WHERE key = ?1",
+                params![TELEGRAM_TOPIC_BINDINGS_KEY],
+            )?;
+        }
+
+        for (key, value) in [
+            (WEBHOOK_URL_KEY, snapshot.webhook_url.as_deref()),
+            (WEBHOOK_SECRET_KEY, snapshot.webhook_secret.as_deref()),
+        ] {
+            if let Some(value) = value {
+                tx.execute(
+                    "INSERT INTO app_settings_text (key, value, created_at, updated_at)
+                     VALUES (?1, ?2, COALESCE((SELECT created_at FROM app_settings_text WHERE key = ?1), ?3), ?3)
+                     ON CONFLICT(key) DO UPDATE SET
+                       value = excluded.value,
+                       updated_at = excluded.updated_at",
+                    params![key, value, now],
+                )?;
+            } else {
+                tx.execute("DELETE FROM app_settings_text WHERE key = ?1", params![key])?;
+            }
+        }
+
+        tx.execute(
+            "DELETE FROM app_settings_text WHERE key LIKE 'task_prompt_template_%'",
+            [],
+        )?;
+        for (kind, template) in &snapshot.task_prompt_templates {
+            let key = format!("{TASK_PROMPT_TEMPLATE_PREFIX}{kind}");
+            let trimmed = template.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+            tx.execute(
+                "INSERT INTO app_settings_text (key, value, created_at, updated_at)
+                 VALUES (?1, ?2, COALESCE((SELECT created_at FROM app_settings_text WHERE key = ?1), ?3), ?3)
+                 ON CONFLICT(key) DO UPDATE SET
+                   value = excluded.value,
+                   updated_at = excluded.updated_at",
+                params![key, trimmed, now],
+            )?;
+        }
+
+        tx.execute(
+            "DELETE FROM app_settings_text WHERE key LIKE 'workspace_thread_run_config_%'",
+            [],
+        )?;
+        for ((workspace_id, thread_id), run_config) in
+            &snapshot.workspace_thread_run_config_overrides
+        {
+            let key = format!("{WORKSPACE_THREAD_RUN_CONFIG_PREFIX}{workspace_id}_{thread_id}");
+            let model_id = run_config.model_id.trim();
+            let effort = run_config.thinking_effort.trim();
+            if model_id.is_empty() || effort.is_empty() {
+                continue;
+            }
+            let value = serde_json::to_string(run_config).unwrap_or_default();
+            if value.trim().is_empty() {
+                continue;
+            }
+            tx.execute(
+                "INSERT INTO app_settings_text (key, value, created_at, updated_at)
+                 VALUES (?1, ?2, COALESCE((SELECT created_at FROM app_settings_text WHERE key = ?1), ?3), ?3)
+                 ON CONFLICT(key) DO UPDATE SET
+                   value = excluded.value,
+                   updated_at = excluded.updated_at",
+                params![key, value, now],
+            )?;
+        }
+
+        if self.persist_ui_state {
+            if let Some(value) = snapshot.last_open_workspace_id {
+                tx.execute(
+                    "INSERT INTO app_settings (key, value, created_at, updated_at)
+                     VALUES (?1, ?2, COALESCE((SELECT created_at FROM app_settings WHERE key = ?1), ?3), ?3)
+                     ON CONFLICT(key) DO UPDATE SET
                        value = excluded.value,
                        updated_at = excluded.updated_at",
                     params![LAST_OPEN_WORKSPACE_ID_KEY, value as i64, now],
@@ -3042,7 +4165,16 @@ impl SqliteDatabase {
                        AND e.thread_local_id = c.thread_local_id
                        AND e.kind IN ('turn_error', 'turn_canceled', 'turn_duration')
                      ORDER BY e.seq DESC
-                     LIMIT 1) AS last_turn_kind
+                     LIMIT 1) AS last_turn_kind,
+                    (SELECT COALESCE(SUM(
+                         COALESCE(json_extract(u.payload_json, '$.event.usage.input_tokens'), 0)
+                         + COALESCE(json_extract(u.payload_json, '$.event.usage.output_tokens'), 0)
+                     ), 0)
+                     FROM conversation_entries u
+                     WHERE u.project_slug = c.project_slug
+                       AND u.workspace_name = c.workspace_name
+                       AND u.thread_local_id = c.thread_local_id
+                       AND u.kind = 'turn_usage') AS total_tokens
              FROM conversations c
              WHERE c.project_slug = ?1 AND c.workspace_name = ?2
              ORDER BY c.updated_at DESC, c.thread_local_id DESC",
@@ -3062,6 +4194,7 @@ impl SqliteDatabase {
                 row.get::<_, Option<i64>>(10)?,
                 row.get::<_, i64>(11)?,
                 row.get::<_, Option<String>>(12)?,
+                row.get::<_, i64>(13)?,
             ))
         })?;
 
@@ -3081,6 +4214,7 @@ impl SqliteDatabase {
                 run_finished_at_unix_ms,
                 pending_prompt_count,
                 last_turn_kind,
+                total_tokens,
             ) = row?;
             let Some(thread_local_id) = u64::try_from(thread_local_id).ok() else {
                 continue;
@@ -3128,6 +4262,7 @@ impl SqliteDatabase {
                 task_status_last_analyzed_message_seq,
                 turn_status,
                 last_turn_result,
+                total_tokens: u64::try_from(total_tokens).unwrap_or_default(),
             });
         }
 
@@ -3213,6 +4348,8 @@ impl SqliteDatabase {
             params![project_slug, workspace_name, thread_local_id as i64],
             |row| row.get(0),
         )?;
+        let entry_id_offset =
+            pruned_entry_id_offset(&tx, project_slug, workspace_name, thread_local_id)?;
         {
             let mut stmt = tx.prepare(
                 "INSERT OR IGNORE INTO conversation_entries
@@ -3222,7 +4359,7 @@ impl SqliteDatabase {
             for entry in entries {
                 let (kind, codex_item_id, entry_id) = conversation_entry_index_fields(entry);
                 let entry_id = if entry_id.is_empty() {
-                    format!("e_{next_seq}")
+                    format!("e_{}", next_seq + entry_id_offset)
                 } else {
                     entry_id.to_owned()
                 };
@@ -3346,6 +4483,51 @@ impl SqliteDatabase {
         Ok(())
     }
 
+    fn update_conversation_item(
+        &mut self,
+        project_slug: &str,
+        workspace_name: &str,
+        thread_local_id: u64,
+        item: &luban_domain::CodexThreadItem,
+    ) -> anyhow::Result<bool> {
+        let row: Option<(i64, String)> = self
+            .conn
+            .query_row(
+                "SELECT rowid, payload_json FROM conversation_entries
+                 WHERE project_slug = ?1 AND workspace_name = ?2 AND thread_local_id = ?3
+                   AND kind = 'codex_item' AND codex_item_id = ?4
+                 ORDER BY seq DESC
+                 LIMIT 1",
+                params![
+                    project_slug,
+                    workspace_name,
+                    thread_local_id as i64,
+                    codex_item_id(item)
+                ],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        let Some((rowid, payload_json)) = row else {
+            return Ok(false);
+        };
+        let mut entry: ConversationEntry =
+            serde_json::from_str(&payload_json).context("failed to parse stored entry")?;
+        let ConversationEntry::AgentEvent {
+            event: luban_domain::AgentEvent::Item { item: stored },
+            ..
+        } = &mut entry
+        else {
+            return Ok(false);
+        };
+        **stored = item.clone();
+        let payload_json = serde_json::to_string(&entry).context("failed to serialize entry")?;
+        self.conn.execute(
+            "UPDATE conversation_entries SET payload_json = ?1 WHERE rowid = ?2",
+            params![payload_json, rowid],
+        )?;
+        Ok(true)
+    }
+
     fn update_conversation_title_if_matches(
         &mut self,
         project_slug: &str,
@@ -3386,7 +4568,7 @@ impl SqliteDatabase {
         let row = self
             .conn
             .query_row(
-                "SELECT title, thread_id, task_status, queue_paused, run_started_at_unix_ms, run_finished_at_unix_ms, agent_runner, agent_model_id, thinking_effort, amp_mode FROM conversations
+                "SELECT title, thread_id, task_status, queue_paused, run_started_at_unix_ms, run_finished_at_unix_ms, agent_runner, agent_model_id, thinking_effort, amp_mode, notes, cwd_subpath, draft, claude_max_output_tokens, claude_system_prompt, auto_commit FROM conversations
                  WHERE project_slug = ?1 AND workspace_name = ?2 AND thread_local_id = ?3",
                 params![project_slug, workspace_name, thread_local_id as i64],
                 |row| {
@@ -3401,6 +4583,12 @@ impl SqliteDatabase {
                         row.get::<_, Option<String>>(7)?,
                         row.get::<_, Option<String>>(8)?,
                         row.get::<_, Option<String>>(9)?,
+                        row.get::<_, String>(10)?,
+                        row.get::<_, Option<String>>(11)?,
+                        row.get::<_, String>(12)?,
+                        row.get::<_, Option<i64>>(13)?,
+                        row.get::<_, Option<String>>(14)?,
+                        row.get::<_, i64>(15)? != 0,
                     ))
                 },
             )
@@ -3417,6 +4605,12 @@ impl SqliteDatabase {
             model_id,
             thinking_effort,
             amp_mode,
+            notes,
+            cwd_subpath,
+            draft,
+            claude_max_output_tokens,
+            claude_system_prompt,
+            auto_commit,
         )) = row
         else {
             return Err(SqliteStoreError::ConversationNotFound.into());
@@ -3478,6 +4672,12 @@ impl SqliteDatabase {
             pending_prompts.push(prompt);
         }
 
+        let hunk_comments =
+            self.load_hunk_comments(project_slug, workspace_name, thread_local_id)?;
+        let collapsed_ranges =
+            self.load_collapsed_ranges(project_slug, workspace_name, thread_local_id)?;
+        let total_usage = self.load_total_usage(project_slug, workspace_name, thread_local_id)?;
+
         let entries_total = entries.len() as u64;
         Ok(ConversationSnapshot {
             title,
@@ -3487,6 +4687,9 @@ impl SqliteDatabase {
             agent_model_id: model_id,
             thinking_effort,
             amp_mode,
+            claude_max_output_tokens: claude_max_output_tokens.and_then(|v| u32::try_from(v).ok()),
+            claude_system_prompt,
+            auto_commit,
             entries,
             entries_total,
             entries_start: 0,
@@ -3494,9 +4697,157 @@ impl SqliteDatabase {
             queue_paused,
             run_started_at_unix_ms,
             run_finished_at_unix_ms,
+            notes,
+            cwd_subpath,
+            hunk_comments,
+            collapsed_ranges,
+            draft,
+            total_usage,
         })
     }
 
+    fn count_conversation_entries(
+        &mut self,
+        project_slug: &str,
+        workspace_name: &str,
+        thread_local_id: u64,
+    ) -> anyhow::Result<u64> {
+        let total: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM conversation_entries
+             WHERE project_slug = ?1 AND workspace_name = ?2 AND thread_local_id = ?3",
+            params![project_slug, workspace_name, thread_local_id as i64],
+            |row| row.get(0),
+        )?;
+        Ok(total as u64)
+    }
+
+    /// Deletes the oldest entries beyond `policy` and renumbers the rest so `seq` stays dense,
+    /// then stores a `HistoryPruned` marker as the first entry. A marker left by an earlier pass
+    /// is folded into the new one. Conversations with an unfinished run are left untouched.
+    /// Returns the number of entries removed, not counting a previous marker.
+    fn prune_conversation_entries(
+        &mut self,
+        project_slug: &str,
+        workspace_name: &str,
+        thread_local_id: u64,
+        policy: ConversationRetentionPolicy,
+        now_unix_seconds: u64,
+    ) -> anyhow::Result<u64> {
+        if !policy.is_enabled() {
+            return Ok(0);
+        }
+
+        let tx = self.conn.transaction()?;
+        let run = tx
+            .query_row(
+                "SELECT run_started_at_unix_ms, run_finished_at_unix_ms FROM conversations
+                 WHERE project_slug = ?1 AND workspace_name = ?2 AND thread_local_id = ?3",
+                params![project_slug, workspace_name, thread_local_id as i64],
+                |row| Ok((row.get::<_, Option<i64>>(0)?, row.get::<_, Option<i64>>(1)?)),
+            )
+            .optional()?;
+        let Some((run_started, run_finished)) = run else {
+            return Ok(0);
+        };
+        if run_started.is_some() && run_finished.is_none() {
+            return Ok(0);
+        }
+
+        let total: i64 = tx.query_row(
+            "SELECT COUNT(*) FROM conversation_entries
+             WHERE project_slug = ?1 AND workspace_name = ?2 AND thread_local_id = ?3",
+            params![project_slug, workspace_name, thread_local_id as i64],
+            |row| row.get(0),
+        )?;
+        let mut cutoff_seq = policy
+            .keep_last_entries
+            .map(|keep| (total as u64).saturating_sub(keep))
+            .unwrap_or(0) as i64;
+        if let Some(days) = policy.max_age_days {
+            let created_before = now_unix_seconds.saturating_sub(days.saturating_mul(86_400));
+            let expired_seq: i64 = tx.query_row(
+                "SELECT COALESCE(MAX(seq), 0) FROM conversation_entries
+                 WHERE project_slug = ?1 AND workspace_name = ?2 AND thread_local_id = ?3
+                   AND created_at < ?4",
+                params![
+                    project_slug,
+                    workspace_name,
+                    thread_local_id as i64,
+                    i64::try_from(created_before).unwrap_or(i64::MAX)
+                ],
+                |row| row.get(0),
+            )?;
+            cutoff_seq = cutoff_seq.max(expired_seq);
+        }
+        if cutoff_seq <= 0 {
+            return Ok(0);
+        }
+
+        let previously_pruned =
+            history_pruned_entries(&tx, project_slug, workspace_name, thread_local_id)?;
+        let removed = (cutoff_seq as u64).saturating_sub(u64::from(previously_pruned.is_some()));
+        if removed == 0 {
+            return Ok(0);
+        }
+
+        tx.execute(
+            "DELETE FROM conversation_entries
+             WHERE project_slug = ?1 AND workspace_name = ?2 AND thread_local_id = ?3
+               AND seq <= ?4",
+            params![
+                project_slug,
+                workspace_name,
+                thread_local_id as i64,
+                cutoff_seq
+            ],
+        )?;
+        // Shift through negative values so the UNIQUE(seq) constraint holds row by row.
+        tx.execute(
+            "UPDATE conversation_entries SET seq = -(seq - ?4 + 1)
+             WHERE project_slug = ?1 AND workspace_name = ?2 AND thread_local_id = ?3",
+            params![
+                project_slug,
+                workspace_name,
+                thread_local_id as i64,
+                cutoff_seq
+            ],
+        )?;
+        tx.execute(
+            "UPDATE conversation_entries SET seq = -seq
+             WHERE project_slug = ?1 AND workspace_name = ?2 AND thread_local_id = ?3",
+            params![project_slug, workspace_name, thread_local_id as i64],
+        )?;
+
+        let now_ms = now_unix_millis();
+        let marker = ConversationEntry::SystemEvent {
+            entry_id: format!("sys_pruned_{now_ms}"),
+            created_at_unix_ms: now_ms,
+            event: luban_domain::ConversationSystemEvent::HistoryPruned {
+                pruned_entries: removed.saturating_add(previously_pruned.unwrap_or(0)),
+            },
+        };
+        let (kind, codex_item_id, entry_id) = conversation_entry_index_fields(&marker);
+        let payload_json = serde_json::to_string(&marker).context("failed to serialize entry")?;
+        tx.execute(
+            "INSERT INTO conversation_entries
+             (project_slug, workspace_name, thread_local_id, seq, entry_id, kind, codex_item_id, payload_json, created_at)
+             VALUES (?1, ?2, ?3, 1, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                project_slug,
+                workspace_name,
+                thread_local_id as i64,
+                entry_id,
+                kind,
+                codex_item_id,
+                payload_json,
+                i64::try_from(now_unix_seconds).unwrap_or(i64::MAX)
+            ],
+        )?;
+        tx.commit()?;
+
+        Ok(removed)
+    }
+
     fn load_conversation_page(
         &mut self,
         project_slug: &str,
@@ -3508,7 +4859,7 @@ impl SqliteDatabase {
         let row = self
             .conn
             .query_row(
-                "SELECT title, thread_id, task_status, queue_paused, run_started_at_unix_ms, run_finished_at_unix_ms, agent_runner, agent_model_id, thinking_effort, amp_mode FROM conversations
+                "SELECT title, thread_id, task_status, queue_paused, run_started_at_unix_ms, run_finished_at_unix_ms, agent_runner, agent_model_id, thinking_effort, amp_mode, notes, cwd_subpath, draft, claude_max_output_tokens, claude_system_prompt, auto_commit FROM conversations
                  WHERE project_slug = ?1 AND workspace_name = ?2 AND thread_local_id = ?3",
                 params![project_slug, workspace_name, thread_local_id as i64],
                 |row| {
@@ -3523,6 +4874,12 @@ impl SqliteDatabase {
                         row.get::<_, Option<String>>(7)?,
                         row.get::<_, Option<String>>(8)?,
                         row.get::<_, Option<String>>(9)?,
+                        row.get::<_, String>(10)?,
+                        row.get::<_, Option<String>>(11)?,
+                        row.get::<_, String>(12)?,
+                        row.get::<_, Option<i64>>(13)?,
+                        row.get::<_, Option<String>>(14)?,
+                        row.get::<_, i64>(15)? != 0,
                     ))
                 },
             )
@@ -3539,6 +4896,12 @@ impl SqliteDatabase {
             model_id,
             thinking_effort,
             amp_mode,
+            notes,
+            cwd_subpath,
+            draft,
+            claude_max_output_tokens,
+            claude_system_prompt,
+            auto_commit,
         )) = row
         else {
             return Err(SqliteStoreError::ConversationNotFound.into());
@@ -3628,6 +4991,12 @@ impl SqliteDatabase {
             pending_prompts.push(prompt);
         }
 
+        let hunk_comments =
+            self.load_hunk_comments(project_slug, workspace_name, thread_local_id)?;
+        let collapsed_ranges =
+            self.load_collapsed_ranges(project_slug, workspace_name, thread_local_id)?;
+        let total_usage = self.load_total_usage(project_slug, workspace_name, thread_local_id)?;
+
         Ok(ConversationSnapshot {
             title,
             thread_id,
@@ -3636,6 +5005,9 @@ impl SqliteDatabase {
             agent_model_id: model_id,
             thinking_effort,
             amp_mode,
+            claude_max_output_tokens: claude_max_output_tokens.and_then(|v| u32::try_from(v).ok()),
+            claude_system_prompt,
+            auto_commit,
             entries,
             entries_total: total_entries,
             entries_start: start as u64,
@@ -3643,35 +5015,209 @@ impl SqliteDatabase {
             queue_paused,
             run_started_at_unix_ms,
             run_finished_at_unix_ms,
+            notes,
+            cwd_subpath,
+            hunk_comments,
+            collapsed_ranges,
+            draft,
+            total_usage,
         })
     }
 
-    fn delete_conversation_thread(
+    /// User message texts of a thread, newest first, with consecutive repeats collapsed.
+    fn load_prompt_history(
         &mut self,
         project_slug: &str,
         workspace_name: &str,
         thread_local_id: u64,
-    ) -> anyhow::Result<()> {
-        let tx = self.conn.transaction()?;
-        tx.execute(
-            "DELETE FROM conversation_queued_prompts
-             WHERE project_slug = ?1 AND workspace_name = ?2 AND thread_local_id = ?3",
-            params![project_slug, workspace_name, thread_local_id as i64],
-        )?;
-        tx.execute(
-            "DELETE FROM conversation_entries
-             WHERE project_slug = ?1 AND workspace_name = ?2 AND thread_local_id = ?3",
-            params![project_slug, workspace_name, thread_local_id as i64],
+        limit: usize,
+    ) -> anyhow::Result<Vec<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT payload_json
+             FROM conversation_entries
+             WHERE project_slug = ?1 AND workspace_name = ?2 AND thread_local_id = ?3
+               AND kind = 'user_message'
+             ORDER BY seq DESC",
         )?;
-        tx.execute(
-            "DELETE FROM conversations
-             WHERE project_slug = ?1 AND workspace_name = ?2 AND thread_local_id = ?3",
+        let rows = stmt.query_map(
+            params![project_slug, workspace_name, thread_local_id as i64],
+            |row| row.get::<_, String>(0),
+        )?;
+
+        let mut prompts: Vec<String> = Vec::new();
+        for row in rows {
+            if prompts.len() >= limit {
+                break;
+            }
+            let json = row?;
+            let entry: ConversationEntry =
+                serde_json::from_str(&json).context("failed to parse entry")?;
+            let ConversationEntry::UserEvent {
+                event: luban_domain::UserEvent::Message { text, .. },
+                ..
+            } = entry
+            else {
+                continue;
+            };
+            if text.trim().is_empty() || prompts.last() == Some(&text) {
+                continue;
+            }
+            prompts.push(text);
+        }
+        Ok(prompts)
+    }
+
+    /// Entries of a workspace (or one thread) whose searchable text contains `query`, oldest
+    /// thread and entry first. A SQL `LIKE` over the payload pre-filters rows; each candidate is
+    /// then checked against its message/command text only.
+    fn search_conversations(
+        &mut self,
+        project_slug: &str,
+        workspace_name: &str,
+        query: &str,
+        thread_local_id: Option<u64>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<ConversationSearchMatch>> {
+        let query = query.trim();
+        if query.is_empty() || limit == 0 {
+            return Ok(Vec::new());
+        }
+
+        let mut stmt = self.conn.prepare(
+            "SELECT thread_local_id, seq, entry_id, payload_json
+             FROM conversation_entries
+             WHERE project_slug = ?1 AND workspace_name = ?2
+               AND (?3 IS NULL OR thread_local_id = ?3)
+               AND payload_json LIKE ?4 ESCAPE '!'
+             ORDER BY thread_local_id ASC, seq ASC",
+        )?;
+        let rows = stmt.query_map(
+            params![
+                project_slug,
+                workspace_name,
+                thread_local_id.map(|id| id as i64),
+                conversation_search::payload_like_pattern(query),
+            ],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                ))
+            },
+        )?;
+
+        let mut matches = Vec::new();
+        for row in rows {
+            if matches.len() >= limit {
+                break;
+            }
+            let (thread_local_id, seq, entry_id, json) = row?;
+            let entry: ConversationEntry =
+                serde_json::from_str(&json).context("failed to parse entry")?;
+            let Some(snippet) = conversation_search::match_snippet(
+                &conversation_search::searchable_texts(&entry),
+                query,
+            ) else {
+                continue;
+            };
+            matches.push(ConversationSearchMatch {
+                thread_id: thread_local_id as u64,
+                entry_id,
+                entry_index: (seq as u64).saturating_sub(1),
+                snippet,
+            });
+        }
+        Ok(matches)
+    }
+
+    fn delete_conversation_thread(
+        &mut self,
+        project_slug: &str,
+        workspace_name: &str,
+        thread_local_id: u64,
+    ) -> anyhow::Result<()> {
+        let tx = self.conn.transaction()?;
+        tx.execute(
+            "DELETE FROM conversation_queued_prompts
+             WHERE project_slug = ?1 AND workspace_name = ?2 AND thread_local_id = ?3",
+            params![project_slug, workspace_name, thread_local_id as i64],
+        )?;
+        tx.execute(
+            "DELETE FROM conversation_hunk_comments
+             WHERE project_slug = ?1 AND workspace_name = ?2 AND thread_local_id = ?3",
+            params![project_slug, workspace_name, thread_local_id as i64],
+        )?;
+        tx.execute(
+            "DELETE FROM conversation_collapsed_ranges
+             WHERE project_slug = ?1 AND workspace_name = ?2 AND thread_local_id = ?3",
+            params![project_slug, workspace_name, thread_local_id as i64],
+        )?;
+        tx.execute(
+            "DELETE FROM conversation_entries
+             WHERE project_slug = ?1 AND workspace_name = ?2 AND thread_local_id = ?3",
+            params![project_slug, workspace_name, thread_local_id as i64],
+        )?;
+        tx.execute(
+            "DELETE FROM conversations
+             WHERE project_slug = ?1 AND workspace_name = ?2 AND thread_local_id = ?3",
             params![project_slug, workspace_name, thread_local_id as i64],
         )?;
         tx.commit()?;
         Ok(())
     }
 
+    fn move_conversation_thread(
+        &mut self,
+        project_slug: &str,
+        workspace_name: &str,
+        thread_local_id: u64,
+        to_workspace_name: &str,
+        to_thread_local_id: u64,
+    ) -> anyhow::Result<()> {
+        let tx = self.conn.transaction()?;
+        let exists = tx
+            .query_row(
+                "SELECT 1 FROM conversations
+                 WHERE project_slug = ?1 AND workspace_name = ?2 AND thread_local_id = ?3",
+                params![project_slug, workspace_name, thread_local_id as i64],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some();
+        if !exists {
+            return Err(SqliteStoreError::ConversationNotFound.into());
+        }
+
+        // Reason: queued prompts reference `conversations` by key; the check must wait until
+        // every table has been re-keyed.
+        tx.execute_batch("PRAGMA defer_foreign_keys = ON")?;
+        for table in [
+            "conversations",
+            "conversation_entries",
+            "conversation_queued_prompts",
+            "conversation_hunk_comments",
+            "conversation_collapsed_ranges",
+        ] {
+            tx.execute(
+                &format!(
+                    "UPDATE {table} SET workspace_name = ?4, thread_local_id = ?5
+                     WHERE project_slug = ?1 AND workspace_name = ?2 AND thread_local_id = ?3"
+                ),
+                params![
+                    project_slug,
+                    workspace_name,
+                    thread_local_id as i64,
+                    to_workspace_name,
+                    to_thread_local_id as i64
+                ],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
     #[allow(clippy::too_many_arguments)]
     fn save_conversation_queue_state(
         &mut self,
@@ -3756,6 +5302,9 @@ impl SqliteDatabase {
         model_id: &str,
         thinking_effort: ThinkingEffort,
         amp_mode: Option<&str>,
+        claude_max_output_tokens: Option<u32>,
+        claude_system_prompt: Option<&str>,
+        auto_commit: bool,
     ) -> anyhow::Result<()> {
         self.ensure_conversation(project_slug, workspace_name, thread_local_id)?;
         let now = now_unix_seconds();
@@ -3765,7 +5314,10 @@ impl SqliteDatabase {
                  agent_model_id = ?5,
                  thinking_effort = ?6,
                  amp_mode = ?7,
-                 updated_at = ?8
+                 claude_max_output_tokens = ?8,
+                 claude_system_prompt = ?9,
+                 auto_commit = ?10,
+                 updated_at = ?11
              WHERE project_slug = ?1 AND workspace_name = ?2 AND thread_local_id = ?3",
             params![
                 project_slug,
@@ -3775,82 +5327,337 @@ impl SqliteDatabase {
                 model_id,
                 thinking_effort.as_str(),
                 amp_mode,
+                claude_max_output_tokens.map(i64::from),
+                claude_system_prompt,
+                auto_commit as i64,
                 now
             ],
         )?;
-        Ok(())
+        Ok(())
+    }
+
+    fn save_conversation_task_status(
+        &mut self,
+        project_slug: &str,
+        workspace_name: &str,
+        thread_local_id: u64,
+        task_status: luban_domain::TaskStatus,
+    ) -> anyhow::Result<()> {
+        self.ensure_conversation(project_slug, workspace_name, thread_local_id)?;
+
+        let previous_status = self
+            .conn
+            .query_row(
+                "SELECT task_status FROM conversations
+                 WHERE project_slug = ?1 AND workspace_name = ?2 AND thread_local_id = ?3",
+                params![project_slug, workspace_name, thread_local_id as i64],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()
+            .context("failed to load existing conversation task status")?
+            .and_then(|raw| luban_domain::parse_task_status(&raw));
+
+        if previous_status.is_some_and(|status| status == task_status) {
+            return Ok(());
+        }
+
+        let now = now_unix_seconds();
+        self.conn.execute(
+            "UPDATE conversations
+             SET task_status = ?4,
+                 updated_at = ?5
+             WHERE project_slug = ?1 AND workspace_name = ?2 AND thread_local_id = ?3",
+            params![
+                project_slug,
+                workspace_name,
+                thread_local_id as i64,
+                task_status.as_str(),
+                now
+            ],
+        )?;
+
+        if let Some(from) = previous_status {
+            let id = format!(
+                "sys_{}",
+                self.conn
+                    .query_row(
+                        "SELECT COALESCE(MAX(seq), 0) + 1 FROM conversation_entries
+                         WHERE project_slug = ?1 AND workspace_name = ?2 AND thread_local_id = ?3",
+                        params![project_slug, workspace_name, thread_local_id as i64],
+                        |row| row.get::<_, i64>(0),
+                    )
+                    .unwrap_or(1)
+                    + pruned_entry_id_offset(
+                        &self.conn,
+                        project_slug,
+                        workspace_name,
+                        thread_local_id,
+                    )?
+            );
+
+            let entry = ConversationEntry::SystemEvent {
+                entry_id: id,
+                created_at_unix_ms: now_unix_millis(),
+                event: luban_domain::ConversationSystemEvent::TaskStatusChanged {
+                    from,
+                    to: task_status,
+                },
+            };
+            self.append_conversation_entries(
+                project_slug,
+                workspace_name,
+                thread_local_id,
+                &[entry],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn save_conversation_notes(
+        &mut self,
+        project_slug: &str,
+        workspace_name: &str,
+        thread_local_id: u64,
+        notes: &str,
+    ) -> anyhow::Result<()> {
+        self.ensure_conversation(project_slug, workspace_name, thread_local_id)?;
+
+        self.conn.execute(
+            "UPDATE conversations
+             SET notes = ?4
+             WHERE project_slug = ?1 AND workspace_name = ?2 AND thread_local_id = ?3",
+            params![project_slug, workspace_name, thread_local_id as i64, notes],
+        )?;
+
+        Ok(())
+    }
+
+    fn save_conversation_draft(
+        &mut self,
+        project_slug: &str,
+        workspace_name: &str,
+        thread_local_id: u64,
+        draft: &str,
+    ) -> anyhow::Result<()> {
+        self.ensure_conversation(project_slug, workspace_name, thread_local_id)?;
+
+        self.conn.execute(
+            "UPDATE conversations
+             SET draft = ?4
+             WHERE project_slug = ?1 AND workspace_name = ?2 AND thread_local_id = ?3",
+            params![project_slug, workspace_name, thread_local_id as i64, draft],
+        )?;
+
+        Ok(())
+    }
+
+    fn save_conversation_cwd_subpath(
+        &mut self,
+        project_slug: &str,
+        workspace_name: &str,
+        thread_local_id: u64,
+        cwd_subpath: Option<&str>,
+    ) -> anyhow::Result<()> {
+        self.ensure_conversation(project_slug, workspace_name, thread_local_id)?;
+
+        self.conn.execute(
+            "UPDATE conversations
+             SET cwd_subpath = ?4
+             WHERE project_slug = ?1 AND workspace_name = ?2 AND thread_local_id = ?3",
+            params![
+                project_slug,
+                workspace_name,
+                thread_local_id as i64,
+                cwd_subpath
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    fn save_conversation_hunk_comment(
+        &mut self,
+        project_slug: &str,
+        workspace_name: &str,
+        thread_local_id: u64,
+        comment: &HunkComment,
+    ) -> anyhow::Result<()> {
+        self.ensure_conversation(project_slug, workspace_name, thread_local_id)?;
+
+        let side = match comment.side {
+            HunkLineSide::Old => "old",
+            HunkLineSide::New => "new",
+        };
+        self.conn.execute(
+            "INSERT INTO conversation_hunk_comments
+             (project_slug, workspace_name, thread_local_id, entry_id, path, side, line, text, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                project_slug,
+                workspace_name,
+                thread_local_id as i64,
+                comment.entry_id,
+                comment.path,
+                side,
+                comment.line as i64,
+                comment.text,
+                comment.created_at_unix_ms as i64
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    fn save_conversation_collapsed_range(
+        &mut self,
+        project_slug: &str,
+        workspace_name: &str,
+        thread_local_id: u64,
+        range: &CollapsedRange,
+    ) -> anyhow::Result<()> {
+        self.ensure_conversation(project_slug, workspace_name, thread_local_id)?;
+        self.conn.execute(
+            "INSERT INTO conversation_collapsed_ranges
+             (project_slug, workspace_name, thread_local_id, start_entry_id, end_entry_id, entry_count, summary_entry_id, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                project_slug,
+                workspace_name,
+                thread_local_id as i64,
+                range.start_entry_id,
+                range.end_entry_id,
+                range.entry_count as i64,
+                range.summary_entry_id,
+                now_unix_seconds()
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    /// Collapsed ranges of a thread, oldest first. Ranges whose summary entry no longer exists
+    /// are skipped.
+    /// Sums the usage of every stored `turn_usage` entry, so the total does not depend on which
+    /// page of entries is loaded.
+    fn load_total_usage(
+        &self,
+        project_slug: &str,
+        workspace_name: &str,
+        thread_local_id: u64,
+    ) -> anyhow::Result<luban_domain::CodexUsage> {
+        let mut stmt = self.conn.prepare(
+            "SELECT payload_json
+             FROM conversation_entries
+             WHERE project_slug = ?1 AND workspace_name = ?2 AND thread_local_id = ?3
+               AND kind = 'turn_usage'",
+        )?;
+        let rows = stmt.query_map(
+            params![project_slug, workspace_name, thread_local_id as i64],
+            |row| row.get::<_, String>(0),
+        )?;
+
+        let mut total = luban_domain::CodexUsage::default();
+        for row in rows {
+            let json = row?;
+            let entry: ConversationEntry =
+                serde_json::from_str(&json).context("failed to parse entry")?;
+            if let ConversationEntry::AgentEvent {
+                event: luban_domain::AgentEvent::TurnUsage { usage: Some(usage) },
+                ..
+            } = entry
+            {
+                total.accumulate(&usage);
+            }
+        }
+        Ok(total)
+    }
+
+    fn load_collapsed_ranges(
+        &self,
+        project_slug: &str,
+        workspace_name: &str,
+        thread_local_id: u64,
+    ) -> anyhow::Result<Vec<CollapsedRange>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT cr.start_entry_id, cr.end_entry_id, cr.entry_count, cr.summary_entry_id
+             FROM conversation_collapsed_ranges cr
+             WHERE cr.project_slug = ?1 AND cr.workspace_name = ?2 AND cr.thread_local_id = ?3
+               AND EXISTS (
+                 SELECT 1 FROM conversation_entries e
+                 WHERE e.project_slug = cr.project_slug
+                   AND e.workspace_name = cr.workspace_name
+                   AND e.thread_local_id = cr.thread_local_id
+                   AND e.entry_id = cr.summary_entry_id
+               )
+             ORDER BY cr.id ASC",
+        )?;
+        let rows = stmt.query_map(
+            params![project_slug, workspace_name, thread_local_id as i64],
+            |row| {
+                Ok(CollapsedRange {
+                    start_entry_id: row.get(0)?,
+                    end_entry_id: row.get(1)?,
+                    entry_count: row.get::<_, i64>(2)?.max(0) as u64,
+                    summary_entry_id: row.get(3)?,
+                })
+            },
+        )?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
     }
 
-    fn save_conversation_task_status(
-        &mut self,
+    /// Hunk comments of a thread in creation order. Comments whose entry no longer exists are
+    /// skipped.
+    fn load_hunk_comments(
+        &self,
         project_slug: &str,
         workspace_name: &str,
         thread_local_id: u64,
-        task_status: luban_domain::TaskStatus,
-    ) -> anyhow::Result<()> {
-        self.ensure_conversation(project_slug, workspace_name, thread_local_id)?;
-
-        let previous_status = self
-            .conn
-            .query_row(
-                "SELECT task_status FROM conversations
-                 WHERE project_slug = ?1 AND workspace_name = ?2 AND thread_local_id = ?3",
-                params![project_slug, workspace_name, thread_local_id as i64],
-                |row| row.get::<_, String>(0),
-            )
-            .optional()
-            .context("failed to load existing conversation task status")?
-            .and_then(|raw| luban_domain::parse_task_status(&raw));
-
-        if previous_status.is_some_and(|status| status == task_status) {
-            return Ok(());
-        }
-
-        let now = now_unix_seconds();
-        self.conn.execute(
-            "UPDATE conversations
-             SET task_status = ?4,
-                 updated_at = ?5
-             WHERE project_slug = ?1 AND workspace_name = ?2 AND thread_local_id = ?3",
-            params![
-                project_slug,
-                workspace_name,
-                thread_local_id as i64,
-                task_status.as_str(),
-                now
-            ],
+    ) -> anyhow::Result<Vec<HunkComment>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT hc.entry_id, hc.path, hc.side, hc.line, hc.text, hc.created_at
+             FROM conversation_hunk_comments hc
+             WHERE hc.project_slug = ?1 AND hc.workspace_name = ?2 AND hc.thread_local_id = ?3
+               AND EXISTS (
+                 SELECT 1 FROM conversation_entries e
+                 WHERE e.project_slug = hc.project_slug
+                   AND e.workspace_name = hc.workspace_name
+                   AND e.thread_local_id = hc.thread_local_id
+                   AND e.entry_id = hc.entry_id
+               )
+             ORDER BY hc.id ASC",
+        )?;
+        let rows = stmt.query_map(
+            params![project_slug, workspace_name, thread_local_id as i64],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, i64>(3)?,
+                    row.get::<_, String>(4)?,
+                    row.get::<_, i64>(5)?,
+                ))
+            },
         )?;
 
-        if let Some(from) = previous_status {
-            let id = format!(
-                "sys_{}",
-                self.conn
-                    .query_row(
-                        "SELECT COALESCE(MAX(seq), 0) + 1 FROM conversation_entries
-                         WHERE project_slug = ?1 AND workspace_name = ?2 AND thread_local_id = ?3",
-                        params![project_slug, workspace_name, thread_local_id as i64],
-                        |row| row.get::<_, i64>(0),
-                    )
-                    .unwrap_or(1)
-            );
-
-            let entry = ConversationEntry::SystemEvent {
-                entry_id: id,
-                created_at_unix_ms: now_unix_millis(),
-                event: luban_domain::ConversationSystemEvent::TaskStatusChanged {
-                    from,
-                    to: task_status,
-                },
+        let mut comments = Vec::new();
+        for row in rows {
+            let (entry_id, path, side, line, text, created_at) = row?;
+            let side = match side.as_str() {
+                "old" => HunkLineSide::Old,
+                _ => HunkLineSide::New,
             };
-            self.append_conversation_entries(
-                project_slug,
-                workspace_name,
-                thread_local_id,
-                &[entry],
-            )?;
+            comments.push(HunkComment {
+                entry_id,
+                path,
+                side,
+                line: line.max(0) as u64,
+                text,
+                created_at_unix_ms: created_at.max(0) as u64,
+            });
         }
-
-        Ok(())
+        Ok(comments)
     }
 
     fn save_conversation_task_status_last_analyzed(
@@ -4273,6 +6080,10 @@ fn apply_migrations(conn: &mut Connection) -> anyhow::Result<()> {
             migrate_conversation_entries_v17(conn)
                 .with_context(|| "failed to migrate conversation entry payloads to v2")?;
         }
+        if *version == 27 {
+            migrate_scope_keys_v27(conn)
+                .with_context(|| "failed to migrate persisted payloads to canonical scope keys")?;
+        }
         conn.pragma_update(None, "user_version", *version as i64)
             .context("failed to update user_version")?;
         current = *version;
@@ -4283,6 +6094,32 @@ fn apply_migrations(conn: &mut Connection) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Rewrites legacy `workspace_id`/`thread_id` keys in the persisted Telegram topic bindings, the
+/// only JSON setting keyed by workdir/task, to `workdir_id`/`task_id`.
+fn migrate_scope_keys_v27(conn: &mut Connection) -> anyhow::Result<()> {
+    let raw = conn
+        .query_row(
+            "SELECT value FROM app_settings_text WHERE key = ?1",
+            params![TELEGRAM_TOPIC_BINDINGS_KEY],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()?;
+    let Some(raw) = raw else {
+        return Ok(());
+    };
+    let Ok(mut value) = serde_json::from_str::<serde_json::Value>(&raw) else {
+        return Ok(());
+    };
+    if !luban_domain::scope_keys::to_canonical(&mut value) {
+        return Ok(());
+    }
+    conn.execute(
+        "UPDATE app_settings_text SET value = ?2 WHERE key = ?1",
+        params![TELEGRAM_TOPIC_BINDINGS_KEY, value.to_string()],
+    )?;
+    Ok(())
+}
+
 fn migrate_conversation_entries_v17(conn: &mut Connection) -> anyhow::Result<()> {
     #[derive(Debug, serde::Deserialize)]
     #[serde(tag = "type", rename_all = "snake_case")]
@@ -4372,7 +6209,10 @@ fn migrate_conversation_entries_v17(conn: &mut Connection) -> anyhow::Result<()>
                 entry_id: String::new(),
                 created_at_unix_ms: 0,
                 runner: None,
-                event: luban_domain::AgentEvent::TurnError { message },
+                event: luban_domain::AgentEvent::TurnError {
+                    message,
+                    code: None,
+                },
             },
         }
     }
@@ -4453,6 +6293,60 @@ fn workspace_status_from_i64(v: i64) -> anyhow::Result<WorkspaceStatus> {
     }
 }
 
+fn project_status_to_i64(status: ProjectStatus) -> i64 {
+    match status {
+        ProjectStatus::Active => 0,
+        ProjectStatus::Archived => 1,
+    }
+}
+
+fn project_status_from_i64(v: i64) -> anyhow::Result<ProjectStatus> {
+    match v {
+        0 => Ok(ProjectStatus::Active),
+        1 => Ok(ProjectStatus::Archived),
+        _ => Err(anyhow!("invalid project status: {v}")),
+    }
+}
+
+/// Total carried by the `HistoryPruned` marker at the head of a pruned conversation.
+fn history_pruned_entries(
+    conn: &Connection,
+    project_slug: &str,
+    workspace_name: &str,
+    thread_local_id: u64,
+) -> anyhow::Result<Option<u64>> {
+    let head_json: Option<String> = conn
+        .query_row(
+            "SELECT payload_json FROM conversation_entries
+             WHERE project_slug = ?1 AND workspace_name = ?2 AND thread_local_id = ?3
+               AND seq = 1",
+            params![project_slug, workspace_name, thread_local_id as i64],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(head_json
+        .and_then(|json| serde_json::from_str::<ConversationEntry>(&json).ok())
+        .and_then(|entry| match entry {
+            ConversationEntry::SystemEvent {
+                event: luban_domain::ConversationSystemEvent::HistoryPruned { pruned_entries },
+                ..
+            } => Some(pruned_entries),
+            _ => None,
+        }))
+}
+
+/// Pruning renumbers `seq` downward, so generated entry ids add back the entries the marker
+/// replaced; otherwise they would reuse ids still held by retained entries.
+fn pruned_entry_id_offset(
+    conn: &Connection,
+    project_slug: &str,
+    workspace_name: &str,
+    thread_local_id: u64,
+) -> anyhow::Result<i64> {
+    let pruned = history_pruned_entries(conn, project_slug, workspace_name, thread_local_id)?;
+    Ok(pruned.map_or(0, |pruned| (pruned as i64).saturating_sub(1)))
+}
+
 fn conversation_entry_index_fields(
     entry: &ConversationEntry,
 ) -> (&'static str, Option<&str>, &str) {
@@ -4482,6 +6376,9 @@ fn conversation_entry_index_fields(
                 Some(codex_item_id(item.as_ref())),
                 entry_id.as_str(),
             ),
+            luban_domain::AgentEvent::TurnStarted { .. } => {
+                ("turn_started", None, entry_id.as_str())
+            }
             luban_domain::AgentEvent::TurnUsage { .. } => ("turn_usage", None, entry_id.as_str()),
             luban_domain::AgentEvent::TurnDuration { .. } => {
                 ("turn_duration", None, entry_id.as_str())
@@ -4560,6 +6457,71 @@ mod tests {
         SqliteDatabase::open(path, SqliteStoreOptions::default()).unwrap()
     }
 
+    #[test]
+    fn scope_key_migration_rewrites_legacy_topic_bindings() {
+        let path = temp_db_path("scope_key_migration_rewrites_legacy_topic_bindings");
+        let mut db = open_db(&path);
+
+        db.conn
+            .execute(
+                "INSERT INTO app_settings_text (key, value, created_at, updated_at)
+                 VALUES (?1, ?2, 0, 0)",
+                params![
+                    TELEGRAM_TOPIC_BINDINGS_KEY,
+                    r#"[{"message_thread_id":7,"workspace_id":3,"thread_id":2}]"#
+                ],
+            )
+            .unwrap();
+        migrate_scope_keys_v27(&mut db.conn).unwrap();
+
+        let raw: String = db
+            .conn
+            .query_row(
+                "SELECT value FROM app_settings_text WHERE key = ?1",
+                params![TELEGRAM_TOPIC_BINDINGS_KEY],
+                |row| row.get(0),
+            )
+            .unwrap();
+        let value: serde_json::Value = serde_json::from_str(&raw).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!([{"message_thread_id": 7, "workdir_id": 3, "task_id": 2}])
+        );
+    }
+
+    #[test]
+    fn legacy_telegram_paired_chat_id_loads_into_paired_chat_ids() {
+        let path = temp_db_path("legacy_telegram_paired_chat_id_loads_into_paired_chat_ids");
+        let mut db = open_db(&path);
+
+        db.conn
+            .execute(
+                "INSERT INTO app_settings (key, value, created_at, updated_at)
+                 VALUES (?1, ?2, 0, 0)",
+                params![TELEGRAM_PAIRED_CHAT_ID_KEY, 42i64],
+            )
+            .unwrap();
+        let mut snapshot = db.load_app_state().unwrap();
+        assert_eq!(snapshot.telegram_paired_chat_ids, vec![42]);
+
+        snapshot.telegram_paired_chat_ids.push(7);
+        db.save_app_state(&snapshot).unwrap();
+        assert_eq!(
+            db.load_app_state().unwrap().telegram_paired_chat_ids,
+            vec![42, 7]
+        );
+        let legacy: Option<i64> = db
+            .conn
+            .query_row(
+                "SELECT value FROM app_settings WHERE key = ?1",
+                params![TELEGRAM_PAIRED_CHAT_ID_KEY],
+                |row| row.get(0),
+            )
+            .optional()
+            .unwrap();
+        assert_eq!(legacy, None, "saving drops the legacy single-chat key");
+    }
+
     #[test]
     fn migrations_create_schema() {
         let path = temp_db_path("migrations_create_schema");
@@ -4682,6 +6644,163 @@ mod tests {
         assert_eq!(threads[0].task_status, luban_domain::TaskStatus::Validating);
     }
 
+    #[test]
+    fn conversation_notes_persist_and_reload() {
+        let path = temp_db_path("conversation_notes_persist_and_reload");
+        let mut db = open_db(&path);
+
+        db.ensure_conversation("p", "w", 1).unwrap();
+        assert_eq!(db.load_conversation("p", "w", 1).unwrap().notes, "");
+
+        db.save_conversation_notes("p", "w", 1, "remember to rebase")
+            .unwrap();
+        drop(db);
+
+        let mut db = open_db(&path);
+        let snapshot = db.load_conversation("p", "w", 1).unwrap();
+        assert_eq!(snapshot.notes, "remember to rebase");
+        let page = db.load_conversation_page("p", "w", 1, None, 10).unwrap();
+        assert_eq!(page.notes, "remember to rebase");
+    }
+
+    #[test]
+    fn conversation_draft_persists_and_clears() {
+        let path = temp_db_path("conversation_draft_persists_and_clears");
+        let mut db = open_db(&path);
+
+        db.ensure_conversation("p", "w", 1).unwrap();
+        assert_eq!(db.load_conversation("p", "w", 1).unwrap().draft, "");
+
+        db.save_conversation_draft("p", "w", 1, "half-written prompt")
+            .unwrap();
+        drop(db);
+
+        let mut db = open_db(&path);
+        assert_eq!(
+            db.load_conversation("p", "w", 1).unwrap().draft,
+            "half-written prompt"
+        );
+        let page = db.load_conversation_page("p", "w", 1, None, 10).unwrap();
+        assert_eq!(page.draft, "half-written prompt");
+
+        db.save_conversation_draft("p", "w", 1, "").unwrap();
+        assert_eq!(db.load_conversation("p", "w", 1).unwrap().draft, "");
+    }
+
+    #[test]
+    fn conversation_cwd_subpath_persists_and_clears() {
+        let path = temp_db_path("conversation_cwd_subpath_persists_and_clears");
+        let mut db = open_db(&path);
+
+        db.ensure_conversation("p", "w", 1).unwrap();
+        assert_eq!(db.load_conversation("p", "w", 1).unwrap().cwd_subpath, None);
+
+        db.save_conversation_cwd_subpath("p", "w", 1, Some("packages/web"))
+            .unwrap();
+        let page = db.load_conversation_page("p", "w", 1, None, 10).unwrap();
+        assert_eq!(page.cwd_subpath.as_deref(), Some("packages/web"));
+
+        db.save_conversation_cwd_subpath("p", "w", 1, None).unwrap();
+        assert_eq!(db.load_conversation("p", "w", 1).unwrap().cwd_subpath, None);
+    }
+
+    #[test]
+    fn hunk_comments_persist_and_drop_with_their_entry() {
+        let path = temp_db_path("hunk_comments_persist_and_drop_with_their_entry");
+        let mut db = open_db(&path);
+
+        let file_change = ConversationEntry::AgentEvent {
+            entry_id: "fc_1".to_owned(),
+            created_at_unix_ms: 0,
+            runner: None,
+            event: luban_domain::AgentEvent::Item {
+                item: Box::new(luban_domain::CodexThreadItem::FileChange {
+                    id: "item_1".to_owned(),
+                    changes: vec![luban_domain::CodexFileUpdateChange {
+                        path: "src/main.rs".to_owned(),
+                        kind: luban_domain::CodexPatchChangeKind::Update,
+                    }],
+                    status: luban_domain::CodexPatchApplyStatus::Completed,
+                }),
+            },
+        };
+        db.ensure_conversation("p", "w", 1).unwrap();
+        db.append_conversation_entries("p", "w", 1, std::slice::from_ref(&file_change))
+            .unwrap();
+
+        let comment = HunkComment {
+            entry_id: "fc_1".to_owned(),
+            path: "src/main.rs".to_owned(),
+            side: HunkLineSide::New,
+            line: 12,
+            text: "extract this".to_owned(),
+            created_at_unix_ms: 42,
+        };
+        db.save_conversation_hunk_comment("p", "w", 1, &comment)
+            .unwrap();
+        drop(db);
+
+        let mut db = open_db(&path);
+        assert_eq!(
+            db.load_conversation("p", "w", 1).unwrap().hunk_comments,
+            vec![comment.clone()]
+        );
+        let page = db.load_conversation_page("p", "w", 1, None, 10).unwrap();
+        assert_eq!(page.hunk_comments, vec![comment]);
+
+        db.replace_conversation_entries("p", "w", 1, &[]).unwrap();
+        assert!(
+            db.load_conversation("p", "w", 1)
+                .unwrap()
+                .hunk_comments
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn collapsed_ranges_persist_while_their_summary_entry_exists() {
+        let path = temp_db_path("collapsed_ranges_persist_while_their_summary_entry_exists");
+        let mut db = open_db(&path);
+
+        let summary = ConversationEntry::SystemEvent {
+            entry_id: "sys_3".to_owned(),
+            created_at_unix_ms: 0,
+            event: luban_domain::ConversationSystemEvent::HistorySummarized {
+                summary_markdown: "- fixed the build".to_owned(),
+                collapsed_entries: 2,
+            },
+        };
+        db.ensure_conversation("p", "w", 1).unwrap();
+        db.append_conversation_entries("p", "w", 1, std::slice::from_ref(&summary))
+            .unwrap();
+
+        let range = CollapsedRange {
+            start_entry_id: "e_1".to_owned(),
+            end_entry_id: "e_2".to_owned(),
+            entry_count: 2,
+            summary_entry_id: "sys_3".to_owned(),
+        };
+        db.save_conversation_collapsed_range("p", "w", 1, &range)
+            .unwrap();
+        drop(db);
+
+        let mut db = open_db(&path);
+        assert_eq!(
+            db.load_conversation("p", "w", 1).unwrap().collapsed_ranges,
+            vec![range.clone()]
+        );
+        let page = db.load_conversation_page("p", "w", 1, None, 10).unwrap();
+        assert_eq!(page.collapsed_ranges, vec![range]);
+
+        db.replace_conversation_entries("p", "w", 1, &[]).unwrap();
+        assert!(
+            db.load_conversation("p", "w", 1)
+                .unwrap()
+                .collapsed_ranges
+                .is_empty()
+        );
+    }
+
     #[test]
     fn list_conversation_threads_does_not_autocreate_threads() {
         let path = temp_db_path("list_conversation_threads_does_not_autocreate_threads");
@@ -4753,6 +6872,64 @@ mod tests {
         assert_eq!(count, 0);
     }
 
+    #[test]
+    fn move_conversation_thread_rekeys_entries_and_queue() {
+        let path = temp_db_path("move_conversation_thread_rekeys_entries_and_queue");
+        let mut db = open_db(&path);
+
+        db.ensure_conversation("p", "w", 1).unwrap();
+        db.append_conversation_entries(
+            "p",
+            "w",
+            1,
+            &[ConversationEntry::UserEvent {
+                entry_id: String::new(),
+                created_at_unix_ms: 0,
+                event: luban_domain::UserEvent::Message {
+                    text: "hello".to_owned(),
+                    attachments: Vec::new(),
+                },
+            }],
+        )
+        .unwrap();
+        let prompt = QueuedPrompt {
+            id: 1,
+            text: "queued".to_owned(),
+            attachments: Vec::new(),
+            run_config: AgentRunConfig {
+                runner: luban_domain::AgentRunnerKind::Codex,
+                model_id: "gpt-5.3-codex".to_owned(),
+                thinking_effort: ThinkingEffort::Minimal,
+                amp_mode: None,
+                claude_max_output_tokens: None,
+                claude_system_prompt: None,
+            },
+        };
+        db.save_conversation_queue_state("p", "w", 1, false, None, None, &[prompt])
+            .unwrap();
+        db.ensure_conversation("p", "other", 3).unwrap();
+
+        let err = db
+            .move_conversation_thread("p", "w", 1, "other", 3)
+            .expect_err("target key is taken");
+        assert!(!err.to_string().is_empty());
+        db.move_conversation_thread("p", "w", 1, "other", 4)
+            .unwrap();
+
+        assert!(db.list_conversation_threads("p", "w").unwrap().is_empty());
+        let moved = db.load_conversation("p", "other", 4).unwrap();
+        assert_eq!(moved.entries.len(), 2);
+        assert_eq!(moved.pending_prompts.len(), 1);
+        assert_eq!(moved.pending_prompts[0].text, "queued");
+
+        let err = db
+            .move_conversation_thread("p", "w", 1, "other", 5)
+            .unwrap_err();
+        assert!(
+            err.downcast_ref::<SqliteStoreError>() == Some(&SqliteStoreError::ConversationNotFound)
+        );
+    }
+
     #[test]
     fn load_conversation_page_includes_title() {
         let path = temp_db_path("load_conversation_page_includes_title");
@@ -4782,6 +6959,57 @@ mod tests {
         assert_eq!(snapshot.title.as_deref(), Some("Hello world"));
     }
 
+    #[test]
+    fn conversation_total_usage_sums_every_stored_turn() {
+        let path = temp_db_path("conversation_total_usage_sums_every_stored_turn");
+        let mut db = open_db(&path);
+
+        db.ensure_conversation("p", "w", 1).unwrap();
+        let turn_usage = |input_tokens, output_tokens| ConversationEntry::AgentEvent {
+            entry_id: String::new(),
+            created_at_unix_ms: 0,
+            runner: None,
+            event: luban_domain::AgentEvent::TurnUsage {
+                usage: Some(luban_domain::CodexUsage {
+                    input_tokens,
+                    cached_input_tokens: 5,
+                    output_tokens,
+                }),
+            },
+        };
+        db.append_conversation_entries("p", "w", 1, &[turn_usage(100, 10)])
+            .unwrap();
+        db.append_conversation_entries(
+            "p",
+            "w",
+            1,
+            &[ConversationEntry::AgentEvent {
+                entry_id: String::new(),
+                created_at_unix_ms: 0,
+                runner: None,
+                event: luban_domain::AgentEvent::TurnUsage { usage: None },
+            }],
+        )
+        .unwrap();
+        db.append_conversation_entries("p", "w", 1, &[turn_usage(200, 20)])
+            .unwrap();
+
+        let expected = luban_domain::CodexUsage {
+            input_tokens: 300,
+            cached_input_tokens: 10,
+            output_tokens: 30,
+        };
+        let snapshot = db.load_conversation("p", "w", 1).unwrap();
+        assert_eq!(snapshot.total_usage, expected);
+        let page = db.load_conversation_page("p", "w", 1, None, 1).unwrap();
+        assert_eq!(page.entries.len(), 1);
+        assert_eq!(page.total_usage, expected);
+
+        let threads = db.list_conversation_threads("p", "w").unwrap();
+        assert_eq!(threads.len(), 1);
+        assert_eq!(threads[0].total_tokens, 330);
+    }
+
     fn create_db_at_schema_version(path: &Path, target_version: u32) {
         let mut conn = Connection::open(path).unwrap();
         configure_connection(&mut conn).unwrap();
@@ -4839,6 +7067,15 @@ mod tests {
                 path: PathBuf::from("/tmp/p"),
                 is_git: true,
                 expanded: false,
+                system_prompt: None,
+                worktree_root: None,
+                branch_name_template: None,
+                status: ProjectStatus::Active,
+                agent_defaults: Default::default(),
+                saved_commands: Vec::new(),
+                agent_env: Vec::new(),
+                pr_remote: None,
+                pr_base_branch: None,
                 workspaces: vec![PersistedWorkspace {
                     id: 2,
                     workspace_name: "w".to_owned(),
@@ -4858,6 +7095,9 @@ mod tests {
             appearance_terminal_font: None,
             agent_default_model_id: None,
             agent_runner_default_models: HashMap::new(),
+            agent_runner_prompt_prefixes: HashMap::new(),
+            agent_runner_prompt_suffixes: HashMap::new(),
+            model_thinking_effort_caps: HashMap::new(),
             agent_default_thinking_effort: None,
             agent_default_runner: None,
             agent_amp_mode: None,
@@ -4868,6 +7108,7 @@ mod tests {
             last_open_workspace_id: None,
             open_button_selection: None,
             sidebar_project_order: Vec::new(),
+            project_groups: Vec::new(),
             workspace_active_thread_id: HashMap::new(),
             workspace_open_tabs: HashMap::new(),
             workspace_archived_tabs: HashMap::new(),
@@ -4881,8 +7122,16 @@ mod tests {
             telegram_enabled: None,
             telegram_bot_token: None,
             telegram_bot_username: None,
-            telegram_paired_chat_id: None,
+            telegram_paired_chat_ids: Vec::new(),
             telegram_topic_bindings: None,
+            webhook_url: None,
+            webhook_secret: None,
+            agent_merge_reasoning_entries: None,
+            agent_pause_queue_on_patch_failure: None,
+            agent_item_dedup_window: None,
+            agent_auto_retry_on_failure: None,
+            agent_command_output_max_bytes: None,
+            auto_archive_after_days: None,
         };
 
         db.save_app_state(&snapshot).unwrap();
@@ -4959,6 +7208,24 @@ mod tests {
                 path: PathBuf::from("/tmp/my-project"),
                 is_git: true,
                 expanded: true,
+                system_prompt: None,
+                worktree_root: None,
+                branch_name_template: Some("{user}/{slug}".to_owned()),
+                status: ProjectStatus::Active,
+                agent_defaults: luban_domain::ProjectAgentDefaults {
+                    runner: Some(luban_domain::AgentRunnerKind::Codex),
+                    model_id: Some("gpt-5.2-codex".to_owned()),
+                    thinking_effort: Some(ThinkingEffort::XHigh),
+                    amp_mode: None,
+                },
+                saved_commands: vec![luban_domain::ProjectSavedCommand {
+                    id: 1,
+                    label: "run tests".to_owned(),
+                    command: "just test".to_owned(),
+                }],
+                agent_env: vec![("API_TOKEN".to_owned(), "secret".to_owned())],
+                pr_remote: Some("upstream".to_owned()),
+                pr_base_branch: Some("develop".to_owned()),
                 workspaces: vec![PersistedWorkspace {
                     id: 10,
                     workspace_name: "alpha".to_owned(),
@@ -4978,6 +7245,9 @@ mod tests {
             appearance_terminal_font: Some("Geist Mono".to_owned()),
             agent_default_model_id: Some("gpt-5.2".to_owned()),
             agent_runner_default_models: HashMap::new(),
+            agent_runner_prompt_prefixes: HashMap::new(),
+            agent_runner_prompt_suffixes: HashMap::new(),
+            model_thinking_effort_caps: HashMap::new(),
             agent_default_thinking_effort: Some("high".to_owned()),
             agent_default_runner: Some("amp".to_owned()),
             agent_amp_mode: Some("rush".to_owned()),
@@ -4988,6 +7258,11 @@ mod tests {
             last_open_workspace_id: Some(10),
             open_button_selection: None,
             sidebar_project_order: vec!["/tmp/my-project".to_owned()],
+            project_groups: vec![PersistedProjectGroup {
+                id: 1,
+                name: "Work".to_owned(),
+                project_ids: vec![1],
+            }],
             workspace_active_thread_id: HashMap::from([(10, 1)]),
             workspace_open_tabs: HashMap::from([(10, vec![1, 2, 3])]),
             workspace_archived_tabs: HashMap::from([(10, vec![9, 8])]),
@@ -5019,8 +7294,16 @@ mod tests {
             telegram_enabled: None,
             telegram_bot_token: None,
             telegram_bot_username: None,
-            telegram_paired_chat_id: None,
+            telegram_paired_chat_ids: Vec::new(),
             telegram_topic_bindings: None,
+            webhook_url: Some("https://example.com/hook".to_owned()),
+            webhook_secret: Some("s3cret".to_owned()),
+            agent_merge_reasoning_entries: None,
+            agent_pause_queue_on_patch_failure: None,
+            agent_item_dedup_window: None,
+            agent_auto_retry_on_failure: None,
+            agent_command_output_max_bytes: None,
+            auto_archive_after_days: None,
         };
 
         db.save_app_state(&snapshot).unwrap();
@@ -5041,6 +7324,15 @@ mod tests {
                 path: PathBuf::from("/tmp/p"),
                 is_git: true,
                 expanded: false,
+                system_prompt: None,
+                worktree_root: None,
+                branch_name_template: None,
+                status: ProjectStatus::Active,
+                agent_defaults: Default::default(),
+                saved_commands: Vec::new(),
+                agent_env: Vec::new(),
+                pr_remote: None,
+                pr_base_branch: None,
                 workspaces: vec![PersistedWorkspace {
                     id: 2,
                     workspace_name: "w".to_owned(),
@@ -5060,6 +7352,9 @@ mod tests {
             appearance_terminal_font: None,
             agent_default_model_id: None,
             agent_runner_default_models: HashMap::new(),
+            agent_runner_prompt_prefixes: HashMap::new(),
+            agent_runner_prompt_suffixes: HashMap::new(),
+            model_thinking_effort_caps: HashMap::new(),
             agent_default_thinking_effort: None,
             agent_default_runner: None,
             agent_amp_mode: None,
@@ -5070,6 +7365,7 @@ mod tests {
             last_open_workspace_id: None,
             open_button_selection: None,
             sidebar_project_order: Vec::new(),
+            project_groups: Vec::new(),
             workspace_active_thread_id: HashMap::new(),
             workspace_open_tabs: HashMap::new(),
             workspace_archived_tabs: HashMap::new(),
@@ -5083,8 +7379,16 @@ mod tests {
             telegram_enabled: None,
             telegram_bot_token: None,
             telegram_bot_username: None,
-            telegram_paired_chat_id: None,
+            telegram_paired_chat_ids: Vec::new(),
             telegram_topic_bindings: None,
+            webhook_url: None,
+            webhook_secret: None,
+            agent_merge_reasoning_entries: None,
+            agent_pause_queue_on_patch_failure: None,
+            agent_item_dedup_window: None,
+            agent_auto_retry_on_failure: None,
+            agent_command_output_max_bytes: None,
+            auto_archive_after_days: None,
         };
         db.save_app_state(&snapshot).unwrap();
 
@@ -5170,6 +7474,8 @@ mod tests {
                     model_id: "gpt-5.3-codex".to_owned(),
                     thinking_effort: ThinkingEffort::Minimal,
                     amp_mode: None,
+                    claude_max_output_tokens: None,
+                    claude_system_prompt: None,
                 },
             },
             QueuedPrompt {
@@ -5181,6 +7487,8 @@ mod tests {
                     model_id: "gpt-5.3-codex".to_owned(),
                     thinking_effort: ThinkingEffort::Minimal,
                     amp_mode: None,
+                    claude_max_output_tokens: None,
+                    claude_system_prompt: None,
                 },
             },
         ];
@@ -5197,6 +7505,193 @@ mod tests {
         assert_eq!(snapshot.pending_prompts[1].text, "queued-b");
     }
 
+    #[test]
+    fn prompt_history_returns_distinct_prompts_newest_first() {
+        let path = temp_db_path("prompt_history_returns_distinct_prompts_newest_first");
+        let mut db = open_db(&path);
+
+        db.ensure_conversation("p", "w", 1).unwrap();
+        let user_message = |text: &str| ConversationEntry::UserEvent {
+            entry_id: String::new(),
+            created_at_unix_ms: 0,
+            event: luban_domain::UserEvent::Message {
+                text: text.to_owned(),
+                attachments: Vec::new(),
+            },
+        };
+        let entries = vec![
+            user_message("fix the build"),
+            ConversationEntry::AgentEvent {
+                entry_id: String::new(),
+                created_at_unix_ms: 0,
+                runner: None,
+                event: luban_domain::AgentEvent::TurnCanceled,
+            },
+            user_message("add a test"),
+            user_message("add a test"),
+            user_message("  "),
+            user_message("ship it"),
+        ];
+        db.append_conversation_entries("p", "w", 1, &entries)
+            .unwrap();
+
+        let history = db.load_prompt_history("p", "w", 1, 10).unwrap();
+        assert_eq!(history, vec!["ship it", "add a test", "fix the build"]);
+
+        let capped = db.load_prompt_history("p", "w", 1, 2).unwrap();
+        assert_eq!(capped, vec!["ship it", "add a test"]);
+    }
+
+    #[test]
+    fn conversation_search_matches_messages_and_command_output_case_insensitively() {
+        let path = temp_db_path(
+            "conversation_search_matches_messages_and_command_output_case_insensitively",
+        );
+        let mut db = open_db(&path);
+
+        let user_message = |text: &str| ConversationEntry::UserEvent {
+            entry_id: String::new(),
+            created_at_unix_ms: 0,
+            event: luban_domain::UserEvent::Message {
+                text: text.to_owned(),
+                attachments: Vec::new(),
+            },
+        };
+        db.ensure_conversation("p", "w", 1).unwrap();
+        db.ensure_conversation("p", "w", 2).unwrap();
+        db.append_conversation_entries(
+            "p",
+            "w",
+            1,
+            &[
+                user_message("Fix the flaky Login test"),
+                ConversationEntry::AgentEvent {
+                    entry_id: String::new(),
+                    created_at_unix_ms: 0,
+                    runner: None,
+                    event: luban_domain::AgentEvent::Item {
+                        item: Box::new(luban_domain::CodexThreadItem::CommandExecution {
+                            id: "cmd_1".to_owned(),
+                            command: "cargo test".to_owned(),
+                            aggregated_output: "test login_redirects ... FAILED".to_owned(),
+                            exit_code: Some(101),
+                            status: luban_domain::CodexCommandExecutionStatus::Failed,
+                            truncated: false,
+                        }),
+                    },
+                },
+            ],
+        )
+        .unwrap();
+        db.append_conversation_entries(
+            "p",
+            "w",
+            2,
+            &[
+                user_message("unrelated"),
+                user_message("100% LOGIN coverage"),
+            ],
+        )
+        .unwrap();
+
+        let matches = db
+            .search_conversations("p", "w", "login", None, 200)
+            .unwrap();
+        let found = matches
+            .iter()
+            .map(|m| (m.thread_id, m.entry_index, m.snippet.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            // Index 0 of each thread is its task-created system event.
+            vec![
+                (1, 1, "Fix the flaky Login test"),
+                (1, 2, "test login_redirects ... FAILED"),
+                (2, 2, "100% LOGIN coverage"),
+            ]
+        );
+        assert!(matches.iter().all(|m| !m.entry_id.is_empty()));
+
+        let scoped = db
+            .search_conversations("p", "w", "login", Some(2), 200)
+            .unwrap();
+        assert_eq!(scoped.len(), 1);
+        assert_eq!(scoped[0].thread_id, 2);
+
+        let capped = db.search_conversations("p", "w", "login", None, 1).unwrap();
+        assert_eq!(capped.len(), 1);
+
+        // `%` is matched literally rather than as a LIKE wildcard.
+        let literal = db.search_conversations("p", "w", "0%", None, 200).unwrap();
+        assert_eq!(literal.len(), 1);
+        assert_eq!(literal[0].snippet, "100% LOGIN coverage");
+
+        // Entry metadata such as the item type is not searchable.
+        assert!(
+            db.search_conversations("p", "w", "command_execution", None, 200)
+                .unwrap()
+                .is_empty()
+        );
+        assert!(
+            db.search_conversations("p", "w", "   ", None, 200)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn turn_started_markers_survive_reload() {
+        let path = temp_db_path("turn_started_markers_survive_reload");
+        let mut db = open_db(&path);
+
+        db.ensure_conversation("p", "w", 1).unwrap();
+        db.append_conversation_entries(
+            "p",
+            "w",
+            1,
+            &[
+                ConversationEntry::AgentEvent {
+                    entry_id: String::new(),
+                    created_at_unix_ms: 1_700_000_000_000,
+                    runner: None,
+                    event: luban_domain::AgentEvent::TurnStarted {
+                        at_unix_ms: 1_700_000_000_000,
+                    },
+                },
+                ConversationEntry::AgentEvent {
+                    entry_id: String::new(),
+                    created_at_unix_ms: 0,
+                    runner: None,
+                    event: luban_domain::AgentEvent::TurnDuration { duration_ms: 2_500 },
+                },
+            ],
+        )
+        .unwrap();
+        drop(db);
+
+        let mut db = open_db(&path);
+        let page = db.load_conversation_page("p", "w", 1, None, 10).unwrap();
+        let timing = page
+            .entries
+            .iter()
+            .filter_map(|entry| match entry {
+                ConversationEntry::AgentEvent {
+                    event: luban_domain::AgentEvent::TurnStarted { at_unix_ms },
+                    ..
+                } => Some(("started", *at_unix_ms)),
+                ConversationEntry::AgentEvent {
+                    event: luban_domain::AgentEvent::TurnDuration { duration_ms },
+                    ..
+                } => Some(("duration", *duration_ms)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            timing,
+            vec![("started", 1_700_000_000_000), ("duration", 2_500)]
+        );
+    }
+
     #[test]
     fn conversation_run_timing_round_trip() {
         let path = temp_db_path("conversation_run_timing_round_trip");
@@ -5233,6 +7728,9 @@ mod tests {
             "gpt-5.3-codex",
             ThinkingEffort::High,
             None,
+            None,
+            None,
+            false,
         )
         .unwrap();
 
@@ -5241,12 +7739,31 @@ mod tests {
         assert_eq!(snapshot.agent_model_id.as_deref(), Some("gpt-5.3-codex"));
         assert_eq!(snapshot.thinking_effort, Some(ThinkingEffort::High));
         assert_eq!(snapshot.amp_mode, None);
+        assert_eq!(snapshot.claude_max_output_tokens, None);
+        assert!(!snapshot.auto_commit);
+
+        db.save_conversation_run_config(
+            "p",
+            "w",
+            1,
+            luban_domain::AgentRunnerKind::Claude,
+            "claude-opus-4-6",
+            ThinkingEffort::High,
+            None,
+            Some(8000),
+            Some("Be terse."),
+            true,
+        )
+        .unwrap();
 
         let snapshot = db.load_conversation_page("p", "w", 1, None, 10).unwrap();
-        assert_eq!(snapshot.runner, Some(luban_domain::AgentRunnerKind::Codex));
-        assert_eq!(snapshot.agent_model_id.as_deref(), Some("gpt-5.3-codex"));
+        assert_eq!(snapshot.runner, Some(luban_domain::AgentRunnerKind::Claude));
+        assert_eq!(snapshot.agent_model_id.as_deref(), Some("claude-opus-4-6"));
         assert_eq!(snapshot.thinking_effort, Some(ThinkingEffort::High));
         assert_eq!(snapshot.amp_mode, None);
+        assert_eq!(snapshot.claude_max_output_tokens, Some(8000));
+        assert_eq!(snapshot.claude_system_prompt.as_deref(), Some("Be terse."));
+        assert!(snapshot.auto_commit);
     }
 
     #[test]
@@ -5290,6 +7807,166 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn conversation_prune_keeps_recent_entries_behind_a_marker() {
+        let path = temp_db_path("conversation_prune_keeps_recent_entries_behind_a_marker");
+        let mut db = open_db(&path);
+
+        db.ensure_conversation("p", "w", 1).unwrap();
+        for idx in 0..10_u64 {
+            let entry = ConversationEntry::AgentEvent {
+                entry_id: String::new(),
+                created_at_unix_ms: 0,
+                runner: None,
+                event: luban_domain::AgentEvent::TurnDuration { duration_ms: idx },
+            };
+            db.append_conversation_entries("p", "w", 1, std::slice::from_ref(&entry))
+                .unwrap();
+        }
+        let now = now_unix_seconds() as u64;
+
+        db.conn
+            .execute(
+                "UPDATE conversations SET run_started_at_unix_ms = 1, run_finished_at_unix_ms = NULL
+                 WHERE project_slug = 'p' AND workspace_name = 'w' AND thread_local_id = 1",
+                [],
+            )
+            .unwrap();
+        let keep_four = ConversationRetentionPolicy {
+            keep_last_entries: Some(4),
+            max_age_days: None,
+        };
+        assert_eq!(
+            db.prune_conversation_entries("p", "w", 1, keep_four, now)
+                .unwrap(),
+            0
+        );
+        db.conn
+            .execute(
+                "UPDATE conversations SET run_finished_at_unix_ms = 2
+                 WHERE project_slug = 'p' AND workspace_name = 'w' AND thread_local_id = 1",
+                [],
+            )
+            .unwrap();
+
+        assert_eq!(
+            db.prune_conversation_entries("p", "w", 1, keep_four, now)
+                .unwrap(),
+            7
+        );
+        let snapshot = db.load_conversation_page("p", "w", 1, None, 100).unwrap();
+        assert_eq!(snapshot.entries_total, 5);
+        assert!(matches!(
+            &snapshot.entries[..],
+            [
+                ConversationEntry::SystemEvent {
+                    event: luban_domain::ConversationSystemEvent::HistoryPruned {
+                        pruned_entries: 7
+                    },
+                    ..
+                },
+                ConversationEntry::AgentEvent {
+                    event: luban_domain::AgentEvent::TurnDuration { duration_ms: 6 },
+                    ..
+                },
+                ..,
+                ConversationEntry::AgentEvent {
+                    event: luban_domain::AgentEvent::TurnDuration { duration_ms: 9 },
+                    ..
+                }
+            ]
+        ));
+        assert_eq!(
+            db.prune_conversation_entries("p", "w", 1, keep_four, now)
+                .unwrap(),
+            0
+        );
+
+        // Age out everything but the newest entry; the marker accumulates the total.
+        db.conn
+            .execute(
+                "UPDATE conversation_entries SET created_at = ?1
+                 WHERE project_slug = 'p' AND workspace_name = 'w' AND thread_local_id = 1
+                   AND seq < 5",
+                params![(now - 10 * 86_400) as i64],
+            )
+            .unwrap();
+        let seven_days = ConversationRetentionPolicy {
+            keep_last_entries: None,
+            max_age_days: Some(7),
+        };
+        assert_eq!(
+            db.prune_conversation_entries("p", "w", 1, seven_days, now)
+                .unwrap(),
+            3
+        );
+        let snapshot = db.load_conversation_page("p", "w", 1, None, 100).unwrap();
+        assert_eq!(snapshot.entries_total, 2);
+        assert!(matches!(
+            &snapshot.entries[..],
+            [
+                ConversationEntry::SystemEvent {
+                    event: luban_domain::ConversationSystemEvent::HistoryPruned {
+                        pruned_entries: 10
+                    },
+                    ..
+                },
+                ConversationEntry::AgentEvent {
+                    event: luban_domain::AgentEvent::TurnDuration { duration_ms: 9 },
+                    ..
+                }
+            ]
+        ));
+    }
+
+    #[test]
+    fn conversation_appends_after_a_prune_are_all_stored() {
+        let path = temp_db_path("conversation_appends_after_a_prune_are_all_stored");
+        let mut db = open_db(&path);
+        let append = |db: &mut SqliteDatabase, duration_ms: u64| {
+            let entry = ConversationEntry::AgentEvent {
+                entry_id: String::new(),
+                created_at_unix_ms: 0,
+                runner: None,
+                event: luban_domain::AgentEvent::TurnDuration { duration_ms },
+            };
+            db.append_conversation_entries("p", "w", 1, std::slice::from_ref(&entry))
+                .unwrap();
+        };
+
+        db.ensure_conversation("p", "w", 1).unwrap();
+        for idx in 0..10_u64 {
+            append(&mut db, idx);
+        }
+        let keep_four = ConversationRetentionPolicy {
+            keep_last_entries: Some(4),
+            max_age_days: None,
+        };
+        let now = now_unix_seconds() as u64;
+        assert!(
+            db.prune_conversation_entries("p", "w", 1, keep_four, now)
+                .unwrap()
+                > 0
+        );
+        for idx in 10..16_u64 {
+            append(&mut db, idx);
+        }
+
+        let snapshot = db.load_conversation_page("p", "w", 1, None, 100).unwrap();
+        let durations = snapshot
+            .entries
+            .iter()
+            .filter_map(|entry| match entry {
+                ConversationEntry::AgentEvent {
+                    event: luban_domain::AgentEvent::TurnDuration { duration_ms },
+                    ..
+                } => Some(*duration_ms),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(durations, (6..16).collect::<Vec<_>>());
+    }
+
     #[test]
     fn conversation_append_allows_same_raw_id_across_turns_when_scoped() {
         let path = temp_db_path("conversation_append_allows_same_raw_id_across_turns_when_scoped");
@@ -5303,6 +7980,15 @@ mod tests {
                 path: PathBuf::from("/tmp/p"),
                 is_git: true,
                 expanded: false,
+                system_prompt: None,
+                worktree_root: None,
+                branch_name_template: None,
+                status: ProjectStatus::Active,
+                agent_defaults: Default::default(),
+                saved_commands: Vec::new(),
+                agent_env: Vec::new(),
+                pr_remote: None,
+                pr_base_branch: None,
                 workspaces: vec![PersistedWorkspace {
                     id: 2,
                     workspace_name: "w".to_owned(),
@@ -5322,6 +8008,9 @@ mod tests {
             appearance_terminal_font: None,
             agent_default_model_id: None,
             agent_runner_default_models: HashMap::new(),
+            agent_runner_prompt_prefixes: HashMap::new(),
+            agent_runner_prompt_suffixes: HashMap::new(),
+            model_thinking_effort_caps: HashMap::new(),
             agent_default_thinking_effort: None,
             agent_default_runner: None,
             agent_amp_mode: None,
@@ -5332,6 +8021,7 @@ mod tests {
             last_open_workspace_id: None,
             open_button_selection: None,
             sidebar_project_order: Vec::new(),
+            project_groups: Vec::new(),
             workspace_active_thread_id: HashMap::new(),
             workspace_open_tabs: HashMap::new(),
             workspace_archived_tabs: HashMap::new(),
@@ -5345,8 +8035,16 @@ mod tests {
             telegram_enabled: None,
             telegram_bot_token: None,
             telegram_bot_username: None,
-            telegram_paired_chat_id: None,
+            telegram_paired_chat_ids: Vec::new(),
             telegram_topic_bindings: None,
+            webhook_url: None,
+            webhook_secret: None,
+            agent_merge_reasoning_entries: None,
+            agent_pause_queue_on_patch_failure: None,
+            agent_item_dedup_window: None,
+            agent_auto_retry_on_failure: None,
+            agent_command_output_max_bytes: None,
+            auto_archive_after_days: None,
         };
         db.save_app_state(&snapshot).unwrap();
 
@@ -5408,6 +8106,15 @@ mod tests {
                     path: PathBuf::from("/tmp/p1"),
                     is_git: true,
                     expanded: false,
+                    system_prompt: None,
+                    worktree_root: None,
+                    branch_name_template: None,
+                    status: ProjectStatus::Active,
+                    agent_defaults: Default::default(),
+                    saved_commands: Vec::new(),
+                    agent_env: Vec::new(),
+                    pr_remote: None,
+                    pr_base_branch: None,
                     workspaces: vec![PersistedWorkspace {
                         id: 10,
                         workspace_name: "w1".to_owned(),
@@ -5424,6 +8131,15 @@ mod tests {
                     path: PathBuf::from("/tmp/p2"),
                     is_git: true,
                     expanded: false,
+                    system_prompt: None,
+                    worktree_root: None,
+                    branch_name_template: None,
+                    status: ProjectStatus::Active,
+                    agent_defaults: Default::default(),
+                    saved_commands: Vec::new(),
+                    agent_env: Vec::new(),
+                    pr_remote: None,
+                    pr_base_branch: None,
                     workspaces: vec![PersistedWorkspace {
                         id: 20,
                         workspace_name: "w".to_owned(),
@@ -5444,6 +8160,9 @@ mod tests {
             appearance_terminal_font: None,
             agent_default_model_id: None,
             agent_runner_default_models: HashMap::new(),
+            agent_runner_prompt_prefixes: HashMap::new(),
+            agent_runner_prompt_suffixes: HashMap::new(),
+            model_thinking_effort_caps: HashMap::new(),
             agent_default_thinking_effort: None,
             agent_default_runner: None,
             agent_amp_mode: None,
@@ -5454,6 +8173,7 @@ mod tests {
             last_open_workspace_id: None,
             open_button_selection: None,
             sidebar_project_order: Vec::new(),
+            project_groups: Vec::new(),
             workspace_active_thread_id: HashMap::new(),
             workspace_open_tabs: HashMap::new(),
             workspace_archived_tabs: HashMap::new(),
@@ -5467,8 +8187,16 @@ mod tests {
             telegram_enabled: None,
             telegram_bot_token: None,
             telegram_bot_username: None,
-            telegram_paired_chat_id: None,
+            telegram_paired_chat_ids: Vec::new(),
             telegram_topic_bindings: None,
+            webhook_url: None,
+            webhook_secret: None,
+            agent_merge_reasoning_entries: None,
+            agent_pause_queue_on_patch_failure: None,
+            agent_item_dedup_window: None,
+            agent_auto_retry_on_failure: None,
+            agent_command_output_max_bytes: None,
+            auto_archive_after_days: None,
         };
 
         db.save_app_state(&snapshot_before).unwrap();
@@ -5493,6 +8221,15 @@ mod tests {
                 path: PathBuf::from("/tmp/p1"),
                 is_git: true,
                 expanded: false,
+                system_prompt: None,
+                worktree_root: None,
+                branch_name_template: None,
+                status: ProjectStatus::Active,
+                agent_defaults: Default::default(),
+                saved_commands: Vec::new(),
+                agent_env: Vec::new(),
+                pr_remote: None,
+                pr_base_branch: None,
                 workspaces: vec![
                     PersistedWorkspace {
                         id: 10,
@@ -5522,6 +8259,9 @@ mod tests {
             appearance_terminal_font: None,
             agent_default_model_id: None,
             agent_runner_default_models: HashMap::new(),
+            agent_runner_prompt_prefixes: HashMap::new(),
+            agent_runner_prompt_suffixes: HashMap::new(),
+            model_thinking_effort_caps: HashMap::new(),
             agent_default_thinking_effort: None,
             agent_default_runner: None,
             agent_amp_mode: None,
@@ -5532,6 +8272,7 @@ mod tests {
             last_open_workspace_id: None,
             open_button_selection: None,
             sidebar_project_order: Vec::new(),
+            project_groups: Vec::new(),
             workspace_active_thread_id: HashMap::new(),
             workspace_open_tabs: HashMap::new(),
             workspace_archived_tabs: HashMap::new(),
@@ -5545,8 +8286,16 @@ mod tests {
             telegram_enabled: None,
             telegram_bot_token: None,
             telegram_bot_username: None,
-            telegram_paired_chat_id: None,
+            telegram_paired_chat_ids: Vec::new(),
             telegram_topic_bindings: None,
+            webhook_url: None,
+            webhook_secret: None,
+            agent_merge_reasoning_entries: None,
+            agent_pause_queue_on_patch_failure: None,
+            agent_item_dedup_window: None,
+            agent_auto_retry_on_failure: None,
+            agent_command_output_max_bytes: None,
+            auto_archive_after_days: None,
         };
 
         db.save_app_state(&snapshot_after).unwrap();
@@ -5576,6 +8325,15 @@ mod tests {
                 path: PathBuf::from("/tmp/p"),
                 is_git: true,
                 expanded: false,
+                system_prompt: None,
+                worktree_root: None,
+                branch_name_template: None,
+                status: ProjectStatus::Active,
+                agent_defaults: Default::default(),
+                saved_commands: Vec::new(),
+                agent_env: Vec::new(),
+                pr_remote: None,
+                pr_base_branch: None,
                 workspaces: vec![PersistedWorkspace {
                     id: 2,
                     workspace_name: "w".to_owned(),
@@ -5595,6 +8353,9 @@ mod tests {
             appearance_terminal_font: None,
             agent_default_model_id: None,
             agent_runner_default_models: HashMap::new(),
+            agent_runner_prompt_prefixes: HashMap::new(),
+            agent_runner_prompt_suffixes: HashMap::new(),
+            model_thinking_effort_caps: HashMap::new(),
             agent_default_thinking_effort: None,
             agent_default_runner: None,
             agent_amp_mode: None,
@@ -5605,6 +8366,7 @@ mod tests {
             last_open_workspace_id: None,
             open_button_selection: None,
             sidebar_project_order: Vec::new(),
+            project_groups: Vec::new(),
             workspace_active_thread_id: HashMap::new(),
             workspace_open_tabs: HashMap::new(),
             workspace_archived_tabs: HashMap::new(),
@@ -5618,8 +8380,16 @@ mod tests {
             telegram_enabled: None,
             telegram_bot_token: None,
             telegram_bot_username: None,
-            telegram_paired_chat_id: None,
+            telegram_paired_chat_ids: Vec::new(),
             telegram_topic_bindings: None,
+            webhook_url: None,
+            webhook_secret: None,
+            agent_merge_reasoning_entries: None,
+            agent_pause_queue_on_patch_failure: None,
+            agent_item_dedup_window: None,
+            agent_auto_retry_on_failure: None,
+            agent_command_output_max_bytes: None,
+            auto_archive_after_days: None,
         };
 
         db.save_app_state(&snapshot).unwrap();
@@ -5657,6 +8427,9 @@ mod tests {
             appearance_terminal_font: None,
             agent_default_model_id: None,
             agent_runner_default_models: HashMap::new(),
+            agent_runner_prompt_prefixes: HashMap::new(),
+            agent_runner_prompt_suffixes: HashMap::new(),
+            model_thinking_effort_caps: HashMap::new(),
             agent_default_thinking_effort: None,
             agent_default_runner: None,
             agent_amp_mode: None,
@@ -5667,6 +8440,7 @@ mod tests {
             last_open_workspace_id: None,
             open_button_selection: None,
             sidebar_project_order: Vec::new(),
+            project_groups: Vec::new(),
             workspace_active_thread_id: HashMap::new(),
             workspace_open_tabs: HashMap::new(),
             workspace_archived_tabs: HashMap::new(),
@@ -5680,8 +8454,16 @@ mod tests {
             telegram_enabled: None,
             telegram_bot_token: None,
             telegram_bot_username: None,
-            telegram_paired_chat_id: None,
+            telegram_paired_chat_ids: Vec::new(),
             telegram_topic_bindings: None,
+            webhook_url: None,
+            webhook_secret: None,
+            agent_merge_reasoning_entries: None,
+            agent_pause_queue_on_patch_failure: None,
+            agent_item_dedup_window: None,
+            agent_auto_retry_on_failure: None,
+            agent_command_output_max_bytes: None,
+            auto_archive_after_days: None,
         };
         db.save_app_state(&empty).unwrap();
 
@@ -73,6 +73,7 @@ async fn ui(addr: Option<SocketAddr>, no_open: bool) -> anyhow::Result<()> {
                 mode: luban_server::AuthMode::SingleUser,
                 bootstrap_token: Some(token.clone()),
             },
+            ..luban_server::ServerConfig::from_env()
         },
     )
     .await?;
@@ -1,8 +1,8 @@
 use crate::{
-    AgentRunnerKind, AgentThreadEvent, AppearanceTheme, AttachmentRef, ChatScrollAnchor,
-    ContextTokenKind, ConversationSnapshot, ConversationThreadMeta, OpenTarget, PersistedAppState,
-    ProjectId, SystemTaskKind, TaskIntentKind, TaskStatus, ThinkingEffort, WorkspaceId,
-    WorkspaceThreadId,
+    AgentRunConfig, AgentRunnerKind, AgentThreadEvent, AppearanceTheme, AttachmentRef,
+    ChatScrollAnchor, ContextTokenKind, ConversationSnapshot, ConversationThreadMeta, MainPane,
+    OpenTarget, PersistedAppState, ProjectAgentDefaults, ProjectGroupId, ProjectId, SystemTaskKind,
+    TabDirection, TaskIntentKind, TaskStatus, ThinkingEffort, WorkspaceId, WorkspaceThreadId,
 };
 use std::collections::HashMap;
 use std::path::PathBuf;
@@ -16,6 +16,12 @@ pub enum Action {
         workspace_id: WorkspaceId,
     },
     DashboardPreviewClosed,
+    /// Clears the unread turn completion badge of one workspace.
+    MarkWorkspaceRead {
+        workspace_id: WorkspaceId,
+    },
+    /// Clears the unread turn completion badges of every workspace.
+    MarkAllRead,
 
     AddProject {
         path: PathBuf,
@@ -24,17 +30,74 @@ pub enum Action {
     ToggleProjectExpanded {
         project_id: ProjectId,
     },
+    ProjectSystemPromptChanged {
+        project_id: ProjectId,
+        system_prompt: Option<String>,
+    },
+    SetProjectWorktreeRoot {
+        project_id: ProjectId,
+        path: Option<PathBuf>,
+    },
+    /// Sets the template new branch names of the project are rendered from; a blank template
+    /// restores the default `luban/{name}`.
+    SetProjectBranchNameTemplate {
+        project_id: ProjectId,
+        template: Option<String>,
+    },
+    SetProjectAgentEnv {
+        project_id: ProjectId,
+        env: Vec<(String, String)>,
+    },
+    /// Sets the git remote and base branch pull requests of the project are looked up with;
+    /// blank values restore `gh`'s defaults.
+    SetProjectPullRequestTarget {
+        project_id: ProjectId,
+        remote: Option<String>,
+        base_branch: Option<String>,
+    },
+    SetProjectAgentDefaults {
+        project_id: ProjectId,
+        defaults: ProjectAgentDefaults,
+    },
+    /// Saves a named command for the project; a label already in use gets a numeric suffix.
+    AddProjectSavedCommand {
+        project_id: ProjectId,
+        label: String,
+        command: String,
+    },
+    RemoveProjectSavedCommand {
+        project_id: ProjectId,
+        id: u64,
+    },
+    ProjectDefaultBranchDetected {
+        project_id: ProjectId,
+        default_branch: Option<String>,
+    },
     DeleteProject {
         project_id: ProjectId,
     },
+    ArchiveProject {
+        project_id: ProjectId,
+    },
+    RestoreProject {
+        project_id: ProjectId,
+    },
     OpenProjectSettings {
         project_id: ProjectId,
     },
+    /// Switches the main pane directly, including back to `MainPane::None` (home).
+    SetMainPane {
+        pane: MainPane,
+    },
 
     CreateWorkspace {
         project_id: ProjectId,
         branch_name_hint: Option<String>,
     },
+    CreateWorkspaceFromBranch {
+        project_id: ProjectId,
+        branch_name: String,
+    },
     EnsureMainWorkspace {
         project_id: ProjectId,
     },
@@ -108,12 +171,43 @@ pub enum Action {
         workspace_id: WorkspaceId,
         message: String,
     },
+    WorkspaceStashChanges {
+        workspace_id: WorkspaceId,
+    },
+    WorkspaceStashPop {
+        workspace_id: WorkspaceId,
+    },
+    WorkspaceStashFinished {
+        workspace_id: WorkspaceId,
+    },
+    WorkspaceStashFailed {
+        workspace_id: WorkspaceId,
+        message: String,
+    },
+    WorkspaceCommit {
+        workspace_id: WorkspaceId,
+        message: String,
+        paths: Option<Vec<String>>,
+    },
+    WorkspaceCommitFinished {
+        workspace_id: WorkspaceId,
+    },
+    WorkspaceCommitFailed {
+        workspace_id: WorkspaceId,
+        message: String,
+    },
 
     ConversationLoaded {
         workspace_id: WorkspaceId,
         thread_id: WorkspaceThreadId,
         snapshot: ConversationSnapshot,
     },
+    /// Stored history was pruned by the retention policy; `snapshot` is the reloaded tail.
+    ConversationHistoryPruned {
+        workspace_id: WorkspaceId,
+        thread_id: WorkspaceThreadId,
+        snapshot: ConversationSnapshot,
+    },
     ConversationLoadFailed {
         workspace_id: WorkspaceId,
         thread_id: WorkspaceThreadId,
@@ -166,6 +260,19 @@ pub enum Action {
         thread_id: WorkspaceThreadId,
         amp_mode: String,
     },
+    /// Sets the Claude-only run options; `None` (or a blank prompt / zero cap) clears each one.
+    ChatClaudeOptionsChanged {
+        workspace_id: WorkspaceId,
+        thread_id: WorkspaceThreadId,
+        max_output_tokens: Option<u32>,
+        system_prompt: Option<String>,
+    },
+    /// Toggles committing the worktree after each successfully completed turn.
+    ChatAutoCommitChanged {
+        workspace_id: WorkspaceId,
+        thread_id: WorkspaceThreadId,
+        enabled: bool,
+    },
     ThinkingEffortChanged {
         workspace_id: WorkspaceId,
         thread_id: WorkspaceThreadId,
@@ -176,6 +283,42 @@ pub enum Action {
         thread_id: WorkspaceThreadId,
         text: String,
     },
+    ThreadNotesChanged {
+        workspace_id: WorkspaceId,
+        thread_id: WorkspaceThreadId,
+        notes: String,
+    },
+    /// The latest notes edit was persisted.
+    ThreadNotesSaved {
+        workspace_id: WorkspaceId,
+        thread_id: WorkspaceThreadId,
+    },
+    /// Sets the worktree-relative directory the thread's agent runs in (`None` for the root).
+    ThreadCwdSubpathChanged {
+        workspace_id: WorkspaceId,
+        thread_id: WorkspaceThreadId,
+        cwd_subpath: Option<String>,
+    },
+    /// Anchors a comment to one line of a file changed by a `FileChange` entry.
+    AddHunkComment {
+        workspace_id: WorkspaceId,
+        thread_id: WorkspaceThreadId,
+        entry_id: String,
+        path: String,
+        side: crate::HunkLineSide,
+        line: u64,
+        text: String,
+    },
+    /// Records an agent-written summary of `start_entry_id..=end_entry_id` and collapses that
+    /// range behind it.
+    ConversationCompacted {
+        workspace_id: WorkspaceId,
+        thread_id: WorkspaceThreadId,
+        start_entry_id: String,
+        end_entry_id: String,
+        entry_count: u64,
+        summary_markdown: String,
+    },
     ChatDraftAttachmentAdded {
         workspace_id: WorkspaceId,
         thread_id: WorkspaceThreadId,
@@ -199,6 +342,9 @@ pub enum Action {
         thread_id: WorkspaceThreadId,
         id: u64,
     },
+    /// Removes the queued prompt whose `QueuedPrompt::id` is `prompt_id`. Ids come from a
+    /// per-conversation counter rather than queue positions, so removal stays correct after
+    /// reorders and earlier removals.
     RemoveQueuedPrompt {
         workspace_id: WorkspaceId,
         thread_id: WorkspaceThreadId,
@@ -218,6 +364,8 @@ pub enum Action {
         attachments: Vec<AttachmentRef>,
         model_id: String,
         thinking_effort: ThinkingEffort,
+        runner: Option<crate::AgentRunnerKind>,
+        amp_mode: Option<String>,
     },
     ClearQueuedPrompts {
         workspace_id: WorkspaceId,
@@ -227,6 +375,18 @@ pub enum Action {
         workspace_id: WorkspaceId,
         thread_id: WorkspaceThreadId,
     },
+    /// Re-run the most recent user message as a new turn with a different run config.
+    ///
+    /// Rejected while a turn is running unless `cancel_first` is set, in which case the running
+    /// turn is canceled before the retry starts.
+    RetryLastTurnWithRunner {
+        workspace_id: WorkspaceId,
+        thread_id: WorkspaceThreadId,
+        runner: AgentRunnerKind,
+        model_id: String,
+        amp_mode: Option<String>,
+        cancel_first: bool,
+    },
     AgentEventReceived {
         workspace_id: WorkspaceId,
         thread_id: WorkspaceThreadId,
@@ -250,14 +410,55 @@ pub enum Action {
         thread_id: WorkspaceThreadId,
         run_id: u64,
     },
+    /// The backoff before automatic retry `attempt` elapsed; stale if the task moved on since.
+    AgentTurnRetryDue {
+        workspace_id: WorkspaceId,
+        thread_id: WorkspaceThreadId,
+        attempt: u8,
+        run_config: AgentRunConfig,
+    },
     CancelAgentTurn {
         workspace_id: WorkspaceId,
         thread_id: WorkspaceThreadId,
     },
+    /// Approves or denies a tool call the running turn is blocked on.
+    RespondToToolApproval {
+        workspace_id: WorkspaceId,
+        thread_id: WorkspaceThreadId,
+        call_id: String,
+        approved: bool,
+    },
+    /// The runner could not take the approval; the call goes back to pending.
+    ToolApprovalDeliveryFailed {
+        workspace_id: WorkspaceId,
+        thread_id: WorkspaceThreadId,
+        run_id: u64,
+        call_id: String,
+        message: String,
+    },
+    /// Cancels the running turn (if any) and drops every queued prompt behind it.
+    CancelAndClearQueue {
+        workspace_id: WorkspaceId,
+        thread_id: WorkspaceThreadId,
+    },
 
     CreateWorkspaceThread {
         workspace_id: WorkspaceId,
     },
+    DuplicateWorkspaceThread {
+        workspace_id: WorkspaceId,
+        thread_id: WorkspaceThreadId,
+        up_to_entry_id: Option<String>,
+    },
+    WorkspaceThreadDuplicated {
+        workspace_id: WorkspaceId,
+        thread_id: WorkspaceThreadId,
+    },
+    WorkspaceThreadDuplicateFailed {
+        workspace_id: WorkspaceId,
+        thread_id: WorkspaceThreadId,
+        message: String,
+    },
     ActivateWorkspaceThread {
         workspace_id: WorkspaceId,
         thread_id: WorkspaceThreadId,
@@ -275,6 +476,10 @@ pub enum Action {
         thread_id: WorkspaceThreadId,
         to_index: usize,
     },
+    ActivateAdjacentWorkspaceThread {
+        workspace_id: WorkspaceId,
+        direction: TabDirection,
+    },
 
     WorkspaceThreadsLoaded {
         workspace_id: WorkspaceId,
@@ -293,6 +498,15 @@ pub enum Action {
         workspace_id: WorkspaceId,
         thread_ids: Vec<WorkspaceThreadId>,
     },
+    /// A thread was moved in persistence to `to_thread_id` in another workspace of the same
+    /// project; carries its in-memory state and tab over. The source workspace's active tab falls
+    /// back to another tab when it was the moved one.
+    WorkspaceThreadMoved {
+        from_workspace_id: WorkspaceId,
+        thread_id: WorkspaceThreadId,
+        to_workspace_id: WorkspaceId,
+        to_thread_id: WorkspaceThreadId,
+    },
 
     ToggleTerminalPane,
     TerminalPaneWidthChanged {
@@ -325,6 +539,37 @@ pub enum Action {
     AgentDroidEnabledChanged {
         enabled: bool,
     },
+    AgentMergeReasoningEntriesChanged {
+        enabled: bool,
+    },
+    AgentPauseQueueOnPatchFailureChanged {
+        enabled: bool,
+    },
+    AgentItemDedupWindowChanged {
+        window: usize,
+    },
+    AgentAutoRetryOnFailureChanged {
+        retries: u8,
+    },
+    AgentCommandOutputMaxBytesChanged {
+        max_bytes: usize,
+    },
+    AutoArchiveConfigChanged {
+        days: Option<u32>,
+    },
+    AgentRunnerPromptPrefixChanged {
+        runner: AgentRunnerKind,
+        prefix: String,
+    },
+    AgentRunnerPromptSuffixChanged {
+        runner: AgentRunnerKind,
+        suffix: String,
+    },
+    /// Sets the highest effort `model_id` honours; `None` removes the cap.
+    ModelThinkingEffortCapChanged {
+        model_id: String,
+        cap: Option<ThinkingEffort>,
+    },
     AgentRunnerChanged {
         runner: AgentRunnerKind,
     },
@@ -341,7 +586,10 @@ pub enum Action {
     TelegramChatPaired {
         chat_id: i64,
     },
-    TelegramUnpaired,
+    /// Removes `chat_id` from the paired chats, or every chat when `None`.
+    TelegramUnpaired {
+        chat_id: Option<i64>,
+    },
     TelegramLastErrorSet {
         message: Option<String>,
     },
@@ -355,6 +603,16 @@ pub enum Action {
         message_thread_id: i64,
     },
     TelegramTopicBindingsCleared,
+    /// Configures the webhook notification sink. A `None` secret keeps the stored secret; a blank
+    /// one removes it so deliveries are sent unsigned.
+    WebhookConfigSet {
+        url: String,
+        secret: Option<String>,
+    },
+    WebhookConfigCleared,
+    WebhookLastErrorSet {
+        message: Option<String>,
+    },
     CodexDefaultsLoaded {
         model_id: Option<String>,
         thinking_effort: Option<ThinkingEffort>,
@@ -394,6 +652,17 @@ pub enum Action {
         thread_id: WorkspaceThreadId,
         task_status: TaskStatus,
     },
+    /// Sets the thread title by hand; the title is trimmed and must not be empty.
+    RenameThread {
+        workspace_id: WorkspaceId,
+        thread_id: WorkspaceThreadId,
+        title: String,
+    },
+    /// Applies `TaskStatusSet` to each target; targets that would be rejected are skipped.
+    BulkSetTaskStatus {
+        targets: Vec<(WorkspaceId, WorkspaceThreadId)>,
+        task_status: TaskStatus,
+    },
     TaskStatusSuggestionCreated {
         workspace_id: WorkspaceId,
         thread_id: WorkspaceThreadId,
@@ -407,6 +676,23 @@ pub enum Action {
         project_ids: Vec<String>,
     },
 
+    CreateProjectGroup {
+        name: String,
+    },
+    RenameProjectGroup {
+        group_id: ProjectGroupId,
+        name: String,
+    },
+    /// Removes the group; its projects become ungrouped.
+    DeleteProjectGroup {
+        group_id: ProjectGroupId,
+    },
+    /// Moves the project into `group_id`, or out of every group when `None`.
+    AssignProjectToGroup {
+        project_id: ProjectId,
+        group_id: Option<ProjectGroupId>,
+    },
+
     OpenButtonSelectionChanged {
         selection: String,
     },
@@ -425,4 +711,8 @@ pub enum Action {
     },
 
     ClearError,
+    /// Removes one entry from `AppState::error_log` by its position (oldest first).
+    DismissError {
+        index: usize,
+    },
 }
@@ -1,7 +1,7 @@
 use crate::{
-    AgentRunnerKind, AgentThreadEvent, AttachmentRef, ContextItem, ConversationEntry,
-    ConversationSnapshot, ConversationThreadMeta, PersistedAppState, QueuedPrompt, SystemTaskKind,
-    TaskStatus, ThinkingEffort,
+    AgentRunnerKind, AgentThreadEvent, AttachmentRef, ContextDirImport, ContextItem,
+    ConversationEntry, ConversationSnapshot, ConversationThreadMeta, PersistedAppState,
+    QueuedPrompt, SystemTaskKind, TaskStatus, ThinkingEffort,
 };
 use std::collections::HashMap;
 use std::{path::PathBuf, sync::Arc, sync::atomic::AtomicBool};
@@ -41,6 +41,25 @@ pub struct PullRequestInfo {
     pub merge_ready: bool,
 }
 
+/// One conversation entry matching a workspace-wide search.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ConversationSearchMatch {
+    pub thread_id: u64,
+    pub entry_id: String,
+    /// Zero-based position of the entry in its thread, for paging to it.
+    pub entry_index: u64,
+    pub snippet: String,
+}
+
+/// Tip commit of a worktree's checked-out branch.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LastCommitInfo {
+    pub sha: String,
+    pub subject: String,
+    pub author: String,
+    pub unix_time: u64,
+}
+
 #[derive(Clone, Debug)]
 pub struct CreatedWorkspace {
     pub workspace_name: String,
@@ -48,6 +67,64 @@ pub struct CreatedWorkspace {
     pub worktree_path: PathBuf,
 }
 
+/// A project's branch name template along with the project slug its `{slug}` placeholder
+/// expands to. See `Project::branch_name_template`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BranchNameTemplate {
+    pub template: String,
+    pub project_slug: String,
+}
+
+/// Where to look up a workdir's pull request. The default (both `None`) is whatever `gh pr view`
+/// resolves for the checked-out branch. See `Project::pull_request_target`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PullRequestTarget {
+    /// Git remote naming the GitHub repository to query.
+    pub remote: Option<String>,
+    /// Only pull requests into this branch are considered.
+    pub base_branch: Option<String>,
+}
+
+/// How much stored conversation history to keep. Either limit may be unset; with both unset
+/// nothing is pruned.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ConversationRetentionPolicy {
+    /// Keep at most this many of the most recent entries per conversation.
+    pub keep_last_entries: Option<u64>,
+    /// Drop entries created more than this many days ago.
+    pub max_age_days: Option<u64>,
+}
+
+impl ConversationRetentionPolicy {
+    pub fn is_enabled(&self) -> bool {
+        self.keep_last_entries.is_some() || self.max_age_days.is_some()
+    }
+}
+
+/// Drift between a project's `git worktree list` and the worktrees Luban tracks for it.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct WorktreeAudit {
+    /// Worktrees git knows about that no tracked workspace points at.
+    pub orphaned: Vec<PathBuf>,
+    /// Tracked worktree paths that no longer exist on disk.
+    pub dangling: Vec<PathBuf>,
+}
+
+/// Outcome of importing prompt templates from a directory, by template key.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PromptTemplatesImportReport {
+    pub imported: Vec<String>,
+    /// Customized templates left in place because the file differs and `force` was not set.
+    pub conflicts: Vec<String>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ConversationImportLineError {
+    /// 1-based line number within the imported text.
+    pub line: u64,
+    pub message: String,
+}
+
 #[derive(Clone, Debug)]
 pub struct RunAgentTurnRequest {
     pub project_slug: String,
@@ -59,8 +136,28 @@ pub struct RunAgentTurnRequest {
     pub attachments: Vec<AttachmentRef>,
     pub runner: AgentRunnerKind,
     pub amp_mode: Option<String>,
+    /// Claude-only options; ignored by other runners.
+    pub claude_max_output_tokens: Option<u32>,
+    pub claude_system_prompt: Option<String>,
+    /// The project's agent environment variables (see `Project::agent_env`), set on the runner
+    /// process on top of the inherited environment.
+    pub agent_env: Vec<(String, String)>,
     pub model: Option<String>,
     pub model_reasoning_effort: Option<String>,
+    /// Worktree-relative directory to run the agent in; `None` runs at the worktree root.
+    pub cwd_subpath: Option<String>,
+    /// Composed system prompt (see `compose_agent_system_prompt`) sent ahead of `prompt`.
+    pub system_prompt: Option<String>,
+    /// Runner-specific text wrapped around `prompt` (see `apply_runner_prompt_affixes`).
+    pub prompt_prefix: Option<String>,
+    pub prompt_suffix: Option<String>,
+    /// Re-runs the last prompt after a transient failure, so the user message is not stored again.
+    pub retry: bool,
+    /// Cap applied to persisted command output (see `cap_command_output`); `0` disables it.
+    pub command_output_max_bytes: usize,
+    /// Whether consecutive reasoning items are stored as one entry (see
+    /// `merge_reasoning_segment`).
+    pub merge_reasoning_entries: bool,
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
@@ -184,6 +281,9 @@ pub enum OpenTarget {
     Vscode,
     Cursor,
     Zed,
+    Idea,
+    Sublime,
+    Neovim,
     Ghostty,
     Finder,
 }
@@ -268,13 +368,74 @@ pub trait ProjectWorkspaceService: Send + Sync {
 
     fn save_app_state(&self, snapshot: PersistedAppState) -> Result<(), String>;
 
+    /// Resolves the branch the project's `origin` remote treats as its default (e.g. `main`,
+    /// `master`, `develop`). Returns `Ok(None)` when it cannot be determined.
+    fn detect_default_branch(&self, _project_path: PathBuf) -> Result<Option<String>, String> {
+        Ok(None)
+    }
+
+    /// `worktree_root` overrides the directory worktrees are created under; worktrees are
+    /// still namespaced as `<root>/<project_slug>/<workspace_name>`. `branch_name_template`
+    /// replaces the default `luban/<workspace_name>` branch name.
     fn create_workspace(
         &self,
         project_path: PathBuf,
         project_slug: String,
         branch_name_hint: Option<String>,
+        worktree_root: Option<PathBuf>,
+        branch_name_template: Option<BranchNameTemplate>,
     ) -> Result<CreatedWorkspace, String>;
 
+    /// Returns the workspace name, branch and worktree path `create_workspace` would use for the
+    /// same inputs right now, without fetching or creating anything. Without a hint the name is
+    /// random, so pass the proposed name back as the hint to get exactly this workspace.
+    fn preview_workspace(
+        &self,
+        _project_path: PathBuf,
+        _project_slug: String,
+        _branch_name_hint: Option<String>,
+        _worktree_root: Option<PathBuf>,
+        _branch_name_template: Option<BranchNameTemplate>,
+    ) -> Result<CreatedWorkspace, String> {
+        Err("unimplemented".to_owned())
+    }
+
+    /// Adds a worktree that checks out an existing branch (local, or fetched from `origin`)
+    /// instead of creating a new one.
+    fn create_workspace_from_branch(
+        &self,
+        _project_path: PathBuf,
+        _project_slug: String,
+        _branch_name: String,
+        _worktree_root: Option<PathBuf>,
+    ) -> Result<CreatedWorkspace, String> {
+        Err("unimplemented".to_owned())
+    }
+
+    /// Compares the project's linked git worktrees against `tracked_worktree_paths`.
+    fn audit_worktrees(
+        &self,
+        _project_path: PathBuf,
+        _tracked_worktree_paths: Vec<PathBuf>,
+    ) -> Result<WorktreeAudit, String> {
+        Err("unimplemented".to_owned())
+    }
+
+    /// Removes worktrees under `<worktree_root>/<project_slug>` that are not in
+    /// `tracked_worktree_paths` and runs `git worktree prune`. The main checkout and worktrees
+    /// outside that directory are never touched. With `dry_run`, only reports what would be
+    /// removed. Returns the orphaned worktree paths.
+    fn prune_orphaned_worktrees(
+        &self,
+        _project_path: PathBuf,
+        _project_slug: String,
+        _worktree_root: Option<PathBuf>,
+        _tracked_worktree_paths: Vec<PathBuf>,
+        _dry_run: bool,
+    ) -> Result<Vec<PathBuf>, String> {
+        Err("unimplemented".to_owned())
+    }
+
     fn open_workspace_in_ide(&self, worktree_path: PathBuf) -> Result<(), String>;
 
     fn open_workspace_with(
@@ -292,10 +453,13 @@ pub trait ProjectWorkspaceService: Send + Sync {
         branch_name: String,
     ) -> Result<(), String>;
 
+    /// Renames the workspace's branch to `luban/<requested suffix>`, or to the rendered
+    /// `branch_name_template` when one is given.
     fn rename_workspace_branch(
         &self,
         worktree_path: PathBuf,
         requested_branch_name: String,
+        branch_name_template: Option<BranchNameTemplate>,
     ) -> Result<String, String>;
 
     fn ensure_conversation(
@@ -327,6 +491,29 @@ pub trait ProjectWorkspaceService: Send + Sync {
         limit: u64,
     ) -> Result<ConversationSnapshot, String>;
 
+    /// Number of stored entries for the conversation; the in-memory copy may hold only a tail.
+    fn conversation_entries_total(
+        &self,
+        _project_slug: String,
+        _workspace_name: String,
+        _thread_id: u64,
+    ) -> Result<u64, String> {
+        Err("unimplemented".to_owned())
+    }
+
+    /// Deletes stored entries older than `policy` allows, leaving a `HistoryPruned` marker in
+    /// their place. Conversations with a run in flight are skipped. Returns the number removed.
+    fn prune_conversation_entries(
+        &self,
+        _project_slug: String,
+        _workspace_name: String,
+        _thread_id: u64,
+        _policy: ConversationRetentionPolicy,
+        _now_unix_seconds: u64,
+    ) -> Result<u64, String> {
+        Ok(0)
+    }
+
     fn append_conversation_entries(
         &self,
         _project_slug: String,
@@ -337,6 +524,55 @@ pub trait ProjectWorkspaceService: Send + Sync {
         Err("unimplemented".to_owned())
     }
 
+    /// Creates `thread_id` and appends `entries` parsed from an exported conversation.
+    fn import_conversation_entries(
+        &self,
+        _project_slug: String,
+        _workspace_name: String,
+        _thread_id: u64,
+        _entries: Vec<ConversationEntry>,
+    ) -> Result<(), String> {
+        Err("unimplemented".to_owned())
+    }
+
+    /// Seeds `thread_id` with the history of `source_thread_id` and titles it "Copy of <title>".
+    /// Without `up_to_entry_id` only user messages are copied; otherwise every entry up to and
+    /// including that entry is.
+    fn duplicate_conversation(
+        &self,
+        _project_slug: String,
+        _workspace_name: String,
+        _source_thread_id: u64,
+        _thread_id: u64,
+        _up_to_entry_id: Option<String>,
+    ) -> Result<(), String> {
+        Err("unimplemented".to_owned())
+    }
+
+    /// Past user prompts of a thread, newest first, with consecutive repeats collapsed.
+    fn load_prompt_history(
+        &self,
+        _project_slug: String,
+        _workspace_name: String,
+        _thread_id: u64,
+        _limit: usize,
+    ) -> Result<Vec<String>, String> {
+        Err("unimplemented".to_owned())
+    }
+
+    /// Entries whose message text, command or command output contains `query`
+    /// (case-insensitive), across all threads of the workspace or only `thread_id`.
+    fn search_conversations(
+        &self,
+        _project_slug: String,
+        _workspace_name: String,
+        _query: String,
+        _thread_id: Option<u64>,
+        _limit: usize,
+    ) -> Result<Vec<ConversationSearchMatch>, String> {
+        Err("unimplemented".to_owned())
+    }
+
     fn delete_conversation_thread(
         &self,
         _project_slug: String,
@@ -346,6 +582,19 @@ pub trait ProjectWorkspaceService: Send + Sync {
         Err("unimplemented".to_owned())
     }
 
+    /// Moves a thread with its history and queue to `to_thread_id` in another workspace of the
+    /// same project.
+    fn move_conversation_thread(
+        &self,
+        _project_slug: String,
+        _workspace_name: String,
+        _thread_id: u64,
+        _to_workspace_name: String,
+        _to_thread_id: u64,
+    ) -> Result<(), String> {
+        Err("unimplemented".to_owned())
+    }
+
     #[allow(clippy::too_many_arguments)]
     fn save_conversation_queue_state(
         &self,
@@ -370,6 +619,9 @@ pub trait ProjectWorkspaceService: Send + Sync {
         _model_id: String,
         _thinking_effort: ThinkingEffort,
         _amp_mode: Option<String>,
+        _claude_max_output_tokens: Option<u32>,
+        _claude_system_prompt: Option<String>,
+        _auto_commit: bool,
     ) -> Result<(), String> {
         Ok(())
     }
@@ -384,6 +636,56 @@ pub trait ProjectWorkspaceService: Send + Sync {
         Ok(())
     }
 
+    fn save_conversation_notes(
+        &self,
+        _project_slug: String,
+        _workspace_name: String,
+        _thread_id: u64,
+        _notes: String,
+    ) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn save_conversation_draft(
+        &self,
+        _project_slug: String,
+        _workspace_name: String,
+        _thread_id: u64,
+        _draft: String,
+    ) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn save_conversation_cwd_subpath(
+        &self,
+        _project_slug: String,
+        _workspace_name: String,
+        _thread_id: u64,
+        _cwd_subpath: Option<String>,
+    ) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn save_conversation_hunk_comment(
+        &self,
+        _project_slug: String,
+        _workspace_name: String,
+        _thread_id: u64,
+        _comment: crate::HunkComment,
+    ) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn save_conversation_collapsed_range(
+        &self,
+        _project_slug: String,
+        _workspace_name: String,
+        _thread_id: u64,
+        _range: crate::CollapsedRange,
+    ) -> Result<(), String> {
+        Ok(())
+    }
+
     fn save_conversation_task_status_last_analyzed(
         &self,
         _project_slug: String,
@@ -420,6 +722,18 @@ pub trait ProjectWorkspaceService: Send + Sync {
         image: ContextImage,
     ) -> Result<AttachmentRef, String>;
 
+    /// Renders a small PNG preview of a stored image attachment. Returns `Ok(None)` when the
+    /// blob cannot be decoded as an image.
+    fn context_image_preview_png(
+        &self,
+        _project_slug: String,
+        _workspace_name: String,
+        _attachment_id: String,
+        _extension: String,
+    ) -> Result<Option<Vec<u8>>, String> {
+        Ok(None)
+    }
+
     fn store_context_text(
         &self,
         project_slug: String,
@@ -456,6 +770,21 @@ pub trait ProjectWorkspaceService: Send + Sync {
         context_id: u64,
     ) -> Result<(), String>;
 
+    /// Recursively imports files under `dir` as context attachments. Paths relative to `dir`
+    /// must match one of `include` (or `include` is empty) and none of `exclude`; files larger
+    /// than `max_file_bytes` (`0` = unlimited) are skipped.
+    fn import_context_dir(
+        &self,
+        _project_slug: String,
+        _workspace_name: String,
+        _dir: PathBuf,
+        _include: Vec<String>,
+        _exclude: Vec<String>,
+        _max_file_bytes: u64,
+    ) -> Result<ContextDirImport, String> {
+        Err("unimplemented".to_owned())
+    }
+
     fn list_new_task_drafts(&self) -> Result<Vec<NewTaskDraft>, String> {
         Err("unimplemented".to_owned())
     }
@@ -516,11 +845,57 @@ pub trait ProjectWorkspaceService: Send + Sync {
         // Default: no-op
     }
 
+    /// Delivers the user's decision on a tool call the thread's running turn is blocked on.
+    fn respond_to_tool_approval(
+        &self,
+        _project_slug: &str,
+        _workspace_name: &str,
+        _thread_local_id: u64,
+        _call_id: &str,
+        _approved: bool,
+    ) -> Result<(), String> {
+        Err("unimplemented".to_owned())
+    }
+
+    /// Fetches the worktree's `origin` remote so that PR lookups see fresh remote refs.
+    fn git_fetch(&self, _worktree_path: PathBuf) -> Result<(), String> {
+        Err("unimplemented".to_owned())
+    }
+
+    /// Tip commit of the worktree's HEAD; `None` for a repository without commits.
+    fn git_last_commit(&self, _worktree_path: PathBuf) -> Result<Option<LastCommitInfo>, String> {
+        Ok(None)
+    }
+
+    /// Stashes uncommitted (including untracked) changes; `Ok(false)` when there was nothing.
+    fn git_stash_push(&self, _worktree_path: PathBuf) -> Result<bool, String> {
+        Err("unimplemented".to_owned())
+    }
+
+    /// Pops the most recent stash entry, failing with a readable message when there is none.
+    fn git_stash_pop(&self, _worktree_path: PathBuf) -> Result<(), String> {
+        Err("unimplemented".to_owned())
+    }
+
+    /// Stages `paths` (every change when `None`) and commits them, returning the short hash of
+    /// the new commit. Fails when nothing ends up staged.
+    fn git_commit(
+        &self,
+        _worktree_path: PathBuf,
+        _message: String,
+        _paths: Option<Vec<String>>,
+    ) -> Result<String, String> {
+        Err("unimplemented".to_owned())
+    }
+
     fn gh_is_authorized(&self) -> Result<bool, String>;
 
+    /// The pull request of the workdir's branch, if any. Fails when `target` names a remote or
+    /// base branch that does not exist.
     fn gh_pull_request_info(
         &self,
         worktree_path: PathBuf,
+        target: PullRequestTarget,
     ) -> Result<Option<PullRequestInfo>, String>;
 
     fn gh_open_pull_request(&self, worktree_path: PathBuf) -> Result<(), String>;
@@ -576,6 +951,21 @@ pub trait ProjectWorkspaceService: Send + Sync {
         Ok(())
     }
 
+    /// Writes every effective task and system prompt template to `<dir>/<key>.md`.
+    fn prompt_templates_export(&self, _dir: PathBuf) -> Result<Vec<String>, String> {
+        Err("unimplemented".to_owned())
+    }
+
+    /// Imports `<dir>/<key>.md` templates; customized templates that differ are reported as
+    /// conflicts unless `force` is set.
+    fn prompt_templates_import(
+        &self,
+        _dir: PathBuf,
+        _force: bool,
+    ) -> Result<PromptTemplatesImportReport, String> {
+        Err("unimplemented".to_owned())
+    }
+
     fn task_suggest_branch_name(
         &self,
         _input: String,
@@ -598,6 +988,30 @@ pub trait ProjectWorkspaceService: Send + Sync {
         Err("unimplemented".to_owned())
     }
 
+    /// Writes a markdown pull request body from a summary of workdir changes and agent messages.
+    fn task_generate_pr_description(
+        &self,
+        _input: String,
+        _runner: AgentRunnerKind,
+        _model_id: String,
+        _thinking_effort: ThinkingEffort,
+        _amp_mode: Option<String>,
+    ) -> Result<String, String> {
+        Err("unimplemented".to_owned())
+    }
+
+    /// Writes a markdown summary of a rendered conversation transcript.
+    fn task_summarize_conversation(
+        &self,
+        _input: String,
+        _runner: AgentRunnerKind,
+        _model_id: String,
+        _thinking_effort: ThinkingEffort,
+        _amp_mode: Option<String>,
+    ) -> Result<String, String> {
+        Err("unimplemented".to_owned())
+    }
+
     fn task_suggest_task_status(
         &self,
         _input: String,
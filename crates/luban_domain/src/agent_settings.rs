@@ -1,4 +1,6 @@
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, serde::Serialize, serde::Deserialize,
+)]
 #[serde(rename_all = "lowercase")]
 pub enum ThinkingEffort {
     Minimal,
@@ -53,6 +55,29 @@ pub fn default_amp_mode() -> &'static str {
     "smart"
 }
 
+/// Wraps the user prompt text in a runner's configured prefix and suffix, skipping blank ones.
+pub fn apply_runner_prompt_affixes(
+    prefix: Option<&str>,
+    prompt: &str,
+    suffix: Option<&str>,
+) -> String {
+    let prefix = prefix.map(str::trim).filter(|s| !s.is_empty());
+    let suffix = suffix.map(str::trim).filter(|s| !s.is_empty());
+    let mut out = String::with_capacity(
+        prompt.len() + prefix.map_or(0, |s| s.len() + 2) + suffix.map_or(0, |s| s.len() + 2),
+    );
+    if let Some(prefix) = prefix {
+        out.push_str(prefix);
+        out.push_str("\n\n");
+    }
+    out.push_str(prompt);
+    if let Some(suffix) = suffix {
+        out.push_str("\n\n");
+        out.push_str(suffix);
+    }
+    out
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -154,6 +179,34 @@ mod tests {
         assert_eq!(agent_model_label("gpt-5.2"), Some("GPT-5.2"));
     }
 
+    #[test]
+    fn clamp_thinking_effort_lets_user_caps_only_narrow_the_model_limit() {
+        assert_eq!(
+            clamp_thinking_effort("gpt-5.3-codex", ThinkingEffort::XHigh, None),
+            ThinkingEffort::XHigh
+        );
+        assert_eq!(
+            clamp_thinking_effort(
+                "gpt-5.3-codex",
+                ThinkingEffort::XHigh,
+                Some(ThinkingEffort::Medium)
+            ),
+            ThinkingEffort::Medium
+        );
+        assert_eq!(
+            clamp_thinking_effort("claude-opus-4-6", ThinkingEffort::High, None),
+            ThinkingEffort::High
+        );
+        assert_eq!(
+            clamp_thinking_effort(
+                "claude-opus-4-6",
+                ThinkingEffort::High,
+                Some(ThinkingEffort::Low)
+            ),
+            ThinkingEffort::Low
+        );
+    }
+
     #[test]
     fn model_valid_for_runner_checks_catalog() {
         assert!(model_valid_for_runner(
@@ -371,6 +424,25 @@ pub fn thinking_effort_supported(model_id: &str, effort: ThinkingEffort) -> bool
         .unwrap_or(false)
 }
 
+/// Lowers `effort` to the highest effort the model supports. `user_cap` can only narrow that
+/// limit further; models without listed efforts are capped by `user_cap` alone.
+pub fn clamp_thinking_effort(
+    model_id: &str,
+    effort: ThinkingEffort,
+    user_cap: Option<ThinkingEffort>,
+) -> ThinkingEffort {
+    let model_cap =
+        find_model_spec(model_id).and_then(|m| m.supported_thinking_efforts.iter().copied().max());
+    let cap = match (model_cap, user_cap) {
+        (Some(model_cap), Some(user_cap)) => Some(model_cap.min(user_cap)),
+        (model_cap, user_cap) => model_cap.or(user_cap),
+    };
+    match cap {
+        Some(cap) => effort.min(cap),
+        None => effort,
+    }
+}
+
 pub fn normalize_thinking_effort(model_id: &str, effort: ThinkingEffort) -> ThinkingEffort {
     let Some(spec) = find_model_spec(model_id) else {
         return default_thinking_effort();
@@ -5,4 +5,5 @@ pub use crate::codex::{
     CodexPatchApplyStatus as AgentPatchApplyStatus, CodexPatchChangeKind as AgentPatchChangeKind,
     CodexThreadError as AgentThreadError, CodexThreadEvent as AgentThreadEvent,
     CodexThreadItem as AgentThreadItem, CodexTodoItem as AgentTodoItem, CodexUsage as AgentUsage,
+    CodexWebSearchResult as AgentWebSearchResult,
 };
@@ -32,6 +32,8 @@ pub enum CodexPatchApplyStatus {
 #[serde(rename_all = "snake_case")]
 pub enum CodexMcpToolCallStatus {
     InProgress,
+    /// The runner is blocked until the user approves or denies the call.
+    AwaitingApproval,
     Completed,
     Failed,
 }
@@ -41,6 +43,15 @@ pub struct CodexErrorMessage {
     pub message: String,
 }
 
+#[derive(Clone, Debug, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CodexWebSearchResult {
+    #[serde(default)]
+    pub title: String,
+    pub url: String,
+    #[serde(default)]
+    pub snippet: String,
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct CodexTodoItem {
     pub text: String,
@@ -64,6 +75,9 @@ pub enum CodexThreadItem {
         #[serde(default)]
         exit_code: Option<i32>,
         status: CodexCommandExecutionStatus,
+        /// Set when `aggregated_output` was cut down to the configured output cap.
+        #[serde(default)]
+        truncated: bool,
     },
     #[serde(rename = "file_change")]
     FileChange {
@@ -90,6 +104,8 @@ pub enum CodexThreadItem {
         id: String,
         #[serde(default)]
         query: String,
+        #[serde(default)]
+        results: Vec<CodexWebSearchResult>,
     },
     #[serde(rename = "todo_list")]
     TodoList {
@@ -100,16 +116,72 @@ pub enum CodexThreadItem {
     Error { id: String, message: String },
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Debug, Default, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct CodexUsage {
     pub input_tokens: u64,
     pub cached_input_tokens: u64,
     pub output_tokens: u64,
 }
 
+impl CodexUsage {
+    /// True when no tokens were reported, e.g. by runners that do not expose usage.
+    pub fn is_empty(&self) -> bool {
+        self.input_tokens == 0 && self.cached_input_tokens == 0 && self.output_tokens == 0
+    }
+
+    pub fn accumulate(&mut self, other: &CodexUsage) {
+        self.input_tokens = self.input_tokens.saturating_add(other.input_tokens);
+        self.cached_input_tokens = self
+            .cached_input_tokens
+            .saturating_add(other.cached_input_tokens);
+        self.output_tokens = self.output_tokens.saturating_add(other.output_tokens);
+    }
+
+    /// Input plus output tokens; cached input is already part of `input_tokens`.
+    pub fn total_tokens(&self) -> u64 {
+        self.input_tokens.saturating_add(self.output_tokens)
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct CodexThreadError {
     pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+}
+
+impl CodexThreadError {
+    /// Whether the failure looks like a passing network or capacity problem that is worth
+    /// retrying unchanged.
+    pub fn is_transient(&self) -> bool {
+        const TRANSIENT_CODES: [&str; 5] = [
+            "transient",
+            "network",
+            "timeout",
+            "rate_limited",
+            "overloaded",
+        ];
+        const TRANSIENT_MESSAGES: [&str; 10] = [
+            "timed out",
+            "timeout",
+            "connection reset",
+            "connection refused",
+            "network",
+            "rate limit",
+            "overloaded",
+            "temporarily unavailable",
+            "503",
+            "429",
+        ];
+
+        if let Some(code) = self.code.as_deref() {
+            return TRANSIENT_CODES.contains(&code);
+        }
+        let message = self.message.to_ascii_lowercase();
+        TRANSIENT_MESSAGES
+            .iter()
+            .any(|needle| message.contains(needle))
+    }
 }
 
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
@@ -31,10 +31,18 @@ pub enum Effect {
         kind: SystemTaskKind,
     },
 
+    DetectProjectDefaultBranch {
+        project_id: ProjectId,
+    },
+
     CreateWorkspace {
         project_id: ProjectId,
         branch_name_hint: Option<String>,
     },
+    CreateWorkspaceFromBranch {
+        project_id: ProjectId,
+        branch_name: String,
+    },
     OpenWorkspaceInIde {
         workspace_id: WorkspaceId,
     },
@@ -55,6 +63,13 @@ pub enum Effect {
         workspace_id: WorkspaceId,
         thread_id: WorkspaceThreadId,
     },
+    /// Copies the history of `source_thread_id` into the freshly created `thread_id`.
+    DuplicateConversation {
+        workspace_id: WorkspaceId,
+        source_thread_id: WorkspaceThreadId,
+        thread_id: WorkspaceThreadId,
+        up_to_entry_id: Option<String>,
+    },
     StoreConversationRunConfig {
         workspace_id: WorkspaceId,
         thread_id: WorkspaceThreadId,
@@ -63,11 +78,48 @@ pub enum Effect {
         thinking_effort: crate::ThinkingEffort,
         amp_mode: Option<String>,
     },
+    /// Stages and commits every worktree change after a completed turn; a clean worktree is
+    /// left alone.
+    AutoCommitTurn {
+        workspace_id: WorkspaceId,
+        thread_id: WorkspaceThreadId,
+        message: String,
+    },
     StoreConversationTaskStatus {
         workspace_id: WorkspaceId,
         thread_id: WorkspaceThreadId,
         task_status: crate::TaskStatus,
     },
+    /// Persists a manual title only if the stored title still equals `expected_current_title`.
+    StoreConversationTitle {
+        workspace_id: WorkspaceId,
+        thread_id: WorkspaceThreadId,
+        expected_current_title: String,
+        title: String,
+    },
+    StoreConversationNotes {
+        workspace_id: WorkspaceId,
+        thread_id: WorkspaceThreadId,
+    },
+    StoreConversationDraft {
+        workspace_id: WorkspaceId,
+        thread_id: WorkspaceThreadId,
+    },
+    StoreConversationCwdSubpath {
+        workspace_id: WorkspaceId,
+        thread_id: WorkspaceThreadId,
+        cwd_subpath: Option<String>,
+    },
+    StoreHunkComment {
+        workspace_id: WorkspaceId,
+        thread_id: WorkspaceThreadId,
+        comment: crate::HunkComment,
+    },
+    StoreCollapsedRange {
+        workspace_id: WorkspaceId,
+        thread_id: WorkspaceThreadId,
+        range: crate::CollapsedRange,
+    },
     LoadConversation {
         workspace_id: WorkspaceId,
         thread_id: WorkspaceThreadId,
@@ -79,12 +131,30 @@ pub enum Effect {
         text: String,
         attachments: Vec<AttachmentRef>,
         run_config: AgentRunConfig,
+        /// Re-runs the last prompt after a transient failure; its user message is already stored.
+        retry: bool,
+    },
+    /// Dispatches `Action::AgentTurnRetryDue` once `delay_ms` has elapsed.
+    ScheduleAgentTurnRetry {
+        workspace_id: WorkspaceId,
+        thread_id: WorkspaceThreadId,
+        attempt: u8,
+        delay_ms: u64,
+        run_config: AgentRunConfig,
     },
     CancelAgentTurn {
         workspace_id: WorkspaceId,
         thread_id: WorkspaceThreadId,
         run_id: u64,
     },
+    /// Forwards the user's decision on a tool call to the agent run waiting for it.
+    RespondToToolApproval {
+        workspace_id: WorkspaceId,
+        thread_id: WorkspaceThreadId,
+        run_id: u64,
+        call_id: String,
+        approved: bool,
+    },
 
     /// Cleanup Claude process associated with a thread.
     /// This is emitted when a thread tab is closed to free resources.
@@ -97,6 +167,17 @@ pub enum Effect {
         workspace_id: WorkspaceId,
         requested_branch_name: String,
     },
+    StashWorkspaceChanges {
+        workspace_id: WorkspaceId,
+    },
+    PopWorkspaceStash {
+        workspace_id: WorkspaceId,
+    },
+    CommitWorkspaceChanges {
+        workspace_id: WorkspaceId,
+        message: String,
+        paths: Option<Vec<String>>,
+    },
     AiRenameWorkspaceBranch {
         workspace_id: WorkspaceId,
         input: String,
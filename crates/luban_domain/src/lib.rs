@@ -2,23 +2,26 @@ mod codex;
 pub use codex::{
     CodexCommandExecutionStatus, CodexErrorMessage, CodexFileUpdateChange, CodexMcpToolCallStatus,
     CodexPatchApplyStatus, CodexPatchChangeKind, CodexThreadError, CodexThreadEvent,
-    CodexThreadItem, CodexTodoItem, CodexUsage,
+    CodexThreadItem, CodexTodoItem, CodexUsage, CodexWebSearchResult,
 };
 
 mod agent_thread;
 pub use agent_thread::{
     AgentCommandExecutionStatus, AgentErrorMessage, AgentFileUpdateChange, AgentMcpToolCallStatus,
     AgentPatchApplyStatus, AgentPatchChangeKind, AgentThreadError, AgentThreadEvent,
-    AgentThreadItem, AgentTodoItem, AgentUsage,
+    AgentThreadItem, AgentTodoItem, AgentUsage, AgentWebSearchResult,
 };
 
 mod adapters;
 pub use adapters::{
-    AmpConfigEntry, AmpConfigEntryKind, ClaudeConfigEntry, ClaudeConfigEntryKind, CodexConfigEntry,
-    CodexConfigEntryKind, ContextImage, CreatedWorkspace, DroidConfigEntry, DroidConfigEntryKind,
-    NewTaskDraft, NewTaskStash, OpenTarget, ProjectIdentity, ProjectWorkspaceService,
-    PullRequestCiState, PullRequestInfo, PullRequestState, RunAgentTurnRequest, TaskIntentKind,
-    TaskIssueInfo, TaskStatusAutoUpdateSuggestion,
+    AmpConfigEntry, AmpConfigEntryKind, BranchNameTemplate, ClaudeConfigEntry,
+    ClaudeConfigEntryKind, CodexConfigEntry, CodexConfigEntryKind, ContextImage,
+    ConversationImportLineError, ConversationRetentionPolicy, ConversationSearchMatch,
+    CreatedWorkspace, DroidConfigEntry, DroidConfigEntryKind, LastCommitInfo, NewTaskDraft,
+    NewTaskStash, OpenTarget, ProjectIdentity, ProjectWorkspaceService,
+    PromptTemplatesImportReport, PullRequestCiState, PullRequestInfo, PullRequestState,
+    PullRequestTarget, RunAgentTurnRequest, TaskIntentKind, TaskIssueInfo,
+    TaskStatusAutoUpdateSuggestion, WorktreeAudit,
 };
 mod context_tokens;
 pub use context_tokens::{
@@ -35,18 +38,20 @@ mod effects;
 pub use effects::Effect;
 mod agent_settings;
 pub mod paths;
+pub mod scope_keys;
 mod task_prompts;
 pub use agent_settings::{
     AgentModelSpec, AgentRunnerKind, ThinkingEffort, agent_model_label, agent_models,
-    default_agent_model_id, default_agent_runner_kind, default_amp_mode, default_model_for_runner,
-    default_thinking_effort, droid_models, model_valid_for_runner, models_for_runner,
-    normalize_thinking_effort, parse_agent_runner_kind, parse_thinking_effort,
-    thinking_effort_supported,
+    apply_runner_prompt_affixes, clamp_thinking_effort, default_agent_model_id,
+    default_agent_runner_kind, default_amp_mode, default_model_for_runner, default_thinking_effort,
+    droid_models, model_valid_for_runner, models_for_runner, normalize_thinking_effort,
+    parse_agent_runner_kind, parse_thinking_effort, thinking_effort_supported,
 };
 pub use task_prompts::{default_task_prompt_template, default_task_prompt_templates};
 mod system_prompts;
 pub use system_prompts::{
-    SystemTaskKind, default_system_prompt_template, default_system_prompt_templates,
+    SystemTaskKind, compose_agent_system_prompt, default_system_prompt_template,
+    default_system_prompt_templates,
 };
 mod dashboard;
 mod time;
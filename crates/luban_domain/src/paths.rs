@@ -45,6 +45,30 @@ pub fn workspace_context_dir(
     workspace_conversation_dir(conversations_root, project_slug, workspace_name).join("context")
 }
 
+/// Normalizes a worktree-relative agent working directory (`/`-separated, no `.` segments).
+/// Empty input means the worktree root (`None`); absolute paths and `..` segments are rejected.
+pub fn normalize_cwd_subpath(raw: &str) -> Result<Option<String>, String> {
+    use std::path::Component;
+
+    let mut parts = Vec::new();
+    for component in Path::new(raw.trim()).components() {
+        match component {
+            Component::CurDir => {}
+            Component::Normal(part) => parts.push(part.to_string_lossy().into_owned()),
+            Component::ParentDir => {
+                return Err("working directory must stay within the worktree".to_owned());
+            }
+            Component::RootDir | Component::Prefix(_) => {
+                return Err("working directory must be relative to the worktree".to_owned());
+            }
+        }
+    }
+    if parts.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(parts.join("/")))
+}
+
 pub(crate) fn normalize_project_path(path: &Path) -> PathBuf {
     use std::path::Component;
 
@@ -99,6 +123,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn normalize_cwd_subpath_accepts_relative_and_rejects_escapes() {
+        assert_eq!(normalize_cwd_subpath("  "), Ok(None));
+        assert_eq!(normalize_cwd_subpath("./"), Ok(None));
+        assert_eq!(
+            normalize_cwd_subpath("./packages//app/"),
+            Ok(Some("packages/app".to_owned()))
+        );
+        assert!(normalize_cwd_subpath("../outside").is_err());
+        assert!(normalize_cwd_subpath("packages/../../outside").is_err());
+        assert!(normalize_cwd_subpath("/etc").is_err());
+    }
+
     #[test]
     fn normalize_project_path_removes_curdir_components() {
         let path = PathBuf::from("a").join(".").join("b");
@@ -4,10 +4,11 @@ use crate::agent_settings::{parse_agent_runner_kind, parse_thinking_effort};
 use crate::time::system_time_from_unix_seconds;
 use crate::{
     AppState, AppearanceFonts, AppearanceTheme, Effect, MainPane, OperationStatus,
-    PersistedAppState, PersistedProject, Project, ProjectId, RightPane, TaskIntentKind, Workspace,
-    WorkspaceId, WorkspaceStatus, WorkspaceTabs, WorkspaceThreadId, default_agent_model_id,
-    default_agent_runner_kind, default_amp_mode, default_system_prompt_templates,
-    default_task_prompt_templates, default_thinking_effort, normalize_thinking_effort,
+    PersistedAppState, PersistedProject, Project, ProjectGroup, ProjectGroupId, ProjectId,
+    ProjectStatus, RightPane, TaskIntentKind, Workspace, WorkspaceId, WorkspaceStatus,
+    WorkspaceTabs, WorkspaceThreadId, default_agent_model_id, default_agent_runner_kind,
+    default_amp_mode, default_system_prompt_templates, default_task_prompt_templates,
+    default_thinking_effort, normalize_thinking_effort,
 };
 use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
@@ -89,6 +90,15 @@ pub(crate) fn apply_persisted_app_state(
             Some((runner, trimmed.to_owned()))
         })
         .collect();
+    state.agent_runner_prompt_prefixes =
+        runner_prompt_affixes(persisted.agent_runner_prompt_prefixes);
+    state.agent_runner_prompt_suffixes =
+        runner_prompt_affixes(persisted.agent_runner_prompt_suffixes);
+    state.model_thinking_effort_caps = persisted
+        .model_thinking_effort_caps
+        .into_iter()
+        .filter_map(|(model_id, cap)| Some((model_id, parse_thinking_effort(&cap)?)))
+        .collect();
     state.agent_default_thinking_effort = agent_default_thinking_effort;
     state.agent_default_runner = agent_default_runner;
     state.agent_amp_mode = agent_amp_mode;
@@ -96,6 +106,26 @@ pub(crate) fn apply_persisted_app_state(
     state.agent_amp_enabled = persisted.agent_amp_enabled.unwrap_or(true);
     state.agent_claude_enabled = persisted.agent_claude_enabled.unwrap_or(true);
     state.agent_droid_enabled = persisted.agent_droid_enabled.unwrap_or(true);
+    state.agent_merge_reasoning_entries = persisted.agent_merge_reasoning_entries.unwrap_or(false);
+    state.agent_pause_queue_on_patch_failure = persisted
+        .agent_pause_queue_on_patch_failure
+        .unwrap_or(false);
+    state.agent_item_dedup_window = persisted
+        .agent_item_dedup_window
+        .map(|window| (window as usize).min(crate::state::MAX_AGENT_ITEM_DEDUP_WINDOW))
+        .unwrap_or(0);
+    state.agent_auto_retry_on_failure = persisted
+        .agent_auto_retry_on_failure
+        .map(|retries| retries.min(u64::from(crate::state::MAX_AGENT_AUTO_RETRY_ON_FAILURE)) as u8)
+        .unwrap_or(0);
+    state.agent_command_output_max_bytes = persisted
+        .agent_command_output_max_bytes
+        .map(|max_bytes| usize::try_from(max_bytes).unwrap_or(usize::MAX))
+        .unwrap_or(crate::state::DEFAULT_AGENT_COMMAND_OUTPUT_MAX_BYTES);
+    state.auto_archive_after_days = persisted
+        .auto_archive_after_days
+        .filter(|days| *days > 0)
+        .map(|days| u32::try_from(days).unwrap_or(u32::MAX));
 
     let telegram_bot_token =
         normalize_optional_string(persisted.telegram_bot_token.as_deref(), 256);
@@ -107,10 +137,15 @@ pub(crate) fn apply_persisted_app_state(
     state.telegram_bot_token = telegram_bot_token;
     state.telegram_bot_username =
         normalize_optional_string(persisted.telegram_bot_username.as_deref(), 64);
-    state.telegram_paired_chat_id = persisted.telegram_paired_chat_id;
+    state.telegram_paired_chat_ids = Vec::new();
+    for chat_id in persisted.telegram_paired_chat_ids {
+        if !state.telegram_paired_chat_ids.contains(&chat_id) {
+            state.telegram_paired_chat_ids.push(chat_id);
+        }
+    }
     state.telegram_config_rev = if state.telegram_enabled
         || state.telegram_bot_token.is_some()
-        || state.telegram_paired_chat_id.is_some()
+        || !state.telegram_paired_chat_ids.is_empty()
     {
         1
     } else {
@@ -118,9 +153,16 @@ pub(crate) fn apply_persisted_app_state(
     };
     state.telegram_last_error = None;
     state.telegram_topic_bindings = load_telegram_topic_bindings(
-        state.telegram_paired_chat_id.is_some(),
+        !state.telegram_paired_chat_ids.is_empty(),
         persisted.telegram_topic_bindings.as_deref(),
     );
+    state.webhook_url = persisted
+        .webhook_url
+        .as_deref()
+        .and_then(crate::reducer::normalize_webhook_url);
+    state.webhook_secret = normalize_optional_string(persisted.webhook_secret.as_deref(), 256);
+    state.webhook_config_rev = u64::from(state.webhook_url.is_some());
+    state.webhook_last_error = None;
 
     state.task_prompt_templates = default_task_prompt_templates();
     state.system_prompt_templates = default_system_prompt_templates();
@@ -186,6 +228,40 @@ pub(crate) fn apply_persisted_app_state(
         }
         out
     };
+    state.project_groups = {
+        let known_projects: HashSet<u64> = state.projects.iter().map(|p| p.id.0).collect();
+        let mut grouped = HashSet::<u64>::new();
+        let mut seen_groups = HashSet::<u64>::new();
+        let mut out = Vec::with_capacity(persisted.project_groups.len());
+        for group in persisted.project_groups {
+            if group.id == 0 || !seen_groups.insert(group.id) {
+                continue;
+            }
+            let name = group.name.trim();
+            if name.is_empty() {
+                continue;
+            }
+            let project_ids = group
+                .project_ids
+                .into_iter()
+                .filter(|id| known_projects.contains(id) && grouped.insert(*id))
+                .map(ProjectId)
+                .collect();
+            out.push(ProjectGroup {
+                id: ProjectGroupId(group.id),
+                name: name.to_owned(),
+                project_ids,
+            });
+        }
+        out
+    };
+    state.next_project_group_id = state
+        .project_groups
+        .iter()
+        .map(|g| g.id.0)
+        .max()
+        .unwrap_or(0)
+        + 1;
     state.workspace_tabs = HashMap::new();
     state.conversations = HashMap::new();
     state.workspace_unread_completions = persisted
@@ -307,7 +383,7 @@ pub(crate) fn apply_persisted_app_state(
             .max(1);
 
         for thread_id in open_tabs.iter().copied() {
-            let mut conversation = state.default_conversation(thread_id);
+            let mut conversation = state.default_conversation(workspace_id, thread_id);
             if let Some(run_config) = state
                 .workspace_thread_run_config_overrides
                 .get(&(workspace_id, thread_id))
@@ -408,6 +484,13 @@ pub(crate) fn apply_persisted_app_state(
     effects.push(Effect::LoadCodexDefaults);
     effects.push(Effect::LoadTaskPromptTemplates);
     effects.push(Effect::LoadSystemPromptTemplates);
+    effects.extend(
+        state
+            .projects
+            .iter()
+            .filter(|p| p.is_git)
+            .map(|p| Effect::DetectProjectDefaultBranch { project_id: p.id }),
+    );
     if projects_upgraded || clear_legacy_templates {
         effects.push(Effect::SaveAppState);
     }
@@ -437,6 +520,13 @@ pub(crate) fn apply_persisted_app_state(
     effects
 }
 
+fn runner_prompt_affixes(raw: HashMap<String, String>) -> HashMap<crate::AgentRunnerKind, String> {
+    raw.into_iter()
+        .filter(|(_, affix)| !affix.trim().is_empty())
+        .filter_map(|(key, affix)| Some((parse_agent_runner_kind(&key)?, affix)))
+        .collect()
+}
+
 fn load_telegram_topic_bindings(
     has_paired_chat: bool,
     raw: Option<&str>,
@@ -451,7 +541,8 @@ fn load_telegram_topic_bindings(
         return HashMap::new();
     };
 
-    let Ok(bindings) = serde_json::from_str::<Vec<crate::TelegramTopicBinding>>(raw) else {
+    let Ok(bindings) = crate::scope_keys::from_legacy::<Vec<crate::TelegramTopicBinding>>(raw)
+    else {
         return HashMap::new();
     };
 
@@ -484,6 +575,16 @@ fn load_projects(projects: Vec<PersistedProject>) -> (Vec<Project>, bool) {
             slug: persisted.slug,
             is_git: persisted.is_git,
             expanded: persisted.expanded,
+            system_prompt: persisted.system_prompt,
+            default_branch: None,
+            worktree_root: persisted.worktree_root,
+            branch_name_template: persisted.branch_name_template,
+            status: persisted.status,
+            agent_defaults: persisted.agent_defaults,
+            saved_commands: persisted.saved_commands,
+            agent_env: persisted.agent_env,
+            pr_remote: persisted.pr_remote,
+            pr_base_branch: persisted.pr_base_branch,
             create_workspace_status: OperationStatus::Idle,
             workspaces: persisted
                 .workspaces
@@ -499,6 +600,8 @@ fn load_projects(projects: Vec<PersistedProject>) -> (Vec<Project>, bool) {
                         .map(system_time_from_unix_seconds),
                     archive_status: OperationStatus::Idle,
                     branch_rename_status: OperationStatus::Idle,
+                    stash_status: OperationStatus::Idle,
+                    commit_status: OperationStatus::Idle,
                 })
                 .collect(),
         };
@@ -524,6 +627,9 @@ fn load_projects(projects: Vec<PersistedProject>) -> (Vec<Project>, bool) {
 
         for other in group {
             canonical.expanded |= other.expanded;
+            if other.status == ProjectStatus::Active {
+                canonical.status = ProjectStatus::Active;
+            }
             canonical.workspaces.extend(other.workspaces);
         }
 
@@ -630,6 +736,15 @@ mod tests {
                 slug: "repo-1".to_owned(),
                 is_git: true,
                 expanded: false,
+                system_prompt: None,
+                worktree_root: None,
+                branch_name_template: None,
+                status: ProjectStatus::Active,
+                agent_defaults: Default::default(),
+                saved_commands: Vec::new(),
+                agent_env: Vec::new(),
+                pr_remote: None,
+                pr_base_branch: None,
                 workspaces: vec![PersistedWorkspace {
                     id: 10,
                     workspace_name: "main".to_owned(),
@@ -646,6 +761,15 @@ mod tests {
                 slug: "repo-2".to_owned(),
                 is_git: true,
                 expanded: true,
+                system_prompt: None,
+                worktree_root: None,
+                branch_name_template: None,
+                status: ProjectStatus::Active,
+                agent_defaults: Default::default(),
+                saved_commands: Vec::new(),
+                agent_env: Vec::new(),
+                pr_remote: None,
+                pr_base_branch: None,
                 workspaces: vec![PersistedWorkspace {
                     id: 11,
                     workspace_name: "main".to_owned(),
@@ -675,6 +799,15 @@ mod tests {
             slug: "repo".to_owned(),
             is_git: true,
             expanded: false,
+            system_prompt: None,
+            worktree_root: None,
+            branch_name_template: None,
+            status: ProjectStatus::Active,
+            agent_defaults: Default::default(),
+            saved_commands: Vec::new(),
+            agent_env: Vec::new(),
+            pr_remote: None,
+            pr_base_branch: None,
             workspaces: vec![
                 PersistedWorkspace {
                     id: 10,
@@ -714,6 +847,8 @@ mod tests {
                 last_activity_at: None,
                 archive_status: OperationStatus::Idle,
                 branch_rename_status: OperationStatus::Idle,
+                stash_status: OperationStatus::Idle,
+                commit_status: OperationStatus::Idle,
             },
             Workspace {
                 id: WorkspaceId(2),
@@ -724,6 +859,8 @@ mod tests {
                 last_activity_at: None,
                 archive_status: OperationStatus::Idle,
                 branch_rename_status: OperationStatus::Idle,
+                stash_status: OperationStatus::Idle,
+                commit_status: OperationStatus::Idle,
             },
             Workspace {
                 id: WorkspaceId(3),
@@ -734,6 +871,8 @@ mod tests {
                 last_activity_at: None,
                 archive_status: OperationStatus::Idle,
                 branch_rename_status: OperationStatus::Idle,
+                stash_status: OperationStatus::Idle,
+                commit_status: OperationStatus::Idle,
             },
             Workspace {
                 id: WorkspaceId(4),
@@ -744,6 +883,8 @@ mod tests {
                 last_activity_at: None,
                 archive_status: OperationStatus::Idle,
                 branch_rename_status: OperationStatus::Idle,
+                stash_status: OperationStatus::Idle,
+                commit_status: OperationStatus::Idle,
             },
         ];
 
@@ -765,6 +906,15 @@ mod tests {
                 slug: "repo".to_owned(),
                 is_git: true,
                 expanded: true,
+                system_prompt: None,
+                worktree_root: None,
+                branch_name_template: None,
+                status: ProjectStatus::Active,
+                agent_defaults: Default::default(),
+                saved_commands: Vec::new(),
+                agent_env: Vec::new(),
+                pr_remote: None,
+                pr_base_branch: None,
                 workspaces: vec![PersistedWorkspace {
                     id: workspace_id,
                     workspace_name: "main".to_owned(),
@@ -784,6 +934,9 @@ mod tests {
             appearance_terminal_font: None,
             agent_default_model_id: None,
             agent_runner_default_models: HashMap::new(),
+            agent_runner_prompt_prefixes: HashMap::new(),
+            agent_runner_prompt_suffixes: HashMap::new(),
+            model_thinking_effort_caps: HashMap::new(),
             agent_default_thinking_effort: None,
             agent_default_runner: None,
             agent_amp_mode: None,
@@ -794,6 +947,7 @@ mod tests {
             last_open_workspace_id: None,
             open_button_selection: None,
             sidebar_project_order: Vec::new(),
+            project_groups: Vec::new(),
             workspace_active_thread_id: HashMap::from([(workspace_id, 2)]),
             workspace_open_tabs: HashMap::from([(workspace_id, vec![1, 2])]),
             workspace_archived_tabs: HashMap::new(),
@@ -807,8 +961,16 @@ mod tests {
             telegram_enabled: None,
             telegram_bot_token: None,
             telegram_bot_username: None,
-            telegram_paired_chat_id: None,
+            telegram_paired_chat_ids: Vec::new(),
             telegram_topic_bindings: None,
+            webhook_url: None,
+            webhook_secret: None,
+            agent_merge_reasoning_entries: None,
+            agent_pause_queue_on_patch_failure: None,
+            agent_item_dedup_window: None,
+            agent_auto_retry_on_failure: None,
+            agent_command_output_max_bytes: None,
+            auto_archive_after_days: None,
         };
 
         let mut state = AppState::new();
@@ -1,6 +1,6 @@
 use crate::time::unix_seconds;
 use crate::{
-    AppState, PersistedAppState, PersistedProject, PersistedWorkspace,
+    AppState, PersistedAppState, PersistedProject, PersistedProjectGroup, PersistedWorkspace,
     PersistedWorkspaceThreadRunConfigOverride,
 };
 use std::collections::HashMap;
@@ -38,6 +38,15 @@ pub(crate) fn to_persisted_app_state(state: &AppState) -> PersistedAppState {
                 slug: p.slug.clone(),
                 is_git: p.is_git,
                 expanded: p.expanded,
+                system_prompt: p.system_prompt.clone(),
+                worktree_root: p.worktree_root.clone(),
+                branch_name_template: p.branch_name_template.clone(),
+                status: p.status,
+                agent_defaults: p.agent_defaults.clone(),
+                saved_commands: p.saved_commands.clone(),
+                agent_env: p.agent_env.clone(),
+                pr_remote: p.pr_remote.clone(),
+                pr_base_branch: p.pr_base_branch.clone(),
                 workspaces: p
                     .workspaces
                     .iter()
@@ -66,6 +75,21 @@ pub(crate) fn to_persisted_app_state(state: &AppState) -> PersistedAppState {
             .iter()
             .map(|(runner, model)| (runner.as_str().to_owned(), model.clone()))
             .collect(),
+        agent_runner_prompt_prefixes: state
+            .agent_runner_prompt_prefixes
+            .iter()
+            .map(|(runner, prefix)| (runner.as_str().to_owned(), prefix.clone()))
+            .collect(),
+        agent_runner_prompt_suffixes: state
+            .agent_runner_prompt_suffixes
+            .iter()
+            .map(|(runner, suffix)| (runner.as_str().to_owned(), suffix.clone()))
+            .collect(),
+        model_thinking_effort_caps: state
+            .model_thinking_effort_caps
+            .iter()
+            .map(|(model_id, cap)| (model_id.clone(), cap.as_str().to_owned()))
+            .collect(),
         agent_default_thinking_effort: Some(
             state.agent_default_thinking_effort.as_str().to_owned(),
         ),
@@ -78,6 +102,15 @@ pub(crate) fn to_persisted_app_state(state: &AppState) -> PersistedAppState {
         last_open_workspace_id: state.last_open_workspace_id.map(|id| id.0),
         open_button_selection: state.open_button_selection.clone(),
         sidebar_project_order: state.sidebar_project_order.clone(),
+        project_groups: state
+            .project_groups
+            .iter()
+            .map(|g| PersistedProjectGroup {
+                id: g.id.0,
+                name: g.name.clone(),
+                project_ids: g.project_ids.iter().map(|id| id.0).collect(),
+            })
+            .collect(),
         workspace_active_thread_id,
         workspace_open_tabs,
         workspace_archived_tabs,
@@ -125,8 +158,16 @@ pub(crate) fn to_persisted_app_state(state: &AppState) -> PersistedAppState {
         telegram_enabled: Some(state.telegram_enabled),
         telegram_bot_token: state.telegram_bot_token.clone(),
         telegram_bot_username: state.telegram_bot_username.clone(),
-        telegram_paired_chat_id: state.telegram_paired_chat_id,
+        telegram_paired_chat_ids: state.telegram_paired_chat_ids.clone(),
         telegram_topic_bindings: serialize_telegram_topic_bindings(&state.telegram_topic_bindings),
+        webhook_url: state.webhook_url.clone(),
+        webhook_secret: state.webhook_secret.clone(),
+        agent_merge_reasoning_entries: Some(state.agent_merge_reasoning_entries),
+        agent_pause_queue_on_patch_failure: Some(state.agent_pause_queue_on_patch_failure),
+        agent_item_dedup_window: Some(state.agent_item_dedup_window as u64),
+        agent_auto_retry_on_failure: Some(u64::from(state.agent_auto_retry_on_failure)),
+        agent_command_output_max_bytes: Some(state.agent_command_output_max_bytes as u64),
+        auto_archive_after_days: state.auto_archive_after_days.map(u64::from),
     }
 }
 
@@ -2,12 +2,12 @@ use crate::persistence;
 use crate::state::{apply_draft_text_diff, entries_is_prefix, entries_is_suffix};
 use crate::{
     Action, AgentRunConfig, AppState, AttachmentRef, CodexThreadEvent, ConversationEntry,
-    DraftAttachment, Effect, MainPane, OperationStatus, PersistedAppState, Project, ProjectId,
-    QueuedPrompt, RightPane, ThinkingEffort, Workspace, WorkspaceConversation, WorkspaceId,
-    WorkspaceStatus, WorkspaceTabs, WorkspaceThreadId, default_agent_model_id,
-    default_system_prompt_template, default_system_prompt_templates, default_task_prompt_template,
-    default_task_prompt_templates, default_thinking_effort, normalize_thinking_effort,
-    thinking_effort_supported,
+    DraftAttachment, Effect, MainPane, OperationStatus, PersistedAppState, Project, ProjectGroup,
+    ProjectGroupId, ProjectId, ProjectSavedCommand, ProjectStatus, QueuedPrompt, RightPane,
+    ThinkingEffort, Workspace, WorkspaceConversation, WorkspaceId, WorkspaceStatus, WorkspaceTabs,
+    WorkspaceThreadId, default_agent_model_id, default_system_prompt_template,
+    default_system_prompt_templates, default_task_prompt_template, default_task_prompt_templates,
+    default_thinking_effort, normalize_thinking_effort, thinking_effort_supported,
 };
 use std::collections::VecDeque;
 use std::{
@@ -21,6 +21,9 @@ mod title;
 use slug::sanitize_slug;
 pub use title::derive_thread_title;
 
+const DETACHED_HEAD_RENAME_ERROR: &str =
+    "Cannot rename branch: workspace is in detached HEAD state; check out a branch first";
+
 fn now_unix_ms() -> u64 {
     use std::time::{SystemTime, UNIX_EPOCH};
     SystemTime::now()
@@ -37,6 +40,7 @@ fn cancel_running_turn(conversation: &mut WorkspaceConversation) -> Option<u64>
     let run_id = conversation.active_run_id?;
     conversation.run_status = OperationStatus::Idle;
     conversation.current_run_config = None;
+    conversation.pending_tool_approvals.clear();
     conversation.active_run_id = None;
     conversation.queue_paused = true;
     conversation.run_finished_at_unix_ms = Some(now_unix_ms());
@@ -79,6 +83,24 @@ fn resolve_enabled_runner(state: &AppState) -> crate::AgentRunnerKind {
     state.agent_default_runner
 }
 
+/// Stores a runner's prompt prefix or suffix; blank text clears the entry.
+fn set_runner_prompt_affix(
+    affixes: &mut HashMap<crate::AgentRunnerKind, String>,
+    runner: crate::AgentRunnerKind,
+    affix: String,
+) -> Vec<Effect> {
+    let changed = if affix.trim().is_empty() {
+        affixes.remove(&runner).is_some()
+    } else {
+        affixes.insert(runner, affix.clone()).as_ref() != Some(&affix)
+    };
+    if changed {
+        vec![Effect::SaveAppState]
+    } else {
+        Vec::new()
+    }
+}
+
 fn truncate_for_system_task(input: &str, max_chars: usize) -> String {
     if max_chars == 0 {
         return String::new();
@@ -90,6 +112,68 @@ fn truncate_for_system_task(input: &str, max_chars: usize) -> String {
     out
 }
 
+/// Accepts an absolute `http(s)` URL of at most 2048 bytes without whitespace.
+pub(crate) fn normalize_webhook_url(raw: &str) -> Option<String> {
+    let url = raw.trim();
+    let has_scheme = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .is_some_and(|rest| !rest.is_empty());
+    if !has_scheme || url.len() > 2048 || url.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return None;
+    }
+    Some(url.to_owned())
+}
+
+fn normalize_project_group_name(raw: &str) -> Option<String> {
+    let trimmed = raw.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+    Some(truncate_for_system_task(
+        trimmed,
+        crate::state::MAX_PROJECT_GROUP_NAME_CHARS,
+    ))
+}
+
+/// Returns `base`, or `base-2`, `base-3`, ... when the label is already taken, mirroring
+/// `unique_project_slug`.
+fn unique_saved_command_label(existing: &[ProjectSavedCommand], base: &str) -> String {
+    let taken = |label: &str| existing.iter().any(|c| c.label == label);
+    if !taken(base) {
+        return base.to_owned();
+    }
+    (2..)
+        .map(|i| format!("{base}-{i}"))
+        .find(|candidate| !taken(candidate))
+        .expect("infinite iterator")
+}
+
+/// Commit message for an auto-commit: the thread title plus the number of user turns loaded.
+fn auto_commit_message(conversation: &WorkspaceConversation) -> String {
+    let turn = conversation
+        .entries
+        .iter()
+        .filter(|entry| {
+            matches!(
+                entry,
+                ConversationEntry::UserEvent {
+                    event: crate::UserEvent::Message { .. },
+                    ..
+                }
+            )
+        })
+        .count()
+        .max(1);
+    let title = conversation.title.trim();
+    let title = if title.is_empty() {
+        "Agent changes"
+    } else {
+        title
+    };
+    format!("{title} (turn {turn})")
+}
+
 fn task_status_auto_update_input(
     conversation: &WorkspaceConversation,
     turn_outcome: &str,
@@ -105,21 +189,15 @@ fn task_status_auto_update_input(
             ConversationEntry::UserEvent {
                 event: crate::UserEvent::Message { text, .. },
                 ..
-            } => {
-                if last_user_message.is_none() {
-                    last_user_message = Some(text.trim().to_owned());
-                }
+            } if last_user_message.is_none() => {
+                last_user_message = Some(text.trim().to_owned());
             }
             ConversationEntry::AgentEvent { event, .. } => match event {
-                crate::AgentEvent::Message { text, .. } => {
-                    if last_agent_message.is_none() {
-                        last_agent_message = Some(text.trim().to_owned());
-                    }
+                crate::AgentEvent::Message { text, .. } if last_agent_message.is_none() => {
+                    last_agent_message = Some(text.trim().to_owned());
                 }
-                crate::AgentEvent::TurnError { message } => {
-                    if last_turn_error.is_none() {
-                        last_turn_error = Some(message.trim().to_owned());
-                    }
+                crate::AgentEvent::TurnError { message, .. } if last_turn_error.is_none() => {
+                    last_turn_error = Some(message.trim().to_owned());
                 }
                 _ => {}
             },
@@ -172,13 +250,14 @@ fn task_status_auto_update_input(
 
 impl AppState {
     const MAIN_WORKSPACE_NAME: &'static str = "main";
-    const MAIN_WORKSPACE_BRANCH: &'static str = "main";
 
     pub fn new() -> Self {
         Self {
             next_project_id: 1,
             next_workspace_id: 1,
+            next_project_group_id: 1,
             projects: Vec::new(),
+            project_groups: Vec::new(),
             main_pane: MainPane::None,
             right_pane: RightPane::None,
             sidebar_width: None,
@@ -188,6 +267,9 @@ impl AppState {
             appearance_fonts: crate::AppearanceFonts::default(),
             agent_default_model_id: default_agent_model_id().to_owned(),
             agent_runner_default_models: HashMap::new(),
+            agent_runner_prompt_prefixes: HashMap::new(),
+            agent_runner_prompt_suffixes: HashMap::new(),
+            model_thinking_effort_caps: HashMap::new(),
             agent_default_thinking_effort: default_thinking_effort(),
             agent_default_runner: crate::default_agent_runner_kind(),
             agent_amp_mode: crate::default_amp_mode().to_owned(),
@@ -195,6 +277,12 @@ impl AppState {
             agent_amp_enabled: true,
             agent_claude_enabled: true,
             agent_droid_enabled: true,
+            agent_merge_reasoning_entries: false,
+            agent_pause_queue_on_patch_failure: false,
+            agent_item_dedup_window: 0,
+            agent_auto_retry_on_failure: 0,
+            agent_command_output_max_bytes: crate::state::DEFAULT_AGENT_COMMAND_OUTPUT_MAX_BYTES,
+            auto_archive_after_days: None,
             conversations: HashMap::new(),
             workspace_tabs: HashMap::new(),
             dashboard_preview_workspace_id: None,
@@ -202,6 +290,7 @@ impl AppState {
             open_button_selection: None,
             sidebar_project_order: Vec::new(),
             last_error: None,
+            error_log: VecDeque::new(),
             workspace_chat_scroll_y10: HashMap::new(),
             workspace_chat_scroll_anchor: HashMap::new(),
             workspace_unread_completions: HashSet::new(),
@@ -212,10 +301,14 @@ impl AppState {
             telegram_enabled: false,
             telegram_bot_token: None,
             telegram_bot_username: None,
-            telegram_paired_chat_id: None,
+            telegram_paired_chat_ids: Vec::new(),
             telegram_config_rev: 0,
             telegram_last_error: None,
             telegram_topic_bindings: HashMap::new(),
+            webhook_url: None,
+            webhook_secret: None,
+            webhook_config_rev: 0,
+            webhook_last_error: None,
         }
     }
 
@@ -264,6 +357,45 @@ impl AppState {
     }
 
     pub fn apply(&mut self, action: Action) -> Vec<Effect> {
+        let mut effects = self.reduce(action);
+        self.clamp_started_run_thinking_efforts(&mut effects);
+        effects
+    }
+
+    /// Applies per-model effort caps to every turn `reduce` started, so both the effect and
+    /// the conversation's `current_run_config` reflect the effort that actually runs.
+    fn clamp_started_run_thinking_efforts(&mut self, effects: &mut [Effect]) {
+        for effect in effects {
+            let Effect::RunAgentTurn {
+                workspace_id,
+                thread_id,
+                run_config,
+                ..
+            } = effect
+            else {
+                continue;
+            };
+            let cap = self
+                .model_thinking_effort_caps
+                .get(&run_config.model_id)
+                .copied();
+            let clamped =
+                crate::clamp_thinking_effort(&run_config.model_id, run_config.thinking_effort, cap);
+            if clamped == run_config.thinking_effort {
+                continue;
+            }
+            run_config.thinking_effort = clamped;
+            if let Some(current) = self
+                .conversations
+                .get_mut(&(*workspace_id, *thread_id))
+                .and_then(|conversation| conversation.current_run_config.as_mut())
+            {
+                current.thinking_effort = clamped;
+            }
+        }
+    }
+
+    fn reduce(&mut self, action: Action) -> Vec<Effect> {
         match action {
             Action::AppStarted => vec![Effect::LoadAppState],
 
@@ -324,10 +456,61 @@ impl AppState {
                 self.dashboard_preview_workspace_id = None;
                 Vec::new()
             }
+            Action::MarkWorkspaceRead { workspace_id } => {
+                if self.workspace_unread_completions.remove(&workspace_id) {
+                    vec![Effect::SaveAppState]
+                } else {
+                    Vec::new()
+                }
+            }
+            Action::MarkAllRead => {
+                if self.workspace_unread_completions.is_empty() {
+                    return Vec::new();
+                }
+                self.workspace_unread_completions.clear();
+                vec![Effect::SaveAppState]
+            }
 
             Action::AddProject { path, is_git } => {
-                self.upsert_project(path, is_git);
-                vec![Effect::SaveAppState]
+                let (project_id, _) = self.upsert_project(path, is_git);
+                let mut effects = vec![Effect::SaveAppState];
+                if is_git {
+                    effects.push(Effect::DetectProjectDefaultBranch { project_id });
+                }
+                effects
+            }
+            Action::ProjectDefaultBranchDetected {
+                project_id,
+                default_branch,
+            } => {
+                let default_branch = default_branch
+                    .map(|b| b.trim().to_owned())
+                    .filter(|b| !b.is_empty());
+                let Some(project) = self.projects.iter_mut().find(|p| p.id == project_id) else {
+                    return Vec::new();
+                };
+                if project.default_branch == default_branch {
+                    return Vec::new();
+                }
+                let previous = project.main_branch_name().to_owned();
+                project.default_branch = default_branch;
+                let next = project.main_branch_name().to_owned();
+                let project_path = project.path.clone();
+                let mut renamed_main = false;
+                for workspace in &mut project.workspaces {
+                    if workspace.workspace_name == Self::MAIN_WORKSPACE_NAME
+                        && workspace.worktree_path == project_path
+                        && workspace.branch_name == previous
+                    {
+                        workspace.branch_name = next.clone();
+                        renamed_main = true;
+                    }
+                }
+                if renamed_main {
+                    vec![Effect::SaveAppState]
+                } else {
+                    Vec::new()
+                }
             }
             Action::ToggleProjectExpanded { project_id } => {
                 if let Some(project) = self.projects.iter_mut().find(|p| p.id == project_id) {
@@ -335,46 +518,238 @@ impl AppState {
                 }
                 vec![Effect::SaveAppState]
             }
+            Action::ProjectSystemPromptChanged {
+                project_id,
+                system_prompt,
+            } => {
+                let system_prompt = system_prompt.filter(|prompt| !prompt.trim().is_empty());
+                let Some(project) = self.projects.iter_mut().find(|p| p.id == project_id) else {
+                    return Vec::new();
+                };
+                if project.system_prompt == system_prompt {
+                    return Vec::new();
+                }
+                project.system_prompt = system_prompt;
+                vec![Effect::SaveAppState]
+            }
+            Action::SetProjectWorktreeRoot { project_id, path } => {
+                let path = path.filter(|path| !path.as_os_str().is_empty());
+                let Some(project) = self.projects.iter_mut().find(|p| p.id == project_id) else {
+                    return Vec::new();
+                };
+                if project.worktree_root == path {
+                    return Vec::new();
+                }
+                project.worktree_root = path;
+                vec![Effect::SaveAppState]
+            }
+            Action::SetProjectBranchNameTemplate {
+                project_id,
+                template,
+            } => {
+                let template = template
+                    .map(|template| template.trim().to_owned())
+                    .filter(|template| !template.is_empty());
+                let Some(project) = self.projects.iter_mut().find(|p| p.id == project_id) else {
+                    return Vec::new();
+                };
+                if project.branch_name_template == template {
+                    return Vec::new();
+                }
+                project.branch_name_template = template;
+                vec![Effect::SaveAppState]
+            }
+            Action::SetProjectAgentEnv { project_id, env } => {
+                let mut normalized: Vec<(String, String)> = Vec::new();
+                for (key, value) in env {
+                    let key = key.trim().to_owned();
+                    if key.is_empty() || key.contains(['=', '\0']) {
+                        continue;
+                    }
+                    normalized.retain(|(existing, _)| *existing != key);
+                    normalized.push((key, value));
+                }
+                let Some(project) = self.projects.iter_mut().find(|p| p.id == project_id) else {
+                    return Vec::new();
+                };
+                if project.agent_env == normalized {
+                    return Vec::new();
+                }
+                project.agent_env = normalized;
+                vec![Effect::SaveAppState]
+            }
+            Action::SetProjectPullRequestTarget {
+                project_id,
+                remote,
+                base_branch,
+            } => {
+                let normalize = |value: Option<String>| {
+                    value
+                        .map(|value| value.trim().to_owned())
+                        .filter(|value| !value.is_empty())
+                };
+                let (remote, base_branch) = (normalize(remote), normalize(base_branch));
+                let Some(project) = self.projects.iter_mut().find(|p| p.id == project_id) else {
+                    return Vec::new();
+                };
+                if project.pr_remote == remote && project.pr_base_branch == base_branch {
+                    return Vec::new();
+                }
+                project.pr_remote = remote;
+                project.pr_base_branch = base_branch;
+                vec![Effect::SaveAppState]
+            }
+            Action::SetProjectAgentDefaults {
+                project_id,
+                mut defaults,
+            } => {
+                defaults.model_id = defaults
+                    .model_id
+                    .map(|model_id| model_id.trim().to_owned())
+                    .filter(|model_id| !model_id.is_empty());
+                defaults.amp_mode = defaults
+                    .amp_mode
+                    .map(|mode| mode.trim().to_owned())
+                    .filter(|mode| !mode.is_empty() && mode.len() <= 32);
+                if let (Some(runner), Some(model_id)) = (defaults.runner, &defaults.model_id)
+                    && !crate::model_valid_for_runner(runner, model_id)
+                {
+                    self.set_error(format!(
+                        "Model {model_id} is not available for {}",
+                        runner.as_str()
+                    ));
+                    return Vec::new();
+                }
+                let Some(project) = self.projects.iter_mut().find(|p| p.id == project_id) else {
+                    return Vec::new();
+                };
+                if project.agent_defaults == defaults {
+                    return Vec::new();
+                }
+                project.agent_defaults = defaults;
+                vec![Effect::SaveAppState]
+            }
+            Action::AddProjectSavedCommand {
+                project_id,
+                label,
+                command,
+            } => {
+                let label = label.trim();
+                let command = command.trim();
+                if label.is_empty() || command.is_empty() {
+                    self.set_error("Saved command needs a label and a command".to_owned());
+                    return Vec::new();
+                }
+                let Some(project) = self.projects.iter_mut().find(|p| p.id == project_id) else {
+                    return Vec::new();
+                };
+                let id = project
+                    .saved_commands
+                    .iter()
+                    .map(|c| c.id + 1)
+                    .max()
+                    .unwrap_or(1);
+                let label = unique_saved_command_label(&project.saved_commands, label);
+                project.saved_commands.push(ProjectSavedCommand {
+                    id,
+                    label,
+                    command: command.to_owned(),
+                });
+                vec![Effect::SaveAppState]
+            }
+            Action::RemoveProjectSavedCommand { project_id, id } => {
+                let Some(project) = self.projects.iter_mut().find(|p| p.id == project_id) else {
+                    return Vec::new();
+                };
+                let before = project.saved_commands.len();
+                project.saved_commands.retain(|c| c.id != id);
+                if project.saved_commands.len() == before {
+                    return Vec::new();
+                }
+                vec![Effect::SaveAppState]
+            }
             Action::DeleteProject { project_id } => self.delete_project(project_id),
+            Action::ArchiveProject { project_id } => self.archive_project(project_id),
+            Action::RestoreProject { project_id } => {
+                let Some(project) = self.projects.iter_mut().find(|p| p.id == project_id) else {
+                    return Vec::new();
+                };
+                if project.status == ProjectStatus::Active {
+                    return Vec::new();
+                }
+                project.status = ProjectStatus::Active;
+                vec![Effect::SaveAppState]
+            }
             Action::OpenProjectSettings { project_id } => {
                 self.main_pane = MainPane::ProjectSettings(project_id);
                 self.right_pane = RightPane::None;
                 self.dashboard_preview_workspace_id = None;
                 Vec::new()
             }
+            Action::SetMainPane { pane } => match pane {
+                MainPane::None => {
+                    self.main_pane = MainPane::None;
+                    self.right_pane = RightPane::None;
+                    self.dashboard_preview_workspace_id = None;
+                    Vec::new()
+                }
+                MainPane::Dashboard => self.apply(Action::OpenDashboard),
+                MainPane::ProjectSettings(project_id) => {
+                    if !self.projects.iter().any(|p| p.id == project_id) {
+                        self.set_error("Project not found".to_owned());
+                        return Vec::new();
+                    }
+                    self.apply(Action::OpenProjectSettings { project_id })
+                }
+                MainPane::Workspace(workspace_id) => {
+                    if self.workspace(workspace_id).is_none() {
+                        self.set_error("Workspace not found".to_owned());
+                        return Vec::new();
+                    }
+                    self.apply(Action::OpenWorkspace { workspace_id })
+                }
+            },
 
             Action::CreateWorkspace {
                 project_id,
                 branch_name_hint,
             } => {
-                if let Some(project) = self.projects.iter_mut().find(|p| p.id == project_id) {
-                    if !project.is_git {
-                        self.last_error =
-                            Some("Cannot create worktrees for a non-git project".to_owned());
-                        return Vec::new();
-                    }
-                    if project.create_workspace_status == OperationStatus::Running {
-                        return Vec::new();
-                    }
-                    project.create_workspace_status = OperationStatus::Running;
-                    if project.workspaces.is_empty() {
-                        self.insert_main_workspace(project_id);
-                    }
+                if !self.begin_create_workspace(project_id) {
+                    return Vec::new();
                 }
                 vec![Effect::CreateWorkspace {
                     project_id,
                     branch_name_hint,
                 }]
             }
+            Action::CreateWorkspaceFromBranch {
+                project_id,
+                branch_name,
+            } => {
+                let branch_name = branch_name.trim();
+                if branch_name.is_empty() {
+                    self.set_error("Branch name is required");
+                    return Vec::new();
+                }
+                if !self.begin_create_workspace(project_id) {
+                    return Vec::new();
+                }
+                vec![Effect::CreateWorkspaceFromBranch {
+                    project_id,
+                    branch_name: branch_name.to_owned(),
+                }]
+            }
             Action::EnsureMainWorkspace { project_id } => {
                 let Some(project) = self.projects.iter().find(|p| p.id == project_id) else {
                     return Vec::new();
                 };
 
+                // Reason: Any workdir checked out at the project root already is the main one,
+                // whatever it is named; adding another would point two workdirs at one tree.
                 let has_main = project
                     .workspaces
                     .iter()
-                    .any(|w| Self::workspace_is_main(project, w));
+                    .any(|w| w.worktree_path == project.path);
                 if has_main {
                     return Vec::new();
                 }
@@ -403,7 +778,7 @@ impl AppState {
                 if let Some(project) = self.projects.iter_mut().find(|p| p.id == project_id) {
                     project.create_workspace_status = OperationStatus::Idle;
                 }
-                self.last_error = Some(message);
+                self.set_error(message);
                 Vec::new()
             }
 
@@ -428,13 +803,13 @@ impl AppState {
             }
             Action::OpenWorkspaceInIde { workspace_id } => {
                 if self.workspace(workspace_id).is_none() {
-                    self.last_error = Some("Workspace not found".to_owned());
+                    self.set_error("Workspace not found".to_owned());
                     return Vec::new();
                 }
                 vec![Effect::OpenWorkspaceInIde { workspace_id }]
             }
             Action::OpenWorkspaceInIdeFailed { message } => {
-                self.last_error = Some(message);
+                self.set_error(message);
                 Vec::new()
             }
             Action::OpenWorkspaceWith {
@@ -442,7 +817,7 @@ impl AppState {
                 target,
             } => {
                 if self.workspace(workspace_id).is_none() {
-                    self.last_error = Some("Workspace not found".to_owned());
+                    self.set_error("Workspace not found".to_owned());
                     return Vec::new();
                 }
                 vec![Effect::OpenWorkspaceWith {
@@ -451,29 +826,29 @@ impl AppState {
                 }]
             }
             Action::OpenWorkspaceWithFailed { message } => {
-                self.last_error = Some(message);
+                self.set_error(message);
                 Vec::new()
             }
             Action::OpenWorkspacePullRequest { workspace_id } => {
                 if self.workspace(workspace_id).is_none() {
-                    self.last_error = Some("Workspace not found".to_owned());
+                    self.set_error("Workspace not found".to_owned());
                     return Vec::new();
                 }
                 vec![Effect::OpenWorkspacePullRequest { workspace_id }]
             }
             Action::OpenWorkspacePullRequestFailed { message } => {
-                self.last_error = Some(message);
+                self.set_error(message);
                 Vec::new()
             }
             Action::OpenWorkspacePullRequestFailedAction { workspace_id } => {
                 if self.workspace(workspace_id).is_none() {
-                    self.last_error = Some("Workspace not found".to_owned());
+                    self.set_error("Workspace not found".to_owned());
                     return Vec::new();
                 }
                 vec![Effect::OpenWorkspacePullRequestFailedAction { workspace_id }]
             }
             Action::OpenWorkspacePullRequestFailedActionFailed { message } => {
-                self.last_error = Some(message);
+                self.set_error(message);
                 Vec::new()
             }
             Action::ArchiveWorkspace { workspace_id } => {
@@ -550,7 +925,7 @@ impl AppState {
                     let workspace = &mut self.projects[project_idx].workspaces[workspace_idx];
                     workspace.archive_status = OperationStatus::Idle;
                 }
-                self.last_error = Some(message);
+                self.set_error(message);
                 Vec::new()
             }
 
@@ -571,6 +946,10 @@ impl AppState {
                 if Self::workspace_is_main(project, workspace) {
                     return Vec::new();
                 }
+                if workspace.is_detached_head() {
+                    self.set_error(DETACHED_HEAD_RENAME_ERROR.to_owned());
+                    return Vec::new();
+                }
 
                 let workspace = &mut self.projects[project_idx].workspaces[workspace_idx];
                 if workspace.branch_rename_status == OperationStatus::Running {
@@ -601,6 +980,10 @@ impl AppState {
                 if Self::workspace_is_main(project, workspace) {
                     return Vec::new();
                 }
+                if workspace.is_detached_head() {
+                    self.set_error(DETACHED_HEAD_RENAME_ERROR.to_owned());
+                    return Vec::new();
+                }
 
                 let current_branch_name = self.projects[project_idx].workspaces[workspace_idx]
                     .branch_name
@@ -723,7 +1106,111 @@ impl AppState {
                     let workspace = &mut self.projects[project_idx].workspaces[workspace_idx];
                     workspace.branch_rename_status = OperationStatus::Idle;
                 }
-                self.last_error = Some(message);
+                self.set_error(message);
+                Vec::new()
+            }
+            Action::WorkspaceStashChanges { workspace_id }
+            | Action::WorkspaceStashPop { workspace_id } => {
+                let pop = matches!(action, Action::WorkspaceStashPop { .. });
+                let Some((project_idx, workspace_idx)) = self.find_workspace_indices(workspace_id)
+                else {
+                    return Vec::new();
+                };
+                if !self.projects[project_idx].is_git {
+                    return Vec::new();
+                }
+                let workspace = &mut self.projects[project_idx].workspaces[workspace_idx];
+                if workspace.stash_status == OperationStatus::Running {
+                    return Vec::new();
+                }
+                workspace.stash_status = OperationStatus::Running;
+                if pop {
+                    vec![Effect::PopWorkspaceStash { workspace_id }]
+                } else {
+                    vec![Effect::StashWorkspaceChanges { workspace_id }]
+                }
+            }
+            Action::WorkspaceStashFinished { workspace_id } => {
+                if let Some((project_idx, workspace_idx)) =
+                    self.find_workspace_indices(workspace_id)
+                {
+                    self.projects[project_idx].workspaces[workspace_idx].stash_status =
+                        OperationStatus::Idle;
+                }
+                Vec::new()
+            }
+            Action::WorkspaceStashFailed {
+                workspace_id,
+                message,
+            } => {
+                if let Some((project_idx, workspace_idx)) =
+                    self.find_workspace_indices(workspace_id)
+                {
+                    self.projects[project_idx].workspaces[workspace_idx].stash_status =
+                        OperationStatus::Idle;
+                }
+                self.set_error(message);
+                Vec::new()
+            }
+            Action::WorkspaceCommit {
+                workspace_id,
+                message,
+                paths,
+            } => {
+                let Some((project_idx, workspace_idx)) = self.find_workspace_indices(workspace_id)
+                else {
+                    return Vec::new();
+                };
+                if !self.projects[project_idx].is_git {
+                    return Vec::new();
+                }
+                let message = message.trim().to_owned();
+                if message.is_empty() {
+                    self.set_error("Commit message cannot be empty".to_owned());
+                    return Vec::new();
+                }
+                let paths = paths.map(|paths| {
+                    paths
+                        .into_iter()
+                        .map(|path| path.trim().to_owned())
+                        .filter(|path| !path.is_empty())
+                        .collect::<Vec<_>>()
+                });
+                if paths.as_ref().is_some_and(Vec::is_empty) {
+                    self.set_error("No files selected to commit".to_owned());
+                    return Vec::new();
+                }
+                let workspace = &mut self.projects[project_idx].workspaces[workspace_idx];
+                if workspace.commit_status == OperationStatus::Running {
+                    return Vec::new();
+                }
+                workspace.commit_status = OperationStatus::Running;
+                vec![Effect::CommitWorkspaceChanges {
+                    workspace_id,
+                    message,
+                    paths,
+                }]
+            }
+            Action::WorkspaceCommitFinished { workspace_id } => {
+                if let Some((project_idx, workspace_idx)) =
+                    self.find_workspace_indices(workspace_id)
+                {
+                    self.projects[project_idx].workspaces[workspace_idx].commit_status =
+                        OperationStatus::Idle;
+                }
+                Vec::new()
+            }
+            Action::WorkspaceCommitFailed {
+                workspace_id,
+                message,
+            } => {
+                if let Some((project_idx, workspace_idx)) =
+                    self.find_workspace_indices(workspace_id)
+                {
+                    self.projects[project_idx].workspaces[workspace_idx].commit_status =
+                        OperationStatus::Idle;
+                }
+                self.set_error(message);
                 Vec::new()
             }
 
@@ -757,10 +1244,18 @@ impl AppState {
                 let snapshot_thinking_effort = snapshot.thinking_effort;
                 let snapshot_runner = snapshot.runner;
                 let snapshot_amp_mode = snapshot.amp_mode.clone();
+                let snapshot_claude_max_output_tokens = snapshot.claude_max_output_tokens;
+                let snapshot_claude_system_prompt = snapshot.claude_system_prompt.clone();
+                let snapshot_auto_commit = snapshot.auto_commit;
 
                 if conversation.thread_id.is_none() {
                     conversation.thread_id = snapshot.thread_id.clone();
                 }
+                // An in-memory draft is newer than the persisted one; only rehydrate an empty box.
+                if conversation.draft.is_empty() && !snapshot.draft.is_empty() {
+                    conversation.draft = std::mem::take(&mut snapshot.draft);
+                    conversation.draft_attachments.clear();
+                }
 
                 let should_apply_snapshot_run_config = !conversation.run_config_overridden_by_user
                     || conversation.agent_model_id.trim().is_empty();
@@ -780,6 +1275,13 @@ impl AppState {
                     {
                         conversation.amp_mode = Some(default_amp_mode);
                     }
+                    if snapshot_claude_max_output_tokens.is_some()
+                        || snapshot_claude_system_prompt.is_some()
+                    {
+                        conversation.claude_max_output_tokens = snapshot_claude_max_output_tokens;
+                        conversation.claude_system_prompt = snapshot_claude_system_prompt;
+                    }
+                    conversation.auto_commit = snapshot_auto_commit;
 
                     if let Some(model_id) = snapshot_model_id {
                         let effort =
@@ -827,15 +1329,29 @@ impl AppState {
 
                 Vec::new()
             }
-            Action::ConversationLoadFailed {
-                workspace_id: _,
-                thread_id: _,
-                message,
-            } => {
-                self.last_error = Some(message);
-                Vec::new()
-            }
-            Action::TerminalCommandStarted {
+            Action::ConversationHistoryPruned {
+                workspace_id,
+                thread_id,
+                snapshot,
+            } => {
+                let Some(conversation) = self.conversations.get_mut(&(workspace_id, thread_id))
+                else {
+                    return Vec::new();
+                };
+                if conversation.run_status != OperationStatus::Running {
+                    conversation.replace_entries_after_prune(snapshot);
+                }
+                Vec::new()
+            }
+            Action::ConversationLoadFailed {
+                workspace_id: _,
+                thread_id: _,
+                message,
+            } => {
+                self.set_error(message);
+                Vec::new()
+            }
+            Action::TerminalCommandStarted {
                 workspace_id,
                 thread_id,
                 command_id,
@@ -900,13 +1416,20 @@ impl AppState {
                     conversation.task_status,
                     crate::TaskStatus::Done | crate::TaskStatus::Canceled
                 ) {
-                    self.last_error = Some("Task is archived".to_owned());
+                    self.set_error("Task is archived".to_owned());
                     return Vec::new();
                 }
+                let had_draft = !conversation.draft.is_empty();
                 conversation.draft.clear();
                 conversation.draft_attachments.clear();
 
                 let mut task_status_effects = Vec::new();
+                if had_draft {
+                    task_status_effects.push(Effect::StoreConversationDraft {
+                        workspace_id,
+                        thread_id,
+                    });
+                }
                 if matches!(
                     conversation.task_status,
                     crate::TaskStatus::Backlog | crate::TaskStatus::Todo
@@ -951,11 +1474,15 @@ impl AppState {
                     }
                 }
 
+                let (claude_max_output_tokens, claude_system_prompt) =
+                    conversation.claude_run_options(runner);
                 let run_config = AgentRunConfig {
                     runner,
                     model_id: conversation.agent_model_id.clone(),
                     thinking_effort: conversation.thinking_effort,
                     amp_mode,
+                    claude_max_output_tokens,
+                    claude_system_prompt,
                 };
 
                 if conversation.run_status == OperationStatus::Running {
@@ -1048,9 +1575,10 @@ impl AppState {
                     conversation.task_status,
                     crate::TaskStatus::Done | crate::TaskStatus::Canceled
                 ) {
-                    self.last_error = Some("Task is archived".to_owned());
+                    self.set_error("Task is archived".to_owned());
                     return Vec::new();
                 }
+                let had_draft = !conversation.draft.is_empty();
                 conversation.draft.clear();
                 conversation.draft_attachments.clear();
 
@@ -1076,11 +1604,15 @@ impl AppState {
                     }
                 }
 
+                let (claude_max_output_tokens, claude_system_prompt) =
+                    conversation.claude_run_options(runner);
                 let run_config = AgentRunConfig {
                     runner,
                     model_id: conversation.agent_model_id.clone(),
                     thinking_effort: conversation.thinking_effort,
                     amp_mode,
+                    claude_max_output_tokens,
+                    claude_system_prompt,
                 };
 
                 let id = conversation.next_queued_prompt_id;
@@ -1092,6 +1624,12 @@ impl AppState {
                     attachments,
                     run_config,
                 });
+                if had_draft {
+                    return vec![Effect::StoreConversationDraft {
+                        workspace_id,
+                        thread_id,
+                    }];
+                }
                 Vec::new()
             }
             Action::ChatModelChanged {
@@ -1241,6 +1779,74 @@ impl AppState {
                     Effect::SaveAppState,
                 ]
             }
+            Action::ChatClaudeOptionsChanged {
+                workspace_id,
+                thread_id,
+                max_output_tokens,
+                system_prompt,
+            } => {
+                let (runner, model_id, thinking_effort, amp_mode) = {
+                    let conversation = self.ensure_conversation_mut(workspace_id, thread_id);
+                    conversation.run_config_overridden_by_user = true;
+                    conversation.claude_max_output_tokens = max_output_tokens.filter(|v| *v > 0);
+                    conversation.claude_system_prompt = system_prompt
+                        .as_deref()
+                        .map(str::trim)
+                        .filter(|v| !v.is_empty())
+                        .map(ToOwned::to_owned);
+                    let runner = conversation.agent_runner;
+                    let amp_mode = if runner == crate::AgentRunnerKind::Amp {
+                        conversation.amp_mode.clone()
+                    } else {
+                        None
+                    };
+                    (
+                        runner,
+                        conversation.agent_model_id.clone(),
+                        conversation.thinking_effort,
+                        amp_mode,
+                    )
+                };
+                vec![Effect::StoreConversationRunConfig {
+                    workspace_id,
+                    thread_id,
+                    runner,
+                    model_id,
+                    thinking_effort,
+                    amp_mode,
+                }]
+            }
+            Action::ChatAutoCommitChanged {
+                workspace_id,
+                thread_id,
+                enabled,
+            } => {
+                let (runner, model_id, thinking_effort, amp_mode) = {
+                    let conversation = self.ensure_conversation_mut(workspace_id, thread_id);
+                    conversation.run_config_overridden_by_user = true;
+                    conversation.auto_commit = enabled;
+                    let runner = conversation.agent_runner;
+                    let amp_mode = if runner == crate::AgentRunnerKind::Amp {
+                        conversation.amp_mode.clone()
+                    } else {
+                        None
+                    };
+                    (
+                        runner,
+                        conversation.agent_model_id.clone(),
+                        conversation.thinking_effort,
+                        amp_mode,
+                    )
+                };
+                vec![Effect::StoreConversationRunConfig {
+                    workspace_id,
+                    thread_id,
+                    runner,
+                    model_id,
+                    thinking_effort,
+                    amp_mode,
+                }]
+            }
             Action::ThinkingEffortChanged {
                 workspace_id,
                 thread_id,
@@ -1290,9 +1896,168 @@ impl AppState {
                 text,
             } => {
                 let conversation = self.ensure_conversation_mut(workspace_id, thread_id);
+                if conversation.draft == text {
+                    return Vec::new();
+                }
                 apply_draft_text_diff(conversation, &text);
+                vec![Effect::StoreConversationDraft {
+                    workspace_id,
+                    thread_id,
+                }]
+            }
+            Action::ThreadNotesChanged {
+                workspace_id,
+                thread_id,
+                notes,
+            } => {
+                let conversation = self.ensure_conversation_mut(workspace_id, thread_id);
+                if conversation.notes == notes {
+                    return Vec::new();
+                }
+                conversation.notes = notes;
+                conversation.notes_save_pending = true;
+                vec![Effect::StoreConversationNotes {
+                    workspace_id,
+                    thread_id,
+                }]
+            }
+            Action::ThreadNotesSaved {
+                workspace_id,
+                thread_id,
+            } => {
+                if let Some(conversation) = self.conversations.get_mut(&(workspace_id, thread_id)) {
+                    conversation.notes_save_pending = false;
+                }
                 Vec::new()
             }
+            Action::ThreadCwdSubpathChanged {
+                workspace_id,
+                thread_id,
+                cwd_subpath,
+            } => {
+                let cwd_subpath =
+                    match crate::paths::normalize_cwd_subpath(cwd_subpath.as_deref().unwrap_or(""))
+                    {
+                        Ok(cwd_subpath) => cwd_subpath,
+                        Err(message) => {
+                            self.set_error(message);
+                            return Vec::new();
+                        }
+                    };
+                let conversation = self.ensure_conversation_mut(workspace_id, thread_id);
+                if conversation.cwd_subpath == cwd_subpath {
+                    return Vec::new();
+                }
+                conversation.cwd_subpath = cwd_subpath.clone();
+                vec![Effect::StoreConversationCwdSubpath {
+                    workspace_id,
+                    thread_id,
+                    cwd_subpath,
+                }]
+            }
+            Action::AddHunkComment {
+                workspace_id,
+                thread_id,
+                entry_id,
+                path,
+                side,
+                line,
+                text,
+            } => {
+                let entry_id = entry_id.trim().to_owned();
+                let path = path.trim().to_owned();
+                let text = text.trim().to_owned();
+                if entry_id.is_empty() || path.is_empty() || text.is_empty() || line == 0 {
+                    self.set_error("hunk comment requires an entry, path, line and text");
+                    return Vec::new();
+                }
+                let conversation = self.ensure_conversation_mut(workspace_id, thread_id);
+                // Entries outside the in-memory window are accepted as-is; the store drops
+                // comments whose entry no longer exists when the conversation is reloaded.
+                let entry = conversation.entries.iter().find(|entry| {
+                    let id = match entry {
+                        ConversationEntry::SystemEvent { entry_id, .. }
+                        | ConversationEntry::UserEvent { entry_id, .. }
+                        | ConversationEntry::AgentEvent { entry_id, .. } => entry_id,
+                    };
+                    *id == entry_id
+                });
+                if let Some(entry) = entry {
+                    let touches_path = match entry {
+                        ConversationEntry::AgentEvent {
+                            event: crate::AgentEvent::Item { item },
+                            ..
+                        } => match item.as_ref() {
+                            crate::CodexThreadItem::FileChange { changes, .. } => {
+                                changes.iter().any(|change| change.path == path)
+                            }
+                            _ => false,
+                        },
+                        _ => false,
+                    };
+                    if !touches_path {
+                        self.set_error(format!(
+                            "entry {entry_id} does not change {path}; cannot attach hunk comment"
+                        ));
+                        return Vec::new();
+                    }
+                }
+                let comment = crate::HunkComment {
+                    entry_id,
+                    path,
+                    side,
+                    line,
+                    text,
+                    created_at_unix_ms: now_unix_ms(),
+                };
+                let conversation = self.ensure_conversation_mut(workspace_id, thread_id);
+                conversation.hunk_comments.push(comment.clone());
+                vec![Effect::StoreHunkComment {
+                    workspace_id,
+                    thread_id,
+                    comment,
+                }]
+            }
+            Action::ConversationCompacted {
+                workspace_id,
+                thread_id,
+                start_entry_id,
+                end_entry_id,
+                entry_count,
+                summary_markdown,
+            } => {
+                let summary_markdown = summary_markdown.trim().to_owned();
+                if summary_markdown.is_empty() || entry_count == 0 {
+                    self.set_error("conversation summary is empty; nothing was collapsed");
+                    return Vec::new();
+                }
+                let Some(conversation) = self.conversations.get_mut(&(workspace_id, thread_id))
+                else {
+                    return Vec::new();
+                };
+                let summary_entry_id =
+                    format!("sys_{}", conversation.entries_total.saturating_add(1));
+                conversation.push_entry(ConversationEntry::SystemEvent {
+                    entry_id: summary_entry_id.clone(),
+                    created_at_unix_ms: now_unix_ms(),
+                    event: crate::ConversationSystemEvent::HistorySummarized {
+                        summary_markdown,
+                        collapsed_entries: entry_count,
+                    },
+                });
+                let range = crate::CollapsedRange {
+                    start_entry_id,
+                    end_entry_id,
+                    entry_count,
+                    summary_entry_id,
+                };
+                conversation.collapsed_ranges.push(range.clone());
+                vec![Effect::StoreCollapsedRange {
+                    workspace_id,
+                    thread_id,
+                    range,
+                }]
+            }
             Action::ChatDraftAttachmentAdded {
                 workspace_id,
                 thread_id,
@@ -1410,7 +2175,10 @@ impl AppState {
                 attachments,
                 model_id,
                 thinking_effort,
+                runner,
+                amp_mode,
             } => {
+                let default_amp_mode = self.agent_amp_mode.clone();
                 let conversation = self.ensure_conversation_mut(workspace_id, thread_id);
                 let Some(pos) = conversation
                     .pending_prompts
@@ -1427,16 +2195,26 @@ impl AppState {
                 }
 
                 let normalized_effort = normalize_thinking_effort(&model_id, thinking_effort);
+                let runner = runner.unwrap_or(conversation.pending_prompts[pos].run_config.runner);
+                let (claude_max_output_tokens, claude_system_prompt) =
+                    conversation.claude_run_options(runner);
                 let entry = conversation.pending_prompts.get_mut(pos).unwrap();
                 entry.text = trimmed;
                 entry.attachments = attachments;
-                let runner = entry.run_config.runner;
-                let amp_mode = entry.run_config.amp_mode.clone();
+                let amp_mode = if runner == crate::AgentRunnerKind::Amp {
+                    amp_mode
+                        .or(entry.run_config.amp_mode.clone())
+                        .or(Some(default_amp_mode))
+                } else {
+                    None
+                };
                 entry.run_config = AgentRunConfig {
                     runner,
                     model_id,
                     thinking_effort: normalized_effort,
                     amp_mode,
+                    claude_max_output_tokens,
+                    claude_system_prompt,
                 };
                 Vec::new()
             }
@@ -1458,6 +2236,92 @@ impl AppState {
                     .into_iter()
                     .collect()
             }
+            Action::RetryLastTurnWithRunner {
+                workspace_id,
+                thread_id,
+                runner,
+                model_id,
+                amp_mode,
+                cancel_first,
+            } => {
+                if !crate::model_valid_for_runner(runner, &model_id) {
+                    self.set_error(format!(
+                        "Model {model_id} is not available for runner {}",
+                        runner.as_str()
+                    ));
+                    return Vec::new();
+                }
+                let default_amp_mode = self.agent_amp_mode.clone();
+                let Some(conversation) = self.conversations.get_mut(&(workspace_id, thread_id))
+                else {
+                    return Vec::new();
+                };
+                if matches!(
+                    conversation.task_status,
+                    crate::TaskStatus::Done | crate::TaskStatus::Canceled
+                ) {
+                    self.set_error("Task is archived".to_owned());
+                    return Vec::new();
+                }
+                let Some((text, attachments)) = last_user_message(conversation) else {
+                    self.set_error("No user message to retry".to_owned());
+                    return Vec::new();
+                };
+
+                let mut effects = Vec::new();
+                if conversation.run_status == OperationStatus::Running {
+                    if !cancel_first {
+                        self.set_error("Cannot retry while a turn is running".to_owned());
+                        return Vec::new();
+                    }
+                    if let Some(run_id) = cancel_running_turn(conversation) {
+                        effects.push(Effect::CancelAgentTurn {
+                            workspace_id,
+                            thread_id,
+                            run_id,
+                        });
+                    }
+                }
+
+                let amp_mode = if runner == crate::AgentRunnerKind::Amp {
+                    amp_mode
+                        .or(conversation.amp_mode.clone())
+                        .or(Some(default_amp_mode))
+                } else {
+                    None
+                };
+                let (claude_max_output_tokens, claude_system_prompt) =
+                    conversation.claude_run_options(runner);
+                let run_config = AgentRunConfig {
+                    runner,
+                    thinking_effort: normalize_thinking_effort(
+                        &model_id,
+                        conversation.thinking_effort,
+                    ),
+                    model_id: model_id.clone(),
+                    amp_mode,
+                    claude_max_output_tokens,
+                    claude_system_prompt,
+                };
+
+                conversation.push_entry(ConversationEntry::SystemEvent {
+                    entry_id: format!("sys_{}", conversation.entries_total.saturating_add(1)),
+                    created_at_unix_ms: now_unix_ms(),
+                    event: crate::ConversationSystemEvent::TurnRetriedWithRunner {
+                        runner,
+                        model_id,
+                    },
+                });
+                effects.push(start_agent_run(
+                    conversation,
+                    workspace_id,
+                    thread_id,
+                    text,
+                    attachments,
+                    run_config,
+                ));
+                effects
+            }
             Action::AgentRunStartedAt {
                 workspace_id,
                 thread_id,
@@ -1501,6 +2365,14 @@ impl AppState {
                 let agent_amp_enabled = self.agent_amp_enabled;
                 let agent_claude_enabled = self.agent_claude_enabled;
                 let agent_droid_enabled = self.agent_droid_enabled;
+                let merge_reasoning_entries = self.agent_merge_reasoning_entries;
+                let pause_queue_on_patch_failure = self.agent_pause_queue_on_patch_failure;
+                let auto_retry_on_failure = self.agent_auto_retry_on_failure;
+                let item_dedup_window = self.agent_item_dedup_window;
+                let command_output_max_bytes = self.agent_command_output_max_bytes;
+                let workspace_is_git = self
+                    .find_workspace_indices(workspace_id)
+                    .is_some_and(|(project_idx, _)| self.projects[project_idx].is_git);
                 let mut last_error_message: Option<String> = None;
                 let effects = {
                     let conversation = self.ensure_conversation_mut(workspace_id, thread_id);
@@ -1516,29 +2388,53 @@ impl AppState {
                             if conversation.active_run_id != Some(run_id) {
                                 return Vec::new();
                             }
-                            let _ = usage;
-                            let finished_run_config = conversation
-                                .current_run_config
-                                .clone()
-                                .unwrap_or(AgentRunConfig {
-                                    runner: conversation.agent_runner,
-                                    model_id: conversation.agent_model_id.clone(),
-                                    thinking_effort: conversation.thinking_effort,
-                                    amp_mode: conversation.amp_mode.clone(),
+                            if !usage.is_empty() {
+                                conversation.push_entry(ConversationEntry::AgentEvent {
+                                    entry_id: String::new(),
+                                    created_at_unix_ms: 0,
+                                    runner: None,
+                                    event: crate::AgentEvent::TurnUsage { usage: Some(usage) },
+                                });
+                            }
+                            let finished_run_config =
+                                conversation.current_run_config.clone().unwrap_or_else(|| {
+                                    let (claude_max_output_tokens, claude_system_prompt) =
+                                        conversation.claude_run_options(conversation.agent_runner);
+                                    AgentRunConfig {
+                                        runner: conversation.agent_runner,
+                                        model_id: conversation.agent_model_id.clone(),
+                                        thinking_effort: conversation.thinking_effort,
+                                        amp_mode: conversation.amp_mode.clone(),
+                                        claude_max_output_tokens,
+                                        claude_system_prompt,
+                                    }
                                 });
                             conversation.run_status = OperationStatus::Idle;
                             conversation.current_run_config = None;
+                            conversation.pending_tool_approvals.clear();
+                            conversation.last_turn_failed_command_ids =
+                                crate::failed_command_ids_in_last_turn(&conversation.entries);
+                            // The commit runs before a queued prompt starts the next turn.
+                            let mut effects = Vec::new();
+                            if conversation.auto_commit && workspace_is_git {
+                                effects.push(Effect::AutoCommitTurn {
+                                    workspace_id,
+                                    thread_id,
+                                    message: auto_commit_message(conversation),
+                                });
+                            }
                             let next =
                                 start_next_queued_prompt(conversation, workspace_id, thread_id);
                             if let Some(effect) = next {
-                                return vec![effect];
+                                effects.push(effect);
+                                return effects;
                             }
 
                             if !matches!(
                                 conversation.task_status,
                                 crate::TaskStatus::Iterating | crate::TaskStatus::Validating
                             ) {
-                                return Vec::new();
+                                return effects;
                             }
                             let runner_enabled = match finished_run_config.runner {
                                 crate::AgentRunnerKind::Codex => agent_codex_enabled,
@@ -1547,10 +2443,10 @@ impl AppState {
                                 crate::AgentRunnerKind::Droid => agent_droid_enabled,
                             };
                             if !runner_enabled {
-                                return Vec::new();
+                                return effects;
                             }
 
-                            vec![Effect::AiAutoUpdateTaskStatus {
+                            effects.push(Effect::AiAutoUpdateTaskStatus {
                                 workspace_id,
                                 thread_id,
                                 input: task_status_auto_update_input(conversation, "completed"),
@@ -1559,7 +2455,8 @@ impl AppState {
                                 model_id: finished_run_config.model_id.clone(),
                                 thinking_effort: finished_run_config.thinking_effort,
                                 amp_mode: finished_run_config.amp_mode.clone(),
-                            }]
+                            });
+                            effects
                         }
                         CodexThreadEvent::TurnDuration { duration_ms } => {
                             if conversation.active_run_id != Some(run_id) {
@@ -1577,14 +2474,18 @@ impl AppState {
                             if conversation.active_run_id != Some(run_id) {
                                 return Vec::new();
                             }
-                            let finished_run_config = conversation
-                                .current_run_config
-                                .clone()
-                                .unwrap_or(AgentRunConfig {
-                                    runner: conversation.agent_runner,
-                                    model_id: conversation.agent_model_id.clone(),
-                                    thinking_effort: conversation.thinking_effort,
-                                    amp_mode: conversation.amp_mode.clone(),
+                            let finished_run_config =
+                                conversation.current_run_config.clone().unwrap_or_else(|| {
+                                    let (claude_max_output_tokens, claude_system_prompt) =
+                                        conversation.claude_run_options(conversation.agent_runner);
+                                    AgentRunConfig {
+                                        runner: conversation.agent_runner,
+                                        model_id: conversation.agent_model_id.clone(),
+                                        thinking_effort: conversation.thinking_effort,
+                                        amp_mode: conversation.amp_mode.clone(),
+                                        claude_max_output_tokens,
+                                        claude_system_prompt,
+                                    }
                                 });
                             let error_message = error.message.clone();
                             conversation.push_entry(ConversationEntry::AgentEvent {
@@ -1593,10 +2494,31 @@ impl AppState {
                                 runner: None,
                                 event: crate::AgentEvent::TurnError {
                                     message: error_message.clone(),
+                                    code: error.code.clone(),
                                 },
                             });
                             conversation.run_status = OperationStatus::Idle;
                             conversation.current_run_config = None;
+                            conversation.pending_tool_approvals.clear();
+                            conversation.last_turn_failed_command_ids =
+                                crate::failed_command_ids_in_last_turn(&conversation.entries);
+
+                            let attempts = conversation.auto_retry_attempts;
+                            if error.is_transient()
+                                && attempts < auto_retry_on_failure
+                                && last_user_message(conversation).is_some()
+                            {
+                                let attempt = attempts.saturating_add(1);
+                                conversation.auto_retry_attempts = attempt;
+                                return vec![Effect::ScheduleAgentTurnRetry {
+                                    workspace_id,
+                                    thread_id,
+                                    attempt,
+                                    delay_ms: crate::state::AGENT_AUTO_RETRY_BASE_DELAY_MS
+                                        << (attempt - 1),
+                                    run_config: finished_run_config,
+                                }];
+                            }
                             conversation.queue_paused = true;
                             last_error_message = Some(error_message);
 
@@ -1626,35 +2548,76 @@ impl AppState {
                                 Vec::new()
                             }
                         }
-                        CodexThreadEvent::ItemStarted { item }
-                        | CodexThreadEvent::ItemUpdated { item } => {
+                        CodexThreadEvent::ItemStarted { mut item }
+                        | CodexThreadEvent::ItemUpdated { mut item } => {
                             if conversation.active_run_id != Some(run_id) {
                                 return Vec::new();
                             }
-                            conversation.push_codex_item(item);
+                            crate::state::cap_command_output(&mut item, command_output_max_bytes);
+                            conversation.track_tool_approval(&item);
+                            if merge_reasoning_entries {
+                                conversation
+                                    .push_codex_item_merging_reasoning(item, item_dedup_window);
+                            } else {
+                                conversation.push_codex_item(item, item_dedup_window);
+                            }
                             Vec::new()
                         }
-                        CodexThreadEvent::ItemCompleted { item } => {
+                        CodexThreadEvent::ItemCompleted { mut item } => {
                             if conversation.active_run_id != Some(run_id) {
                                 return Vec::new();
                             }
-                            conversation.push_codex_item(item);
+                            crate::state::cap_command_output(&mut item, command_output_max_bytes);
+                            conversation.track_tool_approval(&item);
+                            let failed_patch_item_id = match &item {
+                                crate::CodexThreadItem::FileChange {
+                                    id,
+                                    status: crate::CodexPatchApplyStatus::Failed,
+                                    ..
+                                } => Some(id.clone()),
+                                _ => None,
+                            };
+                            if merge_reasoning_entries {
+                                conversation
+                                    .push_codex_item_merging_reasoning(item, item_dedup_window);
+                            } else {
+                                conversation.push_codex_item(item, item_dedup_window);
+                            }
+                            if let Some(item_id) = failed_patch_item_id {
+                                conversation.patch_apply_failed_entry_id =
+                                    file_change_entry_id(conversation, &item_id);
+                                if pause_queue_on_patch_failure {
+                                    conversation.queue_paused = true;
+                                }
+                            }
                             Vec::new()
                         }
                         CodexThreadEvent::Error { message } => {
                             if conversation.active_run_id != Some(run_id) {
                                 return Vec::new();
                             }
-                            conversation.push_entry(ConversationEntry::AgentEvent {
-                                entry_id: String::new(),
-                                created_at_unix_ms: 0,
-                                runner: None,
-                                event: crate::AgentEvent::TurnError {
-                                    message: message.clone(),
-                                },
-                            });
-                            conversation.run_status = OperationStatus::Idle;
-                            conversation.current_run_config = None;
+                            // The runner may already have reported this failure as `turn.failed`.
+                            let already_reported = matches!(
+                                conversation.entries.last(),
+                                Some(ConversationEntry::AgentEvent {
+                                    event: crate::AgentEvent::TurnError { message: last, .. },
+                                    ..
+                                }) if *last == message
+                            );
+                            if !already_reported {
+                                conversation.push_entry(ConversationEntry::AgentEvent {
+                                    entry_id: String::new(),
+                                    created_at_unix_ms: 0,
+                                    runner: None,
+                                    event: crate::AgentEvent::TurnError {
+                                        message: message.clone(),
+                                        code: None,
+                                    },
+                                });
+                            }
+                            conversation.run_status = OperationStatus::Idle;
+                            conversation.current_run_config = None;
+                            conversation.pending_tool_approvals.clear();
                             conversation.queue_paused = true;
                             last_error_message = Some(message);
                             Vec::new()
@@ -1662,7 +2625,7 @@ impl AppState {
                     }
                 };
                 if let Some(message) = last_error_message {
-                    self.last_error = Some(message);
+                    self.set_error(message);
                 }
 
                 effects
@@ -1684,6 +2647,7 @@ impl AppState {
                     if conversation.run_status == OperationStatus::Running {
                         conversation.run_status = OperationStatus::Idle;
                         conversation.current_run_config = None;
+                        conversation.pending_tool_approvals.clear();
                     }
                 }
 
@@ -1696,6 +2660,38 @@ impl AppState {
 
                 effects
             }
+            Action::AgentTurnRetryDue {
+                workspace_id,
+                thread_id,
+                attempt,
+                run_config,
+            } => {
+                let Some(conversation) = self.conversations.get_mut(&(workspace_id, thread_id))
+                else {
+                    return Vec::new();
+                };
+                if conversation.run_status == OperationStatus::Running
+                    || conversation.auto_retry_attempts != attempt
+                    || matches!(
+                        conversation.task_status,
+                        crate::TaskStatus::Done | crate::TaskStatus::Canceled
+                    )
+                {
+                    return Vec::new();
+                }
+                let Some((text, attachments)) = last_user_message(conversation) else {
+                    return Vec::new();
+                };
+                vec![begin_agent_run(
+                    conversation,
+                    workspace_id,
+                    thread_id,
+                    text,
+                    attachments,
+                    run_config,
+                    true,
+                )]
+            }
             Action::CancelAgentTurn {
                 workspace_id,
                 thread_id,
@@ -1704,6 +2700,8 @@ impl AppState {
                 else {
                     return Vec::new();
                 };
+                // Also drops a retry still waiting out its backoff.
+                conversation.auto_retry_attempts = 0;
                 let Some(run_id) = cancel_running_turn(conversation) else {
                     return Vec::new();
                 };
@@ -1713,40 +2711,155 @@ impl AppState {
                     run_id,
                 }]
             }
-            Action::CreateWorkspaceThread { workspace_id } => {
-                let thread_id = {
-                    let tabs = self.ensure_workspace_tabs_mut(workspace_id);
-                    tabs.allocate_thread_id()
+            Action::RespondToToolApproval {
+                workspace_id,
+                thread_id,
+                call_id,
+                approved,
+            } => {
+                let Some(conversation) = self.conversations.get_mut(&(workspace_id, thread_id))
+                else {
+                    return Vec::new();
+                };
+                let Some(run_id) = conversation.active_run_id else {
+                    return Vec::new();
                 };
-                // Reason: Use resolve_enabled_runner so new tasks respect
-                // which runners the user has enabled in settings.
-                let effective_runner = resolve_enabled_runner(self);
-                let model_id = self.resolve_default_model_for_runner(effective_runner);
-                let mut conversation = Self::default_conversation_with_defaults(
+                let before = conversation.pending_tool_approvals.len();
+                conversation
+                    .pending_tool_approvals
+                    .retain(|pending| *pending != call_id);
+                if conversation.pending_tool_approvals.len() == before {
+                    return Vec::new();
+                }
+                vec![Effect::RespondToToolApproval {
+                    workspace_id,
                     thread_id,
-                    model_id,
-                    self.agent_default_thinking_effort,
-                    effective_runner,
-                );
-                conversation.task_status = crate::TaskStatus::Backlog;
-                conversation.push_entry(ConversationEntry::SystemEvent {
-                    entry_id: format!("sys_{}", conversation.entries_total.saturating_add(1)),
-                    created_at_unix_ms: now_unix_ms(),
-                    event: crate::ConversationSystemEvent::TaskCreated,
-                });
-                self.conversations
-                    .insert((workspace_id, thread_id), conversation);
-                self.ensure_workspace_tabs_mut(workspace_id)
-                    .activate(thread_id);
-                vec![
+                    run_id,
+                    call_id,
+                    approved,
+                }]
+            }
+            Action::ToolApprovalDeliveryFailed {
+                workspace_id,
+                thread_id,
+                run_id,
+                call_id,
+                message,
+            } => {
+                if let Some(conversation) = self.conversations.get_mut(&(workspace_id, thread_id))
+                    && conversation.run_status == OperationStatus::Running
+                    && conversation.active_run_id == Some(run_id)
+                    && !conversation.pending_tool_approvals.contains(&call_id)
+                {
+                    conversation.pending_tool_approvals.push(call_id);
+                }
+                self.set_error(message);
+                Vec::new()
+            }
+            Action::CancelAndClearQueue {
+                workspace_id,
+                thread_id,
+            } => {
+                let Some(conversation) = self.conversations.get_mut(&(workspace_id, thread_id))
+                else {
+                    return Vec::new();
+                };
+                conversation.pending_prompts.clear();
+                let run_id = cancel_running_turn(conversation);
+                conversation.queue_paused = false;
+                run_id
+                    .map(|run_id| Effect::CancelAgentTurn {
+                        workspace_id,
+                        thread_id,
+                        run_id,
+                    })
+                    .into_iter()
+                    .collect()
+            }
+            Action::CreateWorkspaceThread { workspace_id } => {
+                let thread_id = self.create_workspace_thread(workspace_id);
+                let mut effects = vec![
                     Effect::SaveAppState,
                     Effect::EnsureConversation {
                         workspace_id,
                         thread_id,
                     },
-                    Effect::LoadWorkspaceThreads { workspace_id },
+                ];
+                // Reason: Store the project-resolved run config with the task so it survives a
+                // reload instead of falling back to the global defaults.
+                if self
+                    .project_for_workspace(workspace_id)
+                    .is_some_and(|project| !project.agent_defaults.is_empty())
+                    && let Some(conversation) = self.conversations.get(&(workspace_id, thread_id))
+                {
+                    effects.push(Effect::StoreConversationRunConfig {
+                        workspace_id,
+                        thread_id,
+                        runner: conversation.agent_runner,
+                        model_id: conversation.agent_model_id.clone(),
+                        thinking_effort: conversation.thinking_effort,
+                        amp_mode: conversation.amp_mode.clone(),
+                    });
+                }
+                effects.push(Effect::LoadWorkspaceThreads { workspace_id });
+                effects
+            }
+            Action::DuplicateWorkspaceThread {
+                workspace_id,
+                thread_id: source_thread_id,
+                up_to_entry_id,
+            } => {
+                if self.find_workspace_indices(workspace_id).is_none() {
+                    return Vec::new();
+                }
+                let source_run_config = self
+                    .conversations
+                    .get(&(workspace_id, source_thread_id))
+                    .map(|source| {
+                        (
+                            source.agent_runner,
+                            source.agent_model_id.clone(),
+                            source.thinking_effort,
+                            source.amp_mode.clone(),
+                        )
+                    });
+
+                let thread_id = self.create_workspace_thread(workspace_id);
+                if let Some((runner, model_id, thinking_effort, amp_mode)) = source_run_config {
+                    let conversation = self.ensure_conversation_mut(workspace_id, thread_id);
+                    conversation.agent_runner = runner;
+                    conversation.agent_model_id = model_id;
+                    conversation.thinking_effort = thinking_effort;
+                    conversation.amp_mode = amp_mode;
+                }
+                vec![
+                    Effect::SaveAppState,
+                    Effect::DuplicateConversation {
+                        workspace_id,
+                        source_thread_id,
+                        thread_id,
+                        up_to_entry_id,
+                    },
                 ]
             }
+            Action::WorkspaceThreadDuplicated {
+                workspace_id,
+                thread_id,
+            } => vec![
+                Effect::LoadConversation {
+                    workspace_id,
+                    thread_id,
+                },
+                Effect::LoadWorkspaceThreads { workspace_id },
+            ],
+            Action::WorkspaceThreadDuplicateFailed {
+                workspace_id,
+                thread_id: _,
+                message,
+            } => {
+                self.set_error(message);
+                vec![Effect::LoadWorkspaceThreads { workspace_id }]
+            }
             Action::ActivateWorkspaceThread {
                 workspace_id,
                 thread_id,
@@ -1761,7 +2874,7 @@ impl AppState {
                         )
                     })
                 {
-                    self.last_error = Some("Task is archived".to_owned());
+                    self.set_error("Task is archived".to_owned());
                     return Vec::new();
                 }
                 let tabs = self.ensure_workspace_tabs_mut(workspace_id);
@@ -1815,7 +2928,7 @@ impl AppState {
                         )
                     })
                 {
-                    self.last_error = Some("Task is archived".to_owned());
+                    self.set_error("Task is archived".to_owned());
                     return Vec::new();
                 }
                 let tabs = self.ensure_workspace_tabs_mut(workspace_id);
@@ -1842,6 +2955,26 @@ impl AppState {
                     Vec::new()
                 }
             }
+            Action::ActivateAdjacentWorkspaceThread {
+                workspace_id,
+                direction,
+            } => {
+                let tabs = self.ensure_workspace_tabs_mut(workspace_id);
+                let Some(thread_id) = tabs.adjacent_tab(direction) else {
+                    return Vec::new();
+                };
+                tabs.activate(thread_id);
+                self.ensure_conversation_mut(workspace_id, thread_id);
+                vec![
+                    Effect::SaveAppState,
+                    Effect::LoadConversation {
+                        workspace_id,
+                        thread_id,
+                    },
+                    // Reloading the thread list republishes the tabs with the new active tab.
+                    Effect::LoadWorkspaceThreads { workspace_id },
+                ]
+            }
             Action::WorkspaceThreadsLoaded {
                 workspace_id,
                 threads,
@@ -1951,9 +3084,50 @@ impl AppState {
                 workspace_id: _,
                 message,
             } => {
-                self.last_error = Some(message);
+                self.set_error(message);
                 Vec::new()
             }
+            Action::WorkspaceThreadMoved {
+                from_workspace_id,
+                thread_id,
+                to_workspace_id,
+                to_thread_id,
+            } => {
+                let from = (from_workspace_id, thread_id);
+                let to = (to_workspace_id, to_thread_id);
+                if let Some(tabs) = self.workspace_tabs.get_mut(&from_workspace_id) {
+                    tabs.remove_thread(thread_id);
+                }
+                if let Some(mut conversation) = self.conversations.remove(&from) {
+                    conversation.local_thread_id = to_thread_id;
+                    self.conversations.insert(to, conversation);
+                }
+                if let Some(value) = self.workspace_chat_scroll_y10.remove(&from) {
+                    self.workspace_chat_scroll_y10.insert(to, value);
+                }
+                if let Some(value) = self.workspace_chat_scroll_anchor.remove(&from) {
+                    self.workspace_chat_scroll_anchor.insert(to, value);
+                }
+                if let Some(value) = self.workspace_thread_run_config_overrides.remove(&from) {
+                    self.workspace_thread_run_config_overrides.insert(to, value);
+                }
+                if self.starred_tasks.remove(&from) {
+                    self.starred_tasks.insert(to);
+                }
+
+                let tabs = self.ensure_workspace_tabs_mut(to_workspace_id);
+                tabs.next_thread_id = tabs.next_thread_id.max(to_thread_id.0 + 1);
+                let activate = tabs.open_tabs.is_empty();
+                tabs.restore_tab(to_thread_id, activate);
+
+                vec![
+                    Effect::SaveAppState,
+                    Effect::CleanupClaudeProcess {
+                        workspace_id: from_workspace_id,
+                        thread_id,
+                    },
+                ]
+            }
             Action::WorkspaceThreadsPurged {
                 workspace_id,
                 thread_ids,
@@ -2106,6 +3280,71 @@ impl AppState {
                 self.agent_droid_enabled = enabled;
                 vec![Effect::SaveAppState]
             }
+            Action::AgentMergeReasoningEntriesChanged { enabled } => {
+                if self.agent_merge_reasoning_entries == enabled {
+                    return Vec::new();
+                }
+                self.agent_merge_reasoning_entries = enabled;
+                vec![Effect::SaveAppState]
+            }
+            Action::AgentPauseQueueOnPatchFailureChanged { enabled } => {
+                if self.agent_pause_queue_on_patch_failure == enabled {
+                    return Vec::new();
+                }
+                self.agent_pause_queue_on_patch_failure = enabled;
+                vec![Effect::SaveAppState]
+            }
+            Action::AgentItemDedupWindowChanged { window } => {
+                let window = window.min(crate::state::MAX_AGENT_ITEM_DEDUP_WINDOW);
+                if self.agent_item_dedup_window == window {
+                    return Vec::new();
+                }
+                self.agent_item_dedup_window = window;
+                vec![Effect::SaveAppState]
+            }
+            Action::AgentAutoRetryOnFailureChanged { retries } => {
+                let retries = retries.min(crate::state::MAX_AGENT_AUTO_RETRY_ON_FAILURE);
+                if self.agent_auto_retry_on_failure == retries {
+                    return Vec::new();
+                }
+                self.agent_auto_retry_on_failure = retries;
+                vec![Effect::SaveAppState]
+            }
+            Action::AgentCommandOutputMaxBytesChanged { max_bytes } => {
+                if self.agent_command_output_max_bytes == max_bytes {
+                    return Vec::new();
+                }
+                self.agent_command_output_max_bytes = max_bytes;
+                vec![Effect::SaveAppState]
+            }
+            Action::AutoArchiveConfigChanged { days } => {
+                let days = days.filter(|days| *days > 0);
+                if self.auto_archive_after_days == days {
+                    return Vec::new();
+                }
+                self.auto_archive_after_days = days;
+                vec![Effect::SaveAppState]
+            }
+            Action::AgentRunnerPromptPrefixChanged { runner, prefix } => {
+                set_runner_prompt_affix(&mut self.agent_runner_prompt_prefixes, runner, prefix)
+            }
+            Action::AgentRunnerPromptSuffixChanged { runner, suffix } => {
+                set_runner_prompt_affix(&mut self.agent_runner_prompt_suffixes, runner, suffix)
+            }
+            Action::ModelThinkingEffortCapChanged { model_id, cap } => {
+                let model_id = model_id.trim().to_owned();
+                if model_id.is_empty() {
+                    return Vec::new();
+                }
+                let previous = match cap {
+                    Some(cap) => self.model_thinking_effort_caps.insert(model_id, cap),
+                    None => self.model_thinking_effort_caps.remove(&model_id),
+                };
+                if previous == cap {
+                    return Vec::new();
+                }
+                vec![Effect::SaveAppState]
+            }
             Action::AgentRunnerChanged { runner } => {
                 if self.agent_default_runner == runner {
                     return Vec::new();
@@ -2152,7 +3391,7 @@ impl AppState {
                 if self.telegram_bot_token.is_none()
                     && !self.telegram_enabled
                     && self.telegram_bot_username.is_none()
-                    && self.telegram_paired_chat_id.is_none()
+                    && self.telegram_paired_chat_ids.is_empty()
                     && self.telegram_topic_bindings.is_empty()
                     && self.telegram_last_error.is_none()
                 {
@@ -2161,7 +3400,7 @@ impl AppState {
                 self.telegram_enabled = false;
                 self.telegram_bot_token = None;
                 self.telegram_bot_username = None;
-                self.telegram_paired_chat_id = None;
+                self.telegram_paired_chat_ids.clear();
                 self.telegram_last_error = None;
                 self.telegram_topic_bindings.clear();
                 self.telegram_config_rev = self.telegram_config_rev.saturating_add(1);
@@ -2181,24 +3420,33 @@ impl AppState {
                 vec![Effect::SaveAppState]
             }
             Action::TelegramChatPaired { chat_id } => {
-                if self.telegram_paired_chat_id == Some(chat_id)
-                    && self.telegram_topic_bindings.is_empty()
-                {
+                if self.telegram_paired_chat_ids.contains(&chat_id) {
                     return Vec::new();
                 }
-                self.telegram_paired_chat_id = Some(chat_id);
+                // Topic bindings left over from an earlier pairing are stale once every chat was
+                // unpaired; additional chats share the bindings of the existing ones.
+                if self.telegram_paired_chat_ids.is_empty() {
+                    self.telegram_topic_bindings.clear();
+                }
+                self.telegram_paired_chat_ids.push(chat_id);
                 self.telegram_last_error = None;
-                self.telegram_topic_bindings.clear();
                 self.telegram_config_rev = self.telegram_config_rev.saturating_add(1);
                 vec![Effect::SaveAppState]
             }
-            Action::TelegramUnpaired => {
-                if self.telegram_paired_chat_id.is_none() && self.telegram_topic_bindings.is_empty()
-                {
+            Action::TelegramUnpaired { chat_id } => {
+                let before = self.telegram_paired_chat_ids.len();
+                match chat_id {
+                    Some(chat_id) => self.telegram_paired_chat_ids.retain(|id| *id != chat_id),
+                    None => self.telegram_paired_chat_ids.clear(),
+                }
+                let clear_bindings = self.telegram_paired_chat_ids.is_empty()
+                    && !self.telegram_topic_bindings.is_empty();
+                if self.telegram_paired_chat_ids.len() == before && !clear_bindings {
                     return Vec::new();
                 }
-                self.telegram_paired_chat_id = None;
-                self.telegram_topic_bindings.clear();
+                if self.telegram_paired_chat_ids.is_empty() {
+                    self.telegram_topic_bindings.clear();
+                }
                 self.telegram_config_rev = self.telegram_config_rev.saturating_add(1);
                 vec![Effect::SaveAppState]
             }
@@ -2215,6 +3463,56 @@ impl AppState {
                 self.telegram_last_error = next;
                 Vec::new()
             }
+            Action::WebhookConfigSet { url, secret } => {
+                let Some(url) = normalize_webhook_url(&url) else {
+                    return Vec::new();
+                };
+                let secret = match secret {
+                    Some(secret) => {
+                        let secret = secret.trim();
+                        if secret.len() > 256 || secret.chars().any(|c| c.is_control()) {
+                            return Vec::new();
+                        }
+                        (!secret.is_empty()).then(|| secret.to_owned())
+                    }
+                    None => self.webhook_secret.clone(),
+                };
+                if self.webhook_url.as_deref() == Some(url.as_str())
+                    && self.webhook_secret == secret
+                {
+                    return Vec::new();
+                }
+                self.webhook_url = Some(url);
+                self.webhook_secret = secret;
+                self.webhook_last_error = None;
+                self.webhook_config_rev = self.webhook_config_rev.saturating_add(1);
+                vec![Effect::SaveAppState]
+            }
+            Action::WebhookConfigCleared => {
+                if self.webhook_url.is_none()
+                    && self.webhook_secret.is_none()
+                    && self.webhook_last_error.is_none()
+                {
+                    return Vec::new();
+                }
+                self.webhook_url = None;
+                self.webhook_secret = None;
+                self.webhook_last_error = None;
+                self.webhook_config_rev = self.webhook_config_rev.saturating_add(1);
+                vec![Effect::SaveAppState]
+            }
+            Action::WebhookLastErrorSet { message } => {
+                let next = message
+                    .as_deref()
+                    .map(str::trim)
+                    .filter(|v| !v.is_empty())
+                    .map(|v| v.chars().take(1024).collect::<String>());
+                if self.webhook_last_error == next {
+                    return Vec::new();
+                }
+                self.webhook_last_error = next;
+                Vec::new()
+            }
             Action::TelegramTopicBound {
                 message_thread_id,
                 workspace_id,
@@ -2397,6 +3695,32 @@ impl AppState {
                     Vec::new()
                 }
             }
+            Action::RenameThread {
+                workspace_id,
+                thread_id,
+                title,
+            } => {
+                let title = title.trim();
+                if title.is_empty() {
+                    self.set_error("Thread title cannot be empty".to_owned());
+                    return Vec::new();
+                }
+                let Some(conversation) = self.conversations.get_mut(&(workspace_id, thread_id))
+                else {
+                    return Vec::new();
+                };
+                if conversation.title == title {
+                    return Vec::new();
+                }
+                let expected_current_title =
+                    std::mem::replace(&mut conversation.title, title.to_owned());
+                vec![Effect::StoreConversationTitle {
+                    workspace_id,
+                    thread_id,
+                    expected_current_title,
+                    title: title.to_owned(),
+                }]
+            }
             Action::TaskStatusSet {
                 workspace_id,
                 thread_id,
@@ -2416,7 +3740,7 @@ impl AppState {
                     existing_status,
                     crate::TaskStatus::Done | crate::TaskStatus::Canceled
                 ) {
-                    self.last_error = Some("Task is archived".to_owned());
+                    self.set_error("Task is archived".to_owned());
                     return Vec::new();
                 }
 
@@ -2512,6 +3836,35 @@ impl AppState {
                 }
                 effects
             }
+            Action::BulkSetTaskStatus {
+                targets,
+                task_status,
+            } => {
+                let total = targets.len();
+                let mut failed = 0usize;
+                let mut effects = Vec::new();
+                for (workspace_id, thread_id) in targets {
+                    if self
+                        .task_status_change_error(workspace_id, thread_id, task_status)
+                        .is_some()
+                    {
+                        failed += 1;
+                        continue;
+                    }
+                    effects.extend(self.apply(Action::TaskStatusSet {
+                        workspace_id,
+                        thread_id,
+                        task_status,
+                    }));
+                }
+                if failed > 0 {
+                    self.set_error(format!(
+                        "Failed to update {failed} of {total} tasks to {}",
+                        task_status.as_str()
+                    ));
+                }
+                effects
+            }
             Action::TaskStatusSuggestionCreated {
                 workspace_id,
                 thread_id,
@@ -2587,15 +3940,94 @@ impl AppState {
                     next.push(key);
                 }
 
+                // A stale client may omit projects added since it last rendered; keep them
+                // visible after the ordered ones, previously ordered projects first.
+                let missing = self
+                    .sidebar_project_order
+                    .iter()
+                    .cloned()
+                    .chain(
+                        self.projects
+                            .iter()
+                            .map(|p| p.path.to_string_lossy().to_string()),
+                    )
+                    .filter(|key| valid.contains(key))
+                    .collect::<Vec<_>>();
+                for key in missing {
+                    if seen.insert(key.clone()) {
+                        next.push(key);
+                    }
+                }
+
                 if self.sidebar_project_order == next {
                     return Vec::new();
                 }
                 self.sidebar_project_order = next;
                 vec![Effect::SaveAppState]
             }
-            Action::OpenButtonSelectionChanged { selection } => {
-                let trimmed = selection.trim();
-                if trimmed.len() > 1024 {
+            Action::CreateProjectGroup { name } => {
+                let Some(name) = normalize_project_group_name(&name) else {
+                    self.set_error("Project group name cannot be empty".to_owned());
+                    return Vec::new();
+                };
+                let id = ProjectGroupId(self.next_project_group_id);
+                self.next_project_group_id += 1;
+                self.project_groups.push(ProjectGroup {
+                    id,
+                    name,
+                    project_ids: Vec::new(),
+                });
+                vec![Effect::SaveAppState]
+            }
+            Action::RenameProjectGroup { group_id, name } => {
+                let Some(name) = normalize_project_group_name(&name) else {
+                    self.set_error("Project group name cannot be empty".to_owned());
+                    return Vec::new();
+                };
+                let Some(group) = self.project_groups.iter_mut().find(|g| g.id == group_id) else {
+                    return Vec::new();
+                };
+                if group.name == name {
+                    return Vec::new();
+                }
+                group.name = name;
+                vec![Effect::SaveAppState]
+            }
+            Action::DeleteProjectGroup { group_id } => {
+                let before = self.project_groups.len();
+                self.project_groups.retain(|g| g.id != group_id);
+                if self.project_groups.len() == before {
+                    return Vec::new();
+                }
+                vec![Effect::SaveAppState]
+            }
+            Action::AssignProjectToGroup {
+                project_id,
+                group_id,
+            } => {
+                if !self.projects.iter().any(|p| p.id == project_id) {
+                    return Vec::new();
+                }
+                if let Some(group_id) = group_id
+                    && !self.project_groups.iter().any(|g| g.id == group_id)
+                {
+                    self.set_error("Project group not found".to_owned());
+                    return Vec::new();
+                }
+                if self.project_group_id(project_id) == group_id {
+                    return Vec::new();
+                }
+                for group in &mut self.project_groups {
+                    group.project_ids.retain(|id| *id != project_id);
+                    if Some(group.id) == group_id {
+                        group.project_ids.push(project_id);
+                    }
+                }
+                vec![Effect::SaveAppState]
+            }
+            Action::OpenButtonSelectionChanged { selection } => {
+                let trimmed = selection.trim();
+                if trimmed.len() > 1024 {
                     return Vec::new();
                 }
                 let next = if trimmed.is_empty() {
@@ -2615,12 +4047,12 @@ impl AppState {
                 persistence::apply_persisted_app_state(self, *persisted)
             }
             Action::AppStateLoadFailed { message } => {
-                self.last_error = Some(message);
+                self.set_error(message);
                 Vec::new()
             }
             Action::AppStateSaved => Vec::new(),
             Action::AppStateSaveFailed { message } => {
-                self.last_error = Some(message);
+                self.set_error(message);
                 Vec::new()
             }
 
@@ -2628,7 +4060,24 @@ impl AppState {
                 self.last_error = None;
                 Vec::new()
             }
+            Action::DismissError { index } => {
+                self.error_log.remove(index);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Sets the current error and appends it to the bounded error log.
+    fn set_error(&mut self, message: impl Into<String>) {
+        let message = message.into();
+        self.error_log.push_back(crate::ErrorLogEntry {
+            message: message.clone(),
+            created_at_unix_ms: now_unix_ms(),
+        });
+        while self.error_log.len() > crate::state::MAX_ERROR_LOG_ENTRIES {
+            self.error_log.pop_front();
         }
+        self.last_error = Some(message);
     }
 
     pub fn to_persisted(&self) -> PersistedAppState {
@@ -2639,6 +4088,14 @@ impl AppState {
         self.projects.iter().find(|p| p.id == project_id)
     }
 
+    /// The group the project is filed under, or `None` when it is ungrouped.
+    pub fn project_group_id(&self, project_id: ProjectId) -> Option<ProjectGroupId> {
+        self.project_groups
+            .iter()
+            .find(|g| g.project_ids.contains(&project_id))
+            .map(|g| g.id)
+    }
+
     pub fn workspace(&self, workspace_id: WorkspaceId) -> Option<&Workspace> {
         self.projects
             .iter()
@@ -2662,6 +4119,24 @@ impl AppState {
         self.conversations.get(&(workspace_id, thread_id))
     }
 
+    /// Why `Action::TaskStatusSet` would reject moving the task to `task_status`, if it would.
+    pub fn task_status_change_error(
+        &self,
+        workspace_id: WorkspaceId,
+        thread_id: WorkspaceThreadId,
+        task_status: crate::TaskStatus,
+    ) -> Option<String> {
+        let conversation = self.conversations.get(&(workspace_id, thread_id));
+        match conversation.map(|c| c.task_status) {
+            None => Some("Task not found".to_owned()),
+            Some(existing) if existing == task_status => None,
+            Some(crate::TaskStatus::Done | crate::TaskStatus::Canceled) => {
+                Some("Task is archived".to_owned())
+            }
+            Some(_) => None,
+        }
+    }
+
     pub fn workspace_tabs(&self, workspace_id: WorkspaceId) -> Option<&WorkspaceTabs> {
         self.workspace_tabs.get(&workspace_id)
     }
@@ -2678,6 +4153,72 @@ impl AppState {
         }
     }
 
+    /// Allocates a new task in the workspace, seeds its conversation, and activates it.
+    fn create_workspace_thread(&mut self, workspace_id: WorkspaceId) -> WorkspaceThreadId {
+        let thread_id = {
+            let tabs = self.ensure_workspace_tabs_mut(workspace_id);
+            tabs.allocate_thread_id()
+        };
+        let (effective_runner, model_id, thinking_effort, amp_mode) =
+            self.new_thread_run_config(workspace_id);
+        let mut conversation = Self::default_conversation_with_defaults(
+            thread_id,
+            model_id,
+            thinking_effort,
+            effective_runner,
+        );
+        conversation.amp_mode = amp_mode;
+        conversation.task_status = crate::TaskStatus::Backlog;
+        conversation.push_entry(ConversationEntry::SystemEvent {
+            entry_id: format!("sys_{}", conversation.entries_total.saturating_add(1)),
+            created_at_unix_ms: now_unix_ms(),
+            event: crate::ConversationSystemEvent::TaskCreated,
+        });
+        self.conversations
+            .insert((workspace_id, thread_id), conversation);
+        self.ensure_workspace_tabs_mut(workspace_id)
+            .activate(thread_id);
+        thread_id
+    }
+
+    fn project_for_workspace(&self, workspace_id: WorkspaceId) -> Option<&Project> {
+        self.projects
+            .iter()
+            .find(|p| p.workspaces.iter().any(|w| w.id == workspace_id))
+    }
+
+    /// Run config a new task in the workspace starts with: the project's agent defaults where
+    /// set and usable, the global defaults otherwise.
+    fn new_thread_run_config(
+        &self,
+        workspace_id: WorkspaceId,
+    ) -> (
+        crate::AgentRunnerKind,
+        String,
+        ThinkingEffort,
+        Option<String>,
+    ) {
+        let defaults = self
+            .project_for_workspace(workspace_id)
+            .map(|project| project.agent_defaults.clone())
+            .unwrap_or_default();
+        // Reason: Use resolve_enabled_runner so new tasks respect
+        // which runners the user has enabled in settings.
+        let runner = defaults
+            .runner
+            .filter(|runner| runner_is_enabled(self, *runner))
+            .unwrap_or_else(|| resolve_enabled_runner(self));
+        let model_id = defaults
+            .model_id
+            .filter(|model_id| crate::model_valid_for_runner(runner, model_id))
+            .unwrap_or_else(|| self.resolve_default_model_for_runner(runner));
+        let thinking_effort = match defaults.thinking_effort {
+            Some(effort) => normalize_thinking_effort(&model_id, effort),
+            None => self.agent_default_thinking_effort,
+        };
+        (runner, model_id, thinking_effort, defaults.amp_mode)
+    }
+
     fn ensure_workspace_tabs_mut(&mut self, workspace_id: WorkspaceId) -> &mut WorkspaceTabs {
         use std::collections::hash_map::Entry;
 
@@ -2692,68 +4233,53 @@ impl AppState {
         workspace_id: WorkspaceId,
         thread_id: WorkspaceThreadId,
     ) -> &mut WorkspaceConversation {
-        use std::collections::hash_map::Entry;
-
         self.ensure_workspace_tabs_mut(workspace_id);
-        let default_thinking_effort = self.agent_default_thinking_effort;
-        let run_config_override = self
-            .workspace_thread_run_config_overrides
-            .get(&(workspace_id, thread_id))
-            .cloned();
-        let effective_runner = resolve_enabled_runner(self);
-        // Reason: Compute before the match to avoid borrowing self while
-        // self.conversations is mutably borrowed by HashMap::entry().
-        let runner_model_id = self.resolve_default_model_for_runner(effective_runner);
-        match self.conversations.entry((workspace_id, thread_id)) {
-            Entry::Occupied(entry) => entry.into_mut(),
-            Entry::Vacant(entry) => {
-                let mut conversation = Self::default_conversation_with_defaults(
-                    thread_id,
-                    runner_model_id,
-                    default_thinking_effort,
-                    effective_runner,
-                );
-                if let Some(run_config) = run_config_override {
-                    let mut overridden = false;
-                    if let Some(runner) = run_config
-                        .runner
-                        .as_deref()
-                        .and_then(crate::agent_settings::parse_agent_runner_kind)
-                    {
-                        conversation.run_config_overridden_by_user = true;
-                        conversation.agent_runner = runner;
-                        overridden = true;
-                    }
-                    if let Some(mode) = run_config
-                        .amp_mode
-                        .as_deref()
-                        .map(str::trim)
-                        .filter(|v| !v.is_empty())
-                    {
-                        conversation.run_config_overridden_by_user = true;
-                        conversation.amp_mode = Some(mode.to_owned());
-                        overridden = true;
-                    }
-                    if let Some(parsed_effort) =
-                        crate::agent_settings::parse_thinking_effort(&run_config.thinking_effort)
-                    {
-                        let normalized =
-                            normalize_thinking_effort(&run_config.model_id, parsed_effort);
-                        conversation.run_config_overridden_by_user = true;
-                        conversation.agent_model_id = run_config.model_id;
-                        conversation.thinking_effort = normalized;
-                        overridden = true;
-                    }
-                    if overridden
-                        && conversation.agent_runner == crate::AgentRunnerKind::Amp
-                        && conversation.amp_mode.is_none()
-                    {
-                        conversation.amp_mode = Some(self.agent_amp_mode.clone());
-                    }
+        let key = (workspace_id, thread_id);
+        // Reason: Build outside of HashMap::entry() since the defaults borrow self.
+        if !self.conversations.contains_key(&key) {
+            let mut conversation = self.default_conversation(workspace_id, thread_id);
+            if let Some(run_config) = self.workspace_thread_run_config_overrides.get(&key) {
+                let mut overridden = false;
+                if let Some(runner) = run_config
+                    .runner
+                    .as_deref()
+                    .and_then(crate::agent_settings::parse_agent_runner_kind)
+                {
+                    conversation.run_config_overridden_by_user = true;
+                    conversation.agent_runner = runner;
+                    overridden = true;
+                }
+                if let Some(mode) = run_config
+                    .amp_mode
+                    .as_deref()
+                    .map(str::trim)
+                    .filter(|v| !v.is_empty())
+                {
+                    conversation.run_config_overridden_by_user = true;
+                    conversation.amp_mode = Some(mode.to_owned());
+                    overridden = true;
+                }
+                if let Some(parsed_effort) =
+                    crate::agent_settings::parse_thinking_effort(&run_config.thinking_effort)
+                {
+                    let normalized = normalize_thinking_effort(&run_config.model_id, parsed_effort);
+                    conversation.run_config_overridden_by_user = true;
+                    conversation.agent_model_id = run_config.model_id.clone();
+                    conversation.thinking_effort = normalized;
+                    overridden = true;
+                }
+                if overridden
+                    && conversation.agent_runner == crate::AgentRunnerKind::Amp
+                    && conversation.amp_mode.is_none()
+                {
+                    conversation.amp_mode = Some(self.agent_amp_mode.clone());
                 }
-                entry.insert(conversation)
             }
+            self.conversations.insert(key, conversation);
         }
+        self.conversations
+            .get_mut(&key)
+            .expect("conversation was inserted above")
     }
 
     /// Resolve the default model ID for a runner: per-runner override →
@@ -2789,14 +4315,23 @@ impl AppState {
             task_status: crate::TaskStatus::Todo,
             draft: String::new(),
             draft_attachments: Vec::new(),
+            notes: String::new(),
+            notes_save_pending: false,
+            cwd_subpath: None,
+            hunk_comments: Vec::new(),
+            collapsed_ranges: Vec::new(),
             run_config_overridden_by_user: false,
             agent_runner,
             agent_model_id: model_id,
             thinking_effort,
             amp_mode: None,
+            claude_max_output_tokens: None,
+            claude_system_prompt: None,
+            auto_commit: false,
             entries: Vec::new(),
             entries_total: 0,
             entries_start: 0,
+            total_usage: crate::CodexUsage::default(),
             active_run_id: None,
             next_run_id: 1,
             run_status: OperationStatus::Idle,
@@ -2806,21 +4341,31 @@ impl AppState {
             next_queued_prompt_id: 1,
             pending_prompts: VecDeque::new(),
             queue_paused: false,
+            pending_tool_approvals: Vec::new(),
+            patch_apply_failed_entry_id: None,
+            auto_retry_attempts: 0,
+            last_turn_failed_command_ids: Vec::new(),
+            merged_reasoning_segments: Vec::new(),
+            follow_latest_item: true,
         }
     }
 
+    /// A not yet loaded or newly seen task of the workspace, using `new_thread_run_config`.
     pub(crate) fn default_conversation(
         &self,
+        workspace_id: WorkspaceId,
         thread_id: WorkspaceThreadId,
     ) -> WorkspaceConversation {
-        let effective_runner = resolve_enabled_runner(self);
-        let model_id = self.resolve_default_model_for_runner(effective_runner);
-        Self::default_conversation_with_defaults(
+        let (effective_runner, model_id, thinking_effort, amp_mode) =
+            self.new_thread_run_config(workspace_id);
+        let mut conversation = Self::default_conversation_with_defaults(
             thread_id,
             model_id,
-            self.agent_default_thinking_effort,
+            thinking_effort,
             effective_runner,
-        )
+        );
+        conversation.amp_mode = amp_mode;
+        conversation
     }
 
     fn add_project(&mut self, path: PathBuf, is_git: bool) -> ProjectId {
@@ -2832,6 +4377,8 @@ impl AppState {
             .find(|p| crate::paths::normalize_project_path(&p.path) == normalized_path)
         {
             project.is_git = is_git;
+            // Re-adding an archived project brings it back rather than doing nothing.
+            project.status = ProjectStatus::Active;
             return project.id;
         }
 
@@ -2854,6 +4401,16 @@ impl AppState {
             slug,
             is_git,
             expanded: false,
+            system_prompt: None,
+            default_branch: None,
+            worktree_root: None,
+            branch_name_template: None,
+            status: ProjectStatus::Active,
+            agent_defaults: Default::default(),
+            saved_commands: Vec::new(),
+            agent_env: Vec::new(),
+            pr_remote: None,
+            pr_base_branch: None,
             create_workspace_status: OperationStatus::Idle,
             workspaces: Vec::new(),
         });
@@ -2867,6 +4424,44 @@ impl AppState {
         (id, self.projects.len() != before)
     }
 
+    /// Hides the project while keeping its workdirs, tasks and history, and moves the main pane
+    /// away from it.
+    fn archive_project(&mut self, project_id: ProjectId) -> Vec<Effect> {
+        let Some(project) = self.projects.iter_mut().find(|p| p.id == project_id) else {
+            return Vec::new();
+        };
+        if project.status == ProjectStatus::Archived {
+            return Vec::new();
+        }
+        project.status = ProjectStatus::Archived;
+
+        let workspace_ids: Vec<WorkspaceId> = project.workspaces.iter().map(|w| w.id).collect();
+
+        if let Some(workspace_id) = self.last_open_workspace_id
+            && workspace_ids.contains(&workspace_id)
+        {
+            self.last_open_workspace_id = None;
+        }
+
+        if let Some(workspace_id) = self.dashboard_preview_workspace_id
+            && workspace_ids.contains(&workspace_id)
+        {
+            self.dashboard_preview_workspace_id = None;
+        }
+
+        let showing_project = match self.main_pane {
+            MainPane::Workspace(workspace_id) => workspace_ids.contains(&workspace_id),
+            MainPane::ProjectSettings(id) => id == project_id,
+            MainPane::None | MainPane::Dashboard => false,
+        };
+        if showing_project {
+            self.main_pane = MainPane::Dashboard;
+            self.right_pane = RightPane::None;
+        }
+
+        vec![Effect::SaveAppState]
+    }
+
     fn delete_project(&mut self, project_id: ProjectId) -> Vec<Effect> {
         let Some(project_idx) = self.projects.iter().position(|p| p.id == project_id) else {
             return Vec::new();
@@ -2879,6 +4474,9 @@ impl AppState {
             .collect();
 
         self.projects.remove(project_idx);
+        for group in &mut self.project_groups {
+            group.project_ids.retain(|id| *id != project_id);
+        }
 
         for workspace_id in &workspace_ids {
             self.workspace_tabs.remove(workspace_id);
@@ -2919,6 +4517,26 @@ impl AppState {
         vec![Effect::SaveAppState]
     }
 
+    /// Marks a worktree creation as running for the project. Returns `false` when the request
+    /// should be dropped (non-git project or a creation already in flight).
+    fn begin_create_workspace(&mut self, project_id: ProjectId) -> bool {
+        let Some(project) = self.projects.iter_mut().find(|p| p.id == project_id) else {
+            return true;
+        };
+        if !project.is_git {
+            self.set_error("Cannot create worktrees for a non-git project".to_owned());
+            return false;
+        }
+        if project.create_workspace_status == OperationStatus::Running {
+            return false;
+        }
+        project.create_workspace_status = OperationStatus::Running;
+        if project.workspaces.is_empty() {
+            self.insert_main_workspace(project_id);
+        }
+        true
+    }
+
     fn insert_main_workspace(&mut self, project_id: ProjectId) -> WorkspaceId {
         let workspace_id = WorkspaceId(self.next_workspace_id);
         self.next_workspace_id += 1;
@@ -2927,15 +4545,18 @@ impl AppState {
             return workspace_id;
         };
 
+        let branch_name = project.main_branch_name().to_owned();
         project.workspaces.push(Workspace {
             id: workspace_id,
             workspace_name: Self::MAIN_WORKSPACE_NAME.to_owned(),
-            branch_name: Self::MAIN_WORKSPACE_BRANCH.to_owned(),
+            branch_name,
             worktree_path: project.path.clone(),
             status: WorkspaceStatus::Active,
             last_activity_at: None,
             archive_status: OperationStatus::Idle,
             branch_rename_status: OperationStatus::Idle,
+            stash_status: OperationStatus::Idle,
+            commit_status: OperationStatus::Idle,
         });
 
         workspace_id
@@ -2966,6 +4587,8 @@ impl AppState {
                 last_activity_at: None,
                 archive_status: OperationStatus::Idle,
                 branch_rename_status: OperationStatus::Idle,
+                stash_status: OperationStatus::Idle,
+                commit_status: OperationStatus::Idle,
             });
             project.expanded = true;
             self.main_pane = MainPane::Workspace(workspace_id);
@@ -3029,6 +4652,40 @@ fn start_next_queued_prompt(
     ))
 }
 
+fn last_user_message(conversation: &WorkspaceConversation) -> Option<(String, Vec<AttachmentRef>)> {
+    conversation
+        .entries
+        .iter()
+        .rev()
+        .find_map(|entry| match entry {
+            ConversationEntry::UserEvent {
+                event: crate::UserEvent::Message { text, attachments },
+                ..
+            } => Some((text.clone(), attachments.clone())),
+            _ => None,
+        })
+}
+
+fn file_change_entry_id(conversation: &WorkspaceConversation, item_id: &str) -> Option<String> {
+    conversation
+        .entries
+        .iter()
+        .rev()
+        .find_map(|entry| match entry {
+            ConversationEntry::AgentEvent {
+                entry_id,
+                event: crate::AgentEvent::Item { item },
+                ..
+            } => match item.as_ref() {
+                crate::CodexThreadItem::FileChange { id, .. } if id == item_id => {
+                    Some(entry_id.clone())
+                }
+                _ => None,
+            },
+            _ => None,
+        })
+}
+
 fn start_agent_run(
     conversation: &mut WorkspaceConversation,
     workspace_id: WorkspaceId,
@@ -3036,20 +4693,54 @@ fn start_agent_run(
     text: String,
     attachments: Vec<AttachmentRef>,
     run_config: AgentRunConfig,
+) -> Effect {
+    conversation.auto_retry_attempts = 0;
+    begin_agent_run(
+        conversation,
+        workspace_id,
+        thread_id,
+        text,
+        attachments,
+        run_config,
+        false,
+    )
+}
+
+/// Starts a run of `text`; a `retry` re-runs a prompt whose user message is already in history.
+fn begin_agent_run(
+    conversation: &mut WorkspaceConversation,
+    workspace_id: WorkspaceId,
+    thread_id: WorkspaceThreadId,
+    text: String,
+    attachments: Vec<AttachmentRef>,
+    run_config: AgentRunConfig,
+    retry: bool,
 ) -> Effect {
     let run_id = conversation.next_run_id;
     conversation.next_run_id = conversation.next_run_id.saturating_add(1);
     conversation.active_run_id = Some(run_id);
-
-    conversation.push_entry(ConversationEntry::UserEvent {
+    conversation.patch_apply_failed_entry_id = None;
+
+    if !retry {
+        conversation.push_entry(ConversationEntry::UserEvent {
+            entry_id: String::new(),
+            created_at_unix_ms: 0,
+            event: crate::UserEvent::Message {
+                text: text.clone(),
+                attachments: attachments.clone(),
+            },
+        });
+    }
+    conversation.run_status = OperationStatus::Running;
+    let started_at_unix_ms = now_unix_ms();
+    conversation.push_entry(ConversationEntry::AgentEvent {
         entry_id: String::new(),
-        created_at_unix_ms: 0,
-        event: crate::UserEvent::Message {
-            text: text.clone(),
-            attachments: attachments.clone(),
+        created_at_unix_ms: started_at_unix_ms,
+        runner: Some(run_config.runner),
+        event: crate::AgentEvent::TurnStarted {
+            at_unix_ms: started_at_unix_ms,
         },
     });
-    conversation.run_status = OperationStatus::Running;
     conversation.run_started_at_unix_ms = None;
     conversation.run_finished_at_unix_ms = None;
     conversation.current_run_config = Some(run_config.clone());
@@ -3061,6 +4752,7 @@ fn start_agent_run(
         text,
         attachments,
         run_config,
+        retry,
     }
 }
 
@@ -3068,8 +4760,9 @@ fn start_agent_run(
 mod tests {
     use super::*;
     use crate::{
-        ChatScrollAnchor, CodexCommandExecutionStatus, CodexThreadError, CodexThreadItem,
-        CodexUsage, ContextTokenKind, ConversationSnapshot, ConversationThreadMeta,
+        BranchNameTemplate, ChatScrollAnchor, CodexCommandExecutionStatus, CodexThreadError,
+        CodexThreadItem, CodexUsage, ContextTokenKind, ConversationSnapshot,
+        ConversationThreadMeta, ProjectAgentDefaults, PullRequestTarget, TabDirection,
     };
 
     fn codex_item_id(item: &CodexThreadItem) -> &str {
@@ -3212,6 +4905,7 @@ mod tests {
                     task_status_last_analyzed_message_seq: 0,
                     turn_status: crate::TurnStatus::Idle,
                     last_turn_result: None,
+                    total_tokens: 0,
                 },
                 ConversationThreadMeta {
                     thread_id: WorkspaceThreadId(2),
@@ -3224,6 +4918,7 @@ mod tests {
                     task_status_last_analyzed_message_seq: 0,
                     turn_status: crate::TurnStatus::Idle,
                     last_turn_result: None,
+                    total_tokens: 0,
                 },
                 ConversationThreadMeta {
                     thread_id: WorkspaceThreadId(1),
@@ -3236,6 +4931,7 @@ mod tests {
                     task_status_last_analyzed_message_seq: 0,
                     turn_status: crate::TurnStatus::Idle,
                     last_turn_result: None,
+                    total_tokens: 0,
                 },
             ],
         });
@@ -3336,6 +5032,68 @@ mod tests {
         assert!(!state.conversations.contains_key(&(workspace_id, thread2)));
     }
 
+    #[test]
+    fn workspace_thread_moved_carries_state_to_target_workspace() {
+        let mut state = AppState::new();
+        state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/repo"),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+        for name in ["w1", "w2"] {
+            state.apply(Action::WorkspaceCreated {
+                project_id,
+                workspace_name: name.to_owned(),
+                branch_name: format!("repo/{name}"),
+                worktree_path: PathBuf::from(format!("/tmp/luban/worktrees/repo/{name}")),
+            });
+        }
+        let from = workspace_id_by_name(&state, "w1");
+        let to = workspace_id_by_name(&state, "w2");
+        state.apply(Action::OpenWorkspace { workspace_id: to });
+        state.apply(Action::OpenWorkspace { workspace_id: from });
+        state.apply(Action::CreateWorkspaceThread { workspace_id: from });
+        state.apply(Action::CreateWorkspaceThread { workspace_id: from });
+        let moved = state
+            .workspace_tabs(from)
+            .expect("missing workspace tabs")
+            .active_tab;
+        assert_eq!(moved, WorkspaceThreadId(2));
+        state.apply(Action::TaskStarSet {
+            workspace_id: from,
+            thread_id: moved,
+            starred: true,
+        });
+
+        let effects = state.apply(Action::WorkspaceThreadMoved {
+            from_workspace_id: from,
+            thread_id: moved,
+            to_workspace_id: to,
+            to_thread_id: WorkspaceThreadId(5),
+        });
+        assert!(
+            effects
+                .iter()
+                .any(|effect| matches!(effect, Effect::SaveAppState))
+        );
+
+        let source = state.workspace_tabs(from).expect("missing source tabs");
+        assert!(!source.open_tabs.contains(&moved));
+        assert_eq!(source.active_tab, WorkspaceThreadId(1));
+        assert!(!state.conversations.contains_key(&(from, moved)));
+        assert!(!state.starred_tasks.contains(&(from, moved)));
+
+        let target = state.workspace_tabs(to).expect("missing target tabs");
+        assert!(target.open_tabs.contains(&WorkspaceThreadId(5)));
+        assert_eq!(target.next_thread_id, 6);
+        let conversation = state
+            .conversations
+            .get(&(to, WorkspaceThreadId(5)))
+            .expect("moved conversation");
+        assert_eq!(conversation.local_thread_id, WorkspaceThreadId(5));
+        assert!(state.starred_tasks.contains(&(to, WorkspaceThreadId(5))));
+    }
+
     #[test]
     fn running_turn_keeps_its_run_config_when_user_changes_defaults() {
         let mut state = AppState::new();
@@ -3408,7 +5166,7 @@ mod tests {
     }
 
     #[test]
-    fn auto_title_thread_ignores_system_events_on_first_user_message() {
+    fn turns_are_clamped_to_the_model_thinking_effort_cap() {
         let mut state = AppState::new();
         state.apply(Action::AddProject {
             path: PathBuf::from("/tmp/repo"),
@@ -3421,56 +5179,208 @@ mod tests {
             branch_name: "repo/w1".to_owned(),
             worktree_path: PathBuf::from("/tmp/luban/worktrees/repo/w1"),
         });
-
         let workspace_id = workspace_id_by_name(&state, "w1");
+        state.apply(Action::CreateWorkspaceThread { workspace_id });
         let thread_id = WorkspaceThreadId(1);
 
-        state.apply(Action::ConversationLoaded {
+        let effects = state.apply(Action::ModelThinkingEffortCapChanged {
+            model_id: " gpt-5.3-codex ".to_owned(),
+            cap: Some(ThinkingEffort::Medium),
+        });
+        assert!(matches!(effects.as_slice(), [Effect::SaveAppState]));
+        state.apply(Action::ChatModelChanged {
             workspace_id,
             thread_id,
-            snapshot: ConversationSnapshot {
-                title: Some("Thread 1".to_owned()),
-                thread_id: None,
-                task_status: crate::TaskStatus::Todo,
-                runner: None,
-                agent_model_id: None,
-                thinking_effort: None,
-                amp_mode: None,
-                entries: vec![ConversationEntry::SystemEvent {
-                    entry_id: "sys_1".to_owned(),
-                    created_at_unix_ms: 1,
-                    event: crate::ConversationSystemEvent::TaskCreated,
-                }],
-                entries_total: 1,
-                entries_start: 0,
-                pending_prompts: Vec::new(),
-                queue_paused: false,
-                run_started_at_unix_ms: None,
-                run_finished_at_unix_ms: None,
-            },
+            model_id: "gpt-5.3-codex".to_owned(),
+        });
+        state.apply(Action::ThinkingEffortChanged {
+            workspace_id,
+            thread_id,
+            thinking_effort: ThinkingEffort::XHigh,
         });
-
-        let text = "Fix title auto summary".to_owned();
-        let expected_title = derive_thread_title(&text);
-        assert!(
-            !expected_title.is_empty(),
-            "expected derive_thread_title to produce a non-empty title"
-        );
 
         let effects = state.apply(Action::SendAgentMessage {
             workspace_id,
             thread_id,
-            text: text.clone(),
+            text: "hi".to_owned(),
             attachments: Vec::new(),
             runner: None,
             amp_mode: None,
         });
+        let sent_effort = effects
+            .iter()
+            .find_map(|effect| match effect {
+                Effect::RunAgentTurn { run_config, .. } => Some(run_config.thinking_effort),
+                _ => None,
+            })
+            .expect("missing RunAgentTurn effect");
+        assert_eq!(sent_effort, ThinkingEffort::Medium);
 
         let conversation = state
             .workspace_thread_conversation(workspace_id, thread_id)
             .expect("missing conversation");
-        assert_eq!(conversation.title, expected_title);
-
+        assert_eq!(conversation.thinking_effort, ThinkingEffort::XHigh);
+        assert_eq!(
+            conversation
+                .current_run_config
+                .as_ref()
+                .map(|config| config.thinking_effort),
+            Some(ThinkingEffort::Medium)
+        );
+    }
+
+    #[test]
+    fn loaded_snapshot_keeps_notes_while_save_is_pending() {
+        let mut state = AppState::new();
+        state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/repo"),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+        state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "w1".to_owned(),
+            branch_name: "repo/w1".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban/worktrees/repo/w1"),
+        });
+        let workspace_id = workspace_id_by_name(&state, "w1");
+        let thread_id = WorkspaceThreadId(1);
+        let snapshot = |notes: &str| ConversationSnapshot {
+            title: Some("Thread 1".to_owned()),
+            thread_id: None,
+            task_status: crate::TaskStatus::Todo,
+            runner: None,
+            agent_model_id: None,
+            thinking_effort: None,
+            amp_mode: None,
+            claude_max_output_tokens: None,
+            claude_system_prompt: None,
+            auto_commit: false,
+            entries: Vec::new(),
+            entries_total: 0,
+            entries_start: 0,
+            pending_prompts: Vec::new(),
+            queue_paused: false,
+            run_started_at_unix_ms: None,
+            run_finished_at_unix_ms: None,
+            notes: notes.to_owned(),
+            cwd_subpath: None,
+            hunk_comments: Vec::new(),
+            collapsed_ranges: Vec::new(),
+            draft: String::new(),
+            total_usage: Default::default(),
+        };
+        let notes = |state: &AppState| {
+            state
+                .workspace_thread_conversation(workspace_id, thread_id)
+                .expect("conversation")
+                .notes
+                .clone()
+        };
+
+        let effects = state.apply(Action::ThreadNotesChanged {
+            workspace_id,
+            thread_id,
+            notes: "local".to_owned(),
+        });
+        assert!(matches!(
+            effects.as_slice(),
+            [Effect::StoreConversationNotes { .. }]
+        ));
+
+        // A snapshot read before the debounced save landed still carries the old notes.
+        state.apply(Action::ConversationLoaded {
+            workspace_id,
+            thread_id,
+            snapshot: snapshot("stale"),
+        });
+        assert_eq!(notes(&state), "local");
+
+        state.apply(Action::ThreadNotesSaved {
+            workspace_id,
+            thread_id,
+        });
+        state.apply(Action::ConversationLoaded {
+            workspace_id,
+            thread_id,
+            snapshot: snapshot("remote"),
+        });
+        assert_eq!(notes(&state), "remote");
+    }
+
+    #[test]
+    fn auto_title_thread_ignores_system_events_on_first_user_message() {
+        let mut state = AppState::new();
+        state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/repo"),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+        state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "w1".to_owned(),
+            branch_name: "repo/w1".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban/worktrees/repo/w1"),
+        });
+
+        let workspace_id = workspace_id_by_name(&state, "w1");
+        let thread_id = WorkspaceThreadId(1);
+
+        state.apply(Action::ConversationLoaded {
+            workspace_id,
+            thread_id,
+            snapshot: ConversationSnapshot {
+                title: Some("Thread 1".to_owned()),
+                thread_id: None,
+                task_status: crate::TaskStatus::Todo,
+                runner: None,
+                agent_model_id: None,
+                thinking_effort: None,
+                amp_mode: None,
+                claude_max_output_tokens: None,
+                claude_system_prompt: None,
+                auto_commit: false,
+                entries: vec![ConversationEntry::SystemEvent {
+                    entry_id: "sys_1".to_owned(),
+                    created_at_unix_ms: 1,
+                    event: crate::ConversationSystemEvent::TaskCreated,
+                }],
+                entries_total: 1,
+                entries_start: 0,
+                pending_prompts: Vec::new(),
+                queue_paused: false,
+                run_started_at_unix_ms: None,
+                run_finished_at_unix_ms: None,
+                notes: String::new(),
+                cwd_subpath: None,
+                hunk_comments: Vec::new(),
+                collapsed_ranges: Vec::new(),
+                draft: String::new(),
+                total_usage: Default::default(),
+            },
+        });
+
+        let text = "Fix title auto summary".to_owned();
+        let expected_title = derive_thread_title(&text);
+        assert!(
+            !expected_title.is_empty(),
+            "expected derive_thread_title to produce a non-empty title"
+        );
+
+        let effects = state.apply(Action::SendAgentMessage {
+            workspace_id,
+            thread_id,
+            text: text.clone(),
+            attachments: Vec::new(),
+            runner: None,
+            amp_mode: None,
+        });
+
+        let conversation = state
+            .workspace_thread_conversation(workspace_id, thread_id)
+            .expect("missing conversation");
+        assert_eq!(conversation.title, expected_title);
+
         let (input, expected_current_title) = effects
             .iter()
             .find_map(|effect| match effect {
@@ -3515,6 +5425,9 @@ mod tests {
                 agent_model_id: None,
                 thinking_effort: None,
                 amp_mode: None,
+                claude_max_output_tokens: None,
+                claude_system_prompt: None,
+                auto_commit: false,
                 entries: vec![ConversationEntry::SystemEvent {
                     entry_id: "sys_1".to_owned(),
                     created_at_unix_ms: 1,
@@ -3526,6 +5439,12 @@ mod tests {
                 queue_paused: false,
                 run_started_at_unix_ms: None,
                 run_finished_at_unix_ms: None,
+                notes: String::new(),
+                cwd_subpath: None,
+                hunk_comments: Vec::new(),
+                collapsed_ranges: Vec::new(),
+                draft: String::new(),
+                total_usage: Default::default(),
             },
         });
 
@@ -3597,6 +5516,9 @@ mod tests {
             agent_model_id: Some("gpt-5.3-codex".to_owned()),
             thinking_effort: Some(ThinkingEffort::High),
             amp_mode: None,
+            claude_max_output_tokens: None,
+            claude_system_prompt: None,
+            auto_commit: false,
             entries: Vec::new(),
             entries_total: 0,
             entries_start: 0,
@@ -3604,6 +5526,12 @@ mod tests {
             queue_paused: false,
             run_started_at_unix_ms: None,
             run_finished_at_unix_ms: None,
+            notes: String::new(),
+            cwd_subpath: None,
+            hunk_comments: Vec::new(),
+            collapsed_ranges: Vec::new(),
+            draft: String::new(),
+            total_usage: Default::default(),
         };
 
         state.apply(Action::ConversationLoaded {
@@ -3763,6 +5691,196 @@ mod tests {
         assert_eq!(running.thinking_effort, ThinkingEffort::Minimal);
     }
 
+    #[test]
+    fn thread_total_usage_sums_turns_and_is_replaced_by_loaded_totals() {
+        let mut state = AppState::new();
+        state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/repo"),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+        state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "w1".to_owned(),
+            branch_name: "repo/w1".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban/worktrees/repo/w1"),
+        });
+        let workspace_id = workspace_id_by_name(&state, "w1");
+        state.apply(Action::CreateWorkspaceThread { workspace_id });
+        let thread_id = WorkspaceThreadId(1);
+
+        for (text, usage) in [
+            (
+                "first",
+                CodexUsage {
+                    input_tokens: 100,
+                    cached_input_tokens: 40,
+                    output_tokens: 10,
+                },
+            ),
+            (
+                "second",
+                CodexUsage {
+                    input_tokens: 200,
+                    cached_input_tokens: 0,
+                    output_tokens: 20,
+                },
+            ),
+        ] {
+            state.apply(Action::SendAgentMessage {
+                workspace_id,
+                thread_id,
+                text: text.to_owned(),
+                attachments: Vec::new(),
+                runner: None,
+                amp_mode: None,
+            });
+            let run_id = state
+                .workspace_thread_conversation(workspace_id, thread_id)
+                .and_then(|c| c.active_run_id)
+                .expect("missing active run id");
+            state.apply(Action::AgentEventReceived {
+                workspace_id,
+                thread_id,
+                run_id,
+                event: CodexThreadEvent::TurnCompleted { usage },
+            });
+        }
+
+        let conversation = state
+            .workspace_thread_conversation(workspace_id, thread_id)
+            .expect("missing conversation");
+        let summed = CodexUsage {
+            input_tokens: 300,
+            cached_input_tokens: 40,
+            output_tokens: 30,
+        };
+        assert_eq!(conversation.total_usage, summed);
+        assert_eq!(conversation.total_usage.total_tokens(), 330);
+        assert_eq!(
+            conversation
+                .entries
+                .iter()
+                .filter(|entry| matches!(
+                    entry,
+                    ConversationEntry::AgentEvent {
+                        event: crate::AgentEvent::TurnUsage { .. },
+                        ..
+                    }
+                ))
+                .count(),
+            2
+        );
+
+        // Storage recomputes the total from the persisted entries, including the live ones.
+        let mut entries = conversation.entries.clone();
+        entries.push(ConversationEntry::AgentEvent {
+            entry_id: "e_stored".to_owned(),
+            created_at_unix_ms: 1,
+            runner: None,
+            event: crate::AgentEvent::TurnDuration { duration_ms: 5 },
+        });
+        state.apply(Action::ConversationLoaded {
+            workspace_id,
+            thread_id,
+            snapshot: ConversationSnapshot {
+                title: None,
+                thread_id: None,
+                task_status: crate::TaskStatus::Todo,
+                runner: None,
+                agent_model_id: None,
+                thinking_effort: None,
+                amp_mode: None,
+                claude_max_output_tokens: None,
+                claude_system_prompt: None,
+                auto_commit: false,
+                entries_total: entries.len() as u64,
+                entries,
+                entries_start: 0,
+                pending_prompts: Vec::new(),
+                queue_paused: false,
+                run_started_at_unix_ms: None,
+                run_finished_at_unix_ms: None,
+                notes: String::new(),
+                cwd_subpath: None,
+                hunk_comments: Vec::new(),
+                collapsed_ranges: Vec::new(),
+                draft: String::new(),
+                total_usage: summed.clone(),
+            },
+        });
+        assert_eq!(
+            state
+                .workspace_thread_conversation(workspace_id, thread_id)
+                .unwrap()
+                .total_usage,
+            summed
+        );
+    }
+
+    #[test]
+    fn each_turn_start_records_a_turn_started_marker_after_its_prompt() {
+        let mut state = AppState::demo();
+        let workspace_id = first_non_main_workspace_id(&state);
+        let thread_id = default_thread_id();
+
+        for text in ["first", "second"] {
+            state.apply(Action::SendAgentMessage {
+                workspace_id,
+                thread_id,
+                text: text.to_owned(),
+                attachments: Vec::new(),
+                runner: None,
+                amp_mode: None,
+            });
+        }
+        let run_id = state
+            .workspace_thread_conversation(workspace_id, thread_id)
+            .and_then(|c| c.active_run_id)
+            .expect("missing active run id");
+        // Completing the first turn starts the queued prompt.
+        state.apply(Action::AgentEventReceived {
+            workspace_id,
+            thread_id,
+            run_id,
+            event: CodexThreadEvent::TurnCompleted {
+                usage: CodexUsage {
+                    input_tokens: 0,
+                    cached_input_tokens: 0,
+                    output_tokens: 0,
+                },
+            },
+        });
+
+        let conversation = state
+            .workspace_thread_conversation(workspace_id, thread_id)
+            .expect("missing conversation");
+        let timeline = conversation
+            .entries
+            .iter()
+            .filter_map(|entry| match entry {
+                ConversationEntry::UserEvent {
+                    event: crate::UserEvent::Message { text, .. },
+                    ..
+                } => Some(text.clone()),
+                ConversationEntry::AgentEvent {
+                    created_at_unix_ms,
+                    event: crate::AgentEvent::TurnStarted { at_unix_ms },
+                    ..
+                } => {
+                    assert!(*at_unix_ms > 0);
+                    assert_eq!(at_unix_ms, created_at_unix_ms);
+                    Some("turn_started".to_owned())
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            timeline,
+            vec!["first", "turn_started", "second", "turn_started"]
+        );
+    }
+
     #[test]
     fn first_message_does_not_trigger_ai_branch_rename() {
         let mut state = AppState::new();
@@ -3918,6 +6036,9 @@ mod tests {
             agent_model_id: None,
             thinking_effort: None,
             amp_mode: None,
+            claude_max_output_tokens: None,
+            claude_system_prompt: None,
+            auto_commit: false,
             entries: (1..=8)
                 .map(|idx| ConversationEntry::UserEvent {
                     entry_id: String::new(),
@@ -3934,6 +6055,12 @@ mod tests {
             queue_paused: false,
             run_started_at_unix_ms: None,
             run_finished_at_unix_ms: None,
+            notes: String::new(),
+            cwd_subpath: None,
+            hunk_comments: Vec::new(),
+            collapsed_ranges: Vec::new(),
+            draft: String::new(),
+            total_usage: Default::default(),
         };
         state.apply(Action::ConversationLoaded {
             workspace_id,
@@ -3993,15 +6120,274 @@ mod tests {
     }
 
     #[test]
-    fn open_dashboard_loads_conversations_for_non_main_workspaces() {
+    fn errors_accumulate_in_order_and_can_be_dismissed() {
         let mut state = AppState::new();
-        state.apply(Action::AddProject {
-            path: PathBuf::from("/tmp/repo"),
-            is_git: true,
-        });
-        let project_id = state.projects[0].id;
-        state.apply(Action::CreateWorkspace {
-            project_id,
+        for message in ["first", "second", "third"] {
+            state.apply(Action::AppStateSaveFailed {
+                message: message.to_owned(),
+            });
+        }
+
+        let messages = |state: &AppState| {
+            state
+                .error_log
+                .iter()
+                .map(|entry| entry.message.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(messages(&state), vec!["first", "second", "third"]);
+        assert_eq!(state.last_error.as_deref(), Some("third"));
+        assert!(
+            state
+                .error_log
+                .iter()
+                .zip(state.error_log.iter().skip(1))
+                .all(|(a, b)| a.created_at_unix_ms <= b.created_at_unix_ms)
+        );
+
+        state.apply(Action::DismissError { index: 1 });
+        assert_eq!(messages(&state), vec!["first", "third"]);
+
+        state.apply(Action::DismissError { index: 5 });
+        assert_eq!(messages(&state), vec!["first", "third"]);
+
+        state.apply(Action::ClearError);
+        assert_eq!(state.last_error, None);
+        assert_eq!(messages(&state), vec!["first", "third"]);
+
+        for i in 0..crate::state::MAX_ERROR_LOG_ENTRIES {
+            state.apply(Action::OpenWorkspaceInIdeFailed {
+                message: format!("overflow {i}"),
+            });
+        }
+        assert_eq!(state.error_log.len(), crate::state::MAX_ERROR_LOG_ENTRIES);
+        assert_eq!(state.error_log[0].message, "overflow 0");
+    }
+
+    #[test]
+    fn branch_rename_is_rejected_on_detached_head() {
+        let mut state = AppState::new();
+        state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/repo"),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+        state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "w1".to_owned(),
+            branch_name: "repo/w1".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban/worktrees/repo/w1"),
+        });
+        let workspace_id = workspace_id_by_name(&state, "w1");
+
+        state.apply(Action::WorkspaceBranchSynced {
+            workspace_id,
+            branch_name: crate::DETACHED_HEAD_BRANCH_NAME.to_owned(),
+        });
+        assert!(state.workspace(workspace_id).unwrap().is_detached_head());
+
+        let effects = state.apply(Action::WorkspaceBranchRenameRequested {
+            workspace_id,
+            requested_branch_name: "feature".to_owned(),
+        });
+        assert!(effects.is_empty());
+        assert!(
+            state
+                .last_error
+                .as_deref()
+                .is_some_and(|e| e.contains("detached HEAD"))
+        );
+        assert_eq!(
+            state.workspace(workspace_id).unwrap().branch_rename_status,
+            OperationStatus::Idle
+        );
+    }
+
+    #[test]
+    fn workspace_stash_tracks_status_and_surfaces_failures() {
+        let mut state = AppState::new();
+        state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/repo"),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+        state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "w1".to_owned(),
+            branch_name: "repo/w1".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban/worktrees/repo/w1"),
+        });
+        let workspace_id = workspace_id_by_name(&state, "w1");
+
+        let effects = state.apply(Action::WorkspaceStashChanges { workspace_id });
+        assert!(matches!(
+            effects.as_slice(),
+            [Effect::StashWorkspaceChanges { workspace_id: id }] if *id == workspace_id
+        ));
+        assert_eq!(
+            state.workspace(workspace_id).unwrap().stash_status,
+            OperationStatus::Running
+        );
+        assert!(
+            state
+                .apply(Action::WorkspaceStashPop { workspace_id })
+                .is_empty()
+        );
+
+        state.apply(Action::WorkspaceStashFinished { workspace_id });
+        assert_eq!(
+            state.workspace(workspace_id).unwrap().stash_status,
+            OperationStatus::Idle
+        );
+
+        let effects = state.apply(Action::WorkspaceStashPop { workspace_id });
+        assert!(matches!(
+            effects.as_slice(),
+            [Effect::PopWorkspaceStash { workspace_id: id }] if *id == workspace_id
+        ));
+        state.apply(Action::WorkspaceStashFailed {
+            workspace_id,
+            message: "No stash entries to pop".to_owned(),
+        });
+        assert_eq!(
+            state.workspace(workspace_id).unwrap().stash_status,
+            OperationStatus::Idle
+        );
+        assert_eq!(state.last_error.as_deref(), Some("No stash entries to pop"));
+    }
+
+    #[test]
+    fn workspace_commit_rejects_empty_input_and_tracks_status() {
+        let mut state = AppState::new();
+        state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/repo"),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+        state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "w1".to_owned(),
+            branch_name: "repo/w1".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban/worktrees/repo/w1"),
+        });
+        let workspace_id = workspace_id_by_name(&state, "w1");
+
+        let effects = state.apply(Action::WorkspaceCommit {
+            workspace_id,
+            message: "  \n".to_owned(),
+            paths: None,
+        });
+        assert!(effects.is_empty());
+        assert_eq!(
+            state.last_error.as_deref(),
+            Some("Commit message cannot be empty")
+        );
+
+        let effects = state.apply(Action::WorkspaceCommit {
+            workspace_id,
+            message: "Fix it".to_owned(),
+            paths: Some(vec![" ".to_owned()]),
+        });
+        assert!(effects.is_empty());
+        assert_eq!(
+            state.last_error.as_deref(),
+            Some("No files selected to commit")
+        );
+
+        let effects = state.apply(Action::WorkspaceCommit {
+            workspace_id,
+            message: " Fix it\n".to_owned(),
+            paths: Some(vec!["src/lib.rs".to_owned()]),
+        });
+        assert!(matches!(
+            effects.as_slice(),
+            [Effect::CommitWorkspaceChanges { workspace_id: id, message, paths }]
+                if *id == workspace_id
+                    && message == "Fix it"
+                    && paths.as_deref() == Some(&["src/lib.rs".to_owned()][..])
+        ));
+        assert_eq!(
+            state.workspace(workspace_id).unwrap().commit_status,
+            OperationStatus::Running
+        );
+
+        state.apply(Action::WorkspaceCommitFailed {
+            workspace_id,
+            message: "Nothing to commit".to_owned(),
+        });
+        assert_eq!(
+            state.workspace(workspace_id).unwrap().commit_status,
+            OperationStatus::Idle
+        );
+        assert_eq!(state.last_error.as_deref(), Some("Nothing to commit"));
+    }
+
+    #[test]
+    fn set_main_pane_switches_panes_and_rejects_unknown_ids() {
+        let mut state = AppState::new();
+        state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/repo"),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+        state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "w1".to_owned(),
+            branch_name: "repo/w1".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban/worktrees/repo/w1"),
+        });
+        let w1 = workspace_id_by_name(&state, "w1");
+
+        state.apply(Action::SetMainPane {
+            pane: MainPane::Workspace(w1),
+        });
+        assert_eq!(state.main_pane, MainPane::Workspace(w1));
+        assert_eq!(state.right_pane, RightPane::Terminal);
+
+        state.apply(Action::SetMainPane {
+            pane: MainPane::ProjectSettings(project_id),
+        });
+        assert_eq!(state.main_pane, MainPane::ProjectSettings(project_id));
+        assert_eq!(state.right_pane, RightPane::None);
+
+        state.apply(Action::SetMainPane {
+            pane: MainPane::Dashboard,
+        });
+        assert_eq!(state.main_pane, MainPane::Dashboard);
+
+        state.apply(Action::SetMainPane {
+            pane: MainPane::None,
+        });
+        assert_eq!(state.main_pane, MainPane::None);
+        assert_eq!(state.right_pane, RightPane::None);
+        assert_eq!(state.last_error, None);
+
+        let effects = state.apply(Action::SetMainPane {
+            pane: MainPane::Workspace(WorkspaceId::from_u64(9999)),
+        });
+        assert!(effects.is_empty());
+        assert_eq!(state.main_pane, MainPane::None);
+        assert_eq!(state.last_error.as_deref(), Some("Workspace not found"));
+
+        state.last_error = None;
+        let effects = state.apply(Action::SetMainPane {
+            pane: MainPane::ProjectSettings(ProjectId::from_u64(9999)),
+        });
+        assert!(effects.is_empty());
+        assert_eq!(state.main_pane, MainPane::None);
+        assert_eq!(state.last_error.as_deref(), Some("Project not found"));
+    }
+
+    #[test]
+    fn open_dashboard_loads_conversations_for_non_main_workspaces() {
+        let mut state = AppState::new();
+        state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/repo"),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+        state.apply(Action::CreateWorkspace {
+            project_id,
             branch_name_hint: None,
         });
         state.apply(Action::WorkspaceCreated {
@@ -4033,6 +6419,30 @@ mod tests {
         );
     }
 
+    #[test]
+    fn mark_read_clears_unread_completions_and_persists() {
+        let mut state = AppState::demo();
+        let w1 = first_non_main_workspace_id(&state);
+        let other = WorkspaceId::from_u64(w1.as_u64() + 1);
+        state.workspace_unread_completions.insert(w1);
+        state.workspace_unread_completions.insert(other);
+
+        let effects = state.apply(Action::MarkWorkspaceRead { workspace_id: w1 });
+        assert!(matches!(effects.as_slice(), [Effect::SaveAppState]));
+        assert!(!state.workspace_has_unread_completion(w1));
+        assert!(state.workspace_has_unread_completion(other));
+        assert!(
+            state
+                .apply(Action::MarkWorkspaceRead { workspace_id: w1 })
+                .is_empty()
+        );
+
+        let effects = state.apply(Action::MarkAllRead);
+        assert!(matches!(effects.as_slice(), [Effect::SaveAppState]));
+        assert!(state.workspace_unread_completions.is_empty());
+        assert!(state.apply(Action::MarkAllRead).is_empty());
+    }
+
     #[test]
     fn right_pane_tracks_selected_main_pane() {
         let mut state = AppState::new();
@@ -4124,6 +6534,9 @@ mod tests {
                 appearance_terminal_font: None,
                 agent_default_model_id: None,
                 agent_runner_default_models: HashMap::new(),
+                agent_runner_prompt_prefixes: HashMap::new(),
+                agent_runner_prompt_suffixes: HashMap::new(),
+                model_thinking_effort_caps: HashMap::new(),
                 agent_default_thinking_effort: None,
                 agent_default_runner: None,
                 agent_amp_mode: None,
@@ -4134,6 +6547,7 @@ mod tests {
                 last_open_workspace_id: None,
                 open_button_selection: None,
                 sidebar_project_order: Vec::new(),
+                project_groups: Vec::new(),
                 workspace_active_thread_id: HashMap::new(),
                 workspace_open_tabs: HashMap::new(),
                 workspace_archived_tabs: HashMap::new(),
@@ -4147,8 +6561,16 @@ mod tests {
                 telegram_enabled: None,
                 telegram_bot_token: None,
                 telegram_bot_username: None,
-                telegram_paired_chat_id: None,
+                telegram_paired_chat_ids: Vec::new(),
                 telegram_topic_bindings: None,
+                webhook_url: None,
+                webhook_secret: None,
+                agent_merge_reasoning_entries: None,
+                agent_pause_queue_on_patch_failure: None,
+                agent_item_dedup_window: None,
+                agent_auto_retry_on_failure: None,
+                agent_command_output_max_bytes: None,
+                auto_archive_after_days: None,
             }),
         });
         assert_eq!(state.terminal_pane_width, Some(480));
@@ -4179,6 +6601,9 @@ mod tests {
                 appearance_terminal_font: None,
                 agent_default_model_id: None,
                 agent_runner_default_models: HashMap::new(),
+                agent_runner_prompt_prefixes: HashMap::new(),
+                agent_runner_prompt_suffixes: HashMap::new(),
+                model_thinking_effort_caps: HashMap::new(),
                 agent_default_thinking_effort: None,
                 agent_default_runner: None,
                 agent_amp_mode: None,
@@ -4189,6 +6614,7 @@ mod tests {
                 last_open_workspace_id: None,
                 open_button_selection: None,
                 sidebar_project_order: Vec::new(),
+                project_groups: Vec::new(),
                 workspace_active_thread_id: HashMap::new(),
                 workspace_open_tabs: HashMap::new(),
                 workspace_archived_tabs: HashMap::new(),
@@ -4202,8 +6628,16 @@ mod tests {
                 telegram_enabled: None,
                 telegram_bot_token: None,
                 telegram_bot_username: None,
-                telegram_paired_chat_id: None,
+                telegram_paired_chat_ids: Vec::new(),
                 telegram_topic_bindings: None,
+                webhook_url: None,
+                webhook_secret: None,
+                agent_merge_reasoning_entries: None,
+                agent_pause_queue_on_patch_failure: None,
+                agent_item_dedup_window: None,
+                agent_auto_retry_on_failure: None,
+                agent_command_output_max_bytes: None,
+                auto_archive_after_days: None,
             }),
         });
         assert_eq!(restored.global_zoom_percent, 135);
@@ -4234,6 +6668,9 @@ mod tests {
                 appearance_terminal_font: None,
                 agent_default_model_id: None,
                 agent_runner_default_models: HashMap::new(),
+                agent_runner_prompt_prefixes: HashMap::new(),
+                agent_runner_prompt_suffixes: HashMap::new(),
+                model_thinking_effort_caps: HashMap::new(),
                 agent_default_thinking_effort: None,
                 agent_default_runner: None,
                 agent_amp_mode: None,
@@ -4244,6 +6681,7 @@ mod tests {
                 last_open_workspace_id: None,
                 open_button_selection: None,
                 sidebar_project_order: Vec::new(),
+                project_groups: Vec::new(),
                 workspace_active_thread_id: HashMap::new(),
                 workspace_open_tabs: HashMap::new(),
                 workspace_archived_tabs: HashMap::new(),
@@ -4257,8 +6695,16 @@ mod tests {
                 telegram_enabled: None,
                 telegram_bot_token: None,
                 telegram_bot_username: None,
-                telegram_paired_chat_id: None,
+                telegram_paired_chat_ids: Vec::new(),
                 telegram_topic_bindings: None,
+                webhook_url: None,
+                webhook_secret: None,
+                agent_merge_reasoning_entries: None,
+                agent_pause_queue_on_patch_failure: None,
+                agent_item_dedup_window: None,
+                agent_auto_retry_on_failure: None,
+                agent_command_output_max_bytes: None,
+                auto_archive_after_days: None,
             }),
         });
         assert_eq!(state.sidebar_width, Some(360));
@@ -4306,818 +6752,2857 @@ mod tests {
     }
 
     #[test]
-    fn appearance_theme_is_persisted() {
+    fn sidebar_project_order_drops_unknown_ids_and_appends_missing_projects() {
         let mut state = AppState::new();
-        let effects = state.apply(Action::AppearanceThemeChanged {
-            theme: crate::AppearanceTheme::Dark,
-        });
-        assert_eq!(state.appearance_theme, crate::AppearanceTheme::Dark);
-        assert_eq!(effects.len(), 1);
-        assert!(matches!(effects[0], Effect::SaveAppState));
+        for name in ["a", "b", "c"] {
+            state.apply(Action::AddProject {
+                path: PathBuf::from(format!("/tmp/sidebar-validate-{name}")),
+                is_git: true,
+            });
+        }
+        let key = |idx: usize| state.projects[idx].path.to_string_lossy().to_string();
+        let (a, b, c) = (key(0), key(1), key(2));
 
-        let persisted = state.to_persisted();
-        assert_eq!(persisted.appearance_theme.as_deref(), Some("dark"));
+        state.apply(Action::SidebarProjectOrderChanged {
+            project_ids: vec![c.clone(), a.clone(), b.clone()],
+        });
+        state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/sidebar-validate-d"),
+            is_git: true,
+        });
+        let d = state.projects[3].path.to_string_lossy().to_string();
 
-        let mut restored = AppState::new();
-        restored.apply(Action::AppStateLoaded {
-            persisted: Box::new(PersistedAppState {
-                projects: Vec::new(),
-                sidebar_width: None,
-                terminal_pane_width: None,
-                global_zoom_percent: None,
-                appearance_theme: Some("light".to_owned()),
-                appearance_ui_font: None,
-                appearance_chat_font: None,
-                appearance_code_font: None,
-                appearance_terminal_font: None,
-                agent_default_model_id: None,
-                agent_runner_default_models: HashMap::new(),
-                agent_default_thinking_effort: None,
-                agent_default_runner: None,
-                agent_amp_mode: None,
-                agent_codex_enabled: Some(true),
-                agent_amp_enabled: Some(true),
-                agent_claude_enabled: Some(true),
-                agent_droid_enabled: Some(true),
-                last_open_workspace_id: None,
-                open_button_selection: None,
-                sidebar_project_order: Vec::new(),
-                workspace_active_thread_id: HashMap::new(),
-                workspace_open_tabs: HashMap::new(),
-                workspace_archived_tabs: HashMap::new(),
-                workspace_next_thread_id: HashMap::new(),
-                workspace_chat_scroll_y10: HashMap::new(),
-                workspace_chat_scroll_anchor: HashMap::new(),
-                workspace_unread_completions: HashMap::new(),
-                workspace_thread_run_config_overrides: HashMap::new(),
-                starred_tasks: HashMap::new(),
-                task_prompt_templates: HashMap::new(),
-                telegram_enabled: None,
-                telegram_bot_token: None,
-                telegram_bot_username: None,
-                telegram_paired_chat_id: None,
-                telegram_topic_bindings: None,
-            }),
+        let effects = state.apply(Action::SidebarProjectOrderChanged {
+            project_ids: vec![
+                b.clone(),
+                "/tmp/sidebar-validate-deleted".to_owned(),
+                a.clone(),
+            ],
         });
-        assert_eq!(restored.appearance_theme, crate::AppearanceTheme::Light);
+        assert!(matches!(effects.as_slice(), [Effect::SaveAppState]));
+        assert_eq!(state.sidebar_project_order, vec![b, a, c, d]);
     }
 
     #[test]
-    fn appearance_fonts_are_persisted() {
+    fn main_workspace_tracks_detected_default_branch() {
         let mut state = AppState::new();
-        let effects = state.apply(Action::AppearanceFontsChanged {
-            ui_font: "Inter".to_owned(),
-            chat_font: "Roboto".to_owned(),
-            code_font: "Geist Mono".to_owned(),
-            terminal_font: "JetBrains Mono".to_owned(),
+        let effects = state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/default-branch"),
+            is_git: true,
         });
-        assert_eq!(
-            state.appearance_fonts,
-            crate::AppearanceFonts {
-                ui_font: "Inter".to_owned(),
-                chat_font: "Roboto".to_owned(),
-                code_font: "Geist Mono".to_owned(),
-                terminal_font: "JetBrains Mono".to_owned(),
-            }
-        );
-        assert_eq!(effects.len(), 1);
-        assert!(matches!(effects[0], Effect::SaveAppState));
+        let project_id = state.projects[0].id;
+        assert!(effects.iter().any(|e| matches!(
+            e,
+            Effect::DetectProjectDefaultBranch { project_id: id } if *id == project_id
+        )));
 
-        let persisted = state.to_persisted();
-        assert_eq!(persisted.appearance_ui_font.as_deref(), Some("Inter"));
-        assert_eq!(persisted.appearance_chat_font.as_deref(), Some("Roboto"));
-        assert_eq!(
-            persisted.appearance_code_font.as_deref(),
-            Some("Geist Mono")
-        );
-        assert_eq!(
-            persisted.appearance_terminal_font.as_deref(),
-            Some("JetBrains Mono")
-        );
+        state.apply(Action::EnsureMainWorkspace { project_id });
+        assert_eq!(state.projects[0].workspaces[0].branch_name, "main");
+
+        let effects = state.apply(Action::ProjectDefaultBranchDetected {
+            project_id,
+            default_branch: Some("master".to_owned()),
+        });
+        assert!(matches!(effects.as_slice(), [Effect::SaveAppState]));
+        assert_eq!(state.projects[0].default_branch.as_deref(), Some("master"));
+        assert_eq!(state.projects[0].workspaces[0].branch_name, "master");
+
+        state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/default-branch-develop"),
+            is_git: true,
+        });
+        let other_id = state.projects[1].id;
+        state.apply(Action::ProjectDefaultBranchDetected {
+            project_id: other_id,
+            default_branch: Some("develop".to_owned()),
+        });
+        state.apply(Action::EnsureMainWorkspace {
+            project_id: other_id,
+        });
+        assert_eq!(state.projects[1].workspaces[0].branch_name, "develop");
     }
 
     #[test]
-    fn workspace_chat_scroll_is_persisted() {
+    fn ensure_main_workspace_is_idempotent_and_respects_root_checkouts() {
         let mut state = AppState::new();
-        let workspace_id = WorkspaceId(42);
-        let thread_id = default_thread_id();
-
-        let effects = state.apply(Action::WorkspaceChatScrollSaved {
-            workspace_id,
-            thread_id,
-            offset_y10: -1234,
+        state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/ensure-main"),
+            is_git: true,
         });
-        assert_eq!(
-            state
-                .workspace_chat_scroll_y10
-                .get(&(workspace_id, thread_id))
-                .copied(),
-            Some(-1234)
-        );
-        assert_eq!(effects.len(), 1);
-        assert!(matches!(effects[0], Effect::SaveAppState));
-
-        let persisted = state.to_persisted();
-        assert_eq!(
-            persisted.workspace_chat_scroll_y10.get(&(42, 1)).copied(),
-            Some(-1234)
-        );
+        let project_id = state.projects[0].id;
+        state.apply(Action::EnsureMainWorkspace { project_id });
+        let effects = state.apply(Action::EnsureMainWorkspace { project_id });
+        assert!(effects.is_empty());
+        assert_eq!(state.projects[0].workspaces.len(), 1);
 
-        let mut loaded = AppState::new();
-        loaded.apply(Action::AppStateLoaded {
-            persisted: Box::new(persisted),
+        state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/ensure-main-root"),
+            is_git: true,
         });
-        assert_eq!(
-            loaded
-                .workspace_chat_scroll_y10
-                .get(&(workspace_id, thread_id))
-                .copied(),
-            Some(-1234)
-        );
+        let other_id = state.projects[1].id;
+        state.apply(Action::WorkspaceCreated {
+            project_id: other_id,
+            workspace_name: "trunk".to_owned(),
+            branch_name: "trunk".to_owned(),
+            worktree_path: PathBuf::from("/tmp/ensure-main-root"),
+        });
+        let effects = state.apply(Action::EnsureMainWorkspace {
+            project_id: other_id,
+        });
+        assert!(effects.is_empty());
+        assert_eq!(state.projects[1].workspaces.len(), 1);
     }
 
     #[test]
-    fn workspace_chat_scroll_anchor_is_persisted() {
+    fn archived_projects_keep_workdirs_slugs_and_persist_until_restored() {
         let mut state = AppState::new();
-        let workspace_id = WorkspaceId(42);
-        let thread_id = default_thread_id();
-
-        let anchor = ChatScrollAnchor::Block {
-            block_id: "history-block-agent-turn-3".to_owned(),
-            block_index: 3,
-            offset_in_block_y10: 420,
-        };
-
-        let effects = state.apply(Action::WorkspaceChatScrollAnchorSaved {
-            workspace_id,
-            thread_id,
-            anchor: anchor.clone(),
+        state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/a/luban"),
+            is_git: true,
         });
-        assert_eq!(
+        let project_id = state.projects[0].id;
+        state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "abandon-about".to_owned(),
+            branch_name: "luban/abandon-about".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban/worktrees/luban/abandon-about"),
+        });
+        let workspace_id = state.projects[0].workspaces[0].id;
+        state.apply(Action::OpenWorkspace { workspace_id });
+
+        let effects = state.apply(Action::ArchiveProject { project_id });
+        assert!(matches!(effects.as_slice(), [Effect::SaveAppState]));
+        assert!(
             state
-                .workspace_chat_scroll_anchor
-                .get(&(workspace_id, thread_id))
-                .cloned(),
-            Some(anchor.clone())
+                .apply(Action::ArchiveProject { project_id })
+                .is_empty()
         );
-        assert_eq!(effects.len(), 1);
-        assert!(matches!(effects[0], Effect::SaveAppState));
+        assert_eq!(state.projects[0].status, ProjectStatus::Archived);
+        assert_eq!(state.projects[0].workspaces.len(), 1);
+        assert_eq!(state.main_pane, MainPane::Dashboard);
 
-        let persisted = state.to_persisted();
-        assert_eq!(
-            persisted
-                .workspace_chat_scroll_anchor
-                .get(&(42, 1))
-                .cloned(),
-            Some(anchor.clone())
-        );
+        state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/b/luban"),
+            is_git: true,
+        });
+        assert_eq!(state.projects[1].slug, "luban-2");
 
-        let mut loaded = AppState::new();
-        loaded.apply(Action::AppStateLoaded {
-            persisted: Box::new(persisted),
+        let mut restored = AppState::new();
+        restored.apply(Action::AppStateLoaded {
+            persisted: Box::new(state.to_persisted()),
         });
-        assert_eq!(
-            loaded
-                .workspace_chat_scroll_anchor
-                .get(&(workspace_id, thread_id))
-                .cloned(),
-            Some(anchor)
-        );
+        assert_eq!(restored.projects[0].status, ProjectStatus::Archived);
+        assert_eq!(restored.projects[1].status, ProjectStatus::Active);
+
+        let effects = state.apply(Action::RestoreProject { project_id });
+        assert!(matches!(effects.as_slice(), [Effect::SaveAppState]));
+        assert_eq!(state.projects[0].status, ProjectStatus::Active);
+        assert_eq!(state.projects[0].slug, "luban");
+        assert!(state.workspace(workspace_id).is_some());
+
+        state.apply(Action::ArchiveProject { project_id });
+        state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/a/luban"),
+            is_git: true,
+        });
+        assert_eq!(state.projects.len(), 2);
+        assert_eq!(state.projects[0].status, ProjectStatus::Active);
     }
 
     #[test]
-    fn workspace_thread_tabs_preserve_order_and_allow_reorder() {
+    fn project_agent_defaults_apply_to_new_tasks_only() {
         let mut state = AppState::new();
         state.apply(Action::AddProject {
-            path: PathBuf::from("/tmp/repo"),
+            path: PathBuf::from("/tmp/agent-defaults"),
             is_git: true,
         });
         let project_id = state.projects[0].id;
         state.apply(Action::WorkspaceCreated {
             project_id,
             workspace_name: "w1".to_owned(),
-            branch_name: "repo/w1".to_owned(),
-            worktree_path: PathBuf::from("/tmp/luban/worktrees/repo/w1"),
+            branch_name: "luban/w1".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban/worktrees/agent-defaults/w1"),
         });
         let workspace_id = workspace_id_by_name(&state, "w1");
-        state.apply(Action::OpenWorkspace { workspace_id });
-
         state.apply(Action::CreateWorkspaceThread { workspace_id });
-        let mut thread_ids = vec![state.active_thread_id(workspace_id).unwrap()];
-        for _ in 0..3 {
-            state.apply(Action::CreateWorkspaceThread { workspace_id });
-            thread_ids.push(state.active_thread_id(workspace_id).unwrap());
-        }
-
-        let tabs = state.workspace_tabs(workspace_id).unwrap();
-        assert_eq!(tabs.open_tabs, thread_ids);
+        let existing = state
+            .workspace_tabs(workspace_id)
+            .expect("missing workspace tabs")
+            .active_tab;
+        let existing_run_config = state
+            .workspace_thread_conversation(workspace_id, existing)
+            .map(|c| (c.agent_runner, c.agent_model_id.clone(), c.thinking_effort))
+            .expect("missing conversation");
 
-        state.apply(Action::ActivateWorkspaceThread {
-            workspace_id,
-            thread_id: thread_ids[1],
+        let rejected = state.apply(Action::SetProjectAgentDefaults {
+            project_id,
+            defaults: ProjectAgentDefaults {
+                runner: Some(crate::AgentRunnerKind::Codex),
+                model_id: Some("claude-opus-4-6".to_owned()),
+                ..Default::default()
+            },
         });
-        let tabs = state.workspace_tabs(workspace_id).unwrap();
-        assert_eq!(tabs.active_tab, thread_ids[1]);
-        assert_eq!(
-            tabs.open_tabs, thread_ids,
-            "activating a thread should not reorder tabs"
-        );
+        assert!(rejected.is_empty());
+        assert!(state.projects[0].agent_defaults.is_empty());
 
-        let effects = state.apply(Action::ReorderWorkspaceThreadTab {
-            workspace_id,
-            thread_id: thread_ids[3],
-            to_index: 1,
+        let effects = state.apply(Action::SetProjectAgentDefaults {
+            project_id,
+            defaults: ProjectAgentDefaults {
+                runner: Some(crate::AgentRunnerKind::Codex),
+                model_id: Some(" gpt-5.2-codex ".to_owned()),
+                thinking_effort: Some(ThinkingEffort::XHigh),
+                amp_mode: Some("  ".to_owned()),
+            },
         });
-        assert_eq!(effects.len(), 1);
-        assert!(matches!(effects[0], Effect::SaveAppState));
-        let tabs = state.workspace_tabs(workspace_id).unwrap();
+        assert!(matches!(effects.as_slice(), [Effect::SaveAppState]));
         assert_eq!(
-            tabs.open_tabs,
-            vec![thread_ids[0], thread_ids[3], thread_ids[1], thread_ids[2]]
+            state.projects[0].agent_defaults.model_id.as_deref(),
+            Some("gpt-5.2-codex")
         );
+        assert_eq!(state.projects[0].agent_defaults.amp_mode, None);
 
-        let persisted = state.to_persisted();
-        assert_eq!(
-            persisted.workspace_open_tabs.get(&workspace_id.0).cloned(),
-            Some(vec![
-                thread_ids[0].0,
-                thread_ids[3].0,
-                thread_ids[1].0,
-                thread_ids[2].0
-            ])
-        );
+        let effects = state.apply(Action::CreateWorkspaceThread { workspace_id });
+        assert!(effects.iter().any(|effect| matches!(
+            effect,
+            Effect::StoreConversationRunConfig { model_id, .. } if model_id == "gpt-5.2-codex"
+        )));
+        let created = state
+            .workspace_tabs(workspace_id)
+            .expect("missing workspace tabs")
+            .active_tab;
+        let conversation = state
+            .workspace_thread_conversation(workspace_id, created)
+            .expect("missing conversation");
+        assert_eq!(conversation.agent_runner, crate::AgentRunnerKind::Codex);
+        assert_eq!(conversation.agent_model_id, "gpt-5.2-codex");
+        assert_eq!(conversation.thinking_effort, ThinkingEffort::XHigh);
 
-        let closed_thread = thread_ids[3];
-        state.apply(Action::CloseWorkspaceThreadTab {
-            workspace_id,
-            thread_id: closed_thread,
-        });
-        let tabs = state.workspace_tabs(workspace_id).unwrap();
-        assert!(
-            !tabs.open_tabs.contains(&closed_thread),
-            "closing a tab should archive it"
-        );
-        assert!(
-            tabs.archived_tabs.contains(&closed_thread),
-            "archived tabs should retain the closed thread id"
-        );
-        assert!(
+        assert_eq!(
             state
-                .workspace_thread_conversation(workspace_id, closed_thread)
-                .is_some(),
-            "archiving a tab should not delete the conversation"
+                .workspace_thread_conversation(workspace_id, existing)
+                .map(|c| (c.agent_runner, c.agent_model_id.clone(), c.thinking_effort)),
+            Some(existing_run_config)
         );
 
-        state.apply(Action::RestoreWorkspaceThreadTab {
-            workspace_id,
-            thread_id: closed_thread,
+        let mut restored = AppState::new();
+        restored.apply(Action::AppStateLoaded {
+            persisted: Box::new(state.to_persisted()),
         });
-        let tabs = state.workspace_tabs(workspace_id).unwrap();
-        assert!(
-            tabs.open_tabs.contains(&closed_thread),
-            "restoring a tab should re-open it"
-        );
-        assert!(
-            !tabs.archived_tabs.contains(&closed_thread),
-            "restoring a tab should remove it from the archive list"
+        assert_eq!(
+            restored.projects[0].agent_defaults,
+            state.projects[0].agent_defaults
         );
     }
 
     #[test]
-    fn project_expanded_is_persisted() {
-        let mut state = AppState::new();
-        state.apply(Action::AddProject {
-            path: PathBuf::from("/tmp/repo"),
-            is_git: true,
-        });
-        let project_id = state.projects[0].id;
-
-        let effects = state.apply(Action::ToggleProjectExpanded { project_id });
-        assert_eq!(effects.len(), 1);
-        assert!(matches!(effects[0], Effect::SaveAppState));
-        assert!(state.projects[0].expanded);
-
-        let persisted = state.to_persisted();
-        assert_eq!(persisted.projects.len(), 1);
-        assert!(persisted.projects[0].expanded);
-
-        let mut loaded = AppState::new();
-        loaded.apply(Action::AppStateLoaded {
-            persisted: Box::new(persisted),
-        });
-        assert!(loaded.projects[0].expanded);
-    }
-
-    #[test]
-    fn agent_item_updates_are_appended_as_entries() {
+    fn loaded_tasks_without_a_run_config_use_project_agent_defaults() {
         let mut state = AppState::new();
         state.apply(Action::AddProject {
-            path: PathBuf::from("/tmp/repo"),
+            path: PathBuf::from("/tmp/agent-defaults-loaded"),
             is_git: true,
         });
         let project_id = state.projects[0].id;
         state.apply(Action::WorkspaceCreated {
             project_id,
-            workspace_name: "abandon-about".to_owned(),
-            branch_name: "luban/abandon-about".to_owned(),
-            worktree_path: PathBuf::from("/tmp/luban/worktrees/repo/abandon-about"),
+            workspace_name: "w1".to_owned(),
+            branch_name: "luban/w1".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban/worktrees/agent-defaults-loaded/w1"),
         });
-        let workspace_id = workspace_id_by_name(&state, "abandon-about");
-        let thread_id = default_thread_id();
+        state.apply(Action::SetProjectAgentDefaults {
+            project_id,
+            defaults: ProjectAgentDefaults {
+                runner: Some(crate::AgentRunnerKind::Claude),
+                model_id: Some("claude-sonnet-4-5-20250929".to_owned()),
+                ..Default::default()
+            },
+        });
+        let workspace_id = workspace_id_by_name(&state, "w1");
+        let thread_id = WorkspaceThreadId(1);
 
-        state.apply(Action::SendAgentMessage {
+        state.apply(Action::OpenWorkspace { workspace_id });
+        state.apply(Action::ConversationLoaded {
             workspace_id,
             thread_id,
-            text: "Test".to_owned(),
-            attachments: Vec::new(),
-            runner: None,
-            amp_mode: None,
-        });
-        let run_id = state
-            .workspace_thread_conversation(workspace_id, thread_id)
-            .expect("missing conversation")
-            .active_run_id
-            .expect("missing active run id");
-
-        state.apply(Action::AgentEventReceived {
-            workspace_id,
-            thread_id,
-            run_id,
-            event: CodexThreadEvent::ItemStarted {
-                item: CodexThreadItem::Reasoning {
-                    id: "r-1".to_owned(),
-                    text: "x".to_owned(),
-                },
-            },
-        });
-        state.apply(Action::AgentEventReceived {
-            workspace_id,
-            thread_id,
-            run_id,
-            event: CodexThreadEvent::ItemStarted {
-                item: CodexThreadItem::CommandExecution {
-                    id: "c-1".to_owned(),
-                    command: "echo hello".to_owned(),
-                    aggregated_output: String::new(),
-                    exit_code: None,
-                    status: CodexCommandExecutionStatus::InProgress,
-                },
+            snapshot: ConversationSnapshot {
+                title: Some("Thread 1".to_owned()),
+                thread_id: None,
+                task_status: crate::TaskStatus::Todo,
+                runner: None,
+                agent_model_id: None,
+                thinking_effort: None,
+                amp_mode: None,
+                claude_max_output_tokens: None,
+                claude_system_prompt: None,
+                auto_commit: false,
+                entries: Vec::new(),
+                entries_total: 0,
+                entries_start: 0,
+                pending_prompts: Vec::new(),
+                queue_paused: false,
+                run_started_at_unix_ms: None,
+                run_finished_at_unix_ms: None,
+                notes: String::new(),
+                cwd_subpath: None,
+                hunk_comments: Vec::new(),
+                collapsed_ranges: Vec::new(),
+                draft: String::new(),
+                total_usage: Default::default(),
             },
         });
 
         let conversation = state
             .workspace_thread_conversation(workspace_id, thread_id)
             .expect("missing conversation");
-
-        let agent_item_entries: Vec<(&str, &str)> = conversation
-            .entries
-            .iter()
-            .filter_map(|entry| match entry {
-                ConversationEntry::AgentEvent {
-                    entry_id,
-                    event: crate::AgentEvent::Item { item },
-                    ..
-                } => Some((entry_id.as_str(), codex_item_id(item.as_ref()))),
-                _ => None,
-            })
-            .collect();
-        assert_eq!(agent_item_entries.len(), 2);
-        assert_eq!(agent_item_entries[0].1, "r-1");
-        assert_eq!(agent_item_entries[1].1, "c-1");
-        assert_ne!(agent_item_entries[0].0, agent_item_entries[1].0);
+        assert_eq!(conversation.agent_runner, crate::AgentRunnerKind::Claude);
+        assert_eq!(conversation.agent_model_id, "claude-sonnet-4-5-20250929");
     }
 
     #[test]
-    fn app_started_emits_load_app_state_effect() {
-        let mut state = AppState::new();
-        let effects = state.apply(Action::AppStarted);
-        assert_eq!(effects.len(), 1);
-        assert!(matches!(effects[0], Effect::LoadAppState));
-    }
-
-    #[test]
-    fn add_project_emits_save_app_state_effect() {
+    fn project_saved_command_labels_are_unique_and_persisted() {
         let mut state = AppState::new();
-        let effects = state.apply(Action::AddProject {
-            path: PathBuf::from("/tmp/repo"),
+        state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/saved-commands"),
             is_git: true,
         });
-        assert_eq!(effects.len(), 1);
-        assert!(matches!(effects[0], Effect::SaveAppState));
+        let project_id = state.projects[0].id;
+        for command in ["just test", "cargo test", "npm test"] {
+            let effects = state.apply(Action::AddProjectSavedCommand {
+                project_id,
+                label: " run tests ".to_owned(),
+                command: command.to_owned(),
+            });
+            assert!(matches!(effects.as_slice(), [Effect::SaveAppState]));
+        }
+        let labels = state.projects[0]
+            .saved_commands
+            .iter()
+            .map(|c| (c.id, c.label.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            labels,
+            vec![(1, "run tests"), (2, "run tests-2"), (3, "run tests-3")]
+        );
+
+        let effects = state.apply(Action::AddProjectSavedCommand {
+            project_id,
+            label: "lint".to_owned(),
+            command: "  ".to_owned(),
+        });
+        assert!(effects.is_empty());
+        assert_eq!(state.projects[0].saved_commands.len(), 3);
+
+        state.apply(Action::RemoveProjectSavedCommand { project_id, id: 2 });
+        assert!(
+            state
+                .apply(Action::RemoveProjectSavedCommand { project_id, id: 2 })
+                .is_empty()
+        );
+
+        let mut restored = AppState::new();
+        restored.apply(Action::AppStateLoaded {
+            persisted: Box::new(state.to_persisted()),
+        });
+        assert_eq!(
+            restored.projects[0].saved_commands,
+            state.projects[0].saved_commands
+        );
+        assert_eq!(restored.projects[0].saved_commands[1].label, "run tests-3");
     }
 
     #[test]
-    fn main_workspace_cannot_be_archived() {
+    fn project_worktree_root_is_set_cleared_and_persisted() {
         let mut state = AppState::new();
         state.apply(Action::AddProject {
-            path: PathBuf::from("/tmp/repo"),
+            path: PathBuf::from("/tmp/worktree-root"),
             is_git: true,
         });
         let project_id = state.projects[0].id;
-        state.apply(Action::CreateWorkspace {
+        assert_eq!(state.projects[0].worktree_root, None);
+
+        let effects = state.apply(Action::SetProjectWorktreeRoot {
             project_id,
-            branch_name_hint: None,
+            path: Some(PathBuf::from("/mnt/big/worktrees")),
         });
+        assert!(matches!(effects.as_slice(), [Effect::SaveAppState]));
+        assert!(
+            state
+                .apply(Action::SetProjectWorktreeRoot {
+                    project_id,
+                    path: Some(PathBuf::from("/mnt/big/worktrees")),
+                })
+                .is_empty()
+        );
 
-        let workspace_id = main_workspace_id(&state);
-        let effects = state.apply(Action::ArchiveWorkspace { workspace_id });
-        assert!(effects.is_empty());
+        let mut restored = AppState::new();
+        restored.apply(Action::AppStateLoaded {
+            persisted: Box::new(state.to_persisted()),
+        });
+        assert_eq!(
+            restored.projects[0].worktree_root.as_deref(),
+            Some(std::path::Path::new("/mnt/big/worktrees"))
+        );
 
-        let project = &state.projects[0];
-        let workspace = project
-            .workspaces
-            .iter()
-            .find(|w| w.id == workspace_id)
-            .expect("missing main workspace after archive attempt");
-        assert_eq!(workspace.archive_status, OperationStatus::Idle);
-        assert_eq!(workspace.status, WorkspaceStatus::Active);
-        assert_eq!(workspace.worktree_path, project.path);
+        state.apply(Action::SetProjectWorktreeRoot {
+            project_id,
+            path: Some(PathBuf::new()),
+        });
+        assert_eq!(state.projects[0].worktree_root, None);
     }
 
     #[test]
-    fn archiving_a_running_workspace_cancels_agent_turns_first() {
+    fn project_branch_name_template_is_trimmed_and_persisted() {
         let mut state = AppState::new();
         state.apply(Action::AddProject {
-            path: PathBuf::from("/tmp/repo"),
+            path: PathBuf::from("/tmp/branch-template"),
             is_git: true,
         });
         let project_id = state.projects[0].id;
 
-        let worktree_path = PathBuf::from("/tmp/repo/worktrees/wt");
-        state.apply(Action::WorkspaceCreated {
+        let effects = state.apply(Action::SetProjectBranchNameTemplate {
             project_id,
-            workspace_name: "wt".to_owned(),
-            branch_name: "feature".to_owned(),
-            worktree_path: worktree_path.clone(),
+            template: Some("  {user}/{name} ".to_owned()),
         });
-
-        let workspace_id = state.projects[0]
-            .workspaces
-            .iter()
-            .find(|w| w.worktree_path == worktree_path)
-            .expect("missing workspace")
-            .id;
-        state.apply(Action::CreateWorkspaceThread { workspace_id });
-        let thread_id = state.active_thread_id(workspace_id).unwrap();
-
-        {
-            let conversation = state
-                .conversations
-                .get_mut(&(workspace_id, thread_id))
-                .expect("missing conversation");
-            conversation.run_status = OperationStatus::Running;
-            conversation.active_run_id = Some(99);
-        }
-
-        let effects = state.apply(Action::ArchiveWorkspace { workspace_id });
-        assert_eq!(effects.len(), 2);
-
-        match &effects[0] {
-            Effect::CancelAgentTurn {
-                workspace_id: wid,
-                thread_id: tid,
-                run_id,
-            } => {
-                assert_eq!(*wid, workspace_id);
-                assert_eq!(*tid, thread_id);
-                assert_eq!(*run_id, 99);
-            }
-            other => panic!("expected CancelAgentTurn, got {other:?}"),
-        }
-        assert!(matches!(
-            &effects[1],
-            Effect::ArchiveWorkspace { workspace_id: wid } if *wid == workspace_id
-        ));
-
-        let conversation = state
-            .conversations
-            .get(&(workspace_id, thread_id))
-            .expect("missing conversation");
-        assert_eq!(conversation.run_status, OperationStatus::Idle);
-        assert_eq!(conversation.active_run_id, None);
-        assert!(conversation.queue_paused);
-        assert!(matches!(
-            conversation.entries.last(),
-            Some(ConversationEntry::AgentEvent {
-                event: crate::AgentEvent::TurnCanceled,
-                ..
+        assert!(matches!(effects.as_slice(), [Effect::SaveAppState]));
+        assert_eq!(
+            state.projects[0].branch_name_template(),
+            Some(BranchNameTemplate {
+                template: "{user}/{name}".to_owned(),
+                project_slug: "branch-template".to_owned(),
             })
-        ));
-
-        let workspace = state
-            .workspace(workspace_id)
-            .expect("missing workspace after archive request");
-        assert_eq!(workspace.archive_status, OperationStatus::Running);
-    }
+        );
 
-    #[test]
-    fn demo_state_is_consistent() {
-        let state = AppState::demo();
+        let mut restored = AppState::new();
+        restored.apply(Action::AppStateLoaded {
+            persisted: Box::new(state.to_persisted()),
+        });
+        assert_eq!(
+            restored.projects[0].branch_name_template.as_deref(),
+            Some("{user}/{name}")
+        );
 
-        assert!(!state.projects.is_empty());
+        state.apply(Action::SetProjectBranchNameTemplate {
+            project_id,
+            template: Some("   ".to_owned()),
+        });
+        assert_eq!(state.projects[0].branch_name_template, None);
     }
 
     #[test]
-    fn project_slug_is_sanitized_and_unique() {
+    fn project_pull_request_target_is_trimmed_and_persisted() {
         let mut state = AppState::new();
         state.apply(Action::AddProject {
-            path: PathBuf::from("/tmp/My Project"),
+            path: PathBuf::from("/tmp/pr-target"),
             is_git: true,
         });
-        state.apply(Action::AddProject {
-            path: PathBuf::from("/home/My Project"),
-            is_git: true,
+        let project_id = state.projects[0].id;
+        assert_eq!(
+            state.projects[0].pull_request_target(),
+            PullRequestTarget::default()
+        );
+
+        let effects = state.apply(Action::SetProjectPullRequestTarget {
+            project_id,
+            remote: Some(" upstream ".to_owned()),
+            base_branch: Some("develop".to_owned()),
         });
+        assert!(matches!(effects.as_slice(), [Effect::SaveAppState]));
 
-        assert_eq!(state.projects.len(), 2);
-        assert_eq!(state.projects[0].slug, "my-project");
-        assert_eq!(state.projects[1].slug, "my-project-2");
+        let mut restored = AppState::new();
+        restored.apply(Action::AppStateLoaded {
+            persisted: Box::new(state.to_persisted()),
+        });
+        assert_eq!(
+            restored.projects[0].pull_request_target(),
+            PullRequestTarget {
+                remote: Some("upstream".to_owned()),
+                base_branch: Some("develop".to_owned()),
+            }
+        );
+
+        state.apply(Action::SetProjectPullRequestTarget {
+            project_id,
+            remote: Some("  ".to_owned()),
+            base_branch: None,
+        });
+        assert_eq!(
+            state.projects[0].pull_request_target(),
+            PullRequestTarget::default()
+        );
     }
 
     #[test]
-    fn projects_are_deduped_by_normalized_path() {
+    fn project_agent_env_is_normalized_and_persisted() {
         let mut state = AppState::new();
         state.apply(Action::AddProject {
-            path: PathBuf::from("/tmp/repo"),
-            is_git: true,
-        });
-        state.apply(Action::AddProject {
-            path: PathBuf::from("/tmp/repo/"),
+            path: PathBuf::from("/tmp/agent-env"),
             is_git: true,
         });
+        let project_id = state.projects[0].id;
 
-        assert_eq!(state.projects.len(), 1);
-        assert_eq!(state.projects[0].path, PathBuf::from("/tmp/repo"));
+        let env = vec![
+            (" API_URL ".to_owned(), "http://old".to_owned()),
+            ("".to_owned(), "ignored".to_owned()),
+            ("A=B".to_owned(), "ignored".to_owned()),
+            ("TOKEN".to_owned(), "secret".to_owned()),
+            ("API_URL".to_owned(), "http://new".to_owned()),
+        ];
+        let effects = state.apply(Action::SetProjectAgentEnv {
+            project_id,
+            env: env.clone(),
+        });
+        assert!(matches!(effects.as_slice(), [Effect::SaveAppState]));
+        let expected = vec![
+            ("TOKEN".to_owned(), "secret".to_owned()),
+            ("API_URL".to_owned(), "http://new".to_owned()),
+        ];
+        assert_eq!(state.projects[0].agent_env, expected);
+        assert!(
+            state
+                .apply(Action::SetProjectAgentEnv { project_id, env })
+                .is_empty()
+        );
+
+        let mut restored = AppState::new();
+        restored.apply(Action::AppStateLoaded {
+            persisted: Box::new(state.to_persisted()),
+        });
+        assert_eq!(restored.projects[0].agent_env, expected);
     }
 
     #[test]
-    fn delete_project_removes_state_and_emits_save_effect() {
+    fn project_group_assignment_moves_projects_between_groups() {
         let mut state = AppState::new();
         state.apply(Action::AddProject {
-            path: PathBuf::from("/tmp/repo"),
+            path: PathBuf::from("/tmp/group-a"),
             is_git: true,
         });
-        let project_id = state.projects[0].id;
-        state.apply(Action::WorkspaceCreated {
-            project_id,
-            workspace_name: "main".to_owned(),
-            branch_name: "main".to_owned(),
-            worktree_path: PathBuf::from("/tmp/repo"),
+        state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/group-b"),
+            is_git: true,
         });
-        let main_id = workspace_id_by_name(&state, "main");
+        let project_a = state.projects[0].id;
+        let project_b = state.projects[1].id;
 
-        state.apply(Action::OpenWorkspace {
-            workspace_id: main_id,
+        let effects = state.apply(Action::CreateProjectGroup {
+            name: "  Work  ".to_owned(),
         });
-        assert!(matches!(state.main_pane, MainPane::Workspace(_)));
+        assert!(matches!(effects.as_slice(), [Effect::SaveAppState]));
+        state.apply(Action::CreateProjectGroup {
+            name: "Personal".to_owned(),
+        });
+        let work = state.project_groups[0].id;
+        let personal = state.project_groups[1].id;
+        assert_eq!(state.project_groups[0].name, "Work");
+        assert!(
+            state
+                .apply(Action::CreateProjectGroup {
+                    name: "   ".to_owned(),
+                })
+                .is_empty()
+        );
+        assert_eq!(state.project_groups.len(), 2);
 
-        let effects = state.apply(Action::DeleteProject { project_id });
-        assert_eq!(effects.len(), 1);
-        assert!(matches!(effects[0], Effect::SaveAppState));
+        state.apply(Action::AssignProjectToGroup {
+            project_id: project_a,
+            group_id: Some(work),
+        });
+        state.apply(Action::AssignProjectToGroup {
+            project_id: project_b,
+            group_id: Some(work),
+        });
+        assert_eq!(
+            state.project_groups[0].project_ids,
+            vec![project_a, project_b]
+        );
 
-        assert!(state.projects.is_empty());
-        assert!(!state.workspace_tabs.contains_key(&main_id));
-        assert!(state.conversations.keys().all(|(wid, _)| *wid != main_id));
-        assert!(state.last_open_workspace_id.is_none());
-        assert_eq!(state.main_pane, MainPane::Dashboard);
-        assert_eq!(state.right_pane, RightPane::None);
-    }
+        state.apply(Action::AssignProjectToGroup {
+            project_id: project_a,
+            group_id: Some(personal),
+        });
+        assert_eq!(state.project_groups[0].project_ids, vec![project_b]);
+        assert_eq!(state.project_groups[1].project_ids, vec![project_a]);
+        assert_eq!(state.project_group_id(project_a), Some(personal));
 
-    #[test]
-    fn create_workspace_sets_busy_and_emits_effect() {
-        let mut state = AppState::new();
-        state.apply(Action::AddProject {
-            path: PathBuf::from("/tmp/repo"),
-            is_git: true,
+        state.apply(Action::AssignProjectToGroup {
+            project_id: project_b,
+            group_id: None,
         });
-        let project_id = state.projects[0].id;
+        assert_eq!(state.project_group_id(project_b), None);
+        assert!(state.project_groups[0].project_ids.is_empty());
 
-        let effects = state.apply(Action::CreateWorkspace {
-            project_id,
-            branch_name_hint: None,
+        state.apply(Action::RenameProjectGroup {
+            group_id: personal,
+            name: "Side projects".to_owned(),
         });
-        assert_eq!(effects.len(), 1);
-        assert!(matches!(effects[0], Effect::CreateWorkspace { .. }));
 
-        let project = state.project(project_id).unwrap();
-        assert_eq!(project.create_workspace_status, OperationStatus::Running);
+        let mut restored = AppState::new();
+        restored.apply(Action::AppStateLoaded {
+            persisted: Box::new(state.to_persisted()),
+        });
+        assert_eq!(restored.project_groups, state.project_groups);
+        assert_eq!(restored.project_groups[1].name, "Side projects");
+        restored.apply(Action::CreateProjectGroup {
+            name: "Later".to_owned(),
+        });
+        assert_eq!(
+            restored.project_groups[2].id.as_u64(),
+            personal.as_u64() + 1
+        );
     }
 
     #[test]
-    fn open_workspace_emits_conversation_load_effect() {
+    fn duplicating_a_thread_creates_an_active_copy_and_emits_effect() {
         let mut state = AppState::demo();
         let workspace_id = first_non_main_workspace_id(&state);
-
-        let effects = state.apply(Action::OpenWorkspace { workspace_id });
-        assert_eq!(effects.len(), 3);
-        assert!(matches!(effects[0], Effect::SaveAppState));
-        assert!(matches!(effects[1], Effect::LoadWorkspaceThreads { .. }));
-        assert!(matches!(effects[2], Effect::LoadConversation { .. }));
-    }
-
-    #[test]
-    fn app_state_restores_last_open_workspace() {
-        let mut state = AppState::new();
-        state.apply(Action::AddProject {
-            path: PathBuf::from("/tmp/repo"),
-            is_git: true,
+        let source_thread_id = default_thread_id();
+        state.apply(Action::SendAgentMessage {
+            workspace_id,
+            thread_id: source_thread_id,
+            text: "Explore option A".to_owned(),
+            attachments: Vec::new(),
+            runner: None,
+            amp_mode: None,
         });
-        let project_id = state.projects[0].id;
-        state.apply(Action::WorkspaceCreated {
-            project_id,
-            workspace_name: "abandon-about".to_owned(),
-            branch_name: "luban/abandon-about".to_owned(),
-            worktree_path: PathBuf::from("/tmp/luban/worktrees/repo/abandon-about"),
+        state.apply(Action::ChatRunnerChanged {
+            workspace_id,
+            thread_id: source_thread_id,
+            runner: crate::AgentRunnerKind::Claude,
         });
-        let workspace_id = workspace_id_by_name(&state, "abandon-about");
-        state.apply(Action::OpenWorkspace { workspace_id });
-
-        let persisted = state.to_persisted();
-        assert_eq!(persisted.last_open_workspace_id, Some(workspace_id.0));
 
-        let mut loaded = AppState::new();
-        let effects = loaded.apply(Action::AppStateLoaded {
-            persisted: Box::new(persisted),
+        let effects = state.apply(Action::DuplicateWorkspaceThread {
+            workspace_id,
+            thread_id: source_thread_id,
+            up_to_entry_id: None,
         });
+        let thread_id = state.workspace_tabs(workspace_id).unwrap().active_tab;
+        assert_ne!(thread_id, source_thread_id);
+        assert!(effects.iter().any(|effect| matches!(
+            effect,
+            Effect::DuplicateConversation {
+                source_thread_id: source,
+                thread_id: copy,
+                up_to_entry_id: None,
+                ..
+            } if *source == source_thread_id && *copy == thread_id
+        )));
+        let copy = state
+            .workspace_thread_conversation(workspace_id, thread_id)
+            .unwrap();
+        assert_eq!(copy.agent_runner, crate::AgentRunnerKind::Claude);
 
+        let effects = state.apply(Action::WorkspaceThreadDuplicated {
+            workspace_id,
+            thread_id,
+        });
         assert!(
-            matches!(loaded.main_pane, MainPane::Workspace(id) if id == workspace_id),
-            "expected main pane to restore workspace"
+            effects
+                .iter()
+                .any(|effect| matches!(effect, Effect::LoadWorkspaceThreads { .. }))
         );
-        assert_eq!(loaded.right_pane, RightPane::Terminal);
-        assert_eq!(effects.len(), 5);
-        assert!(matches!(effects[0], Effect::LoadCodexDefaults));
-        assert!(matches!(effects[1], Effect::LoadTaskPromptTemplates));
-        assert!(matches!(effects[2], Effect::LoadSystemPromptTemplates));
-        assert!(matches!(effects[3], Effect::LoadWorkspaceThreads { .. }));
-        assert!(matches!(
-            effects[4],
-            Effect::LoadConversation { workspace_id: id, .. } if id == workspace_id
-        ));
     }
 
     #[test]
-    fn chat_drafts_are_isolated_and_preserved_on_reload() {
+    fn deleting_project_group_ungroups_its_projects() {
         let mut state = AppState::new();
         state.apply(Action::AddProject {
-            path: PathBuf::from("/tmp/repo"),
+            path: PathBuf::from("/tmp/ungroup-a"),
             is_git: true,
         });
-        let project_id = state.projects[0].id;
-
-        state.apply(Action::WorkspaceCreated {
-            project_id,
-            workspace_name: "w1".to_owned(),
-            branch_name: "repo/w1".to_owned(),
-            worktree_path: PathBuf::from("/tmp/repo/worktrees/w1"),
+        state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/ungroup-b"),
+            is_git: true,
         });
-        state.apply(Action::WorkspaceCreated {
-            project_id,
-            workspace_name: "w2".to_owned(),
-            branch_name: "repo/w2".to_owned(),
-            worktree_path: PathBuf::from("/tmp/repo/worktrees/w2"),
+        let project_a = state.projects[0].id;
+        let project_b = state.projects[1].id;
+        state.apply(Action::CreateProjectGroup {
+            name: "Archive".to_owned(),
         });
+        let group_id = state.project_groups[0].id;
+        for project_id in [project_a, project_b] {
+            state.apply(Action::AssignProjectToGroup {
+                project_id,
+                group_id: Some(group_id),
+            });
+        }
 
-        let w1 = workspace_id_by_name(&state, "w1");
-        let w2 = workspace_id_by_name(&state, "w2");
-        let thread_id = default_thread_id();
-        state.apply(Action::CreateWorkspaceThread { workspace_id: w1 });
-        state.apply(Action::CreateWorkspaceThread { workspace_id: w2 });
+        let effects = state.apply(Action::DeleteProjectGroup { group_id });
+        assert!(matches!(effects.as_slice(), [Effect::SaveAppState]));
+        assert!(state.project_groups.is_empty());
+        assert_eq!(state.projects.len(), 2);
+        assert_eq!(state.project_group_id(project_a), None);
+        assert_eq!(state.project_group_id(project_b), None);
 
-        state.apply(Action::ChatDraftChanged {
-            workspace_id: w1,
-            thread_id,
-            text: "draft-1".to_owned(),
+        state.apply(Action::CreateProjectGroup {
+            name: "Active".to_owned(),
         });
-        state.apply(Action::ChatDraftChanged {
-            workspace_id: w2,
-            thread_id,
-            text: "draft-2".to_owned(),
+        let group_id = state.project_groups[0].id;
+        state.apply(Action::AssignProjectToGroup {
+            project_id: project_a,
+            group_id: Some(group_id),
         });
+        state.apply(Action::DeleteProject {
+            project_id: project_a,
+        });
+        assert!(state.project_groups[0].project_ids.is_empty());
+    }
 
-        assert_eq!(state.workspace_conversation(w1).unwrap().draft, "draft-1");
-        assert_eq!(state.workspace_conversation(w2).unwrap().draft, "draft-2");
+    #[test]
+    fn appearance_theme_is_persisted() {
+        let mut state = AppState::new();
+        let effects = state.apply(Action::AppearanceThemeChanged {
+            theme: crate::AppearanceTheme::Dark,
+        });
+        assert_eq!(state.appearance_theme, crate::AppearanceTheme::Dark);
+        assert_eq!(effects.len(), 1);
+        assert!(matches!(effects[0], Effect::SaveAppState));
 
-        state.apply(Action::ConversationLoaded {
-            workspace_id: w1,
-            thread_id,
-            snapshot: ConversationSnapshot {
-                title: None,
-                thread_id: None,
-                task_status: crate::TaskStatus::Todo,
-                runner: None,
-                agent_model_id: None,
-                thinking_effort: None,
-                amp_mode: None,
-                entries: Vec::new(),
-                entries_total: 0,
-                entries_start: 0,
-                pending_prompts: Vec::new(),
-                queue_paused: false,
-                run_started_at_unix_ms: None,
-                run_finished_at_unix_ms: None,
-            },
+        let persisted = state.to_persisted();
+        assert_eq!(persisted.appearance_theme.as_deref(), Some("dark"));
+
+        let mut restored = AppState::new();
+        restored.apply(Action::AppStateLoaded {
+            persisted: Box::new(PersistedAppState {
+                projects: Vec::new(),
+                sidebar_width: None,
+                terminal_pane_width: None,
+                global_zoom_percent: None,
+                appearance_theme: Some("light".to_owned()),
+                appearance_ui_font: None,
+                appearance_chat_font: None,
+                appearance_code_font: None,
+                appearance_terminal_font: None,
+                agent_default_model_id: None,
+                agent_runner_default_models: HashMap::new(),
+                agent_runner_prompt_prefixes: HashMap::new(),
+                agent_runner_prompt_suffixes: HashMap::new(),
+                model_thinking_effort_caps: HashMap::new(),
+                agent_default_thinking_effort: None,
+                agent_default_runner: None,
+                agent_amp_mode: None,
+                agent_codex_enabled: Some(true),
+                agent_amp_enabled: Some(true),
+                agent_claude_enabled: Some(true),
+                agent_droid_enabled: Some(true),
+                last_open_workspace_id: None,
+                open_button_selection: None,
+                sidebar_project_order: Vec::new(),
+                project_groups: Vec::new(),
+                workspace_active_thread_id: HashMap::new(),
+                workspace_open_tabs: HashMap::new(),
+                workspace_archived_tabs: HashMap::new(),
+                workspace_next_thread_id: HashMap::new(),
+                workspace_chat_scroll_y10: HashMap::new(),
+                workspace_chat_scroll_anchor: HashMap::new(),
+                workspace_unread_completions: HashMap::new(),
+                workspace_thread_run_config_overrides: HashMap::new(),
+                starred_tasks: HashMap::new(),
+                task_prompt_templates: HashMap::new(),
+                telegram_enabled: None,
+                telegram_bot_token: None,
+                telegram_bot_username: None,
+                telegram_paired_chat_ids: Vec::new(),
+                telegram_topic_bindings: None,
+                webhook_url: None,
+                webhook_secret: None,
+                agent_merge_reasoning_entries: None,
+                agent_pause_queue_on_patch_failure: None,
+                agent_item_dedup_window: None,
+                agent_auto_retry_on_failure: None,
+                agent_command_output_max_bytes: None,
+                auto_archive_after_days: None,
+            }),
         });
-        assert_eq!(state.workspace_conversation(w1).unwrap().draft, "draft-1");
+        assert_eq!(restored.appearance_theme, crate::AppearanceTheme::Light);
     }
 
     #[test]
-    fn chat_draft_edits_update_attachment_anchors_without_removing() {
+    fn appearance_fonts_are_persisted() {
         let mut state = AppState::new();
-        state.apply(Action::AddProject {
-            path: PathBuf::from("/tmp/repo"),
-            is_git: true,
+        let effects = state.apply(Action::AppearanceFontsChanged {
+            ui_font: "Inter".to_owned(),
+            chat_font: "Roboto".to_owned(),
+            code_font: "Geist Mono".to_owned(),
+            terminal_font: "JetBrains Mono".to_owned(),
         });
-        let project_id = state.projects[0].id;
+        assert_eq!(
+            state.appearance_fonts,
+            crate::AppearanceFonts {
+                ui_font: "Inter".to_owned(),
+                chat_font: "Roboto".to_owned(),
+                code_font: "Geist Mono".to_owned(),
+                terminal_font: "JetBrains Mono".to_owned(),
+            }
+        );
+        assert_eq!(effects.len(), 1);
+        assert!(matches!(effects[0], Effect::SaveAppState));
 
-        state.apply(Action::WorkspaceCreated {
-            project_id,
-            workspace_name: "w1".to_owned(),
-            branch_name: "repo/w1".to_owned(),
-            worktree_path: PathBuf::from("/tmp/repo/worktrees/w1"),
-        });
-        let w1 = workspace_id_by_name(&state, "w1");
+        let persisted = state.to_persisted();
+        assert_eq!(persisted.appearance_ui_font.as_deref(), Some("Inter"));
+        assert_eq!(persisted.appearance_chat_font.as_deref(), Some("Roboto"));
+        assert_eq!(
+            persisted.appearance_code_font.as_deref(),
+            Some("Geist Mono")
+        );
+        assert_eq!(
+            persisted.appearance_terminal_font.as_deref(),
+            Some("JetBrains Mono")
+        );
+    }
+
+    #[test]
+    fn workspace_chat_scroll_is_persisted() {
+        let mut state = AppState::new();
+        let workspace_id = WorkspaceId(42);
+        let thread_id = default_thread_id();
+
+        let effects = state.apply(Action::WorkspaceChatScrollSaved {
+            workspace_id,
+            thread_id,
+            offset_y10: -1234,
+        });
+        assert_eq!(
+            state
+                .workspace_chat_scroll_y10
+                .get(&(workspace_id, thread_id))
+                .copied(),
+            Some(-1234)
+        );
+        assert_eq!(effects.len(), 1);
+        assert!(matches!(effects[0], Effect::SaveAppState));
+
+        let persisted = state.to_persisted();
+        assert_eq!(
+            persisted.workspace_chat_scroll_y10.get(&(42, 1)).copied(),
+            Some(-1234)
+        );
+
+        let mut loaded = AppState::new();
+        loaded.apply(Action::AppStateLoaded {
+            persisted: Box::new(persisted),
+        });
+        assert_eq!(
+            loaded
+                .workspace_chat_scroll_y10
+                .get(&(workspace_id, thread_id))
+                .copied(),
+            Some(-1234)
+        );
+    }
+
+    #[test]
+    fn workspace_chat_scroll_anchor_is_persisted() {
+        let mut state = AppState::new();
+        let workspace_id = WorkspaceId(42);
+        let thread_id = default_thread_id();
+
+        let anchor = ChatScrollAnchor::Block {
+            block_id: "history-block-agent-turn-3".to_owned(),
+            block_index: 3,
+            offset_in_block_y10: 420,
+        };
+
+        let effects = state.apply(Action::WorkspaceChatScrollAnchorSaved {
+            workspace_id,
+            thread_id,
+            anchor: anchor.clone(),
+        });
+        assert_eq!(
+            state
+                .workspace_chat_scroll_anchor
+                .get(&(workspace_id, thread_id))
+                .cloned(),
+            Some(anchor.clone())
+        );
+        assert_eq!(effects.len(), 1);
+        assert!(matches!(effects[0], Effect::SaveAppState));
+
+        let persisted = state.to_persisted();
+        assert_eq!(
+            persisted
+                .workspace_chat_scroll_anchor
+                .get(&(42, 1))
+                .cloned(),
+            Some(anchor.clone())
+        );
+
+        let mut loaded = AppState::new();
+        loaded.apply(Action::AppStateLoaded {
+            persisted: Box::new(persisted),
+        });
+        assert_eq!(
+            loaded
+                .workspace_chat_scroll_anchor
+                .get(&(workspace_id, thread_id))
+                .cloned(),
+            Some(anchor)
+        );
+    }
+
+    #[test]
+    fn workspace_thread_tabs_preserve_order_and_allow_reorder() {
+        let mut state = AppState::new();
+        state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/repo"),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+        state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "w1".to_owned(),
+            branch_name: "repo/w1".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban/worktrees/repo/w1"),
+        });
+        let workspace_id = workspace_id_by_name(&state, "w1");
+        state.apply(Action::OpenWorkspace { workspace_id });
+
+        state.apply(Action::CreateWorkspaceThread { workspace_id });
+        let mut thread_ids = vec![state.active_thread_id(workspace_id).unwrap()];
+        for _ in 0..3 {
+            state.apply(Action::CreateWorkspaceThread { workspace_id });
+            thread_ids.push(state.active_thread_id(workspace_id).unwrap());
+        }
+
+        let tabs = state.workspace_tabs(workspace_id).unwrap();
+        assert_eq!(tabs.open_tabs, thread_ids);
+
+        state.apply(Action::ActivateWorkspaceThread {
+            workspace_id,
+            thread_id: thread_ids[1],
+        });
+        let tabs = state.workspace_tabs(workspace_id).unwrap();
+        assert_eq!(tabs.active_tab, thread_ids[1]);
+        assert_eq!(
+            tabs.open_tabs, thread_ids,
+            "activating a thread should not reorder tabs"
+        );
+
+        let effects = state.apply(Action::ReorderWorkspaceThreadTab {
+            workspace_id,
+            thread_id: thread_ids[3],
+            to_index: 1,
+        });
+        assert_eq!(effects.len(), 1);
+        assert!(matches!(effects[0], Effect::SaveAppState));
+        let tabs = state.workspace_tabs(workspace_id).unwrap();
+        assert_eq!(
+            tabs.open_tabs,
+            vec![thread_ids[0], thread_ids[3], thread_ids[1], thread_ids[2]]
+        );
+
+        let persisted = state.to_persisted();
+        assert_eq!(
+            persisted.workspace_open_tabs.get(&workspace_id.0).cloned(),
+            Some(vec![
+                thread_ids[0].0,
+                thread_ids[3].0,
+                thread_ids[1].0,
+                thread_ids[2].0
+            ])
+        );
+
+        let closed_thread = thread_ids[3];
+        state.apply(Action::CloseWorkspaceThreadTab {
+            workspace_id,
+            thread_id: closed_thread,
+        });
+        let tabs = state.workspace_tabs(workspace_id).unwrap();
+        assert!(
+            !tabs.open_tabs.contains(&closed_thread),
+            "closing a tab should archive it"
+        );
+        assert!(
+            tabs.archived_tabs.contains(&closed_thread),
+            "archived tabs should retain the closed thread id"
+        );
+        assert!(
+            state
+                .workspace_thread_conversation(workspace_id, closed_thread)
+                .is_some(),
+            "archiving a tab should not delete the conversation"
+        );
+
+        state.apply(Action::RestoreWorkspaceThreadTab {
+            workspace_id,
+            thread_id: closed_thread,
+        });
+        let tabs = state.workspace_tabs(workspace_id).unwrap();
+        assert!(
+            tabs.open_tabs.contains(&closed_thread),
+            "restoring a tab should re-open it"
+        );
+        assert!(
+            !tabs.archived_tabs.contains(&closed_thread),
+            "restoring a tab should remove it from the archive list"
+        );
+    }
+
+    #[test]
+    fn activate_adjacent_thread_wraps_around_open_tabs() {
+        let mut state = AppState::new();
+        state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/repo"),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+        state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "w1".to_owned(),
+            branch_name: "repo/w1".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban/worktrees/repo/w1"),
+        });
+        let workspace_id = workspace_id_by_name(&state, "w1");
+        state.apply(Action::OpenWorkspace { workspace_id });
+
+        state.apply(Action::CreateWorkspaceThread { workspace_id });
+        let single = state.active_thread_id(workspace_id).unwrap();
+        let effects = state.apply(Action::ActivateAdjacentWorkspaceThread {
+            workspace_id,
+            direction: TabDirection::Next,
+        });
+        assert!(effects.is_empty(), "a single open tab has no neighbour");
+        assert_eq!(state.active_thread_id(workspace_id), Some(single));
+
+        let mut thread_ids = vec![single];
+        for _ in 0..2 {
+            state.apply(Action::CreateWorkspaceThread { workspace_id });
+            thread_ids.push(state.active_thread_id(workspace_id).unwrap());
+        }
+
+        let effects = state.apply(Action::ActivateAdjacentWorkspaceThread {
+            workspace_id,
+            direction: TabDirection::Next,
+        });
+        assert_eq!(state.active_thread_id(workspace_id), Some(thread_ids[0]));
+        assert!(matches!(effects[0], Effect::SaveAppState));
+        assert!(effects.iter().any(|effect| matches!(
+            effect,
+            Effect::LoadConversation { thread_id, .. } if *thread_id == thread_ids[0]
+        )));
+        assert!(
+            effects
+                .iter()
+                .any(|effect| matches!(effect, Effect::LoadWorkspaceThreads { .. }))
+        );
+
+        state.apply(Action::ActivateAdjacentWorkspaceThread {
+            workspace_id,
+            direction: TabDirection::Prev,
+        });
+        assert_eq!(state.active_thread_id(workspace_id), Some(thread_ids[2]));
+
+        state.apply(Action::CloseWorkspaceThreadTab {
+            workspace_id,
+            thread_id: thread_ids[1],
+        });
+        assert_eq!(state.active_thread_id(workspace_id), Some(thread_ids[0]));
+        state.apply(Action::ActivateAdjacentWorkspaceThread {
+            workspace_id,
+            direction: TabDirection::Next,
+        });
+        assert_eq!(
+            state.active_thread_id(workspace_id),
+            Some(thread_ids[2]),
+            "archived tabs are skipped"
+        );
+        assert_eq!(
+            state.workspace_tabs(workspace_id).unwrap().open_tabs,
+            vec![thread_ids[0], thread_ids[2]],
+            "cycling does not reorder tabs"
+        );
+    }
+
+    #[test]
+    fn project_expanded_is_persisted() {
+        let mut state = AppState::new();
+        state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/repo"),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+
+        let effects = state.apply(Action::ToggleProjectExpanded { project_id });
+        assert_eq!(effects.len(), 1);
+        assert!(matches!(effects[0], Effect::SaveAppState));
+        assert!(state.projects[0].expanded);
+
+        let persisted = state.to_persisted();
+        assert_eq!(persisted.projects.len(), 1);
+        assert!(persisted.projects[0].expanded);
+
+        let mut loaded = AppState::new();
+        loaded.apply(Action::AppStateLoaded {
+            persisted: Box::new(persisted),
+        });
+        assert!(loaded.projects[0].expanded);
+    }
+
+    #[test]
+    fn agent_item_updates_are_appended_as_entries() {
+        let mut state = AppState::new();
+        state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/repo"),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+        state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "abandon-about".to_owned(),
+            branch_name: "luban/abandon-about".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban/worktrees/repo/abandon-about"),
+        });
+        let workspace_id = workspace_id_by_name(&state, "abandon-about");
+        let thread_id = default_thread_id();
+
+        state.apply(Action::SendAgentMessage {
+            workspace_id,
+            thread_id,
+            text: "Test".to_owned(),
+            attachments: Vec::new(),
+            runner: None,
+            amp_mode: None,
+        });
+        let run_id = state
+            .workspace_thread_conversation(workspace_id, thread_id)
+            .expect("missing conversation")
+            .active_run_id
+            .expect("missing active run id");
+
+        state.apply(Action::AgentEventReceived {
+            workspace_id,
+            thread_id,
+            run_id,
+            event: CodexThreadEvent::ItemStarted {
+                item: CodexThreadItem::Reasoning {
+                    id: "r-1".to_owned(),
+                    text: "x".to_owned(),
+                },
+            },
+        });
+        state.apply(Action::AgentEventReceived {
+            workspace_id,
+            thread_id,
+            run_id,
+            event: CodexThreadEvent::ItemStarted {
+                item: CodexThreadItem::CommandExecution {
+                    id: "c-1".to_owned(),
+                    command: "echo hello".to_owned(),
+                    aggregated_output: String::new(),
+                    exit_code: None,
+                    status: CodexCommandExecutionStatus::InProgress,
+                    truncated: false,
+                },
+            },
+        });
+
+        let conversation = state
+            .workspace_thread_conversation(workspace_id, thread_id)
+            .expect("missing conversation");
+
+        let agent_item_entries: Vec<(&str, &str)> = conversation
+            .entries
+            .iter()
+            .filter_map(|entry| match entry {
+                ConversationEntry::AgentEvent {
+                    entry_id,
+                    event: crate::AgentEvent::Item { item },
+                    ..
+                } => Some((entry_id.as_str(), codex_item_id(item.as_ref()))),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(agent_item_entries.len(), 2);
+        assert_eq!(agent_item_entries[0].1, "r-1");
+        assert_eq!(agent_item_entries[1].1, "c-1");
+        assert_ne!(agent_item_entries[0].0, agent_item_entries[1].0);
+    }
+
+    #[test]
+    fn runner_prompt_affixes_wrap_user_text_and_skip_blank_entries() {
+        let mut state = AppState::new();
+        let effects = state.apply(Action::AgentRunnerPromptPrefixChanged {
+            runner: crate::AgentRunnerKind::Codex,
+            prefix: "Follow AGENTS.md.".to_owned(),
+        });
+        assert!(matches!(effects.as_slice(), [Effect::SaveAppState]));
+        state.apply(Action::AgentRunnerPromptSuffixChanged {
+            runner: crate::AgentRunnerKind::Codex,
+            suffix: "Reply tersely.\n".to_owned(),
+        });
+        let effects = state.apply(Action::AgentRunnerPromptPrefixChanged {
+            runner: crate::AgentRunnerKind::Amp,
+            prefix: "  \n".to_owned(),
+        });
+        assert!(effects.is_empty());
+
+        let effective = |state: &AppState, runner| {
+            crate::apply_runner_prompt_affixes(
+                state
+                    .agent_runner_prompt_prefixes()
+                    .get(&runner)
+                    .map(String::as_str),
+                "Fix the bug",
+                state
+                    .agent_runner_prompt_suffixes()
+                    .get(&runner)
+                    .map(String::as_str),
+            )
+        };
+        assert_eq!(
+            effective(&state, crate::AgentRunnerKind::Codex),
+            "Follow AGENTS.md.\n\nFix the bug\n\nReply tersely."
+        );
+        assert_eq!(
+            effective(&state, crate::AgentRunnerKind::Amp),
+            "Fix the bug"
+        );
+
+        let persisted = state.to_persisted();
+        assert_eq!(
+            persisted
+                .agent_runner_prompt_prefixes
+                .get("codex")
+                .map(String::as_str),
+            Some("Follow AGENTS.md.")
+        );
+
+        let effects = state.apply(Action::AgentRunnerPromptPrefixChanged {
+            runner: crate::AgentRunnerKind::Codex,
+            prefix: String::new(),
+        });
+        assert!(matches!(effects.as_slice(), [Effect::SaveAppState]));
+        assert_eq!(
+            effective(&state, crate::AgentRunnerKind::Codex),
+            "Fix the bug\n\nReply tersely."
+        );
+    }
+
+    #[test]
+    fn duplicate_completed_item_after_other_item_is_dropped_with_dedup_window() {
+        fn command(id: &str, completed: bool) -> CodexThreadItem {
+            CodexThreadItem::CommandExecution {
+                id: id.to_owned(),
+                command: "echo hi".to_owned(),
+                aggregated_output: if completed { "hi\n" } else { "" }.to_owned(),
+                exit_code: completed.then_some(0),
+                status: if completed {
+                    crate::CodexCommandExecutionStatus::Completed
+                } else {
+                    crate::CodexCommandExecutionStatus::InProgress
+                },
+                truncated: false,
+            }
+        }
+
+        fn entries_added_by_replayed_stream(dedup_window: usize) -> usize {
+            let mut state = AppState::new();
+            state.apply(Action::AgentItemDedupWindowChanged {
+                window: dedup_window,
+            });
+            state.apply(Action::AddProject {
+                path: PathBuf::from("/tmp/repo"),
+                is_git: true,
+            });
+            let project_id = state.projects[0].id;
+            state.apply(Action::WorkspaceCreated {
+                project_id,
+                workspace_name: "abandon-about".to_owned(),
+                branch_name: "luban/abandon-about".to_owned(),
+                worktree_path: PathBuf::from("/tmp/luban/worktrees/repo/abandon-about"),
+            });
+            let workspace_id = workspace_id_by_name(&state, "abandon-about");
+            let thread_id = default_thread_id();
+
+            state.apply(Action::SendAgentMessage {
+                workspace_id,
+                thread_id,
+                text: "Test".to_owned(),
+                attachments: Vec::new(),
+                runner: None,
+                amp_mode: None,
+            });
+            let conversation = state
+                .workspace_thread_conversation(workspace_id, thread_id)
+                .expect("missing conversation");
+            let run_id = conversation.active_run_id.expect("missing active run id");
+            let entries_before = conversation.entries.len();
+
+            let events = [
+                CodexThreadEvent::ItemStarted {
+                    item: command("cmd-a", false),
+                },
+                CodexThreadEvent::ItemCompleted {
+                    item: command("cmd-a", true),
+                },
+                CodexThreadEvent::ItemCompleted {
+                    item: command("cmd-b", true),
+                },
+                // The stream reconnects and replays the first item.
+                CodexThreadEvent::ItemStarted {
+                    item: command("cmd-a", false),
+                },
+                CodexThreadEvent::ItemCompleted {
+                    item: command("cmd-a", true),
+                },
+            ];
+            for event in events {
+                state.apply(Action::AgentEventReceived {
+                    workspace_id,
+                    thread_id,
+                    run_id,
+                    event,
+                });
+            }
+
+            state
+                .workspace_thread_conversation(workspace_id, thread_id)
+                .expect("missing conversation")
+                .entries
+                .len()
+                - entries_before
+        }
+
+        assert_eq!(entries_added_by_replayed_stream(0), 5);
+        assert_eq!(entries_added_by_replayed_stream(8), 3);
+    }
+
+    #[test]
+    fn failed_patch_apply_flags_entry_and_pauses_queue_when_enabled() {
+        let mut state = AppState::new();
+        state.apply(Action::AgentPauseQueueOnPatchFailureChanged { enabled: true });
+        state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/repo"),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+        state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "abandon-about".to_owned(),
+            branch_name: "luban/abandon-about".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban/worktrees/repo/abandon-about"),
+        });
+        let workspace_id = workspace_id_by_name(&state, "abandon-about");
+        let thread_id = default_thread_id();
+
+        state.apply(Action::SendAgentMessage {
+            workspace_id,
+            thread_id,
+            text: "Test".to_owned(),
+            attachments: Vec::new(),
+            runner: None,
+            amp_mode: None,
+        });
+        let run_id = state
+            .workspace_thread_conversation(workspace_id, thread_id)
+            .expect("missing conversation")
+            .active_run_id
+            .expect("missing active run id");
+
+        let file_change = |status| CodexThreadItem::FileChange {
+            id: "patch-1".to_owned(),
+            changes: vec![crate::CodexFileUpdateChange {
+                path: "src/lib.rs".to_owned(),
+                kind: crate::CodexPatchChangeKind::Update,
+            }],
+            status,
+        };
+        state.apply(Action::AgentEventReceived {
+            workspace_id,
+            thread_id,
+            run_id,
+            event: CodexThreadEvent::ItemStarted {
+                item: file_change(crate::CodexPatchApplyStatus::InProgress),
+            },
+        });
+        let conversation = state
+            .workspace_thread_conversation(workspace_id, thread_id)
+            .expect("missing conversation");
+        assert_eq!(conversation.patch_apply_failed_entry_id, None);
+        assert!(!conversation.queue_paused);
+
+        state.apply(Action::AgentEventReceived {
+            workspace_id,
+            thread_id,
+            run_id,
+            event: CodexThreadEvent::ItemCompleted {
+                item: file_change(crate::CodexPatchApplyStatus::Failed),
+            },
+        });
+        let conversation = state
+            .workspace_thread_conversation(workspace_id, thread_id)
+            .expect("missing conversation");
+        let Some(ConversationEntry::AgentEvent { entry_id, .. }) = conversation.entries.last()
+        else {
+            panic!("expected the file change entry last");
+        };
+        assert_eq!(
+            conversation.patch_apply_failed_entry_id.as_deref(),
+            Some(entry_id.as_str())
+        );
+        assert!(conversation.queue_paused);
+    }
+
+    #[test]
+    fn consecutive_reasoning_items_merge_when_enabled() {
+        let mut state = AppState::new();
+        state.apply(Action::AgentMergeReasoningEntriesChanged { enabled: true });
+        state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/repo"),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+        state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "abandon-about".to_owned(),
+            branch_name: "luban/abandon-about".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban/worktrees/repo/abandon-about"),
+        });
+        let workspace_id = workspace_id_by_name(&state, "abandon-about");
+        let thread_id = default_thread_id();
+
+        state.apply(Action::SendAgentMessage {
+            workspace_id,
+            thread_id,
+            text: "Test".to_owned(),
+            attachments: Vec::new(),
+            runner: None,
+            amp_mode: None,
+        });
+        let run_id = state
+            .workspace_thread_conversation(workspace_id, thread_id)
+            .expect("missing conversation")
+            .active_run_id
+            .expect("missing active run id");
+        let entries_before = state
+            .workspace_thread_conversation(workspace_id, thread_id)
+            .expect("missing conversation")
+            .entries
+            .len();
+
+        for (id, text) in [("r-1", "first"), ("r-2", "second"), ("r-3", "third")] {
+            state.apply(Action::AgentEventReceived {
+                workspace_id,
+                thread_id,
+                run_id,
+                event: CodexThreadEvent::ItemCompleted {
+                    item: CodexThreadItem::Reasoning {
+                        id: id.to_owned(),
+                        text: text.to_owned(),
+                    },
+                },
+            });
+        }
+
+        let conversation = state
+            .workspace_thread_conversation(workspace_id, thread_id)
+            .expect("missing conversation");
+        assert_eq!(conversation.entries.len(), entries_before + 1);
+        match conversation.entries.last() {
+            Some(ConversationEntry::AgentEvent {
+                entry_id,
+                event: crate::AgentEvent::Item { item },
+                ..
+            }) => {
+                assert_eq!(entry_id, &format!("e_{}", entries_before + 1));
+                match item.as_ref() {
+                    CodexThreadItem::Reasoning { id, text } => {
+                        assert_eq!(id, "r-1");
+                        assert_eq!(text, "first\n\nsecond\n\nthird");
+                    }
+                    other => panic!("expected reasoning item, got {other:?}"),
+                }
+            }
+            other => panic!("expected merged reasoning entry, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn web_search_item_accumulates_results_across_updates() {
+        let mut state = AppState::new();
+        state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/repo"),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+        state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "abandon-about".to_owned(),
+            branch_name: "luban/abandon-about".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban/worktrees/repo/abandon-about"),
+        });
+        let workspace_id = workspace_id_by_name(&state, "abandon-about");
+        let thread_id = default_thread_id();
+
+        state.apply(Action::SendAgentMessage {
+            workspace_id,
+            thread_id,
+            text: "Test".to_owned(),
+            attachments: Vec::new(),
+            runner: None,
+            amp_mode: None,
+        });
+        let run_id = state
+            .workspace_thread_conversation(workspace_id, thread_id)
+            .expect("missing conversation")
+            .active_run_id
+            .expect("missing active run id");
+
+        let result = |n: u32| crate::CodexWebSearchResult {
+            title: format!("Result {n}"),
+            url: format!("https://example.com/{n}"),
+            snippet: format!("snippet {n}"),
+        };
+        let web_search = |results: Vec<crate::CodexWebSearchResult>| CodexThreadItem::WebSearch {
+            id: "ws-1".to_owned(),
+            query: "luban".to_owned(),
+            results,
+        };
+        for event in [
+            CodexThreadEvent::ItemStarted {
+                item: web_search(Vec::new()),
+            },
+            CodexThreadEvent::ItemUpdated {
+                item: web_search(vec![result(1)]),
+            },
+            CodexThreadEvent::ItemUpdated {
+                item: web_search(vec![result(1), result(2)]),
+            },
+            CodexThreadEvent::ItemCompleted {
+                item: web_search(Vec::new()),
+            },
+        ] {
+            state.apply(Action::AgentEventReceived {
+                workspace_id,
+                thread_id,
+                run_id,
+                event,
+            });
+        }
+
+        let conversation = state
+            .workspace_thread_conversation(workspace_id, thread_id)
+            .expect("missing conversation");
+        match conversation.entries.last() {
+            Some(ConversationEntry::AgentEvent {
+                event: crate::AgentEvent::Item { item },
+                ..
+            }) => match item.as_ref() {
+                CodexThreadItem::WebSearch { id, results, .. } => {
+                    assert_eq!(id, "ws-1");
+                    assert_eq!(results, &vec![result(1), result(2)]);
+                }
+                other => panic!("expected web search item, got {other:?}"),
+            },
+            other => panic!("expected web search entry, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn app_started_emits_load_app_state_effect() {
+        let mut state = AppState::new();
+        let effects = state.apply(Action::AppStarted);
+        assert_eq!(effects.len(), 1);
+        assert!(matches!(effects[0], Effect::LoadAppState));
+    }
+
+    #[test]
+    fn add_project_emits_save_app_state_effect() {
+        let mut state = AppState::new();
+        let effects = state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/repo"),
+            is_git: true,
+        });
+        assert_eq!(effects.len(), 2);
+        assert!(matches!(effects[0], Effect::SaveAppState));
+        assert!(matches!(
+            effects[1],
+            Effect::DetectProjectDefaultBranch { .. }
+        ));
+    }
+
+    #[test]
+    fn main_workspace_cannot_be_archived() {
+        let mut state = AppState::new();
+        state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/repo"),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+        state.apply(Action::CreateWorkspace {
+            project_id,
+            branch_name_hint: None,
+        });
+
+        let workspace_id = main_workspace_id(&state);
+        let effects = state.apply(Action::ArchiveWorkspace { workspace_id });
+        assert!(effects.is_empty());
+
+        let project = &state.projects[0];
+        let workspace = project
+            .workspaces
+            .iter()
+            .find(|w| w.id == workspace_id)
+            .expect("missing main workspace after archive attempt");
+        assert_eq!(workspace.archive_status, OperationStatus::Idle);
+        assert_eq!(workspace.status, WorkspaceStatus::Active);
+        assert_eq!(workspace.worktree_path, project.path);
+    }
+
+    #[test]
+    fn archiving_a_running_workspace_cancels_agent_turns_first() {
+        let mut state = AppState::new();
+        state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/repo"),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+
+        let worktree_path = PathBuf::from("/tmp/repo/worktrees/wt");
+        state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "wt".to_owned(),
+            branch_name: "feature".to_owned(),
+            worktree_path: worktree_path.clone(),
+        });
+
+        let workspace_id = state.projects[0]
+            .workspaces
+            .iter()
+            .find(|w| w.worktree_path == worktree_path)
+            .expect("missing workspace")
+            .id;
+        state.apply(Action::CreateWorkspaceThread { workspace_id });
+        let thread_id = state.active_thread_id(workspace_id).unwrap();
+
+        {
+            let conversation = state
+                .conversations
+                .get_mut(&(workspace_id, thread_id))
+                .expect("missing conversation");
+            conversation.run_status = OperationStatus::Running;
+            conversation.active_run_id = Some(99);
+        }
+
+        let effects = state.apply(Action::ArchiveWorkspace { workspace_id });
+        assert_eq!(effects.len(), 2);
+
+        match &effects[0] {
+            Effect::CancelAgentTurn {
+                workspace_id: wid,
+                thread_id: tid,
+                run_id,
+            } => {
+                assert_eq!(*wid, workspace_id);
+                assert_eq!(*tid, thread_id);
+                assert_eq!(*run_id, 99);
+            }
+            other => panic!("expected CancelAgentTurn, got {other:?}"),
+        }
+        assert!(matches!(
+            &effects[1],
+            Effect::ArchiveWorkspace { workspace_id: wid } if *wid == workspace_id
+        ));
+
+        let conversation = state
+            .conversations
+            .get(&(workspace_id, thread_id))
+            .expect("missing conversation");
+        assert_eq!(conversation.run_status, OperationStatus::Idle);
+        assert_eq!(conversation.active_run_id, None);
+        assert!(conversation.queue_paused);
+        assert!(matches!(
+            conversation.entries.last(),
+            Some(ConversationEntry::AgentEvent {
+                event: crate::AgentEvent::TurnCanceled,
+                ..
+            })
+        ));
+
+        let workspace = state
+            .workspace(workspace_id)
+            .expect("missing workspace after archive request");
+        assert_eq!(workspace.archive_status, OperationStatus::Running);
+    }
+
+    #[test]
+    fn demo_state_is_consistent() {
+        let state = AppState::demo();
+
+        assert!(!state.projects.is_empty());
+    }
+
+    #[test]
+    fn project_slug_is_sanitized_and_unique() {
+        let mut state = AppState::new();
+        state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/My Project"),
+            is_git: true,
+        });
+        state.apply(Action::AddProject {
+            path: PathBuf::from("/home/My Project"),
+            is_git: true,
+        });
+
+        assert_eq!(state.projects.len(), 2);
+        assert_eq!(state.projects[0].slug, "my-project");
+        assert_eq!(state.projects[1].slug, "my-project-2");
+    }
+
+    #[test]
+    fn projects_are_deduped_by_normalized_path() {
+        let mut state = AppState::new();
+        state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/repo"),
+            is_git: true,
+        });
+        state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/repo/"),
+            is_git: true,
+        });
+
+        assert_eq!(state.projects.len(), 1);
+        assert_eq!(state.projects[0].path, PathBuf::from("/tmp/repo"));
+    }
+
+    #[test]
+    fn delete_project_removes_state_and_emits_save_effect() {
+        let mut state = AppState::new();
+        state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/repo"),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+        state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "main".to_owned(),
+            branch_name: "main".to_owned(),
+            worktree_path: PathBuf::from("/tmp/repo"),
+        });
+        let main_id = workspace_id_by_name(&state, "main");
+
+        state.apply(Action::OpenWorkspace {
+            workspace_id: main_id,
+        });
+        assert!(matches!(state.main_pane, MainPane::Workspace(_)));
+
+        let effects = state.apply(Action::DeleteProject { project_id });
+        assert_eq!(effects.len(), 1);
+        assert!(matches!(effects[0], Effect::SaveAppState));
+
+        assert!(state.projects.is_empty());
+        assert!(!state.workspace_tabs.contains_key(&main_id));
+        assert!(state.conversations.keys().all(|(wid, _)| *wid != main_id));
+        assert!(state.last_open_workspace_id.is_none());
+        assert_eq!(state.main_pane, MainPane::Dashboard);
+        assert_eq!(state.right_pane, RightPane::None);
+    }
+
+    #[test]
+    fn create_workspace_sets_busy_and_emits_effect() {
+        let mut state = AppState::new();
+        state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/repo"),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+
+        let effects = state.apply(Action::CreateWorkspace {
+            project_id,
+            branch_name_hint: None,
+        });
+        assert_eq!(effects.len(), 1);
+        assert!(matches!(effects[0], Effect::CreateWorkspace { .. }));
+
+        let project = state.project(project_id).unwrap();
+        assert_eq!(project.create_workspace_status, OperationStatus::Running);
+    }
+
+    #[test]
+    fn open_workspace_emits_conversation_load_effect() {
+        let mut state = AppState::demo();
+        let workspace_id = first_non_main_workspace_id(&state);
+
+        let effects = state.apply(Action::OpenWorkspace { workspace_id });
+        assert_eq!(effects.len(), 3);
+        assert!(matches!(effects[0], Effect::SaveAppState));
+        assert!(matches!(effects[1], Effect::LoadWorkspaceThreads { .. }));
+        assert!(matches!(effects[2], Effect::LoadConversation { .. }));
+    }
+
+    #[test]
+    fn app_state_restores_last_open_workspace() {
+        let mut state = AppState::new();
+        state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/repo"),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+        state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "abandon-about".to_owned(),
+            branch_name: "luban/abandon-about".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban/worktrees/repo/abandon-about"),
+        });
+        let workspace_id = workspace_id_by_name(&state, "abandon-about");
+        state.apply(Action::OpenWorkspace { workspace_id });
+
+        let persisted = state.to_persisted();
+        assert_eq!(persisted.last_open_workspace_id, Some(workspace_id.0));
+
+        let mut loaded = AppState::new();
+        let effects = loaded.apply(Action::AppStateLoaded {
+            persisted: Box::new(persisted),
+        });
+
+        assert!(
+            matches!(loaded.main_pane, MainPane::Workspace(id) if id == workspace_id),
+            "expected main pane to restore workspace"
+        );
+        assert_eq!(loaded.right_pane, RightPane::Terminal);
+        assert_eq!(effects.len(), 6);
+        assert!(matches!(effects[0], Effect::LoadCodexDefaults));
+        assert!(matches!(effects[1], Effect::LoadTaskPromptTemplates));
+        assert!(matches!(effects[2], Effect::LoadSystemPromptTemplates));
+        assert!(matches!(
+            effects[3],
+            Effect::DetectProjectDefaultBranch { project_id: id } if id == project_id
+        ));
+        assert!(matches!(effects[4], Effect::LoadWorkspaceThreads { .. }));
+        assert!(matches!(
+            effects[5],
+            Effect::LoadConversation { workspace_id: id, .. } if id == workspace_id
+        ));
+    }
+
+    #[test]
+    fn chat_drafts_are_isolated_and_preserved_on_reload() {
+        let mut state = AppState::new();
+        state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/repo"),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+
+        state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "w1".to_owned(),
+            branch_name: "repo/w1".to_owned(),
+            worktree_path: PathBuf::from("/tmp/repo/worktrees/w1"),
+        });
+        state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "w2".to_owned(),
+            branch_name: "repo/w2".to_owned(),
+            worktree_path: PathBuf::from("/tmp/repo/worktrees/w2"),
+        });
+
+        let w1 = workspace_id_by_name(&state, "w1");
+        let w2 = workspace_id_by_name(&state, "w2");
+        let thread_id = default_thread_id();
+        state.apply(Action::CreateWorkspaceThread { workspace_id: w1 });
+        state.apply(Action::CreateWorkspaceThread { workspace_id: w2 });
+
+        state.apply(Action::ChatDraftChanged {
+            workspace_id: w1,
+            thread_id,
+            text: "draft-1".to_owned(),
+        });
+        state.apply(Action::ChatDraftChanged {
+            workspace_id: w2,
+            thread_id,
+            text: "draft-2".to_owned(),
+        });
+
+        assert_eq!(state.workspace_conversation(w1).unwrap().draft, "draft-1");
+        assert_eq!(state.workspace_conversation(w2).unwrap().draft, "draft-2");
+
+        state.apply(Action::ConversationLoaded {
+            workspace_id: w1,
+            thread_id,
+            snapshot: ConversationSnapshot {
+                title: None,
+                thread_id: None,
+                task_status: crate::TaskStatus::Todo,
+                runner: None,
+                agent_model_id: None,
+                thinking_effort: None,
+                amp_mode: None,
+                claude_max_output_tokens: None,
+                claude_system_prompt: None,
+                auto_commit: false,
+                entries: Vec::new(),
+                entries_total: 0,
+                entries_start: 0,
+                pending_prompts: Vec::new(),
+                queue_paused: false,
+                run_started_at_unix_ms: None,
+                run_finished_at_unix_ms: None,
+                notes: String::new(),
+                cwd_subpath: None,
+                hunk_comments: Vec::new(),
+                collapsed_ranges: Vec::new(),
+                draft: String::new(),
+                total_usage: Default::default(),
+            },
+        });
+        assert_eq!(state.workspace_conversation(w1).unwrap().draft, "draft-1");
+    }
+
+    #[test]
+    fn chat_draft_is_persisted_cleared_on_send_and_rehydrated_on_load() {
+        let mut state = AppState::new();
+        state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/repo"),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+        state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "w1".to_owned(),
+            branch_name: "repo/w1".to_owned(),
+            worktree_path: PathBuf::from("/tmp/repo/worktrees/w1"),
+        });
+        let w1 = workspace_id_by_name(&state, "w1");
+        let thread_id = default_thread_id();
+        state.apply(Action::CreateWorkspaceThread { workspace_id: w1 });
+
+        let is_store_draft = |effects: &[Effect]| {
+            effects.iter().any(|effect| {
+                matches!(
+                    effect,
+                    Effect::StoreConversationDraft { workspace_id, thread_id: tid }
+                        if *workspace_id == w1 && *tid == thread_id
+                )
+            })
+        };
+
+        let effects = state.apply(Action::ChatDraftChanged {
+            workspace_id: w1,
+            thread_id,
+            text: "half".to_owned(),
+        });
+        assert!(is_store_draft(&effects));
+        let effects = state.apply(Action::ChatDraftChanged {
+            workspace_id: w1,
+            thread_id,
+            text: "half".to_owned(),
+        });
+        assert!(effects.is_empty());
+        let effects = state.apply(Action::ChatDraftChanged {
+            workspace_id: w1,
+            thread_id,
+            text: String::new(),
+        });
+        assert!(is_store_draft(&effects));
+
+        state.apply(Action::ChatDraftChanged {
+            workspace_id: w1,
+            thread_id,
+            text: "ship it".to_owned(),
+        });
+        let effects = state.apply(Action::SendAgentMessage {
+            workspace_id: w1,
+            thread_id,
+            text: "ship it".to_owned(),
+            attachments: Vec::new(),
+            runner: None,
+            amp_mode: None,
+        });
+        assert!(is_store_draft(&effects));
+        assert_eq!(state.workspace_conversation(w1).unwrap().draft, "");
+
+        let mut restored = AppState::new();
+        restored.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/repo"),
+            is_git: true,
+        });
+        let project_id = restored.projects[0].id;
+        restored.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "w1".to_owned(),
+            branch_name: "repo/w1".to_owned(),
+            worktree_path: PathBuf::from("/tmp/repo/worktrees/w1"),
+        });
+        let w1 = workspace_id_by_name(&restored, "w1");
+        restored.apply(Action::ConversationLoaded {
+            workspace_id: w1,
+            thread_id,
+            snapshot: ConversationSnapshot {
+                title: None,
+                thread_id: None,
+                task_status: crate::TaskStatus::Todo,
+                runner: None,
+                agent_model_id: None,
+                thinking_effort: None,
+                amp_mode: None,
+                claude_max_output_tokens: None,
+                claude_system_prompt: None,
+                auto_commit: false,
+                entries: Vec::new(),
+                entries_total: 0,
+                entries_start: 0,
+                pending_prompts: Vec::new(),
+                queue_paused: false,
+                run_started_at_unix_ms: None,
+                run_finished_at_unix_ms: None,
+                notes: String::new(),
+                cwd_subpath: None,
+                hunk_comments: Vec::new(),
+                collapsed_ranges: Vec::new(),
+                draft: "from last session".to_owned(),
+                total_usage: Default::default(),
+            },
+        });
+        assert_eq!(
+            restored
+                .workspace_thread_conversation(w1, thread_id)
+                .unwrap()
+                .draft,
+            "from last session"
+        );
+    }
+
+    #[test]
+    fn chat_draft_edits_update_attachment_anchors_without_removing() {
+        let mut state = AppState::new();
+        state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/repo"),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+
+        state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "w1".to_owned(),
+            branch_name: "repo/w1".to_owned(),
+            worktree_path: PathBuf::from("/tmp/repo/worktrees/w1"),
+        });
+        let w1 = workspace_id_by_name(&state, "w1");
+        let thread_id = default_thread_id();
+        state.apply(Action::CreateWorkspaceThread { workspace_id: w1 });
+
+        state.apply(Action::ChatDraftChanged {
+            workspace_id: w1,
+            thread_id,
+            text: "0123456789".to_owned(),
+        });
+        state.apply(Action::ChatDraftAttachmentAdded {
+            workspace_id: w1,
+            thread_id,
+            id: 1,
+            kind: ContextTokenKind::Image,
+            anchor: 8,
+        });
+        state.apply(Action::ChatDraftAttachmentResolved {
+            workspace_id: w1,
+            thread_id,
+            id: 1,
+            attachment: crate::AttachmentRef {
+                id: "blob-a".to_owned(),
+                kind: crate::AttachmentKind::Image,
+                name: "a.png".to_owned(),
+                extension: "png".to_owned(),
+                mime: None,
+                byte_len: 1,
+            },
+        });
+        state.apply(Action::ChatDraftAttachmentAdded {
+            workspace_id: w1,
+            thread_id,
+            id: 2,
+            kind: ContextTokenKind::Text,
+            anchor: 5,
+        });
+        state.apply(Action::ChatDraftAttachmentResolved {
+            workspace_id: w1,
+            thread_id,
+            id: 2,
+            attachment: crate::AttachmentRef {
+                id: "blob-b".to_owned(),
+                kind: crate::AttachmentKind::Text,
+                name: "b.txt".to_owned(),
+                extension: "txt".to_owned(),
+                mime: None,
+                byte_len: 1,
+            },
+        });
+
+        // Delete bytes [3,7): "3456" -> "012789".
+        state.apply(Action::ChatDraftChanged {
+            workspace_id: w1,
+            thread_id,
+            text: "012789".to_owned(),
+        });
+
+        let conversation = state
+            .workspace_conversation(w1)
+            .expect("missing conversation");
+        assert_eq!(conversation.draft, "012789");
+        assert_eq!(conversation.draft_attachments.len(), 2);
+
+        let a = conversation
+            .draft_attachments
+            .iter()
+            .find(|a| a.id == 1)
+            .expect("missing attachment 1");
+        let b = conversation
+            .draft_attachments
+            .iter()
+            .find(|a| a.id == 2)
+            .expect("missing attachment 2");
+
+        // Anchor 8 shifts by -4 -> 4.
+        assert_eq!(a.anchor, 4);
+        // Anchor 5 is inside the deleted range -> snaps to start (3).
+        assert_eq!(b.anchor, 3);
+    }
+
+    #[test]
+    fn conversation_loaded_does_not_reset_running_turn_state() {
+        let mut state = AppState::demo();
+        let workspace_id = first_non_main_workspace_id(&state);
+        let thread_id = default_thread_id();
+
+        state.apply(Action::SendAgentMessage {
+            workspace_id,
+            thread_id,
+            text: "Hello".to_owned(),
+            attachments: Vec::new(),
+            runner: None,
+            amp_mode: None,
+        });
+        let run_id = state
+            .workspace_thread_conversation(workspace_id, thread_id)
+            .expect("missing conversation")
+            .active_run_id
+            .expect("missing active run id");
+
+        let item = CodexThreadItem::AgentMessage {
+            id: "item_0".to_owned(),
+            text: "Hi".to_owned(),
+        };
+        state.apply(Action::AgentEventReceived {
+            workspace_id,
+            thread_id,
+            run_id,
+            event: CodexThreadEvent::ItemStarted { item },
+        });
+
+        assert_eq!(
+            state
+                .workspace_conversation(workspace_id)
+                .unwrap()
+                .run_status,
+            OperationStatus::Running
+        );
+        let before_entries = state
+            .workspace_conversation(workspace_id)
+            .unwrap()
+            .entries
+            .iter()
+            .filter(|e| !matches!(e, ConversationEntry::SystemEvent { .. }))
+            .collect::<Vec<_>>();
+        assert_eq!(before_entries.len(), 3);
+        assert!(matches!(
+            &before_entries[1],
+            ConversationEntry::AgentEvent {
+                event: crate::AgentEvent::TurnStarted { .. },
+                ..
+            }
+        ));
+        assert!(matches!(
+            &before_entries[2],
+            ConversationEntry::AgentEvent {
+                event: crate::AgentEvent::Message { id, .. },
+                ..
+            } if id == "item_0"
+        ));
+
+        state.apply(Action::ConversationLoaded {
+            workspace_id,
+            thread_id,
+            snapshot: ConversationSnapshot {
+                title: None,
+                thread_id: Some("thread_0".to_owned()),
+                task_status: crate::TaskStatus::Todo,
+                runner: None,
+                agent_model_id: None,
+                thinking_effort: None,
+                amp_mode: None,
+                claude_max_output_tokens: None,
+                claude_system_prompt: None,
+                auto_commit: false,
+                entries: Vec::new(),
+                entries_total: 0,
+                entries_start: 0,
+                pending_prompts: Vec::new(),
+                queue_paused: false,
+                run_started_at_unix_ms: None,
+                run_finished_at_unix_ms: None,
+                notes: String::new(),
+                cwd_subpath: None,
+                hunk_comments: Vec::new(),
+                collapsed_ranges: Vec::new(),
+                draft: String::new(),
+                total_usage: Default::default(),
+            },
+        });
+
+        let conversation = state.workspace_conversation(workspace_id).unwrap();
+        assert_eq!(conversation.run_status, OperationStatus::Running);
+        let entries = conversation
+            .entries
+            .iter()
+            .filter(|e| !matches!(e, ConversationEntry::SystemEvent { .. }))
+            .collect::<Vec<_>>();
+        assert_eq!(entries.len(), 3);
+        assert!(matches!(
+            &entries[0],
+            ConversationEntry::UserEvent {
+                event: crate::UserEvent::Message { text, .. },
+                ..
+            } if text == "Hello"
+        ));
+        assert_eq!(conversation.thread_id.as_deref(), Some("thread_0"));
+    }
+
+    #[test]
+    fn conversation_loaded_does_not_overwrite_newer_local_entries() {
+        let mut state = AppState::demo();
+        let workspace_id = first_non_main_workspace_id(&state);
+        let thread_id = default_thread_id();
+
+        state.apply(Action::SendAgentMessage {
+            workspace_id,
+            thread_id,
+            text: "Hello".to_owned(),
+            attachments: Vec::new(),
+            runner: None,
+            amp_mode: None,
+        });
+        state.apply(Action::AgentEventReceived {
+            workspace_id,
+            thread_id,
+            run_id: state
+                .workspace_thread_conversation(workspace_id, thread_id)
+                .expect("missing conversation")
+                .active_run_id
+                .expect("missing active run id"),
+            event: CodexThreadEvent::TurnDuration { duration_ms: 1234 },
+        });
+
+        state.apply(Action::ConversationLoaded {
+            workspace_id,
+            thread_id,
+            snapshot: ConversationSnapshot {
+                title: None,
+                thread_id: None,
+                task_status: crate::TaskStatus::Todo,
+                runner: None,
+                agent_model_id: None,
+                thinking_effort: None,
+                amp_mode: None,
+                claude_max_output_tokens: None,
+                claude_system_prompt: None,
+                auto_commit: false,
+                entries: vec![ConversationEntry::UserEvent {
+                    entry_id: String::new(),
+                    created_at_unix_ms: 1,
+                    event: crate::UserEvent::Message {
+                        text: "Hello".to_owned(),
+                        attachments: Vec::new(),
+                    },
+                }],
+                entries_total: 0,
+                entries_start: 0,
+                pending_prompts: Vec::new(),
+                queue_paused: false,
+                run_started_at_unix_ms: None,
+                run_finished_at_unix_ms: None,
+                notes: String::new(),
+                cwd_subpath: None,
+                hunk_comments: Vec::new(),
+                collapsed_ranges: Vec::new(),
+                draft: String::new(),
+                total_usage: Default::default(),
+            },
+        });
+
+        let after = state
+            .workspace_conversation(workspace_id)
+            .unwrap()
+            .entries
+            .iter()
+            .filter(|e| !matches!(e, ConversationEntry::SystemEvent { .. }))
+            .collect::<Vec<_>>();
+        assert_eq!(after.len(), 3);
+        assert!(matches!(
+            &after[0],
+            ConversationEntry::UserEvent {
+                event: crate::UserEvent::Message { text, .. },
+                ..
+            } if text == "Hello"
+        ));
+        assert!(matches!(
+            &after[1],
+            ConversationEntry::AgentEvent {
+                event: crate::AgentEvent::TurnStarted { .. },
+                ..
+            }
+        ));
+        assert!(matches!(
+            &after[2],
+            ConversationEntry::AgentEvent {
+                event: crate::AgentEvent::TurnDuration { duration_ms: 1234 },
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn conversation_loaded_replaces_entries_when_snapshot_is_newer() {
+        let mut state = AppState::demo();
+        let workspace_id = first_non_main_workspace_id(&state);
+        let thread_id = default_thread_id();
+
+        state.apply(Action::ConversationLoaded {
+            workspace_id,
+            thread_id,
+            snapshot: ConversationSnapshot {
+                title: None,
+                thread_id: None,
+                task_status: crate::TaskStatus::Todo,
+                runner: None,
+                agent_model_id: None,
+                thinking_effort: None,
+                amp_mode: None,
+                claude_max_output_tokens: None,
+                claude_system_prompt: None,
+                auto_commit: false,
+                entries: vec![ConversationEntry::UserEvent {
+                    entry_id: String::new(),
+                    created_at_unix_ms: 1,
+                    event: crate::UserEvent::Message {
+                        text: "Hello".to_owned(),
+                        attachments: Vec::new(),
+                    },
+                }],
+                entries_total: 0,
+                entries_start: 0,
+                pending_prompts: Vec::new(),
+                queue_paused: false,
+                run_started_at_unix_ms: None,
+                run_finished_at_unix_ms: None,
+                notes: String::new(),
+                cwd_subpath: None,
+                hunk_comments: Vec::new(),
+                collapsed_ranges: Vec::new(),
+                draft: String::new(),
+                total_usage: Default::default(),
+            },
+        });
+
+        state.apply(Action::ConversationLoaded {
+            workspace_id,
+            thread_id,
+            snapshot: ConversationSnapshot {
+                title: None,
+                thread_id: None,
+                task_status: crate::TaskStatus::Todo,
+                runner: None,
+                agent_model_id: None,
+                thinking_effort: None,
+                amp_mode: None,
+                claude_max_output_tokens: None,
+                claude_system_prompt: None,
+                auto_commit: false,
+                entries: vec![
+                    ConversationEntry::UserEvent {
+                        entry_id: String::new(),
+                        created_at_unix_ms: 1,
+                        event: crate::UserEvent::Message {
+                            text: "Hello".to_owned(),
+                            attachments: Vec::new(),
+                        },
+                    },
+                    ConversationEntry::AgentEvent {
+                        entry_id: String::new(),
+                        created_at_unix_ms: 2,
+                        runner: None,
+                        event: crate::AgentEvent::TurnDuration { duration_ms: 1234 },
+                    },
+                ],
+                entries_total: 0,
+                entries_start: 0,
+                pending_prompts: Vec::new(),
+                queue_paused: false,
+                run_started_at_unix_ms: None,
+                run_finished_at_unix_ms: None,
+                notes: String::new(),
+                cwd_subpath: None,
+                hunk_comments: Vec::new(),
+                collapsed_ranges: Vec::new(),
+                draft: String::new(),
+                total_usage: Default::default(),
+            },
+        });
+
+        let after = &state.workspace_conversation(workspace_id).unwrap().entries;
+        assert!(matches!(
+            &after[..],
+            [
+                ConversationEntry::UserEvent {
+                    event: crate::UserEvent::Message { .. },
+                    ..
+                },
+                ConversationEntry::AgentEvent {
+                    event: crate::AgentEvent::TurnDuration { duration_ms: 1234 },
+                    ..
+                }
+            ]
+        ));
+    }
+
+    #[test]
+    fn conversation_loaded_restores_queued_prompts_when_local_is_empty() {
+        let mut state = AppState::demo();
+        let workspace_id = first_non_main_workspace_id(&state);
+        let thread_id = default_thread_id();
+
+        state.apply(Action::ConversationLoaded {
+            workspace_id,
+            thread_id,
+            snapshot: ConversationSnapshot {
+                title: None,
+                thread_id: None,
+                task_status: crate::TaskStatus::Todo,
+                runner: None,
+                agent_model_id: None,
+                thinking_effort: None,
+                amp_mode: None,
+                claude_max_output_tokens: None,
+                claude_system_prompt: None,
+                auto_commit: false,
+                entries: Vec::new(),
+                entries_total: 0,
+                entries_start: 0,
+                pending_prompts: vec![QueuedPrompt {
+                    id: 3,
+                    text: "Queued".to_owned(),
+                    attachments: Vec::new(),
+                    run_config: AgentRunConfig {
+                        runner: crate::AgentRunnerKind::Codex,
+                        model_id: "gpt-5.3-codex".to_owned(),
+                        thinking_effort: ThinkingEffort::Minimal,
+                        amp_mode: None,
+                        claude_max_output_tokens: None,
+                        claude_system_prompt: None,
+                    },
+                }],
+                queue_paused: true,
+                run_started_at_unix_ms: None,
+                run_finished_at_unix_ms: None,
+                notes: String::new(),
+                cwd_subpath: None,
+                hunk_comments: Vec::new(),
+                collapsed_ranges: Vec::new(),
+                draft: String::new(),
+                total_usage: Default::default(),
+            },
+        });
+
+        let conversation = state.workspace_conversation(workspace_id).unwrap();
+        assert!(conversation.queue_paused);
+        assert_eq!(conversation.pending_prompts.len(), 1);
+        assert_eq!(conversation.pending_prompts[0].id, 3);
+        assert_eq!(conversation.next_queued_prompt_id, 4);
+    }
+
+    #[test]
+    fn conversation_loaded_applies_persisted_run_config() {
+        let mut state = AppState::demo();
+        let workspace_id = first_non_main_workspace_id(&state);
+        let thread_id = default_thread_id();
+
+        state.apply(Action::ConversationLoaded {
+            workspace_id,
+            thread_id,
+            snapshot: ConversationSnapshot {
+                title: None,
+                thread_id: None,
+                task_status: crate::TaskStatus::Todo,
+                runner: None,
+                agent_model_id: Some("gpt-5.3-codex".to_owned()),
+                thinking_effort: Some(ThinkingEffort::High),
+                amp_mode: None,
+                claude_max_output_tokens: None,
+                claude_system_prompt: None,
+                auto_commit: false,
+                entries: Vec::new(),
+                entries_total: 0,
+                entries_start: 0,
+                pending_prompts: Vec::new(),
+                queue_paused: false,
+                run_started_at_unix_ms: None,
+                run_finished_at_unix_ms: None,
+                notes: String::new(),
+                cwd_subpath: None,
+                hunk_comments: Vec::new(),
+                collapsed_ranges: Vec::new(),
+                draft: String::new(),
+                total_usage: Default::default(),
+            },
+        });
+
+        let conversation = state
+            .workspace_thread_conversation(workspace_id, thread_id)
+            .expect("missing conversation");
+        assert_eq!(conversation.agent_model_id, "gpt-5.3-codex");
+        assert_eq!(conversation.thinking_effort, ThinkingEffort::High);
+    }
+
+    #[test]
+    fn conversation_entries_are_bounded_in_memory() {
+        let mut state = AppState::demo();
+        let workspace_id = first_non_main_workspace_id(&state);
+        let thread_id = default_thread_id();
+
+        state.apply(Action::SendAgentMessage {
+            workspace_id,
+            thread_id,
+            text: "Hello".to_owned(),
+            attachments: Vec::new(),
+            runner: None,
+            amp_mode: None,
+        });
+        let run_id = state
+            .workspace_thread_conversation(workspace_id, thread_id)
+            .expect("missing conversation")
+            .active_run_id
+            .expect("missing active run id");
+
+        let total = crate::state::MAX_CONVERSATION_ENTRIES_IN_MEMORY + 100;
+        for idx in 0..total {
+            state.apply(Action::AgentEventReceived {
+                workspace_id,
+                thread_id,
+                run_id,
+                event: CodexThreadEvent::TurnDuration {
+                    duration_ms: idx as u64,
+                },
+            });
+        }
+
+        let conversation = state.workspace_conversation(workspace_id).unwrap();
+        assert_eq!(
+            conversation.entries.len(),
+            crate::state::MAX_CONVERSATION_ENTRIES_IN_MEMORY
+        );
+        // System event, prompt and turn-started marker precede the durations.
+        assert_eq!(conversation.entries_start, 103);
+        assert_eq!(conversation.entries_total, (total + 3) as u64);
+    }
+
+    #[test]
+    fn send_agent_message_sets_running_and_emits_effect() {
+        let mut state = AppState::demo();
+        let workspace_id = first_non_main_workspace_id(&state);
+        let thread_id = default_thread_id();
+
+        let effects = state.apply(Action::SendAgentMessage {
+            workspace_id,
+            thread_id,
+            text: "Hello".to_owned(),
+            attachments: Vec::new(),
+            runner: None,
+            amp_mode: None,
+        });
+        let run_effect = effects
+            .iter()
+            .find(|e| matches!(e, Effect::RunAgentTurn { .. }))
+            .expect("missing RunAgentTurn effect");
+        assert!(matches!(
+            run_effect,
+            Effect::RunAgentTurn {
+                workspace_id: wid,
+                thread_id: tid,
+                text,
+                run_config,
+                ..
+            } if *wid == workspace_id
+                && *tid == thread_id
+                && text == "Hello"
+                && run_config.model_id == default_agent_model_id()
+                && run_config.thinking_effort == default_thinking_effort()
+        ));
+
+        let conversation = state.workspace_conversation(workspace_id).unwrap();
+        assert_eq!(conversation.run_status, OperationStatus::Running);
+        let user_messages = conversation
+            .entries
+            .iter()
+            .filter_map(|e| match e {
+                ConversationEntry::UserEvent {
+                    event: crate::UserEvent::Message { text, .. },
+                    ..
+                } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(user_messages, vec!["Hello"]);
+    }
+
+    #[test]
+    fn bulk_set_task_status_moves_each_target_and_records_system_events() {
+        let mut state = AppState::demo();
+        let workspace_id = first_non_main_workspace_id(&state);
+        let first = default_thread_id();
+        state.apply(Action::CreateWorkspaceThread { workspace_id });
+        let second = state
+            .workspace_tabs(workspace_id)
+            .expect("missing tabs")
+            .active_tab;
+        assert_ne!(first, second);
+        let missing = WorkspaceThreadId::from_u64(999);
+
+        let effects = state.apply(Action::BulkSetTaskStatus {
+            targets: vec![
+                (workspace_id, first),
+                (workspace_id, missing),
+                (workspace_id, second),
+            ],
+            task_status: crate::TaskStatus::Done,
+        });
+
+        for thread_id in [first, second] {
+            assert!(effects.iter().any(|effect| matches!(
+                effect,
+                Effect::StoreConversationTaskStatus {
+                    thread_id: tid,
+                    task_status: crate::TaskStatus::Done,
+                    ..
+                } if *tid == thread_id
+            )));
+            let conversation = state
+                .workspace_thread_conversation(workspace_id, thread_id)
+                .expect("missing conversation");
+            assert_eq!(conversation.task_status, crate::TaskStatus::Done);
+            assert!(matches!(
+                conversation.entries.last(),
+                Some(ConversationEntry::SystemEvent {
+                    event: crate::ConversationSystemEvent::TaskStatusChanged {
+                        to: crate::TaskStatus::Done,
+                        ..
+                    },
+                    ..
+                })
+            ));
+        }
+        assert!(
+            state
+                .workspace_thread_conversation(workspace_id, missing)
+                .is_none()
+        );
+        assert_eq!(
+            state.last_error.as_deref(),
+            Some("Failed to update 1 of 3 tasks to done")
+        );
+    }
+
+    #[test]
+    fn task_status_canceled_cancels_running_turn_and_emits_effect() {
+        let mut state = AppState::demo();
+        let workspace_id = first_non_main_workspace_id(&state);
+        let thread_id = default_thread_id();
+
+        state.apply(Action::SendAgentMessage {
+            workspace_id,
+            thread_id,
+            text: "Hello".to_owned(),
+            attachments: Vec::new(),
+            runner: None,
+            amp_mode: None,
+        });
+        let run_id = state
+            .workspace_thread_conversation(workspace_id, thread_id)
+            .expect("missing conversation")
+            .active_run_id
+            .expect("missing active run id");
+
+        let effects = state.apply(Action::TaskStatusSet {
+            workspace_id,
+            thread_id,
+            task_status: crate::TaskStatus::Canceled,
+        });
+
+        assert!(
+            effects.iter().any(|e| matches!(
+                e,
+                Effect::CancelAgentTurn { workspace_id: wid, thread_id: tid, run_id: rid }
+                    if *wid == workspace_id && *tid == thread_id && *rid == run_id
+            )),
+            "expected CancelAgentTurn effect"
+        );
+
+        let conversation = state
+            .workspace_thread_conversation(workspace_id, thread_id)
+            .expect("missing conversation");
+        assert_eq!(conversation.task_status, crate::TaskStatus::Canceled);
+        assert_eq!(conversation.run_status, OperationStatus::Idle);
+        assert_eq!(conversation.active_run_id, None);
+        assert!(conversation.queue_paused);
+        assert!(conversation.run_finished_at_unix_ms.is_some());
+        assert!(conversation.entries.iter().any(|e| {
+            matches!(
+                e,
+                ConversationEntry::AgentEvent {
+                    event: crate::AgentEvent::TurnCanceled,
+                    ..
+                }
+            )
+        }));
+    }
+
+    #[test]
+    fn task_status_done_cancels_running_turn_and_triggers_auto_archive_check() {
+        let mut state = AppState::demo();
+        let workspace_id = first_non_main_workspace_id(&state);
+        let thread_id = default_thread_id();
+
+        state.apply(Action::SendAgentMessage {
+            workspace_id,
+            thread_id,
+            text: "Hello".to_owned(),
+            attachments: Vec::new(),
+            runner: None,
+            amp_mode: None,
+        });
+        let run_id = state
+            .workspace_thread_conversation(workspace_id, thread_id)
+            .expect("missing conversation")
+            .active_run_id
+            .expect("missing active run id");
+
+        let effects = state.apply(Action::TaskStatusSet {
+            workspace_id,
+            thread_id,
+            task_status: crate::TaskStatus::Done,
+        });
+
+        assert!(
+            effects.iter().any(|e| matches!(
+                e,
+                Effect::CancelAgentTurn { workspace_id: wid, thread_id: tid, run_id: rid }
+                    if *wid == workspace_id && *tid == thread_id && *rid == run_id
+            )),
+            "expected CancelAgentTurn effect"
+        );
+        assert!(
+            effects.iter().any(|e| matches!(
+                e,
+                Effect::MaybeAutoArchiveWorkspace { workspace_id: wid } if *wid == workspace_id
+            )),
+            "expected MaybeAutoArchiveWorkspace effect"
+        );
+
+        let conversation = state
+            .workspace_thread_conversation(workspace_id, thread_id)
+            .expect("missing conversation");
+        assert_eq!(conversation.task_status, crate::TaskStatus::Done);
+        assert_eq!(conversation.run_status, OperationStatus::Idle);
+        assert_eq!(conversation.active_run_id, None);
+        assert!(conversation.queue_paused);
+    }
+
+    #[test]
+    fn send_agent_message_is_blocked_for_archived_tasks() {
+        let mut state = AppState::demo();
+        let workspace_id = first_non_main_workspace_id(&state);
         let thread_id = default_thread_id();
-        state.apply(Action::CreateWorkspaceThread { workspace_id: w1 });
 
-        state.apply(Action::ChatDraftChanged {
-            workspace_id: w1,
+        state.apply(Action::SendAgentMessage {
+            workspace_id,
             thread_id,
-            text: "0123456789".to_owned(),
+            text: "Hello".to_owned(),
+            attachments: Vec::new(),
+            runner: None,
+            amp_mode: None,
         });
-        state.apply(Action::ChatDraftAttachmentAdded {
-            workspace_id: w1,
+
+        state.apply(Action::TaskStatusSet {
+            workspace_id,
             thread_id,
-            id: 1,
-            kind: ContextTokenKind::Image,
-            anchor: 8,
+            task_status: crate::TaskStatus::Done,
         });
-        state.apply(Action::ChatDraftAttachmentResolved {
-            workspace_id: w1,
+
+        let effects = state.apply(Action::SendAgentMessage {
+            workspace_id,
             thread_id,
-            id: 1,
-            attachment: crate::AttachmentRef {
-                id: "blob-a".to_owned(),
-                kind: crate::AttachmentKind::Image,
-                name: "a.png".to_owned(),
-                extension: "png".to_owned(),
-                mime: None,
-                byte_len: 1,
-            },
+            text: "Should be blocked".to_owned(),
+            attachments: Vec::new(),
+            runner: None,
+            amp_mode: None,
         });
-        state.apply(Action::ChatDraftAttachmentAdded {
-            workspace_id: w1,
+        assert!(effects.is_empty());
+        assert_eq!(state.last_error.as_deref(), Some("Task is archived"));
+    }
+
+    #[test]
+    fn agent_item_completed_is_idempotent() {
+        let mut state = AppState::demo();
+        let workspace_id = first_non_main_workspace_id(&state);
+        let thread_id = default_thread_id();
+
+        state.apply(Action::SendAgentMessage {
+            workspace_id,
             thread_id,
-            id: 2,
-            kind: ContextTokenKind::Text,
-            anchor: 5,
+            text: "Hello".to_owned(),
+            attachments: Vec::new(),
+            runner: None,
+            amp_mode: None,
         });
-        state.apply(Action::ChatDraftAttachmentResolved {
-            workspace_id: w1,
+        let run_id = state
+            .workspace_thread_conversation(workspace_id, thread_id)
+            .expect("missing conversation")
+            .active_run_id
+            .expect("missing active run id");
+
+        let item = CodexThreadItem::AgentMessage {
+            id: "item_0".to_owned(),
+            text: "Hi".to_owned(),
+        };
+
+        state.apply(Action::AgentEventReceived {
+            workspace_id,
             thread_id,
-            id: 2,
-            attachment: crate::AttachmentRef {
-                id: "blob-b".to_owned(),
-                kind: crate::AttachmentKind::Text,
-                name: "b.txt".to_owned(),
-                extension: "txt".to_owned(),
-                mime: None,
-                byte_len: 1,
-            },
+            run_id,
+            event: CodexThreadEvent::ItemCompleted { item: item.clone() },
         });
-
-        // Delete bytes [3,7): "3456" -> "012789".
-        state.apply(Action::ChatDraftChanged {
-            workspace_id: w1,
+        state.apply(Action::AgentEventReceived {
+            workspace_id,
             thread_id,
-            text: "012789".to_owned(),
+            run_id,
+            event: CodexThreadEvent::ItemCompleted { item },
         });
 
-        let conversation = state
-            .workspace_conversation(w1)
-            .expect("missing conversation");
-        assert_eq!(conversation.draft, "012789");
-        assert_eq!(conversation.draft_attachments.len(), 2);
-
-        let a = conversation
-            .draft_attachments
-            .iter()
-            .find(|a| a.id == 1)
-            .expect("missing attachment 1");
-        let b = conversation
-            .draft_attachments
+        let conversation = state.workspace_conversation(workspace_id).unwrap();
+        let completed_items = conversation
+            .entries
             .iter()
-            .find(|a| a.id == 2)
-            .expect("missing attachment 2");
-
-        // Anchor 8 shifts by -4 -> 4.
-        assert_eq!(a.anchor, 4);
-        // Anchor 5 is inside the deleted range -> snaps to start (3).
-        assert_eq!(b.anchor, 3);
+            .filter(|e| {
+                matches!(
+                    e,
+                    ConversationEntry::AgentEvent {
+                        event: crate::AgentEvent::Message { id, .. },
+                        ..
+                    } if id == "item_0"
+                )
+            })
+            .count();
+        assert_eq!(completed_items, 1);
     }
 
     #[test]
-    fn conversation_loaded_does_not_reset_running_turn_state() {
+    fn agent_item_completed_is_idempotent_even_if_not_last_entry() {
         let mut state = AppState::demo();
         let workspace_id = first_non_main_workspace_id(&state);
         let thread_id = default_thread_id();
@@ -5140,77 +9625,123 @@ mod tests {
             id: "item_0".to_owned(),
             text: "Hi".to_owned(),
         };
+
         state.apply(Action::AgentEventReceived {
             workspace_id,
             thread_id,
             run_id,
-            event: CodexThreadEvent::ItemStarted { item },
+            event: CodexThreadEvent::ItemCompleted { item: item.clone() },
+        });
+        state.apply(Action::AgentEventReceived {
+            workspace_id,
+            thread_id,
+            run_id,
+            event: CodexThreadEvent::TurnDuration { duration_ms: 1000 },
+        });
+        state.apply(Action::AgentEventReceived {
+            workspace_id,
+            thread_id,
+            run_id,
+            event: CodexThreadEvent::ItemCompleted { item },
         });
 
-        assert_eq!(
-            state
-                .workspace_conversation(workspace_id)
-                .unwrap()
-                .run_status,
-            OperationStatus::Running
-        );
-        let before_entries = state
-            .workspace_conversation(workspace_id)
-            .unwrap()
+        let conversation = state.workspace_conversation(workspace_id).unwrap();
+        let completed_items = conversation
             .entries
             .iter()
-            .filter(|e| !matches!(e, ConversationEntry::SystemEvent { .. }))
-            .collect::<Vec<_>>();
-        assert_eq!(before_entries.len(), 2);
+            .filter(|e| {
+                matches!(
+                    e,
+                    ConversationEntry::AgentEvent {
+                        event: crate::AgentEvent::Message { id, .. },
+                        ..
+                    } if id == "item_0"
+                )
+            })
+            .count();
+        assert_eq!(completed_items, 1);
+    }
+
+    #[test]
+    fn cancel_agent_turn_sets_idle_and_emits_effect() {
+        let mut state = AppState::demo();
+        let workspace_id = first_non_main_workspace_id(&state);
+        let thread_id = default_thread_id();
+
+        state.apply(Action::SendAgentMessage {
+            workspace_id,
+            thread_id,
+            text: "Hello".to_owned(),
+            attachments: Vec::new(),
+            runner: None,
+            amp_mode: None,
+        });
+
+        let effects = state.apply(Action::CancelAgentTurn {
+            workspace_id,
+            thread_id,
+        });
+        assert_eq!(effects.len(), 1);
+        assert!(matches!(effects[0], Effect::CancelAgentTurn { .. }));
+
+        let conversation = state.workspace_conversation(workspace_id).unwrap();
+        assert_eq!(conversation.run_status, OperationStatus::Idle);
         assert!(matches!(
-            &before_entries[1],
-            ConversationEntry::AgentEvent {
-                event: crate::AgentEvent::Message { id, .. },
+            conversation.entries.last(),
+            Some(ConversationEntry::AgentEvent {
+                event: crate::AgentEvent::TurnCanceled,
                 ..
-            } if id == "item_0"
+            })
         ));
+    }
 
-        state.apply(Action::ConversationLoaded {
-            workspace_id,
-            thread_id,
-            snapshot: ConversationSnapshot {
-                title: None,
-                thread_id: Some("thread_0".to_owned()),
-                task_status: crate::TaskStatus::Todo,
+    #[test]
+    fn cancel_and_clear_queue_stops_turn_and_drops_queued_prompts() {
+        let mut state = AppState::demo();
+        let workspace_id = first_non_main_workspace_id(&state);
+        let thread_id = default_thread_id();
+
+        for text in ["Hello", "Queued 1", "Queued 2"] {
+            state.apply(Action::SendAgentMessage {
+                workspace_id,
+                thread_id,
+                text: text.to_owned(),
+                attachments: Vec::new(),
                 runner: None,
-                agent_model_id: None,
-                thinking_effort: None,
                 amp_mode: None,
-                entries: Vec::new(),
-                entries_total: 0,
-                entries_start: 0,
-                pending_prompts: Vec::new(),
-                queue_paused: false,
-                run_started_at_unix_ms: None,
-                run_finished_at_unix_ms: None,
-            },
+            });
+        }
+        assert_eq!(
+            state
+                .workspace_conversation(workspace_id)
+                .unwrap()
+                .pending_prompts
+                .len(),
+            2
+        );
+
+        let effects = state.apply(Action::CancelAndClearQueue {
+            workspace_id,
+            thread_id,
         });
+        assert_eq!(effects.len(), 1);
+        assert!(matches!(effects[0], Effect::CancelAgentTurn { .. }));
 
         let conversation = state.workspace_conversation(workspace_id).unwrap();
-        assert_eq!(conversation.run_status, OperationStatus::Running);
-        let entries = conversation
-            .entries
-            .iter()
-            .filter(|e| !matches!(e, ConversationEntry::SystemEvent { .. }))
-            .collect::<Vec<_>>();
-        assert_eq!(entries.len(), 2);
+        assert_eq!(conversation.run_status, OperationStatus::Idle);
+        assert!(conversation.pending_prompts.is_empty());
+        assert!(!conversation.queue_paused);
         assert!(matches!(
-            &entries[0],
-            ConversationEntry::UserEvent {
-                event: crate::UserEvent::Message { text, .. },
+            conversation.entries.last(),
+            Some(ConversationEntry::AgentEvent {
+                event: crate::AgentEvent::TurnCanceled,
                 ..
-            } if text == "Hello"
+            })
         ));
-        assert_eq!(conversation.thread_id.as_deref(), Some("thread_0"));
     }
 
     #[test]
-    fn conversation_loaded_does_not_overwrite_newer_local_entries() {
+    fn retry_last_turn_with_runner_reuses_prompt_with_new_runner() {
         let mut state = AppState::demo();
         let workspace_id = first_non_main_workspace_id(&state);
         let thread_id = default_thread_id();
@@ -5218,468 +9749,469 @@ mod tests {
         state.apply(Action::SendAgentMessage {
             workspace_id,
             thread_id,
-            text: "Hello".to_owned(),
+            text: "Explain the bug".to_owned(),
             attachments: Vec::new(),
-            runner: None,
+            runner: Some(crate::AgentRunnerKind::Codex),
             amp_mode: None,
         });
-        state.apply(Action::AgentEventReceived {
+
+        let effects = state.apply(Action::RetryLastTurnWithRunner {
             workspace_id,
             thread_id,
-            run_id: state
-                .workspace_thread_conversation(workspace_id, thread_id)
-                .expect("missing conversation")
-                .active_run_id
-                .expect("missing active run id"),
-            event: CodexThreadEvent::TurnDuration { duration_ms: 1234 },
+            runner: crate::AgentRunnerKind::Droid,
+            model_id: "claude-opus-4-6".to_owned(),
+            amp_mode: None,
+            cancel_first: false,
         });
+        assert!(effects.is_empty());
+        assert_eq!(
+            state.last_error.as_deref(),
+            Some("Cannot retry while a turn is running")
+        );
 
-        state.apply(Action::ConversationLoaded {
+        let effects = state.apply(Action::RetryLastTurnWithRunner {
             workspace_id,
             thread_id,
-            snapshot: ConversationSnapshot {
-                title: None,
-                thread_id: None,
-                task_status: crate::TaskStatus::Todo,
-                runner: None,
-                agent_model_id: None,
-                thinking_effort: None,
-                amp_mode: None,
-                entries: vec![ConversationEntry::UserEvent {
-                    entry_id: String::new(),
-                    created_at_unix_ms: 1,
-                    event: crate::UserEvent::Message {
-                        text: "Hello".to_owned(),
-                        attachments: Vec::new(),
-                    },
-                }],
-                entries_total: 0,
-                entries_start: 0,
-                pending_prompts: Vec::new(),
-                queue_paused: false,
-                run_started_at_unix_ms: None,
-                run_finished_at_unix_ms: None,
-            },
+            runner: crate::AgentRunnerKind::Droid,
+            model_id: "claude-opus-4-6".to_owned(),
+            amp_mode: None,
+            cancel_first: true,
         });
+        assert!(matches!(
+            effects[0],
+            Effect::CancelAgentTurn { run_id: 1, .. }
+        ));
+        assert!(matches!(
+            &effects[1],
+            Effect::RunAgentTurn {
+                run_id: 2,
+                text,
+                run_config,
+                ..
+            } if text == "Explain the bug"
+                && run_config.runner == crate::AgentRunnerKind::Droid
+                && run_config.model_id == "claude-opus-4-6"
+        ));
 
-        let after = state
-            .workspace_conversation(workspace_id)
-            .unwrap()
+        let conversation = state.workspace_conversation(workspace_id).unwrap();
+        assert_eq!(conversation.run_status, OperationStatus::Running);
+        assert_eq!(
+            conversation
+                .current_run_config
+                .as_ref()
+                .map(|config| config.runner),
+            Some(crate::AgentRunnerKind::Droid)
+        );
+        let retried_at = conversation
             .entries
             .iter()
-            .filter(|e| !matches!(e, ConversationEntry::SystemEvent { .. }))
-            .collect::<Vec<_>>();
-        assert_eq!(after.len(), 2);
+            .position(|entry| {
+                matches!(
+                    entry,
+                    ConversationEntry::SystemEvent {
+                        event: crate::ConversationSystemEvent::TurnRetriedWithRunner {
+                            runner: crate::AgentRunnerKind::Droid,
+                            model_id,
+                        },
+                        ..
+                    } if model_id == "claude-opus-4-6"
+                )
+            })
+            .expect("missing runner retry system event");
         assert!(matches!(
-            &after[0],
+            &conversation.entries[retried_at + 1],
             ConversationEntry::UserEvent {
                 event: crate::UserEvent::Message { text, .. },
                 ..
-            } if text == "Hello"
-        ));
-        assert!(matches!(
-            &after[1],
-            ConversationEntry::AgentEvent {
-                event: crate::AgentEvent::TurnDuration { duration_ms: 1234 },
-                ..
-            }
+            } if text == "Explain the bug"
         ));
     }
 
     #[test]
-    fn conversation_loaded_replaces_entries_when_snapshot_is_newer() {
+    fn send_agent_message_while_running_is_queued() {
         let mut state = AppState::demo();
         let workspace_id = first_non_main_workspace_id(&state);
         let thread_id = default_thread_id();
 
-        state.apply(Action::ConversationLoaded {
+        state.apply(Action::SendAgentMessage {
             workspace_id,
             thread_id,
-            snapshot: ConversationSnapshot {
-                title: None,
-                thread_id: None,
-                task_status: crate::TaskStatus::Todo,
-                runner: None,
-                agent_model_id: None,
-                thinking_effort: None,
-                amp_mode: None,
-                entries: vec![ConversationEntry::UserEvent {
-                    entry_id: String::new(),
-                    created_at_unix_ms: 1,
-                    event: crate::UserEvent::Message {
-                        text: "Hello".to_owned(),
-                        attachments: Vec::new(),
-                    },
-                }],
-                entries_total: 0,
-                entries_start: 0,
-                pending_prompts: Vec::new(),
-                queue_paused: false,
-                run_started_at_unix_ms: None,
-                run_finished_at_unix_ms: None,
-            },
+            text: "First".to_owned(),
+            attachments: Vec::new(),
+            runner: None,
+            amp_mode: None,
         });
-
-        state.apply(Action::ConversationLoaded {
+        let effects = state.apply(Action::SendAgentMessage {
             workspace_id,
             thread_id,
-            snapshot: ConversationSnapshot {
-                title: None,
-                thread_id: None,
-                task_status: crate::TaskStatus::Todo,
-                runner: None,
-                agent_model_id: None,
-                thinking_effort: None,
-                amp_mode: None,
-                entries: vec![
-                    ConversationEntry::UserEvent {
-                        entry_id: String::new(),
-                        created_at_unix_ms: 1,
-                        event: crate::UserEvent::Message {
-                            text: "Hello".to_owned(),
-                            attachments: Vec::new(),
-                        },
-                    },
-                    ConversationEntry::AgentEvent {
-                        entry_id: String::new(),
-                        created_at_unix_ms: 2,
-                        runner: None,
-                        event: crate::AgentEvent::TurnDuration { duration_ms: 1234 },
-                    },
-                ],
-                entries_total: 0,
-                entries_start: 0,
-                pending_prompts: Vec::new(),
-                queue_paused: false,
-                run_started_at_unix_ms: None,
-                run_finished_at_unix_ms: None,
-            },
+            text: "Second".to_owned(),
+            attachments: Vec::new(),
+            runner: None,
+            amp_mode: None,
         });
+        assert!(effects.is_empty());
 
-        let after = &state.workspace_conversation(workspace_id).unwrap().entries;
-        assert!(matches!(
-            &after[..],
-            [
-                ConversationEntry::UserEvent {
-                    event: crate::UserEvent::Message { .. },
-                    ..
-                },
-                ConversationEntry::AgentEvent {
-                    event: crate::AgentEvent::TurnDuration { duration_ms: 1234 },
-                    ..
-                }
-            ]
-        ));
+        let conversation = state.workspace_conversation(workspace_id).unwrap();
+        assert_eq!(
+            conversation
+                .entries
+                .iter()
+                .filter(|e| matches!(e, ConversationEntry::UserEvent { .. }))
+                .count(),
+            1
+        );
+        assert_eq!(conversation.pending_prompts.len(), 1);
+        assert_eq!(conversation.pending_prompts[0].text, "Second");
+        assert_eq!(conversation.pending_prompts[0].id, 1);
+        assert_eq!(conversation.run_status, OperationStatus::Running);
     }
 
     #[test]
-    fn conversation_loaded_restores_queued_prompts_when_local_is_empty() {
+    fn queued_prompts_can_be_reordered_and_edited() {
         let mut state = AppState::demo();
         let workspace_id = first_non_main_workspace_id(&state);
         let thread_id = default_thread_id();
 
-        state.apply(Action::ConversationLoaded {
+        state.apply(Action::SendAgentMessage {
             workspace_id,
             thread_id,
-            snapshot: ConversationSnapshot {
-                title: None,
-                thread_id: None,
-                task_status: crate::TaskStatus::Todo,
-                runner: None,
-                agent_model_id: None,
-                thinking_effort: None,
-                amp_mode: None,
-                entries: Vec::new(),
-                entries_total: 0,
-                entries_start: 0,
-                pending_prompts: vec![QueuedPrompt {
-                    id: 3,
-                    text: "Queued".to_owned(),
-                    attachments: Vec::new(),
-                    run_config: AgentRunConfig {
-                        runner: crate::AgentRunnerKind::Codex,
-                        model_id: "gpt-5.3-codex".to_owned(),
-                        thinking_effort: ThinkingEffort::Minimal,
-                        amp_mode: None,
-                    },
-                }],
-                queue_paused: true,
-                run_started_at_unix_ms: None,
-                run_finished_at_unix_ms: None,
-            },
+            text: "First".to_owned(),
+            attachments: Vec::new(),
+            runner: None,
+            amp_mode: None,
+        });
+        state.apply(Action::SendAgentMessage {
+            workspace_id,
+            thread_id,
+            text: "Second".to_owned(),
+            attachments: Vec::new(),
+            runner: None,
+            amp_mode: None,
+        });
+        state.apply(Action::SendAgentMessage {
+            workspace_id,
+            thread_id,
+            text: "Third".to_owned(),
+            attachments: Vec::new(),
+            runner: None,
+            amp_mode: None,
         });
 
         let conversation = state.workspace_conversation(workspace_id).unwrap();
-        assert!(conversation.queue_paused);
-        assert_eq!(conversation.pending_prompts.len(), 1);
-        assert_eq!(conversation.pending_prompts[0].id, 3);
-        assert_eq!(conversation.next_queued_prompt_id, 4);
-    }
+        assert_eq!(conversation.pending_prompts.len(), 2);
+        assert_eq!(conversation.pending_prompts[0].id, 1);
+        assert_eq!(conversation.pending_prompts[1].id, 2);
 
-    #[test]
-    fn conversation_loaded_applies_persisted_run_config() {
-        let mut state = AppState::demo();
-        let workspace_id = first_non_main_workspace_id(&state);
-        let thread_id = default_thread_id();
+        state.apply(Action::ReorderQueuedPrompt {
+            workspace_id,
+            thread_id,
+            active_id: 2,
+            over_id: 1,
+        });
+
+        let conversation = state.workspace_conversation(workspace_id).unwrap();
+        assert_eq!(conversation.pending_prompts[0].text, "Third");
+        assert_eq!(conversation.pending_prompts[1].text, "Second");
+
+        state.apply(Action::UpdateQueuedPrompt {
+            workspace_id,
+            thread_id,
+            prompt_id: 1,
+            text: "Second updated".to_owned(),
+            attachments: Vec::new(),
+            model_id: default_agent_model_id().to_owned(),
+            thinking_effort: default_thinking_effort(),
+            runner: None,
+            amp_mode: None,
+        });
 
-        state.apply(Action::ConversationLoaded {
+        let conversation = state.workspace_conversation(workspace_id).unwrap();
+        assert_eq!(conversation.pending_prompts[1].text, "Second updated");
+
+        state.apply(Action::RemoveQueuedPrompt {
             workspace_id,
             thread_id,
-            snapshot: ConversationSnapshot {
-                title: None,
-                thread_id: None,
-                task_status: crate::TaskStatus::Todo,
-                runner: None,
-                agent_model_id: Some("gpt-5.3-codex".to_owned()),
-                thinking_effort: Some(ThinkingEffort::High),
-                amp_mode: None,
-                entries: Vec::new(),
-                entries_total: 0,
-                entries_start: 0,
-                pending_prompts: Vec::new(),
-                queue_paused: false,
-                run_started_at_unix_ms: None,
-                run_finished_at_unix_ms: None,
-            },
+            prompt_id: 2,
         });
 
-        let conversation = state
-            .workspace_thread_conversation(workspace_id, thread_id)
-            .expect("missing conversation");
-        assert_eq!(conversation.agent_model_id, "gpt-5.3-codex");
-        assert_eq!(conversation.thinking_effort, ThinkingEffort::High);
+        let conversation = state.workspace_conversation(workspace_id).unwrap();
+        assert_eq!(conversation.pending_prompts.len(), 1);
+        assert_eq!(conversation.pending_prompts[0].id, 1);
     }
 
     #[test]
-    fn conversation_entries_are_bounded_in_memory() {
+    fn removing_queued_prompts_by_id_keeps_remaining_order() {
         let mut state = AppState::demo();
         let workspace_id = first_non_main_workspace_id(&state);
         let thread_id = default_thread_id();
-
-        state.apply(Action::SendAgentMessage {
-            workspace_id,
-            thread_id,
-            text: "Hello".to_owned(),
-            attachments: Vec::new(),
-            runner: None,
-            amp_mode: None,
-        });
-        let run_id = state
-            .workspace_thread_conversation(workspace_id, thread_id)
-            .expect("missing conversation")
-            .active_run_id
-            .expect("missing active run id");
-
-        let total = crate::state::MAX_CONVERSATION_ENTRIES_IN_MEMORY + 100;
-        for idx in 0..total {
-            state.apply(Action::AgentEventReceived {
+        let send = |state: &mut AppState, text: &str| {
+            state.apply(Action::SendAgentMessage {
                 workspace_id,
                 thread_id,
-                run_id,
-                event: CodexThreadEvent::TurnDuration {
-                    duration_ms: idx as u64,
-                },
+                text: text.to_owned(),
+                attachments: Vec::new(),
+                runner: None,
+                amp_mode: None,
+            });
+        };
+        let remove = |state: &mut AppState, prompt_id: u64| {
+            state.apply(Action::RemoveQueuedPrompt {
+                workspace_id,
+                thread_id,
+                prompt_id,
             });
+        };
+        let queue = |state: &AppState| {
+            state
+                .workspace_conversation(workspace_id)
+                .unwrap()
+                .pending_prompts
+                .iter()
+                .map(|p| (p.id, p.text.clone()))
+                .collect::<Vec<_>>()
+        };
+
+        for text in ["Running", "A", "B", "C", "D"] {
+            send(&mut state, text);
         }
+        remove(&mut state, 2);
+        send(&mut state, "E");
+        remove(&mut state, 4);
+        remove(&mut state, 1);
+        send(&mut state, "F");
+        remove(&mut state, 2);
 
-        let conversation = state.workspace_conversation(workspace_id).unwrap();
         assert_eq!(
-            conversation.entries.len(),
-            crate::state::MAX_CONVERSATION_ENTRIES_IN_MEMORY
+            queue(&state),
+            vec![
+                (3, "C".to_owned()),
+                (5, "E".to_owned()),
+                (6, "F".to_owned()),
+            ]
+        );
+
+        remove(&mut state, 5);
+        assert_eq!(
+            queue(&state),
+            vec![(3, "C".to_owned()), (6, "F".to_owned())]
         );
-        assert_eq!(conversation.entries_start, 102);
-        assert_eq!(conversation.entries_total, (total + 2) as u64);
     }
 
     #[test]
-    fn send_agent_message_sets_running_and_emits_effect() {
+    fn queued_prompt_runner_and_amp_mode_can_be_edited_in_place() {
         let mut state = AppState::demo();
         let workspace_id = first_non_main_workspace_id(&state);
         let thread_id = default_thread_id();
 
-        let effects = state.apply(Action::SendAgentMessage {
+        for text in ["First", "Second", "Third"] {
+            state.apply(Action::SendAgentMessage {
+                workspace_id,
+                thread_id,
+                text: text.to_owned(),
+                attachments: Vec::new(),
+                runner: None,
+                amp_mode: None,
+            });
+        }
+        let update = |runner, amp_mode: Option<&str>| Action::UpdateQueuedPrompt {
             workspace_id,
             thread_id,
-            text: "Hello".to_owned(),
+            prompt_id: 1,
+            text: "Second".to_owned(),
             attachments: Vec::new(),
-            runner: None,
-            amp_mode: None,
-        });
-        let run_effect = effects
-            .iter()
-            .find(|e| matches!(e, Effect::RunAgentTurn { .. }))
-            .expect("missing RunAgentTurn effect");
-        assert!(matches!(
-            run_effect,
-            Effect::RunAgentTurn {
-                workspace_id: wid,
-                thread_id: tid,
-                text,
-                run_config,
-                ..
-            } if *wid == workspace_id
-                && *tid == thread_id
-                && text == "Hello"
-                && run_config.model_id == default_agent_model_id()
-                && run_config.thinking_effort == default_thinking_effort()
-        ));
+            model_id: default_agent_model_id().to_owned(),
+            thinking_effort: default_thinking_effort(),
+            runner,
+            amp_mode: amp_mode.map(str::to_owned),
+        };
 
+        state.apply(update(Some(crate::AgentRunnerKind::Amp), Some("rush")));
         let conversation = state.workspace_conversation(workspace_id).unwrap();
-        assert_eq!(conversation.run_status, OperationStatus::Running);
-        let user_messages = conversation
-            .entries
-            .iter()
-            .filter_map(|e| match e {
-                ConversationEntry::UserEvent {
-                    event: crate::UserEvent::Message { text, .. },
-                    ..
-                } => Some(text.as_str()),
-                _ => None,
-            })
-            .collect::<Vec<_>>();
-        assert_eq!(user_messages, vec!["Hello"]);
+        assert_eq!(conversation.pending_prompts[0].id, 1);
+        let run_config = &conversation.pending_prompts[0].run_config;
+        assert_eq!(run_config.runner, crate::AgentRunnerKind::Amp);
+        assert_eq!(run_config.amp_mode.as_deref(), Some("rush"));
+
+        state.apply(update(None, None));
+        let run_config = &state
+            .workspace_conversation(workspace_id)
+            .unwrap()
+            .pending_prompts[0]
+            .run_config;
+        assert_eq!(run_config.runner, crate::AgentRunnerKind::Amp);
+        assert_eq!(run_config.amp_mode.as_deref(), Some("rush"));
+
+        state.apply(update(Some(crate::AgentRunnerKind::Claude), Some("rush")));
+        let conversation = state.workspace_conversation(workspace_id).unwrap();
+        assert_eq!(conversation.pending_prompts[0].id, 1);
+        assert_eq!(conversation.pending_prompts[1].text, "Third");
+        let run_config = &conversation.pending_prompts[0].run_config;
+        assert_eq!(run_config.runner, crate::AgentRunnerKind::Claude);
+        assert_eq!(run_config.amp_mode, None);
     }
 
     #[test]
-    fn task_status_canceled_cancels_running_turn_and_emits_effect() {
+    fn claude_options_only_reach_claude_turns() {
         let mut state = AppState::demo();
         let workspace_id = first_non_main_workspace_id(&state);
         let thread_id = default_thread_id();
 
-        state.apply(Action::SendAgentMessage {
+        let effects = state.apply(Action::ChatClaudeOptionsChanged {
             workspace_id,
             thread_id,
-            text: "Hello".to_owned(),
-            attachments: Vec::new(),
-            runner: None,
-            amp_mode: None,
+            max_output_tokens: Some(4096),
+            system_prompt: Some("  Be terse.\n".to_owned()),
         });
-        let run_id = state
-            .workspace_thread_conversation(workspace_id, thread_id)
-            .expect("missing conversation")
-            .active_run_id
-            .expect("missing active run id");
+        assert!(matches!(
+            effects.as_slice(),
+            [Effect::StoreConversationRunConfig { .. }]
+        ));
+        let conversation = state.workspace_conversation(workspace_id).unwrap();
+        assert_eq!(conversation.claude_max_output_tokens, Some(4096));
+        assert_eq!(
+            conversation.claude_system_prompt.as_deref(),
+            Some("Be terse.")
+        );
 
-        let effects = state.apply(Action::TaskStatusSet {
+        let send = |runner| Action::SendAgentMessage {
             workspace_id,
             thread_id,
-            task_status: crate::TaskStatus::Canceled,
-        });
+            text: "Go".to_owned(),
+            attachments: Vec::new(),
+            runner: Some(runner),
+            amp_mode: None,
+        };
+        let effects = state.apply(send(crate::AgentRunnerKind::Codex));
+        let run_config = effects
+            .iter()
+            .find_map(|effect| match effect {
+                Effect::RunAgentTurn { run_config, .. } => Some(run_config),
+                _ => None,
+            })
+            .expect("missing run effect");
+        assert_eq!(run_config.claude_max_output_tokens, None);
+        assert_eq!(run_config.claude_system_prompt, None);
 
-        assert!(
-            effects.iter().any(|e| matches!(
-                e,
-                Effect::CancelAgentTurn { workspace_id: wid, thread_id: tid, run_id: rid }
-                    if *wid == workspace_id && *tid == thread_id && *rid == run_id
-            )),
-            "expected CancelAgentTurn effect"
+        state.apply(send(crate::AgentRunnerKind::Claude));
+        let run_config = &state
+            .workspace_conversation(workspace_id)
+            .unwrap()
+            .pending_prompts[0]
+            .run_config;
+        assert_eq!(run_config.claude_max_output_tokens, Some(4096));
+        assert_eq!(
+            run_config.claude_system_prompt.as_deref(),
+            Some("Be terse.")
         );
 
-        let conversation = state
-            .workspace_thread_conversation(workspace_id, thread_id)
-            .expect("missing conversation");
-        assert_eq!(conversation.task_status, crate::TaskStatus::Canceled);
-        assert_eq!(conversation.run_status, OperationStatus::Idle);
-        assert_eq!(conversation.active_run_id, None);
-        assert!(conversation.queue_paused);
-        assert!(conversation.run_finished_at_unix_ms.is_some());
-        assert!(conversation.entries.iter().any(|e| {
-            matches!(
-                e,
-                ConversationEntry::AgentEvent {
-                    event: crate::AgentEvent::TurnCanceled,
-                    ..
-                }
-            )
-        }));
+        state.apply(Action::ChatClaudeOptionsChanged {
+            workspace_id,
+            thread_id,
+            max_output_tokens: Some(0),
+            system_prompt: Some("   ".to_owned()),
+        });
+        let conversation = state.workspace_conversation(workspace_id).unwrap();
+        assert_eq!(conversation.claude_max_output_tokens, None);
+        assert_eq!(conversation.claude_system_prompt, None);
     }
 
     #[test]
-    fn task_status_done_cancels_running_turn_and_triggers_auto_archive_check() {
+    fn auto_commit_only_follows_successful_turns() {
         let mut state = AppState::demo();
         let workspace_id = first_non_main_workspace_id(&state);
         let thread_id = default_thread_id();
 
-        state.apply(Action::SendAgentMessage {
+        let effects = state.apply(Action::ChatAutoCommitChanged {
             workspace_id,
             thread_id,
-            text: "Hello".to_owned(),
-            attachments: Vec::new(),
-            runner: None,
-            amp_mode: None,
+            enabled: true,
         });
-        let run_id = state
-            .workspace_thread_conversation(workspace_id, thread_id)
-            .expect("missing conversation")
-            .active_run_id
-            .expect("missing active run id");
+        assert!(matches!(
+            effects.as_slice(),
+            [Effect::StoreConversationRunConfig { .. }]
+        ));
+        assert!(
+            state
+                .workspace_conversation(workspace_id)
+                .unwrap()
+                .auto_commit
+        );
 
-        let effects = state.apply(Action::TaskStatusSet {
+        let start_turn = |state: &mut AppState| {
+            state.apply(Action::SendAgentMessage {
+                workspace_id,
+                thread_id,
+                text: "Go".to_owned(),
+                attachments: Vec::new(),
+                runner: None,
+                amp_mode: None,
+            });
+            state
+                .workspace_thread_conversation(workspace_id, thread_id)
+                .and_then(|c| c.active_run_id)
+                .expect("missing active run id")
+        };
+        let is_auto_commit = |effect: &Effect| matches!(effect, Effect::AutoCommitTurn { .. });
+
+        let run_id = start_turn(&mut state);
+        let effects = state.apply(Action::AgentEventReceived {
             workspace_id,
             thread_id,
-            task_status: crate::TaskStatus::Done,
+            run_id,
+            event: CodexThreadEvent::TurnFailed {
+                error: crate::CodexThreadError {
+                    message: "boom".to_owned(),
+                    code: None,
+                },
+            },
         });
+        assert!(!effects.iter().any(is_auto_commit));
 
-        assert!(
-            effects.iter().any(|e| matches!(
-                e,
-                Effect::CancelAgentTurn { workspace_id: wid, thread_id: tid, run_id: rid }
-                    if *wid == workspace_id && *tid == thread_id && *rid == run_id
-            )),
-            "expected CancelAgentTurn effect"
-        );
-        assert!(
-            effects.iter().any(|e| matches!(
-                e,
-                Effect::MaybeAutoArchiveWorkspace { workspace_id: wid } if *wid == workspace_id
-            )),
-            "expected MaybeAutoArchiveWorkspace effect"
-        );
-
-        let conversation = state
-            .workspace_thread_conversation(workspace_id, thread_id)
-            .expect("missing conversation");
-        assert_eq!(conversation.task_status, crate::TaskStatus::Done);
-        assert_eq!(conversation.run_status, OperationStatus::Idle);
-        assert_eq!(conversation.active_run_id, None);
-        assert!(conversation.queue_paused);
-    }
-
-    #[test]
-    fn send_agent_message_is_blocked_for_archived_tasks() {
-        let mut state = AppState::demo();
-        let workspace_id = first_non_main_workspace_id(&state);
-        let thread_id = default_thread_id();
+        start_turn(&mut state);
+        let effects = state.apply(Action::CancelAgentTurn {
+            workspace_id,
+            thread_id,
+        });
+        assert!(!effects.iter().any(is_auto_commit));
 
-        state.apply(Action::SendAgentMessage {
+        let run_id = start_turn(&mut state);
+        let effects = state.apply(Action::AgentEventReceived {
             workspace_id,
             thread_id,
-            text: "Hello".to_owned(),
-            attachments: Vec::new(),
-            runner: None,
-            amp_mode: None,
+            run_id,
+            event: CodexThreadEvent::TurnCompleted {
+                usage: CodexUsage::default(),
+            },
         });
+        let title = state
+            .workspace_conversation(workspace_id)
+            .unwrap()
+            .title
+            .clone();
+        assert!(matches!(
+            effects.first(),
+            Some(Effect::AutoCommitTurn { message, .. }) if *message == format!("{title} (turn 3)")
+        ));
 
-        state.apply(Action::TaskStatusSet {
+        state.apply(Action::ChatAutoCommitChanged {
             workspace_id,
             thread_id,
-            task_status: crate::TaskStatus::Done,
+            enabled: false,
         });
-
-        let effects = state.apply(Action::SendAgentMessage {
+        let run_id = start_turn(&mut state);
+        let effects = state.apply(Action::AgentEventReceived {
             workspace_id,
             thread_id,
-            text: "Should be blocked".to_owned(),
-            attachments: Vec::new(),
-            runner: None,
-            amp_mode: None,
+            run_id,
+            event: CodexThreadEvent::TurnCompleted {
+                usage: CodexUsage::default(),
+            },
         });
-        assert!(effects.is_empty());
-        assert_eq!(state.last_error.as_deref(), Some("Task is archived"));
+        assert!(!effects.iter().any(is_auto_commit));
     }
 
     #[test]
-    fn agent_item_completed_is_idempotent() {
+    fn completed_turn_auto_sends_next_queued_prompt() {
         let mut state = AppState::demo();
         let workspace_id = first_non_main_workspace_id(&state);
         let thread_id = default_thread_id();
@@ -5687,54 +10219,72 @@ mod tests {
         state.apply(Action::SendAgentMessage {
             workspace_id,
             thread_id,
-            text: "Hello".to_owned(),
+            text: "First".to_owned(),
+            attachments: Vec::new(),
+            runner: None,
+            amp_mode: None,
+        });
+        state.apply(Action::SendAgentMessage {
+            workspace_id,
+            thread_id,
+            text: "Second".to_owned(),
             attachments: Vec::new(),
             runner: None,
             amp_mode: None,
         });
+
         let run_id = state
             .workspace_thread_conversation(workspace_id, thread_id)
             .expect("missing conversation")
             .active_run_id
             .expect("missing active run id");
-
-        let item = CodexThreadItem::AgentMessage {
-            id: "item_0".to_owned(),
-            text: "Hi".to_owned(),
-        };
-
-        state.apply(Action::AgentEventReceived {
-            workspace_id,
-            thread_id,
-            run_id,
-            event: CodexThreadEvent::ItemCompleted { item: item.clone() },
-        });
-        state.apply(Action::AgentEventReceived {
+        let effects = state.apply(Action::AgentEventReceived {
             workspace_id,
             thread_id,
             run_id,
-            event: CodexThreadEvent::ItemCompleted { item },
+            event: CodexThreadEvent::TurnCompleted {
+                usage: CodexUsage {
+                    input_tokens: 0,
+                    cached_input_tokens: 0,
+                    output_tokens: 0,
+                },
+            },
         });
+        assert_eq!(effects.len(), 1);
+        assert!(matches!(
+            &effects[0],
+            Effect::RunAgentTurn {
+                workspace_id: wid,
+                thread_id: tid,
+                text,
+                run_config,
+                ..
+            } if *wid == workspace_id
+                && *tid == thread_id
+                && text == "Second"
+                && run_config.model_id == default_agent_model_id()
+                && run_config.thinking_effort == default_thinking_effort()
+        ));
 
         let conversation = state.workspace_conversation(workspace_id).unwrap();
-        let completed_items = conversation
+        assert_eq!(conversation.run_status, OperationStatus::Running);
+        assert!(conversation.pending_prompts.is_empty());
+        let user_messages = conversation
             .entries
             .iter()
-            .filter(|e| {
-                matches!(
-                    e,
-                    ConversationEntry::AgentEvent {
-                        event: crate::AgentEvent::Message { id, .. },
-                        ..
-                    } if id == "item_0"
-                )
+            .filter_map(|e| match e {
+                ConversationEntry::UserEvent {
+                    event: crate::UserEvent::Message { text, .. },
+                    ..
+                } => Some(text.as_str()),
+                _ => None,
             })
-            .count();
-        assert_eq!(completed_items, 1);
+            .collect::<Vec<_>>();
+        assert_eq!(user_messages, vec!["First", "Second"]);
     }
 
     #[test]
-    fn agent_item_completed_is_idempotent_even_if_not_last_entry() {
+    fn tool_approval_moves_turn_to_awaiting_until_resolved() {
         let mut state = AppState::demo();
         let workspace_id = first_non_main_workspace_id(&state);
         let thread_id = default_thread_id();
@@ -5742,94 +10292,129 @@ mod tests {
         state.apply(Action::SendAgentMessage {
             workspace_id,
             thread_id,
-            text: "Hello".to_owned(),
+            text: "Deploy".to_owned(),
             attachments: Vec::new(),
             runner: None,
             amp_mode: None,
         });
         let run_id = state
             .workspace_thread_conversation(workspace_id, thread_id)
-            .expect("missing conversation")
-            .active_run_id
+            .and_then(|conversation| conversation.active_run_id)
             .expect("missing active run id");
-
-        let item = CodexThreadItem::AgentMessage {
-            id: "item_0".to_owned(),
-            text: "Hi".to_owned(),
+        let tool_call = |status| CodexThreadItem::McpToolCall {
+            id: "call-1".to_owned(),
+            server: "deploy".to_owned(),
+            tool: "release".to_owned(),
+            arguments: serde_json::Value::Null,
+            result: None,
+            error: None,
+            status,
+        };
+        let turn_status = |state: &AppState| {
+            state
+                .workspace_thread_conversation(workspace_id, thread_id)
+                .expect("missing conversation")
+                .turn_status()
         };
 
         state.apply(Action::AgentEventReceived {
             workspace_id,
             thread_id,
             run_id,
-            event: CodexThreadEvent::ItemCompleted { item: item.clone() },
+            event: CodexThreadEvent::ItemStarted {
+                item: tool_call(crate::CodexMcpToolCallStatus::AwaitingApproval),
+            },
         });
-        state.apply(Action::AgentEventReceived {
+        assert_eq!(turn_status(&state), crate::TurnStatus::Awaiting);
+
+        assert!(
+            state
+                .apply(Action::RespondToToolApproval {
+                    workspace_id,
+                    thread_id,
+                    call_id: "other".to_owned(),
+                    approved: true,
+                })
+                .is_empty()
+        );
+        let effects = state.apply(Action::RespondToToolApproval {
             workspace_id,
             thread_id,
-            run_id,
-            event: CodexThreadEvent::TurnDuration { duration_ms: 1000 },
+            call_id: "call-1".to_owned(),
+            approved: true,
         });
-        state.apply(Action::AgentEventReceived {
+        assert!(matches!(
+            effects.as_slice(),
+            [Effect::RespondToToolApproval { run_id: rid, call_id, approved: true, .. }]
+                if *rid == run_id && call_id == "call-1"
+        ));
+        assert_eq!(turn_status(&state), crate::TurnStatus::Running);
+
+        state.apply(Action::ToolApprovalDeliveryFailed {
             workspace_id,
             thread_id,
-            run_id,
-            event: CodexThreadEvent::ItemCompleted { item },
+            run_id: run_id + 1,
+            call_id: "call-1".to_owned(),
+            message: "stale run".to_owned(),
         });
+        assert_eq!(turn_status(&state), crate::TurnStatus::Running);
 
-        let conversation = state.workspace_conversation(workspace_id).unwrap();
-        let completed_items = conversation
-            .entries
-            .iter()
-            .filter(|e| {
-                matches!(
-                    e,
-                    ConversationEntry::AgentEvent {
-                        event: crate::AgentEvent::Message { id, .. },
-                        ..
-                    } if id == "item_0"
-                )
-            })
-            .count();
-        assert_eq!(completed_items, 1);
-    }
-
-    #[test]
-    fn cancel_agent_turn_sets_idle_and_emits_effect() {
-        let mut state = AppState::demo();
-        let workspace_id = first_non_main_workspace_id(&state);
-        let thread_id = default_thread_id();
-
-        state.apply(Action::SendAgentMessage {
+        state.apply(Action::ToolApprovalDeliveryFailed {
             workspace_id,
             thread_id,
-            text: "Hello".to_owned(),
-            attachments: Vec::new(),
-            runner: None,
-            amp_mode: None,
+            run_id,
+            call_id: "call-1".to_owned(),
+            message: "runner rejected approval".to_owned(),
         });
-
-        let effects = state.apply(Action::CancelAgentTurn {
+        assert_eq!(turn_status(&state), crate::TurnStatus::Awaiting);
+        assert_eq!(
+            state.error_log.back().map(|entry| entry.message.as_str()),
+            Some("runner rejected approval")
+        );
+        let effects = state.apply(Action::RespondToToolApproval {
             workspace_id,
             thread_id,
+            call_id: "call-1".to_owned(),
+            approved: true,
         });
         assert_eq!(effects.len(), 1);
-        assert!(matches!(effects[0], Effect::CancelAgentTurn { .. }));
+        assert_eq!(turn_status(&state), crate::TurnStatus::Running);
 
-        let conversation = state.workspace_conversation(workspace_id).unwrap();
-        assert_eq!(conversation.run_status, OperationStatus::Idle);
-        assert!(matches!(
-            conversation.entries.last(),
-            Some(ConversationEntry::AgentEvent {
-                event: crate::AgentEvent::TurnCanceled,
-                ..
-            })
-        ));
+        state.apply(Action::AgentEventReceived {
+            workspace_id,
+            thread_id,
+            run_id,
+            event: CodexThreadEvent::ItemUpdated {
+                item: tool_call(crate::CodexMcpToolCallStatus::AwaitingApproval),
+            },
+        });
+        assert_eq!(turn_status(&state), crate::TurnStatus::Awaiting);
+        state.apply(Action::AgentEventReceived {
+            workspace_id,
+            thread_id,
+            run_id,
+            event: CodexThreadEvent::TurnCompleted {
+                usage: CodexUsage {
+                    input_tokens: 0,
+                    cached_input_tokens: 0,
+                    output_tokens: 0,
+                },
+            },
+        });
+        assert_eq!(turn_status(&state), crate::TurnStatus::Idle);
+        assert!(
+            state
+                .workspace_thread_conversation(workspace_id, thread_id)
+                .expect("missing conversation")
+                .pending_tool_approvals
+                .is_empty()
+        );
     }
 
     #[test]
-    fn send_agent_message_while_running_is_queued() {
+    fn transient_turn_failures_auto_retry_up_to_budget_then_pause() {
         let mut state = AppState::demo();
+        state.apply(Action::AgentAutoRetryOnFailureChanged { retries: 2 });
         let workspace_id = first_non_main_workspace_id(&state);
         let thread_id = default_thread_id();
 
@@ -5841,7 +10426,7 @@ mod tests {
             runner: None,
             amp_mode: None,
         });
-        let effects = state.apply(Action::SendAgentMessage {
+        state.apply(Action::SendAgentMessage {
             workspace_id,
             thread_id,
             text: "Second".to_owned(),
@@ -5849,96 +10434,271 @@ mod tests {
             runner: None,
             amp_mode: None,
         });
-        assert!(effects.is_empty());
 
+        let fail_active_run = |state: &mut AppState, message: &str| {
+            let run_id = state
+                .workspace_thread_conversation(workspace_id, thread_id)
+                .expect("missing conversation")
+                .active_run_id
+                .expect("missing active run id");
+            state.apply(Action::AgentEventReceived {
+                workspace_id,
+                thread_id,
+                run_id,
+                event: CodexThreadEvent::TurnFailed {
+                    error: CodexThreadError {
+                        message: message.to_owned(),
+                        code: None,
+                    },
+                },
+            })
+        };
+
+        for expected_attempt in 1..=2u8 {
+            let effects = fail_active_run(&mut state, "stream disconnected: connection reset");
+            let [
+                Effect::ScheduleAgentTurnRetry {
+                    attempt,
+                    delay_ms,
+                    run_config,
+                    ..
+                },
+            ] = effects.as_slice()
+            else {
+                panic!("expected a scheduled retry, got {effects:?}");
+            };
+            assert_eq!(*attempt, expected_attempt);
+            assert_eq!(
+                *delay_ms,
+                crate::state::AGENT_AUTO_RETRY_BASE_DELAY_MS << (expected_attempt - 1)
+            );
+            let conversation = state.workspace_conversation(workspace_id).unwrap();
+            assert_eq!(conversation.run_status, OperationStatus::Idle);
+            assert!(!conversation.queue_paused);
+
+            let due = Action::AgentTurnRetryDue {
+                workspace_id,
+                thread_id,
+                attempt: *attempt,
+                run_config: run_config.clone(),
+            };
+            let stale = Action::AgentTurnRetryDue {
+                workspace_id,
+                thread_id,
+                attempt: attempt.wrapping_sub(1),
+                run_config: run_config.clone(),
+            };
+            assert!(state.apply(stale).is_empty());
+            let effects = state.apply(due);
+            assert!(matches!(
+                effects.as_slice(),
+                [Effect::RunAgentTurn { text, retry: true, .. }] if text == "First"
+            ));
+            let conversation = state.workspace_conversation(workspace_id).unwrap();
+            assert_eq!(conversation.run_status, OperationStatus::Running);
+            assert_eq!(conversation.pending_prompts.len(), 1);
+        }
+        let user_messages = state
+            .workspace_conversation(workspace_id)
+            .unwrap()
+            .entries
+            .iter()
+            .filter(|entry| {
+                matches!(
+                    entry,
+                    ConversationEntry::UserEvent {
+                        event: crate::UserEvent::Message { text, .. },
+                        ..
+                    } if text == "First"
+                )
+            })
+            .count();
+        assert_eq!(user_messages, 1);
+
+        let effects = fail_active_run(&mut state, "stream disconnected: connection reset");
+        assert!(
+            !effects
+                .iter()
+                .any(|effect| matches!(effect, Effect::RunAgentTurn { .. }))
+        );
         let conversation = state.workspace_conversation(workspace_id).unwrap();
-        assert_eq!(
-            conversation
-                .entries
+        assert_eq!(conversation.run_status, OperationStatus::Idle);
+        assert!(conversation.queue_paused);
+
+        state.apply(Action::ResumeQueuedPrompts {
+            workspace_id,
+            thread_id,
+        });
+        let effects = fail_active_run(&mut state, "invalid api key");
+        assert!(
+            !effects
                 .iter()
-                .filter(|e| matches!(e, ConversationEntry::UserEvent { .. }))
-                .count(),
-            1
+                .any(|effect| matches!(effect, Effect::RunAgentTurn { .. }))
+        );
+        assert!(
+            state
+                .workspace_conversation(workspace_id)
+                .unwrap()
+                .queue_paused
         );
-        assert_eq!(conversation.pending_prompts.len(), 1);
-        assert_eq!(conversation.pending_prompts[0].text, "Second");
-        assert_eq!(conversation.pending_prompts[0].id, 1);
-        assert_eq!(conversation.run_status, OperationStatus::Running);
     }
 
     #[test]
-    fn queued_prompts_can_be_reordered_and_edited() {
+    fn oversized_command_output_keeps_head_and_tail_and_is_flagged() {
         let mut state = AppState::demo();
         let workspace_id = first_non_main_workspace_id(&state);
         let thread_id = default_thread_id();
+        assert_eq!(state.agent_command_output_max_bytes(), 256 * 1024);
 
         state.apply(Action::SendAgentMessage {
             workspace_id,
             thread_id,
-            text: "First".to_owned(),
-            attachments: Vec::new(),
-            runner: None,
-            amp_mode: None,
-        });
-        state.apply(Action::SendAgentMessage {
-            workspace_id,
-            thread_id,
-            text: "Second".to_owned(),
-            attachments: Vec::new(),
-            runner: None,
-            amp_mode: None,
-        });
-        state.apply(Action::SendAgentMessage {
-            workspace_id,
-            thread_id,
-            text: "Third".to_owned(),
+            text: "Build everything".to_owned(),
             attachments: Vec::new(),
             runner: None,
             amp_mode: None,
         });
+        let run_id = state
+            .workspace_thread_conversation(workspace_id, thread_id)
+            .expect("missing conversation")
+            .active_run_id
+            .expect("missing active run id");
 
-        let conversation = state.workspace_conversation(workspace_id).unwrap();
-        assert_eq!(conversation.pending_prompts.len(), 2);
-        assert_eq!(conversation.pending_prompts[0].id, 1);
-        assert_eq!(conversation.pending_prompts[1].id, 2);
+        let head = "HEAD-START\n";
+        let tail = "\nTAIL-END";
+        let output = format!("{head}{}{tail}", "é".repeat(512 * 1024));
+        for (id, aggregated_output) in [
+            ("cmd_big", output.clone()),
+            ("cmd_small", "ok\n".to_owned()),
+        ] {
+            state.apply(Action::AgentEventReceived {
+                workspace_id,
+                thread_id,
+                run_id,
+                event: CodexThreadEvent::ItemCompleted {
+                    item: CodexThreadItem::CommandExecution {
+                        id: id.to_owned(),
+                        command: "cargo build -vv".to_owned(),
+                        aggregated_output,
+                        exit_code: Some(0),
+                        status: CodexCommandExecutionStatus::Completed,
+                        truncated: false,
+                    },
+                },
+            });
+        }
 
-        state.apply(Action::ReorderQueuedPrompt {
-            workspace_id,
-            thread_id,
-            active_id: 2,
-            over_id: 1,
-        });
+        let conversation = state
+            .workspace_thread_conversation(workspace_id, thread_id)
+            .expect("missing conversation");
+        let command = |wanted: &str| {
+            conversation
+                .entries
+                .iter()
+                .find_map(|entry| match entry {
+                    ConversationEntry::AgentEvent {
+                        event: crate::AgentEvent::Item { item },
+                        ..
+                    } => match item.as_ref() {
+                        CodexThreadItem::CommandExecution {
+                            id,
+                            aggregated_output,
+                            truncated,
+                            ..
+                        } if id == wanted => Some((aggregated_output.clone(), *truncated)),
+                        _ => None,
+                    },
+                    _ => None,
+                })
+                .expect("missing command entry")
+        };
 
-        let conversation = state.workspace_conversation(workspace_id).unwrap();
-        assert_eq!(conversation.pending_prompts[0].text, "Third");
-        assert_eq!(conversation.pending_prompts[1].text, "Second");
+        let (big, big_truncated) = command("cmd_big");
+        assert!(big_truncated);
+        assert!(big.starts_with(head));
+        assert!(big.ends_with(tail));
+        assert!(big.contains("bytes truncated]"));
+        assert!(big.len() < output.len());
+        assert!(
+            big.len() <= 256 * 1024 + 64,
+            "capped output is {} bytes",
+            big.len()
+        );
 
-        state.apply(Action::UpdateQueuedPrompt {
+        assert_eq!(command("cmd_small"), ("ok\n".to_owned(), false));
+    }
+
+    #[test]
+    fn carriage_return_progress_collapses_to_final_rendered_lines() {
+        let mut state = AppState::demo();
+        let workspace_id = first_non_main_workspace_id(&state);
+        let thread_id = default_thread_id();
+
+        state.apply(Action::SendAgentMessage {
             workspace_id,
             thread_id,
-            prompt_id: 1,
-            text: "Second updated".to_owned(),
+            text: "Download the toolchain".to_owned(),
             attachments: Vec::new(),
-            model_id: default_agent_model_id().to_owned(),
-            thinking_effort: default_thinking_effort(),
+            runner: None,
+            amp_mode: None,
         });
+        let run_id = state
+            .workspace_thread_conversation(workspace_id, thread_id)
+            .expect("missing conversation")
+            .active_run_id
+            .expect("missing active run id");
 
-        let conversation = state.workspace_conversation(workspace_id).unwrap();
-        assert_eq!(conversation.pending_prompts[1].text, "Second updated");
-
-        state.apply(Action::RemoveQueuedPrompt {
+        let mut output = "Fetching index\r\n".to_owned();
+        for pct in 0..=100 {
+            output.push_str(&format!("\rDownloading [{pct:>3}%]"));
+        }
+        output.push_str("\nstep 1/3\rstep 2/3\rstep 3/3\nok 12345\rdone\n");
+        state.apply(Action::AgentEventReceived {
             workspace_id,
             thread_id,
-            prompt_id: 2,
+            run_id,
+            event: CodexThreadEvent::ItemCompleted {
+                item: CodexThreadItem::CommandExecution {
+                    id: "cmd_progress".to_owned(),
+                    command: "./install.sh".to_owned(),
+                    aggregated_output: output,
+                    exit_code: Some(0),
+                    status: CodexCommandExecutionStatus::Completed,
+                    truncated: false,
+                },
+            },
         });
 
-        let conversation = state.workspace_conversation(workspace_id).unwrap();
-        assert_eq!(conversation.pending_prompts.len(), 1);
-        assert_eq!(conversation.pending_prompts[0].id, 1);
+        let conversation = state
+            .workspace_thread_conversation(workspace_id, thread_id)
+            .expect("missing conversation");
+        let (aggregated_output, truncated) = conversation
+            .entries
+            .iter()
+            .find_map(|entry| match entry {
+                ConversationEntry::AgentEvent {
+                    event: crate::AgentEvent::Item { item },
+                    ..
+                } => match item.as_ref() {
+                    CodexThreadItem::CommandExecution {
+                        aggregated_output,
+                        truncated,
+                        ..
+                    } => Some((aggregated_output.clone(), *truncated)),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .expect("missing command entry");
+        assert_eq!(
+            aggregated_output,
+            "Fetching index\nDownloading [100%]\nstep 3/3\ndone2345\n"
+        );
+        assert!(!truncated);
     }
 
     #[test]
-    fn completed_turn_auto_sends_next_queued_prompt() {
+    fn completed_turn_lists_commands_that_exited_non_zero() {
         let mut state = AppState::demo();
         let workspace_id = first_non_main_workspace_id(&state);
         let thread_id = default_thread_id();
@@ -5946,26 +10706,35 @@ mod tests {
         state.apply(Action::SendAgentMessage {
             workspace_id,
             thread_id,
-            text: "First".to_owned(),
-            attachments: Vec::new(),
-            runner: None,
-            amp_mode: None,
-        });
-        state.apply(Action::SendAgentMessage {
-            workspace_id,
-            thread_id,
-            text: "Second".to_owned(),
+            text: "Run the tests".to_owned(),
             attachments: Vec::new(),
             runner: None,
             amp_mode: None,
         });
-
         let run_id = state
             .workspace_thread_conversation(workspace_id, thread_id)
             .expect("missing conversation")
             .active_run_id
             .expect("missing active run id");
-        let effects = state.apply(Action::AgentEventReceived {
+
+        for (id, exit_code) in [("cmd-ok", 0), ("cmd-failed", 101)] {
+            state.apply(Action::AgentEventReceived {
+                workspace_id,
+                thread_id,
+                run_id,
+                event: CodexThreadEvent::ItemCompleted {
+                    item: CodexThreadItem::CommandExecution {
+                        id: id.to_owned(),
+                        command: "cargo test".to_owned(),
+                        aggregated_output: String::new(),
+                        exit_code: Some(exit_code),
+                        status: CodexCommandExecutionStatus::Completed,
+                        truncated: false,
+                    },
+                },
+            });
+        }
+        state.apply(Action::AgentEventReceived {
             workspace_id,
             thread_id,
             run_id,
@@ -5977,37 +10746,16 @@ mod tests {
                 },
             },
         });
-        assert_eq!(effects.len(), 1);
-        assert!(matches!(
-            &effects[0],
-            Effect::RunAgentTurn {
-                workspace_id: wid,
-                thread_id: tid,
-                text,
-                run_config,
-                ..
-            } if *wid == workspace_id
-                && *tid == thread_id
-                && text == "Second"
-                && run_config.model_id == default_agent_model_id()
-                && run_config.thinking_effort == default_thinking_effort()
-        ));
 
-        let conversation = state.workspace_conversation(workspace_id).unwrap();
-        assert_eq!(conversation.run_status, OperationStatus::Running);
-        assert!(conversation.pending_prompts.is_empty());
-        let user_messages = conversation
-            .entries
-            .iter()
-            .filter_map(|e| match e {
-                ConversationEntry::UserEvent {
-                    event: crate::UserEvent::Message { text, .. },
-                    ..
-                } => Some(text.as_str()),
-                _ => None,
-            })
-            .collect::<Vec<_>>();
-        assert_eq!(user_messages, vec!["First", "Second"]);
+        let conversation = state
+            .workspace_thread_conversation(workspace_id, thread_id)
+            .expect("missing conversation");
+        assert_eq!(conversation.run_status, OperationStatus::Idle);
+        assert!(conversation.had_failed_commands());
+        assert_eq!(
+            conversation.last_turn_failed_command_ids,
+            vec!["cmd-failed".to_owned()]
+        );
     }
 
     #[test]
@@ -6045,6 +10793,7 @@ mod tests {
             event: CodexThreadEvent::TurnFailed {
                 error: CodexThreadError {
                     message: "boom".to_owned(),
+                    code: None,
                 },
             },
         });
@@ -6266,4 +11015,175 @@ mod tests {
         assert!(effects.is_empty());
         assert_eq!(state.last_error.as_deref(), Some("Workspace not found"));
     }
+
+    #[test]
+    fn telegram_pairing_accumulates_chats_and_unpairs_one_or_all() {
+        let mut state = AppState::new();
+        state.apply(Action::TelegramChatPaired { chat_id: 1 });
+        state.apply(Action::TelegramTopicBound {
+            message_thread_id: 7,
+            workspace_id: 1,
+            thread_id: 1,
+            replayed_up_to: None,
+        });
+        state.apply(Action::TelegramChatPaired { chat_id: 2 });
+        assert!(
+            state
+                .apply(Action::TelegramChatPaired { chat_id: 2 })
+                .is_empty(),
+            "re-pairing a chat is a no-op"
+        );
+        assert_eq!(state.telegram_paired_chat_ids(), &[1, 2]);
+        assert_eq!(state.telegram_topic_bindings().len(), 1);
+
+        state.apply(Action::TelegramUnpaired { chat_id: Some(1) });
+        assert_eq!(state.telegram_paired_chat_ids(), &[2]);
+        assert_eq!(state.telegram_topic_bindings().len(), 1);
+
+        let effects = state.apply(Action::TelegramUnpaired { chat_id: None });
+        assert!(matches!(effects.as_slice(), [Effect::SaveAppState]));
+        assert!(state.telegram_paired_chat_ids().is_empty());
+        assert!(state.telegram_topic_bindings().is_empty());
+    }
+
+    #[test]
+    fn webhook_config_validates_url_and_keeps_secret_when_omitted() {
+        let mut state = AppState::new();
+        assert!(
+            state
+                .apply(Action::WebhookConfigSet {
+                    url: "ftp://example.com/hook".to_owned(),
+                    secret: None,
+                })
+                .is_empty()
+        );
+
+        let effects = state.apply(Action::WebhookConfigSet {
+            url: " https://example.com/hook ".to_owned(),
+            secret: Some("s3cret".to_owned()),
+        });
+        assert!(matches!(effects.as_slice(), [Effect::SaveAppState]));
+        assert_eq!(state.webhook_url(), Some("https://example.com/hook"));
+        let rev = state.webhook_config_rev();
+
+        state.apply(Action::WebhookLastErrorSet {
+            message: Some("timed out".to_owned()),
+        });
+        state.apply(Action::WebhookConfigSet {
+            url: "https://example.com/other".to_owned(),
+            secret: None,
+        });
+        assert_eq!(state.webhook_secret(), Some("s3cret"));
+        assert_eq!(state.webhook_last_error(), None);
+        assert!(state.webhook_config_rev() > rev);
+
+        let mut restored = AppState::new();
+        restored.apply(Action::AppStateLoaded {
+            persisted: Box::new(state.to_persisted()),
+        });
+        assert_eq!(restored.webhook_url(), Some("https://example.com/other"));
+        assert_eq!(restored.webhook_secret(), Some("s3cret"));
+
+        state.apply(Action::WebhookConfigSet {
+            url: "https://example.com/other".to_owned(),
+            secret: Some(" ".to_owned()),
+        });
+        assert_eq!(state.webhook_secret(), None);
+        state.apply(Action::WebhookConfigCleared);
+        assert_eq!(state.webhook_url(), None);
+    }
+
+    #[test]
+    fn auto_archive_config_treats_zero_as_disabled_and_round_trips() {
+        let mut state = AppState::new();
+        assert_eq!(state.auto_archive_after_days(), None);
+
+        let effects = state.apply(Action::AutoArchiveConfigChanged { days: Some(14) });
+        assert!(matches!(effects.as_slice(), [Effect::SaveAppState]));
+        assert_eq!(state.auto_archive_after_days(), Some(14));
+        assert!(
+            state
+                .apply(Action::AutoArchiveConfigChanged { days: Some(14) })
+                .is_empty()
+        );
+
+        let mut restored = AppState::new();
+        restored.apply(Action::AppStateLoaded {
+            persisted: Box::new(state.to_persisted()),
+        });
+        assert_eq!(restored.auto_archive_after_days(), Some(14));
+
+        state.apply(Action::AutoArchiveConfigChanged { days: Some(0) });
+        assert_eq!(state.auto_archive_after_days(), None);
+        assert_eq!(state.to_persisted().auto_archive_after_days, None);
+    }
+
+    #[test]
+    fn manual_thread_rename_wins_over_later_auto_title() {
+        let mut state = AppState::demo();
+        let workspace_id = first_non_main_workspace_id(&state);
+        state.apply(Action::CreateWorkspaceThread { workspace_id });
+        let thread_id = state
+            .workspace_tabs(workspace_id)
+            .expect("missing workspace tabs")
+            .active_tab;
+        let original_title = state
+            .workspace_thread_conversation(workspace_id, thread_id)
+            .expect("missing conversation")
+            .title
+            .clone();
+        assert!(original_title.starts_with("Thread "));
+
+        let effects = state.apply(Action::RenameThread {
+            workspace_id,
+            thread_id,
+            title: "   ".to_owned(),
+        });
+        assert!(effects.is_empty());
+        assert_eq!(
+            state.last_error.as_deref(),
+            Some("Thread title cannot be empty")
+        );
+
+        let effects = state.apply(Action::RenameThread {
+            workspace_id,
+            thread_id,
+            title: "  Manual title  ".to_owned(),
+        });
+        match effects.as_slice() {
+            [
+                Effect::StoreConversationTitle {
+                    expected_current_title,
+                    title,
+                    ..
+                },
+            ] => {
+                assert_eq!(expected_current_title, &original_title);
+                assert_eq!(title, "Manual title");
+            }
+            other => panic!("unexpected effects: {other:?}"),
+        }
+
+        let effects = state.apply(Action::SendAgentMessage {
+            workspace_id,
+            thread_id,
+            text: "Fix the flaky login test".to_owned(),
+            attachments: Vec::new(),
+            runner: None,
+            amp_mode: None,
+        });
+        assert!(
+            !effects
+                .iter()
+                .any(|effect| matches!(effect, Effect::AiAutoTitleThread { .. })),
+            "a manually titled thread should not be auto-titled"
+        );
+        assert_eq!(
+            state
+                .workspace_thread_conversation(workspace_id, thread_id)
+                .expect("missing conversation")
+                .title,
+            "Manual title"
+        );
+    }
 }
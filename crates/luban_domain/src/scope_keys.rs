@@ -0,0 +1,96 @@
+//! Canonical JSON keys for workdir/task scoped payloads.
+//!
+//! The codebase historically called a workdir a "workspace" and a task a "thread". The wire API
+//! accepts both spellings through serde aliases; persisted JSON payloads are normalized to the
+//! canonical `workdir_id`/`task_id` keys so storage carries a single shape.
+//!
+//! The Telegram topic bindings are the only such payload today. Relational tables keep their
+//! `workspace_id`/`thread_id` columns: those are schema names, not JSON keys, and are not
+//! rewritten.
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+pub const WORKDIR_ID_KEY: &str = "workdir_id";
+pub const TASK_ID_KEY: &str = "task_id";
+pub const LEGACY_WORKDIR_ID_KEY: &str = "workspace_id";
+pub const LEGACY_TASK_ID_KEY: &str = "thread_id";
+
+const KEY_PAIRS: [(&str, &str); 2] = [
+    (LEGACY_WORKDIR_ID_KEY, WORKDIR_ID_KEY),
+    (LEGACY_TASK_ID_KEY, TASK_ID_KEY),
+];
+
+/// Renames legacy scope keys to their canonical names, recursing into arrays and objects.
+///
+/// Only integer-valued keys are renamed: `thread_id` also names remote agent thread ids, which are
+/// strings and must be left alone. A canonical key that is already present wins over its legacy
+/// counterpart. Returns whether anything changed.
+pub fn to_canonical(value: &mut Value) -> bool {
+    let mut changed = false;
+    match value {
+        Value::Array(items) => {
+            for item in items {
+                changed |= to_canonical(item);
+            }
+        }
+        Value::Object(map) => {
+            for (legacy, canonical) in KEY_PAIRS {
+                if !map.get(legacy).is_some_and(Value::is_u64) {
+                    continue;
+                }
+                if let Some(id) = map.remove(legacy) {
+                    map.entry(canonical).or_insert(id);
+                    changed = true;
+                }
+            }
+            for item in map.values_mut() {
+                changed |= to_canonical(item);
+            }
+        }
+        _ => {}
+    }
+    changed
+}
+
+/// Deserializes a persisted payload written with either legacy or canonical scope keys.
+pub fn from_legacy<T: DeserializeOwned>(raw: &str) -> serde_json::Result<T> {
+    let mut value: Value = serde_json::from_str(raw)?;
+    to_canonical(&mut value);
+    serde_json::from_value(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TelegramTopicBinding;
+
+    #[test]
+    fn legacy_and_canonical_keys_deserialize_to_the_same_binding() {
+        let legacy = r#"[{"message_thread_id":7,"workspace_id":3,"thread_id":2}]"#;
+        let canonical = r#"[{"message_thread_id":7,"workdir_id":3,"task_id":2}]"#;
+
+        let from_legacy_keys: Vec<TelegramTopicBinding> = from_legacy(legacy).unwrap();
+        let from_canonical_keys: Vec<TelegramTopicBinding> = from_legacy(canonical).unwrap();
+        assert_eq!(from_legacy_keys, from_canonical_keys);
+        assert_eq!(from_legacy_keys[0].workdir_id().as_u64(), 3);
+        assert_eq!(from_legacy_keys[0].task_id().as_u64(), 2);
+
+        let written = serde_json::to_value(&from_legacy_keys).unwrap();
+        assert_eq!(written[0][WORKDIR_ID_KEY], 3);
+        assert_eq!(written[0][TASK_ID_KEY], 2);
+        assert!(written[0].get(LEGACY_WORKDIR_ID_KEY).is_none());
+    }
+
+    #[test]
+    fn to_canonical_leaves_remote_thread_ids_alone() {
+        let mut value = serde_json::json!({
+            "thread_id": "thr_remote",
+            "nested": [{"workspace_id": 1, "workdir_id": 9}],
+        });
+        assert!(to_canonical(&mut value));
+        assert_eq!(value["thread_id"], "thr_remote");
+        assert_eq!(value["nested"][0], serde_json::json!({"workdir_id": 9}));
+        assert!(!to_canonical(&mut value));
+    }
+}
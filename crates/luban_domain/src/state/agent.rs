@@ -9,6 +9,12 @@ pub struct AgentRunConfig {
     pub thinking_effort: ThinkingEffort,
     #[serde(default)]
     pub amp_mode: Option<String>,
+    /// Claude-only output cap; `None` for other runners.
+    #[serde(default)]
+    pub claude_max_output_tokens: Option<u32>,
+    /// Claude-only replacement for the CLI's default system prompt; `None` for other runners.
+    #[serde(default)]
+    pub claude_system_prompt: Option<String>,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
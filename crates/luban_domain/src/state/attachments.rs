@@ -16,6 +16,14 @@ pub struct AttachmentRef {
     pub byte_len: u64,
 }
 
+/// Result of importing a directory tree as context attachments.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ContextDirImport {
+    pub attachments: Vec<AttachmentRef>,
+    /// One note per matched file that was not imported, e.g. because it exceeds the size cap.
+    pub skipped: Vec<String>,
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct ContextItem {
     pub id: u64,
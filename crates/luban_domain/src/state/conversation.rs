@@ -4,7 +4,9 @@ use super::{
     attachments::AttachmentRef,
     layout::OperationStatus,
 };
-use crate::{CodexThreadItem, CodexUsage, ContextTokenKind, TaskStatus, ThinkingEffort};
+use crate::{
+    CodexThreadItem, CodexUsage, CodexWebSearchResult, ContextTokenKind, TaskStatus, ThinkingEffort,
+};
 use std::collections::VecDeque;
 
 fn now_unix_ms() -> u64 {
@@ -32,6 +34,19 @@ pub enum ConversationSystemEvent {
         #[serde(default)]
         explanation_markdown: String,
     },
+    TurnRetriedWithRunner {
+        runner: crate::AgentRunnerKind,
+        model_id: String,
+    },
+    /// Synthetic entry holding an agent-written summary of a collapsed range of earlier entries.
+    HistorySummarized {
+        summary_markdown: String,
+        collapsed_entries: u64,
+    },
+    /// Marks where older entries were deleted by the conversation retention policy.
+    HistoryPruned {
+        pruned_entries: u64,
+    },
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
@@ -61,12 +76,31 @@ pub enum UserEvent {
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum AgentEvent {
-    Message { id: String, text: String },
-    Item { item: Box<CodexThreadItem> },
-    TurnUsage { usage: Option<CodexUsage> },
-    TurnDuration { duration_ms: u64 },
+    Message {
+        id: String,
+        text: String,
+    },
+    Item {
+        item: Box<CodexThreadItem>,
+    },
+    /// Marks the start of a turn, recorded right after the prompt that started it. Together with
+    /// `TurnDuration` this lets history render per-turn wall-clock timing.
+    TurnStarted {
+        at_unix_ms: u64,
+    },
+    TurnUsage {
+        usage: Option<CodexUsage>,
+    },
+    TurnDuration {
+        duration_ms: u64,
+    },
     TurnCanceled,
-    TurnError { message: String },
+    TurnError {
+        message: String,
+        /// Machine-readable classification, e.g. `runner_not_found` for spawn failures.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        code: Option<String>,
+    },
 }
 
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
@@ -99,6 +133,36 @@ pub enum ConversationEntry {
     },
 }
 
+/// Which side of a diff hunk a line number refers to.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HunkLineSide {
+    Old,
+    New,
+}
+
+/// User comment anchored to a single line of a file touched by a `FileChange` entry.
+#[derive(Clone, Debug, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct HunkComment {
+    pub entry_id: String,
+    pub path: String,
+    pub side: HunkLineSide,
+    pub line: u64,
+    pub text: String,
+    #[serde(default)]
+    pub created_at_unix_ms: u64,
+}
+
+/// Contiguous run of entries, `start_entry_id..=end_entry_id`, replaced in the UI by the summary in
+/// `summary_entry_id`. The collapsed entries stay in storage and can still be paged in.
+#[derive(Clone, Debug, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CollapsedRange {
+    pub start_entry_id: String,
+    pub end_entry_id: String,
+    pub entry_count: u64,
+    pub summary_entry_id: String,
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ChatScrollAnchor {
@@ -183,6 +247,10 @@ fn entry_is_same(a: &ConversationEntry, b: &ConversationEntry) -> bool {
                     && a_created_at == b_created_at
                     && codex_item_id(a_item) == codex_item_id(b_item)
             }
+            (
+                AgentEvent::TurnStarted { at_unix_ms: a },
+                AgentEvent::TurnStarted { at_unix_ms: b },
+            ) => a_entry_id == b_entry_id && a_created_at == b_created_at && a == b,
             (
                 AgentEvent::TurnUsage { usage: a_usage },
                 AgentEvent::TurnUsage { usage: b_usage },
@@ -194,8 +262,20 @@ fn entry_is_same(a: &ConversationEntry, b: &ConversationEntry) -> bool {
             (AgentEvent::TurnCanceled, AgentEvent::TurnCanceled) => {
                 a_entry_id == b_entry_id && a_created_at == b_created_at
             }
-            (AgentEvent::TurnError { message: a }, AgentEvent::TurnError { message: b }) => {
-                a_entry_id == b_entry_id && a_created_at == b_created_at && a == b
+            (
+                AgentEvent::TurnError {
+                    message: a,
+                    code: a_code,
+                },
+                AgentEvent::TurnError {
+                    message: b,
+                    code: b_code,
+                },
+            ) => {
+                a_entry_id == b_entry_id
+                    && a_created_at == b_created_at
+                    && a == b
+                    && a_code == b_code
             }
             _ => false,
         },
@@ -239,6 +319,12 @@ pub struct ConversationSnapshot {
     pub thinking_effort: Option<crate::ThinkingEffort>,
     #[serde(default)]
     pub amp_mode: Option<String>,
+    #[serde(default)]
+    pub claude_max_output_tokens: Option<u32>,
+    #[serde(default)]
+    pub claude_system_prompt: Option<String>,
+    #[serde(default)]
+    pub auto_commit: bool,
     pub entries: Vec<ConversationEntry>,
     #[serde(default)]
     pub entries_total: u64,
@@ -252,9 +338,39 @@ pub struct ConversationSnapshot {
     pub run_started_at_unix_ms: Option<u64>,
     #[serde(default)]
     pub run_finished_at_unix_ms: Option<u64>,
+    #[serde(default)]
+    pub notes: String,
+    #[serde(default)]
+    pub cwd_subpath: Option<String>,
+    #[serde(default)]
+    pub hunk_comments: Vec<HunkComment>,
+    #[serde(default)]
+    pub collapsed_ranges: Vec<CollapsedRange>,
+    /// Unsent composer text persisted so the input box survives restarts.
+    #[serde(default)]
+    pub draft: String,
+    /// Sum of every stored `TurnUsage` entry of the thread, not just the loaded page.
+    #[serde(default)]
+    pub total_usage: CodexUsage,
+}
+
+/// Turn status of a conversation without a running turn, decided by its prompt queue.
+fn idle_turn_status(has_pending_prompts: bool, queue_paused: bool) -> crate::TurnStatus {
+    if !has_pending_prompts {
+        crate::TurnStatus::Idle
+    } else if queue_paused {
+        crate::TurnStatus::Paused
+    } else {
+        crate::TurnStatus::Awaiting
+    }
 }
 
 impl ConversationSnapshot {
+    /// Turn status of a stored conversation, which never has a turn in flight.
+    pub fn turn_status(&self) -> crate::TurnStatus {
+        idle_turn_status(!self.pending_prompts.is_empty(), self.queue_paused)
+    }
+
     pub(crate) fn ensure_entry_ids(&mut self) {
         let base = self.entries_start;
         for (idx, entry) in self.entries.iter_mut().enumerate() {
@@ -270,6 +386,174 @@ impl ConversationSnapshot {
     }
 }
 
+/// Ids of command executions that exited non-zero since the latest user message, in order.
+pub fn failed_command_ids_in_last_turn(entries: &[ConversationEntry]) -> Vec<String> {
+    let turn_start = entries
+        .iter()
+        .rposition(|entry| {
+            matches!(
+                entry,
+                ConversationEntry::UserEvent {
+                    event: UserEvent::Message { .. },
+                    ..
+                }
+            )
+        })
+        .map(|idx| idx + 1)
+        .unwrap_or(0);
+    entries[turn_start..]
+        .iter()
+        .filter_map(|entry| match entry {
+            ConversationEntry::AgentEvent {
+                event: AgentEvent::Item { item },
+                ..
+            } => match item.as_ref() {
+                CodexThreadItem::CommandExecution {
+                    id,
+                    exit_code: Some(code),
+                    ..
+                } if *code != 0 => Some(id.clone()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+/// Index range of `entries` that compacting up to `before_entry_id` would summarize: everything
+/// after the end of the last collapsed range and before the cutoff entry. Returns `None` when the
+/// cutoff is not among `entries` or the range would be empty.
+pub fn compaction_range(
+    entries: &[ConversationEntry],
+    collapsed_ranges: &[CollapsedRange],
+    before_entry_id: &str,
+) -> Option<std::ops::Range<usize>> {
+    fn entry_id(entry: &ConversationEntry) -> &str {
+        match entry {
+            ConversationEntry::SystemEvent { entry_id, .. }
+            | ConversationEntry::UserEvent { entry_id, .. }
+            | ConversationEntry::AgentEvent { entry_id, .. } => entry_id,
+        }
+    }
+    let end = entries
+        .iter()
+        .position(|entry| entry_id(entry) == before_entry_id)?;
+    let start = collapsed_ranges
+        .last()
+        .and_then(|range| {
+            entries
+                .iter()
+                .position(|entry| entry_id(entry) == range.end_entry_id)
+        })
+        .map(|idx| idx + 1)
+        .unwrap_or(0);
+    (start < end).then_some(start..end)
+}
+
+/// Renders carriage-return overwrites the way a terminal would: within each line, every `\r`
+/// moves back to the first column and later text overwrites earlier text. A `\r` right before a
+/// `\n` is a plain line ending. Returns `None` when the output has no carriage returns.
+fn collapse_carriage_returns(output: &str) -> Option<String> {
+    if !output.contains('\r') {
+        return None;
+    }
+    let mut out = String::with_capacity(output.len());
+    let mut rendered = Vec::new();
+    for (idx, line) in output.split('\n').enumerate() {
+        if idx > 0 {
+            out.push('\n');
+        }
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        rendered.clear();
+        for segment in line.split('\r') {
+            for (col, ch) in segment.chars().enumerate() {
+                match rendered.get_mut(col) {
+                    Some(slot) => *slot = ch,
+                    None => rendered.push(ch),
+                }
+            }
+        }
+        out.extend(rendered.iter());
+    }
+    Some(out)
+}
+
+/// Records `text` as item `id`'s segment of a merged reasoning entry and returns the entry's
+/// combined text. The backend uses it too, so stored and in-memory entries read the same.
+pub fn merge_reasoning_segment(
+    segments: &mut Vec<(String, String)>,
+    id: String,
+    text: String,
+) -> String {
+    match segments
+        .iter_mut()
+        .find(|(segment_id, _)| *segment_id == id)
+    {
+        Some((_, segment_text)) => *segment_text = text,
+        None => segments.push((id, text)),
+    }
+    segments
+        .iter()
+        .map(|(_, text)| text.as_str())
+        .filter(|text| !text.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Appends the web search `results` whose urls are not among `previous` yet, keeping the order in
+/// which results first arrived. Shared with the backend so stored search items keep every result.
+pub fn merge_web_search_results(
+    previous: &[CodexWebSearchResult],
+    results: Vec<CodexWebSearchResult>,
+) -> Vec<CodexWebSearchResult> {
+    let mut merged = previous.to_vec();
+    for result in results {
+        if !merged.iter().any(|existing| existing.url == result.url) {
+            merged.push(result);
+        }
+    }
+    merged
+}
+
+/// Caps a command execution's `aggregated_output` at `max_bytes` (`0` disables the cap), keeping
+/// the first and last half of the budget around a marker and setting `truncated`. Carriage-return
+/// progress output is collapsed to its final rendered lines first. Other items are left untouched.
+pub fn cap_command_output(item: &mut CodexThreadItem, max_bytes: usize) {
+    let CodexThreadItem::CommandExecution {
+        aggregated_output,
+        truncated,
+        ..
+    } = item
+    else {
+        return;
+    };
+    if max_bytes == 0 {
+        return;
+    }
+    if let Some(collapsed) = collapse_carriage_returns(aggregated_output) {
+        *aggregated_output = collapsed;
+    }
+    if aggregated_output.len() <= max_bytes {
+        return;
+    }
+
+    let mut head_end = max_bytes / 2;
+    while !aggregated_output.is_char_boundary(head_end) {
+        head_end -= 1;
+    }
+    let mut tail_start = aggregated_output.len() - max_bytes / 2;
+    while !aggregated_output.is_char_boundary(tail_start) {
+        tail_start += 1;
+    }
+    let omitted = tail_start - head_end;
+    *aggregated_output = format!(
+        "{}\n… [{omitted} bytes truncated] …\n{}",
+        &aggregated_output[..head_end],
+        &aggregated_output[tail_start..]
+    );
+    *truncated = true;
+}
+
 fn default_task_status() -> TaskStatus {
     TaskStatus::Todo
 }
@@ -286,6 +570,8 @@ pub struct ConversationThreadMeta {
     pub task_status_last_analyzed_message_seq: u64,
     pub turn_status: crate::TurnStatus,
     pub last_turn_result: Option<crate::TurnResult>,
+    /// `CodexUsage::total_tokens` summed over the thread's turns.
+    pub total_tokens: u64,
 }
 
 #[derive(Clone, Debug)]
@@ -296,14 +582,33 @@ pub struct WorkspaceConversation {
     pub task_status: TaskStatus,
     pub draft: String,
     pub draft_attachments: Vec<DraftAttachment>,
+    /// Free-form user scratchpad attached to the thread; never sent to the agent.
+    pub notes: String,
+    /// Set by a notes edit until the engine reports it persisted; loaded snapshots keep the local
+    /// notes meanwhile because the stored ones are older.
+    pub notes_save_pending: bool,
+    /// Worktree-relative directory the agent runs in; `None` means the worktree root.
+    pub cwd_subpath: Option<String>,
+    /// Line-anchored comments on `FileChange` entries, in creation order.
+    pub hunk_comments: Vec<HunkComment>,
+    /// Ranges summarized by `CompactConversation`, oldest first.
+    pub collapsed_ranges: Vec<CollapsedRange>,
     pub run_config_overridden_by_user: bool,
     pub agent_runner: crate::AgentRunnerKind,
     pub agent_model_id: String,
     pub thinking_effort: ThinkingEffort,
     pub amp_mode: Option<String>,
+    /// Claude-only run options; kept across runner switches but only applied to Claude turns.
+    pub claude_max_output_tokens: Option<u32>,
+    pub claude_system_prompt: Option<String>,
+    /// Commit the worktree after each successfully completed turn.
+    pub auto_commit: bool,
     pub entries: Vec<ConversationEntry>,
     pub entries_total: u64,
     pub entries_start: u64,
+    /// Token usage summed over every turn of the thread. Loading a snapshot replaces it with the
+    /// stored total, so live turns that were also persisted are not counted twice.
+    pub total_usage: CodexUsage,
     pub active_run_id: Option<u64>,
     pub next_run_id: u64,
     pub run_status: OperationStatus,
@@ -313,9 +618,38 @@ pub struct WorkspaceConversation {
     pub next_queued_prompt_id: u64,
     pub pending_prompts: VecDeque<QueuedPrompt>,
     pub queue_paused: bool,
+    /// Ids of tool calls in the running turn that wait for the user's approval.
+    pub pending_tool_approvals: Vec<String>,
+    /// Entry id of a file change in the current or last turn that failed to apply.
+    pub patch_apply_failed_entry_id: Option<String>,
+    /// Transient-failure retries already spent on the current prompt.
+    pub auto_retry_attempts: u8,
+    /// Command executions of the last finished turn that exited non-zero.
+    pub last_turn_failed_command_ids: Vec<String>,
+    /// Per-item texts folded into the trailing merged reasoning entry, in arrival order.
+    pub(crate) merged_reasoning_segments: Vec<(String, String)>,
+    /// Whether the most recent agent item update targeted the newest item (or started a new
+    /// one), so clients can keep following the bottom of the stream. `false` means an older item
+    /// was updated and the scroll position should be preserved.
+    pub follow_latest_item: bool,
 }
 
 impl WorkspaceConversation {
+    /// Claude run options for a turn on `runner`; both are `None` unless it is Claude.
+    pub fn claude_run_options(
+        &self,
+        runner: crate::AgentRunnerKind,
+    ) -> (Option<u32>, Option<String>) {
+        if runner == crate::AgentRunnerKind::Claude {
+            (
+                self.claude_max_output_tokens,
+                self.claude_system_prompt.clone(),
+            )
+        } else {
+            (None, None)
+        }
+    }
+
     pub(crate) fn reset_entries_from_snapshot(&mut self, snapshot: ConversationSnapshot) {
         self.task_status = snapshot.task_status;
         self.entries = snapshot.entries;
@@ -326,8 +660,32 @@ impl WorkspaceConversation {
         );
         self.entries_start = snapshot.entries_start;
         self.ensure_loaded_entry_ids();
+        self.merged_reasoning_segments.clear();
         self.run_started_at_unix_ms = snapshot.run_started_at_unix_ms;
         self.run_finished_at_unix_ms = snapshot.run_finished_at_unix_ms;
+        if !self.notes_save_pending {
+            self.notes = snapshot.notes;
+        }
+        self.cwd_subpath = snapshot.cwd_subpath;
+        self.hunk_comments = snapshot.hunk_comments;
+        self.collapsed_ranges = snapshot.collapsed_ranges;
+        self.total_usage = snapshot.total_usage;
+        self.trim_entries_to_limit();
+    }
+
+    /// Swaps in the entry window of a conversation whose stored history was just pruned,
+    /// keeping everything else (notes, drafts, run state) as it is in memory.
+    pub(crate) fn replace_entries_after_prune(&mut self, snapshot: ConversationSnapshot) {
+        self.entries = snapshot.entries;
+        self.entries_start = snapshot.entries_start;
+        self.entries_total = snapshot.entries_total.max(
+            snapshot
+                .entries_start
+                .saturating_add(self.entries.len() as u64),
+        );
+        self.ensure_loaded_entry_ids();
+        self.merged_reasoning_segments.clear();
+        self.collapsed_ranges = snapshot.collapsed_ranges;
         self.trim_entries_to_limit();
     }
 
@@ -345,8 +703,16 @@ impl WorkspaceConversation {
                     .unwrap_or(self.agent_runner),
             );
         }
+        if let ConversationEntry::AgentEvent {
+            event: AgentEvent::TurnUsage { usage: Some(usage) },
+            ..
+        } = &entry
+        {
+            self.total_usage.accumulate(usage);
+        }
         self.ensure_entry_created_at(&mut entry);
         self.ensure_entry_id(&mut entry);
+        self.merged_reasoning_segments.clear();
         self.entries.push(entry);
         self.entries_total = self
             .entries_total
@@ -354,12 +720,46 @@ impl WorkspaceConversation {
         self.trim_entries_to_limit();
     }
 
+    pub fn had_failed_commands(&self) -> bool {
+        !self.last_turn_failed_command_ids.is_empty()
+    }
+
+    pub fn turn_status(&self) -> crate::TurnStatus {
+        if self.run_status == OperationStatus::Running {
+            if self.pending_tool_approvals.is_empty() {
+                crate::TurnStatus::Running
+            } else {
+                crate::TurnStatus::Awaiting
+            }
+        } else {
+            idle_turn_status(!self.pending_prompts.is_empty(), self.queue_paused)
+        }
+    }
+
+    /// Records whether a tool call item is blocked on the user's approval.
+    pub(crate) fn track_tool_approval(&mut self, item: &CodexThreadItem) {
+        let CodexThreadItem::McpToolCall { id, status, .. } = item else {
+            return;
+        };
+        if *status == crate::CodexMcpToolCallStatus::AwaitingApproval {
+            if !self.pending_tool_approvals.contains(id) {
+                self.pending_tool_approvals.push(id.clone());
+            }
+        } else {
+            self.pending_tool_approvals.retain(|pending| pending != id);
+        }
+    }
+
     pub(crate) fn push_entry(&mut self, entry: ConversationEntry) {
         self.push_entry_and_update_totals(entry);
     }
 
-    pub(crate) fn push_codex_item(&mut self, item: CodexThreadItem) {
-        if self.should_skip_codex_item(&item) {
+    /// Appends an agent item unless it duplicates one already recorded. See
+    /// `should_skip_codex_item` for how `dedup_window` widens the check.
+    pub(crate) fn push_codex_item(&mut self, mut item: CodexThreadItem, dedup_window: usize) {
+        self.follow_latest_item = self.item_is_latest_or_new(codex_item_id(&item));
+        self.accumulate_web_search_results(&mut item);
+        if self.should_skip_codex_item(&item, dedup_window) {
             return;
         }
 
@@ -382,29 +782,125 @@ impl WorkspaceConversation {
         self.push_entry(entry);
     }
 
-    fn should_skip_codex_item(&self, item: &CodexThreadItem) -> bool {
-        let incoming_id = codex_item_id(item);
+    /// Like `push_codex_item`, but folds a reasoning item into the previous entry when that
+    /// entry is also a reasoning item, keeping the first entry's ids.
+    pub(crate) fn push_codex_item_merging_reasoning(
+        &mut self,
+        item: CodexThreadItem,
+        dedup_window: usize,
+    ) {
+        let CodexThreadItem::Reasoning { id, text } = item else {
+            self.push_codex_item(item, dedup_window);
+            return;
+        };
+
+        let Some(ConversationEntry::AgentEvent {
+            event: AgentEvent::Item { item: existing },
+            ..
+        }) = self.entries.last_mut()
+        else {
+            self.push_codex_item(CodexThreadItem::Reasoning { id, text }, dedup_window);
+            return;
+        };
+        let CodexThreadItem::Reasoning {
+            id: existing_id,
+            text: existing_text,
+        } = existing.as_mut()
+        else {
+            self.push_codex_item(CodexThreadItem::Reasoning { id, text }, dedup_window);
+            return;
+        };
+
+        self.follow_latest_item = true;
+        if self.merged_reasoning_segments.is_empty() {
+            self.merged_reasoning_segments
+                .push((existing_id.clone(), existing_text.clone()));
+        }
+        *existing_text = merge_reasoning_segment(&mut self.merged_reasoning_segments, id, text);
+    }
+
+    /// Web search results arrive incrementally across updates; carry the results already seen
+    /// for the same item forward so the latest entry always holds the full list.
+    fn accumulate_web_search_results(&self, item: &mut CodexThreadItem) {
+        let CodexThreadItem::WebSearch { id, results, .. } = item else {
+            return;
+        };
+        let previous = self.entries.iter().rev().find_map(|entry| match entry {
+            ConversationEntry::AgentEvent {
+                event: AgentEvent::Item { item: existing },
+                ..
+            } => match existing.as_ref() {
+                CodexThreadItem::WebSearch {
+                    id: existing_id,
+                    results: existing_results,
+                    ..
+                } if existing_id == id => Some(existing_results),
+                _ => None,
+            },
+            _ => None,
+        });
+        let Some(previous) = previous else {
+            return;
+        };
+
+        *results = merge_web_search_results(previous, std::mem::take(results));
+    }
+
+    fn item_is_latest_or_new(&self, incoming_id: &str) -> bool {
+        let mut passed_other_item = false;
         for entry in self.entries.iter().rev() {
             let ConversationEntry::AgentEvent { event, .. } = entry else {
                 continue;
             };
-
-            match event {
-                AgentEvent::Message { id, text } if id == incoming_id => {
-                    return match item {
-                        CodexThreadItem::AgentMessage { text: incoming, .. } => incoming == text,
-                        _ => false,
-                    };
-                }
-                AgentEvent::Item { item: existing }
-                    if codex_item_id(existing.as_ref()) == incoming_id =>
-                {
-                    let existing = serde_json::to_value(existing.as_ref());
-                    let incoming = serde_json::to_value(item);
-                    return existing.ok() == incoming.ok();
-                }
+            let id = match event {
+                AgentEvent::Message { id, .. } => id.as_str(),
+                AgentEvent::Item { item } => codex_item_id(item.as_ref()),
                 _ => continue,
             };
+            if id == incoming_id {
+                return !passed_other_item;
+            }
+            passed_other_item = true;
+        }
+        true
+    }
+
+    /// By default an item is skipped only when it equals the latest entry with the same id.
+    /// A non-zero `dedup_window` additionally skips it when any of the last `dedup_window`
+    /// entries is an identical copy, which catches replays from flaky streams that arrive after
+    /// a later update of the same item.
+    fn should_skip_codex_item(&self, item: &CodexThreadItem, dedup_window: usize) -> bool {
+        let incoming_id = codex_item_id(item);
+        let mut incoming_value = None;
+        let mut is_duplicate = |event: &AgentEvent| match event {
+            AgentEvent::Message { id, text } if id == incoming_id => Some(match item {
+                CodexThreadItem::AgentMessage { text: incoming, .. } => incoming == text,
+                _ => false,
+            }),
+            AgentEvent::Item { item: existing }
+                if codex_item_id(existing.as_ref()) == incoming_id =>
+            {
+                let incoming =
+                    incoming_value.get_or_insert_with(|| serde_json::to_value(item).ok());
+                Some(serde_json::to_value(existing.as_ref()).ok() == *incoming)
+            }
+            _ => None,
+        };
+
+        let mut seen_same_id = false;
+        for (index, entry) in self.entries.iter().rev().enumerate() {
+            // Past the window only the latest entry with the same id is compared.
+            if seen_same_id && index >= dedup_window {
+                return false;
+            }
+            let ConversationEntry::AgentEvent { event, .. } = entry else {
+                continue;
+            };
+            match is_duplicate(event) {
+                Some(true) => return true,
+                Some(false) => seen_same_id = true,
+                None => continue,
+            }
         }
         false
     }
@@ -527,7 +1023,8 @@ mod tests {
 
     fn conversation_with_draft(draft: &str, anchors: &[usize]) -> WorkspaceConversation {
         let state = crate::AppState::new();
-        let mut conversation = state.default_conversation(WorkspaceThreadId(1));
+        let mut conversation =
+            state.default_conversation(crate::WorkspaceId(1), WorkspaceThreadId(1));
         conversation.draft = draft.to_owned();
         conversation.draft_attachments = anchors
             .iter()
@@ -586,7 +1083,8 @@ mod tests {
     #[test]
     fn trim_entries_drops_overflow_and_updates_entries_start() {
         let state = crate::AppState::new();
-        let mut conversation = state.default_conversation(WorkspaceThreadId(1));
+        let mut conversation =
+            state.default_conversation(crate::WorkspaceId(1), WorkspaceThreadId(1));
         conversation.entries_start = 100;
 
         let mut entries = Vec::with_capacity(MAX_CONVERSATION_ENTRIES_IN_MEMORY + 2);
@@ -651,22 +1149,31 @@ mod tests {
     #[test]
     fn push_codex_item_appends_updates_and_assigns_entry_ids() {
         let state = crate::AppState::new();
-        let mut conversation = state.default_conversation(WorkspaceThreadId(1));
+        let mut conversation =
+            state.default_conversation(crate::WorkspaceId(1), WorkspaceThreadId(1));
 
-        conversation.push_codex_item(CodexThreadItem::CommandExecution {
-            id: "cmd_1".to_owned(),
-            command: "echo hi".to_owned(),
-            aggregated_output: String::new(),
-            exit_code: None,
-            status: crate::CodexCommandExecutionStatus::InProgress,
-        });
-        conversation.push_codex_item(CodexThreadItem::CommandExecution {
-            id: "cmd_1".to_owned(),
-            command: "echo hi".to_owned(),
-            aggregated_output: "hi\n".to_owned(),
-            exit_code: Some(0),
-            status: crate::CodexCommandExecutionStatus::Completed,
-        });
+        conversation.push_codex_item(
+            CodexThreadItem::CommandExecution {
+                id: "cmd_1".to_owned(),
+                command: "echo hi".to_owned(),
+                aggregated_output: String::new(),
+                exit_code: None,
+                status: crate::CodexCommandExecutionStatus::InProgress,
+                truncated: false,
+            },
+            0,
+        );
+        conversation.push_codex_item(
+            CodexThreadItem::CommandExecution {
+                id: "cmd_1".to_owned(),
+                command: "echo hi".to_owned(),
+                aggregated_output: "hi\n".to_owned(),
+                exit_code: Some(0),
+                status: crate::CodexCommandExecutionStatus::Completed,
+                truncated: false,
+            },
+            0,
+        );
 
         assert_eq!(conversation.entries.len(), 2);
         assert_eq!(conversation.entries_total, 2);
@@ -695,10 +1202,68 @@ mod tests {
         assert_ne!(first_entry_id, second_entry_id);
     }
 
+    #[test]
+    fn push_codex_item_follows_only_updates_to_the_latest_item() {
+        let state = crate::AppState::new();
+        let mut conversation =
+            state.default_conversation(crate::WorkspaceId(1), WorkspaceThreadId(1));
+        let command = |id: &str, output: &str| CodexThreadItem::CommandExecution {
+            id: id.to_owned(),
+            command: "make".to_owned(),
+            aggregated_output: output.to_owned(),
+            exit_code: None,
+            status: crate::CodexCommandExecutionStatus::InProgress,
+            truncated: false,
+        };
+
+        conversation.push_codex_item(command("cmd_1", ""), 0);
+        assert!(conversation.follow_latest_item, "a new item is appended");
+        conversation.push_codex_item(command("cmd_2", ""), 0);
+        conversation.push_codex_item(command("cmd_2", "building\n"), 0);
+        assert!(
+            conversation.follow_latest_item,
+            "updating the latest item should follow"
+        );
+
+        conversation.push_codex_item(command("cmd_1", "late output\n"), 0);
+        assert!(
+            !conversation.follow_latest_item,
+            "updating an earlier item should preserve the scroll position"
+        );
+    }
+
+    #[test]
+    fn push_codex_item_ignores_duplicates_just_outside_the_dedup_window() {
+        let state = crate::AppState::new();
+        let command = |id: &str, output: &str| CodexThreadItem::CommandExecution {
+            id: id.to_owned(),
+            command: "make".to_owned(),
+            aggregated_output: output.to_owned(),
+            exit_code: None,
+            status: crate::CodexCommandExecutionStatus::InProgress,
+            truncated: false,
+        };
+        let entries_after_replay = |dedup_window: usize| {
+            let mut conversation =
+                state.default_conversation(crate::WorkspaceId(1), WorkspaceThreadId(1));
+            conversation.push_codex_item(command("cmd_1", ""), dedup_window);
+            conversation.push_codex_item(command("cmd_2", ""), dedup_window);
+            conversation.push_codex_item(command("cmd_3", ""), dedup_window);
+            conversation.push_codex_item(command("cmd_1", "done\n"), dedup_window);
+            // The stream replays the first copy of cmd_1, now four entries back.
+            conversation.push_codex_item(command("cmd_1", ""), dedup_window);
+            conversation.entries.len()
+        };
+
+        assert_eq!(entries_after_replay(3), 5, "the copy is outside the window");
+        assert_eq!(entries_after_replay(4), 4, "the copy is inside the window");
+    }
+
     #[test]
     fn push_entry_fills_created_at_unix_ms_for_user_and_agent_entries() {
         let state = crate::AppState::new();
-        let mut conversation = state.default_conversation(WorkspaceThreadId(1));
+        let mut conversation =
+            state.default_conversation(crate::WorkspaceId(1), WorkspaceThreadId(1));
 
         conversation.push_entry(ConversationEntry::UserEvent {
             entry_id: String::new(),
@@ -11,6 +11,19 @@ impl ProjectId {
     }
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct ProjectGroupId(pub(crate) u64);
+
+impl ProjectGroupId {
+    pub fn as_u64(self) -> u64 {
+        self.0
+    }
+
+    pub fn from_u64(id: u64) -> Self {
+        Self(id)
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
 pub struct WorkspaceId(pub(crate) u64);
 
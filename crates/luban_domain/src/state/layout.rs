@@ -20,6 +20,14 @@ pub enum WorkspaceStatus {
     Archived,
 }
 
+/// Archived projects keep their workdirs and history but are hidden from the sidebar.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ProjectStatus {
+    #[default]
+    Active,
+    Archived,
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum OperationStatus {
     Idle,
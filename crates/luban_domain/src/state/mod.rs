@@ -11,21 +11,33 @@ mod workspace;
 
 pub use agent::{AgentRunConfig, QueuedPrompt};
 pub use appearance::{AppearanceFonts, AppearanceTheme};
-pub use attachments::{AttachmentKind, AttachmentRef, ContextItem};
+pub use attachments::{AttachmentKind, AttachmentRef, ContextDirImport, ContextItem};
 pub use conversation::{
-    AgentEvent, ChatScrollAnchor, ConversationEntry, ConversationSnapshot, ConversationSystemEvent,
-    ConversationThreadMeta, DraftAttachment, UserEvent, WorkspaceConversation,
+    AgentEvent, ChatScrollAnchor, CollapsedRange, ConversationEntry, ConversationSnapshot,
+    ConversationSystemEvent, ConversationThreadMeta, DraftAttachment, HunkComment, HunkLineSide,
+    UserEvent, WorkspaceConversation, cap_command_output, compaction_range,
+    failed_command_ids_in_last_turn, merge_reasoning_segment, merge_web_search_results,
 };
-pub use ids::{ProjectId, WorkspaceId, WorkspaceThreadId};
-pub use layout::{MainPane, OperationStatus, RightPane, WorkspaceStatus};
+pub use ids::{ProjectGroupId, ProjectId, WorkspaceId, WorkspaceThreadId};
+pub use layout::{MainPane, OperationStatus, ProjectStatus, RightPane, WorkspaceStatus};
 pub use persisted::{
-    PersistedAppState, PersistedProject, PersistedWorkspace,
+    PersistedAppState, PersistedProject, PersistedProjectGroup, PersistedWorkspace,
     PersistedWorkspaceThreadRunConfigOverride,
 };
-pub use tabs::WorkspaceTabs;
+pub use tabs::{TabDirection, WorkspaceTabs};
 pub use task::{TaskStatus, TurnResult, TurnStatus, parse_task_status};
-pub use workspace::{AppState, Project, TelegramTopicBinding, Workspace};
+pub use workspace::{
+    AppState, DETACHED_HEAD_BRANCH_NAME, ErrorLogEntry, FALLBACK_DEFAULT_BRANCH_NAME, Project,
+    ProjectAgentDefaults, ProjectGroup, ProjectSavedCommand, TelegramTopicBinding, Workspace,
+};
 
 pub(crate) const MAX_CONVERSATION_ENTRIES_IN_MEMORY: usize = 5000;
+pub(crate) const MAX_ERROR_LOG_ENTRIES: usize = 50;
+pub(crate) const MAX_PROJECT_GROUP_NAME_CHARS: usize = 128;
+pub(crate) const MAX_AGENT_ITEM_DEDUP_WINDOW: usize = 512;
+pub(crate) const MAX_AGENT_AUTO_RETRY_ON_FAILURE: u8 = 5;
+/// Backoff before the first automatic retry; each further attempt doubles it.
+pub(crate) const AGENT_AUTO_RETRY_BASE_DELAY_MS: u64 = 2_000;
+pub(crate) const DEFAULT_AGENT_COMMAND_OUTPUT_MAX_BYTES: usize = 256 * 1024;
 
 pub(crate) use conversation::{apply_draft_text_diff, entries_is_prefix, entries_is_suffix};
@@ -1,4 +1,6 @@
-use super::{ChatScrollAnchor, WorkspaceStatus};
+use super::{
+    ChatScrollAnchor, ProjectAgentDefaults, ProjectSavedCommand, ProjectStatus, WorkspaceStatus,
+};
 use std::{collections::HashMap, path::PathBuf};
 
 #[derive(Clone, Debug, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
@@ -25,6 +27,12 @@ pub struct PersistedAppState {
     pub agent_default_model_id: Option<String>,
     /// Per-runner model overrides, stored as JSON: `{"codex":"gpt-5.2","droid":"claude-opus-4-6"}`
     pub agent_runner_default_models: HashMap<String, String>,
+    /// Text placed before the user prompt for a runner, keyed by runner name.
+    pub agent_runner_prompt_prefixes: HashMap<String, String>,
+    /// Text placed after the user prompt for a runner, keyed by runner name.
+    pub agent_runner_prompt_suffixes: HashMap<String, String>,
+    /// Highest thinking effort each model honours, stored as JSON: `{"gpt-5.2":"medium"}`
+    pub model_thinking_effort_caps: HashMap<String, String>,
     pub agent_default_thinking_effort: Option<String>,
     pub agent_default_runner: Option<String>,
     pub agent_amp_mode: Option<String>,
@@ -35,6 +43,7 @@ pub struct PersistedAppState {
     pub last_open_workspace_id: Option<u64>,
     pub open_button_selection: Option<String>,
     pub sidebar_project_order: Vec<String>,
+    pub project_groups: Vec<PersistedProjectGroup>,
     pub workspace_active_thread_id: HashMap<u64, u64>,
     pub workspace_open_tabs: HashMap<u64, Vec<u64>>,
     pub workspace_archived_tabs: HashMap<u64, Vec<u64>>,
@@ -49,8 +58,24 @@ pub struct PersistedAppState {
     pub telegram_enabled: Option<bool>,
     pub telegram_bot_token: Option<String>,
     pub telegram_bot_username: Option<String>,
-    pub telegram_paired_chat_id: Option<i64>,
+    pub telegram_paired_chat_ids: Vec<i64>,
     pub telegram_topic_bindings: Option<String>,
+    pub webhook_url: Option<String>,
+    pub webhook_secret: Option<String>,
+    pub agent_merge_reasoning_entries: Option<bool>,
+    pub agent_pause_queue_on_patch_failure: Option<bool>,
+    pub agent_item_dedup_window: Option<u64>,
+    pub agent_auto_retry_on_failure: Option<u64>,
+    pub agent_command_output_max_bytes: Option<u64>,
+    pub auto_archive_after_days: Option<u64>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PersistedProjectGroup {
+    pub id: u64,
+    pub name: String,
+    #[serde(default)]
+    pub project_ids: Vec<u64>,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -61,6 +86,15 @@ pub struct PersistedProject {
     pub slug: String,
     pub is_git: bool,
     pub expanded: bool,
+    pub system_prompt: Option<String>,
+    pub worktree_root: Option<PathBuf>,
+    pub branch_name_template: Option<String>,
+    pub status: ProjectStatus,
+    pub agent_defaults: ProjectAgentDefaults,
+    pub saved_commands: Vec<ProjectSavedCommand>,
+    pub agent_env: Vec<(String, String)>,
+    pub pr_remote: Option<String>,
+    pub pr_base_branch: Option<String>,
     pub workspaces: Vec<PersistedWorkspace>,
 }
 
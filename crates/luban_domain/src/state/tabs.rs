@@ -1,5 +1,12 @@
 use super::WorkspaceThreadId;
 
+/// Direction for cycling the active tab through `WorkspaceTabs::open_tabs`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TabDirection {
+    Next,
+    Prev,
+}
+
 #[derive(Clone, Debug)]
 pub struct WorkspaceTabs {
     pub open_tabs: Vec<WorkspaceThreadId>,
@@ -86,6 +93,26 @@ impl WorkspaceTabs {
         id
     }
 
+    /// The open tab before or after the active one, wrapping around at either end. `None` when
+    /// fewer than two tabs are open.
+    pub fn adjacent_tab(&self, direction: TabDirection) -> Option<WorkspaceThreadId> {
+        let len = self.open_tabs.len();
+        if len < 2 {
+            return None;
+        }
+        let index = match self.open_tabs.iter().position(|id| *id == self.active_tab) {
+            Some(active) => match direction {
+                TabDirection::Next => (active + 1) % len,
+                TabDirection::Prev => (active + len - 1) % len,
+            },
+            None => match direction {
+                TabDirection::Next => 0,
+                TabDirection::Prev => len - 1,
+            },
+        };
+        Some(self.open_tabs[index])
+    }
+
     pub fn reorder_tab(&mut self, thread_id: WorkspaceThreadId, to_index: usize) -> bool {
         let Some(from_index) = self.open_tabs.iter().position(|id| *id == thread_id) else {
             return false;
@@ -130,4 +157,29 @@ mod tests {
         assert!(tabs.open_tabs.contains(&WorkspaceThreadId(2)));
         assert!(!tabs.archived_tabs.contains(&WorkspaceThreadId(2)));
     }
+
+    #[test]
+    fn adjacent_tab_wraps_and_skips_archived_tabs() {
+        let mut tabs = WorkspaceTabs::new_with_initial(WorkspaceThreadId(1));
+        assert_eq!(tabs.adjacent_tab(TabDirection::Next), None);
+
+        tabs.restore_tab(WorkspaceThreadId(2), false);
+        tabs.restore_tab(WorkspaceThreadId(3), false);
+        tabs.restore_tab(WorkspaceThreadId(4), false);
+        tabs.archive_tab(WorkspaceThreadId(4));
+
+        assert_eq!(
+            tabs.adjacent_tab(TabDirection::Prev),
+            Some(WorkspaceThreadId(3))
+        );
+        tabs.activate(WorkspaceThreadId(3));
+        assert_eq!(
+            tabs.adjacent_tab(TabDirection::Next),
+            Some(WorkspaceThreadId(1))
+        );
+        assert_eq!(
+            tabs.adjacent_tab(TabDirection::Prev),
+            Some(WorkspaceThreadId(2))
+        );
+    }
 }
@@ -1,23 +1,35 @@
 use super::{
     AppearanceFonts, AppearanceTheme, ChatScrollAnchor, MainPane, OperationStatus,
-    PersistedWorkspaceThreadRunConfigOverride, ProjectId, RightPane, WorkspaceConversation,
-    WorkspaceId, WorkspaceStatus, WorkspaceTabs, WorkspaceThreadId,
+    PersistedWorkspaceThreadRunConfigOverride, ProjectGroupId, ProjectId, ProjectStatus, RightPane,
+    WorkspaceConversation, WorkspaceId, WorkspaceStatus, WorkspaceTabs, WorkspaceThreadId,
 };
-use crate::{SystemTaskKind, TaskIntentKind};
+use crate::{BranchNameTemplate, PullRequestTarget, SystemTaskKind, TaskIntentKind};
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{HashMap, HashSet, VecDeque},
     path::PathBuf,
 };
 
 #[derive(Clone, Debug, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct TelegramTopicBinding {
     pub message_thread_id: i64,
+    #[serde(rename = "workdir_id")]
     pub workspace_id: u64,
+    #[serde(rename = "task_id")]
     pub thread_id: u64,
     #[serde(default)]
     pub replayed_up_to: Option<u64>,
 }
 
+impl TelegramTopicBinding {
+    pub fn workdir_id(&self) -> WorkspaceId {
+        WorkspaceId::from_u64(self.workspace_id)
+    }
+
+    pub fn task_id(&self) -> WorkspaceThreadId {
+        WorkspaceThreadId::from_u64(self.thread_id)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Workspace {
     pub id: WorkspaceId,
@@ -28,6 +40,18 @@ pub struct Workspace {
     pub last_activity_at: Option<std::time::SystemTime>,
     pub archive_status: OperationStatus,
     pub branch_rename_status: OperationStatus,
+    /// Running while a `git stash push` / `git stash pop` is in flight for the worktree.
+    pub stash_status: OperationStatus,
+    pub commit_status: OperationStatus,
+}
+
+/// Branch name reported by the git layer when a worktree has a detached HEAD.
+pub const DETACHED_HEAD_BRANCH_NAME: &str = "HEAD";
+
+impl Workspace {
+    pub fn is_detached_head(&self) -> bool {
+        self.branch_name == DETACHED_HEAD_BRANCH_NAME
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -38,16 +62,112 @@ pub struct Project {
     pub slug: String,
     pub is_git: bool,
     pub expanded: bool,
+    /// Instructions sent as a system prompt with every agent turn in this project.
+    pub system_prompt: Option<String>,
+    /// Branch the remote treats as its default, detected when the project is added or loaded.
+    /// `None` until detection finishes, or when it could not be determined.
+    pub default_branch: Option<String>,
+    /// Directory new worktrees are created under (as `<root>/<project slug>/<workdir name>`).
+    /// `None` uses Luban's default worktrees directory.
+    pub worktree_root: Option<PathBuf>,
+    /// Template new branch names are rendered from, e.g. `alice/{name}`; supports `{name}`
+    /// (the workdir name), `{slug}` (the project slug) and `{user}` (the git user name).
+    /// `None` keeps the default `luban/{name}`.
+    pub branch_name_template: Option<String>,
+    pub status: ProjectStatus,
+    /// Run config new tasks in this project start with; unset fields use the global defaults.
+    pub agent_defaults: ProjectAgentDefaults,
+    /// Named command snippets offered in the terminal command palette; labels are unique.
+    pub saved_commands: Vec<ProjectSavedCommand>,
+    /// Environment variables set on agent processes started for this project. They override
+    /// variables inherited from Luban's own environment. Keys are unique.
+    pub agent_env: Vec<(String, String)>,
+    /// Git remote whose GitHub repository pull requests are looked up in; `None` lets `gh`
+    /// pick the repository as it does by default.
+    pub pr_remote: Option<String>,
+    /// Base branch pull requests of this project target; `None` matches any base.
+    pub pr_base_branch: Option<String>,
     pub create_workspace_status: OperationStatus,
     pub workspaces: Vec<Workspace>,
 }
 
+/// Per-project overrides of the global agent defaults, applied when a task is created.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ProjectAgentDefaults {
+    pub runner: Option<crate::AgentRunnerKind>,
+    pub model_id: Option<String>,
+    pub thinking_effort: Option<crate::ThinkingEffort>,
+    pub amp_mode: Option<String>,
+}
+
+impl ProjectAgentDefaults {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+/// A named terminal command saved for a project, e.g. `run tests` => `just test`.
+#[derive(Clone, Debug, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ProjectSavedCommand {
+    pub id: u64,
+    pub label: String,
+    pub command: String,
+}
+
+/// Branch assumed for the main workdir until the project's default branch is known.
+pub const FALLBACK_DEFAULT_BRANCH_NAME: &str = "main";
+
+impl Project {
+    /// The branch the project's main workdir tracks: the detected default branch, or `main`.
+    pub fn main_branch_name(&self) -> &str {
+        self.default_branch
+            .as_deref()
+            .unwrap_or(FALLBACK_DEFAULT_BRANCH_NAME)
+    }
+
+    /// Where pull requests of the project's workdirs are looked up.
+    pub fn pull_request_target(&self) -> PullRequestTarget {
+        PullRequestTarget {
+            remote: self.pr_remote.clone(),
+            base_branch: self.pr_base_branch.clone(),
+        }
+    }
+
+    /// The project's branch name template, if one is set.
+    pub fn branch_name_template(&self) -> Option<BranchNameTemplate> {
+        self.branch_name_template
+            .clone()
+            .map(|template| BranchNameTemplate {
+                template,
+                project_slug: self.slug.clone(),
+            })
+    }
+}
+
+/// A sidebar folder. A project belongs to at most one group; projects outside every group are
+/// shown ungrouped.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ProjectGroup {
+    pub id: ProjectGroupId,
+    pub name: String,
+    pub project_ids: Vec<ProjectId>,
+}
+
+/// A user-facing error recorded in `AppState::error_log`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ErrorLogEntry {
+    pub message: String,
+    pub created_at_unix_ms: u64,
+}
+
 #[derive(Clone, Debug)]
 pub struct AppState {
     pub(crate) next_project_id: u64,
     pub(crate) next_workspace_id: u64,
+    pub(crate) next_project_group_id: u64,
 
     pub projects: Vec<Project>,
+    pub project_groups: Vec<ProjectGroup>,
     pub main_pane: MainPane,
     pub right_pane: RightPane,
     pub sidebar_width: Option<u16>,
@@ -59,6 +179,10 @@ pub struct AppState {
     /// Per-runner model chosen by the user (e.g. Droid → "claude-opus-4-6").
     /// Takes precedence over `agent_default_model_id` when creating new tasks.
     pub(crate) agent_runner_default_models: HashMap<crate::AgentRunnerKind, String>,
+    pub(crate) agent_runner_prompt_prefixes: HashMap<crate::AgentRunnerKind, String>,
+    pub(crate) agent_runner_prompt_suffixes: HashMap<crate::AgentRunnerKind, String>,
+    /// Highest effort a model honours; turns on that model are clamped to it.
+    pub(crate) model_thinking_effort_caps: HashMap<String, crate::ThinkingEffort>,
     pub(crate) agent_default_thinking_effort: crate::ThinkingEffort,
     pub(crate) agent_default_runner: crate::AgentRunnerKind,
     pub(crate) agent_amp_mode: String,
@@ -66,6 +190,20 @@ pub struct AppState {
     pub(crate) agent_amp_enabled: bool,
     pub(crate) agent_claude_enabled: bool,
     pub(crate) agent_droid_enabled: bool,
+    /// Collapse consecutive reasoning items within a turn into a single entry.
+    pub(crate) agent_merge_reasoning_entries: bool,
+    /// Pause the prompt queue when a file change item fails to apply, since queued prompts may
+    /// assume the change landed.
+    pub(crate) agent_pause_queue_on_patch_failure: bool,
+    /// Number of trailing entries searched for an identical copy of an incoming agent item.
+    /// `0` keeps the default policy of comparing only against the latest entry with the same id.
+    pub(crate) agent_item_dedup_window: usize,
+    /// How many times a turn that fails with a transient error is re-run before the queue pauses.
+    pub(crate) agent_auto_retry_on_failure: u8,
+    /// Byte cap on a command execution's `aggregated_output`; `0` disables the cap.
+    pub(crate) agent_command_output_max_bytes: usize,
+    /// Idle days after which a workspace without an open PR or running turn is archived.
+    pub(crate) auto_archive_after_days: Option<u32>,
     pub conversations: HashMap<(WorkspaceId, WorkspaceThreadId), WorkspaceConversation>,
     pub workspace_tabs: HashMap<WorkspaceId, WorkspaceTabs>,
     pub dashboard_preview_workspace_id: Option<WorkspaceId>,
@@ -73,6 +211,8 @@ pub struct AppState {
     pub open_button_selection: Option<String>,
     pub sidebar_project_order: Vec<String>,
     pub last_error: Option<String>,
+    /// Recent errors, oldest first, capped at `MAX_ERROR_LOG_ENTRIES`.
+    pub error_log: VecDeque<ErrorLogEntry>,
     pub workspace_chat_scroll_y10: HashMap<(WorkspaceId, WorkspaceThreadId), i32>,
     pub workspace_chat_scroll_anchor: HashMap<(WorkspaceId, WorkspaceThreadId), ChatScrollAnchor>,
     pub workspace_unread_completions: HashSet<WorkspaceId>,
@@ -84,10 +224,14 @@ pub struct AppState {
     pub(crate) telegram_enabled: bool,
     pub(crate) telegram_bot_token: Option<String>,
     pub(crate) telegram_bot_username: Option<String>,
-    pub(crate) telegram_paired_chat_id: Option<i64>,
+    pub(crate) telegram_paired_chat_ids: Vec<i64>,
     pub(crate) telegram_config_rev: u64,
     pub(crate) telegram_last_error: Option<String>,
     pub(crate) telegram_topic_bindings: HashMap<i64, TelegramTopicBinding>,
+    pub(crate) webhook_url: Option<String>,
+    pub(crate) webhook_secret: Option<String>,
+    pub(crate) webhook_config_rev: u64,
+    pub(crate) webhook_last_error: Option<String>,
 }
 
 impl AppState {
@@ -107,6 +251,30 @@ impl AppState {
         self.agent_droid_enabled
     }
 
+    pub fn agent_merge_reasoning_entries(&self) -> bool {
+        self.agent_merge_reasoning_entries
+    }
+
+    pub fn agent_pause_queue_on_patch_failure(&self) -> bool {
+        self.agent_pause_queue_on_patch_failure
+    }
+
+    pub fn agent_item_dedup_window(&self) -> usize {
+        self.agent_item_dedup_window
+    }
+
+    pub fn agent_auto_retry_on_failure(&self) -> u8 {
+        self.agent_auto_retry_on_failure
+    }
+
+    pub fn agent_command_output_max_bytes(&self) -> usize {
+        self.agent_command_output_max_bytes
+    }
+
+    pub fn auto_archive_after_days(&self) -> Option<u32> {
+        self.auto_archive_after_days
+    }
+
     pub fn agent_default_model_id(&self) -> &str {
         &self.agent_default_model_id
     }
@@ -115,6 +283,18 @@ impl AppState {
         &self.agent_runner_default_models
     }
 
+    pub fn agent_runner_prompt_prefixes(&self) -> &HashMap<crate::AgentRunnerKind, String> {
+        &self.agent_runner_prompt_prefixes
+    }
+
+    pub fn agent_runner_prompt_suffixes(&self) -> &HashMap<crate::AgentRunnerKind, String> {
+        &self.agent_runner_prompt_suffixes
+    }
+
+    pub fn model_thinking_effort_caps(&self) -> &HashMap<String, crate::ThinkingEffort> {
+        &self.model_thinking_effort_caps
+    }
+
     pub fn agent_default_thinking_effort(&self) -> crate::ThinkingEffort {
         self.agent_default_thinking_effort
     }
@@ -139,8 +319,8 @@ impl AppState {
         self.telegram_bot_username.as_deref()
     }
 
-    pub fn telegram_paired_chat_id(&self) -> Option<i64> {
-        self.telegram_paired_chat_id
+    pub fn telegram_paired_chat_ids(&self) -> &[i64] {
+        &self.telegram_paired_chat_ids
     }
 
     pub fn telegram_config_rev(&self) -> u64 {
@@ -154,4 +334,21 @@ impl AppState {
     pub fn telegram_topic_bindings(&self) -> &HashMap<i64, TelegramTopicBinding> {
         &self.telegram_topic_bindings
     }
+
+    /// The webhook notification sink is enabled whenever a URL is configured.
+    pub fn webhook_url(&self) -> Option<&str> {
+        self.webhook_url.as_deref()
+    }
+
+    pub fn webhook_secret(&self) -> Option<&str> {
+        self.webhook_secret.as_deref()
+    }
+
+    pub fn webhook_config_rev(&self) -> u64 {
+        self.webhook_config_rev
+    }
+
+    pub fn webhook_last_error(&self) -> Option<&str> {
+        self.webhook_last_error.as_deref()
+    }
 }
@@ -6,14 +6,18 @@ pub enum SystemTaskKind {
     RenameBranch,
     AutoTitleThread,
     AutoUpdateTaskStatus,
+    PrDescription,
+    ConversationSummary,
 }
 
 impl SystemTaskKind {
-    pub const ALL: [SystemTaskKind; 4] = [
+    pub const ALL: [SystemTaskKind; 6] = [
         SystemTaskKind::InferType,
         SystemTaskKind::RenameBranch,
         SystemTaskKind::AutoTitleThread,
         SystemTaskKind::AutoUpdateTaskStatus,
+        SystemTaskKind::PrDescription,
+        SystemTaskKind::ConversationSummary,
     ];
 
     pub fn as_key(self) -> &'static str {
@@ -22,6 +26,8 @@ impl SystemTaskKind {
             SystemTaskKind::RenameBranch => "rename-branch",
             SystemTaskKind::AutoTitleThread => "auto-title-thread",
             SystemTaskKind::AutoUpdateTaskStatus => "auto-update-task-status",
+            SystemTaskKind::PrDescription => "pr-description",
+            SystemTaskKind::ConversationSummary => "conversation-summary",
         }
     }
 
@@ -31,10 +37,33 @@ impl SystemTaskKind {
             SystemTaskKind::RenameBranch => "Rename Branch",
             SystemTaskKind::AutoTitleThread => "Auto Title Thread",
             SystemTaskKind::AutoUpdateTaskStatus => "Suggest Task Status",
+            SystemTaskKind::PrDescription => "PR Description",
+            SystemTaskKind::ConversationSummary => "Conversation Summary",
         }
     }
 }
 
+/// Composes the system prompt sent with an agent turn from its global, project, and thread
+/// layers. Layers are emitted from least to most specific so that, on conflict, thread
+/// instructions win over project ones and project instructions win over global ones. Blank
+/// layers are skipped; returns `None` when every layer is blank.
+pub fn compose_agent_system_prompt(
+    global: Option<&str>,
+    project: Option<&str>,
+    thread: Option<&str>,
+) -> Option<String> {
+    let layers = [global, project, thread]
+        .into_iter()
+        .flatten()
+        .map(str::trim)
+        .filter(|layer| !layer.is_empty())
+        .collect::<Vec<_>>();
+    if layers.is_empty() {
+        return None;
+    }
+    Some(layers.join("\n\n"))
+}
+
 pub fn default_system_prompt_templates() -> HashMap<SystemTaskKind, String> {
     let mut out = HashMap::new();
     for kind in SystemTaskKind::ALL {
@@ -168,5 +197,67 @@ explanation_markdown rules:
 "#
             .to_owned()
         }
+        SystemTaskKind::PrDescription => {
+            r###"You are writing a pull request description for the changes in a working directory.
+
+Rules:
+- Do NOT run commands.
+- Do NOT modify files.
+- Output ONLY the pull request body as GitHub-flavored markdown. Do NOT wrap it in code fences.
+- Start with a "## Summary" section of 1-3 sentences explaining what the change does and why.
+- Follow with a "## Changes" section listing the changed files by path with a short note for each.
+- Base the description on the changed files and the agent messages in the input. Do NOT invent facts beyond the input.
+- Do NOT include any personally identifying information.
+
+Input:
+{{task_input}}
+
+Context (JSON):
+{{context_json}}
+"###
+            .to_owned()
+        }
+        SystemTaskKind::ConversationSummary => {
+            r###"You are summarizing the earlier part of a coding task conversation so it can be collapsed.
+
+Rules:
+- Do NOT run commands.
+- Do NOT modify files.
+- Output ONLY the summary as GitHub-flavored markdown. Do NOT wrap it in code fences.
+- Capture what the user asked for, the decisions made, the files changed, commands that mattered (and whether they passed), and anything left unresolved.
+- Prefer short bullets; keep it under 400 words.
+- Base the summary only on the transcript in the input. Do NOT invent facts beyond the input.
+
+Input:
+{{task_input}}
+
+Context (JSON):
+{{context_json}}
+"###
+            .to_owned()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compose_agent_system_prompt_orders_layers_and_skips_blank_ones() {
+        assert_eq!(
+            compose_agent_system_prompt(Some("global"), Some("project"), Some("thread")).as_deref(),
+            Some("global\n\nproject\n\nthread")
+        );
+        assert_eq!(
+            compose_agent_system_prompt(Some(" global \n"), Some("  \n"), Some("thread"))
+                .as_deref(),
+            Some("global\n\nthread")
+        );
+        assert_eq!(
+            compose_agent_system_prompt(None, Some("project"), None).as_deref(),
+            Some("project")
+        );
+        assert_eq!(compose_agent_system_prompt(None, Some(""), None), None);
     }
 }
@@ -1,5 +1,6 @@
 use crate::branch_watch::BranchWatchHandle;
 use anyhow::Context as _;
+use base64::Engine as _;
 use luban_api::{
     AppSnapshot, ConversationSnapshot, PullRequestCiState, PullRequestSnapshot, PullRequestState,
     ThreadsSnapshot, WorkspaceTabsSnapshot, WsServerMessage,
@@ -7,8 +8,9 @@ use luban_api::{
 use luban_backend::{GitWorkspaceService, SqliteStoreOptions};
 use luban_domain::{
     Action, AppState, AttachmentKind, AttachmentRef, CodexThreadEvent, CodexThreadItem,
-    ConversationEntry, ConversationThreadMeta, Effect, OpenTarget, OperationStatus,
-    ProjectWorkspaceService, PullRequestCiState as DomainPullRequestCiState, PullRequestInfo,
+    ConversationEntry, ConversationRetentionPolicy, ConversationThreadMeta, Effect, LastCommitInfo,
+    OpenTarget, OperationStatus, ProjectStatus, ProjectWorkspaceService,
+    PullRequestCiState as DomainPullRequestCiState, PullRequestInfo,
     PullRequestState as DomainPullRequestState, ThinkingEffort, WorkspaceId, WorkspaceThreadId,
 };
 use rand::RngCore as _;
@@ -27,9 +29,36 @@ use tokio::sync::{broadcast, mpsc, oneshot};
 #[derive(Clone)]
 pub struct EngineHandle {
     tx: mpsc::Sender<EngineCommand>,
+    /// Set once `Engine::bootstrap` has finished; commands sent earlier wait behind it.
+    bootstrapped: Arc<AtomicBool>,
+    started_at: Instant,
+}
+
+/// Lightweight engine status for health probes.
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct EngineStatus {
+    pub rev: u64,
+    pub projects: u64,
+    pub active_turns: u64,
+    pub uptime_secs: u64,
 }
 
 impl EngineHandle {
+    /// Returns `None` until the engine has bootstrapped.
+    pub async fn status(&self) -> anyhow::Result<Option<EngineStatus>> {
+        if !self.bootstrapped.load(Ordering::Acquire) {
+            return Ok(None);
+        }
+        let (tx, rx) = oneshot::channel();
+        self.tx
+            .send(EngineCommand::GetStatus { reply: tx })
+            .await
+            .context("engine unavailable")?;
+        let mut status = rx.await.context("engine stopped")??;
+        status.uptime_secs = self.started_at.elapsed().as_secs();
+        Ok(Some(status))
+    }
+
     pub async fn current_rev(&self) -> anyhow::Result<u64> {
         let (tx, rx) = oneshot::channel();
         self.tx
@@ -69,6 +98,32 @@ impl EngineHandle {
         thread_id: luban_api::WorkspaceThreadId,
         before: Option<u64>,
         limit: Option<u64>,
+    ) -> anyhow::Result<ConversationSnapshot> {
+        self.send_conversation_snapshot(workspace_id, thread_id, before, limit, None)
+            .await
+    }
+
+    /// Like `conversation_snapshot`, but `before`, `limit` and the returned `entries_start` page
+    /// over the entries `filter` keeps.
+    pub async fn filtered_conversation_snapshot(
+        &self,
+        workspace_id: luban_api::WorkspaceId,
+        thread_id: luban_api::WorkspaceThreadId,
+        before: Option<u64>,
+        limit: Option<u64>,
+        filter: ConversationEntryFilter,
+    ) -> anyhow::Result<ConversationSnapshot> {
+        self.send_conversation_snapshot(workspace_id, thread_id, before, limit, Some(filter))
+            .await
+    }
+
+    async fn send_conversation_snapshot(
+        &self,
+        workspace_id: luban_api::WorkspaceId,
+        thread_id: luban_api::WorkspaceThreadId,
+        before: Option<u64>,
+        limit: Option<u64>,
+        filter: Option<ConversationEntryFilter>,
     ) -> anyhow::Result<ConversationSnapshot> {
         let (tx, rx) = oneshot::channel();
         self.tx
@@ -77,6 +132,7 @@ impl EngineHandle {
                 thread_id,
                 before,
                 limit,
+                filter,
                 reply: tx,
             })
             .await
@@ -99,6 +155,23 @@ impl EngineHandle {
         rx.await.context("engine stopped")?
     }
 
+    pub async fn prompt_history(
+        &self,
+        workspace_id: luban_api::WorkspaceId,
+        thread_id: luban_api::WorkspaceThreadId,
+    ) -> anyhow::Result<Vec<String>> {
+        let (tx, rx) = oneshot::channel();
+        self.tx
+            .send(EngineCommand::GetPromptHistory {
+                workspace_id,
+                thread_id,
+                reply: tx,
+            })
+            .await
+            .context("engine unavailable")?;
+        rx.await.context("engine stopped")?
+    }
+
     pub async fn starred_tasks_snapshot(
         &self,
     ) -> anyhow::Result<std::collections::HashSet<(u64, u64)>> {
@@ -110,6 +183,31 @@ impl EngineHandle {
         rx.await.context("engine stopped")?
     }
 
+    pub async fn starred_tasks_detailed(
+        &self,
+    ) -> Result<Vec<luban_api::TaskSummarySnapshot>, String> {
+        let (tx, rx) = oneshot::channel();
+        if self
+            .tx
+            .send(EngineCommand::GetStarredTasksDetailed { reply: tx })
+            .await
+            .is_err()
+        {
+            return Err("engine unavailable".to_owned());
+        }
+        rx.await
+            .unwrap_or_else(|_| Err("engine stopped".to_owned()))
+    }
+
+    pub async fn webhook_runtime_config(&self) -> anyhow::Result<WebhookRuntimeConfig> {
+        let (tx, rx) = oneshot::channel();
+        self.tx
+            .send(EngineCommand::GetWebhookRuntimeConfig { reply: tx })
+            .await
+            .context("engine unavailable")?;
+        rx.await.context("engine stopped")
+    }
+
     pub async fn telegram_runtime_config(&self) -> anyhow::Result<TelegramRuntimeConfig> {
         let (tx, rx) = oneshot::channel();
         self.tx
@@ -141,6 +239,52 @@ impl EngineHandle {
             .unwrap_or_else(|_| Err("engine stopped".to_owned()))
     }
 
+    pub async fn import_attachments_from_dir(
+        &self,
+        workspace_id: luban_api::WorkspaceId,
+        dir: PathBuf,
+        include: Vec<String>,
+        exclude: Vec<String>,
+    ) -> Result<ImportedAttachments, String> {
+        let (tx, rx) = oneshot::channel();
+        if self
+            .tx
+            .send(EngineCommand::ImportAttachmentsFromDir {
+                workspace_id,
+                dir,
+                include,
+                exclude,
+                reply: tx,
+            })
+            .await
+            .is_err()
+        {
+            return Err("engine unavailable".to_owned());
+        }
+        rx.await
+            .unwrap_or_else(|_| Err("engine stopped".to_owned()))
+    }
+
+    pub async fn audit_worktrees(
+        &self,
+        project_id: luban_api::ProjectId,
+    ) -> Result<luban_api::WorktreeAuditSnapshot, String> {
+        let (tx, rx) = oneshot::channel();
+        if self
+            .tx
+            .send(EngineCommand::AuditWorktrees {
+                project_id,
+                reply: tx,
+            })
+            .await
+            .is_err()
+        {
+            return Err("engine unavailable".to_owned());
+        }
+        rx.await
+            .unwrap_or_else(|_| Err("engine stopped".to_owned()))
+    }
+
     pub async fn dispatch_domain_action(&self, action: Action) -> anyhow::Result<()> {
         self.tx
             .send(EngineCommand::DispatchAction {
@@ -151,6 +295,17 @@ impl EngineHandle {
         Ok(())
     }
 
+    /// Stops the engine: new commands are refused, commands already queued are handled, pending
+    /// debounced notes/draft writes are flushed, and the call returns once all of that persisted.
+    pub async fn shutdown(&self) -> anyhow::Result<()> {
+        let (tx, rx) = oneshot::channel();
+        self.tx
+            .send(EngineCommand::Shutdown { reply: tx })
+            .await
+            .context("engine unavailable")?;
+        rx.await.context("engine stopped")
+    }
+
     pub async fn apply_client_action(
         &self,
         request_id: String,
@@ -181,6 +336,10 @@ pub enum EngineCommand {
     GetAppSnapshot {
         reply: oneshot::Sender<anyhow::Result<AppSnapshot>>,
     },
+    /// Replies with `uptime_secs` left at zero; `EngineHandle::status` fills it in.
+    GetStatus {
+        reply: oneshot::Sender<anyhow::Result<EngineStatus>>,
+    },
     GetThreadsSnapshot {
         workspace_id: luban_api::WorkspaceId,
         reply: oneshot::Sender<anyhow::Result<ThreadsSnapshot>>,
@@ -190,15 +349,30 @@ pub enum EngineCommand {
         thread_id: luban_api::WorkspaceThreadId,
         before: Option<u64>,
         limit: Option<u64>,
+        filter: Option<ConversationEntryFilter>,
         reply: oneshot::Sender<anyhow::Result<ConversationSnapshot>>,
     },
     GetWorkspaceWorktreePath {
         workspace_id: luban_api::WorkspaceId,
         reply: oneshot::Sender<anyhow::Result<Option<PathBuf>>>,
     },
+    /// Past user prompts of a task for up-arrow recall, newest first and capped at
+    /// `PROMPT_HISTORY_LIMIT`.
+    GetPromptHistory {
+        workspace_id: luban_api::WorkspaceId,
+        thread_id: luban_api::WorkspaceThreadId,
+        reply: oneshot::Sender<anyhow::Result<Vec<String>>>,
+    },
     GetStarredTasks {
         reply: oneshot::Sender<anyhow::Result<std::collections::HashSet<(u64, u64)>>>,
     },
+    /// Full summaries of starred tasks across all workspaces, most recently updated first.
+    GetStarredTasksDetailed {
+        reply: oneshot::Sender<Result<Vec<luban_api::TaskSummarySnapshot>, String>>,
+    },
+    GetWebhookRuntimeConfig {
+        reply: oneshot::Sender<WebhookRuntimeConfig>,
+    },
     GetTelegramRuntimeConfig {
         reply: oneshot::Sender<anyhow::Result<TelegramRuntimeConfig>>,
     },
@@ -212,20 +386,46 @@ pub enum EngineCommand {
         action: luban_api::ClientAction,
         reply: oneshot::Sender<Result<u64, String>>,
     },
+    /// Recursively imports a directory as workspace attachments, filtered by include/exclude
+    /// globs matched against paths relative to `dir`.
+    ImportAttachmentsFromDir {
+        workspace_id: luban_api::WorkspaceId,
+        dir: PathBuf,
+        include: Vec<String>,
+        exclude: Vec<String>,
+        reply: oneshot::Sender<Result<ImportedAttachments, String>>,
+    },
+    /// Compares a project's tracked workspaces against `git worktree list`.
+    AuditWorktrees {
+        project_id: luban_api::ProjectId,
+        reply: oneshot::Sender<Result<luban_api::WorktreeAuditSnapshot, String>>,
+    },
     DispatchAction {
         action: Box<Action>,
     },
+    /// Handled by the engine run loop; see `EngineHandle::shutdown`.
+    Shutdown {
+        reply: oneshot::Sender<()>,
+    },
     AutoArchiveWorkspace {
         workspace_id: WorkspaceId,
     },
     RefreshPullRequests {
         workspace_id: Option<WorkspaceId>,
     },
+    WorktreeDirtyUpdated {
+        workspace_id: WorkspaceId,
+        dirty: bool,
+    },
+    /// `Err` means the lookup itself failed (e.g. a network blip or rate limit), as opposed to
+    /// `Ok(None)` for a branch without a pull request.
     PullRequestInfoUpdated {
         workspace_id: WorkspaceId,
-        info: Option<PullRequestInfo>,
+        info: Result<Option<PullRequestInfo>, String>,
     },
     PruneArchivedTasks,
+    PruneConversationHistory,
+    ArchiveInactiveWorkspaces,
     WorkspaceThreadsInvalidated {
         workspace_id: WorkspaceId,
     },
@@ -233,15 +433,44 @@ pub enum EngineCommand {
         workspace_id: WorkspaceId,
         branch_name: String,
     },
+    LastCommitUpdated {
+        workspace_id: WorkspaceId,
+        last_commit: Option<LastCommitInfo>,
+    },
+    FlushConversationNotes {
+        workspace_id: WorkspaceId,
+        thread_id: WorkspaceThreadId,
+        generation: u64,
+    },
+    FlushConversationDraft {
+        workspace_id: WorkspaceId,
+        thread_id: WorkspaceThreadId,
+        generation: u64,
+    },
 }
 
 #[derive(Clone, Debug)]
 struct PullRequestCacheEntry {
     info: Option<PullRequestInfo>,
+    /// Why the last lookup failed; cleared by the next successful one.
+    error: Option<String>,
     next_refresh_at: Instant,
     consecutive_empty: u32,
+    consecutive_errors: u32,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct WorktreeDirtyCacheEntry {
+    dirty: bool,
+    checked_at: Instant,
 }
 
+const CONVERSATION_NOTES_PERSIST_DEBOUNCE: Duration = Duration::from_millis(500);
+const CONVERSATION_DRAFT_PERSIST_DEBOUNCE: Duration = Duration::from_millis(500);
+const PROMPT_HISTORY_LIMIT: usize = 100;
+const CONVERSATION_SEARCH_MAX_RESULTS: usize = 200;
+const ATTACHMENT_THUMBNAIL_CACHE_LIMIT: usize = 512;
+
 const PULL_REQUEST_REFRESH_TICK_INTERVAL: Duration = Duration::from_secs(30);
 const PULL_REQUEST_REFRESH_MAX_PER_TICK: usize = 2;
 const PULL_REQUEST_REFRESH_JITTER_WINDOW_SECS: u64 = 10;
@@ -256,24 +485,80 @@ const PULL_REQUEST_REFRESH_INTERVAL_EMPTY_INITIAL: Duration = Duration::from_sec
 const PULL_REQUEST_REFRESH_INTERVAL_EMPTY_MEDIUM: Duration = Duration::from_secs(3 * 60);
 const PULL_REQUEST_REFRESH_INTERVAL_EMPTY_MAX: Duration = Duration::from_secs(10 * 60);
 
+const PULL_REQUEST_REFRESH_INTERVAL_ERROR_INITIAL: Duration = Duration::from_secs(15);
+const PULL_REQUEST_REFRESH_INTERVAL_ERROR_MAX: Duration = Duration::from_secs(2 * 60);
+
+const GIT_FETCH_BEFORE_PULL_REQUEST_MIN_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+const WORKTREE_DIRTY_REFRESH_MIN_INTERVAL: Duration = Duration::from_secs(3);
+
 const TASK_ARCHIVE_AFTER_SECONDS: u64 = 7 * 24 * 60 * 60;
 const TASK_PURGE_AFTER_SECONDS: u64 = 2 * TASK_ARCHIVE_AFTER_SECONDS;
 const TASK_PURGE_TICK_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
 const TASK_PURGE_STARTUP_DELAY: Duration = Duration::from_secs(60);
 
+const INACTIVE_WORKSPACE_ARCHIVE_TICK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const INACTIVE_WORKSPACE_ARCHIVE_STARTUP_DELAY: Duration = Duration::from_secs(5 * 60);
+
+const CONVERSATION_RETENTION_TICK_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+const CONVERSATION_RETENTION_STARTUP_DELAY: Duration = Duration::from_secs(2 * 60);
+
+const AUTO_TITLE_MAX_ATTEMPTS: usize = 3;
+const AUTO_TITLE_RETRY_DELAY: Duration = Duration::from_millis(750);
+
+/// Asks `suggest` for a thread title up to `AUTO_TITLE_MAX_ATTEMPTS` times, sleeping
+/// `retry_delay` between attempts. Failed or empty suggestions fall back to a title derived from
+/// the first user message, which may itself be empty.
+fn suggest_thread_title_with_retries(
+    input: &str,
+    retry_delay: Duration,
+    mut suggest: impl FnMut() -> Result<String, String>,
+) -> String {
+    for attempt in 0..AUTO_TITLE_MAX_ATTEMPTS {
+        if attempt > 0 {
+            std::thread::sleep(retry_delay);
+        }
+        match suggest() {
+            Ok(suggested) => {
+                let title = luban_domain::derive_thread_title(&suggested);
+                if !title.is_empty() {
+                    return title;
+                }
+                tracing::debug!(attempt, "auto title suggestion was empty");
+            }
+            Err(err) => tracing::debug!(attempt, error = %err, "auto title suggestion failed"),
+        }
+    }
+    luban_domain::derive_thread_title(input)
+}
+
 fn pull_request_refresh_jitter(workspace_id: WorkspaceId) -> Duration {
     let window = PULL_REQUEST_REFRESH_JITTER_WINDOW_SECS.max(1);
     Duration::from_secs(workspace_id.as_u64() % window)
 }
 
+/// Schedules the next pull request lookup for a workspace and returns it together with the
+/// updated `consecutive_empty` and `consecutive_errors` counters. Failed lookups retry on their
+/// own short, doubling schedule and leave the empty-result backoff untouched.
 fn pull_request_next_refresh_at(
     workspace_id: WorkspaceId,
     now: Instant,
     previous: Option<&PullRequestCacheEntry>,
-    info: Option<&PullRequestInfo>,
-) -> (Instant, u32) {
-    let (interval, consecutive_empty) = match info {
-        Some(pr) => {
+    info: Result<Option<&PullRequestInfo>, &str>,
+) -> (Instant, u32, u32) {
+    let (interval, consecutive_empty, consecutive_errors) = match info {
+        Err(_) => {
+            let consecutive_errors = previous
+                .map(|e| e.consecutive_errors)
+                .unwrap_or(0)
+                .saturating_add(1);
+            let interval = PULL_REQUEST_REFRESH_INTERVAL_ERROR_INITIAL
+                .saturating_mul(1 << (consecutive_errors - 1).min(8))
+                .min(PULL_REQUEST_REFRESH_INTERVAL_ERROR_MAX);
+            let consecutive_empty = previous.map(|e| e.consecutive_empty).unwrap_or(0);
+            (interval, consecutive_empty, consecutive_errors)
+        }
+        Ok(Some(pr)) => {
             let interval = if pr.state != DomainPullRequestState::Open {
                 PULL_REQUEST_REFRESH_INTERVAL_CLOSED
             } else {
@@ -294,9 +579,9 @@ fn pull_request_next_refresh_at(
                     None => PULL_REQUEST_REFRESH_INTERVAL_OPEN_CI_UNKNOWN,
                 }
             };
-            (interval, 0)
+            (interval, 0, 0)
         }
-        None => {
+        Ok(None) => {
             let prev = previous.map(|e| e.consecutive_empty).unwrap_or(0);
             let consecutive_empty = prev.saturating_add(1);
             let interval = match consecutive_empty {
@@ -304,7 +589,7 @@ fn pull_request_next_refresh_at(
                 2 => PULL_REQUEST_REFRESH_INTERVAL_EMPTY_MEDIUM,
                 _ => PULL_REQUEST_REFRESH_INTERVAL_EMPTY_MAX,
             };
-            (interval, consecutive_empty)
+            (interval, consecutive_empty, 0)
         }
     };
 
@@ -314,7 +599,7 @@ fn pull_request_next_refresh_at(
         .checked_add(pull_request_refresh_jitter(workspace_id))
         .unwrap_or(now);
 
-    (next_refresh_at, consecutive_empty)
+    (next_refresh_at, consecutive_empty, consecutive_errors)
 }
 
 pub struct Engine {
@@ -329,17 +614,98 @@ pub struct Engine {
     pull_requests_in_flight: HashSet<WorkspaceId>,
     workspace_threads_cache: HashMap<WorkspaceId, Vec<ConversationThreadMeta>>,
     auto_archive_workspaces: HashSet<WorkspaceId>,
-    telegram_pairing: Option<TelegramPairingState>,
+    pending_notes_flushes: HashMap<(WorkspaceId, WorkspaceThreadId), u64>,
+    pending_draft_flushes: HashMap<(WorkspaceId, WorkspaceThreadId), u64>,
+    /// Engine rev at which each conversation last published a change; used to reject mutating
+    /// actions sent against an older `ConversationSnapshot.rev`.
+    conversation_revs: HashMap<(WorkspaceId, WorkspaceThreadId), u64>,
+    /// Last `git fetch` started ahead of a PR refresh, per workspace.
+    last_git_fetch_at: HashMap<WorkspaceId, Instant>,
+    /// Cached uncommitted-changes flag per workspace, surfaced as
+    /// `ConversationSnapshot.worktree_dirty`.
+    worktree_dirty: HashMap<WorkspaceId, WorktreeDirtyCacheEntry>,
+    worktree_dirty_in_flight: HashSet<WorkspaceId>,
+    /// Workspaces whose in-flight check may already be stale and must run again once it lands.
+    worktree_dirty_rerun: HashSet<WorkspaceId>,
+    /// Tip commit per git workspace, refreshed when the branch watcher observes a branch change.
+    last_commits: HashMap<WorkspaceId, Option<LastCommitInfo>>,
+    /// Runs holding a concurrent turn slot, keyed by task.
+    agent_turn_slots: HashMap<(WorkspaceId, WorkspaceThreadId), u64>,
+    /// Turns waiting for a slot, oldest first.
+    deferred_agent_turns: VecDeque<DeferredAgentTurn>,
+    request_cancellations: RequestCancellations,
+    attachment_thumbnails: AttachmentThumbnails,
+    /// Outstanding pairing codes; each one can be redeemed by a single chat.
+    telegram_pairings: Vec<TelegramPairingState>,
+    config: EngineConfig,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct EngineConfig {
+    pub conversation_load: crate::ConversationLoadConfig,
+    /// Directory added as a project on first launch when no projects are persisted.
+    pub seed_project_path: Option<PathBuf>,
+    /// Run `git fetch` before PR lookups, at most once per
+    /// `GIT_FETCH_BEFORE_PULL_REQUEST_MIN_INTERVAL` per workspace.
+    pub fetch_before_pull_request_refresh: bool,
+    /// Client action `type` names rejected before they are applied.
+    pub denied_client_actions: HashSet<String>,
+    /// Agent turns allowed to run at once across all tasks; further turns wait in FIFO order.
+    /// `0` means unlimited.
+    pub max_concurrent_turns: usize,
+    /// Ceiling for each attachment and for the attachments of one message together, checked
+    /// before a message is sent, queued or its queued prompt updated. `0` means unlimited.
+    pub max_attachment_bytes: u64,
+    /// Stored conversation history beyond this is pruned periodically. Disabled by default.
+    pub conversation_retention: ConversationRetentionPolicy,
+}
+
+/// Narrows a conversation page to agent items of the given kinds (and user messages when
+/// requested). Paging then runs over the matching entries only.
+#[derive(Clone, Debug)]
+pub struct ConversationEntryFilter {
+    pub kinds: Vec<luban_api::AgentItemKind>,
+    pub include_user_messages: bool,
+}
+
+impl ConversationEntryFilter {
+    pub fn keeps(&self, entry: &luban_api::ConversationEntry) -> bool {
+        match entry {
+            luban_api::ConversationEntry::AgentEvent(luban_api::AgentEventEntry {
+                event: luban_api::AgentEvent::Item(item),
+                ..
+            }) => self.kinds.contains(&item.kind),
+            luban_api::ConversationEntry::UserEvent(luban_api::UserEventEntry {
+                event: luban_api::UserEvent::Message(_),
+                ..
+            }) => self.include_user_messages,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ImportedAttachments {
+    pub attachments: Vec<luban_api::AttachmentRef>,
+    /// Per-file notes for matched files that were not imported.
+    pub skipped: Vec<String>,
 }
 
 #[derive(Clone, Debug)]
 pub struct TelegramRuntimeConfig {
     pub enabled: bool,
     pub bot_token: Option<String>,
-    pub paired_chat_id: Option<i64>,
+    pub paired_chat_ids: Vec<i64>,
     pub topic_bindings: Vec<TelegramTopicBindingRoute>,
 }
 
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct WebhookRuntimeConfig {
+    /// `None` while the webhook sink is disabled.
+    pub url: Option<String>,
+    pub secret: Option<String>,
+}
+
 #[derive(Clone, Debug)]
 pub struct TelegramTopicBindingRoute {
     pub message_thread_id: i64,
@@ -360,9 +726,148 @@ struct CancelFlagEntry {
     flag: Arc<AtomicBool>,
 }
 
+/// A `RunAgentTurn` effect held back until a concurrent turn slot frees up.
+struct DeferredAgentTurn {
+    workspace_id: WorkspaceId,
+    thread_id: WorkspaceThreadId,
+    run_id: u64,
+    effect: Effect,
+}
+
+/// Cancel flags for request-scoped background operations, keyed by client request id.
+#[derive(Clone, Default)]
+struct RequestCancellations {
+    flags: Arc<std::sync::Mutex<HashMap<String, Arc<AtomicBool>>>>,
+}
+
+impl RequestCancellations {
+    fn register(&self, request_id: &str) -> Arc<AtomicBool> {
+        let flag = Arc::new(AtomicBool::new(false));
+        if let Ok(mut flags) = self.flags.lock() {
+            flags.insert(request_id.to_owned(), flag.clone());
+        }
+        flag
+    }
+
+    fn cancel(&self, request_id: &str) -> bool {
+        let Ok(flags) = self.flags.lock() else {
+            return false;
+        };
+        let Some(flag) = flags.get(request_id) else {
+            return false;
+        };
+        flag.store(true, Ordering::SeqCst);
+        true
+    }
+
+    fn finish(&self, request_id: &str, flag: &Arc<AtomicBool>) {
+        if let Ok(mut flags) = self.flags.lock()
+            && flags
+                .get(request_id)
+                .is_some_and(|current| Arc::ptr_eq(current, flag))
+        {
+            flags.remove(request_id);
+        }
+    }
+}
+
+/// Encoded image attachment previews keyed by attachment id. `None` records attachments that
+/// could not be decoded, so they are not re-read on every snapshot. Holds at most
+/// `ATTACHMENT_THUMBNAIL_CACHE_LIMIT` entries, evicting the oldest first.
+#[derive(Clone, Default)]
+struct AttachmentThumbnails {
+    cache: Arc<std::sync::Mutex<AttachmentThumbnailCache>>,
+}
+
+#[derive(Default)]
+struct AttachmentThumbnailCache {
+    entries: HashMap<String, Option<String>>,
+    order: VecDeque<String>,
+}
+
+impl AttachmentThumbnails {
+    fn get(&self, attachment_id: &str) -> Option<Option<String>> {
+        self.cache.lock().ok()?.entries.get(attachment_id).cloned()
+    }
+
+    fn insert(&self, attachment_id: String, thumbnail: Option<String>) {
+        let Ok(mut cache) = self.cache.lock() else {
+            return;
+        };
+        if cache
+            .entries
+            .insert(attachment_id.clone(), thumbnail)
+            .is_none()
+        {
+            cache.order.push_back(attachment_id);
+        }
+        while cache.order.len() > ATTACHMENT_THUMBNAIL_CACHE_LIMIT {
+            if let Some(oldest) = cache.order.pop_front() {
+                cache.entries.remove(&oldest);
+            }
+        }
+    }
+}
+
+fn conversation_snapshot_attachments_mut(
+    snapshot: &mut ConversationSnapshot,
+) -> impl Iterator<Item = &mut luban_api::AttachmentRef> {
+    let messages = snapshot
+        .entries
+        .iter_mut()
+        .filter_map(|entry| match entry {
+            luban_api::ConversationEntry::UserEvent(luban_api::UserEventEntry {
+                event: luban_api::UserEvent::Message(message),
+                ..
+            }) => Some(message.attachments.iter_mut()),
+            _ => None,
+        })
+        .flatten();
+    let queued = snapshot
+        .pending_prompts
+        .iter_mut()
+        .flat_map(|prompt| prompt.attachments.iter_mut());
+    messages.chain(queued)
+}
+
+/// Event sender for a request-scoped background operation. Once the request is canceled via
+/// `ClientAction::CancelRequest`, pending work is skipped and its events are dropped.
+struct RequestEvents {
+    events: broadcast::Sender<WsServerMessage>,
+    request_id: String,
+    canceled: Arc<AtomicBool>,
+    registry: RequestCancellations,
+}
+
+impl RequestEvents {
+    fn is_canceled(&self) -> bool {
+        self.canceled.load(Ordering::SeqCst)
+    }
+
+    fn send(&self, message: WsServerMessage) -> bool {
+        if self.is_canceled() {
+            return false;
+        }
+        self.events.send(message).is_ok()
+    }
+}
+
+impl Drop for RequestEvents {
+    fn drop(&mut self) {
+        self.registry.finish(&self.request_id, &self.canceled);
+    }
+}
+
 impl Engine {
     pub fn start(
         services: Arc<dyn ProjectWorkspaceService>,
+    ) -> (EngineHandle, broadcast::Sender<WsServerMessage>) {
+        Self::start_with_config(services, EngineConfig::default())
+    }
+
+    pub fn start_with_config(
+        services: Arc<dyn ProjectWorkspaceService>,
+        config: EngineConfig,
     ) -> (EngineHandle, broadcast::Sender<WsServerMessage>) {
         let (tx, mut rx) = mpsc::channel::<EngineCommand>(256);
         let (events, _) = broadcast::channel::<WsServerMessage>(256);
@@ -380,7 +885,23 @@ impl Engine {
             pull_requests_in_flight: HashSet::new(),
             workspace_threads_cache: HashMap::new(),
             auto_archive_workspaces: HashSet::new(),
-            telegram_pairing: None,
+            pending_notes_flushes: HashMap::new(),
+            pending_draft_flushes: HashMap::new(),
+            conversation_revs: HashMap::new(),
+            last_git_fetch_at: HashMap::new(),
+            worktree_dirty: HashMap::new(),
+            worktree_dirty_in_flight: HashSet::new(),
+            worktree_dirty_rerun: HashSet::new(),
+            last_commits: HashMap::new(),
+            agent_turn_slots: HashMap::new(),
+            deferred_agent_turns: VecDeque::new(),
+            request_cancellations: RequestCancellations::default(),
+            attachment_thumbnails: AttachmentThumbnails::default(),
+            telegram_pairings: Vec::new(),
+            config: EngineConfig {
+                conversation_load: config.conversation_load.clamped(),
+                ..config
+            },
         };
 
         let refresh_tx = tx.clone();
@@ -406,14 +927,98 @@ impl Engine {
             }
         });
 
+        let inactive_archive_tx = tx.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(INACTIVE_WORKSPACE_ARCHIVE_STARTUP_DELAY).await;
+            let mut interval = tokio::time::interval(INACTIVE_WORKSPACE_ARCHIVE_TICK_INTERVAL);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                let _ = inactive_archive_tx
+                    .send(EngineCommand::ArchiveInactiveWorkspaces)
+                    .await;
+            }
+        });
+
+        if engine.config.conversation_retention.is_enabled() {
+            let retention_tx = tx.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(CONVERSATION_RETENTION_STARTUP_DELAY).await;
+                let mut interval = tokio::time::interval(CONVERSATION_RETENTION_TICK_INTERVAL);
+                interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+                loop {
+                    interval.tick().await;
+                    let _ = retention_tx
+                        .send(EngineCommand::PruneConversationHistory)
+                        .await;
+                }
+            });
+        }
+
+        let bootstrapped = Arc::new(AtomicBool::new(false));
+        let bootstrapped_for_engine = bootstrapped.clone();
         tokio::spawn(async move {
             engine.bootstrap().await;
+            bootstrapped_for_engine.store(true, Ordering::Release);
             while let Some(cmd) = rx.recv().await {
+                if let EngineCommand::Shutdown { reply } = cmd {
+                    let replies = engine.drain_for_shutdown(&mut rx).await;
+                    for reply in std::iter::once(reply).chain(replies) {
+                        let _ = reply.send(());
+                    }
+                    break;
+                }
                 engine.handle(cmd).await;
             }
         });
 
-        (EngineHandle { tx }, events)
+        (
+            EngineHandle {
+                tx,
+                bootstrapped,
+                started_at: Instant::now(),
+            },
+            events,
+        )
+    }
+
+    /// Closes the command queue, handles what was already queued and flushes debounced writes.
+    ///
+    /// Effects such as `SaveAppState` and queue persistence are awaited inline by `handle`, so
+    /// everything a drained command persists has landed once this returns. Returns the reply
+    /// channels of any further `Shutdown` commands found in the queue.
+    async fn drain_for_shutdown(
+        &mut self,
+        rx: &mut mpsc::Receiver<EngineCommand>,
+    ) -> Vec<oneshot::Sender<()>> {
+        rx.close();
+        let mut replies = Vec::new();
+        while let Some(cmd) = rx.recv().await {
+            match cmd {
+                EngineCommand::Shutdown { reply } => replies.push(reply),
+                cmd => self.handle(cmd).await,
+            }
+        }
+
+        let notes = self
+            .pending_notes_flushes
+            .iter()
+            .map(|(key, generation)| (*key, *generation))
+            .collect::<Vec<_>>();
+        for ((workspace_id, thread_id), generation) in notes {
+            self.flush_conversation_notes(workspace_id, thread_id, generation)
+                .await;
+        }
+        let drafts = self
+            .pending_draft_flushes
+            .iter()
+            .map(|(key, generation)| (*key, *generation))
+            .collect::<Vec<_>>();
+        for ((workspace_id, thread_id), generation) in drafts {
+            self.flush_conversation_draft(workspace_id, thread_id, generation)
+                .await;
+        }
+        replies
     }
 
     async fn bootstrap(&mut self) {
@@ -548,6 +1153,7 @@ impl Engine {
                                 runner: None,
                                 event: luban_domain::AgentEvent::TurnError {
                                     message: "Agent run interrupted by server restart.".to_owned(),
+                                    code: None,
                                 },
                             }],
                         )?;
@@ -641,6 +1247,7 @@ impl Engine {
                             runner: None,
                             event: luban_domain::AgentEvent::TurnError {
                                 message: "Agent run interrupted by server restart.".to_owned(),
+                                code: None,
                             },
                         }],
                     )?;
@@ -780,20 +1387,182 @@ impl Engine {
         }
     }
 
-    async fn telegram_pair_start(&mut self, request_id: String) -> Result<(), String> {
-        if crate::telegram::telegram_disabled() {
-            return Err("telegram integration is disabled".to_owned());
-        }
-
-        if !self.state.telegram_enabled() {
-            return Err("telegram is not enabled".to_owned());
+    /// Applies the conversation retention policy to every stored conversation. Threads with a
+    /// run in flight are skipped here and again inside the store; loaded conversations that lost
+    /// entries are reloaded so subscribers see the pruned history.
+    async fn prune_conversation_history(&mut self) {
+        let policy = self.config.conversation_retention;
+        if !policy.is_enabled() {
+            return;
         }
-        let Some(token) = self.state.telegram_bot_token() else {
-            return Err("telegram bot token is not configured".to_owned());
-        };
+        let now = now_unix_seconds();
 
-        let username = crate::telegram::telegram_get_me_username(token).await?;
-        self.process_action_queue(Action::TelegramBotUsernameSet {
+        let mut workspaces = Vec::new();
+        for project in &self.state.projects {
+            for workspace in &project.workspaces {
+                workspaces.push((
+                    workspace.id,
+                    WorkspaceScope {
+                        project_slug: project.slug.clone(),
+                        workspace_name: workspace.workspace_name.clone(),
+                    },
+                ));
+            }
+        }
+
+        let limit = self.config.conversation_load.max_entries_limit as u64;
+        for (workspace_id, scope) in workspaces {
+            let running = self
+                .state
+                .conversations
+                .iter()
+                .filter(|((wid, _), c)| {
+                    *wid == workspace_id && c.run_status == OperationStatus::Running
+                })
+                .map(|((_, tid), _)| tid.as_u64())
+                .collect::<HashSet<_>>();
+            let loaded = self
+                .state
+                .conversations
+                .keys()
+                .filter(|(wid, _)| *wid == workspace_id)
+                .map(|(_, tid)| tid.as_u64())
+                .collect::<HashSet<_>>();
+
+            let services = self.services.clone();
+            let result = tokio::task::spawn_blocking(move || {
+                let threads = services.list_conversation_threads(
+                    scope.project_slug.clone(),
+                    scope.workspace_name.clone(),
+                )?;
+                let mut pruned = 0u64;
+                let mut reloaded = Vec::new();
+                for thread in threads {
+                    let thread_id = thread.thread_id.as_u64();
+                    if running.contains(&thread_id)
+                        || thread.turn_status == luban_domain::TurnStatus::Running
+                    {
+                        continue;
+                    }
+                    let Ok(removed) = services.prune_conversation_entries(
+                        scope.project_slug.clone(),
+                        scope.workspace_name.clone(),
+                        thread_id,
+                        policy,
+                        now,
+                    ) else {
+                        continue;
+                    };
+                    if removed == 0 {
+                        continue;
+                    }
+                    pruned = pruned.saturating_add(removed);
+                    if loaded.contains(&thread_id)
+                        && let Ok(snapshot) = services.load_conversation_page(
+                            scope.project_slug.clone(),
+                            scope.workspace_name.clone(),
+                            thread_id,
+                            None,
+                            limit,
+                        )
+                    {
+                        reloaded.push((thread.thread_id, snapshot));
+                    }
+                }
+                Ok::<_, String>((pruned, reloaded))
+            })
+            .await
+            .ok()
+            .unwrap_or_else(|| Err("failed to join conversation prune task".to_owned()));
+
+            let Ok((pruned, reloaded)) = result else {
+                continue;
+            };
+            if pruned == 0 {
+                continue;
+            }
+            tracing::info!(
+                workspace_id = workspace_id.as_u64(),
+                pruned,
+                "pruned conversation history"
+            );
+            for (thread_id, snapshot) in reloaded {
+                self.process_action_queue(Action::ConversationHistoryPruned {
+                    workspace_id,
+                    thread_id,
+                    snapshot,
+                })
+                .await;
+            }
+        }
+    }
+
+    async fn archive_inactive_workspaces(&mut self) {
+        let Some(days) = self.state.auto_archive_after_days() else {
+            return;
+        };
+        let cutoff = now_unix_seconds().saturating_sub(u64::from(days) * 24 * 60 * 60);
+
+        let candidates = inactive_workspace_archive_candidates(&self.state, |workspace_id| {
+            match self.pull_requests.get(&workspace_id) {
+                Some(entry) => entry
+                    .info
+                    .as_ref()
+                    .is_some_and(|pr| pr.state == DomainPullRequestState::Open),
+                // Not refreshed yet; wait until the PR state is known.
+                None => true,
+            }
+        });
+
+        for (workspace_id, last_activity_at, scope) in candidates {
+            let services = self.services.clone();
+            let result = tokio::task::spawn_blocking(move || {
+                services.list_conversation_threads(scope.project_slug, scope.workspace_name)
+            })
+            .await
+            .ok()
+            .unwrap_or_else(|| Err("failed to join inactive workspace scan task".to_owned()));
+
+            let Ok(threads) = result else {
+                continue;
+            };
+            if threads
+                .iter()
+                .any(|t| t.turn_status == luban_domain::TurnStatus::Running)
+            {
+                continue;
+            }
+            let Some(last_activity) = latest_workspace_activity(last_activity_at, &threads) else {
+                continue;
+            };
+            if last_activity >= cutoff {
+                continue;
+            }
+
+            tracing::info!(
+                workspace_id = workspace_id.as_u64(),
+                idle_days = days,
+                "archiving inactive workspace"
+            );
+            self.process_action_queue(Action::ArchiveWorkspace { workspace_id })
+                .await;
+        }
+    }
+
+    async fn telegram_pair_start(&mut self, request_id: String) -> Result<(), String> {
+        if crate::telegram::telegram_disabled() {
+            return Err("telegram integration is disabled".to_owned());
+        }
+
+        if !self.state.telegram_enabled() {
+            return Err("telegram is not enabled".to_owned());
+        }
+        let Some(token) = self.state.telegram_bot_token() else {
+            return Err("telegram bot token is not configured".to_owned());
+        };
+
+        let username = crate::telegram::telegram_get_me_username(token).await?;
+        self.process_action_queue(Action::TelegramBotUsernameSet {
             username: Some(username.clone()),
         })
         .await;
@@ -801,9 +1570,12 @@ impl Engine {
         let mut bytes = [0u8; 16];
         OsRng.fill_bytes(&mut bytes);
         let code = hex_lower(&bytes);
-        self.telegram_pairing = Some(TelegramPairingState {
+        let now = Instant::now();
+        self.telegram_pairings
+            .retain(|pairing| now < pairing.expires_at);
+        self.telegram_pairings.push(TelegramPairingState {
             code: code.clone(),
-            expires_at: Instant::now() + Duration::from_secs(10 * 60),
+            expires_at: now + Duration::from_secs(10 * 60),
         });
 
         let url = format!("https://t.me/{username}?start={code}");
@@ -814,6 +1586,93 @@ impl Engine {
         Ok(())
     }
 
+    async fn ensure_project_main_workspace(
+        &mut self,
+        project_id: luban_domain::ProjectId,
+    ) -> Result<WorkspaceId, String> {
+        self.process_action_queue(Action::EnsureMainWorkspace { project_id })
+            .await;
+        project_main_workspace_id(&self.state, project_id)
+            .ok_or_else(|| "failed to locate main workspace after ensuring it".to_owned())
+    }
+
+    /// Ensures the project's main workspace has a task to show, then opens the workspace on it.
+    ///
+    /// Reuses the active tab first, then the most recently updated unfinished persisted task, and
+    /// only creates a task when neither exists, so repeated calls land on the same task.
+    async fn open_project_main(
+        &mut self,
+        project_id: luban_domain::ProjectId,
+    ) -> Result<(WorkspaceId, WorkspaceThreadId), String> {
+        let workspace_id = self.ensure_project_main_workspace(project_id).await?;
+
+        let thread_id = match self.state.active_thread_id(workspace_id) {
+            Some(thread_id) => thread_id,
+            None => match self.latest_unfinished_thread(workspace_id).await {
+                Some(thread_id) => {
+                    self.process_action_queue(Action::ActivateWorkspaceThread {
+                        workspace_id,
+                        thread_id,
+                    })
+                    .await;
+                    thread_id
+                }
+                None => {
+                    self.process_action_queue(Action::CreateWorkspaceThread { workspace_id })
+                        .await;
+                    self.state
+                        .active_thread_id(workspace_id)
+                        .ok_or_else(|| "failed to create a task in the main workdir".to_owned())?
+                }
+            },
+        };
+
+        self.process_action_queue(Action::OpenWorkspace { workspace_id })
+            .await;
+        Ok((workspace_id, thread_id))
+    }
+
+    async fn latest_unfinished_thread(
+        &mut self,
+        workspace_id: WorkspaceId,
+    ) -> Option<WorkspaceThreadId> {
+        if !self.workspace_threads_cache.contains_key(&workspace_id) {
+            let scope = workspace_scope(&self.state, workspace_id)?;
+            let services = self.services.clone();
+            let result = tokio::task::spawn_blocking(move || {
+                services.list_conversation_threads(scope.project_slug, scope.workspace_name)
+            })
+            .await
+            .unwrap_or_else(|_| Err("failed to join list threads task".to_owned()));
+            match result {
+                Ok(mut threads) => {
+                    dedup_thread_metas_in_place(&mut threads);
+                    self.workspace_threads_cache.insert(workspace_id, threads);
+                }
+                Err(message) => {
+                    tracing::warn!(
+                        workspace_id = workspace_id.as_u64(),
+                        error = %message,
+                        "failed to list tasks while opening main workdir"
+                    );
+                    return None;
+                }
+            }
+        }
+
+        self.workspace_threads_cache
+            .get(&workspace_id)?
+            .iter()
+            .filter(|thread| {
+                !matches!(
+                    thread.task_status,
+                    luban_domain::TaskStatus::Done | luban_domain::TaskStatus::Canceled
+                )
+            })
+            .max_by_key(|thread| thread.updated_at_unix_seconds)
+            .map(|thread| thread.thread_id)
+    }
+
     async fn execute_task_prompt(
         &mut self,
         prompt: String,
@@ -882,10 +1741,30 @@ impl Engine {
             EngineCommand::GetRev { reply } => {
                 let _ = reply.send(Ok(self.rev));
             }
+            EngineCommand::Shutdown { reply } => {
+                let _ = reply.send(());
+            }
             EngineCommand::GetAppSnapshot { reply } => {
                 self.refresh_pull_requests_for_all_workspaces();
                 let _ = reply.send(Ok(self.app_snapshot()));
             }
+            EngineCommand::GetStatus { reply } => {
+                let active_turns = self
+                    .state
+                    .conversations
+                    .iter()
+                    .filter(|((workspace_id, thread_id), conversation)| {
+                        conversation.run_status == OperationStatus::Running
+                            && !self.agent_turn_is_deferred(*workspace_id, *thread_id)
+                    })
+                    .count() as u64;
+                let _ = reply.send(Ok(EngineStatus {
+                    rev: self.rev,
+                    projects: self.state.projects.len() as u64,
+                    active_turns,
+                    uptime_secs: 0,
+                }));
+            }
             EngineCommand::GetThreadsSnapshot {
                 workspace_id,
                 reply,
@@ -961,6 +1840,7 @@ impl Engine {
                                         luban_api::TurnResult::Failed
                                     }
                                 }),
+                                total_tokens: t.total_tokens,
                             })
                             .collect::<Vec<_>>();
 
@@ -983,11 +1863,32 @@ impl Engine {
                 thread_id,
                 before,
                 limit,
+                filter,
                 reply,
             } => {
-                let snapshot = self
-                    .get_conversation_snapshot(workspace_id, thread_id, before, limit)
+                let mut snapshot = match filter {
+                    Some(filter) => {
+                        self.get_filtered_conversation_snapshot(
+                            workspace_id,
+                            thread_id,
+                            before,
+                            limit,
+                            &filter,
+                        )
+                        .await
+                    }
+                    None => {
+                        self.get_conversation_snapshot(workspace_id, thread_id, before, limit)
+                            .await
+                    }
+                };
+                if let Ok(snapshot) = snapshot.as_mut() {
+                    self.fill_attachment_thumbnails(
+                        WorkspaceId::from_u64(workspace_id.0),
+                        snapshot,
+                    )
                     .await;
+                }
                 let _ = reply.send(snapshot);
             }
             EngineCommand::GetWorkspaceWorktreePath {
@@ -998,6 +1899,33 @@ impl Engine {
                 let path = self.state.workspace(id).map(|w| w.worktree_path.clone());
                 let _ = reply.send(Ok(path));
             }
+            EngineCommand::GetPromptHistory {
+                workspace_id,
+                thread_id,
+                reply,
+            } => {
+                let Some(scope) =
+                    workspace_scope(&self.state, WorkspaceId::from_u64(workspace_id.0))
+                else {
+                    let _ = reply.send(Err(anyhow::anyhow!("workspace not found")));
+                    return;
+                };
+                let services = self.services.clone();
+                tokio::spawn(async move {
+                    let result = tokio::task::spawn_blocking(move || {
+                        services.load_prompt_history(
+                            scope.project_slug,
+                            scope.workspace_name,
+                            thread_id.0,
+                            PROMPT_HISTORY_LIMIT,
+                        )
+                    })
+                    .await
+                    .unwrap_or_else(|_| Err("failed to join prompt history task".to_owned()))
+                    .map_err(|message| anyhow::anyhow!(message));
+                    let _ = reply.send(result);
+                });
+            }
             EngineCommand::GetStarredTasks { reply } => {
                 let starred = self
                     .state
@@ -1007,11 +1935,101 @@ impl Engine {
                     .collect::<std::collections::HashSet<_>>();
                 let _ = reply.send(Ok(starred));
             }
+            EngineCommand::GetStarredTasksDetailed { reply } => {
+                let tasks = self.starred_task_summaries().await;
+                let _ = reply.send(tasks);
+            }
+            EngineCommand::ImportAttachmentsFromDir {
+                workspace_id,
+                dir,
+                include,
+                exclude,
+                reply,
+            } => {
+                let Some(scope) =
+                    workspace_scope(&self.state, WorkspaceId::from_u64(workspace_id.0))
+                else {
+                    let _ = reply.send(Err("workspace not found".to_owned()));
+                    return;
+                };
+                let services = self.services.clone();
+                let max_file_bytes = self.config.max_attachment_bytes;
+                tokio::spawn(async move {
+                    let result = tokio::task::spawn_blocking(move || {
+                        let imported = services.import_context_dir(
+                            scope.project_slug.clone(),
+                            scope.workspace_name.clone(),
+                            dir,
+                            include,
+                            exclude,
+                            max_file_bytes,
+                        )?;
+                        let created_at_unix_ms = now_unix_ms();
+                        for attachment in &imported.attachments {
+                            services.record_context_item(
+                                scope.project_slug.clone(),
+                                scope.workspace_name.clone(),
+                                attachment.clone(),
+                                created_at_unix_ms,
+                            )?;
+                        }
+                        Ok(ImportedAttachments {
+                            attachments: imported
+                                .attachments
+                                .iter()
+                                .map(map_attachment_ref)
+                                .collect(),
+                            skipped: imported.skipped,
+                        })
+                    })
+                    .await
+                    .unwrap_or_else(|_| Err("failed to join attachment import task".to_owned()));
+                    let _ = reply.send(result);
+                });
+            }
+            EngineCommand::AuditWorktrees { project_id, reply } => {
+                let path = expand_user_path(&project_id.0);
+                let Some(project) = find_project_id_by_path(&self.state, &path)
+                    .and_then(|id| self.state.project(id))
+                else {
+                    let _ = reply.send(Err("project not found".to_owned()));
+                    return;
+                };
+                let project_path = project.path.clone();
+                let workspaces = project.workspaces.clone();
+                let services = self.services.clone();
+                tokio::spawn(async move {
+                    let result = tokio::task::spawn_blocking(move || {
+                        services.audit_worktrees(project_path, owned_worktree_paths(&workspaces))
+                    })
+                    .await
+                    .unwrap_or_else(|_| Err("failed to join worktree audit task".to_owned()))
+                    .map(|audit| luban_api::WorktreeAuditSnapshot {
+                        orphaned: audit
+                            .orphaned
+                            .iter()
+                            .map(|p| p.to_string_lossy().into_owned())
+                            .collect(),
+                        dangling: audit
+                            .dangling
+                            .iter()
+                            .map(|p| p.to_string_lossy().into_owned())
+                            .collect(),
+                    });
+                    let _ = reply.send(result);
+                });
+            }
+            EngineCommand::GetWebhookRuntimeConfig { reply } => {
+                let _ = reply.send(WebhookRuntimeConfig {
+                    url: self.state.webhook_url().map(ToOwned::to_owned),
+                    secret: self.state.webhook_secret().map(ToOwned::to_owned),
+                });
+            }
             EngineCommand::GetTelegramRuntimeConfig { reply } => {
                 let cfg = TelegramRuntimeConfig {
                     enabled: self.state.telegram_enabled(),
                     bot_token: self.state.telegram_bot_token().map(ToOwned::to_owned),
-                    paired_chat_id: self.state.telegram_paired_chat_id(),
+                    paired_chat_ids: self.state.telegram_paired_chat_ids().to_vec(),
                     topic_bindings: self
                         .state
                         .telegram_topic_bindings()
@@ -1032,27 +2050,24 @@ impl Engine {
                 reply,
             } => {
                 let now = Instant::now();
-                let Some(pairing) = self.telegram_pairing.as_ref() else {
+                if self.telegram_pairings.is_empty() {
                     let _ = reply.send(Err("pairing is not active".to_owned()));
                     return;
-                };
-                if now >= pairing.expires_at {
-                    self.telegram_pairing = None;
-                    let _ = reply.send(Err("pairing code expired".to_owned()));
-                    return;
                 }
-                if pairing.code != code.trim() {
+                let Some(index) = self
+                    .telegram_pairings
+                    .iter()
+                    .position(|pairing| pairing.code == code.trim())
+                else {
                     let _ = reply.send(Err("invalid pairing code".to_owned()));
                     return;
-                }
-                if let Some(existing) = self.state.telegram_paired_chat_id()
-                    && existing != chat_id
-                {
-                    let _ = reply.send(Err("telegram is already paired".to_owned()));
+                };
+                let pairing = self.telegram_pairings.remove(index);
+                if now >= pairing.expires_at {
+                    let _ = reply.send(Err("pairing code expired".to_owned()));
                     return;
                 }
 
-                self.telegram_pairing = None;
                 self.process_action_queue(Action::TelegramChatPaired { chat_id })
                     .await;
                 let _ = reply.send(Ok(()));
@@ -1062,6 +2077,46 @@ impl Engine {
                 action,
                 reply,
             } => {
+                if !self.config.denied_client_actions.is_empty()
+                    && let Some(name) = client_action_type(&action)
+                    && self.config.denied_client_actions.contains(&name)
+                {
+                    let _ = reply.send(Err(format!(
+                        "unauthorized: action '{name}' is disabled on this server"
+                    )));
+                    return;
+                }
+
+                if let Err(message) =
+                    check_attachment_sizes(&action, self.config.max_attachment_bytes)
+                {
+                    let _ = reply.send(Err(message));
+                    return;
+                }
+
+                if let luban_api::ClientAction::CancelRequest { request_id: target } = &action {
+                    self.request_cancellations.cancel(target);
+                    let _ = reply.send(Ok(self.rev));
+                    return;
+                }
+
+                if let Some((workspace_id, thread_id, expected_rev)) =
+                    client_action_expected_conversation_rev(&action)
+                {
+                    let wid = WorkspaceId::from_u64(workspace_id.0);
+                    let tid = WorkspaceThreadId::from_u64(thread_id.0);
+                    let current_rev = self.conversation_revs.get(&(wid, tid)).copied();
+                    if let Some(current_rev) = current_rev
+                        && current_rev > expected_rev
+                    {
+                        self.publish_conversation_snapshot(wid, tid, None).await;
+                        let _ = reply.send(Err(format!(
+                            "conversation changed at rev {current_rev} after rev {expected_rev}; refresh and retry"
+                        )));
+                        return;
+                    }
+                }
+
                 if matches!(action, luban_api::ClientAction::PickProjectPath) {
                     let events = self.events.clone();
                     let rev = self.rev;
@@ -1203,31 +2258,12 @@ impl Engine {
                         return;
                     };
 
-                    self.process_action_queue(Action::EnsureMainWorkspace { project_id })
-                        .await;
-
-                    let main_workspace_id = self
-                        .state
-                        .projects
-                        .iter()
-                        .find(|p| p.id == project_id)
-                        .and_then(|p| {
-                            let active = p
-                                .workspaces
-                                .iter()
-                                .filter(|w| w.status == luban_domain::WorkspaceStatus::Active);
-                            active
-                                .clone()
-                                .find(|w| w.workspace_name == "main" && w.worktree_path == p.path)
-                                .map(|w| w.id)
-                                .or_else(|| active.clone().next().map(|w| w.id))
-                        });
-
-                    let Some(workspace_id) = main_workspace_id else {
-                        let _ = reply.send(Err(
-                            "failed to locate main workspace after ensuring it".to_owned(),
-                        ));
-                        return;
+                    let workspace_id = match self.ensure_project_main_workspace(project_id).await {
+                        Ok(workspace_id) => workspace_id,
+                        Err(message) => {
+                            let _ = reply.send(Err(message));
+                            return;
+                        }
                     };
 
                     let _ = self.events.send(WsServerMessage::Event {
@@ -1250,14 +2286,42 @@ impl Engine {
                     return;
                 }
 
-                if matches!(action, luban_api::ClientAction::TelegramPairStart) {
-                    let res = self.telegram_pair_start(request_id.clone()).await;
-                    let _ = reply.send(res.map(|_| self.rev));
-                    return;
-                }
-
-                if let luban_api::ClientAction::TaskExecute {
-                    prompt,
+                if let luban_api::ClientAction::OpenProjectMain { project_id } = &action {
+                    let path = expand_user_path(&project_id.0);
+                    let Some(id) = find_project_id_by_path(&self.state, &path) else {
+                        let _ = reply.send(Err("project not found".to_owned()));
+                        return;
+                    };
+                    let (workspace_id, thread_id) = match self.open_project_main(id).await {
+                        Ok(ids) => ids,
+                        Err(message) => {
+                            let _ = reply.send(Err(message));
+                            return;
+                        }
+                    };
+
+                    let _ = self.events.send(WsServerMessage::Event {
+                        rev: self.rev,
+                        event: Box::new(luban_api::ServerEvent::ProjectMainOpened {
+                            request_id: request_id.clone(),
+                            project_id: project_id.clone(),
+                            workspace_id: luban_api::WorkspaceId(workspace_id.as_u64()),
+                            thread_id: luban_api::WorkspaceThreadId(thread_id.as_u64()),
+                        }),
+                    });
+
+                    let _ = reply.send(Ok(self.rev));
+                    return;
+                }
+
+                if matches!(action, luban_api::ClientAction::TelegramPairStart) {
+                    let res = self.telegram_pair_start(request_id.clone()).await;
+                    let _ = reply.send(res.map(|_| self.rev));
+                    return;
+                }
+
+                if let luban_api::ClientAction::TaskExecute {
+                    prompt,
                     mode,
                     workdir_id,
                     attachments,
@@ -1405,10 +2469,13 @@ impl Engine {
 
                 if matches!(action, luban_api::ClientAction::CodexCheck) {
                     let services = self.services.clone();
-                    let events = self.events.clone();
+                    let events = self.request_events(&request_id);
                     let request_id = request_id.clone();
                     let rev = self.rev;
                     tokio::spawn(async move {
+                        if events.is_canceled() {
+                            return;
+                        }
                         let result = tokio::task::spawn_blocking(move || services.codex_check())
                             .await
                             .ok()
@@ -1435,10 +2502,13 @@ impl Engine {
 
                 if matches!(action, luban_api::ClientAction::AmpCheck) {
                     let services = self.services.clone();
-                    let events = self.events.clone();
+                    let events = self.request_events(&request_id);
                     let request_id = request_id.clone();
                     let rev = self.rev;
                     tokio::spawn(async move {
+                        if events.is_canceled() {
+                            return;
+                        }
                         let result = tokio::task::spawn_blocking(move || services.amp_check())
                             .await
                             .ok()
@@ -1465,10 +2535,13 @@ impl Engine {
 
                 if matches!(action, luban_api::ClientAction::ClaudeCheck) {
                     let services = self.services.clone();
-                    let events = self.events.clone();
+                    let events = self.request_events(&request_id);
                     let request_id = request_id.clone();
                     let rev = self.rev;
                     tokio::spawn(async move {
+                        if events.is_canceled() {
+                            return;
+                        }
                         let result = tokio::task::spawn_blocking(move || services.claude_check())
                             .await
                             .ok()
@@ -1493,46 +2566,35 @@ impl Engine {
                     return;
                 }
 
-                if matches!(action, luban_api::ClientAction::CodexConfigTree) {
-                    fn map_entry(
-                        entry: luban_domain::CodexConfigEntry,
-                    ) -> luban_api::CodexConfigEntrySnapshot {
-                        luban_api::CodexConfigEntrySnapshot {
-                            path: entry.path,
-                            name: entry.name,
-                            kind: match entry.kind {
-                                luban_domain::CodexConfigEntryKind::File => {
-                                    luban_api::CodexConfigEntryKind::File
-                                }
-                                luban_domain::CodexConfigEntryKind::Folder => {
-                                    luban_api::CodexConfigEntryKind::Folder
-                                }
-                            },
-                            children: entry.children.into_iter().map(map_entry).collect(),
-                        }
-                    }
-
-                    let services = self.services.clone();
-                    let events = self.events.clone();
+                if matches!(action, luban_api::ClientAction::ListStarredTasks) {
+                    let tx = self.tx.clone();
+                    let events = self.request_events(&request_id);
                     let request_id = request_id.clone();
                     let rev = self.rev;
                     tokio::spawn(async move {
-                        let result =
-                            tokio::task::spawn_blocking(move || services.codex_config_tree())
+                        let (reply_tx, reply_rx) = oneshot::channel();
+                        let result = if tx
+                            .send(EngineCommand::GetStarredTasksDetailed { reply: reply_tx })
+                            .await
+                            .is_err()
+                        {
+                            Err("engine unavailable".to_owned())
+                        } else {
+                            reply_rx
                                 .await
-                                .ok()
-                                .unwrap_or_else(|| {
-                                    Err("failed to join codex config tree task".to_owned())
-                                });
+                                .unwrap_or_else(|_| Err("engine stopped".to_owned()))
+                        };
+                        if events.is_canceled() {
+                            return;
+                        }
 
                         match result {
-                            Ok(tree) => {
-                                let tree = tree.into_iter().map(map_entry).collect();
+                            Ok(tasks) => {
                                 let _ = events.send(WsServerMessage::Event {
                                     rev,
-                                    event: Box::new(luban_api::ServerEvent::CodexConfigTreeReady {
+                                    event: Box::new(luban_api::ServerEvent::StarredTasksListed {
                                         request_id,
-                                        tree,
+                                        tasks,
                                     }),
                                 });
                             }
@@ -1549,47 +2611,62 @@ impl Engine {
                     return;
                 }
 
-                if matches!(action, luban_api::ClientAction::AmpConfigTree) {
-                    fn map_entry(
-                        entry: luban_domain::AmpConfigEntry,
-                    ) -> luban_api::AmpConfigEntrySnapshot {
-                        luban_api::AmpConfigEntrySnapshot {
-                            path: entry.path,
-                            name: entry.name,
-                            kind: match entry.kind {
-                                luban_domain::AmpConfigEntryKind::File => {
-                                    luban_api::AmpConfigEntryKind::File
-                                }
-                                luban_domain::AmpConfigEntryKind::Folder => {
-                                    luban_api::AmpConfigEntryKind::Folder
-                                }
-                            },
-                            children: entry.children.into_iter().map(map_entry).collect(),
-                        }
-                    }
-
-                    let services = self.services.clone();
-                    let events = self.events.clone();
+                if let luban_api::ClientAction::GetWorkspaceDiffPage {
+                    workspace_id: api_workspace_id,
+                    offset,
+                    limit,
+                    context_lines,
+                } = &action
+                {
+                    let workspace_id = WorkspaceId::from_u64(api_workspace_id.0);
+                    let Some(worktree_path) = self
+                        .state
+                        .workspace(workspace_id)
+                        .map(|w| w.worktree_path.clone())
+                    else {
+                        let _ = reply.send(Err("workspace not found".to_owned()));
+                        return;
+                    };
+                    let api_workspace_id = *api_workspace_id;
+                    let offset = *offset;
+                    let limit = *limit;
+                    let context_lines =
+                        context_lines.unwrap_or(crate::git_changes::DEFAULT_DIFF_CONTEXT_LINES);
+                    let events = self.request_events(&request_id);
                     let request_id = request_id.clone();
                     let rev = self.rev;
                     tokio::spawn(async move {
-                        let result =
-                            tokio::task::spawn_blocking(move || services.amp_config_tree())
-                                .await
-                                .ok()
-                                .unwrap_or_else(|| {
-                                    Err("failed to join amp config tree task".to_owned())
-                                });
+                        if events.is_canceled() {
+                            return;
+                        }
+                        let result = tokio::task::spawn_blocking(move || {
+                            crate::git_changes::collect_diff_page(
+                                &worktree_path,
+                                usize::try_from(offset).unwrap_or(usize::MAX),
+                                usize::try_from(limit).unwrap_or(usize::MAX),
+                                context_lines,
+                            )
+                            .map_err(|err| format!("{err:#}"))
+                        })
+                        .await
+                        .ok()
+                        .unwrap_or_else(|| Err("failed to join diff page task".to_owned()));
 
                         match result {
-                            Ok(tree) => {
-                                let tree = tree.into_iter().map(map_entry).collect();
+                            Ok((files, total)) => {
                                 let _ = events.send(WsServerMessage::Event {
                                     rev,
-                                    event: Box::new(luban_api::ServerEvent::AmpConfigTreeReady {
-                                        request_id,
-                                        tree,
-                                    }),
+                                    event: Box::new(
+                                        luban_api::ServerEvent::WorkspaceDiffPageReady {
+                                            request_id,
+                                            offset,
+                                            total: total as u64,
+                                            diff: luban_api::WorkspaceDiffSnapshot {
+                                                workspace_id: api_workspace_id,
+                                                files,
+                                            },
+                                        },
+                                    ),
                                 });
                             }
                             Err(message) => {
@@ -1605,53 +2682,41 @@ impl Engine {
                     return;
                 }
 
-                if let luban_api::ClientAction::CodexConfigListDir { path } = &action {
-                    fn map_entry(
-                        entry: luban_domain::CodexConfigEntry,
-                    ) -> luban_api::CodexConfigEntrySnapshot {
-                        luban_api::CodexConfigEntrySnapshot {
-                            path: entry.path,
-                            name: entry.name,
-                            kind: match entry.kind {
-                                luban_domain::CodexConfigEntryKind::File => {
-                                    luban_api::CodexConfigEntryKind::File
-                                }
-                                luban_domain::CodexConfigEntryKind::Folder => {
-                                    luban_api::CodexConfigEntryKind::Folder
-                                }
-                            },
-                            children: entry.children.into_iter().map(map_entry).collect(),
-                        }
-                    }
-
-                    let services = self.services.clone();
-                    let events = self.events.clone();
+                if let luban_api::ClientAction::AuditWorktrees { project_id } = &action {
+                    let project_id = project_id.clone();
+                    let tx = self.tx.clone();
+                    let events = self.request_events(&request_id);
                     let request_id = request_id.clone();
                     let rev = self.rev;
-                    let path = path.clone();
                     tokio::spawn(async move {
-                        let path_for_task = path.clone();
-                        let result = tokio::task::spawn_blocking(move || {
-                            services.codex_config_list_dir(path_for_task)
-                        })
-                        .await
-                        .ok()
-                        .unwrap_or_else(|| {
-                            Err("failed to join codex config list dir task".to_owned())
-                        });
+                        let (reply_tx, reply_rx) = oneshot::channel();
+                        let result = if tx
+                            .send(EngineCommand::AuditWorktrees {
+                                project_id: project_id.clone(),
+                                reply: reply_tx,
+                            })
+                            .await
+                            .is_err()
+                        {
+                            Err("engine unavailable".to_owned())
+                        } else {
+                            reply_rx
+                                .await
+                                .unwrap_or_else(|_| Err("engine stopped".to_owned()))
+                        };
+                        if events.is_canceled() {
+                            return;
+                        }
 
                         match result {
-                            Ok(entries) => {
-                                let entries = entries.into_iter().map(map_entry).collect();
+                            Ok(audit) => {
                                 let _ = events.send(WsServerMessage::Event {
                                     rev,
-                                    event: Box::new(
-                                        luban_api::ServerEvent::CodexConfigListDirReady {
-                                            request_id,
-                                            path,
-                                            entries,
-                                        },
-                                    ),
+                                    event: Box::new(luban_api::ServerEvent::WorktreeAuditReady {
+                                        request_id,
+                                        project_id,
+                                        audit,
+                                    }),
                                 });
                             }
                             Err(message) => {
@@ -1667,49 +2732,58 @@ impl Engine {
                     return;
                 }
 
-                if let luban_api::ClientAction::AmpConfigListDir { path } = &action {
-                    fn map_entry(
-                        entry: luban_domain::AmpConfigEntry,
-                    ) -> luban_api::AmpConfigEntrySnapshot {
-                        luban_api::AmpConfigEntrySnapshot {
-                            path: entry.path,
-                            name: entry.name,
-                            kind: match entry.kind {
-                                luban_domain::AmpConfigEntryKind::File => {
-                                    luban_api::AmpConfigEntryKind::File
-                                }
-                                luban_domain::AmpConfigEntryKind::Folder => {
-                                    luban_api::AmpConfigEntryKind::Folder
-                                }
-                            },
-                            children: entry.children.into_iter().map(map_entry).collect(),
-                        }
-                    }
-
+                if let luban_api::ClientAction::PruneOrphanedWorktrees {
+                    project_id,
+                    dry_run,
+                } = &action
+                {
+                    let path = expand_user_path(&project_id.0);
+                    let Some(project) = find_project_id_by_path(&self.state, &path)
+                        .and_then(|id| self.state.project(id))
+                    else {
+                        let _ = reply.send(Err("project not found".to_owned()));
+                        return;
+                    };
+                    let project_path = project.path.clone();
+                    let project_slug = project.slug.clone();
+                    let worktree_root = project.worktree_root.clone();
+                    let workspaces = project.workspaces.clone();
+                    let project_id = project_id.clone();
+                    let dry_run = *dry_run;
                     let services = self.services.clone();
-                    let events = self.events.clone();
+                    let events = self.request_events(&request_id);
                     let request_id = request_id.clone();
                     let rev = self.rev;
-                    let path = path.clone();
                     tokio::spawn(async move {
-                        let path_for_task = path.clone();
+                        if events.is_canceled() {
+                            return;
+                        }
                         let result = tokio::task::spawn_blocking(move || {
-                            services.amp_config_list_dir(path_for_task)
+                            services.prune_orphaned_worktrees(
+                                project_path,
+                                project_slug,
+                                worktree_root,
+                                owned_worktree_paths(&workspaces),
+                                dry_run,
+                            )
                         })
                         .await
                         .ok()
-                        .unwrap_or_else(|| Err("failed to join amp config list task".to_owned()));
+                        .unwrap_or_else(|| Err("failed to join worktree prune task".to_owned()));
 
                         match result {
-                            Ok(entries) => {
-                                let entries = entries.into_iter().map(map_entry).collect();
+                            Ok(pruned) => {
                                 let _ = events.send(WsServerMessage::Event {
                                     rev,
                                     event: Box::new(
-                                        luban_api::ServerEvent::AmpConfigListDirReady {
+                                        luban_api::ServerEvent::OrphanedWorktreesPruned {
                                             request_id,
-                                            path,
-                                            entries,
+                                            project_id,
+                                            dry_run,
+                                            pruned: pruned
+                                                .iter()
+                                                .map(|p| p.to_string_lossy().into_owned())
+                                                .collect(),
                                         },
                                     ),
                                 });
@@ -1727,29 +2801,82 @@ impl Engine {
                     return;
                 }
 
-                if let luban_api::ClientAction::CodexConfigReadFile { path } = &action {
+                if let luban_api::ClientAction::ImportThreadJsonl {
+                    workspace_id: api_workspace_id,
+                    jsonl,
+                } = &action
+                {
+                    let workspace_id = WorkspaceId::from_u64(api_workspace_id.0);
+                    let Some(scope) = workspace_scope(&self.state, workspace_id) else {
+                        let _ = reply.send(Err("workspace not found".to_owned()));
+                        return;
+                    };
+                    // Reason: the reducer owns thread id allocation, so create an empty task
+                    // first and fill its history from storage once the import lands.
+                    self.process_action_queue(Action::CreateWorkspaceThread { workspace_id })
+                        .await;
+                    let Some(thread_id) = self
+                        .state
+                        .workspace_tabs(workspace_id)
+                        .map(|tabs| tabs.active_tab)
+                    else {
+                        let _ = reply.send(Err("failed to create task".to_owned()));
+                        return;
+                    };
+
+                    let api_workspace_id = *api_workspace_id;
+                    let jsonl = jsonl.clone();
                     let services = self.services.clone();
-                    let events = self.events.clone();
+                    let tx = self.tx.clone();
+                    let events = self.request_events(&request_id);
                     let request_id = request_id.clone();
                     let rev = self.rev;
-                    let path = path.clone();
                     tokio::spawn(async move {
-                        let path_for_task = path.clone();
+                        let thread_local_id = thread_id.as_u64();
                         let result = tokio::task::spawn_blocking(move || {
-                            services.codex_config_read_file(path_for_task)
+                            let (entries, errors) = parse_conversation_jsonl(&jsonl);
+                            let imported = entries.len() as u64;
+                            services
+                                .import_conversation_entries(
+                                    scope.project_slug,
+                                    scope.workspace_name,
+                                    thread_local_id,
+                                    entries,
+                                )
+                                .map(|()| (imported, errors))
                         })
                         .await
                         .ok()
-                        .unwrap_or_else(|| Err("failed to join codex config read task".to_owned()));
+                        .unwrap_or_else(|| Err("failed to join thread import task".to_owned()));
+
+                        let _ = tx
+                            .send(EngineCommand::DispatchAction {
+                                action: Box::new(Action::ActivateWorkspaceThread {
+                                    workspace_id,
+                                    thread_id,
+                                }),
+                            })
+                            .await;
+                        if events.is_canceled() {
+                            return;
+                        }
 
                         match result {
-                            Ok(contents) => {
+                            Ok((imported, errors)) => {
                                 let _ = events.send(WsServerMessage::Event {
                                     rev,
-                                    event: Box::new(luban_api::ServerEvent::CodexConfigFileReady {
+                                    event: Box::new(luban_api::ServerEvent::ThreadJsonlImported {
                                         request_id,
-                                        path,
-                                        contents,
+                                        workspace_id: api_workspace_id,
+                                        thread_id: luban_api::WorkspaceThreadId(thread_local_id),
+                                        imported,
+                                        errors: errors
+                                            .into_iter()
+                                            .map(|e| luban_api::JsonlImportLineError {
+                                                line: e.line,
+                                                message: e.message,
+                                            })
+                                            .collect(),
                                     }),
                                 });
                             }
@@ -1766,228 +2893,234 @@ impl Engine {
                     return;
                 }
 
-                if let luban_api::ClientAction::AmpConfigReadFile { path } = &action {
+                if let luban_api::ClientAction::ExportPromptTemplates { dir } = &action {
+                    let dir = dir.clone();
+                    let path = expand_user_path(&dir);
                     let services = self.services.clone();
-                    let events = self.events.clone();
+                    let events = self.request_events(&request_id);
                     let request_id = request_id.clone();
                     let rev = self.rev;
-                    let path = path.clone();
                     tokio::spawn(async move {
-                        let path_for_task = path.clone();
                         let result = tokio::task::spawn_blocking(move || {
-                            services.amp_config_read_file(path_for_task)
+                            services.prompt_templates_export(path)
                         })
                         .await
                         .ok()
-                        .unwrap_or_else(|| Err("failed to join amp config read task".to_owned()));
-
-                        match result {
-                            Ok(contents) => {
-                                let _ = events.send(WsServerMessage::Event {
-                                    rev,
-                                    event: Box::new(luban_api::ServerEvent::AmpConfigFileReady {
-                                        request_id,
-                                        path,
-                                        contents,
-                                    }),
-                                });
-                            }
-                            Err(message) => {
-                                let _ = events.send(WsServerMessage::Error {
-                                    request_id: Some(request_id),
-                                    message,
-                                });
-                            }
+                        .unwrap_or_else(|| {
+                            Err("failed to join prompt template export task".to_owned())
+                        });
+                        if events.is_canceled() {
+                            return;
                         }
+                        let message = match result {
+                            Ok(written) => WsServerMessage::Event {
+                                rev,
+                                event: Box::new(luban_api::ServerEvent::PromptTemplatesExported {
+                                    request_id,
+                                    dir,
+                                    written,
+                                }),
+                            },
+                            Err(message) => WsServerMessage::Error {
+                                request_id: Some(request_id),
+                                message,
+                            },
+                        };
+                        let _ = events.send(message);
                     });
 
                     let _ = reply.send(Ok(self.rev));
                     return;
                 }
 
-                if let luban_api::ClientAction::CodexConfigWriteFile { path, contents } = &action {
+                if let luban_api::ClientAction::ImportPromptTemplatesFromDir { dir, force } =
+                    &action
+                {
+                    let path = expand_user_path(dir);
+                    let force = *force;
                     let services = self.services.clone();
-                    let events = self.events.clone();
+                    let tx = self.tx.clone();
+                    let events = self.request_events(&request_id);
                     let request_id = request_id.clone();
                     let rev = self.rev;
-                    let path = path.clone();
-                    let contents = contents.clone();
                     tokio::spawn(async move {
-                        let path_for_task = path.clone();
                         let result = tokio::task::spawn_blocking(move || {
-                            services.codex_config_write_file(path_for_task, contents)
+                            let report = services.prompt_templates_import(path, force)?;
+                            // Reason: the import writes storage directly, so refresh the in-memory
+                            // templates from it rather than replaying each change.
+                            let task_templates = services.task_prompt_templates_load()?;
+                            let system_templates = services.system_prompt_templates_load()?;
+                            Ok::<_, String>((report, task_templates, system_templates))
                         })
                         .await
                         .ok()
                         .unwrap_or_else(|| {
-                            Err("failed to join codex config write task".to_owned())
+                            Err("failed to join prompt template import task".to_owned())
                         });
 
-                        match result {
-                            Ok(()) => {
-                                let _ = events.send(WsServerMessage::Event {
+                        let message = match result {
+                            Ok((report, task_templates, system_templates)) => {
+                                for action in [
+                                    Action::TaskPromptTemplatesLoaded {
+                                        templates: task_templates,
+                                    },
+                                    Action::SystemPromptTemplatesLoaded {
+                                        templates: system_templates,
+                                    },
+                                ] {
+                                    let _ = tx
+                                        .send(EngineCommand::DispatchAction {
+                                            action: Box::new(action),
+                                        })
+                                        .await;
+                                }
+                                WsServerMessage::Event {
                                     rev,
-                                    event: Box::new(luban_api::ServerEvent::CodexConfigFileSaved {
-                                        request_id,
-                                        path,
-                                    }),
-                                });
-                            }
-                            Err(message) => {
-                                let _ = events.send(WsServerMessage::Error {
-                                    request_id: Some(request_id),
-                                    message,
-                                });
+                                    event: Box::new(
+                                        luban_api::ServerEvent::PromptTemplatesImported {
+                                            request_id,
+                                            imported: report.imported,
+                                            conflicts: report.conflicts,
+                                        },
+                                    ),
+                                }
                             }
+                            Err(message) => WsServerMessage::Error {
+                                request_id: Some(request_id),
+                                message,
+                            },
+                        };
+                        if events.is_canceled() {
+                            return;
                         }
+                        let _ = events.send(message);
                     });
 
                     let _ = reply.send(Ok(self.rev));
                     return;
                 }
 
-                if let luban_api::ClientAction::AmpConfigWriteFile { path, contents } = &action {
+                if let luban_api::ClientAction::PreviewWorkspace {
+                    project_id,
+                    branch_name_hint,
+                } = &action
+                {
+                    let path = expand_user_path(&project_id.0);
+                    let Some(project) = find_project_id_by_path(&self.state, &path)
+                        .and_then(|id| self.state.projects.iter().find(|p| p.id == id))
+                    else {
+                        let _ = reply.send(Err("project not found".to_owned()));
+                        return;
+                    };
+
+                    let project_path = project.path.clone();
+                    let project_slug = project.slug.clone();
+                    let worktree_root = project.worktree_root.clone();
+                    let branch_name_template = project.branch_name_template();
+                    let branch_name_hint = branch_name_hint.clone();
                     let services = self.services.clone();
-                    let events = self.events.clone();
+                    let events = self.request_events(&request_id);
                     let request_id = request_id.clone();
                     let rev = self.rev;
-                    let path = path.clone();
-                    let contents = contents.clone();
                     tokio::spawn(async move {
-                        let path_for_task = path.clone();
                         let result = tokio::task::spawn_blocking(move || {
-                            services.amp_config_write_file(path_for_task, contents)
+                            services.preview_workspace(
+                                project_path,
+                                project_slug,
+                                branch_name_hint,
+                                worktree_root,
+                                branch_name_template,
+                            )
                         })
                         .await
                         .ok()
-                        .unwrap_or_else(|| Err("failed to join amp config write task".to_owned()));
-
-                        match result {
-                            Ok(()) => {
-                                let _ = events.send(WsServerMessage::Event {
-                                    rev,
-                                    event: Box::new(luban_api::ServerEvent::AmpConfigFileSaved {
-                                        request_id,
-                                        path,
-                                    }),
-                                });
-                            }
-                            Err(message) => {
-                                let _ = events.send(WsServerMessage::Error {
-                                    request_id: Some(request_id),
-                                    message,
-                                });
-                            }
-                        }
-                    });
-
-                    let _ = reply.send(Ok(self.rev));
-                    return;
-                }
-
-                if matches!(action, luban_api::ClientAction::ClaudeConfigTree) {
-                    fn map_entry(
-                        entry: luban_domain::ClaudeConfigEntry,
-                    ) -> luban_api::ClaudeConfigEntrySnapshot {
-                        luban_api::ClaudeConfigEntrySnapshot {
-                            path: entry.path,
-                            name: entry.name,
-                            kind: match entry.kind {
-                                luban_domain::ClaudeConfigEntryKind::File => {
-                                    luban_api::ClaudeConfigEntryKind::File
-                                }
-                                luban_domain::ClaudeConfigEntryKind::Folder => {
-                                    luban_api::ClaudeConfigEntryKind::Folder
-                                }
+                        .unwrap_or_else(|| Err("failed to join preview workspace task".to_owned()));
+
+                        let message = match result {
+                            Ok(preview) => WsServerMessage::Event {
+                                rev,
+                                event: Box::new(luban_api::ServerEvent::WorkspacePreview {
+                                    request_id,
+                                    workspace_name: preview.workspace_name,
+                                    branch_name: preview.branch_name,
+                                    worktree_path: preview
+                                        .worktree_path
+                                        .to_string_lossy()
+                                        .into_owned(),
+                                }),
                             },
-                            children: entry.children.into_iter().map(map_entry).collect(),
-                        }
-                    }
-
-                    let services = self.services.clone();
-                    let events = self.events.clone();
-                    let request_id = request_id.clone();
-                    let rev = self.rev;
-                    tokio::spawn(async move {
-                        let result =
-                            tokio::task::spawn_blocking(move || services.claude_config_tree())
-                                .await
-                                .ok()
-                                .unwrap_or_else(|| {
-                                    Err("failed to join claude config tree task".to_owned())
-                                });
-
-                        match result {
-                            Ok(tree) => {
-                                let tree = tree.into_iter().map(map_entry).collect();
-                                let _ = events.send(WsServerMessage::Event {
-                                    rev,
-                                    event: Box::new(
-                                        luban_api::ServerEvent::ClaudeConfigTreeReady {
-                                            request_id,
-                                            tree,
-                                        },
-                                    ),
-                                });
-                            }
-                            Err(message) => {
-                                let _ = events.send(WsServerMessage::Error {
-                                    request_id: Some(request_id),
-                                    message,
-                                });
-                            }
+                            Err(message) => WsServerMessage::Error {
+                                request_id: Some(request_id),
+                                message,
+                            },
+                        };
+                        if events.is_canceled() {
+                            return;
                         }
+                        let _ = events.send(message);
                     });
 
                     let _ = reply.send(Ok(self.rev));
                     return;
                 }
 
-                if let luban_api::ClientAction::ClaudeConfigListDir { path } = &action {
-                    fn map_entry(
-                        entry: luban_domain::ClaudeConfigEntry,
-                    ) -> luban_api::ClaudeConfigEntrySnapshot {
-                        luban_api::ClaudeConfigEntrySnapshot {
-                            path: entry.path,
-                            name: entry.name,
-                            kind: match entry.kind {
-                                luban_domain::ClaudeConfigEntryKind::File => {
-                                    luban_api::ClaudeConfigEntryKind::File
-                                }
-                                luban_domain::ClaudeConfigEntryKind::Folder => {
-                                    luban_api::ClaudeConfigEntryKind::Folder
-                                }
-                            },
-                            children: entry.children.into_iter().map(map_entry).collect(),
-                        }
-                    }
+                if let luban_api::ClientAction::SearchConversations {
+                    workspace_id: api_workspace_id,
+                    query,
+                    thread_id: api_thread_id,
+                } = &action
+                {
+                    let workspace_id = WorkspaceId::from_u64(api_workspace_id.0);
+                    let Some(scope) = workspace_scope(&self.state, workspace_id) else {
+                        let _ = reply.send(Err("workspace not found".to_owned()));
+                        return;
+                    };
 
+                    let query = query.clone();
+                    let thread_local_id = api_thread_id.map(|id| id.0);
                     let services = self.services.clone();
-                    let events = self.events.clone();
+                    let events = self.request_events(&request_id);
                     let request_id = request_id.clone();
                     let rev = self.rev;
-                    let path = path.clone();
                     tokio::spawn(async move {
-                        let path_for_task = path.clone();
                         let result = tokio::task::spawn_blocking(move || {
-                            services.claude_config_list_dir(path_for_task)
+                            if query.trim().is_empty() {
+                                return Ok(Vec::new());
+                            }
+                            services.search_conversations(
+                                scope.project_slug,
+                                scope.workspace_name,
+                                query,
+                                thread_local_id,
+                                CONVERSATION_SEARCH_MAX_RESULTS,
+                            )
                         })
                         .await
                         .ok()
                         .unwrap_or_else(|| {
-                            Err("failed to join claude config list dir task".to_owned())
+                            Err("failed to join conversation search task".to_owned())
                         });
 
+                        if events.is_canceled() {
+                            return;
+                        }
                         match result {
-                            Ok(entries) => {
-                                let entries = entries.into_iter().map(map_entry).collect();
+                            Ok(matches) => {
+                                let matches = matches
+                                    .into_iter()
+                                    .map(|m| luban_api::ConversationSearchMatch {
+                                        thread_id: luban_api::WorkspaceThreadId(m.thread_id),
+                                        entry_id: m.entry_id,
+                                        entry_index: m.entry_index,
+                                        snippet: m.snippet,
+                                    })
+                                    .collect();
                                 let _ = events.send(WsServerMessage::Event {
                                     rev,
                                     event: Box::new(
-                                        luban_api::ServerEvent::ClaudeConfigListDirReady {
+                                        luban_api::ServerEvent::ConversationSearchResults {
                                             request_id,
-                                            path,
-                                            entries,
+                                            matches,
                                         },
                                     ),
                                 });
@@ -2005,34 +3138,99 @@ impl Engine {
                     return;
                 }
 
-                if let luban_api::ClientAction::ClaudeConfigReadFile { path } = &action {
+                if let luban_api::ClientAction::GeneratePrDescription {
+                    workspace_id: api_workspace_id,
+                    thread_id: api_thread_id,
+                } = &action
+                {
+                    let workspace_id = WorkspaceId::from_u64(api_workspace_id.0);
+                    let thread_id = WorkspaceThreadId::from_u64(api_thread_id.0);
+                    let Some(worktree_path) = self
+                        .state
+                        .workspace(workspace_id)
+                        .map(|workspace| workspace.worktree_path.clone())
+                    else {
+                        let _ = reply.send(Err("workspace not found".to_owned()));
+                        return;
+                    };
+                    let Some(conversation) = self
+                        .state
+                        .workspace_thread_conversation(workspace_id, thread_id)
+                    else {
+                        let _ = reply.send(Err("task not found".to_owned()));
+                        return;
+                    };
+                    let agent_messages = conversation
+                        .entries
+                        .iter()
+                        .filter_map(|entry| match entry {
+                            luban_domain::ConversationEntry::AgentEvent {
+                                event: luban_domain::AgentEvent::Message { text, .. },
+                                ..
+                            } => Some(text.clone()),
+                            _ => None,
+                        })
+                        .collect::<Vec<_>>();
+                    let runner = conversation.agent_runner;
+                    let model_id = conversation.agent_model_id.clone();
+                    let thinking_effort = conversation.thinking_effort;
+                    let amp_mode = conversation.amp_mode.clone();
+
+                    let api_workspace_id = *api_workspace_id;
+                    let api_thread_id = *api_thread_id;
                     let services = self.services.clone();
-                    let events = self.events.clone();
+                    let events = self.request_events(&request_id);
                     let request_id = request_id.clone();
                     let rev = self.rev;
-                    let path = path.clone();
                     tokio::spawn(async move {
-                        let path_for_task = path.clone();
                         let result = tokio::task::spawn_blocking(move || {
-                            services.claude_config_read_file(path_for_task)
+                            let files = crate::pr_description::unique_changed_files(
+                                crate::git_changes::collect_changes(&worktree_path)
+                                    .map_err(|err| format!("{err:#}"))?,
+                            );
+                            if files.is_empty() {
+                                return Ok::<_, String>((
+                                    crate::pr_description::NO_CHANGES_MESSAGE.to_owned(),
+                                    false,
+                                ));
+                            }
+                            let input =
+                                crate::pr_description::generator_input(&files, &agent_messages);
+                            let body = services
+                                .task_generate_pr_description(
+                                    input,
+                                    runner,
+                                    model_id,
+                                    thinking_effort,
+                                    amp_mode,
+                                )
+                                .unwrap_or_else(|message| {
+                                    tracing::warn!(
+                                        message = %message,
+                                        "failed to generate pr description; using the template"
+                                    );
+                                    crate::pr_description::template_body(&files, &agent_messages)
+                                });
+                            Ok((body, true))
                         })
                         .await
                         .ok()
-                        .unwrap_or_else(|| {
-                            Err("failed to join claude config read task".to_owned())
-                        });
+                        .unwrap_or_else(|| Err("failed to join pr description task".to_owned()));
 
+                        if events.is_canceled() {
+                            return;
+                        }
                         match result {
-                            Ok(contents) => {
+                            Ok((body, has_changes)) => {
                                 let _ = events.send(WsServerMessage::Event {
                                     rev,
-                                    event: Box::new(
-                                        luban_api::ServerEvent::ClaudeConfigFileReady {
-                                            request_id,
-                                            path,
-                                            contents,
-                                        },
-                                    ),
+                                    event: Box::new(luban_api::ServerEvent::PrDescriptionReady {
+                                        request_id,
+                                        workspace_id: api_workspace_id,
+                                        thread_id: api_thread_id,
+                                        body,
+                                        has_changes,
+                                    }),
                                 });
                             }
                             Err(message) => {
@@ -2048,37 +3246,131 @@ impl Engine {
                     return;
                 }
 
-                if let luban_api::ClientAction::ClaudeConfigWriteFile { path, contents } = &action {
+                if let luban_api::ClientAction::CompactConversation {
+                    workspace_id: api_workspace_id,
+                    thread_id: api_thread_id,
+                    before_entry_id,
+                } = &action
+                {
+                    let workspace_id = WorkspaceId::from_u64(api_workspace_id.0);
+                    let thread_id = WorkspaceThreadId::from_u64(api_thread_id.0);
+                    let Some(scope) = workspace_scope(&self.state, workspace_id) else {
+                        let _ = reply.send(Err("workspace not found".to_owned()));
+                        return;
+                    };
+                    let Some(conversation) = self
+                        .state
+                        .workspace_thread_conversation(workspace_id, thread_id)
+                    else {
+                        let _ = reply.send(Err("task not found".to_owned()));
+                        return;
+                    };
+                    let in_memory_entries =
+                        (conversation.entries_start == 0).then(|| conversation.entries.clone());
+                    let collapsed_ranges = conversation.collapsed_ranges.clone();
+                    let title = conversation.title.clone();
+                    let runner = conversation.agent_runner;
+                    let model_id = conversation.agent_model_id.clone();
+                    let thinking_effort = conversation.thinking_effort;
+                    let amp_mode = conversation.amp_mode.clone();
+
+                    let api_workspace_id = *api_workspace_id;
+                    let api_thread_id = *api_thread_id;
+                    let before_entry_id = before_entry_id.clone();
                     let services = self.services.clone();
-                    let events = self.events.clone();
+                    let tx = self.tx.clone();
+                    let events = self.request_events(&request_id);
                     let request_id = request_id.clone();
                     let rev = self.rev;
-                    let path = path.clone();
-                    let contents = contents.clone();
                     tokio::spawn(async move {
-                        let path_for_task = path.clone();
                         let result = tokio::task::spawn_blocking(move || {
-                            services.claude_config_write_file(path_for_task, contents)
+                            let entries = match in_memory_entries {
+                                Some(entries) => entries,
+                                None => {
+                                    services
+                                        .load_conversation(
+                                            scope.project_slug,
+                                            scope.workspace_name,
+                                            thread_id.as_u64(),
+                                        )?
+                                        .entries
+                                }
+                            };
+                            let range = luban_domain::compaction_range(
+                                &entries,
+                                &collapsed_ranges,
+                                &before_entry_id,
+                            )
+                            .ok_or_else(|| {
+                                "no uncollapsed entries before the given entry".to_owned()
+                            })?;
+                            let summarized = &entries[range];
+                            let transcript = crate::transcript::render_transcript(
+                                &title,
+                                &summarized
+                                    .iter()
+                                    .map(map_conversation_entry)
+                                    .collect::<Vec<_>>(),
+                            );
+                            let summary_markdown = services.task_summarize_conversation(
+                                transcript,
+                                runner,
+                                model_id,
+                                thinking_effort,
+                                amp_mode,
+                            )?;
+                            let entry_id = |entry: &luban_api::ConversationEntry| match entry {
+                                luban_api::ConversationEntry::SystemEvent(e) => e.entry_id.clone(),
+                                luban_api::ConversationEntry::UserEvent(e) => e.entry_id.clone(),
+                                luban_api::ConversationEntry::AgentEvent(e) => e.entry_id.clone(),
+                            };
+                            let first = map_conversation_entry(&summarized[0]);
+                            let last = map_conversation_entry(&summarized[summarized.len() - 1]);
+                            let entry_count = summarized.len() as u64;
+                            Ok::<_, String>((
+                                Action::ConversationCompacted {
+                                    workspace_id,
+                                    thread_id,
+                                    start_entry_id: entry_id(&first),
+                                    end_entry_id: entry_id(&last),
+                                    entry_count,
+                                    summary_markdown,
+                                },
+                                entry_count,
+                            ))
                         })
                         .await
                         .ok()
                         .unwrap_or_else(|| {
-                            Err("failed to join claude config write task".to_owned())
+                            Err("failed to join compact conversation task".to_owned())
                         });
 
                         match result {
-                            Ok(()) => {
+                            Ok((action, collapsed_entries)) => {
+                                let _ = tx
+                                    .send(EngineCommand::DispatchAction {
+                                        action: Box::new(action),
+                                    })
+                                    .await;
+                                if events.is_canceled() {
+                                    return;
+                                }
                                 let _ = events.send(WsServerMessage::Event {
                                     rev,
                                     event: Box::new(
-                                        luban_api::ServerEvent::ClaudeConfigFileSaved {
+                                        luban_api::ServerEvent::ConversationCompacted {
                                             request_id,
-                                            path,
+                                            workspace_id: api_workspace_id,
+                                            thread_id: api_thread_id,
+                                            collapsed_entries,
                                         },
                                     ),
                                 });
                             }
                             Err(message) => {
+                                if events.is_canceled() {
+                                    return;
+                                }
                                 let _ = events.send(WsServerMessage::Error {
                                     request_id: Some(request_id),
                                     message,
@@ -2091,51 +3383,78 @@ impl Engine {
                     return;
                 }
 
-                // --- Droid config handlers ---
+                if matches!(action, luban_api::ClientAction::CodexConfigTree) {
+                    fn map_entry(
+                        entry: luban_domain::CodexConfigEntry,
+                    ) -> luban_api::CodexConfigEntrySnapshot {
+                        luban_api::CodexConfigEntrySnapshot {
+                            path: entry.path,
+                            name: entry.name,
+                            kind: match entry.kind {
+                                luban_domain::CodexConfigEntryKind::File => {
+                                    luban_api::CodexConfigEntryKind::File
+                                }
+                                luban_domain::CodexConfigEntryKind::Folder => {
+                                    luban_api::CodexConfigEntryKind::Folder
+                                }
+                            },
+                            children: entry.children.into_iter().map(map_entry).collect(),
+                        }
+                    }
 
-                if matches!(action, luban_api::ClientAction::DroidCheck) {
                     let services = self.services.clone();
-                    let events = self.events.clone();
+                    let events = self.request_events(&request_id);
                     let request_id = request_id.clone();
                     let rev = self.rev;
                     tokio::spawn(async move {
-                        let result = tokio::task::spawn_blocking(move || services.droid_check())
-                            .await
-                            .ok()
-                            .unwrap_or_else(|| Err("failed to join droid check task".to_owned()));
-
-                        let (ok, message) = match result {
-                            Ok(()) => (true, None),
-                            Err(message) => (false, Some(message)),
-                        };
+                        if events.is_canceled() {
+                            return;
+                        }
+                        let result =
+                            tokio::task::spawn_blocking(move || services.codex_config_tree())
+                                .await
+                                .ok()
+                                .unwrap_or_else(|| {
+                                    Err("failed to join codex config tree task".to_owned())
+                                });
 
-                        let _ = events.send(WsServerMessage::Event {
-                            rev,
-                            event: Box::new(luban_api::ServerEvent::DroidCheckReady {
-                                request_id,
-                                ok,
-                                message,
-                            }),
-                        });
+                        match result {
+                            Ok(tree) => {
+                                let tree = tree.into_iter().map(map_entry).collect();
+                                let _ = events.send(WsServerMessage::Event {
+                                    rev,
+                                    event: Box::new(luban_api::ServerEvent::CodexConfigTreeReady {
+                                        request_id,
+                                        tree,
+                                    }),
+                                });
+                            }
+                            Err(message) => {
+                                let _ = events.send(WsServerMessage::Error {
+                                    request_id: Some(request_id),
+                                    message,
+                                });
+                            }
+                        }
                     });
 
                     let _ = reply.send(Ok(self.rev));
                     return;
                 }
 
-                if matches!(action, luban_api::ClientAction::DroidConfigTree) {
+                if matches!(action, luban_api::ClientAction::AmpConfigTree) {
                     fn map_entry(
-                        entry: luban_domain::DroidConfigEntry,
-                    ) -> luban_api::DroidConfigEntrySnapshot {
-                        luban_api::DroidConfigEntrySnapshot {
+                        entry: luban_domain::AmpConfigEntry,
+                    ) -> luban_api::AmpConfigEntrySnapshot {
+                        luban_api::AmpConfigEntrySnapshot {
                             path: entry.path,
                             name: entry.name,
                             kind: match entry.kind {
-                                luban_domain::DroidConfigEntryKind::File => {
-                                    luban_api::DroidConfigEntryKind::File
+                                luban_domain::AmpConfigEntryKind::File => {
+                                    luban_api::AmpConfigEntryKind::File
                                 }
-                                luban_domain::DroidConfigEntryKind::Folder => {
-                                    luban_api::DroidConfigEntryKind::Folder
+                                luban_domain::AmpConfigEntryKind::Folder => {
+                                    luban_api::AmpConfigEntryKind::Folder
                                 }
                             },
                             children: entry.children.into_iter().map(map_entry).collect(),
@@ -2143,24 +3462,27 @@ impl Engine {
                     }
 
                     let services = self.services.clone();
-                    let events = self.events.clone();
+                    let events = self.request_events(&request_id);
                     let request_id = request_id.clone();
                     let rev = self.rev;
                     tokio::spawn(async move {
+                        if events.is_canceled() {
+                            return;
+                        }
                         let result =
-                            tokio::task::spawn_blocking(move || services.droid_config_tree())
+                            tokio::task::spawn_blocking(move || services.amp_config_tree())
                                 .await
                                 .ok()
                                 .unwrap_or_else(|| {
-                                    Err("failed to join droid config tree task".to_owned())
+                                    Err("failed to join amp config tree task".to_owned())
                                 });
 
                         match result {
-                            Ok(entries) => {
-                                let tree = entries.into_iter().map(map_entry).collect();
+                            Ok(tree) => {
+                                let tree = tree.into_iter().map(map_entry).collect();
                                 let _ = events.send(WsServerMessage::Event {
                                     rev,
-                                    event: Box::new(luban_api::ServerEvent::DroidConfigTreeReady {
+                                    event: Box::new(luban_api::ServerEvent::AmpConfigTreeReady {
                                         request_id,
                                         tree,
                                     }),
@@ -2179,19 +3501,19 @@ impl Engine {
                     return;
                 }
 
-                if let luban_api::ClientAction::DroidConfigListDir { path } = &action {
+                if let luban_api::ClientAction::CodexConfigListDir { path } = &action {
                     fn map_entry(
-                        entry: luban_domain::DroidConfigEntry,
-                    ) -> luban_api::DroidConfigEntrySnapshot {
-                        luban_api::DroidConfigEntrySnapshot {
+                        entry: luban_domain::CodexConfigEntry,
+                    ) -> luban_api::CodexConfigEntrySnapshot {
+                        luban_api::CodexConfigEntrySnapshot {
                             path: entry.path,
                             name: entry.name,
                             kind: match entry.kind {
-                                luban_domain::DroidConfigEntryKind::File => {
-                                    luban_api::DroidConfigEntryKind::File
+                                luban_domain::CodexConfigEntryKind::File => {
+                                    luban_api::CodexConfigEntryKind::File
                                 }
-                                luban_domain::DroidConfigEntryKind::Folder => {
-                                    luban_api::DroidConfigEntryKind::Folder
+                                luban_domain::CodexConfigEntryKind::Folder => {
+                                    luban_api::CodexConfigEntryKind::Folder
                                 }
                             },
                             children: entry.children.into_iter().map(map_entry).collect(),
@@ -2199,19 +3521,22 @@ impl Engine {
                     }
 
                     let services = self.services.clone();
-                    let events = self.events.clone();
+                    let events = self.request_events(&request_id);
                     let request_id = request_id.clone();
                     let rev = self.rev;
                     let path = path.clone();
                     tokio::spawn(async move {
+                        if events.is_canceled() {
+                            return;
+                        }
                         let path_for_task = path.clone();
                         let result = tokio::task::spawn_blocking(move || {
-                            services.droid_config_list_dir(path_for_task)
+                            services.codex_config_list_dir(path_for_task)
                         })
                         .await
                         .ok()
                         .unwrap_or_else(|| {
-                            Err("failed to join droid config list dir task".to_owned())
+                            Err("failed to join codex config list dir task".to_owned())
                         });
 
                         match result {
@@ -2220,7 +3545,7 @@ impl Engine {
                                 let _ = events.send(WsServerMessage::Event {
                                     rev,
                                     event: Box::new(
-                                        luban_api::ServerEvent::DroidConfigListDirReady {
+                                        luban_api::ServerEvent::CodexConfigListDirReady {
                                             request_id,
                                             path,
                                             entries,
@@ -2241,26 +3566,92 @@ impl Engine {
                     return;
                 }
 
-                if let luban_api::ClientAction::DroidConfigReadFile { path } = &action {
+                if let luban_api::ClientAction::AmpConfigListDir { path } = &action {
+                    fn map_entry(
+                        entry: luban_domain::AmpConfigEntry,
+                    ) -> luban_api::AmpConfigEntrySnapshot {
+                        luban_api::AmpConfigEntrySnapshot {
+                            path: entry.path,
+                            name: entry.name,
+                            kind: match entry.kind {
+                                luban_domain::AmpConfigEntryKind::File => {
+                                    luban_api::AmpConfigEntryKind::File
+                                }
+                                luban_domain::AmpConfigEntryKind::Folder => {
+                                    luban_api::AmpConfigEntryKind::Folder
+                                }
+                            },
+                            children: entry.children.into_iter().map(map_entry).collect(),
+                        }
+                    }
+
                     let services = self.services.clone();
-                    let events = self.events.clone();
+                    let events = self.request_events(&request_id);
                     let request_id = request_id.clone();
                     let rev = self.rev;
                     let path = path.clone();
                     tokio::spawn(async move {
+                        if events.is_canceled() {
+                            return;
+                        }
                         let path_for_task = path.clone();
                         let result = tokio::task::spawn_blocking(move || {
-                            services.droid_config_read_file(path_for_task)
+                            services.amp_config_list_dir(path_for_task)
                         })
                         .await
                         .ok()
-                        .unwrap_or_else(|| Err("failed to join droid config read task".to_owned()));
+                        .unwrap_or_else(|| Err("failed to join amp config list task".to_owned()));
+
+                        match result {
+                            Ok(entries) => {
+                                let entries = entries.into_iter().map(map_entry).collect();
+                                let _ = events.send(WsServerMessage::Event {
+                                    rev,
+                                    event: Box::new(
+                                        luban_api::ServerEvent::AmpConfigListDirReady {
+                                            request_id,
+                                            path,
+                                            entries,
+                                        },
+                                    ),
+                                });
+                            }
+                            Err(message) => {
+                                let _ = events.send(WsServerMessage::Error {
+                                    request_id: Some(request_id),
+                                    message,
+                                });
+                            }
+                        }
+                    });
+
+                    let _ = reply.send(Ok(self.rev));
+                    return;
+                }
+
+                if let luban_api::ClientAction::CodexConfigReadFile { path } = &action {
+                    let services = self.services.clone();
+                    let events = self.request_events(&request_id);
+                    let request_id = request_id.clone();
+                    let rev = self.rev;
+                    let path = path.clone();
+                    tokio::spawn(async move {
+                        if events.is_canceled() {
+                            return;
+                        }
+                        let path_for_task = path.clone();
+                        let result = tokio::task::spawn_blocking(move || {
+                            services.codex_config_read_file(path_for_task)
+                        })
+                        .await
+                        .ok()
+                        .unwrap_or_else(|| Err("failed to join codex config read task".to_owned()));
 
                         match result {
                             Ok(contents) => {
                                 let _ = events.send(WsServerMessage::Event {
                                     rev,
-                                    event: Box::new(luban_api::ServerEvent::DroidConfigFileReady {
+                                    event: Box::new(luban_api::ServerEvent::CodexConfigFileReady {
                                         request_id,
                                         path,
                                         contents,
@@ -2280,31 +3671,32 @@ impl Engine {
                     return;
                 }
 
-                if let luban_api::ClientAction::DroidConfigWriteFile { path, contents } = &action {
+                if let luban_api::ClientAction::AmpConfigReadFile { path } = &action {
                     let services = self.services.clone();
-                    let events = self.events.clone();
+                    let events = self.request_events(&request_id);
                     let request_id = request_id.clone();
                     let rev = self.rev;
                     let path = path.clone();
-                    let contents = contents.clone();
                     tokio::spawn(async move {
+                        if events.is_canceled() {
+                            return;
+                        }
                         let path_for_task = path.clone();
                         let result = tokio::task::spawn_blocking(move || {
-                            services.droid_config_write_file(path_for_task, contents)
+                            services.amp_config_read_file(path_for_task)
                         })
                         .await
                         .ok()
-                        .unwrap_or_else(|| {
-                            Err("failed to join droid config write task".to_owned())
-                        });
+                        .unwrap_or_else(|| Err("failed to join amp config read task".to_owned()));
 
                         match result {
-                            Ok(()) => {
+                            Ok(contents) => {
                                 let _ = events.send(WsServerMessage::Event {
                                     rev,
-                                    event: Box::new(luban_api::ServerEvent::DroidConfigFileSaved {
+                                    event: Box::new(luban_api::ServerEvent::AmpConfigFileReady {
                                         request_id,
                                         path,
+                                        contents,
                                     }),
                                 });
                             }
@@ -2321,3961 +3713,10682 @@ impl Engine {
                     return;
                 }
 
-                if let luban_api::ClientAction::OpenWorkspace { workspace_id } = &action {
-                    self.maybe_refresh_pull_request(WorkspaceId::from_u64(workspace_id.0));
-                }
-
-                match &action {
-                    luban_api::ClientAction::DeleteProject { project_id } => {
-                        let path = expand_user_path(&project_id.0);
-                        let Some(id) = find_project_id_by_path(&self.state, &path) else {
-                            let _ = reply.send(Err("project not found".to_owned()));
-                            return;
-                        };
-                        self.process_action_queue(Action::DeleteProject { project_id: id })
-                            .await;
-                        let _ = reply.send(Ok(self.rev));
-                        return;
-                    }
-                    luban_api::ClientAction::DeleteWorkspaceThread {
-                        workspace_id,
-                        thread_id,
-                    } => {
-                        let workspace_id = WorkspaceId::from_u64(workspace_id.0);
-                        let thread_id = WorkspaceThreadId::from_u64(thread_id.0);
-                        let Some(scope) = workspace_scope(&self.state, workspace_id) else {
-                            let _ = reply.send(Err("workspace not found".to_owned()));
+                if let luban_api::ClientAction::CodexConfigWriteFile { path, contents } = &action {
+                    let services = self.services.clone();
+                    let events = self.request_events(&request_id);
+                    let request_id = request_id.clone();
+                    let rev = self.rev;
+                    let path = path.clone();
+                    let contents = contents.clone();
+                    tokio::spawn(async move {
+                        if events.is_canceled() {
                             return;
-                        };
-                        let services = self.services.clone();
-                        let project_slug = scope.project_slug.clone();
-                        let workspace_name = scope.workspace_name.clone();
-                        let delete_result = tokio::task::spawn_blocking(move || {
-                            services.delete_conversation_thread(
-                                project_slug,
-                                workspace_name,
-                                thread_id.as_u64(),
-                            )
+                        }
+                        let path_for_task = path.clone();
+                        let result = tokio::task::spawn_blocking(move || {
+                            services.codex_config_write_file(path_for_task, contents)
                         })
                         .await
                         .ok()
-                        .unwrap_or_else(|| Err("failed to join delete thread task".to_owned()));
+                        .unwrap_or_else(|| {
+                            Err("failed to join codex config write task".to_owned())
+                        });
 
-                        if let Err(msg) = delete_result {
-                            let _ = reply.send(Err(msg));
-                            return;
+                        match result {
+                            Ok(()) => {
+                                let _ = events.send(WsServerMessage::Event {
+                                    rev,
+                                    event: Box::new(luban_api::ServerEvent::CodexConfigFileSaved {
+                                        request_id,
+                                        path,
+                                    }),
+                                });
+                            }
+                            Err(message) => {
+                                let _ = events.send(WsServerMessage::Error {
+                                    request_id: Some(request_id),
+                                    message,
+                                });
+                            }
                         }
+                    });
 
-                        // Purge in-memory state for the deleted thread
-                        self.process_action_queue(Action::WorkspaceThreadsPurged {
-                            workspace_id,
-                            thread_ids: vec![thread_id],
-                        })
-                        .await;
+                    let _ = reply.send(Ok(self.rev));
+                    return;
+                }
 
-                        // Refresh thread list from DB
-                        let services = self.services.clone();
-                        let project_slug = scope.project_slug;
-                        let workspace_name = scope.workspace_name;
-                        if let Ok(threads) = tokio::task::spawn_blocking(move || {
-                            services.list_conversation_threads(project_slug, workspace_name)
+                if let luban_api::ClientAction::AmpConfigWriteFile { path, contents } = &action {
+                    let services = self.services.clone();
+                    let events = self.request_events(&request_id);
+                    let request_id = request_id.clone();
+                    let rev = self.rev;
+                    let path = path.clone();
+                    let contents = contents.clone();
+                    tokio::spawn(async move {
+                        if events.is_canceled() {
+                            return;
+                        }
+                        let path_for_task = path.clone();
+                        let result = tokio::task::spawn_blocking(move || {
+                            services.amp_config_write_file(path_for_task, contents)
                         })
                         .await
                         .ok()
-                        .unwrap_or_else(|| Err("failed to join list threads task".to_owned()))
-                        {
-                            self.process_action_queue(Action::WorkspaceThreadsLoaded {
-                                workspace_id,
-                                threads,
-                            })
-                            .await;
-                        }
+                        .unwrap_or_else(|| Err("failed to join amp config write task".to_owned()));
 
-                        let _ = reply.send(Ok(self.rev));
-                        return;
-                    }
-                    luban_api::ClientAction::ToggleProjectExpanded { project_id } => {
-                        let path = expand_user_path(&project_id.0);
-                        let Some(id) = find_project_id_by_path(&self.state, &path) else {
-                            let _ = reply.send(Err("project not found".to_owned()));
-                            return;
-                        };
-                        self.process_action_queue(Action::ToggleProjectExpanded { project_id: id })
-                            .await;
-                        let _ = reply.send(Ok(self.rev));
-                        return;
-                    }
-                    luban_api::ClientAction::CreateWorkspace { project_id } => {
-                        let path = expand_user_path(&project_id.0);
-                        let Some(id) = find_project_id_by_path(&self.state, &path) else {
-                            let _ = reply.send(Err("project not found".to_owned()));
-                            return;
-                        };
-                        self.process_action_queue(Action::CreateWorkspace {
-                            project_id: id,
-                            branch_name_hint: None,
-                        })
-                        .await;
-                        let _ = reply.send(Ok(self.rev));
-                        return;
-                    }
-                    luban_api::ClientAction::EnsureMainWorkspace { project_id } => {
-                        let path = expand_user_path(&project_id.0);
-                        let Some(id) = find_project_id_by_path(&self.state, &path) else {
-                            let _ = reply.send(Err("project not found".to_owned()));
-                            return;
-                        };
-                        self.process_action_queue(Action::EnsureMainWorkspace { project_id: id })
-                            .await;
-                        let _ = reply.send(Ok(self.rev));
-                        return;
-                    }
-                    luban_api::ClientAction::CancelAndSendAgentMessage {
-                        workspace_id,
-                        thread_id,
-                        text,
-                        attachments,
-                        runner,
-                        amp_mode,
-                    } => {
-                        let wid = WorkspaceId::from_u64(workspace_id.0);
-                        let tid = WorkspaceThreadId::from_u64(thread_id.0);
-                        self.process_action_queue(Action::CancelAgentTurn {
-                            workspace_id: wid,
-                            thread_id: tid,
-                        })
-                        .await;
-                        let runner = runner.map(map_api_agent_runner_kind);
-                        let amp_mode = if runner == Some(luban_domain::AgentRunnerKind::Amp) {
-                            amp_mode.clone()
-                        } else {
-                            None
-                        };
-                        self.process_action_queue(Action::SendAgentMessage {
-                            workspace_id: wid,
-                            thread_id: tid,
-                            text: text.clone(),
-                            attachments: attachments
-                                .iter()
-                                .cloned()
-                                .map(map_api_attachment)
-                                .collect(),
-                            runner,
-                            amp_mode,
-                        })
-                        .await;
-                        let _ = reply.send(Ok(self.rev));
-                        return;
-                    }
-                    _ => {}
-                }
+                        match result {
+                            Ok(()) => {
+                                let _ = events.send(WsServerMessage::Event {
+                                    rev,
+                                    event: Box::new(luban_api::ServerEvent::AmpConfigFileSaved {
+                                        request_id,
+                                        path,
+                                    }),
+                                });
+                            }
+                            Err(message) => {
+                                let _ = events.send(WsServerMessage::Error {
+                                    request_id: Some(request_id),
+                                    message,
+                                });
+                            }
+                        }
+                    });
 
-                let mapped = map_client_action(action);
-                let Some(action) = mapped else {
-                    let _ = reply.send(Err("unsupported action".to_owned()));
+                    let _ = reply.send(Ok(self.rev));
                     return;
-                };
-
-                self.process_action_queue(action).await;
-                let _ = reply.send(Ok(self.rev));
-            }
-            EngineCommand::DispatchAction { action } => {
-                if let Action::WorkspaceArchived { workspace_id } = action.as_ref() {
-                    self.auto_archive_workspaces.remove(workspace_id);
                 }
-                self.process_action_queue(*action).await;
-            }
-            EngineCommand::AutoArchiveWorkspace { workspace_id } => {
-                self.auto_archive_workspaces.insert(workspace_id);
-                self.process_action_queue(Action::ArchiveWorkspace { workspace_id })
-                    .await;
-            }
-            EngineCommand::RefreshPullRequests { workspace_id } => match workspace_id {
-                Some(id) => self.maybe_refresh_pull_request(id),
-                None => self.refresh_pull_requests_for_all_workspaces(),
-            },
-            EngineCommand::PullRequestInfoUpdated { workspace_id, info } => {
-                self.pull_requests_in_flight.remove(&workspace_id);
 
-                let now = Instant::now();
-                let previous = self.pull_requests.get(&workspace_id);
-                let (next_refresh_at, consecutive_empty) =
-                    pull_request_next_refresh_at(workspace_id, now, previous, info.as_ref());
+                if matches!(action, luban_api::ClientAction::ClaudeConfigTree) {
+                    fn map_entry(
+                        entry: luban_domain::ClaudeConfigEntry,
+                    ) -> luban_api::ClaudeConfigEntrySnapshot {
+                        luban_api::ClaudeConfigEntrySnapshot {
+                            path: entry.path,
+                            name: entry.name,
+                            kind: match entry.kind {
+                                luban_domain::ClaudeConfigEntryKind::File => {
+                                    luban_api::ClaudeConfigEntryKind::File
+                                }
+                                luban_domain::ClaudeConfigEntryKind::Folder => {
+                                    luban_api::ClaudeConfigEntryKind::Folder
+                                }
+                            },
+                            children: entry.children.into_iter().map(map_entry).collect(),
+                        }
+                    }
 
-                let changed = self
-                    .pull_requests
-                    .get(&workspace_id)
-                    .map(|e| e.info != info)
-                    .unwrap_or(true);
+                    let services = self.services.clone();
+                    let events = self.request_events(&request_id);
+                    let request_id = request_id.clone();
+                    let rev = self.rev;
+                    tokio::spawn(async move {
+                        if events.is_canceled() {
+                            return;
+                        }
+                        let result =
+                            tokio::task::spawn_blocking(move || services.claude_config_tree())
+                                .await
+                                .ok()
+                                .unwrap_or_else(|| {
+                                    Err("failed to join claude config tree task".to_owned())
+                                });
 
-                self.pull_requests.insert(
-                    workspace_id,
-                    PullRequestCacheEntry {
-                        info,
-                        next_refresh_at,
-                        consecutive_empty,
-                    },
-                );
+                        match result {
+                            Ok(tree) => {
+                                let tree = tree.into_iter().map(map_entry).collect();
+                                let _ = events.send(WsServerMessage::Event {
+                                    rev,
+                                    event: Box::new(
+                                        luban_api::ServerEvent::ClaudeConfigTreeReady {
+                                            request_id,
+                                            tree,
+                                        },
+                                    ),
+                                });
+                            }
+                            Err(message) => {
+                                let _ = events.send(WsServerMessage::Error {
+                                    request_id: Some(request_id),
+                                    message,
+                                });
+                            }
+                        }
+                    });
 
-                if changed {
-                    self.rev = self.rev.saturating_add(1);
-                    self.publish_app_snapshot();
+                    let _ = reply.send(Ok(self.rev));
+                    return;
                 }
 
-                if changed
-                    && let Some(pr) = self
-                        .pull_requests
-                        .get(&workspace_id)
-                        .and_then(|entry| entry.info.as_ref())
-                    && pr.state == DomainPullRequestState::Merged
-                {
-                    self.spawn_task_status_suggest_done_for_merged_pr(workspace_id, pr.number);
-                }
-            }
-            EngineCommand::PruneArchivedTasks => {
-                self.prune_archived_tasks().await;
-            }
-            EngineCommand::WorkspaceThreadsInvalidated { workspace_id } => {
-                self.workspace_threads_cache.remove(&workspace_id);
-                self.rev = self.rev.saturating_add(1);
-                self.publish_app_snapshot();
-            }
-            EngineCommand::WorkspaceBranchObserved {
-                workspace_id,
-                branch_name,
-            } => {
-                self.process_action_queue(Action::WorkspaceBranchSynced {
-                    workspace_id,
-                    branch_name,
-                })
-                .await;
-            }
-        }
-    }
+                if let luban_api::ClientAction::ClaudeConfigListDir { path } = &action {
+                    fn map_entry(
+                        entry: luban_domain::ClaudeConfigEntry,
+                    ) -> luban_api::ClaudeConfigEntrySnapshot {
+                        luban_api::ClaudeConfigEntrySnapshot {
+                            path: entry.path,
+                            name: entry.name,
+                            kind: match entry.kind {
+                                luban_domain::ClaudeConfigEntryKind::File => {
+                                    luban_api::ClaudeConfigEntryKind::File
+                                }
+                                luban_domain::ClaudeConfigEntryKind::Folder => {
+                                    luban_api::ClaudeConfigEntryKind::Folder
+                                }
+                            },
+                            children: entry.children.into_iter().map(map_entry).collect(),
+                        }
+                    }
 
-    fn spawn_task_status_suggest_done_for_merged_pr(
-        &self,
-        workspace_id: WorkspaceId,
-        pr_number: u64,
-    ) {
-        let Some(scope) = workspace_scope(&self.state, workspace_id) else {
-            return;
-        };
+                    let services = self.services.clone();
+                    let events = self.request_events(&request_id);
+                    let request_id = request_id.clone();
+                    let rev = self.rev;
+                    let path = path.clone();
+                    tokio::spawn(async move {
+                        if events.is_canceled() {
+                            return;
+                        }
+                        let path_for_task = path.clone();
+                        let result = tokio::task::spawn_blocking(move || {
+                            services.claude_config_list_dir(path_for_task)
+                        })
+                        .await
+                        .ok()
+                        .unwrap_or_else(|| {
+                            Err("failed to join claude config list dir task".to_owned())
+                        });
 
-        let services = self.services.clone();
-        let tx = self.tx.clone();
-        let project_slug = scope.project_slug;
-        let workspace_name = scope.workspace_name;
+                        match result {
+                            Ok(entries) => {
+                                let entries = entries.into_iter().map(map_entry).collect();
+                                let _ = events.send(WsServerMessage::Event {
+                                    rev,
+                                    event: Box::new(
+                                        luban_api::ServerEvent::ClaudeConfigListDirReady {
+                                            request_id,
+                                            path,
+                                            entries,
+                                        },
+                                    ),
+                                });
+                            }
+                            Err(message) => {
+                                let _ = events.send(WsServerMessage::Error {
+                                    request_id: Some(request_id),
+                                    message,
+                                });
+                            }
+                        }
+                    });
 
-        tokio::spawn(async move {
-            let thread_ids = tokio::task::spawn_blocking(move || {
-                services.list_conversation_tasks_for_merged_pr(
-                    project_slug.clone(),
-                    workspace_name.clone(),
-                    pr_number,
-                )
-            })
-            .await
-            .ok()
-            .and_then(|res| res.ok())
-            .unwrap_or_default();
+                    let _ = reply.send(Ok(self.rev));
+                    return;
+                }
 
-            if !thread_ids.is_empty() {
-                for thread_local_id in thread_ids {
-                    let _ = tx
-                        .send(EngineCommand::DispatchAction {
-                            action: Box::new(Action::TaskStatusSuggestionCreated {
-                                workspace_id,
-                                thread_id: WorkspaceThreadId::from_u64(thread_local_id),
-                                expected_current_task_status: luban_domain::TaskStatus::Validating,
-                                suggested_task_status: luban_domain::TaskStatus::Done,
-                                title: format!("Suggest moving to done (PR #{pr_number} merged)"),
-                                explanation_markdown: format!(
-                                    "- PR #{pr_number} is merged.\n- Consider marking this task as done."
-                                ),
-                            }),
+                if let luban_api::ClientAction::ClaudeConfigReadFile { path } = &action {
+                    let services = self.services.clone();
+                    let events = self.request_events(&request_id);
+                    let request_id = request_id.clone();
+                    let rev = self.rev;
+                    let path = path.clone();
+                    tokio::spawn(async move {
+                        if events.is_canceled() {
+                            return;
+                        }
+                        let path_for_task = path.clone();
+                        let result = tokio::task::spawn_blocking(move || {
+                            services.claude_config_read_file(path_for_task)
                         })
-                        .await;
-                }
+                        .await
+                        .ok()
+                        .unwrap_or_else(|| {
+                            Err("failed to join claude config read task".to_owned())
+                        });
 
-                // No automatic status updates: keep thread metadata stable until the user applies.
-            }
-        });
-    }
+                        match result {
+                            Ok(contents) => {
+                                let _ = events.send(WsServerMessage::Event {
+                                    rev,
+                                    event: Box::new(
+                                        luban_api::ServerEvent::ClaudeConfigFileReady {
+                                            request_id,
+                                            path,
+                                            contents,
+                                        },
+                                    ),
+                                });
+                            }
+                            Err(message) => {
+                                let _ = events.send(WsServerMessage::Error {
+                                    request_id: Some(request_id),
+                                    message,
+                                });
+                            }
+                        }
+                    });
 
-    async fn get_conversation_snapshot(
-        &self,
-        workspace_id: luban_api::WorkspaceId,
-        thread_id: luban_api::WorkspaceThreadId,
-        before: Option<u64>,
-        limit: Option<u64>,
-    ) -> anyhow::Result<ConversationSnapshot> {
-        if let Ok(snapshot) = self.conversation_snapshot(workspace_id, thread_id, before, limit) {
-            return Ok(snapshot);
-        }
+                    let _ = reply.send(Ok(self.rev));
+                    return;
+                }
 
-        const DEFAULT_ENTRIES_LIMIT: usize = 2000;
-        const MAX_ENTRIES_LIMIT: usize = 5000;
+                if let luban_api::ClientAction::ClaudeConfigWriteFile { path, contents } = &action {
+                    let services = self.services.clone();
+                    let events = self.request_events(&request_id);
+                    let request_id = request_id.clone();
+                    let rev = self.rev;
+                    let path = path.clone();
+                    let contents = contents.clone();
+                    tokio::spawn(async move {
+                        if events.is_canceled() {
+                            return;
+                        }
+                        let path_for_task = path.clone();
+                        let result = tokio::task::spawn_blocking(move || {
+                            services.claude_config_write_file(path_for_task, contents)
+                        })
+                        .await
+                        .ok()
+                        .unwrap_or_else(|| {
+                            Err("failed to join claude config write task".to_owned())
+                        });
 
-        let limit = limit
-            .and_then(|v| usize::try_from(v).ok())
-            .unwrap_or(DEFAULT_ENTRIES_LIMIT)
-            .clamp(1, MAX_ENTRIES_LIMIT);
+                        match result {
+                            Ok(()) => {
+                                let _ = events.send(WsServerMessage::Event {
+                                    rev,
+                                    event: Box::new(
+                                        luban_api::ServerEvent::ClaudeConfigFileSaved {
+                                            request_id,
+                                            path,
+                                        },
+                                    ),
+                                });
+                            }
+                            Err(message) => {
+                                let _ = events.send(WsServerMessage::Error {
+                                    request_id: Some(request_id),
+                                    message,
+                                });
+                            }
+                        }
+                    });
 
-        let wid = WorkspaceId::from_u64(workspace_id.0);
-        let Some(scope) = workspace_scope(&self.state, wid) else {
-            return Err(anyhow::anyhow!("workspace not found"));
-        };
+                    let _ = reply.send(Ok(self.rev));
+                    return;
+                }
 
-        let services = self.services.clone();
-        let tid = thread_id.0;
-        let loaded = tokio::task::spawn_blocking(move || {
-            services.load_conversation_page(
-                scope.project_slug,
-                scope.workspace_name,
-                tid,
-                before,
-                limit as u64,
-            )
-        })
-        .await
-        .ok()
-        .unwrap_or_else(|| Err("failed to join load conversation task".to_owned()))
-        .map_err(|e| anyhow::anyhow!(e))?;
+                // --- Droid config handlers ---
 
-        let entries_total = loaded.entries_total;
-        let entries_start = loaded.entries_start;
-        let entries_end = entries_start.saturating_add(loaded.entries.len() as u64);
-        let entries_truncated = entries_start > 0 || entries_end < entries_total;
+                if matches!(action, luban_api::ClientAction::DroidCheck) {
+                    let services = self.services.clone();
+                    let events = self.request_events(&request_id);
+                    let request_id = request_id.clone();
+                    let rev = self.rev;
+                    tokio::spawn(async move {
+                        if events.is_canceled() {
+                            return;
+                        }
+                        let result = tokio::task::spawn_blocking(move || services.droid_check())
+                            .await
+                            .ok()
+                            .unwrap_or_else(|| Err("failed to join droid check task".to_owned()));
 
-        let runner = loaded
-            .runner
-            .unwrap_or_else(|| self.state.agent_default_runner());
-        let model_id = loaded
-            .agent_model_id
-            .as_deref()
-            .map(str::trim)
-            .filter(|v| !v.is_empty())
-            .unwrap_or_else(|| self.state.agent_default_model_id())
-            .to_owned();
-        let thinking_effort = loaded
-            .thinking_effort
-            .unwrap_or_else(|| self.state.agent_default_thinking_effort());
-        let amp_mode = if runner == luban_domain::AgentRunnerKind::Amp {
-            loaded
-                .amp_mode
-                .as_deref()
-                .map(str::trim)
-                .filter(|v| !v.is_empty())
-                .map(ToOwned::to_owned)
-                .or_else(|| Some(self.state.agent_amp_mode().to_owned()))
-        } else {
-            None
-        };
-
-        let title = self
-            .state
-            .workspace_thread_conversation(wid, WorkspaceThreadId::from_u64(tid))
-            .map(|c| c.title.clone())
-            .or_else(|| loaded.title.clone())
-            .unwrap_or_else(|| format!("Thread {tid}"));
+                        let (ok, message) = match result {
+                            Ok(()) => (true, None),
+                            Err(message) => (false, Some(message)),
+                        };
 
-        Ok(ConversationSnapshot {
-            rev: self.rev,
-            workspace_id,
-            thread_id,
-            task_status: match loaded.task_status {
-                luban_domain::TaskStatus::Backlog => luban_api::TaskStatus::Backlog,
-                luban_domain::TaskStatus::Todo => luban_api::TaskStatus::Todo,
-                luban_domain::TaskStatus::Iterating => luban_api::TaskStatus::Iterating,
-                luban_domain::TaskStatus::Validating => luban_api::TaskStatus::Validating,
-                luban_domain::TaskStatus::Done => luban_api::TaskStatus::Done,
-                luban_domain::TaskStatus::Canceled => luban_api::TaskStatus::Canceled,
-            },
-            agent_runner: match runner {
-                luban_domain::AgentRunnerKind::Codex => luban_api::AgentRunnerKind::Codex,
-                luban_domain::AgentRunnerKind::Amp => luban_api::AgentRunnerKind::Amp,
-                luban_domain::AgentRunnerKind::Claude => luban_api::AgentRunnerKind::Claude,
-                luban_domain::AgentRunnerKind::Droid => luban_api::AgentRunnerKind::Droid,
-            },
-            agent_model_id: model_id.clone(),
-            thinking_effort: match thinking_effort {
-                ThinkingEffort::Minimal => luban_api::ThinkingEffort::Minimal,
-                ThinkingEffort::Low => luban_api::ThinkingEffort::Low,
-                ThinkingEffort::Medium => luban_api::ThinkingEffort::Medium,
-                ThinkingEffort::High => luban_api::ThinkingEffort::High,
-                ThinkingEffort::XHigh => luban_api::ThinkingEffort::XHigh,
-            },
-            amp_mode,
-            run_status: luban_api::OperationStatus::Idle,
-            run_started_at_unix_ms: loaded.run_started_at_unix_ms,
-            run_finished_at_unix_ms: loaded.run_finished_at_unix_ms,
-            entries: loaded.entries.iter().map(map_conversation_entry).collect(),
-            entries_total,
-            entries_start,
-            entries_truncated,
-            pending_prompts: loaded
-                .pending_prompts
-                .iter()
-                .map(|prompt| luban_api::QueuedPromptSnapshot {
-                    id: prompt.id,
-                    text: prompt.text.clone(),
-                    attachments: prompt.attachments.iter().map(map_attachment_ref).collect(),
-                    run_config: luban_api::AgentRunConfigSnapshot {
-                        runner: match prompt.run_config.runner {
-                            luban_domain::AgentRunnerKind::Codex => {
-                                luban_api::AgentRunnerKind::Codex
-                            }
-                            luban_domain::AgentRunnerKind::Amp => luban_api::AgentRunnerKind::Amp,
-                            luban_domain::AgentRunnerKind::Claude => {
-                                luban_api::AgentRunnerKind::Claude
-                            }
-                            luban_domain::AgentRunnerKind::Droid => {
-                                luban_api::AgentRunnerKind::Droid
-                            }
-                        },
-                        model_id: prompt.run_config.model_id.clone(),
-                        thinking_effort: match prompt.run_config.thinking_effort {
-                            ThinkingEffort::Minimal => luban_api::ThinkingEffort::Minimal,
-                            ThinkingEffort::Low => luban_api::ThinkingEffort::Low,
-                            ThinkingEffort::Medium => luban_api::ThinkingEffort::Medium,
-                            ThinkingEffort::High => luban_api::ThinkingEffort::High,
-                            ThinkingEffort::XHigh => luban_api::ThinkingEffort::XHigh,
-                        },
-                        amp_mode: prompt.run_config.amp_mode.clone(),
-                    },
-                })
-                .collect(),
-            queue_paused: loaded.queue_paused,
-            remote_thread_id: loaded.thread_id,
-            title,
-        })
-    }
+                        let _ = events.send(WsServerMessage::Event {
+                            rev,
+                            event: Box::new(luban_api::ServerEvent::DroidCheckReady {
+                                request_id,
+                                ok,
+                                message,
+                            }),
+                        });
+                    });
 
-    async fn process_action_queue(&mut self, initial: Action) {
-        let mut actions = VecDeque::from([initial]);
-        let mut effects = VecDeque::<Effect>::new();
+                    let _ = reply.send(Ok(self.rev));
+                    return;
+                }
 
-        while let Some(action) = actions.pop_front() {
-            self.rev = self.rev.saturating_add(1);
+                if matches!(action, luban_api::ClientAction::DroidConfigTree) {
+                    fn map_entry(
+                        entry: luban_domain::DroidConfigEntry,
+                    ) -> luban_api::DroidConfigEntrySnapshot {
+                        luban_api::DroidConfigEntrySnapshot {
+                            path: entry.path,
+                            name: entry.name,
+                            kind: match entry.kind {
+                                luban_domain::DroidConfigEntryKind::File => {
+                                    luban_api::DroidConfigEntryKind::File
+                                }
+                                luban_domain::DroidConfigEntryKind::Folder => {
+                                    luban_api::DroidConfigEntryKind::Folder
+                                }
+                            },
+                            children: entry.children.into_iter().map(map_entry).collect(),
+                        }
+                    }
 
-            let should_persist_latest_conversation_entry = matches!(
-                &action,
-                Action::TerminalCommandStarted { .. }
-                    | Action::TerminalCommandFinished { .. }
-                    | Action::TaskStatusSuggestionCreated { .. }
-            );
-            let should_sync_branch_watchers = should_sync_branch_watchers(&action);
-            let mut conversation_keys = Vec::<(WorkspaceId, WorkspaceThreadId)>::new();
-            let action_conversation_key = conversation_key_for_action(&action);
-            if let Some(key) = action_conversation_key {
-                conversation_keys.push(key);
-            }
-            let queue_state_key = queue_state_key_for_action(&action);
-            let threads_event = threads_event_for_action(&action);
-            let task_summaries_workspace_id = task_summaries_workspace_id_for_action(&action);
+                    let services = self.services.clone();
+                    let events = self.request_events(&request_id);
+                    let request_id = request_id.clone();
+                    let rev = self.rev;
+                    tokio::spawn(async move {
+                        if events.is_canceled() {
+                            return;
+                        }
+                        let result =
+                            tokio::task::spawn_blocking(move || services.droid_config_tree())
+                                .await
+                                .ok()
+                                .unwrap_or_else(|| {
+                                    Err("failed to join droid config tree task".to_owned())
+                                });
 
-            let new_effects = self.state.apply(action);
-            conversation_keys.extend(conversation_keys_for_effects(&new_effects));
-            if should_sync_branch_watchers {
-                self.sync_branch_watchers();
-            }
-            self.publish_app_snapshot();
+                        match result {
+                            Ok(entries) => {
+                                let tree = entries.into_iter().map(map_entry).collect();
+                                let _ = events.send(WsServerMessage::Event {
+                                    rev,
+                                    event: Box::new(luban_api::ServerEvent::DroidConfigTreeReady {
+                                        request_id,
+                                        tree,
+                                    }),
+                                });
+                            }
+                            Err(message) => {
+                                let _ = events.send(WsServerMessage::Error {
+                                    request_id: Some(request_id),
+                                    message,
+                                });
+                            }
+                        }
+                    });
 
-            if !conversation_keys.is_empty() {
-                let mut seen = HashSet::<(u64, u64)>::new();
-                for (wid, tid) in conversation_keys {
-                    if !seen.insert((wid.as_u64(), tid.as_u64())) {
-                        continue;
-                    }
-                    self.publish_conversation_snapshot(wid, tid);
+                    let _ = reply.send(Ok(self.rev));
+                    return;
                 }
-            }
-            if let Some((wid, mut threads)) = threads_event {
-                self.publish_threads_event(wid, &threads);
-                dedup_thread_metas_in_place(&mut threads);
-                self.workspace_threads_cache.insert(wid, threads);
-            }
-            if let Some(wid) = task_summaries_workspace_id {
-                self.publish_task_summaries_event(wid);
-            }
-            if let Some((wid, tid)) = queue_state_key {
-                self.persist_queue_state(wid, tid).await;
-            }
-            if should_persist_latest_conversation_entry
-                && let Some((wid, tid)) = action_conversation_key
-            {
-                self.persist_latest_conversation_entry(wid, tid).await;
-            }
-
-            effects.extend(new_effects);
 
-            while let Some(effect) = effects.pop_front() {
-                match self.run_effect(effect).await {
-                    Ok(mut followups) => actions.append(&mut followups),
-                    Err(err) => {
-                        tracing::error!(error = %err, "effect failed");
+                if let luban_api::ClientAction::DroidConfigListDir { path } = &action {
+                    fn map_entry(
+                        entry: luban_domain::DroidConfigEntry,
+                    ) -> luban_api::DroidConfigEntrySnapshot {
+                        luban_api::DroidConfigEntrySnapshot {
+                            path: entry.path,
+                            name: entry.name,
+                            kind: match entry.kind {
+                                luban_domain::DroidConfigEntryKind::File => {
+                                    luban_api::DroidConfigEntryKind::File
+                                }
+                                luban_domain::DroidConfigEntryKind::Folder => {
+                                    luban_api::DroidConfigEntryKind::Folder
+                                }
+                            },
+                            children: entry.children.into_iter().map(map_entry).collect(),
+                        }
                     }
-                }
-            }
-        }
-    }
-
-    async fn persist_latest_conversation_entry(
-        &self,
-        workspace_id: WorkspaceId,
-        thread_id: WorkspaceThreadId,
-    ) {
-        let Some(scope) = workspace_scope(&self.state, workspace_id) else {
-            return;
-        };
-        let Some(conversation) = self
-            .state
-            .workspace_thread_conversation(workspace_id, thread_id)
-        else {
-            return;
-        };
-        let Some(entry) = conversation.entries.last() else {
-            return;
-        };
 
-        let services = self.services.clone();
-        let project_slug = scope.project_slug;
-        let workspace_name = scope.workspace_name;
-        let thread_local_id = thread_id.as_u64();
-        let entry = entry.clone();
-        let result = tokio::task::spawn_blocking(move || {
-            services.append_conversation_entries(
-                project_slug,
-                workspace_name,
-                thread_local_id,
-                vec![entry],
-            )
-        })
-        .await;
-
-        match result {
-            Ok(Ok(())) => {}
-            Ok(Err(message)) => {
-                tracing::error!(message = %message, "failed to persist conversation entry");
-            }
-            Err(err) => {
-                tracing::error!(error = %err, "failed to join conversation persistence task");
-            }
-        }
-    }
-
-    fn sync_branch_watchers(&self) {
-        let workspaces = self
-            .state
-            .projects
-            .iter()
-            .filter(|p| p.is_git)
-            .flat_map(|p| {
-                p.workspaces.iter().filter_map(|w| {
-                    if w.status != luban_domain::WorkspaceStatus::Active {
-                        return None;
-                    }
-                    Some((w.id, w.worktree_path.clone()))
-                })
-            })
-            .collect::<Vec<_>>();
-        self.branch_watch.sync_workspaces(workspaces);
-    }
-
-    async fn persist_queue_state(&self, workspace_id: WorkspaceId, thread_id: WorkspaceThreadId) {
-        let Some(scope) = workspace_scope(&self.state, workspace_id) else {
-            return;
-        };
-        let Some(conversation) = self
-            .state
-            .workspace_thread_conversation(workspace_id, thread_id)
-        else {
-            return;
-        };
+                    let services = self.services.clone();
+                    let events = self.request_events(&request_id);
+                    let request_id = request_id.clone();
+                    let rev = self.rev;
+                    let path = path.clone();
+                    tokio::spawn(async move {
+                        if events.is_canceled() {
+                            return;
+                        }
+                        let path_for_task = path.clone();
+                        let result = tokio::task::spawn_blocking(move || {
+                            services.droid_config_list_dir(path_for_task)
+                        })
+                        .await
+                        .ok()
+                        .unwrap_or_else(|| {
+                            Err("failed to join droid config list dir task".to_owned())
+                        });
 
-        let queue_paused = conversation.queue_paused;
-        let run_started_at_unix_ms = conversation.run_started_at_unix_ms;
-        let run_finished_at_unix_ms = conversation.run_finished_at_unix_ms;
-        let pending_prompts = conversation
-            .pending_prompts
-            .iter()
-            .cloned()
-            .collect::<Vec<_>>();
+                        match result {
+                            Ok(entries) => {
+                                let entries = entries.into_iter().map(map_entry).collect();
+                                let _ = events.send(WsServerMessage::Event {
+                                    rev,
+                                    event: Box::new(
+                                        luban_api::ServerEvent::DroidConfigListDirReady {
+                                            request_id,
+                                            path,
+                                            entries,
+                                        },
+                                    ),
+                                });
+                            }
+                            Err(message) => {
+                                let _ = events.send(WsServerMessage::Error {
+                                    request_id: Some(request_id),
+                                    message,
+                                });
+                            }
+                        }
+                    });
 
-        let services = self.services.clone();
-        let project_slug = scope.project_slug;
-        let workspace_name = scope.workspace_name;
-        let thread_local_id = thread_id.as_u64();
-        let result = tokio::task::spawn_blocking(move || {
-            services.save_conversation_queue_state(
-                project_slug,
-                workspace_name,
-                thread_local_id,
-                queue_paused,
-                run_started_at_unix_ms,
-                run_finished_at_unix_ms,
-                pending_prompts,
-            )
-        })
-        .await;
+                    let _ = reply.send(Ok(self.rev));
+                    return;
+                }
 
-        match result {
-            Ok(Ok(())) => {}
-            Ok(Err(message)) => {
-                tracing::error!(message = %message, "failed to persist queued prompts");
-            }
-            Err(err) => {
-                tracing::error!(error = %err, "failed to join queued prompt persistence task");
-            }
-        }
-    }
+                if let luban_api::ClientAction::DroidConfigReadFile { path } = &action {
+                    let services = self.services.clone();
+                    let events = self.request_events(&request_id);
+                    let request_id = request_id.clone();
+                    let rev = self.rev;
+                    let path = path.clone();
+                    tokio::spawn(async move {
+                        if events.is_canceled() {
+                            return;
+                        }
+                        let path_for_task = path.clone();
+                        let result = tokio::task::spawn_blocking(move || {
+                            services.droid_config_read_file(path_for_task)
+                        })
+                        .await
+                        .ok()
+                        .unwrap_or_else(|| Err("failed to join droid config read task".to_owned()));
 
-    fn refresh_pull_requests_for_all_workspaces(&mut self) {
-        let now = Instant::now();
-        let workspace_ids = self
-            .state
-            .projects
-            .iter()
-            .flat_map(|project| {
-                project.workspaces.iter().filter_map(|workspace| {
-                    if workspace.status != luban_domain::WorkspaceStatus::Active {
-                        return None;
-                    }
-                    Some(workspace.id)
-                })
-            })
-            .collect::<Vec<_>>();
+                        match result {
+                            Ok(contents) => {
+                                let _ = events.send(WsServerMessage::Event {
+                                    rev,
+                                    event: Box::new(luban_api::ServerEvent::DroidConfigFileReady {
+                                        request_id,
+                                        path,
+                                        contents,
+                                    }),
+                                });
+                            }
+                            Err(message) => {
+                                let _ = events.send(WsServerMessage::Error {
+                                    request_id: Some(request_id),
+                                    message,
+                                });
+                            }
+                        }
+                    });
 
-        let mut candidates = workspace_ids
-            .into_iter()
-            .filter(|workspace_id| self.should_start_pull_request_refresh(*workspace_id, now))
-            .collect::<Vec<_>>();
+                    let _ = reply.send(Ok(self.rev));
+                    return;
+                }
 
-        candidates.sort_by_key(|workspace_id| {
-            self.pull_requests
-                .get(workspace_id)
-                .map(|e| e.next_refresh_at)
-                .unwrap_or(now)
-        });
+                if let luban_api::ClientAction::DroidConfigWriteFile { path, contents } = &action {
+                    let services = self.services.clone();
+                    let events = self.request_events(&request_id);
+                    let request_id = request_id.clone();
+                    let rev = self.rev;
+                    let path = path.clone();
+                    let contents = contents.clone();
+                    tokio::spawn(async move {
+                        if events.is_canceled() {
+                            return;
+                        }
+                        let path_for_task = path.clone();
+                        let result = tokio::task::spawn_blocking(move || {
+                            services.droid_config_write_file(path_for_task, contents)
+                        })
+                        .await
+                        .ok()
+                        .unwrap_or_else(|| {
+                            Err("failed to join droid config write task".to_owned())
+                        });
 
-        for workspace_id in candidates
-            .into_iter()
-            .take(PULL_REQUEST_REFRESH_MAX_PER_TICK)
-        {
-            self.start_pull_request_refresh(workspace_id);
-        }
-    }
+                        match result {
+                            Ok(()) => {
+                                let _ = events.send(WsServerMessage::Event {
+                                    rev,
+                                    event: Box::new(luban_api::ServerEvent::DroidConfigFileSaved {
+                                        request_id,
+                                        path,
+                                    }),
+                                });
+                            }
+                            Err(message) => {
+                                let _ = events.send(WsServerMessage::Error {
+                                    request_id: Some(request_id),
+                                    message,
+                                });
+                            }
+                        }
+                    });
 
-    fn maybe_refresh_pull_request(&mut self, workspace_id: WorkspaceId) {
-        let now = Instant::now();
-        if !self.should_start_pull_request_refresh(workspace_id, now) {
-            return;
-        }
-        self.start_pull_request_refresh(workspace_id);
-    }
+                    let _ = reply.send(Ok(self.rev));
+                    return;
+                }
 
-    fn should_start_pull_request_refresh(&self, workspace_id: WorkspaceId, now: Instant) -> bool {
-        if self.pull_requests_in_flight.contains(&workspace_id) {
-            return false;
-        }
-        if self.state.workspace(workspace_id).is_none() {
-            return false;
-        }
-        if let Some(entry) = self.pull_requests.get(&workspace_id) {
-            return now >= entry.next_refresh_at;
-        }
-        true
-    }
+                if let luban_api::ClientAction::OpenWorkspace { workspace_id } = &action {
+                    self.maybe_refresh_pull_request(WorkspaceId::from_u64(workspace_id.0));
+                }
 
-    fn start_pull_request_refresh(&mut self, workspace_id: WorkspaceId) {
-        let Some(workspace) = self.state.workspace(workspace_id) else {
-            return;
-        };
-
-        self.pull_requests_in_flight.insert(workspace_id);
-
-        let services = self.services.clone();
-        let tx = self.tx.clone();
-        let worktree_path = workspace.worktree_path.clone();
-
-        std::thread::spawn(move || {
-            let info = services.gh_pull_request_info(worktree_path).ok().flatten();
-            let _ = tx.blocking_send(EngineCommand::PullRequestInfoUpdated { workspace_id, info });
-        });
-    }
-
-    async fn run_effect(&mut self, effect: Effect) -> anyhow::Result<VecDeque<Action>> {
-        match effect {
-            Effect::LoadAppState => {
-                let services = self.services.clone();
-                let loaded = tokio::task::spawn_blocking(move || services.load_app_state())
-                    .await
-                    .ok()
-                    .unwrap_or_else(|| Err("failed to join load task".to_owned()));
-                let action = match loaded {
-                    Ok(persisted) => Action::AppStateLoaded {
-                        persisted: Box::new(persisted),
-                    },
-                    Err(message) => Action::AppStateLoadFailed { message },
-                };
-                Ok(VecDeque::from([action]))
-            }
-            Effect::SaveAppState => {
-                let services = self.services.clone();
-                let snapshot = self.state.to_persisted();
-                let saved = tokio::task::spawn_blocking(move || services.save_app_state(snapshot))
-                    .await
-                    .ok()
-                    .unwrap_or_else(|| Err("failed to join save task".to_owned()));
-                let action = match saved {
-                    Ok(()) => Action::AppStateSaved,
-                    Err(message) => Action::AppStateSaveFailed { message },
-                };
-                Ok(VecDeque::from([action]))
-            }
-            Effect::LoadCodexDefaults => {
-                let services = self.services.clone();
-                let loaded = tokio::task::spawn_blocking(move || {
-                    services.codex_config_read_file("config.toml".to_owned())
-                })
-                .await
-                .ok()
-                .unwrap_or_else(|| Err("failed to join codex config read task".to_owned()));
-
-                let contents = match loaded {
-                    Ok(contents) => contents,
-                    Err(message) => {
-                        tracing::debug!(message = %message, "codex defaults unavailable");
-                        return Ok(VecDeque::new());
+                match &action {
+                    luban_api::ClientAction::DeleteProject { project_id } => {
+                        let path = expand_user_path(&project_id.0);
+                        let Some(id) = find_project_id_by_path(&self.state, &path) else {
+                            let _ = reply.send(Err("project not found".to_owned()));
+                            return;
+                        };
+                        self.process_action_queue(Action::DeleteProject { project_id: id })
+                            .await;
+                        let _ = reply.send(Ok(self.rev));
+                        return;
                     }
-                };
+                    luban_api::ClientAction::ArchiveProject { project_id } => {
+                        let path = expand_user_path(&project_id.0);
+                        let Some(id) = find_project_id_by_path(&self.state, &path) else {
+                            let _ = reply.send(Err("project not found".to_owned()));
+                            return;
+                        };
+                        self.process_action_queue(Action::ArchiveProject { project_id: id })
+                            .await;
+                        let _ = reply.send(Ok(self.rev));
+                        return;
+                    }
+                    luban_api::ClientAction::RestoreProject { project_id } => {
+                        let path = expand_user_path(&project_id.0);
+                        let Some(id) = find_project_id_by_path(&self.state, &path) else {
+                            let _ = reply.send(Err("project not found".to_owned()));
+                            return;
+                        };
+                        self.process_action_queue(Action::RestoreProject { project_id: id })
+                            .await;
+                        let _ = reply.send(Ok(self.rev));
+                        return;
+                    }
+                    luban_api::ClientAction::DeleteWorkspaceThread {
+                        workspace_id,
+                        thread_id,
+                    } => {
+                        let workspace_id = WorkspaceId::from_u64(workspace_id.0);
+                        let thread_id = WorkspaceThreadId::from_u64(thread_id.0);
+                        let Some(scope) = workspace_scope(&self.state, workspace_id) else {
+                            let _ = reply.send(Err("workspace not found".to_owned()));
+                            return;
+                        };
+                        let services = self.services.clone();
+                        let project_slug = scope.project_slug.clone();
+                        let workspace_name = scope.workspace_name.clone();
+                        let delete_result = tokio::task::spawn_blocking(move || {
+                            services.delete_conversation_thread(
+                                project_slug,
+                                workspace_name,
+                                thread_id.as_u64(),
+                            )
+                        })
+                        .await
+                        .ok()
+                        .unwrap_or_else(|| Err("failed to join delete thread task".to_owned()));
 
-                let (model_id, thinking_effort) = parse_codex_defaults_toml(&contents);
-                if model_id.is_none() && thinking_effort.is_none() {
-                    return Ok(VecDeque::new());
-                }
+                        if let Err(msg) = delete_result {
+                            let _ = reply.send(Err(msg));
+                            return;
+                        }
 
-                Ok(VecDeque::from([Action::CodexDefaultsLoaded {
-                    model_id,
-                    thinking_effort,
-                }]))
-            }
-            Effect::LoadTaskPromptTemplates => {
-                let services = self.services.clone();
-                let loaded =
-                    tokio::task::spawn_blocking(move || services.task_prompt_templates_load())
+                        // Purge in-memory state for the deleted thread
+                        self.process_action_queue(Action::WorkspaceThreadsPurged {
+                            workspace_id,
+                            thread_ids: vec![thread_id],
+                        })
+                        .await;
+
+                        // Refresh thread list from DB
+                        let services = self.services.clone();
+                        let project_slug = scope.project_slug;
+                        let workspace_name = scope.workspace_name;
+                        if let Ok(threads) = tokio::task::spawn_blocking(move || {
+                            services.list_conversation_threads(project_slug, workspace_name)
+                        })
                         .await
                         .ok()
-                        .unwrap_or_else(|| {
-                            Err("failed to join task prompt templates load task".to_owned())
-                        });
-                match loaded {
-                    Ok(templates) => Ok(VecDeque::from([Action::TaskPromptTemplatesLoaded {
-                        templates,
-                    }])),
-                    Err(message) => {
-                        tracing::warn!(message = %message, "failed to load task prompt templates");
-                        Ok(VecDeque::new())
+                        .unwrap_or_else(|| Err("failed to join list threads task".to_owned()))
+                        {
+                            self.process_action_queue(Action::WorkspaceThreadsLoaded {
+                                workspace_id,
+                                threads,
+                            })
+                            .await;
+                        }
+
+                        let _ = reply.send(Ok(self.rev));
+                        return;
                     }
-                }
-            }
-            Effect::LoadSystemPromptTemplates => {
-                let services = self.services.clone();
-                let loaded =
-                    tokio::task::spawn_blocking(move || services.system_prompt_templates_load())
-                        .await
-                        .ok()
-                        .unwrap_or_else(|| {
-                            Err("failed to join system prompt templates load task".to_owned())
-                        });
-                match loaded {
-                    Ok(templates) => Ok(VecDeque::from([Action::SystemPromptTemplatesLoaded {
-                        templates,
-                    }])),
-                    Err(message) => {
-                        tracing::warn!(message = %message, "failed to load system prompt templates");
-                        Ok(VecDeque::new())
+                    luban_api::ClientAction::MoveThread {
+                        from_workspace_id,
+                        thread_id,
+                        to_workspace_id,
+                    } => {
+                        let from_workspace_id = WorkspaceId::from_u64(from_workspace_id.0);
+                        let thread_id = WorkspaceThreadId::from_u64(thread_id.0);
+                        let to_workspace_id = WorkspaceId::from_u64(to_workspace_id.0);
+                        let result = self
+                            .move_thread(from_workspace_id, thread_id, to_workspace_id)
+                            .await;
+                        let _ = reply.send(result.map(|()| self.rev));
+                        return;
                     }
-                }
-            }
-            Effect::MigrateLegacyTaskPromptTemplates { templates } => {
-                if templates.is_empty() {
-                    return Ok(VecDeque::new());
-                }
-                let services = self.services.clone();
-                let migrated = tokio::task::spawn_blocking(move || {
-                    let existing = services.task_prompt_templates_load().unwrap_or_default();
-                    if !existing.is_empty() {
-                        return Ok::<(), String>(());
+                    luban_api::ClientAction::ToggleProjectExpanded { project_id } => {
+                        let path = expand_user_path(&project_id.0);
+                        let Some(id) = find_project_id_by_path(&self.state, &path) else {
+                            let _ = reply.send(Err("project not found".to_owned()));
+                            return;
+                        };
+                        self.process_action_queue(Action::ToggleProjectExpanded { project_id: id })
+                            .await;
+                        let _ = reply.send(Ok(self.rev));
+                        return;
                     }
-                    for (kind, template) in templates {
-                        services.task_prompt_template_store(kind, template)?;
+                    luban_api::ClientAction::AssignProjectToGroup {
+                        project_id,
+                        group_id,
+                    } => {
+                        let path = expand_user_path(&project_id.0);
+                        let Some(id) = find_project_id_by_path(&self.state, &path) else {
+                            let _ = reply.send(Err("project not found".to_owned()));
+                            return;
+                        };
+                        self.process_action_queue(Action::AssignProjectToGroup {
+                            project_id: id,
+                            group_id: group_id
+                                .map(|id| luban_domain::ProjectGroupId::from_u64(id.0)),
+                        })
+                        .await;
+                        let _ = reply.send(Ok(self.rev));
+                        return;
                     }
-                    Ok(())
-                })
-                .await
-                .ok()
-                .unwrap_or_else(|| {
-                    Err("failed to join task prompt templates migrate task".to_owned())
-                });
-                if let Err(message) = migrated {
-                    tracing::warn!(message = %message, "failed to migrate legacy task prompt templates");
-                }
-                Ok(VecDeque::new())
-            }
-            Effect::StoreTaskPromptTemplate {
-                intent_kind,
-                template,
-            } => {
-                let services = self.services.clone();
-                let saved = tokio::task::spawn_blocking(move || {
-                    services.task_prompt_template_store(intent_kind, template)
-                })
-                .await
-                .ok()
-                .unwrap_or_else(|| {
-                    Err("failed to join task prompt template store task".to_owned())
-                });
-                if let Err(message) = saved {
-                    tracing::warn!(message = %message, "failed to store task prompt template");
-                }
-                Ok(VecDeque::new())
-            }
-            Effect::DeleteTaskPromptTemplate { intent_kind } => {
-                let services = self.services.clone();
-                let deleted = tokio::task::spawn_blocking(move || {
-                    services.task_prompt_template_delete(intent_kind)
-                })
-                .await
-                .ok()
-                .unwrap_or_else(|| {
-                    Err("failed to join task prompt template delete task".to_owned())
-                });
-                if let Err(message) = deleted {
-                    tracing::warn!(message = %message, "failed to delete task prompt template");
-                }
-                Ok(VecDeque::new())
-            }
-            Effect::StoreSystemPromptTemplate { kind, template } => {
-                let services = self.services.clone();
-                let saved = tokio::task::spawn_blocking(move || {
-                    services.system_prompt_template_store(kind, template)
-                })
-                .await
-                .ok()
-                .unwrap_or_else(|| {
-                    Err("failed to join system prompt template store task".to_owned())
-                });
-                if let Err(message) = saved {
-                    tracing::warn!(message = %message, "failed to store system prompt template");
-                }
-                Ok(VecDeque::new())
-            }
-            Effect::DeleteSystemPromptTemplate { kind } => {
-                let services = self.services.clone();
-                let deleted = tokio::task::spawn_blocking(move || {
-                    services.system_prompt_template_delete(kind)
-                })
-                .await
-                .ok()
-                .unwrap_or_else(|| {
-                    Err("failed to join system prompt template delete task".to_owned())
-                });
-                if let Err(message) = deleted {
-                    tracing::warn!(message = %message, "failed to delete system prompt template");
-                }
-                Ok(VecDeque::new())
-            }
-            Effect::CreateWorkspace {
-                project_id,
-                branch_name_hint,
-            } => {
-                let Some(project) = self.state.projects.iter().find(|p| p.id == project_id) else {
-                    return Ok(VecDeque::from([Action::WorkspaceCreateFailed {
-                        project_id,
-                        message: "project not found".to_owned(),
-                    }]));
-                };
-                let project_path = project.path.clone();
-                let project_slug = project.slug.clone();
-                let services = self.services.clone();
-
-                let created = tokio::task::spawn_blocking(move || {
-                    services.create_workspace(project_path, project_slug, branch_name_hint)
-                })
-                .await
-                .ok()
-                .unwrap_or_else(|| Err("failed to join create workspace task".to_owned()));
-
-                let action = match created {
-                    Ok(created) => Action::WorkspaceCreated {
-                        project_id,
-                        workspace_name: created.workspace_name,
-                        branch_name: created.branch_name,
-                        worktree_path: created.worktree_path,
-                    },
-                    Err(message) => Action::WorkspaceCreateFailed {
+                    luban_api::ClientAction::ProjectSystemPromptChanged {
                         project_id,
-                        message,
-                    },
-                };
-                Ok(VecDeque::from([action]))
-            }
-            Effect::RenameWorkspaceBranch {
-                workspace_id,
-                requested_branch_name,
-            } => {
-                let Some(workspace) = self.state.workspace(workspace_id) else {
-                    return Ok(VecDeque::from([Action::WorkspaceBranchRenameFailed {
-                        workspace_id,
-                        message: "workspace not found".to_owned(),
-                    }]));
-                };
-
-                let worktree_path = workspace.worktree_path.clone();
-                let services = self.services.clone();
-                let tx = self.tx.clone();
-                tokio::spawn(async move {
-                    let result = tokio::task::spawn_blocking(move || {
-                        services.rename_workspace_branch(worktree_path, requested_branch_name)
-                    })
-                    .await
-                    .ok()
-                    .unwrap_or_else(|| {
-                        Err("failed to join rename workspace branch task".to_owned())
-                    });
-
-                    let action = match result {
-                        Ok(branch_name) => Action::WorkspaceBranchRenamed {
-                            workspace_id,
-                            branch_name,
-                        },
-                        Err(message) => Action::WorkspaceBranchRenameFailed {
-                            workspace_id,
-                            message,
-                        },
-                    };
-                    let _ = tx
-                        .send(EngineCommand::DispatchAction {
-                            action: Box::new(action),
-                        })
-                        .await;
-                });
-
-                Ok(VecDeque::new())
-            }
-            Effect::AiRenameWorkspaceBranch {
-                workspace_id,
-                input,
-                runner,
-                model_id,
-                thinking_effort,
-                amp_mode,
-            } => {
-                if workspace_scope(&self.state, workspace_id).is_none() {
-                    return Ok(VecDeque::from([Action::WorkspaceBranchRenameFailed {
-                        workspace_id,
-                        message: "workspace not found".to_owned(),
-                    }]));
-                };
-
-                let worktree_path = self
-                    .state
-                    .workspace(workspace_id)
-                    .map(|w| w.worktree_path.clone())
-                    .unwrap_or_else(|| std::env::current_dir().unwrap_or_default());
-
-                let services = self.services.clone();
-                let tx = self.tx.clone();
-                tokio::spawn(async move {
-                    let result = tokio::task::spawn_blocking(move || {
-                        let suggested = services.task_suggest_branch_name(
-                            input,
-                            runner,
-                            model_id,
-                            thinking_effort,
-                            amp_mode,
-                        )?;
-                        services.rename_workspace_branch(worktree_path, suggested)
-                    })
-                    .await
-                    .ok()
-                    .unwrap_or_else(|| {
-                        Err("failed to join ai rename workspace branch task".to_owned())
-                    });
-
-                    let action = match result {
-                        Ok(branch_name) => Action::WorkspaceBranchRenamed {
-                            workspace_id,
-                            branch_name,
-                        },
-                        Err(message) => Action::WorkspaceBranchRenameFailed {
-                            workspace_id,
-                            message,
-                        },
-                    };
-                    let _ = tx
-                        .send(EngineCommand::DispatchAction {
-                            action: Box::new(action),
+                        system_prompt,
+                    } => {
+                        let path = expand_user_path(&project_id.0);
+                        let Some(id) = find_project_id_by_path(&self.state, &path) else {
+                            let _ = reply.send(Err("project not found".to_owned()));
+                            return;
+                        };
+                        self.process_action_queue(Action::ProjectSystemPromptChanged {
+                            project_id: id,
+                            system_prompt: system_prompt.clone(),
                         })
                         .await;
-                });
-
-                Ok(VecDeque::new())
-            }
-            Effect::AiAutoTitleThread {
-                workspace_id,
-                thread_id,
-                input,
-                expected_current_title,
-                runner,
-                model_id,
-                thinking_effort,
-                amp_mode,
-            } => {
-                let Some(scope) = workspace_scope(&self.state, workspace_id) else {
-                    return Ok(VecDeque::new());
-                };
-
-                let use_fake_agent = std::env::var_os("LUBAN_E2E_ROOT").is_some()
-                    && std::env::var("LUBAN_CODEX_BIN")
-                        .ok()
-                        .is_some_and(|bin| bin == "/usr/bin/false");
-
-                let services = self.services.clone();
-                let tx = self.tx.clone();
-                let project_slug = scope.project_slug;
-                let workspace_name = scope.workspace_name;
-                let thread_local_id = thread_id.as_u64();
-                tokio::spawn(async move {
-                    let services_for_suggest = services.clone();
-                    let project_slug_for_update = project_slug.clone();
-                    let workspace_name_for_update = workspace_name.clone();
-                    let result = tokio::task::spawn_blocking(move || {
-                        let suggested = if use_fake_agent {
-                            let derived = luban_domain::derive_thread_title(&input);
-                            if derived.is_empty() {
-                                "Thread".to_owned()
-                            } else {
-                                derived
-                            }
-                        } else {
-                            services_for_suggest.task_suggest_thread_title(
-                                input,
-                                runner,
-                                model_id,
-                                thinking_effort,
-                                amp_mode,
-                            )?
+                        let _ = reply.send(Ok(self.rev));
+                        return;
+                    }
+                    luban_api::ClientAction::SetProjectWorktreeRoot { project_id, path } => {
+                        let project_path = expand_user_path(&project_id.0);
+                        let Some(id) = find_project_id_by_path(&self.state, &project_path) else {
+                            let _ = reply.send(Err("project not found".to_owned()));
+                            return;
                         };
-
-                        let suggested = luban_domain::derive_thread_title(&suggested);
-                        if suggested.is_empty() {
-                            return Ok::<_, String>(false);
+                        let worktree_root = path
+                            .as_deref()
+                            .filter(|path| !path.trim().is_empty())
+                            .map(expand_user_path);
+                        if worktree_root
+                            .as_ref()
+                            .is_some_and(|root| !root.is_absolute())
+                        {
+                            let _ = reply
+                                .send(Err("worktree root must be an absolute path".to_owned()));
+                            return;
                         }
-
-                        services_for_suggest.conversation_update_title_if_matches(
-                            project_slug_for_update,
-                            workspace_name_for_update,
-                            thread_local_id,
-                            expected_current_title,
-                            suggested,
-                        )
-                    })
-                    .await
-                    .ok()
-                    .unwrap_or_else(|| Err("failed to join auto title thread task".to_owned()));
-
-                    let Ok(updated) = result else {
-                        return;
-                    };
-                    if !updated {
+                        self.process_action_queue(Action::SetProjectWorktreeRoot {
+                            project_id: id,
+                            path: worktree_root,
+                        })
+                        .await;
+                        let _ = reply.send(Ok(self.rev));
                         return;
                     }
-
-                    let services_for_list = services.clone();
-                    let project_slug_for_list = project_slug.clone();
-                    let workspace_name_for_list = workspace_name.clone();
-                    let result = tokio::task::spawn_blocking(move || {
-                        services_for_list.list_conversation_threads(
-                            project_slug_for_list,
-                            workspace_name_for_list,
-                        )
-                    })
-                    .await
-                    .ok()
-                    .unwrap_or_else(|| Err("failed to join list threads task".to_owned()));
-
-                    let Ok(threads) = result else {
+                    luban_api::ClientAction::SetProjectBranchNameTemplate {
+                        project_id,
+                        template,
+                    } => {
+                        let path = expand_user_path(&project_id.0);
+                        let Some(id) = find_project_id_by_path(&self.state, &path) else {
+                            let _ = reply.send(Err("project not found".to_owned()));
+                            return;
+                        };
+                        self.process_action_queue(Action::SetProjectBranchNameTemplate {
+                            project_id: id,
+                            template: template.clone(),
+                        })
+                        .await;
+                        let _ = reply.send(Ok(self.rev));
                         return;
-                    };
-
-                    let _ = tx
-                        .send(EngineCommand::DispatchAction {
-                            action: Box::new(Action::WorkspaceThreadsLoaded {
-                                workspace_id,
-                                threads,
-                            }),
+                    }
+                    luban_api::ClientAction::SetProjectPullRequestTarget {
+                        project_id,
+                        remote,
+                        base_branch,
+                    } => {
+                        let path = expand_user_path(&project_id.0);
+                        let Some(id) = find_project_id_by_path(&self.state, &path) else {
+                            let _ = reply.send(Err("project not found".to_owned()));
+                            return;
+                        };
+                        self.process_action_queue(Action::SetProjectPullRequestTarget {
+                            project_id: id,
+                            remote: remote.clone(),
+                            base_branch: base_branch.clone(),
                         })
                         .await;
-                });
-
-                Ok(VecDeque::new())
-            }
-            Effect::AiAutoUpdateTaskStatus {
-                workspace_id,
-                thread_id,
-                input,
-                expected_current_task_status,
-                runner,
-                model_id,
-                thinking_effort,
-                amp_mode,
-            } => {
-                let Some(scope) = workspace_scope(&self.state, workspace_id) else {
-                    return Ok(VecDeque::new());
-                };
-
-                let project_slug = scope.project_slug;
-                let workspace_name = scope.workspace_name;
-                let thread_local_id = thread_id.as_u64();
-
-                let services = self.services.clone();
-                let tx = self.tx.clone();
-                tokio::spawn(async move {
-                    let result = tokio::task::spawn_blocking(move || {
-                        let suggested = services.task_suggest_task_status_auto_update(
-                            input,
-                            runner,
-                            model_id,
-                            thinking_effort,
-                            amp_mode,
-                        )?;
-
-                        let _ = services.save_conversation_task_status_last_analyzed(
-                            project_slug.clone(),
-                            workspace_name.clone(),
-                            thread_local_id,
-                        );
-
-                        if suggested.task_status == luban_domain::TaskStatus::Validating
-                            && let Some(pr_number) = suggested.validation_pr_number
-                            && matches!(
-                                expected_current_task_status,
-                                luban_domain::TaskStatus::Iterating
-                                    | luban_domain::TaskStatus::Validating
-                            )
-                        {
-                            let _ = services.save_conversation_task_validation_pr(
-                                project_slug.clone(),
-                                workspace_name.clone(),
-                                thread_local_id,
-                                pr_number,
-                                suggested.validation_pr_url.clone(),
-                            );
+                        // Look the pull requests up again right away against the new target.
+                        let workspace_ids = self
+                            .state
+                            .projects
+                            .iter()
+                            .find(|p| p.id == id)
+                            .map(|p| p.workspaces.iter().map(|w| w.id).collect::<Vec<_>>())
+                            .unwrap_or_default();
+                        for workspace_id in workspace_ids {
+                            self.pull_requests.remove(&workspace_id);
+                            self.maybe_refresh_pull_request(workspace_id);
                         }
-
-                        Ok::<_, String>(suggested)
-                    })
-                    .await
-                    .ok()
-                    .unwrap_or_else(|| {
-                        Err("failed to join auto update task status task".to_owned())
-                    });
-
-                    let Ok(suggested) = result else {
+                        let _ = reply.send(Ok(self.rev));
                         return;
-                    };
-
-                    let suggested_task_status = suggested.task_status;
-                    let title = format!("Suggest moving to {}", suggested_task_status.as_str());
-                    let explanation_markdown = suggested.explanation_markdown.unwrap_or_default();
-
-                    let _ = tx
-                        .send(EngineCommand::DispatchAction {
-                            action: Box::new(Action::TaskStatusSuggestionCreated {
-                                workspace_id,
-                                thread_id,
-                                expected_current_task_status,
-                                suggested_task_status,
-                                title,
-                                explanation_markdown,
-                            }),
+                    }
+                    luban_api::ClientAction::SetProjectAgentEnv { project_id, env } => {
+                        let path = expand_user_path(&project_id.0);
+                        let Some(id) = find_project_id_by_path(&self.state, &path) else {
+                            let _ = reply.send(Err("project not found".to_owned()));
+                            return;
+                        };
+                        let stored = self
+                            .state
+                            .projects
+                            .iter()
+                            .find(|p| p.id == id)
+                            .map(|p| p.agent_env.as_slice())
+                            .unwrap_or_default();
+                        let env = env
+                            .iter()
+                            .filter_map(|var| {
+                                let value = match &var.value {
+                                    Some(value) => value.clone(),
+                                    None => stored
+                                        .iter()
+                                        .find(|(key, _)| key == var.key.trim())
+                                        .map(|(_, value)| value.clone())?,
+                                };
+                                Some((var.key.clone(), value))
+                            })
+                            .collect();
+                        self.process_action_queue(Action::SetProjectAgentEnv {
+                            project_id: id,
+                            env,
                         })
                         .await;
-                });
-
-                Ok(VecDeque::new())
-            }
-            Effect::LoadWorkspaceThreads { workspace_id } => {
-                let Some(scope) = workspace_scope(&self.state, workspace_id) else {
-                    return Ok(VecDeque::new());
-                };
-                let services = self.services.clone();
-                let project_slug_for_list = scope.project_slug.clone();
-                let workspace_name_for_list = scope.workspace_name.clone();
-                let result = tokio::task::spawn_blocking(move || {
-                    services
-                        .list_conversation_threads(project_slug_for_list, workspace_name_for_list)
-                })
-                .await
-                .ok()
-                .unwrap_or_else(|| Err("failed to join list threads task".to_owned()));
-                let action = match result {
-                    Ok(threads) => Action::WorkspaceThreadsLoaded {
-                        workspace_id,
-                        threads,
-                    },
-                    Err(message) => Action::WorkspaceThreadsLoadFailed {
-                        workspace_id,
-                        message,
-                    },
-                };
-                Ok(VecDeque::from([action]))
-            }
-            Effect::LoadConversation {
-                workspace_id,
-                thread_id,
-            } => {
-                let Some(scope) = workspace_scope(&self.state, workspace_id) else {
-                    return Ok(VecDeque::new());
-                };
-                let services = self.services.clone();
-                let thread_local_id = thread_id.as_u64();
-                let result = tokio::task::spawn_blocking(move || {
-                    services.load_conversation_page(
-                        scope.project_slug,
-                        scope.workspace_name,
-                        thread_local_id,
-                        None,
-                        5000,
-                    )
-                })
-                .await
-                .ok()
-                .unwrap_or_else(|| Err("failed to join load conversation task".to_owned()));
-                let action = match result {
-                    Ok(snapshot) => Action::ConversationLoaded {
-                        workspace_id,
-                        thread_id,
-                        snapshot,
-                    },
-                    Err(message) => Action::ConversationLoadFailed {
-                        workspace_id,
-                        thread_id,
-                        message,
-                    },
-                };
-                Ok(VecDeque::from([action]))
-            }
-            Effect::EnsureConversation {
-                workspace_id,
-                thread_id,
-            } => {
-                let Some(scope) = workspace_scope(&self.state, workspace_id) else {
-                    return Ok(VecDeque::new());
-                };
-                let services = self.services.clone();
-                let thread_local_id = thread_id.as_u64();
-                let _ = tokio::task::spawn_blocking(move || {
-                    services.ensure_conversation(
-                        scope.project_slug,
-                        scope.workspace_name,
-                        thread_local_id,
-                    )
-                })
-                .await;
-                Ok(VecDeque::new())
-            }
-            Effect::StoreConversationRunConfig {
-                workspace_id,
-                thread_id,
-                runner,
-                model_id,
-                thinking_effort,
-                amp_mode,
-            } => {
-                let Some(scope) = workspace_scope(&self.state, workspace_id) else {
-                    return Ok(VecDeque::new());
-                };
-                let services = self.services.clone();
-                let thread_local_id = thread_id.as_u64();
-                let _ = tokio::task::spawn_blocking(move || {
-                    services.save_conversation_run_config(
-                        scope.project_slug,
-                        scope.workspace_name,
-                        thread_local_id,
+                        let _ = reply.send(Ok(self.rev));
+                        return;
+                    }
+                    luban_api::ClientAction::SetProjectAgentDefaults {
+                        project_id,
                         runner,
                         model_id,
                         thinking_effort,
                         amp_mode,
-                    )
-                })
-                .await;
-                Ok(VecDeque::new())
-            }
-            Effect::StoreConversationTaskStatus {
-                workspace_id,
-                thread_id,
-                task_status,
-            } => {
-                let Some(scope) = workspace_scope(&self.state, workspace_id) else {
-                    return Ok(VecDeque::new());
-                };
-                let services = self.services.clone();
-                let thread_local_id = thread_id.as_u64();
-                let _ = tokio::task::spawn_blocking(move || {
-                    services.save_conversation_task_status(
-                        scope.project_slug,
-                        scope.workspace_name,
-                        thread_local_id,
-                        task_status,
-                    )
-                })
-                .await;
-                Ok(VecDeque::new())
+                    } => {
+                        let path = expand_user_path(&project_id.0);
+                        let Some(id) = find_project_id_by_path(&self.state, &path) else {
+                            let _ = reply.send(Err("project not found".to_owned()));
+                            return;
+                        };
+                        self.process_action_queue(Action::SetProjectAgentDefaults {
+                            project_id: id,
+                            defaults: luban_domain::ProjectAgentDefaults {
+                                runner: runner.map(map_api_agent_runner_kind),
+                                model_id: model_id.clone(),
+                                thinking_effort: thinking_effort.map(map_api_thinking_effort),
+                                amp_mode: amp_mode.clone(),
+                            },
+                        })
+                        .await;
+                        let _ = reply.send(Ok(self.rev));
+                        return;
+                    }
+                    luban_api::ClientAction::AddProjectSavedCommand {
+                        project_id,
+                        label,
+                        command,
+                    } => {
+                        let path = expand_user_path(&project_id.0);
+                        let Some(id) = find_project_id_by_path(&self.state, &path) else {
+                            let _ = reply.send(Err("project not found".to_owned()));
+                            return;
+                        };
+                        self.process_action_queue(Action::AddProjectSavedCommand {
+                            project_id: id,
+                            label: label.clone(),
+                            command: command.clone(),
+                        })
+                        .await;
+                        let _ = reply.send(Ok(self.rev));
+                        return;
+                    }
+                    luban_api::ClientAction::RemoveProjectSavedCommand { project_id, id } => {
+                        let path = expand_user_path(&project_id.0);
+                        let Some(project_id) = find_project_id_by_path(&self.state, &path) else {
+                            let _ = reply.send(Err("project not found".to_owned()));
+                            return;
+                        };
+                        self.process_action_queue(Action::RemoveProjectSavedCommand {
+                            project_id,
+                            id: *id,
+                        })
+                        .await;
+                        let _ = reply.send(Ok(self.rev));
+                        return;
+                    }
+                    luban_api::ClientAction::SetMainPane { pane } => {
+                        let pane = match pane {
+                            luban_api::MainPaneSnapshot::None => luban_domain::MainPane::None,
+                            luban_api::MainPaneSnapshot::Dashboard => {
+                                luban_domain::MainPane::Dashboard
+                            }
+                            luban_api::MainPaneSnapshot::ProjectSettings { project_id } => {
+                                let path = expand_user_path(&project_id.0);
+                                let Some(id) = find_project_id_by_path(&self.state, &path) else {
+                                    let _ = reply.send(Err("project not found".to_owned()));
+                                    return;
+                                };
+                                luban_domain::MainPane::ProjectSettings(id)
+                            }
+                            luban_api::MainPaneSnapshot::Workspace { workspace_id } => {
+                                let id = WorkspaceId::from_u64(workspace_id.0);
+                                if self.state.workspace(id).is_none() {
+                                    let _ = reply.send(Err("workspace not found".to_owned()));
+                                    return;
+                                }
+                                luban_domain::MainPane::Workspace(id)
+                            }
+                        };
+                        self.process_action_queue(Action::SetMainPane { pane })
+                            .await;
+                        let _ = reply.send(Ok(self.rev));
+                        return;
+                    }
+                    luban_api::ClientAction::CreateWorkspace {
+                        project_id,
+                        branch_name_hint,
+                    } => {
+                        let path = expand_user_path(&project_id.0);
+                        let Some(id) = find_project_id_by_path(&self.state, &path) else {
+                            let _ = reply.send(Err("project not found".to_owned()));
+                            return;
+                        };
+                        self.process_action_queue(Action::CreateWorkspace {
+                            project_id: id,
+                            branch_name_hint: branch_name_hint.clone(),
+                        })
+                        .await;
+                        let _ = reply.send(Ok(self.rev));
+                        return;
+                    }
+                    luban_api::ClientAction::CreateWorkspaceFromBranch {
+                        project_id,
+                        branch_name,
+                    } => {
+                        let path = expand_user_path(&project_id.0);
+                        let Some(id) = find_project_id_by_path(&self.state, &path) else {
+                            let _ = reply.send(Err("project not found".to_owned()));
+                            return;
+                        };
+                        self.process_action_queue(Action::CreateWorkspaceFromBranch {
+                            project_id: id,
+                            branch_name: branch_name.clone(),
+                        })
+                        .await;
+                        let _ = reply.send(Ok(self.rev));
+                        return;
+                    }
+                    luban_api::ClientAction::EnsureMainWorkspace { project_id } => {
+                        let path = expand_user_path(&project_id.0);
+                        let Some(id) = find_project_id_by_path(&self.state, &path) else {
+                            let _ = reply.send(Err("project not found".to_owned()));
+                            return;
+                        };
+                        self.process_action_queue(Action::EnsureMainWorkspace { project_id: id })
+                            .await;
+                        let _ = reply.send(Ok(self.rev));
+                        return;
+                    }
+                    luban_api::ClientAction::CancelAndSendAgentMessage {
+                        workspace_id,
+                        thread_id,
+                        text,
+                        attachments,
+                        runner,
+                        amp_mode,
+                        ..
+                    } => {
+                        let wid = WorkspaceId::from_u64(workspace_id.0);
+                        let tid = WorkspaceThreadId::from_u64(thread_id.0);
+                        self.process_action_queue(Action::CancelAgentTurn {
+                            workspace_id: wid,
+                            thread_id: tid,
+                        })
+                        .await;
+                        let runner = runner.map(map_api_agent_runner_kind);
+                        let amp_mode = if runner == Some(luban_domain::AgentRunnerKind::Amp) {
+                            amp_mode.clone()
+                        } else {
+                            None
+                        };
+                        self.process_action_queue(Action::SendAgentMessage {
+                            workspace_id: wid,
+                            thread_id: tid,
+                            text: text.clone(),
+                            attachments: attachments
+                                .iter()
+                                .cloned()
+                                .map(map_api_attachment)
+                                .collect(),
+                            runner,
+                            amp_mode,
+                        })
+                        .await;
+                        let _ = reply.send(Ok(self.rev));
+                        return;
+                    }
+                    _ => {}
+                }
+
+                let mapped = map_client_action(action);
+                let Some(action) = mapped else {
+                    let _ = reply.send(Err("unsupported action".to_owned()));
+                    return;
+                };
+
+                if let Action::BulkSetTaskStatus {
+                    targets,
+                    task_status,
+                } = &action
+                {
+                    let closes_tasks = matches!(
+                        task_status,
+                        luban_domain::TaskStatus::Done | luban_domain::TaskStatus::Canceled
+                    );
+                    let results: Vec<luban_api::TaskStatusSetResult> = targets
+                        .iter()
+                        .map(|(wid, tid)| {
+                            let message =
+                                self.state
+                                    .task_status_change_error(*wid, *tid, *task_status);
+                            luban_api::TaskStatusSetResult {
+                                workspace_id: luban_api::WorkspaceId(wid.as_u64()),
+                                thread_id: luban_api::WorkspaceThreadId(tid.as_u64()),
+                                ok: message.is_none(),
+                                message,
+                            }
+                        })
+                        .collect();
+                    let closed_keys = targets
+                        .iter()
+                        .zip(&results)
+                        .filter(|(_, result)| closes_tasks && result.ok)
+                        .map(|(key, _)| *key)
+                        .collect::<Vec<_>>();
+                    self.process_action_queue(action).await;
+                    // Closing a task drops its queued prompts; persist that per target, as
+                    // `queue_state_key_for_action` does for single `TaskStatusSet` actions.
+                    for (wid, tid) in closed_keys {
+                        self.persist_queue_state(wid, tid).await;
+                    }
+                    let _ = self.events.send(WsServerMessage::Event {
+                        rev: self.rev,
+                        event: Box::new(luban_api::ServerEvent::TaskStatusBulkSet {
+                            request_id,
+                            results,
+                        }),
+                    });
+                    let _ = reply.send(Ok(self.rev));
+                    return;
+                }
+
+                self.process_action_queue(action).await;
+                let _ = reply.send(Ok(self.rev));
             }
-            Effect::RunAgentTurn {
+            EngineCommand::DispatchAction { action } => {
+                if let Action::WorkspaceArchived { workspace_id } = action.as_ref() {
+                    self.auto_archive_workspaces.remove(workspace_id);
+                }
+                self.process_action_queue(*action).await;
+            }
+            EngineCommand::AutoArchiveWorkspace { workspace_id } => {
+                self.auto_archive_workspaces.insert(workspace_id);
+                self.process_action_queue(Action::ArchiveWorkspace { workspace_id })
+                    .await;
+            }
+            EngineCommand::RefreshPullRequests { workspace_id } => match workspace_id {
+                Some(id) => self.maybe_refresh_pull_request(id),
+                None => self.refresh_pull_requests_for_all_workspaces(),
+            },
+            EngineCommand::WorktreeDirtyUpdated {
                 workspace_id,
-                thread_id,
-                run_id,
-                text,
-                attachments,
-                run_config,
+                dirty,
             } => {
-                let started_at_unix_ms = std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_millis()
-                    .try_into()
-                    .unwrap_or(0u64);
+                self.worktree_dirty_in_flight.remove(&workspace_id);
+                let previous = self
+                    .worktree_dirty
+                    .insert(
+                        workspace_id,
+                        WorktreeDirtyCacheEntry {
+                            dirty,
+                            checked_at: Instant::now(),
+                        },
+                    )
+                    .map(|entry| entry.dirty);
+                if previous.unwrap_or(false) != dirty {
+                    self.rev = self.rev.saturating_add(1);
+                    let thread_ids = self
+                        .state
+                        .conversations
+                        .keys()
+                        .filter(|(wid, _)| *wid == workspace_id)
+                        .map(|(_, tid)| *tid)
+                        .collect::<Vec<_>>();
+                    for thread_id in thread_ids {
+                        self.publish_conversation_snapshot(workspace_id, thread_id, None)
+                            .await;
+                    }
+                }
+                if self.worktree_dirty_rerun.remove(&workspace_id) {
+                    self.maybe_refresh_worktree_dirty(workspace_id, true);
+                }
+            }
+            EngineCommand::PullRequestInfoUpdated { workspace_id, info } => {
+                self.pull_requests_in_flight.remove(&workspace_id);
 
-                let use_fake_agent = std::env::var_os("LUBAN_E2E_ROOT").is_some()
-                    && std::env::var("LUBAN_CODEX_BIN")
-                        .ok()
-                        .is_some_and(|bin| bin == "/usr/bin/false");
-                let fake_agent_delay = if use_fake_agent {
-                    let prompt = text.as_str();
-                    if prompt.contains("e2e-running-card")
-                        || prompt.contains("e2e-streaming-message")
-                    {
-                        Duration::from_millis(3500)
-                    } else if prompt.contains("e2e-ansi-output") {
-                        Duration::from_millis(600)
-                    } else if prompt.contains("e2e-cancel") {
-                        Duration::from_millis(2500)
-                    } else if prompt.contains("e2e-queued") {
-                        Duration::from_millis(1500)
-                    } else {
-                        Duration::from_millis(50)
+                let now = Instant::now();
+                let previous = self.pull_requests.get(&workspace_id);
+                let (next_refresh_at, consecutive_empty, consecutive_errors) =
+                    pull_request_next_refresh_at(
+                        workspace_id,
+                        now,
+                        previous,
+                        info.as_ref().map(Option::as_ref).map_err(String::as_str),
+                    );
+                let targeted = self
+                    .state
+                    .projects
+                    .iter()
+                    .find(|p| p.workspaces.iter().any(|w| w.id == workspace_id))
+                    .is_some_and(|p| {
+                        p.pull_request_target() != luban_domain::PullRequestTarget::default()
+                    });
+                let (info, error) = match info {
+                    Ok(info) => (info, None),
+                    Err(message) => {
+                        tracing::warn!(
+                            workspace_id = workspace_id.as_u64(),
+                            error = %message,
+                            "pull request refresh failed"
+                        );
+                        // Keep showing the last known pull request until a lookup succeeds.
+                        // Only a configured target yields errors worth surfacing; default
+                        // lookups fail transiently and are retried quietly.
+                        (
+                            previous.and_then(|entry| entry.info),
+                            targeted.then_some(message),
+                        )
                     }
-                } else {
-                    Duration::from_millis(0)
                 };
 
-                let Some(scope) = workspace_scope(&self.state, workspace_id) else {
-                    return Ok(VecDeque::new());
-                };
+                let changed = self
+                    .pull_requests
+                    .get(&workspace_id)
+                    .map(|e| e.info != info || e.error != error)
+                    .unwrap_or(true);
 
-                let worktree_path = self
-                    .state
-                    .workspace(workspace_id)
-                    .map(|w| w.worktree_path.clone())
-                    .unwrap_or_else(|| std::env::current_dir().unwrap_or_default());
+                self.pull_requests.insert(
+                    workspace_id,
+                    PullRequestCacheEntry {
+                        info,
+                        error,
+                        next_refresh_at,
+                        consecutive_empty,
+                        consecutive_errors,
+                    },
+                );
 
-                let remote_thread_id = self
-                    .state
-                    .workspace_thread_conversation(workspace_id, thread_id)
-                    .and_then(|c| c.thread_id.clone());
+                if changed {
+                    self.rev = self.rev.saturating_add(1);
+                    self.publish_app_snapshot();
+                }
 
-                let request = luban_domain::RunAgentTurnRequest {
-                    project_slug: scope.project_slug,
-                    workspace_name: scope.workspace_name,
-                    worktree_path,
-                    thread_local_id: thread_id.as_u64(),
-                    thread_id: remote_thread_id,
-                    prompt: text,
-                    attachments,
-                    runner: run_config.runner,
-                    amp_mode: run_config.amp_mode.clone(),
-                    model: Some(run_config.model_id.clone()),
-                    model_reasoning_effort: Some(run_config.thinking_effort.as_str().to_owned()),
-                };
+                if changed
+                    && let Some(pr) = self
+                        .pull_requests
+                        .get(&workspace_id)
+                        .and_then(|entry| entry.info.as_ref())
+                    && pr.state == DomainPullRequestState::Merged
+                {
+                    self.spawn_task_status_suggest_done_for_merged_pr(workspace_id, pr.number);
+                }
+            }
+            EngineCommand::PruneArchivedTasks => {
+                self.prune_archived_tasks().await;
+            }
+            EngineCommand::PruneConversationHistory => {
+                self.prune_conversation_history().await;
+            }
+            EngineCommand::ArchiveInactiveWorkspaces => {
+                self.archive_inactive_workspaces().await;
+            }
+            EngineCommand::WorkspaceThreadsInvalidated { workspace_id } => {
+                self.workspace_threads_cache.remove(&workspace_id);
+                self.rev = self.rev.saturating_add(1);
+                self.publish_app_snapshot();
+            }
+            EngineCommand::WorkspaceBranchObserved {
+                workspace_id,
+                branch_name,
+            } => {
+                self.process_action_queue(Action::WorkspaceBranchSynced {
+                    workspace_id,
+                    branch_name,
+                })
+                .await;
+                self.refresh_last_commit(workspace_id);
+            }
+            EngineCommand::LastCommitUpdated {
+                workspace_id,
+                last_commit,
+            } => {
+                if self.last_commits.get(&workspace_id) != Some(&last_commit) {
+                    self.last_commits.insert(workspace_id, last_commit);
+                    self.rev = self.rev.saturating_add(1);
+                    self.publish_app_snapshot();
+                }
+            }
+            EngineCommand::FlushConversationNotes {
+                workspace_id,
+                thread_id,
+                generation,
+            } => {
+                self.flush_conversation_notes(workspace_id, thread_id, generation)
+                    .await;
+            }
+            EngineCommand::FlushConversationDraft {
+                workspace_id,
+                thread_id,
+                generation,
+            } => {
+                self.flush_conversation_draft(workspace_id, thread_id, generation)
+                    .await;
+            }
+        }
+    }
 
-                let cancel = Arc::new(AtomicBool::new(false));
-                self.cancel_flags.insert(
-                    (workspace_id, thread_id),
-                    CancelFlagEntry {
-                        run_id,
-                        flag: cancel.clone(),
-                    },
-                );
+    /// Relocates a task's persisted conversation into another workdir of the same project and
+    /// refreshes the task lists of both workdirs.
+    async fn move_thread(
+        &mut self,
+        from_workspace_id: WorkspaceId,
+        thread_id: WorkspaceThreadId,
+        to_workspace_id: WorkspaceId,
+    ) -> Result<(), String> {
+        if from_workspace_id == to_workspace_id {
+            return Err("task is already in this workspace".to_owned());
+        }
+        let from_scope = workspace_scope(&self.state, from_workspace_id)
+            .ok_or_else(|| "workspace not found".to_owned())?;
+        let to_scope = workspace_scope(&self.state, to_workspace_id)
+            .ok_or_else(|| "target workspace not found".to_owned())?;
+        if from_scope.project_slug != to_scope.project_slug {
+            return Err("target workspace belongs to a different project".to_owned());
+        }
+        let key = (from_workspace_id, thread_id);
+        let running = self
+            .state
+            .workspace_thread_conversation(from_workspace_id, thread_id)
+            .is_some_and(|c| c.run_status == OperationStatus::Running)
+            || self.agent_turn_slots.contains_key(&key)
+            || self.agent_turn_is_deferred(from_workspace_id, thread_id);
+        if running {
+            return Err("task is running; cancel it before moving".to_owned());
+        }
 
-                if use_fake_agent {
-                    let tx = self.tx.clone();
-                    std::thread::spawn(move || {
-                        let deadline = fake_agent_delay;
-                        let start = Instant::now();
-                        let prompt = request.prompt.clone();
+        // Persist debounced edits under the old key before the rows are rekeyed.
+        if let Some(generation) = self.pending_notes_flushes.get(&key).copied() {
+            self.flush_conversation_notes(from_workspace_id, thread_id, generation)
+                .await;
+        }
+        if let Some(generation) = self.pending_draft_flushes.get(&key).copied() {
+            self.flush_conversation_draft(from_workspace_id, thread_id, generation)
+                .await;
+        }
 
-                        let emit_many_steps = prompt.contains("e2e-many-steps");
-                        let emit_pagination_steps = prompt.contains("e2e-pagination-steps");
-                        let emit_markdown_reasoning = prompt.contains("e2e-thinking-markdown");
-                        let emit_file_change = prompt.contains("e2e-file-change");
-                        let emit_streaming_message = prompt.contains("e2e-streaming-message");
-                        let emit_long_output = prompt.contains("e2e-long-output");
+        let target_threads = self
+            .list_threads_blocking(to_scope.clone())
+            .await
+            .unwrap_or_default();
+        let next_persisted = target_threads
+            .iter()
+            .map(|t| t.thread_id.as_u64() + 1)
+            .max()
+            .unwrap_or(1);
+        let next_open = self
+            .state
+            .workspace_tabs(to_workspace_id)
+            .map(|tabs| tabs.next_thread_id)
+            .unwrap_or(1);
+        let to_thread_id = WorkspaceThreadId::from_u64(next_persisted.max(next_open));
 
-                        if emit_many_steps || emit_pagination_steps {
-                            let count = if emit_pagination_steps {
-                                2505u32
-                            } else {
-                                12_000u32
-                            };
-                            // Generate a large amount of completed items to stress the UI render/timing
-                            // paths. This is used only in e2e mode (`LUBAN_E2E_ROOT` + fake codex bin).
-                            // Keep the IDs simple and stable.
-                            for i in 0..count {
-                                if cancel.load(Ordering::SeqCst) {
-                                    break;
-                                }
-                                let _ = tx.blocking_send(EngineCommand::DispatchAction {
-                                    action: Box::new(Action::AgentEventReceived {
-                                        workspace_id,
-                                        thread_id,
-                                        run_id,
-                                        event: luban_domain::CodexThreadEvent::ItemCompleted {
-                                            item: luban_domain::CodexThreadItem::CommandExecution {
-                                                id: format!("e2e_many_{i}"),
-                                                command: format!("echo {i}"),
-                                                aggregated_output: "ok".to_owned(),
-                                                exit_code: Some(0),
-                                                status: luban_domain::CodexCommandExecutionStatus::Completed,
-                                            },
-                                        },
-                                    }),
-                                });
-                            }
+        let services = self.services.clone();
+        let (project_slug, workspace_name) = (
+            from_scope.project_slug.clone(),
+            from_scope.workspace_name.clone(),
+        );
+        let to_workspace_name = to_scope.workspace_name.clone();
+        tokio::task::spawn_blocking(move || {
+            services.move_conversation_thread(
+                project_slug,
+                workspace_name,
+                thread_id.as_u64(),
+                to_workspace_name,
+                to_thread_id.as_u64(),
+            )
+        })
+        .await
+        .ok()
+        .unwrap_or_else(|| Err("failed to join move thread task".to_owned()))?;
 
-                            if !cancel.load(Ordering::SeqCst) {
-                                let _ = tx.blocking_send(EngineCommand::DispatchAction {
-                                    action: Box::new(Action::AgentEventReceived {
-                                        workspace_id,
-                                        thread_id,
-                                        run_id,
-                                        event: luban_domain::CodexThreadEvent::TurnFailed {
-                                            error: luban_domain::CodexThreadError {
-                                                message: "e2e agent stub".to_owned(),
-                                            },
-                                        },
-                                    }),
-                                });
-                            }
+        self.process_action_queue(Action::WorkspaceThreadMoved {
+            from_workspace_id,
+            thread_id,
+            to_workspace_id,
+            to_thread_id,
+        })
+        .await;
 
-                            let _ = tx.blocking_send(EngineCommand::DispatchAction {
-                                action: Box::new(Action::AgentTurnFinished {
-                                    workspace_id,
-                                    thread_id,
-                                    run_id,
-                                }),
-                            });
-                            return;
-                        }
+        for (workspace_id, scope) in [(from_workspace_id, from_scope), (to_workspace_id, to_scope)]
+        {
+            if let Ok(threads) = self.list_threads_blocking(scope).await {
+                self.process_action_queue(Action::WorkspaceThreadsLoaded {
+                    workspace_id,
+                    threads,
+                })
+                .await;
+            }
+        }
+        Ok(())
+    }
 
-                        let mut sent_1_start = false;
-                        let mut sent_1_done = false;
-                        let mut sent_2_start = false;
-                        let mut sent_2_done = false;
-                        let mut sent_3_start = false;
-                        let mut sent_ansi_output = false;
-                        let mut streaming_started = false;
-                        let mut streaming_completed = false;
-                        let streaming_id = "e2e_stream_msg_1".to_owned();
-                        let streaming_needle = "e2e-selection-needle";
-                        let mut streaming_text = String::new();
-                        let mut streaming_chunks_sent: u32 = 0;
+    async fn list_threads_blocking(
+        &self,
+        scope: WorkspaceScope,
+    ) -> Result<Vec<luban_domain::ConversationThreadMeta>, String> {
+        let services = self.services.clone();
+        tokio::task::spawn_blocking(move || {
+            services.list_conversation_threads(scope.project_slug, scope.workspace_name)
+        })
+        .await
+        .ok()
+        .unwrap_or_else(|| Err("failed to join list threads task".to_owned()))
+    }
 
-                        while start.elapsed() < deadline && !cancel.load(Ordering::SeqCst) {
-                            let elapsed = start.elapsed();
+    async fn flush_conversation_notes(
+        &mut self,
+        workspace_id: WorkspaceId,
+        thread_id: WorkspaceThreadId,
+        generation: u64,
+    ) {
+        let key = (workspace_id, thread_id);
+        // A newer edit rescheduled the flush; only the latest generation persists.
+        if self.pending_notes_flushes.get(&key) != Some(&generation) {
+            return;
+        }
+        self.pending_notes_flushes.remove(&key);
 
-                            if emit_streaming_message && !streaming_completed {
-                                if !streaming_started && elapsed >= Duration::from_millis(50) {
-                                    streaming_started = true;
-                                    streaming_text =
-                                        format!("Streaming...\n\n{streaming_needle}\n\n");
-                                    let _ = tx.blocking_send(EngineCommand::DispatchAction {
-                                        action: Box::new(Action::AgentEventReceived {
-                                            workspace_id,
-                                            thread_id,
-                                            run_id,
-                                            event: luban_domain::CodexThreadEvent::ItemStarted {
-                                                item: luban_domain::CodexThreadItem::AgentMessage {
-                                                    id: streaming_id.clone(),
-                                                    text: streaming_text.clone(),
-                                                },
-                                            },
-                                        }),
-                                    });
-                                }
+        let Some(scope) = workspace_scope(&self.state, workspace_id) else {
+            return;
+        };
+        let Some(notes) = self
+            .state
+            .workspace_thread_conversation(workspace_id, thread_id)
+            .map(|c| c.notes.clone())
+        else {
+            return;
+        };
 
-                                if streaming_started {
-                                    let chunk_every_ms = 120u64;
-                                    let elapsed_ms = elapsed.as_millis() as u64;
-                                    let expected_chunks =
-                                        (elapsed_ms / chunk_every_ms).min(25) as u32;
-                                    while streaming_chunks_sent < expected_chunks {
-                                        streaming_chunks_sent += 1;
-                                        streaming_text.push_str(&format!(
-                                            "chunk-{:02}\n",
-                                            streaming_chunks_sent
-                                        ));
-                                        let _ = tx.blocking_send(EngineCommand::DispatchAction {
-                                            action: Box::new(Action::AgentEventReceived {
-                                                workspace_id,
-                                                thread_id,
-                                                run_id,
-                                                event: luban_domain::CodexThreadEvent::ItemUpdated {
-                                                    item: luban_domain::CodexThreadItem::AgentMessage {
-                                                        id: streaming_id.clone(),
-                                                        text: streaming_text.clone(),
-                                                    },
-                                                },
-                                            }),
-                                        });
-                                    }
-                                }
+        let services = self.services.clone();
+        let thread_local_id = thread_id.as_u64();
+        let _ = tokio::task::spawn_blocking(move || {
+            services.save_conversation_notes(
+                scope.project_slug,
+                scope.workspace_name,
+                thread_local_id,
+                notes,
+            )
+        })
+        .await;
 
-                                if elapsed >= Duration::from_millis(3000) {
-                                    streaming_completed = true;
-                                    let _ = tx.blocking_send(EngineCommand::DispatchAction {
-                                        action: Box::new(Action::AgentEventReceived {
-                                            workspace_id,
-                                            thread_id,
-                                            run_id,
-                                            event: luban_domain::CodexThreadEvent::ItemCompleted {
-                                                item: luban_domain::CodexThreadItem::AgentMessage {
-                                                    id: streaming_id.clone(),
-                                                    text: streaming_text.clone(),
-                                                },
-                                            },
-                                        }),
-                                    });
-                                }
-                            }
+        // Only bookkeeping changes, so the action is applied without a new `rev`.
+        if !self.pending_notes_flushes.contains_key(&key) {
+            let _ = self.state.apply(Action::ThreadNotesSaved {
+                workspace_id,
+                thread_id,
+            });
+        }
+    }
 
-                            if prompt.contains("e2e-ansi-output")
-                                && !sent_ansi_output
-                                && elapsed >= Duration::from_millis(75)
-                            {
-                                sent_ansi_output = true;
-                                let aggregated_output = [
-                                    "[[2m[WebServer] [[22m Finished 'dev' profile [unoptimized + debuginfo] target(s) in 0.33s",
-                                    "[[2m[WebServer] [[22m Running 'target/debug/luban_server'",
-                                    "",
-                                    "(node:4596) Warning: The 'NO_COLOR' env is ignored due to the 'FORCE_COLOR' env being set.",
-                                    "",
-                                    "[[1A[[2K[[0G [[32m√[[39m [[2mtests/e2e/chat-ui.spec.ts:334:5 › enter commits IME composition without sending[[22m",
-                                    "[[32m  2 passed[[39m[[2m (14.1s)[[22m",
-                                ]
-                                .join("\n");
-                                let _ = tx.blocking_send(EngineCommand::DispatchAction {
-                                    action: Box::new(Action::AgentEventReceived {
-                                        workspace_id,
-                                        thread_id,
-                                        run_id,
-                                        event: luban_domain::CodexThreadEvent::ItemCompleted {
-                                            item: luban_domain::CodexThreadItem::CommandExecution {
-                                                id: "e2e_ansi_cmd_1".to_owned(),
-                                                command: "zsh -lc \"just test-ui\"".to_owned(),
-                                                aggregated_output,
-                                                exit_code: Some(0),
-                                                status: luban_domain::CodexCommandExecutionStatus::Completed,
-                                            },
-                                        },
-                                    }),
-                                });
-                            }
+    async fn flush_conversation_draft(
+        &mut self,
+        workspace_id: WorkspaceId,
+        thread_id: WorkspaceThreadId,
+        generation: u64,
+    ) {
+        let key = (workspace_id, thread_id);
+        if self.pending_draft_flushes.get(&key) != Some(&generation) {
+            return;
+        }
+        self.pending_draft_flushes.remove(&key);
 
-                            if prompt.contains("e2e-running-card") {
-                                if !sent_1_start && elapsed >= Duration::from_millis(50) {
-                                    sent_1_start = true;
-                                    let _ = tx.blocking_send(EngineCommand::DispatchAction {
-                                        action: Box::new(Action::AgentEventReceived {
-                                            workspace_id,
-                                            thread_id,
-                                            run_id,
-                                            event: luban_domain::CodexThreadEvent::ItemStarted {
-                                                item: luban_domain::CodexThreadItem::CommandExecution {
-                                                    id: "e2e_cmd_1".to_owned(),
-                                                    command: "echo 1".to_owned(),
-                                                    aggregated_output: "".to_owned(),
-                                                    exit_code: None,
-                                                    status: luban_domain::CodexCommandExecutionStatus::InProgress,
-                                                },
-                                            },
-                                        }),
-                                    });
-                                }
-                                if !sent_1_done && elapsed >= Duration::from_millis(250) {
-                                    sent_1_done = true;
-                                    let _ = tx.blocking_send(EngineCommand::DispatchAction {
-	                                        action: Box::new(Action::AgentEventReceived {
-	                                            workspace_id,
-	                                            thread_id,
-	                                            run_id,
-	                                            event: luban_domain::CodexThreadEvent::ItemCompleted {
-	                                                item: luban_domain::CodexThreadItem::CommandExecution {
-	                                                    id: "e2e_cmd_1".to_owned(),
-	                                                    command: "echo 1".to_owned(),
-	                                                    aggregated_output: "".to_owned(),
-	                                                    exit_code: Some(0),
-	                                                    status: luban_domain::CodexCommandExecutionStatus::Completed,
-	                                                },
-	                                            },
-	                                        }),
-	                                    });
-                                }
-                                if !sent_2_start && elapsed >= Duration::from_millis(350) {
-                                    sent_2_start = true;
-                                    let _ = tx.blocking_send(EngineCommand::DispatchAction {
-                                        action: Box::new(Action::AgentEventReceived {
-                                            workspace_id,
-                                            thread_id,
-                                            run_id,
-                                            event: luban_domain::CodexThreadEvent::ItemStarted {
-                                                item: luban_domain::CodexThreadItem::CommandExecution {
-                                                    id: "e2e_cmd_2".to_owned(),
-                                                    command: "echo 2".to_owned(),
-                                                    aggregated_output: "".to_owned(),
-                                                    exit_code: None,
-                                                    status: luban_domain::CodexCommandExecutionStatus::InProgress,
-                                                },
-                                            },
-                                        }),
-                                    });
-                                }
-                                if !sent_2_done && elapsed >= Duration::from_millis(1750) {
-                                    sent_2_done = true;
-                                    let aggregated_output = if emit_long_output {
-                                        [
-                                            "test io::commit::conflict_resolver::tests::test_conflicting_rebase::ours_1__update_full__::other_1__update_full__ ... ok",
-                                            "test io::commit::conflict_resolver::tests::test_conflicting_rebase::ours_1__update_full__::other_2__update_partial__ ... ok",
-                                            "test io::commit::conflict_resolver::tests::test_conflicting_rebase::ours_2__update_partial__::other_4__delete_partial__ ... ok",
-                                        ]
-                                        .join("\n")
-                                    } else {
-                                        "ok".to_owned()
-                                    };
-                                    let _ = tx.blocking_send(EngineCommand::DispatchAction {
-                                        action: Box::new(Action::AgentEventReceived {
-                                            workspace_id,
-                                            thread_id,
-                                            run_id,
-                                            event: luban_domain::CodexThreadEvent::ItemCompleted {
-                                                item: luban_domain::CodexThreadItem::CommandExecution {
-                                                    id: "e2e_cmd_2".to_owned(),
-                                                    command: "echo 2".to_owned(),
-                                                    aggregated_output,
-                                                    exit_code: Some(0),
-                                                    status: luban_domain::CodexCommandExecutionStatus::Completed,
-                                                },
-                                            },
-                                        }),
-                                    });
-                                }
-                                if !sent_3_start && elapsed >= Duration::from_millis(1800) {
-                                    sent_3_start = true;
-                                    let _ = tx.blocking_send(EngineCommand::DispatchAction {
-                                        action: Box::new(Action::AgentEventReceived {
-                                            workspace_id,
-                                            thread_id,
-                                            run_id,
-                                            event: luban_domain::CodexThreadEvent::ItemStarted {
-                                                item: luban_domain::CodexThreadItem::CommandExecution {
-                                                    id: "e2e_cmd_3".to_owned(),
-                                                    command: "echo 3".to_owned(),
-                                                    aggregated_output: "".to_owned(),
-                                                    exit_code: None,
-                                                    status: luban_domain::CodexCommandExecutionStatus::InProgress,
-                                                },
-                                            },
-                                        }),
-                                    });
-                                }
-                            }
+        let Some(scope) = workspace_scope(&self.state, workspace_id) else {
+            return;
+        };
+        let Some(draft) = self
+            .state
+            .workspace_thread_conversation(workspace_id, thread_id)
+            .map(|c| c.draft.clone())
+        else {
+            return;
+        };
 
-                            std::thread::sleep(Duration::from_millis(25));
-                        }
+        let services = self.services.clone();
+        let thread_local_id = thread_id.as_u64();
+        let _ = tokio::task::spawn_blocking(move || {
+            services.save_conversation_draft(
+                scope.project_slug,
+                scope.workspace_name,
+                thread_local_id,
+                draft,
+            )
+        })
+        .await;
+    }
 
-                        if !cancel.load(Ordering::SeqCst) {
-                            if emit_markdown_reasoning {
-                                let _ = tx.blocking_send(EngineCommand::DispatchAction {
-                                    action: Box::new(Action::AgentEventReceived {
-                                        workspace_id,
-                                        thread_id,
-                                        run_id,
-                                        event: luban_domain::CodexThreadEvent::ItemStarted {
-                                            item: luban_domain::CodexThreadItem::Reasoning {
-                                                id: "e2e_reasoning_1".to_owned(),
-                                                text:
-                                                    "**Plan**: verify markdown summary stripping."
-                                                        .to_owned(),
-                                            },
-                                        },
-                                    }),
-                                });
+    fn spawn_task_status_suggest_done_for_merged_pr(
+        &self,
+        workspace_id: WorkspaceId,
+        pr_number: u64,
+    ) {
+        let Some(scope) = workspace_scope(&self.state, workspace_id) else {
+            return;
+        };
 
-                                let _ = tx.blocking_send(EngineCommand::DispatchAction {
-                                    action: Box::new(Action::AgentEventReceived {
-                                        workspace_id,
-                                        thread_id,
-                                        run_id,
-                                        event: luban_domain::CodexThreadEvent::ItemCompleted {
-                                            item: luban_domain::CodexThreadItem::Reasoning {
-                                                id: "e2e_reasoning_1".to_owned(),
-                                                text:
-                                                    "**Plan**: verify markdown summary stripping."
-                                                        .to_owned(),
-                                            },
-                                        },
-                                    }),
-                                });
-                            }
+        let services = self.services.clone();
+        let tx = self.tx.clone();
+        let project_slug = scope.project_slug;
+        let workspace_name = scope.workspace_name;
 
-                            if prompt.contains("e2e-mermaid") {
-                                let _ = tx.blocking_send(EngineCommand::DispatchAction {
-                                    action: Box::new(Action::AgentEventReceived {
-                                        workspace_id,
-                                        thread_id,
-                                        run_id,
-                                        event: luban_domain::CodexThreadEvent::ItemCompleted {
-                                            item: luban_domain::CodexThreadItem::AgentMessage {
-                                                id: "e2e_mermaid_1".to_owned(),
-                                                text: prompt.clone(),
-                                            },
-                                        },
-                                    }),
-                                });
-                            }
+        tokio::spawn(async move {
+            let thread_ids = tokio::task::spawn_blocking(move || {
+                services.list_conversation_tasks_for_merged_pr(
+                    project_slug.clone(),
+                    workspace_name.clone(),
+                    pr_number,
+                )
+            })
+            .await
+            .ok()
+            .and_then(|res| res.ok())
+            .unwrap_or_default();
 
-                            if emit_file_change {
-                                let _ = tx.blocking_send(EngineCommand::DispatchAction {
-                                    action: Box::new(Action::AgentEventReceived {
-                                        workspace_id,
-                                        thread_id,
-                                        run_id,
-                                        event: luban_domain::CodexThreadEvent::ItemCompleted {
-                                            item: luban_domain::CodexThreadItem::FileChange {
-                                                id: "e2e_file_change_1".to_owned(),
-                                                changes: vec![
-                                                    luban_domain::CodexFileUpdateChange {
-                                                        path: "src/e2e-file-change/a.txt".to_owned(),
-                                                        kind: luban_domain::CodexPatchChangeKind::Add,
-                                                    },
-                                                    luban_domain::CodexFileUpdateChange {
-                                                        path: "web/e2e-file-change/b.ts".to_owned(),
-                                                        kind: luban_domain::CodexPatchChangeKind::Update,
-                                                    },
-                                                    luban_domain::CodexFileUpdateChange {
-                                                        path: "README.md".to_owned(),
-                                                        kind: luban_domain::CodexPatchChangeKind::Delete,
-                                                    },
-                                                ],
-                                                status: luban_domain::CodexPatchApplyStatus::Completed,
-                                            },
-                                        },
-                                    }),
-                                });
-                            }
-
-                            let _ = tx.blocking_send(EngineCommand::DispatchAction {
-                                action: Box::new(Action::AgentEventReceived {
-                                    workspace_id,
-                                    thread_id,
-                                    run_id,
-                                    event: luban_domain::CodexThreadEvent::TurnFailed {
-                                        error: luban_domain::CodexThreadError {
-                                            message: "e2e agent stub".to_owned(),
-                                        },
-                                    },
-                                }),
-                            });
-                        }
-
-                        if cancel.load(Ordering::SeqCst) {
-                            return;
-                        }
-
-                        let _ = tx.blocking_send(EngineCommand::DispatchAction {
-                            action: Box::new(Action::AgentRunFinishedAt {
-                                workspace_id,
-                                thread_id,
-                                run_id,
-                                finished_at_unix_ms: std::time::SystemTime::now()
-                                    .duration_since(std::time::UNIX_EPOCH)
-                                    .unwrap_or_default()
-                                    .as_millis()
-                                    .try_into()
-                                    .unwrap_or(0u64),
-                            }),
-                        });
-
-                        let _ = tx.blocking_send(EngineCommand::DispatchAction {
-                            action: Box::new(Action::AgentTurnFinished {
+            if !thread_ids.is_empty() {
+                for thread_local_id in thread_ids {
+                    let _ = tx
+                        .send(EngineCommand::DispatchAction {
+                            action: Box::new(Action::TaskStatusSuggestionCreated {
                                 workspace_id,
-                                thread_id,
-                                run_id,
+                                thread_id: WorkspaceThreadId::from_u64(thread_local_id),
+                                expected_current_task_status: luban_domain::TaskStatus::Validating,
+                                suggested_task_status: luban_domain::TaskStatus::Done,
+                                title: format!("Suggest moving to done (PR #{pr_number} merged)"),
+                                explanation_markdown: format!(
+                                    "- PR #{pr_number} is merged.\n- Consider marking this task as done."
+                                ),
                             }),
-                        });
-                    });
-
-                    return Ok(VecDeque::from([Action::AgentRunStartedAt {
-                        workspace_id,
-                        thread_id,
-                        run_id,
-                        started_at_unix_ms,
-                    }]));
-                }
-
-                let services = self.services.clone();
-                let tx = self.tx.clone();
-                std::thread::spawn(move || {
-                    let on_event: Arc<dyn Fn(luban_domain::AgentThreadEvent) + Send + Sync> = {
-                        let tx = tx.clone();
-                        Arc::new(move |event| {
-                            let _ = tx.blocking_send(EngineCommand::DispatchAction {
-                                action: Box::new(Action::AgentEventReceived {
-                                    workspace_id,
-                                    thread_id,
-                                    run_id,
-                                    event,
-                                }),
-                            });
                         })
-                    };
+                        .await;
+                }
 
-                    let result =
-                        services.run_agent_turn_streamed(request, cancel.clone(), on_event);
-                    if let Err(message) = result
-                        && !cancel.load(Ordering::SeqCst)
-                    {
-                        let _ = tx.blocking_send(EngineCommand::DispatchAction {
-                            action: Box::new(Action::AgentEventReceived {
-                                workspace_id,
-                                thread_id,
-                                run_id,
-                                event: luban_domain::CodexThreadEvent::Error { message },
-                            }),
-                        });
-                    }
+                // No automatic status updates: keep thread metadata stable until the user applies.
+            }
+        });
+    }
 
-                    if cancel.load(Ordering::SeqCst) {
-                        return;
-                    }
+    fn request_events(&self, request_id: &str) -> RequestEvents {
+        RequestEvents {
+            events: self.events.clone(),
+            request_id: request_id.to_owned(),
+            canceled: self.request_cancellations.register(request_id),
+            registry: self.request_cancellations.clone(),
+        }
+    }
 
-                    let _ = tx.blocking_send(EngineCommand::DispatchAction {
-                        action: Box::new(Action::AgentRunFinishedAt {
-                            workspace_id,
-                            thread_id,
-                            run_id,
-                            finished_at_unix_ms: std::time::SystemTime::now()
-                                .duration_since(std::time::UNIX_EPOCH)
-                                .unwrap_or_default()
-                                .as_millis()
-                                .try_into()
-                                .unwrap_or(0u64),
-                        }),
-                    });
+    /// Sets `thumbnail_base64` on the snapshot's image attachments. Previews missing from the
+    /// cache are rendered on the blocking pool; attachments that cannot be previewed keep `None`.
+    async fn fill_attachment_thumbnails(
+        &self,
+        workspace_id: WorkspaceId,
+        snapshot: &mut ConversationSnapshot,
+    ) {
+        let mut missing = Vec::<(String, String)>::new();
+        for attachment in conversation_snapshot_attachments_mut(snapshot) {
+            if attachment.kind != luban_api::AttachmentKind::Image
+                || self.attachment_thumbnails.get(&attachment.id).is_some()
+                || missing.iter().any(|(id, _)| *id == attachment.id)
+            {
+                continue;
+            }
+            missing.push((attachment.id.clone(), attachment.extension.clone()));
+        }
 
-                    let _ = tx.blocking_send(EngineCommand::DispatchAction {
-                        action: Box::new(Action::AgentTurnFinished {
-                            workspace_id,
-                            thread_id,
-                            run_id,
-                        }),
-                    });
-                });
+        if !missing.is_empty()
+            && let Some(scope) = workspace_scope(&self.state, workspace_id)
+        {
+            let services = self.services.clone();
+            let thumbnails = self.attachment_thumbnails.clone();
+            let _ = tokio::task::spawn_blocking(move || {
+                for (id, extension) in missing {
+                    // Read errors may be transient, so only decode failures are cached.
+                    let Ok(png) = services.context_image_preview_png(
+                        scope.project_slug.clone(),
+                        scope.workspace_name.clone(),
+                        id.clone(),
+                        extension,
+                    ) else {
+                        continue;
+                    };
+                    let thumbnail =
+                        png.map(|png| base64::engine::general_purpose::STANDARD.encode(png));
+                    thumbnails.insert(id, thumbnail);
+                }
+            })
+            .await;
+        }
 
-                Ok(VecDeque::from([Action::AgentRunStartedAt {
-                    workspace_id,
-                    thread_id,
-                    run_id,
-                    started_at_unix_ms,
-                }]))
+        for attachment in conversation_snapshot_attachments_mut(snapshot) {
+            if attachment.kind == luban_api::AttachmentKind::Image {
+                attachment.thumbnail_base64 =
+                    self.attachment_thumbnails.get(&attachment.id).flatten();
             }
-            Effect::CancelAgentTurn {
-                workspace_id,
-                thread_id,
-                run_id,
-            } => {
-                if let Some(entry) = self.cancel_flags.get(&(workspace_id, thread_id))
-                    && entry.run_id == run_id
-                {
-                    entry.flag.store(true, Ordering::SeqCst);
+        }
+    }
+
+    fn conversation_entries_limit(&self, limit: Option<u64>) -> usize {
+        limit
+            .and_then(|v| usize::try_from(v).ok())
+            .unwrap_or(self.config.conversation_load.default_entries_limit)
+            .clamp(1, self.config.conversation_load.max_entries_limit)
+    }
+
+    /// Scans pages backwards from `before` until `limit` entries pass `filter` or the thread
+    /// starts. `entries_start` becomes the cursor for the next filtered page: the index of the
+    /// oldest returned entry of a full page, or where the scan stopped otherwise.
+    async fn get_filtered_conversation_snapshot(
+        &self,
+        workspace_id: luban_api::WorkspaceId,
+        thread_id: luban_api::WorkspaceThreadId,
+        before: Option<u64>,
+        limit: Option<u64>,
+        filter: &ConversationEntryFilter,
+    ) -> anyhow::Result<ConversationSnapshot> {
+        let wanted = self.conversation_entries_limit(limit);
+        let page_limit = Some(self.config.conversation_load.max_entries_limit as u64);
+
+        let mut snapshot = self
+            .get_conversation_snapshot(workspace_id, thread_id, before, page_limit)
+            .await?;
+        let entries_end = snapshot.entries_start + snapshot.entries.len() as u64;
+        let mut page_start = snapshot.entries_start;
+        let mut page = std::mem::take(&mut snapshot.entries);
+        let mut kept = VecDeque::new();
+        loop {
+            for (offset, entry) in page.into_iter().enumerate().rev() {
+                if filter.keeps(&entry) {
+                    kept.push_front((page_start + offset as u64, entry));
                 }
-                let finished_at_unix_ms = std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_millis()
-                    .try_into()
-                    .unwrap_or(0u64);
-                Ok(VecDeque::from([Action::AgentRunFinishedAt {
-                    workspace_id,
-                    thread_id,
-                    run_id,
-                    finished_at_unix_ms,
-                }]))
             }
-            Effect::CleanupClaudeProcess {
-                workspace_id,
-                thread_id,
-            } => {
-                // Clean up any persistent Claude process for this thread
-                if let Some(scope) = workspace_scope(&self.state, workspace_id) {
-                    self.services.cleanup_claude_process(
-                        &scope.project_slug,
-                        &scope.workspace_name,
-                        thread_id.as_u64(),
-                    );
-                }
-                Ok(VecDeque::new())
+            if kept.len() >= wanted || page_start == 0 {
+                break;
             }
-            Effect::OpenWorkspacePullRequest { workspace_id } => {
-                let Some(workspace) = self.state.workspace(workspace_id) else {
-                    return Ok(VecDeque::new());
-                };
-                let worktree_path = workspace.worktree_path.clone();
-                let services = self.services.clone();
-                let result = tokio::task::spawn_blocking(move || {
-                    services.gh_open_pull_request(worktree_path)
-                })
-                .await
-                .ok()
-                .unwrap_or_else(|| Err("failed to join open pull request task".to_owned()));
-                match result {
-                    Ok(()) => Ok(VecDeque::new()),
-                    Err(message) => {
-                        let _ = self.events.send(WsServerMessage::Event {
-                            rev: self.rev,
-                            event: Box::new(luban_api::ServerEvent::Toast {
-                                message: message.clone(),
-                            }),
-                        });
-                        Ok(VecDeque::from([Action::OpenWorkspacePullRequestFailed {
-                            message,
-                        }]))
-                    }
-                }
+            let previous = self
+                .get_conversation_snapshot(workspace_id, thread_id, Some(page_start), page_limit)
+                .await?;
+            if previous.entries.is_empty() {
+                break;
             }
-            Effect::OpenWorkspacePullRequestFailedAction { workspace_id } => {
-                let Some(workspace) = self.state.workspace(workspace_id) else {
-                    return Ok(VecDeque::new());
-                };
-                let worktree_path = workspace.worktree_path.clone();
-                let services = self.services.clone();
-                let result = tokio::task::spawn_blocking(move || {
-                    services.gh_open_pull_request_failed_action(worktree_path)
-                })
-                .await
-                .ok()
-                .unwrap_or_else(|| {
-                    Err("failed to join open pull request failed action task".to_owned())
-                });
-                match result {
-                    Ok(()) => Ok(VecDeque::new()),
-                    Err(message) => {
-                        let _ = self.events.send(WsServerMessage::Event {
-                            rev: self.rev,
-                            event: Box::new(luban_api::ServerEvent::Toast {
-                                message: message.clone(),
-                            }),
-                        });
-                        Ok(VecDeque::from([
-                            Action::OpenWorkspacePullRequestFailedActionFailed { message },
-                        ]))
-                    }
-                }
-            }
-            Effect::OpenWorkspaceInIde { workspace_id } => {
-                let Some(workspace) = self.state.workspace(workspace_id) else {
-                    return Ok(VecDeque::new());
-                };
-
-                let services = self.services.clone();
-                let worktree_path = workspace.worktree_path.clone();
-                let result = tokio::task::spawn_blocking(move || {
-                    services.open_workspace_in_ide(worktree_path)
-                })
-                .await
-                .ok()
-                .unwrap_or_else(|| Err("failed to join open workspace in ide task".to_owned()));
-
-                match result {
-                    Ok(()) => Ok(VecDeque::new()),
-                    Err(message) => {
-                        let _ = self.events.send(WsServerMessage::Event {
-                            rev: self.rev,
-                            event: Box::new(luban_api::ServerEvent::Toast {
-                                message: message.clone(),
-                            }),
-                        });
-                        Ok(VecDeque::from([Action::OpenWorkspaceInIdeFailed {
-                            message,
-                        }]))
-                    }
-                }
-            }
-            Effect::OpenWorkspaceWith {
-                workspace_id,
-                target,
-            } => {
-                let Some(workspace) = self.state.workspace(workspace_id) else {
-                    return Ok(VecDeque::new());
-                };
-
-                let services = self.services.clone();
-                let worktree_path = workspace.worktree_path.clone();
-                let result = tokio::task::spawn_blocking(move || {
-                    services.open_workspace_with(worktree_path, target)
-                })
-                .await
-                .ok()
-                .unwrap_or_else(|| Err("failed to join open workspace with task".to_owned()));
-
-                match result {
-                    Ok(()) => Ok(VecDeque::new()),
-                    Err(message) => {
-                        let _ = self.events.send(WsServerMessage::Event {
-                            rev: self.rev,
-                            event: Box::new(luban_api::ServerEvent::Toast {
-                                message: message.clone(),
-                            }),
-                        });
-                        Ok(VecDeque::from([Action::OpenWorkspaceWithFailed {
-                            message,
-                        }]))
-                    }
-                }
-            }
-            Effect::ArchiveWorkspace { workspace_id } => {
-                let scope = workspace_scope(&self.state, workspace_id);
-                let should_emit_task_archived_events =
-                    self.auto_archive_workspaces.contains(&workspace_id);
+            page_start = previous.entries_start;
+            page = previous.entries;
+        }
 
-                let mut claude_cleanup_threads = Vec::new();
-                let (project_slug, workspace_name) = scope
-                    .as_ref()
-                    .map(|s| (s.project_slug.clone(), s.workspace_name.clone()))
-                    .unwrap_or_default();
-                if !project_slug.is_empty() && !workspace_name.is_empty() {
-                    for (wid, thread_id) in self.state.conversations.keys() {
-                        if *wid != workspace_id {
-                            continue;
-                        }
-                        claude_cleanup_threads.push(thread_id.as_u64());
-                    }
-                }
+        while kept.len() > wanted {
+            kept.pop_front();
+        }
+        let entries_start = match kept.front() {
+            Some((index, _)) if kept.len() == wanted => *index,
+            _ => page_start,
+        };
+        snapshot.entries_start = entries_start;
+        snapshot.entries_truncated = entries_start > 0 || entries_end < snapshot.entries_total;
+        snapshot.entries = kept.into_iter().map(|(_, entry)| entry).collect();
+        Ok(snapshot)
+    }
 
-                let mut project_path: Option<PathBuf> = None;
-                let mut worktree_path: Option<PathBuf> = None;
-                let mut branch_name: Option<String> = None;
+    async fn get_conversation_snapshot(
+        &self,
+        workspace_id: luban_api::WorkspaceId,
+        thread_id: luban_api::WorkspaceThreadId,
+        before: Option<u64>,
+        limit: Option<u64>,
+    ) -> anyhow::Result<ConversationSnapshot> {
+        if let Ok(snapshot) = self.conversation_snapshot(workspace_id, thread_id, before, limit) {
+            return Ok(snapshot);
+        }
 
-                for project in &self.state.projects {
-                    for workspace in &project.workspaces {
-                        if workspace.id == workspace_id {
-                            project_path = Some(project.path.clone());
-                            worktree_path = Some(workspace.worktree_path.clone());
-                            branch_name = Some(workspace.branch_name.clone());
-                            break;
-                        }
-                    }
-                    if project_path.is_some() {
-                        break;
-                    }
-                }
+        let limit = self.conversation_entries_limit(limit);
 
-                let (Some(project_path), Some(worktree_path), Some(branch_name)) =
-                    (project_path, worktree_path, branch_name)
-                else {
-                    return Ok(VecDeque::from([Action::WorkspaceArchiveFailed {
-                        workspace_id,
-                        message: "workspace not found".to_owned(),
-                    }]));
-                };
+        let wid = WorkspaceId::from_u64(workspace_id.0);
+        let Some(scope) = workspace_scope(&self.state, wid) else {
+            return Err(anyhow::anyhow!("workspace not found"));
+        };
 
-                let services = self.services.clone();
-                let tx = self.tx.clone();
-                tokio::task::spawn_blocking(move || {
-                    for thread_id in claude_cleanup_threads {
-                        services.cleanup_claude_process(&project_slug, &workspace_name, thread_id);
-                    }
+        let services = self.services.clone();
+        let tid = thread_id.0;
+        let loaded = tokio::task::spawn_blocking(move || {
+            services.load_conversation_page(
+                scope.project_slug,
+                scope.workspace_name,
+                tid,
+                before,
+                limit as u64,
+            )
+        })
+        .await
+        .ok()
+        .unwrap_or_else(|| Err("failed to join load conversation task".to_owned()))
+        .map_err(|e| anyhow::anyhow!(e))?;
 
-                    let result: Result<(), String> = (|| {
-                        services.archive_workspace(project_path, worktree_path, branch_name)?;
-                        if !should_emit_task_archived_events {
-                            return Ok(());
-                        }
-                        if project_slug.is_empty() || workspace_name.is_empty() {
-                            return Ok(());
-                        }
+        let entries_total = loaded.entries_total;
+        let entries_start = loaded.entries_start;
+        let entries_end = entries_start.saturating_add(loaded.entries.len() as u64);
+        let entries_truncated = entries_start > 0 || entries_end < entries_total;
 
-                        let threads = services.list_conversation_threads(
-                            project_slug.clone(),
-                            workspace_name.clone(),
-                        )?;
-                        for meta in threads {
-                            if !matches!(
-                                meta.task_status,
-                                luban_domain::TaskStatus::Done | luban_domain::TaskStatus::Canceled
-                            ) {
-                                continue;
-                            }
+        let runner = loaded
+            .runner
+            .unwrap_or_else(|| self.state.agent_default_runner());
+        let model_id = loaded
+            .agent_model_id
+            .as_deref()
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .unwrap_or_else(|| self.state.agent_default_model_id())
+            .to_owned();
+        let thinking_effort = loaded
+            .thinking_effort
+            .unwrap_or_else(|| self.state.agent_default_thinking_effort());
+        let amp_mode = if runner == luban_domain::AgentRunnerKind::Amp {
+            loaded
+                .amp_mode
+                .as_deref()
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(ToOwned::to_owned)
+                .or_else(|| Some(self.state.agent_amp_mode().to_owned()))
+        } else {
+            None
+        };
+        let (claude_max_output_tokens, claude_system_prompt) =
+            if runner == luban_domain::AgentRunnerKind::Claude {
+                (
+                    loaded.claude_max_output_tokens,
+                    loaded.claude_system_prompt.clone(),
+                )
+            } else {
+                (None, None)
+            };
 
-                            let recent = services.load_conversation_page(
-                                project_slug.clone(),
-                                workspace_name.clone(),
-                                meta.thread_id.as_u64(),
-                                None,
-                                32,
-                            )?;
-                            let already_archived = recent.entries.iter().any(|entry| {
-                                matches!(
-                                    entry,
-                                    luban_domain::ConversationEntry::SystemEvent { event, .. }
-                                        if matches!(
-                                            event,
-                                            luban_domain::ConversationSystemEvent::TaskArchived
-                                        )
-                                )
-                            });
-                            if already_archived {
-                                continue;
-                            }
+        let title = self
+            .state
+            .workspace_thread_conversation(wid, WorkspaceThreadId::from_u64(tid))
+            .map(|c| c.title.clone())
+            .or_else(|| loaded.title.clone())
+            .unwrap_or_else(|| format!("Thread {tid}"));
+        let thread_key = (wid, WorkspaceThreadId::from_u64(tid));
+        let notes = if self.pending_notes_flushes.contains_key(&thread_key) {
+            self.state
+                .workspace_thread_conversation(thread_key.0, thread_key.1)
+                .map(|c| c.notes.clone())
+                .unwrap_or_default()
+        } else {
+            loaded.notes.clone()
+        };
+        let draft = if self.pending_draft_flushes.contains_key(&thread_key) {
+            self.state
+                .workspace_thread_conversation(thread_key.0, thread_key.1)
+                .map(|c| c.draft.clone())
+                .unwrap_or_default()
+        } else {
+            loaded.draft.clone()
+        };
+        let cwd_subpath = self
+            .state
+            .workspace_thread_conversation(wid, WorkspaceThreadId::from_u64(tid))
+            .map(|c| c.cwd_subpath.clone())
+            .unwrap_or_else(|| loaded.cwd_subpath.clone());
+        let hunk_comments = loaded.hunk_comments.iter().map(map_hunk_comment).collect();
+        let collapsed_ranges = self
+            .state
+            .workspace_thread_conversation(wid, WorkspaceThreadId::from_u64(tid))
+            .map(|c| c.collapsed_ranges.as_slice())
+            .unwrap_or(loaded.collapsed_ranges.as_slice())
+            .iter()
+            .map(map_collapsed_range)
+            .collect();
+        let patch_apply_failed_entry_id = self
+            .state
+            .workspace_thread_conversation(wid, WorkspaceThreadId::from_u64(tid))
+            .and_then(|c| c.patch_apply_failed_entry_id.clone());
+        let failed_command_ids = self
+            .state
+            .workspace_thread_conversation(wid, WorkspaceThreadId::from_u64(tid))
+            .map(|c| c.last_turn_failed_command_ids.clone())
+            .unwrap_or_else(|| luban_domain::failed_command_ids_in_last_turn(&loaded.entries));
+        let total_usage = self
+            .state
+            .workspace_thread_conversation(wid, WorkspaceThreadId::from_u64(tid))
+            .map(|c| c.total_usage.clone())
+            .unwrap_or_else(|| loaded.total_usage.clone());
+        let turn_status = self
+            .state
+            .workspace_thread_conversation(wid, WorkspaceThreadId::from_u64(tid))
+            .map(|c| c.turn_status())
+            .unwrap_or_else(|| loaded.turn_status());
 
-                            services.append_conversation_entries(
-                                project_slug.clone(),
-                                workspace_name.clone(),
-                                meta.thread_id.as_u64(),
-                                vec![luban_domain::ConversationEntry::SystemEvent {
-                                    entry_id: String::new(),
-                                    created_at_unix_ms: now_unix_ms(),
-                                    event: luban_domain::ConversationSystemEvent::TaskArchived,
-                                }],
-                            )?;
-                        }
-                        Ok(())
-                    })();
-
-                    let action = match result {
-                        Ok(()) => Action::WorkspaceArchived { workspace_id },
-                        Err(message) => Action::WorkspaceArchiveFailed {
-                            workspace_id,
-                            message,
+        Ok(ConversationSnapshot {
+            rev: self.rev,
+            workspace_id,
+            thread_id,
+            task_status: match loaded.task_status {
+                luban_domain::TaskStatus::Backlog => luban_api::TaskStatus::Backlog,
+                luban_domain::TaskStatus::Todo => luban_api::TaskStatus::Todo,
+                luban_domain::TaskStatus::Iterating => luban_api::TaskStatus::Iterating,
+                luban_domain::TaskStatus::Validating => luban_api::TaskStatus::Validating,
+                luban_domain::TaskStatus::Done => luban_api::TaskStatus::Done,
+                luban_domain::TaskStatus::Canceled => luban_api::TaskStatus::Canceled,
+            },
+            agent_runner: match runner {
+                luban_domain::AgentRunnerKind::Codex => luban_api::AgentRunnerKind::Codex,
+                luban_domain::AgentRunnerKind::Amp => luban_api::AgentRunnerKind::Amp,
+                luban_domain::AgentRunnerKind::Claude => luban_api::AgentRunnerKind::Claude,
+                luban_domain::AgentRunnerKind::Droid => luban_api::AgentRunnerKind::Droid,
+            },
+            agent_model_id: model_id.clone(),
+            thinking_effort: match thinking_effort {
+                ThinkingEffort::Minimal => luban_api::ThinkingEffort::Minimal,
+                ThinkingEffort::Low => luban_api::ThinkingEffort::Low,
+                ThinkingEffort::Medium => luban_api::ThinkingEffort::Medium,
+                ThinkingEffort::High => luban_api::ThinkingEffort::High,
+                ThinkingEffort::XHigh => luban_api::ThinkingEffort::XHigh,
+            },
+            run_thinking_effort: None,
+            amp_mode: amp_mode.map(luban_api::AmpMode::from),
+            claude_max_output_tokens,
+            claude_system_prompt,
+            auto_commit: loaded.auto_commit,
+            run_status: luban_api::OperationStatus::Idle,
+            turn_status: map_domain_turn_status(turn_status),
+            run_started_at_unix_ms: loaded.run_started_at_unix_ms,
+            run_finished_at_unix_ms: loaded.run_finished_at_unix_ms,
+            entries: loaded.entries.iter().map(map_conversation_entry).collect(),
+            entries_total,
+            entries_start,
+            entries_truncated,
+            pending_prompts: loaded
+                .pending_prompts
+                .iter()
+                .map(|prompt| luban_api::QueuedPromptSnapshot {
+                    id: prompt.id,
+                    text: prompt.text.clone(),
+                    attachments: prompt.attachments.iter().map(map_attachment_ref).collect(),
+                    run_config: luban_api::AgentRunConfigSnapshot {
+                        runner: match prompt.run_config.runner {
+                            luban_domain::AgentRunnerKind::Codex => {
+                                luban_api::AgentRunnerKind::Codex
+                            }
+                            luban_domain::AgentRunnerKind::Amp => luban_api::AgentRunnerKind::Amp,
+                            luban_domain::AgentRunnerKind::Claude => {
+                                luban_api::AgentRunnerKind::Claude
+                            }
+                            luban_domain::AgentRunnerKind::Droid => {
+                                luban_api::AgentRunnerKind::Droid
+                            }
                         },
-                    };
-                    let _ = tx.blocking_send(EngineCommand::DispatchAction {
-                        action: Box::new(action),
-                    });
-                });
+                        model_id: prompt.run_config.model_id.clone(),
+                        thinking_effort: match prompt.run_config.thinking_effort {
+                            ThinkingEffort::Minimal => luban_api::ThinkingEffort::Minimal,
+                            ThinkingEffort::Low => luban_api::ThinkingEffort::Low,
+                            ThinkingEffort::Medium => luban_api::ThinkingEffort::Medium,
+                            ThinkingEffort::High => luban_api::ThinkingEffort::High,
+                            ThinkingEffort::XHigh => luban_api::ThinkingEffort::XHigh,
+                        },
+                        amp_mode: prompt.run_config.amp_mode.clone(),
+                        claude_max_output_tokens: prompt.run_config.claude_max_output_tokens,
+                        claude_system_prompt: prompt.run_config.claude_system_prompt.clone(),
+                    },
+                })
+                .collect(),
+            queue_paused: loaded.queue_paused,
+            remote_thread_id: loaded.thread_id,
+            title,
+            notes,
+            cwd_subpath,
+            hunk_comments,
+            collapsed_ranges,
+            draft,
+            patch_apply_failed_entry_id,
+            had_failed_commands: !failed_command_ids.is_empty(),
+            failed_command_ids,
+            worktree_dirty: self.worktree_dirty_for(wid),
+            total_usage: map_token_usage(&total_usage),
+        })
+    }
 
-                Ok(VecDeque::new())
+    async fn process_action_queue(&mut self, initial: Action) {
+        let mut actions = VecDeque::from([initial]);
+        let mut effects = VecDeque::<Effect>::new();
+
+        while let Some(action) = actions.pop_front() {
+            self.rev = self.rev.saturating_add(1);
+
+            let should_persist_latest_conversation_entry = matches!(
+                &action,
+                Action::TerminalCommandStarted { .. }
+                    | Action::TerminalCommandFinished { .. }
+                    | Action::TaskStatusSuggestionCreated { .. }
+            );
+            let system_events_since = system_events_key_for_action(&action).map(|(wid, tid)| {
+                let entries_total = self
+                    .state
+                    .workspace_thread_conversation(wid, tid)
+                    .map(|c| c.entries_total)
+                    .unwrap_or(0);
+                (wid, tid, entries_total)
+            });
+            let should_sync_branch_watchers = should_sync_branch_watchers(&action);
+            let mut conversation_keys = Vec::<(WorkspaceId, WorkspaceThreadId)>::new();
+            let action_conversation_key = conversation_key_for_action(&action);
+            if let Some(key) = action_conversation_key {
+                conversation_keys.push(key);
             }
-            Effect::MaybeAutoArchiveWorkspace { workspace_id } => {
-                let Some(scope) = workspace_scope(&self.state, workspace_id) else {
-                    return Ok(VecDeque::new());
-                };
+            let queue_state_key = queue_state_key_for_action(&action);
+            let threads_event = threads_event_for_action(&action);
+            let task_summaries_workspace_id = task_summaries_workspace_id_for_action(&action);
+            let read_workspace_ids = workspaces_marked_read_by_action(&self.state, &action);
+            let agent_item_key = agent_item_key_for_action(&action);
+            let worktree_dirty_refresh = worktree_dirty_refresh_for_action(&action);
+            let last_commit_refresh = last_commit_refresh_for_action(&action);
+            let finished_turn = finished_agent_turn_for_action(&action);
+            let cache_prune = conversation_cache_prune_for_action(&action);
 
-                let mut project_is_git = false;
-                let mut workspace_is_main = false;
-                let mut workspace_status = None;
-                let mut archive_status = None;
-                for project in &self.state.projects {
-                    for workspace in &project.workspaces {
-                        if workspace.id != workspace_id {
-                            continue;
-                        }
-                        project_is_git = project.is_git;
-                        workspace_is_main = workspace.workspace_name == "main";
-                        workspace_status = Some(workspace.status);
-                        archive_status = Some(workspace.archive_status);
-                        break;
+            let new_effects = self.state.apply(action);
+            let follow = agent_item_key.and_then(|(wid, tid)| {
+                self.state
+                    .workspace_thread_conversation(wid, tid)
+                    .map(|c| (wid, tid, c.follow_latest_item))
+            });
+            conversation_keys.extend(conversation_keys_for_effects(&new_effects));
+            if should_sync_branch_watchers {
+                self.sync_branch_watchers();
+            }
+            self.publish_app_snapshot();
+
+            if !conversation_keys.is_empty() {
+                let mut seen = HashSet::<(u64, u64)>::new();
+                for (wid, tid) in conversation_keys {
+                    if !seen.insert((wid.as_u64(), tid.as_u64())) {
+                        continue;
                     }
+                    self.conversation_revs.insert((wid, tid), self.rev);
+                    let follow = follow
+                        .filter(|(fwid, ftid, _)| (*fwid, *ftid) == (wid, tid))
+                        .map(|(_, _, follow)| follow);
+                    self.publish_conversation_snapshot(wid, tid, follow).await;
                 }
+            }
+            if let Some(prune) = cache_prune {
+                self.prune_conversation_caches(prune);
+            }
+            if let Some((wid, mut threads)) = threads_event {
+                self.publish_threads_event(wid, &threads);
+                dedup_thread_metas_in_place(&mut threads);
+                self.workspace_threads_cache.insert(wid, threads);
+            }
+            if let Some(wid) = task_summaries_workspace_id {
+                self.publish_task_summaries_event(wid);
+            }
+            for wid in read_workspace_ids {
+                self.publish_task_summaries_event(wid);
+            }
+            if let Some((wid, force)) = worktree_dirty_refresh {
+                self.maybe_refresh_worktree_dirty(wid, force);
+            }
+            if let Some(wid) = last_commit_refresh {
+                self.refresh_last_commit(wid);
+            }
+            if let Some((wid, tid)) = queue_state_key {
+                self.persist_queue_state(wid, tid).await;
+            }
+            if should_persist_latest_conversation_entry
+                && let Some((wid, tid)) = action_conversation_key
+            {
+                self.persist_latest_conversation_entry(wid, tid).await;
+            }
+            if let Some((wid, tid, entries_total)) = system_events_since {
+                self.persist_system_events_since(wid, tid, entries_total)
+                    .await;
+            }
 
-                if !project_is_git
-                    || workspace_is_main
-                    || workspace_status != Some(luban_domain::WorkspaceStatus::Active)
-                    || archive_status == Some(luban_domain::OperationStatus::Running)
-                {
-                    return Ok(VecDeque::new());
-                }
+            if let Some((wid, tid, run_id)) = finished_turn
+                && self.agent_turn_slots.get(&(wid, tid)) == Some(&run_id)
+            {
+                self.agent_turn_slots.remove(&(wid, tid));
+            }
 
-                let services = self.services.clone();
-                let project_slug = scope.project_slug.clone();
-                let workspace_name = scope.workspace_name.clone();
-                let result = tokio::task::spawn_blocking(move || {
-                    let threads =
-                        services.list_conversation_threads(project_slug, workspace_name)?;
-                    if threads.is_empty() {
-                        return Ok(false);
-                    }
-                    let all_closed_and_idle = threads.iter().all(|t| {
-                        matches!(
-                            t.task_status,
-                            luban_domain::TaskStatus::Done | luban_domain::TaskStatus::Canceled
-                        ) && t.turn_status == luban_domain::TurnStatus::Idle
-                    });
-                    Ok(all_closed_and_idle)
-                })
-                .await
-                .ok()
-                .unwrap_or_else(|| Err("failed to join maybe archive workspace task".to_owned()));
+            effects.extend(new_effects);
 
-                let Ok(should_archive) = result else {
-                    return Ok(VecDeque::new());
-                };
-                if !should_archive {
-                    return Ok(VecDeque::new());
+            loop {
+                while let Some(effect) = effects.pop_front() {
+                    match self.run_effect(effect).await {
+                        Ok(mut followups) => actions.append(&mut followups),
+                        Err(err) => {
+                            tracing::error!(error = %err, "effect failed");
+                        }
+                    }
+                }
+                effects.extend(self.reconcile_agent_turn_slots());
+                if effects.is_empty() {
+                    break;
                 }
-
-                self.auto_archive_workspaces.insert(workspace_id);
-                Ok(VecDeque::from([Action::ArchiveWorkspace { workspace_id }]))
             }
         }
     }
 
-    fn publish_app_snapshot(&self) {
-        let _ = self.events.send(WsServerMessage::Event {
-            rev: self.rev,
-            event: Box::new(luban_api::ServerEvent::AppChanged {
-                rev: self.rev,
-                snapshot: Box::new(self.app_snapshot()),
-            }),
-        });
+    fn agent_turn_slots_full(&self) -> bool {
+        let limit = self.config.max_concurrent_turns;
+        limit > 0 && self.agent_turn_slots.len() >= limit
     }
 
-    fn publish_threads_event(
+    fn agent_turn_is_deferred(
         &self,
         workspace_id: WorkspaceId,
-        threads: &[luban_domain::ConversationThreadMeta],
-    ) {
-        let api_id = luban_api::WorkspaceId(workspace_id.as_u64());
-        let tabs = self
-            .state
-            .workspace_tabs(workspace_id)
-            .map(map_workspace_tabs_snapshot)
-            .unwrap_or_default();
-        let mut seen_thread_ids = HashSet::<WorkspaceThreadId>::new();
-        let threads = threads
+        thread_id: WorkspaceThreadId,
+    ) -> bool {
+        self.deferred_agent_turns
             .iter()
-            .filter(|t| seen_thread_ids.insert(t.thread_id))
-            .map(|t| luban_api::ThreadMeta {
-                thread_id: luban_api::WorkspaceThreadId(t.thread_id.as_u64()),
-                remote_thread_id: t.remote_thread_id.clone(),
-                title: t.title.clone(),
-                created_at_unix_seconds: t.created_at_unix_seconds,
-                updated_at_unix_seconds: t.updated_at_unix_seconds,
-                task_status: match t.task_status {
-                    luban_domain::TaskStatus::Backlog => luban_api::TaskStatus::Backlog,
-                    luban_domain::TaskStatus::Todo => luban_api::TaskStatus::Todo,
-                    luban_domain::TaskStatus::Iterating => luban_api::TaskStatus::Iterating,
-                    luban_domain::TaskStatus::Validating => luban_api::TaskStatus::Validating,
-                    luban_domain::TaskStatus::Done => luban_api::TaskStatus::Done,
-                    luban_domain::TaskStatus::Canceled => luban_api::TaskStatus::Canceled,
-                },
-                turn_status: match t.turn_status {
-                    luban_domain::TurnStatus::Idle => luban_api::TurnStatus::Idle,
-                    luban_domain::TurnStatus::Running => luban_api::TurnStatus::Running,
-                    luban_domain::TurnStatus::Awaiting => luban_api::TurnStatus::Awaiting,
-                    luban_domain::TurnStatus::Paused => luban_api::TurnStatus::Paused,
-                },
-                last_turn_result: t.last_turn_result.map(|v| match v {
-                    luban_domain::TurnResult::Completed => luban_api::TurnResult::Completed,
-                    luban_domain::TurnResult::Failed => luban_api::TurnResult::Failed,
-                }),
-            })
-            .collect::<Vec<_>>();
+            .any(|t| t.workspace_id == workspace_id && t.thread_id == thread_id)
+    }
 
-        let _ = self.events.send(WsServerMessage::Event {
-            rev: self.rev,
-            event: Box::new(luban_api::ServerEvent::WorkspaceThreadsChanged {
-                workspace_id: api_id,
-                tabs,
-                threads,
-            }),
-        });
+    /// Frees slots held by runs the reducer no longer considers active, drops waiting turns that
+    /// were cancelled, and returns the waiting turns that can start now, oldest first.
+    fn reconcile_agent_turn_slots(&mut self) -> Vec<Effect> {
+        let state = &self.state;
+        let is_active = |workspace_id, thread_id, run_id| {
+            state
+                .workspace_thread_conversation(workspace_id, thread_id)
+                .is_some_and(|c| {
+                    c.run_status == OperationStatus::Running && c.active_run_id == Some(run_id)
+                })
+        };
+        self.agent_turn_slots
+            .retain(|(wid, tid), run_id| is_active(*wid, *tid, *run_id));
+        self.deferred_agent_turns
+            .retain(|t| is_active(t.workspace_id, t.thread_id, t.run_id));
+
+        let limit = self.config.max_concurrent_turns;
+        let free = if limit == 0 {
+            self.deferred_agent_turns.len()
+        } else {
+            limit.saturating_sub(self.agent_turn_slots.len())
+        };
+        let count = free.min(self.deferred_agent_turns.len());
+        self.deferred_agent_turns
+            .drain(..count)
+            .map(|t| t.effect)
+            .collect()
     }
 
-    fn publish_task_summaries_event(&self, workspace_id: WorkspaceId) {
-        let Some((project_id, workspace)) = self.state.projects.iter().find_map(|project| {
-            project
-                .workspaces
-                .iter()
-                .find(|w| w.id == workspace_id)
-                .map(|workspace| {
-                    (
-                        luban_api::ProjectId(project.path.to_string_lossy().to_string()),
-                        workspace,
-                    )
-                })
-        }) else {
+    async fn persist_latest_conversation_entry(
+        &self,
+        workspace_id: WorkspaceId,
+        thread_id: WorkspaceThreadId,
+    ) {
+        let Some(scope) = workspace_scope(&self.state, workspace_id) else {
             return;
         };
-
-        let Some(threads) = self.workspace_threads_cache.get(&workspace_id) else {
+        let Some(conversation) = self
+            .state
+            .workspace_thread_conversation(workspace_id, thread_id)
+        else {
+            return;
+        };
+        let Some(entry) = conversation.entries.last() else {
             return;
         };
+        let entry = entry.clone();
+        self.persist_conversation_entries(scope, thread_id, vec![entry])
+            .await;
+    }
 
-        let active_thread_id = self
+    /// Persist system events appended by the reducer after `entries_total` was observed.
+    ///
+    /// User and agent entries are persisted by the agent runner; system events produced while
+    /// starting a turn (for example runner retries) have no other writer.
+    async fn persist_system_events_since(
+        &self,
+        workspace_id: WorkspaceId,
+        thread_id: WorkspaceThreadId,
+        entries_total: u64,
+    ) {
+        let Some(scope) = workspace_scope(&self.state, workspace_id) else {
+            return;
+        };
+        let Some(conversation) = self
             .state
-            .workspace_tabs
-            .get(&workspace_id)
-            .map(|tabs| tabs.active_tab)
-            .unwrap_or(WorkspaceThreadId::from_u64(1));
+            .workspace_thread_conversation(workspace_id, thread_id)
+        else {
+            return;
+        };
+        let skip = entries_total.saturating_sub(conversation.entries_start) as usize;
+        let entries = conversation
+            .entries
+            .iter()
+            .skip(skip)
+            .filter(|entry| matches!(entry, ConversationEntry::SystemEvent { .. }))
+            .cloned()
+            .collect::<Vec<_>>();
+        if entries.is_empty() {
+            return;
+        }
+        self.persist_conversation_entries(scope, thread_id, entries)
+            .await;
+    }
 
-        let workspace_has_running_turn = self.state.workspace_has_running_turn(workspace_id);
-        let workspace_has_unread_completion =
-            self.state.workspace_has_unread_completion(workspace_id);
+    async fn persist_conversation_entries(
+        &self,
+        scope: WorkspaceScope,
+        thread_id: WorkspaceThreadId,
+        entries: Vec<ConversationEntry>,
+    ) {
+        let services = self.services.clone();
+        let project_slug = scope.project_slug;
+        let workspace_name = scope.workspace_name;
+        let thread_local_id = thread_id.as_u64();
+        let result = tokio::task::spawn_blocking(move || {
+            services.append_conversation_entries(
+                project_slug,
+                workspace_name,
+                thread_local_id,
+                entries,
+            )
+        })
+        .await;
 
-        let tasks = threads
+        match result {
+            Ok(Ok(())) => {}
+            Ok(Err(message)) => {
+                tracing::error!(message = %message, "failed to persist conversation entry");
+            }
+            Err(err) => {
+                tracing::error!(error = %err, "failed to join conversation persistence task");
+            }
+        }
+    }
+
+    fn sync_branch_watchers(&self) {
+        let workspaces = self
+            .state
+            .projects
             .iter()
-            .map(|t| luban_api::TaskSummarySnapshot {
-                project_id: project_id.clone(),
-                workspace_id: luban_api::WorkspaceId(workspace_id.as_u64()),
-                thread_id: luban_api::WorkspaceThreadId(t.thread_id.as_u64()),
-                title: t.title.clone(),
-                created_at_unix_seconds: t.created_at_unix_seconds,
-                updated_at_unix_seconds: t.updated_at_unix_seconds,
-                branch_name: workspace.branch_name.clone(),
-                workspace_name: workspace.workspace_name.clone(),
-                agent_run_status: if workspace_has_running_turn && t.thread_id == active_thread_id {
-                    luban_api::OperationStatus::Running
-                } else {
-                    luban_api::OperationStatus::Idle
-                },
-                has_unread_completion: workspace_has_unread_completion
-                    && t.thread_id == active_thread_id,
-                task_status: map_domain_task_status(t.task_status),
-                turn_status: map_domain_turn_status(t.turn_status),
-                last_turn_result: t.last_turn_result.map(map_domain_turn_result),
-                is_starred: self
-                    .state
-                    .starred_tasks
-                    .contains(&(workspace_id, t.thread_id)),
+            .filter(|p| p.is_git && p.status == ProjectStatus::Active)
+            .flat_map(|p| {
+                p.workspaces.iter().filter_map(|w| {
+                    if w.status != luban_domain::WorkspaceStatus::Active {
+                        return None;
+                    }
+                    Some((w.id, w.worktree_path.clone()))
+                })
             })
             .collect::<Vec<_>>();
+        for (workspace_id, _) in &workspaces {
+            if !self.last_commits.contains_key(workspace_id) {
+                self.refresh_last_commit(*workspace_id);
+            }
+        }
+        self.branch_watch.sync_workspaces(workspaces);
+    }
 
-        let _ = self.events.send(WsServerMessage::Event {
-            rev: self.rev,
-            event: Box::new(luban_api::ServerEvent::TaskSummariesChanged {
-                project_id,
-                workspace_id: luban_api::WorkspaceId(workspace_id.as_u64()),
-                tasks,
-            }),
+    fn refresh_last_commit(&self, workspace_id: WorkspaceId) {
+        let Some(workspace) = self.state.workspace(workspace_id) else {
+            return;
+        };
+        let services = self.services.clone();
+        let tx = self.tx.clone();
+        let worktree_path = workspace.worktree_path.clone();
+        std::thread::spawn(move || {
+            let last_commit = match services.git_last_commit(worktree_path) {
+                Ok(last_commit) => last_commit,
+                Err(err) => {
+                    tracing::debug!(
+                        workspace_id = workspace_id.as_u64(),
+                        error = %err,
+                        "failed to read last commit"
+                    );
+                    None
+                }
+            };
+            let _ = tx.blocking_send(EngineCommand::LastCommitUpdated {
+                workspace_id,
+                last_commit,
+            });
         });
     }
 
-    fn publish_conversation_snapshot(
-        &self,
-        workspace_id: WorkspaceId,
-        thread_id: WorkspaceThreadId,
-    ) {
-        let api_wid = luban_api::WorkspaceId(workspace_id.as_u64());
-        let api_tid = luban_api::WorkspaceThreadId(thread_id.as_u64());
-        if let Ok(snapshot) = self.conversation_snapshot(api_wid, api_tid, None, None) {
-            let _ = self.events.send(WsServerMessage::Event {
-                rev: self.rev,
-                event: Box::new(luban_api::ServerEvent::ConversationChanged {
-                    snapshot: Box::new(snapshot),
-                }),
-            });
+    async fn persist_queue_state(&self, workspace_id: WorkspaceId, thread_id: WorkspaceThreadId) {
+        let Some(scope) = workspace_scope(&self.state, workspace_id) else {
+            return;
+        };
+        let Some(conversation) = self
+            .state
+            .workspace_thread_conversation(workspace_id, thread_id)
+        else {
+            return;
+        };
+
+        let queue_paused = conversation.queue_paused;
+        let run_started_at_unix_ms = conversation.run_started_at_unix_ms;
+        let run_finished_at_unix_ms = conversation.run_finished_at_unix_ms;
+        let pending_prompts = conversation
+            .pending_prompts
+            .iter()
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let services = self.services.clone();
+        let project_slug = scope.project_slug;
+        let workspace_name = scope.workspace_name;
+        let thread_local_id = thread_id.as_u64();
+        let result = tokio::task::spawn_blocking(move || {
+            services.save_conversation_queue_state(
+                project_slug,
+                workspace_name,
+                thread_local_id,
+                queue_paused,
+                run_started_at_unix_ms,
+                run_finished_at_unix_ms,
+                pending_prompts,
+            )
+        })
+        .await;
+
+        match result {
+            Ok(Ok(())) => {}
+            Ok(Err(message)) => {
+                tracing::error!(message = %message, "failed to persist queued prompts");
+            }
+            Err(err) => {
+                tracing::error!(error = %err, "failed to join queued prompt persistence task");
+            }
         }
     }
 
-    fn app_snapshot(&self) -> AppSnapshot {
-        let mut running_workspaces = std::collections::HashSet::<WorkspaceId>::new();
-        for ((workspace_id, _), conversation) in &self.state.conversations {
-            if conversation.run_status == OperationStatus::Running {
-                running_workspaces.insert(*workspace_id);
-            }
+    fn refresh_pull_requests_for_all_workspaces(&mut self) {
+        let now = Instant::now();
+        let workspace_ids = self
+            .state
+            .projects
+            .iter()
+            .filter(|project| project.status == ProjectStatus::Active)
+            .flat_map(|project| {
+                project.workspaces.iter().filter_map(|workspace| {
+                    if workspace.status != luban_domain::WorkspaceStatus::Active {
+                        return None;
+                    }
+                    Some(workspace.id)
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut candidates = workspace_ids
+            .into_iter()
+            .filter(|workspace_id| self.should_start_pull_request_refresh(*workspace_id, now))
+            .collect::<Vec<_>>();
+
+        candidates.sort_by_key(|workspace_id| {
+            self.pull_requests
+                .get(workspace_id)
+                .map(|e| e.next_refresh_at)
+                .unwrap_or(now)
+        });
+
+        for workspace_id in candidates
+            .into_iter()
+            .take(PULL_REQUEST_REFRESH_MAX_PER_TICK)
+        {
+            self.start_pull_request_refresh(workspace_id);
         }
+    }
 
-        AppSnapshot {
-            rev: self.rev,
-            projects: self
-                .state
-                .projects
-                .iter()
-                .map(|p| {
-                    let path = p.path.to_string_lossy().to_string();
-                    luban_api::ProjectSnapshot {
-                        id: luban_api::ProjectId(path.clone()),
-                        name: p.name.clone(),
-                        slug: p.slug.clone(),
-                        path,
-                        is_git: p.is_git,
-                        expanded: p.expanded,
-                        create_workspace_status: match p.create_workspace_status {
-                            OperationStatus::Idle => luban_api::OperationStatus::Idle,
-                            OperationStatus::Running => luban_api::OperationStatus::Running,
-                        },
-                        workspaces: p
-                            .workspaces
-                            .iter()
-                            .map(|w| luban_api::WorkspaceSnapshot {
-                                id: luban_api::WorkspaceId(w.id.as_u64()),
-                                short_id: workspace_short_id(&p.slug, w.id.as_u64()),
-                                workspace_name: w.workspace_name.clone(),
-                                branch_name: w.branch_name.clone(),
-                                worktree_path: w.worktree_path.to_string_lossy().to_string(),
-                                status: match w.status {
-                                    luban_domain::WorkspaceStatus::Active => {
-                                        luban_api::WorkspaceStatus::Active
-                                    }
-                                    luban_domain::WorkspaceStatus::Archived => {
-                                        luban_api::WorkspaceStatus::Archived
-                                    }
-                                },
-                                archive_status: match w.archive_status {
-                                    OperationStatus::Idle => luban_api::OperationStatus::Idle,
-                                    OperationStatus::Running => luban_api::OperationStatus::Running,
-                                },
-                                branch_rename_status: match w.branch_rename_status {
-                                    OperationStatus::Idle => luban_api::OperationStatus::Idle,
-                                    OperationStatus::Running => luban_api::OperationStatus::Running,
-                                },
-                                agent_run_status: if running_workspaces.contains(&w.id) {
-                                    luban_api::OperationStatus::Running
-                                } else {
-                                    luban_api::OperationStatus::Idle
-                                },
-                                has_unread_completion: self
-                                    .state
-                                    .workspace_unread_completions
-                                    .contains(&w.id),
-                                pull_request: self
-                                    .pull_requests
-                                    .get(&w.id)
-                                    .and_then(|entry| entry.info)
-                                    .map(map_pull_request_info),
-                            })
-                            .collect(),
-                    }
-                })
-                .collect(),
-            appearance: luban_api::AppearanceSnapshot {
-                theme: match self.state.appearance_theme {
-                    luban_domain::AppearanceTheme::Light => luban_api::AppearanceTheme::Light,
-                    luban_domain::AppearanceTheme::Dark => luban_api::AppearanceTheme::Dark,
-                    luban_domain::AppearanceTheme::System => luban_api::AppearanceTheme::System,
-                },
-                fonts: luban_api::AppearanceFontsSnapshot {
-                    ui_font: self.state.appearance_fonts.ui_font.clone(),
-                    chat_font: self.state.appearance_fonts.chat_font.clone(),
-                    code_font: self.state.appearance_fonts.code_font.clone(),
-                    terminal_font: self.state.appearance_fonts.terminal_font.clone(),
-                },
-                global_zoom: (self.state.global_zoom_percent as f64) / 100.0,
-            },
-            agent: luban_api::AgentSettingsSnapshot {
-                codex_enabled: self.state.agent_codex_enabled(),
-                amp_enabled: self.state.agent_amp_enabled(),
-                claude_enabled: self.state.agent_claude_enabled(),
-                droid_enabled: self.state.agent_droid_enabled(),
-                default_model_id: Some(self.state.agent_default_model_id().to_owned()),
-                runner_default_models: self
-                    .state
-                    .agent_runner_default_models()
-                    .iter()
-                    .map(|(k, v)| (k.as_str().to_owned(), v.clone()))
-                    .collect(),
-                default_thinking_effort: Some(match self.state.agent_default_thinking_effort() {
-                    ThinkingEffort::Minimal => luban_api::ThinkingEffort::Minimal,
-                    ThinkingEffort::Low => luban_api::ThinkingEffort::Low,
-                    ThinkingEffort::Medium => luban_api::ThinkingEffort::Medium,
-                    ThinkingEffort::High => luban_api::ThinkingEffort::High,
-                    ThinkingEffort::XHigh => luban_api::ThinkingEffort::XHigh,
-                }),
-                default_runner: Some(match self.state.agent_default_runner() {
-                    luban_domain::AgentRunnerKind::Codex => luban_api::AgentRunnerKind::Codex,
-                    luban_domain::AgentRunnerKind::Amp => luban_api::AgentRunnerKind::Amp,
-                    luban_domain::AgentRunnerKind::Claude => luban_api::AgentRunnerKind::Claude,
-                    luban_domain::AgentRunnerKind::Droid => luban_api::AgentRunnerKind::Droid,
-                }),
-                amp_mode: Some(self.state.agent_amp_mode().to_owned()),
-            },
-            task: luban_api::TaskSettingsSnapshot {
-                prompt_templates: luban_domain::TaskIntentKind::ALL
-                    .iter()
-                    .copied()
-                    .filter_map(|kind| {
-                        self.state.task_prompt_templates.get(&kind).map(|template| {
-                            luban_api::TaskPromptTemplateSnapshot {
-                                intent_kind: map_task_intent_kind(kind),
-                                template: template.clone(),
-                            }
-                        })
-                    })
-                    .collect(),
-                default_prompt_templates: luban_domain::TaskIntentKind::ALL
-                    .iter()
-                    .copied()
-                    .map(|kind| luban_api::TaskPromptTemplateSnapshot {
-                        intent_kind: map_task_intent_kind(kind),
-                        template: luban_domain::default_task_prompt_template(kind),
-                    })
-                    .collect(),
-                system_prompt_templates: luban_domain::SystemTaskKind::ALL
-                    .iter()
-                    .copied()
-                    .filter_map(|kind| {
-                        self.state
-                            .system_prompt_templates
-                            .get(&kind)
-                            .map(|template| luban_api::SystemPromptTemplateSnapshot {
-                                kind: map_system_task_kind(kind),
-                                template: template.clone(),
-                            })
-                    })
-                    .collect(),
-                default_system_prompt_templates: luban_domain::SystemTaskKind::ALL
-                    .iter()
-                    .copied()
-                    .map(|kind| luban_api::SystemPromptTemplateSnapshot {
-                        kind: map_system_task_kind(kind),
-                        template: luban_domain::default_system_prompt_template(kind),
-                    })
-                    .collect(),
-            },
-            ui: {
-                let active_workspace_id = match self.state.main_pane {
-                    luban_domain::MainPane::Workspace(id) => Some(id),
-                    _ => self.state.last_open_workspace_id,
-                };
-                let active_thread_id =
-                    active_workspace_id.and_then(|id| self.state.active_thread_id(id));
-                luban_api::UiSnapshot {
-                    active_workspace_id: active_workspace_id
-                        .map(|id| luban_api::WorkspaceId(id.as_u64())),
-                    active_thread_id: active_thread_id
-                        .map(|id| luban_api::WorkspaceThreadId(id.as_u64())),
-                    open_button_selection: self.state.open_button_selection.clone(),
-                    sidebar_project_order: self
-                        .state
-                        .sidebar_project_order
-                        .iter()
-                        .cloned()
-                        .map(luban_api::ProjectId)
-                        .collect(),
-                }
-            },
-            integrations: luban_api::IntegrationsSnapshot {
-                telegram: luban_api::TelegramIntegrationSnapshot {
-                    enabled: self.state.telegram_enabled(),
-                    has_token: self.state.telegram_bot_token().is_some(),
-                    bot_username: self.state.telegram_bot_username().map(ToOwned::to_owned),
-                    paired_chat_id: self.state.telegram_paired_chat_id(),
-                    config_rev: self.state.telegram_config_rev(),
-                    last_error: self.state.telegram_last_error().map(ToOwned::to_owned),
-                },
-            },
+    fn maybe_refresh_pull_request(&mut self, workspace_id: WorkspaceId) {
+        let now = Instant::now();
+        if !self.should_start_pull_request_refresh(workspace_id, now) {
+            return;
         }
+        self.start_pull_request_refresh(workspace_id);
     }
 
-    // Threads snapshots are served via `ProjectWorkspaceService::list_conversation_threads` in the command handler.
-
-    fn conversation_snapshot(
-        &self,
-        workspace_id: luban_api::WorkspaceId,
-        thread_id: luban_api::WorkspaceThreadId,
-        before: Option<u64>,
-        limit: Option<u64>,
-    ) -> anyhow::Result<ConversationSnapshot> {
-        const DEFAULT_ENTRIES_LIMIT: usize = 2000;
-        const MAX_ENTRIES_LIMIT: usize = 5000;
-
-        let limit = limit
-            .and_then(|v| usize::try_from(v).ok())
-            .unwrap_or(DEFAULT_ENTRIES_LIMIT)
-            .clamp(1, MAX_ENTRIES_LIMIT);
+    fn should_start_pull_request_refresh(&self, workspace_id: WorkspaceId, now: Instant) -> bool {
+        if self.pull_requests_in_flight.contains(&workspace_id) {
+            return false;
+        }
+        let Some(project) = self
+            .state
+            .projects
+            .iter()
+            .find(|p| p.workspaces.iter().any(|w| w.id == workspace_id))
+        else {
+            return false;
+        };
+        if project.status == ProjectStatus::Archived {
+            return false;
+        }
+        if let Some(entry) = self.pull_requests.get(&workspace_id) {
+            return now >= entry.next_refresh_at;
+        }
+        true
+    }
 
-        let wid = WorkspaceId::from_u64(workspace_id.0);
-        let tid = WorkspaceThreadId::from_u64(thread_id.0);
-        let Some(conversation) = self.state.workspace_thread_conversation(wid, tid) else {
-            return Err(anyhow::anyhow!("conversation not found"));
+    fn start_pull_request_refresh(&mut self, workspace_id: WorkspaceId) {
+        let Some(workspace) = self.state.workspace(workspace_id) else {
+            return;
         };
 
-        let window_start = usize::try_from(conversation.entries_start).unwrap_or(0);
-        let window_end = window_start.saturating_add(conversation.entries.len());
-        let total_entries = usize::try_from(conversation.entries_total).unwrap_or(window_end);
+        let worktree_path = workspace.worktree_path.clone();
+        let target = self
+            .state
+            .projects
+            .iter()
+            .find(|p| p.workspaces.iter().any(|w| w.id == workspace_id))
+            .map(|p| p.pull_request_target())
+            .unwrap_or_default();
 
-        let before = before
-            .and_then(|v| usize::try_from(v).ok())
-            .unwrap_or(total_entries)
-            .min(total_entries);
-        let end = before;
-        let start = end.saturating_sub(limit);
-        let entries_truncated = start > 0 || end < total_entries;
+        self.pull_requests_in_flight.insert(workspace_id);
 
-        if start < window_start || end > window_end {
-            return Err(anyhow::anyhow!("requested slice is not in memory"));
+        let services = self.services.clone();
+        let tx = self.tx.clone();
+
+        let now = Instant::now();
+        let fetch_first = self.config.fetch_before_pull_request_refresh
+            && self.last_git_fetch_at.get(&workspace_id).is_none_or(|at| {
+                now.duration_since(*at) >= GIT_FETCH_BEFORE_PULL_REQUEST_MIN_INTERVAL
+            });
+        if fetch_first {
+            self.last_git_fetch_at.insert(workspace_id, now);
         }
 
-        let local_start = start.saturating_sub(window_start);
-        let local_end = end.saturating_sub(window_start);
+        std::thread::spawn(move || {
+            if fetch_first && let Err(message) = services.git_fetch(worktree_path.clone()) {
+                tracing::warn!(
+                    workspace_id = workspace_id.as_u64(),
+                    error = %message,
+                    "git fetch before pull request refresh failed"
+                );
+            }
+            let info = services.gh_pull_request_info(worktree_path, target);
+            let _ = tx.blocking_send(EngineCommand::PullRequestInfoUpdated { workspace_id, info });
+        });
+    }
 
-        Ok(ConversationSnapshot {
-            rev: self.rev,
-            workspace_id,
-            thread_id,
-            task_status: match conversation.task_status {
-                luban_domain::TaskStatus::Backlog => luban_api::TaskStatus::Backlog,
-                luban_domain::TaskStatus::Todo => luban_api::TaskStatus::Todo,
-                luban_domain::TaskStatus::Iterating => luban_api::TaskStatus::Iterating,
-                luban_domain::TaskStatus::Validating => luban_api::TaskStatus::Validating,
-                luban_domain::TaskStatus::Done => luban_api::TaskStatus::Done,
-                luban_domain::TaskStatus::Canceled => luban_api::TaskStatus::Canceled,
-            },
-            agent_runner: match conversation.agent_runner {
-                luban_domain::AgentRunnerKind::Codex => luban_api::AgentRunnerKind::Codex,
-                luban_domain::AgentRunnerKind::Amp => luban_api::AgentRunnerKind::Amp,
-                luban_domain::AgentRunnerKind::Claude => luban_api::AgentRunnerKind::Claude,
-                luban_domain::AgentRunnerKind::Droid => luban_api::AgentRunnerKind::Droid,
-            },
-            agent_model_id: conversation.agent_model_id.clone(),
-            thinking_effort: match conversation.thinking_effort {
-                ThinkingEffort::Minimal => luban_api::ThinkingEffort::Minimal,
-                ThinkingEffort::Low => luban_api::ThinkingEffort::Low,
-                ThinkingEffort::Medium => luban_api::ThinkingEffort::Medium,
-                ThinkingEffort::High => luban_api::ThinkingEffort::High,
-                ThinkingEffort::XHigh => luban_api::ThinkingEffort::XHigh,
-            },
-            amp_mode: if conversation.agent_runner == luban_domain::AgentRunnerKind::Amp {
-                conversation
-                    .amp_mode
-                    .clone()
-                    .or_else(|| Some(self.state.agent_amp_mode().to_owned()))
-            } else {
-                None
-            },
-            run_status: match conversation.run_status {
-                OperationStatus::Idle => luban_api::OperationStatus::Idle,
-                OperationStatus::Running => luban_api::OperationStatus::Running,
-            },
-            run_started_at_unix_ms: conversation.run_started_at_unix_ms,
-            run_finished_at_unix_ms: conversation.run_finished_at_unix_ms,
-            entries: conversation
-                .entries
-                .get(local_start..local_end)
-                .unwrap_or_default()
-                .iter()
-                .map(map_conversation_entry)
-                .collect(),
-            entries_total: total_entries as u64,
-            entries_start: start as u64,
-            entries_truncated,
-            pending_prompts: conversation
-                .pending_prompts
+    /// Re-checks whether the workspace has uncommitted changes. Unforced checks are skipped when
+    /// the cached value is recent; a forced check requested mid-flight runs again afterwards.
+    fn maybe_refresh_worktree_dirty(&mut self, workspace_id: WorkspaceId, force: bool) {
+        if self.worktree_dirty_in_flight.contains(&workspace_id) {
+            if force {
+                self.worktree_dirty_rerun.insert(workspace_id);
+            }
+            return;
+        }
+        let now = Instant::now();
+        if !force
+            && self.worktree_dirty.get(&workspace_id).is_some_and(|entry| {
+                now.duration_since(entry.checked_at) < WORKTREE_DIRTY_REFRESH_MIN_INTERVAL
+            })
+        {
+            return;
+        }
+        let Some(worktree_path) = self.state.projects.iter().find_map(|project| {
+            if !project.is_git {
+                return None;
+            }
+            project
+                .workspaces
                 .iter()
-                .map(|prompt| luban_api::QueuedPromptSnapshot {
-                    id: prompt.id,
-                    text: prompt.text.clone(),
-                    attachments: prompt.attachments.iter().map(map_attachment_ref).collect(),
-                    run_config: luban_api::AgentRunConfigSnapshot {
-                        runner: match prompt.run_config.runner {
-                            luban_domain::AgentRunnerKind::Codex => {
-                                luban_api::AgentRunnerKind::Codex
-                            }
-                            luban_domain::AgentRunnerKind::Amp => luban_api::AgentRunnerKind::Amp,
-                            luban_domain::AgentRunnerKind::Claude => {
-                                luban_api::AgentRunnerKind::Claude
-                            }
-                            luban_domain::AgentRunnerKind::Droid => {
-                                luban_api::AgentRunnerKind::Droid
-                            }
-                        },
-                        model_id: prompt.run_config.model_id.clone(),
-                        thinking_effort: match prompt.run_config.thinking_effort {
-                            ThinkingEffort::Minimal => luban_api::ThinkingEffort::Minimal,
-                            ThinkingEffort::Low => luban_api::ThinkingEffort::Low,
-                            ThinkingEffort::Medium => luban_api::ThinkingEffort::Medium,
-                            ThinkingEffort::High => luban_api::ThinkingEffort::High,
-                            ThinkingEffort::XHigh => luban_api::ThinkingEffort::XHigh,
-                        },
-                        amp_mode: prompt.run_config.amp_mode.clone(),
-                    },
-                })
-                .collect(),
-            queue_paused: conversation.queue_paused,
-            remote_thread_id: conversation.thread_id.clone(),
-            title: conversation.title.clone(),
-        })
-    }
-}
+                .find(|workspace| workspace.id == workspace_id)
+                .map(|workspace| workspace.worktree_path.clone())
+        }) else {
+            return;
+        };
 
-fn hex_lower(bytes: &[u8]) -> String {
-    const HEX: &[u8; 16] = b"0123456789abcdef";
-    let mut out = String::with_capacity(bytes.len() * 2);
-    for b in bytes {
-        out.push(HEX[(b >> 4) as usize] as char);
-        out.push(HEX[(b & 0x0f) as usize] as char);
+        self.worktree_dirty_in_flight.insert(workspace_id);
+        let tx = self.tx.clone();
+        std::thread::spawn(move || {
+            let dirty = match crate::git_changes::collect_changes(&worktree_path) {
+                Ok(files) => !files.is_empty(),
+                Err(err) => {
+                    tracing::debug!(
+                        workspace_id = workspace_id.as_u64(),
+                        error = %err,
+                        "failed to collect worktree changes"
+                    );
+                    false
+                }
+            };
+            let _ = tx.blocking_send(EngineCommand::WorktreeDirtyUpdated {
+                workspace_id,
+                dirty,
+            });
+        });
     }
-    out
-}
 
-fn normalize_project_path(path: &std::path::Path) -> PathBuf {
-    use std::path::Component;
+    async fn run_effect(&mut self, effect: Effect) -> anyhow::Result<VecDeque<Action>> {
+        match effect {
+            Effect::LoadAppState => {
+                let services = self.services.clone();
+                let loaded = tokio::task::spawn_blocking(move || services.load_app_state())
+                    .await
+                    .ok()
+                    .unwrap_or_else(|| Err("failed to join load task".to_owned()));
+                let persisted = match loaded {
+                    Ok(persisted) => persisted,
+                    Err(message) => {
+                        return Ok(VecDeque::from([Action::AppStateLoadFailed { message }]));
+                    }
+                };
 
-    let mut out = PathBuf::new();
-    for component in path.components() {
-        match component {
-            Component::CurDir => {}
-            Component::ParentDir => {
-                let popped = out.pop();
-                if !popped {
-                    out.push(component);
+                let seed_project_path = self
+                    .config
+                    .seed_project_path
+                    .clone()
+                    .filter(|_| persisted.projects.is_empty());
+                let mut actions = VecDeque::from([Action::AppStateLoaded {
+                    persisted: Box::new(persisted),
+                }]);
+                if let Some(path) = seed_project_path {
+                    let services = self.services.clone();
+                    let identity =
+                        tokio::task::spawn_blocking(move || services.project_identity(path))
+                            .await
+                            .ok()
+                            .and_then(Result::ok);
+                    if let Some(identity) = identity.filter(|identity| identity.is_git) {
+                        actions.push_back(Action::AddProject {
+                            path: identity.root_path,
+                            is_git: true,
+                        });
+                    }
                 }
+                Ok(actions)
             }
-            other => out.push(other),
-        }
-    }
-    out
-}
-
-fn find_project_id_by_path(
-    state: &AppState,
-    path: &std::path::Path,
-) -> Option<luban_domain::ProjectId> {
-    let normalized_path = normalize_project_path(path);
-    state
-        .projects
-        .iter()
-        .find(|p| normalize_project_path(&p.path) == normalized_path)
-        .map(|p| p.id)
-}
-
-fn map_task_intent_kind(kind: luban_domain::TaskIntentKind) -> luban_api::TaskIntentKind {
-    match kind {
-        luban_domain::TaskIntentKind::Fix => luban_api::TaskIntentKind::Fix,
-        luban_domain::TaskIntentKind::Implement => luban_api::TaskIntentKind::Implement,
-        luban_domain::TaskIntentKind::Review => luban_api::TaskIntentKind::Review,
-        luban_domain::TaskIntentKind::Discuss => luban_api::TaskIntentKind::Discuss,
-        luban_domain::TaskIntentKind::Other => luban_api::TaskIntentKind::Other,
-    }
-}
-
-fn map_system_task_kind(kind: luban_domain::SystemTaskKind) -> luban_api::SystemTaskKind {
-    match kind {
-        luban_domain::SystemTaskKind::InferType => luban_api::SystemTaskKind::InferType,
-        luban_domain::SystemTaskKind::RenameBranch => luban_api::SystemTaskKind::RenameBranch,
-        luban_domain::SystemTaskKind::AutoTitleThread => luban_api::SystemTaskKind::AutoTitleThread,
-        luban_domain::SystemTaskKind::AutoUpdateTaskStatus => {
-            luban_api::SystemTaskKind::AutoUpdateTaskStatus
-        }
-    }
-}
-
-fn pick_project_folder() -> Option<PathBuf> {
-    #[cfg(target_os = "macos")]
-    {
-        // `rfd` requires a windowed environment and a main-thread call on macOS. In our
-        // localhost server process we may run in a non-windowed environment, so use the
-        // system dialog via AppleScript instead.
-        let output = Command::new("osascript")
-            .args([
-                "-e",
-                "POSIX path of (choose folder with prompt \"Select project folder\")",
-            ])
-            .output()
-            .ok()?;
-
-        if !output.status.success() {
-            return None;
-        }
-
-        let raw = String::from_utf8_lossy(&output.stdout);
-        let path = raw.trim().trim_end_matches('/').trim();
-        if path.is_empty() {
-            return None;
-        }
-        Some(PathBuf::from(path))
-    }
+            Effect::SaveAppState => {
+                let services = self.services.clone();
+                let snapshot = self.state.to_persisted();
+                let saved = tokio::task::spawn_blocking(move || services.save_app_state(snapshot))
+                    .await
+                    .ok()
+                    .unwrap_or_else(|| Err("failed to join save task".to_owned()));
+                let action = match saved {
+                    Ok(()) => Action::AppStateSaved,
+                    Err(message) => Action::AppStateSaveFailed { message },
+                };
+                Ok(VecDeque::from([action]))
+            }
+            Effect::LoadCodexDefaults => {
+                let services = self.services.clone();
+                let loaded = tokio::task::spawn_blocking(move || {
+                    services.codex_config_read_file("config.toml".to_owned())
+                })
+                .await
+                .ok()
+                .unwrap_or_else(|| Err("failed to join codex config read task".to_owned()));
 
-    #[cfg(not(target_os = "macos"))]
-    {
-        rfd::FileDialog::new()
-            .set_title("Select project folder")
-            .pick_folder()
-    }
-}
+                let contents = match loaded {
+                    Ok(contents) => contents,
+                    Err(message) => {
+                        tracing::debug!(message = %message, "codex defaults unavailable");
+                        return Ok(VecDeque::new());
+                    }
+                };
 
-#[derive(Clone)]
-struct WorkspaceScope {
-    project_slug: String,
-    workspace_name: String,
-}
+                let (model_id, thinking_effort) = parse_codex_defaults_toml(&contents);
+                if model_id.is_none() && thinking_effort.is_none() {
+                    return Ok(VecDeque::new());
+                }
 
-fn workspace_scope(state: &AppState, workspace_id: WorkspaceId) -> Option<WorkspaceScope> {
-    for project in &state.projects {
-        for workspace in &project.workspaces {
-            if workspace.id == workspace_id {
-                return Some(WorkspaceScope {
-                    project_slug: project.slug.clone(),
-                    workspace_name: workspace.workspace_name.clone(),
-                });
+                Ok(VecDeque::from([Action::CodexDefaultsLoaded {
+                    model_id,
+                    thinking_effort,
+                }]))
             }
-        }
-    }
-    None
-}
-
-fn should_sync_branch_watchers(action: &Action) -> bool {
-    matches!(
-        action,
-        Action::AppStateLoaded { .. }
-            | Action::AddProject { .. }
-            | Action::CreateWorkspace { .. }
-            | Action::EnsureMainWorkspace { .. }
-            | Action::WorkspaceCreated { .. }
-            | Action::WorkspaceArchived { .. }
-            | Action::DeleteProject { .. }
-    )
-}
-
-fn conversation_key_for_action(action: &Action) -> Option<(WorkspaceId, WorkspaceThreadId)> {
-    match action {
-        Action::TerminalCommandStarted {
-            workspace_id,
-            thread_id,
-            ..
-        } => Some((*workspace_id, *thread_id)),
-        Action::TerminalCommandFinished {
-            workspace_id,
-            thread_id,
-            ..
-        } => Some((*workspace_id, *thread_id)),
-        Action::SendAgentMessage {
-            workspace_id,
-            thread_id,
-            ..
-        } => Some((*workspace_id, *thread_id)),
-        Action::TaskStatusSet {
-            workspace_id,
-            thread_id,
-            ..
-        } => Some((*workspace_id, *thread_id)),
-        Action::TaskStatusSuggestionCreated {
-            workspace_id,
-            thread_id,
-            ..
-        } => Some((*workspace_id, *thread_id)),
-        Action::QueueAgentMessage {
-            workspace_id,
-            thread_id,
-            ..
-        } => Some((*workspace_id, *thread_id)),
-        Action::ConversationLoaded {
-            workspace_id,
-            thread_id,
-            ..
-        } => Some((*workspace_id, *thread_id)),
-        Action::ConversationLoadFailed {
-            workspace_id,
-            thread_id,
-            ..
-        } => Some((*workspace_id, *thread_id)),
-        Action::AgentEventReceived {
-            workspace_id,
-            thread_id,
-            ..
-        } => Some((*workspace_id, *thread_id)),
-        Action::AgentRunStartedAt {
-            workspace_id,
-            thread_id,
-            ..
-        } => Some((*workspace_id, *thread_id)),
-        Action::AgentRunFinishedAt {
-            workspace_id,
-            thread_id,
-            ..
-        } => Some((*workspace_id, *thread_id)),
-        Action::AgentTurnFinished {
-            workspace_id,
-            thread_id,
-            ..
-        } => Some((*workspace_id, *thread_id)),
-        Action::CancelAgentTurn {
-            workspace_id,
-            thread_id,
-        } => Some((*workspace_id, *thread_id)),
-        Action::ChatModelChanged {
-            workspace_id,
-            thread_id,
-            ..
-        } => Some((*workspace_id, *thread_id)),
-        Action::ChatRunnerChanged {
-            workspace_id,
-            thread_id,
-            ..
-        } => Some((*workspace_id, *thread_id)),
-        Action::ChatAmpModeChanged {
-            workspace_id,
-            thread_id,
-            ..
-        } => Some((*workspace_id, *thread_id)),
-        Action::ThinkingEffortChanged {
-            workspace_id,
-            thread_id,
-            ..
-        } => Some((*workspace_id, *thread_id)),
-        Action::RemoveQueuedPrompt {
-            workspace_id,
-            thread_id,
-            ..
-        } => Some((*workspace_id, *thread_id)),
-        Action::ReorderQueuedPrompt {
-            workspace_id,
-            thread_id,
-            ..
-        } => Some((*workspace_id, *thread_id)),
-        Action::UpdateQueuedPrompt {
-            workspace_id,
-            thread_id,
-            ..
-        } => Some((*workspace_id, *thread_id)),
-        Action::ClearQueuedPrompts {
-            workspace_id,
-            thread_id,
-        } => Some((*workspace_id, *thread_id)),
-        Action::ResumeQueuedPrompts {
-            workspace_id,
-            thread_id,
-        } => Some((*workspace_id, *thread_id)),
-        _ => None,
-    }
-}
-
-fn conversation_keys_for_effects(effects: &[Effect]) -> Vec<(WorkspaceId, WorkspaceThreadId)> {
-    let mut out = Vec::new();
-    for effect in effects {
-        let key = match effect {
-            Effect::EnsureConversation {
-                workspace_id,
-                thread_id,
-            }
-            | Effect::StoreConversationRunConfig {
-                workspace_id,
-                thread_id,
-                ..
+            Effect::LoadTaskPromptTemplates => {
+                let services = self.services.clone();
+                let loaded =
+                    tokio::task::spawn_blocking(move || services.task_prompt_templates_load())
+                        .await
+                        .ok()
+                        .unwrap_or_else(|| {
+                            Err("failed to join task prompt templates load task".to_owned())
+                        });
+                match loaded {
+                    Ok(templates) => Ok(VecDeque::from([Action::TaskPromptTemplatesLoaded {
+                        templates,
+                    }])),
+                    Err(message) => {
+                        tracing::warn!(message = %message, "failed to load task prompt templates");
+                        Ok(VecDeque::new())
+                    }
+                }
             }
-            | Effect::StoreConversationTaskStatus {
-                workspace_id,
-                thread_id,
-                ..
+            Effect::LoadSystemPromptTemplates => {
+                let services = self.services.clone();
+                let loaded =
+                    tokio::task::spawn_blocking(move || services.system_prompt_templates_load())
+                        .await
+                        .ok()
+                        .unwrap_or_else(|| {
+                            Err("failed to join system prompt templates load task".to_owned())
+                        });
+                match loaded {
+                    Ok(templates) => Ok(VecDeque::from([Action::SystemPromptTemplatesLoaded {
+                        templates,
+                    }])),
+                    Err(message) => {
+                        tracing::warn!(message = %message, "failed to load system prompt templates");
+                        Ok(VecDeque::new())
+                    }
+                }
             }
-            | Effect::LoadConversation {
-                workspace_id,
-                thread_id,
+            Effect::MigrateLegacyTaskPromptTemplates { templates } => {
+                if templates.is_empty() {
+                    return Ok(VecDeque::new());
+                }
+                let services = self.services.clone();
+                let migrated = tokio::task::spawn_blocking(move || {
+                    let existing = services.task_prompt_templates_load().unwrap_or_default();
+                    if !existing.is_empty() {
+                        return Ok::<(), String>(());
+                    }
+                    for (kind, template) in templates {
+                        services.task_prompt_template_store(kind, template)?;
+                    }
+                    Ok(())
+                })
+                .await
+                .ok()
+                .unwrap_or_else(|| {
+                    Err("failed to join task prompt templates migrate task".to_owned())
+                });
+                if let Err(message) = migrated {
+                    tracing::warn!(message = %message, "failed to migrate legacy task prompt templates");
+                }
+                Ok(VecDeque::new())
             }
-            | Effect::RunAgentTurn {
-                workspace_id,
-                thread_id,
-                ..
+            Effect::StoreTaskPromptTemplate {
+                intent_kind,
+                template,
+            } => {
+                let services = self.services.clone();
+                let saved = tokio::task::spawn_blocking(move || {
+                    services.task_prompt_template_store(intent_kind, template)
+                })
+                .await
+                .ok()
+                .unwrap_or_else(|| {
+                    Err("failed to join task prompt template store task".to_owned())
+                });
+                if let Err(message) = saved {
+                    tracing::warn!(message = %message, "failed to store task prompt template");
+                }
+                Ok(VecDeque::new())
             }
-            | Effect::CancelAgentTurn {
-                workspace_id,
-                thread_id,
-                ..
+            Effect::DeleteTaskPromptTemplate { intent_kind } => {
+                let services = self.services.clone();
+                let deleted = tokio::task::spawn_blocking(move || {
+                    services.task_prompt_template_delete(intent_kind)
+                })
+                .await
+                .ok()
+                .unwrap_or_else(|| {
+                    Err("failed to join task prompt template delete task".to_owned())
+                });
+                if let Err(message) = deleted {
+                    tracing::warn!(message = %message, "failed to delete task prompt template");
+                }
+                Ok(VecDeque::new())
             }
-            | Effect::CleanupClaudeProcess {
-                workspace_id,
-                thread_id,
+            Effect::StoreSystemPromptTemplate { kind, template } => {
+                let services = self.services.clone();
+                let saved = tokio::task::spawn_blocking(move || {
+                    services.system_prompt_template_store(kind, template)
+                })
+                .await
+                .ok()
+                .unwrap_or_else(|| {
+                    Err("failed to join system prompt template store task".to_owned())
+                });
+                if let Err(message) = saved {
+                    tracing::warn!(message = %message, "failed to store system prompt template");
+                }
+                Ok(VecDeque::new())
             }
-            | Effect::AiAutoTitleThread {
-                workspace_id,
-                thread_id,
-                ..
+            Effect::DeleteSystemPromptTemplate { kind } => {
+                let services = self.services.clone();
+                let deleted = tokio::task::spawn_blocking(move || {
+                    services.system_prompt_template_delete(kind)
+                })
+                .await
+                .ok()
+                .unwrap_or_else(|| {
+                    Err("failed to join system prompt template delete task".to_owned())
+                });
+                if let Err(message) = deleted {
+                    tracing::warn!(message = %message, "failed to delete system prompt template");
+                }
+                Ok(VecDeque::new())
             }
-            | Effect::AiAutoUpdateTaskStatus {
-                workspace_id,
-                thread_id,
-                ..
-            } => Some((*workspace_id, *thread_id)),
-            _ => None,
-        };
+            Effect::DetectProjectDefaultBranch { project_id } => {
+                let Some(project) = self.state.project(project_id) else {
+                    return Ok(VecDeque::new());
+                };
+                let project_path = project.path.clone();
+                let services = self.services.clone();
+                let tx = self.tx.clone();
+                tokio::spawn(async move {
+                    let result = tokio::task::spawn_blocking(move || {
+                        services.detect_default_branch(project_path)
+                    })
+                    .await
+                    .ok()
+                    .unwrap_or_else(|| Err("failed to join default branch task".to_owned()));
 
-        if let Some(key) = key {
-            out.push(key);
-        }
-    }
-    out
-}
+                    let default_branch = match result {
+                        Ok(default_branch) => default_branch,
+                        Err(message) => {
+                            tracing::warn!(message = %message, "failed to detect default branch");
+                            return;
+                        }
+                    };
+                    let _ = tx
+                        .send(EngineCommand::DispatchAction {
+                            action: Box::new(Action::ProjectDefaultBranchDetected {
+                                project_id,
+                                default_branch,
+                            }),
+                        })
+                        .await;
+                });
 
-fn queue_state_key_for_action(action: &Action) -> Option<(WorkspaceId, WorkspaceThreadId)> {
-    match action {
-        Action::SendAgentMessage {
-            workspace_id,
-            thread_id,
-            ..
-        } => Some((*workspace_id, *thread_id)),
-        Action::QueueAgentMessage {
-            workspace_id,
-            thread_id,
-            ..
-        } => Some((*workspace_id, *thread_id)),
-        Action::RemoveQueuedPrompt {
-            workspace_id,
-            thread_id,
-            ..
-        } => Some((*workspace_id, *thread_id)),
-        Action::ReorderQueuedPrompt {
-            workspace_id,
-            thread_id,
-            ..
-        } => Some((*workspace_id, *thread_id)),
-        Action::UpdateQueuedPrompt {
-            workspace_id,
-            thread_id,
-            ..
-        } => Some((*workspace_id, *thread_id)),
-        Action::ClearQueuedPrompts {
-            workspace_id,
-            thread_id,
-        } => Some((*workspace_id, *thread_id)),
-        Action::ResumeQueuedPrompts {
-            workspace_id,
-            thread_id,
-        } => Some((*workspace_id, *thread_id)),
-        Action::CancelAgentTurn {
-            workspace_id,
-            thread_id,
-        } => Some((*workspace_id, *thread_id)),
-        Action::TaskStatusSet {
-            workspace_id,
-            thread_id,
-            task_status: luban_domain::TaskStatus::Canceled | luban_domain::TaskStatus::Done,
-        } => Some((*workspace_id, *thread_id)),
-        Action::AgentEventReceived {
-            workspace_id,
-            thread_id,
-            run_id: _,
-            event:
-                CodexThreadEvent::TurnCompleted { .. }
-                | CodexThreadEvent::TurnFailed { .. }
-                | CodexThreadEvent::Error { .. },
-        } => Some((*workspace_id, *thread_id)),
-        Action::AgentRunStartedAt {
-            workspace_id,
-            thread_id,
-            ..
-        } => Some((*workspace_id, *thread_id)),
-        Action::AgentRunFinishedAt {
-            workspace_id,
-            thread_id,
-            ..
-        } => Some((*workspace_id, *thread_id)),
-        _ => None,
-    }
-}
+                Ok(VecDeque::new())
+            }
+            Effect::CreateWorkspace {
+                project_id,
+                branch_name_hint,
+            } => {
+                let Some(project) = self.state.projects.iter().find(|p| p.id == project_id) else {
+                    return Ok(VecDeque::from([Action::WorkspaceCreateFailed {
+                        project_id,
+                        message: "project not found".to_owned(),
+                    }]));
+                };
+                let project_path = project.path.clone();
+                let project_slug = project.slug.clone();
+                let worktree_root = project.worktree_root.clone();
+                let branch_name_template = project.branch_name_template();
+                let services = self.services.clone();
 
-fn threads_event_for_action(
-    action: &Action,
-) -> Option<(WorkspaceId, Vec<luban_domain::ConversationThreadMeta>)> {
-    match action {
-        Action::WorkspaceThreadsLoaded {
-            workspace_id,
-            threads,
-        } => Some((*workspace_id, threads.clone())),
-        _ => None,
-    }
-}
+                let created = tokio::task::spawn_blocking(move || {
+                    services.create_workspace(
+                        project_path,
+                        project_slug,
+                        branch_name_hint,
+                        worktree_root,
+                        branch_name_template,
+                    )
+                })
+                .await
+                .ok()
+                .unwrap_or_else(|| Err("failed to join create workspace task".to_owned()));
 
-fn task_summaries_workspace_id_for_action(action: &Action) -> Option<WorkspaceId> {
-    match action {
-        Action::WorkspaceThreadsLoaded { workspace_id, .. } => Some(*workspace_id),
-        Action::TaskStarSet { workspace_id, .. } => Some(*workspace_id),
-        Action::OpenWorkspace { workspace_id } => Some(*workspace_id),
-        Action::DashboardPreviewOpened { workspace_id } => Some(*workspace_id),
-        Action::CreateWorkspaceThread { workspace_id } => Some(*workspace_id),
-        Action::ActivateWorkspaceThread { workspace_id, .. } => Some(*workspace_id),
-        Action::CloseWorkspaceThreadTab { workspace_id, .. } => Some(*workspace_id),
-        Action::RestoreWorkspaceThreadTab { workspace_id, .. } => Some(*workspace_id),
-        Action::ReorderWorkspaceThreadTab { workspace_id, .. } => Some(*workspace_id),
-        Action::SendAgentMessage { workspace_id, .. } => Some(*workspace_id),
-        Action::QueueAgentMessage { workspace_id, .. } => Some(*workspace_id),
-        Action::AgentTurnFinished { workspace_id, .. } => Some(*workspace_id),
-        _ => None,
-    }
-}
+                let action = match created {
+                    Ok(created) => Action::WorkspaceCreated {
+                        project_id,
+                        workspace_name: created.workspace_name,
+                        branch_name: created.branch_name,
+                        worktree_path: created.worktree_path,
+                    },
+                    Err(message) => Action::WorkspaceCreateFailed {
+                        project_id,
+                        message,
+                    },
+                };
+                Ok(VecDeque::from([action]))
+            }
+            Effect::CreateWorkspaceFromBranch {
+                project_id,
+                branch_name,
+            } => {
+                let Some(project) = self.state.projects.iter().find(|p| p.id == project_id) else {
+                    return Ok(VecDeque::from([Action::WorkspaceCreateFailed {
+                        project_id,
+                        message: "project not found".to_owned(),
+                    }]));
+                };
+                let project_path = project.path.clone();
+                let project_slug = project.slug.clone();
+                let worktree_root = project.worktree_root.clone();
+                let services = self.services.clone();
 
-fn dedup_thread_metas_in_place(metas: &mut Vec<ConversationThreadMeta>) {
-    let mut seen = HashSet::<WorkspaceThreadId>::new();
-    metas.retain(|t| seen.insert(t.thread_id));
-}
+                let created = tokio::task::spawn_blocking(move || {
+                    services.create_workspace_from_branch(
+                        project_path,
+                        project_slug,
+                        branch_name,
+                        worktree_root,
+                    )
+                })
+                .await
+                .ok()
+                .unwrap_or_else(|| Err("failed to join create workspace task".to_owned()));
 
-fn map_domain_task_status(status: luban_domain::TaskStatus) -> luban_api::TaskStatus {
-    match status {
-        luban_domain::TaskStatus::Backlog => luban_api::TaskStatus::Backlog,
-        luban_domain::TaskStatus::Todo => luban_api::TaskStatus::Todo,
-        luban_domain::TaskStatus::Iterating => luban_api::TaskStatus::Iterating,
-        luban_domain::TaskStatus::Validating => luban_api::TaskStatus::Validating,
-        luban_domain::TaskStatus::Done => luban_api::TaskStatus::Done,
-        luban_domain::TaskStatus::Canceled => luban_api::TaskStatus::Canceled,
-    }
-}
+                let action = match created {
+                    Ok(created) => Action::WorkspaceCreated {
+                        project_id,
+                        workspace_name: created.workspace_name,
+                        branch_name: created.branch_name,
+                        worktree_path: created.worktree_path,
+                    },
+                    Err(message) => Action::WorkspaceCreateFailed {
+                        project_id,
+                        message,
+                    },
+                };
+                Ok(VecDeque::from([action]))
+            }
+            Effect::RenameWorkspaceBranch {
+                workspace_id,
+                requested_branch_name,
+            } => {
+                let Some(workspace) = self.state.workspace(workspace_id) else {
+                    return Ok(VecDeque::from([Action::WorkspaceBranchRenameFailed {
+                        workspace_id,
+                        message: "workspace not found".to_owned(),
+                    }]));
+                };
 
-fn map_domain_turn_status(status: luban_domain::TurnStatus) -> luban_api::TurnStatus {
-    match status {
-        luban_domain::TurnStatus::Idle => luban_api::TurnStatus::Idle,
-        luban_domain::TurnStatus::Running => luban_api::TurnStatus::Running,
-        luban_domain::TurnStatus::Awaiting => luban_api::TurnStatus::Awaiting,
-        luban_domain::TurnStatus::Paused => luban_api::TurnStatus::Paused,
-    }
-}
+                let worktree_path = workspace.worktree_path.clone();
+                let services = self.services.clone();
+                let tx = self.tx.clone();
+                tokio::spawn(async move {
+                    let result = tokio::task::spawn_blocking(move || {
+                        services.rename_workspace_branch(worktree_path, requested_branch_name, None)
+                    })
+                    .await
+                    .ok()
+                    .unwrap_or_else(|| {
+                        Err("failed to join rename workspace branch task".to_owned())
+                    });
 
-fn map_domain_turn_result(result: luban_domain::TurnResult) -> luban_api::TurnResult {
-    match result {
-        luban_domain::TurnResult::Completed => luban_api::TurnResult::Completed,
-        luban_domain::TurnResult::Failed => luban_api::TurnResult::Failed,
-    }
-}
+                    let action = match result {
+                        Ok(branch_name) => Action::WorkspaceBranchRenamed {
+                            workspace_id,
+                            branch_name,
+                        },
+                        Err(message) => Action::WorkspaceBranchRenameFailed {
+                            workspace_id,
+                            message,
+                        },
+                    };
+                    let _ = tx
+                        .send(EngineCommand::DispatchAction {
+                            action: Box::new(action),
+                        })
+                        .await;
+                });
 
-fn parse_codex_defaults_toml(contents: &str) -> (Option<String>, Option<ThinkingEffort>) {
-    fn strip_comment(line: &str) -> &str {
-        let mut in_single = false;
-        let mut in_double = false;
-        for (idx, ch) in line.char_indices() {
-            match ch {
-                '\'' if !in_double => in_single = !in_single,
-                '"' if !in_single => in_double = !in_double,
-                '#' if !in_single && !in_double => return &line[..idx],
-                _ => {}
+                Ok(VecDeque::new())
             }
-        }
-        line
-    }
-
-    fn parse_string_value(raw: &str) -> Option<String> {
-        let trimmed = raw.trim();
-        if trimmed.is_empty() {
-            return None;
-        }
-        if let Some(rest) = trimmed.strip_prefix('"') {
-            let end = rest.find('"')?;
-            return Some(rest[..end].to_owned());
-        }
-        if let Some(rest) = trimmed.strip_prefix('\'') {
-            let end = rest.find('\'')?;
-            return Some(rest[..end].to_owned());
-        }
-        None
-    }
+            Effect::StashWorkspaceChanges { workspace_id }
+            | Effect::PopWorkspaceStash { workspace_id } => {
+                let pop = matches!(effect, Effect::PopWorkspaceStash { .. });
+                let Some(workspace) = self.state.workspace(workspace_id) else {
+                    return Ok(VecDeque::from([Action::WorkspaceStashFailed {
+                        workspace_id,
+                        message: "workspace not found".to_owned(),
+                    }]));
+                };
 
-    fn parse_effort(raw: &str) -> Option<ThinkingEffort> {
-        match raw.trim().to_ascii_lowercase().as_str() {
-            "minimal" => Some(ThinkingEffort::Minimal),
-            "low" => Some(ThinkingEffort::Low),
-            "medium" => Some(ThinkingEffort::Medium),
-            "high" => Some(ThinkingEffort::High),
-            "xhigh" => Some(ThinkingEffort::XHigh),
-            _ => None,
-        }
-    }
+                let worktree_path = workspace.worktree_path.clone();
+                let services = self.services.clone();
+                let tx = self.tx.clone();
+                let events = self.events.clone();
+                let rev = self.rev;
+                tokio::spawn(async move {
+                    let result = tokio::task::spawn_blocking(move || {
+                        if pop {
+                            services
+                                .git_stash_pop(worktree_path)
+                                .map(|()| "Restored stashed changes".to_owned())
+                        } else {
+                            services.git_stash_push(worktree_path).map(|stashed| {
+                                if stashed {
+                                    "Stashed uncommitted changes".to_owned()
+                                } else {
+                                    "No local changes to stash".to_owned()
+                                }
+                            })
+                        }
+                    })
+                    .await
+                    .ok()
+                    .unwrap_or_else(|| Err("failed to join git stash task".to_owned()));
 
-    let mut in_root = true;
-    let mut model_id: Option<String> = None;
-    let mut effort: Option<ThinkingEffort> = None;
+                    let (message, action) = match result {
+                        Ok(message) => (message, Action::WorkspaceStashFinished { workspace_id }),
+                        Err(message) => (
+                            message.clone(),
+                            Action::WorkspaceStashFailed {
+                                workspace_id,
+                                message,
+                            },
+                        ),
+                    };
+                    let _ = tx
+                        .send(EngineCommand::DispatchAction {
+                            action: Box::new(action),
+                        })
+                        .await;
+                    let _ = events.send(WsServerMessage::Event {
+                        rev,
+                        event: Box::new(luban_api::ServerEvent::Toast { message }),
+                    });
+                });
 
-    for raw_line in contents.lines() {
-        let line = strip_comment(raw_line).trim();
-        if line.is_empty() {
-            continue;
-        }
-        if line.starts_with('[') {
-            in_root = false;
-            continue;
-        }
-        if !in_root {
-            continue;
-        }
+                Ok(VecDeque::new())
+            }
+            Effect::CommitWorkspaceChanges {
+                workspace_id,
+                message,
+                paths,
+            } => {
+                let Some(workspace) = self.state.workspace(workspace_id) else {
+                    return Ok(VecDeque::from([Action::WorkspaceCommitFailed {
+                        workspace_id,
+                        message: "workspace not found".to_owned(),
+                    }]));
+                };
 
-        let Some((key, value)) = line.split_once('=') else {
-            continue;
-        };
-        let key = key.trim();
-        let value = value.trim();
+                let worktree_path = workspace.worktree_path.clone();
+                let services = self.services.clone();
+                let tx = self.tx.clone();
+                let events = self.events.clone();
+                let rev = self.rev;
+                tokio::spawn(async move {
+                    let result = tokio::task::spawn_blocking(move || {
+                        services.git_commit(worktree_path, message, paths)
+                    })
+                    .await
+                    .ok()
+                    .unwrap_or_else(|| Err("failed to join git commit task".to_owned()));
 
-        if key == "model" && model_id.is_none() {
-            model_id = parse_string_value(value).map(|v| v.trim().to_owned());
-            continue;
-        }
-        if key == "model_reasoning_effort" && effort.is_none() {
-            if let Some(value) = parse_string_value(value) {
-                effort = parse_effort(&value);
-            }
-            continue;
-        }
-    }
+                    let action = match result {
+                        Ok(short_hash) => {
+                            let _ = events.send(WsServerMessage::Event {
+                                rev,
+                                event: Box::new(luban_api::ServerEvent::Toast {
+                                    message: format!("Committed {short_hash}"),
+                                }),
+                            });
+                            Action::WorkspaceCommitFinished { workspace_id }
+                        }
+                        Err(message) => Action::WorkspaceCommitFailed {
+                            workspace_id,
+                            message,
+                        },
+                    };
+                    let _ = tx
+                        .send(EngineCommand::DispatchAction {
+                            action: Box::new(action),
+                        })
+                        .await;
+                });
 
-    (
-        model_id.and_then(|v| {
-            let trimmed = v.trim();
-            if trimmed.is_empty() {
-                None
-            } else {
-                Some(trimmed.to_owned())
+                Ok(VecDeque::new())
             }
-        }),
-        effort,
-    )
-}
-
-fn map_pull_request_info(info: PullRequestInfo) -> PullRequestSnapshot {
-    let state = match info.state {
-        DomainPullRequestState::Open => PullRequestState::Open,
-        DomainPullRequestState::Closed => PullRequestState::Closed,
-        DomainPullRequestState::Merged => PullRequestState::Merged,
-    };
-    let ci_state = info.ci_state.map(|s| match s {
-        DomainPullRequestCiState::Pending => PullRequestCiState::Pending,
-        DomainPullRequestCiState::Success => PullRequestCiState::Success,
-        DomainPullRequestCiState::Failure => PullRequestCiState::Failure,
-    });
-    PullRequestSnapshot {
-        number: info.number,
-        is_draft: info.is_draft,
-        state,
-        ci_state,
-        merge_ready: info.merge_ready,
-    }
-}
+            Effect::AutoCommitTurn {
+                workspace_id,
+                thread_id,
+                message,
+            } => {
+                let Some(workspace) = self.state.workspace(workspace_id) else {
+                    return Ok(VecDeque::new());
+                };
+                if workspace.commit_status == OperationStatus::Running {
+                    return Ok(VecDeque::new());
+                }
 
-fn workspace_short_id(project_slug: &str, workspace_id: u64) -> String {
-    let mut prefix = project_slug
-        .chars()
-        .filter(|c| c.is_ascii_alphanumeric())
-        .map(|c| c.to_ascii_lowercase())
-        .take(2)
-        .collect::<String>();
-    while prefix.len() < 2 {
-        prefix.push('x');
-    }
+                // Awaited inline so a queued prompt's turn only starts once the commit is done.
+                let worktree_path = workspace.worktree_path.clone();
+                let services = self.services.clone();
+                let result = tokio::task::spawn_blocking(move || {
+                    match crate::git_changes::collect_changes(&worktree_path) {
+                        Ok(files) if files.is_empty() => return Ok(None),
+                        Ok(_) => {}
+                        Err(err) => return Err(err.to_string()),
+                    }
+                    services.git_commit(worktree_path, message, None).map(Some)
+                })
+                .await
+                .ok()
+                .unwrap_or_else(|| Err("failed to join git commit task".to_owned()));
 
-    let mut suffix = to_base36(workspace_id);
-    if suffix.len() < 2 {
-        suffix.insert(0, '0');
-    }
+                match result {
+                    Ok(None) => Ok(VecDeque::new()),
+                    Ok(Some(short_hash)) => {
+                        tracing::info!(
+                            workspace_id = workspace_id.as_u64(),
+                            thread_id = thread_id.as_u64(),
+                            commit = %short_hash,
+                            "auto-committed agent turn"
+                        );
+                        let _ = self.events.send(WsServerMessage::Event {
+                            rev: self.rev,
+                            event: Box::new(luban_api::ServerEvent::Toast {
+                                message: format!("Auto-committed {short_hash}"),
+                            }),
+                        });
+                        Ok(VecDeque::from([Action::WorkspaceCommitFinished {
+                            workspace_id,
+                        }]))
+                    }
+                    Err(message) => Ok(VecDeque::from([Action::WorkspaceCommitFailed {
+                        workspace_id,
+                        message: format!("Auto-commit failed: {message}"),
+                    }])),
+                }
+            }
+            Effect::AiRenameWorkspaceBranch {
+                workspace_id,
+                input,
+                runner,
+                model_id,
+                thinking_effort,
+                amp_mode,
+            } => {
+                if workspace_scope(&self.state, workspace_id).is_none() {
+                    return Ok(VecDeque::from([Action::WorkspaceBranchRenameFailed {
+                        workspace_id,
+                        message: "workspace not found".to_owned(),
+                    }]));
+                };
 
-    format!("{prefix}{suffix}")
-}
+                let worktree_path = self
+                    .state
+                    .workspace(workspace_id)
+                    .map(|w| w.worktree_path.clone())
+                    .unwrap_or_else(|| std::env::current_dir().unwrap_or_default());
+                let branch_name_template = self
+                    .state
+                    .projects
+                    .iter()
+                    .find(|p| p.workspaces.iter().any(|w| w.id == workspace_id))
+                    .and_then(|project| project.branch_name_template());
 
-fn to_base36(mut n: u64) -> String {
-    const DIGITS: &[u8; 36] = b"0123456789abcdefghijklmnopqrstuvwxyz";
-    if n == 0 {
-        return "0".to_owned();
-    }
-    let mut out = Vec::new();
-    while n > 0 {
-        out.push(DIGITS[(n % 36) as usize]);
-        n /= 36;
-    }
-    out.reverse();
-    String::from_utf8(out).unwrap_or_else(|_| "0".to_owned())
-}
+                let services = self.services.clone();
+                let tx = self.tx.clone();
+                tokio::spawn(async move {
+                    let result = tokio::task::spawn_blocking(move || {
+                        let suggested = services.task_suggest_branch_name(
+                            input,
+                            runner,
+                            model_id,
+                            thinking_effort,
+                            amp_mode,
+                        )?;
+                        services.rename_workspace_branch(
+                            worktree_path,
+                            suggested,
+                            branch_name_template,
+                        )
+                    })
+                    .await
+                    .ok()
+                    .unwrap_or_else(|| {
+                        Err("failed to join ai rename workspace branch task".to_owned())
+                    });
 
-fn now_unix_seconds() -> u64 {
-    std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs()
-}
+                    let action = match result {
+                        Ok(branch_name) => Action::WorkspaceBranchRenamed {
+                            workspace_id,
+                            branch_name,
+                        },
+                        Err(message) => Action::WorkspaceBranchRenameFailed {
+                            workspace_id,
+                            message,
+                        },
+                    };
+                    let _ = tx
+                        .send(EngineCommand::DispatchAction {
+                            action: Box::new(action),
+                        })
+                        .await;
+                });
 
-fn now_unix_ms() -> u64 {
-    std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_millis()
-        .try_into()
-        .unwrap_or(0u64)
-}
+                Ok(VecDeque::new())
+            }
+            Effect::AiAutoTitleThread {
+                workspace_id,
+                thread_id,
+                input,
+                expected_current_title,
+                runner,
+                model_id,
+                thinking_effort,
+                amp_mode,
+            } => {
+                let Some(scope) = workspace_scope(&self.state, workspace_id) else {
+                    return Ok(VecDeque::new());
+                };
 
-fn map_workspace_tabs_snapshot(tabs: &luban_domain::WorkspaceTabs) -> WorkspaceTabsSnapshot {
-    WorkspaceTabsSnapshot {
-        open_tabs: tabs
-            .open_tabs
-            .iter()
-            .map(|id| luban_api::WorkspaceThreadId(id.as_u64()))
-            .collect(),
-        archived_tabs: tabs
-            .archived_tabs
-            .iter()
-            .map(|id| luban_api::WorkspaceThreadId(id.as_u64()))
-            .collect(),
-        active_tab: luban_api::WorkspaceThreadId(tabs.active_tab.as_u64()),
-    }
-}
+                let use_fake_agent = std::env::var_os("LUBAN_E2E_ROOT").is_some()
+                    && std::env::var("LUBAN_CODEX_BIN")
+                        .ok()
+                        .is_some_and(|bin| bin == "/usr/bin/false");
 
-fn map_conversation_entry(entry: &ConversationEntry) -> luban_api::ConversationEntry {
-    match entry {
-        ConversationEntry::SystemEvent {
-            entry_id,
-            created_at_unix_ms,
-            event,
-        } => luban_api::ConversationEntry::SystemEvent(luban_api::ConversationSystemEventEntry {
-            entry_id: entry_id.clone(),
-            created_at_unix_ms: *created_at_unix_ms,
-            event: match event {
-                luban_domain::ConversationSystemEvent::TaskCreated => {
-                    luban_api::ConversationSystemEvent::TaskCreated
-                }
-                luban_domain::ConversationSystemEvent::TaskArchived => {
-                    luban_api::ConversationSystemEvent::TaskArchived
-                }
-                luban_domain::ConversationSystemEvent::TaskStatusChanged { from, to } => {
-                    luban_api::ConversationSystemEvent::TaskStatusChanged {
-                        from: match from {
-                            luban_domain::TaskStatus::Backlog => luban_api::TaskStatus::Backlog,
-                            luban_domain::TaskStatus::Todo => luban_api::TaskStatus::Todo,
-                            luban_domain::TaskStatus::Iterating => luban_api::TaskStatus::Iterating,
-                            luban_domain::TaskStatus::Validating => {
-                                luban_api::TaskStatus::Validating
-                            }
-                            luban_domain::TaskStatus::Done => luban_api::TaskStatus::Done,
-                            luban_domain::TaskStatus::Canceled => luban_api::TaskStatus::Canceled,
-                        },
-                        to: match to {
-                            luban_domain::TaskStatus::Backlog => luban_api::TaskStatus::Backlog,
-                            luban_domain::TaskStatus::Todo => luban_api::TaskStatus::Todo,
-                            luban_domain::TaskStatus::Iterating => luban_api::TaskStatus::Iterating,
-                            luban_domain::TaskStatus::Validating => {
-                                luban_api::TaskStatus::Validating
+                let services = self.services.clone();
+                let tx = self.tx.clone();
+                let project_slug = scope.project_slug;
+                let workspace_name = scope.workspace_name;
+                let thread_local_id = thread_id.as_u64();
+                tokio::spawn(async move {
+                    let services_for_suggest = services.clone();
+                    let project_slug_for_update = project_slug.clone();
+                    let workspace_name_for_update = workspace_name.clone();
+                    let result = tokio::task::spawn_blocking(move || {
+                        let suggested = if use_fake_agent {
+                            let derived = luban_domain::derive_thread_title(&input);
+                            if derived.is_empty() {
+                                "Thread".to_owned()
+                            } else {
+                                derived
                             }
-                            luban_domain::TaskStatus::Done => luban_api::TaskStatus::Done,
-                            luban_domain::TaskStatus::Canceled => luban_api::TaskStatus::Canceled,
-                        },
+                        } else {
+                            suggest_thread_title_with_retries(
+                                &input,
+                                AUTO_TITLE_RETRY_DELAY,
+                                || {
+                                    services_for_suggest.task_suggest_thread_title(
+                                        input.clone(),
+                                        runner,
+                                        model_id.clone(),
+                                        thinking_effort,
+                                        amp_mode.clone(),
+                                    )
+                                },
+                            )
+                        };
+                        if suggested.is_empty() {
+                            return Ok::<_, String>(false);
+                        }
+
+                        services_for_suggest.conversation_update_title_if_matches(
+                            project_slug_for_update,
+                            workspace_name_for_update,
+                            thread_local_id,
+                            expected_current_title,
+                            suggested,
+                        )
+                    })
+                    .await
+                    .ok()
+                    .unwrap_or_else(|| Err("failed to join auto title thread task".to_owned()));
+
+                    let Ok(updated) = result else {
+                        return;
+                    };
+                    if !updated {
+                        return;
                     }
-                }
-                luban_domain::ConversationSystemEvent::TaskStatusSuggestion {
-                    from,
-                    to,
-                    title,
-                    explanation_markdown,
-                } => luban_api::ConversationSystemEvent::TaskStatusSuggestion {
-                    from: match from {
-                        luban_domain::TaskStatus::Backlog => luban_api::TaskStatus::Backlog,
-                        luban_domain::TaskStatus::Todo => luban_api::TaskStatus::Todo,
-                        luban_domain::TaskStatus::Iterating => luban_api::TaskStatus::Iterating,
-                        luban_domain::TaskStatus::Validating => luban_api::TaskStatus::Validating,
-                        luban_domain::TaskStatus::Done => luban_api::TaskStatus::Done,
-                        luban_domain::TaskStatus::Canceled => luban_api::TaskStatus::Canceled,
-                    },
-                    to: match to {
-                        luban_domain::TaskStatus::Backlog => luban_api::TaskStatus::Backlog,
-                        luban_domain::TaskStatus::Todo => luban_api::TaskStatus::Todo,
-                        luban_domain::TaskStatus::Iterating => luban_api::TaskStatus::Iterating,
-                        luban_domain::TaskStatus::Validating => luban_api::TaskStatus::Validating,
-                        luban_domain::TaskStatus::Done => luban_api::TaskStatus::Done,
-                        luban_domain::TaskStatus::Canceled => luban_api::TaskStatus::Canceled,
-                    },
-                    title: title.clone(),
-                    explanation_markdown: explanation_markdown.clone(),
-                },
-            },
-        }),
-        ConversationEntry::UserEvent {
-            entry_id,
-            created_at_unix_ms,
-            event,
-        } => {
-            let event = match event {
-                luban_domain::UserEvent::Message { text, attachments } => {
-                    luban_api::UserEvent::Message(luban_api::UserMessage {
-                        text: text.clone(),
-                        attachments: attachments.iter().map(map_attachment_ref).collect(),
+
+                    let services_for_list = services.clone();
+                    let project_slug_for_list = project_slug.clone();
+                    let workspace_name_for_list = workspace_name.clone();
+                    let result = tokio::task::spawn_blocking(move || {
+                        services_for_list.list_conversation_threads(
+                            project_slug_for_list,
+                            workspace_name_for_list,
+                        )
                     })
-                }
-                luban_domain::UserEvent::TerminalCommandStarted {
-                    id,
-                    command,
-                    reconnect,
-                } => luban_api::UserEvent::TerminalCommandStarted(
-                    luban_api::TerminalCommandStarted {
-                        id: id.clone(),
-                        command: command.clone(),
-                        reconnect: reconnect.clone(),
-                    },
-                ),
-                luban_domain::UserEvent::TerminalCommandFinished {
-                    id,
-                    command,
-                    reconnect,
-                    output_base64,
-                    output_byte_len,
-                } => luban_api::UserEvent::TerminalCommandFinished(
-                    luban_api::TerminalCommandFinished {
-                        id: id.clone(),
-                        command: command.clone(),
-                        reconnect: reconnect.clone(),
-                        output_base64: output_base64.clone(),
-                        output_byte_len: *output_byte_len,
-                    },
-                ),
-            };
-            luban_api::ConversationEntry::UserEvent(luban_api::UserEventEntry {
-                entry_id: entry_id.clone(),
-                created_at_unix_ms: *created_at_unix_ms,
-                event,
-            })
-        }
-        ConversationEntry::AgentEvent {
-            entry_id,
-            created_at_unix_ms,
-            runner,
-            event,
-        } => {
-            let event = match event {
-                luban_domain::AgentEvent::Message { id, text } => {
-                    luban_api::AgentEvent::Message(luban_api::AgentMessage {
-                        id: id.clone(),
-                        text: text.clone(),
-                    })
-                }
-                luban_domain::AgentEvent::Item { item } => {
-                    map_codex_thread_item_to_agent_event(item.as_ref())
-                }
-                luban_domain::AgentEvent::TurnUsage { usage } => {
-                    let usage_json = usage.as_ref().and_then(|u| serde_json::to_value(u).ok());
-                    luban_api::AgentEvent::TurnUsage { usage_json }
-                }
-                luban_domain::AgentEvent::TurnDuration { duration_ms } => {
-                    luban_api::AgentEvent::TurnDuration {
-                        duration_ms: *duration_ms,
-                    }
-                }
-                luban_domain::AgentEvent::TurnCanceled => luban_api::AgentEvent::TurnCanceled,
-                luban_domain::AgentEvent::TurnError { message } => {
-                    luban_api::AgentEvent::TurnError {
-                        message: message.clone(),
-                    }
-                }
-            };
-            luban_api::ConversationEntry::AgentEvent(luban_api::AgentEventEntry {
-                entry_id: entry_id.clone(),
-                created_at_unix_ms: *created_at_unix_ms,
-                runner: runner.map(|r| match r {
-                    luban_domain::AgentRunnerKind::Codex => luban_api::AgentRunnerKind::Codex,
-                    luban_domain::AgentRunnerKind::Amp => luban_api::AgentRunnerKind::Amp,
-                    luban_domain::AgentRunnerKind::Claude => luban_api::AgentRunnerKind::Claude,
-                    luban_domain::AgentRunnerKind::Droid => luban_api::AgentRunnerKind::Droid,
-                }),
-                event,
-            })
-        }
-    }
-}
+                    .await
+                    .ok()
+                    .unwrap_or_else(|| Err("failed to join list threads task".to_owned()));
 
-fn map_codex_thread_item_to_agent_event(item: &CodexThreadItem) -> luban_api::AgentEvent {
-    match item {
-        CodexThreadItem::AgentMessage { id, text } => {
-            luban_api::AgentEvent::Message(luban_api::AgentMessage {
-                id: id.clone(),
-                text: text.clone(),
-            })
-        }
-        _ => {
-            let id = codex_item_id(item).to_owned();
-            let (kind, payload) = map_agent_item(item);
-            luban_api::AgentEvent::Item(luban_api::AgentItem { id, kind, payload })
-        }
-    }
-}
+                    let Ok(threads) = result else {
+                        return;
+                    };
 
-fn map_attachment_ref(att: &AttachmentRef) -> luban_api::AttachmentRef {
-    luban_api::AttachmentRef {
-        id: att.id.clone(),
-        kind: match att.kind {
-            AttachmentKind::Image => luban_api::AttachmentKind::Image,
-            AttachmentKind::Text => luban_api::AttachmentKind::Text,
-            AttachmentKind::File => luban_api::AttachmentKind::File,
-        },
-        name: att.name.clone(),
-        extension: att.extension.clone(),
-        mime: att.mime.clone(),
-        byte_len: att.byte_len,
-    }
-}
+                    let _ = tx
+                        .send(EngineCommand::DispatchAction {
+                            action: Box::new(Action::WorkspaceThreadsLoaded {
+                                workspace_id,
+                                threads,
+                            }),
+                        })
+                        .await;
+                });
 
-fn map_agent_item(item: &CodexThreadItem) -> (luban_api::AgentItemKind, serde_json::Value) {
-    let kind = match item {
-        CodexThreadItem::Reasoning { .. } => luban_api::AgentItemKind::Reasoning,
-        CodexThreadItem::CommandExecution { .. } => luban_api::AgentItemKind::CommandExecution,
-        CodexThreadItem::FileChange { .. } => luban_api::AgentItemKind::FileChange,
-        CodexThreadItem::McpToolCall { .. } => luban_api::AgentItemKind::McpToolCall,
-        CodexThreadItem::WebSearch { .. } => luban_api::AgentItemKind::WebSearch,
-        CodexThreadItem::TodoList { .. } => luban_api::AgentItemKind::TodoList,
-        CodexThreadItem::Error { .. } => luban_api::AgentItemKind::Error,
-        CodexThreadItem::AgentMessage { .. } => {
-            unreachable!("agent messages are mapped to AgentEvent::Message")
-        }
-    };
-    let payload = serde_json::to_value(item).unwrap_or(serde_json::Value::Null);
-    (kind, payload)
-}
+                Ok(VecDeque::new())
+            }
+            Effect::AiAutoUpdateTaskStatus {
+                workspace_id,
+                thread_id,
+                input,
+                expected_current_task_status,
+                runner,
+                model_id,
+                thinking_effort,
+                amp_mode,
+            } => {
+                let Some(scope) = workspace_scope(&self.state, workspace_id) else {
+                    return Ok(VecDeque::new());
+                };
 
-fn codex_item_id(item: &CodexThreadItem) -> &str {
-    match item {
-        CodexThreadItem::AgentMessage { id, .. } => id,
-        CodexThreadItem::Reasoning { id, .. } => id,
-        CodexThreadItem::CommandExecution { id, .. } => id,
-        CodexThreadItem::FileChange { id, .. } => id,
-        CodexThreadItem::McpToolCall { id, .. } => id,
-        CodexThreadItem::WebSearch { id, .. } => id,
-        CodexThreadItem::TodoList { id, .. } => id,
-        CodexThreadItem::Error { id, .. } => id,
-    }
-}
+                let project_slug = scope.project_slug;
+                let workspace_name = scope.workspace_name;
+                let thread_local_id = thread_id.as_u64();
 
-fn map_client_action(action: luban_api::ClientAction) -> Option<Action> {
-    match action {
-        luban_api::ClientAction::PickProjectPath => None,
-        luban_api::ClientAction::AddProject { path } => Some(Action::AddProject {
-            path: expand_user_path(&path),
-            is_git: true,
-        }),
-        luban_api::ClientAction::AddProjectAndOpen { .. } => None,
-        luban_api::ClientAction::TaskExecute { .. } => None,
-        luban_api::ClientAction::TelegramBotTokenSet { token } => {
-            Some(Action::TelegramBotTokenSet { token })
-        }
-        luban_api::ClientAction::TelegramBotTokenClear => Some(Action::TelegramBotTokenCleared),
-        luban_api::ClientAction::TelegramPairStart => None,
-        luban_api::ClientAction::TelegramUnpair => Some(Action::TelegramUnpaired),
-        luban_api::ClientAction::TaskStarSet {
-            workspace_id,
-            thread_id,
-            starred,
-        } => Some(Action::TaskStarSet {
-            workspace_id: WorkspaceId::from_u64(workspace_id.0),
-            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
-            starred,
-        }),
-        luban_api::ClientAction::TaskStatusSet {
-            workspace_id,
-            thread_id,
-            task_status,
-        } => Some(Action::TaskStatusSet {
-            workspace_id: WorkspaceId::from_u64(workspace_id.0),
-            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
-            task_status: match task_status {
-                luban_api::TaskStatus::Backlog => luban_domain::TaskStatus::Backlog,
-                luban_api::TaskStatus::Todo => luban_domain::TaskStatus::Todo,
-                luban_api::TaskStatus::Iterating => luban_domain::TaskStatus::Iterating,
-                luban_api::TaskStatus::Validating => luban_domain::TaskStatus::Validating,
-                luban_api::TaskStatus::Done => luban_domain::TaskStatus::Done,
-                luban_api::TaskStatus::Canceled => luban_domain::TaskStatus::Canceled,
-            },
-        }),
-        luban_api::ClientAction::FeedbackSubmit { .. } => None,
-        luban_api::ClientAction::DeleteProject { .. } => None,
-        luban_api::ClientAction::ToggleProjectExpanded { .. } => None,
-        luban_api::ClientAction::CreateWorkspace { .. } => None,
-        luban_api::ClientAction::OpenWorkspace { workspace_id } => Some(Action::OpenWorkspace {
-            workspace_id: WorkspaceId::from_u64(workspace_id.0),
-        }),
-        luban_api::ClientAction::OpenWorkspaceInIde { workspace_id } => {
-            Some(Action::OpenWorkspaceInIde {
-                workspace_id: WorkspaceId::from_u64(workspace_id.0),
-            })
-        }
-        luban_api::ClientAction::OpenWorkspaceWith {
-            workspace_id,
-            target,
-        } => Some(Action::OpenWorkspaceWith {
-            workspace_id: WorkspaceId::from_u64(workspace_id.0),
-            target: match target {
-                luban_api::OpenTarget::Vscode => OpenTarget::Vscode,
-                luban_api::OpenTarget::Cursor => OpenTarget::Cursor,
-                luban_api::OpenTarget::Zed => OpenTarget::Zed,
-                luban_api::OpenTarget::Ghostty => OpenTarget::Ghostty,
-                luban_api::OpenTarget::Finder => OpenTarget::Finder,
-            },
-        }),
-        luban_api::ClientAction::OpenWorkspacePullRequest { workspace_id } => {
-            Some(Action::OpenWorkspacePullRequest {
-                workspace_id: WorkspaceId::from_u64(workspace_id.0),
-            })
-        }
-        luban_api::ClientAction::OpenWorkspacePullRequestFailedAction { workspace_id } => {
-            Some(Action::OpenWorkspacePullRequestFailedAction {
-                workspace_id: WorkspaceId::from_u64(workspace_id.0),
-            })
-        }
-        luban_api::ClientAction::ArchiveWorkspace { workspace_id } => {
-            Some(Action::ArchiveWorkspace {
-                workspace_id: WorkspaceId::from_u64(workspace_id.0),
-            })
-        }
-        luban_api::ClientAction::EnsureMainWorkspace { .. } => None,
-        luban_api::ClientAction::ChatModelChanged {
-            workspace_id,
-            thread_id,
-            model_id,
-        } => Some(Action::ChatModelChanged {
-            workspace_id: WorkspaceId::from_u64(workspace_id.0),
-            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
-            model_id,
-        }),
-        luban_api::ClientAction::ChatRunnerChanged {
-            workspace_id,
-            thread_id,
-            runner,
-        } => Some(Action::ChatRunnerChanged {
-            workspace_id: WorkspaceId::from_u64(workspace_id.0),
-            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
-            runner: map_api_agent_runner_kind(runner),
-        }),
-        luban_api::ClientAction::ChatAmpModeChanged {
-            workspace_id,
-            thread_id,
-            amp_mode,
-        } => Some(Action::ChatAmpModeChanged {
-            workspace_id: WorkspaceId::from_u64(workspace_id.0),
-            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
-            amp_mode,
-        }),
-        luban_api::ClientAction::ThinkingEffortChanged {
-            workspace_id,
-            thread_id,
-            thinking_effort,
-        } => Some(Action::ThinkingEffortChanged {
-            workspace_id: WorkspaceId::from_u64(workspace_id.0),
-            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
-            thinking_effort: match thinking_effort {
-                luban_api::ThinkingEffort::Minimal => ThinkingEffort::Minimal,
-                luban_api::ThinkingEffort::Low => ThinkingEffort::Low,
-                luban_api::ThinkingEffort::Medium => ThinkingEffort::Medium,
-                luban_api::ThinkingEffort::High => ThinkingEffort::High,
-                luban_api::ThinkingEffort::XHigh => ThinkingEffort::XHigh,
-            },
-        }),
-        luban_api::ClientAction::TerminalCommandStart { .. } => None,
-        luban_api::ClientAction::SendAgentMessage {
-            workspace_id,
-            thread_id,
-            text,
-            attachments,
-            runner,
-            amp_mode,
-        } => Some(Action::SendAgentMessage {
-            workspace_id: WorkspaceId::from_u64(workspace_id.0),
-            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
-            text,
-            attachments: attachments.into_iter().map(map_api_attachment).collect(),
-            runner: runner.map(map_api_agent_runner_kind),
+                let services = self.services.clone();
+                let tx = self.tx.clone();
+                tokio::spawn(async move {
+                    let result = tokio::task::spawn_blocking(move || {
+                        let suggested = services.task_suggest_task_status_auto_update(
+                            input,
+                            runner,
+                            model_id,
+                            thinking_effort,
+                            amp_mode,
+                        )?;
+
+                        let _ = services.save_conversation_task_status_last_analyzed(
+                            project_slug.clone(),
+                            workspace_name.clone(),
+                            thread_local_id,
+                        );
+
+                        if suggested.task_status == luban_domain::TaskStatus::Validating
+                            && let Some(pr_number) = suggested.validation_pr_number
+                            && matches!(
+                                expected_current_task_status,
+                                luban_domain::TaskStatus::Iterating
+                                    | luban_domain::TaskStatus::Validating
+                            )
+                        {
+                            let _ = services.save_conversation_task_validation_pr(
+                                project_slug.clone(),
+                                workspace_name.clone(),
+                                thread_local_id,
+                                pr_number,
+                                suggested.validation_pr_url.clone(),
+                            );
+                        }
+
+                        Ok::<_, String>(suggested)
+                    })
+                    .await
+                    .ok()
+                    .unwrap_or_else(|| {
+                        Err("failed to join auto update task status task".to_owned())
+                    });
+
+                    let Ok(suggested) = result else {
+                        return;
+                    };
+
+                    let suggested_task_status = suggested.task_status;
+                    let title = format!("Suggest moving to {}", suggested_task_status.as_str());
+                    let explanation_markdown = suggested.explanation_markdown.unwrap_or_default();
+
+                    let _ = tx
+                        .send(EngineCommand::DispatchAction {
+                            action: Box::new(Action::TaskStatusSuggestionCreated {
+                                workspace_id,
+                                thread_id,
+                                expected_current_task_status,
+                                suggested_task_status,
+                                title,
+                                explanation_markdown,
+                            }),
+                        })
+                        .await;
+                });
+
+                Ok(VecDeque::new())
+            }
+            Effect::LoadWorkspaceThreads { workspace_id } => {
+                let Some(scope) = workspace_scope(&self.state, workspace_id) else {
+                    return Ok(VecDeque::new());
+                };
+                let services = self.services.clone();
+                let project_slug_for_list = scope.project_slug.clone();
+                let workspace_name_for_list = scope.workspace_name.clone();
+                let result = tokio::task::spawn_blocking(move || {
+                    services
+                        .list_conversation_threads(project_slug_for_list, workspace_name_for_list)
+                })
+                .await
+                .ok()
+                .unwrap_or_else(|| Err("failed to join list threads task".to_owned()));
+                let action = match result {
+                    Ok(threads) => Action::WorkspaceThreadsLoaded {
+                        workspace_id,
+                        threads,
+                    },
+                    Err(message) => Action::WorkspaceThreadsLoadFailed {
+                        workspace_id,
+                        message,
+                    },
+                };
+                Ok(VecDeque::from([action]))
+            }
+            Effect::LoadConversation {
+                workspace_id,
+                thread_id,
+            } => {
+                let Some(scope) = workspace_scope(&self.state, workspace_id) else {
+                    return Ok(VecDeque::new());
+                };
+                let services = self.services.clone();
+                let thread_local_id = thread_id.as_u64();
+                let limit = self.config.conversation_load.max_entries_limit as u64;
+                let result = tokio::task::spawn_blocking(move || {
+                    services.load_conversation_page(
+                        scope.project_slug,
+                        scope.workspace_name,
+                        thread_local_id,
+                        None,
+                        limit,
+                    )
+                })
+                .await
+                .ok()
+                .unwrap_or_else(|| Err("failed to join load conversation task".to_owned()));
+                let action = match result {
+                    Ok(snapshot) => Action::ConversationLoaded {
+                        workspace_id,
+                        thread_id,
+                        snapshot,
+                    },
+                    Err(message) => Action::ConversationLoadFailed {
+                        workspace_id,
+                        thread_id,
+                        message,
+                    },
+                };
+                Ok(VecDeque::from([action]))
+            }
+            Effect::EnsureConversation {
+                workspace_id,
+                thread_id,
+            } => {
+                let Some(scope) = workspace_scope(&self.state, workspace_id) else {
+                    return Ok(VecDeque::new());
+                };
+                let services = self.services.clone();
+                let thread_local_id = thread_id.as_u64();
+                let _ = tokio::task::spawn_blocking(move || {
+                    services.ensure_conversation(
+                        scope.project_slug,
+                        scope.workspace_name,
+                        thread_local_id,
+                    )
+                })
+                .await;
+                Ok(VecDeque::new())
+            }
+            Effect::DuplicateConversation {
+                workspace_id,
+                source_thread_id,
+                thread_id,
+                up_to_entry_id,
+            } => {
+                let Some(scope) = workspace_scope(&self.state, workspace_id) else {
+                    return Ok(VecDeque::new());
+                };
+                let services = self.services.clone();
+                let tx = self.tx.clone();
+                tokio::spawn(async move {
+                    let result = tokio::task::spawn_blocking(move || {
+                        services.duplicate_conversation(
+                            scope.project_slug,
+                            scope.workspace_name,
+                            source_thread_id.as_u64(),
+                            thread_id.as_u64(),
+                            up_to_entry_id,
+                        )
+                    })
+                    .await
+                    .ok()
+                    .unwrap_or_else(|| Err("failed to join duplicate task".to_owned()));
+
+                    let action = match result {
+                        Ok(()) => Action::WorkspaceThreadDuplicated {
+                            workspace_id,
+                            thread_id,
+                        },
+                        Err(message) => Action::WorkspaceThreadDuplicateFailed {
+                            workspace_id,
+                            thread_id,
+                            message,
+                        },
+                    };
+                    let _ = tx
+                        .send(EngineCommand::DispatchAction {
+                            action: Box::new(action),
+                        })
+                        .await;
+                });
+                Ok(VecDeque::new())
+            }
+            Effect::StoreConversationRunConfig {
+                workspace_id,
+                thread_id,
+                runner,
+                model_id,
+                thinking_effort,
+                amp_mode,
+            } => {
+                let Some(scope) = workspace_scope(&self.state, workspace_id) else {
+                    return Ok(VecDeque::new());
+                };
+                let conversation = self
+                    .state
+                    .workspace_thread_conversation(workspace_id, thread_id);
+                let (claude_max_output_tokens, claude_system_prompt) = conversation
+                    .map(|c| c.claude_run_options(runner))
+                    .unwrap_or_default();
+                let auto_commit = conversation.is_some_and(|c| c.auto_commit);
+                let services = self.services.clone();
+                let thread_local_id = thread_id.as_u64();
+                let _ = tokio::task::spawn_blocking(move || {
+                    services.save_conversation_run_config(
+                        scope.project_slug,
+                        scope.workspace_name,
+                        thread_local_id,
+                        runner,
+                        model_id,
+                        thinking_effort,
+                        amp_mode,
+                        claude_max_output_tokens,
+                        claude_system_prompt,
+                        auto_commit,
+                    )
+                })
+                .await;
+                Ok(VecDeque::new())
+            }
+            Effect::StoreConversationCwdSubpath {
+                workspace_id,
+                thread_id,
+                cwd_subpath,
+            } => {
+                let Some(scope) = workspace_scope(&self.state, workspace_id) else {
+                    return Ok(VecDeque::new());
+                };
+                let services = self.services.clone();
+                let thread_local_id = thread_id.as_u64();
+                let _ = tokio::task::spawn_blocking(move || {
+                    services.save_conversation_cwd_subpath(
+                        scope.project_slug,
+                        scope.workspace_name,
+                        thread_local_id,
+                        cwd_subpath,
+                    )
+                })
+                .await;
+                Ok(VecDeque::new())
+            }
+            Effect::StoreHunkComment {
+                workspace_id,
+                thread_id,
+                comment,
+            } => {
+                let Some(scope) = workspace_scope(&self.state, workspace_id) else {
+                    return Ok(VecDeque::new());
+                };
+                let services = self.services.clone();
+                let thread_local_id = thread_id.as_u64();
+                let _ = tokio::task::spawn_blocking(move || {
+                    services.save_conversation_hunk_comment(
+                        scope.project_slug,
+                        scope.workspace_name,
+                        thread_local_id,
+                        comment,
+                    )
+                })
+                .await;
+                Ok(VecDeque::new())
+            }
+            Effect::StoreCollapsedRange {
+                workspace_id,
+                thread_id,
+                range,
+            } => {
+                let Some(scope) = workspace_scope(&self.state, workspace_id) else {
+                    return Ok(VecDeque::new());
+                };
+                let services = self.services.clone();
+                let thread_local_id = thread_id.as_u64();
+                let _ = tokio::task::spawn_blocking(move || {
+                    services.save_conversation_collapsed_range(
+                        scope.project_slug,
+                        scope.workspace_name,
+                        thread_local_id,
+                        range,
+                    )
+                })
+                .await;
+                Ok(VecDeque::new())
+            }
+            Effect::StoreConversationTaskStatus {
+                workspace_id,
+                thread_id,
+                task_status,
+            } => {
+                let Some(scope) = workspace_scope(&self.state, workspace_id) else {
+                    return Ok(VecDeque::new());
+                };
+                let services = self.services.clone();
+                let thread_local_id = thread_id.as_u64();
+                let _ = tokio::task::spawn_blocking(move || {
+                    services.save_conversation_task_status(
+                        scope.project_slug,
+                        scope.workspace_name,
+                        thread_local_id,
+                        task_status,
+                    )
+                })
+                .await;
+                Ok(VecDeque::new())
+            }
+            Effect::StoreConversationTitle {
+                workspace_id,
+                thread_id,
+                expected_current_title,
+                title,
+            } => {
+                let Some(scope) = workspace_scope(&self.state, workspace_id) else {
+                    return Ok(VecDeque::new());
+                };
+                let services = self.services.clone();
+                let thread_local_id = thread_id.as_u64();
+                let result = tokio::task::spawn_blocking(move || {
+                    // A concurrent auto-title that already changed the stored title wins; the
+                    // reload below then restores it in the live state.
+                    services.conversation_update_title_if_matches(
+                        scope.project_slug.clone(),
+                        scope.workspace_name.clone(),
+                        thread_local_id,
+                        expected_current_title,
+                        title,
+                    )?;
+                    services.list_conversation_threads(scope.project_slug, scope.workspace_name)
+                })
+                .await
+                .ok()
+                .unwrap_or_else(|| Err("failed to join rename thread task".to_owned()));
+
+                let threads = result.map_err(|message| anyhow::anyhow!(message))?;
+                Ok(VecDeque::from([Action::WorkspaceThreadsLoaded {
+                    workspace_id,
+                    threads,
+                }]))
+            }
+            Effect::StoreConversationNotes {
+                workspace_id,
+                thread_id,
+            } => {
+                let key = (workspace_id, thread_id);
+                let generation = self
+                    .pending_notes_flushes
+                    .get(&key)
+                    .map_or(1, |g| g.wrapping_add(1));
+                self.pending_notes_flushes.insert(key, generation);
+                let tx = self.tx.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(CONVERSATION_NOTES_PERSIST_DEBOUNCE).await;
+                    let _ = tx
+                        .send(EngineCommand::FlushConversationNotes {
+                            workspace_id,
+                            thread_id,
+                            generation,
+                        })
+                        .await;
+                });
+                Ok(VecDeque::new())
+            }
+            Effect::StoreConversationDraft {
+                workspace_id,
+                thread_id,
+            } => {
+                let key = (workspace_id, thread_id);
+                let generation = self
+                    .pending_draft_flushes
+                    .get(&key)
+                    .map_or(1, |g| g.wrapping_add(1));
+                self.pending_draft_flushes.insert(key, generation);
+                let tx = self.tx.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(CONVERSATION_DRAFT_PERSIST_DEBOUNCE).await;
+                    let _ = tx
+                        .send(EngineCommand::FlushConversationDraft {
+                            workspace_id,
+                            thread_id,
+                            generation,
+                        })
+                        .await;
+                });
+                Ok(VecDeque::new())
+            }
+            Effect::ScheduleAgentTurnRetry {
+                workspace_id,
+                thread_id,
+                attempt,
+                delay_ms,
+                run_config,
+            } => {
+                let tx = self.tx.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+                    let _ = tx
+                        .send(EngineCommand::DispatchAction {
+                            action: Box::new(Action::AgentTurnRetryDue {
+                                workspace_id,
+                                thread_id,
+                                attempt,
+                                run_config,
+                            }),
+                        })
+                        .await;
+                });
+                Ok(VecDeque::new())
+            }
+            Effect::RunAgentTurn {
+                workspace_id,
+                thread_id,
+                run_id,
+                text,
+                attachments,
+                run_config,
+                retry,
+            } => {
+                if run_config.runner == luban_domain::AgentRunnerKind::Amp
+                    && let Some(mode) = run_config.amp_mode.as_deref()
+                    && !luban_api::AmpMode::parse(mode).is_known()
+                {
+                    let message =
+                        format!("Unknown Amp mode \"{mode}\"; supported modes are smart and rush");
+                    let _ = self.events.send(WsServerMessage::Event {
+                        rev: self.rev,
+                        event: Box::new(luban_api::ServerEvent::Toast {
+                            message: message.clone(),
+                        }),
+                    });
+                    return Ok(VecDeque::from([
+                        Action::AgentEventReceived {
+                            workspace_id,
+                            thread_id,
+                            run_id,
+                            event: luban_domain::CodexThreadEvent::TurnFailed {
+                                error: luban_domain::CodexThreadError {
+                                    message,
+                                    code: None,
+                                },
+                            },
+                        },
+                        Action::AgentTurnFinished {
+                            workspace_id,
+                            thread_id,
+                            run_id,
+                        },
+                    ]));
+                }
+
+                if self.agent_turn_slots_full() {
+                    self.deferred_agent_turns.push_back(DeferredAgentTurn {
+                        workspace_id,
+                        thread_id,
+                        run_id,
+                        effect: Effect::RunAgentTurn {
+                            workspace_id,
+                            thread_id,
+                            run_id,
+                            text,
+                            attachments,
+                            run_config,
+                            retry,
+                        },
+                    });
+                    return Ok(VecDeque::new());
+                }
+
+                let started_at_unix_ms = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis()
+                    .try_into()
+                    .unwrap_or(0u64);
+
+                let use_fake_agent = std::env::var_os("LUBAN_E2E_ROOT").is_some()
+                    && std::env::var("LUBAN_CODEX_BIN")
+                        .ok()
+                        .is_some_and(|bin| bin == "/usr/bin/false");
+                let fake_agent_delay = if use_fake_agent {
+                    let prompt = text.as_str();
+                    if prompt.contains("e2e-running-card")
+                        || prompt.contains("e2e-streaming-message")
+                    {
+                        Duration::from_millis(3500)
+                    } else if prompt.contains("e2e-ansi-output") {
+                        Duration::from_millis(600)
+                    } else if prompt.contains("e2e-cancel") {
+                        Duration::from_millis(2500)
+                    } else if prompt.contains("e2e-queued") {
+                        Duration::from_millis(1500)
+                    } else {
+                        Duration::from_millis(50)
+                    }
+                } else {
+                    Duration::from_millis(0)
+                };
+
+                let Some(scope) = workspace_scope(&self.state, workspace_id) else {
+                    return Ok(VecDeque::new());
+                };
+
+                let worktree_path = self
+                    .state
+                    .workspace(workspace_id)
+                    .map(|w| w.worktree_path.clone())
+                    .unwrap_or_else(|| std::env::current_dir().unwrap_or_default());
+
+                let conversation = self
+                    .state
+                    .workspace_thread_conversation(workspace_id, thread_id);
+                let remote_thread_id = conversation.and_then(|c| c.thread_id.clone());
+                let cwd_subpath = conversation.and_then(|c| c.cwd_subpath.clone());
+                let project = self
+                    .state
+                    .projects
+                    .iter()
+                    .find(|p| p.workspaces.iter().any(|w| w.id == workspace_id));
+                let project_system_prompt = project.and_then(|p| p.system_prompt.as_deref());
+                let system_prompt =
+                    luban_domain::compose_agent_system_prompt(None, project_system_prompt, None);
+                let agent_env = project.map(|p| p.agent_env.clone()).unwrap_or_default();
+
+                let request = luban_domain::RunAgentTurnRequest {
+                    project_slug: scope.project_slug,
+                    workspace_name: scope.workspace_name,
+                    worktree_path,
+                    thread_local_id: thread_id.as_u64(),
+                    thread_id: remote_thread_id,
+                    prompt: text,
+                    attachments,
+                    runner: run_config.runner,
+                    amp_mode: run_config.amp_mode.clone(),
+                    claude_max_output_tokens: run_config.claude_max_output_tokens,
+                    claude_system_prompt: run_config.claude_system_prompt.clone(),
+                    agent_env,
+                    model: Some(run_config.model_id.clone()),
+                    model_reasoning_effort: Some(run_config.thinking_effort.as_str().to_owned()),
+                    cwd_subpath,
+                    system_prompt,
+                    prompt_prefix: self
+                        .state
+                        .agent_runner_prompt_prefixes()
+                        .get(&run_config.runner)
+                        .cloned(),
+                    prompt_suffix: self
+                        .state
+                        .agent_runner_prompt_suffixes()
+                        .get(&run_config.runner)
+                        .cloned(),
+                    retry,
+                    command_output_max_bytes: self.state.agent_command_output_max_bytes(),
+                    merge_reasoning_entries: self.state.agent_merge_reasoning_entries(),
+                };
+
+                let cancel = Arc::new(AtomicBool::new(false));
+                self.cancel_flags.insert(
+                    (workspace_id, thread_id),
+                    CancelFlagEntry {
+                        run_id,
+                        flag: cancel.clone(),
+                    },
+                );
+                self.agent_turn_slots
+                    .insert((workspace_id, thread_id), run_id);
+
+                if use_fake_agent {
+                    let tx = self.tx.clone();
+                    std::thread::spawn(move || {
+                        let deadline = fake_agent_delay;
+                        let start = Instant::now();
+                        let prompt = request.prompt.clone();
+
+                        let emit_many_steps = prompt.contains("e2e-many-steps");
+                        let emit_pagination_steps = prompt.contains("e2e-pagination-steps");
+                        let emit_markdown_reasoning = prompt.contains("e2e-thinking-markdown");
+                        let emit_file_change = prompt.contains("e2e-file-change");
+                        let emit_streaming_message = prompt.contains("e2e-streaming-message");
+                        let emit_long_output = prompt.contains("e2e-long-output");
+
+                        if emit_many_steps || emit_pagination_steps {
+                            let count = if emit_pagination_steps {
+                                2505u32
+                            } else {
+                                12_000u32
+                            };
+                            // Generate a large amount of completed items to stress the UI render/timing
+                            // paths. This is used only in e2e mode (`LUBAN_E2E_ROOT` + fake codex bin).
+                            // Keep the IDs simple and stable.
+                            for i in 0..count {
+                                if cancel.load(Ordering::SeqCst) {
+                                    break;
+                                }
+                                let _ = tx.blocking_send(EngineCommand::DispatchAction {
+                                    action: Box::new(Action::AgentEventReceived {
+                                        workspace_id,
+                                        thread_id,
+                                        run_id,
+                                        event: luban_domain::CodexThreadEvent::ItemCompleted {
+                                            item: luban_domain::CodexThreadItem::CommandExecution {
+                                                id: format!("e2e_many_{i}"),
+                                                command: format!("echo {i}"),
+                                                aggregated_output: "ok".to_owned(),
+                                                exit_code: Some(0),
+                                                status: luban_domain::CodexCommandExecutionStatus::Completed,
+                                                truncated: false,
+                                            },
+                                        },
+                                    }),
+                                });
+                            }
+
+                            if !cancel.load(Ordering::SeqCst) {
+                                let _ = tx.blocking_send(EngineCommand::DispatchAction {
+                                    action: Box::new(Action::AgentEventReceived {
+                                        workspace_id,
+                                        thread_id,
+                                        run_id,
+                                        event: luban_domain::CodexThreadEvent::TurnFailed {
+                                            error: luban_domain::CodexThreadError {
+                                                message: "e2e agent stub".to_owned(),
+                                                code: None,
+                                            },
+                                        },
+                                    }),
+                                });
+                            }
+
+                            let _ = tx.blocking_send(EngineCommand::DispatchAction {
+                                action: Box::new(Action::AgentTurnFinished {
+                                    workspace_id,
+                                    thread_id,
+                                    run_id,
+                                }),
+                            });
+                            return;
+                        }
+
+                        let mut sent_1_start = false;
+                        let mut sent_1_done = false;
+                        let mut sent_2_start = false;
+                        let mut sent_2_done = false;
+                        let mut sent_3_start = false;
+                        let mut sent_ansi_output = false;
+                        let mut streaming_started = false;
+                        let mut streaming_completed = false;
+                        let streaming_id = "e2e_stream_msg_1".to_owned();
+                        let streaming_needle = "e2e-selection-needle";
+                        let mut streaming_text = String::new();
+                        let mut streaming_chunks_sent: u32 = 0;
+
+                        while start.elapsed() < deadline && !cancel.load(Ordering::SeqCst) {
+                            let elapsed = start.elapsed();
+
+                            if emit_streaming_message && !streaming_completed {
+                                if !streaming_started && elapsed >= Duration::from_millis(50) {
+                                    streaming_started = true;
+                                    streaming_text =
+                                        format!("Streaming...\n\n{streaming_needle}\n\n");
+                                    let _ = tx.blocking_send(EngineCommand::DispatchAction {
+                                        action: Box::new(Action::AgentEventReceived {
+                                            workspace_id,
+                                            thread_id,
+                                            run_id,
+                                            event: luban_domain::CodexThreadEvent::ItemStarted {
+                                                item: luban_domain::CodexThreadItem::AgentMessage {
+                                                    id: streaming_id.clone(),
+                                                    text: streaming_text.clone(),
+                                                },
+                                            },
+                                        }),
+                                    });
+                                }
+
+                                if streaming_started {
+                                    let chunk_every_ms = 120u64;
+                                    let elapsed_ms = elapsed.as_millis() as u64;
+                                    let expected_chunks =
+                                        (elapsed_ms / chunk_every_ms).min(25) as u32;
+                                    while streaming_chunks_sent < expected_chunks {
+                                        streaming_chunks_sent += 1;
+                                        streaming_text.push_str(&format!(
+                                            "chunk-{:02}\n",
+                                            streaming_chunks_sent
+                                        ));
+                                        let _ = tx.blocking_send(EngineCommand::DispatchAction {
+                                            action: Box::new(Action::AgentEventReceived {
+                                                workspace_id,
+                                                thread_id,
+                                                run_id,
+                                                event: luban_domain::CodexThreadEvent::ItemUpdated {
+                                                    item: luban_domain::CodexThreadItem::AgentMessage {
+                                                        id: streaming_id.clone(),
+                                                        text: streaming_text.clone(),
+                                                    },
+                                                },
+                                            }),
+                                        });
+                                    }
+                                }
+
+                                if elapsed >= Duration::from_millis(3000) {
+                                    streaming_completed = true;
+                                    let _ = tx.blocking_send(EngineCommand::DispatchAction {
+                                        action: Box::new(Action::AgentEventReceived {
+                                            workspace_id,
+                                            thread_id,
+                                            run_id,
+                                            event: luban_domain::CodexThreadEvent::ItemCompleted {
+                                                item: luban_domain::CodexThreadItem::AgentMessage {
+                                                    id: streaming_id.clone(),
+                                                    text: streaming_text.clone(),
+                                                },
+                                            },
+                                        }),
+                                    });
+                                }
+                            }
+
+                            if prompt.contains("e2e-ansi-output")
+                                && !sent_ansi_output
+                                && elapsed >= Duration::from_millis(75)
+                            {
+                                sent_ansi_output = true;
+                                let aggregated_output = [
+                                    "[[2m[WebServer] [[22m Finished 'dev' profile [unoptimized + debuginfo] target(s) in 0.33s",
+                                    "[[2m[WebServer] [[22m Running 'target/debug/luban_server'",
+                                    "",
+                                    "(node:4596) Warning: The 'NO_COLOR' env is ignored due to the 'FORCE_COLOR' env being set.",
+                                    "",
+                                    "[[1A[[2K[[0G [[32m√[[39m [[2mtests/e2e/chat-ui.spec.ts:334:5 › enter commits IME composition without sending[[22m",
+                                    "[[32m  2 passed[[39m[[2m (14.1s)[[22m",
+                                ]
+                                .join("\n");
+                                let _ = tx.blocking_send(EngineCommand::DispatchAction {
+                                    action: Box::new(Action::AgentEventReceived {
+                                        workspace_id,
+                                        thread_id,
+                                        run_id,
+                                        event: luban_domain::CodexThreadEvent::ItemCompleted {
+                                            item: luban_domain::CodexThreadItem::CommandExecution {
+                                                id: "e2e_ansi_cmd_1".to_owned(),
+                                                command: "zsh -lc \"just test-ui\"".to_owned(),
+                                                aggregated_output,
+                                                exit_code: Some(0),
+                                                status: luban_domain::CodexCommandExecutionStatus::Completed,
+                                                truncated: false,
+                                            },
+                                        },
+                                    }),
+                                });
+                            }
+
+                            if prompt.contains("e2e-running-card") {
+                                if !sent_1_start && elapsed >= Duration::from_millis(50) {
+                                    sent_1_start = true;
+                                    let _ = tx.blocking_send(EngineCommand::DispatchAction {
+                                        action: Box::new(Action::AgentEventReceived {
+                                            workspace_id,
+                                            thread_id,
+                                            run_id,
+                                            event: luban_domain::CodexThreadEvent::ItemStarted {
+                                                item: luban_domain::CodexThreadItem::CommandExecution {
+                                                    id: "e2e_cmd_1".to_owned(),
+                                                    command: "echo 1".to_owned(),
+                                                    aggregated_output: "".to_owned(),
+                                                    exit_code: None,
+                                                    status: luban_domain::CodexCommandExecutionStatus::InProgress,
+                                                    truncated: false,
+                                                },
+                                            },
+                                        }),
+                                    });
+                                }
+                                if !sent_1_done && elapsed >= Duration::from_millis(250) {
+                                    sent_1_done = true;
+                                    let _ = tx.blocking_send(EngineCommand::DispatchAction {
+	                                        action: Box::new(Action::AgentEventReceived {
+	                                            workspace_id,
+	                                            thread_id,
+	                                            run_id,
+	                                            event: luban_domain::CodexThreadEvent::ItemCompleted {
+	                                                item: luban_domain::CodexThreadItem::CommandExecution {
+	                                                    id: "e2e_cmd_1".to_owned(),
+	                                                    command: "echo 1".to_owned(),
+	                                                    aggregated_output: "".to_owned(),
+	                                                    exit_code: Some(0),
+	                                                    status: luban_domain::CodexCommandExecutionStatus::Completed,
+	                                                    truncated: false,
+	                                                },
+	                                            },
+	                                        }),
+	                                    });
+                                }
+                                if !sent_2_start && elapsed >= Duration::from_millis(350) {
+                                    sent_2_start = true;
+                                    let _ = tx.blocking_send(EngineCommand::DispatchAction {
+                                        action: Box::new(Action::AgentEventReceived {
+                                            workspace_id,
+                                            thread_id,
+                                            run_id,
+                                            event: luban_domain::CodexThreadEvent::ItemStarted {
+                                                item: luban_domain::CodexThreadItem::CommandExecution {
+                                                    id: "e2e_cmd_2".to_owned(),
+                                                    command: "echo 2".to_owned(),
+                                                    aggregated_output: "".to_owned(),
+                                                    exit_code: None,
+                                                    status: luban_domain::CodexCommandExecutionStatus::InProgress,
+                                                    truncated: false,
+                                                },
+                                            },
+                                        }),
+                                    });
+                                }
+                                if !sent_2_done && elapsed >= Duration::from_millis(1750) {
+                                    sent_2_done = true;
+                                    let aggregated_output = if emit_long_output {
+                                        [
+                                            "test io::commit::conflict_resolver::tests::test_conflicting_rebase::ours_1__update_full__::other_1__update_full__ ... ok",
+                                            "test io::commit::conflict_resolver::tests::test_conflicting_rebase::ours_1__update_full__::other_2__update_partial__ ... ok",
+                                            "test io::commit::conflict_resolver::tests::test_conflicting_rebase::ours_2__update_partial__::other_4__delete_partial__ ... ok",
+                                        ]
+                                        .join("\n")
+                                    } else {
+                                        "ok".to_owned()
+                                    };
+                                    let _ = tx.blocking_send(EngineCommand::DispatchAction {
+                                        action: Box::new(Action::AgentEventReceived {
+                                            workspace_id,
+                                            thread_id,
+                                            run_id,
+                                            event: luban_domain::CodexThreadEvent::ItemCompleted {
+                                                item: luban_domain::CodexThreadItem::CommandExecution {
+                                                    id: "e2e_cmd_2".to_owned(),
+                                                    command: "echo 2".to_owned(),
+                                                    aggregated_output,
+                                                    exit_code: Some(0),
+                                                    status: luban_domain::CodexCommandExecutionStatus::Completed,
+                                                    truncated: false,
+                                                },
+                                            },
+                                        }),
+                                    });
+                                }
+                                if !sent_3_start && elapsed >= Duration::from_millis(1800) {
+                                    sent_3_start = true;
+                                    let _ = tx.blocking_send(EngineCommand::DispatchAction {
+                                        action: Box::new(Action::AgentEventReceived {
+                                            workspace_id,
+                                            thread_id,
+                                            run_id,
+                                            event: luban_domain::CodexThreadEvent::ItemStarted {
+                                                item: luban_domain::CodexThreadItem::CommandExecution {
+                                                    id: "e2e_cmd_3".to_owned(),
+                                                    command: "echo 3".to_owned(),
+                                                    aggregated_output: "".to_owned(),
+                                                    exit_code: None,
+                                                    status: luban_domain::CodexCommandExecutionStatus::InProgress,
+                                                    truncated: false,
+                                                },
+                                            },
+                                        }),
+                                    });
+                                }
+                            }
+
+                            std::thread::sleep(Duration::from_millis(25));
+                        }
+
+                        if !cancel.load(Ordering::SeqCst) {
+                            if emit_markdown_reasoning {
+                                let _ = tx.blocking_send(EngineCommand::DispatchAction {
+                                    action: Box::new(Action::AgentEventReceived {
+                                        workspace_id,
+                                        thread_id,
+                                        run_id,
+                                        event: luban_domain::CodexThreadEvent::ItemStarted {
+                                            item: luban_domain::CodexThreadItem::Reasoning {
+                                                id: "e2e_reasoning_1".to_owned(),
+                                                text:
+                                                    "**Plan**: verify markdown summary stripping."
+                                                        .to_owned(),
+                                            },
+                                        },
+                                    }),
+                                });
+
+                                let _ = tx.blocking_send(EngineCommand::DispatchAction {
+                                    action: Box::new(Action::AgentEventReceived {
+                                        workspace_id,
+                                        thread_id,
+                                        run_id,
+                                        event: luban_domain::CodexThreadEvent::ItemCompleted {
+                                            item: luban_domain::CodexThreadItem::Reasoning {
+                                                id: "e2e_reasoning_1".to_owned(),
+                                                text:
+                                                    "**Plan**: verify markdown summary stripping."
+                                                        .to_owned(),
+                                            },
+                                        },
+                                    }),
+                                });
+                            }
+
+                            if prompt.contains("e2e-mermaid") {
+                                let _ = tx.blocking_send(EngineCommand::DispatchAction {
+                                    action: Box::new(Action::AgentEventReceived {
+                                        workspace_id,
+                                        thread_id,
+                                        run_id,
+                                        event: luban_domain::CodexThreadEvent::ItemCompleted {
+                                            item: luban_domain::CodexThreadItem::AgentMessage {
+                                                id: "e2e_mermaid_1".to_owned(),
+                                                text: prompt.clone(),
+                                            },
+                                        },
+                                    }),
+                                });
+                            }
+
+                            if emit_file_change {
+                                let _ = tx.blocking_send(EngineCommand::DispatchAction {
+                                    action: Box::new(Action::AgentEventReceived {
+                                        workspace_id,
+                                        thread_id,
+                                        run_id,
+                                        event: luban_domain::CodexThreadEvent::ItemCompleted {
+                                            item: luban_domain::CodexThreadItem::FileChange {
+                                                id: "e2e_file_change_1".to_owned(),
+                                                changes: vec![
+                                                    luban_domain::CodexFileUpdateChange {
+                                                        path: "src/e2e-file-change/a.txt".to_owned(),
+                                                        kind: luban_domain::CodexPatchChangeKind::Add,
+                                                    },
+                                                    luban_domain::CodexFileUpdateChange {
+                                                        path: "web/e2e-file-change/b.ts".to_owned(),
+                                                        kind: luban_domain::CodexPatchChangeKind::Update,
+                                                    },
+                                                    luban_domain::CodexFileUpdateChange {
+                                                        path: "README.md".to_owned(),
+                                                        kind: luban_domain::CodexPatchChangeKind::Delete,
+                                                    },
+                                                ],
+                                                status: luban_domain::CodexPatchApplyStatus::Completed,
+                                            },
+                                        },
+                                    }),
+                                });
+                            }
+
+                            let _ = tx.blocking_send(EngineCommand::DispatchAction {
+                                action: Box::new(Action::AgentEventReceived {
+                                    workspace_id,
+                                    thread_id,
+                                    run_id,
+                                    event: luban_domain::CodexThreadEvent::TurnFailed {
+                                        error: luban_domain::CodexThreadError {
+                                            message: "e2e agent stub".to_owned(),
+                                            code: None,
+                                        },
+                                    },
+                                }),
+                            });
+                        }
+
+                        if cancel.load(Ordering::SeqCst) {
+                            return;
+                        }
+
+                        let _ = tx.blocking_send(EngineCommand::DispatchAction {
+                            action: Box::new(Action::AgentRunFinishedAt {
+                                workspace_id,
+                                thread_id,
+                                run_id,
+                                finished_at_unix_ms: std::time::SystemTime::now()
+                                    .duration_since(std::time::UNIX_EPOCH)
+                                    .unwrap_or_default()
+                                    .as_millis()
+                                    .try_into()
+                                    .unwrap_or(0u64),
+                            }),
+                        });
+
+                        let _ = tx.blocking_send(EngineCommand::DispatchAction {
+                            action: Box::new(Action::AgentTurnFinished {
+                                workspace_id,
+                                thread_id,
+                                run_id,
+                            }),
+                        });
+                    });
+
+                    return Ok(VecDeque::from([Action::AgentRunStartedAt {
+                        workspace_id,
+                        thread_id,
+                        run_id,
+                        started_at_unix_ms,
+                    }]));
+                }
+
+                let services = self.services.clone();
+                let tx = self.tx.clone();
+                std::thread::spawn(move || {
+                    let on_event: Arc<dyn Fn(luban_domain::AgentThreadEvent) + Send + Sync> = {
+                        let tx = tx.clone();
+                        Arc::new(move |event| {
+                            let _ = tx.blocking_send(EngineCommand::DispatchAction {
+                                action: Box::new(Action::AgentEventReceived {
+                                    workspace_id,
+                                    thread_id,
+                                    run_id,
+                                    event,
+                                }),
+                            });
+                        })
+                    };
+
+                    let result =
+                        services.run_agent_turn_streamed(request, cancel.clone(), on_event);
+                    if let Err(message) = result
+                        && !cancel.load(Ordering::SeqCst)
+                    {
+                        let _ = tx.blocking_send(EngineCommand::DispatchAction {
+                            action: Box::new(Action::AgentEventReceived {
+                                workspace_id,
+                                thread_id,
+                                run_id,
+                                event: luban_domain::CodexThreadEvent::Error { message },
+                            }),
+                        });
+                    }
+
+                    if cancel.load(Ordering::SeqCst) {
+                        return;
+                    }
+
+                    let _ = tx.blocking_send(EngineCommand::DispatchAction {
+                        action: Box::new(Action::AgentRunFinishedAt {
+                            workspace_id,
+                            thread_id,
+                            run_id,
+                            finished_at_unix_ms: std::time::SystemTime::now()
+                                .duration_since(std::time::UNIX_EPOCH)
+                                .unwrap_or_default()
+                                .as_millis()
+                                .try_into()
+                                .unwrap_or(0u64),
+                        }),
+                    });
+
+                    let _ = tx.blocking_send(EngineCommand::DispatchAction {
+                        action: Box::new(Action::AgentTurnFinished {
+                            workspace_id,
+                            thread_id,
+                            run_id,
+                        }),
+                    });
+                });
+
+                Ok(VecDeque::from([Action::AgentRunStartedAt {
+                    workspace_id,
+                    thread_id,
+                    run_id,
+                    started_at_unix_ms,
+                }]))
+            }
+            Effect::CancelAgentTurn {
+                workspace_id,
+                thread_id,
+                run_id,
+            } => {
+                if let Some(idx) = self.deferred_agent_turns.iter().position(|t| {
+                    t.workspace_id == workspace_id && t.thread_id == thread_id && t.run_id == run_id
+                }) {
+                    // The runner never started, so there is nothing to stop or time.
+                    self.deferred_agent_turns.remove(idx);
+                    return Ok(VecDeque::new());
+                }
+                if let Some(entry) = self.cancel_flags.get(&(workspace_id, thread_id))
+                    && entry.run_id == run_id
+                {
+                    entry.flag.store(true, Ordering::SeqCst);
+                }
+                let finished_at_unix_ms = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis()
+                    .try_into()
+                    .unwrap_or(0u64);
+                Ok(VecDeque::from([Action::AgentRunFinishedAt {
+                    workspace_id,
+                    thread_id,
+                    run_id,
+                    finished_at_unix_ms,
+                }]))
+            }
+            Effect::RespondToToolApproval {
+                workspace_id,
+                thread_id,
+                run_id,
+                call_id,
+                approved,
+            } => {
+                let Some(scope) = workspace_scope(&self.state, workspace_id) else {
+                    return Ok(VecDeque::new());
+                };
+                let services = self.services.clone();
+                let tx = self.tx.clone();
+                tokio::spawn(async move {
+                    let delivered_call_id = call_id.clone();
+                    let result = tokio::task::spawn_blocking(move || {
+                        services.respond_to_tool_approval(
+                            &scope.project_slug,
+                            &scope.workspace_name,
+                            thread_id.as_u64(),
+                            &call_id,
+                            approved,
+                        )
+                    })
+                    .await
+                    .ok()
+                    .unwrap_or_else(|| Err("failed to join tool approval task".to_owned()));
+                    if let Err(message) = result {
+                        tracing::warn!(
+                            workspace_id = workspace_id.as_u64(),
+                            thread_id = thread_id.as_u64(),
+                            run_id,
+                            error = %message,
+                            "failed to deliver tool approval"
+                        );
+                        let _ = tx
+                            .send(EngineCommand::DispatchAction {
+                                action: Box::new(Action::ToolApprovalDeliveryFailed {
+                                    workspace_id,
+                                    thread_id,
+                                    run_id,
+                                    call_id: delivered_call_id,
+                                    message: format!("Failed to deliver tool approval: {message}"),
+                                }),
+                            })
+                            .await;
+                    }
+                });
+                Ok(VecDeque::new())
+            }
+            Effect::CleanupClaudeProcess {
+                workspace_id,
+                thread_id,
+            } => {
+                // Clean up any persistent Claude process for this thread
+                if let Some(scope) = workspace_scope(&self.state, workspace_id) {
+                    self.services.cleanup_claude_process(
+                        &scope.project_slug,
+                        &scope.workspace_name,
+                        thread_id.as_u64(),
+                    );
+                }
+                Ok(VecDeque::new())
+            }
+            Effect::OpenWorkspacePullRequest { workspace_id } => {
+                let Some(workspace) = self.state.workspace(workspace_id) else {
+                    return Ok(VecDeque::new());
+                };
+                let worktree_path = workspace.worktree_path.clone();
+                let services = self.services.clone();
+                let result = tokio::task::spawn_blocking(move || {
+                    services.gh_open_pull_request(worktree_path)
+                })
+                .await
+                .ok()
+                .unwrap_or_else(|| Err("failed to join open pull request task".to_owned()));
+                match result {
+                    Ok(()) => Ok(VecDeque::new()),
+                    Err(message) => {
+                        let _ = self.events.send(WsServerMessage::Event {
+                            rev: self.rev,
+                            event: Box::new(luban_api::ServerEvent::Toast {
+                                message: message.clone(),
+                            }),
+                        });
+                        Ok(VecDeque::from([Action::OpenWorkspacePullRequestFailed {
+                            message,
+                        }]))
+                    }
+                }
+            }
+            Effect::OpenWorkspacePullRequestFailedAction { workspace_id } => {
+                let Some(workspace) = self.state.workspace(workspace_id) else {
+                    return Ok(VecDeque::new());
+                };
+                let worktree_path = workspace.worktree_path.clone();
+                let services = self.services.clone();
+                let result = tokio::task::spawn_blocking(move || {
+                    services.gh_open_pull_request_failed_action(worktree_path)
+                })
+                .await
+                .ok()
+                .unwrap_or_else(|| {
+                    Err("failed to join open pull request failed action task".to_owned())
+                });
+                match result {
+                    Ok(()) => Ok(VecDeque::new()),
+                    Err(message) => {
+                        let _ = self.events.send(WsServerMessage::Event {
+                            rev: self.rev,
+                            event: Box::new(luban_api::ServerEvent::Toast {
+                                message: message.clone(),
+                            }),
+                        });
+                        Ok(VecDeque::from([
+                            Action::OpenWorkspacePullRequestFailedActionFailed { message },
+                        ]))
+                    }
+                }
+            }
+            Effect::OpenWorkspaceInIde { workspace_id } => {
+                let Some(workspace) = self.state.workspace(workspace_id) else {
+                    return Ok(VecDeque::new());
+                };
+
+                let services = self.services.clone();
+                let worktree_path = workspace.worktree_path.clone();
+                let result = tokio::task::spawn_blocking(move || {
+                    services.open_workspace_in_ide(worktree_path)
+                })
+                .await
+                .ok()
+                .unwrap_or_else(|| Err("failed to join open workspace in ide task".to_owned()));
+
+                match result {
+                    Ok(()) => Ok(VecDeque::new()),
+                    Err(message) => {
+                        let _ = self.events.send(WsServerMessage::Event {
+                            rev: self.rev,
+                            event: Box::new(luban_api::ServerEvent::Toast {
+                                message: message.clone(),
+                            }),
+                        });
+                        Ok(VecDeque::from([Action::OpenWorkspaceInIdeFailed {
+                            message,
+                        }]))
+                    }
+                }
+            }
+            Effect::OpenWorkspaceWith {
+                workspace_id,
+                target,
+            } => {
+                let Some(workspace) = self.state.workspace(workspace_id) else {
+                    return Ok(VecDeque::new());
+                };
+
+                let services = self.services.clone();
+                let worktree_path = workspace.worktree_path.clone();
+                let result = tokio::task::spawn_blocking(move || {
+                    services.open_workspace_with(worktree_path, target)
+                })
+                .await
+                .ok()
+                .unwrap_or_else(|| Err("failed to join open workspace with task".to_owned()));
+
+                match result {
+                    Ok(()) => Ok(VecDeque::new()),
+                    Err(message) => {
+                        let _ = self.events.send(WsServerMessage::Event {
+                            rev: self.rev,
+                            event: Box::new(luban_api::ServerEvent::Toast {
+                                message: message.clone(),
+                            }),
+                        });
+                        Ok(VecDeque::from([Action::OpenWorkspaceWithFailed {
+                            message,
+                        }]))
+                    }
+                }
+            }
+            Effect::ArchiveWorkspace { workspace_id } => {
+                let scope = workspace_scope(&self.state, workspace_id);
+                let should_emit_task_archived_events =
+                    self.auto_archive_workspaces.contains(&workspace_id);
+
+                let mut claude_cleanup_threads = Vec::new();
+                let (project_slug, workspace_name) = scope
+                    .as_ref()
+                    .map(|s| (s.project_slug.clone(), s.workspace_name.clone()))
+                    .unwrap_or_default();
+                if !project_slug.is_empty() && !workspace_name.is_empty() {
+                    for (wid, thread_id) in self.state.conversations.keys() {
+                        if *wid != workspace_id {
+                            continue;
+                        }
+                        claude_cleanup_threads.push(thread_id.as_u64());
+                    }
+                }
+
+                let mut project_path: Option<PathBuf> = None;
+                let mut worktree_path: Option<PathBuf> = None;
+                let mut branch_name: Option<String> = None;
+
+                for project in &self.state.projects {
+                    for workspace in &project.workspaces {
+                        if workspace.id == workspace_id {
+                            project_path = Some(project.path.clone());
+                            worktree_path = Some(workspace.worktree_path.clone());
+                            branch_name = Some(workspace.branch_name.clone());
+                            break;
+                        }
+                    }
+                    if project_path.is_some() {
+                        break;
+                    }
+                }
+
+                let (Some(project_path), Some(worktree_path), Some(branch_name)) =
+                    (project_path, worktree_path, branch_name)
+                else {
+                    return Ok(VecDeque::from([Action::WorkspaceArchiveFailed {
+                        workspace_id,
+                        message: "workspace not found".to_owned(),
+                    }]));
+                };
+
+                let services = self.services.clone();
+                let tx = self.tx.clone();
+                tokio::task::spawn_blocking(move || {
+                    for thread_id in claude_cleanup_threads {
+                        services.cleanup_claude_process(&project_slug, &workspace_name, thread_id);
+                    }
+
+                    let result: Result<(), String> = (|| {
+                        services.archive_workspace(project_path, worktree_path, branch_name)?;
+                        if !should_emit_task_archived_events {
+                            return Ok(());
+                        }
+                        if project_slug.is_empty() || workspace_name.is_empty() {
+                            return Ok(());
+                        }
+
+                        let threads = services.list_conversation_threads(
+                            project_slug.clone(),
+                            workspace_name.clone(),
+                        )?;
+                        for meta in threads {
+                            if !matches!(
+                                meta.task_status,
+                                luban_domain::TaskStatus::Done | luban_domain::TaskStatus::Canceled
+                            ) {
+                                continue;
+                            }
+
+                            let recent = services.load_conversation_page(
+                                project_slug.clone(),
+                                workspace_name.clone(),
+                                meta.thread_id.as_u64(),
+                                None,
+                                32,
+                            )?;
+                            let already_archived = recent.entries.iter().any(|entry| {
+                                matches!(
+                                    entry,
+                                    luban_domain::ConversationEntry::SystemEvent { event, .. }
+                                        if matches!(
+                                            event,
+                                            luban_domain::ConversationSystemEvent::TaskArchived
+                                        )
+                                )
+                            });
+                            if already_archived {
+                                continue;
+                            }
+
+                            services.append_conversation_entries(
+                                project_slug.clone(),
+                                workspace_name.clone(),
+                                meta.thread_id.as_u64(),
+                                vec![luban_domain::ConversationEntry::SystemEvent {
+                                    entry_id: String::new(),
+                                    created_at_unix_ms: now_unix_ms(),
+                                    event: luban_domain::ConversationSystemEvent::TaskArchived,
+                                }],
+                            )?;
+                        }
+                        Ok(())
+                    })();
+
+                    let action = match result {
+                        Ok(()) => Action::WorkspaceArchived { workspace_id },
+                        Err(message) => Action::WorkspaceArchiveFailed {
+                            workspace_id,
+                            message,
+                        },
+                    };
+                    let _ = tx.blocking_send(EngineCommand::DispatchAction {
+                        action: Box::new(action),
+                    });
+                });
+
+                Ok(VecDeque::new())
+            }
+            Effect::MaybeAutoArchiveWorkspace { workspace_id } => {
+                let Some(scope) = workspace_scope(&self.state, workspace_id) else {
+                    return Ok(VecDeque::new());
+                };
+
+                let mut project_is_git = false;
+                let mut workspace_is_main = false;
+                let mut workspace_status = None;
+                let mut archive_status = None;
+                for project in &self.state.projects {
+                    for workspace in &project.workspaces {
+                        if workspace.id != workspace_id {
+                            continue;
+                        }
+                        project_is_git = project.is_git;
+                        workspace_is_main = workspace.workspace_name == "main";
+                        workspace_status = Some(workspace.status);
+                        archive_status = Some(workspace.archive_status);
+                        break;
+                    }
+                }
+
+                if !project_is_git
+                    || workspace_is_main
+                    || workspace_status != Some(luban_domain::WorkspaceStatus::Active)
+                    || archive_status == Some(luban_domain::OperationStatus::Running)
+                {
+                    return Ok(VecDeque::new());
+                }
+
+                let services = self.services.clone();
+                let project_slug = scope.project_slug.clone();
+                let workspace_name = scope.workspace_name.clone();
+                let result = tokio::task::spawn_blocking(move || {
+                    let threads =
+                        services.list_conversation_threads(project_slug, workspace_name)?;
+                    if threads.is_empty() {
+                        return Ok(false);
+                    }
+                    let all_closed_and_idle = threads.iter().all(|t| {
+                        matches!(
+                            t.task_status,
+                            luban_domain::TaskStatus::Done | luban_domain::TaskStatus::Canceled
+                        ) && t.turn_status == luban_domain::TurnStatus::Idle
+                    });
+                    Ok(all_closed_and_idle)
+                })
+                .await
+                .ok()
+                .unwrap_or_else(|| Err("failed to join maybe archive workspace task".to_owned()));
+
+                let Ok(should_archive) = result else {
+                    return Ok(VecDeque::new());
+                };
+                if !should_archive {
+                    return Ok(VecDeque::new());
+                }
+
+                self.auto_archive_workspaces.insert(workspace_id);
+                Ok(VecDeque::from([Action::ArchiveWorkspace { workspace_id }]))
+            }
+        }
+    }
+
+    fn publish_app_snapshot(&self) {
+        let _ = self.events.send(WsServerMessage::Event {
+            rev: self.rev,
+            event: Box::new(luban_api::ServerEvent::AppChanged {
+                rev: self.rev,
+                snapshot: Box::new(without_archived_projects(self.app_snapshot())),
+            }),
+        });
+    }
+
+    fn publish_threads_event(
+        &self,
+        workspace_id: WorkspaceId,
+        threads: &[luban_domain::ConversationThreadMeta],
+    ) {
+        let api_id = luban_api::WorkspaceId(workspace_id.as_u64());
+        let tabs = self
+            .state
+            .workspace_tabs(workspace_id)
+            .map(map_workspace_tabs_snapshot)
+            .unwrap_or_default();
+        let mut seen_thread_ids = HashSet::<WorkspaceThreadId>::new();
+        let threads = threads
+            .iter()
+            .filter(|t| seen_thread_ids.insert(t.thread_id))
+            .map(|t| luban_api::ThreadMeta {
+                thread_id: luban_api::WorkspaceThreadId(t.thread_id.as_u64()),
+                remote_thread_id: t.remote_thread_id.clone(),
+                title: t.title.clone(),
+                created_at_unix_seconds: t.created_at_unix_seconds,
+                updated_at_unix_seconds: t.updated_at_unix_seconds,
+                task_status: match t.task_status {
+                    luban_domain::TaskStatus::Backlog => luban_api::TaskStatus::Backlog,
+                    luban_domain::TaskStatus::Todo => luban_api::TaskStatus::Todo,
+                    luban_domain::TaskStatus::Iterating => luban_api::TaskStatus::Iterating,
+                    luban_domain::TaskStatus::Validating => luban_api::TaskStatus::Validating,
+                    luban_domain::TaskStatus::Done => luban_api::TaskStatus::Done,
+                    luban_domain::TaskStatus::Canceled => luban_api::TaskStatus::Canceled,
+                },
+                turn_status: match t.turn_status {
+                    _ if self.agent_turn_is_deferred(workspace_id, t.thread_id) => {
+                        luban_api::TurnStatus::Awaiting
+                    }
+                    luban_domain::TurnStatus::Idle => luban_api::TurnStatus::Idle,
+                    luban_domain::TurnStatus::Running => luban_api::TurnStatus::Running,
+                    luban_domain::TurnStatus::Awaiting => luban_api::TurnStatus::Awaiting,
+                    luban_domain::TurnStatus::Paused => luban_api::TurnStatus::Paused,
+                },
+                last_turn_result: t.last_turn_result.map(|v| match v {
+                    luban_domain::TurnResult::Completed => luban_api::TurnResult::Completed,
+                    luban_domain::TurnResult::Failed => luban_api::TurnResult::Failed,
+                }),
+                total_tokens: t.total_tokens,
+            })
+            .collect::<Vec<_>>();
+
+        let _ = self.events.send(WsServerMessage::Event {
+            rev: self.rev,
+            event: Box::new(luban_api::ServerEvent::WorkspaceThreadsChanged {
+                workspace_id: api_id,
+                tabs,
+                threads,
+            }),
+        });
+    }
+
+    fn publish_task_summaries_event(&self, workspace_id: WorkspaceId) {
+        let Some((project_id, tasks)) = self.workspace_task_summaries(workspace_id) else {
+            return;
+        };
+
+        let _ = self.events.send(WsServerMessage::Event {
+            rev: self.rev,
+            event: Box::new(luban_api::ServerEvent::TaskSummariesChanged {
+                project_id,
+                workspace_id: luban_api::WorkspaceId(workspace_id.as_u64()),
+                tasks,
+            }),
+        });
+    }
+
+    /// Summarizes the cached threads of a workspace; `None` when the workspace or its thread
+    /// list is not known yet.
+    fn workspace_task_summaries(
+        &self,
+        workspace_id: WorkspaceId,
+    ) -> Option<(luban_api::ProjectId, Vec<luban_api::TaskSummarySnapshot>)> {
+        let (project_id, workspace) = self.state.projects.iter().find_map(|project| {
+            project
+                .workspaces
+                .iter()
+                .find(|w| w.id == workspace_id)
+                .map(|workspace| {
+                    (
+                        luban_api::ProjectId(project.path.to_string_lossy().to_string()),
+                        workspace,
+                    )
+                })
+        })?;
+
+        let threads = self.workspace_threads_cache.get(&workspace_id)?;
+
+        let active_thread_id = self
+            .state
+            .workspace_tabs
+            .get(&workspace_id)
+            .map(|tabs| tabs.active_tab)
+            .unwrap_or(WorkspaceThreadId::from_u64(1));
+
+        let workspace_has_running_turn = self.state.workspace_has_running_turn(workspace_id);
+        let workspace_has_unread_completion =
+            self.state.workspace_has_unread_completion(workspace_id);
+
+        let tasks = threads
+            .iter()
+            .map(|t| luban_api::TaskSummarySnapshot {
+                project_id: project_id.clone(),
+                workspace_id: luban_api::WorkspaceId(workspace_id.as_u64()),
+                thread_id: luban_api::WorkspaceThreadId(t.thread_id.as_u64()),
+                title: t.title.clone(),
+                created_at_unix_seconds: t.created_at_unix_seconds,
+                updated_at_unix_seconds: t.updated_at_unix_seconds,
+                branch_name: workspace.branch_name.clone(),
+                workspace_name: workspace.workspace_name.clone(),
+                agent_run_status: if workspace_has_running_turn && t.thread_id == active_thread_id {
+                    luban_api::OperationStatus::Running
+                } else {
+                    luban_api::OperationStatus::Idle
+                },
+                has_unread_completion: workspace_has_unread_completion
+                    && t.thread_id == active_thread_id,
+                task_status: map_domain_task_status(t.task_status),
+                turn_status: if self.agent_turn_is_deferred(workspace_id, t.thread_id) {
+                    luban_api::TurnStatus::Awaiting
+                } else {
+                    map_domain_turn_status(
+                        self.state
+                            .workspace_thread_conversation(workspace_id, t.thread_id)
+                            .map_or(t.turn_status, |conversation| conversation.turn_status()),
+                    )
+                },
+                last_turn_result: t.last_turn_result.map(map_domain_turn_result),
+                is_starred: self
+                    .state
+                    .starred_tasks
+                    .contains(&(workspace_id, t.thread_id)),
+            })
+            .collect::<Vec<_>>();
+
+        Some((project_id, tasks))
+    }
+
+    async fn starred_task_summaries(
+        &mut self,
+    ) -> Result<Vec<luban_api::TaskSummarySnapshot>, String> {
+        let workspace_ids = self
+            .state
+            .starred_tasks
+            .iter()
+            .map(|(workspace_id, _)| *workspace_id)
+            .collect::<HashSet<_>>();
+
+        let mut tasks = Vec::new();
+        for workspace_id in workspace_ids {
+            if !self.workspace_threads_cache.contains_key(&workspace_id) {
+                let Some(scope) = workspace_scope(&self.state, workspace_id) else {
+                    continue;
+                };
+                let services = self.services.clone();
+                let mut threads = tokio::task::spawn_blocking(move || {
+                    services.list_conversation_threads(scope.project_slug, scope.workspace_name)
+                })
+                .await
+                .unwrap_or_else(|_| Err("failed to join list threads task".to_owned()))?;
+                dedup_thread_metas_in_place(&mut threads);
+                self.workspace_threads_cache.insert(workspace_id, threads);
+            }
+
+            if let Some((_, summaries)) = self.workspace_task_summaries(workspace_id) {
+                tasks.extend(summaries.into_iter().filter(|task| task.is_starred));
+            }
+        }
+
+        tasks.sort_by(|a, b| {
+            b.updated_at_unix_seconds
+                .cmp(&a.updated_at_unix_seconds)
+                .then_with(|| b.created_at_unix_seconds.cmp(&a.created_at_unix_seconds))
+        });
+        Ok(tasks)
+    }
+
+    /// Drops per-conversation revs and cached thread lists that belong to deleted or moved
+    /// threads, or to workspaces that are no longer active.
+    fn prune_conversation_caches(&mut self, prune: ConversationCachePrune) {
+        match prune {
+            ConversationCachePrune::Threads(keys) => {
+                for key in keys {
+                    self.conversation_revs.remove(&key);
+                }
+            }
+            ConversationCachePrune::InactiveWorkspaces => {
+                let active = self
+                    .state
+                    .projects
+                    .iter()
+                    .filter(|p| p.status == ProjectStatus::Active)
+                    .flat_map(|p| p.workspaces.iter())
+                    .filter(|w| w.status == luban_domain::WorkspaceStatus::Active)
+                    .map(|w| w.id)
+                    .collect::<HashSet<_>>();
+                self.conversation_revs
+                    .retain(|(wid, _), _| active.contains(wid));
+                self.workspace_threads_cache
+                    .retain(|wid, _| active.contains(wid));
+            }
+        }
+    }
+
+    fn worktree_dirty_for(&self, workspace_id: WorkspaceId) -> bool {
+        self.worktree_dirty
+            .get(&workspace_id)
+            .is_some_and(|entry| entry.dirty)
+    }
+
+    /// Publishes the in-memory conversation, with `entries_total` taken from the store so the
+    /// client keeps offering older pages after the in-memory cap trimmed them.
+    async fn publish_conversation_snapshot(
+        &self,
+        workspace_id: WorkspaceId,
+        thread_id: WorkspaceThreadId,
+        follow: Option<bool>,
+    ) {
+        let api_wid = luban_api::WorkspaceId(workspace_id.as_u64());
+        let api_tid = luban_api::WorkspaceThreadId(thread_id.as_u64());
+        let rev = self.rev;
+        let Ok(mut snapshot) = self.conversation_snapshot(api_wid, api_tid, None, None) else {
+            return;
+        };
+        if let Some(scope) = workspace_scope(&self.state, workspace_id) {
+            let services = self.services.clone();
+            let stored_total = tokio::task::spawn_blocking(move || {
+                services.conversation_entries_total(
+                    scope.project_slug,
+                    scope.workspace_name,
+                    thread_id.as_u64(),
+                )
+            })
+            .await
+            .ok()
+            .and_then(Result::ok);
+            if let Some(stored_total) = stored_total {
+                apply_stored_entries_total(&mut snapshot, stored_total);
+            }
+        }
+        self.fill_attachment_thumbnails(workspace_id, &mut snapshot)
+            .await;
+        let _ = self.events.send(WsServerMessage::Event {
+            rev,
+            event: Box::new(luban_api::ServerEvent::ConversationChanged {
+                snapshot: Box::new(snapshot),
+                follow,
+            }),
+        });
+    }
+
+    fn app_snapshot(&self) -> AppSnapshot {
+        let mut running_workspaces = std::collections::HashSet::<WorkspaceId>::new();
+        let mut running_turns = 0u64;
+        let mut queued_prompts_total = 0u64;
+        for ((workspace_id, thread_id), conversation) in &self.state.conversations {
+            if conversation.run_status == OperationStatus::Running
+                && !self.agent_turn_is_deferred(*workspace_id, *thread_id)
+            {
+                running_workspaces.insert(*workspace_id);
+                running_turns += 1;
+            }
+            queued_prompts_total += conversation.pending_prompts.len() as u64;
+        }
+
+        AppSnapshot {
+            rev: self.rev,
+            projects: self
+                .state
+                .projects
+                .iter()
+                .map(|p| {
+                    let path = p.path.to_string_lossy().to_string();
+                    luban_api::ProjectSnapshot {
+                        id: luban_api::ProjectId(path.clone()),
+                        name: p.name.clone(),
+                        slug: p.slug.clone(),
+                        path,
+                        is_git: p.is_git,
+                        expanded: p.expanded,
+                        system_prompt: p.system_prompt.clone(),
+                        worktree_root: p
+                            .worktree_root
+                            .as_ref()
+                            .map(|root| root.to_string_lossy().into_owned()),
+                        branch_name_template: p.branch_name_template.clone(),
+                        pr_remote: p.pr_remote.clone(),
+                        pr_base_branch: p.pr_base_branch.clone(),
+                        status: match p.status {
+                            ProjectStatus::Active => luban_api::ProjectStatus::Active,
+                            ProjectStatus::Archived => luban_api::ProjectStatus::Archived,
+                        },
+                        agent_defaults: luban_api::ProjectAgentDefaultsSnapshot {
+                            runner: p.agent_defaults.runner.map(map_agent_runner_kind),
+                            model_id: p.agent_defaults.model_id.clone(),
+                            thinking_effort: p
+                                .agent_defaults
+                                .thinking_effort
+                                .map(map_thinking_effort),
+                            amp_mode: p.agent_defaults.amp_mode.clone(),
+                        },
+                        saved_commands: p
+                            .saved_commands
+                            .iter()
+                            .map(|c| luban_api::ProjectSavedCommandSnapshot {
+                                id: c.id,
+                                label: c.label.clone(),
+                                command: c.command.clone(),
+                            })
+                            .collect(),
+                        agent_env: p
+                            .agent_env
+                            .iter()
+                            .map(|(key, value)| luban_api::ProjectAgentEnvVarSnapshot {
+                                key: key.clone(),
+                                has_value: !value.is_empty(),
+                            })
+                            .collect(),
+                        create_workspace_status: match p.create_workspace_status {
+                            OperationStatus::Idle => luban_api::OperationStatus::Idle,
+                            OperationStatus::Running => luban_api::OperationStatus::Running,
+                        },
+                        workspaces: p
+                            .workspaces
+                            .iter()
+                            .map(|w| luban_api::WorkspaceSnapshot {
+                                id: luban_api::WorkspaceId(w.id.as_u64()),
+                                short_id: workspace_short_id(&p.slug, w.id.as_u64()),
+                                workspace_name: w.workspace_name.clone(),
+                                branch_name: w.branch_name.clone(),
+                                detached_head: w.is_detached_head(),
+                                worktree_path: w.worktree_path.to_string_lossy().to_string(),
+                                status: match w.status {
+                                    luban_domain::WorkspaceStatus::Active => {
+                                        luban_api::WorkspaceStatus::Active
+                                    }
+                                    luban_domain::WorkspaceStatus::Archived => {
+                                        luban_api::WorkspaceStatus::Archived
+                                    }
+                                },
+                                archive_status: match w.archive_status {
+                                    OperationStatus::Idle => luban_api::OperationStatus::Idle,
+                                    OperationStatus::Running => luban_api::OperationStatus::Running,
+                                },
+                                branch_rename_status: match w.branch_rename_status {
+                                    OperationStatus::Idle => luban_api::OperationStatus::Idle,
+                                    OperationStatus::Running => luban_api::OperationStatus::Running,
+                                },
+                                stash_status: match w.stash_status {
+                                    OperationStatus::Idle => luban_api::OperationStatus::Idle,
+                                    OperationStatus::Running => luban_api::OperationStatus::Running,
+                                },
+                                commit_status: match w.commit_status {
+                                    OperationStatus::Idle => luban_api::OperationStatus::Idle,
+                                    OperationStatus::Running => luban_api::OperationStatus::Running,
+                                },
+                                agent_run_status: if running_workspaces.contains(&w.id) {
+                                    luban_api::OperationStatus::Running
+                                } else {
+                                    luban_api::OperationStatus::Idle
+                                },
+                                has_unread_completion: self
+                                    .state
+                                    .workspace_unread_completions
+                                    .contains(&w.id),
+                                pull_request: self
+                                    .pull_requests
+                                    .get(&w.id)
+                                    .and_then(|entry| entry.info)
+                                    .map(map_pull_request_info),
+                                pull_request_error: self
+                                    .pull_requests
+                                    .get(&w.id)
+                                    .and_then(|entry| entry.error.clone()),
+                                last_commit: self.last_commits.get(&w.id).cloned().flatten().map(
+                                    |c| luban_api::LastCommitSnapshot {
+                                        sha: c.sha,
+                                        subject: c.subject,
+                                        author: c.author,
+                                        unix_time: c.unix_time,
+                                    },
+                                ),
+                            })
+                            .collect(),
+                    }
+                })
+                .collect(),
+            project_groups: self
+                .state
+                .project_groups
+                .iter()
+                .map(|g| luban_api::ProjectGroupSnapshot {
+                    id: luban_api::ProjectGroupId(g.id.as_u64()),
+                    name: g.name.clone(),
+                    project_ids: g
+                        .project_ids
+                        .iter()
+                        .filter_map(|id| self.state.project(*id))
+                        .map(|p| luban_api::ProjectId(p.path.to_string_lossy().to_string()))
+                        .collect(),
+                })
+                .collect(),
+            appearance: luban_api::AppearanceSnapshot {
+                theme: match self.state.appearance_theme {
+                    luban_domain::AppearanceTheme::Light => luban_api::AppearanceTheme::Light,
+                    luban_domain::AppearanceTheme::Dark => luban_api::AppearanceTheme::Dark,
+                    luban_domain::AppearanceTheme::System => luban_api::AppearanceTheme::System,
+                },
+                fonts: luban_api::AppearanceFontsSnapshot {
+                    ui_font: self.state.appearance_fonts.ui_font.clone(),
+                    chat_font: self.state.appearance_fonts.chat_font.clone(),
+                    code_font: self.state.appearance_fonts.code_font.clone(),
+                    terminal_font: self.state.appearance_fonts.terminal_font.clone(),
+                },
+                global_zoom: (self.state.global_zoom_percent as f64) / 100.0,
+            },
+            agent: luban_api::AgentSettingsSnapshot {
+                codex_enabled: self.state.agent_codex_enabled(),
+                amp_enabled: self.state.agent_amp_enabled(),
+                claude_enabled: self.state.agent_claude_enabled(),
+                droid_enabled: self.state.agent_droid_enabled(),
+                default_model_id: Some(self.state.agent_default_model_id().to_owned()),
+                runner_default_models: self
+                    .state
+                    .agent_runner_default_models()
+                    .iter()
+                    .map(|(k, v)| (k.as_str().to_owned(), v.clone()))
+                    .collect(),
+                runner_prompt_prefixes: self
+                    .state
+                    .agent_runner_prompt_prefixes()
+                    .iter()
+                    .map(|(k, v)| (k.as_str().to_owned(), v.clone()))
+                    .collect(),
+                runner_prompt_suffixes: self
+                    .state
+                    .agent_runner_prompt_suffixes()
+                    .iter()
+                    .map(|(k, v)| (k.as_str().to_owned(), v.clone()))
+                    .collect(),
+                model_thinking_effort_caps: self
+                    .state
+                    .model_thinking_effort_caps()
+                    .iter()
+                    .map(|(model_id, cap)| (model_id.clone(), map_thinking_effort(*cap)))
+                    .collect(),
+                default_thinking_effort: Some(match self.state.agent_default_thinking_effort() {
+                    ThinkingEffort::Minimal => luban_api::ThinkingEffort::Minimal,
+                    ThinkingEffort::Low => luban_api::ThinkingEffort::Low,
+                    ThinkingEffort::Medium => luban_api::ThinkingEffort::Medium,
+                    ThinkingEffort::High => luban_api::ThinkingEffort::High,
+                    ThinkingEffort::XHigh => luban_api::ThinkingEffort::XHigh,
+                }),
+                default_runner: Some(match self.state.agent_default_runner() {
+                    luban_domain::AgentRunnerKind::Codex => luban_api::AgentRunnerKind::Codex,
+                    luban_domain::AgentRunnerKind::Amp => luban_api::AgentRunnerKind::Amp,
+                    luban_domain::AgentRunnerKind::Claude => luban_api::AgentRunnerKind::Claude,
+                    luban_domain::AgentRunnerKind::Droid => luban_api::AgentRunnerKind::Droid,
+                }),
+                amp_mode: Some(luban_api::AmpMode::parse(self.state.agent_amp_mode())),
+                merge_reasoning_entries: self.state.agent_merge_reasoning_entries(),
+                pause_queue_on_patch_failure: self.state.agent_pause_queue_on_patch_failure(),
+                item_dedup_window: self.state.agent_item_dedup_window() as u64,
+                auto_retry_on_failure: self.state.agent_auto_retry_on_failure(),
+                command_output_max_bytes: self.state.agent_command_output_max_bytes() as u64,
+            },
+            task: luban_api::TaskSettingsSnapshot {
+                prompt_templates: luban_domain::TaskIntentKind::ALL
+                    .iter()
+                    .copied()
+                    .filter_map(|kind| {
+                        self.state.task_prompt_templates.get(&kind).map(|template| {
+                            luban_api::TaskPromptTemplateSnapshot {
+                                intent_kind: map_task_intent_kind(kind),
+                                template: template.clone(),
+                            }
+                        })
+                    })
+                    .collect(),
+                default_prompt_templates: luban_domain::TaskIntentKind::ALL
+                    .iter()
+                    .copied()
+                    .map(|kind| luban_api::TaskPromptTemplateSnapshot {
+                        intent_kind: map_task_intent_kind(kind),
+                        template: luban_domain::default_task_prompt_template(kind),
+                    })
+                    .collect(),
+                system_prompt_templates: luban_domain::SystemTaskKind::ALL
+                    .iter()
+                    .copied()
+                    .filter_map(|kind| {
+                        self.state
+                            .system_prompt_templates
+                            .get(&kind)
+                            .map(|template| luban_api::SystemPromptTemplateSnapshot {
+                                kind: map_system_task_kind(kind),
+                                template: template.clone(),
+                            })
+                    })
+                    .collect(),
+                default_system_prompt_templates: luban_domain::SystemTaskKind::ALL
+                    .iter()
+                    .copied()
+                    .map(|kind| luban_api::SystemPromptTemplateSnapshot {
+                        kind: map_system_task_kind(kind),
+                        template: luban_domain::default_system_prompt_template(kind),
+                    })
+                    .collect(),
+            },
+            ui: {
+                let active_workspace_id = match self.state.main_pane {
+                    luban_domain::MainPane::Workspace(id) => Some(id),
+                    _ => self.state.last_open_workspace_id,
+                };
+                let active_thread_id =
+                    active_workspace_id.and_then(|id| self.state.active_thread_id(id));
+                luban_api::UiSnapshot {
+                    active_workspace_id: active_workspace_id
+                        .map(|id| luban_api::WorkspaceId(id.as_u64())),
+                    active_thread_id: active_thread_id
+                        .map(|id| luban_api::WorkspaceThreadId(id.as_u64())),
+                    open_button_selection: self.state.open_button_selection.clone(),
+                    sidebar_project_order: self
+                        .state
+                        .sidebar_project_order
+                        .iter()
+                        .cloned()
+                        .map(luban_api::ProjectId)
+                        .collect(),
+                }
+            },
+            integrations: luban_api::IntegrationsSnapshot {
+                telegram: luban_api::TelegramIntegrationSnapshot {
+                    enabled: self.state.telegram_enabled(),
+                    has_token: self.state.telegram_bot_token().is_some(),
+                    bot_username: self.state.telegram_bot_username().map(ToOwned::to_owned),
+                    paired_chat_ids: self.state.telegram_paired_chat_ids().to_vec(),
+                    config_rev: self.state.telegram_config_rev(),
+                    last_error: self.state.telegram_last_error().map(ToOwned::to_owned),
+                },
+                webhook: luban_api::WebhookIntegrationSnapshot {
+                    enabled: self.state.webhook_url().is_some(),
+                    url: self.state.webhook_url().map(ToOwned::to_owned),
+                    has_secret: self.state.webhook_secret().is_some(),
+                    config_rev: self.state.webhook_config_rev(),
+                    last_error: self.state.webhook_last_error().map(ToOwned::to_owned),
+                },
+            },
+            running_turns,
+            waiting_turns: self.deferred_agent_turns.len() as u64,
+            max_concurrent_turns: self.config.max_concurrent_turns as u64,
+            conversation_retention: luban_api::ConversationRetentionSnapshot {
+                keep_last_entries: self.config.conversation_retention.keep_last_entries,
+                max_age_days: self.config.conversation_retention.max_age_days,
+            },
+            auto_archive_after_days: self.state.auto_archive_after_days(),
+            queued_prompts_total,
+            main_pane: match self.state.main_pane {
+                luban_domain::MainPane::None => luban_api::MainPaneSnapshot::None,
+                luban_domain::MainPane::Dashboard => luban_api::MainPaneSnapshot::Dashboard,
+                luban_domain::MainPane::ProjectSettings(id) => {
+                    match self.state.projects.iter().find(|p| p.id == id) {
+                        Some(project) => luban_api::MainPaneSnapshot::ProjectSettings {
+                            project_id: luban_api::ProjectId(
+                                project.path.to_string_lossy().to_string(),
+                            ),
+                        },
+                        None => luban_api::MainPaneSnapshot::None,
+                    }
+                }
+                luban_domain::MainPane::Workspace(id) => luban_api::MainPaneSnapshot::Workspace {
+                    workspace_id: luban_api::WorkspaceId(id.as_u64()),
+                },
+            },
+            errors: self
+                .state
+                .error_log
+                .iter()
+                .map(|entry| luban_api::ErrorLogEntrySnapshot {
+                    message: entry.message.clone(),
+                    created_at_unix_ms: entry.created_at_unix_ms,
+                })
+                .collect(),
+        }
+    }
+
+    // Threads snapshots are served via `ProjectWorkspaceService::list_conversation_threads` in the command handler.
+
+    fn conversation_snapshot(
+        &self,
+        workspace_id: luban_api::WorkspaceId,
+        thread_id: luban_api::WorkspaceThreadId,
+        before: Option<u64>,
+        limit: Option<u64>,
+    ) -> anyhow::Result<ConversationSnapshot> {
+        let limit = self.conversation_entries_limit(limit);
+
+        let wid = WorkspaceId::from_u64(workspace_id.0);
+        let tid = WorkspaceThreadId::from_u64(thread_id.0);
+        let Some(conversation) = self.state.workspace_thread_conversation(wid, tid) else {
+            return Err(anyhow::anyhow!("conversation not found"));
+        };
+
+        let window_start = usize::try_from(conversation.entries_start).unwrap_or(0);
+        let window_end = window_start.saturating_add(conversation.entries.len());
+        let total_entries = usize::try_from(conversation.entries_total).unwrap_or(window_end);
+
+        let before = before
+            .and_then(|v| usize::try_from(v).ok())
+            .unwrap_or(total_entries)
+            .min(total_entries);
+        let end = before;
+        let start = end.saturating_sub(limit);
+        let entries_truncated = start > 0 || end < total_entries;
+
+        if start < window_start || end > window_end {
+            return Err(anyhow::anyhow!("requested slice is not in memory"));
+        }
+
+        let local_start = start.saturating_sub(window_start);
+        let local_end = end.saturating_sub(window_start);
+        let (claude_max_output_tokens, claude_system_prompt) =
+            conversation.claude_run_options(conversation.agent_runner);
+        let total_usage = conversation.total_usage.clone();
+
+        Ok(ConversationSnapshot {
+            rev: self.rev,
+            workspace_id,
+            thread_id,
+            task_status: match conversation.task_status {
+                luban_domain::TaskStatus::Backlog => luban_api::TaskStatus::Backlog,
+                luban_domain::TaskStatus::Todo => luban_api::TaskStatus::Todo,
+                luban_domain::TaskStatus::Iterating => luban_api::TaskStatus::Iterating,
+                luban_domain::TaskStatus::Validating => luban_api::TaskStatus::Validating,
+                luban_domain::TaskStatus::Done => luban_api::TaskStatus::Done,
+                luban_domain::TaskStatus::Canceled => luban_api::TaskStatus::Canceled,
+            },
+            agent_runner: match conversation.agent_runner {
+                luban_domain::AgentRunnerKind::Codex => luban_api::AgentRunnerKind::Codex,
+                luban_domain::AgentRunnerKind::Amp => luban_api::AgentRunnerKind::Amp,
+                luban_domain::AgentRunnerKind::Claude => luban_api::AgentRunnerKind::Claude,
+                luban_domain::AgentRunnerKind::Droid => luban_api::AgentRunnerKind::Droid,
+            },
+            agent_model_id: conversation.agent_model_id.clone(),
+            thinking_effort: match conversation.thinking_effort {
+                ThinkingEffort::Minimal => luban_api::ThinkingEffort::Minimal,
+                ThinkingEffort::Low => luban_api::ThinkingEffort::Low,
+                ThinkingEffort::Medium => luban_api::ThinkingEffort::Medium,
+                ThinkingEffort::High => luban_api::ThinkingEffort::High,
+                ThinkingEffort::XHigh => luban_api::ThinkingEffort::XHigh,
+            },
+            run_thinking_effort: conversation
+                .current_run_config
+                .as_ref()
+                .map(|config| map_thinking_effort(config.thinking_effort)),
+            amp_mode: if conversation.agent_runner == luban_domain::AgentRunnerKind::Amp {
+                conversation
+                    .amp_mode
+                    .as_deref()
+                    .or(Some(self.state.agent_amp_mode()))
+                    .map(luban_api::AmpMode::parse)
+            } else {
+                None
+            },
+            claude_max_output_tokens,
+            claude_system_prompt,
+            auto_commit: conversation.auto_commit,
+            run_status: match conversation.run_status {
+                OperationStatus::Idle => luban_api::OperationStatus::Idle,
+                OperationStatus::Running => luban_api::OperationStatus::Running,
+            },
+            turn_status: if self.agent_turn_is_deferred(wid, tid) {
+                luban_api::TurnStatus::Awaiting
+            } else {
+                map_domain_turn_status(conversation.turn_status())
+            },
+            run_started_at_unix_ms: conversation.run_started_at_unix_ms,
+            run_finished_at_unix_ms: conversation.run_finished_at_unix_ms,
+            entries: conversation
+                .entries
+                .get(local_start..local_end)
+                .unwrap_or_default()
+                .iter()
+                .map(map_conversation_entry)
+                .collect(),
+            entries_total: total_entries as u64,
+            entries_start: start as u64,
+            entries_truncated,
+            pending_prompts: conversation
+                .pending_prompts
+                .iter()
+                .map(|prompt| luban_api::QueuedPromptSnapshot {
+                    id: prompt.id,
+                    text: prompt.text.clone(),
+                    attachments: prompt.attachments.iter().map(map_attachment_ref).collect(),
+                    run_config: luban_api::AgentRunConfigSnapshot {
+                        runner: match prompt.run_config.runner {
+                            luban_domain::AgentRunnerKind::Codex => {
+                                luban_api::AgentRunnerKind::Codex
+                            }
+                            luban_domain::AgentRunnerKind::Amp => luban_api::AgentRunnerKind::Amp,
+                            luban_domain::AgentRunnerKind::Claude => {
+                                luban_api::AgentRunnerKind::Claude
+                            }
+                            luban_domain::AgentRunnerKind::Droid => {
+                                luban_api::AgentRunnerKind::Droid
+                            }
+                        },
+                        model_id: prompt.run_config.model_id.clone(),
+                        thinking_effort: match prompt.run_config.thinking_effort {
+                            ThinkingEffort::Minimal => luban_api::ThinkingEffort::Minimal,
+                            ThinkingEffort::Low => luban_api::ThinkingEffort::Low,
+                            ThinkingEffort::Medium => luban_api::ThinkingEffort::Medium,
+                            ThinkingEffort::High => luban_api::ThinkingEffort::High,
+                            ThinkingEffort::XHigh => luban_api::ThinkingEffort::XHigh,
+                        },
+                        amp_mode: prompt.run_config.amp_mode.clone(),
+                        claude_max_output_tokens: prompt.run_config.claude_max_output_tokens,
+                        claude_system_prompt: prompt.run_config.claude_system_prompt.clone(),
+                    },
+                })
+                .collect(),
+            queue_paused: conversation.queue_paused,
+            remote_thread_id: conversation.thread_id.clone(),
+            title: conversation.title.clone(),
+            notes: conversation.notes.clone(),
+            cwd_subpath: conversation.cwd_subpath.clone(),
+            hunk_comments: conversation
+                .hunk_comments
+                .iter()
+                .map(map_hunk_comment)
+                .collect(),
+            collapsed_ranges: conversation
+                .collapsed_ranges
+                .iter()
+                .map(map_collapsed_range)
+                .collect(),
+            draft: conversation.draft.clone(),
+            patch_apply_failed_entry_id: conversation.patch_apply_failed_entry_id.clone(),
+            had_failed_commands: conversation.had_failed_commands(),
+            failed_command_ids: conversation.last_turn_failed_command_ids.clone(),
+            worktree_dirty: self.worktree_dirty_for(wid),
+            total_usage: map_token_usage(&total_usage),
+        })
+    }
+}
+
+fn hex_lower(bytes: &[u8]) -> String {
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        out.push(HEX[(b >> 4) as usize] as char);
+        out.push(HEX[(b & 0x0f) as usize] as char);
+    }
+    out
+}
+
+fn normalize_project_path(path: &std::path::Path) -> PathBuf {
+    use std::path::Component;
+
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                let popped = out.pop();
+                if !popped {
+                    out.push(component);
+                }
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+/// Drops archived projects (and their group memberships) from a snapshot meant for the sidebar.
+pub(crate) fn without_archived_projects(mut snapshot: AppSnapshot) -> AppSnapshot {
+    let archived = snapshot
+        .projects
+        .iter()
+        .filter(|p| p.status == luban_api::ProjectStatus::Archived)
+        .map(|p| p.id.clone())
+        .collect::<HashSet<_>>();
+    if archived.is_empty() {
+        return snapshot;
+    }
+    snapshot.projects.retain(|p| !archived.contains(&p.id));
+    for group in &mut snapshot.project_groups {
+        group.project_ids.retain(|id| !archived.contains(id));
+    }
+    snapshot
+}
+
+/// Returns the project's main workspace: the active one checked out at the project root, preferring
+/// the one named `main`, or else the first active workspace.
+fn project_main_workspace_id(
+    state: &AppState,
+    project_id: luban_domain::ProjectId,
+) -> Option<WorkspaceId> {
+    let project = state.projects.iter().find(|p| p.id == project_id)?;
+    let active = || {
+        project
+            .workspaces
+            .iter()
+            .filter(|w| w.status == luban_domain::WorkspaceStatus::Active)
+    };
+    active()
+        .find(|w| w.workspace_name == "main" && w.worktree_path == project.path)
+        .or_else(|| active().find(|w| w.worktree_path == project.path))
+        .or_else(|| active().next())
+        .map(|w| w.id)
+}
+
+/// Worktree paths the workspaces own, shared by the worktree audit and the orphan prune.
+/// Active workspaces always own theirs. Archived ones own a directory only while it is still on
+/// disk, so a leftover is neither reported as orphaned nor removed from under a workspace the user
+/// may restore, and one that archiving removed is not reported as dangling.
+fn owned_worktree_paths(workspaces: &[luban_domain::Workspace]) -> Vec<PathBuf> {
+    workspaces
+        .iter()
+        .filter(|w| w.status == luban_domain::WorkspaceStatus::Active || w.worktree_path.exists())
+        .map(|w| w.worktree_path.clone())
+        .collect()
+}
+
+fn find_project_id_by_path(
+    state: &AppState,
+    path: &std::path::Path,
+) -> Option<luban_domain::ProjectId> {
+    let normalized_path = normalize_project_path(path);
+    state
+        .projects
+        .iter()
+        .find(|p| normalize_project_path(&p.path) == normalized_path)
+        .map(|p| p.id)
+}
+
+fn map_task_intent_kind(kind: luban_domain::TaskIntentKind) -> luban_api::TaskIntentKind {
+    match kind {
+        luban_domain::TaskIntentKind::Fix => luban_api::TaskIntentKind::Fix,
+        luban_domain::TaskIntentKind::Implement => luban_api::TaskIntentKind::Implement,
+        luban_domain::TaskIntentKind::Review => luban_api::TaskIntentKind::Review,
+        luban_domain::TaskIntentKind::Discuss => luban_api::TaskIntentKind::Discuss,
+        luban_domain::TaskIntentKind::Other => luban_api::TaskIntentKind::Other,
+    }
+}
+
+fn map_system_task_kind(kind: luban_domain::SystemTaskKind) -> luban_api::SystemTaskKind {
+    match kind {
+        luban_domain::SystemTaskKind::InferType => luban_api::SystemTaskKind::InferType,
+        luban_domain::SystemTaskKind::RenameBranch => luban_api::SystemTaskKind::RenameBranch,
+        luban_domain::SystemTaskKind::AutoTitleThread => luban_api::SystemTaskKind::AutoTitleThread,
+        luban_domain::SystemTaskKind::AutoUpdateTaskStatus => {
+            luban_api::SystemTaskKind::AutoUpdateTaskStatus
+        }
+        luban_domain::SystemTaskKind::PrDescription => luban_api::SystemTaskKind::PrDescription,
+        luban_domain::SystemTaskKind::ConversationSummary => {
+            luban_api::SystemTaskKind::ConversationSummary
+        }
+    }
+}
+
+fn pick_project_folder() -> Option<PathBuf> {
+    #[cfg(target_os = "macos")]
+    {
+        // `rfd` requires a windowed environment and a main-thread call on macOS. In our
+        // localhost server process we may run in a non-windowed environment, so use the
+        // system dialog via AppleScript instead.
+        let output = Command::new("osascript")
+            .args([
+                "-e",
+                "POSIX path of (choose folder with prompt \"Select project folder\")",
+            ])
+            .output()
+            .ok()?;
+
+        if !output.status.success() {
+            return None;
+        }
+
+        let raw = String::from_utf8_lossy(&output.stdout);
+        let path = raw.trim().trim_end_matches('/').trim();
+        if path.is_empty() {
+            return None;
+        }
+        Some(PathBuf::from(path))
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        rfd::FileDialog::new()
+            .set_title("Select project folder")
+            .pick_folder()
+    }
+}
+
+#[derive(Clone)]
+struct WorkspaceScope {
+    project_slug: String,
+    workspace_name: String,
+}
+
+/// Active workspaces the inactivity sweep may archive, with their recorded last activity in unix
+/// seconds. The main workspace, workspaces with an open pull request and workspaces with a
+/// running turn are never returned.
+fn inactive_workspace_archive_candidates(
+    state: &AppState,
+    has_open_pull_request: impl Fn(WorkspaceId) -> bool,
+) -> Vec<(WorkspaceId, Option<u64>, WorkspaceScope)> {
+    let mut candidates = Vec::new();
+    for project in &state.projects {
+        if project.status != ProjectStatus::Active || !project.is_git {
+            continue;
+        }
+        for workspace in &project.workspaces {
+            if workspace.status != luban_domain::WorkspaceStatus::Active
+                || workspace.archive_status == OperationStatus::Running
+                || workspace.workspace_name == "main"
+                || workspace.worktree_path == project.path
+            {
+                continue;
+            }
+            if has_open_pull_request(workspace.id) {
+                continue;
+            }
+            let has_running_turn = state.conversations.iter().any(|((wid, _), conversation)| {
+                *wid == workspace.id && conversation.run_status == OperationStatus::Running
+            });
+            if has_running_turn {
+                continue;
+            }
+            let last_activity_at = workspace
+                .last_activity_at
+                .and_then(|at| at.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| d.as_secs());
+            candidates.push((
+                workspace.id,
+                last_activity_at,
+                WorkspaceScope {
+                    project_slug: project.slug.clone(),
+                    workspace_name: workspace.workspace_name.clone(),
+                },
+            ));
+        }
+    }
+    candidates
+}
+
+/// Newest of the workspace's recorded activity and its threads' last update, if either is known.
+fn latest_workspace_activity(
+    last_activity_at: Option<u64>,
+    threads: &[ConversationThreadMeta],
+) -> Option<u64> {
+    threads
+        .iter()
+        .map(|t| t.updated_at_unix_seconds)
+        .chain(last_activity_at)
+        .max()
+}
+
+fn workspace_scope(state: &AppState, workspace_id: WorkspaceId) -> Option<WorkspaceScope> {
+    for project in &state.projects {
+        for workspace in &project.workspaces {
+            if workspace.id == workspace_id {
+                return Some(WorkspaceScope {
+                    project_slug: project.slug.clone(),
+                    workspace_name: workspace.workspace_name.clone(),
+                });
+            }
+        }
+    }
+    None
+}
+
+/// Raises a live snapshot's `entries_total` to the stored count and recomputes
+/// `entries_truncated` against it.
+fn apply_stored_entries_total(snapshot: &mut ConversationSnapshot, stored_total: u64) {
+    let end = snapshot
+        .entries_start
+        .saturating_add(snapshot.entries.len() as u64);
+    snapshot.entries_total = snapshot.entries_total.max(stored_total);
+    snapshot.entries_truncated = snapshot.entries_start > 0 || end < snapshot.entries_total;
+}
+
+fn should_sync_branch_watchers(action: &Action) -> bool {
+    matches!(
+        action,
+        Action::AppStateLoaded { .. }
+            | Action::AddProject { .. }
+            | Action::CreateWorkspace { .. }
+            | Action::CreateWorkspaceFromBranch { .. }
+            | Action::EnsureMainWorkspace { .. }
+            | Action::WorkspaceCreated { .. }
+            | Action::WorkspaceArchived { .. }
+            | Action::DeleteProject { .. }
+            | Action::ArchiveProject { .. }
+            | Action::RestoreProject { .. }
+    )
+}
+
+/// Rejects a message whose attachments exceed `limit` bytes, individually or together.
+fn check_attachment_sizes(action: &luban_api::ClientAction, limit: u64) -> Result<(), String> {
+    let attachments = match action {
+        luban_api::ClientAction::SendAgentMessage { attachments, .. }
+        | luban_api::ClientAction::CancelAndSendAgentMessage { attachments, .. }
+        | luban_api::ClientAction::QueueAgentMessage { attachments, .. }
+        | luban_api::ClientAction::UpdateQueuedPrompt { attachments, .. } => attachments,
+        _ => return Ok(()),
+    };
+    if limit == 0 {
+        return Ok(());
+    }
+
+    if let Some(attachment) = attachments.iter().find(|a| a.byte_len > limit) {
+        return Err(format!(
+            "attachment '{}' is {} bytes, over the {limit} byte limit",
+            attachment.name, attachment.byte_len
+        ));
+    }
+    let total = attachments
+        .iter()
+        .fold(0u64, |sum, a| sum.saturating_add(a.byte_len));
+    if total > limit {
+        return Err(format!(
+            "attachments total {total} bytes, over the {limit} byte limit per message"
+        ));
+    }
+    Ok(())
+}
+
+/// Returns the conversation and the `ConversationSnapshot.rev` a mutating client action was
+/// issued against, when the client supplied one.
+fn client_action_expected_conversation_rev(
+    action: &luban_api::ClientAction,
+) -> Option<(luban_api::WorkspaceId, luban_api::WorkspaceThreadId, u64)> {
+    match action {
+        luban_api::ClientAction::SendAgentMessage {
+            workspace_id,
+            thread_id,
+            expected_rev,
+            ..
+        }
+        | luban_api::ClientAction::CancelAndSendAgentMessage {
+            workspace_id,
+            thread_id,
+            expected_rev,
+            ..
+        }
+        | luban_api::ClientAction::QueueAgentMessage {
+            workspace_id,
+            thread_id,
+            expected_rev,
+            ..
+        }
+        | luban_api::ClientAction::RemoveQueuedPrompt {
+            workspace_id,
+            thread_id,
+            expected_rev,
+            ..
+        }
+        | luban_api::ClientAction::ReorderQueuedPrompt {
+            workspace_id,
+            thread_id,
+            expected_rev,
+            ..
+        }
+        | luban_api::ClientAction::UpdateQueuedPrompt {
+            workspace_id,
+            thread_id,
+            expected_rev,
+            ..
+        }
+        | luban_api::ClientAction::CancelAndClearQueue {
+            workspace_id,
+            thread_id,
+            expected_rev,
+        } => expected_rev.map(|rev| (*workspace_id, *thread_id, rev)),
+        _ => None,
+    }
+}
+
+/// Run whose concurrent turn slot frees up once `action` is applied: the turn reported a terminal
+/// event or its runner finished.
+fn finished_agent_turn_for_action(
+    action: &Action,
+) -> Option<(WorkspaceId, WorkspaceThreadId, u64)> {
+    match action {
+        Action::AgentTurnFinished {
+            workspace_id,
+            thread_id,
+            run_id,
+        }
+        | Action::AgentEventReceived {
+            workspace_id,
+            thread_id,
+            run_id,
+            event:
+                luban_domain::CodexThreadEvent::TurnCompleted { .. }
+                | luban_domain::CodexThreadEvent::TurnFailed { .. },
+        } => Some((*workspace_id, *thread_id, *run_id)),
+        _ => None,
+    }
+}
+
+/// Workspace whose uncommitted-changes flag should be re-checked after `action`, and whether the
+/// check bypasses the debounce (the worktree has likely just changed).
+fn worktree_dirty_refresh_for_action(action: &Action) -> Option<(WorkspaceId, bool)> {
+    match action {
+        Action::ConversationLoaded { workspace_id, .. } => Some((*workspace_id, false)),
+        Action::AgentTurnFinished { workspace_id, .. }
+        | Action::TerminalCommandFinished { workspace_id, .. }
+        | Action::WorkspaceStashFinished { workspace_id }
+        | Action::WorkspaceCommitFinished { workspace_id } => Some((*workspace_id, true)),
+        _ => None,
+    }
+}
+
+/// Workspace whose tip commit moved because of `action`; the branch watcher only notices HEAD
+/// switching branches, not new commits on the same branch.
+fn last_commit_refresh_for_action(action: &Action) -> Option<WorkspaceId> {
+    match action {
+        Action::WorkspaceCommitFinished { workspace_id } => Some(*workspace_id),
+        _ => None,
+    }
+}
+
+fn conversation_key_for_action(action: &Action) -> Option<(WorkspaceId, WorkspaceThreadId)> {
+    match action {
+        Action::TerminalCommandStarted {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        Action::TerminalCommandFinished {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        Action::SendAgentMessage {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        Action::TaskStatusSet {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        Action::RenameThread {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        Action::TaskStatusSuggestionCreated {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        Action::QueueAgentMessage {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        Action::ConversationLoaded {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        Action::ConversationHistoryPruned {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        Action::ConversationLoadFailed {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        Action::AgentEventReceived {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        Action::AgentRunStartedAt {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        Action::AgentTurnRetryDue {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        Action::AgentRunFinishedAt {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        Action::AgentTurnFinished {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        Action::CancelAgentTurn {
+            workspace_id,
+            thread_id,
+        } => Some((*workspace_id, *thread_id)),
+        Action::CancelAndClearQueue {
+            workspace_id,
+            thread_id,
+        } => Some((*workspace_id, *thread_id)),
+        Action::RespondToToolApproval {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        Action::ToolApprovalDeliveryFailed {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        Action::ChatModelChanged {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        Action::ChatRunnerChanged {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        Action::ChatAmpModeChanged {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        Action::ChatAutoCommitChanged {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        Action::ThinkingEffortChanged {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        Action::ThreadNotesChanged {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        Action::ThreadCwdSubpathChanged {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        Action::AddHunkComment {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        Action::ConversationCompacted {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        Action::RemoveQueuedPrompt {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        Action::ReorderQueuedPrompt {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        Action::UpdateQueuedPrompt {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        Action::ClearQueuedPrompts {
+            workspace_id,
+            thread_id,
+        } => Some((*workspace_id, *thread_id)),
+        Action::ResumeQueuedPrompts {
+            workspace_id,
+            thread_id,
+        } => Some((*workspace_id, *thread_id)),
+        Action::RetryLastTurnWithRunner {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        _ => None,
+    }
+}
+
+fn agent_item_key_for_action(action: &Action) -> Option<(WorkspaceId, WorkspaceThreadId)> {
+    match action {
+        Action::AgentEventReceived {
+            workspace_id,
+            thread_id,
+            event:
+                CodexThreadEvent::ItemStarted { .. }
+                | CodexThreadEvent::ItemUpdated { .. }
+                | CodexThreadEvent::ItemCompleted { .. },
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        _ => None,
+    }
+}
+
+fn system_events_key_for_action(action: &Action) -> Option<(WorkspaceId, WorkspaceThreadId)> {
+    match action {
+        Action::RetryLastTurnWithRunner {
+            workspace_id,
+            thread_id,
+            ..
+        }
+        | Action::ConversationCompacted {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        _ => None,
+    }
+}
+
+fn conversation_keys_for_effects(effects: &[Effect]) -> Vec<(WorkspaceId, WorkspaceThreadId)> {
+    let mut out = Vec::new();
+    for effect in effects {
+        let key = match effect {
+            Effect::EnsureConversation {
+                workspace_id,
+                thread_id,
+            }
+            | Effect::DuplicateConversation {
+                workspace_id,
+                thread_id,
+                ..
+            }
+            | Effect::StoreConversationRunConfig {
+                workspace_id,
+                thread_id,
+                ..
+            }
+            | Effect::StoreConversationCwdSubpath {
+                workspace_id,
+                thread_id,
+                ..
+            }
+            | Effect::StoreHunkComment {
+                workspace_id,
+                thread_id,
+                ..
+            }
+            | Effect::StoreCollapsedRange {
+                workspace_id,
+                thread_id,
+                ..
+            }
+            | Effect::StoreConversationTaskStatus {
+                workspace_id,
+                thread_id,
+                ..
+            }
+            | Effect::LoadConversation {
+                workspace_id,
+                thread_id,
+            }
+            | Effect::RunAgentTurn {
+                workspace_id,
+                thread_id,
+                ..
+            }
+            | Effect::CancelAgentTurn {
+                workspace_id,
+                thread_id,
+                ..
+            }
+            | Effect::RespondToToolApproval {
+                workspace_id,
+                thread_id,
+                ..
+            }
+            | Effect::CleanupClaudeProcess {
+                workspace_id,
+                thread_id,
+            }
+            | Effect::AiAutoTitleThread {
+                workspace_id,
+                thread_id,
+                ..
+            }
+            | Effect::AiAutoUpdateTaskStatus {
+                workspace_id,
+                thread_id,
+                ..
+            } => Some((*workspace_id, *thread_id)),
+            _ => None,
+        };
+
+        if let Some(key) = key {
+            out.push(key);
+        }
+    }
+    out
+}
+
+fn queue_state_key_for_action(action: &Action) -> Option<(WorkspaceId, WorkspaceThreadId)> {
+    match action {
+        Action::SendAgentMessage {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        Action::QueueAgentMessage {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        Action::RemoveQueuedPrompt {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        Action::ReorderQueuedPrompt {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        Action::UpdateQueuedPrompt {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        Action::ClearQueuedPrompts {
+            workspace_id,
+            thread_id,
+        } => Some((*workspace_id, *thread_id)),
+        Action::ResumeQueuedPrompts {
+            workspace_id,
+            thread_id,
+        } => Some((*workspace_id, *thread_id)),
+        Action::RetryLastTurnWithRunner {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        Action::CancelAgentTurn {
+            workspace_id,
+            thread_id,
+        } => Some((*workspace_id, *thread_id)),
+        Action::CancelAndClearQueue {
+            workspace_id,
+            thread_id,
+        } => Some((*workspace_id, *thread_id)),
+        Action::TaskStatusSet {
+            workspace_id,
+            thread_id,
+            task_status: luban_domain::TaskStatus::Canceled | luban_domain::TaskStatus::Done,
+        } => Some((*workspace_id, *thread_id)),
+        Action::AgentEventReceived {
+            workspace_id,
+            thread_id,
+            run_id: _,
+            event:
+                CodexThreadEvent::TurnCompleted { .. }
+                | CodexThreadEvent::TurnFailed { .. }
+                | CodexThreadEvent::Error { .. },
+        } => Some((*workspace_id, *thread_id)),
+        Action::AgentRunStartedAt {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        Action::AgentTurnRetryDue {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        Action::AgentRunFinishedAt {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some((*workspace_id, *thread_id)),
+        _ => None,
+    }
+}
+
+enum ConversationCachePrune {
+    Threads(Vec<(WorkspaceId, WorkspaceThreadId)>),
+    InactiveWorkspaces,
+}
+
+fn conversation_cache_prune_for_action(action: &Action) -> Option<ConversationCachePrune> {
+    match action {
+        Action::WorkspaceThreadsPurged {
+            workspace_id,
+            thread_ids,
+        } => Some(ConversationCachePrune::Threads(
+            thread_ids
+                .iter()
+                .map(|thread_id| (*workspace_id, *thread_id))
+                .collect(),
+        )),
+        Action::WorkspaceThreadMoved {
+            from_workspace_id,
+            thread_id,
+            ..
+        } => Some(ConversationCachePrune::Threads(vec![(
+            *from_workspace_id,
+            *thread_id,
+        )])),
+        Action::WorkspaceArchived { .. }
+        | Action::ArchiveProject { .. }
+        | Action::DeleteProject { .. } => Some(ConversationCachePrune::InactiveWorkspaces),
+        _ => None,
+    }
+}
+
+fn threads_event_for_action(
+    action: &Action,
+) -> Option<(WorkspaceId, Vec<luban_domain::ConversationThreadMeta>)> {
+    match action {
+        Action::WorkspaceThreadsLoaded {
+            workspace_id,
+            threads,
+        } => Some((*workspace_id, threads.clone())),
+        _ => None,
+    }
+}
+
+fn task_summaries_workspace_id_for_action(action: &Action) -> Option<WorkspaceId> {
+    match action {
+        Action::WorkspaceThreadsLoaded { workspace_id, .. } => Some(*workspace_id),
+        Action::TaskStarSet { workspace_id, .. } => Some(*workspace_id),
+        Action::OpenWorkspace { workspace_id } => Some(*workspace_id),
+        Action::DashboardPreviewOpened { workspace_id } => Some(*workspace_id),
+        Action::CreateWorkspaceThread { workspace_id } => Some(*workspace_id),
+        Action::ActivateWorkspaceThread { workspace_id, .. } => Some(*workspace_id),
+        Action::CloseWorkspaceThreadTab { workspace_id, .. } => Some(*workspace_id),
+        Action::RestoreWorkspaceThreadTab { workspace_id, .. } => Some(*workspace_id),
+        Action::ReorderWorkspaceThreadTab { workspace_id, .. } => Some(*workspace_id),
+        Action::ActivateAdjacentWorkspaceThread { workspace_id, .. } => Some(*workspace_id),
+        Action::SendAgentMessage { workspace_id, .. } => Some(*workspace_id),
+        Action::QueueAgentMessage { workspace_id, .. } => Some(*workspace_id),
+        Action::RetryLastTurnWithRunner { workspace_id, .. } => Some(*workspace_id),
+        Action::AgentTurnFinished { workspace_id, .. } => Some(*workspace_id),
+        Action::AgentTurnRetryDue { workspace_id, .. } => Some(*workspace_id),
+        Action::RespondToToolApproval { workspace_id, .. } => Some(*workspace_id),
+        Action::ToolApprovalDeliveryFailed { workspace_id, .. } => Some(*workspace_id),
+        Action::AgentEventReceived {
+            workspace_id,
+            event:
+                CodexThreadEvent::ItemStarted { item }
+                | CodexThreadEvent::ItemUpdated { item }
+                | CodexThreadEvent::ItemCompleted { item },
+            ..
+        } if matches!(item, luban_domain::CodexThreadItem::McpToolCall { .. }) => {
+            Some(*workspace_id)
+        }
+        _ => None,
+    }
+}
+
+/// Workspaces whose unread badge a `MarkWorkspaceRead` / `MarkAllRead` is about to clear.
+fn workspaces_marked_read_by_action(state: &AppState, action: &Action) -> Vec<WorkspaceId> {
+    match action {
+        Action::MarkWorkspaceRead { workspace_id }
+            if state.workspace_has_unread_completion(*workspace_id) =>
+        {
+            vec![*workspace_id]
+        }
+        Action::MarkAllRead => state.workspace_unread_completions.iter().copied().collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn dedup_thread_metas_in_place(metas: &mut Vec<ConversationThreadMeta>) {
+    let mut seen = HashSet::<WorkspaceThreadId>::new();
+    metas.retain(|t| seen.insert(t.thread_id));
+}
+
+fn map_domain_task_status(status: luban_domain::TaskStatus) -> luban_api::TaskStatus {
+    match status {
+        luban_domain::TaskStatus::Backlog => luban_api::TaskStatus::Backlog,
+        luban_domain::TaskStatus::Todo => luban_api::TaskStatus::Todo,
+        luban_domain::TaskStatus::Iterating => luban_api::TaskStatus::Iterating,
+        luban_domain::TaskStatus::Validating => luban_api::TaskStatus::Validating,
+        luban_domain::TaskStatus::Done => luban_api::TaskStatus::Done,
+        luban_domain::TaskStatus::Canceled => luban_api::TaskStatus::Canceled,
+    }
+}
+
+fn map_domain_turn_status(status: luban_domain::TurnStatus) -> luban_api::TurnStatus {
+    match status {
+        luban_domain::TurnStatus::Idle => luban_api::TurnStatus::Idle,
+        luban_domain::TurnStatus::Running => luban_api::TurnStatus::Running,
+        luban_domain::TurnStatus::Awaiting => luban_api::TurnStatus::Awaiting,
+        luban_domain::TurnStatus::Paused => luban_api::TurnStatus::Paused,
+    }
+}
+
+fn map_domain_turn_result(result: luban_domain::TurnResult) -> luban_api::TurnResult {
+    match result {
+        luban_domain::TurnResult::Completed => luban_api::TurnResult::Completed,
+        luban_domain::TurnResult::Failed => luban_api::TurnResult::Failed,
+    }
+}
+
+fn map_token_usage(usage: &luban_domain::CodexUsage) -> luban_api::TokenUsageSnapshot {
+    luban_api::TokenUsageSnapshot {
+        input_tokens: usage.input_tokens,
+        cached_input_tokens: usage.cached_input_tokens,
+        output_tokens: usage.output_tokens,
+    }
+}
+
+fn parse_codex_defaults_toml(contents: &str) -> (Option<String>, Option<ThinkingEffort>) {
+    fn strip_comment(line: &str) -> &str {
+        let mut in_single = false;
+        let mut in_double = false;
+        for (idx, ch) in line.char_indices() {
+            match ch {
+                '\'' if !in_double => in_single = !in_single,
+                '"' if !in_single => in_double = !in_double,
+                '#' if !in_single && !in_double => return &line[..idx],
+                _ => {}
+            }
+        }
+        line
+    }
+
+    fn parse_string_value(raw: &str) -> Option<String> {
+        let trimmed = raw.trim();
+        if trimmed.is_empty() {
+            return None;
+        }
+        if let Some(rest) = trimmed.strip_prefix('"') {
+            let end = rest.find('"')?;
+            return Some(rest[..end].to_owned());
+        }
+        if let Some(rest) = trimmed.strip_prefix('\'') {
+            let end = rest.find('\'')?;
+            return Some(rest[..end].to_owned());
+        }
+        None
+    }
+
+    fn parse_effort(raw: &str) -> Option<ThinkingEffort> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "minimal" => Some(ThinkingEffort::Minimal),
+            "low" => Some(ThinkingEffort::Low),
+            "medium" => Some(ThinkingEffort::Medium),
+            "high" => Some(ThinkingEffort::High),
+            "xhigh" => Some(ThinkingEffort::XHigh),
+            _ => None,
+        }
+    }
+
+    let mut in_root = true;
+    let mut model_id: Option<String> = None;
+    let mut effort: Option<ThinkingEffort> = None;
+
+    for raw_line in contents.lines() {
+        let line = strip_comment(raw_line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('[') {
+            in_root = false;
+            continue;
+        }
+        if !in_root {
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim();
+        let value = value.trim();
+
+        if key == "model" && model_id.is_none() {
+            model_id = parse_string_value(value).map(|v| v.trim().to_owned());
+            continue;
+        }
+        if key == "model_reasoning_effort" && effort.is_none() {
+            if let Some(value) = parse_string_value(value) {
+                effort = parse_effort(&value);
+            }
+            continue;
+        }
+    }
+
+    (
+        model_id.and_then(|v| {
+            let trimmed = v.trim();
+            if trimmed.is_empty() {
+                None
+            } else {
+                Some(trimmed.to_owned())
+            }
+        }),
+        effort,
+    )
+}
+
+fn map_pull_request_info(info: PullRequestInfo) -> PullRequestSnapshot {
+    let state = match info.state {
+        DomainPullRequestState::Open => PullRequestState::Open,
+        DomainPullRequestState::Closed => PullRequestState::Closed,
+        DomainPullRequestState::Merged => PullRequestState::Merged,
+    };
+    let ci_state = info.ci_state.map(|s| match s {
+        DomainPullRequestCiState::Pending => PullRequestCiState::Pending,
+        DomainPullRequestCiState::Success => PullRequestCiState::Success,
+        DomainPullRequestCiState::Failure => PullRequestCiState::Failure,
+    });
+    PullRequestSnapshot {
+        number: info.number,
+        is_draft: info.is_draft,
+        state,
+        ci_state,
+        merge_ready: info.merge_ready,
+    }
+}
+
+fn workspace_short_id(project_slug: &str, workspace_id: u64) -> String {
+    let mut prefix = project_slug
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .take(2)
+        .collect::<String>();
+    while prefix.len() < 2 {
+        prefix.push('x');
+    }
+
+    let mut suffix = to_base36(workspace_id);
+    if suffix.len() < 2 {
+        suffix.insert(0, '0');
+    }
+
+    format!("{prefix}{suffix}")
+}
+
+fn to_base36(mut n: u64) -> String {
+    const DIGITS: &[u8; 36] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+    if n == 0 {
+        return "0".to_owned();
+    }
+    let mut out = Vec::new();
+    while n > 0 {
+        out.push(DIGITS[(n % 36) as usize]);
+        n /= 36;
+    }
+    out.reverse();
+    String::from_utf8(out).unwrap_or_else(|_| "0".to_owned())
+}
+
+fn now_unix_seconds() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn now_unix_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+        .try_into()
+        .unwrap_or(0u64)
+}
+
+fn map_workspace_tabs_snapshot(tabs: &luban_domain::WorkspaceTabs) -> WorkspaceTabsSnapshot {
+    WorkspaceTabsSnapshot {
+        open_tabs: tabs
+            .open_tabs
+            .iter()
+            .map(|id| luban_api::WorkspaceThreadId(id.as_u64()))
+            .collect(),
+        archived_tabs: tabs
+            .archived_tabs
+            .iter()
+            .map(|id| luban_api::WorkspaceThreadId(id.as_u64()))
+            .collect(),
+        active_tab: luban_api::WorkspaceThreadId(tabs.active_tab.as_u64()),
+    }
+}
+
+fn map_conversation_entry(entry: &ConversationEntry) -> luban_api::ConversationEntry {
+    match entry {
+        ConversationEntry::SystemEvent {
+            entry_id,
+            created_at_unix_ms,
+            event,
+        } => luban_api::ConversationEntry::SystemEvent(luban_api::ConversationSystemEventEntry {
+            entry_id: entry_id.clone(),
+            created_at_unix_ms: *created_at_unix_ms,
+            event: match event {
+                luban_domain::ConversationSystemEvent::TaskCreated => {
+                    luban_api::ConversationSystemEvent::TaskCreated
+                }
+                luban_domain::ConversationSystemEvent::TaskArchived => {
+                    luban_api::ConversationSystemEvent::TaskArchived
+                }
+                luban_domain::ConversationSystemEvent::TaskStatusChanged { from, to } => {
+                    luban_api::ConversationSystemEvent::TaskStatusChanged {
+                        from: match from {
+                            luban_domain::TaskStatus::Backlog => luban_api::TaskStatus::Backlog,
+                            luban_domain::TaskStatus::Todo => luban_api::TaskStatus::Todo,
+                            luban_domain::TaskStatus::Iterating => luban_api::TaskStatus::Iterating,
+                            luban_domain::TaskStatus::Validating => {
+                                luban_api::TaskStatus::Validating
+                            }
+                            luban_domain::TaskStatus::Done => luban_api::TaskStatus::Done,
+                            luban_domain::TaskStatus::Canceled => luban_api::TaskStatus::Canceled,
+                        },
+                        to: match to {
+                            luban_domain::TaskStatus::Backlog => luban_api::TaskStatus::Backlog,
+                            luban_domain::TaskStatus::Todo => luban_api::TaskStatus::Todo,
+                            luban_domain::TaskStatus::Iterating => luban_api::TaskStatus::Iterating,
+                            luban_domain::TaskStatus::Validating => {
+                                luban_api::TaskStatus::Validating
+                            }
+                            luban_domain::TaskStatus::Done => luban_api::TaskStatus::Done,
+                            luban_domain::TaskStatus::Canceled => luban_api::TaskStatus::Canceled,
+                        },
+                    }
+                }
+                luban_domain::ConversationSystemEvent::TaskStatusSuggestion {
+                    from,
+                    to,
+                    title,
+                    explanation_markdown,
+                } => luban_api::ConversationSystemEvent::TaskStatusSuggestion {
+                    from: match from {
+                        luban_domain::TaskStatus::Backlog => luban_api::TaskStatus::Backlog,
+                        luban_domain::TaskStatus::Todo => luban_api::TaskStatus::Todo,
+                        luban_domain::TaskStatus::Iterating => luban_api::TaskStatus::Iterating,
+                        luban_domain::TaskStatus::Validating => luban_api::TaskStatus::Validating,
+                        luban_domain::TaskStatus::Done => luban_api::TaskStatus::Done,
+                        luban_domain::TaskStatus::Canceled => luban_api::TaskStatus::Canceled,
+                    },
+                    to: match to {
+                        luban_domain::TaskStatus::Backlog => luban_api::TaskStatus::Backlog,
+                        luban_domain::TaskStatus::Todo => luban_api::TaskStatus::Todo,
+                        luban_domain::TaskStatus::Iterating => luban_api::TaskStatus::Iterating,
+                        luban_domain::TaskStatus::Validating => luban_api::TaskStatus::Validating,
+                        luban_domain::TaskStatus::Done => luban_api::TaskStatus::Done,
+                        luban_domain::TaskStatus::Canceled => luban_api::TaskStatus::Canceled,
+                    },
+                    title: title.clone(),
+                    explanation_markdown: explanation_markdown.clone(),
+                },
+                luban_domain::ConversationSystemEvent::TurnRetriedWithRunner {
+                    runner,
+                    model_id,
+                } => luban_api::ConversationSystemEvent::TurnRetriedWithRunner {
+                    runner: match runner {
+                        luban_domain::AgentRunnerKind::Codex => luban_api::AgentRunnerKind::Codex,
+                        luban_domain::AgentRunnerKind::Amp => luban_api::AgentRunnerKind::Amp,
+                        luban_domain::AgentRunnerKind::Claude => luban_api::AgentRunnerKind::Claude,
+                        luban_domain::AgentRunnerKind::Droid => luban_api::AgentRunnerKind::Droid,
+                    },
+                    model_id: model_id.clone(),
+                },
+                luban_domain::ConversationSystemEvent::HistorySummarized {
+                    summary_markdown,
+                    collapsed_entries,
+                } => luban_api::ConversationSystemEvent::HistorySummarized {
+                    summary_markdown: summary_markdown.clone(),
+                    collapsed_entries: *collapsed_entries,
+                },
+                luban_domain::ConversationSystemEvent::HistoryPruned { pruned_entries } => {
+                    luban_api::ConversationSystemEvent::HistoryPruned {
+                        pruned_entries: *pruned_entries,
+                    }
+                }
+            },
+        }),
+        ConversationEntry::UserEvent {
+            entry_id,
+            created_at_unix_ms,
+            event,
+        } => {
+            let event = match event {
+                luban_domain::UserEvent::Message { text, attachments } => {
+                    luban_api::UserEvent::Message(luban_api::UserMessage {
+                        text: text.clone(),
+                        attachments: attachments.iter().map(map_attachment_ref).collect(),
+                    })
+                }
+                luban_domain::UserEvent::TerminalCommandStarted {
+                    id,
+                    command,
+                    reconnect,
+                } => luban_api::UserEvent::TerminalCommandStarted(
+                    luban_api::TerminalCommandStarted {
+                        id: id.clone(),
+                        command: command.clone(),
+                        reconnect: reconnect.clone(),
+                    },
+                ),
+                luban_domain::UserEvent::TerminalCommandFinished {
+                    id,
+                    command,
+                    reconnect,
+                    output_base64,
+                    output_byte_len,
+                } => luban_api::UserEvent::TerminalCommandFinished(
+                    luban_api::TerminalCommandFinished {
+                        id: id.clone(),
+                        command: command.clone(),
+                        reconnect: reconnect.clone(),
+                        output_base64: output_base64.clone(),
+                        output_byte_len: *output_byte_len,
+                    },
+                ),
+            };
+            luban_api::ConversationEntry::UserEvent(luban_api::UserEventEntry {
+                entry_id: entry_id.clone(),
+                created_at_unix_ms: *created_at_unix_ms,
+                event,
+            })
+        }
+        ConversationEntry::AgentEvent {
+            entry_id,
+            created_at_unix_ms,
+            runner,
+            event,
+        } => {
+            let event = match event {
+                luban_domain::AgentEvent::Message { id, text } => {
+                    luban_api::AgentEvent::Message(luban_api::AgentMessage {
+                        id: id.clone(),
+                        text: text.clone(),
+                    })
+                }
+                luban_domain::AgentEvent::Item { item } => {
+                    map_codex_thread_item_to_agent_event(item.as_ref())
+                }
+                luban_domain::AgentEvent::TurnUsage { usage } => {
+                    let usage_json = usage.as_ref().and_then(|u| serde_json::to_value(u).ok());
+                    luban_api::AgentEvent::TurnUsage { usage_json }
+                }
+                luban_domain::AgentEvent::TurnStarted { at_unix_ms } => {
+                    luban_api::AgentEvent::TurnStarted {
+                        at_unix_ms: *at_unix_ms,
+                    }
+                }
+                luban_domain::AgentEvent::TurnDuration { duration_ms } => {
+                    luban_api::AgentEvent::TurnDuration {
+                        duration_ms: *duration_ms,
+                    }
+                }
+                luban_domain::AgentEvent::TurnCanceled => luban_api::AgentEvent::TurnCanceled,
+                luban_domain::AgentEvent::TurnError { message, code } => {
+                    luban_api::AgentEvent::TurnError {
+                        message: message.clone(),
+                        code: code.clone(),
+                    }
+                }
+            };
+            luban_api::ConversationEntry::AgentEvent(luban_api::AgentEventEntry {
+                entry_id: entry_id.clone(),
+                created_at_unix_ms: *created_at_unix_ms,
+                runner: runner.map(|r| match r {
+                    luban_domain::AgentRunnerKind::Codex => luban_api::AgentRunnerKind::Codex,
+                    luban_domain::AgentRunnerKind::Amp => luban_api::AgentRunnerKind::Amp,
+                    luban_domain::AgentRunnerKind::Claude => luban_api::AgentRunnerKind::Claude,
+                    luban_domain::AgentRunnerKind::Droid => luban_api::AgentRunnerKind::Droid,
+                }),
+                event,
+            })
+        }
+    }
+}
+
+fn map_codex_thread_item_to_agent_event(item: &CodexThreadItem) -> luban_api::AgentEvent {
+    match item {
+        CodexThreadItem::AgentMessage { id, text } => {
+            luban_api::AgentEvent::Message(luban_api::AgentMessage {
+                id: id.clone(),
+                text: text.clone(),
+            })
+        }
+        _ => {
+            let id = codex_item_id(item).to_owned();
+            let (kind, payload) = map_agent_item(item);
+            luban_api::AgentEvent::Item(luban_api::AgentItem { id, kind, payload })
+        }
+    }
+}
+
+fn map_attachment_ref(att: &AttachmentRef) -> luban_api::AttachmentRef {
+    luban_api::AttachmentRef {
+        id: att.id.clone(),
+        kind: match att.kind {
+            AttachmentKind::Image => luban_api::AttachmentKind::Image,
+            AttachmentKind::Text => luban_api::AttachmentKind::Text,
+            AttachmentKind::File => luban_api::AttachmentKind::File,
+        },
+        name: att.name.clone(),
+        extension: att.extension.clone(),
+        mime: att.mime.clone(),
+        byte_len: att.byte_len,
+        thumbnail_base64: None,
+    }
+}
+
+fn map_agent_item(item: &CodexThreadItem) -> (luban_api::AgentItemKind, serde_json::Value) {
+    let kind = match item {
+        CodexThreadItem::Reasoning { .. } => luban_api::AgentItemKind::Reasoning,
+        CodexThreadItem::CommandExecution { .. } => luban_api::AgentItemKind::CommandExecution,
+        CodexThreadItem::FileChange { .. } => luban_api::AgentItemKind::FileChange,
+        CodexThreadItem::McpToolCall { .. } => luban_api::AgentItemKind::McpToolCall,
+        CodexThreadItem::WebSearch { .. } => luban_api::AgentItemKind::WebSearch,
+        CodexThreadItem::TodoList { .. } => luban_api::AgentItemKind::TodoList,
+        CodexThreadItem::Error { .. } => luban_api::AgentItemKind::Error,
+        CodexThreadItem::AgentMessage { .. } => {
+            unreachable!("agent messages are mapped to AgentEvent::Message")
+        }
+    };
+    let payload = serde_json::to_value(item).unwrap_or(serde_json::Value::Null);
+    (kind, payload)
+}
+
+fn codex_item_id(item: &CodexThreadItem) -> &str {
+    match item {
+        CodexThreadItem::AgentMessage { id, .. } => id,
+        CodexThreadItem::Reasoning { id, .. } => id,
+        CodexThreadItem::CommandExecution { id, .. } => id,
+        CodexThreadItem::FileChange { id, .. } => id,
+        CodexThreadItem::McpToolCall { id, .. } => id,
+        CodexThreadItem::WebSearch { id, .. } => id,
+        CodexThreadItem::TodoList { id, .. } => id,
+        CodexThreadItem::Error { id, .. } => id,
+    }
+}
+
+fn map_client_action(action: luban_api::ClientAction) -> Option<Action> {
+    match action {
+        luban_api::ClientAction::PickProjectPath => None,
+        luban_api::ClientAction::AddProject { path } => Some(Action::AddProject {
+            path: expand_user_path(&path),
+            is_git: true,
+        }),
+        luban_api::ClientAction::AddProjectAndOpen { .. } => None,
+        luban_api::ClientAction::TaskExecute { .. } => None,
+        luban_api::ClientAction::TelegramBotTokenSet { token } => {
+            Some(Action::TelegramBotTokenSet { token })
+        }
+        luban_api::ClientAction::TelegramBotTokenClear => Some(Action::TelegramBotTokenCleared),
+        luban_api::ClientAction::WebhookConfigSet { url, secret } => {
+            Some(Action::WebhookConfigSet { url, secret })
+        }
+        luban_api::ClientAction::WebhookConfigClear => Some(Action::WebhookConfigCleared),
+        luban_api::ClientAction::TelegramPairStart => None,
+        luban_api::ClientAction::TelegramUnpair { chat_id } => {
+            Some(Action::TelegramUnpaired { chat_id })
+        }
+        luban_api::ClientAction::TaskStarSet {
+            workspace_id,
+            thread_id,
+            starred,
+        } => Some(Action::TaskStarSet {
+            workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
+            starred,
+        }),
+        luban_api::ClientAction::TaskStatusSet {
+            workspace_id,
+            thread_id,
+            task_status,
+        } => Some(Action::TaskStatusSet {
+            workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
+            task_status: map_api_task_status(task_status),
+        }),
+        luban_api::ClientAction::RenameThread {
+            workspace_id,
+            thread_id,
+            title,
+        } => Some(Action::RenameThread {
+            workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
+            title,
+        }),
+        luban_api::ClientAction::BulkSetTaskStatus {
+            targets,
+            task_status,
+        } => Some(Action::BulkSetTaskStatus {
+            targets: targets
+                .into_iter()
+                .map(|(workspace_id, thread_id)| {
+                    (
+                        WorkspaceId::from_u64(workspace_id.0),
+                        WorkspaceThreadId::from_u64(thread_id.0),
+                    )
+                })
+                .collect(),
+            task_status: map_api_task_status(task_status),
+        }),
+        luban_api::ClientAction::FeedbackSubmit { .. } => None,
+        luban_api::ClientAction::DeleteProject { .. } => None,
+        luban_api::ClientAction::ArchiveProject { .. } => None,
+        luban_api::ClientAction::RestoreProject { .. } => None,
+        luban_api::ClientAction::ToggleProjectExpanded { .. } => None,
+        luban_api::ClientAction::AssignProjectToGroup { .. } => None,
+        luban_api::ClientAction::ProjectSystemPromptChanged { .. } => None,
+        luban_api::ClientAction::SetProjectWorktreeRoot { .. } => None,
+        luban_api::ClientAction::SetProjectBranchNameTemplate { .. } => None,
+        luban_api::ClientAction::SetProjectPullRequestTarget { .. } => None,
+        luban_api::ClientAction::SetProjectAgentEnv { .. } => None,
+        luban_api::ClientAction::SetProjectAgentDefaults { .. } => None,
+        luban_api::ClientAction::AddProjectSavedCommand { .. } => None,
+        luban_api::ClientAction::RemoveProjectSavedCommand { .. } => None,
+        luban_api::ClientAction::ListStarredTasks => None,
+        luban_api::ClientAction::GetWorkspaceDiffPage { .. } => None,
+        luban_api::ClientAction::AuditWorktrees { .. } => None,
+        luban_api::ClientAction::PruneOrphanedWorktrees { .. } => None,
+        luban_api::ClientAction::ImportThreadJsonl { .. } => None,
+        luban_api::ClientAction::ExportPromptTemplates { .. } => None,
+        luban_api::ClientAction::ImportPromptTemplatesFromDir { .. } => None,
+        luban_api::ClientAction::GeneratePrDescription { .. } => None,
+        luban_api::ClientAction::CompactConversation { .. } => None,
+        luban_api::ClientAction::SearchConversations { .. } => None,
+        luban_api::ClientAction::CreateWorkspace { .. } => None,
+        luban_api::ClientAction::PreviewWorkspace { .. } => None,
+        luban_api::ClientAction::CreateWorkspaceFromBranch { .. } => None,
+        luban_api::ClientAction::SetMainPane { .. } => None,
+        luban_api::ClientAction::MarkWorkspaceRead { workspace_id } => {
+            Some(Action::MarkWorkspaceRead {
+                workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            })
+        }
+        luban_api::ClientAction::MarkAllRead => Some(Action::MarkAllRead),
+        luban_api::ClientAction::OpenWorkspace { workspace_id } => Some(Action::OpenWorkspace {
+            workspace_id: WorkspaceId::from_u64(workspace_id.0),
+        }),
+        luban_api::ClientAction::OpenWorkspaceInIde { workspace_id } => {
+            Some(Action::OpenWorkspaceInIde {
+                workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            })
+        }
+        luban_api::ClientAction::OpenWorkspaceWith {
+            workspace_id,
+            target,
+        } => Some(Action::OpenWorkspaceWith {
+            workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            target: match target {
+                luban_api::OpenTarget::Vscode => OpenTarget::Vscode,
+                luban_api::OpenTarget::Cursor => OpenTarget::Cursor,
+                luban_api::OpenTarget::Zed => OpenTarget::Zed,
+                luban_api::OpenTarget::Idea => OpenTarget::Idea,
+                luban_api::OpenTarget::Sublime => OpenTarget::Sublime,
+                luban_api::OpenTarget::Neovim => OpenTarget::Neovim,
+                luban_api::OpenTarget::Ghostty => OpenTarget::Ghostty,
+                luban_api::OpenTarget::Finder => OpenTarget::Finder,
+            },
+        }),
+        luban_api::ClientAction::OpenWorkspacePullRequest { workspace_id } => {
+            Some(Action::OpenWorkspacePullRequest {
+                workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            })
+        }
+        luban_api::ClientAction::OpenWorkspacePullRequestFailedAction { workspace_id } => {
+            Some(Action::OpenWorkspacePullRequestFailedAction {
+                workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            })
+        }
+        luban_api::ClientAction::ArchiveWorkspace { workspace_id } => {
+            Some(Action::ArchiveWorkspace {
+                workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            })
+        }
+        luban_api::ClientAction::EnsureMainWorkspace { .. } => None,
+        luban_api::ClientAction::OpenProjectMain { .. } => None,
+        luban_api::ClientAction::ChatModelChanged {
+            workspace_id,
+            thread_id,
+            model_id,
+        } => Some(Action::ChatModelChanged {
+            workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
+            model_id,
+        }),
+        luban_api::ClientAction::ChatRunnerChanged {
+            workspace_id,
+            thread_id,
+            runner,
+        } => Some(Action::ChatRunnerChanged {
+            workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
+            runner: map_api_agent_runner_kind(runner),
+        }),
+        luban_api::ClientAction::ChatAmpModeChanged {
+            workspace_id,
+            thread_id,
+            amp_mode,
+        } => Some(Action::ChatAmpModeChanged {
+            workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
+            amp_mode: amp_mode.into(),
+        }),
+        luban_api::ClientAction::ChatClaudeOptionsChanged {
+            workspace_id,
+            thread_id,
+            max_output_tokens,
+            system_prompt,
+        } => Some(Action::ChatClaudeOptionsChanged {
+            workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
+            max_output_tokens,
+            system_prompt,
+        }),
+        luban_api::ClientAction::ChatAutoCommitChanged {
+            workspace_id,
+            thread_id,
+            enabled,
+        } => Some(Action::ChatAutoCommitChanged {
+            workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
+            enabled,
+        }),
+        luban_api::ClientAction::ThinkingEffortChanged {
+            workspace_id,
+            thread_id,
+            thinking_effort,
+        } => Some(Action::ThinkingEffortChanged {
+            workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
+            thinking_effort: match thinking_effort {
+                luban_api::ThinkingEffort::Minimal => ThinkingEffort::Minimal,
+                luban_api::ThinkingEffort::Low => ThinkingEffort::Low,
+                luban_api::ThinkingEffort::Medium => ThinkingEffort::Medium,
+                luban_api::ThinkingEffort::High => ThinkingEffort::High,
+                luban_api::ThinkingEffort::XHigh => ThinkingEffort::XHigh,
+            },
+        }),
+        luban_api::ClientAction::ChatDraftChanged {
+            workspace_id,
+            thread_id,
+            text,
+        } => Some(Action::ChatDraftChanged {
+            workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
+            text,
+        }),
+        luban_api::ClientAction::ThreadNotesChanged {
+            workspace_id,
+            thread_id,
+            notes,
+        } => Some(Action::ThreadNotesChanged {
+            workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
+            notes,
+        }),
+        luban_api::ClientAction::ThreadCwdSubpathChanged {
+            workspace_id,
+            thread_id,
+            cwd_subpath,
+        } => Some(Action::ThreadCwdSubpathChanged {
+            workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
+            cwd_subpath,
+        }),
+        luban_api::ClientAction::AddHunkComment {
+            workspace_id,
+            thread_id,
+            entry_id,
+            path,
+            side,
+            line,
+            text,
+        } => Some(Action::AddHunkComment {
+            workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
+            entry_id,
+            path,
+            side: match side {
+                luban_api::HunkLineSide::Old => luban_domain::HunkLineSide::Old,
+                luban_api::HunkLineSide::New => luban_domain::HunkLineSide::New,
+            },
+            line,
+            text,
+        }),
+        luban_api::ClientAction::TerminalCommandStart { .. } => None,
+        luban_api::ClientAction::ListTerminalHistory { .. } => None,
+        luban_api::ClientAction::SendAgentMessage {
+            workspace_id,
+            thread_id,
+            text,
+            attachments,
+            runner,
+            amp_mode,
+            ..
+        } => Some(Action::SendAgentMessage {
+            workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
+            text,
+            attachments: attachments.into_iter().map(map_api_attachment).collect(),
+            runner: runner.map(map_api_agent_runner_kind),
+            amp_mode,
+        }),
+        luban_api::ClientAction::CancelAndSendAgentMessage { .. } => None,
+        luban_api::ClientAction::QueueAgentMessage {
+            workspace_id,
+            thread_id,
+            text,
+            attachments,
+            runner,
+            amp_mode,
+            ..
+        } => Some(Action::QueueAgentMessage {
+            workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
+            text,
+            attachments: attachments.into_iter().map(map_api_attachment).collect(),
+            runner: runner.map(map_api_agent_runner_kind),
+            amp_mode,
+        }),
+        luban_api::ClientAction::RemoveQueuedPrompt {
+            workspace_id,
+            thread_id,
+            prompt_id,
+            ..
+        } => Some(Action::RemoveQueuedPrompt {
+            workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
+            prompt_id,
+        }),
+        luban_api::ClientAction::ReorderQueuedPrompt {
+            workspace_id,
+            thread_id,
+            active_id,
+            over_id,
+            ..
+        } => Some(Action::ReorderQueuedPrompt {
+            workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
+            active_id,
+            over_id,
+        }),
+        luban_api::ClientAction::UpdateQueuedPrompt {
+            workspace_id,
+            thread_id,
+            prompt_id,
+            text,
+            attachments,
+            model_id,
+            thinking_effort,
+            runner,
+            amp_mode,
+            ..
+        } => Some(Action::UpdateQueuedPrompt {
+            workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
+            prompt_id,
+            text,
+            attachments: attachments.into_iter().map(map_api_attachment).collect(),
+            model_id,
+            thinking_effort: match thinking_effort {
+                luban_api::ThinkingEffort::Minimal => ThinkingEffort::Minimal,
+                luban_api::ThinkingEffort::Low => ThinkingEffort::Low,
+                luban_api::ThinkingEffort::Medium => ThinkingEffort::Medium,
+                luban_api::ThinkingEffort::High => ThinkingEffort::High,
+                luban_api::ThinkingEffort::XHigh => ThinkingEffort::XHigh,
+            },
+            runner: runner.map(map_api_agent_runner_kind),
             amp_mode,
         }),
-        luban_api::ClientAction::CancelAndSendAgentMessage { .. } => None,
-        luban_api::ClientAction::QueueAgentMessage {
+        luban_api::ClientAction::WorkspaceRenameBranch {
+            workspace_id,
+            branch_name,
+        } => Some(Action::WorkspaceBranchRenameRequested {
+            workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            requested_branch_name: branch_name,
+        }),
+        luban_api::ClientAction::WorkspaceStashChanges { workspace_id } => {
+            Some(Action::WorkspaceStashChanges {
+                workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            })
+        }
+        luban_api::ClientAction::WorkspaceCommit {
+            workspace_id,
+            message,
+            paths,
+        } => Some(Action::WorkspaceCommit {
+            workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            message,
+            paths,
+        }),
+        luban_api::ClientAction::WorkspaceStashPop { workspace_id } => {
+            Some(Action::WorkspaceStashPop {
+                workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            })
+        }
+        luban_api::ClientAction::WorkspaceAiRenameBranch {
+            workspace_id,
+            thread_id,
+        } => Some(Action::WorkspaceBranchAiRenameRequested {
+            workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
+        }),
+        luban_api::ClientAction::CancelAgentTurn {
+            workspace_id,
+            thread_id,
+        } => Some(Action::CancelAgentTurn {
+            workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
+        }),
+        luban_api::ClientAction::RespondToToolApproval {
+            workspace_id,
+            thread_id,
+            call_id,
+            approved,
+        } => Some(Action::RespondToToolApproval {
+            workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
+            call_id,
+            approved,
+        }),
+        luban_api::ClientAction::CancelAndClearQueue {
+            workspace_id,
+            thread_id,
+            ..
+        } => Some(Action::CancelAndClearQueue {
+            workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
+        }),
+        luban_api::ClientAction::RetryLastTurnWithRunner {
+            workspace_id,
+            thread_id,
+            runner,
+            model_id,
+            amp_mode,
+            cancel_first,
+        } => {
+            let runner = map_api_agent_runner_kind(runner);
+            Some(Action::RetryLastTurnWithRunner {
+                workspace_id: WorkspaceId::from_u64(workspace_id.0),
+                thread_id: WorkspaceThreadId::from_u64(thread_id.0),
+                runner,
+                model_id,
+                amp_mode: if runner == luban_domain::AgentRunnerKind::Amp {
+                    amp_mode
+                } else {
+                    None
+                },
+                cancel_first,
+            })
+        }
+        luban_api::ClientAction::CreateWorkspaceThread { workspace_id } => {
+            Some(Action::CreateWorkspaceThread {
+                workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            })
+        }
+        luban_api::ClientAction::DuplicateWorkspaceThread {
+            workspace_id,
+            thread_id,
+            up_to_entry_id,
+        } => Some(Action::DuplicateWorkspaceThread {
+            workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
+            up_to_entry_id,
+        }),
+        luban_api::ClientAction::ActivateWorkspaceThread {
+            workspace_id,
+            thread_id,
+        } => Some(Action::ActivateWorkspaceThread {
+            workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
+        }),
+        luban_api::ClientAction::CloseWorkspaceThreadTab {
+            workspace_id,
+            thread_id,
+        } => Some(Action::CloseWorkspaceThreadTab {
+            workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
+        }),
+        // Handled directly in apply_client_action (DB delete + domain purge)
+        luban_api::ClientAction::DeleteWorkspaceThread { .. } => None,
+        luban_api::ClientAction::MoveThread { .. } => None,
+        luban_api::ClientAction::RestoreWorkspaceThreadTab {
+            workspace_id,
+            thread_id,
+        } => Some(Action::RestoreWorkspaceThreadTab {
+            workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
+        }),
+        luban_api::ClientAction::ReorderWorkspaceThreadTab {
+            workspace_id,
+            thread_id,
+            to_index,
+        } => Some(Action::ReorderWorkspaceThreadTab {
+            workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
+            to_index,
+        }),
+        luban_api::ClientAction::ActivateAdjacentThread {
+            workspace_id,
+            direction,
+        } => Some(Action::ActivateAdjacentWorkspaceThread {
+            workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            direction: match direction {
+                luban_api::TabDirection::Next => luban_domain::TabDirection::Next,
+                luban_api::TabDirection::Prev => luban_domain::TabDirection::Prev,
+            },
+        }),
+        luban_api::ClientAction::OpenButtonSelectionChanged { selection } => {
+            Some(Action::OpenButtonSelectionChanged { selection })
+        }
+        luban_api::ClientAction::SidebarProjectOrderChanged { project_ids } => {
+            Some(Action::SidebarProjectOrderChanged {
+                project_ids: project_ids.into_iter().map(|id| id.0).collect(),
+            })
+        }
+        luban_api::ClientAction::CreateProjectGroup { name } => {
+            Some(Action::CreateProjectGroup { name })
+        }
+        luban_api::ClientAction::RenameProjectGroup { group_id, name } => {
+            Some(Action::RenameProjectGroup {
+                group_id: luban_domain::ProjectGroupId::from_u64(group_id.0),
+                name,
+            })
+        }
+        luban_api::ClientAction::DeleteProjectGroup { group_id } => {
+            Some(Action::DeleteProjectGroup {
+                group_id: luban_domain::ProjectGroupId::from_u64(group_id.0),
+            })
+        }
+        luban_api::ClientAction::AppearanceThemeChanged { theme } => {
+            Some(Action::AppearanceThemeChanged {
+                theme: match theme {
+                    luban_api::AppearanceTheme::Light => luban_domain::AppearanceTheme::Light,
+                    luban_api::AppearanceTheme::Dark => luban_domain::AppearanceTheme::Dark,
+                    luban_api::AppearanceTheme::System => luban_domain::AppearanceTheme::System,
+                },
+            })
+        }
+        luban_api::ClientAction::AppearanceFontsChanged { fonts } => {
+            Some(Action::AppearanceFontsChanged {
+                ui_font: fonts.ui_font,
+                chat_font: fonts.chat_font,
+                code_font: fonts.code_font,
+                terminal_font: fonts.terminal_font,
+            })
+        }
+        luban_api::ClientAction::AppearanceGlobalZoomChanged { zoom } => {
+            Some(Action::AppearanceGlobalZoomChanged { zoom })
+        }
+        luban_api::ClientAction::CodexEnabledChanged { enabled } => {
+            Some(Action::AgentCodexEnabledChanged { enabled })
+        }
+        luban_api::ClientAction::AmpEnabledChanged { enabled } => {
+            Some(Action::AgentAmpEnabledChanged { enabled })
+        }
+        luban_api::ClientAction::ClaudeEnabledChanged { enabled } => {
+            Some(Action::AgentClaudeEnabledChanged { enabled })
+        }
+        luban_api::ClientAction::DroidEnabledChanged { enabled } => {
+            Some(Action::AgentDroidEnabledChanged { enabled })
+        }
+        luban_api::ClientAction::MergeReasoningEntriesChanged { enabled } => {
+            Some(Action::AgentMergeReasoningEntriesChanged { enabled })
+        }
+        luban_api::ClientAction::PauseQueueOnPatchFailureChanged { enabled } => {
+            Some(Action::AgentPauseQueueOnPatchFailureChanged { enabled })
+        }
+        luban_api::ClientAction::RunnerPromptPrefixChanged { runner, prefix } => {
+            Some(Action::AgentRunnerPromptPrefixChanged {
+                runner: map_api_agent_runner_kind(runner),
+                prefix,
+            })
+        }
+        luban_api::ClientAction::RunnerPromptSuffixChanged { runner, suffix } => {
+            Some(Action::AgentRunnerPromptSuffixChanged {
+                runner: map_api_agent_runner_kind(runner),
+                suffix,
+            })
+        }
+        luban_api::ClientAction::ModelThinkingEffortCapChanged { model_id, cap } => {
+            Some(Action::ModelThinkingEffortCapChanged {
+                model_id,
+                cap: cap.map(map_api_thinking_effort),
+            })
+        }
+        luban_api::ClientAction::ItemDedupWindowChanged { window } => {
+            Some(Action::AgentItemDedupWindowChanged {
+                window: usize::try_from(window).unwrap_or(usize::MAX),
+            })
+        }
+        luban_api::ClientAction::AutoRetryOnFailureChanged { retries } => {
+            Some(Action::AgentAutoRetryOnFailureChanged { retries })
+        }
+        luban_api::ClientAction::CommandOutputMaxBytesChanged { max_bytes } => {
+            Some(Action::AgentCommandOutputMaxBytesChanged {
+                max_bytes: usize::try_from(max_bytes).unwrap_or(usize::MAX),
+            })
+        }
+        luban_api::ClientAction::AutoArchiveConfigChanged { days } => {
+            Some(Action::AutoArchiveConfigChanged { days })
+        }
+        luban_api::ClientAction::AgentRunnerChanged { runner } => {
+            Some(Action::AgentRunnerChanged {
+                runner: match runner {
+                    luban_api::AgentRunnerKind::Codex => luban_domain::AgentRunnerKind::Codex,
+                    luban_api::AgentRunnerKind::Amp => luban_domain::AgentRunnerKind::Amp,
+                    luban_api::AgentRunnerKind::Claude => luban_domain::AgentRunnerKind::Claude,
+                    luban_api::AgentRunnerKind::Droid => luban_domain::AgentRunnerKind::Droid,
+                },
+            })
+        }
+        luban_api::ClientAction::AgentAmpModeChanged { mode } => {
+            Some(Action::AgentAmpModeChanged { mode: mode.into() })
+        }
+        luban_api::ClientAction::TaskPromptTemplateChanged {
+            intent_kind,
+            template,
+        } => Some(Action::TaskPromptTemplateChanged {
+            intent_kind: match intent_kind {
+                luban_api::TaskIntentKind::Fix => luban_domain::TaskIntentKind::Fix,
+                luban_api::TaskIntentKind::Implement => luban_domain::TaskIntentKind::Implement,
+                luban_api::TaskIntentKind::Review => luban_domain::TaskIntentKind::Review,
+                luban_api::TaskIntentKind::Discuss => luban_domain::TaskIntentKind::Discuss,
+                luban_api::TaskIntentKind::Other => luban_domain::TaskIntentKind::Other,
+            },
+            template,
+        }),
+        luban_api::ClientAction::SystemPromptTemplateChanged { kind, template } => {
+            Some(Action::SystemPromptTemplateChanged {
+                kind: match kind {
+                    luban_api::SystemTaskKind::InferType => luban_domain::SystemTaskKind::InferType,
+                    luban_api::SystemTaskKind::RenameBranch => {
+                        luban_domain::SystemTaskKind::RenameBranch
+                    }
+                    luban_api::SystemTaskKind::AutoTitleThread => {
+                        luban_domain::SystemTaskKind::AutoTitleThread
+                    }
+                    luban_api::SystemTaskKind::AutoUpdateTaskStatus => {
+                        luban_domain::SystemTaskKind::AutoUpdateTaskStatus
+                    }
+                    luban_api::SystemTaskKind::PrDescription => {
+                        luban_domain::SystemTaskKind::PrDescription
+                    }
+                    luban_api::SystemTaskKind::ConversationSummary => {
+                        luban_domain::SystemTaskKind::ConversationSummary
+                    }
+                },
+                template,
+            })
+        }
+        luban_api::ClientAction::CodexCheck
+        | luban_api::ClientAction::CodexConfigTree
+        | luban_api::ClientAction::CodexConfigListDir { .. }
+        | luban_api::ClientAction::CodexConfigReadFile { .. }
+        | luban_api::ClientAction::CodexConfigWriteFile { .. }
+        | luban_api::ClientAction::AmpCheck
+        | luban_api::ClientAction::AmpConfigTree
+        | luban_api::ClientAction::AmpConfigListDir { .. }
+        | luban_api::ClientAction::AmpConfigReadFile { .. }
+        | luban_api::ClientAction::AmpConfigWriteFile { .. }
+        | luban_api::ClientAction::ClaudeCheck
+        | luban_api::ClientAction::ClaudeConfigTree
+        | luban_api::ClientAction::ClaudeConfigListDir { .. }
+        | luban_api::ClientAction::ClaudeConfigReadFile { .. }
+        | luban_api::ClientAction::ClaudeConfigWriteFile { .. }
+        | luban_api::ClientAction::DroidCheck
+        | luban_api::ClientAction::DroidConfigTree
+        | luban_api::ClientAction::DroidConfigListDir { .. }
+        | luban_api::ClientAction::DroidConfigReadFile { .. }
+        | luban_api::ClientAction::DroidConfigWriteFile { .. }
+        | luban_api::ClientAction::CancelRequest { .. } => None,
+        luban_api::ClientAction::DismissError { index } => Some(Action::DismissError {
+            index: usize::try_from(index).unwrap_or(usize::MAX),
+        }),
+    }
+}
+
+fn expand_user_path(raw: &str) -> PathBuf {
+    let trimmed = raw.trim();
+    if trimmed == "~" {
+        if let Some(home) = std::env::var_os("HOME") {
+            return PathBuf::from(home);
+        }
+        return PathBuf::from(trimmed);
+    }
+
+    if let Some(suffix) = trimmed.strip_prefix("~/")
+        && let Some(home) = std::env::var_os("HOME")
+    {
+        return PathBuf::from(home).join(suffix);
+    }
+
+    PathBuf::from(trimmed)
+}
+
+/// Parses one exported `luban_api::ConversationEntry` per line, collecting malformed lines
+/// instead of failing.
+fn parse_conversation_jsonl(
+    jsonl: &str,
+) -> (
+    Vec<ConversationEntry>,
+    Vec<luban_domain::ConversationImportLineError>,
+) {
+    let mut entries = Vec::new();
+    let mut errors = Vec::new();
+    for (idx, line) in jsonl.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let parsed = serde_json::from_str::<luban_api::ConversationEntry>(line)
+            .map_err(|err| err.to_string())
+            .and_then(map_api_conversation_entry);
+        match parsed {
+            // The new task records its own creation; an exported one would duplicate it.
+            Ok(ConversationEntry::SystemEvent {
+                event: luban_domain::ConversationSystemEvent::TaskCreated,
+                ..
+            }) => {}
+            Ok(entry) => entries.push(entry),
+            Err(message) => errors.push(luban_domain::ConversationImportLineError {
+                line: idx as u64 + 1,
+                message,
+            }),
+        }
+    }
+    (entries, errors)
+}
+
+fn map_api_conversation_entry(
+    entry: luban_api::ConversationEntry,
+) -> Result<ConversationEntry, String> {
+    Ok(match entry {
+        luban_api::ConversationEntry::SystemEvent(entry) => ConversationEntry::SystemEvent {
+            entry_id: entry.entry_id,
+            created_at_unix_ms: entry.created_at_unix_ms,
+            event: match entry.event {
+                luban_api::ConversationSystemEvent::TaskCreated => {
+                    luban_domain::ConversationSystemEvent::TaskCreated
+                }
+                luban_api::ConversationSystemEvent::TaskArchived => {
+                    luban_domain::ConversationSystemEvent::TaskArchived
+                }
+                luban_api::ConversationSystemEvent::TaskStatusChanged { from, to } => {
+                    luban_domain::ConversationSystemEvent::TaskStatusChanged {
+                        from: map_api_task_status(from),
+                        to: map_api_task_status(to),
+                    }
+                }
+                luban_api::ConversationSystemEvent::TaskStatusSuggestion {
+                    from,
+                    to,
+                    title,
+                    explanation_markdown,
+                } => luban_domain::ConversationSystemEvent::TaskStatusSuggestion {
+                    from: map_api_task_status(from),
+                    to: map_api_task_status(to),
+                    title,
+                    explanation_markdown,
+                },
+                luban_api::ConversationSystemEvent::TurnRetriedWithRunner { runner, model_id } => {
+                    luban_domain::ConversationSystemEvent::TurnRetriedWithRunner {
+                        runner: map_api_agent_runner_kind(runner),
+                        model_id,
+                    }
+                }
+                luban_api::ConversationSystemEvent::HistorySummarized {
+                    summary_markdown,
+                    collapsed_entries,
+                } => luban_domain::ConversationSystemEvent::HistorySummarized {
+                    summary_markdown,
+                    collapsed_entries,
+                },
+                luban_api::ConversationSystemEvent::HistoryPruned { pruned_entries } => {
+                    luban_domain::ConversationSystemEvent::HistoryPruned { pruned_entries }
+                }
+            },
+        },
+        luban_api::ConversationEntry::UserEvent(entry) => ConversationEntry::UserEvent {
+            entry_id: entry.entry_id,
+            created_at_unix_ms: entry.created_at_unix_ms,
+            event: match entry.event {
+                luban_api::UserEvent::Message(message) => luban_domain::UserEvent::Message {
+                    text: message.text,
+                    attachments: message
+                        .attachments
+                        .into_iter()
+                        .map(map_api_attachment)
+                        .collect(),
+                },
+                luban_api::UserEvent::TerminalCommandStarted(started) => {
+                    luban_domain::UserEvent::TerminalCommandStarted {
+                        id: started.id,
+                        command: started.command,
+                        reconnect: started.reconnect,
+                    }
+                }
+                luban_api::UserEvent::TerminalCommandFinished(finished) => {
+                    luban_domain::UserEvent::TerminalCommandFinished {
+                        id: finished.id,
+                        command: finished.command,
+                        reconnect: finished.reconnect,
+                        output_base64: finished.output_base64,
+                        output_byte_len: finished.output_byte_len,
+                    }
+                }
+            },
+        },
+        luban_api::ConversationEntry::AgentEvent(entry) => ConversationEntry::AgentEvent {
+            entry_id: entry.entry_id,
+            created_at_unix_ms: entry.created_at_unix_ms,
+            runner: entry.runner.map(map_api_agent_runner_kind),
+            event: match entry.event {
+                luban_api::AgentEvent::Message(message) => luban_domain::AgentEvent::Message {
+                    id: message.id,
+                    text: message.text,
+                },
+                // `map_agent_item` serializes the whole item as the payload.
+                luban_api::AgentEvent::Item(item) => luban_domain::AgentEvent::Item {
+                    item: Box::new(
+                        serde_json::from_value::<CodexThreadItem>(item.payload)
+                            .map_err(|err| format!("invalid item {}: {err}", item.id))?,
+                    ),
+                },
+                luban_api::AgentEvent::TurnUsage { usage_json } => {
+                    luban_domain::AgentEvent::TurnUsage {
+                        usage: usage_json
+                            .map(serde_json::from_value)
+                            .transpose()
+                            .map_err(|err| format!("invalid turn usage: {err}"))?,
+                    }
+                }
+                luban_api::AgentEvent::TurnStarted { at_unix_ms } => {
+                    luban_domain::AgentEvent::TurnStarted { at_unix_ms }
+                }
+                luban_api::AgentEvent::TurnDuration { duration_ms } => {
+                    luban_domain::AgentEvent::TurnDuration { duration_ms }
+                }
+                luban_api::AgentEvent::TurnCanceled => luban_domain::AgentEvent::TurnCanceled,
+                luban_api::AgentEvent::TurnError { message, code } => {
+                    luban_domain::AgentEvent::TurnError { message, code }
+                }
+            },
+        },
+    })
+}
+
+fn map_api_attachment(att: luban_api::AttachmentRef) -> AttachmentRef {
+    AttachmentRef {
+        id: att.id,
+        kind: match att.kind {
+            luban_api::AttachmentKind::Image => AttachmentKind::Image,
+            luban_api::AttachmentKind::Text => AttachmentKind::Text,
+            luban_api::AttachmentKind::File => AttachmentKind::File,
+        },
+        name: att.name,
+        extension: att.extension,
+        mime: att.mime,
+        byte_len: att.byte_len,
+    }
+}
+
+/// The wire `type` tag of a client action, e.g. `archive_workdir`.
+fn client_action_type(action: &luban_api::ClientAction) -> Option<String> {
+    let value = serde_json::to_value(action).ok()?;
+    value.get("type")?.as_str().map(ToOwned::to_owned)
+}
+
+fn map_api_task_status(status: luban_api::TaskStatus) -> luban_domain::TaskStatus {
+    match status {
+        luban_api::TaskStatus::Backlog => luban_domain::TaskStatus::Backlog,
+        luban_api::TaskStatus::Todo => luban_domain::TaskStatus::Todo,
+        luban_api::TaskStatus::Iterating => luban_domain::TaskStatus::Iterating,
+        luban_api::TaskStatus::Validating => luban_domain::TaskStatus::Validating,
+        luban_api::TaskStatus::Done => luban_domain::TaskStatus::Done,
+        luban_api::TaskStatus::Canceled => luban_domain::TaskStatus::Canceled,
+    }
+}
+
+fn map_api_agent_runner_kind(kind: luban_api::AgentRunnerKind) -> luban_domain::AgentRunnerKind {
+    match kind {
+        luban_api::AgentRunnerKind::Codex => luban_domain::AgentRunnerKind::Codex,
+        luban_api::AgentRunnerKind::Amp => luban_domain::AgentRunnerKind::Amp,
+        luban_api::AgentRunnerKind::Claude => luban_domain::AgentRunnerKind::Claude,
+        luban_api::AgentRunnerKind::Droid => luban_domain::AgentRunnerKind::Droid,
+    }
+}
+
+fn map_agent_runner_kind(kind: luban_domain::AgentRunnerKind) -> luban_api::AgentRunnerKind {
+    match kind {
+        luban_domain::AgentRunnerKind::Codex => luban_api::AgentRunnerKind::Codex,
+        luban_domain::AgentRunnerKind::Amp => luban_api::AgentRunnerKind::Amp,
+        luban_domain::AgentRunnerKind::Claude => luban_api::AgentRunnerKind::Claude,
+        luban_domain::AgentRunnerKind::Droid => luban_api::AgentRunnerKind::Droid,
+    }
+}
+
+fn map_thinking_effort(effort: ThinkingEffort) -> luban_api::ThinkingEffort {
+    match effort {
+        ThinkingEffort::Minimal => luban_api::ThinkingEffort::Minimal,
+        ThinkingEffort::Low => luban_api::ThinkingEffort::Low,
+        ThinkingEffort::Medium => luban_api::ThinkingEffort::Medium,
+        ThinkingEffort::High => luban_api::ThinkingEffort::High,
+        ThinkingEffort::XHigh => luban_api::ThinkingEffort::XHigh,
+    }
+}
+
+fn map_api_thinking_effort(effort: luban_api::ThinkingEffort) -> ThinkingEffort {
+    match effort {
+        luban_api::ThinkingEffort::Minimal => ThinkingEffort::Minimal,
+        luban_api::ThinkingEffort::Low => ThinkingEffort::Low,
+        luban_api::ThinkingEffort::Medium => ThinkingEffort::Medium,
+        luban_api::ThinkingEffort::High => ThinkingEffort::High,
+        luban_api::ThinkingEffort::XHigh => ThinkingEffort::XHigh,
+    }
+}
+
+fn map_hunk_comment(comment: &luban_domain::HunkComment) -> luban_api::HunkCommentSnapshot {
+    luban_api::HunkCommentSnapshot {
+        entry_id: comment.entry_id.clone(),
+        path: comment.path.clone(),
+        side: match comment.side {
+            luban_domain::HunkLineSide::Old => luban_api::HunkLineSide::Old,
+            luban_domain::HunkLineSide::New => luban_api::HunkLineSide::New,
+        },
+        line: comment.line,
+        text: comment.text.clone(),
+        created_at_unix_ms: comment.created_at_unix_ms,
+    }
+}
+
+fn map_collapsed_range(range: &luban_domain::CollapsedRange) -> luban_api::CollapsedRangeSnapshot {
+    luban_api::CollapsedRangeSnapshot {
+        start_entry_id: range.start_entry_id.clone(),
+        end_entry_id: range.end_entry_id.clone(),
+        entry_count: range.entry_count,
+        summary_entry_id: range.summary_entry_id.clone(),
+    }
+}
+
+pub fn new_default_services() -> anyhow::Result<Arc<dyn ProjectWorkspaceService>> {
+    Ok(GitWorkspaceService::new_with_options(SqliteStoreOptions {
+        persist_ui_state: true,
+    })
+    .context("failed to init backend services")?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use luban_domain::{
+        CodexCommandExecutionStatus, ContextImage, ContextItem,
+        ConversationSnapshot as DomainConversationSnapshot, ConversationThreadMeta,
+        PersistedAppState, PersistedProject, PersistedWorkspace, WorkspaceStatus,
+    };
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    type SavedQueueState = (
+        bool,
+        Option<u64>,
+        Option<u64>,
+        Vec<luban_domain::QueuedPrompt>,
+    );
+
+    struct TestServices;
+
+    impl ProjectWorkspaceService for TestServices {
+        fn load_app_state(&self) -> Result<PersistedAppState, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn save_app_state(&self, _snapshot: PersistedAppState) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn create_workspace(
+            &self,
+            _project_path: PathBuf,
+            _project_slug: String,
+            _branch_name_hint: Option<String>,
+            _worktree_root: Option<PathBuf>,
+            _branch_name_template: Option<luban_domain::BranchNameTemplate>,
+        ) -> Result<luban_domain::CreatedWorkspace, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn open_workspace_in_ide(&self, _worktree_path: PathBuf) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn archive_workspace(
+            &self,
+            _project_path: PathBuf,
+            _worktree_path: PathBuf,
+            _branch_name: String,
+        ) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn rename_workspace_branch(
+            &self,
+            _worktree_path: PathBuf,
+            _requested_branch_name: String,
+            _branch_name_template: Option<luban_domain::BranchNameTemplate>,
+        ) -> Result<String, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn ensure_conversation(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _thread_id: u64,
+        ) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn list_conversation_threads(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+        ) -> Result<Vec<ConversationThreadMeta>, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn load_conversation(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _thread_id: u64,
+        ) -> Result<DomainConversationSnapshot, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn load_conversation_page(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _thread_id: u64,
+            _before: Option<u64>,
+            _limit: u64,
+        ) -> Result<DomainConversationSnapshot, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn store_context_image(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _image: ContextImage,
+        ) -> Result<AttachmentRef, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn store_context_text(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _text: String,
+            _extension: String,
+        ) -> Result<AttachmentRef, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn store_context_file(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _source_path: PathBuf,
+        ) -> Result<AttachmentRef, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn record_context_item(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _attachment: AttachmentRef,
+            _created_at_unix_ms: u64,
+        ) -> Result<u64, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn list_context_items(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+        ) -> Result<Vec<ContextItem>, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn delete_context_item(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _context_id: u64,
+        ) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn run_agent_turn_streamed(
+            &self,
+            _request: luban_domain::RunAgentTurnRequest,
+            _cancel: Arc<AtomicBool>,
+            _on_event: Arc<dyn Fn(luban_domain::AgentThreadEvent) + Send + Sync>,
+        ) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn gh_is_authorized(&self) -> Result<bool, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn gh_pull_request_info(
+            &self,
+            _worktree_path: PathBuf,
+            _target: luban_domain::PullRequestTarget,
+        ) -> Result<Option<PullRequestInfo>, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn gh_open_pull_request(&self, _worktree_path: PathBuf) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn gh_open_pull_request_failed_action(
+            &self,
+            _worktree_path: PathBuf,
+        ) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+    }
+
+    #[derive(Default)]
+    struct ReconcileRecordingServices {
+        appended_entries: Mutex<Vec<ConversationEntry>>,
+        saved_queue_state: Mutex<Vec<SavedQueueState>>,
+    }
+
+    impl ProjectWorkspaceService for ReconcileRecordingServices {
+        fn load_app_state(&self) -> Result<PersistedAppState, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn save_app_state(&self, _snapshot: PersistedAppState) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn create_workspace(
+            &self,
+            _project_path: PathBuf,
+            _project_slug: String,
+            _branch_name_hint: Option<String>,
+            _worktree_root: Option<PathBuf>,
+            _branch_name_template: Option<luban_domain::BranchNameTemplate>,
+        ) -> Result<luban_domain::CreatedWorkspace, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn open_workspace_in_ide(&self, _worktree_path: PathBuf) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn archive_workspace(
+            &self,
+            _project_path: PathBuf,
+            _worktree_path: PathBuf,
+            _branch_name: String,
+        ) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn rename_workspace_branch(
+            &self,
+            _worktree_path: PathBuf,
+            _requested_branch_name: String,
+            _branch_name_template: Option<luban_domain::BranchNameTemplate>,
+        ) -> Result<String, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn ensure_conversation(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _thread_id: u64,
+        ) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn list_conversation_threads(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+        ) -> Result<Vec<ConversationThreadMeta>, String> {
+            Ok(vec![ConversationThreadMeta {
+                thread_id: WorkspaceThreadId::from_u64(1),
+                remote_thread_id: None,
+                title: "t1".to_owned(),
+                created_at_unix_seconds: 1,
+                updated_at_unix_seconds: 2,
+                task_status: luban_domain::TaskStatus::Todo,
+                last_message_seq: 1,
+                task_status_last_analyzed_message_seq: 0,
+                turn_status: luban_domain::TurnStatus::Running,
+                last_turn_result: None,
+                total_tokens: 0,
+            }])
+        }
+
+        fn load_conversation(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _thread_id: u64,
+        ) -> Result<DomainConversationSnapshot, String> {
+            Ok(DomainConversationSnapshot {
+                title: Some("t1".to_owned()),
+                thread_id: None,
+                task_status: luban_domain::TaskStatus::Todo,
+                runner: None,
+                agent_model_id: None,
+                thinking_effort: None,
+                amp_mode: None,
+                claude_max_output_tokens: None,
+                claude_system_prompt: None,
+                auto_commit: false,
+                entries: vec![ConversationEntry::UserEvent {
+                    entry_id: "e_1".to_owned(),
+                    created_at_unix_ms: 1,
+                    event: luban_domain::UserEvent::Message {
+                        text: "hi".to_owned(),
+                        attachments: Vec::new(),
+                    },
+                }],
+                entries_total: 1,
+                entries_start: 0,
+                pending_prompts: vec![luban_domain::QueuedPrompt {
+                    id: 1,
+                    text: "queued".to_owned(),
+                    attachments: Vec::new(),
+                    run_config: luban_domain::AgentRunConfig {
+                        runner: luban_domain::AgentRunnerKind::Codex,
+                        model_id: "gpt-5.2".to_owned(),
+                        thinking_effort: ThinkingEffort::Medium,
+                        amp_mode: None,
+                        claude_max_output_tokens: None,
+                        claude_system_prompt: None,
+                    },
+                }],
+                queue_paused: false,
+                run_started_at_unix_ms: Some(10),
+                run_finished_at_unix_ms: None,
+                notes: String::new(),
+                cwd_subpath: None,
+                hunk_comments: Vec::new(),
+                collapsed_ranges: Vec::new(),
+                draft: String::new(),
+                total_usage: Default::default(),
+            })
+        }
+
+        fn load_conversation_page(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _thread_id: u64,
+            _before: Option<u64>,
+            _limit: u64,
+        ) -> Result<DomainConversationSnapshot, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn append_conversation_entries(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _thread_id: u64,
+            entries: Vec<ConversationEntry>,
+        ) -> Result<(), String> {
+            self.appended_entries
+                .lock()
+                .map_err(|_| "poisoned mutex".to_owned())?
+                .extend(entries);
+            Ok(())
+        }
+
+        fn save_conversation_queue_state(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _thread_id: u64,
+            queue_paused: bool,
+            run_started_at_unix_ms: Option<u64>,
+            run_finished_at_unix_ms: Option<u64>,
+            pending_prompts: Vec<luban_domain::QueuedPrompt>,
+        ) -> Result<(), String> {
+            self.saved_queue_state
+                .lock()
+                .map_err(|_| "poisoned mutex".to_owned())?
+                .push((
+                    queue_paused,
+                    run_started_at_unix_ms,
+                    run_finished_at_unix_ms,
+                    pending_prompts,
+                ));
+            Ok(())
+        }
+
+        fn store_context_image(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _image: ContextImage,
+        ) -> Result<AttachmentRef, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn store_context_text(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _text: String,
+            _extension: String,
+        ) -> Result<AttachmentRef, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn store_context_file(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _source_path: PathBuf,
+        ) -> Result<AttachmentRef, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn record_context_item(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _attachment: AttachmentRef,
+            _created_at_unix_ms: u64,
+        ) -> Result<u64, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn list_context_items(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+        ) -> Result<Vec<ContextItem>, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn delete_context_item(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _context_id: u64,
+        ) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn run_agent_turn_streamed(
+            &self,
+            _request: luban_domain::RunAgentTurnRequest,
+            _cancel: Arc<AtomicBool>,
+            _on_event: Arc<dyn Fn(luban_domain::AgentThreadEvent) + Send + Sync>,
+        ) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn gh_is_authorized(&self) -> Result<bool, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn gh_pull_request_info(
+            &self,
+            _worktree_path: PathBuf,
+            _target: luban_domain::PullRequestTarget,
+        ) -> Result<Option<PullRequestInfo>, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn gh_open_pull_request(&self, _worktree_path: PathBuf) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn gh_open_pull_request_failed_action(
+            &self,
+            _worktree_path: PathBuf,
+        ) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+    }
+
+    struct IdentityServices;
+
+    impl ProjectWorkspaceService for IdentityServices {
+        fn load_app_state(&self) -> Result<PersistedAppState, String> {
+            Ok(PersistedAppState {
+                projects: Vec::new(),
+                sidebar_width: None,
+                terminal_pane_width: None,
+                global_zoom_percent: None,
+                appearance_theme: None,
+                appearance_ui_font: None,
+                appearance_chat_font: None,
+                appearance_code_font: None,
+                appearance_terminal_font: None,
+                agent_default_model_id: None,
+                agent_runner_default_models: HashMap::new(),
+                agent_runner_prompt_prefixes: HashMap::new(),
+                agent_runner_prompt_suffixes: HashMap::new(),
+                model_thinking_effort_caps: HashMap::new(),
+                agent_default_thinking_effort: None,
+                agent_default_runner: None,
+                agent_amp_mode: None,
+                agent_codex_enabled: Some(true),
+                agent_amp_enabled: Some(true),
+                agent_claude_enabled: Some(true),
+                agent_droid_enabled: Some(true),
+                last_open_workspace_id: None,
+                open_button_selection: None,
+                sidebar_project_order: Vec::new(),
+                project_groups: Vec::new(),
+                workspace_active_thread_id: HashMap::new(),
+                workspace_open_tabs: HashMap::new(),
+                workspace_archived_tabs: HashMap::new(),
+                workspace_next_thread_id: HashMap::new(),
+                workspace_chat_scroll_y10: HashMap::new(),
+                workspace_chat_scroll_anchor: HashMap::new(),
+                workspace_unread_completions: HashMap::new(),
+                workspace_thread_run_config_overrides: HashMap::new(),
+                starred_tasks: HashMap::new(),
+                task_prompt_templates: HashMap::new(),
+                telegram_enabled: None,
+                telegram_bot_token: None,
+                telegram_bot_username: None,
+                telegram_paired_chat_ids: Vec::new(),
+                telegram_topic_bindings: None,
+                webhook_url: None,
+                webhook_secret: None,
+                agent_merge_reasoning_entries: None,
+                agent_pause_queue_on_patch_failure: None,
+                agent_item_dedup_window: None,
+                agent_auto_retry_on_failure: None,
+                agent_command_output_max_bytes: None,
+                auto_archive_after_days: None,
+            })
+        }
+
+        fn save_app_state(&self, _snapshot: PersistedAppState) -> Result<(), String> {
+            Ok(())
+        }
+
+        fn create_workspace(
+            &self,
+            _project_path: PathBuf,
+            _project_slug: String,
+            _branch_name_hint: Option<String>,
+            _worktree_root: Option<PathBuf>,
+            _branch_name_template: Option<luban_domain::BranchNameTemplate>,
+        ) -> Result<luban_domain::CreatedWorkspace, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn open_workspace_in_ide(&self, _worktree_path: PathBuf) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn archive_workspace(
+            &self,
+            _project_path: PathBuf,
+            _worktree_path: PathBuf,
+            _branch_name: String,
+        ) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn rename_workspace_branch(
+            &self,
+            _worktree_path: PathBuf,
+            _requested_branch_name: String,
+            _branch_name_template: Option<luban_domain::BranchNameTemplate>,
+        ) -> Result<String, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn ensure_conversation(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _thread_id: u64,
+        ) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn list_conversation_threads(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+        ) -> Result<Vec<ConversationThreadMeta>, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn load_conversation(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _thread_id: u64,
+        ) -> Result<DomainConversationSnapshot, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn load_conversation_page(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _thread_id: u64,
+            _before: Option<u64>,
+            _limit: u64,
+        ) -> Result<DomainConversationSnapshot, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn store_context_image(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _image: ContextImage,
+        ) -> Result<AttachmentRef, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn store_context_text(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _text: String,
+            _extension: String,
+        ) -> Result<AttachmentRef, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn store_context_file(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _source_path: PathBuf,
+        ) -> Result<AttachmentRef, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn record_context_item(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _attachment: AttachmentRef,
+            _created_at_unix_ms: u64,
+        ) -> Result<u64, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn list_context_items(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+        ) -> Result<Vec<ContextItem>, String> {
+            Ok(Vec::new())
+        }
+
+        fn delete_context_item(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _context_id: u64,
+        ) -> Result<(), String> {
+            Ok(())
+        }
+
+        fn run_agent_turn_streamed(
+            &self,
+            _request: luban_domain::RunAgentTurnRequest,
+            _cancel: Arc<AtomicBool>,
+            _on_event: Arc<dyn Fn(luban_domain::AgentThreadEvent) + Send + Sync>,
+        ) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn gh_is_authorized(&self) -> Result<bool, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn gh_pull_request_info(
+            &self,
+            _worktree_path: PathBuf,
+            _target: luban_domain::PullRequestTarget,
+        ) -> Result<Option<PullRequestInfo>, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn gh_open_pull_request(&self, _worktree_path: PathBuf) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn gh_open_pull_request_failed_action(
+            &self,
+            _worktree_path: PathBuf,
+        ) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn project_identity(&self, path: PathBuf) -> Result<luban_domain::ProjectIdentity, String> {
+            Ok(luban_domain::ProjectIdentity {
+                root_path: path,
+                github_repo: Some("github.com/example/repo".to_owned()),
+                is_git: true,
+            })
+        }
+    }
+
+    #[test]
+    fn inactive_workspace_archive_candidates_skip_main_open_prs_and_running_turns() {
+        let mut state = AppState::new();
+        let _ = state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/luban-auto-archive"),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+        for (name, path) in [
+            ("main", "/tmp/luban-auto-archive"),
+            ("idle", "/tmp/luban-auto-archive-idle"),
+            ("reviewing", "/tmp/luban-auto-archive-reviewing"),
+            ("busy", "/tmp/luban-auto-archive-busy"),
+        ] {
+            let _ = state.apply(Action::WorkspaceCreated {
+                project_id,
+                workspace_name: name.to_owned(),
+                branch_name: name.to_owned(),
+                worktree_path: PathBuf::from(path),
+            });
+        }
+        let ids = state.projects[0]
+            .workspaces
+            .iter()
+            .map(|w| w.id)
+            .collect::<Vec<_>>();
+        let (idle, reviewing, busy) = (ids[1], ids[2], ids[3]);
+        state.projects[0].workspaces[1].last_activity_at =
+            Some(std::time::UNIX_EPOCH + Duration::from_secs(1_000));
+
+        let _ = state.apply(Action::CreateWorkspaceThread { workspace_id: busy });
+        for ((workspace_id, _), conversation) in state.conversations.iter_mut() {
+            if *workspace_id == busy {
+                conversation.run_status = OperationStatus::Running;
+            }
+        }
+
+        let candidates =
+            inactive_workspace_archive_candidates(&state, |workspace_id| workspace_id == reviewing);
+        let candidates = candidates
+            .into_iter()
+            .map(|(workspace_id, last_activity_at, scope)| {
+                (workspace_id, last_activity_at, scope.workspace_name)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(candidates, vec![(idle, Some(1_000), "idle".to_owned())]);
+
+        let thread = ConversationThreadMeta {
+            thread_id: WorkspaceThreadId::from_u64(1),
+            remote_thread_id: None,
+            title: String::new(),
+            created_at_unix_seconds: 500,
+            updated_at_unix_seconds: 2_000,
+            task_status: luban_domain::TaskStatus::Done,
+            last_message_seq: 0,
+            task_status_last_analyzed_message_seq: 0,
+            turn_status: luban_domain::TurnStatus::Idle,
+            last_turn_result: None,
+            total_tokens: 0,
+        };
+        assert_eq!(latest_workspace_activity(None, &[]), None);
+        assert_eq!(latest_workspace_activity(Some(1_000), &[]), Some(1_000));
+        assert_eq!(
+            latest_workspace_activity(Some(1_000), std::slice::from_ref(&thread)),
+            Some(2_000)
+        );
+    }
+
+    #[test]
+    fn app_snapshot_includes_pull_request_info() {
+        let mut state = AppState::new();
+        let _ = state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/luban-server-test"),
+            is_git: true,
+        });
+
+        let project_id = state.projects[0].id;
+        let _ = state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "main".to_owned(),
+            branch_name: "main".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban-server-test"),
+        });
+
+        let workspace_id = state.projects[0].workspaces[0].id;
+
+        let (events, _) = broadcast::channel::<WsServerMessage>(1);
+        let (tx, _rx) = mpsc::channel::<EngineCommand>(1);
+        let mut engine = Engine {
+            state,
+            rev: 1,
+            services: Arc::new(TestServices),
+            events,
+            tx,
+            branch_watch: BranchWatchHandle::disabled(),
+            cancel_flags: HashMap::new(),
+            pull_requests: HashMap::new(),
+            pull_requests_in_flight: HashSet::new(),
+            workspace_threads_cache: HashMap::new(),
+            auto_archive_workspaces: HashSet::new(),
+            pending_notes_flushes: HashMap::new(),
+            pending_draft_flushes: HashMap::new(),
+            conversation_revs: HashMap::new(),
+            last_git_fetch_at: HashMap::new(),
+            worktree_dirty: HashMap::new(),
+            worktree_dirty_in_flight: HashSet::new(),
+            worktree_dirty_rerun: HashSet::new(),
+            last_commits: HashMap::new(),
+            agent_turn_slots: HashMap::new(),
+            deferred_agent_turns: VecDeque::new(),
+            request_cancellations: RequestCancellations::default(),
+            attachment_thumbnails: AttachmentThumbnails::default(),
+            telegram_pairings: Vec::new(),
+            config: EngineConfig::default(),
+        };
+
+        engine.pull_requests.insert(
             workspace_id,
-            thread_id,
-            text,
-            attachments,
-            runner,
-            amp_mode,
-        } => Some(Action::QueueAgentMessage {
-            workspace_id: WorkspaceId::from_u64(workspace_id.0),
-            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
-            text,
-            attachments: attachments.into_iter().map(map_api_attachment).collect(),
-            runner: runner.map(map_api_agent_runner_kind),
-            amp_mode,
-        }),
-        luban_api::ClientAction::RemoveQueuedPrompt {
+            PullRequestCacheEntry {
+                info: Some(PullRequestInfo {
+                    number: 42,
+                    is_draft: false,
+                    state: DomainPullRequestState::Open,
+                    ci_state: Some(DomainPullRequestCiState::Pending),
+                    merge_ready: false,
+                }),
+                error: None,
+                next_refresh_at: Instant::now(),
+                consecutive_empty: 0,
+                consecutive_errors: 0,
+            },
+        );
+
+        let snapshot = engine.app_snapshot();
+        let pr = snapshot.projects[0].workspaces[0].pull_request;
+        assert_eq!(
+            pr,
+            Some(PullRequestSnapshot {
+                number: 42,
+                is_draft: false,
+                state: PullRequestState::Open,
+                ci_state: Some(PullRequestCiState::Pending),
+                merge_ready: false,
+            })
+        );
+    }
+
+    #[test]
+    fn app_snapshot_marks_merged_pull_requests() {
+        let mut state = AppState::new();
+        let _ = state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/luban-server-test"),
+            is_git: true,
+        });
+
+        let project_id = state.projects[0].id;
+        let _ = state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "main".to_owned(),
+            branch_name: "main".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban-server-test"),
+        });
+
+        let workspace_id = state.projects[0].workspaces[0].id;
+
+        let (events, _) = broadcast::channel::<WsServerMessage>(1);
+        let (tx, _rx) = mpsc::channel::<EngineCommand>(1);
+        let mut engine = Engine {
+            state,
+            rev: 1,
+            services: Arc::new(TestServices),
+            events,
+            tx,
+            branch_watch: BranchWatchHandle::disabled(),
+            cancel_flags: HashMap::new(),
+            pull_requests: HashMap::new(),
+            pull_requests_in_flight: HashSet::new(),
+            workspace_threads_cache: HashMap::new(),
+            auto_archive_workspaces: HashSet::new(),
+            pending_notes_flushes: HashMap::new(),
+            pending_draft_flushes: HashMap::new(),
+            conversation_revs: HashMap::new(),
+            last_git_fetch_at: HashMap::new(),
+            worktree_dirty: HashMap::new(),
+            worktree_dirty_in_flight: HashSet::new(),
+            worktree_dirty_rerun: HashSet::new(),
+            last_commits: HashMap::new(),
+            agent_turn_slots: HashMap::new(),
+            deferred_agent_turns: VecDeque::new(),
+            request_cancellations: RequestCancellations::default(),
+            attachment_thumbnails: AttachmentThumbnails::default(),
+            telegram_pairings: Vec::new(),
+            config: EngineConfig::default(),
+        };
+
+        engine.pull_requests.insert(
+            workspace_id,
+            PullRequestCacheEntry {
+                info: Some(PullRequestInfo {
+                    number: 7,
+                    is_draft: false,
+                    state: DomainPullRequestState::Merged,
+                    ci_state: Some(DomainPullRequestCiState::Success),
+                    merge_ready: false,
+                }),
+                error: None,
+                next_refresh_at: Instant::now(),
+                consecutive_empty: 0,
+                consecutive_errors: 0,
+            },
+        );
+
+        let snapshot = engine.app_snapshot();
+        let pr = snapshot.projects[0].workspaces[0].pull_request;
+        assert_eq!(
+            pr,
+            Some(PullRequestSnapshot {
+                number: 7,
+                is_draft: false,
+                state: PullRequestState::Merged,
+                ci_state: Some(PullRequestCiState::Success),
+                merge_ready: false,
+            })
+        );
+    }
+
+    #[test]
+    fn app_snapshot_rolls_up_running_turns_and_queued_prompts() {
+        let mut state = AppState::new();
+        let _ = state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/luban-server-test"),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+        let _ = state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "main".to_owned(),
+            branch_name: "main".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban-server-test"),
+        });
+        let workspace_id = state.projects[0].workspaces[0].id;
+
+        let send = |state: &mut AppState, thread_id: u64, text: &str| {
+            let _ = state.apply(Action::SendAgentMessage {
+                workspace_id,
+                thread_id: WorkspaceThreadId::from_u64(thread_id),
+                text: text.to_owned(),
+                attachments: Vec::new(),
+                runner: None,
+                amp_mode: None,
+            });
+        };
+
+        // Running with two queued prompts.
+        send(&mut state, 1, "run");
+        send(&mut state, 1, "queued 1");
+        send(&mut state, 1, "queued 2");
+        // Running with an empty queue.
+        send(&mut state, 2, "run");
+        // Canceled (idle) with one prompt left in the paused queue.
+        send(&mut state, 3, "run");
+        send(&mut state, 3, "queued");
+        let _ = state.apply(Action::CancelAgentTurn {
+            workspace_id,
+            thread_id: WorkspaceThreadId::from_u64(3),
+        });
+
+        let (events, _) = broadcast::channel::<WsServerMessage>(1);
+        let (tx, _rx) = mpsc::channel::<EngineCommand>(1);
+        let engine = Engine {
+            state,
+            rev: 1,
+            services: Arc::new(TestServices),
+            events,
+            tx,
+            branch_watch: BranchWatchHandle::disabled(),
+            cancel_flags: HashMap::new(),
+            pull_requests: HashMap::new(),
+            pull_requests_in_flight: HashSet::new(),
+            workspace_threads_cache: HashMap::new(),
+            auto_archive_workspaces: HashSet::new(),
+            pending_notes_flushes: HashMap::new(),
+            pending_draft_flushes: HashMap::new(),
+            conversation_revs: HashMap::new(),
+            last_git_fetch_at: HashMap::new(),
+            worktree_dirty: HashMap::new(),
+            worktree_dirty_in_flight: HashSet::new(),
+            worktree_dirty_rerun: HashSet::new(),
+            last_commits: HashMap::new(),
+            agent_turn_slots: HashMap::new(),
+            deferred_agent_turns: VecDeque::new(),
+            request_cancellations: RequestCancellations::default(),
+            attachment_thumbnails: AttachmentThumbnails::default(),
+            telegram_pairings: Vec::new(),
+            config: EngineConfig::default(),
+        };
+
+        let snapshot = engine.app_snapshot();
+        assert_eq!(snapshot.running_turns, 2);
+        assert_eq!(snapshot.queued_prompts_total, 3);
+    }
+
+    #[test]
+    fn archived_projects_are_hidden_from_snapshots_and_skip_pull_request_refresh() {
+        let mut state = AppState::new();
+        for path in ["/tmp/luban-server-test-a", "/tmp/luban-server-test-b"] {
+            let _ = state.apply(Action::AddProject {
+                path: PathBuf::from(path),
+                is_git: true,
+            });
+        }
+        let project_id = state.projects[0].id;
+        let _ = state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "main".to_owned(),
+            branch_name: "main".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban-server-test-a"),
+        });
+        let workspace_id = state.projects[0].workspaces[0].id;
+        let _ = state.apply(Action::CreateProjectGroup {
+            name: "Work".to_owned(),
+        });
+        let group_id = state.project_groups[0].id;
+        for project_id in state.projects.iter().map(|p| p.id).collect::<Vec<_>>() {
+            let _ = state.apply(Action::AssignProjectToGroup {
+                project_id,
+                group_id: Some(group_id),
+            });
+        }
+        let _ = state.apply(Action::ArchiveProject { project_id });
+
+        let (events, _) = broadcast::channel::<WsServerMessage>(1);
+        let (tx, _rx) = mpsc::channel::<EngineCommand>(1);
+        let engine = Engine {
+            state,
+            rev: 1,
+            services: Arc::new(TestServices),
+            events,
+            tx,
+            branch_watch: BranchWatchHandle::disabled(),
+            cancel_flags: HashMap::new(),
+            pull_requests: HashMap::new(),
+            pull_requests_in_flight: HashSet::new(),
+            workspace_threads_cache: HashMap::new(),
+            auto_archive_workspaces: HashSet::new(),
+            pending_notes_flushes: HashMap::new(),
+            pending_draft_flushes: HashMap::new(),
+            conversation_revs: HashMap::new(),
+            last_git_fetch_at: HashMap::new(),
+            worktree_dirty: HashMap::new(),
+            worktree_dirty_in_flight: HashSet::new(),
+            worktree_dirty_rerun: HashSet::new(),
+            last_commits: HashMap::new(),
+            agent_turn_slots: HashMap::new(),
+            deferred_agent_turns: VecDeque::new(),
+            request_cancellations: RequestCancellations::default(),
+            attachment_thumbnails: AttachmentThumbnails::default(),
+            telegram_pairings: Vec::new(),
+            config: EngineConfig::default(),
+        };
+
+        let full = engine.app_snapshot();
+        assert_eq!(full.projects.len(), 2);
+        assert_eq!(full.projects[0].status, luban_api::ProjectStatus::Archived);
+
+        let visible = without_archived_projects(full);
+        assert_eq!(visible.projects.len(), 1);
+        assert_eq!(visible.projects[0].path, "/tmp/luban-server-test-b");
+        assert_eq!(
+            visible.project_groups[0].project_ids,
+            vec![luban_api::ProjectId("/tmp/luban-server-test-b".to_owned())]
+        );
+
+        assert!(!engine.should_start_pull_request_refresh(workspace_id, Instant::now()));
+    }
+
+    #[test]
+    fn auto_title_retries_empty_suggestions_then_falls_back_to_user_message() {
+        let mut attempts = 0;
+        let title = suggest_thread_title_with_retries(
+            "Fix the flaky login test\nIt times out on CI",
+            Duration::ZERO,
+            || {
+                attempts += 1;
+                Ok("  \n".to_owned())
+            },
+        );
+        assert_eq!(attempts, AUTO_TITLE_MAX_ATTEMPTS);
+        assert_eq!(title, "Fix the flaky login test");
+
+        let mut attempts = 0;
+        let title = suggest_thread_title_with_retries("ignored", Duration::ZERO, || {
+            attempts += 1;
+            if attempts == 1 {
+                Err("rate limited".to_owned())
+            } else {
+                Ok("Login test timeout".to_owned())
+            }
+        });
+        assert_eq!(attempts, 2);
+        assert_eq!(title, "Login test timeout");
+    }
+
+    #[test]
+    fn pull_request_refresh_backoff_increases_on_empty_results() {
+        let now = Instant::now();
+        let workspace_id = WorkspaceId::from_u64(10);
+        let previous = PullRequestCacheEntry {
+            info: None,
+            error: None,
+            next_refresh_at: now,
+            consecutive_empty: 1,
+            consecutive_errors: 0,
+        };
+
+        let (next, empty_count, _) =
+            pull_request_next_refresh_at(workspace_id, now, Some(&previous), Ok(None));
+        assert_eq!(empty_count, 2);
+        let delta = next.duration_since(now);
+        assert!(
+            delta >= PULL_REQUEST_REFRESH_INTERVAL_EMPTY_MEDIUM,
+            "expected at least {:?}, got {:?}",
+            PULL_REQUEST_REFRESH_INTERVAL_EMPTY_MEDIUM,
+            delta
+        );
+    }
+
+    #[test]
+    fn pull_request_refresh_pending_ci_is_frequently_refreshed() {
+        let now = Instant::now();
+        let workspace_id = WorkspaceId::from_u64(10);
+        let info = PullRequestInfo {
+            number: 1,
+            is_draft: false,
+            state: DomainPullRequestState::Open,
+            ci_state: Some(DomainPullRequestCiState::Pending),
+            merge_ready: false,
+        };
+
+        let (next, empty_count, _) =
+            pull_request_next_refresh_at(workspace_id, now, None, Ok(Some(&info)));
+        assert_eq!(empty_count, 0);
+        let delta = next.duration_since(now);
+        assert!(
+            delta >= PULL_REQUEST_REFRESH_INTERVAL_OPEN_CI_PENDING,
+            "expected at least {:?}, got {:?}",
+            PULL_REQUEST_REFRESH_INTERVAL_OPEN_CI_PENDING,
+            delta
+        );
+        assert!(
+            delta
+                < PULL_REQUEST_REFRESH_INTERVAL_OPEN_CI_PENDING
+                    + Duration::from_secs(PULL_REQUEST_REFRESH_JITTER_WINDOW_SECS + 1),
+            "expected jitter window <= {:?}, got {:?}",
+            Duration::from_secs(PULL_REQUEST_REFRESH_JITTER_WINDOW_SECS + 1),
+            delta
+        );
+    }
+
+    #[test]
+    fn pull_request_refresh_errors_retry_quickly_without_counting_as_empty() {
+        // Workspace 20 has no jitter, so intervals can be asserted exactly.
+        let now = Instant::now();
+        let workspace_id = WorkspaceId::from_u64(20);
+        let mut previous = PullRequestCacheEntry {
+            info: None,
+            error: None,
+            next_refresh_at: now,
+            consecutive_empty: 2,
+            consecutive_errors: 0,
+        };
+
+        let mut retries = Vec::new();
+        for _ in 0..5 {
+            let (next, empty_count, error_count) = pull_request_next_refresh_at(
+                workspace_id,
+                now,
+                Some(&previous),
+                Err("rate limited"),
+            );
+            assert_eq!(empty_count, 2);
+            retries.push(next.duration_since(now));
+            previous.consecutive_errors = error_count;
+        }
+        assert_eq!(
+            retries,
+            [15, 30, 60, 120, 120].map(Duration::from_secs).to_vec()
+        );
+        assert_eq!(previous.consecutive_errors, 5);
+
+        let (_, empty_count, error_count) =
+            pull_request_next_refresh_at(workspace_id, now, Some(&previous), Ok(None));
+        assert_eq!(empty_count, 3);
+        assert_eq!(error_count, 0);
+    }
+
+    #[tokio::test]
+    async fn pull_request_refresh_error_keeps_last_known_pull_request() {
+        let mut state = AppState::new();
+        let _ = state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/luban-server-test"),
+            is_git: true,
+        });
+
+        let project_id = state.projects[0].id;
+        let _ = state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "main".to_owned(),
+            branch_name: "main".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban-server-test"),
+        });
+
+        let workspace_id = state.projects[0].workspaces[0].id;
+
+        let (events, _) = broadcast::channel::<WsServerMessage>(1);
+        let (tx, _rx) = mpsc::channel::<EngineCommand>(1);
+        let mut engine = Engine {
+            state,
+            rev: 1,
+            services: Arc::new(TestServices),
+            events,
+            tx,
+            branch_watch: BranchWatchHandle::disabled(),
+            cancel_flags: HashMap::new(),
+            pull_requests: HashMap::new(),
+            pull_requests_in_flight: HashSet::new(),
+            workspace_threads_cache: HashMap::new(),
+            auto_archive_workspaces: HashSet::new(),
+            pending_notes_flushes: HashMap::new(),
+            pending_draft_flushes: HashMap::new(),
+            conversation_revs: HashMap::new(),
+            last_git_fetch_at: HashMap::new(),
+            worktree_dirty: HashMap::new(),
+            worktree_dirty_in_flight: HashSet::new(),
+            worktree_dirty_rerun: HashSet::new(),
+            last_commits: HashMap::new(),
+            agent_turn_slots: HashMap::new(),
+            deferred_agent_turns: VecDeque::new(),
+            request_cancellations: RequestCancellations::default(),
+            attachment_thumbnails: AttachmentThumbnails::default(),
+            telegram_pairings: Vec::new(),
+            config: EngineConfig::default(),
+        };
+
+        let info = PullRequestInfo {
+            number: 7,
+            is_draft: false,
+            state: DomainPullRequestState::Open,
+            ci_state: Some(DomainPullRequestCiState::Success),
+            merge_ready: false,
+        };
+
+        engine
+            .handle(EngineCommand::PullRequestInfoUpdated {
+                workspace_id,
+                info: Ok(Some(info)),
+            })
+            .await;
+        let rev = engine.rev;
+        engine
+            .handle(EngineCommand::PullRequestInfoUpdated {
+                workspace_id,
+                info: Err("gh pr view failed: timeout".to_owned()),
+            })
+            .await;
+
+        let entry = engine
+            .pull_requests
+            .get(&workspace_id)
+            .expect("cache entry");
+        assert_eq!(entry.info, Some(info));
+        assert_eq!(entry.consecutive_errors, 1);
+        assert_eq!(
+            engine.rev, rev,
+            "a failed lookup should not publish a change"
+        );
+    }
+
+    #[tokio::test]
+    async fn worktree_dirty_tracks_uncommitted_changes() {
+        let repo = tempfile::tempdir().expect("temp dir");
+        let repo_path = repo.path().to_path_buf();
+        let git = |args: &[&str]| {
+            let status = std::process::Command::new("git")
+                .args(args)
+                .current_dir(&repo_path)
+                .status()
+                .expect("git should run");
+            assert!(status.success(), "git {args:?} failed");
+        };
+        git(&["init"]);
+        git(&["config", "user.email", "dirty@example.com"]);
+        git(&["config", "user.name", "luban-dirty"]);
+        std::fs::write(repo_path.join("file.txt"), "a\n").expect("write file");
+        git(&["add", "."]);
+        git(&["commit", "-m", "init"]);
+
+        let mut state = AppState::new();
+        let _ = state.apply(Action::AddProject {
+            path: repo_path.clone(),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+        let _ = state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "main".to_owned(),
+            branch_name: "main".to_owned(),
+            worktree_path: repo_path.clone(),
+        });
+        let workspace_id = state.projects[0].workspaces[0].id;
+        let thread_id = WorkspaceThreadId::from_u64(1);
+        state.apply(Action::SendAgentMessage {
             workspace_id,
             thread_id,
-            prompt_id,
-        } => Some(Action::RemoveQueuedPrompt {
-            workspace_id: WorkspaceId::from_u64(workspace_id.0),
-            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
-            prompt_id,
-        }),
-        luban_api::ClientAction::ReorderQueuedPrompt {
+            text: "seed".to_owned(),
+            attachments: Vec::new(),
+            runner: None,
+            amp_mode: None,
+        });
+
+        let (events, _) = broadcast::channel::<WsServerMessage>(16);
+        let (tx, mut rx) = mpsc::channel::<EngineCommand>(4);
+        let mut engine = Engine {
+            state,
+            rev: 1,
+            services: Arc::new(TestServices),
+            events,
+            tx,
+            branch_watch: BranchWatchHandle::disabled(),
+            cancel_flags: HashMap::new(),
+            pull_requests: HashMap::new(),
+            pull_requests_in_flight: HashSet::new(),
+            workspace_threads_cache: HashMap::new(),
+            auto_archive_workspaces: HashSet::new(),
+            pending_notes_flushes: HashMap::new(),
+            pending_draft_flushes: HashMap::new(),
+            conversation_revs: HashMap::new(),
+            last_git_fetch_at: HashMap::new(),
+            worktree_dirty: HashMap::new(),
+            worktree_dirty_in_flight: HashSet::new(),
+            worktree_dirty_rerun: HashSet::new(),
+            last_commits: HashMap::new(),
+            agent_turn_slots: HashMap::new(),
+            deferred_agent_turns: VecDeque::new(),
+            request_cancellations: RequestCancellations::default(),
+            attachment_thumbnails: AttachmentThumbnails::default(),
+            telegram_pairings: Vec::new(),
+            config: EngineConfig::default(),
+        };
+
+        let api_wid = luban_api::WorkspaceId(workspace_id.as_u64());
+        let api_tid = luban_api::WorkspaceThreadId(thread_id.as_u64());
+        let mut refresh_and_check = async |engine: &mut Engine| {
+            engine.maybe_refresh_worktree_dirty(workspace_id, true);
+            let cmd = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+                .await
+                .expect("timed out waiting for worktree check")
+                .expect("engine channel closed");
+            assert!(matches!(cmd, EngineCommand::WorktreeDirtyUpdated { .. }));
+            engine.handle(cmd).await;
+            engine
+                .conversation_snapshot(api_wid, api_tid, None, None)
+                .expect("snapshot should build")
+                .worktree_dirty
+        };
+
+        assert!(!refresh_and_check(&mut engine).await, "clean worktree");
+        std::fs::write(repo_path.join("file.txt"), "b\n").expect("modify file");
+        assert!(refresh_and_check(&mut engine).await, "modified file");
+        git(&["checkout", "--", "file.txt"]);
+        assert!(!refresh_and_check(&mut engine).await, "reverted file");
+
+        std::fs::write(repo_path.join("new.txt"), "c\n").expect("write untracked file");
+        engine.state.projects[0].is_git = false;
+        engine.worktree_dirty.clear();
+        engine.maybe_refresh_worktree_dirty(workspace_id, true);
+        assert!(engine.worktree_dirty_in_flight.is_empty());
+        assert!(
+            !engine
+                .conversation_snapshot(api_wid, api_tid, None, None)
+                .expect("snapshot should build")
+                .worktree_dirty,
+            "non-git workspaces are never dirty"
+        );
+    }
+
+    #[test]
+    fn conversation_snapshots_are_truncated_to_tail() {
+        let mut state = AppState::new();
+        let _ = state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/luban-server-test"),
+            is_git: true,
+        });
+
+        let project_id = state.projects[0].id;
+        let _ = state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "main".to_owned(),
+            branch_name: "main".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban-server-test"),
+        });
+
+        let workspace_id = state.projects[0].workspaces[0].id;
+        let thread_id = WorkspaceThreadId::from_u64(1);
+
+        state.apply(Action::SendAgentMessage {
             workspace_id,
             thread_id,
-            active_id,
-            over_id,
-        } => Some(Action::ReorderQueuedPrompt {
-            workspace_id: WorkspaceId::from_u64(workspace_id.0),
-            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
-            active_id,
-            over_id,
-        }),
-        luban_api::ClientAction::UpdateQueuedPrompt {
+            text: "seed".to_owned(),
+            attachments: Vec::new(),
+            runner: None,
+            amp_mode: None,
+        });
+
+        let key = (workspace_id, thread_id);
+        let convo = state
+            .conversations
+            .get_mut(&key)
+            .expect("conversation must exist");
+        for i in 0..7000u32 {
+            convo.entries.push(ConversationEntry::AgentEvent {
+                entry_id: String::new(),
+                created_at_unix_ms: i as u64,
+                runner: None,
+                event: luban_domain::AgentEvent::Item {
+                    item: Box::new(CodexThreadItem::CommandExecution {
+                        id: format!("cmd_{i}"),
+                        command: format!("echo {i}"),
+                        aggregated_output: String::new(),
+                        exit_code: Some(0),
+                        status: CodexCommandExecutionStatus::Completed,
+                        truncated: false,
+                    }),
+                },
+            });
+        }
+        convo.entries_start = 0;
+        convo.entries_total = convo.entries.len() as u64;
+        let total = convo.entries.len();
+
+        let (events, _) = broadcast::channel::<WsServerMessage>(1);
+        let (tx, _rx) = mpsc::channel::<EngineCommand>(1);
+        let engine = Engine {
+            state,
+            rev: 1,
+            services: Arc::new(TestServices),
+            events,
+            tx,
+            branch_watch: BranchWatchHandle::disabled(),
+            cancel_flags: HashMap::new(),
+            pull_requests: HashMap::new(),
+            pull_requests_in_flight: HashSet::new(),
+            workspace_threads_cache: HashMap::new(),
+            auto_archive_workspaces: HashSet::new(),
+            pending_notes_flushes: HashMap::new(),
+            pending_draft_flushes: HashMap::new(),
+            conversation_revs: HashMap::new(),
+            last_git_fetch_at: HashMap::new(),
+            worktree_dirty: HashMap::new(),
+            worktree_dirty_in_flight: HashSet::new(),
+            worktree_dirty_rerun: HashSet::new(),
+            last_commits: HashMap::new(),
+            agent_turn_slots: HashMap::new(),
+            deferred_agent_turns: VecDeque::new(),
+            request_cancellations: RequestCancellations::default(),
+            attachment_thumbnails: AttachmentThumbnails::default(),
+            telegram_pairings: Vec::new(),
+            config: EngineConfig::default(),
+        };
+
+        let api_wid = luban_api::WorkspaceId(workspace_id.as_u64());
+        let api_tid = luban_api::WorkspaceThreadId(thread_id.as_u64());
+
+        let snapshot = engine
+            .conversation_snapshot(api_wid, api_tid, None, None)
+            .expect("snapshot must build");
+        assert!(
+            snapshot.entries_truncated,
+            "large conversations must be truncated"
+        );
+        assert_eq!(snapshot.entries_total, total as u64);
+        assert_eq!(
+            snapshot.entries_start + snapshot.entries.len() as u64,
+            snapshot.entries_total
+        );
+        assert!(snapshot.entries.len() <= 2000);
+    }
+
+    #[test]
+    fn archived_workspaces_own_their_worktree_only_while_it_exists() {
+        let root = std::env::temp_dir().join(format!(
+            "luban-owned-worktrees-{}-{}",
+            std::process::id(),
+            now_unix_ms()
+        ));
+        let kept = root.join("kept");
+        std::fs::create_dir_all(&kept).expect("create worktree dir");
+
+        let mut state = AppState::new();
+        let _ = state.apply(Action::AddProject {
+            path: root.clone(),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+        for (name, path) in [
+            ("active", root.join("active")),
+            ("kept", kept.clone()),
+            ("removed", root.join("removed")),
+        ] {
+            let _ = state.apply(Action::WorkspaceCreated {
+                project_id,
+                workspace_name: name.to_owned(),
+                branch_name: name.to_owned(),
+                worktree_path: path,
+            });
+        }
+        for workspace in &mut state.projects[0].workspaces {
+            if workspace.workspace_name != "active" {
+                workspace.status = luban_domain::WorkspaceStatus::Archived;
+            }
+        }
+
+        assert_eq!(
+            owned_worktree_paths(&state.projects[0].workspaces),
+            vec![root.join("active"), kept]
+        );
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn filtered_conversation_snapshots_page_over_matching_entries() {
+        let mut state = AppState::new();
+        let _ = state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/luban-server-test"),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+        let _ = state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "main".to_owned(),
+            branch_name: "main".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban-server-test"),
+        });
+        let workspace_id = state.projects[0].workspaces[0].id;
+        let thread_id = WorkspaceThreadId::from_u64(1);
+        state.apply(Action::SendAgentMessage {
             workspace_id,
             thread_id,
-            prompt_id,
-            text,
-            attachments,
-            model_id,
-            thinking_effort,
-        } => Some(Action::UpdateQueuedPrompt {
-            workspace_id: WorkspaceId::from_u64(workspace_id.0),
-            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
-            prompt_id,
-            text,
-            attachments: attachments.into_iter().map(map_api_attachment).collect(),
-            model_id,
-            thinking_effort: match thinking_effort {
-                luban_api::ThinkingEffort::Minimal => ThinkingEffort::Minimal,
-                luban_api::ThinkingEffort::Low => ThinkingEffort::Low,
-                luban_api::ThinkingEffort::Medium => ThinkingEffort::Medium,
-                luban_api::ThinkingEffort::High => ThinkingEffort::High,
-                luban_api::ThinkingEffort::XHigh => ThinkingEffort::XHigh,
+            text: "seed".to_owned(),
+            attachments: Vec::new(),
+            runner: None,
+            amp_mode: None,
+        });
+
+        let convo = state
+            .conversations
+            .get_mut(&(workspace_id, thread_id))
+            .expect("conversation must exist");
+        let base = convo.entries.len() as u64;
+        for i in 0..12u32 {
+            let item = if i % 5 == 1 {
+                CodexThreadItem::FileChange {
+                    id: format!("patch_{i}"),
+                    changes: Vec::new(),
+                    status: luban_domain::CodexPatchApplyStatus::Completed,
+                }
+            } else {
+                CodexThreadItem::CommandExecution {
+                    id: format!("cmd_{i}"),
+                    command: format!("echo {i}"),
+                    aggregated_output: String::new(),
+                    exit_code: Some(0),
+                    status: CodexCommandExecutionStatus::Completed,
+                    truncated: false,
+                }
+            };
+            convo.entries.push(ConversationEntry::AgentEvent {
+                entry_id: String::new(),
+                created_at_unix_ms: i as u64,
+                runner: None,
+                event: luban_domain::AgentEvent::Item {
+                    item: Box::new(item),
+                },
+            });
+        }
+        convo.entries_start = 0;
+        convo.entries_total = convo.entries.len() as u64;
+
+        let (events, _) = broadcast::channel::<WsServerMessage>(1);
+        let (tx, _rx) = mpsc::channel::<EngineCommand>(1);
+        let engine = Engine {
+            state,
+            rev: 1,
+            services: Arc::new(TestServices),
+            events,
+            tx,
+            branch_watch: BranchWatchHandle::disabled(),
+            cancel_flags: HashMap::new(),
+            pull_requests: HashMap::new(),
+            pull_requests_in_flight: HashSet::new(),
+            workspace_threads_cache: HashMap::new(),
+            auto_archive_workspaces: HashSet::new(),
+            pending_notes_flushes: HashMap::new(),
+            pending_draft_flushes: HashMap::new(),
+            conversation_revs: HashMap::new(),
+            last_git_fetch_at: HashMap::new(),
+            worktree_dirty: HashMap::new(),
+            worktree_dirty_in_flight: HashSet::new(),
+            worktree_dirty_rerun: HashSet::new(),
+            last_commits: HashMap::new(),
+            agent_turn_slots: HashMap::new(),
+            deferred_agent_turns: VecDeque::new(),
+            request_cancellations: RequestCancellations::default(),
+            attachment_thumbnails: AttachmentThumbnails::default(),
+            telegram_pairings: Vec::new(),
+            config: EngineConfig {
+                conversation_load: crate::ConversationLoadConfig {
+                    default_entries_limit: 2,
+                    max_entries_limit: 4,
+                },
+                ..EngineConfig::default()
             },
-        }),
-        luban_api::ClientAction::WorkspaceRenameBranch {
-            workspace_id,
-            branch_name,
-        } => Some(Action::WorkspaceBranchRenameRequested {
-            workspace_id: WorkspaceId::from_u64(workspace_id.0),
-            requested_branch_name: branch_name,
-        }),
-        luban_api::ClientAction::WorkspaceAiRenameBranch {
-            workspace_id,
-            thread_id,
-        } => Some(Action::WorkspaceBranchAiRenameRequested {
-            workspace_id: WorkspaceId::from_u64(workspace_id.0),
-            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
-        }),
-        luban_api::ClientAction::CancelAgentTurn {
+        };
+
+        let api_wid = luban_api::WorkspaceId(workspace_id.as_u64());
+        let api_tid = luban_api::WorkspaceThreadId(thread_id.as_u64());
+        let filter = ConversationEntryFilter {
+            kinds: vec![luban_api::AgentItemKind::FileChange],
+            include_user_messages: false,
+        };
+        let item_ids = |snapshot: &ConversationSnapshot| {
+            snapshot
+                .entries
+                .iter()
+                .map(|entry| match entry {
+                    luban_api::ConversationEntry::AgentEvent(luban_api::AgentEventEntry {
+                        event: luban_api::AgentEvent::Item(item),
+                        ..
+                    }) => item.id.clone(),
+                    other => panic!("unexpected entry: {other:?}"),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let latest = engine
+            .get_filtered_conversation_snapshot(api_wid, api_tid, None, None, &filter)
+            .await
+            .expect("filtered snapshot must build");
+        assert_eq!(item_ids(&latest), vec!["patch_6", "patch_11"]);
+        assert_eq!(latest.entries_start, base + 6);
+        assert!(latest.entries_truncated);
+        assert_eq!(latest.entries_total, base + 12);
+
+        let older = engine
+            .get_filtered_conversation_snapshot(
+                api_wid,
+                api_tid,
+                Some(latest.entries_start),
+                None,
+                &filter,
+            )
+            .await
+            .expect("filtered snapshot must build");
+        assert_eq!(item_ids(&older), vec!["patch_1"]);
+        assert_eq!(older.entries_start, 0);
+    }
+
+    /// Points `LUBAN_ROOT` at a fresh temp dir while held; tests that construct default services
+    /// serialize on it because the variable is process-wide.
+    struct LubanRootEnvGuard {
+        _lock: std::sync::MutexGuard<'static, ()>,
+        prev_root: Option<std::ffi::OsString>,
+        root: PathBuf,
+    }
+
+    impl LubanRootEnvGuard {
+        fn set(name: &str) -> Self {
+            static ENV_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+            let lock = ENV_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+
+            let root = std::env::temp_dir().join(format!(
+                "luban-tests-{name}-{}-{}",
+                std::process::id(),
+                std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_nanos()
+            ));
+            std::fs::create_dir_all(&root).expect("create temp root");
+
+            let prev_root = std::env::var_os(luban_domain::paths::LUBAN_ROOT_ENV);
+            unsafe {
+                std::env::set_var(luban_domain::paths::LUBAN_ROOT_ENV, root.as_os_str());
+            }
+            Self {
+                _lock: lock,
+                prev_root,
+                root,
+            }
+        }
+    }
+
+    impl Drop for LubanRootEnvGuard {
+        fn drop(&mut self) {
+            if let Some(prev) = self.prev_root.take() {
+                unsafe {
+                    std::env::set_var(luban_domain::paths::LUBAN_ROOT_ENV, prev);
+                }
+            } else {
+                unsafe {
+                    std::env::remove_var(luban_domain::paths::LUBAN_ROOT_ENV);
+                }
+            }
+            let _ = std::fs::remove_dir_all(&self.root);
+        }
+    }
+
+    #[tokio::test]
+    async fn shutdown_persists_queued_mutations_before_returning() {
+        let env_guard = LubanRootEnvGuard::set("shutdown-persists-queued-mutations");
+        let services = new_default_services().expect("init services");
+        let (engine, _events) = Engine::start(services.clone());
+
+        engine
+            .dispatch_domain_action(Action::AppearanceThemeChanged {
+                theme: luban_domain::AppearanceTheme::Dark,
+            })
+            .await
+            .expect("queue mutation");
+        engine.shutdown().await.expect("shutdown");
+
+        let loaded = services.load_app_state().expect("load app state");
+        assert_eq!(loaded.appearance_theme.as_deref(), Some("dark"));
+        assert!(
+            engine.current_rev().await.is_err(),
+            "a stopped engine must refuse new commands"
+        );
+        drop(env_guard);
+    }
+
+    #[test]
+    fn default_services_persist_ui_state() {
+        let env_guard = LubanRootEnvGuard::set("default-services-persist-ui-state");
+
+        let services = new_default_services().expect("init services");
+
+        let snapshot = PersistedAppState {
+            projects: vec![PersistedProject {
+                id: 1,
+                slug: "p".to_owned(),
+                name: "P".to_owned(),
+                path: PathBuf::from("/tmp/p"),
+                is_git: true,
+                expanded: false,
+                system_prompt: None,
+                worktree_root: None,
+                branch_name_template: None,
+                status: ProjectStatus::Active,
+                agent_defaults: Default::default(),
+                saved_commands: Vec::new(),
+                agent_env: Vec::new(),
+                pr_remote: None,
+                pr_base_branch: None,
+                workspaces: vec![PersistedWorkspace {
+                    id: 10,
+                    workspace_name: "main".to_owned(),
+                    branch_name: "main".to_owned(),
+                    worktree_path: PathBuf::from("/tmp/p"),
+                    status: WorkspaceStatus::Active,
+                    last_activity_at_unix_seconds: None,
+                }],
+            }],
+            sidebar_width: None,
+            terminal_pane_width: None,
+            global_zoom_percent: None,
+            appearance_theme: None,
+            appearance_ui_font: None,
+            appearance_chat_font: None,
+            appearance_code_font: None,
+            appearance_terminal_font: None,
+            agent_default_model_id: None,
+            agent_runner_default_models: HashMap::new(),
+            agent_runner_prompt_prefixes: HashMap::new(),
+            agent_runner_prompt_suffixes: HashMap::new(),
+            model_thinking_effort_caps: HashMap::new(),
+            agent_default_thinking_effort: None,
+            agent_default_runner: None,
+            agent_amp_mode: None,
+            agent_codex_enabled: Some(true),
+            agent_amp_enabled: Some(true),
+            agent_claude_enabled: Some(true),
+            agent_droid_enabled: Some(true),
+            last_open_workspace_id: Some(10),
+            open_button_selection: None,
+            sidebar_project_order: Vec::new(),
+            project_groups: Vec::new(),
+            workspace_active_thread_id: HashMap::from([(10, 2)]),
+            workspace_open_tabs: HashMap::from([(10, vec![1, 2])]),
+            workspace_archived_tabs: HashMap::new(),
+            workspace_next_thread_id: HashMap::from([(10, 3)]),
+            workspace_chat_scroll_y10: HashMap::new(),
+            workspace_chat_scroll_anchor: HashMap::new(),
+            workspace_unread_completions: HashMap::new(),
+            workspace_thread_run_config_overrides: HashMap::new(),
+            starred_tasks: HashMap::new(),
+            task_prompt_templates: HashMap::new(),
+            telegram_enabled: None,
+            telegram_bot_token: None,
+            telegram_bot_username: None,
+            telegram_paired_chat_ids: Vec::new(),
+            telegram_topic_bindings: None,
+            webhook_url: None,
+            webhook_secret: None,
+            agent_merge_reasoning_entries: None,
+            agent_pause_queue_on_patch_failure: None,
+            agent_item_dedup_window: None,
+            agent_auto_retry_on_failure: None,
+            agent_command_output_max_bytes: None,
+            auto_archive_after_days: None,
+        };
+
+        services
+            .save_app_state(snapshot.clone())
+            .expect("save app state");
+        let loaded = services.load_app_state().expect("load app state");
+
+        assert_eq!(loaded.workspace_open_tabs.get(&10), Some(&vec![1, 2]));
+        assert_eq!(loaded.workspace_next_thread_id.get(&10), Some(&3));
+        assert_eq!(loaded.workspace_active_thread_id.get(&10), Some(&2));
+        drop(env_guard);
+    }
+
+    #[test]
+    fn workspace_threads_changed_includes_tabs_snapshot() {
+        let mut state = AppState::new();
+        let _ = state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/luban-server-test"),
+            is_git: true,
+        });
+
+        let project_id = state.projects[0].id;
+        let _ = state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "main".to_owned(),
+            branch_name: "main".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban-server-test"),
+        });
+
+        let workspace_id = state.projects[0].workspaces[0].id;
+        state.apply(Action::OpenWorkspace { workspace_id });
+
+        state.apply(Action::CreateWorkspaceThread { workspace_id });
+        state.apply(Action::CreateWorkspaceThread { workspace_id });
+
+        let open_tabs = state
+            .workspace_tabs(workspace_id)
+            .expect("workspace tabs exist after opening workspace")
+            .open_tabs
+            .clone();
+
+        let archived_thread = open_tabs[0];
+        state.apply(Action::CloseWorkspaceThreadTab {
             workspace_id,
-            thread_id,
-        } => Some(Action::CancelAgentTurn {
-            workspace_id: WorkspaceId::from_u64(workspace_id.0),
-            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
-        }),
-        luban_api::ClientAction::CreateWorkspaceThread { workspace_id } => {
-            Some(Action::CreateWorkspaceThread {
-                workspace_id: WorkspaceId::from_u64(workspace_id.0),
+            thread_id: archived_thread,
+        });
+
+        let tabs = state.workspace_tabs(workspace_id).unwrap();
+        assert!(tabs.archived_tabs.contains(&archived_thread));
+
+        let mut meta_ids = Vec::new();
+        meta_ids.extend(tabs.open_tabs.iter().copied());
+        meta_ids.extend(tabs.archived_tabs.iter().copied());
+        let metas = meta_ids
+            .iter()
+            .map(|id| ConversationThreadMeta {
+                thread_id: *id,
+                remote_thread_id: None,
+                title: format!("thread-{}", id.as_u64()),
+                created_at_unix_seconds: 0,
+                updated_at_unix_seconds: 0,
+                task_status: luban_domain::TaskStatus::Todo,
+                last_message_seq: 0,
+                task_status_last_analyzed_message_seq: 0,
+                turn_status: luban_domain::TurnStatus::Idle,
+                last_turn_result: None,
+                total_tokens: 0,
             })
-        }
-        luban_api::ClientAction::ActivateWorkspaceThread {
-            workspace_id,
-            thread_id,
-        } => Some(Action::ActivateWorkspaceThread {
-            workspace_id: WorkspaceId::from_u64(workspace_id.0),
-            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
-        }),
-        luban_api::ClientAction::CloseWorkspaceThreadTab {
-            workspace_id,
-            thread_id,
-        } => Some(Action::CloseWorkspaceThreadTab {
-            workspace_id: WorkspaceId::from_u64(workspace_id.0),
-            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
-        }),
-        // Handled directly in apply_client_action (DB delete + domain purge)
-        luban_api::ClientAction::DeleteWorkspaceThread { .. } => None,
-        luban_api::ClientAction::RestoreWorkspaceThreadTab {
+            .collect::<Vec<_>>();
+
+        let (events, _) = broadcast::channel::<WsServerMessage>(4);
+        let mut rx = events.subscribe();
+        let (tx, _rx_cmd) = mpsc::channel::<EngineCommand>(1);
+        let engine = Engine {
+            state,
+            rev: 1,
+            services: Arc::new(TestServices),
+            events,
+            tx,
+            branch_watch: BranchWatchHandle::disabled(),
+            cancel_flags: HashMap::new(),
+            pull_requests: HashMap::new(),
+            pull_requests_in_flight: HashSet::new(),
+            workspace_threads_cache: HashMap::new(),
+            auto_archive_workspaces: HashSet::new(),
+            pending_notes_flushes: HashMap::new(),
+            pending_draft_flushes: HashMap::new(),
+            conversation_revs: HashMap::new(),
+            last_git_fetch_at: HashMap::new(),
+            worktree_dirty: HashMap::new(),
+            worktree_dirty_in_flight: HashSet::new(),
+            worktree_dirty_rerun: HashSet::new(),
+            last_commits: HashMap::new(),
+            agent_turn_slots: HashMap::new(),
+            deferred_agent_turns: VecDeque::new(),
+            request_cancellations: RequestCancellations::default(),
+            attachment_thumbnails: AttachmentThumbnails::default(),
+            telegram_pairings: Vec::new(),
+            config: EngineConfig::default(),
+        };
+
+        engine.publish_threads_event(workspace_id, &metas);
+
+        let message = rx.try_recv().expect("expected a threads event");
+        let WsServerMessage::Event { event, .. } = message else {
+            panic!("expected WsServerMessage::Event");
+        };
+
+        let luban_api::ServerEvent::WorkspaceThreadsChanged {
+            workspace_id: wid,
+            tabs,
+            ..
+        } = *event
+        else {
+            panic!("expected workspace_threads_changed");
+        };
+
+        assert_eq!(wid.0, workspace_id.as_u64());
+        assert_eq!(
+            tabs.open_tabs.len() + tabs.archived_tabs.len(),
+            metas.len(),
+            "tabs snapshot should match the set of known thread ids"
+        );
+        assert!(
+            tabs.archived_tabs
+                .iter()
+                .any(|id| id.0 == archived_thread.as_u64())
+        );
+    }
+
+    #[test]
+    fn workspace_threads_changed_dedups_duplicate_thread_ids() {
+        let mut state = AppState::new();
+        let _ = state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/luban-server-test"),
+            is_git: true,
+        });
+
+        let project_id = state.projects[0].id;
+        let _ = state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "main".to_owned(),
+            branch_name: "main".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban-server-test"),
+        });
+
+        let workspace_id = state.projects[0].workspaces[0].id;
+        state.apply(Action::OpenWorkspace { workspace_id });
+
+        let thread_id = state
+            .workspace_tabs(workspace_id)
+            .expect("workspace tabs exist after opening workspace")
+            .active_tab;
+
+        let metas = vec![
+            ConversationThreadMeta {
+                thread_id,
+                remote_thread_id: None,
+                title: "alpha".to_owned(),
+                created_at_unix_seconds: 0,
+                updated_at_unix_seconds: 0,
+                task_status: luban_domain::TaskStatus::Todo,
+                last_message_seq: 0,
+                task_status_last_analyzed_message_seq: 0,
+                turn_status: luban_domain::TurnStatus::Idle,
+                last_turn_result: None,
+                total_tokens: 0,
+            },
+            ConversationThreadMeta {
+                thread_id,
+                remote_thread_id: None,
+                title: "beta".to_owned(),
+                created_at_unix_seconds: 0,
+                updated_at_unix_seconds: 0,
+                task_status: luban_domain::TaskStatus::Todo,
+                last_message_seq: 0,
+                task_status_last_analyzed_message_seq: 0,
+                turn_status: luban_domain::TurnStatus::Idle,
+                last_turn_result: None,
+                total_tokens: 0,
+            },
+        ];
+
+        let (events, _) = broadcast::channel::<WsServerMessage>(4);
+        let mut rx = events.subscribe();
+        let (tx, _rx_cmd) = mpsc::channel::<EngineCommand>(1);
+        let engine = Engine {
+            state,
+            rev: 1,
+            services: Arc::new(TestServices),
+            events,
+            tx,
+            branch_watch: BranchWatchHandle::disabled(),
+            cancel_flags: HashMap::new(),
+            pull_requests: HashMap::new(),
+            pull_requests_in_flight: HashSet::new(),
+            workspace_threads_cache: HashMap::new(),
+            auto_archive_workspaces: HashSet::new(),
+            pending_notes_flushes: HashMap::new(),
+            pending_draft_flushes: HashMap::new(),
+            conversation_revs: HashMap::new(),
+            last_git_fetch_at: HashMap::new(),
+            worktree_dirty: HashMap::new(),
+            worktree_dirty_in_flight: HashSet::new(),
+            worktree_dirty_rerun: HashSet::new(),
+            last_commits: HashMap::new(),
+            agent_turn_slots: HashMap::new(),
+            deferred_agent_turns: VecDeque::new(),
+            request_cancellations: RequestCancellations::default(),
+            attachment_thumbnails: AttachmentThumbnails::default(),
+            telegram_pairings: Vec::new(),
+            config: EngineConfig::default(),
+        };
+
+        engine.publish_threads_event(workspace_id, &metas);
+
+        let message = rx.try_recv().expect("expected a threads event");
+        let WsServerMessage::Event { event, .. } = message else {
+            panic!("expected WsServerMessage::Event");
+        };
+
+        let luban_api::ServerEvent::WorkspaceThreadsChanged { threads, .. } = *event else {
+            panic!("expected workspace_threads_changed");
+        };
+
+        assert_eq!(threads.len(), 1);
+        assert_eq!(threads[0].thread_id.0, thread_id.as_u64());
+        assert_eq!(threads[0].title, "alpha");
+    }
+
+    #[tokio::test]
+    async fn mark_all_read_publishes_cleared_task_summaries() {
+        let mut state = AppState::new();
+        let _ = state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/luban-server-test"),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+        let _ = state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "main".to_owned(),
+            branch_name: "main".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban-server-test"),
+        });
+        let workspace_id = state.projects[0].workspaces[0].id;
+        let _ = state.apply(Action::OpenWorkspace { workspace_id });
+        let thread_id = state
+            .workspace_tabs(workspace_id)
+            .expect("workspace tabs exist after opening workspace")
+            .active_tab;
+        state.workspace_unread_completions.insert(workspace_id);
+
+        let (events, mut rx) = broadcast::channel::<WsServerMessage>(16);
+        let (tx, _rx_cmd) = mpsc::channel::<EngineCommand>(16);
+        let mut engine = Engine {
+            state,
+            rev: 1,
+            services: Arc::new(TestServices),
+            events,
+            tx,
+            branch_watch: BranchWatchHandle::disabled(),
+            cancel_flags: HashMap::new(),
+            pull_requests: HashMap::new(),
+            pull_requests_in_flight: HashSet::new(),
+            workspace_threads_cache: HashMap::new(),
+            auto_archive_workspaces: HashSet::new(),
+            pending_notes_flushes: HashMap::new(),
+            pending_draft_flushes: HashMap::new(),
+            conversation_revs: HashMap::new(),
+            last_git_fetch_at: HashMap::new(),
+            worktree_dirty: HashMap::new(),
+            worktree_dirty_in_flight: HashSet::new(),
+            worktree_dirty_rerun: HashSet::new(),
+            last_commits: HashMap::new(),
+            agent_turn_slots: HashMap::new(),
+            deferred_agent_turns: VecDeque::new(),
+            request_cancellations: RequestCancellations::default(),
+            attachment_thumbnails: AttachmentThumbnails::default(),
+            telegram_pairings: Vec::new(),
+            config: EngineConfig::default(),
+        };
+        engine.workspace_threads_cache.insert(
             workspace_id,
-            thread_id,
-        } => Some(Action::RestoreWorkspaceThreadTab {
-            workspace_id: WorkspaceId::from_u64(workspace_id.0),
-            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
-        }),
-        luban_api::ClientAction::ReorderWorkspaceThreadTab {
+            vec![ConversationThreadMeta {
+                thread_id,
+                remote_thread_id: None,
+                title: "active".to_owned(),
+                created_at_unix_seconds: 1,
+                updated_at_unix_seconds: 2,
+                task_status: luban_domain::TaskStatus::Todo,
+                last_message_seq: 0,
+                task_status_last_analyzed_message_seq: 0,
+                turn_status: luban_domain::TurnStatus::Idle,
+                last_turn_result: Some(luban_domain::TurnResult::Completed),
+                total_tokens: 0,
+            }],
+        );
+
+        engine.process_action_queue(Action::MarkAllRead).await;
+        assert!(!engine.state.workspace_has_unread_completion(workspace_id));
+
+        let tasks = loop {
+            let msg = rx.try_recv().expect("task summaries should be published");
+            if let WsServerMessage::Event { event, .. } = msg
+                && let luban_api::ServerEvent::TaskSummariesChanged {
+                    workspace_id: wid,
+                    tasks,
+                    ..
+                } = *event
+            {
+                assert_eq!(wid.0, workspace_id.as_u64());
+                break tasks;
+            }
+        };
+        assert_eq!(tasks.len(), 1);
+        assert!(!tasks[0].has_unread_completion);
+    }
+
+    #[test]
+    fn task_summaries_changed_marks_running_unread_and_starred() {
+        let mut state = AppState::new();
+        let _ = state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/luban-server-test"),
+            is_git: true,
+        });
+
+        let project_id = state.projects[0].id;
+        let _ = state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "main".to_owned(),
+            branch_name: "main".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban-server-test"),
+        });
+
+        let workspace_id = state.projects[0].workspaces[0].id;
+        state.apply(Action::OpenWorkspace { workspace_id });
+
+        let active_thread_id = state
+            .workspace_tabs(workspace_id)
+            .expect("workspace tabs exist after opening workspace")
+            .active_tab;
+        let other_thread_id =
+            WorkspaceThreadId::from_u64(active_thread_id.as_u64().saturating_add(1));
+
+        state.apply(Action::ConversationLoaded {
             workspace_id,
+            thread_id: active_thread_id,
+            snapshot: luban_domain::ConversationSnapshot {
+                title: Some("active".to_owned()),
+                thread_id: None,
+                task_status: luban_domain::TaskStatus::Todo,
+                runner: None,
+                agent_model_id: None,
+                thinking_effort: None,
+                amp_mode: None,
+                claude_max_output_tokens: None,
+                claude_system_prompt: None,
+                auto_commit: false,
+                entries: Vec::new(),
+                entries_total: 0,
+                entries_start: 0,
+                pending_prompts: Vec::new(),
+                queue_paused: false,
+                run_started_at_unix_ms: None,
+                run_finished_at_unix_ms: None,
+                notes: String::new(),
+                cwd_subpath: None,
+                hunk_comments: Vec::new(),
+                collapsed_ranges: Vec::new(),
+                draft: String::new(),
+                total_usage: Default::default(),
+            },
+        });
+
+        state
+            .conversations
+            .get_mut(&(workspace_id, active_thread_id))
+            .expect("expected conversation to exist after ConversationLoaded")
+            .run_status = OperationStatus::Running;
+        state.workspace_unread_completions.insert(workspace_id);
+        state.starred_tasks.insert((workspace_id, other_thread_id));
+
+        let metas = vec![
+            ConversationThreadMeta {
+                thread_id: active_thread_id,
+                remote_thread_id: None,
+                title: "active".to_owned(),
+                created_at_unix_seconds: 1,
+                updated_at_unix_seconds: 2,
+                task_status: luban_domain::TaskStatus::Todo,
+                last_message_seq: 0,
+                task_status_last_analyzed_message_seq: 0,
+                turn_status: luban_domain::TurnStatus::Idle,
+                last_turn_result: Some(luban_domain::TurnResult::Completed),
+                total_tokens: 0,
+            },
+            ConversationThreadMeta {
+                thread_id: other_thread_id,
+                remote_thread_id: None,
+                title: "other".to_owned(),
+                created_at_unix_seconds: 3,
+                updated_at_unix_seconds: 4,
+                task_status: luban_domain::TaskStatus::Backlog,
+                last_message_seq: 0,
+                task_status_last_analyzed_message_seq: 0,
+                turn_status: luban_domain::TurnStatus::Awaiting,
+                last_turn_result: None,
+                total_tokens: 0,
+            },
+        ];
+
+        let (events, _) = broadcast::channel::<WsServerMessage>(4);
+        let mut rx = events.subscribe();
+        let (tx, _rx_cmd) = mpsc::channel::<EngineCommand>(1);
+        let mut engine = Engine {
+            state,
+            rev: 1,
+            services: Arc::new(TestServices),
+            events,
+            tx,
+            branch_watch: BranchWatchHandle::disabled(),
+            cancel_flags: HashMap::new(),
+            pull_requests: HashMap::new(),
+            pull_requests_in_flight: HashSet::new(),
+            workspace_threads_cache: HashMap::new(),
+            auto_archive_workspaces: HashSet::new(),
+            pending_notes_flushes: HashMap::new(),
+            pending_draft_flushes: HashMap::new(),
+            conversation_revs: HashMap::new(),
+            last_git_fetch_at: HashMap::new(),
+            worktree_dirty: HashMap::new(),
+            worktree_dirty_in_flight: HashSet::new(),
+            worktree_dirty_rerun: HashSet::new(),
+            last_commits: HashMap::new(),
+            agent_turn_slots: HashMap::new(),
+            deferred_agent_turns: VecDeque::new(),
+            request_cancellations: RequestCancellations::default(),
+            attachment_thumbnails: AttachmentThumbnails::default(),
+            telegram_pairings: Vec::new(),
+            config: EngineConfig::default(),
+        };
+        engine.workspace_threads_cache.insert(workspace_id, metas);
+
+        engine.publish_task_summaries_event(workspace_id);
+
+        let message = rx.try_recv().expect("expected a task summaries event");
+        let WsServerMessage::Event { event, .. } = message else {
+            panic!("expected WsServerMessage::Event");
+        };
+
+        let luban_api::ServerEvent::TaskSummariesChanged {
+            workspace_id: wid,
+            tasks,
+            ..
+        } = *event
+        else {
+            panic!("expected task_summaries_changed");
+        };
+        assert_eq!(wid.0, workspace_id.as_u64());
+
+        let active = tasks
+            .iter()
+            .find(|t| t.thread_id.0 == active_thread_id.as_u64())
+            .expect("active task should be present");
+        let other = tasks
+            .iter()
+            .find(|t| t.thread_id.0 == other_thread_id.as_u64())
+            .expect("other task should be present");
+
+        assert_eq!(active.agent_run_status, luban_api::OperationStatus::Running);
+        assert!(active.has_unread_completion);
+        assert!(!active.is_starred);
+
+        assert_eq!(other.agent_run_status, luban_api::OperationStatus::Idle);
+        assert!(!other.has_unread_completion);
+        assert!(other.is_starred);
+    }
+
+    #[tokio::test]
+    async fn task_star_set_emits_task_summaries_changed() {
+        let mut state = AppState::new();
+        let _ = state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/luban-server-test"),
+            is_git: true,
+        });
+
+        let project_id = state.projects[0].id;
+        let _ = state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "main".to_owned(),
+            branch_name: "main".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban-server-test"),
+        });
+
+        let workspace_id = state.projects[0].workspaces[0].id;
+        state.apply(Action::OpenWorkspace { workspace_id });
+        let thread_id = state
+            .workspace_tabs(workspace_id)
+            .expect("workspace tabs exist after opening workspace")
+            .active_tab;
+
+        let metas = vec![ConversationThreadMeta {
             thread_id,
-            to_index,
-        } => Some(Action::ReorderWorkspaceThreadTab {
-            workspace_id: WorkspaceId::from_u64(workspace_id.0),
-            thread_id: WorkspaceThreadId::from_u64(thread_id.0),
-            to_index,
-        }),
-        luban_api::ClientAction::OpenButtonSelectionChanged { selection } => {
-            Some(Action::OpenButtonSelectionChanged { selection })
-        }
-        luban_api::ClientAction::SidebarProjectOrderChanged { project_ids } => {
-            Some(Action::SidebarProjectOrderChanged {
-                project_ids: project_ids.into_iter().map(|id| id.0).collect(),
-            })
-        }
-        luban_api::ClientAction::AppearanceThemeChanged { theme } => {
-            Some(Action::AppearanceThemeChanged {
-                theme: match theme {
-                    luban_api::AppearanceTheme::Light => luban_domain::AppearanceTheme::Light,
-                    luban_api::AppearanceTheme::Dark => luban_domain::AppearanceTheme::Dark,
-                    luban_api::AppearanceTheme::System => luban_domain::AppearanceTheme::System,
-                },
+            remote_thread_id: None,
+            title: "alpha".to_owned(),
+            created_at_unix_seconds: 1,
+            updated_at_unix_seconds: 2,
+            task_status: luban_domain::TaskStatus::Todo,
+            last_message_seq: 0,
+            task_status_last_analyzed_message_seq: 0,
+            turn_status: luban_domain::TurnStatus::Idle,
+            last_turn_result: None,
+            total_tokens: 0,
+        }];
+
+        let (events, _) = broadcast::channel::<WsServerMessage>(16);
+        let mut rx = events.subscribe();
+        let (tx, _rx_cmd) = mpsc::channel::<EngineCommand>(1);
+        let mut engine = Engine {
+            state,
+            rev: 1,
+            services: Arc::new(IdentityServices),
+            events,
+            tx,
+            branch_watch: BranchWatchHandle::disabled(),
+            cancel_flags: HashMap::new(),
+            pull_requests: HashMap::new(),
+            pull_requests_in_flight: HashSet::new(),
+            workspace_threads_cache: HashMap::new(),
+            auto_archive_workspaces: HashSet::new(),
+            pending_notes_flushes: HashMap::new(),
+            pending_draft_flushes: HashMap::new(),
+            conversation_revs: HashMap::new(),
+            last_git_fetch_at: HashMap::new(),
+            worktree_dirty: HashMap::new(),
+            worktree_dirty_in_flight: HashSet::new(),
+            worktree_dirty_rerun: HashSet::new(),
+            last_commits: HashMap::new(),
+            agent_turn_slots: HashMap::new(),
+            deferred_agent_turns: VecDeque::new(),
+            request_cancellations: RequestCancellations::default(),
+            attachment_thumbnails: AttachmentThumbnails::default(),
+            telegram_pairings: Vec::new(),
+            config: EngineConfig::default(),
+        };
+        engine.workspace_threads_cache.insert(workspace_id, metas);
+
+        engine
+            .process_action_queue(Action::TaskStarSet {
+                workspace_id,
+                thread_id,
+                starred: true,
             })
+            .await;
+
+        let mut saw = false;
+        for _ in 0..20 {
+            let msg = match tokio::time::timeout(Duration::from_secs(1), rx.recv()).await {
+                Ok(Ok(msg)) => msg,
+                _ => continue,
+            };
+            let WsServerMessage::Event { event, .. } = msg else {
+                continue;
+            };
+            let luban_api::ServerEvent::TaskSummariesChanged { tasks, .. } = *event else {
+                continue;
+            };
+            let Some(task) = tasks.iter().find(|t| t.thread_id.0 == thread_id.as_u64()) else {
+                continue;
+            };
+            if task.is_starred {
+                saw = true;
+                break;
+            }
         }
-        luban_api::ClientAction::AppearanceFontsChanged { fonts } => {
-            Some(Action::AppearanceFontsChanged {
-                ui_font: fonts.ui_font,
-                chat_font: fonts.chat_font,
-                code_font: fonts.code_font,
-                terminal_font: fonts.terminal_font,
+        assert!(
+            saw,
+            "expected a task_summaries_changed event reflecting the star"
+        );
+    }
+
+    #[tokio::test]
+    async fn denied_client_actions_are_rejected_before_applying() {
+        let mut state = AppState::new();
+        let _ = state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/luban-server-test"),
+            is_git: true,
+        });
+        let expanded_before = state.projects[0].expanded;
+
+        let (events, _) = broadcast::channel::<WsServerMessage>(16);
+        let (tx, _rx_cmd) = mpsc::channel::<EngineCommand>(16);
+        let mut engine = Engine {
+            state,
+            rev: 1,
+            services: Arc::new(TestServices),
+            events,
+            tx,
+            branch_watch: BranchWatchHandle::disabled(),
+            cancel_flags: HashMap::new(),
+            pull_requests: HashMap::new(),
+            pull_requests_in_flight: HashSet::new(),
+            workspace_threads_cache: HashMap::new(),
+            auto_archive_workspaces: HashSet::new(),
+            pending_notes_flushes: HashMap::new(),
+            pending_draft_flushes: HashMap::new(),
+            conversation_revs: HashMap::new(),
+            last_git_fetch_at: HashMap::new(),
+            worktree_dirty: HashMap::new(),
+            worktree_dirty_in_flight: HashSet::new(),
+            worktree_dirty_rerun: HashSet::new(),
+            last_commits: HashMap::new(),
+            agent_turn_slots: HashMap::new(),
+            deferred_agent_turns: VecDeque::new(),
+            request_cancellations: RequestCancellations::default(),
+            attachment_thumbnails: AttachmentThumbnails::default(),
+            telegram_pairings: Vec::new(),
+            config: EngineConfig {
+                denied_client_actions: HashSet::from(["delete_project".to_owned()]),
+                ..EngineConfig::default()
+            },
+        };
+        let project_id = luban_api::ProjectId("/tmp/luban-server-test".to_owned());
+
+        let (reply, rx) = oneshot::channel();
+        engine
+            .handle(EngineCommand::ApplyClientAction {
+                request_id: "req-1".to_owned(),
+                action: luban_api::ClientAction::DeleteProject {
+                    project_id: project_id.clone(),
+                },
+                reply,
             })
+            .await;
+        let err = rx
+            .await
+            .expect("reply should be sent")
+            .expect_err("denied action should be rejected");
+        assert!(err.contains("unauthorized"), "{err}");
+        assert!(err.contains("delete_project"), "{err}");
+        assert_eq!(engine.state.projects.len(), 1);
+
+        let (reply, rx) = oneshot::channel();
+        engine
+            .handle(EngineCommand::ApplyClientAction {
+                request_id: "req-2".to_owned(),
+                action: luban_api::ClientAction::ToggleProjectExpanded { project_id },
+                reply,
+            })
+            .await;
+        rx.await
+            .expect("reply should be sent")
+            .expect("allowed action should apply");
+        assert_ne!(engine.state.projects[0].expanded, expanded_before);
+    }
+
+    #[tokio::test]
+    async fn oversized_attachments_are_rejected_without_queueing() {
+        let mut state = AppState::new();
+        let _ = state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/luban-server-test"),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+        let _ = state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "main".to_owned(),
+            branch_name: "main".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban-server-test"),
+        });
+        let workspace_id = state.projects[0].workspaces[0].id;
+        let _ = state.apply(Action::OpenWorkspace { workspace_id });
+        let _ = state.apply(Action::CreateWorkspaceThread { workspace_id });
+        let thread_id = state
+            .workspace_tabs(workspace_id)
+            .and_then(|tabs| tabs.open_tabs.last().copied())
+            .expect("thread should exist");
+
+        let (events, _) = broadcast::channel::<WsServerMessage>(16);
+        let (tx, _rx_cmd) = mpsc::channel::<EngineCommand>(16);
+        let mut engine = Engine {
+            state,
+            rev: 1,
+            services: Arc::new(TestServices),
+            events,
+            tx,
+            branch_watch: BranchWatchHandle::disabled(),
+            cancel_flags: HashMap::new(),
+            pull_requests: HashMap::new(),
+            pull_requests_in_flight: HashSet::new(),
+            workspace_threads_cache: HashMap::new(),
+            auto_archive_workspaces: HashSet::new(),
+            pending_notes_flushes: HashMap::new(),
+            pending_draft_flushes: HashMap::new(),
+            conversation_revs: HashMap::new(),
+            last_git_fetch_at: HashMap::new(),
+            worktree_dirty: HashMap::new(),
+            worktree_dirty_in_flight: HashSet::new(),
+            worktree_dirty_rerun: HashSet::new(),
+            last_commits: HashMap::new(),
+            agent_turn_slots: HashMap::new(),
+            deferred_agent_turns: VecDeque::new(),
+            request_cancellations: RequestCancellations::default(),
+            attachment_thumbnails: AttachmentThumbnails::default(),
+            telegram_pairings: Vec::new(),
+            config: EngineConfig {
+                max_attachment_bytes: 1024,
+                ..EngineConfig::default()
+            },
+        };
+
+        let attachment = |name: &str, byte_len: u64| luban_api::AttachmentRef {
+            id: name.to_owned(),
+            kind: luban_api::AttachmentKind::File,
+            name: name.to_owned(),
+            extension: "bin".to_owned(),
+            mime: None,
+            byte_len,
+            thumbnail_base64: None,
+        };
+        let cases = [
+            (
+                vec![attachment("huge.bin", 4096)],
+                "'huge.bin' is 4096 bytes",
+            ),
+            (
+                vec![attachment("a.bin", 600), attachment("b.bin", 600)],
+                "total 1200 bytes",
+            ),
+        ];
+        for (attachments, expected) in cases {
+            let (reply, rx) = oneshot::channel();
+            engine
+                .handle(EngineCommand::ApplyClientAction {
+                    request_id: "req-1".to_owned(),
+                    action: luban_api::ClientAction::QueueAgentMessage {
+                        workspace_id: luban_api::WorkspaceId(workspace_id.as_u64()),
+                        thread_id: luban_api::WorkspaceThreadId(thread_id.as_u64()),
+                        text: "see attached".to_owned(),
+                        attachments,
+                        runner: None,
+                        amp_mode: None,
+                        expected_rev: None,
+                    },
+                    reply,
+                })
+                .await;
+            let err = rx
+                .await
+                .expect("reply should be sent")
+                .expect_err("oversized attachments should be rejected");
+            assert!(err.contains(expected), "{err}");
+            assert!(err.contains("1024 byte limit"), "{err}");
         }
-        luban_api::ClientAction::AppearanceGlobalZoomChanged { zoom } => {
-            Some(Action::AppearanceGlobalZoomChanged { zoom })
-        }
-        luban_api::ClientAction::CodexEnabledChanged { enabled } => {
-            Some(Action::AgentCodexEnabledChanged { enabled })
-        }
-        luban_api::ClientAction::AmpEnabledChanged { enabled } => {
-            Some(Action::AgentAmpEnabledChanged { enabled })
+
+        let conversation = engine
+            .state
+            .workspace_thread_conversation(workspace_id, thread_id)
+            .expect("conversation should exist");
+        assert!(conversation.pending_prompts.is_empty());
+        assert_eq!(engine.rev, 1);
+    }
+
+    #[tokio::test]
+    async fn move_thread_rejects_running_tasks_and_other_projects() {
+        let mut state = AppState::new();
+        for path in ["/tmp/luban-server-test", "/tmp/luban-server-other"] {
+            let _ = state.apply(Action::AddProject {
+                path: PathBuf::from(path),
+                is_git: true,
+            });
         }
-        luban_api::ClientAction::ClaudeEnabledChanged { enabled } => {
-            Some(Action::AgentClaudeEnabledChanged { enabled })
+        let project_id = state.projects[0].id;
+        let other_project_id = state.projects[1].id;
+        for (project_id, name, path) in [
+            (project_id, "main", "/tmp/luban-server-test"),
+            (project_id, "w2", "/tmp/luban-server-test-w2"),
+            (other_project_id, "main", "/tmp/luban-server-other"),
+        ] {
+            let _ = state.apply(Action::WorkspaceCreated {
+                project_id,
+                workspace_name: name.to_owned(),
+                branch_name: name.to_owned(),
+                worktree_path: PathBuf::from(path),
+            });
         }
-        luban_api::ClientAction::DroidEnabledChanged { enabled } => {
-            Some(Action::AgentDroidEnabledChanged { enabled })
+        let workspace_id = state.projects[0].workspaces[0].id;
+        let sibling_id = state.projects[0].workspaces[1].id;
+        let other_id = state.projects[1].workspaces[0].id;
+        let _ = state.apply(Action::OpenWorkspace { workspace_id });
+        let _ = state.apply(Action::CreateWorkspaceThread { workspace_id });
+        let thread_id = state
+            .workspace_tabs(workspace_id)
+            .and_then(|tabs| tabs.open_tabs.last().copied())
+            .expect("thread should exist");
+        state
+            .conversations
+            .get_mut(&(workspace_id, thread_id))
+            .expect("conversation should exist")
+            .run_status = OperationStatus::Running;
+
+        let (events, _) = broadcast::channel::<WsServerMessage>(16);
+        let (tx, _rx_cmd) = mpsc::channel::<EngineCommand>(16);
+        let mut engine = Engine {
+            state,
+            rev: 1,
+            services: Arc::new(TestServices),
+            events,
+            tx,
+            branch_watch: BranchWatchHandle::disabled(),
+            cancel_flags: HashMap::new(),
+            pull_requests: HashMap::new(),
+            pull_requests_in_flight: HashSet::new(),
+            workspace_threads_cache: HashMap::new(),
+            auto_archive_workspaces: HashSet::new(),
+            pending_notes_flushes: HashMap::new(),
+            pending_draft_flushes: HashMap::new(),
+            conversation_revs: HashMap::new(),
+            last_git_fetch_at: HashMap::new(),
+            worktree_dirty: HashMap::new(),
+            worktree_dirty_in_flight: HashSet::new(),
+            worktree_dirty_rerun: HashSet::new(),
+            last_commits: HashMap::new(),
+            agent_turn_slots: HashMap::new(),
+            deferred_agent_turns: VecDeque::new(),
+            request_cancellations: RequestCancellations::default(),
+            attachment_thumbnails: AttachmentThumbnails::default(),
+            telegram_pairings: Vec::new(),
+            config: EngineConfig::default(),
+        };
+
+        let cases = [
+            (sibling_id, "cancel it before moving"),
+            (other_id, "different project"),
+        ];
+        for (to_workspace_id, expected) in cases {
+            let (reply, rx) = oneshot::channel();
+            engine
+                .handle(EngineCommand::ApplyClientAction {
+                    request_id: "req-1".to_owned(),
+                    action: luban_api::ClientAction::MoveThread {
+                        from_workspace_id: luban_api::WorkspaceId(workspace_id.as_u64()),
+                        thread_id: luban_api::WorkspaceThreadId(thread_id.as_u64()),
+                        to_workspace_id: luban_api::WorkspaceId(to_workspace_id.as_u64()),
+                    },
+                    reply,
+                })
+                .await;
+            let err = rx
+                .await
+                .expect("reply should be sent")
+                .expect_err("move should be rejected");
+            assert!(err.contains(expected), "{err}");
         }
-        luban_api::ClientAction::AgentRunnerChanged { runner } => {
-            Some(Action::AgentRunnerChanged {
-                runner: match runner {
-                    luban_api::AgentRunnerKind::Codex => luban_domain::AgentRunnerKind::Codex,
-                    luban_api::AgentRunnerKind::Amp => luban_domain::AgentRunnerKind::Amp,
-                    luban_api::AgentRunnerKind::Claude => luban_domain::AgentRunnerKind::Claude,
-                    luban_api::AgentRunnerKind::Droid => luban_domain::AgentRunnerKind::Droid,
-                },
-            })
+
+        assert!(
+            engine
+                .state
+                .workspace_thread_conversation(workspace_id, thread_id)
+                .is_some()
+        );
+        assert_eq!(engine.rev, 1);
+    }
+
+    #[tokio::test]
+    async fn starred_tasks_detailed_combines_workspaces_by_recency() {
+        let mut state = AppState::new();
+        let _ = state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/luban-server-test"),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+        for (name, path) in [
+            ("main", "/tmp/luban-server-test"),
+            ("w1", "/tmp/luban-server-test-w1"),
+        ] {
+            let _ = state.apply(Action::WorkspaceCreated {
+                project_id,
+                workspace_name: name.to_owned(),
+                branch_name: name.to_owned(),
+                worktree_path: PathBuf::from(path),
+            });
         }
-        luban_api::ClientAction::AgentAmpModeChanged { mode } => {
-            Some(Action::AgentAmpModeChanged { mode })
+        let main_id = state.projects[0].workspaces[0].id;
+        let w1_id = state.projects[0].workspaces[1].id;
+
+        let meta =
+            |thread_id: u64, title: &str, updated_at_unix_seconds: u64| ConversationThreadMeta {
+                thread_id: WorkspaceThreadId::from_u64(thread_id),
+                remote_thread_id: None,
+                title: title.to_owned(),
+                created_at_unix_seconds: 1,
+                updated_at_unix_seconds,
+                task_status: luban_domain::TaskStatus::Todo,
+                last_message_seq: 0,
+                task_status_last_analyzed_message_seq: 0,
+                turn_status: luban_domain::TurnStatus::Idle,
+                last_turn_result: None,
+                total_tokens: 0,
+            };
+        for (workspace_id, thread_id) in [(main_id, 1), (main_id, 3), (w1_id, 2)] {
+            state
+                .starred_tasks
+                .insert((workspace_id, WorkspaceThreadId::from_u64(thread_id)));
         }
-        luban_api::ClientAction::TaskPromptTemplateChanged {
-            intent_kind,
-            template,
-        } => Some(Action::TaskPromptTemplateChanged {
-            intent_kind: match intent_kind {
-                luban_api::TaskIntentKind::Fix => luban_domain::TaskIntentKind::Fix,
-                luban_api::TaskIntentKind::Implement => luban_domain::TaskIntentKind::Implement,
-                luban_api::TaskIntentKind::Review => luban_domain::TaskIntentKind::Review,
-                luban_api::TaskIntentKind::Discuss => luban_domain::TaskIntentKind::Discuss,
-                luban_api::TaskIntentKind::Other => luban_domain::TaskIntentKind::Other,
-            },
-            template,
-        }),
-        luban_api::ClientAction::SystemPromptTemplateChanged { kind, template } => {
-            Some(Action::SystemPromptTemplateChanged {
-                kind: match kind {
-                    luban_api::SystemTaskKind::InferType => luban_domain::SystemTaskKind::InferType,
-                    luban_api::SystemTaskKind::RenameBranch => {
-                        luban_domain::SystemTaskKind::RenameBranch
-                    }
-                    luban_api::SystemTaskKind::AutoTitleThread => {
-                        luban_domain::SystemTaskKind::AutoTitleThread
-                    }
-                    luban_api::SystemTaskKind::AutoUpdateTaskStatus => {
-                        luban_domain::SystemTaskKind::AutoUpdateTaskStatus
-                    }
-                },
-                template,
+
+        let (events, _) = broadcast::channel::<WsServerMessage>(4);
+        let (tx, _rx_cmd) = mpsc::channel::<EngineCommand>(1);
+        let mut engine = Engine {
+            state,
+            rev: 1,
+            services: Arc::new(TestServices),
+            events,
+            tx,
+            branch_watch: BranchWatchHandle::disabled(),
+            cancel_flags: HashMap::new(),
+            pull_requests: HashMap::new(),
+            pull_requests_in_flight: HashSet::new(),
+            workspace_threads_cache: HashMap::new(),
+            auto_archive_workspaces: HashSet::new(),
+            pending_notes_flushes: HashMap::new(),
+            pending_draft_flushes: HashMap::new(),
+            conversation_revs: HashMap::new(),
+            last_git_fetch_at: HashMap::new(),
+            worktree_dirty: HashMap::new(),
+            worktree_dirty_in_flight: HashSet::new(),
+            worktree_dirty_rerun: HashSet::new(),
+            last_commits: HashMap::new(),
+            agent_turn_slots: HashMap::new(),
+            deferred_agent_turns: VecDeque::new(),
+            request_cancellations: RequestCancellations::default(),
+            attachment_thumbnails: AttachmentThumbnails::default(),
+            telegram_pairings: Vec::new(),
+            config: EngineConfig::default(),
+        };
+        engine.workspace_threads_cache.insert(
+            main_id,
+            vec![
+                meta(1, "main oldest", 10),
+                meta(2, "main unstarred", 40),
+                meta(3, "main newest", 30),
+            ],
+        );
+        engine
+            .workspace_threads_cache
+            .insert(w1_id, vec![meta(2, "w1 middle", 20)]);
+
+        let (reply, rx) = oneshot::channel();
+        engine
+            .handle(EngineCommand::GetStarredTasksDetailed { reply })
+            .await;
+        let tasks = rx
+            .await
+            .expect("reply should be sent")
+            .expect("starred tasks should load");
+
+        let titles = tasks.iter().map(|t| t.title.as_str()).collect::<Vec<_>>();
+        assert_eq!(titles, vec!["main newest", "w1 middle", "main oldest"]);
+        assert!(tasks.iter().all(|t| t.is_starred));
+        assert_eq!(tasks[1].workspace_id.0, w1_id.as_u64());
+        assert_eq!(tasks[1].workspace_name, "w1");
+    }
+
+    #[tokio::test]
+    async fn task_status_set_emits_conversation_changed() {
+        let mut state = AppState::new();
+        let _ = state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/luban-server-test"),
+            is_git: true,
+        });
+
+        let project_id = state.projects[0].id;
+        let _ = state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "main".to_owned(),
+            branch_name: "main".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban-server-test"),
+        });
+
+        let workspace_id = state.projects[0].workspaces[0].id;
+        state.apply(Action::OpenWorkspace { workspace_id });
+        state.apply(Action::CreateWorkspaceThread { workspace_id });
+        let thread_id = state
+            .workspace_tabs(workspace_id)
+            .expect("workspace tabs exist after creating thread")
+            .active_tab;
+
+        let (events, _) = broadcast::channel::<WsServerMessage>(16);
+        let mut rx = events.subscribe();
+        let (tx, _rx_cmd) = mpsc::channel::<EngineCommand>(1);
+        let mut engine = Engine {
+            state,
+            rev: 1,
+            services: Arc::new(IdentityServices),
+            events,
+            tx,
+            branch_watch: BranchWatchHandle::disabled(),
+            cancel_flags: HashMap::new(),
+            pull_requests: HashMap::new(),
+            pull_requests_in_flight: HashSet::new(),
+            workspace_threads_cache: HashMap::new(),
+            auto_archive_workspaces: HashSet::new(),
+            pending_notes_flushes: HashMap::new(),
+            pending_draft_flushes: HashMap::new(),
+            conversation_revs: HashMap::new(),
+            last_git_fetch_at: HashMap::new(),
+            worktree_dirty: HashMap::new(),
+            worktree_dirty_in_flight: HashSet::new(),
+            worktree_dirty_rerun: HashSet::new(),
+            last_commits: HashMap::new(),
+            agent_turn_slots: HashMap::new(),
+            deferred_agent_turns: VecDeque::new(),
+            request_cancellations: RequestCancellations::default(),
+            attachment_thumbnails: AttachmentThumbnails::default(),
+            telegram_pairings: Vec::new(),
+            config: EngineConfig::default(),
+        };
+
+        engine
+            .process_action_queue(Action::TaskStatusSet {
+                workspace_id,
+                thread_id,
+                task_status: luban_domain::TaskStatus::Done,
             })
+            .await;
+
+        let mut saw = false;
+        for _ in 0..40 {
+            let msg = match tokio::time::timeout(Duration::from_secs(1), rx.recv()).await {
+                Ok(Ok(msg)) => msg,
+                _ => continue,
+            };
+            let WsServerMessage::Event { event, .. } = msg else {
+                continue;
+            };
+            let luban_api::ServerEvent::ConversationChanged { snapshot, .. } = *event else {
+                continue;
+            };
+            if snapshot.workspace_id.0 != workspace_id.as_u64()
+                || snapshot.thread_id.0 != thread_id.as_u64()
+            {
+                continue;
+            }
+            if snapshot.task_status != luban_api::TaskStatus::Done {
+                continue;
+            }
+            let has_status_event = snapshot.entries.iter().any(|e| {
+                matches!(
+                    e,
+                    luban_api::ConversationEntry::SystemEvent(
+                        luban_api::ConversationSystemEventEntry {
+                            event: luban_api::ConversationSystemEvent::TaskStatusChanged { .. },
+                            ..
+                        }
+                    )
+                )
+            });
+            if has_status_event {
+                saw = true;
+                break;
+            }
         }
-        luban_api::ClientAction::CodexCheck
-        | luban_api::ClientAction::CodexConfigTree
-        | luban_api::ClientAction::CodexConfigListDir { .. }
-        | luban_api::ClientAction::CodexConfigReadFile { .. }
-        | luban_api::ClientAction::CodexConfigWriteFile { .. }
-        | luban_api::ClientAction::AmpCheck
-        | luban_api::ClientAction::AmpConfigTree
-        | luban_api::ClientAction::AmpConfigListDir { .. }
-        | luban_api::ClientAction::AmpConfigReadFile { .. }
-        | luban_api::ClientAction::AmpConfigWriteFile { .. }
-        | luban_api::ClientAction::ClaudeCheck
-        | luban_api::ClientAction::ClaudeConfigTree
-        | luban_api::ClientAction::ClaudeConfigListDir { .. }
-        | luban_api::ClientAction::ClaudeConfigReadFile { .. }
-        | luban_api::ClientAction::ClaudeConfigWriteFile { .. }
-        | luban_api::ClientAction::DroidCheck
-        | luban_api::ClientAction::DroidConfigTree
-        | luban_api::ClientAction::DroidConfigListDir { .. }
-        | luban_api::ClientAction::DroidConfigReadFile { .. }
-        | luban_api::ClientAction::DroidConfigWriteFile { .. } => None,
+
+        assert!(
+            saw,
+            "expected a conversation_changed event reflecting the status change"
+        );
     }
-}
 
-fn expand_user_path(raw: &str) -> PathBuf {
-    let trimmed = raw.trim();
-    if trimmed == "~" {
-        if let Some(home) = std::env::var_os("HOME") {
-            return PathBuf::from(home);
+    #[tokio::test]
+    async fn task_status_suggestion_created_emits_conversation_changed() {
+        let mut state = AppState::new();
+        let _ = state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/luban-server-test"),
+            is_git: true,
+        });
+
+        let project_id = state.projects[0].id;
+        let _ = state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "main".to_owned(),
+            branch_name: "main".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban-server-test"),
+        });
+
+        let workspace_id = state.projects[0].workspaces[0].id;
+        state.apply(Action::OpenWorkspace { workspace_id });
+        state.apply(Action::CreateWorkspaceThread { workspace_id });
+        let thread_id = state
+            .workspace_tabs(workspace_id)
+            .expect("workspace tabs exist after creating thread")
+            .active_tab;
+
+        let (events, _) = broadcast::channel::<WsServerMessage>(16);
+        let mut rx = events.subscribe();
+        let (tx, _rx_cmd) = mpsc::channel::<EngineCommand>(1);
+        let mut engine = Engine {
+            state,
+            rev: 1,
+            services: Arc::new(IdentityServices),
+            events,
+            tx,
+            branch_watch: BranchWatchHandle::disabled(),
+            cancel_flags: HashMap::new(),
+            pull_requests: HashMap::new(),
+            pull_requests_in_flight: HashSet::new(),
+            workspace_threads_cache: HashMap::new(),
+            auto_archive_workspaces: HashSet::new(),
+            pending_notes_flushes: HashMap::new(),
+            pending_draft_flushes: HashMap::new(),
+            conversation_revs: HashMap::new(),
+            last_git_fetch_at: HashMap::new(),
+            worktree_dirty: HashMap::new(),
+            worktree_dirty_in_flight: HashSet::new(),
+            worktree_dirty_rerun: HashSet::new(),
+            last_commits: HashMap::new(),
+            agent_turn_slots: HashMap::new(),
+            deferred_agent_turns: VecDeque::new(),
+            request_cancellations: RequestCancellations::default(),
+            attachment_thumbnails: AttachmentThumbnails::default(),
+            telegram_pairings: Vec::new(),
+            config: EngineConfig::default(),
+        };
+
+        engine
+            .process_action_queue(Action::TaskStatusSuggestionCreated {
+                workspace_id,
+                thread_id,
+                expected_current_task_status: luban_domain::TaskStatus::Backlog,
+                suggested_task_status: luban_domain::TaskStatus::Done,
+                title: "Suggest moving to done".to_owned(),
+                explanation_markdown: "- Work appears complete.".to_owned(),
+            })
+            .await;
+
+        let mut saw = false;
+        for _ in 0..40 {
+            let msg = match tokio::time::timeout(Duration::from_secs(1), rx.recv()).await {
+                Ok(Ok(msg)) => msg,
+                _ => continue,
+            };
+            let WsServerMessage::Event { event, .. } = msg else {
+                continue;
+            };
+            let luban_api::ServerEvent::ConversationChanged { snapshot, .. } = *event else {
+                continue;
+            };
+            if snapshot.workspace_id.0 != workspace_id.as_u64()
+                || snapshot.thread_id.0 != thread_id.as_u64()
+            {
+                continue;
+            }
+            if snapshot.task_status != luban_api::TaskStatus::Backlog {
+                continue;
+            }
+            let has_suggestion_event = snapshot.entries.iter().any(|e| {
+                matches!(
+                    e,
+                    luban_api::ConversationEntry::SystemEvent(
+                        luban_api::ConversationSystemEventEntry {
+                            event: luban_api::ConversationSystemEvent::TaskStatusSuggestion { .. },
+                            ..
+                        }
+                    )
+                )
+            });
+            if has_suggestion_event {
+                saw = true;
+                break;
+            }
         }
-        return PathBuf::from(trimmed);
-    }
 
-    if let Some(suffix) = trimmed.strip_prefix("~/")
-        && let Some(home) = std::env::var_os("HOME")
-    {
-        return PathBuf::from(home).join(suffix);
+        assert!(
+            saw,
+            "expected a conversation_changed event reflecting the suggestion"
+        );
     }
 
-    PathBuf::from(trimmed)
-}
+    #[tokio::test]
+    async fn stale_rev_update_queued_prompt_is_rejected() {
+        let mut state = AppState::new();
+        let _ = state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/luban-server-test"),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+        let _ = state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "main".to_owned(),
+            branch_name: "main".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban-server-test"),
+        });
+        let workspace_id = state.projects[0].workspaces[0].id;
+        state.apply(Action::OpenWorkspace { workspace_id });
+        state.apply(Action::CreateWorkspaceThread { workspace_id });
+        let thread_id = state
+            .workspace_tabs(workspace_id)
+            .expect("workspace tabs exist after creating thread")
+            .active_tab;
+        state.apply(Action::QueueAgentMessage {
+            workspace_id,
+            thread_id,
+            text: "queued".to_owned(),
+            attachments: Vec::new(),
+            runner: None,
+            amp_mode: None,
+        });
+        let queued = state
+            .workspace_thread_conversation(workspace_id, thread_id)
+            .and_then(|c| c.pending_prompts.front().cloned())
+            .expect("message should be queued");
 
-fn map_api_attachment(att: luban_api::AttachmentRef) -> AttachmentRef {
-    AttachmentRef {
-        id: att.id,
-        kind: match att.kind {
-            luban_api::AttachmentKind::Image => AttachmentKind::Image,
-            luban_api::AttachmentKind::Text => AttachmentKind::Text,
-            luban_api::AttachmentKind::File => AttachmentKind::File,
-        },
-        name: att.name,
-        extension: att.extension,
-        mime: att.mime,
-        byte_len: att.byte_len,
-    }
-}
+        let (events, _) = broadcast::channel::<WsServerMessage>(16);
+        let (tx, _rx_cmd) = mpsc::channel::<EngineCommand>(1);
+        let mut engine = Engine {
+            state,
+            rev: 1,
+            services: Arc::new(IdentityServices),
+            events,
+            tx,
+            branch_watch: BranchWatchHandle::disabled(),
+            cancel_flags: HashMap::new(),
+            pull_requests: HashMap::new(),
+            pull_requests_in_flight: HashSet::new(),
+            workspace_threads_cache: HashMap::new(),
+            auto_archive_workspaces: HashSet::new(),
+            pending_notes_flushes: HashMap::new(),
+            pending_draft_flushes: HashMap::new(),
+            conversation_revs: HashMap::new(),
+            last_git_fetch_at: HashMap::new(),
+            worktree_dirty: HashMap::new(),
+            worktree_dirty_in_flight: HashSet::new(),
+            worktree_dirty_rerun: HashSet::new(),
+            last_commits: HashMap::new(),
+            agent_turn_slots: HashMap::new(),
+            deferred_agent_turns: VecDeque::new(),
+            request_cancellations: RequestCancellations::default(),
+            attachment_thumbnails: AttachmentThumbnails::default(),
+            telegram_pairings: Vec::new(),
+            config: EngineConfig::default(),
+        };
+        let api_wid = luban_api::WorkspaceId(workspace_id.as_u64());
+        let api_tid = luban_api::WorkspaceThreadId(thread_id.as_u64());
 
-fn map_api_agent_runner_kind(kind: luban_api::AgentRunnerKind) -> luban_domain::AgentRunnerKind {
-    match kind {
-        luban_api::AgentRunnerKind::Codex => luban_domain::AgentRunnerKind::Codex,
-        luban_api::AgentRunnerKind::Amp => luban_domain::AgentRunnerKind::Amp,
-        luban_api::AgentRunnerKind::Claude => luban_domain::AgentRunnerKind::Claude,
-        luban_api::AgentRunnerKind::Droid => luban_domain::AgentRunnerKind::Droid,
+        let update = |text: &str, expected_rev: u64| luban_api::ClientAction::UpdateQueuedPrompt {
+            workspace_id: api_wid,
+            thread_id: api_tid,
+            prompt_id: queued.id,
+            text: text.to_owned(),
+            attachments: Vec::new(),
+            model_id: queued.run_config.model_id.clone(),
+            thinking_effort: luban_api::ThinkingEffort::Medium,
+            runner: None,
+            amp_mode: None,
+            expected_rev: Some(expected_rev),
+        };
+        let mut apply = async |action| {
+            let (reply, rx) = oneshot::channel();
+            engine
+                .handle(EngineCommand::ApplyClientAction {
+                    request_id: "req".to_owned(),
+                    action,
+                    reply,
+                })
+                .await;
+            rx.await.expect("engine should reply")
+        };
+
+        let seen_rev = apply(update("first edit", 1))
+            .await
+            .expect("edit against the current rev should succeed");
+        apply(update("stale edit", 1))
+            .await
+            .expect_err("edit against a stale rev should be rejected");
+        apply(update("fresh edit", seen_rev))
+            .await
+            .expect("edit against the latest rev should succeed");
+
+        let snapshot = engine
+            .conversation_snapshot(api_wid, api_tid, None, None)
+            .expect("conversation snapshot");
+        assert_eq!(snapshot.pending_prompts.len(), 1);
+        assert_eq!(snapshot.pending_prompts[0].text, "fresh edit");
     }
-}
 
-pub fn new_default_services() -> anyhow::Result<Arc<dyn ProjectWorkspaceService>> {
-    Ok(GitWorkspaceService::new_with_options(SqliteStoreOptions {
-        persist_ui_state: true,
-    })
-    .context("failed to init backend services")?)
-}
+    #[tokio::test]
+    async fn add_project_reuses_existing_by_github_repo() {
+        let (engine, _events) = Engine::start(Arc::new(IdentityServices));
+        engine
+            .apply_client_action(
+                "req-1".to_owned(),
+                luban_api::ClientAction::AddProject {
+                    path: "/tmp/repo-a".to_owned(),
+                },
+            )
+            .await
+            .expect("add first project should succeed");
+        engine
+            .apply_client_action(
+                "req-2".to_owned(),
+                luban_api::ClientAction::AddProject {
+                    path: "/tmp/repo-b".to_owned(),
+                },
+            )
+            .await
+            .expect("add second project should be reused");
+
+        let snapshot = engine.app_snapshot().await.expect("snapshot should work");
+        assert_eq!(snapshot.projects.len(), 1);
+        let loaded_path = normalize_project_path(std::path::Path::new(&snapshot.projects[0].path));
+        let expected_path = normalize_project_path(std::path::Path::new("/tmp/repo-a"));
+        assert_eq!(loaded_path, expected_path);
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use luban_domain::{
-        CodexCommandExecutionStatus, ContextImage, ContextItem,
-        ConversationSnapshot as DomainConversationSnapshot, ConversationThreadMeta,
-        PersistedAppState, PersistedProject, PersistedWorkspace, WorkspaceStatus,
-    };
-    use std::collections::HashMap;
-    use std::sync::Mutex;
-    use std::sync::OnceLock;
-    use std::sync::atomic::{AtomicBool, Ordering};
-    use std::time::Duration;
+    #[tokio::test]
+    async fn open_project_main_is_idempotent() {
+        let (engine, events) = Engine::start(Arc::new(IdentityServices));
+        let mut events = events.subscribe();
+        engine
+            .apply_client_action(
+                "req-add".to_owned(),
+                luban_api::ClientAction::AddProject {
+                    path: "/tmp/open-main".to_owned(),
+                },
+            )
+            .await
+            .expect("add project should succeed");
+        let snapshot = engine.app_snapshot().await.expect("snapshot should work");
+        let project_id = snapshot.projects[0].id.clone();
+
+        let mut opened = Vec::new();
+        for request_id in ["req-open-1", "req-open-2"] {
+            engine
+                .apply_client_action(
+                    request_id.to_owned(),
+                    luban_api::ClientAction::OpenProjectMain {
+                        project_id: project_id.clone(),
+                    },
+                )
+                .await
+                .expect("open project main should succeed");
+            let ids = loop {
+                let msg = tokio::time::timeout(Duration::from_secs(5), events.recv())
+                    .await
+                    .expect("timed out waiting for project main")
+                    .expect("events channel closed");
+                if let WsServerMessage::Event { event, .. } = msg
+                    && let luban_api::ServerEvent::ProjectMainOpened {
+                        request_id: reply_id,
+                        workspace_id,
+                        thread_id,
+                        ..
+                    } = *event
+                    && reply_id == request_id
+                {
+                    break (workspace_id, thread_id);
+                }
+            };
+            opened.push(ids);
+        }
+        assert_eq!(opened[0], opened[1]);
 
-    type SavedQueueState = (
-        bool,
-        Option<u64>,
-        Option<u64>,
-        Vec<luban_domain::QueuedPrompt>,
-    );
+        let snapshot = engine.app_snapshot().await.expect("snapshot should work");
+        let project = &snapshot.projects[0];
+        assert_eq!(project.workspaces.len(), 1);
+        assert_eq!(project.workspaces[0].id, opened[0].0);
+    }
 
-    struct TestServices;
+    struct ArchiveOkServices {
+        calls: Arc<std::sync::Mutex<Vec<(PathBuf, PathBuf)>>>,
+        cancel_flag: Option<Arc<AtomicBool>>,
+    }
 
-    impl ProjectWorkspaceService for TestServices {
+    impl ProjectWorkspaceService for ArchiveOkServices {
         fn load_app_state(&self) -> Result<PersistedAppState, String> {
-            Err("unimplemented".to_owned())
+            Ok(PersistedAppState {
+                projects: Vec::new(),
+                sidebar_width: None,
+                terminal_pane_width: None,
+                global_zoom_percent: None,
+                appearance_theme: None,
+                appearance_ui_font: None,
+                appearance_chat_font: None,
+                appearance_code_font: None,
+                appearance_terminal_font: None,
+                agent_default_model_id: None,
+                agent_runner_default_models: HashMap::new(),
+                agent_runner_prompt_prefixes: HashMap::new(),
+                agent_runner_prompt_suffixes: HashMap::new(),
+                model_thinking_effort_caps: HashMap::new(),
+                agent_default_thinking_effort: None,
+                agent_default_runner: None,
+                agent_amp_mode: None,
+                agent_codex_enabled: Some(true),
+                agent_amp_enabled: Some(true),
+                agent_claude_enabled: Some(true),
+                agent_droid_enabled: Some(true),
+                last_open_workspace_id: None,
+                open_button_selection: None,
+                sidebar_project_order: Vec::new(),
+                project_groups: Vec::new(),
+                workspace_active_thread_id: HashMap::new(),
+                workspace_open_tabs: HashMap::new(),
+                workspace_archived_tabs: HashMap::new(),
+                workspace_next_thread_id: HashMap::new(),
+                workspace_chat_scroll_y10: HashMap::new(),
+                workspace_chat_scroll_anchor: HashMap::new(),
+                workspace_unread_completions: HashMap::new(),
+                workspace_thread_run_config_overrides: HashMap::new(),
+                starred_tasks: HashMap::new(),
+                task_prompt_templates: HashMap::new(),
+                telegram_enabled: None,
+                telegram_bot_token: None,
+                telegram_bot_username: None,
+                telegram_paired_chat_ids: Vec::new(),
+                telegram_topic_bindings: None,
+                webhook_url: None,
+                webhook_secret: None,
+                agent_merge_reasoning_entries: None,
+                agent_pause_queue_on_patch_failure: None,
+                agent_item_dedup_window: None,
+                agent_auto_retry_on_failure: None,
+                agent_command_output_max_bytes: None,
+                auto_archive_after_days: None,
+            })
         }
 
         fn save_app_state(&self, _snapshot: PersistedAppState) -> Result<(), String> {
-            Err("unimplemented".to_owned())
+            Ok(())
         }
 
         fn create_workspace(
@@ -6283,6 +14396,8 @@ mod tests {
             _project_path: PathBuf,
             _project_slug: String,
             _branch_name_hint: Option<String>,
+            _worktree_root: Option<PathBuf>,
+            _branch_name_template: Option<luban_domain::BranchNameTemplate>,
         ) -> Result<luban_domain::CreatedWorkspace, String> {
             Err("unimplemented".to_owned())
         }
@@ -6293,17 +14408,27 @@ mod tests {
 
         fn archive_workspace(
             &self,
-            _project_path: PathBuf,
-            _worktree_path: PathBuf,
+            project_path: PathBuf,
+            worktree_path: PathBuf,
             _branch_name: String,
         ) -> Result<(), String> {
-            Err("unimplemented".to_owned())
+            if let Some(cancel_flag) = &self.cancel_flag
+                && !cancel_flag.load(Ordering::SeqCst)
+            {
+                return Err("archive workspace called before agent cancel".to_owned());
+            }
+            self.calls
+                .lock()
+                .expect("mutex poisoned")
+                .push((project_path, worktree_path));
+            Ok(())
         }
 
         fn rename_workspace_branch(
             &self,
             _worktree_path: PathBuf,
             _requested_branch_name: String,
+            _branch_name_template: Option<luban_domain::BranchNameTemplate>,
         ) -> Result<String, String> {
             Err("unimplemented".to_owned())
         }
@@ -6388,63 +14513,339 @@ mod tests {
             _project_slug: String,
             _workspace_name: String,
         ) -> Result<Vec<ContextItem>, String> {
+            Ok(Vec::new())
+        }
+
+        fn delete_context_item(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _context_id: u64,
+        ) -> Result<(), String> {
+            Ok(())
+        }
+
+        fn run_agent_turn_streamed(
+            &self,
+            _request: luban_domain::RunAgentTurnRequest,
+            _cancel: Arc<AtomicBool>,
+            _on_event: Arc<dyn Fn(luban_domain::AgentThreadEvent) + Send + Sync>,
+        ) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn gh_is_authorized(&self) -> Result<bool, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn gh_pull_request_info(
+            &self,
+            _worktree_path: PathBuf,
+            _target: luban_domain::PullRequestTarget,
+        ) -> Result<Option<PullRequestInfo>, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn gh_open_pull_request(&self, _worktree_path: PathBuf) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn gh_open_pull_request_failed_action(
+            &self,
+            _worktree_path: PathBuf,
+        ) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn project_identity(
+            &self,
+            _path: PathBuf,
+        ) -> Result<luban_domain::ProjectIdentity, String> {
             Err("unimplemented".to_owned())
         }
+    }
+
+    #[tokio::test]
+    async fn archive_workspace_runs_effect_and_marks_archived() {
+        let calls = Arc::new(std::sync::Mutex::new(Vec::<(PathBuf, PathBuf)>::new()));
+        let services: Arc<dyn ProjectWorkspaceService> = Arc::new(ArchiveOkServices {
+            calls: calls.clone(),
+            cancel_flag: None,
+        });
+
+        let mut state = AppState::new();
+        let project_path = PathBuf::from("/tmp/luban-server-archive-test");
+        let _ = state.apply(Action::AddProject {
+            path: project_path.clone(),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+
+        let worktree_path = PathBuf::from("/tmp/luban-server-archive-test-wt");
+        let _ = state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "wt".to_owned(),
+            branch_name: "feature".to_owned(),
+            worktree_path: worktree_path.clone(),
+        });
+
+        let workspace_id = state
+            .projects
+            .iter()
+            .flat_map(|p| p.workspaces.iter())
+            .find(|w| w.worktree_path == worktree_path)
+            .expect("workspace should exist")
+            .id;
+
+        let (events, _) = broadcast::channel::<WsServerMessage>(16);
+        let (tx, mut rx) = mpsc::channel::<EngineCommand>(16);
+        let mut engine = Engine {
+            state,
+            rev: 1,
+            services,
+            events,
+            tx,
+            branch_watch: BranchWatchHandle::disabled(),
+            cancel_flags: HashMap::new(),
+            pull_requests: HashMap::new(),
+            pull_requests_in_flight: HashSet::new(),
+            workspace_threads_cache: HashMap::new(),
+            auto_archive_workspaces: HashSet::new(),
+            pending_notes_flushes: HashMap::new(),
+            pending_draft_flushes: HashMap::new(),
+            conversation_revs: HashMap::new(),
+            last_git_fetch_at: HashMap::new(),
+            worktree_dirty: HashMap::new(),
+            worktree_dirty_in_flight: HashSet::new(),
+            worktree_dirty_rerun: HashSet::new(),
+            last_commits: HashMap::new(),
+            agent_turn_slots: HashMap::new(),
+            deferred_agent_turns: VecDeque::new(),
+            request_cancellations: RequestCancellations::default(),
+            attachment_thumbnails: AttachmentThumbnails::default(),
+            telegram_pairings: Vec::new(),
+            config: EngineConfig::default(),
+        };
+        let (thread_1, thread_2) = (
+            WorkspaceThreadId::from_u64(1),
+            WorkspaceThreadId::from_u64(2),
+        );
+        engine.conversation_revs.insert((workspace_id, thread_1), 1);
+        engine.conversation_revs.insert((workspace_id, thread_2), 1);
+        engine
+            .workspace_threads_cache
+            .insert(workspace_id, Vec::new());
+
+        engine
+            .process_action_queue(Action::WorkspaceThreadsPurged {
+                workspace_id,
+                thread_ids: vec![thread_2],
+            })
+            .await;
+        assert!(
+            engine
+                .conversation_revs
+                .contains_key(&(workspace_id, thread_1))
+        );
+        assert!(
+            !engine
+                .conversation_revs
+                .contains_key(&(workspace_id, thread_2))
+        );
+
+        engine
+            .process_action_queue(Action::ArchiveWorkspace { workspace_id })
+            .await;
+        let cmd = tokio::time::timeout(std::time::Duration::from_secs(3), rx.recv())
+            .await
+            .expect("timed out waiting for archive completion")
+            .expect("engine command channel closed");
+        engine.handle(cmd).await;
+
+        let workspace = engine
+            .state
+            .workspace(workspace_id)
+            .expect("workspace should still exist after archive");
+        assert_eq!(workspace.status, luban_domain::WorkspaceStatus::Archived);
+        assert_eq!(engine.state.main_pane, luban_domain::MainPane::None);
+        assert_eq!(engine.state.right_pane, luban_domain::RightPane::None);
+        assert!(
+            engine
+                .conversation_revs
+                .keys()
+                .all(|(wid, _)| *wid != workspace_id),
+            "archived workspaces drop their conversation revs"
+        );
+        assert!(!engine.workspace_threads_cache.contains_key(&workspace_id));
+
+        let calls = calls.lock().expect("mutex poisoned");
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].0, project_path);
+        assert_eq!(calls[0].1, worktree_path);
+    }
+
+    #[tokio::test]
+    async fn archive_workspace_cancels_agent_turns_before_archiving() {
+        let calls = Arc::new(std::sync::Mutex::new(Vec::<(PathBuf, PathBuf)>::new()));
+        let cancel_flag = Arc::new(AtomicBool::new(false));
+        let services: Arc<dyn ProjectWorkspaceService> = Arc::new(ArchiveOkServices {
+            calls: calls.clone(),
+            cancel_flag: Some(cancel_flag.clone()),
+        });
+
+        let mut state = AppState::new();
+        let project_path = PathBuf::from("/tmp/luban-server-archive-cancel-test");
+        let _ = state.apply(Action::AddProject {
+            path: project_path.clone(),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+
+        let worktree_path = PathBuf::from("/tmp/luban-server-archive-cancel-test-wt");
+        let _ = state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "wt".to_owned(),
+            branch_name: "feature".to_owned(),
+            worktree_path: worktree_path.clone(),
+        });
 
-        fn delete_context_item(
-            &self,
-            _project_slug: String,
-            _workspace_name: String,
-            _context_id: u64,
-        ) -> Result<(), String> {
-            Err("unimplemented".to_owned())
-        }
+        let workspace_id = state
+            .projects
+            .iter()
+            .flat_map(|p| p.workspaces.iter())
+            .find(|w| w.worktree_path == worktree_path)
+            .expect("workspace should exist")
+            .id;
 
-        fn run_agent_turn_streamed(
-            &self,
-            _request: luban_domain::RunAgentTurnRequest,
-            _cancel: Arc<AtomicBool>,
-            _on_event: Arc<dyn Fn(luban_domain::AgentThreadEvent) + Send + Sync>,
-        ) -> Result<(), String> {
-            Err("unimplemented".to_owned())
-        }
+        state.apply(Action::CreateWorkspaceThread { workspace_id });
+        let thread_id = state
+            .active_thread_id(workspace_id)
+            .expect("active thread should exist");
 
-        fn gh_is_authorized(&self) -> Result<bool, String> {
-            Err("unimplemented".to_owned())
+        let run_id = 7u64;
+        {
+            let conversation = state
+                .conversations
+                .get_mut(&(workspace_id, thread_id))
+                .expect("conversation should exist");
+            conversation.run_status = OperationStatus::Running;
+            conversation.active_run_id = Some(run_id);
         }
 
-        fn gh_pull_request_info(
-            &self,
-            _worktree_path: PathBuf,
-        ) -> Result<Option<PullRequestInfo>, String> {
-            Err("unimplemented".to_owned())
-        }
+        let (events, _) = broadcast::channel::<WsServerMessage>(16);
+        let (tx, mut rx) = mpsc::channel::<EngineCommand>(16);
+        let mut engine = Engine {
+            state,
+            rev: 1,
+            services,
+            events,
+            tx,
+            branch_watch: BranchWatchHandle::disabled(),
+            cancel_flags: HashMap::from([(
+                (workspace_id, thread_id),
+                CancelFlagEntry {
+                    run_id,
+                    flag: cancel_flag.clone(),
+                },
+            )]),
+            pull_requests: HashMap::new(),
+            pull_requests_in_flight: HashSet::new(),
+            workspace_threads_cache: HashMap::new(),
+            auto_archive_workspaces: HashSet::new(),
+            pending_notes_flushes: HashMap::new(),
+            pending_draft_flushes: HashMap::new(),
+            conversation_revs: HashMap::new(),
+            last_git_fetch_at: HashMap::new(),
+            worktree_dirty: HashMap::new(),
+            worktree_dirty_in_flight: HashSet::new(),
+            worktree_dirty_rerun: HashSet::new(),
+            last_commits: HashMap::new(),
+            agent_turn_slots: HashMap::new(),
+            deferred_agent_turns: VecDeque::new(),
+            request_cancellations: RequestCancellations::default(),
+            attachment_thumbnails: AttachmentThumbnails::default(),
+            telegram_pairings: Vec::new(),
+            config: EngineConfig::default(),
+        };
 
-        fn gh_open_pull_request(&self, _worktree_path: PathBuf) -> Result<(), String> {
-            Err("unimplemented".to_owned())
-        }
+        engine
+            .process_action_queue(Action::ArchiveWorkspace { workspace_id })
+            .await;
+        let cmd = tokio::time::timeout(std::time::Duration::from_secs(3), rx.recv())
+            .await
+            .expect("timed out waiting for archive completion")
+            .expect("engine command channel closed");
+        engine.handle(cmd).await;
 
-        fn gh_open_pull_request_failed_action(
-            &self,
-            _worktree_path: PathBuf,
-        ) -> Result<(), String> {
-            Err("unimplemented".to_owned())
-        }
+        assert!(cancel_flag.load(Ordering::SeqCst));
+
+        let calls = calls.lock().expect("mutex poisoned");
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].0, project_path);
+        assert_eq!(calls[0].1, worktree_path);
     }
 
-    #[derive(Default)]
-    struct ReconcileRecordingServices {
-        appended_entries: Mutex<Vec<ConversationEntry>>,
-        saved_queue_state: Mutex<Vec<SavedQueueState>>,
+    struct OpenInIdeServices {
+        opened: Arc<std::sync::Mutex<Vec<PathBuf>>>,
+        opened_with: Arc<std::sync::Mutex<Vec<(PathBuf, OpenTarget)>>>,
     }
 
-    impl ProjectWorkspaceService for ReconcileRecordingServices {
+    impl ProjectWorkspaceService for OpenInIdeServices {
         fn load_app_state(&self) -> Result<PersistedAppState, String> {
-            Err("unimplemented".to_owned())
+            Ok(PersistedAppState {
+                projects: Vec::new(),
+                sidebar_width: None,
+                terminal_pane_width: None,
+                global_zoom_percent: None,
+                appearance_theme: None,
+                appearance_ui_font: None,
+                appearance_chat_font: None,
+                appearance_code_font: None,
+                appearance_terminal_font: None,
+                agent_default_model_id: None,
+                agent_runner_default_models: HashMap::new(),
+                agent_runner_prompt_prefixes: HashMap::new(),
+                agent_runner_prompt_suffixes: HashMap::new(),
+                model_thinking_effort_caps: HashMap::new(),
+                agent_default_thinking_effort: None,
+                agent_default_runner: None,
+                agent_amp_mode: None,
+                agent_codex_enabled: Some(true),
+                agent_amp_enabled: Some(true),
+                agent_claude_enabled: Some(true),
+                agent_droid_enabled: Some(true),
+                last_open_workspace_id: None,
+                open_button_selection: None,
+                sidebar_project_order: Vec::new(),
+                project_groups: Vec::new(),
+                workspace_active_thread_id: HashMap::new(),
+                workspace_open_tabs: HashMap::new(),
+                workspace_archived_tabs: HashMap::new(),
+                workspace_next_thread_id: HashMap::new(),
+                workspace_chat_scroll_y10: HashMap::new(),
+                workspace_chat_scroll_anchor: HashMap::new(),
+                workspace_unread_completions: HashMap::new(),
+                workspace_thread_run_config_overrides: HashMap::new(),
+                starred_tasks: HashMap::new(),
+                task_prompt_templates: HashMap::new(),
+                telegram_enabled: None,
+                telegram_bot_token: None,
+                telegram_bot_username: None,
+                telegram_paired_chat_ids: Vec::new(),
+                telegram_topic_bindings: None,
+                webhook_url: None,
+                webhook_secret: None,
+                agent_merge_reasoning_entries: None,
+                agent_pause_queue_on_patch_failure: None,
+                agent_item_dedup_window: None,
+                agent_auto_retry_on_failure: None,
+                agent_command_output_max_bytes: None,
+                auto_archive_after_days: None,
+            })
         }
 
         fn save_app_state(&self, _snapshot: PersistedAppState) -> Result<(), String> {
-            Err("unimplemented".to_owned())
+            Ok(())
         }
 
         fn create_workspace(
@@ -6452,12 +14853,30 @@ mod tests {
             _project_path: PathBuf,
             _project_slug: String,
             _branch_name_hint: Option<String>,
+            _worktree_root: Option<PathBuf>,
+            _branch_name_template: Option<luban_domain::BranchNameTemplate>,
         ) -> Result<luban_domain::CreatedWorkspace, String> {
             Err("unimplemented".to_owned())
         }
 
-        fn open_workspace_in_ide(&self, _worktree_path: PathBuf) -> Result<(), String> {
-            Err("unimplemented".to_owned())
+        fn open_workspace_in_ide(&self, worktree_path: PathBuf) -> Result<(), String> {
+            self.opened
+                .lock()
+                .expect("mutex poisoned")
+                .push(worktree_path);
+            Ok(())
+        }
+
+        fn open_workspace_with(
+            &self,
+            worktree_path: PathBuf,
+            target: OpenTarget,
+        ) -> Result<(), String> {
+            self.opened_with
+                .lock()
+                .expect("mutex poisoned")
+                .push((worktree_path, target));
+            Ok(())
         }
 
         fn archive_workspace(
@@ -6473,6 +14892,7 @@ mod tests {
             &self,
             _worktree_path: PathBuf,
             _requested_branch_name: String,
+            _branch_name_template: Option<luban_domain::BranchNameTemplate>,
         ) -> Result<String, String> {
             Err("unimplemented".to_owned())
         }
@@ -6491,106 +14911,27 @@ mod tests {
             _project_slug: String,
             _workspace_name: String,
         ) -> Result<Vec<ConversationThreadMeta>, String> {
-            Ok(vec![ConversationThreadMeta {
-                thread_id: WorkspaceThreadId::from_u64(1),
-                remote_thread_id: None,
-                title: "t1".to_owned(),
-                created_at_unix_seconds: 1,
-                updated_at_unix_seconds: 2,
-                task_status: luban_domain::TaskStatus::Todo,
-                last_message_seq: 1,
-                task_status_last_analyzed_message_seq: 0,
-                turn_status: luban_domain::TurnStatus::Running,
-                last_turn_result: None,
-            }])
-        }
-
-        fn load_conversation(
-            &self,
-            _project_slug: String,
-            _workspace_name: String,
-            _thread_id: u64,
-        ) -> Result<DomainConversationSnapshot, String> {
-            Ok(DomainConversationSnapshot {
-                title: Some("t1".to_owned()),
-                thread_id: None,
-                task_status: luban_domain::TaskStatus::Todo,
-                runner: None,
-                agent_model_id: None,
-                thinking_effort: None,
-                amp_mode: None,
-                entries: vec![ConversationEntry::UserEvent {
-                    entry_id: "e_1".to_owned(),
-                    created_at_unix_ms: 1,
-                    event: luban_domain::UserEvent::Message {
-                        text: "hi".to_owned(),
-                        attachments: Vec::new(),
-                    },
-                }],
-                entries_total: 1,
-                entries_start: 0,
-                pending_prompts: vec![luban_domain::QueuedPrompt {
-                    id: 1,
-                    text: "queued".to_owned(),
-                    attachments: Vec::new(),
-                    run_config: luban_domain::AgentRunConfig {
-                        runner: luban_domain::AgentRunnerKind::Codex,
-                        model_id: "gpt-5.2".to_owned(),
-                        thinking_effort: ThinkingEffort::Medium,
-                        amp_mode: None,
-                    },
-                }],
-                queue_paused: false,
-                run_started_at_unix_ms: Some(10),
-                run_finished_at_unix_ms: None,
-            })
-        }
-
-        fn load_conversation_page(
-            &self,
-            _project_slug: String,
-            _workspace_name: String,
-            _thread_id: u64,
-            _before: Option<u64>,
-            _limit: u64,
-        ) -> Result<DomainConversationSnapshot, String> {
             Err("unimplemented".to_owned())
         }
 
-        fn append_conversation_entries(
-            &self,
-            _project_slug: String,
-            _workspace_name: String,
-            _thread_id: u64,
-            entries: Vec<ConversationEntry>,
-        ) -> Result<(), String> {
-            self.appended_entries
-                .lock()
-                .map_err(|_| "poisoned mutex".to_owned())?
-                .extend(entries);
-            Ok(())
-        }
-
-        fn save_conversation_queue_state(
-            &self,
-            _project_slug: String,
-            _workspace_name: String,
-            _thread_id: u64,
-            queue_paused: bool,
-            run_started_at_unix_ms: Option<u64>,
-            run_finished_at_unix_ms: Option<u64>,
-            pending_prompts: Vec<luban_domain::QueuedPrompt>,
-        ) -> Result<(), String> {
-            self.saved_queue_state
-                .lock()
-                .map_err(|_| "poisoned mutex".to_owned())?
-                .push((
-                    queue_paused,
-                    run_started_at_unix_ms,
-                    run_finished_at_unix_ms,
-                    pending_prompts,
-                ));
-            Ok(())
+        fn load_conversation(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _thread_id: u64,
+        ) -> Result<DomainConversationSnapshot, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn load_conversation_page(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _thread_id: u64,
+            _before: Option<u64>,
+            _limit: u64,
+        ) -> Result<DomainConversationSnapshot, String> {
+            Err("unimplemented".to_owned())
         }
 
         fn store_context_image(
@@ -6636,7 +14977,7 @@ mod tests {
             _project_slug: String,
             _workspace_name: String,
         ) -> Result<Vec<ContextItem>, String> {
-            Err("unimplemented".to_owned())
+            Ok(Vec::new())
         }
 
         fn delete_context_item(
@@ -6645,7 +14986,7 @@ mod tests {
             _workspace_name: String,
             _context_id: u64,
         ) -> Result<(), String> {
-            Err("unimplemented".to_owned())
+            Ok(())
         }
 
         fn run_agent_turn_streamed(
@@ -6664,6 +15005,7 @@ mod tests {
         fn gh_pull_request_info(
             &self,
             _worktree_path: PathBuf,
+            _target: luban_domain::PullRequestTarget,
         ) -> Result<Option<PullRequestInfo>, String> {
             Err("unimplemented".to_owned())
         }
@@ -6678,11 +15020,150 @@ mod tests {
         ) -> Result<(), String> {
             Err("unimplemented".to_owned())
         }
+
+        fn project_identity(
+            &self,
+            _path: PathBuf,
+        ) -> Result<luban_domain::ProjectIdentity, String> {
+            Err("unimplemented".to_owned())
+        }
     }
 
-    struct IdentityServices;
+    #[tokio::test]
+    async fn open_workspace_in_ide_runs_effect() {
+        let opened = Arc::new(std::sync::Mutex::new(Vec::<PathBuf>::new()));
+        let opened_with = Arc::new(std::sync::Mutex::new(Vec::<(PathBuf, OpenTarget)>::new()));
+        let services: Arc<dyn ProjectWorkspaceService> = Arc::new(OpenInIdeServices {
+            opened: opened.clone(),
+            opened_with: opened_with.clone(),
+        });
 
-    impl ProjectWorkspaceService for IdentityServices {
+        let mut state = AppState::new();
+        let _ = state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/luban-server-open-ide-test"),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+        let _ = state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "main".to_owned(),
+            branch_name: "main".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban-server-open-ide-test"),
+        });
+        let workspace_id = state.projects[0].workspaces[0].id;
+        let worktree_path = state.projects[0].workspaces[0].worktree_path.clone();
+
+        let (events, _) = broadcast::channel::<WsServerMessage>(16);
+        let (tx, _rx) = mpsc::channel::<EngineCommand>(16);
+        let mut engine = Engine {
+            state,
+            rev: 1,
+            services,
+            events,
+            tx,
+            branch_watch: BranchWatchHandle::disabled(),
+            cancel_flags: HashMap::new(),
+            pull_requests: HashMap::new(),
+            pull_requests_in_flight: HashSet::new(),
+            workspace_threads_cache: HashMap::new(),
+            auto_archive_workspaces: HashSet::new(),
+            pending_notes_flushes: HashMap::new(),
+            pending_draft_flushes: HashMap::new(),
+            conversation_revs: HashMap::new(),
+            last_git_fetch_at: HashMap::new(),
+            worktree_dirty: HashMap::new(),
+            worktree_dirty_in_flight: HashSet::new(),
+            worktree_dirty_rerun: HashSet::new(),
+            last_commits: HashMap::new(),
+            agent_turn_slots: HashMap::new(),
+            deferred_agent_turns: VecDeque::new(),
+            request_cancellations: RequestCancellations::default(),
+            attachment_thumbnails: AttachmentThumbnails::default(),
+            telegram_pairings: Vec::new(),
+            config: EngineConfig::default(),
+        };
+
+        engine
+            .process_action_queue(Action::OpenWorkspaceInIde { workspace_id })
+            .await;
+
+        let opened = opened.lock().expect("mutex poisoned");
+        assert_eq!(opened.as_slice(), &[worktree_path]);
+    }
+
+    #[tokio::test]
+    async fn open_workspace_with_runs_effect() {
+        let opened = Arc::new(std::sync::Mutex::new(Vec::<PathBuf>::new()));
+        let opened_with = Arc::new(std::sync::Mutex::new(Vec::<(PathBuf, OpenTarget)>::new()));
+        let services: Arc<dyn ProjectWorkspaceService> = Arc::new(OpenInIdeServices {
+            opened: opened.clone(),
+            opened_with: opened_with.clone(),
+        });
+
+        let mut state = AppState::new();
+        let _ = state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/luban-server-open-with-test"),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+        let _ = state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "main".to_owned(),
+            branch_name: "main".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban-server-open-with-test"),
+        });
+        let workspace_id = state.projects[0].workspaces[0].id;
+        let worktree_path = state.projects[0].workspaces[0].worktree_path.clone();
+
+        let (events, _) = broadcast::channel::<WsServerMessage>(16);
+        let (tx, _rx) = mpsc::channel::<EngineCommand>(16);
+        let mut engine = Engine {
+            state,
+            rev: 1,
+            services,
+            events,
+            tx,
+            branch_watch: BranchWatchHandle::disabled(),
+            cancel_flags: HashMap::new(),
+            pull_requests: HashMap::new(),
+            pull_requests_in_flight: HashSet::new(),
+            workspace_threads_cache: HashMap::new(),
+            auto_archive_workspaces: HashSet::new(),
+            pending_notes_flushes: HashMap::new(),
+            pending_draft_flushes: HashMap::new(),
+            conversation_revs: HashMap::new(),
+            last_git_fetch_at: HashMap::new(),
+            worktree_dirty: HashMap::new(),
+            worktree_dirty_in_flight: HashSet::new(),
+            worktree_dirty_rerun: HashSet::new(),
+            last_commits: HashMap::new(),
+            agent_turn_slots: HashMap::new(),
+            deferred_agent_turns: VecDeque::new(),
+            request_cancellations: RequestCancellations::default(),
+            attachment_thumbnails: AttachmentThumbnails::default(),
+            telegram_pairings: Vec::new(),
+            config: EngineConfig::default(),
+        };
+
+        engine
+            .process_action_queue(Action::OpenWorkspaceWith {
+                workspace_id,
+                target: OpenTarget::Vscode,
+            })
+            .await;
+
+        let opened_with = opened_with.lock().expect("mutex poisoned");
+        assert_eq!(
+            opened_with.as_slice(),
+            &[(worktree_path, OpenTarget::Vscode)]
+        );
+    }
+
+    struct CaptureRunAgentTurnServices {
+        sender: std::sync::mpsc::Sender<luban_domain::RunAgentTurnRequest>,
+    }
+
+    impl ProjectWorkspaceService for CaptureRunAgentTurnServices {
         fn load_app_state(&self) -> Result<PersistedAppState, String> {
             Ok(PersistedAppState {
                 projects: Vec::new(),
@@ -6696,6 +15177,9 @@ mod tests {
                 appearance_terminal_font: None,
                 agent_default_model_id: None,
                 agent_runner_default_models: HashMap::new(),
+                agent_runner_prompt_prefixes: HashMap::new(),
+                agent_runner_prompt_suffixes: HashMap::new(),
+                model_thinking_effort_caps: HashMap::new(),
                 agent_default_thinking_effort: None,
                 agent_default_runner: None,
                 agent_amp_mode: None,
@@ -6706,6 +15190,7 @@ mod tests {
                 last_open_workspace_id: None,
                 open_button_selection: None,
                 sidebar_project_order: Vec::new(),
+                project_groups: Vec::new(),
                 workspace_active_thread_id: HashMap::new(),
                 workspace_open_tabs: HashMap::new(),
                 workspace_archived_tabs: HashMap::new(),
@@ -6719,8 +15204,16 @@ mod tests {
                 telegram_enabled: None,
                 telegram_bot_token: None,
                 telegram_bot_username: None,
-                telegram_paired_chat_id: None,
+                telegram_paired_chat_ids: Vec::new(),
                 telegram_topic_bindings: None,
+                webhook_url: None,
+                webhook_secret: None,
+                agent_merge_reasoning_entries: None,
+                agent_pause_queue_on_patch_failure: None,
+                agent_item_dedup_window: None,
+                agent_auto_retry_on_failure: None,
+                agent_command_output_max_bytes: None,
+                auto_archive_after_days: None,
             })
         }
 
@@ -6733,6 +15226,8 @@ mod tests {
             _project_path: PathBuf,
             _project_slug: String,
             _branch_name_hint: Option<String>,
+            _worktree_root: Option<PathBuf>,
+            _branch_name_template: Option<luban_domain::BranchNameTemplate>,
         ) -> Result<luban_domain::CreatedWorkspace, String> {
             Err("unimplemented".to_owned())
         }
@@ -6754,6 +15249,7 @@ mod tests {
             &self,
             _worktree_path: PathBuf,
             _requested_branch_name: String,
+            _branch_name_template: Option<luban_domain::BranchNameTemplate>,
         ) -> Result<String, String> {
             Err("unimplemented".to_owned())
         }
@@ -6814,528 +15310,489 @@ mod tests {
             Err("unimplemented".to_owned())
         }
 
-        fn store_context_file(
-            &self,
-            _project_slug: String,
-            _workspace_name: String,
-            _source_path: PathBuf,
-        ) -> Result<AttachmentRef, String> {
-            Err("unimplemented".to_owned())
+        fn store_context_file(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _source_path: PathBuf,
+        ) -> Result<AttachmentRef, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn record_context_item(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _attachment: AttachmentRef,
+            _created_at_unix_ms: u64,
+        ) -> Result<u64, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn list_context_items(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+        ) -> Result<Vec<ContextItem>, String> {
+            Ok(Vec::new())
+        }
+
+        fn delete_context_item(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _context_id: u64,
+        ) -> Result<(), String> {
+            Ok(())
+        }
+
+        fn run_agent_turn_streamed(
+            &self,
+            request: luban_domain::RunAgentTurnRequest,
+            _cancel: Arc<AtomicBool>,
+            _on_event: Arc<dyn Fn(luban_domain::AgentThreadEvent) + Send + Sync>,
+        ) -> Result<(), String> {
+            let _ = self.sender.send(request);
+            Ok(())
+        }
+
+        fn gh_is_authorized(&self) -> Result<bool, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn gh_pull_request_info(
+            &self,
+            _worktree_path: PathBuf,
+            _target: luban_domain::PullRequestTarget,
+        ) -> Result<Option<PullRequestInfo>, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn gh_open_pull_request(&self, _worktree_path: PathBuf) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn gh_open_pull_request_failed_action(
+            &self,
+            _worktree_path: PathBuf,
+        ) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn project_identity(
+            &self,
+            _path: PathBuf,
+        ) -> Result<luban_domain::ProjectIdentity, String> {
+            Err("unimplemented".to_owned())
+        }
+    }
+
+    struct SlowRenameServices {
+        delay: Duration,
+    }
+
+    impl ProjectWorkspaceService for SlowRenameServices {
+        fn load_app_state(&self) -> Result<PersistedAppState, String> {
+            Ok(PersistedAppState {
+                projects: Vec::new(),
+                sidebar_width: None,
+                terminal_pane_width: None,
+                global_zoom_percent: None,
+                appearance_theme: None,
+                appearance_ui_font: None,
+                appearance_chat_font: None,
+                appearance_code_font: None,
+                appearance_terminal_font: None,
+                agent_default_model_id: None,
+                agent_runner_default_models: HashMap::new(),
+                agent_runner_prompt_prefixes: HashMap::new(),
+                agent_runner_prompt_suffixes: HashMap::new(),
+                model_thinking_effort_caps: HashMap::new(),
+                agent_default_thinking_effort: None,
+                agent_default_runner: None,
+                agent_amp_mode: None,
+                agent_codex_enabled: Some(true),
+                agent_amp_enabled: Some(true),
+                agent_claude_enabled: Some(true),
+                agent_droid_enabled: Some(true),
+                last_open_workspace_id: None,
+                open_button_selection: None,
+                sidebar_project_order: Vec::new(),
+                project_groups: Vec::new(),
+                workspace_active_thread_id: HashMap::new(),
+                workspace_open_tabs: HashMap::new(),
+                workspace_archived_tabs: HashMap::new(),
+                workspace_next_thread_id: HashMap::new(),
+                workspace_chat_scroll_y10: HashMap::new(),
+                workspace_chat_scroll_anchor: HashMap::new(),
+                workspace_unread_completions: HashMap::new(),
+                workspace_thread_run_config_overrides: HashMap::new(),
+                starred_tasks: HashMap::new(),
+                task_prompt_templates: HashMap::new(),
+                telegram_enabled: None,
+                telegram_bot_token: None,
+                telegram_bot_username: None,
+                telegram_paired_chat_ids: Vec::new(),
+                telegram_topic_bindings: None,
+                webhook_url: None,
+                webhook_secret: None,
+                agent_merge_reasoning_entries: None,
+                agent_pause_queue_on_patch_failure: None,
+                agent_item_dedup_window: None,
+                agent_auto_retry_on_failure: None,
+                agent_command_output_max_bytes: None,
+                auto_archive_after_days: None,
+            })
+        }
+
+        fn save_app_state(&self, _snapshot: PersistedAppState) -> Result<(), String> {
+            Ok(())
         }
 
-        fn record_context_item(
+        fn create_workspace(
             &self,
+            _project_path: PathBuf,
             _project_slug: String,
-            _workspace_name: String,
-            _attachment: AttachmentRef,
-            _created_at_unix_ms: u64,
-        ) -> Result<u64, String> {
+            _branch_name_hint: Option<String>,
+            _worktree_root: Option<PathBuf>,
+            _branch_name_template: Option<luban_domain::BranchNameTemplate>,
+        ) -> Result<luban_domain::CreatedWorkspace, String> {
             Err("unimplemented".to_owned())
         }
 
-        fn list_context_items(
-            &self,
-            _project_slug: String,
-            _workspace_name: String,
-        ) -> Result<Vec<ContextItem>, String> {
-            Ok(Vec::new())
-        }
-
-        fn delete_context_item(
-            &self,
-            _project_slug: String,
-            _workspace_name: String,
-            _context_id: u64,
-        ) -> Result<(), String> {
-            Ok(())
+        fn open_workspace_in_ide(&self, _worktree_path: PathBuf) -> Result<(), String> {
+            Err("unimplemented".to_owned())
         }
 
-        fn run_agent_turn_streamed(
+        fn archive_workspace(
             &self,
-            _request: luban_domain::RunAgentTurnRequest,
-            _cancel: Arc<AtomicBool>,
-            _on_event: Arc<dyn Fn(luban_domain::AgentThreadEvent) + Send + Sync>,
+            _project_path: PathBuf,
+            _worktree_path: PathBuf,
+            _branch_name: String,
         ) -> Result<(), String> {
             Err("unimplemented".to_owned())
         }
 
-        fn gh_is_authorized(&self) -> Result<bool, String> {
-            Err("unimplemented".to_owned())
-        }
-
-        fn gh_pull_request_info(
+        fn rename_workspace_branch(
             &self,
             _worktree_path: PathBuf,
-        ) -> Result<Option<PullRequestInfo>, String> {
-            Err("unimplemented".to_owned())
-        }
-
-        fn gh_open_pull_request(&self, _worktree_path: PathBuf) -> Result<(), String> {
-            Err("unimplemented".to_owned())
+            requested_branch_name: String,
+            _branch_name_template: Option<luban_domain::BranchNameTemplate>,
+        ) -> Result<String, String> {
+            std::thread::sleep(self.delay);
+            Ok(requested_branch_name)
         }
 
-        fn gh_open_pull_request_failed_action(
+        fn ensure_conversation(
             &self,
-            _worktree_path: PathBuf,
+            _project_slug: String,
+            _workspace_name: String,
+            _thread_id: u64,
         ) -> Result<(), String> {
             Err("unimplemented".to_owned())
         }
 
-        fn project_identity(&self, path: PathBuf) -> Result<luban_domain::ProjectIdentity, String> {
-            Ok(luban_domain::ProjectIdentity {
-                root_path: path,
-                github_repo: Some("github.com/example/repo".to_owned()),
-                is_git: true,
-            })
-        }
-    }
-
-    #[test]
-    fn app_snapshot_includes_pull_request_info() {
-        let mut state = AppState::new();
-        let _ = state.apply(Action::AddProject {
-            path: PathBuf::from("/tmp/luban-server-test"),
-            is_git: true,
-        });
-
-        let project_id = state.projects[0].id;
-        let _ = state.apply(Action::WorkspaceCreated {
-            project_id,
-            workspace_name: "main".to_owned(),
-            branch_name: "main".to_owned(),
-            worktree_path: PathBuf::from("/tmp/luban-server-test"),
-        });
-
-        let workspace_id = state.projects[0].workspaces[0].id;
-
-        let (events, _) = broadcast::channel::<WsServerMessage>(1);
-        let (tx, _rx) = mpsc::channel::<EngineCommand>(1);
-        let mut engine = Engine {
-            state,
-            rev: 1,
-            services: Arc::new(TestServices),
-            events,
-            tx,
-            branch_watch: BranchWatchHandle::disabled(),
-            cancel_flags: HashMap::new(),
-            pull_requests: HashMap::new(),
-            pull_requests_in_flight: HashSet::new(),
-            workspace_threads_cache: HashMap::new(),
-            auto_archive_workspaces: HashSet::new(),
-            telegram_pairing: None,
-        };
-
-        engine.pull_requests.insert(
-            workspace_id,
-            PullRequestCacheEntry {
-                info: Some(PullRequestInfo {
-                    number: 42,
-                    is_draft: false,
-                    state: DomainPullRequestState::Open,
-                    ci_state: Some(DomainPullRequestCiState::Pending),
-                    merge_ready: false,
-                }),
-                next_refresh_at: Instant::now(),
-                consecutive_empty: 0,
-            },
-        );
-
-        let snapshot = engine.app_snapshot();
-        let pr = snapshot.projects[0].workspaces[0].pull_request;
-        assert_eq!(
-            pr,
-            Some(PullRequestSnapshot {
-                number: 42,
-                is_draft: false,
-                state: PullRequestState::Open,
-                ci_state: Some(PullRequestCiState::Pending),
-                merge_ready: false,
-            })
-        );
-    }
-
-    #[test]
-    fn app_snapshot_marks_merged_pull_requests() {
-        let mut state = AppState::new();
-        let _ = state.apply(Action::AddProject {
-            path: PathBuf::from("/tmp/luban-server-test"),
-            is_git: true,
-        });
-
-        let project_id = state.projects[0].id;
-        let _ = state.apply(Action::WorkspaceCreated {
-            project_id,
-            workspace_name: "main".to_owned(),
-            branch_name: "main".to_owned(),
-            worktree_path: PathBuf::from("/tmp/luban-server-test"),
-        });
-
-        let workspace_id = state.projects[0].workspaces[0].id;
-
-        let (events, _) = broadcast::channel::<WsServerMessage>(1);
-        let (tx, _rx) = mpsc::channel::<EngineCommand>(1);
-        let mut engine = Engine {
-            state,
-            rev: 1,
-            services: Arc::new(TestServices),
-            events,
-            tx,
-            branch_watch: BranchWatchHandle::disabled(),
-            cancel_flags: HashMap::new(),
-            pull_requests: HashMap::new(),
-            pull_requests_in_flight: HashSet::new(),
-            workspace_threads_cache: HashMap::new(),
-            auto_archive_workspaces: HashSet::new(),
-            telegram_pairing: None,
-        };
-
-        engine.pull_requests.insert(
-            workspace_id,
-            PullRequestCacheEntry {
-                info: Some(PullRequestInfo {
-                    number: 7,
-                    is_draft: false,
-                    state: DomainPullRequestState::Merged,
-                    ci_state: Some(DomainPullRequestCiState::Success),
-                    merge_ready: false,
-                }),
-                next_refresh_at: Instant::now(),
-                consecutive_empty: 0,
-            },
-        );
-
-        let snapshot = engine.app_snapshot();
-        let pr = snapshot.projects[0].workspaces[0].pull_request;
-        assert_eq!(
-            pr,
-            Some(PullRequestSnapshot {
-                number: 7,
-                is_draft: false,
-                state: PullRequestState::Merged,
-                ci_state: Some(PullRequestCiState::Success),
-                merge_ready: false,
-            })
-        );
-    }
-
-    #[test]
-    fn pull_request_refresh_backoff_increases_on_empty_results() {
-        let now = Instant::now();
-        let workspace_id = WorkspaceId::from_u64(10);
-        let previous = PullRequestCacheEntry {
-            info: None,
-            next_refresh_at: now,
-            consecutive_empty: 1,
-        };
-
-        let (next, empty_count) =
-            pull_request_next_refresh_at(workspace_id, now, Some(&previous), None);
-        assert_eq!(empty_count, 2);
-        let delta = next.duration_since(now);
-        assert!(
-            delta >= PULL_REQUEST_REFRESH_INTERVAL_EMPTY_MEDIUM,
-            "expected at least {:?}, got {:?}",
-            PULL_REQUEST_REFRESH_INTERVAL_EMPTY_MEDIUM,
-            delta
-        );
-    }
-
-    #[test]
-    fn pull_request_refresh_pending_ci_is_frequently_refreshed() {
-        let now = Instant::now();
-        let workspace_id = WorkspaceId::from_u64(10);
-        let info = PullRequestInfo {
-            number: 1,
-            is_draft: false,
-            state: DomainPullRequestState::Open,
-            ci_state: Some(DomainPullRequestCiState::Pending),
-            merge_ready: false,
-        };
-
-        let (next, empty_count) =
-            pull_request_next_refresh_at(workspace_id, now, None, Some(&info));
-        assert_eq!(empty_count, 0);
-        let delta = next.duration_since(now);
-        assert!(
-            delta >= PULL_REQUEST_REFRESH_INTERVAL_OPEN_CI_PENDING,
-            "expected at least {:?}, got {:?}",
-            PULL_REQUEST_REFRESH_INTERVAL_OPEN_CI_PENDING,
-            delta
-        );
-        assert!(
-            delta
-                < PULL_REQUEST_REFRESH_INTERVAL_OPEN_CI_PENDING
-                    + Duration::from_secs(PULL_REQUEST_REFRESH_JITTER_WINDOW_SECS + 1),
-            "expected jitter window <= {:?}, got {:?}",
-            Duration::from_secs(PULL_REQUEST_REFRESH_JITTER_WINDOW_SECS + 1),
-            delta
-        );
-    }
+        fn list_conversation_threads(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+        ) -> Result<Vec<ConversationThreadMeta>, String> {
+            Err("unimplemented".to_owned())
+        }
 
-    #[test]
-    fn conversation_snapshots_are_truncated_to_tail() {
-        let mut state = AppState::new();
-        let _ = state.apply(Action::AddProject {
-            path: PathBuf::from("/tmp/luban-server-test"),
-            is_git: true,
-        });
+        fn load_conversation(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _thread_id: u64,
+        ) -> Result<DomainConversationSnapshot, String> {
+            Err("unimplemented".to_owned())
+        }
 
-        let project_id = state.projects[0].id;
-        let _ = state.apply(Action::WorkspaceCreated {
-            project_id,
-            workspace_name: "main".to_owned(),
-            branch_name: "main".to_owned(),
-            worktree_path: PathBuf::from("/tmp/luban-server-test"),
-        });
+        fn load_conversation_page(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _thread_id: u64,
+            _before: Option<u64>,
+            _limit: u64,
+        ) -> Result<DomainConversationSnapshot, String> {
+            Err("unimplemented".to_owned())
+        }
 
-        let workspace_id = state.projects[0].workspaces[0].id;
-        let thread_id = WorkspaceThreadId::from_u64(1);
+        fn store_context_image(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _image: ContextImage,
+        ) -> Result<AttachmentRef, String> {
+            Err("unimplemented".to_owned())
+        }
 
-        state.apply(Action::SendAgentMessage {
-            workspace_id,
-            thread_id,
-            text: "seed".to_owned(),
-            attachments: Vec::new(),
-            runner: None,
-            amp_mode: None,
-        });
+        fn store_context_text(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _text: String,
+            _extension: String,
+        ) -> Result<AttachmentRef, String> {
+            Err("unimplemented".to_owned())
+        }
 
-        let key = (workspace_id, thread_id);
-        let convo = state
-            .conversations
-            .get_mut(&key)
-            .expect("conversation must exist");
-        for i in 0..7000u32 {
-            convo.entries.push(ConversationEntry::AgentEvent {
-                entry_id: String::new(),
-                created_at_unix_ms: i as u64,
-                runner: None,
-                event: luban_domain::AgentEvent::Item {
-                    item: Box::new(CodexThreadItem::CommandExecution {
-                        id: format!("cmd_{i}"),
-                        command: format!("echo {i}"),
-                        aggregated_output: String::new(),
-                        exit_code: Some(0),
-                        status: CodexCommandExecutionStatus::Completed,
-                    }),
-                },
-            });
+        fn store_context_file(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _source_path: PathBuf,
+        ) -> Result<AttachmentRef, String> {
+            Err("unimplemented".to_owned())
         }
-        convo.entries_start = 0;
-        convo.entries_total = convo.entries.len() as u64;
-        let total = convo.entries.len();
 
-        let (events, _) = broadcast::channel::<WsServerMessage>(1);
-        let (tx, _rx) = mpsc::channel::<EngineCommand>(1);
-        let engine = Engine {
-            state,
-            rev: 1,
-            services: Arc::new(TestServices),
-            events,
-            tx,
-            branch_watch: BranchWatchHandle::disabled(),
-            cancel_flags: HashMap::new(),
-            pull_requests: HashMap::new(),
-            pull_requests_in_flight: HashSet::new(),
-            workspace_threads_cache: HashMap::new(),
-            auto_archive_workspaces: HashSet::new(),
-            telegram_pairing: None,
-        };
+        fn record_context_item(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _attachment: AttachmentRef,
+            _created_at_unix_ms: u64,
+        ) -> Result<u64, String> {
+            Err("unimplemented".to_owned())
+        }
 
-        let api_wid = luban_api::WorkspaceId(workspace_id.as_u64());
-        let api_tid = luban_api::WorkspaceThreadId(thread_id.as_u64());
+        fn list_context_items(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+        ) -> Result<Vec<ContextItem>, String> {
+            Err("unimplemented".to_owned())
+        }
 
-        let snapshot = engine
-            .conversation_snapshot(api_wid, api_tid, None, None)
-            .expect("snapshot must build");
-        assert!(
-            snapshot.entries_truncated,
-            "large conversations must be truncated"
-        );
-        assert_eq!(snapshot.entries_total, total as u64);
-        assert_eq!(
-            snapshot.entries_start + snapshot.entries.len() as u64,
-            snapshot.entries_total
-        );
-        assert!(snapshot.entries.len() <= 2000);
-    }
+        fn delete_context_item(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _context_id: u64,
+        ) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
 
-    #[test]
-    fn default_services_persist_ui_state() {
-        static ENV_LOCK: OnceLock<std::sync::Mutex<()>> = OnceLock::new();
-        let _guard = ENV_LOCK
-            .get_or_init(|| std::sync::Mutex::new(()))
-            .lock()
-            .expect("mutex poisoned");
+        fn run_agent_turn_streamed(
+            &self,
+            _request: luban_domain::RunAgentTurnRequest,
+            _cancel: Arc<AtomicBool>,
+            _on_event: Arc<dyn Fn(luban_domain::AgentThreadEvent) + Send + Sync>,
+        ) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
 
-        struct EnvGuard {
-            prev_root: Option<std::ffi::OsString>,
-            root: PathBuf,
+        fn gh_is_authorized(&self) -> Result<bool, String> {
+            Err("unimplemented".to_owned())
         }
 
-        impl Drop for EnvGuard {
-            fn drop(&mut self) {
-                if let Some(prev) = self.prev_root.take() {
-                    unsafe {
-                        std::env::set_var(luban_domain::paths::LUBAN_ROOT_ENV, prev);
-                    }
-                } else {
-                    unsafe {
-                        std::env::remove_var(luban_domain::paths::LUBAN_ROOT_ENV);
-                    }
-                }
-                let _ = std::fs::remove_dir_all(&self.root);
-            }
+        fn gh_pull_request_info(
+            &self,
+            _worktree_path: PathBuf,
+            _target: luban_domain::PullRequestTarget,
+        ) -> Result<Option<PullRequestInfo>, String> {
+            Err("unimplemented".to_owned())
         }
 
-        let root = std::env::temp_dir().join(format!(
-            "luban-tests-default-services-persist-ui-state-{}-{}",
-            std::process::id(),
-            std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap_or_default()
-                .as_nanos()
-        ));
-        std::fs::create_dir_all(&root).expect("create temp root");
+        fn gh_open_pull_request(&self, _worktree_path: PathBuf) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
 
-        let env_guard = EnvGuard {
-            prev_root: std::env::var_os(luban_domain::paths::LUBAN_ROOT_ENV),
-            root: root.clone(),
-        };
-        unsafe {
-            std::env::set_var(luban_domain::paths::LUBAN_ROOT_ENV, root.as_os_str());
+        fn gh_open_pull_request_failed_action(
+            &self,
+            _worktree_path: PathBuf,
+        ) -> Result<(), String> {
+            Err("unimplemented".to_owned())
         }
 
-        let services = new_default_services().expect("init services");
+        fn project_identity(
+            &self,
+            _path: PathBuf,
+        ) -> Result<luban_domain::ProjectIdentity, String> {
+            Err("unimplemented".to_owned())
+        }
+    }
 
-        let snapshot = PersistedAppState {
-            projects: vec![PersistedProject {
-                id: 1,
-                slug: "p".to_owned(),
-                name: "P".to_owned(),
-                path: PathBuf::from("/tmp/p"),
-                is_git: true,
-                expanded: false,
-                workspaces: vec![PersistedWorkspace {
-                    id: 10,
-                    workspace_name: "main".to_owned(),
-                    branch_name: "main".to_owned(),
-                    worktree_path: PathBuf::from("/tmp/p"),
-                    status: WorkspaceStatus::Active,
-                    last_activity_at_unix_seconds: None,
-                }],
-            }],
-            sidebar_width: None,
-            terminal_pane_width: None,
-            global_zoom_percent: None,
-            appearance_theme: None,
-            appearance_ui_font: None,
-            appearance_chat_font: None,
-            appearance_code_font: None,
-            appearance_terminal_font: None,
-            agent_default_model_id: None,
-            agent_runner_default_models: HashMap::new(),
-            agent_default_thinking_effort: None,
-            agent_default_runner: None,
-            agent_amp_mode: None,
-            agent_codex_enabled: Some(true),
-            agent_amp_enabled: Some(true),
-            agent_claude_enabled: Some(true),
-            agent_droid_enabled: Some(true),
-            last_open_workspace_id: Some(10),
-            open_button_selection: None,
-            sidebar_project_order: Vec::new(),
-            workspace_active_thread_id: HashMap::from([(10, 2)]),
-            workspace_open_tabs: HashMap::from([(10, vec![1, 2])]),
-            workspace_archived_tabs: HashMap::new(),
-            workspace_next_thread_id: HashMap::from([(10, 3)]),
-            workspace_chat_scroll_y10: HashMap::new(),
-            workspace_chat_scroll_anchor: HashMap::new(),
-            workspace_unread_completions: HashMap::new(),
-            workspace_thread_run_config_overrides: HashMap::new(),
-            starred_tasks: HashMap::new(),
-            task_prompt_templates: HashMap::new(),
-            telegram_enabled: None,
-            telegram_bot_token: None,
-            telegram_bot_username: None,
-            telegram_paired_chat_id: None,
-            telegram_topic_bindings: None,
+    #[tokio::test]
+    async fn workspace_branch_rename_does_not_block_engine() {
+        let services: Arc<dyn ProjectWorkspaceService> = Arc::new(SlowRenameServices {
+            delay: Duration::from_secs(2),
+        });
+
+        let mut state = AppState::new();
+        let _ = state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/luban-server-rename-test"),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+        let _ = state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "w1".to_owned(),
+            branch_name: "luban/w1".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban-server-rename-test"),
+        });
+        let workspace_id = state.projects[0].workspaces[0].id;
+
+        let (events, _) = broadcast::channel::<WsServerMessage>(16);
+        let (tx, mut rx) = mpsc::channel::<EngineCommand>(16);
+        let mut engine = Engine {
+            state,
+            rev: 1,
+            services,
+            events,
+            tx: tx.clone(),
+            branch_watch: BranchWatchHandle::disabled(),
+            cancel_flags: HashMap::new(),
+            pull_requests: HashMap::new(),
+            pull_requests_in_flight: HashSet::new(),
+            workspace_threads_cache: HashMap::new(),
+            auto_archive_workspaces: HashSet::new(),
+            pending_notes_flushes: HashMap::new(),
+            pending_draft_flushes: HashMap::new(),
+            conversation_revs: HashMap::new(),
+            last_git_fetch_at: HashMap::new(),
+            worktree_dirty: HashMap::new(),
+            worktree_dirty_in_flight: HashSet::new(),
+            worktree_dirty_rerun: HashSet::new(),
+            last_commits: HashMap::new(),
+            agent_turn_slots: HashMap::new(),
+            deferred_agent_turns: VecDeque::new(),
+            request_cancellations: RequestCancellations::default(),
+            attachment_thumbnails: AttachmentThumbnails::default(),
+            telegram_pairings: Vec::new(),
+            config: EngineConfig::default(),
         };
 
-        services
-            .save_app_state(snapshot.clone())
-            .expect("save app state");
-        let loaded = services.load_app_state().expect("load app state");
+        let rename = tokio::time::timeout(
+            Duration::from_millis(200),
+            engine.process_action_queue(Action::WorkspaceBranchRenameRequested {
+                workspace_id,
+                requested_branch_name: "luban/rename-test".to_owned(),
+            }),
+        )
+        .await;
+        assert!(rename.is_ok(), "rename action should not block");
 
-        assert_eq!(loaded.workspace_open_tabs.get(&10), Some(&vec![1, 2]));
-        assert_eq!(loaded.workspace_next_thread_id.get(&10), Some(&3));
-        assert_eq!(loaded.workspace_active_thread_id.get(&10), Some(&2));
-        drop(env_guard);
+        // Drain the dispatch action so the spawned task does not leak.
+        let _ = tokio::time::timeout(Duration::from_secs(5), async {
+            while let Some(cmd) = rx.recv().await {
+                if let EngineCommand::DispatchAction { action } = cmd {
+                    engine.process_action_queue(*action).await;
+                    break;
+                }
+            }
+        })
+        .await;
     }
 
-    #[test]
-    fn workspace_threads_changed_includes_tabs_snapshot() {
+    #[tokio::test]
+    async fn agent_turn_does_not_override_codex_defaults() {
+        let (sender, receiver) = std::sync::mpsc::channel::<luban_domain::RunAgentTurnRequest>();
+        let services: Arc<dyn ProjectWorkspaceService> =
+            Arc::new(CaptureRunAgentTurnServices { sender });
+
         let mut state = AppState::new();
         let _ = state.apply(Action::AddProject {
-            path: PathBuf::from("/tmp/luban-server-test"),
+            path: PathBuf::from("/tmp/luban-server-agent-turn-test"),
             is_git: true,
         });
-
         let project_id = state.projects[0].id;
         let _ = state.apply(Action::WorkspaceCreated {
             project_id,
             workspace_name: "main".to_owned(),
             branch_name: "main".to_owned(),
-            worktree_path: PathBuf::from("/tmp/luban-server-test"),
+            worktree_path: PathBuf::from("/tmp/luban-server-agent-turn-test"),
         });
 
         let workspace_id = state.projects[0].workspaces[0].id;
-        state.apply(Action::OpenWorkspace { workspace_id });
-
-        state.apply(Action::CreateWorkspaceThread { workspace_id });
-        state.apply(Action::CreateWorkspaceThread { workspace_id });
-
-        let open_tabs = state
-            .workspace_tabs(workspace_id)
-            .expect("workspace tabs exist after opening workspace")
-            .open_tabs
-            .clone();
+        let thread_id = WorkspaceThreadId::from_u64(1);
 
-        let archived_thread = open_tabs[0];
-        state.apply(Action::CloseWorkspaceThreadTab {
+        let _ = state.apply(Action::ChatModelChanged {
             workspace_id,
-            thread_id: archived_thread,
+            thread_id,
+            model_id: "not-a-real-model".to_owned(),
         });
 
-        let tabs = state.workspace_tabs(workspace_id).unwrap();
-        assert!(tabs.archived_tabs.contains(&archived_thread));
+        let (events, _) = broadcast::channel::<WsServerMessage>(16);
+        let (tx, _rx) = mpsc::channel::<EngineCommand>(16);
+        let mut engine = Engine {
+            state,
+            rev: 1,
+            services,
+            events,
+            tx,
+            branch_watch: BranchWatchHandle::disabled(),
+            cancel_flags: HashMap::new(),
+            pull_requests: HashMap::new(),
+            pull_requests_in_flight: HashSet::new(),
+            workspace_threads_cache: HashMap::new(),
+            auto_archive_workspaces: HashSet::new(),
+            pending_notes_flushes: HashMap::new(),
+            pending_draft_flushes: HashMap::new(),
+            conversation_revs: HashMap::new(),
+            last_git_fetch_at: HashMap::new(),
+            worktree_dirty: HashMap::new(),
+            worktree_dirty_in_flight: HashSet::new(),
+            worktree_dirty_rerun: HashSet::new(),
+            last_commits: HashMap::new(),
+            agent_turn_slots: HashMap::new(),
+            deferred_agent_turns: VecDeque::new(),
+            request_cancellations: RequestCancellations::default(),
+            attachment_thumbnails: AttachmentThumbnails::default(),
+            telegram_pairings: Vec::new(),
+            config: EngineConfig::default(),
+        };
 
-        let mut meta_ids = Vec::new();
-        meta_ids.extend(tabs.open_tabs.iter().copied());
-        meta_ids.extend(tabs.archived_tabs.iter().copied());
-        let metas = meta_ids
-            .iter()
-            .map(|id| ConversationThreadMeta {
-                thread_id: *id,
-                remote_thread_id: None,
-                title: format!("thread-{}", id.as_u64()),
-                created_at_unix_seconds: 0,
-                updated_at_unix_seconds: 0,
-                task_status: luban_domain::TaskStatus::Todo,
-                last_message_seq: 0,
-                task_status_last_analyzed_message_seq: 0,
-                turn_status: luban_domain::TurnStatus::Idle,
-                last_turn_result: None,
+        engine
+            .process_action_queue(Action::SendAgentMessage {
+                workspace_id,
+                thread_id,
+                text: "hello".to_owned(),
+                attachments: Vec::new(),
+                runner: None,
+                amp_mode: None,
             })
-            .collect::<Vec<_>>();
+            .await;
 
-        let (events, _) = broadcast::channel::<WsServerMessage>(4);
-        let mut rx = events.subscribe();
-        let (tx, _rx_cmd) = mpsc::channel::<EngineCommand>(1);
-        let engine = Engine {
+        let request = receiver
+            .recv_timeout(std::time::Duration::from_secs(2))
+            .expect("expected agent turn request");
+
+        assert_eq!(request.runner, luban_domain::AgentRunnerKind::Codex);
+        assert!(request.amp_mode.is_none());
+        assert_eq!(request.model.as_deref(), Some("not-a-real-model"));
+        assert_eq!(request.model_reasoning_effort.as_deref(), Some("medium"));
+    }
+
+    #[tokio::test]
+    async fn agent_turns_over_the_concurrency_limit_wait_in_fifo_order() {
+        let (sender, receiver) = std::sync::mpsc::channel::<luban_domain::RunAgentTurnRequest>();
+        let services: Arc<dyn ProjectWorkspaceService> =
+            Arc::new(CaptureRunAgentTurnServices { sender });
+
+        let mut state = AppState::new();
+        let _ = state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/luban-server-turn-limit-test"),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+        let _ = state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "main".to_owned(),
+            branch_name: "main".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban-server-turn-limit-test"),
+        });
+        let workspace_id = state.projects[0].workspaces[0].id;
+        let thread_ids = [1, 2, 3].map(WorkspaceThreadId::from_u64);
+
+        let (events, _) = broadcast::channel::<WsServerMessage>(16);
+        let (tx, mut rx) = mpsc::channel::<EngineCommand>(64);
+        let mut engine = Engine {
             state,
             rev: 1,
-            services: Arc::new(TestServices),
+            services,
             events,
             tx,
             branch_watch: BranchWatchHandle::disabled(),
@@ -7344,212 +15801,330 @@ mod tests {
             pull_requests_in_flight: HashSet::new(),
             workspace_threads_cache: HashMap::new(),
             auto_archive_workspaces: HashSet::new(),
-            telegram_pairing: None,
+            pending_notes_flushes: HashMap::new(),
+            pending_draft_flushes: HashMap::new(),
+            conversation_revs: HashMap::new(),
+            last_git_fetch_at: HashMap::new(),
+            worktree_dirty: HashMap::new(),
+            worktree_dirty_in_flight: HashSet::new(),
+            worktree_dirty_rerun: HashSet::new(),
+            last_commits: HashMap::new(),
+            agent_turn_slots: HashMap::new(),
+            deferred_agent_turns: VecDeque::new(),
+            request_cancellations: RequestCancellations::default(),
+            attachment_thumbnails: AttachmentThumbnails::default(),
+            telegram_pairings: Vec::new(),
+            config: EngineConfig {
+                max_concurrent_turns: 1,
+                ..EngineConfig::default()
+            },
         };
 
-        engine.publish_threads_event(workspace_id, &metas);
+        for (thread_id, text) in thread_ids.into_iter().zip(["first", "second", "third"]) {
+            engine
+                .process_action_queue(Action::SendAgentMessage {
+                    workspace_id,
+                    thread_id,
+                    text: text.to_owned(),
+                    attachments: Vec::new(),
+                    runner: None,
+                    amp_mode: None,
+                })
+                .await;
+        }
 
-        let message = rx.try_recv().expect("expected a threads event");
-        let WsServerMessage::Event { event, .. } = message else {
-            panic!("expected WsServerMessage::Event");
+        let request = receiver
+            .recv_timeout(std::time::Duration::from_secs(2))
+            .expect("expected the first turn to start");
+        assert_eq!(request.prompt, "first");
+        assert_eq!(engine.deferred_agent_turns.len(), 2);
+
+        let snapshot = engine.app_snapshot();
+        assert_eq!(snapshot.running_turns, 1);
+        assert_eq!(snapshot.waiting_turns, 2);
+        assert_eq!(snapshot.max_concurrent_turns, 1);
+        let waiting = engine
+            .conversation_snapshot(
+                luban_api::WorkspaceId(workspace_id.as_u64()),
+                luban_api::WorkspaceThreadId(thread_ids[1].as_u64()),
+                None,
+                None,
+            )
+            .expect("conversation snapshot should build");
+        assert_eq!(waiting.turn_status, luban_api::TurnStatus::Awaiting);
+        assert_eq!(waiting.run_started_at_unix_ms, None);
+
+        engine
+            .process_action_queue(Action::CancelAgentTurn {
+                workspace_id,
+                thread_id: thread_ids[2],
+            })
+            .await;
+        assert_eq!(engine.deferred_agent_turns.len(), 1);
+
+        // Finishing the first turn hands its slot to the oldest waiting turn.
+        let second = loop {
+            let cmd = tokio::time::timeout(std::time::Duration::from_secs(2), rx.recv())
+                .await
+                .expect("timed out waiting for the first turn to finish")
+                .expect("engine command channel closed");
+            engine.handle(cmd).await;
+            if let Ok(request) = receiver.try_recv() {
+                break request;
+            }
         };
+        assert_eq!(second.prompt, "second");
+        assert!(engine.deferred_agent_turns.is_empty());
+
+        while let Ok(Some(cmd)) =
+            tokio::time::timeout(std::time::Duration::from_millis(200), rx.recv()).await
+        {
+            engine.handle(cmd).await;
+        }
+        assert!(
+            receiver.try_recv().is_err(),
+            "cancelled waiting turn must never reach the runner"
+        );
+        assert!(engine.agent_turn_slots.is_empty());
+        assert_eq!(engine.app_snapshot().running_turns, 0);
+    }
+
+    #[derive(Default)]
+    struct ImagePreviewServices {
+        calls: Mutex<Vec<String>>,
+    }
+
+    impl ProjectWorkspaceService for ImagePreviewServices {
+        fn load_app_state(&self) -> Result<PersistedAppState, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn save_app_state(&self, _snapshot: PersistedAppState) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn create_workspace(
+            &self,
+            _project_path: PathBuf,
+            _project_slug: String,
+            _branch_name_hint: Option<String>,
+            _worktree_root: Option<PathBuf>,
+            _branch_name_template: Option<luban_domain::BranchNameTemplate>,
+        ) -> Result<luban_domain::CreatedWorkspace, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn open_workspace_in_ide(&self, _worktree_path: PathBuf) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn archive_workspace(
+            &self,
+            _project_path: PathBuf,
+            _worktree_path: PathBuf,
+            _branch_name: String,
+        ) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn rename_workspace_branch(
+            &self,
+            _worktree_path: PathBuf,
+            _requested_branch_name: String,
+            _branch_name_template: Option<luban_domain::BranchNameTemplate>,
+        ) -> Result<String, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn ensure_conversation(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _thread_id: u64,
+        ) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn list_conversation_threads(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+        ) -> Result<Vec<ConversationThreadMeta>, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn load_conversation(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _thread_id: u64,
+        ) -> Result<DomainConversationSnapshot, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn load_conversation_page(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _thread_id: u64,
+            _before: Option<u64>,
+            _limit: u64,
+        ) -> Result<DomainConversationSnapshot, String> {
+            Err("unimplemented".to_owned())
+        }
 
-        let luban_api::ServerEvent::WorkspaceThreadsChanged {
-            workspace_id: wid,
-            tabs,
-            ..
-        } = *event
-        else {
-            panic!("expected workspace_threads_changed");
-        };
+        fn store_context_image(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _image: ContextImage,
+        ) -> Result<AttachmentRef, String> {
+            Err("unimplemented".to_owned())
+        }
 
-        assert_eq!(wid.0, workspace_id.as_u64());
-        assert_eq!(
-            tabs.open_tabs.len() + tabs.archived_tabs.len(),
-            metas.len(),
-            "tabs snapshot should match the set of known thread ids"
-        );
-        assert!(
-            tabs.archived_tabs
-                .iter()
-                .any(|id| id.0 == archived_thread.as_u64())
-        );
-    }
+        fn store_context_text(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _text: String,
+            _extension: String,
+        ) -> Result<AttachmentRef, String> {
+            Err("unimplemented".to_owned())
+        }
 
-    #[test]
-    fn workspace_threads_changed_dedups_duplicate_thread_ids() {
-        let mut state = AppState::new();
-        let _ = state.apply(Action::AddProject {
-            path: PathBuf::from("/tmp/luban-server-test"),
-            is_git: true,
-        });
+        fn store_context_file(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _source_path: PathBuf,
+        ) -> Result<AttachmentRef, String> {
+            Err("unimplemented".to_owned())
+        }
 
-        let project_id = state.projects[0].id;
-        let _ = state.apply(Action::WorkspaceCreated {
-            project_id,
-            workspace_name: "main".to_owned(),
-            branch_name: "main".to_owned(),
-            worktree_path: PathBuf::from("/tmp/luban-server-test"),
-        });
+        fn record_context_item(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _attachment: AttachmentRef,
+            _created_at_unix_ms: u64,
+        ) -> Result<u64, String> {
+            Err("unimplemented".to_owned())
+        }
 
-        let workspace_id = state.projects[0].workspaces[0].id;
-        state.apply(Action::OpenWorkspace { workspace_id });
+        fn list_context_items(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+        ) -> Result<Vec<ContextItem>, String> {
+            Err("unimplemented".to_owned())
+        }
 
-        let thread_id = state
-            .workspace_tabs(workspace_id)
-            .expect("workspace tabs exist after opening workspace")
-            .active_tab;
+        fn delete_context_item(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _context_id: u64,
+        ) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
 
-        let metas = vec![
-            ConversationThreadMeta {
-                thread_id,
-                remote_thread_id: None,
-                title: "alpha".to_owned(),
-                created_at_unix_seconds: 0,
-                updated_at_unix_seconds: 0,
-                task_status: luban_domain::TaskStatus::Todo,
-                last_message_seq: 0,
-                task_status_last_analyzed_message_seq: 0,
-                turn_status: luban_domain::TurnStatus::Idle,
-                last_turn_result: None,
-            },
-            ConversationThreadMeta {
-                thread_id,
-                remote_thread_id: None,
-                title: "beta".to_owned(),
-                created_at_unix_seconds: 0,
-                updated_at_unix_seconds: 0,
-                task_status: luban_domain::TaskStatus::Todo,
-                last_message_seq: 0,
-                task_status_last_analyzed_message_seq: 0,
-                turn_status: luban_domain::TurnStatus::Idle,
-                last_turn_result: None,
-            },
-        ];
+        fn run_agent_turn_streamed(
+            &self,
+            _request: luban_domain::RunAgentTurnRequest,
+            _cancel: Arc<AtomicBool>,
+            _on_event: Arc<dyn Fn(luban_domain::AgentThreadEvent) + Send + Sync>,
+        ) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
 
-        let (events, _) = broadcast::channel::<WsServerMessage>(4);
-        let mut rx = events.subscribe();
-        let (tx, _rx_cmd) = mpsc::channel::<EngineCommand>(1);
-        let engine = Engine {
-            state,
-            rev: 1,
-            services: Arc::new(TestServices),
-            events,
-            tx,
-            branch_watch: BranchWatchHandle::disabled(),
-            cancel_flags: HashMap::new(),
-            pull_requests: HashMap::new(),
-            pull_requests_in_flight: HashSet::new(),
-            workspace_threads_cache: HashMap::new(),
-            auto_archive_workspaces: HashSet::new(),
-            telegram_pairing: None,
-        };
+        fn gh_is_authorized(&self) -> Result<bool, String> {
+            Err("unimplemented".to_owned())
+        }
 
-        engine.publish_threads_event(workspace_id, &metas);
+        fn gh_pull_request_info(
+            &self,
+            _worktree_path: PathBuf,
+            _target: luban_domain::PullRequestTarget,
+        ) -> Result<Option<PullRequestInfo>, String> {
+            Err("unimplemented".to_owned())
+        }
 
-        let message = rx.try_recv().expect("expected a threads event");
-        let WsServerMessage::Event { event, .. } = message else {
-            panic!("expected WsServerMessage::Event");
-        };
+        fn gh_open_pull_request(&self, _worktree_path: PathBuf) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
 
-        let luban_api::ServerEvent::WorkspaceThreadsChanged { threads, .. } = *event else {
-            panic!("expected workspace_threads_changed");
-        };
+        fn gh_open_pull_request_failed_action(
+            &self,
+            _worktree_path: PathBuf,
+        ) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
 
-        assert_eq!(threads.len(), 1);
-        assert_eq!(threads[0].thread_id.0, thread_id.as_u64());
-        assert_eq!(threads[0].title, "alpha");
+        fn context_image_preview_png(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            attachment_id: String,
+            _extension: String,
+        ) -> Result<Option<Vec<u8>>, String> {
+            self.calls
+                .lock()
+                .expect("mutex ok")
+                .push(attachment_id.clone());
+            match attachment_id.as_str() {
+                "broken" => Ok(None),
+                "unreadable" => Err("read failed".to_owned()),
+                _ => Ok(Some(attachment_id.into_bytes())),
+            }
+        }
     }
 
-    #[test]
-    fn task_summaries_changed_marks_running_unread_and_starred() {
+    #[tokio::test]
+    async fn conversation_snapshots_carry_cached_image_thumbnails() {
         let mut state = AppState::new();
         let _ = state.apply(Action::AddProject {
-            path: PathBuf::from("/tmp/luban-server-test"),
+            path: PathBuf::from("/tmp/luban-server-thumbnail-test"),
             is_git: true,
         });
-
         let project_id = state.projects[0].id;
         let _ = state.apply(Action::WorkspaceCreated {
             project_id,
-            workspace_name: "main".to_owned(),
-            branch_name: "main".to_owned(),
-            worktree_path: PathBuf::from("/tmp/luban-server-test"),
-        });
-
-        let workspace_id = state.projects[0].workspaces[0].id;
-        state.apply(Action::OpenWorkspace { workspace_id });
-
-        let active_thread_id = state
-            .workspace_tabs(workspace_id)
-            .expect("workspace tabs exist after opening workspace")
-            .active_tab;
-        let other_thread_id =
-            WorkspaceThreadId::from_u64(active_thread_id.as_u64().saturating_add(1));
-
-        state.apply(Action::ConversationLoaded {
-            workspace_id,
-            thread_id: active_thread_id,
-            snapshot: luban_domain::ConversationSnapshot {
-                title: Some("active".to_owned()),
-                thread_id: None,
-                task_status: luban_domain::TaskStatus::Todo,
-                runner: None,
-                agent_model_id: None,
-                thinking_effort: None,
-                amp_mode: None,
-                entries: Vec::new(),
-                entries_total: 0,
-                entries_start: 0,
-                pending_prompts: Vec::new(),
-                queue_paused: false,
-                run_started_at_unix_ms: None,
-                run_finished_at_unix_ms: None,
-            },
-        });
-
-        state
-            .conversations
-            .get_mut(&(workspace_id, active_thread_id))
-            .expect("expected conversation to exist after ConversationLoaded")
-            .run_status = OperationStatus::Running;
-        state.workspace_unread_completions.insert(workspace_id);
-        state.starred_tasks.insert((workspace_id, other_thread_id));
-
-        let metas = vec![
-            ConversationThreadMeta {
-                thread_id: active_thread_id,
-                remote_thread_id: None,
-                title: "active".to_owned(),
-                created_at_unix_seconds: 1,
-                updated_at_unix_seconds: 2,
-                task_status: luban_domain::TaskStatus::Todo,
-                last_message_seq: 0,
-                task_status_last_analyzed_message_seq: 0,
-                turn_status: luban_domain::TurnStatus::Idle,
-                last_turn_result: Some(luban_domain::TurnResult::Completed),
-            },
-            ConversationThreadMeta {
-                thread_id: other_thread_id,
-                remote_thread_id: None,
-                title: "other".to_owned(),
-                created_at_unix_seconds: 3,
-                updated_at_unix_seconds: 4,
-                task_status: luban_domain::TaskStatus::Backlog,
-                last_message_seq: 0,
-                task_status_last_analyzed_message_seq: 0,
-                turn_status: luban_domain::TurnStatus::Awaiting,
-                last_turn_result: None,
-            },
-        ];
+            workspace_name: "main".to_owned(),
+            branch_name: "main".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban-server-thumbnail-test"),
+        });
 
-        let (events, _) = broadcast::channel::<WsServerMessage>(4);
-        let mut rx = events.subscribe();
-        let (tx, _rx_cmd) = mpsc::channel::<EngineCommand>(1);
-        let mut engine = Engine {
+        let workspace_id = state.projects[0].workspaces[0].id;
+        let thread_id = WorkspaceThreadId::from_u64(1);
+        let attachment = |id: &str, kind: AttachmentKind| AttachmentRef {
+            id: id.to_owned(),
+            kind,
+            name: format!("{id}.png"),
+            extension: "png".to_owned(),
+            mime: None,
+            byte_len: 1,
+        };
+        state.apply(Action::SendAgentMessage {
+            workspace_id,
+            thread_id,
+            text: "look".to_owned(),
+            attachments: vec![
+                attachment("image", AttachmentKind::Image),
+                attachment("broken", AttachmentKind::Image),
+                attachment("unreadable", AttachmentKind::Image),
+                attachment("notes", AttachmentKind::File),
+            ],
+            runner: None,
+            amp_mode: None,
+        });
+
+        let services = Arc::new(ImagePreviewServices::default());
+        let (events, _) = broadcast::channel::<WsServerMessage>(1);
+        let (tx, _rx) = mpsc::channel::<EngineCommand>(1);
+        let engine = Engine {
             state,
             rev: 1,
-            services: Arc::new(TestServices),
+            services: services.clone(),
             events,
             tx,
             branch_watch: BranchWatchHandle::disabled(),
@@ -7558,88 +16133,102 @@ mod tests {
             pull_requests_in_flight: HashSet::new(),
             workspace_threads_cache: HashMap::new(),
             auto_archive_workspaces: HashSet::new(),
-            telegram_pairing: None,
-        };
-        engine.workspace_threads_cache.insert(workspace_id, metas);
-
-        engine.publish_task_summaries_event(workspace_id);
-
-        let message = rx.try_recv().expect("expected a task summaries event");
-        let WsServerMessage::Event { event, .. } = message else {
-            panic!("expected WsServerMessage::Event");
+            pending_notes_flushes: HashMap::new(),
+            pending_draft_flushes: HashMap::new(),
+            conversation_revs: HashMap::new(),
+            last_git_fetch_at: HashMap::new(),
+            worktree_dirty: HashMap::new(),
+            worktree_dirty_in_flight: HashSet::new(),
+            worktree_dirty_rerun: HashSet::new(),
+            last_commits: HashMap::new(),
+            agent_turn_slots: HashMap::new(),
+            deferred_agent_turns: VecDeque::new(),
+            request_cancellations: RequestCancellations::default(),
+            attachment_thumbnails: AttachmentThumbnails::default(),
+            telegram_pairings: Vec::new(),
+            config: EngineConfig::default(),
         };
 
-        let luban_api::ServerEvent::TaskSummariesChanged {
-            workspace_id: wid,
-            tasks,
-            ..
-        } = *event
-        else {
-            panic!("expected task_summaries_changed");
-        };
-        assert_eq!(wid.0, workspace_id.as_u64());
+        let api_wid = luban_api::WorkspaceId(workspace_id.as_u64());
+        let api_tid = luban_api::WorkspaceThreadId(thread_id.as_u64());
+        for _ in 0..2 {
+            let mut snapshot = engine
+                .conversation_snapshot(api_wid, api_tid, None, None)
+                .expect("snapshot should be in memory");
+            engine
+                .fill_attachment_thumbnails(workspace_id, &mut snapshot)
+                .await;
+            let thumbnails = conversation_snapshot_attachments_mut(&mut snapshot)
+                .map(|a| (a.id.clone(), a.thumbnail_base64.clone()))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                thumbnails,
+                vec![
+                    (
+                        "image".to_owned(),
+                        Some(base64::engine::general_purpose::STANDARD.encode("image"))
+                    ),
+                    ("broken".to_owned(), None),
+                    ("unreadable".to_owned(), None),
+                    ("notes".to_owned(), None),
+                ]
+            );
+        }
 
-        let active = tasks
-            .iter()
-            .find(|t| t.thread_id.0 == active_thread_id.as_u64())
-            .expect("active task should be present");
-        let other = tasks
-            .iter()
-            .find(|t| t.thread_id.0 == other_thread_id.as_u64())
-            .expect("other task should be present");
+        assert_eq!(
+            *services.calls.lock().expect("mutex ok"),
+            vec![
+                "image".to_owned(),
+                "broken".to_owned(),
+                "unreadable".to_owned(),
+                "unreadable".to_owned(),
+            ],
+            "decoded previews are cached; read errors are retried"
+        );
+    }
 
-        assert_eq!(active.agent_run_status, luban_api::OperationStatus::Running);
-        assert!(active.has_unread_completion);
-        assert!(!active.is_starred);
+    #[test]
+    fn attachment_thumbnail_cache_evicts_oldest_entries() {
+        let thumbnails = AttachmentThumbnails::default();
+        for index in 0..=ATTACHMENT_THUMBNAIL_CACHE_LIMIT {
+            thumbnails.insert(format!("a{index}"), None);
+        }
 
-        assert_eq!(other.agent_run_status, luban_api::OperationStatus::Idle);
-        assert!(!other.has_unread_completion);
-        assert!(other.is_starred);
+        assert_eq!(thumbnails.get("a0"), None, "the oldest entry is evicted");
+        assert_eq!(thumbnails.get("a1"), Some(None));
+        assert_eq!(
+            thumbnails.get(&format!("a{ATTACHMENT_THUMBNAIL_CACHE_LIMIT}")),
+            Some(None)
+        );
     }
 
     #[tokio::test]
-    async fn task_star_set_emits_task_summaries_changed() {
+    async fn task_execute_start_passes_attachments_to_agent_turn() {
+        let (sender, receiver) = std::sync::mpsc::channel::<luban_domain::RunAgentTurnRequest>();
+        let services: Arc<dyn ProjectWorkspaceService> =
+            Arc::new(CaptureRunAgentTurnServices { sender });
+
         let mut state = AppState::new();
         let _ = state.apply(Action::AddProject {
-            path: PathBuf::from("/tmp/luban-server-test"),
+            path: PathBuf::from("/tmp/luban-server-task-execute-attachments-test"),
             is_git: true,
         });
-
         let project_id = state.projects[0].id;
         let _ = state.apply(Action::WorkspaceCreated {
             project_id,
             workspace_name: "main".to_owned(),
             branch_name: "main".to_owned(),
-            worktree_path: PathBuf::from("/tmp/luban-server-test"),
+            worktree_path: PathBuf::from("/tmp/luban-server-task-execute-attachments-test"),
         });
 
         let workspace_id = state.projects[0].workspaces[0].id;
-        state.apply(Action::OpenWorkspace { workspace_id });
-        let thread_id = state
-            .workspace_tabs(workspace_id)
-            .expect("workspace tabs exist after opening workspace")
-            .active_tab;
-
-        let metas = vec![ConversationThreadMeta {
-            thread_id,
-            remote_thread_id: None,
-            title: "alpha".to_owned(),
-            created_at_unix_seconds: 1,
-            updated_at_unix_seconds: 2,
-            task_status: luban_domain::TaskStatus::Todo,
-            last_message_seq: 0,
-            task_status_last_analyzed_message_seq: 0,
-            turn_status: luban_domain::TurnStatus::Idle,
-            last_turn_result: None,
-        }];
 
         let (events, _) = broadcast::channel::<WsServerMessage>(16);
-        let mut rx = events.subscribe();
-        let (tx, _rx_cmd) = mpsc::channel::<EngineCommand>(1);
+        let (tx, _rx) = mpsc::channel::<EngineCommand>(16);
         let mut engine = Engine {
             state,
             rev: 1,
-            services: Arc::new(IdentityServices),
+            services,
             events,
             tx,
             branch_watch: BranchWatchHandle::disabled(),
@@ -7648,75 +16237,86 @@ mod tests {
             pull_requests_in_flight: HashSet::new(),
             workspace_threads_cache: HashMap::new(),
             auto_archive_workspaces: HashSet::new(),
-            telegram_pairing: None,
+            pending_notes_flushes: HashMap::new(),
+            pending_draft_flushes: HashMap::new(),
+            conversation_revs: HashMap::new(),
+            last_git_fetch_at: HashMap::new(),
+            worktree_dirty: HashMap::new(),
+            worktree_dirty_in_flight: HashSet::new(),
+            worktree_dirty_rerun: HashSet::new(),
+            last_commits: HashMap::new(),
+            agent_turn_slots: HashMap::new(),
+            deferred_agent_turns: VecDeque::new(),
+            request_cancellations: RequestCancellations::default(),
+            attachment_thumbnails: AttachmentThumbnails::default(),
+            telegram_pairings: Vec::new(),
+            config: EngineConfig::default(),
         };
-        engine.workspace_threads_cache.insert(workspace_id, metas);
 
-        engine
-            .process_action_queue(Action::TaskStarSet {
-                workspace_id,
-                thread_id,
-                starred: true,
-            })
-            .await;
+        let api_attachment = luban_api::AttachmentRef {
+            id: "att-test-1".to_owned(),
+            kind: luban_api::AttachmentKind::Image,
+            name: "screenshot.png".to_owned(),
+            extension: "png".to_owned(),
+            mime: Some("image/png".to_owned()),
+            byte_len: 123,
+            thumbnail_base64: None,
+        };
 
-        let mut saw = false;
-        for _ in 0..20 {
-            let msg = match tokio::time::timeout(Duration::from_secs(1), rx.recv()).await {
-                Ok(Ok(msg)) => msg,
-                _ => continue,
-            };
-            let WsServerMessage::Event { event, .. } = msg else {
-                continue;
-            };
-            let luban_api::ServerEvent::TaskSummariesChanged { tasks, .. } = *event else {
-                continue;
-            };
-            let Some(task) = tasks.iter().find(|t| t.thread_id.0 == thread_id.as_u64()) else {
-                continue;
-            };
-            if task.is_starred {
-                saw = true;
-                break;
-            }
-        }
-        assert!(
-            saw,
-            "expected a task_summaries_changed event reflecting the star"
+        let _ = engine
+            .execute_task_prompt(
+                "hello".to_owned(),
+                luban_api::TaskExecuteMode::Start,
+                Some(luban_api::WorkspaceId(workspace_id.as_u64())),
+                vec![api_attachment.clone()],
+            )
+            .await
+            .expect("task execute prompt should succeed");
+
+        let request = receiver
+            .recv_timeout(std::time::Duration::from_secs(2))
+            .expect("expected agent turn request");
+
+        assert_eq!(request.attachments.len(), 1);
+        assert_eq!(request.attachments[0].id, api_attachment.id);
+        assert_eq!(request.attachments[0].name, api_attachment.name);
+        assert_eq!(request.attachments[0].extension, api_attachment.extension);
+        assert_eq!(request.attachments[0].mime, api_attachment.mime);
+        assert_eq!(request.attachments[0].byte_len, api_attachment.byte_len);
+        assert_eq!(
+            request.attachments[0].kind,
+            luban_domain::AttachmentKind::Image
         );
     }
 
     #[tokio::test]
-    async fn task_status_set_emits_conversation_changed() {
+    async fn agent_turn_uses_pinned_chat_runner_and_amp_mode() {
+        let (sender, receiver) = std::sync::mpsc::channel::<luban_domain::RunAgentTurnRequest>();
+        let services: Arc<dyn ProjectWorkspaceService> =
+            Arc::new(CaptureRunAgentTurnServices { sender });
+
         let mut state = AppState::new();
         let _ = state.apply(Action::AddProject {
-            path: PathBuf::from("/tmp/luban-server-test"),
+            path: PathBuf::from("/tmp/luban-server-pinned-run-config-test"),
             is_git: true,
         });
-
         let project_id = state.projects[0].id;
         let _ = state.apply(Action::WorkspaceCreated {
             project_id,
             workspace_name: "main".to_owned(),
             branch_name: "main".to_owned(),
-            worktree_path: PathBuf::from("/tmp/luban-server-test"),
+            worktree_path: PathBuf::from("/tmp/luban-server-pinned-run-config-test"),
         });
 
         let workspace_id = state.projects[0].workspaces[0].id;
-        state.apply(Action::OpenWorkspace { workspace_id });
-        state.apply(Action::CreateWorkspaceThread { workspace_id });
-        let thread_id = state
-            .workspace_tabs(workspace_id)
-            .expect("workspace tabs exist after creating thread")
-            .active_tab;
+        let thread_id = WorkspaceThreadId::from_u64(1);
 
         let (events, _) = broadcast::channel::<WsServerMessage>(16);
-        let mut rx = events.subscribe();
-        let (tx, _rx_cmd) = mpsc::channel::<EngineCommand>(1);
+        let (tx, _rx) = mpsc::channel::<EngineCommand>(16);
         let mut engine = Engine {
             state,
             rev: 1,
-            services: Arc::new(IdentityServices),
+            services,
             events,
             tx,
             branch_watch: BranchWatchHandle::disabled(),
@@ -7725,91 +16325,85 @@ mod tests {
             pull_requests_in_flight: HashSet::new(),
             workspace_threads_cache: HashMap::new(),
             auto_archive_workspaces: HashSet::new(),
-            telegram_pairing: None,
+            pending_notes_flushes: HashMap::new(),
+            pending_draft_flushes: HashMap::new(),
+            conversation_revs: HashMap::new(),
+            last_git_fetch_at: HashMap::new(),
+            worktree_dirty: HashMap::new(),
+            worktree_dirty_in_flight: HashSet::new(),
+            worktree_dirty_rerun: HashSet::new(),
+            last_commits: HashMap::new(),
+            agent_turn_slots: HashMap::new(),
+            deferred_agent_turns: VecDeque::new(),
+            request_cancellations: RequestCancellations::default(),
+            attachment_thumbnails: AttachmentThumbnails::default(),
+            telegram_pairings: Vec::new(),
+            config: EngineConfig::default(),
         };
 
         engine
-            .process_action_queue(Action::TaskStatusSet {
+            .process_action_queue(Action::ChatRunnerChanged {
                 workspace_id,
                 thread_id,
-                task_status: luban_domain::TaskStatus::Done,
+                runner: luban_domain::AgentRunnerKind::Amp,
             })
             .await;
 
-        let mut saw = false;
-        for _ in 0..40 {
-            let msg = match tokio::time::timeout(Duration::from_secs(1), rx.recv()).await {
-                Ok(Ok(msg)) => msg,
-                _ => continue,
-            };
-            let WsServerMessage::Event { event, .. } = msg else {
-                continue;
-            };
-            let luban_api::ServerEvent::ConversationChanged { snapshot } = *event else {
-                continue;
-            };
-            if snapshot.workspace_id.0 != workspace_id.as_u64()
-                || snapshot.thread_id.0 != thread_id.as_u64()
-            {
-                continue;
-            }
-            if snapshot.task_status != luban_api::TaskStatus::Done {
-                continue;
-            }
-            let has_status_event = snapshot.entries.iter().any(|e| {
-                matches!(
-                    e,
-                    luban_api::ConversationEntry::SystemEvent(
-                        luban_api::ConversationSystemEventEntry {
-                            event: luban_api::ConversationSystemEvent::TaskStatusChanged { .. },
-                            ..
-                        }
-                    )
-                )
-            });
-            if has_status_event {
-                saw = true;
-                break;
-            }
-        }
+        engine
+            .process_action_queue(Action::ChatAmpModeChanged {
+                workspace_id,
+                thread_id,
+                amp_mode: "rush".to_owned(),
+            })
+            .await;
 
-        assert!(
-            saw,
-            "expected a conversation_changed event reflecting the status change"
-        );
+        engine
+            .process_action_queue(Action::SendAgentMessage {
+                workspace_id,
+                thread_id,
+                text: "hello".to_owned(),
+                attachments: Vec::new(),
+                runner: None,
+                amp_mode: None,
+            })
+            .await;
+
+        let request = receiver
+            .recv_timeout(std::time::Duration::from_secs(2))
+            .expect("expected agent turn request");
+
+        assert_eq!(request.runner, luban_domain::AgentRunnerKind::Amp);
+        assert_eq!(request.amp_mode.as_deref(), Some("rush"));
     }
 
-    #[tokio::test]
-    async fn task_status_suggestion_created_emits_conversation_changed() {
+    #[tokio::test]
+    async fn agent_turn_with_unknown_amp_mode_is_rejected_with_toast() {
+        let (sender, receiver) = std::sync::mpsc::channel::<luban_domain::RunAgentTurnRequest>();
+        let services: Arc<dyn ProjectWorkspaceService> =
+            Arc::new(CaptureRunAgentTurnServices { sender });
+
         let mut state = AppState::new();
         let _ = state.apply(Action::AddProject {
-            path: PathBuf::from("/tmp/luban-server-test"),
+            path: PathBuf::from("/tmp/luban-server-unknown-amp-mode-test"),
             is_git: true,
         });
-
         let project_id = state.projects[0].id;
         let _ = state.apply(Action::WorkspaceCreated {
             project_id,
             workspace_name: "main".to_owned(),
             branch_name: "main".to_owned(),
-            worktree_path: PathBuf::from("/tmp/luban-server-test"),
+            worktree_path: PathBuf::from("/tmp/luban-server-unknown-amp-mode-test"),
         });
 
         let workspace_id = state.projects[0].workspaces[0].id;
-        state.apply(Action::OpenWorkspace { workspace_id });
-        state.apply(Action::CreateWorkspaceThread { workspace_id });
-        let thread_id = state
-            .workspace_tabs(workspace_id)
-            .expect("workspace tabs exist after creating thread")
-            .active_tab;
+        let thread_id = WorkspaceThreadId::from_u64(1);
 
         let (events, _) = broadcast::channel::<WsServerMessage>(16);
-        let mut rx = events.subscribe();
-        let (tx, _rx_cmd) = mpsc::channel::<EngineCommand>(1);
+        let (tx, _rx) = mpsc::channel::<EngineCommand>(16);
         let mut engine = Engine {
             state,
             rev: 1,
-            services: Arc::new(IdentityServices),
+            services,
             events,
             tx,
             branch_watch: BranchWatchHandle::disabled(),
@@ -7818,137 +16412,233 @@ mod tests {
             pull_requests_in_flight: HashSet::new(),
             workspace_threads_cache: HashMap::new(),
             auto_archive_workspaces: HashSet::new(),
-            telegram_pairing: None,
+            pending_notes_flushes: HashMap::new(),
+            pending_draft_flushes: HashMap::new(),
+            conversation_revs: HashMap::new(),
+            last_git_fetch_at: HashMap::new(),
+            worktree_dirty: HashMap::new(),
+            worktree_dirty_in_flight: HashSet::new(),
+            worktree_dirty_rerun: HashSet::new(),
+            last_commits: HashMap::new(),
+            agent_turn_slots: HashMap::new(),
+            deferred_agent_turns: VecDeque::new(),
+            request_cancellations: RequestCancellations::default(),
+            attachment_thumbnails: AttachmentThumbnails::default(),
+            telegram_pairings: Vec::new(),
+            config: EngineConfig::default(),
         };
+        let mut events = engine.events.subscribe();
 
         engine
-            .process_action_queue(Action::TaskStatusSuggestionCreated {
+            .process_action_queue(Action::ChatRunnerChanged {
                 workspace_id,
                 thread_id,
-                expected_current_task_status: luban_domain::TaskStatus::Backlog,
-                suggested_task_status: luban_domain::TaskStatus::Done,
-                title: "Suggest moving to done".to_owned(),
-                explanation_markdown: "- Work appears complete.".to_owned(),
+                runner: luban_domain::AgentRunnerKind::Amp,
+            })
+            .await;
+        engine
+            .process_action_queue(Action::ChatAmpModeChanged {
+                workspace_id,
+                thread_id,
+                amp_mode: "rsuh".to_owned(),
+            })
+            .await;
+        engine
+            .process_action_queue(Action::SendAgentMessage {
+                workspace_id,
+                thread_id,
+                text: "hello".to_owned(),
+                attachments: Vec::new(),
+                runner: None,
+                amp_mode: None,
             })
             .await;
 
-        let mut saw = false;
-        for _ in 0..40 {
-            let msg = match tokio::time::timeout(Duration::from_secs(1), rx.recv()).await {
-                Ok(Ok(msg)) => msg,
-                _ => continue,
-            };
-            let WsServerMessage::Event { event, .. } = msg else {
-                continue;
-            };
-            let luban_api::ServerEvent::ConversationChanged { snapshot } = *event else {
-                continue;
-            };
-            if snapshot.workspace_id.0 != workspace_id.as_u64()
-                || snapshot.thread_id.0 != thread_id.as_u64()
+        assert!(
+            receiver
+                .recv_timeout(std::time::Duration::from_millis(200))
+                .is_err(),
+            "the Amp runner must not be invoked"
+        );
+
+        let mut toast = None;
+        while let Ok(msg) = events.try_recv() {
+            if let WsServerMessage::Event { event, .. } = msg
+                && let luban_api::ServerEvent::Toast { message } = *event
             {
-                continue;
-            }
-            if snapshot.task_status != luban_api::TaskStatus::Backlog {
-                continue;
-            }
-            let has_suggestion_event = snapshot.entries.iter().any(|e| {
-                matches!(
-                    e,
-                    luban_api::ConversationEntry::SystemEvent(
-                        luban_api::ConversationSystemEventEntry {
-                            event: luban_api::ConversationSystemEvent::TaskStatusSuggestion { .. },
-                            ..
-                        }
-                    )
-                )
-            });
-            if has_suggestion_event {
-                saw = true;
-                break;
+                toast = Some(message);
             }
         }
+        let toast = toast.expect("expected an error toast");
+        assert!(toast.contains("rsuh"), "{toast}");
 
-        assert!(
-            saw,
-            "expected a conversation_changed event reflecting the suggestion"
-        );
+        let conversation = engine
+            .state
+            .workspace_thread_conversation(workspace_id, thread_id)
+            .expect("conversation");
+        assert_eq!(conversation.run_status, OperationStatus::Idle);
     }
 
     #[tokio::test]
-    async fn add_project_reuses_existing_by_github_repo() {
-        let (engine, _events) = Engine::start(Arc::new(IdentityServices));
-        engine
-            .apply_client_action(
-                "req-1".to_owned(),
-                luban_api::ClientAction::AddProject {
-                    path: "/tmp/repo-a".to_owned(),
-                },
-            )
-            .await
-            .expect("add first project should succeed");
-        engine
-            .apply_client_action(
-                "req-2".to_owned(),
-                luban_api::ClientAction::AddProject {
-                    path: "/tmp/repo-b".to_owned(),
-                },
-            )
-            .await
-            .expect("add second project should be reused");
+    async fn reconcile_stale_running_turns_appends_error_and_sets_finished_at() {
+        let services: Arc<ReconcileRecordingServices> =
+            Arc::new(ReconcileRecordingServices::default());
+        let services_dyn: Arc<dyn ProjectWorkspaceService> = services.clone();
 
-        let snapshot = engine.app_snapshot().await.expect("snapshot should work");
-        assert_eq!(snapshot.projects.len(), 1);
-        let loaded_path = normalize_project_path(std::path::Path::new(&snapshot.projects[0].path));
-        let expected_path = normalize_project_path(std::path::Path::new("/tmp/repo-a"));
-        assert_eq!(loaded_path, expected_path);
-    }
+        let mut state = AppState::new();
+        let _ = state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/luban-server-reconcile-test"),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+        let _ = state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "main".to_owned(),
+            branch_name: "main".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban-server-reconcile-test"),
+        });
 
-    struct ArchiveOkServices {
-        calls: Arc<std::sync::Mutex<Vec<(PathBuf, PathBuf)>>>,
-        cancel_flag: Option<Arc<AtomicBool>>,
+        let (events, _) = broadcast::channel::<WsServerMessage>(16);
+        let (tx, _rx) = mpsc::channel::<EngineCommand>(16);
+        let mut engine = Engine {
+            state,
+            rev: 1,
+            services: services_dyn,
+            events,
+            tx,
+            branch_watch: BranchWatchHandle::disabled(),
+            cancel_flags: HashMap::new(),
+            pull_requests: HashMap::new(),
+            pull_requests_in_flight: HashSet::new(),
+            workspace_threads_cache: HashMap::new(),
+            auto_archive_workspaces: HashSet::new(),
+            pending_notes_flushes: HashMap::new(),
+            pending_draft_flushes: HashMap::new(),
+            conversation_revs: HashMap::new(),
+            last_git_fetch_at: HashMap::new(),
+            worktree_dirty: HashMap::new(),
+            worktree_dirty_in_flight: HashSet::new(),
+            worktree_dirty_rerun: HashSet::new(),
+            last_commits: HashMap::new(),
+            agent_turn_slots: HashMap::new(),
+            deferred_agent_turns: VecDeque::new(),
+            request_cancellations: RequestCancellations::default(),
+            attachment_thumbnails: AttachmentThumbnails::default(),
+            telegram_pairings: Vec::new(),
+            config: EngineConfig::default(),
+        };
+
+        engine.reconcile_stale_running_turns().await;
+
+        let appended = services.appended_entries.lock().expect("mutex ok").clone();
+        assert!(
+            appended.iter().any(|e| matches!(
+                e,
+                ConversationEntry::AgentEvent {
+                    event: luban_domain::AgentEvent::TurnError { message, .. },
+                    ..
+                } if message == "Agent run interrupted by server restart."
+            )),
+            "expected reconcile to append a turn_error entry"
+        );
+
+        let saved = services.saved_queue_state.lock().expect("mutex ok").clone();
+        assert_eq!(saved.len(), 1);
+        let (queue_paused, run_started, run_finished, pending) = &saved[0];
+        assert!(*queue_paused);
+        assert_eq!(*run_started, Some(10));
+        assert!(run_finished.is_some());
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].text, "queued");
     }
 
-    impl ProjectWorkspaceService for ArchiveOkServices {
-        fn load_app_state(&self) -> Result<PersistedAppState, String> {
-            Ok(PersistedAppState {
-                projects: Vec::new(),
-                sidebar_width: None,
-                terminal_pane_width: None,
-                global_zoom_percent: None,
-                appearance_theme: None,
-                appearance_ui_font: None,
-                appearance_chat_font: None,
-                appearance_code_font: None,
-                appearance_terminal_font: None,
-                agent_default_model_id: None,
-                agent_runner_default_models: HashMap::new(),
-                agent_default_thinking_effort: None,
-                agent_default_runner: None,
-                agent_amp_mode: None,
-                agent_codex_enabled: Some(true),
-                agent_amp_enabled: Some(true),
-                agent_claude_enabled: Some(true),
-                agent_droid_enabled: Some(true),
-                last_open_workspace_id: None,
-                open_button_selection: None,
-                sidebar_project_order: Vec::new(),
-                workspace_active_thread_id: HashMap::new(),
-                workspace_open_tabs: HashMap::new(),
-                workspace_archived_tabs: HashMap::new(),
-                workspace_next_thread_id: HashMap::new(),
-                workspace_chat_scroll_y10: HashMap::new(),
-                workspace_chat_scroll_anchor: HashMap::new(),
-                workspace_unread_completions: HashMap::new(),
-                workspace_thread_run_config_overrides: HashMap::new(),
-                starred_tasks: HashMap::new(),
-                task_prompt_templates: HashMap::new(),
-                telegram_enabled: None,
-                telegram_bot_token: None,
-                telegram_bot_username: None,
-                telegram_paired_chat_id: None,
-                telegram_topic_bindings: None,
-            })
+    fn persisted_with_single_git_workspace(workspace_id: u64) -> PersistedAppState {
+        PersistedAppState {
+            projects: vec![PersistedProject {
+                id: 1,
+                name: "Repo".to_owned(),
+                path: PathBuf::from("/tmp/luban-engine-bootstrap"),
+                slug: "repo".to_owned(),
+                is_git: true,
+                expanded: true,
+                system_prompt: None,
+                worktree_root: None,
+                branch_name_template: None,
+                status: ProjectStatus::Active,
+                agent_defaults: Default::default(),
+                saved_commands: Vec::new(),
+                agent_env: Vec::new(),
+                pr_remote: None,
+                pr_base_branch: None,
+                workspaces: vec![PersistedWorkspace {
+                    id: workspace_id,
+                    workspace_name: "dev".to_owned(),
+                    branch_name: "dev".to_owned(),
+                    worktree_path: PathBuf::from("/tmp/luban-engine-bootstrap/dev"),
+                    status: WorkspaceStatus::Active,
+                    last_activity_at_unix_seconds: None,
+                }],
+            }],
+            sidebar_width: None,
+            terminal_pane_width: None,
+            global_zoom_percent: None,
+            appearance_theme: None,
+            appearance_ui_font: None,
+            appearance_chat_font: None,
+            appearance_code_font: None,
+            appearance_terminal_font: None,
+            agent_default_model_id: None,
+            agent_runner_default_models: HashMap::new(),
+            agent_runner_prompt_prefixes: HashMap::new(),
+            agent_runner_prompt_suffixes: HashMap::new(),
+            model_thinking_effort_caps: HashMap::new(),
+            agent_default_thinking_effort: None,
+            agent_default_runner: None,
+            agent_amp_mode: None,
+            agent_codex_enabled: Some(true),
+            agent_amp_enabled: Some(true),
+            agent_claude_enabled: Some(true),
+            agent_droid_enabled: Some(true),
+            last_open_workspace_id: None,
+            open_button_selection: None,
+            sidebar_project_order: Vec::new(),
+            project_groups: Vec::new(),
+            workspace_active_thread_id: HashMap::new(),
+            workspace_open_tabs: HashMap::new(),
+            workspace_archived_tabs: HashMap::new(),
+            workspace_next_thread_id: HashMap::new(),
+            workspace_chat_scroll_y10: HashMap::new(),
+            workspace_chat_scroll_anchor: HashMap::new(),
+            workspace_unread_completions: HashMap::new(),
+            workspace_thread_run_config_overrides: HashMap::new(),
+            starred_tasks: HashMap::new(),
+            task_prompt_templates: HashMap::new(),
+            telegram_enabled: None,
+            telegram_bot_token: None,
+            telegram_bot_username: None,
+            telegram_paired_chat_ids: Vec::new(),
+            telegram_topic_bindings: None,
+            webhook_url: None,
+            webhook_secret: None,
+            agent_merge_reasoning_entries: None,
+            agent_pause_queue_on_patch_failure: None,
+            agent_item_dedup_window: None,
+            agent_auto_retry_on_failure: None,
+            agent_command_output_max_bytes: None,
+            auto_archive_after_days: None,
+        }
+    }
+
+    #[derive(Clone)]
+    struct BootstrapHangServices {
+        persisted: PersistedAppState,
+        list_threads_delay: Duration,
+        archive_delay: Duration,
+    }
+
+    impl ProjectWorkspaceService for BootstrapHangServices {
+        fn load_app_state(&self) -> Result<PersistedAppState, String> {
+            Ok(self.persisted.clone())
         }
 
         fn save_app_state(&self, _snapshot: PersistedAppState) -> Result<(), String> {
@@ -7960,6 +16650,8 @@ mod tests {
             _project_path: PathBuf,
             _project_slug: String,
             _branch_name_hint: Option<String>,
+            _worktree_root: Option<PathBuf>,
+            _branch_name_template: Option<luban_domain::BranchNameTemplate>,
         ) -> Result<luban_domain::CreatedWorkspace, String> {
             Err("unimplemented".to_owned())
         }
@@ -7970,19 +16662,11 @@ mod tests {
 
         fn archive_workspace(
             &self,
-            project_path: PathBuf,
-            worktree_path: PathBuf,
+            _project_path: PathBuf,
+            _worktree_path: PathBuf,
             _branch_name: String,
         ) -> Result<(), String> {
-            if let Some(cancel_flag) = &self.cancel_flag
-                && !cancel_flag.load(Ordering::SeqCst)
-            {
-                return Err("archive workspace called before agent cancel".to_owned());
-            }
-            self.calls
-                .lock()
-                .expect("mutex poisoned")
-                .push((project_path, worktree_path));
+            std::thread::sleep(self.archive_delay);
             Ok(())
         }
 
@@ -7990,6 +16674,7 @@ mod tests {
             &self,
             _worktree_path: PathBuf,
             _requested_branch_name: String,
+            _branch_name_template: Option<luban_domain::BranchNameTemplate>,
         ) -> Result<String, String> {
             Err("unimplemented".to_owned())
         }
@@ -8008,7 +16693,20 @@ mod tests {
             _project_slug: String,
             _workspace_name: String,
         ) -> Result<Vec<ConversationThreadMeta>, String> {
-            Err("unimplemented".to_owned())
+            std::thread::sleep(self.list_threads_delay);
+            Ok(vec![ConversationThreadMeta {
+                thread_id: luban_domain::WorkspaceThreadId::from_u64(1),
+                remote_thread_id: None,
+                title: "Done: completed successfully".to_owned(),
+                created_at_unix_seconds: 1,
+                updated_at_unix_seconds: 1,
+                task_status: luban_domain::TaskStatus::Done,
+                last_message_seq: 0,
+                task_status_last_analyzed_message_seq: 0,
+                turn_status: luban_domain::TurnStatus::Idle,
+                last_turn_result: Some(luban_domain::TurnResult::Completed),
+                total_tokens: 0,
+            }])
         }
 
         fn load_conversation(
@@ -8102,6 +16800,7 @@ mod tests {
         fn gh_pull_request_info(
             &self,
             _worktree_path: PathBuf,
+            _target: luban_domain::PullRequestTarget,
         ) -> Result<Option<PullRequestInfo>, String> {
             Err("unimplemented".to_owned())
         }
@@ -8116,224 +16815,63 @@ mod tests {
         ) -> Result<(), String> {
             Err("unimplemented".to_owned())
         }
-
-        fn project_identity(
-            &self,
-            _path: PathBuf,
-        ) -> Result<luban_domain::ProjectIdentity, String> {
-            Err("unimplemented".to_owned())
-        }
     }
 
     #[tokio::test]
-    async fn archive_workspace_runs_effect_and_marks_archived() {
-        let calls = Arc::new(std::sync::Mutex::new(Vec::<(PathBuf, PathBuf)>::new()));
-        let services: Arc<dyn ProjectWorkspaceService> = Arc::new(ArchiveOkServices {
-            calls: calls.clone(),
-            cancel_flag: None,
-        });
-
-        let mut state = AppState::new();
-        let project_path = PathBuf::from("/tmp/luban-server-archive-test");
-        let _ = state.apply(Action::AddProject {
-            path: project_path.clone(),
-            is_git: true,
-        });
-        let project_id = state.projects[0].id;
-
-        let worktree_path = PathBuf::from("/tmp/luban-server-archive-test-wt");
-        let _ = state.apply(Action::WorkspaceCreated {
-            project_id,
-            workspace_name: "wt".to_owned(),
-            branch_name: "feature".to_owned(),
-            worktree_path: worktree_path.clone(),
+    async fn bootstrap_does_not_block_on_auto_archive_scan() {
+        let services: Arc<dyn ProjectWorkspaceService> = Arc::new(BootstrapHangServices {
+            persisted: persisted_with_single_git_workspace(10),
+            list_threads_delay: Duration::from_secs(2),
+            archive_delay: Duration::from_millis(0),
         });
+        let (engine, _events) = Engine::start(services);
 
-        let workspace_id = state
-            .projects
-            .iter()
-            .flat_map(|p| p.workspaces.iter())
-            .find(|w| w.worktree_path == worktree_path)
-            .expect("workspace should exist")
-            .id;
-
-        let (events, _) = broadcast::channel::<WsServerMessage>(16);
-        let (tx, mut rx) = mpsc::channel::<EngineCommand>(16);
-        let mut engine = Engine {
-            state,
-            rev: 1,
-            services,
-            events,
-            tx,
-            branch_watch: BranchWatchHandle::disabled(),
-            cancel_flags: HashMap::new(),
-            pull_requests: HashMap::new(),
-            pull_requests_in_flight: HashSet::new(),
-            workspace_threads_cache: HashMap::new(),
-            auto_archive_workspaces: HashSet::new(),
-            telegram_pairing: None,
-        };
-
-        engine
-            .process_action_queue(Action::ArchiveWorkspace { workspace_id })
-            .await;
-        let cmd = tokio::time::timeout(std::time::Duration::from_secs(3), rx.recv())
+        let snap = tokio::time::timeout(Duration::from_millis(300), engine.app_snapshot())
             .await
-            .expect("timed out waiting for archive completion")
-            .expect("engine command channel closed");
-        engine.handle(cmd).await;
-
-        let workspace = engine
-            .state
-            .workspace(workspace_id)
-            .expect("workspace should still exist after archive");
-        assert_eq!(workspace.status, luban_domain::WorkspaceStatus::Archived);
-        assert_eq!(engine.state.main_pane, luban_domain::MainPane::None);
-        assert_eq!(engine.state.right_pane, luban_domain::RightPane::None);
-
-        let calls = calls.lock().expect("mutex poisoned");
-        assert_eq!(calls.len(), 1);
-        assert_eq!(calls[0].0, project_path);
-        assert_eq!(calls[0].1, worktree_path);
+            .expect("app snapshot should not be blocked by bootstrap maintenance")
+            .expect("snapshot should succeed");
+        assert_eq!(snap.projects.len(), 1);
     }
 
     #[tokio::test]
-    async fn archive_workspace_cancels_agent_turns_before_archiving() {
-        let calls = Arc::new(std::sync::Mutex::new(Vec::<(PathBuf, PathBuf)>::new()));
-        let cancel_flag = Arc::new(AtomicBool::new(false));
-        let services: Arc<dyn ProjectWorkspaceService> = Arc::new(ArchiveOkServices {
-            calls: calls.clone(),
-            cancel_flag: Some(cancel_flag.clone()),
-        });
-
-        let mut state = AppState::new();
-        let project_path = PathBuf::from("/tmp/luban-server-archive-cancel-test");
-        let _ = state.apply(Action::AddProject {
-            path: project_path.clone(),
-            is_git: true,
-        });
-        let project_id = state.projects[0].id;
-
-        let worktree_path = PathBuf::from("/tmp/luban-server-archive-cancel-test-wt");
-        let _ = state.apply(Action::WorkspaceCreated {
-            project_id,
-            workspace_name: "wt".to_owned(),
-            branch_name: "feature".to_owned(),
-            worktree_path: worktree_path.clone(),
+    async fn engine_remains_responsive_while_archive_workspace_runs() {
+        let services: Arc<dyn ProjectWorkspaceService> = Arc::new(BootstrapHangServices {
+            persisted: persisted_with_single_git_workspace(10),
+            list_threads_delay: Duration::from_millis(0),
+            archive_delay: Duration::from_secs(2),
         });
+        let (engine, _events) = Engine::start(services);
 
-        let workspace_id = state
-            .projects
-            .iter()
-            .flat_map(|p| p.workspaces.iter())
-            .find(|w| w.worktree_path == worktree_path)
-            .expect("workspace should exist")
-            .id;
-
-        state.apply(Action::CreateWorkspaceThread { workspace_id });
-        let thread_id = state
-            .active_thread_id(workspace_id)
-            .expect("active thread should exist");
-
-        let run_id = 7u64;
-        {
-            let conversation = state
-                .conversations
-                .get_mut(&(workspace_id, thread_id))
-                .expect("conversation should exist");
-            conversation.run_status = OperationStatus::Running;
-            conversation.active_run_id = Some(run_id);
-        }
-
-        let (events, _) = broadcast::channel::<WsServerMessage>(16);
-        let (tx, mut rx) = mpsc::channel::<EngineCommand>(16);
-        let mut engine = Engine {
-            state,
-            rev: 1,
-            services,
-            events,
-            tx,
-            branch_watch: BranchWatchHandle::disabled(),
-            cancel_flags: HashMap::from([(
-                (workspace_id, thread_id),
-                CancelFlagEntry {
-                    run_id,
-                    flag: cancel_flag.clone(),
-                },
-            )]),
-            pull_requests: HashMap::new(),
-            pull_requests_in_flight: HashSet::new(),
-            workspace_threads_cache: HashMap::new(),
-            auto_archive_workspaces: HashSet::new(),
-            telegram_pairing: None,
-        };
-
-        engine
-            .process_action_queue(Action::ArchiveWorkspace { workspace_id })
-            .await;
-        let cmd = tokio::time::timeout(std::time::Duration::from_secs(3), rx.recv())
+        let _ = tokio::time::timeout(Duration::from_secs(1), engine.app_snapshot())
             .await
-            .expect("timed out waiting for archive completion")
-            .expect("engine command channel closed");
-        engine.handle(cmd).await;
-
-        assert!(cancel_flag.load(Ordering::SeqCst));
-
-        let calls = calls.lock().expect("mutex poisoned");
-        assert_eq!(calls.len(), 1);
-        assert_eq!(calls[0].0, project_path);
-        assert_eq!(calls[0].1, worktree_path);
-    }
-
-    struct OpenInIdeServices {
-        opened: Arc<std::sync::Mutex<Vec<PathBuf>>>,
-        opened_with: Arc<std::sync::Mutex<Vec<(PathBuf, OpenTarget)>>>,
-    }
-
-    impl ProjectWorkspaceService for OpenInIdeServices {
-        fn load_app_state(&self) -> Result<PersistedAppState, String> {
-            Ok(PersistedAppState {
-                projects: Vec::new(),
-                sidebar_width: None,
-                terminal_pane_width: None,
-                global_zoom_percent: None,
-                appearance_theme: None,
-                appearance_ui_font: None,
-                appearance_chat_font: None,
-                appearance_code_font: None,
-                appearance_terminal_font: None,
-                agent_default_model_id: None,
-                agent_runner_default_models: HashMap::new(),
-                agent_default_thinking_effort: None,
-                agent_default_runner: None,
-                agent_amp_mode: None,
-                agent_codex_enabled: Some(true),
-                agent_amp_enabled: Some(true),
-                agent_claude_enabled: Some(true),
-                agent_droid_enabled: Some(true),
-                last_open_workspace_id: None,
-                open_button_selection: None,
-                sidebar_project_order: Vec::new(),
-                workspace_active_thread_id: HashMap::new(),
-                workspace_open_tabs: HashMap::new(),
-                workspace_archived_tabs: HashMap::new(),
-                workspace_next_thread_id: HashMap::new(),
-                workspace_chat_scroll_y10: HashMap::new(),
-                workspace_chat_scroll_anchor: HashMap::new(),
-                workspace_unread_completions: HashMap::new(),
-                workspace_thread_run_config_overrides: HashMap::new(),
-                starred_tasks: HashMap::new(),
-                task_prompt_templates: HashMap::new(),
-                telegram_enabled: None,
-                telegram_bot_token: None,
-                telegram_bot_username: None,
-                telegram_paired_chat_id: None,
-                telegram_topic_bindings: None,
+            .expect("bootstrap should complete")
+            .expect("snapshot should succeed");
+
+        engine
+            .dispatch_domain_action(Action::ArchiveWorkspace {
+                workspace_id: WorkspaceId::from_u64(10),
             })
+            .await
+            .expect("dispatch archive action");
+
+        let snap = tokio::time::timeout(Duration::from_millis(300), engine.app_snapshot())
+            .await
+            .expect("app snapshot should remain responsive during archive")
+            .expect("snapshot should succeed");
+        assert_eq!(snap.projects.len(), 1);
+    }
+    #[derive(Default)]
+    struct PageLimitRecordingServices {
+        limits: Mutex<Vec<u64>>,
+    }
+
+    impl ProjectWorkspaceService for PageLimitRecordingServices {
+        fn load_app_state(&self) -> Result<PersistedAppState, String> {
+            Err("unimplemented".to_owned())
         }
 
         fn save_app_state(&self, _snapshot: PersistedAppState) -> Result<(), String> {
-            Ok(())
+            Err("unimplemented".to_owned())
         }
 
         fn create_workspace(
@@ -8341,28 +16879,14 @@ mod tests {
             _project_path: PathBuf,
             _project_slug: String,
             _branch_name_hint: Option<String>,
+            _worktree_root: Option<PathBuf>,
+            _branch_name_template: Option<luban_domain::BranchNameTemplate>,
         ) -> Result<luban_domain::CreatedWorkspace, String> {
             Err("unimplemented".to_owned())
         }
 
-        fn open_workspace_in_ide(&self, worktree_path: PathBuf) -> Result<(), String> {
-            self.opened
-                .lock()
-                .expect("mutex poisoned")
-                .push(worktree_path);
-            Ok(())
-        }
-
-        fn open_workspace_with(
-            &self,
-            worktree_path: PathBuf,
-            target: OpenTarget,
-        ) -> Result<(), String> {
-            self.opened_with
-                .lock()
-                .expect("mutex poisoned")
-                .push((worktree_path, target));
-            Ok(())
+        fn open_workspace_in_ide(&self, _worktree_path: PathBuf) -> Result<(), String> {
+            Err("unimplemented".to_owned())
         }
 
         fn archive_workspace(
@@ -8378,6 +16902,7 @@ mod tests {
             &self,
             _worktree_path: PathBuf,
             _requested_branch_name: String,
+            _branch_name_template: Option<luban_domain::BranchNameTemplate>,
         ) -> Result<String, String> {
             Err("unimplemented".to_owned())
         }
@@ -8414,8 +16939,12 @@ mod tests {
             _workspace_name: String,
             _thread_id: u64,
             _before: Option<u64>,
-            _limit: u64,
+            limit: u64,
         ) -> Result<DomainConversationSnapshot, String> {
+            self.limits
+                .lock()
+                .expect("limits lock poisoned")
+                .push(limit);
             Err("unimplemented".to_owned())
         }
 
@@ -8462,7 +16991,7 @@ mod tests {
             _project_slug: String,
             _workspace_name: String,
         ) -> Result<Vec<ContextItem>, String> {
-            Ok(Vec::new())
+            Err("unimplemented".to_owned())
         }
 
         fn delete_context_item(
@@ -8471,7 +17000,7 @@ mod tests {
             _workspace_name: String,
             _context_id: u64,
         ) -> Result<(), String> {
-            Ok(())
+            Err("unimplemented".to_owned())
         }
 
         fn run_agent_turn_streamed(
@@ -8490,6 +17019,7 @@ mod tests {
         fn gh_pull_request_info(
             &self,
             _worktree_path: PathBuf,
+            _target: luban_domain::PullRequestTarget,
         ) -> Result<Option<PullRequestInfo>, String> {
             Err("unimplemented".to_owned())
         }
@@ -8504,27 +17034,13 @@ mod tests {
         ) -> Result<(), String> {
             Err("unimplemented".to_owned())
         }
-
-        fn project_identity(
-            &self,
-            _path: PathBuf,
-        ) -> Result<luban_domain::ProjectIdentity, String> {
-            Err("unimplemented".to_owned())
-        }
     }
 
     #[tokio::test]
-    async fn open_workspace_in_ide_runs_effect() {
-        let opened = Arc::new(std::sync::Mutex::new(Vec::<PathBuf>::new()));
-        let opened_with = Arc::new(std::sync::Mutex::new(Vec::<(PathBuf, OpenTarget)>::new()));
-        let services: Arc<dyn ProjectWorkspaceService> = Arc::new(OpenInIdeServices {
-            opened: opened.clone(),
-            opened_with: opened_with.clone(),
-        });
-
+    async fn configured_conversation_load_limits_apply_to_fallback_and_initial_load() {
         let mut state = AppState::new();
         let _ = state.apply(Action::AddProject {
-            path: PathBuf::from("/tmp/luban-server-open-ide-test"),
+            path: PathBuf::from("/tmp/luban-server-test"),
             is_git: true,
         });
         let project_id = state.projects[0].id;
@@ -8532,17 +17048,17 @@ mod tests {
             project_id,
             workspace_name: "main".to_owned(),
             branch_name: "main".to_owned(),
-            worktree_path: PathBuf::from("/tmp/luban-server-open-ide-test"),
+            worktree_path: PathBuf::from("/tmp/luban-server-test"),
         });
         let workspace_id = state.projects[0].workspaces[0].id;
-        let worktree_path = state.projects[0].workspaces[0].worktree_path.clone();
 
-        let (events, _) = broadcast::channel::<WsServerMessage>(16);
-        let (tx, _rx) = mpsc::channel::<EngineCommand>(16);
+        let services = Arc::new(PageLimitRecordingServices::default());
+        let (events, _) = broadcast::channel::<WsServerMessage>(1);
+        let (tx, _rx) = mpsc::channel::<EngineCommand>(1);
         let mut engine = Engine {
             state,
             rev: 1,
-            services,
+            services: services.clone(),
             events,
             tx,
             branch_watch: BranchWatchHandle::disabled(),
@@ -8551,29 +17067,236 @@ mod tests {
             pull_requests_in_flight: HashSet::new(),
             workspace_threads_cache: HashMap::new(),
             auto_archive_workspaces: HashSet::new(),
-            telegram_pairing: None,
+            pending_notes_flushes: HashMap::new(),
+            pending_draft_flushes: HashMap::new(),
+            conversation_revs: HashMap::new(),
+            last_git_fetch_at: HashMap::new(),
+            worktree_dirty: HashMap::new(),
+            worktree_dirty_in_flight: HashSet::new(),
+            worktree_dirty_rerun: HashSet::new(),
+            last_commits: HashMap::new(),
+            agent_turn_slots: HashMap::new(),
+            deferred_agent_turns: VecDeque::new(),
+            request_cancellations: RequestCancellations::default(),
+            attachment_thumbnails: AttachmentThumbnails::default(),
+            telegram_pairings: Vec::new(),
+            config: EngineConfig {
+                conversation_load: crate::ConversationLoadConfig {
+                    default_entries_limit: 50,
+                    max_entries_limit: 100,
+                },
+                ..EngineConfig::default()
+            },
         };
 
-        engine
-            .process_action_queue(Action::OpenWorkspaceInIde { workspace_id })
+        let api_wid = luban_api::WorkspaceId(workspace_id.as_u64());
+        let api_tid = luban_api::WorkspaceThreadId(1);
+        let _ = engine
+            .get_conversation_snapshot(api_wid, api_tid, None, None)
+            .await;
+        let _ = engine
+            .get_conversation_snapshot(api_wid, api_tid, None, Some(10_000))
+            .await;
+        let _ = engine
+            .run_effect(Effect::LoadConversation {
+                workspace_id,
+                thread_id: WorkspaceThreadId::from_u64(1),
+            })
             .await;
 
-        let opened = opened.lock().expect("mutex poisoned");
-        assert_eq!(opened.as_slice(), &[worktree_path]);
+        let limits = services
+            .limits
+            .lock()
+            .expect("limits lock poisoned")
+            .clone();
+        assert_eq!(limits, vec![50, 100, 100]);
+    }
+
+    struct StoredEntriesTotalServices {
+        stored_total: u64,
+    }
+
+    impl ProjectWorkspaceService for StoredEntriesTotalServices {
+        fn load_app_state(&self) -> Result<PersistedAppState, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn save_app_state(&self, _snapshot: PersistedAppState) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn create_workspace(
+            &self,
+            _project_path: PathBuf,
+            _project_slug: String,
+            _branch_name_hint: Option<String>,
+            _worktree_root: Option<PathBuf>,
+            _branch_name_template: Option<luban_domain::BranchNameTemplate>,
+        ) -> Result<luban_domain::CreatedWorkspace, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn open_workspace_in_ide(&self, _worktree_path: PathBuf) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn archive_workspace(
+            &self,
+            _project_path: PathBuf,
+            _worktree_path: PathBuf,
+            _branch_name: String,
+        ) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn rename_workspace_branch(
+            &self,
+            _worktree_path: PathBuf,
+            _requested_branch_name: String,
+            _branch_name_template: Option<luban_domain::BranchNameTemplate>,
+        ) -> Result<String, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn ensure_conversation(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _thread_id: u64,
+        ) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn list_conversation_threads(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+        ) -> Result<Vec<ConversationThreadMeta>, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn load_conversation(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _thread_id: u64,
+        ) -> Result<DomainConversationSnapshot, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn load_conversation_page(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _thread_id: u64,
+            _before: Option<u64>,
+            _limit: u64,
+        ) -> Result<DomainConversationSnapshot, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn conversation_entries_total(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _thread_id: u64,
+        ) -> Result<u64, String> {
+            Ok(self.stored_total)
+        }
+
+        fn store_context_image(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _image: ContextImage,
+        ) -> Result<AttachmentRef, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn store_context_text(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _text: String,
+            _extension: String,
+        ) -> Result<AttachmentRef, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn store_context_file(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _source_path: PathBuf,
+        ) -> Result<AttachmentRef, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn record_context_item(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _attachment: AttachmentRef,
+            _created_at_unix_ms: u64,
+        ) -> Result<u64, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn list_context_items(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+        ) -> Result<Vec<ContextItem>, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn delete_context_item(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _context_id: u64,
+        ) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn run_agent_turn_streamed(
+            &self,
+            _request: luban_domain::RunAgentTurnRequest,
+            _cancel: Arc<AtomicBool>,
+            _on_event: Arc<dyn Fn(luban_domain::AgentThreadEvent) + Send + Sync>,
+        ) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn gh_is_authorized(&self) -> Result<bool, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn gh_pull_request_info(
+            &self,
+            _worktree_path: PathBuf,
+            _target: luban_domain::PullRequestTarget,
+        ) -> Result<Option<PullRequestInfo>, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn gh_open_pull_request(&self, _worktree_path: PathBuf) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn gh_open_pull_request_failed_action(
+            &self,
+            _worktree_path: PathBuf,
+        ) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
     }
 
     #[tokio::test]
-    async fn open_workspace_with_runs_effect() {
-        let opened = Arc::new(std::sync::Mutex::new(Vec::<PathBuf>::new()));
-        let opened_with = Arc::new(std::sync::Mutex::new(Vec::<(PathBuf, OpenTarget)>::new()));
-        let services: Arc<dyn ProjectWorkspaceService> = Arc::new(OpenInIdeServices {
-            opened: opened.clone(),
-            opened_with: opened_with.clone(),
-        });
-
+    async fn live_conversation_updates_report_stored_entries_total() {
         let mut state = AppState::new();
         let _ = state.apply(Action::AddProject {
-            path: PathBuf::from("/tmp/luban-server-open-with-test"),
+            path: PathBuf::from("/tmp/luban-server-test"),
             is_git: true,
         });
         let project_id = state.projects[0].id;
@@ -8581,17 +17304,33 @@ mod tests {
             project_id,
             workspace_name: "main".to_owned(),
             branch_name: "main".to_owned(),
-            worktree_path: PathBuf::from("/tmp/luban-server-open-with-test"),
+            worktree_path: PathBuf::from("/tmp/luban-server-test"),
         });
         let workspace_id = state.projects[0].workspaces[0].id;
-        let worktree_path = state.projects[0].workspaces[0].worktree_path.clone();
+        let _ = state.apply(Action::OpenWorkspace { workspace_id });
+        let _ = state.apply(Action::CreateWorkspaceThread { workspace_id });
+        let thread_id = state
+            .workspace_tabs(workspace_id)
+            .and_then(|tabs| tabs.open_tabs.last().copied())
+            .expect("thread should exist");
 
-        let (events, _) = broadcast::channel::<WsServerMessage>(16);
-        let (tx, _rx) = mpsc::channel::<EngineCommand>(16);
+        // Only the tail of a 5100-entry history is in memory, as when a conversation starts
+        // streaming before its history page is loaded.
+        let stored_total = 5100u64;
+        let in_memory = state
+            .conversations
+            .get(&(workspace_id, thread_id))
+            .expect("conversation should exist")
+            .entries
+            .len() as u64;
+        assert!(in_memory < stored_total);
+
+        let (events, mut rx) = broadcast::channel::<WsServerMessage>(16);
+        let (tx, _rx_cmd) = mpsc::channel::<EngineCommand>(16);
         let mut engine = Engine {
             state,
             rev: 1,
-            services,
+            services: Arc::new(StoredEntriesTotalServices { stored_total }),
             events,
             tx,
             branch_watch: BranchWatchHandle::disabled(),
@@ -8600,71 +17339,62 @@ mod tests {
             pull_requests_in_flight: HashSet::new(),
             workspace_threads_cache: HashMap::new(),
             auto_archive_workspaces: HashSet::new(),
-            telegram_pairing: None,
+            pending_notes_flushes: HashMap::new(),
+            pending_draft_flushes: HashMap::new(),
+            conversation_revs: HashMap::new(),
+            last_git_fetch_at: HashMap::new(),
+            worktree_dirty: HashMap::new(),
+            worktree_dirty_in_flight: HashSet::new(),
+            worktree_dirty_rerun: HashSet::new(),
+            last_commits: HashMap::new(),
+            agent_turn_slots: HashMap::new(),
+            deferred_agent_turns: VecDeque::new(),
+            request_cancellations: RequestCancellations::default(),
+            attachment_thumbnails: AttachmentThumbnails::default(),
+            telegram_pairings: Vec::new(),
+            config: EngineConfig::default(),
         };
 
-        engine
-            .process_action_queue(Action::OpenWorkspaceWith {
-                workspace_id,
-                target: OpenTarget::Vscode,
-            })
-            .await;
+        for task_status in [
+            luban_domain::TaskStatus::Iterating,
+            luban_domain::TaskStatus::Validating,
+        ] {
+            engine
+                .process_action_queue(Action::TaskStatusSet {
+                    workspace_id,
+                    thread_id,
+                    task_status,
+                })
+                .await;
 
-        let opened_with = opened_with.lock().expect("mutex poisoned");
-        assert_eq!(
-            opened_with.as_slice(),
-            &[(worktree_path, OpenTarget::Vscode)]
-        );
+            let snapshot = loop {
+                let msg = rx.try_recv().expect("conversation should be published");
+                if let WsServerMessage::Event { event, .. } = msg
+                    && let luban_api::ServerEvent::ConversationChanged { snapshot, .. } = *event
+                {
+                    break snapshot;
+                }
+            };
+            assert_eq!(snapshot.entries_total, stored_total);
+            assert!(snapshot.entries_truncated);
+            assert!(
+                snapshot.entries_start + (snapshot.entries.len() as u64) < snapshot.entries_total
+            );
+        }
     }
 
-    struct CaptureRunAgentTurnServices {
-        sender: std::sync::mpsc::Sender<luban_domain::RunAgentTurnRequest>,
+    #[derive(Default)]
+    struct GitFetchRecordingServices {
+        calls: Mutex<Vec<&'static str>>,
     }
 
-    impl ProjectWorkspaceService for CaptureRunAgentTurnServices {
+    impl ProjectWorkspaceService for GitFetchRecordingServices {
         fn load_app_state(&self) -> Result<PersistedAppState, String> {
-            Ok(PersistedAppState {
-                projects: Vec::new(),
-                sidebar_width: None,
-                terminal_pane_width: None,
-                global_zoom_percent: None,
-                appearance_theme: None,
-                appearance_ui_font: None,
-                appearance_chat_font: None,
-                appearance_code_font: None,
-                appearance_terminal_font: None,
-                agent_default_model_id: None,
-                agent_runner_default_models: HashMap::new(),
-                agent_default_thinking_effort: None,
-                agent_default_runner: None,
-                agent_amp_mode: None,
-                agent_codex_enabled: Some(true),
-                agent_amp_enabled: Some(true),
-                agent_claude_enabled: Some(true),
-                agent_droid_enabled: Some(true),
-                last_open_workspace_id: None,
-                open_button_selection: None,
-                sidebar_project_order: Vec::new(),
-                workspace_active_thread_id: HashMap::new(),
-                workspace_open_tabs: HashMap::new(),
-                workspace_archived_tabs: HashMap::new(),
-                workspace_next_thread_id: HashMap::new(),
-                workspace_chat_scroll_y10: HashMap::new(),
-                workspace_chat_scroll_anchor: HashMap::new(),
-                workspace_unread_completions: HashMap::new(),
-                workspace_thread_run_config_overrides: HashMap::new(),
-                starred_tasks: HashMap::new(),
-                task_prompt_templates: HashMap::new(),
-                telegram_enabled: None,
-                telegram_bot_token: None,
-                telegram_bot_username: None,
-                telegram_paired_chat_id: None,
-                telegram_topic_bindings: None,
-            })
+            Err("unimplemented".to_owned())
         }
 
         fn save_app_state(&self, _snapshot: PersistedAppState) -> Result<(), String> {
-            Ok(())
+            Err("unimplemented".to_owned())
         }
 
         fn create_workspace(
@@ -8672,6 +17402,8 @@ mod tests {
             _project_path: PathBuf,
             _project_slug: String,
             _branch_name_hint: Option<String>,
+            _worktree_root: Option<PathBuf>,
+            _branch_name_template: Option<luban_domain::BranchNameTemplate>,
         ) -> Result<luban_domain::CreatedWorkspace, String> {
             Err("unimplemented".to_owned())
         }
@@ -8693,6 +17425,7 @@ mod tests {
             &self,
             _worktree_path: PathBuf,
             _requested_branch_name: String,
+            _branch_name_template: Option<luban_domain::BranchNameTemplate>,
         ) -> Result<String, String> {
             Err("unimplemented".to_owned())
         }
@@ -8777,7 +17510,7 @@ mod tests {
             _project_slug: String,
             _workspace_name: String,
         ) -> Result<Vec<ContextItem>, String> {
-            Ok(Vec::new())
+            Err("unimplemented".to_owned())
         }
 
         fn delete_context_item(
@@ -8786,16 +17519,23 @@ mod tests {
             _workspace_name: String,
             _context_id: u64,
         ) -> Result<(), String> {
-            Ok(())
+            Err("unimplemented".to_owned())
         }
 
         fn run_agent_turn_streamed(
             &self,
-            request: luban_domain::RunAgentTurnRequest,
+            _request: luban_domain::RunAgentTurnRequest,
             _cancel: Arc<AtomicBool>,
             _on_event: Arc<dyn Fn(luban_domain::AgentThreadEvent) + Send + Sync>,
         ) -> Result<(), String> {
-            let _ = self.sender.send(request);
+            Err("unimplemented".to_owned())
+        }
+
+        fn git_fetch(&self, _worktree_path: PathBuf) -> Result<(), String> {
+            self.calls
+                .lock()
+                .expect("calls lock poisoned")
+                .push("fetch");
             Ok(())
         }
 
@@ -8806,8 +17546,10 @@ mod tests {
         fn gh_pull_request_info(
             &self,
             _worktree_path: PathBuf,
+            _target: luban_domain::PullRequestTarget,
         ) -> Result<Option<PullRequestInfo>, String> {
-            Err("unimplemented".to_owned())
+            self.calls.lock().expect("calls lock poisoned").push("pr");
+            Ok(None)
         }
 
         fn gh_open_pull_request(&self, _worktree_path: PathBuf) -> Result<(), String> {
@@ -8820,20 +17562,151 @@ mod tests {
         ) -> Result<(), String> {
             Err("unimplemented".to_owned())
         }
+    }
 
-        fn project_identity(
-            &self,
-            _path: PathBuf,
-        ) -> Result<luban_domain::ProjectIdentity, String> {
-            Err("unimplemented".to_owned())
+    #[tokio::test]
+    async fn pull_request_refresh_fetches_first_only_when_enabled_and_rate_limited() {
+        async fn refresh_calls(
+            fetch_before_pull_request_refresh: bool,
+            refreshes: usize,
+        ) -> Vec<&'static str> {
+            let mut state = AppState::new();
+            let _ = state.apply(Action::AddProject {
+                path: PathBuf::from("/tmp/luban-server-test"),
+                is_git: true,
+            });
+            let project_id = state.projects[0].id;
+            let _ = state.apply(Action::WorkspaceCreated {
+                project_id,
+                workspace_name: "main".to_owned(),
+                branch_name: "main".to_owned(),
+                worktree_path: PathBuf::from("/tmp/luban-server-test"),
+            });
+            let workspace_id = state.projects[0].workspaces[0].id;
+
+            let services = Arc::new(GitFetchRecordingServices::default());
+            let (events, _) = broadcast::channel::<WsServerMessage>(1);
+            let (tx, mut rx) = mpsc::channel::<EngineCommand>(4);
+            let mut engine = Engine {
+                state,
+                rev: 1,
+                services: services.clone(),
+                events,
+                tx,
+                branch_watch: BranchWatchHandle::disabled(),
+                cancel_flags: HashMap::new(),
+                pull_requests: HashMap::new(),
+                pull_requests_in_flight: HashSet::new(),
+                workspace_threads_cache: HashMap::new(),
+                auto_archive_workspaces: HashSet::new(),
+                pending_notes_flushes: HashMap::new(),
+                pending_draft_flushes: HashMap::new(),
+                conversation_revs: HashMap::new(),
+                last_git_fetch_at: HashMap::new(),
+                worktree_dirty: HashMap::new(),
+                worktree_dirty_in_flight: HashSet::new(),
+                worktree_dirty_rerun: HashSet::new(),
+                last_commits: HashMap::new(),
+                agent_turn_slots: HashMap::new(),
+                deferred_agent_turns: VecDeque::new(),
+                request_cancellations: RequestCancellations::default(),
+                attachment_thumbnails: AttachmentThumbnails::default(),
+                telegram_pairings: Vec::new(),
+                config: EngineConfig {
+                    fetch_before_pull_request_refresh,
+                    ..EngineConfig::default()
+                },
+            };
+
+            for _ in 0..refreshes {
+                engine.start_pull_request_refresh(workspace_id);
+                let cmd = tokio::time::timeout(Duration::from_secs(2), rx.recv())
+                    .await
+                    .expect("timed out waiting for pull request refresh")
+                    .expect("engine channel closed");
+                assert!(matches!(
+                    cmd,
+                    EngineCommand::PullRequestInfoUpdated { info: Ok(None), .. }
+                ));
+            }
+
+            services.calls.lock().expect("calls lock poisoned").clone()
         }
+
+        assert_eq!(refresh_calls(true, 2).await, vec!["fetch", "pr", "pr"]);
+        assert_eq!(refresh_calls(false, 1).await, vec!["pr"]);
     }
 
-    struct SlowRenameServices {
-        delay: Duration,
+    #[tokio::test]
+    async fn load_app_state_seeds_project_only_when_no_projects_persisted() {
+        let seed_path = PathBuf::from("/tmp/luban-seed-cwd-project");
+        let new_engine = |services: Arc<dyn ProjectWorkspaceService>| {
+            let (events, _) = broadcast::channel::<WsServerMessage>(1);
+            let (tx, _rx) = mpsc::channel::<EngineCommand>(1);
+            Engine {
+                state: AppState::new(),
+                rev: 1,
+                services,
+                events,
+                tx,
+                branch_watch: BranchWatchHandle::disabled(),
+                cancel_flags: HashMap::new(),
+                pull_requests: HashMap::new(),
+                pull_requests_in_flight: HashSet::new(),
+                workspace_threads_cache: HashMap::new(),
+                auto_archive_workspaces: HashSet::new(),
+                pending_notes_flushes: HashMap::new(),
+                pending_draft_flushes: HashMap::new(),
+                conversation_revs: HashMap::new(),
+                last_git_fetch_at: HashMap::new(),
+                worktree_dirty: HashMap::new(),
+                worktree_dirty_in_flight: HashSet::new(),
+                worktree_dirty_rerun: HashSet::new(),
+                last_commits: HashMap::new(),
+                agent_turn_slots: HashMap::new(),
+                deferred_agent_turns: VecDeque::new(),
+                request_cancellations: RequestCancellations::default(),
+                attachment_thumbnails: AttachmentThumbnails::default(),
+                telegram_pairings: Vec::new(),
+                config: EngineConfig {
+                    seed_project_path: Some(seed_path.clone()),
+                    ..EngineConfig::default()
+                },
+            }
+        };
+
+        let mut engine = new_engine(Arc::new(IdentityServices));
+        let actions = engine
+            .run_effect(Effect::LoadAppState)
+            .await
+            .expect("load app state");
+        assert_eq!(actions.len(), 2);
+        for action in actions {
+            let _ = engine.state.apply(action);
+        }
+        assert_eq!(engine.state.projects.len(), 1);
+        assert_eq!(engine.state.projects[0].path, seed_path);
+        assert!(engine.state.projects[0].is_git);
+
+        let mut engine = new_engine(Arc::new(BootstrapHangServices {
+            persisted: persisted_with_single_git_workspace(10),
+            list_threads_delay: Duration::from_millis(0),
+            archive_delay: Duration::from_millis(0),
+        }));
+        let actions = engine
+            .run_effect(Effect::LoadAppState)
+            .await
+            .expect("load app state");
+        assert_eq!(actions.len(), 1);
+        assert!(matches!(actions[0], Action::AppStateLoaded { .. }));
     }
 
-    impl ProjectWorkspaceService for SlowRenameServices {
+    struct SlowConfigReadServices {
+        started: Mutex<std::sync::mpsc::Sender<()>>,
+        release: Mutex<std::sync::mpsc::Receiver<()>>,
+    }
+
+    impl ProjectWorkspaceService for SlowConfigReadServices {
         fn load_app_state(&self) -> Result<PersistedAppState, String> {
             Ok(PersistedAppState {
                 projects: Vec::new(),
@@ -8847,6 +17720,9 @@ mod tests {
                 appearance_terminal_font: None,
                 agent_default_model_id: None,
                 agent_runner_default_models: HashMap::new(),
+                agent_runner_prompt_prefixes: HashMap::new(),
+                agent_runner_prompt_suffixes: HashMap::new(),
+                model_thinking_effort_caps: HashMap::new(),
                 agent_default_thinking_effort: None,
                 agent_default_runner: None,
                 agent_amp_mode: None,
@@ -8857,6 +17733,7 @@ mod tests {
                 last_open_workspace_id: None,
                 open_button_selection: None,
                 sidebar_project_order: Vec::new(),
+                project_groups: Vec::new(),
                 workspace_active_thread_id: HashMap::new(),
                 workspace_open_tabs: HashMap::new(),
                 workspace_archived_tabs: HashMap::new(),
@@ -8870,8 +17747,16 @@ mod tests {
                 telegram_enabled: None,
                 telegram_bot_token: None,
                 telegram_bot_username: None,
-                telegram_paired_chat_id: None,
+                telegram_paired_chat_ids: Vec::new(),
                 telegram_topic_bindings: None,
+                webhook_url: None,
+                webhook_secret: None,
+                agent_merge_reasoning_entries: None,
+                agent_pause_queue_on_patch_failure: None,
+                agent_item_dedup_window: None,
+                agent_auto_retry_on_failure: None,
+                agent_command_output_max_bytes: None,
+                auto_archive_after_days: None,
             })
         }
 
@@ -8884,6 +17769,8 @@ mod tests {
             _project_path: PathBuf,
             _project_slug: String,
             _branch_name_hint: Option<String>,
+            _worktree_root: Option<PathBuf>,
+            _branch_name_template: Option<luban_domain::BranchNameTemplate>,
         ) -> Result<luban_domain::CreatedWorkspace, String> {
             Err("unimplemented".to_owned())
         }
@@ -8904,10 +17791,10 @@ mod tests {
         fn rename_workspace_branch(
             &self,
             _worktree_path: PathBuf,
-            requested_branch_name: String,
+            _requested_branch_name: String,
+            _branch_name_template: Option<luban_domain::BranchNameTemplate>,
         ) -> Result<String, String> {
-            std::thread::sleep(self.delay);
-            Ok(requested_branch_name)
+            Err("unimplemented".to_owned())
         }
 
         fn ensure_conversation(
@@ -8990,455 +17877,441 @@ mod tests {
             _project_slug: String,
             _workspace_name: String,
         ) -> Result<Vec<ContextItem>, String> {
-            Err("unimplemented".to_owned())
-        }
-
-        fn delete_context_item(
-            &self,
-            _project_slug: String,
-            _workspace_name: String,
-            _context_id: u64,
-        ) -> Result<(), String> {
-            Err("unimplemented".to_owned())
-        }
-
-        fn run_agent_turn_streamed(
-            &self,
-            _request: luban_domain::RunAgentTurnRequest,
-            _cancel: Arc<AtomicBool>,
-            _on_event: Arc<dyn Fn(luban_domain::AgentThreadEvent) + Send + Sync>,
-        ) -> Result<(), String> {
-            Err("unimplemented".to_owned())
-        }
-
-        fn gh_is_authorized(&self) -> Result<bool, String> {
-            Err("unimplemented".to_owned())
-        }
-
-        fn gh_pull_request_info(
-            &self,
-            _worktree_path: PathBuf,
-        ) -> Result<Option<PullRequestInfo>, String> {
-            Err("unimplemented".to_owned())
-        }
-
-        fn gh_open_pull_request(&self, _worktree_path: PathBuf) -> Result<(), String> {
-            Err("unimplemented".to_owned())
-        }
-
-        fn gh_open_pull_request_failed_action(
-            &self,
-            _worktree_path: PathBuf,
-        ) -> Result<(), String> {
-            Err("unimplemented".to_owned())
-        }
-
-        fn project_identity(
-            &self,
-            _path: PathBuf,
-        ) -> Result<luban_domain::ProjectIdentity, String> {
-            Err("unimplemented".to_owned())
-        }
-    }
-
-    #[tokio::test]
-    async fn workspace_branch_rename_does_not_block_engine() {
-        let services: Arc<dyn ProjectWorkspaceService> = Arc::new(SlowRenameServices {
-            delay: Duration::from_secs(2),
-        });
-
-        let mut state = AppState::new();
-        let _ = state.apply(Action::AddProject {
-            path: PathBuf::from("/tmp/luban-server-rename-test"),
-            is_git: true,
-        });
-        let project_id = state.projects[0].id;
-        let _ = state.apply(Action::WorkspaceCreated {
-            project_id,
-            workspace_name: "w1".to_owned(),
-            branch_name: "luban/w1".to_owned(),
-            worktree_path: PathBuf::from("/tmp/luban-server-rename-test"),
-        });
-        let workspace_id = state.projects[0].workspaces[0].id;
-
-        let (events, _) = broadcast::channel::<WsServerMessage>(16);
-        let (tx, mut rx) = mpsc::channel::<EngineCommand>(16);
-        let mut engine = Engine {
-            state,
-            rev: 1,
-            services,
-            events,
-            tx: tx.clone(),
-            branch_watch: BranchWatchHandle::disabled(),
-            cancel_flags: HashMap::new(),
-            pull_requests: HashMap::new(),
-            pull_requests_in_flight: HashSet::new(),
-            workspace_threads_cache: HashMap::new(),
-            auto_archive_workspaces: HashSet::new(),
-            telegram_pairing: None,
-        };
-
-        let rename = tokio::time::timeout(
-            Duration::from_millis(200),
-            engine.process_action_queue(Action::WorkspaceBranchRenameRequested {
-                workspace_id,
-                requested_branch_name: "luban/rename-test".to_owned(),
-            }),
-        )
-        .await;
-        assert!(rename.is_ok(), "rename action should not block");
-
-        // Drain the dispatch action so the spawned task does not leak.
-        let _ = tokio::time::timeout(Duration::from_secs(5), async {
-            while let Some(cmd) = rx.recv().await {
-                if let EngineCommand::DispatchAction { action } = cmd {
-                    engine.process_action_queue(*action).await;
-                    break;
-                }
-            }
-        })
-        .await;
-    }
-
-    #[tokio::test]
-    async fn agent_turn_does_not_override_codex_defaults() {
-        let (sender, receiver) = std::sync::mpsc::channel::<luban_domain::RunAgentTurnRequest>();
-        let services: Arc<dyn ProjectWorkspaceService> =
-            Arc::new(CaptureRunAgentTurnServices { sender });
+            Ok(Vec::new())
+        }
 
-        let mut state = AppState::new();
-        let _ = state.apply(Action::AddProject {
-            path: PathBuf::from("/tmp/luban-server-agent-turn-test"),
-            is_git: true,
-        });
-        let project_id = state.projects[0].id;
-        let _ = state.apply(Action::WorkspaceCreated {
-            project_id,
-            workspace_name: "main".to_owned(),
-            branch_name: "main".to_owned(),
-            worktree_path: PathBuf::from("/tmp/luban-server-agent-turn-test"),
-        });
+        fn delete_context_item(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _context_id: u64,
+        ) -> Result<(), String> {
+            Ok(())
+        }
 
-        let workspace_id = state.projects[0].workspaces[0].id;
-        let thread_id = WorkspaceThreadId::from_u64(1);
+        fn run_agent_turn_streamed(
+            &self,
+            _request: luban_domain::RunAgentTurnRequest,
+            _cancel: Arc<AtomicBool>,
+            _on_event: Arc<dyn Fn(luban_domain::AgentThreadEvent) + Send + Sync>,
+        ) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
 
-        let _ = state.apply(Action::ChatModelChanged {
-            workspace_id,
-            thread_id,
-            model_id: "not-a-real-model".to_owned(),
-        });
+        fn gh_is_authorized(&self) -> Result<bool, String> {
+            Err("unimplemented".to_owned())
+        }
 
-        let (events, _) = broadcast::channel::<WsServerMessage>(16);
-        let (tx, _rx) = mpsc::channel::<EngineCommand>(16);
-        let mut engine = Engine {
-            state,
-            rev: 1,
-            services,
-            events,
-            tx,
-            branch_watch: BranchWatchHandle::disabled(),
-            cancel_flags: HashMap::new(),
-            pull_requests: HashMap::new(),
-            pull_requests_in_flight: HashSet::new(),
-            workspace_threads_cache: HashMap::new(),
-            auto_archive_workspaces: HashSet::new(),
-            telegram_pairing: None,
-        };
+        fn gh_pull_request_info(
+            &self,
+            _worktree_path: PathBuf,
+            _target: luban_domain::PullRequestTarget,
+        ) -> Result<Option<PullRequestInfo>, String> {
+            Err("unimplemented".to_owned())
+        }
 
-        engine
-            .process_action_queue(Action::SendAgentMessage {
-                workspace_id,
-                thread_id,
-                text: "hello".to_owned(),
-                attachments: Vec::new(),
-                runner: None,
-                amp_mode: None,
-            })
-            .await;
+        fn gh_open_pull_request(&self, _worktree_path: PathBuf) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
 
-        let request = receiver
-            .recv_timeout(std::time::Duration::from_secs(2))
-            .expect("expected agent turn request");
+        fn gh_open_pull_request_failed_action(
+            &self,
+            _worktree_path: PathBuf,
+        ) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
 
-        assert_eq!(request.runner, luban_domain::AgentRunnerKind::Codex);
-        assert!(request.amp_mode.is_none());
-        assert_eq!(request.model.as_deref(), Some("not-a-real-model"));
-        assert_eq!(request.model_reasoning_effort.as_deref(), Some("medium"));
+        fn codex_config_read_file(&self, path: String) -> Result<String, String> {
+            if path != "slow.toml" {
+                return Err("unimplemented".to_owned());
+            }
+            let _ = self.started.lock().expect("started lock poisoned").send(());
+            self.release
+                .lock()
+                .expect("release lock poisoned")
+                .recv()
+                .map_err(|_| "release channel closed".to_owned())?;
+            Ok(format!("contents of {path}"))
+        }
     }
 
     #[tokio::test]
-    async fn task_execute_start_passes_attachments_to_agent_turn() {
-        let (sender, receiver) = std::sync::mpsc::channel::<luban_domain::RunAgentTurnRequest>();
-        let services: Arc<dyn ProjectWorkspaceService> =
-            Arc::new(CaptureRunAgentTurnServices { sender });
-
-        let mut state = AppState::new();
-        let _ = state.apply(Action::AddProject {
-            path: PathBuf::from("/tmp/luban-server-task-execute-attachments-test"),
-            is_git: true,
-        });
-        let project_id = state.projects[0].id;
-        let _ = state.apply(Action::WorkspaceCreated {
-            project_id,
-            workspace_name: "main".to_owned(),
-            branch_name: "main".to_owned(),
-            worktree_path: PathBuf::from("/tmp/luban-server-task-execute-attachments-test"),
+    async fn canceled_config_read_does_not_emit_ready_event() {
+        let (started_tx, started_rx) = std::sync::mpsc::channel();
+        let (release_tx, release_rx) = std::sync::mpsc::channel();
+        let services: Arc<dyn ProjectWorkspaceService> = Arc::new(SlowConfigReadServices {
+            started: Mutex::new(started_tx),
+            release: Mutex::new(release_rx),
         });
+        let (engine, events) = Engine::start(services);
+        let mut rx = events.subscribe();
 
-        let workspace_id = state.projects[0].workspaces[0].id;
-
-        let (events, _) = broadcast::channel::<WsServerMessage>(16);
-        let (tx, _rx) = mpsc::channel::<EngineCommand>(16);
-        let mut engine = Engine {
-            state,
-            rev: 1,
-            services,
-            events,
-            tx,
-            branch_watch: BranchWatchHandle::disabled(),
-            cancel_flags: HashMap::new(),
-            pull_requests: HashMap::new(),
-            pull_requests_in_flight: HashSet::new(),
-            workspace_threads_cache: HashMap::new(),
-            auto_archive_workspaces: HashSet::new(),
-            telegram_pairing: None,
-        };
-
-        let api_attachment = luban_api::AttachmentRef {
-            id: "att-test-1".to_owned(),
-            kind: luban_api::AttachmentKind::Image,
-            name: "screenshot.png".to_owned(),
-            extension: "png".to_owned(),
-            mime: Some("image/png".to_owned()),
-            byte_len: 123,
+        let ready_request_ids = |rx: &mut broadcast::Receiver<WsServerMessage>| {
+            let mut out = Vec::new();
+            loop {
+                let msg = match rx.try_recv() {
+                    Ok(msg) => msg,
+                    Err(broadcast::error::TryRecvError::Lagged(_)) => continue,
+                    Err(_) => break,
+                };
+                if let WsServerMessage::Event { event, .. } = msg
+                    && let luban_api::ServerEvent::CodexConfigFileReady { request_id, .. } = *event
+                {
+                    out.push(request_id);
+                }
+            }
+            out
         };
 
-        let _ = engine
-            .execute_task_prompt(
-                "hello".to_owned(),
-                luban_api::TaskExecuteMode::Start,
-                Some(luban_api::WorkspaceId(workspace_id.as_u64())),
-                vec![api_attachment.clone()],
+        release_tx.send(()).expect("release first read");
+        engine
+            .apply_client_action(
+                "read-kept".to_owned(),
+                luban_api::ClientAction::CodexConfigReadFile {
+                    path: "slow.toml".to_owned(),
+                },
             )
             .await
-            .expect("task execute prompt should succeed");
+            .expect("apply read action");
+        started_rx
+            .recv_timeout(Duration::from_secs(2))
+            .expect("first read should start");
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(ready_request_ids(&mut rx), vec!["read-kept".to_owned()]);
 
-        let request = receiver
-            .recv_timeout(std::time::Duration::from_secs(2))
-            .expect("expected agent turn request");
+        engine
+            .apply_client_action(
+                "read-canceled".to_owned(),
+                luban_api::ClientAction::CodexConfigReadFile {
+                    path: "slow.toml".to_owned(),
+                },
+            )
+            .await
+            .expect("apply read action");
+        started_rx
+            .recv_timeout(Duration::from_secs(2))
+            .expect("second read should start");
+        engine
+            .apply_client_action(
+                "cancel".to_owned(),
+                luban_api::ClientAction::CancelRequest {
+                    request_id: "read-canceled".to_owned(),
+                },
+            )
+            .await
+            .expect("apply cancel action");
+        release_tx.send(()).expect("release second read");
+        tokio::time::sleep(Duration::from_millis(200)).await;
 
-        assert_eq!(request.attachments.len(), 1);
-        assert_eq!(request.attachments[0].id, api_attachment.id);
-        assert_eq!(request.attachments[0].name, api_attachment.name);
-        assert_eq!(request.attachments[0].extension, api_attachment.extension);
-        assert_eq!(request.attachments[0].mime, api_attachment.mime);
-        assert_eq!(request.attachments[0].byte_len, api_attachment.byte_len);
-        assert_eq!(
-            request.attachments[0].kind,
-            luban_domain::AttachmentKind::Image
+        assert!(
+            ready_request_ids(&mut rx).is_empty(),
+            "canceled read must not emit CodexConfigFileReady"
         );
     }
 
-    #[tokio::test]
-    async fn agent_turn_uses_pinned_chat_runner_and_amp_mode() {
-        let (sender, receiver) = std::sync::mpsc::channel::<luban_domain::RunAgentTurnRequest>();
-        let services: Arc<dyn ProjectWorkspaceService> =
-            Arc::new(CaptureRunAgentTurnServices { sender });
+    struct PrDescriptionServices {
+        inputs: std::sync::Mutex<Vec<String>>,
+    }
 
-        let mut state = AppState::new();
-        let _ = state.apply(Action::AddProject {
-            path: PathBuf::from("/tmp/luban-server-pinned-run-config-test"),
-            is_git: true,
-        });
-        let project_id = state.projects[0].id;
-        let _ = state.apply(Action::WorkspaceCreated {
-            project_id,
-            workspace_name: "main".to_owned(),
-            branch_name: "main".to_owned(),
-            worktree_path: PathBuf::from("/tmp/luban-server-pinned-run-config-test"),
-        });
+    impl ProjectWorkspaceService for PrDescriptionServices {
+        fn load_app_state(&self) -> Result<PersistedAppState, String> {
+            Ok(PersistedAppState {
+                projects: Vec::new(),
+                sidebar_width: None,
+                terminal_pane_width: None,
+                global_zoom_percent: None,
+                appearance_theme: None,
+                appearance_ui_font: None,
+                appearance_chat_font: None,
+                appearance_code_font: None,
+                appearance_terminal_font: None,
+                agent_default_model_id: None,
+                agent_runner_default_models: HashMap::new(),
+                agent_runner_prompt_prefixes: HashMap::new(),
+                agent_runner_prompt_suffixes: HashMap::new(),
+                model_thinking_effort_caps: HashMap::new(),
+                agent_default_thinking_effort: None,
+                agent_default_runner: None,
+                agent_amp_mode: None,
+                agent_codex_enabled: Some(true),
+                agent_amp_enabled: Some(true),
+                agent_claude_enabled: Some(true),
+                agent_droid_enabled: Some(true),
+                last_open_workspace_id: None,
+                open_button_selection: None,
+                sidebar_project_order: Vec::new(),
+                project_groups: Vec::new(),
+                workspace_active_thread_id: HashMap::new(),
+                workspace_open_tabs: HashMap::new(),
+                workspace_archived_tabs: HashMap::new(),
+                workspace_next_thread_id: HashMap::new(),
+                workspace_chat_scroll_y10: HashMap::new(),
+                workspace_chat_scroll_anchor: HashMap::new(),
+                workspace_unread_completions: HashMap::new(),
+                workspace_thread_run_config_overrides: HashMap::new(),
+                starred_tasks: HashMap::new(),
+                task_prompt_templates: HashMap::new(),
+                telegram_enabled: None,
+                telegram_bot_token: None,
+                telegram_bot_username: None,
+                telegram_paired_chat_ids: Vec::new(),
+                telegram_topic_bindings: None,
+                webhook_url: None,
+                webhook_secret: None,
+                agent_merge_reasoning_entries: None,
+                agent_pause_queue_on_patch_failure: None,
+                agent_item_dedup_window: None,
+                agent_auto_retry_on_failure: None,
+                agent_command_output_max_bytes: None,
+                auto_archive_after_days: None,
+            })
+        }
+
+        fn save_app_state(&self, _snapshot: PersistedAppState) -> Result<(), String> {
+            Ok(())
+        }
+
+        fn create_workspace(
+            &self,
+            _project_path: PathBuf,
+            _project_slug: String,
+            _branch_name_hint: Option<String>,
+            _worktree_root: Option<PathBuf>,
+            _branch_name_template: Option<luban_domain::BranchNameTemplate>,
+        ) -> Result<luban_domain::CreatedWorkspace, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn open_workspace_in_ide(&self, _worktree_path: PathBuf) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn archive_workspace(
+            &self,
+            _project_path: PathBuf,
+            _worktree_path: PathBuf,
+            _branch_name: String,
+        ) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn rename_workspace_branch(
+            &self,
+            _worktree_path: PathBuf,
+            _requested_branch_name: String,
+            _branch_name_template: Option<luban_domain::BranchNameTemplate>,
+        ) -> Result<String, String> {
+            Err("unimplemented".to_owned())
+        }
 
-        let workspace_id = state.projects[0].workspaces[0].id;
-        let thread_id = WorkspaceThreadId::from_u64(1);
+        fn ensure_conversation(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _thread_id: u64,
+        ) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
 
-        let (events, _) = broadcast::channel::<WsServerMessage>(16);
-        let (tx, _rx) = mpsc::channel::<EngineCommand>(16);
-        let mut engine = Engine {
-            state,
-            rev: 1,
-            services,
-            events,
-            tx,
-            branch_watch: BranchWatchHandle::disabled(),
-            cancel_flags: HashMap::new(),
-            pull_requests: HashMap::new(),
-            pull_requests_in_flight: HashSet::new(),
-            workspace_threads_cache: HashMap::new(),
-            auto_archive_workspaces: HashSet::new(),
-            telegram_pairing: None,
-        };
+        fn list_conversation_threads(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+        ) -> Result<Vec<ConversationThreadMeta>, String> {
+            Err("unimplemented".to_owned())
+        }
 
-        engine
-            .process_action_queue(Action::ChatRunnerChanged {
-                workspace_id,
-                thread_id,
-                runner: luban_domain::AgentRunnerKind::Amp,
-            })
-            .await;
+        fn load_conversation(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _thread_id: u64,
+        ) -> Result<DomainConversationSnapshot, String> {
+            Err("unimplemented".to_owned())
+        }
 
-        engine
-            .process_action_queue(Action::ChatAmpModeChanged {
-                workspace_id,
-                thread_id,
-                amp_mode: "rush".to_owned(),
-            })
-            .await;
+        fn load_conversation_page(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _thread_id: u64,
+            _before: Option<u64>,
+            _limit: u64,
+        ) -> Result<DomainConversationSnapshot, String> {
+            Err("unimplemented".to_owned())
+        }
 
-        engine
-            .process_action_queue(Action::SendAgentMessage {
-                workspace_id,
-                thread_id,
-                text: "hello".to_owned(),
-                attachments: Vec::new(),
-                runner: None,
-                amp_mode: None,
-            })
-            .await;
+        fn store_context_image(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _image: ContextImage,
+        ) -> Result<AttachmentRef, String> {
+            Err("unimplemented".to_owned())
+        }
 
-        let request = receiver
-            .recv_timeout(std::time::Duration::from_secs(2))
-            .expect("expected agent turn request");
+        fn store_context_text(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _text: String,
+            _extension: String,
+        ) -> Result<AttachmentRef, String> {
+            Err("unimplemented".to_owned())
+        }
 
-        assert_eq!(request.runner, luban_domain::AgentRunnerKind::Amp);
-        assert_eq!(request.amp_mode.as_deref(), Some("rush"));
-    }
+        fn store_context_file(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _source_path: PathBuf,
+        ) -> Result<AttachmentRef, String> {
+            Err("unimplemented".to_owned())
+        }
 
-    #[tokio::test]
-    async fn reconcile_stale_running_turns_appends_error_and_sets_finished_at() {
-        let services: Arc<ReconcileRecordingServices> =
-            Arc::new(ReconcileRecordingServices::default());
-        let services_dyn: Arc<dyn ProjectWorkspaceService> = services.clone();
+        fn record_context_item(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _attachment: AttachmentRef,
+            _created_at_unix_ms: u64,
+        ) -> Result<u64, String> {
+            Err("unimplemented".to_owned())
+        }
 
-        let mut state = AppState::new();
-        let _ = state.apply(Action::AddProject {
-            path: PathBuf::from("/tmp/luban-server-reconcile-test"),
-            is_git: true,
-        });
-        let project_id = state.projects[0].id;
-        let _ = state.apply(Action::WorkspaceCreated {
-            project_id,
-            workspace_name: "main".to_owned(),
-            branch_name: "main".to_owned(),
-            worktree_path: PathBuf::from("/tmp/luban-server-reconcile-test"),
-        });
+        fn list_context_items(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+        ) -> Result<Vec<ContextItem>, String> {
+            Ok(Vec::new())
+        }
 
-        let (events, _) = broadcast::channel::<WsServerMessage>(16);
-        let (tx, _rx) = mpsc::channel::<EngineCommand>(16);
-        let mut engine = Engine {
-            state,
-            rev: 1,
-            services: services_dyn,
-            events,
-            tx,
-            branch_watch: BranchWatchHandle::disabled(),
-            cancel_flags: HashMap::new(),
-            pull_requests: HashMap::new(),
-            pull_requests_in_flight: HashSet::new(),
-            workspace_threads_cache: HashMap::new(),
-            auto_archive_workspaces: HashSet::new(),
-            telegram_pairing: None,
-        };
+        fn delete_context_item(
+            &self,
+            _project_slug: String,
+            _workspace_name: String,
+            _context_id: u64,
+        ) -> Result<(), String> {
+            Ok(())
+        }
 
-        engine.reconcile_stale_running_turns().await;
+        fn run_agent_turn_streamed(
+            &self,
+            _request: luban_domain::RunAgentTurnRequest,
+            _cancel: Arc<AtomicBool>,
+            _on_event: Arc<dyn Fn(luban_domain::AgentThreadEvent) + Send + Sync>,
+        ) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
 
-        let appended = services.appended_entries.lock().expect("mutex ok").clone();
-        assert!(
-            appended.iter().any(|e| matches!(
-                e,
-                ConversationEntry::AgentEvent {
-                    event: luban_domain::AgentEvent::TurnError { message },
-                    ..
-                } if message == "Agent run interrupted by server restart."
-            )),
-            "expected reconcile to append a turn_error entry"
-        );
+        fn gh_is_authorized(&self) -> Result<bool, String> {
+            Err("unimplemented".to_owned())
+        }
 
-        let saved = services.saved_queue_state.lock().expect("mutex ok").clone();
-        assert_eq!(saved.len(), 1);
-        let (queue_paused, run_started, run_finished, pending) = &saved[0];
-        assert!(*queue_paused);
-        assert_eq!(*run_started, Some(10));
-        assert!(run_finished.is_some());
-        assert_eq!(pending.len(), 1);
-        assert_eq!(pending[0].text, "queued");
-    }
+        fn gh_pull_request_info(
+            &self,
+            _worktree_path: PathBuf,
+            _target: luban_domain::PullRequestTarget,
+        ) -> Result<Option<PullRequestInfo>, String> {
+            Err("unimplemented".to_owned())
+        }
 
-    fn persisted_with_single_git_workspace(workspace_id: u64) -> PersistedAppState {
-        PersistedAppState {
-            projects: vec![PersistedProject {
-                id: 1,
-                name: "Repo".to_owned(),
-                path: PathBuf::from("/tmp/luban-engine-bootstrap"),
-                slug: "repo".to_owned(),
-                is_git: true,
-                expanded: true,
-                workspaces: vec![PersistedWorkspace {
-                    id: workspace_id,
-                    workspace_name: "dev".to_owned(),
-                    branch_name: "dev".to_owned(),
-                    worktree_path: PathBuf::from("/tmp/luban-engine-bootstrap/dev"),
-                    status: WorkspaceStatus::Active,
-                    last_activity_at_unix_seconds: None,
-                }],
-            }],
-            sidebar_width: None,
-            terminal_pane_width: None,
-            global_zoom_percent: None,
-            appearance_theme: None,
-            appearance_ui_font: None,
-            appearance_chat_font: None,
-            appearance_code_font: None,
-            appearance_terminal_font: None,
-            agent_default_model_id: None,
-            agent_runner_default_models: HashMap::new(),
-            agent_default_thinking_effort: None,
-            agent_default_runner: None,
-            agent_amp_mode: None,
-            agent_codex_enabled: Some(true),
-            agent_amp_enabled: Some(true),
-            agent_claude_enabled: Some(true),
-            agent_droid_enabled: Some(true),
-            last_open_workspace_id: None,
-            open_button_selection: None,
-            sidebar_project_order: Vec::new(),
-            workspace_active_thread_id: HashMap::new(),
-            workspace_open_tabs: HashMap::new(),
-            workspace_archived_tabs: HashMap::new(),
-            workspace_next_thread_id: HashMap::new(),
-            workspace_chat_scroll_y10: HashMap::new(),
-            workspace_chat_scroll_anchor: HashMap::new(),
-            workspace_unread_completions: HashMap::new(),
-            workspace_thread_run_config_overrides: HashMap::new(),
-            starred_tasks: HashMap::new(),
-            task_prompt_templates: HashMap::new(),
-            telegram_enabled: None,
-            telegram_bot_token: None,
-            telegram_bot_username: None,
-            telegram_paired_chat_id: None,
-            telegram_topic_bindings: None,
+        fn gh_open_pull_request(&self, _worktree_path: PathBuf) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn gh_open_pull_request_failed_action(
+            &self,
+            _worktree_path: PathBuf,
+        ) -> Result<(), String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn project_identity(
+            &self,
+            _path: PathBuf,
+        ) -> Result<luban_domain::ProjectIdentity, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn task_generate_pr_description(
+            &self,
+            input: String,
+            _runner: luban_domain::AgentRunnerKind,
+            _model_id: String,
+            _thinking_effort: luban_domain::ThinkingEffort,
+            _amp_mode: Option<String>,
+        ) -> Result<String, String> {
+            let files = input
+                .lines()
+                .filter_map(|line| line.strip_prefix("- `"))
+                .filter_map(|line| line.split('`').next())
+                .collect::<Vec<_>>();
+            self.inputs.lock().expect("inputs lock").push(input.clone());
+            Ok(format!(
+                "## Summary\n\nFake description.\n\n## Changes\n\n{}",
+                files.join("\n")
+            ))
         }
     }
 
-    #[derive(Clone)]
-    struct BootstrapHangServices {
-        persisted: PersistedAppState,
-        list_threads_delay: Duration,
-        archive_delay: Duration,
+    struct CompactServices {
+        inputs: std::sync::Mutex<Vec<String>>,
     }
 
-    impl ProjectWorkspaceService for BootstrapHangServices {
+    impl ProjectWorkspaceService for CompactServices {
         fn load_app_state(&self) -> Result<PersistedAppState, String> {
-            Ok(self.persisted.clone())
+            Ok(PersistedAppState {
+                projects: Vec::new(),
+                sidebar_width: None,
+                terminal_pane_width: None,
+                global_zoom_percent: None,
+                appearance_theme: None,
+                appearance_ui_font: None,
+                appearance_chat_font: None,
+                appearance_code_font: None,
+                appearance_terminal_font: None,
+                agent_default_model_id: None,
+                agent_runner_default_models: HashMap::new(),
+                agent_runner_prompt_prefixes: HashMap::new(),
+                agent_runner_prompt_suffixes: HashMap::new(),
+                model_thinking_effort_caps: HashMap::new(),
+                agent_default_thinking_effort: None,
+                agent_default_runner: None,
+                agent_amp_mode: None,
+                agent_codex_enabled: Some(true),
+                agent_amp_enabled: Some(true),
+                agent_claude_enabled: Some(true),
+                agent_droid_enabled: Some(true),
+                last_open_workspace_id: None,
+                open_button_selection: None,
+                sidebar_project_order: Vec::new(),
+                project_groups: Vec::new(),
+                workspace_active_thread_id: HashMap::new(),
+                workspace_open_tabs: HashMap::new(),
+                workspace_archived_tabs: HashMap::new(),
+                workspace_next_thread_id: HashMap::new(),
+                workspace_chat_scroll_y10: HashMap::new(),
+                workspace_chat_scroll_anchor: HashMap::new(),
+                workspace_unread_completions: HashMap::new(),
+                workspace_thread_run_config_overrides: HashMap::new(),
+                starred_tasks: HashMap::new(),
+                task_prompt_templates: HashMap::new(),
+                telegram_enabled: None,
+                telegram_bot_token: None,
+                telegram_bot_username: None,
+                telegram_paired_chat_ids: Vec::new(),
+                telegram_topic_bindings: None,
+                webhook_url: None,
+                webhook_secret: None,
+                agent_merge_reasoning_entries: None,
+                agent_pause_queue_on_patch_failure: None,
+                agent_item_dedup_window: None,
+                agent_auto_retry_on_failure: None,
+                agent_command_output_max_bytes: None,
+                auto_archive_after_days: None,
+            })
         }
 
         fn save_app_state(&self, _snapshot: PersistedAppState) -> Result<(), String> {
@@ -9450,6 +18323,8 @@ mod tests {
             _project_path: PathBuf,
             _project_slug: String,
             _branch_name_hint: Option<String>,
+            _worktree_root: Option<PathBuf>,
+            _branch_name_template: Option<luban_domain::BranchNameTemplate>,
         ) -> Result<luban_domain::CreatedWorkspace, String> {
             Err("unimplemented".to_owned())
         }
@@ -9464,14 +18339,14 @@ mod tests {
             _worktree_path: PathBuf,
             _branch_name: String,
         ) -> Result<(), String> {
-            std::thread::sleep(self.archive_delay);
-            Ok(())
+            Err("unimplemented".to_owned())
         }
 
         fn rename_workspace_branch(
             &self,
             _worktree_path: PathBuf,
             _requested_branch_name: String,
+            _branch_name_template: Option<luban_domain::BranchNameTemplate>,
         ) -> Result<String, String> {
             Err("unimplemented".to_owned())
         }
@@ -9490,19 +18365,7 @@ mod tests {
             _project_slug: String,
             _workspace_name: String,
         ) -> Result<Vec<ConversationThreadMeta>, String> {
-            std::thread::sleep(self.list_threads_delay);
-            Ok(vec![ConversationThreadMeta {
-                thread_id: luban_domain::WorkspaceThreadId::from_u64(1),
-                remote_thread_id: None,
-                title: "Done: completed successfully".to_owned(),
-                created_at_unix_seconds: 1,
-                updated_at_unix_seconds: 1,
-                task_status: luban_domain::TaskStatus::Done,
-                last_message_seq: 0,
-                task_status_last_analyzed_message_seq: 0,
-                turn_status: luban_domain::TurnStatus::Idle,
-                last_turn_result: Some(luban_domain::TurnResult::Completed),
-            }])
+            Err("unimplemented".to_owned())
         }
 
         fn load_conversation(
@@ -9596,6 +18459,7 @@ mod tests {
         fn gh_pull_request_info(
             &self,
             _worktree_path: PathBuf,
+            _target: luban_domain::PullRequestTarget,
         ) -> Result<Option<PullRequestInfo>, String> {
             Err("unimplemented".to_owned())
         }
@@ -9610,49 +18474,375 @@ mod tests {
         ) -> Result<(), String> {
             Err("unimplemented".to_owned())
         }
+
+        fn project_identity(
+            &self,
+            _path: PathBuf,
+        ) -> Result<luban_domain::ProjectIdentity, String> {
+            Err("unimplemented".to_owned())
+        }
+
+        fn task_summarize_conversation(
+            &self,
+            input: String,
+            _runner: luban_domain::AgentRunnerKind,
+            _model_id: String,
+            _thinking_effort: luban_domain::ThinkingEffort,
+            _amp_mode: Option<String>,
+        ) -> Result<String, String> {
+            self.inputs.lock().expect("inputs lock").push(input);
+            Ok("- Asked for the first two changes".to_owned())
+        }
     }
 
     #[tokio::test]
-    async fn bootstrap_does_not_block_on_auto_archive_scan() {
-        let services: Arc<dyn ProjectWorkspaceService> = Arc::new(BootstrapHangServices {
-            persisted: persisted_with_single_git_workspace(10),
-            list_threads_delay: Duration::from_secs(2),
-            archive_delay: Duration::from_millis(0),
+    async fn compact_conversation_summarizes_entries_before_the_cutoff() {
+        let mut state = AppState::new();
+        let _ = state.apply(Action::AddProject {
+            path: PathBuf::from("/tmp/luban-compact"),
+            is_git: true,
         });
-        let (engine, _events) = Engine::start(services);
+        let project_id = state.projects[0].id;
+        let _ = state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "main".to_owned(),
+            branch_name: "main".to_owned(),
+            worktree_path: PathBuf::from("/tmp/luban-compact"),
+        });
+        let workspace_id = state.projects[0].workspaces[0].id;
+        let thread_id = WorkspaceThreadId::from_u64(1);
+        for text in ["first change", "second change", "third change"] {
+            state.apply(Action::SendAgentMessage {
+                workspace_id,
+                thread_id,
+                text: text.to_owned(),
+                attachments: Vec::new(),
+                runner: None,
+                amp_mode: None,
+            });
+            state.apply(Action::AgentTurnFinished {
+                workspace_id,
+                thread_id,
+                run_id: state
+                    .workspace_thread_conversation(workspace_id, thread_id)
+                    .and_then(|c| c.active_run_id)
+                    .unwrap_or_default(),
+            });
+        }
+        let third_entry_id = state
+            .workspace_thread_conversation(workspace_id, thread_id)
+            .and_then(|c| {
+                c.entries.iter().find_map(|entry| match entry {
+                    luban_domain::ConversationEntry::UserEvent {
+                        entry_id,
+                        event: luban_domain::UserEvent::Message { text, .. },
+                        ..
+                    } if text == "third change" => Some(entry_id.clone()),
+                    _ => None,
+                })
+            })
+            .expect("third message entry");
 
-        let snap = tokio::time::timeout(Duration::from_millis(300), engine.app_snapshot())
+        let services = Arc::new(CompactServices {
+            inputs: std::sync::Mutex::new(Vec::new()),
+        });
+        let (events, _) = broadcast::channel::<WsServerMessage>(16);
+        let (tx, mut rx_cmd) = mpsc::channel::<EngineCommand>(16);
+        let mut engine = Engine {
+            state,
+            rev: 1,
+            services: services.clone(),
+            events,
+            tx,
+            branch_watch: BranchWatchHandle::disabled(),
+            cancel_flags: HashMap::new(),
+            pull_requests: HashMap::new(),
+            pull_requests_in_flight: HashSet::new(),
+            workspace_threads_cache: HashMap::new(),
+            auto_archive_workspaces: HashSet::new(),
+            pending_notes_flushes: HashMap::new(),
+            pending_draft_flushes: HashMap::new(),
+            conversation_revs: HashMap::new(),
+            last_git_fetch_at: HashMap::new(),
+            worktree_dirty: HashMap::new(),
+            worktree_dirty_in_flight: HashSet::new(),
+            worktree_dirty_rerun: HashSet::new(),
+            last_commits: HashMap::new(),
+            agent_turn_slots: HashMap::new(),
+            deferred_agent_turns: VecDeque::new(),
+            request_cancellations: RequestCancellations::default(),
+            attachment_thumbnails: AttachmentThumbnails::default(),
+            telegram_pairings: Vec::new(),
+            config: EngineConfig::default(),
+        };
+
+        let mut events = engine.events.subscribe();
+        let (reply, rx) = oneshot::channel();
+        engine
+            .handle(EngineCommand::ApplyClientAction {
+                request_id: "req-compact".to_owned(),
+                action: luban_api::ClientAction::CompactConversation {
+                    workspace_id: luban_api::WorkspaceId(workspace_id.as_u64()),
+                    thread_id: luban_api::WorkspaceThreadId(thread_id.as_u64()),
+                    before_entry_id: third_entry_id.clone(),
+                },
+                reply,
+            })
+            .await;
+        rx.await
+            .expect("reply should be sent")
+            .expect("action should be accepted");
+
+        let command = tokio::time::timeout(Duration::from_secs(5), rx_cmd.recv())
             .await
-            .expect("app snapshot should not be blocked by bootstrap maintenance")
-            .expect("snapshot should succeed");
-        assert_eq!(snap.projects.len(), 1);
+            .expect("timed out waiting for compaction")
+            .expect("command channel closed");
+        engine.handle(command).await;
+
+        let collapsed_entries = loop {
+            let msg = tokio::time::timeout(Duration::from_secs(5), events.recv())
+                .await
+                .expect("timed out waiting for compaction reply")
+                .expect("events channel closed");
+            if let WsServerMessage::Event { event, .. } = msg
+                && let luban_api::ServerEvent::ConversationCompacted {
+                    request_id,
+                    collapsed_entries,
+                    ..
+                } = *event
+            {
+                assert_eq!(request_id, "req-compact");
+                break collapsed_entries;
+            }
+        };
+
+        let inputs = services.inputs.lock().expect("inputs lock").clone();
+        assert_eq!(inputs.len(), 1);
+        assert!(inputs[0].contains("first change"), "{}", inputs[0]);
+        assert!(inputs[0].contains("second change"), "{}", inputs[0]);
+        assert!(!inputs[0].contains("third change"), "{}", inputs[0]);
+
+        let conversation = engine
+            .state
+            .workspace_thread_conversation(workspace_id, thread_id)
+            .expect("conversation");
+        let range = conversation
+            .collapsed_ranges
+            .last()
+            .expect("collapsed range");
+        assert_eq!(range.entry_count, collapsed_entries);
+        assert!(
+            matches!(
+                conversation.entries.last(),
+                Some(luban_domain::ConversationEntry::SystemEvent {
+                    entry_id,
+                    event: luban_domain::ConversationSystemEvent::HistorySummarized { .. },
+                    ..
+                }) if *entry_id == range.summary_entry_id
+            ),
+            "summary entry should be appended"
+        );
     }
 
     #[tokio::test]
-    async fn engine_remains_responsive_while_archive_workspace_runs() {
-        let services: Arc<dyn ProjectWorkspaceService> = Arc::new(BootstrapHangServices {
-            persisted: persisted_with_single_git_workspace(10),
-            list_threads_delay: Duration::from_millis(0),
-            archive_delay: Duration::from_secs(2),
+    async fn generate_pr_description_references_changed_files() {
+        let repo = tempfile::tempdir().expect("temp dir");
+        let repo_path = repo.path().to_path_buf();
+        let git = |args: &[&str]| {
+            let status = std::process::Command::new("git")
+                .args(args)
+                .current_dir(&repo_path)
+                .status()
+                .expect("git should run");
+            assert!(status.success(), "git {args:?} failed");
+        };
+        git(&["init"]);
+        git(&["config", "user.email", "pr@example.com"]);
+        git(&["config", "user.name", "luban-pr"]);
+        std::fs::write(repo_path.join("file.txt"), "a\n").expect("write file");
+        git(&["add", "."]);
+        git(&["commit", "-m", "init"]);
+
+        let mut state = AppState::new();
+        let _ = state.apply(Action::AddProject {
+            path: repo_path.clone(),
+            is_git: true,
+        });
+        let project_id = state.projects[0].id;
+        let _ = state.apply(Action::WorkspaceCreated {
+            project_id,
+            workspace_name: "main".to_owned(),
+            branch_name: "main".to_owned(),
+            worktree_path: repo_path.clone(),
+        });
+        let workspace_id = state.projects[0].workspaces[0].id;
+        let thread_id = WorkspaceThreadId::from_u64(1);
+        state.apply(Action::SendAgentMessage {
+            workspace_id,
+            thread_id,
+            text: "seed".to_owned(),
+            attachments: Vec::new(),
+            runner: None,
+            amp_mode: None,
         });
-        let (engine, _events) = Engine::start(services);
 
-        let _ = tokio::time::timeout(Duration::from_secs(1), engine.app_snapshot())
-            .await
-            .expect("bootstrap should complete")
-            .expect("snapshot should succeed");
+        let services = Arc::new(PrDescriptionServices {
+            inputs: std::sync::Mutex::new(Vec::new()),
+        });
+        let (events, _) = broadcast::channel::<WsServerMessage>(16);
+        let (tx, _rx_cmd) = mpsc::channel::<EngineCommand>(16);
+        let mut engine = Engine {
+            state,
+            rev: 1,
+            services: services.clone(),
+            events,
+            tx,
+            branch_watch: BranchWatchHandle::disabled(),
+            cancel_flags: HashMap::new(),
+            pull_requests: HashMap::new(),
+            pull_requests_in_flight: HashSet::new(),
+            workspace_threads_cache: HashMap::new(),
+            auto_archive_workspaces: HashSet::new(),
+            pending_notes_flushes: HashMap::new(),
+            pending_draft_flushes: HashMap::new(),
+            conversation_revs: HashMap::new(),
+            last_git_fetch_at: HashMap::new(),
+            worktree_dirty: HashMap::new(),
+            worktree_dirty_in_flight: HashSet::new(),
+            worktree_dirty_rerun: HashSet::new(),
+            last_commits: HashMap::new(),
+            agent_turn_slots: HashMap::new(),
+            deferred_agent_turns: VecDeque::new(),
+            request_cancellations: RequestCancellations::default(),
+            attachment_thumbnails: AttachmentThumbnails::default(),
+            telegram_pairings: Vec::new(),
+            config: EngineConfig::default(),
+        };
 
-        engine
-            .dispatch_domain_action(Action::ArchiveWorkspace {
-                workspace_id: WorkspaceId::from_u64(10),
+        let api_wid = luban_api::WorkspaceId(workspace_id.as_u64());
+        let api_tid = luban_api::WorkspaceThreadId(thread_id.as_u64());
+        let generate = async |engine: &mut Engine, request_id: &str| {
+            let mut events = engine.events.subscribe();
+            let (reply, rx) = oneshot::channel();
+            engine
+                .handle(EngineCommand::ApplyClientAction {
+                    request_id: request_id.to_owned(),
+                    action: luban_api::ClientAction::GeneratePrDescription {
+                        workspace_id: api_wid,
+                        thread_id: api_tid,
+                    },
+                    reply,
+                })
+                .await;
+            rx.await
+                .expect("reply should be sent")
+                .expect("action should be accepted");
+            loop {
+                let msg = tokio::time::timeout(Duration::from_secs(5), events.recv())
+                    .await
+                    .expect("timed out waiting for pr description")
+                    .expect("events channel closed");
+                if let WsServerMessage::Event { event, .. } = msg
+                    && let luban_api::ServerEvent::PrDescriptionReady {
+                        request_id: got,
+                        body,
+                        has_changes,
+                        ..
+                    } = *event
+                {
+                    assert_eq!(got, request_id);
+                    return (body, has_changes);
+                }
+            }
+        };
+
+        let (body, has_changes) = generate(&mut engine, "req-clean").await;
+        assert!(!has_changes);
+        assert!(body.contains("no changes"), "{body}");
+        assert!(
+            services.inputs.lock().expect("inputs lock").is_empty(),
+            "generator should not run without changes"
+        );
+
+        std::fs::write(repo_path.join("file.txt"), "b\n").expect("modify file");
+        std::fs::write(repo_path.join("added.txt"), "c\n").expect("write untracked file");
+        let (body, has_changes) = generate(&mut engine, "req-dirty").await;
+        assert!(has_changes);
+        assert!(body.contains("file.txt"), "{body}");
+        assert!(body.contains("added.txt"), "{body}");
+        let inputs = services.inputs.lock().expect("inputs lock");
+        assert_eq!(inputs.len(), 1);
+        assert!(
+            inputs[0].contains("`file.txt` (modified, +1 -1)"),
+            "{}",
+            inputs[0]
+        );
+    }
+
+    #[test]
+    fn exported_conversation_jsonl_maps_back_to_domain_entries() {
+        let exported = [
+            ConversationEntry::SystemEvent {
+                entry_id: "e_1".to_owned(),
+                created_at_unix_ms: 1,
+                event: luban_domain::ConversationSystemEvent::TaskCreated,
+            },
+            ConversationEntry::UserEvent {
+                entry_id: "e_2".to_owned(),
+                created_at_unix_ms: 2,
+                event: luban_domain::UserEvent::Message {
+                    text: "run the tests".to_owned(),
+                    attachments: Vec::new(),
+                },
+            },
+            ConversationEntry::AgentEvent {
+                entry_id: "e_3".to_owned(),
+                created_at_unix_ms: 3,
+                runner: Some(luban_domain::AgentRunnerKind::Codex),
+                event: luban_domain::AgentEvent::Item {
+                    item: Box::new(CodexThreadItem::CommandExecution {
+                        id: "cmd_1".to_owned(),
+                        command: "cargo test".to_owned(),
+                        aggregated_output: "ok".to_owned(),
+                        exit_code: Some(0),
+                        status: luban_domain::CodexCommandExecutionStatus::Completed,
+                        truncated: false,
+                    }),
+                },
+            },
+        ];
+        let mut lines = exported
+            .iter()
+            .map(|entry| {
+                serde_json::to_string(&map_conversation_entry(entry))
+                    .expect("entry should serialize")
             })
-            .await
-            .expect("dispatch archive action");
+            .collect::<Vec<_>>();
+        assert!(
+            lines[2].contains(r#""type":"item""#)
+                && lines[2].contains(r#""kind":"command_execution""#),
+            "{}",
+            lines[2]
+        );
+        lines.push(String::new());
+        lines.push("not json".to_owned());
+        lines.push(
+            r#"{"type":"agent_event","event":{"type":"item","id":"x","kind":"reasoning","payload":{}}}"#
+                .to_owned(),
+        );
 
-        let snap = tokio::time::timeout(Duration::from_millis(300), engine.app_snapshot())
-            .await
-            .expect("app snapshot should remain responsive during archive")
-            .expect("snapshot should succeed");
-        assert_eq!(snap.projects.len(), 1);
+        let (entries, errors) = parse_conversation_jsonl(&lines.join("\n"));
+
+        let serialize = |entries: &[ConversationEntry]| {
+            entries
+                .iter()
+                .map(|entry| serde_json::to_string(entry).expect("entry should serialize"))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(serialize(&entries), serialize(&exported[1..]));
+        assert_eq!(
+            errors.iter().map(|e| e.line).collect::<Vec<_>>(),
+            vec![5, 6]
+        );
     }
 }
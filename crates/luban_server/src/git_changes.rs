@@ -1,9 +1,45 @@
 use anyhow::{Context as _, anyhow};
 use luban_api::{
-    ChangedFileSnapshot, DiffFileContents, FileChangeGroup, FileChangeStatus,
+    ChangedFileSnapshot, DiffFileContents, DiffHunkSnapshot, FileChangeGroup, FileChangeStatus,
     WorkspaceDiffFileSnapshot,
 };
-use std::{ffi::OsStr, path::Path, process::Command};
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsStr,
+    io::Write as _,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::{
+        LazyLock, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+/// Numstat results keyed by the exact blobs they were computed from, shared by every refresh.
+static DIFF_STATS_CACHE: LazyLock<DiffStatsCache> = LazyLock::new(DiffStatsCache::default);
+
+type DiffStats = (Option<u64>, Option<u64>);
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct DiffStatsKey {
+    worktree_path: PathBuf,
+    /// `file_id`, so the same path in different groups never shares an entry.
+    file_id: String,
+    old_oid: String,
+    new_oid: String,
+}
+
+/// Caches per-file `additions`/`deletions` across change refreshes.
+///
+/// Entries are keyed by the old and new blob ids of the file, so any content change is a new key
+/// and a cached value can never describe a different tree state. Entries for files that leave the
+/// changed set, or whose blob ids moved on, are dropped on the next collection for that worktree.
+#[derive(Default)]
+struct DiffStatsCache {
+    entries: Mutex<HashMap<DiffStatsKey, DiffStats>>,
+    /// Lookups answered from the cache since startup, also reported in tracing.
+    hits: AtomicU64,
+}
 
 fn run_git_bytes<I, S>(repo_path: &Path, args: I) -> anyhow::Result<Vec<u8>>
 where
@@ -30,6 +66,37 @@ where
     Ok(output.stdout)
 }
 
+fn run_git_with_stdin<I, S>(repo_path: &Path, args: I, stdin: &[u8]) -> anyhow::Result<Vec<u8>>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let mut child = Command::new("git")
+        .args(args)
+        .current_dir(repo_path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("failed to spawn git")?;
+    if let Some(mut input) = child.stdin.take() {
+        input
+            .write_all(stdin)
+            .context("failed to write git stdin")?;
+    }
+    let output = child.wait_with_output().context("failed to wait for git")?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "git failed ({}):\nstderr:\n{}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(output.stdout)
+}
+
 fn run_git_text<I, S>(repo_path: &Path, args: I) -> anyhow::Result<String>
 where
     I: IntoIterator<Item = S>,
@@ -119,10 +186,31 @@ fn parse_name_status_line(
     })
 }
 
-fn parse_status_porcelain_v2(repo_path: &Path) -> anyhow::Result<Vec<ChangedFileSnapshot>> {
+/// A changed file together with the blob ids its stats are computed from.
+///
+/// An empty id stands for "no blob" (added or deleted side). `new_oid` is `None` while the worktree
+/// side still needs hashing, and stays `None` when it cannot be hashed.
+struct ChangeEntry {
+    file: ChangedFileSnapshot,
+    old_oid: Option<String>,
+    new_oid: Option<String>,
+}
+
+impl ChangeEntry {
+    fn stats_key(&self, repo_path: &Path) -> Option<DiffStatsKey> {
+        Some(DiffStatsKey {
+            worktree_path: repo_path.to_path_buf(),
+            file_id: self.file.id.clone(),
+            old_oid: self.old_oid.clone()?,
+            new_oid: self.new_oid.clone()?,
+        })
+    }
+}
+
+fn parse_status_porcelain_v2(repo_path: &Path) -> anyhow::Result<Vec<ChangeEntry>> {
     let out = run_git_bytes(repo_path, ["status", "--porcelain=v2", "-z"])?;
     let text = String::from_utf8_lossy(&out);
-    let mut files: Vec<ChangedFileSnapshot> = Vec::new();
+    let mut entries: Vec<ChangeEntry> = Vec::new();
 
     for record in text.split('\0') {
         let record = record.trim_end_matches('\n');
@@ -135,15 +223,19 @@ fn parse_status_porcelain_v2(repo_path: &Path) -> anyhow::Result<Vec<ChangedFile
 
         if let Some(rest) = record.strip_prefix("? ") {
             let path = rest.to_owned();
-            files.push(ChangedFileSnapshot {
-                id: file_id(FileChangeGroup::Unstaged, &path),
-                name: basename(&path),
-                path,
-                status: FileChangeStatus::Added,
-                group: FileChangeGroup::Unstaged,
-                additions: None,
-                deletions: None,
-                old_path: None,
+            entries.push(ChangeEntry {
+                file: ChangedFileSnapshot {
+                    id: file_id(FileChangeGroup::Unstaged, &path),
+                    name: basename(&path),
+                    path,
+                    status: FileChangeStatus::Added,
+                    group: FileChangeGroup::Unstaged,
+                    additions: None,
+                    deletions: None,
+                    old_path: None,
+                },
+                old_oid: Some(String::new()),
+                new_oid: None,
             });
             continue;
         }
@@ -155,8 +247,8 @@ fn parse_status_porcelain_v2(repo_path: &Path) -> anyhow::Result<Vec<ChangedFile
             let _m1 = parts.next().unwrap_or("");
             let _m2 = parts.next().unwrap_or("");
             let _m3 = parts.next().unwrap_or("");
-            let _mh = parts.next().unwrap_or("");
-            let _mi = parts.next().unwrap_or("");
+            let head_oid = parts.next().unwrap_or("");
+            let index_oid = parts.next().unwrap_or("");
             let path = parts.next().unwrap_or("").to_owned();
 
             let mut xy_chars = xy.chars();
@@ -169,16 +261,21 @@ fn parse_status_porcelain_v2(repo_path: &Path) -> anyhow::Result<Vec<ChangedFile
             } else {
                 continue;
             };
-
-            files.push(ChangedFileSnapshot {
-                id: file_id(group, &path),
-                name: basename(&path),
-                path,
-                status: status_from_code(code),
-                group,
-                additions: None,
-                deletions: None,
-                old_path: None,
+            let (old_oid, new_oid) = status_entry_oids(group, code, head_oid, index_oid);
+
+            entries.push(ChangeEntry {
+                file: ChangedFileSnapshot {
+                    id: file_id(group, &path),
+                    name: basename(&path),
+                    path,
+                    status: status_from_code(code),
+                    group,
+                    additions: None,
+                    deletions: None,
+                    old_path: None,
+                },
+                old_oid,
+                new_oid,
             });
             continue;
         }
@@ -190,42 +287,134 @@ fn parse_status_porcelain_v2(repo_path: &Path) -> anyhow::Result<Vec<ChangedFile
             let _m1 = parts.next().unwrap_or("");
             let _m2 = parts.next().unwrap_or("");
             let _m3 = parts.next().unwrap_or("");
-            let _mh = parts.next().unwrap_or("");
-            let _mi = parts.next().unwrap_or("");
+            let head_oid = parts.next().unwrap_or("");
+            let index_oid = parts.next().unwrap_or("");
             let _score = parts.next().unwrap_or("");
             let paths = parts.next().unwrap_or("");
 
             let mut xy_chars = xy.chars();
             let x = xy_chars.next().unwrap_or('.');
             let y = xy_chars.next().unwrap_or('.');
-            let group = if y != '.' {
-                FileChangeGroup::Unstaged
+            let (group, code) = if y != '.' {
+                (FileChangeGroup::Unstaged, y)
             } else if x != '.' {
-                FileChangeGroup::Staged
+                (FileChangeGroup::Staged, x)
             } else {
                 continue;
             };
+            let (old_oid, new_oid) = status_entry_oids(group, code, head_oid, index_oid);
 
             let (old_path, path) = paths
                 .split_once('\t')
                 .map(|(a, b)| (a.to_owned(), b.to_owned()))
                 .unwrap_or_else(|| (paths.to_owned(), paths.to_owned()));
 
-            files.push(ChangedFileSnapshot {
-                id: file_id(group, &path),
-                name: basename(&path),
-                path,
-                status: FileChangeStatus::Renamed,
-                group,
-                additions: None,
-                deletions: None,
-                old_path: Some(old_path),
+            entries.push(ChangeEntry {
+                file: ChangedFileSnapshot {
+                    id: file_id(group, &path),
+                    name: basename(&path),
+                    path,
+                    status: FileChangeStatus::Renamed,
+                    group,
+                    additions: None,
+                    deletions: None,
+                    old_path: Some(old_path),
+                },
+                old_oid,
+                new_oid,
             });
             continue;
         }
     }
 
-    Ok(files)
+    Ok(entries)
+}
+
+/// Staged changes diff HEAD against the index; unstaged ones diff the index against the worktree,
+/// whose blob id is filled in later by `hash_worktree_blobs`.
+fn status_entry_oids(
+    group: FileChangeGroup,
+    code: char,
+    head_oid: &str,
+    index_oid: &str,
+) -> (Option<String>, Option<String>) {
+    match group {
+        FileChangeGroup::Unstaged if code == 'D' => {
+            (Some(index_oid.to_owned()), Some(String::new()))
+        }
+        FileChangeGroup::Unstaged => (Some(index_oid.to_owned()), None),
+        _ => (Some(head_oid.to_owned()), Some(index_oid.to_owned())),
+    }
+}
+
+/// Fills in `new_oid` for unstaged entries by hashing their worktree files in one `git` call.
+fn hash_worktree_blobs(repo_path: &Path, entries: &mut [ChangeEntry]) {
+    let pending = entries
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| {
+            entry.new_oid.is_none()
+                && entry.file.group == FileChangeGroup::Unstaged
+                && !entry.file.path.contains('\n')
+                && repo_path.join(&entry.file.path).is_file()
+        })
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+    if pending.is_empty() {
+        return;
+    }
+
+    let stdin = pending
+        .iter()
+        .map(|&index| entries[index].file.path.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let out = match run_git_with_stdin(
+        repo_path,
+        ["hash-object", "--stdin-paths"],
+        stdin.as_bytes(),
+    ) {
+        Ok(out) => out,
+        Err(err) => {
+            tracing::debug!(error = %err, "failed to hash worktree files for diff stats");
+            return;
+        }
+    };
+    let text = String::from_utf8_lossy(&out);
+    let oids = text.lines().collect::<Vec<_>>();
+    if oids.len() != pending.len() {
+        return;
+    }
+    for (index, oid) in pending.into_iter().zip(oids) {
+        entries[index].new_oid = Some(oid.trim().to_owned());
+    }
+}
+
+/// Parses `git diff --raw --no-abbrev` output into committed-group entries.
+fn parse_raw_diff(out: &str, upstream: &str) -> Vec<ChangeEntry> {
+    let mut entries = Vec::new();
+    for line in out.lines() {
+        let Some((meta, paths)) = line.split_once('\t') else {
+            continue;
+        };
+        let meta = meta.trim_start_matches(':').split(' ').collect::<Vec<_>>();
+        let [_, _, old_oid, new_oid, code] = meta.as_slice() else {
+            continue;
+        };
+        let Some(file) = parse_name_status_line(
+            FileChangeGroup::Committed,
+            &format!("{code}\t{paths}"),
+            Some(upstream),
+        ) else {
+            continue;
+        };
+        entries.push(ChangeEntry {
+            file,
+            old_oid: Some((*old_oid).to_owned()),
+            new_oid: Some((*new_oid).to_owned()),
+        });
+    }
+    entries
 }
 
 fn compute_numstat(
@@ -367,84 +556,451 @@ fn diff_contents_for_file(
     }
 }
 
-pub fn collect_changes(repo_path: &Path) -> anyhow::Result<Vec<ChangedFileSnapshot>> {
-    let upstream = upstream_ref(repo_path);
-    let mut staged_unstaged = parse_status_porcelain_v2(repo_path)?;
+fn parse_hunk_range(range: &str) -> Option<(u64, u64)> {
+    match range.split_once(',') {
+        Some((start, lines)) => Some((start.parse().ok()?, lines.parse().ok()?)),
+        None => Some((range.parse().ok()?, 1)),
+    }
+}
+
+fn parse_unified_hunks(patch: &str) -> Vec<DiffHunkSnapshot> {
+    let mut hunks: Vec<DiffHunkSnapshot> = Vec::new();
+    for line in patch.lines() {
+        if let Some(header) = line.strip_prefix("@@ -") {
+            let mut ranges = header.split_whitespace();
+            let old = ranges.next().and_then(parse_hunk_range);
+            let new = ranges
+                .next()
+                .and_then(|r| r.strip_prefix('+'))
+                .and_then(parse_hunk_range);
+            if let (Some((old_start, old_lines)), Some((new_start, new_lines))) = (old, new) {
+                hunks.push(DiffHunkSnapshot {
+                    old_start,
+                    old_lines,
+                    new_start,
+                    new_lines,
+                    lines: Vec::new(),
+                });
+            }
+            continue;
+        }
+        // File headers precede the first hunk, so only lines inside a hunk are kept.
+        if let Some(hunk) = hunks.last_mut()
+            && matches!(line.chars().next(), Some(' ' | '+' | '-' | '\\'))
+        {
+            hunk.lines.push(line.to_owned());
+        }
+    }
+    hunks
+}
+
+/// Returns the file's hunks, and whether git reported it as binary (in which case there are none).
+fn diff_hunks_for_file(
+    repo_path: &Path,
+    file: &ChangedFileSnapshot,
+    upstream: Option<&str>,
+    context_lines: u32,
+) -> (Vec<DiffHunkSnapshot>, bool) {
+    let mut args: Vec<String> = vec![
+        "diff".to_owned(),
+        "--no-color".to_owned(),
+        "--no-ext-diff".to_owned(),
+        format!("-U{context_lines}"),
+    ];
+    let untracked = file.group == FileChangeGroup::Unstaged
+        && file.status == FileChangeStatus::Added
+        && git_show_index_utf8(repo_path, &file.path).is_empty();
+    if untracked {
+        args.push("--no-index".to_owned());
+        args.push("--".to_owned());
+        args.push("/dev/null".to_owned());
+        args.push(file.path.clone());
+    } else {
+        match file.group {
+            FileChangeGroup::Committed => {
+                let Some(upstream) = upstream else {
+                    return (Vec::new(), false);
+                };
+                args.push(format!("{upstream}..HEAD"));
+            }
+            FileChangeGroup::Staged => args.push("--cached".to_owned()),
+            FileChangeGroup::Unstaged => {}
+        }
+        args.push("--find-renames".to_owned());
+        args.push("--".to_owned());
+        if let Some(old_path) = file.old_path.as_deref() {
+            args.push(old_path.to_owned());
+        }
+        args.push(file.path.clone());
+    }
 
-    let mut present_paths = std::collections::HashSet::new();
-    for f in &staged_unstaged {
-        present_paths.insert(f.path.clone());
+    // `--no-index` exits with 1 when the files differ, so the status is not checked.
+    let out = Command::new("git")
+        .args(&args)
+        .current_dir(repo_path)
+        .output();
+    match out {
+        Ok(out) => {
+            let patch = String::from_utf8_lossy(&out.stdout);
+            let binary = patch
+                .lines()
+                .any(|line| line.starts_with("Binary files ") || line == "GIT binary patch");
+            (parse_unified_hunks(&patch), binary)
+        }
+        Err(_) => (Vec::new(), false),
     }
+}
 
-    if let Some(upstream) = upstream.as_deref() {
+fn collect_change_entries(
+    repo_path: &Path,
+    upstream: Option<&str>,
+) -> anyhow::Result<Vec<ChangeEntry>> {
+    let mut entries = parse_status_porcelain_v2(repo_path)?;
+
+    let mut present_paths = HashSet::new();
+    for entry in &entries {
+        present_paths.insert(entry.file.path.clone());
+    }
+
+    if let Some(upstream) = upstream {
         let out = run_git_text(
             repo_path,
             [
                 "diff",
-                "--name-status",
+                "--raw",
+                "--no-abbrev",
                 "--find-renames",
                 &format!("{upstream}..HEAD"),
             ],
         )
         .unwrap_or_default();
-        let mut committed: Vec<ChangedFileSnapshot> = Vec::new();
-        for line in out.lines() {
-            if let Some(file) =
-                parse_name_status_line(FileChangeGroup::Committed, line, Some(upstream))
-            {
-                if present_paths.contains(&file.path) {
-                    continue;
+        let committed = parse_raw_diff(&out, upstream)
+            .into_iter()
+            .filter(|entry| !present_paths.contains(&entry.file.path))
+            .collect::<Vec<_>>();
+        entries.splice(0..0, committed);
+    }
+
+    hash_worktree_blobs(repo_path, &mut entries);
+    Ok(entries)
+}
+
+pub fn collect_changes(repo_path: &Path) -> anyhow::Result<Vec<ChangedFileSnapshot>> {
+    collect_changes_with_cache(repo_path, &DIFF_STATS_CACHE)
+}
+
+fn collect_changes_with_cache(
+    repo_path: &Path,
+    cache: &DiffStatsCache,
+) -> anyhow::Result<Vec<ChangedFileSnapshot>> {
+    let upstream = upstream_ref(repo_path);
+    let upstream = upstream.as_deref();
+    let mut entries = collect_change_entries(repo_path, upstream)?;
+
+    let keys = entries
+        .iter()
+        .map(|entry| entry.stats_key(repo_path))
+        .collect::<Vec<_>>();
+    let cached = {
+        let map = cache.entries.lock().expect("diff stats cache poisoned");
+        keys.iter()
+            .map(|key| key.as_ref().and_then(|key| map.get(key).copied()))
+            .collect::<Vec<_>>()
+    };
+
+    let mut hits = 0u64;
+    let mut computed = Vec::new();
+    for (index, entry) in entries.iter_mut().enumerate() {
+        let (add, del) = match cached[index] {
+            Some(stats) => {
+                hits += 1;
+                stats
+            }
+            None => {
+                let stats = compute_numstat(repo_path, &entry.file, upstream);
+                if let Some(key) = keys[index].clone() {
+                    computed.push((key, stats));
                 }
-                committed.push(file);
+                stats
             }
-        }
-        staged_unstaged.splice(0..0, committed);
+        };
+        entry.file.additions = add;
+        entry.file.deletions = del;
     }
 
-    let upstream = upstream.as_deref();
-    for file in &mut staged_unstaged {
-        let (add, del) = compute_numstat(repo_path, file, upstream);
-        file.additions = add;
-        file.deletions = del;
+    // Reason: Files may change while their stats are computed. Only results whose blob ids are
+    // unchanged afterwards are cached, so a stored value always matches the content it is keyed by.
+    if !computed.is_empty() {
+        let current = collect_change_entries(repo_path, upstream)
+            .map(|entries| {
+                entries
+                    .iter()
+                    .filter_map(|entry| entry.stats_key(repo_path))
+                    .collect::<HashSet<_>>()
+            })
+            .unwrap_or_default();
+        computed.retain(|(key, _)| current.contains(key));
     }
 
-    Ok(staged_unstaged)
+    let live = keys.into_iter().flatten().collect::<HashSet<_>>();
+    let mut map = cache.entries.lock().expect("diff stats cache poisoned");
+    map.retain(|key, _| key.worktree_path != repo_path || live.contains(key));
+    let stored = computed.len();
+    map.extend(computed);
+    drop(map);
+
+    let total_hits = cache.hits.fetch_add(hits, Ordering::Relaxed) + hits;
+    tracing::debug!(
+        worktree = %repo_path.display(),
+        files = entries.len(),
+        hits,
+        stored,
+        total_hits,
+        "collected worktree changes"
+    );
+
+    Ok(entries.into_iter().map(|entry| entry.file).collect())
 }
 
-pub fn collect_diff(repo_path: &Path) -> anyhow::Result<Vec<WorkspaceDiffFileSnapshot>> {
+pub const DEFAULT_DIFF_CONTEXT_LINES: u32 = 3;
+
+/// Upper bound on the files returned by one `collect_diff_page` call.
+pub const MAX_DIFF_PAGE_LIMIT: usize = 200;
+
+pub fn collect_diff(
+    repo_path: &Path,
+    context_lines: u32,
+) -> anyhow::Result<Vec<WorkspaceDiffFileSnapshot>> {
     let upstream = upstream_ref(repo_path);
     let mut files = collect_changes(repo_path)?;
 
     // Ensure deterministic ordering: group then path.
     files.sort_by(|a, b| {
-        fn rank(group: FileChangeGroup) -> u8 {
-            match group {
-                FileChangeGroup::Committed => 0,
-                FileChangeGroup::Staged => 1,
-                FileChangeGroup::Unstaged => 2,
-            }
-        }
-
-        rank(a.group)
-            .cmp(&rank(b.group))
+        group_rank(a.group)
+            .cmp(&group_rank(b.group))
             .then_with(|| a.path.cmp(&b.path))
     });
 
-    let mut out = Vec::with_capacity(files.len());
-    for file in files {
-        let (old_contents, new_contents) =
-            diff_contents_for_file(repo_path, &file, upstream.as_deref());
-        out.push(WorkspaceDiffFileSnapshot {
-            old_file: DiffFileContents {
-                name: file.name.clone(),
-                contents: old_contents,
-            },
-            new_file: DiffFileContents {
-                name: file.name.clone(),
-                contents: new_contents,
-            },
-            file,
-        });
+    Ok(files
+        .into_iter()
+        .map(|file| diff_file_snapshot(repo_path, file, upstream.as_deref(), context_lines))
+        .collect())
+}
+
+/// Loads the diff of `limit` changed files starting at `offset`, ordered by path (then group, for
+/// a path changed in several groups) so pages stay stable while files are added or removed
+/// elsewhere. Only files on the page have their contents loaded. Returns the page and the total
+/// number of changed files.
+pub fn collect_diff_page(
+    repo_path: &Path,
+    offset: usize,
+    limit: usize,
+    context_lines: u32,
+) -> anyhow::Result<(Vec<WorkspaceDiffFileSnapshot>, usize)> {
+    let upstream = upstream_ref(repo_path);
+    let mut files = collect_changes(repo_path)?;
+    files.sort_by(|a, b| {
+        a.path
+            .cmp(&b.path)
+            .then_with(|| group_rank(a.group).cmp(&group_rank(b.group)))
+    });
+
+    let total = files.len();
+    let page = files
+        .into_iter()
+        .skip(offset)
+        .take(limit.min(MAX_DIFF_PAGE_LIMIT))
+        .map(|file| diff_file_snapshot(repo_path, file, upstream.as_deref(), context_lines))
+        .collect();
+    Ok((page, total))
+}
+
+fn group_rank(group: FileChangeGroup) -> u8 {
+    match group {
+        FileChangeGroup::Committed => 0,
+        FileChangeGroup::Staged => 1,
+        FileChangeGroup::Unstaged => 2,
+    }
+}
+
+/// Binary files (as detected by git) are returned with empty contents and no hunks.
+fn diff_file_snapshot(
+    repo_path: &Path,
+    file: ChangedFileSnapshot,
+    upstream: Option<&str>,
+    context_lines: u32,
+) -> WorkspaceDiffFileSnapshot {
+    let (hunks, binary) = diff_hunks_for_file(repo_path, &file, upstream, context_lines);
+    let (old_contents, new_contents) = if binary {
+        (String::new(), String::new())
+    } else {
+        diff_contents_for_file(repo_path, &file, upstream)
+    };
+    WorkspaceDiffFileSnapshot {
+        old_file: DiffFileContents {
+            name: file.name.clone(),
+            contents: old_contents,
+        },
+        new_file: DiffFileContents {
+            name: file.name.clone(),
+            contents: new_contents,
+        },
+        file,
+        hunks,
+        binary,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn git(repo_path: &Path, args: &[&str]) {
+        run_git_bytes(repo_path, args).expect("git command failed");
+    }
+
+    #[test]
+    fn context_lines_control_hunk_size() {
+        let repo = tempfile::tempdir().expect("temp dir");
+        let repo_path = repo.path();
+        git(repo_path, &["init"]);
+        git(repo_path, &["config", "user.email", "diff@example.com"]);
+        git(repo_path, &["config", "user.name", "luban-diff"]);
+        let original = (1..=20).map(|n| format!("line {n}\n")).collect::<String>();
+        std::fs::write(repo_path.join("file.txt"), &original).expect("write file");
+        git(repo_path, &["add", "."]);
+        git(repo_path, &["commit", "-m", "init"]);
+        std::fs::write(
+            repo_path.join("file.txt"),
+            original.replace("line 10\n", "line ten\n"),
+        )
+        .expect("modify file");
+        std::fs::write(repo_path.join("new.txt"), "a\nb\n").expect("write untracked file");
+
+        let hunks_for = |context_lines, path: &str| {
+            collect_diff(repo_path, context_lines)
+                .expect("collect diff")
+                .into_iter()
+                .find(|f| f.file.path == path)
+                .expect("file should be in the diff")
+                .hunks
+        };
+
+        let tight = hunks_for(0, "file.txt");
+        assert_eq!(tight.len(), 1);
+        assert_eq!(tight[0].lines, vec!["-line 10", "+line ten"]);
+        assert_eq!((tight[0].old_start, tight[0].old_lines), (10, 1));
+
+        let default = hunks_for(DEFAULT_DIFF_CONTEXT_LINES, "file.txt");
+        assert_eq!(default.len(), 1);
+        assert_eq!(default[0].lines.len(), 8);
+        assert_eq!((default[0].old_start, default[0].old_lines), (7, 7));
+
+        let untracked = hunks_for(0, "new.txt");
+        assert_eq!(untracked.len(), 1);
+        assert_eq!(untracked[0].lines, vec!["+a", "+b"]);
+    }
+
+    #[test]
+    fn diff_pages_are_ordered_by_path_and_flag_binary_files() {
+        let repo = tempfile::tempdir().expect("temp dir");
+        let repo_path = repo.path();
+        git(repo_path, &["init"]);
+        git(repo_path, &["config", "user.email", "diff@example.com"]);
+        git(repo_path, &["config", "user.name", "luban-diff"]);
+        std::fs::write(repo_path.join("b.txt"), "b\n").expect("write b");
+        std::fs::write(repo_path.join("image.bin"), [0u8, 159, 146, 150]).expect("write bin");
+        git(repo_path, &["add", "."]);
+        git(repo_path, &["commit", "-m", "init"]);
+
+        std::fs::write(repo_path.join("b.txt"), "b2\n").expect("modify b");
+        std::fs::write(repo_path.join("image.bin"), [0u8, 1, 2, 3]).expect("modify bin");
+        std::fs::write(repo_path.join("a.txt"), "a\n").expect("write a");
+        std::fs::write(repo_path.join("c.txt"), "c\n").expect("write c");
+        git(repo_path, &["add", "c.txt"]);
+
+        let page = |offset, limit| {
+            let (files, total) = collect_diff_page(repo_path, offset, limit, 3).expect("diff page");
+            assert_eq!(total, 4);
+            files
+        };
+        let paths = |files: &[WorkspaceDiffFileSnapshot]| {
+            files
+                .iter()
+                .map(|f| f.file.path.clone())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(paths(&page(0, 2)), ["a.txt", "b.txt"]);
+        let rest = page(2, 10);
+        assert_eq!(paths(&rest), ["c.txt", "image.bin"]);
+        assert!(page(4, 10).is_empty());
+
+        let binary = &rest[1];
+        assert!(binary.binary);
+        assert!(binary.hunks.is_empty());
+        assert!(binary.old_file.contents.is_empty() && binary.new_file.contents.is_empty());
+        assert!(!rest[0].binary);
+        assert_eq!(rest[0].new_file.contents, "c\n");
+    }
+
+    #[test]
+    fn diff_stats_cache_reuses_unchanged_files_and_never_serves_stale_stats() {
+        let repo = tempfile::tempdir().expect("temp dir");
+        let repo_path = repo.path();
+        git(repo_path, &["init"]);
+        git(repo_path, &["config", "user.email", "diff@example.com"]);
+        git(repo_path, &["config", "user.name", "luban-diff"]);
+        std::fs::write(repo_path.join("a.txt"), "one\ntwo\n").expect("write a");
+        std::fs::write(repo_path.join("b.txt"), "x\n").expect("write b");
+        git(repo_path, &["add", "."]);
+        git(repo_path, &["commit", "-m", "init"]);
+
+        std::fs::write(repo_path.join("a.txt"), "one\ntwo\nthree\n").expect("modify a");
+        std::fs::write(repo_path.join("b.txt"), "y\n").expect("modify b");
+        std::fs::write(repo_path.join("c.txt"), "new\n").expect("write c");
+        git(repo_path, &["add", "b.txt"]);
+
+        let cache = DiffStatsCache::default();
+        let hits = || cache.hits.load(Ordering::Relaxed);
+        let stats = |cache: &DiffStatsCache| {
+            let mut files = collect_changes_with_cache(repo_path, cache)
+                .expect("collect changes")
+                .into_iter()
+                .map(|f| (f.id, f.additions, f.deletions))
+                .collect::<Vec<_>>();
+            files.sort();
+            files
+        };
+
+        let first = stats(&cache);
+        assert_eq!(
+            first,
+            vec![
+                ("staged:b.txt".to_owned(), Some(1), Some(1)),
+                ("unstaged:a.txt".to_owned(), Some(1), Some(0)),
+                ("unstaged:c.txt".to_owned(), Some(1), Some(0)),
+            ]
+        );
+        assert_eq!(hits(), 0);
+
+        assert_eq!(stats(&cache), first);
+        assert_eq!(hits(), 3);
+
+        std::fs::write(repo_path.join("a.txt"), "one\n").expect("shrink a");
+        std::fs::remove_file(repo_path.join("c.txt")).expect("remove c");
+        let after = stats(&cache);
+        assert_eq!(
+            after,
+            vec![
+                ("staged:b.txt".to_owned(), Some(1), Some(1)),
+                ("unstaged:a.txt".to_owned(), Some(0), Some(1)),
+            ]
+        );
+        assert_eq!(hits(), 4, "only b.txt is unchanged");
+
+        let entries = cache.entries.lock().expect("cache lock");
+        assert_eq!(entries.len(), 2);
+        assert!(entries.keys().all(|key| key.file_id != "unstaged:c.txt"));
     }
-    Ok(out)
 }
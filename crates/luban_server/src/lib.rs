@@ -1,6 +1,7 @@
 use anyhow::Context as _;
 use axum::Router;
 use std::net::SocketAddr;
+use std::time::Duration;
 
 mod auth;
 mod branch_watch;
@@ -8,10 +9,14 @@ pub mod engine;
 mod git_changes;
 mod idempotency;
 mod mentions;
+mod pr_description;
 mod project_avatars;
 pub mod pty;
 pub mod server;
 mod telegram;
+mod transcript;
+mod webhook;
+mod ws_coalesce;
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum AuthMode {
@@ -34,9 +39,111 @@ impl Default for AuthConfig {
     }
 }
 
-#[derive(Clone, Debug, Default)]
+/// Page sizes used when loading conversation entries from storage.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ConversationLoadConfig {
+    /// Entries returned when a conversation request does not specify a limit.
+    pub default_entries_limit: usize,
+    /// Upper bound for conversation requests and the initial conversation load.
+    pub max_entries_limit: usize,
+}
+
+impl ConversationLoadConfig {
+    pub const MAX_ENTRIES_LIMIT_CEILING: usize = 5000;
+
+    pub fn clamped(self) -> Self {
+        let max_entries_limit = self
+            .max_entries_limit
+            .clamp(1, Self::MAX_ENTRIES_LIMIT_CEILING);
+        Self {
+            default_entries_limit: self.default_entries_limit.clamp(1, max_entries_limit),
+            max_entries_limit,
+        }
+    }
+}
+
+impl Default for ConversationLoadConfig {
+    fn default() -> Self {
+        Self {
+            default_entries_limit: 2000,
+            max_entries_limit: 5000,
+        }
+    }
+}
+
+/// Server-initiated keepalive for the `/api/events` websocket.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct WsKeepaliveConfig {
+    /// How often the server sends a websocket ping frame.
+    pub ping_interval: Duration,
+    /// Connections that send no pong (or any other frame) for this long are dropped.
+    pub pong_timeout: Duration,
+}
+
+impl Default for WsKeepaliveConfig {
+    fn default() -> Self {
+        Self {
+            ping_interval: Duration::from_secs(30),
+            pong_timeout: Duration::from_secs(90),
+        }
+    }
+}
+
+/// Agent turns allowed to run at once unless `LUBAN_MAX_CONCURRENT_TURNS` overrides it.
+pub const DEFAULT_MAX_CONCURRENT_TURNS: usize = 4;
+
+/// Attachment bytes allowed per message unless `LUBAN_MAX_ATTACHMENT_BYTES` overrides it.
+pub const DEFAULT_MAX_ATTACHMENT_BYTES: u64 = 20 * 1024 * 1024;
+
+#[derive(Clone, Debug)]
 pub struct ServerConfig {
     pub auth: AuthConfig,
+    pub conversation_load: ConversationLoadConfig,
+    pub ws_keepalive: WsKeepaliveConfig,
+    /// Seed the current working directory as a project on first launch (git repos only).
+    pub seed_cwd_project: bool,
+    /// Run `git fetch` in a workspace before refreshing its pull request info.
+    pub fetch_before_pull_request_refresh: bool,
+    /// Client action `type` names (e.g. `delete_project`) the engine refuses to apply.
+    pub denied_client_actions: std::collections::HashSet<String>,
+    /// Agent turns allowed to run at once across all tasks; `0` means unlimited.
+    pub max_concurrent_turns: usize,
+    /// Ceiling for each attachment and for all attachments of one message; `0` means unlimited.
+    pub max_attachment_bytes: u64,
+    /// Limits on stored conversation history, enforced by a periodic maintenance task.
+    pub conversation_retention: luban_domain::ConversationRetentionPolicy,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            auth: AuthConfig::default(),
+            conversation_load: ConversationLoadConfig::default(),
+            ws_keepalive: WsKeepaliveConfig::default(),
+            seed_cwd_project: false,
+            fetch_before_pull_request_refresh: false,
+            denied_client_actions: std::collections::HashSet::new(),
+            max_concurrent_turns: DEFAULT_MAX_CONCURRENT_TURNS,
+            max_attachment_bytes: DEFAULT_MAX_ATTACHMENT_BYTES,
+            conversation_retention: luban_domain::ConversationRetentionPolicy::default(),
+        }
+    }
+}
+
+/// Maps `DeleteProject` or ` delete_project ` to the wire `type` name `delete_project`.
+fn normalize_client_action_type(raw: &str) -> String {
+    let mut out = String::new();
+    for (idx, ch) in raw.trim().chars().enumerate() {
+        if ch.is_ascii_uppercase() {
+            if idx > 0 && !out.ends_with('_') {
+                out.push('_');
+            }
+            out.push(ch.to_ascii_lowercase());
+        } else {
+            out.push(ch);
+        }
+    }
+    out
 }
 
 impl ServerConfig {
@@ -59,16 +166,119 @@ impl ServerConfig {
             .map(|v| v.trim().to_owned())
             .filter(|v| !v.is_empty());
 
+        if let Some(limit) = std::env::var("LUBAN_CONVERSATION_PAGE_SIZE")
+            .ok()
+            .and_then(|v| v.trim().parse::<usize>().ok())
+        {
+            out.conversation_load.default_entries_limit = limit;
+        }
+        if let Some(limit) = std::env::var("LUBAN_CONVERSATION_MAX_PAGE_SIZE")
+            .ok()
+            .and_then(|v| v.trim().parse::<usize>().ok())
+        {
+            out.conversation_load.max_entries_limit = limit;
+        }
+        out.conversation_load = out.conversation_load.clamped();
+
+        if let Some(secs) = std::env::var("LUBAN_WS_PING_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .filter(|secs| *secs > 0)
+        {
+            out.ws_keepalive.ping_interval = Duration::from_secs(secs);
+        }
+        if let Some(secs) = std::env::var("LUBAN_WS_PING_TIMEOUT_SECS")
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .filter(|secs| *secs > 0)
+        {
+            out.ws_keepalive.pong_timeout = Duration::from_secs(secs);
+        }
+
+        out.seed_cwd_project = std::env::var("LUBAN_SEED_CWD_PROJECT")
+            .ok()
+            .map(|v| v.trim().to_ascii_lowercase())
+            .is_some_and(|v| v == "1" || v == "true" || v == "yes");
+
+        out.fetch_before_pull_request_refresh = std::env::var("LUBAN_FETCH_BEFORE_PR_REFRESH")
+            .ok()
+            .map(|v| v.trim().to_ascii_lowercase())
+            .is_some_and(|v| v == "1" || v == "true" || v == "yes");
+
+        out.denied_client_actions = std::env::var("LUBAN_DENIED_ACTIONS")
+            .unwrap_or_default()
+            .split(',')
+            .map(normalize_client_action_type)
+            .filter(|name| !name.is_empty())
+            .collect();
+
+        if let Some(limit) = std::env::var("LUBAN_MAX_CONCURRENT_TURNS")
+            .ok()
+            .and_then(|v| v.trim().parse::<usize>().ok())
+        {
+            out.max_concurrent_turns = limit;
+        }
+
+        if let Some(limit) = std::env::var("LUBAN_MAX_ATTACHMENT_BYTES")
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+        {
+            out.max_attachment_bytes = limit;
+        }
+
+        let positive = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .filter(|v| *v > 0)
+        };
+        out.conversation_retention = luban_domain::ConversationRetentionPolicy {
+            keep_last_entries: positive("LUBAN_CONVERSATION_KEEP_LAST_ENTRIES"),
+            max_age_days: positive("LUBAN_CONVERSATION_RETENTION_DAYS"),
+        };
+
+        out
+    }
+
+    /// Optional features advertised to clients in the websocket `Hello` handshake.
+    pub fn capabilities(&self) -> Vec<String> {
+        // The Server-Sent Events fallback of `GET /api/events` is always served.
+        let mut out = vec!["sse_events".to_owned()];
+        if self.auth.mode == AuthMode::SingleUser {
+            out.push("auth".to_owned());
+        }
+        if self.seed_cwd_project {
+            out.push("seed_cwd_project".to_owned());
+        }
+        if self.conversation_retention.is_enabled() {
+            out.push("conversation_retention".to_owned());
+        }
+        let mut denied = self
+            .denied_client_actions
+            .iter()
+            .map(|name| format!("denied_action:{name}"))
+            .collect::<Vec<_>>();
+        denied.sort();
+        out.extend(denied);
         out
     }
 }
 
 pub struct StartedServer {
     pub addr: SocketAddr,
+    engine: engine::EngineHandle,
     handle: Option<tokio::task::JoinHandle<anyhow::Result<()>>>,
 }
 
 impl StartedServer {
+    /// Stops the engine after it handled every queued command and finished persisting, then
+    /// stops serving. Prefer this over dropping the server when the app quits.
+    pub async fn shutdown(self) -> anyhow::Result<()> {
+        let result = self.engine.shutdown().await;
+        drop(self);
+        result
+    }
+
     pub async fn wait(self) -> anyhow::Result<()> {
         let mut this = self;
         let handle = this.handle.take().context("server task already consumed")?;
@@ -97,7 +307,7 @@ pub async fn start_server_with_config(
     addr: SocketAddr,
     config: ServerConfig,
 ) -> anyhow::Result<StartedServer> {
-    let app: Router = server::router(config).await?;
+    let (app, engine): (Router, engine::EngineHandle) = server::router_with_engine(config).await?;
 
     let listener = tokio::net::TcpListener::bind(addr)
         .await
@@ -112,6 +322,7 @@ pub async fn start_server_with_config(
 
     Ok(StartedServer {
         addr: actual,
+        engine,
         handle: Some(handle),
     })
 }
@@ -179,6 +390,20 @@ mod tests {
         assert_eq!(cfg.auth.bootstrap_token, None);
     }
 
+    #[test]
+    fn server_config_from_env_parses_seed_cwd_project() {
+        let env = EnvGuard::lock(vec!["LUBAN_SEED_CWD_PROJECT"]);
+
+        env.remove("LUBAN_SEED_CWD_PROJECT");
+        assert!(!ServerConfig::from_env().seed_cwd_project);
+
+        env.set("LUBAN_SEED_CWD_PROJECT", " TRUE ");
+        assert!(ServerConfig::from_env().seed_cwd_project);
+
+        env.set("LUBAN_SEED_CWD_PROJECT", "0");
+        assert!(!ServerConfig::from_env().seed_cwd_project);
+    }
+
     #[test]
     fn server_config_from_env_parses_auth_mode_single_user() {
         let env = EnvGuard::lock(vec!["LUBAN_AUTH_MODE"]);
@@ -196,6 +421,130 @@ mod tests {
         }
     }
 
+    #[test]
+    fn server_config_from_env_parses_denied_actions() {
+        let env = EnvGuard::lock(vec!["LUBAN_DENIED_ACTIONS"]);
+
+        env.remove("LUBAN_DENIED_ACTIONS");
+        assert!(ServerConfig::from_env().denied_client_actions.is_empty());
+
+        env.set("LUBAN_DENIED_ACTIONS", "DeleteProject, archive_workdir,,");
+        let mut denied = ServerConfig::from_env()
+            .denied_client_actions
+            .into_iter()
+            .collect::<Vec<_>>();
+        denied.sort();
+        assert_eq!(denied, vec!["archive_workdir", "delete_project"]);
+    }
+
+    #[test]
+    fn capabilities_follow_server_config() {
+        assert_eq!(ServerConfig::default().capabilities(), vec!["sse_events"]);
+
+        let cfg = ServerConfig {
+            auth: AuthConfig {
+                mode: AuthMode::SingleUser,
+                bootstrap_token: None,
+            },
+            seed_cwd_project: true,
+            denied_client_actions: ["delete_project", "archive_workdir"]
+                .into_iter()
+                .map(str::to_owned)
+                .collect(),
+            conversation_retention: luban_domain::ConversationRetentionPolicy {
+                keep_last_entries: Some(100),
+                max_age_days: None,
+            },
+            ..Default::default()
+        };
+        assert_eq!(
+            cfg.capabilities(),
+            vec![
+                "sse_events",
+                "auth",
+                "seed_cwd_project",
+                "conversation_retention",
+                "denied_action:archive_workdir",
+                "denied_action:delete_project",
+            ]
+        );
+    }
+
+    #[test]
+    fn server_config_from_env_parses_max_concurrent_turns() {
+        let env = EnvGuard::lock(vec!["LUBAN_MAX_CONCURRENT_TURNS"]);
+
+        env.remove("LUBAN_MAX_CONCURRENT_TURNS");
+        assert_eq!(
+            ServerConfig::from_env().max_concurrent_turns,
+            DEFAULT_MAX_CONCURRENT_TURNS
+        );
+
+        env.set("LUBAN_MAX_CONCURRENT_TURNS", " 2 ");
+        assert_eq!(ServerConfig::from_env().max_concurrent_turns, 2);
+
+        env.set("LUBAN_MAX_CONCURRENT_TURNS", "0");
+        assert_eq!(ServerConfig::from_env().max_concurrent_turns, 0);
+
+        env.set("LUBAN_MAX_CONCURRENT_TURNS", "lots");
+        assert_eq!(
+            ServerConfig::from_env().max_concurrent_turns,
+            DEFAULT_MAX_CONCURRENT_TURNS
+        );
+    }
+
+    #[test]
+    fn server_config_from_env_parses_max_attachment_bytes() {
+        let env = EnvGuard::lock(vec!["LUBAN_MAX_ATTACHMENT_BYTES"]);
+
+        env.remove("LUBAN_MAX_ATTACHMENT_BYTES");
+        assert_eq!(
+            ServerConfig::from_env().max_attachment_bytes,
+            DEFAULT_MAX_ATTACHMENT_BYTES
+        );
+
+        env.set("LUBAN_MAX_ATTACHMENT_BYTES", " 1048576 ");
+        assert_eq!(ServerConfig::from_env().max_attachment_bytes, 1048576);
+
+        env.set("LUBAN_MAX_ATTACHMENT_BYTES", "big");
+        assert_eq!(
+            ServerConfig::from_env().max_attachment_bytes,
+            DEFAULT_MAX_ATTACHMENT_BYTES
+        );
+    }
+
+    #[test]
+    fn server_config_from_env_parses_conversation_retention() {
+        let env = EnvGuard::lock(vec![
+            "LUBAN_CONVERSATION_KEEP_LAST_ENTRIES",
+            "LUBAN_CONVERSATION_RETENTION_DAYS",
+        ]);
+
+        env.remove("LUBAN_CONVERSATION_KEEP_LAST_ENTRIES");
+        env.remove("LUBAN_CONVERSATION_RETENTION_DAYS");
+        assert!(!ServerConfig::from_env().conversation_retention.is_enabled());
+
+        env.set("LUBAN_CONVERSATION_KEEP_LAST_ENTRIES", " 500 ");
+        env.set("LUBAN_CONVERSATION_RETENTION_DAYS", "0");
+        assert_eq!(
+            ServerConfig::from_env().conversation_retention,
+            luban_domain::ConversationRetentionPolicy {
+                keep_last_entries: Some(500),
+                max_age_days: None,
+            }
+        );
+
+        env.set("LUBAN_CONVERSATION_KEEP_LAST_ENTRIES", "all");
+        env.set("LUBAN_CONVERSATION_RETENTION_DAYS", "30");
+        assert_eq!(
+            ServerConfig::from_env().conversation_retention,
+            luban_domain::ConversationRetentionPolicy {
+                keep_last_entries: None,
+                max_age_days: Some(30),
+            }
+        );
+    }
+
     #[test]
     fn server_config_from_env_trims_bootstrap_token() {
         let env = EnvGuard::lock(vec!["LUBAN_AUTH_BOOTSTRAP_TOKEN"]);
@@ -1,6 +1,13 @@
 use anyhow::Context as _;
 use luban_api::{MentionItemKind, MentionItemSnapshot};
 
+/// Items returned when a mention request does not specify a limit.
+pub const DEFAULT_MENTION_LIMIT: usize = 20;
+/// Upper bound for the `limit` query parameter.
+pub const MAX_MENTION_LIMIT: usize = 200;
+/// Matching files collected before ranking; the limit is applied after sorting by mtime.
+const MAX_MENTION_CANDIDATES: usize = 2000;
+
 fn should_skip_dir(name: &str) -> bool {
     matches!(name, ".git" | "target" | "node_modules")
 }
@@ -42,6 +49,53 @@ fn fuzzy_match_ascii(needle_lower: &[u8], haystack: &[u8]) -> bool {
     true
 }
 
+/// Lists tracked and untracked-but-not-ignored files via git, which honors `.gitignore`,
+/// `.git/info/exclude` and the global excludes file. Returns `None` outside a git worktree or
+/// when git is unavailable.
+fn scan_paths_with_git(
+    worktree_path: &std::path::Path,
+    needle_lower: &[u8],
+    max_files: usize,
+) -> Option<Vec<(String, String)>> {
+    let output = std::process::Command::new("git")
+        .args([
+            "ls-files",
+            "--cached",
+            "--others",
+            "--exclude-standard",
+            "-z",
+        ])
+        .current_dir(worktree_path)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let mut out: Vec<(String, String)> = Vec::with_capacity(max_files.min(64));
+    let mut seen = std::collections::HashSet::new();
+    for raw in output.stdout.split(|b| *b == 0) {
+        if raw.is_empty() {
+            continue;
+        }
+        let file = String::from_utf8_lossy(raw).replace('\\', "/");
+        let name = file.rsplit('/').next().unwrap_or(file.as_str());
+        if !fuzzy_match_ascii(needle_lower, name.as_bytes()) {
+            continue;
+        }
+        // Deleted-but-tracked files are still listed by `--cached`.
+        if !worktree_path.join(&file).is_file() || !seen.insert(file.clone()) {
+            continue;
+        }
+        let name_lower = name.to_ascii_lowercase();
+        out.push((file, name_lower));
+        if out.len() >= max_files {
+            break;
+        }
+    }
+    Some(out)
+}
+
 fn scan_paths_without_rg(
     worktree_path: &std::path::Path,
     needle_lower: &[u8],
@@ -106,23 +160,29 @@ fn scan_paths_without_rg(
     Ok(out)
 }
 
+/// Fuzzy-matches `query` against file names in the worktree, skipping git-ignored paths.
+/// Matching folders come first, then files; each group is ordered by most recent modification.
 pub fn search_workspace_mentions(
     worktree_path: &std::path::Path,
     query: &str,
+    limit: usize,
 ) -> anyhow::Result<Vec<MentionItemSnapshot>> {
+    let limit = limit.clamp(1, MAX_MENTION_LIMIT);
     let trimmed = query.trim();
     if trimmed.is_empty() {
         return Ok(Vec::new());
     }
 
     let glob = fuzzy_glob_pattern(trimmed);
-    let max_files = 200usize;
+    let max_files = MAX_MENTION_CANDIDATES;
 
     let needle_lower = trimmed.to_ascii_lowercase();
     let needle_bytes = needle_lower.as_bytes();
 
-    let mut file_paths: Vec<(String, String)> = match std::process::Command::new("rg")
-        .args(["--files", "--hidden", "--sort", "path", "--iglob", &glob])
+    let file_paths: Vec<(String, String)> = match std::process::Command::new("rg")
+        .args([
+            "--files", "--hidden", "--sort", "path", "--iglob", &glob, "--glob", "!.git",
+        ])
         .current_dir(worktree_path)
         .output()
     {
@@ -135,7 +195,6 @@ pub fn search_workspace_mentions(
                 );
             }
 
-            let max_rg_lines = 2000usize;
             let mut out = Vec::with_capacity(max_files.min(64));
             for line in String::from_utf8_lossy(&output.stdout).lines() {
                 let line = line.trim();
@@ -154,26 +213,42 @@ pub fn search_workspace_mentions(
                 if out.len() >= max_files {
                     break;
                 }
-                if out.len() >= max_rg_lines {
-                    break;
-                }
             }
             out
         }
         Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
-            scan_paths_without_rg(worktree_path, needle_bytes, max_files)?
+            match scan_paths_with_git(worktree_path, needle_bytes, max_files) {
+                Some(out) => out,
+                None => scan_paths_without_rg(worktree_path, needle_bytes, max_files)?,
+            }
         }
         Err(err) => return Err(err).context("failed to execute rg"),
     };
 
-    file_paths.sort_by(|(a_path, a_name_lower), (b_path, b_name_lower)| {
-        a_name_lower
-            .cmp(b_name_lower)
-            .then_with(|| a_path.cmp(b_path))
-    });
+    let modified = |file: &str| {
+        std::fs::metadata(worktree_path.join(file))
+            .and_then(|meta| meta.modified())
+            .unwrap_or(std::time::SystemTime::UNIX_EPOCH)
+    };
+    let mut file_paths = file_paths
+        .into_iter()
+        .map(|(path, name_lower)| {
+            let mtime = modified(&path);
+            (path, name_lower, mtime)
+        })
+        .collect::<Vec<_>>();
+    file_paths.sort_by(
+        |(a_path, a_name_lower, a_mtime), (b_path, b_name_lower, b_mtime)| {
+            b_mtime
+                .cmp(a_mtime)
+                .then_with(|| a_name_lower.cmp(b_name_lower))
+                .then_with(|| a_path.cmp(b_path))
+        },
+    );
 
-    let mut folder_paths = std::collections::BTreeSet::new();
-    for (file, _) in &file_paths {
+    // Folders inherit the newest modification time of the matched files beneath them.
+    let mut folder_paths: Vec<(String, std::time::SystemTime)> = Vec::new();
+    for (file, _, mtime) in &file_paths {
         let path = std::path::Path::new(file);
         let mut parent = path.parent();
         while let Some(dir) = parent {
@@ -183,14 +258,19 @@ pub fn search_workspace_mentions(
             }
             let name = s.rsplit('/').next().unwrap_or(&s);
             if fuzzy_match_ascii(needle_bytes, name.as_bytes()) {
-                folder_paths.insert(format!("{}/", s.trim_end_matches('/')));
+                let folder = format!("{}/", s.trim_end_matches('/'));
+                if !folder_paths.iter().any(|(existing, _)| *existing == folder) {
+                    folder_paths.push((folder, *mtime));
+                }
             }
             parent = dir.parent();
         }
     }
 
+    folder_paths.sort_by(|(a, a_mtime), (b, b_mtime)| b_mtime.cmp(a_mtime).then_with(|| a.cmp(b)));
+
     let mut items = Vec::new();
-    for folder in folder_paths.into_iter() {
+    for (folder, _) in folder_paths.into_iter() {
         let name = folder
             .trim_end_matches('/')
             .rsplit('/')
@@ -203,12 +283,12 @@ pub fn search_workspace_mentions(
             path: folder,
             kind: MentionItemKind::Folder,
         });
-        if items.len() >= 20 {
+        if items.len() >= limit {
             return Ok(items);
         }
     }
 
-    for (file, _) in file_paths.into_iter() {
+    for (file, _, _) in file_paths.into_iter() {
         let name = file.rsplit('/').next().unwrap_or(&file).to_owned();
         items.push(MentionItemSnapshot {
             id: format!("file:{file}"),
@@ -216,7 +296,7 @@ pub fn search_workspace_mentions(
             path: file,
             kind: MentionItemKind::File,
         });
-        if items.len() >= 20 {
+        if items.len() >= limit {
             break;
         }
     }
@@ -239,6 +319,83 @@ mod tests {
         assert!(out.iter().any(|(path, _)| path == "README.md"));
     }
 
+    fn git_init(dir: &std::path::Path) {
+        let status = std::process::Command::new("git")
+            .args(["init", "-q"])
+            .current_dir(dir)
+            .status()
+            .expect("git init");
+        assert!(status.success());
+    }
+
+    #[test]
+    fn mentions_skip_git_ignored_paths() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        git_init(dir.path());
+        std::fs::write(dir.path().join(".gitignore"), b"dist/\n").expect("write");
+        std::fs::write(dir.path().join(".git/info/exclude"), b"local-notes.md\n").expect("write");
+        std::fs::create_dir_all(dir.path().join("dist/docs")).expect("mkdir");
+        std::fs::write(dir.path().join("dist/docs/notes.md"), b"built").expect("write");
+        std::fs::write(dir.path().join("local-notes.md"), b"mine").expect("write");
+        std::fs::create_dir_all(dir.path().join("docs")).expect("mkdir");
+        std::fs::write(dir.path().join("docs/notes.md"), b"kept").expect("write");
+
+        let items =
+            search_workspace_mentions(dir.path(), "notes", DEFAULT_MENTION_LIMIT).expect("search");
+        let paths = items.iter().map(|i| i.path.as_str()).collect::<Vec<_>>();
+        assert_eq!(paths, vec!["docs/notes.md"]);
+    }
+
+    #[test]
+    fn mentions_prefer_recently_modified_files_and_respect_the_limit() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        git_init(dir.path());
+        let now = std::time::SystemTime::now();
+        for (idx, name) in ["a_plan.md", "b_plan.md", "c_plan.md"]
+            .into_iter()
+            .enumerate()
+        {
+            let path = dir.path().join(name);
+            std::fs::write(&path, b"plan").expect("write");
+            let age = std::time::Duration::from_secs(60 * (3 - idx as u64));
+            std::fs::File::options()
+                .write(true)
+                .open(&path)
+                .and_then(|f| f.set_modified(now - age))
+                .expect("set mtime");
+        }
+        std::fs::File::options()
+            .write(true)
+            .open(dir.path().join("a_plan.md"))
+            .and_then(|f| f.set_modified(now))
+            .expect("set mtime");
+
+        let items = search_workspace_mentions(dir.path(), "plan", 2).expect("search");
+        let paths = items.iter().map(|i| i.path.as_str()).collect::<Vec<_>>();
+        assert_eq!(paths, vec!["a_plan.md", "c_plan.md"]);
+    }
+
+    #[test]
+    fn mentions_rank_all_matches_before_applying_the_limit() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        git_init(dir.path());
+        let now = std::time::SystemTime::now();
+        for idx in 0..=MAX_MENTION_LIMIT {
+            let path = dir.path().join(format!("f{idx:03}_plan.md"));
+            std::fs::write(&path, b"plan").expect("write");
+            let age = std::time::Duration::from_secs(60 * (MAX_MENTION_LIMIT - idx) as u64);
+            std::fs::File::options()
+                .write(true)
+                .open(&path)
+                .and_then(|f| f.set_modified(now - age))
+                .expect("set mtime");
+        }
+
+        let items = search_workspace_mentions(dir.path(), "plan", 1).expect("search");
+        let paths = items.iter().map(|i| i.path.as_str()).collect::<Vec<_>>();
+        assert_eq!(paths, vec![format!("f{MAX_MENTION_LIMIT:03}_plan.md")]);
+    }
+
     #[test]
     fn fuzzy_match_ascii_is_ordered() {
         let needle = "rdm".as_bytes();
@@ -0,0 +1,92 @@
+use luban_api::{ChangedFileSnapshot, FileChangeStatus};
+use std::collections::HashSet;
+
+pub(crate) const NO_CHANGES_MESSAGE: &str = "There are no changes in this workdir to describe. \
+Commit or edit some files before generating a pull request description.";
+
+/// Agent messages longer than this are truncated before being sent to the generator.
+const MAX_AGENT_MESSAGE_CHARS: usize = 2000;
+/// Only the most recent agent messages are included to keep the prompt bounded.
+const MAX_AGENT_MESSAGES: usize = 20;
+
+/// Collapses the per-group change list into one entry per path, keeping the first occurrence
+/// (committed changes are listed before staged and unstaged ones).
+pub(crate) fn unique_changed_files(files: Vec<ChangedFileSnapshot>) -> Vec<ChangedFileSnapshot> {
+    let mut seen = HashSet::new();
+    files
+        .into_iter()
+        .filter(|file| seen.insert(file.path.clone()))
+        .collect()
+}
+
+fn status_label(status: FileChangeStatus) -> &'static str {
+    match status {
+        FileChangeStatus::Modified => "modified",
+        FileChangeStatus::Added => "added",
+        FileChangeStatus::Deleted => "deleted",
+        FileChangeStatus::Renamed => "renamed",
+    }
+}
+
+fn file_line(file: &ChangedFileSnapshot) -> String {
+    let mut line = format!("- `{}` ({}", file.path, status_label(file.status));
+    if let Some(old_path) = file.old_path.as_deref() {
+        line.push_str(&format!(" from `{old_path}`"));
+    }
+    if let (Some(additions), Some(deletions)) = (file.additions, file.deletions) {
+        line.push_str(&format!(", +{additions} -{deletions}"));
+    }
+    line.push(')');
+    line
+}
+
+/// Builds the generator input from the changed files and the task's agent messages.
+pub(crate) fn generator_input(files: &[ChangedFileSnapshot], agent_messages: &[String]) -> String {
+    let mut out = String::from("Changed files:\n");
+    for file in files {
+        out.push_str(&file_line(file));
+        out.push('\n');
+    }
+
+    let recent = &agent_messages[agent_messages.len().saturating_sub(MAX_AGENT_MESSAGES)..];
+    if !recent.is_empty() {
+        out.push_str("\nAgent messages:\n");
+        for message in recent {
+            let message = message.trim();
+            if message.is_empty() {
+                continue;
+            }
+            let truncated = message
+                .chars()
+                .take(MAX_AGENT_MESSAGE_CHARS)
+                .collect::<String>();
+            out.push_str("---\n");
+            out.push_str(&truncated);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// Markdown body used when the agent cannot produce a description.
+pub(crate) fn template_body(files: &[ChangedFileSnapshot], agent_messages: &[String]) -> String {
+    let mut out = String::from("## Summary\n\n");
+    match agent_messages
+        .iter()
+        .rev()
+        .map(|message| message.trim())
+        .find(|message| !message.is_empty())
+    {
+        Some(last) => {
+            out.push_str(last);
+            out.push('\n');
+        }
+        None => out.push_str("_No summary available._\n"),
+    }
+    out.push_str("\n## Changes\n\n");
+    for file in files {
+        out.push_str(&file_line(file));
+        out.push('\n');
+    }
+    out
+}
@@ -7,7 +7,7 @@ use std::collections::{HashMap, VecDeque};
 use std::io::{Read as _, Write};
 use std::path::PathBuf;
 use std::sync::atomic::Ordering;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, OnceLock};
 use tokio::sync::{broadcast, mpsc, watch};
 use tokio::time::Duration;
 
@@ -17,6 +17,8 @@ type PtySessions = HashMap<PtyKey, Arc<PtySession>>;
 const MAX_OUTPUT_HISTORY_BYTES: usize = 512 * 1024;
 const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
 const LIVE_BUFFER_CAPACITY: usize = 64;
+const MAX_COMMAND_RECORDS: usize = 500;
+pub const DEFAULT_TERMINAL_HISTORY_LIMIT: usize = 50;
 
 #[derive(Clone, Debug)]
 enum PtyProgram {
@@ -42,9 +44,25 @@ fn trace_bytes(label: &str, bytes: &[u8]) {
     tracing::info!(label = %label, len = bytes.len(), hex = %out);
 }
 
+/// A command started through `PtyManager::spawn_command`.
+struct CommandRecord {
+    command: String,
+    started_at_unix_ms: u64,
+    exit_code: Arc<OnceLock<i32>>,
+}
+
+/// One entry of a workspace's terminal command history, newest first.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TerminalHistoryRecord {
+    pub command: String,
+    pub exit_code: Option<i32>,
+    pub started_at_unix_ms: u64,
+}
+
 #[derive(Clone)]
 pub struct PtyManager {
     inner: Arc<Mutex<PtySessions>>,
+    commands: Arc<Mutex<HashMap<u64, VecDeque<CommandRecord>>>>,
     idle_timeout: Duration,
 }
 
@@ -52,6 +70,7 @@ impl PtyManager {
     pub fn new() -> Self {
         Self {
             inner: Arc::new(Mutex::new(HashMap::new())),
+            commands: Arc::new(Mutex::new(HashMap::new())),
             idle_timeout: DEFAULT_IDLE_TIMEOUT,
         }
     }
@@ -72,12 +91,61 @@ impl PtyManager {
         cwd: PathBuf,
         command: String,
     ) -> anyhow::Result<Arc<PtySession>> {
-        self.get_or_create_with_program(
+        let session = self.get_or_create_with_program(
             workspace_id,
             reconnect,
             cwd,
-            PtyProgram::ShellCommand { command },
-        )
+            PtyProgram::ShellCommand {
+                command: command.clone(),
+            },
+        )?;
+        self.record_command(workspace_id, command, session.exit_code.clone());
+        Ok(session)
+    }
+
+    fn record_command(&self, workspace_id: u64, command: String, exit_code: Arc<OnceLock<i32>>) {
+        let started_at_unix_ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        let mut guard = self.commands.lock().expect("pty command lock poisoned");
+        let records = guard.entry(workspace_id).or_default();
+        records.push_back(CommandRecord {
+            command,
+            started_at_unix_ms,
+            exit_code,
+        });
+        while records.len() > MAX_COMMAND_RECORDS {
+            records.pop_front();
+        }
+    }
+
+    /// Returns up to `limit` commands run in the workspace, newest first.
+    ///
+    /// Consecutive runs of the same command collapse into their newest run.
+    pub fn terminal_history(&self, workspace_id: u64, limit: usize) -> Vec<TerminalHistoryRecord> {
+        let guard = self.commands.lock().expect("pty command lock poisoned");
+        let Some(records) = guard.get(&workspace_id) else {
+            return Vec::new();
+        };
+        let mut out = Vec::<TerminalHistoryRecord>::new();
+        for record in records.iter().rev() {
+            if out.len() >= limit {
+                break;
+            }
+            if out
+                .last()
+                .is_some_and(|last| last.command == record.command)
+            {
+                continue;
+            }
+            out.push(TerminalHistoryRecord {
+                command: record.command.clone(),
+                exit_code: record.exit_code.get().copied(),
+                started_at_unix_ms: record.started_at_unix_ms,
+            });
+        }
+        out
     }
 
     fn get_or_create_with_program(
@@ -121,6 +189,7 @@ pub struct PtySession {
     writer: Arc<Mutex<Option<Box<dyn Write + Send>>>>,
     master: Arc<Mutex<Option<Box<dyn MasterPty + Send>>>>,
     child: Arc<Mutex<Option<Box<dyn portable_pty::Child + Send>>>>,
+    exit_code: Arc<OnceLock<i32>>,
 }
 
 #[derive(Default)]
@@ -234,6 +303,11 @@ impl PtySession {
         let connection_count_for_thread = connection_count_tx.clone();
         let manager_for_thread = manager.clone();
         let key_for_thread = key.clone();
+        let child: Arc<Mutex<Option<Box<dyn portable_pty::Child + Send>>>> =
+            Arc::new(Mutex::new(Some(child)));
+        let child_for_thread = child.clone();
+        let exit_code = Arc::new(OnceLock::new());
+        let exit_code_for_thread = exit_code.clone();
 
         std::thread::Builder::new()
             .name("luban-pty-read".to_owned())
@@ -273,6 +347,9 @@ impl PtySession {
                         Err(_) => break,
                     }
                 }
+                if let Some(code) = reap_exit_code(&child_for_thread) {
+                    let _ = exit_code_for_thread.set(code);
+                }
                 terminated_for_thread.store(true, Ordering::SeqCst);
                 if let Ok(mut guard) = state_for_thread.lock() {
                     guard.active = None;
@@ -294,7 +371,8 @@ impl PtySession {
             state,
             writer: Arc::new(Mutex::new(Some(writer))),
             master: Arc::new(Mutex::new(Some(pair.master))),
-            child: Arc::new(Mutex::new(Some(child))),
+            child,
+            exit_code,
         };
 
         session.spawn_idle_reaper(idle_timeout, manager, key);
@@ -441,6 +519,22 @@ impl PtySession {
     }
 }
 
+/// Polls the child briefly after its output closed; `None` when it is still running or was
+/// already taken by the idle reaper.
+fn reap_exit_code(child: &Mutex<Option<Box<dyn portable_pty::Child + Send>>>) -> Option<i32> {
+    for _ in 0..10 {
+        let status = {
+            let mut guard = child.lock().ok()?;
+            guard.as_mut()?.try_wait().ok()?
+        };
+        if let Some(status) = status {
+            return Some(status.exit_code() as i32);
+        }
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+    None
+}
+
 fn default_shell_path() -> PathBuf {
     if let Some(shell) = std::env::var_os("SHELL")
         && !shell.to_string_lossy().trim().is_empty()
@@ -602,6 +696,39 @@ mod tests {
         assert!(!history.chunks.is_empty());
     }
 
+    #[test]
+    fn terminal_history_dedupes_consecutive_commands_and_caps_at_limit() {
+        let manager = PtyManager::new();
+        let finished = |code: i32| {
+            let cell = Arc::new(OnceLock::new());
+            cell.set(code).expect("set exit code");
+            cell
+        };
+        manager.record_command(1, "cargo build".to_owned(), finished(101));
+        manager.record_command(1, "cargo test".to_owned(), finished(0));
+        manager.record_command(1, "cargo test".to_owned(), finished(1));
+        manager.record_command(1, "cargo build".to_owned(), Arc::new(OnceLock::new()));
+        manager.record_command(2, "ls".to_owned(), finished(0));
+
+        let history = manager.terminal_history(1, DEFAULT_TERMINAL_HISTORY_LIMIT);
+        let commands = history
+            .iter()
+            .map(|r| (r.command.as_str(), r.exit_code))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            commands,
+            vec![
+                ("cargo build", None),
+                ("cargo test", Some(1)),
+                ("cargo build", Some(101)),
+            ]
+        );
+        assert!(history[0].started_at_unix_ms >= history[1].started_at_unix_ms);
+
+        assert_eq!(manager.terminal_history(1, 2).len(), 2);
+        assert!(manager.terminal_history(3, 10).is_empty());
+    }
+
     #[test]
     fn shell_command_args_for_cmd() {
         let args = shell_command_args(
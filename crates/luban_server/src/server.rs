@@ -1,14 +1,20 @@
 use crate::auth;
-use crate::engine::{Engine, EngineHandle, new_default_services};
+use crate::engine::{
+    ConversationEntryFilter, Engine, EngineConfig, EngineHandle, new_default_services,
+    without_archived_projects,
+};
 use crate::idempotency::{Begin, IdempotencyStore};
 use crate::mentions;
 use crate::project_avatars;
 use crate::pty::PtyManager;
+use crate::ws_coalesce::{
+    RecentConversations, coalesce_ws_events, drain_queued, is_resync_snapshot,
+};
 use anyhow::Context as _;
 use axum::middleware;
 use axum::{
     Json, Router,
-    extract::{Multipart, Path, Query, State, ws::WebSocketUpgrade},
+    extract::{FromRequestParts as _, Multipart, Path, Query, State, ws::WebSocketUpgrade},
     response::IntoResponse,
     routing::{delete, get, post, put},
 };
@@ -21,15 +27,41 @@ use luban_api::{
 use luban_domain::paths;
 use luban_domain::{ContextImage, ProjectWorkspaceService};
 use rand::RngCore as _;
+use std::collections::VecDeque;
 use std::path::PathBuf;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::sync::broadcast;
 use tower_http::services::{ServeDir, ServeFile};
 
 pub async fn router(config: crate::ServerConfig) -> anyhow::Result<Router> {
+    Ok(router_with_engine(config).await?.0)
+}
+
+/// Builds the router and also returns the engine behind it, for callers that shut it down.
+pub(crate) async fn router_with_engine(
+    config: crate::ServerConfig,
+) -> anyhow::Result<(Router, EngineHandle)> {
     let services = new_default_services()?;
-    let (engine, events) = Engine::start(services.clone());
+    let (engine, events) = Engine::start_with_config(
+        services.clone(),
+        EngineConfig {
+            conversation_load: config.conversation_load,
+            seed_project_path: if config.seed_cwd_project {
+                std::env::current_dir().ok()
+            } else {
+                None
+            },
+            fetch_before_pull_request_refresh: config.fetch_before_pull_request_refresh,
+            denied_client_actions: config.denied_client_actions.clone(),
+            max_concurrent_turns: config.max_concurrent_turns,
+            max_attachment_bytes: config.max_attachment_bytes,
+            conversation_retention: config.conversation_retention,
+        },
+    );
     crate::telegram::start_gateway(engine.clone(), events.clone());
+    crate::webhook::start_sink(engine.clone(), events.clone());
+
+    let engine_for_shutdown = engine.clone();
 
     let avatar_http = reqwest::Client::builder()
         .timeout(Duration::from_secs(10))
@@ -39,6 +71,7 @@ pub async fn router(config: crate::ServerConfig) -> anyhow::Result<Router> {
         .build()
         .context("failed to build avatar http client")?;
 
+    let capabilities = config.capabilities();
     let state = AppStateHolder {
         engine,
         events,
@@ -50,6 +83,8 @@ pub async fn router(config: crate::ServerConfig) -> anyhow::Result<Router> {
             std::time::Duration::from_secs(10 * 60),
             256,
         ),
+        capabilities,
+        ws_keepalive: config.ws_keepalive,
     };
 
     let api_public = Router::new().route("/health", get(health));
@@ -78,6 +113,10 @@ pub async fn router(config: crate::ServerConfig) -> anyhow::Result<Router> {
             "/workdirs/{workdir_id}/conversations/{task_id}",
             get(get_conversation),
         )
+        .route(
+            "/workdirs/{workdir_id}/conversations/{task_id}/transcript.md",
+            get(get_conversation_transcript),
+        )
         .route(
             "/workdirs/{workdir_id}/attachments",
             post(upload_attachment),
@@ -97,7 +136,8 @@ pub async fn router(config: crate::ServerConfig) -> anyhow::Result<Router> {
             "/workdirs/{workdir_id}/context/{context_id}",
             delete(delete_context_item),
         )
-        .route("/events", get(ws_events))
+        .route("/events", get(get_events))
+        .route("/actions", post(post_action))
         .route("/pty/{workdir_id}/{task_id}", get(ws_pty))
         .route_layer(middleware::from_fn_with_state(
             state.clone(),
@@ -112,17 +152,32 @@ pub async fn router(config: crate::ServerConfig) -> anyhow::Result<Router> {
     let web_index = web_dist.join("index.html");
     let web = ServeDir::new(web_dist).not_found_service(ServeFile::new(web_index));
 
-    Ok(Router::new()
+    let router = Router::new()
+        .route("/healthz", get(healthz))
         .merge(auth::router())
         .nest("/api", api)
         .fallback_service(web)
-        .with_state(state))
+        .with_state(state);
+    Ok((router, engine_for_shutdown))
 }
 
 async fn health() -> &'static str {
     "ok"
 }
 
+/// Readiness probe for supervisors; never behind auth.
+async fn healthz(State(state): State<AppStateHolder>) -> impl IntoResponse {
+    match state.engine.status().await {
+        Ok(Some(status)) => Json(status).into_response(),
+        Ok(None) => (
+            axum::http::StatusCode::SERVICE_UNAVAILABLE,
+            "engine is still starting",
+        )
+            .into_response(),
+        Err(err) => (axum::http::StatusCode::SERVICE_UNAVAILABLE, err.to_string()).into_response(),
+    }
+}
+
 fn resolve_codex_root() -> anyhow::Result<PathBuf> {
     if let Some(root) = std::env::var_os(paths::LUBAN_CODEX_ROOT_ENV) {
         let root = root.to_string_lossy();
@@ -160,11 +215,24 @@ pub(crate) struct AppStateHolder {
     avatar_http: reqwest::Client,
     pub(crate) auth: auth::AuthState,
     idempotency_attachments: IdempotencyStore<luban_api::AttachmentRef>,
+    capabilities: Vec<String>,
+    ws_keepalive: crate::WsKeepaliveConfig,
 }
 
-async fn get_app(State(state): State<AppStateHolder>) -> impl IntoResponse {
+#[derive(serde::Deserialize)]
+struct AppQuery {
+    /// Keeps archived projects in `projects`; they are omitted by default.
+    #[serde(default)]
+    include_archived: bool,
+}
+
+async fn get_app(
+    State(state): State<AppStateHolder>,
+    Query(query): Query<AppQuery>,
+) -> impl IntoResponse {
     match state.engine.app_snapshot().await {
-        Ok(snapshot) => Json(snapshot).into_response(),
+        Ok(snapshot) if query.include_archived => Json(snapshot).into_response(),
+        Ok(snapshot) => Json(without_archived_projects(snapshot)).into_response(),
         Err(err) => (
             axum::http::StatusCode::INTERNAL_SERVER_ERROR,
             err.to_string(),
@@ -554,6 +622,8 @@ async fn get_threads(
 #[derive(serde::Deserialize)]
 struct MentionQuery {
     q: String,
+    /// Defaults to `mentions::DEFAULT_MENTION_LIMIT`; clamped to `mentions::MAX_MENTION_LIMIT`.
+    limit: Option<usize>,
 }
 
 async fn get_workspace_mentions(
@@ -576,8 +646,9 @@ async fn get_workspace_mentions(
     };
 
     let q = query.q;
+    let limit = query.limit.unwrap_or(mentions::DEFAULT_MENTION_LIMIT);
     let result = tokio::task::spawn_blocking(move || {
-        mentions::search_workspace_mentions(&worktree_path, &q)
+        mentions::search_workspace_mentions(&worktree_path, &q, limit)
     })
     .await;
     match result {
@@ -600,29 +671,249 @@ async fn get_conversation(
     Path((workspace_id, thread_id)): Path<(u64, u64)>,
     Query(query): Query<ConversationQuery>,
 ) -> impl IntoResponse {
-    match state
-        .engine
-        .conversation_snapshot(
-            luban_api::WorkspaceId(workspace_id),
-            luban_api::WorkspaceThreadId(thread_id),
-            query.before,
-            query.limit,
-        )
-        .await
+    let kinds = match query
+        .kinds
+        .as_deref()
+        .map(parse_agent_item_kinds)
+        .transpose()
     {
+        Ok(kinds) => kinds,
+        Err(message) => return (axum::http::StatusCode::BAD_REQUEST, message).into_response(),
+    };
+
+    let workspace_id = luban_api::WorkspaceId(workspace_id);
+    let thread_id = luban_api::WorkspaceThreadId(thread_id);
+    let snapshot = match kinds {
+        Some(kinds) => {
+            let filter = ConversationEntryFilter {
+                kinds,
+                include_user_messages: query.include_user_messages,
+            };
+            state
+                .engine
+                .filtered_conversation_snapshot(
+                    workspace_id,
+                    thread_id,
+                    query.before,
+                    query.limit,
+                    filter,
+                )
+                .await
+        }
+        None => {
+            state
+                .engine
+                .conversation_snapshot(workspace_id, thread_id, query.before, query.limit)
+                .await
+        }
+    };
+    match snapshot {
         Ok(snapshot) => Json(snapshot).into_response(),
         Err(err) => (axum::http::StatusCode::NOT_FOUND, err.to_string()).into_response(),
     }
 }
 
+/// Renders the whole task history as Markdown, paging backwards through the conversation.
+async fn get_conversation_transcript(
+    State(state): State<AppStateHolder>,
+    Path((workspace_id, thread_id)): Path<(u64, u64)>,
+) -> impl IntoResponse {
+    let workspace_id = luban_api::WorkspaceId(workspace_id);
+    let thread_id = luban_api::WorkspaceThreadId(thread_id);
+
+    let mut title = String::new();
+    let mut pages = Vec::new();
+    let mut before = None;
+    loop {
+        let snapshot = match state
+            .engine
+            .conversation_snapshot(workspace_id, thread_id, before, Some(u64::MAX))
+            .await
+        {
+            Ok(snapshot) => snapshot,
+            Err(err) => {
+                return (axum::http::StatusCode::NOT_FOUND, err.to_string()).into_response();
+            }
+        };
+        if before.is_none() {
+            title = snapshot.title;
+        }
+        let start = snapshot.entries_start;
+        let empty = snapshot.entries.is_empty();
+        pages.push(snapshot.entries);
+        if start == 0 || empty {
+            break;
+        }
+        before = Some(start);
+    }
+    let entries = pages.into_iter().rev().flatten().collect::<Vec<_>>();
+
+    (
+        [(
+            axum::http::header::CONTENT_TYPE,
+            "text/markdown; charset=utf-8",
+        )],
+        crate::transcript::render_transcript(&title, &entries),
+    )
+        .into_response()
+}
+
 #[derive(serde::Deserialize)]
 struct ConversationQuery {
     before: Option<u64>,
     limit: Option<u64>,
+    /// Comma-separated `AgentItemKind` values. When set, only agent items of these kinds (and user
+    /// messages when requested) are returned, and `before`/`limit` page over those entries.
+    kinds: Option<String>,
+    #[serde(default)]
+    include_user_messages: bool,
+}
+
+fn parse_agent_item_kinds(raw: &str) -> Result<Vec<luban_api::AgentItemKind>, String> {
+    raw.split(',')
+        .map(str::trim)
+        .filter(|kind| !kind.is_empty())
+        .map(|kind| {
+            serde_json::from_value(serde_json::Value::String(kind.to_owned()))
+                .map_err(|_| format!("invalid agent item kind: {kind}"))
+        })
+        .collect()
+}
+
+/// `GET /api/events` serves the websocket feed, or the same events as Server-Sent Events for
+/// clients that cannot upgrade (e.g. behind proxies that strip websockets).
+async fn get_events(
+    State(state): State<AppStateHolder>,
+    request: axum::extract::Request,
+) -> axum::response::Response {
+    let (mut parts, _body) = request.into_parts();
+    if parts.headers.contains_key(axum::http::header::UPGRADE) {
+        return match WebSocketUpgrade::from_request_parts(&mut parts, &state).await {
+            Ok(ws) => ws
+                .on_upgrade(move |socket| ws_events_task(socket, state))
+                .into_response(),
+            Err(rejection) => rejection.into_response(),
+        };
+    }
+
+    let last_seen_rev = parts
+        .headers
+        .get("last-event-id")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok());
+    sse_events(state, last_seen_rev).await.into_response()
+}
+
+/// Broadcast subscription backing one SSE response. Dropping the response body (the client
+/// disconnected) drops this and with it the broadcast receiver.
+struct SseFeed {
+    engine: EngineHandle,
+    rx: broadcast::Receiver<WsServerMessage>,
+    pending: VecDeque<WsServerMessage>,
+    recent_conversations: RecentConversations,
 }
 
-async fn ws_events(ws: WebSocketUpgrade, State(state): State<AppStateHolder>) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| ws_events_task(socket, state))
+async fn sse_events(
+    state: AppStateHolder,
+    last_seen_rev: Option<u64>,
+) -> axum::response::Sse<
+    impl futures::Stream<Item = Result<axum::response::sse::Event, std::convert::Infallible>>,
+> {
+    let rx = state.events.subscribe();
+    let engine = state.engine.clone();
+    let pending = app_snapshot_message(&engine, last_seen_rev)
+        .await
+        .ok()
+        .flatten()
+        .into_iter()
+        .collect();
+    let feed = SseFeed {
+        engine,
+        rx,
+        pending,
+        recent_conversations: RecentConversations::default(),
+    };
+
+    let stream = futures::stream::unfold(feed, |mut feed| async move {
+        loop {
+            if let Some(msg) = feed.pending.pop_front() {
+                return Some((Ok(sse_event(&msg)), feed));
+            }
+            let (batch, lagged) = match feed.rx.recv().await {
+                Ok(outgoing) => drain_queued(Some(outgoing), &mut feed.rx),
+                Err(broadcast::error::RecvError::Lagged(_)) => {
+                    (drain_queued(None, &mut feed.rx).0, true)
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            };
+            let msgs =
+                ws_batch_messages(&feed.engine, batch, lagged, &mut feed.recent_conversations)
+                    .await
+                    .ok()?;
+            feed.pending.extend(msgs);
+        }
+    });
+
+    axum::response::Sse::new(stream).keep_alive(
+        axum::response::sse::KeepAlive::new().interval(state.ws_keepalive.ping_interval),
+    )
+}
+
+/// One `WsServerMessage` per SSE event, serialized exactly as on the websocket; events carry
+/// their `rev` as the SSE id so a reconnecting client resumes via `Last-Event-ID`.
+fn sse_event(msg: &WsServerMessage) -> axum::response::sse::Event {
+    let event =
+        axum::response::sse::Event::default().data(serde_json::to_string(msg).unwrap_or_default());
+    match msg {
+        WsServerMessage::Event { rev, .. } => event.id(rev.to_string()),
+        _ => event,
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct ActionRequest {
+    request_id: String,
+    action: Box<luban_api::ClientAction>,
+}
+
+/// Applies a client action for SSE clients; replies with the `Ack` / `Error` message the
+/// websocket would send. Actions that stream results back on the socket need the websocket.
+async fn post_action(
+    State(state): State<AppStateHolder>,
+    Json(request): Json<ActionRequest>,
+) -> impl IntoResponse {
+    let ActionRequest { request_id, action } = request;
+    if matches!(
+        *action,
+        luban_api::ClientAction::TerminalCommandStart { .. }
+            | luban_api::ClientAction::ListTerminalHistory { .. }
+    ) {
+        return (
+            axum::http::StatusCode::BAD_REQUEST,
+            Json(WsServerMessage::Error {
+                request_id: Some(request_id),
+                message: "action is only available over the websocket".to_owned(),
+            }),
+        );
+    }
+
+    match state
+        .engine
+        .apply_client_action(request_id.clone(), *action)
+        .await
+    {
+        Ok(rev) => (
+            axum::http::StatusCode::OK,
+            Json(WsServerMessage::Ack { request_id, rev }),
+        ),
+        Err(message) => (
+            axum::http::StatusCode::BAD_REQUEST,
+            Json(WsServerMessage::Error {
+                request_id: Some(request_id),
+                message,
+            }),
+        ),
+    }
 }
 
 async fn ws_events_task(mut socket: axum::extract::ws::WebSocket, state: AppStateHolder) {
@@ -634,36 +925,122 @@ async fn ws_events_task(mut socket: axum::extract::ws::WebSocket, state: AppStat
         .send(json_text(&WsServerMessage::Hello {
             protocol_version: PROTOCOL_VERSION,
             current_rev,
+            capabilities: state.capabilities.clone(),
         }))
         .await;
 
+    let keepalive = state.ws_keepalive;
+    let mut ping_interval = tokio::time::interval_at(
+        tokio::time::Instant::now() + keepalive.ping_interval,
+        keepalive.ping_interval,
+    );
+    ping_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let mut last_seen = tokio::time::Instant::now();
+    let mut recent_conversations = RecentConversations::default();
+
     loop {
         tokio::select! {
             incoming = socket.recv() => {
                 let Some(Ok(msg)) = incoming else { break };
+                last_seen = tokio::time::Instant::now();
                 if handle_ws_incoming(msg, &state, &mut socket).await.is_err() {
                     break;
                 }
             }
+            _ = ping_interval.tick() => {
+                // Reason: proxies may keep a dead TCP connection open without ever delivering a
+                // close frame; a peer that stops answering pings is treated as gone.
+                if last_seen.elapsed() >= keepalive.pong_timeout {
+                    tracing::debug!("dropping websocket connection after pong timeout");
+                    break;
+                }
+                if socket
+                    .send(axum::extract::ws::Message::Ping(Default::default()))
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+            }
             outgoing = rx.recv() => {
-                match outgoing {
-                    Ok(outgoing) => {
-                        if socket.send(json_text(&outgoing)).await.is_err() {
-                            break;
-                        }
-                    }
+                let (batch, lagged) = match outgoing {
+                    Ok(outgoing) => drain_queued(Some(outgoing), &mut rx),
                     Err(broadcast::error::RecvError::Lagged(_)) => {
-                        if send_app_snapshot_if_needed(&engine, None, &mut socket).await.is_err() {
-                            break;
-                        }
+                        (drain_queued(None, &mut rx).0, true)
                     }
                     Err(broadcast::error::RecvError::Closed) => break,
+                };
+                if send_ws_batch(&engine, batch, lagged, &mut recent_conversations, &mut socket)
+                    .await
+                    .is_err()
+                {
+                    break;
                 }
             }
         }
     }
 }
 
+/// Writes a batch of broadcast messages prepared by `ws_batch_messages`.
+async fn send_ws_batch(
+    engine: &EngineHandle,
+    batch: Vec<WsServerMessage>,
+    lagged: bool,
+    recent_conversations: &mut RecentConversations,
+    socket: &mut axum::extract::ws::WebSocket,
+) -> anyhow::Result<()> {
+    for msg in ws_batch_messages(engine, batch, lagged, recent_conversations).await? {
+        socket.send(json_text(&msg)).await?;
+    }
+    Ok(())
+}
+
+/// Compacts a batch of broadcast messages with `coalesce_ws_events`.
+///
+/// A lagged subscriber has missed messages that cannot be replayed, so the app and conversation
+/// snapshots in its batch are replaced with fresh ones at the current `rev`: the app snapshot and
+/// the conversations this connection was sent most recently.
+async fn ws_batch_messages(
+    engine: &EngineHandle,
+    batch: Vec<WsServerMessage>,
+    lagged: bool,
+    recent_conversations: &mut RecentConversations,
+) -> anyhow::Result<Vec<WsServerMessage>> {
+    let mut batch = coalesce_ws_events(batch);
+    recent_conversations.record(&batch);
+    if lagged {
+        batch.retain(|msg| !is_resync_snapshot(msg));
+    }
+    if !lagged {
+        return Ok(batch);
+    }
+
+    let conversations = recent_conversations.keys();
+    tracing::debug!(
+        conversations = conversations.len(),
+        "resyncing lagged event subscriber"
+    );
+    batch.extend(app_snapshot_message(engine, None).await?);
+    let rev = engine.current_rev().await.unwrap_or(0);
+    for (workspace_id, thread_id) in conversations {
+        let Ok(snapshot) = engine
+            .conversation_snapshot(workspace_id, thread_id, None, None)
+            .await
+        else {
+            recent_conversations.remove((workspace_id, thread_id));
+            continue;
+        };
+        batch.push(WsServerMessage::Event {
+            rev,
+            event: Box::new(luban_api::ServerEvent::ConversationChanged {
+                snapshot: Box::new(snapshot),
+                follow: None,
+            }),
+        });
+    }
+    Ok(batch)
+}
+
 fn json_text<T: serde::Serialize>(value: &T) -> axum::extract::ws::Message {
     axum::extract::ws::Message::Text(serde_json::to_string(value).unwrap_or_default().into())
 }
@@ -717,6 +1094,36 @@ async fn handle_ws_incoming(
                 )
                 .await
             }
+            luban_api::ClientAction::ListTerminalHistory {
+                workspace_id,
+                limit,
+            } => {
+                let limit = limit
+                    .map(|limit| limit as usize)
+                    .unwrap_or(crate::pty::DEFAULT_TERMINAL_HISTORY_LIMIT);
+                let commands = state
+                    .pty
+                    .terminal_history(workspace_id.0, limit)
+                    .into_iter()
+                    .map(|record| luban_api::TerminalHistoryEntry {
+                        command: record.command,
+                        exit_code: record.exit_code,
+                        started_at_unix_ms: record.started_at_unix_ms,
+                    })
+                    .collect();
+                let rev = engine.current_rev().await.unwrap_or(0);
+                socket
+                    .send(json_text(&WsServerMessage::Event {
+                        rev,
+                        event: Box::new(luban_api::ServerEvent::TerminalHistory {
+                            request_id,
+                            workspace_id,
+                            commands,
+                        }),
+                    }))
+                    .await?;
+                Ok(())
+            }
             other => {
                 let ack = engine.apply_client_action(request_id.clone(), other).await;
                 let msg = match ack {
@@ -850,21 +1257,30 @@ async fn send_app_snapshot_if_needed(
     last_seen_rev: Option<u64>,
     socket: &mut axum::extract::ws::WebSocket,
 ) -> anyhow::Result<()> {
+    if let Some(msg) = app_snapshot_message(engine, last_seen_rev).await? {
+        socket.send(json_text(&msg)).await?;
+    }
+    Ok(())
+}
+
+/// `AppChanged` at the current `rev`, or `None` when the client has already seen it.
+async fn app_snapshot_message(
+    engine: &EngineHandle,
+    last_seen_rev: Option<u64>,
+) -> anyhow::Result<Option<WsServerMessage>> {
     let current_rev = engine.current_rev().await.unwrap_or(0);
     if last_seen_rev == Some(current_rev) {
-        return Ok(());
+        return Ok(None);
     }
 
     let snapshot = engine.app_snapshot().await?;
-    let msg = WsServerMessage::Event {
+    Ok(Some(WsServerMessage::Event {
         rev: current_rev,
         event: Box::new(luban_api::ServerEvent::AppChanged {
             rev: current_rev,
-            snapshot: Box::new(snapshot),
+            snapshot: Box::new(without_archived_projects(snapshot)),
         }),
-    };
-    socket.send(json_text(&msg)).await?;
-    Ok(())
+    }))
 }
 
 async fn ws_pty(
@@ -1029,9 +1445,16 @@ async fn get_changes(
     }
 }
 
+#[derive(serde::Deserialize)]
+struct DiffQuery {
+    /// Unchanged lines kept around each change in `hunks` (default 3).
+    context_lines: Option<u32>,
+}
+
 async fn get_diff(
     State(state): State<AppStateHolder>,
     Path(workspace_id): Path<u64>,
+    Query(query): Query<DiffQuery>,
 ) -> impl IntoResponse {
     let Some((_project_slug, _workspace_name, worktree_path)) =
         workspace_info_from_snapshot(&state.engine.app_snapshot().await.ok(), workspace_id)
@@ -1040,8 +1463,13 @@ async fn get_diff(
     };
 
     let repo_path = PathBuf::from(worktree_path);
-    let result =
-        tokio::task::spawn_blocking(move || crate::git_changes::collect_diff(&repo_path)).await;
+    let context_lines = query
+        .context_lines
+        .unwrap_or(crate::git_changes::DEFAULT_DIFF_CONTEXT_LINES);
+    let result = tokio::task::spawn_blocking(move || {
+        crate::git_changes::collect_diff(&repo_path, context_lines)
+    })
+    .await;
 
     match result {
         Ok(Ok(files)) => Json(WorkspaceDiffSnapshot {
@@ -1092,6 +1520,7 @@ async fn get_context(
                         extension: item.attachment.extension,
                         mime: item.attachment.mime,
                         byte_len: item.attachment.byte_len,
+                        thumbnail_base64: None,
                     },
                     created_at_unix_ms: item.created_at_unix_ms,
                 })
@@ -1477,6 +1906,7 @@ async fn upload_attachment(
                     extension: att.extension,
                     mime: att.mime,
                     byte_len: att.byte_len,
+                    thumbnail_base64: None,
                 };
                 Ok(api)
             }
@@ -1572,7 +2002,77 @@ fn workspace_info_from_snapshot(
 
 #[cfg(test)]
 mod tests {
-    use super::append_timestamp_to_basename;
+    use super::{ConversationEntryFilter, append_timestamp_to_basename, parse_agent_item_kinds};
+    use luban_api::{
+        AgentEvent, AgentEventEntry, AgentItem, AgentItemKind, ConversationEntry, UserEvent,
+        UserEventEntry, UserMessage,
+    };
+
+    fn agent_item(id: &str, kind: AgentItemKind) -> ConversationEntry {
+        ConversationEntry::AgentEvent(AgentEventEntry {
+            entry_id: format!("e_{id}"),
+            created_at_unix_ms: 0,
+            runner: None,
+            event: AgentEvent::Item(AgentItem {
+                id: id.to_owned(),
+                kind,
+                payload: serde_json::Value::Null,
+            }),
+        })
+    }
+
+    fn entry_ids(entries: &[ConversationEntry]) -> Vec<&str> {
+        entries
+            .iter()
+            .map(|entry| match entry {
+                ConversationEntry::SystemEvent(entry) => entry.entry_id.as_str(),
+                ConversationEntry::UserEvent(entry) => entry.entry_id.as_str(),
+                ConversationEntry::AgentEvent(entry) => entry.entry_id.as_str(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn conversation_entries_can_be_filtered_to_file_changes() {
+        let mixed = vec![
+            ConversationEntry::UserEvent(UserEventEntry {
+                entry_id: "e_user".to_owned(),
+                created_at_unix_ms: 0,
+                event: UserEvent::Message(UserMessage {
+                    text: "edit things".to_owned(),
+                    attachments: Vec::new(),
+                }),
+            }),
+            agent_item("reason", AgentItemKind::Reasoning),
+            agent_item("patch-1", AgentItemKind::FileChange),
+            agent_item("cmd", AgentItemKind::CommandExecution),
+            ConversationEntry::AgentEvent(AgentEventEntry {
+                entry_id: "e_turn".to_owned(),
+                created_at_unix_ms: 0,
+                runner: None,
+                event: AgentEvent::TurnDuration { duration_ms: 10 },
+            }),
+            agent_item("patch-2", AgentItemKind::FileChange),
+        ];
+
+        let mut filter = ConversationEntryFilter {
+            kinds: parse_agent_item_kinds("file_change").expect("kinds should parse"),
+            include_user_messages: false,
+        };
+        let mut entries = mixed.clone();
+        entries.retain(|entry| filter.keeps(entry));
+        assert_eq!(entry_ids(&entries), vec!["e_patch-1", "e_patch-2"]);
+
+        filter.include_user_messages = true;
+        let mut entries = mixed;
+        entries.retain(|entry| filter.keeps(entry));
+        assert_eq!(
+            entry_ids(&entries),
+            vec!["e_user", "e_patch-1", "e_patch-2"]
+        );
+
+        assert!(parse_agent_item_kinds("file_change,unknown").is_err());
+    }
 
     #[test]
     fn timestamp_appended_for_simple_names() {
@@ -162,11 +162,13 @@ struct TelegramGateway {
     api_base: String,
     last_config_rev: u64,
     runtime: TelegramRuntimeConfig,
-    session: TelegramSession,
+    /// Menu and active-task state, per paired chat.
+    sessions: HashMap<i64, TelegramSession>,
     last_seen_entry_index: HashMap<(i64, Option<i64>, u64, u64), u64>,
     progress_messages: HashMap<(i64, Option<i64>, u64, u64), ProgressMessageState>,
     relay_messages: HashMap<(i64, Option<i64>, u64, u64), RelayMessageState>,
-    reply_routes: HashMap<i64, ReplyRoute>,
+    /// Keyed by `(chat_id, message_id)`; message ids are only unique within a chat.
+    reply_routes: HashMap<(i64, i64), ReplyRoute>,
     topic_bindings: HashMap<i64, TopicBinding>,
     inbox_initialized_workspaces: HashSet<u64>,
     inbox_task_status: HashMap<(u64, u64), TaskStatus>,
@@ -246,10 +248,10 @@ impl TelegramGateway {
             runtime: TelegramRuntimeConfig {
                 enabled: false,
                 bot_token: None,
-                paired_chat_id: None,
+                paired_chat_ids: Vec::new(),
                 topic_bindings: Vec::new(),
             },
-            session: TelegramSession::default(),
+            sessions: HashMap::new(),
             last_seen_entry_index: HashMap::new(),
             progress_messages: HashMap::new(),
             relay_messages: HashMap::new(),
@@ -307,7 +309,10 @@ impl TelegramGateway {
         let project_id = project.id.clone();
         let existing_ids: HashSet<u64> = project.workspaces.iter().map(|w| w.id.0).collect();
 
-        let action = luban_api::ClientAction::CreateWorkspace { project_id };
+        let action = luban_api::ClientAction::CreateWorkspace {
+            project_id,
+            branch_name_hint: None,
+        };
         let _ = self
             .engine
             .apply_client_action("telegram_create_worktree".to_owned(), action)
@@ -424,23 +429,19 @@ impl TelegramGateway {
             ServerEvent::TaskSummariesChanged { tasks, .. } => {
                 self.handle_task_summaries_changed(tasks).await;
             }
-            ServerEvent::ConversationChanged { snapshot } => {
-                let Some(chat_id) = self.runtime.paired_chat_id else {
-                    return;
-                };
-                self.forward_conversation_updates(chat_id, None, &snapshot)
-                    .await;
+            ServerEvent::ConversationChanged { snapshot, .. } => {
+                for chat_id in self.runtime.paired_chat_ids.clone() {
+                    self.forward_conversation_updates(chat_id, None, &snapshot)
+                        .await;
+                }
             }
             _ => {}
         }
     }
 
     async fn handle_task_summaries_changed(&mut self, tasks: Vec<luban_api::TaskSummarySnapshot>) {
-        let Some(chat_id) = self.runtime.paired_chat_id else {
-            return;
-        };
-
-        if tasks.is_empty() {
+        let chat_ids = self.runtime.paired_chat_ids.clone();
+        if chat_ids.is_empty() || tasks.is_empty() {
             return;
         }
 
@@ -465,7 +466,11 @@ impl TelegramGateway {
                         "Comment",
                         &format!("comment:{}:{}", t.workspace_id.0, t.thread_id.0),
                     )]]);
-                    let _ = self.send_message(chat_id, None, &text, Some(kb)).await;
+                    for &chat_id in &chat_ids {
+                        let _ = self
+                            .send_message(chat_id, None, &text, Some(kb.clone()))
+                            .await;
+                    }
                 }
                 Some(prev) => {
                     self.inbox_task_status.insert(key, t.task_status);
@@ -475,7 +480,11 @@ impl TelegramGateway {
                             "Comment",
                             &format!("comment:{}:{}", t.workspace_id.0, t.thread_id.0),
                         )]]);
-                        let _ = self.send_message(chat_id, None, &text, Some(kb)).await;
+                        for &chat_id in &chat_ids {
+                            let _ = self
+                                .send_message(chat_id, None, &text, Some(kb.clone()))
+                                .await;
+                        }
                     }
                 }
             }
@@ -486,20 +495,20 @@ impl TelegramGateway {
         match self.engine.telegram_runtime_config().await {
             Ok(cfg) => {
                 let token_changed = cfg.bot_token != self.runtime.bot_token;
-                let paired_chat_changed = cfg.paired_chat_id != self.runtime.paired_chat_id;
                 self.runtime = cfg;
                 if token_changed {
-                    self.session = TelegramSession::default();
-                    self.last_seen_entry_index.clear();
-                    self.reply_routes.clear();
-                    self.relay_messages.clear();
-                }
-                if paired_chat_changed {
-                    self.session = TelegramSession::default();
+                    self.sessions.clear();
                     self.last_seen_entry_index.clear();
                     self.reply_routes.clear();
                     self.relay_messages.clear();
                 }
+                // Drop per-chat state of chats that are no longer paired.
+                let paired = self.runtime.paired_chat_ids.clone();
+                self.sessions.retain(|chat_id, _| paired.contains(chat_id));
+                self.last_seen_entry_index
+                    .retain(|key, _| paired.contains(&key.0));
+                self.reply_routes.retain(|key, _| paired.contains(&key.0));
+                self.relay_messages.retain(|key, _| paired.contains(&key.0));
 
                 self.topic_bindings = self
                     .runtime
@@ -517,7 +526,7 @@ impl TelegramGateway {
                     })
                     .collect();
 
-                if let Some(chat_id) = self.runtime.paired_chat_id {
+                for &chat_id in &self.runtime.paired_chat_ids {
                     for (topic_id, binding) in &self.topic_bindings {
                         let Some(replayed_up_to) = binding.replayed_up_to else {
                             continue;
@@ -547,7 +556,17 @@ impl TelegramGateway {
             .retain(|_, route| now.duration_since(route.created_at) <= ttl);
     }
 
-    fn insert_reply_route(&mut self, message_id: i64, workspace_id: u64, thread_id: u64) {
+    fn session(&mut self, chat_id: i64) -> &mut TelegramSession {
+        self.sessions.entry(chat_id).or_default()
+    }
+
+    fn insert_reply_route(
+        &mut self,
+        chat_id: i64,
+        message_id: i64,
+        workspace_id: u64,
+        thread_id: u64,
+    ) {
         self.prune_reply_routes();
 
         if self.reply_routes.len() >= TELEGRAM_REPLY_ROUTE_MAX_ROUTES {
@@ -564,7 +583,7 @@ impl TelegramGateway {
         }
 
         self.reply_routes.insert(
-            message_id,
+            (chat_id, message_id),
             ReplyRoute {
                 workspace_id,
                 thread_id,
@@ -642,7 +661,7 @@ impl TelegramGateway {
                 if let Some(state) = self.progress_messages.get_mut(&key) {
                     state.message_id = message_id;
                 }
-                self.insert_reply_route(message_id, workspace_id, thread_id);
+                self.insert_reply_route(chat_id, message_id, workspace_id, thread_id);
                 Ok(())
             }
         }
@@ -731,7 +750,7 @@ impl TelegramGateway {
                 final_text: None,
             },
         );
-        self.insert_reply_route(message_id, workspace_id, thread_id);
+        self.insert_reply_route(chat_id, message_id, workspace_id, thread_id);
     }
 
     async fn handle_updates(&mut self, updates: Vec<TelegramUpdate>) {
@@ -793,7 +812,7 @@ impl TelegramGateway {
             return Ok(());
         }
 
-        let Some(paired_chat_id) = self.runtime.paired_chat_id else {
+        if self.runtime.paired_chat_ids.is_empty() {
             self.send_message(
                 chat_id,
                 None,
@@ -802,8 +821,8 @@ impl TelegramGateway {
             )
             .await?;
             return Ok(());
-        };
-        if paired_chat_id != chat_id {
+        }
+        if !self.runtime.paired_chat_ids.contains(&chat_id) {
             return Ok(());
         }
 
@@ -822,20 +841,22 @@ impl TelegramGateway {
             return Ok(());
         }
 
-        if let Some((wid, tid)) = self.session.pending_comment_target.take() {
-            self.session.active_workspace_id = Some(wid);
-            self.session.active_thread_id = Some(tid);
+        if let Some((wid, tid)) = self.session(chat_id).pending_comment_target.take() {
+            let session = self.session(chat_id);
+            session.active_workspace_id = Some(wid);
+            session.active_thread_id = Some(tid);
             self.begin_turn_progress_message(chat_id, None, wid, tid)
                 .await;
             self.send_agent_message(wid, tid, text).await;
             return Ok(());
         }
 
-        if let Some(project_slug) = self.session.pending_new_task_project_slug.take() {
+        if let Some(project_slug) = self.session(chat_id).pending_new_task_project_slug.take() {
             match self.create_task_in_new_worktree(&project_slug).await {
                 Ok((wid, tid)) => {
-                    self.session.active_workspace_id = Some(wid);
-                    self.session.active_thread_id = Some(tid);
+                    let session = self.session(chat_id);
+                    session.active_workspace_id = Some(wid);
+                    session.active_thread_id = Some(tid);
                     self.begin_turn_progress_message(chat_id, None, wid, tid)
                         .await;
                     self.send_agent_message(wid, tid, text).await;
@@ -859,7 +880,7 @@ impl TelegramGateway {
         let now = Instant::now();
         let Some((wid, tid)) = resolve_message_target(
             &msg,
-            &self.session,
+            self.sessions.entry(chat_id).or_default(),
             &self.reply_routes,
             &self.topic_bindings,
             now,
@@ -867,8 +888,9 @@ impl TelegramGateway {
             self.send_home(chat_id).await?;
             return Ok(());
         };
-        self.session.active_workspace_id = Some(wid);
-        self.session.active_thread_id = Some(tid);
+        let session = self.session(chat_id);
+        session.active_workspace_id = Some(wid);
+        session.active_thread_id = Some(tid);
 
         self.begin_turn_progress_message(chat_id, None, wid, tid)
             .await;
@@ -884,6 +906,7 @@ impl TelegramGateway {
             attachments: Vec::new(),
             runner: None,
             amp_mode: None,
+            expected_rev: None,
         };
         let _ = self
             .engine
@@ -904,7 +927,7 @@ impl TelegramGateway {
         let action = parse_callback_action(data);
         self.answer_callback_query(&cb.id).await?;
 
-        let Some(paired_chat_id) = self.runtime.paired_chat_id else {
+        if self.runtime.paired_chat_ids.is_empty() {
             self.send_message(
                 chat_id,
                 None,
@@ -913,8 +936,8 @@ impl TelegramGateway {
             )
             .await?;
             return Ok(());
-        };
-        if paired_chat_id != chat_id {
+        }
+        if !self.runtime.paired_chat_ids.contains(&chat_id) {
             return Ok(());
         }
 
@@ -928,16 +951,18 @@ impl TelegramGateway {
             CallbackAction::Home => self.send_home(chat_id).await?,
             CallbackAction::Workspaces => self.send_workspaces(chat_id).await?,
             CallbackAction::SelectWorkspace { workspace_id } => {
-                self.session.active_workspace_id = Some(workspace_id);
-                self.session.active_thread_id = None;
+                let session = self.session(chat_id);
+                session.active_workspace_id = Some(workspace_id);
+                session.active_thread_id = None;
                 self.send_tasks(chat_id, workspace_id).await?;
             }
             CallbackAction::SelectTask {
                 workspace_id,
                 thread_id,
             } => {
-                self.session.active_workspace_id = Some(workspace_id);
-                self.session.active_thread_id = Some(thread_id);
+                let session = self.session(chat_id);
+                session.active_workspace_id = Some(workspace_id);
+                session.active_thread_id = Some(thread_id);
                 self.send_task_selected(chat_id, workspace_id, thread_id)
                     .await?;
             }
@@ -948,7 +973,7 @@ impl TelegramGateway {
                 workspace_id,
                 thread_id,
             } => {
-                self.session.pending_comment_target = Some((workspace_id, thread_id));
+                self.session(chat_id).pending_comment_target = Some((workspace_id, thread_id));
                 let title = self.task_title_or_default(workspace_id, thread_id).await;
                 let text = format!("Comment on: {title}\n\nSend your message now.");
                 self.send_message(chat_id, None, &text, Some(home_reply_keyboard()))
@@ -984,8 +1009,9 @@ impl TelegramGateway {
             .map(|t| t.thread_id.0)
             .max()
             .unwrap_or(1);
-        self.session.active_workspace_id = Some(workspace_id);
-        self.session.active_thread_id = Some(new_tid);
+        let session = self.session(chat_id);
+        session.active_workspace_id = Some(workspace_id);
+        session.active_thread_id = Some(new_tid);
         self.send_task_selected(chat_id, workspace_id, new_tid)
             .await?;
         Ok(())
@@ -1379,18 +1405,19 @@ impl TelegramGateway {
     }
 
     async fn send_home(&mut self, chat_id: i64) -> anyhow::Result<()> {
-        self.session.ui_state = TelegramUiState::Home;
-        self.session.keyboard_routes.clear();
+        let session = self.session(chat_id);
+        session.ui_state = TelegramUiState::Home;
+        session.keyboard_routes.clear();
 
         let mut text = "Luban (Telegram)\n\nUse the keyboard to select a task.\nIf you don't see it, tap the keyboard icon next to the input field.\n\nMessages are sent to the active task.".to_owned();
         if let (Some(wid), Some(tid)) = (
-            self.session.active_workspace_id,
-            self.session.active_thread_id,
+            self.session(chat_id).active_workspace_id,
+            self.session(chat_id).active_thread_id,
         ) {
             let title = self.task_title_or_default(wid, tid).await;
             text.push_str(&format!("\n\nActive task: {title}"));
         }
-        if self.session.pending_comment_target.is_some() {
+        if self.session(chat_id).pending_comment_target.is_some() {
             text.push_str("\n\nComment mode is active.");
         }
 
@@ -1425,42 +1452,44 @@ impl TelegramGateway {
     async fn handle_keyboard_input(&mut self, chat_id: i64, text: &str) -> anyhow::Result<bool> {
         match text {
             KB_HOME => {
-                self.session.pending_comment_target = None;
-                self.session.pending_new_task_project_slug = None;
+                let session = self.session(chat_id);
+                session.pending_comment_target = None;
+                session.pending_new_task_project_slug = None;
                 self.send_home(chat_id).await?;
                 return Ok(true);
             }
             KB_CANCEL => {
-                self.session.pending_comment_target = None;
-                self.session.pending_new_task_project_slug = None;
+                let session = self.session(chat_id);
+                session.pending_comment_target = None;
+                session.pending_new_task_project_slug = None;
                 self.send_home(chat_id).await?;
                 return Ok(true);
             }
             KB_PROJECTS => {
-                self.session.pending_new_task_project_slug = None;
+                self.session(chat_id).pending_new_task_project_slug = None;
                 self.send_project_menu(chat_id).await?;
                 return Ok(true);
             }
             KB_RECENT_TASKS => {
-                self.session.pending_new_task_project_slug = None;
+                self.session(chat_id).pending_new_task_project_slug = None;
                 self.send_recent_tasks_menu(chat_id).await?;
                 return Ok(true);
             }
             KB_ACTIVE_TASK => {
-                self.session.pending_new_task_project_slug = None;
+                self.session(chat_id).pending_new_task_project_slug = None;
                 self.send_active_task(chat_id).await?;
                 return Ok(true);
             }
             KB_BACK => {
-                self.session.pending_new_task_project_slug = None;
+                self.session(chat_id).pending_new_task_project_slug = None;
                 self.handle_keyboard_back(chat_id).await?;
                 return Ok(true);
             }
             _ => {}
         }
 
-        if let Some(route) = self.session.keyboard_routes.get(text).cloned() {
-            self.session.pending_comment_target = None;
+        if let Some(route) = self.session(chat_id).keyboard_routes.get(text).cloned() {
+            self.session(chat_id).pending_comment_target = None;
             self.handle_keyboard_route(chat_id, route).await?;
             return Ok(true);
         }
@@ -1469,7 +1498,7 @@ impl TelegramGateway {
     }
 
     async fn handle_keyboard_back(&mut self, chat_id: i64) -> anyhow::Result<()> {
-        match self.session.ui_state.clone() {
+        match self.session(chat_id).ui_state.clone() {
             TelegramUiState::SelectingProject | TelegramUiState::Home => {
                 self.send_home(chat_id).await
             }
@@ -1485,7 +1514,7 @@ impl TelegramGateway {
         chat_id: i64,
         route: KeyboardRoute,
     ) -> anyhow::Result<()> {
-        self.session.pending_new_task_project_slug = None;
+        self.session(chat_id).pending_new_task_project_slug = None;
         match route {
             KeyboardRoute::SelectProject { project_slug } => {
                 self.send_project_task_menu(chat_id, &project_slug).await?;
@@ -1494,10 +1523,11 @@ impl TelegramGateway {
                 workspace_id,
                 thread_id,
             } => {
-                self.session.active_workspace_id = Some(workspace_id);
-                self.session.active_thread_id = Some(thread_id);
-                self.session.ui_state = TelegramUiState::Home;
-                self.session.keyboard_routes.clear();
+                let session = self.session(chat_id);
+                session.active_workspace_id = Some(workspace_id);
+                session.active_thread_id = Some(thread_id);
+                session.ui_state = TelegramUiState::Home;
+                session.keyboard_routes.clear();
                 let title = self.task_title_or_default(workspace_id, thread_id).await;
                 let text = format!("Selected: {title}");
                 self.send_message(chat_id, None, &text, Some(home_reply_keyboard()))
@@ -1508,7 +1538,7 @@ impl TelegramGateway {
                     .await?;
             }
             KeyboardRoute::CreateNewWorktree { project_slug } => {
-                begin_pending_new_task(&mut self.session, project_slug);
+                begin_pending_new_task(self.session(chat_id), project_slug);
                 self.send_message(
                     chat_id,
                     None,
@@ -1519,10 +1549,11 @@ impl TelegramGateway {
             }
             KeyboardRoute::SelectWorktree { workspace_id } => {
                 let thread_id = self.create_task(workspace_id).await?;
-                self.session.active_workspace_id = Some(workspace_id);
-                self.session.active_thread_id = Some(thread_id);
-                self.session.ui_state = TelegramUiState::Home;
-                self.session.keyboard_routes.clear();
+                let session = self.session(chat_id);
+                session.active_workspace_id = Some(workspace_id);
+                session.active_thread_id = Some(thread_id);
+                session.ui_state = TelegramUiState::Home;
+                session.keyboard_routes.clear();
                 let title = self.task_title_or_default(workspace_id, thread_id).await;
                 let text = format!("Created and selected: {title}");
                 self.send_message(chat_id, None, &text, Some(home_reply_keyboard()))
@@ -1534,8 +1565,8 @@ impl TelegramGateway {
 
     async fn send_active_task(&mut self, chat_id: i64) -> anyhow::Result<()> {
         if let (Some(wid), Some(tid)) = (
-            self.session.active_workspace_id,
-            self.session.active_thread_id,
+            self.session(chat_id).active_workspace_id,
+            self.session(chat_id).active_thread_id,
         ) {
             let title = self.task_title_or_default(wid, tid).await;
             let text = format!("Active task: {title}");
@@ -1557,14 +1588,15 @@ impl TelegramGateway {
     async fn send_project_menu(&mut self, chat_id: i64) -> anyhow::Result<()> {
         let app = self.engine.app_snapshot().await.context("app snapshot")?;
 
-        self.session.ui_state = TelegramUiState::SelectingProject;
-        self.session.keyboard_routes.clear();
+        let session = self.session(chat_id);
+        session.ui_state = TelegramUiState::SelectingProject;
+        session.keyboard_routes.clear();
 
         let mut rows = Vec::new();
         for project in &app.projects {
             let label = truncate_label(&project.name, 48);
-            let label = ensure_unique_label(&self.session.keyboard_routes, label);
-            self.session.keyboard_routes.insert(
+            let label = ensure_unique_label(&self.session(chat_id).keyboard_routes, label);
+            self.session(chat_id).keyboard_routes.insert(
                 label.clone(),
                 KeyboardRoute::SelectProject {
                     project_slug: project.slug.clone(),
@@ -1587,8 +1619,9 @@ impl TelegramGateway {
     ) -> anyhow::Result<()> {
         let tasks = self.project_recent_tasks(project_slug).await?;
 
-        self.session.ui_state = TelegramUiState::SelectingTask;
-        self.session.keyboard_routes.clear();
+        let session = self.session(chat_id);
+        session.ui_state = TelegramUiState::SelectingTask;
+        session.keyboard_routes.clear();
 
         let mut rows = Vec::new();
         for t in tasks.into_iter().take(12) {
@@ -1597,8 +1630,8 @@ impl TelegramGateway {
                 truncate_label(&t.title, 32),
                 truncate_label(&t.workspace_name, 16)
             );
-            let label = ensure_unique_label(&self.session.keyboard_routes, label);
-            self.session.keyboard_routes.insert(
+            let label = ensure_unique_label(&self.session(chat_id).keyboard_routes, label);
+            self.session(chat_id).keyboard_routes.insert(
                 label.clone(),
                 KeyboardRoute::SelectTask {
                     workspace_id: t.workspace_id,
@@ -1608,9 +1641,11 @@ impl TelegramGateway {
             rows.push(vec![label]);
         }
 
-        let new_task_label =
-            ensure_unique_label(&self.session.keyboard_routes, KB_NEW_TASK.to_owned());
-        self.session.keyboard_routes.insert(
+        let new_task_label = ensure_unique_label(
+            &self.session(chat_id).keyboard_routes,
+            KB_NEW_TASK.to_owned(),
+        );
+        self.session(chat_id).keyboard_routes.insert(
             new_task_label.clone(),
             KeyboardRoute::NewTask {
                 project_slug: project_slug.to_owned(),
@@ -1637,18 +1672,18 @@ impl TelegramGateway {
             return Ok(());
         };
 
-        self.session.ui_state = TelegramUiState::SelectingWorktree {
+        self.session(chat_id).ui_state = TelegramUiState::SelectingWorktree {
             project_slug: project_slug.to_owned(),
         };
-        self.session.keyboard_routes.clear();
+        self.session(chat_id).keyboard_routes.clear();
 
         let mut rows = Vec::new();
 
         let create_new_label = ensure_unique_label(
-            &self.session.keyboard_routes,
+            &self.session(chat_id).keyboard_routes,
             KB_CREATE_NEW_WORKTREE.to_owned(),
         );
-        self.session.keyboard_routes.insert(
+        self.session(chat_id).keyboard_routes.insert(
             create_new_label.clone(),
             KeyboardRoute::CreateNewWorktree {
                 project_slug: project_slug.to_owned(),
@@ -1659,9 +1694,11 @@ impl TelegramGateway {
         let workspaces = self.project_workspaces_for_new_task_menu(project).await?;
         for ws in &workspaces {
             let label = format!("{} ({})", ws.workspace_name, ws.branch_name);
-            let label =
-                ensure_unique_label(&self.session.keyboard_routes, truncate_label(&label, 48));
-            self.session.keyboard_routes.insert(
+            let label = ensure_unique_label(
+                &self.session(chat_id).keyboard_routes,
+                truncate_label(&label, 48),
+            );
+            self.session(chat_id).keyboard_routes.insert(
                 label.clone(),
                 KeyboardRoute::SelectWorktree {
                     workspace_id: ws.id.0,
@@ -1701,8 +1738,9 @@ impl TelegramGateway {
         }
         out.sort_by_key(|t| std::cmp::Reverse(t.updated_at_unix_seconds));
 
-        self.session.ui_state = TelegramUiState::Home;
-        self.session.keyboard_routes.clear();
+        let session = self.session(chat_id);
+        session.ui_state = TelegramUiState::Home;
+        session.keyboard_routes.clear();
 
         let mut rows = Vec::new();
         for t in out.into_iter().take(12) {
@@ -1711,8 +1749,8 @@ impl TelegramGateway {
                 truncate_label(&t.title, 32),
                 truncate_label(&t.workspace_name, 16)
             );
-            let label = ensure_unique_label(&self.session.keyboard_routes, label);
-            self.session.keyboard_routes.insert(
+            let label = ensure_unique_label(&self.session(chat_id).keyboard_routes, label);
+            self.session(chat_id).keyboard_routes.insert(
                 label.clone(),
                 KeyboardRoute::SelectTask {
                     workspace_id: t.workspace_id,
@@ -1800,7 +1838,7 @@ impl TelegramGateway {
             .send_message_with_id(chat_id, None, &text, Some(kb), None)
             .await?
         {
-            self.insert_reply_route(message_id, workspace_id, thread_id);
+            self.insert_reply_route(chat_id, message_id, workspace_id, thread_id);
         }
         Ok(())
     }
@@ -1926,7 +1964,12 @@ impl TelegramGateway {
             message_id
         };
 
-        self.insert_reply_route(message_id, snapshot.workspace_id.0, snapshot.thread_id.0);
+        self.insert_reply_route(
+            chat_id,
+            message_id,
+            snapshot.workspace_id.0,
+            snapshot.thread_id.0,
+        );
         self.relay_messages.insert(
             key,
             RelayMessageState {
@@ -2600,12 +2643,13 @@ fn format_conversation_entry_for_progress(entry: &ConversationEntry) -> Option<P
             luban_api::AgentEvent::Item(item) => {
                 format_agent_item_for_progress(item).map(ProgressUpdate::Event)
             }
-            luban_api::AgentEvent::TurnError { message } => {
+            luban_api::AgentEvent::TurnError { message, .. } => {
                 Some(ProgressUpdate::Final(format!("Turn failed: {message}")))
             }
             luban_api::AgentEvent::TurnCanceled => {
                 Some(ProgressUpdate::Final("Turn canceled.".to_owned()))
             }
+            luban_api::AgentEvent::TurnStarted { .. } => None,
             luban_api::AgentEvent::TurnDuration { .. } => None,
             luban_api::AgentEvent::TurnUsage { .. } => None,
         },
@@ -2774,7 +2818,9 @@ fn format_conversation_entry_for_telegram(entry: &ConversationEntry) -> Option<S
         ConversationEntry::AgentEvent(v) => match &v.event {
             luban_api::AgentEvent::Message(msg) => Some(msg.text.clone()),
             luban_api::AgentEvent::TurnDuration { .. } => None,
-            luban_api::AgentEvent::TurnError { message } => Some(format!("Turn failed: {message}")),
+            luban_api::AgentEvent::TurnError { message, .. } => {
+                Some(format!("Turn failed: {message}"))
+            }
             luban_api::AgentEvent::TurnCanceled => Some("Turn canceled.".to_owned()),
             _ => None,
         },
@@ -2785,13 +2831,13 @@ fn format_conversation_entry_for_telegram(entry: &ConversationEntry) -> Option<S
 fn resolve_message_target(
     msg: &TelegramMessage,
     session: &TelegramSession,
-    routes: &HashMap<i64, ReplyRoute>,
+    routes: &HashMap<(i64, i64), ReplyRoute>,
     topic_bindings: &HashMap<i64, TopicBinding>,
     now: Instant,
 ) -> Option<(u64, u64)> {
     let ttl = Duration::from_secs(TELEGRAM_REPLY_ROUTE_TTL_SECS);
     if let Some(reply_to) = msg.reply_to_message.as_deref()
-        && let Some(route) = routes.get(&reply_to.message_id)
+        && let Some(route) = routes.get(&(msg.chat.id, reply_to.message_id))
         && now.duration_since(route.created_at) <= ttl
     {
         return Some((route.workspace_id, route.thread_id));
@@ -2842,7 +2888,7 @@ mod tests {
         let now = Instant::now();
         let mut routes = HashMap::new();
         routes.insert(
-            10,
+            (1, 10),
             ReplyRoute {
                 workspace_id: 1,
                 thread_id: 2,
@@ -2866,7 +2912,7 @@ mod tests {
         let now = Instant::now();
         let mut routes = HashMap::new();
         routes.insert(
-            10,
+            (1, 10),
             ReplyRoute {
                 workspace_id: 1,
                 thread_id: 2,
@@ -2885,6 +2931,30 @@ mod tests {
         );
     }
 
+    #[test]
+    fn resolve_message_target_ignores_reply_routes_of_other_chats() {
+        let now = Instant::now();
+        let mut routes = HashMap::new();
+        routes.insert(
+            (2, 10),
+            ReplyRoute {
+                workspace_id: 1,
+                thread_id: 2,
+                created_at: now,
+            },
+        );
+        let session = TelegramSession {
+            active_workspace_id: Some(3),
+            active_thread_id: Some(4),
+            ..Default::default()
+        };
+        let topic_bindings = HashMap::new();
+        assert_eq!(
+            resolve_message_target(&msg_with_reply(10), &session, &routes, &topic_bindings, now),
+            Some((3, 4))
+        );
+    }
+
     #[test]
     fn resolve_message_target_falls_back_to_session() {
         let now = Instant::now();
@@ -2976,13 +3046,18 @@ mod tests {
             short_id: format!("w{id}"),
             workspace_name: workspace_name.to_owned(),
             branch_name: "branch".to_owned(),
+            detached_head: false,
             worktree_path: "/tmp/worktree".to_owned(),
             status,
             archive_status: luban_api::OperationStatus::Idle,
             branch_rename_status: luban_api::OperationStatus::Idle,
+            stash_status: luban_api::OperationStatus::Idle,
+            commit_status: luban_api::OperationStatus::Idle,
             agent_run_status: luban_api::OperationStatus::Idle,
             has_unread_completion: false,
             pull_request: None,
+            pull_request_error: None,
+            last_commit: None,
         }
     }
 
@@ -2996,6 +3071,7 @@ mod tests {
             task_status: status,
             turn_status: Default::default(),
             last_turn_result: None,
+            total_tokens: 0,
         }
     }
 
@@ -0,0 +1,264 @@
+use luban_api::{AgentEvent, AgentItem, AgentItemKind, ConversationEntry, UserEvent};
+
+/// Command output beyond this many bytes is cut from the transcript.
+const MAX_COMMAND_OUTPUT_BYTES: usize = 16 * 1024;
+
+/// Renders a task's conversation as a shareable Markdown document.
+///
+/// Reasoning items, usage reports and system events are left out; everything a reader needs to
+/// follow the work (messages, commands, file changes, todo lists, errors and turn durations) is
+/// kept in conversation order.
+pub(crate) fn render_transcript(title: &str, entries: &[ConversationEntry]) -> String {
+    let title = title.trim();
+    let mut out = format!(
+        "# {}\n",
+        if title.is_empty() {
+            "Untitled task"
+        } else {
+            title
+        }
+    );
+
+    for entry in entries {
+        let block = match entry {
+            ConversationEntry::SystemEvent(_) => None,
+            ConversationEntry::UserEvent(entry) => render_user_event(&entry.event),
+            ConversationEntry::AgentEvent(entry) => render_agent_event(&entry.event),
+        };
+        if let Some(block) = block {
+            out.push('\n');
+            out.push_str(block.trim_end());
+            out.push('\n');
+        }
+    }
+    out
+}
+
+fn render_user_event(event: &UserEvent) -> Option<String> {
+    match event {
+        UserEvent::Message(message) => {
+            let mut out = format!("## User\n\n{}\n", message.text.trim());
+            if !message.attachments.is_empty() {
+                out.push('\n');
+                for attachment in &message.attachments {
+                    out.push_str(&format!("- Attachment: `{}`\n", attachment.name));
+                }
+            }
+            Some(out)
+        }
+        UserEvent::TerminalCommandStarted(_) => None,
+        UserEvent::TerminalCommandFinished(command) => Some(format!(
+            "**Terminal**\n\n{}\n_Output: {} bytes (not included)_\n",
+            fenced("sh", &format!("$ {}", command.command)),
+            command.output_byte_len
+        )),
+    }
+}
+
+fn render_agent_event(event: &AgentEvent) -> Option<String> {
+    match event {
+        AgentEvent::Message(message) => Some(format!("## Agent\n\n{}\n", message.text.trim())),
+        AgentEvent::Item(item) => render_agent_item(item),
+        AgentEvent::TurnDuration { duration_ms } => {
+            Some(format!("_Turn took {}_\n", format_duration(*duration_ms)))
+        }
+        AgentEvent::TurnCanceled => Some("_Turn canceled_\n".to_owned()),
+        AgentEvent::TurnError { message, .. } => Some(format!("> **Turn failed:** {message}\n")),
+        AgentEvent::TurnUsage { .. } | AgentEvent::TurnStarted { .. } => None,
+    }
+}
+
+fn render_agent_item(item: &AgentItem) -> Option<String> {
+    let payload = &item.payload;
+    let str_field = |key: &str| payload.get(key).and_then(|v| v.as_str()).unwrap_or("");
+
+    match item.kind {
+        AgentItemKind::Reasoning => None,
+        AgentItemKind::CommandExecution => {
+            let mut out = format!(
+                "**Command**\n\n{}\n",
+                fenced("sh", &format!("$ {}", str_field("command")))
+            );
+            let output = str_field("aggregated_output");
+            if !output.trim().is_empty() {
+                out.push('\n');
+                out.push_str(&fenced("text", &truncate_output(output)));
+                out.push('\n');
+            }
+            if let Some(exit_code) = payload.get("exit_code").and_then(|v| v.as_i64()) {
+                out.push_str(&format!("\n_Exit code {exit_code}_\n"));
+            }
+            Some(out)
+        }
+        AgentItemKind::FileChange => {
+            let changes = payload
+                .get("changes")
+                .and_then(|v| v.as_array())
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let mut out = String::from("**File changes**\n\n");
+            for change in changes {
+                let path = change.get("path").and_then(|v| v.as_str()).unwrap_or("");
+                let kind = change.get("kind").and_then(|v| v.as_str()).unwrap_or("");
+                out.push_str(&format!("- `{path}` ({kind})\n"));
+            }
+            Some(out)
+        }
+        AgentItemKind::TodoList => {
+            let items = payload
+                .get("items")
+                .and_then(|v| v.as_array())
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let mut out = String::from("**Todo list**\n\n");
+            for todo in items {
+                let text = todo.get("text").and_then(|v| v.as_str()).unwrap_or("");
+                let completed = todo
+                    .get("completed")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false);
+                let mark = if completed { "x" } else { " " };
+                out.push_str(&format!("- [{mark}] {text}\n"));
+            }
+            Some(out)
+        }
+        AgentItemKind::McpToolCall => Some(format!(
+            "**Tool call:** `{}.{}`\n",
+            str_field("server"),
+            str_field("tool")
+        )),
+        AgentItemKind::WebSearch => Some(format!("**Web search:** {}\n", str_field("query"))),
+        AgentItemKind::Error => Some(format!("> **Error:** {}\n", str_field("message"))),
+    }
+}
+
+/// Wraps `content` in a code fence longer than any backtick run it contains.
+fn fenced(lang: &str, content: &str) -> String {
+    let mut longest = 0;
+    let mut run = 0;
+    for ch in content.chars() {
+        if ch == '`' {
+            run += 1;
+            longest = longest.max(run);
+        } else {
+            run = 0;
+        }
+    }
+    let fence = "`".repeat(longest.max(2) + 1);
+    format!("{fence}{lang}\n{}\n{fence}", content.trim_end_matches('\n'))
+}
+
+/// Caps command output at `MAX_COMMAND_OUTPUT_BYTES`; output with control characters is treated
+/// as binary and left out entirely.
+fn truncate_output(output: &str) -> String {
+    let binary = output
+        .chars()
+        .any(|ch| ch.is_control() && !matches!(ch, '\n' | '\r' | '\t'));
+    if binary {
+        return format!("(truncated {} bytes)", output.len());
+    }
+    if output.len() <= MAX_COMMAND_OUTPUT_BYTES {
+        return output.to_owned();
+    }
+
+    let mut end = MAX_COMMAND_OUTPUT_BYTES;
+    while !output.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!(
+        "{}\n(truncated {} bytes)",
+        output[..end].trim_end_matches('\n'),
+        output.len() - end
+    )
+}
+
+fn format_duration(duration_ms: u64) -> String {
+    let secs = duration_ms / 1000;
+    match secs {
+        0 => format!("{duration_ms}ms"),
+        1..60 => format!("{secs}s"),
+        60..3600 => format!("{}m {}s", secs / 60, secs % 60),
+        _ => format!("{}h {}m", secs / 3600, (secs % 3600) / 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(value: serde_json::Value) -> ConversationEntry {
+        serde_json::from_value(value).expect("conversation entry")
+    }
+
+    #[test]
+    fn transcript_renders_messages_items_and_turn_durations() {
+        let entries = vec![
+            entry(serde_json::json!({
+                "type": "user_event",
+                "event": { "type": "message", "text": "Fix the build", "attachments": [] },
+            })),
+            entry(serde_json::json!({
+                "type": "agent_event",
+                "event": {
+                    "type": "item",
+                    "id": "i1",
+                    "kind": "command_execution",
+                    "payload": {
+                        "command": "cargo build",
+                        "aggregated_output": format!("{}tail", "x".repeat(MAX_COMMAND_OUTPUT_BYTES)),
+                        "exit_code": 101,
+                    },
+                },
+            })),
+            entry(serde_json::json!({
+                "type": "agent_event",
+                "event": {
+                    "type": "item",
+                    "id": "i2",
+                    "kind": "file_change",
+                    "payload": { "changes": [{ "path": "src/lib.rs", "kind": "update" }] },
+                },
+            })),
+            entry(serde_json::json!({
+                "type": "agent_event",
+                "event": {
+                    "type": "item",
+                    "id": "i3",
+                    "kind": "todo_list",
+                    "payload": { "items": [
+                        { "text": "Build", "completed": true },
+                        { "text": "Test", "completed": false },
+                    ] },
+                },
+            })),
+            entry(serde_json::json!({
+                "type": "agent_event",
+                "event": { "type": "message", "id": "m1", "text": "Fixed." },
+            })),
+            entry(serde_json::json!({
+                "type": "agent_event",
+                "event": { "type": "turn_duration", "duration_ms": 83_000 },
+            })),
+        ];
+
+        let markdown = render_transcript("Build fix", &entries);
+        assert!(markdown.starts_with("# Build fix\n"), "{markdown}");
+        assert!(
+            markdown.contains("## User\n\nFix the build\n"),
+            "{markdown}"
+        );
+        assert!(markdown.contains("```sh\n$ cargo build\n```"), "{markdown}");
+        assert!(markdown.contains("(truncated 4 bytes)\n```"), "{markdown}");
+        assert!(markdown.contains("_Exit code 101_"), "{markdown}");
+        assert!(markdown.contains("- `src/lib.rs` (update)"), "{markdown}");
+        assert!(markdown.contains("- [x] Build\n- [ ] Test"), "{markdown}");
+        assert!(markdown.contains("## Agent\n\nFixed.\n"), "{markdown}");
+        assert!(markdown.contains("_Turn took 1m 23s_"), "{markdown}");
+    }
+
+    #[test]
+    fn fenced_outgrows_backticks_in_content() {
+        assert_eq!(fenced("text", "a ``` b"), "````text\na ``` b\n````");
+        assert_eq!(truncate_output("a\u{0}b"), "(truncated 3 bytes)");
+    }
+}
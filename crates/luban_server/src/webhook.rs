@@ -0,0 +1,413 @@
+use crate::engine::{EngineHandle, WebhookRuntimeConfig};
+use luban_api::{
+    AppSnapshot, PullRequestState, ServerEvent, TaskSummarySnapshot, TurnResult, TurnStatus,
+    WsServerMessage,
+};
+use luban_domain::Action;
+use serde::Serialize;
+use sha2::{Digest as _, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, watch};
+
+const WEBHOOK_REQUEST_TIMEOUT_SECS: u64 = 10;
+/// Notifications waiting for delivery; newer ones are dropped while the queue is full.
+const WEBHOOK_QUEUE_CAPACITY: usize = 256;
+/// Attempts per notification before it is dropped.
+const WEBHOOK_MAX_ATTEMPTS: u32 = 5;
+const WEBHOOK_BACKOFF_INITIAL: Duration = Duration::from_secs(1);
+const WEBHOOK_BACKOFF_MAX: Duration = Duration::from_secs(5 * 60);
+
+/// `sha256=<hex>` HMAC-SHA256 of the request body, keyed with the configured secret.
+const WEBHOOK_SIGNATURE_HEADER: &str = "X-Luban-Signature";
+const WEBHOOK_EVENT_HEADER: &str = "X-Luban-Event";
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum WebhookEventKind {
+    TurnCompleted,
+    PrReady,
+}
+
+impl WebhookEventKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            WebhookEventKind::TurnCompleted => "turn_completed",
+            WebhookEventKind::PrReady => "pr_ready",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+struct WebhookPayload {
+    event: WebhookEventKind,
+    workdir_id: u64,
+    workdir_name: String,
+    /// The task whose turn completed; `None` for PR events.
+    task_id: Option<u64>,
+    /// Task title, or the branch name for PR events.
+    title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    turn_result: Option<TurnResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pr_number: Option<u64>,
+}
+
+/// Turns snapshot events into notifications by comparing them with the previously seen state.
+/// The first snapshot of each workdir (and of the app) only seeds that state, so nothing that
+/// happened before the server started is reported.
+#[derive(Default)]
+struct WebhookWatcher {
+    turn_status: HashMap<(u64, u64), TurnStatus>,
+    seeded_workspaces: HashSet<u64>,
+    merge_ready: HashMap<u64, bool>,
+    seeded_pull_requests: bool,
+}
+
+impl WebhookWatcher {
+    fn task_summaries_changed(&mut self, tasks: &[TaskSummarySnapshot]) -> Vec<WebhookPayload> {
+        let Some(first) = tasks.first() else {
+            return Vec::new();
+        };
+        let seeded = !self.seeded_workspaces.insert(first.workspace_id.0);
+
+        let mut out = Vec::new();
+        for task in tasks {
+            let previous = self
+                .turn_status
+                .insert((task.workspace_id.0, task.thread_id.0), task.turn_status);
+            let was_running = matches!(previous, Some(TurnStatus::Running | TurnStatus::Awaiting));
+            if seeded
+                && was_running
+                && task.turn_status == TurnStatus::Idle
+                && let Some(result) = task.last_turn_result
+            {
+                out.push(WebhookPayload {
+                    event: WebhookEventKind::TurnCompleted,
+                    workdir_id: task.workspace_id.0,
+                    workdir_name: task.workspace_name.clone(),
+                    task_id: Some(task.thread_id.0),
+                    title: task.title.trim().to_owned(),
+                    turn_result: Some(result),
+                    pr_number: None,
+                });
+            }
+        }
+        out
+    }
+
+    fn app_changed(&mut self, snapshot: &AppSnapshot) -> Vec<WebhookPayload> {
+        let seeded = std::mem::replace(&mut self.seeded_pull_requests, true);
+
+        let mut out = Vec::new();
+        let mut live = HashSet::new();
+        for workspace in snapshot.projects.iter().flat_map(|p| &p.workspaces) {
+            live.insert(workspace.id.0);
+            let pull_request = workspace
+                .pull_request
+                .filter(|pr| pr.state == PullRequestState::Open && pr.merge_ready);
+            let was_ready = self
+                .merge_ready
+                .insert(workspace.id.0, pull_request.is_some())
+                .unwrap_or(false);
+            if seeded
+                && !was_ready
+                && let Some(pr) = pull_request
+            {
+                out.push(WebhookPayload {
+                    event: WebhookEventKind::PrReady,
+                    workdir_id: workspace.id.0,
+                    workdir_name: workspace.workspace_name.clone(),
+                    task_id: None,
+                    title: workspace.branch_name.clone(),
+                    turn_result: None,
+                    pr_number: Some(pr.number),
+                });
+            }
+        }
+        self.merge_ready.retain(|id, _| live.contains(id));
+        out
+    }
+}
+
+/// Starts the webhook sink: one task watches engine events for notifications, another delivers
+/// them in order, backing off while the endpoint keeps failing.
+pub(crate) fn start_sink(engine: EngineHandle, events: broadcast::Sender<WsServerMessage>) {
+    let (config_tx, config_rx) = watch::channel(WebhookRuntimeConfig::default());
+    let (queue_tx, queue_rx) = mpsc::channel(WEBHOOK_QUEUE_CAPACITY);
+    tokio::spawn(deliver_notifications(engine.clone(), config_rx, queue_rx));
+
+    let mut events = events.subscribe();
+    tokio::spawn(async move {
+        let mut watcher = WebhookWatcher::default();
+        let mut last_config_rev = None;
+        refresh_config(&engine, &config_tx).await;
+
+        loop {
+            let msg = match events.recv().await {
+                Ok(msg) => msg,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            let WsServerMessage::Event { event, .. } = msg else {
+                continue;
+            };
+            let payloads = match *event {
+                ServerEvent::AppChanged { snapshot, .. } => {
+                    let config_rev = snapshot.integrations.webhook.config_rev;
+                    if last_config_rev != Some(config_rev) {
+                        last_config_rev = Some(config_rev);
+                        refresh_config(&engine, &config_tx).await;
+                    }
+                    watcher.app_changed(&snapshot)
+                }
+                ServerEvent::TaskSummariesChanged { tasks, .. } => {
+                    watcher.task_summaries_changed(&tasks)
+                }
+                _ => continue,
+            };
+            if config_tx.borrow().url.is_none() {
+                continue;
+            }
+            for payload in payloads {
+                if queue_tx.try_send(payload).is_err() {
+                    tracing::warn!("webhook queue is full; dropping notification");
+                }
+            }
+        }
+    });
+}
+
+async fn refresh_config(engine: &EngineHandle, config: &watch::Sender<WebhookRuntimeConfig>) {
+    match engine.webhook_runtime_config().await {
+        Ok(next) => {
+            config.send_if_modified(|current| {
+                let changed = *current != next;
+                *current = next;
+                changed
+            });
+        }
+        Err(err) => tracing::warn!(error = %err, "failed to refresh webhook runtime config"),
+    }
+}
+
+async fn deliver_notifications(
+    engine: EngineHandle,
+    config: watch::Receiver<WebhookRuntimeConfig>,
+    mut queue: mpsc::Receiver<WebhookPayload>,
+) {
+    let http = reqwest::Client::builder()
+        .timeout(Duration::from_secs(WEBHOOK_REQUEST_TIMEOUT_SECS))
+        .build()
+        .unwrap_or_else(|_| reqwest::Client::new());
+    let mut backoff = WEBHOOK_BACKOFF_INITIAL;
+    let mut failing = false;
+
+    while let Some(payload) = queue.recv().await {
+        for attempt in 1..=WEBHOOK_MAX_ATTEMPTS {
+            let WebhookRuntimeConfig { url, secret } = config.borrow().clone();
+            let Some(url) = url else {
+                break;
+            };
+            match post_payload(&http, &url, secret.as_deref(), &payload).await {
+                Ok(()) => {
+                    if std::mem::take(&mut failing) {
+                        set_last_error(&engine, None).await;
+                    }
+                    backoff = WEBHOOK_BACKOFF_INITIAL;
+                    break;
+                }
+                Err(message) => {
+                    tracing::warn!(attempt, error = %message, "webhook delivery failed");
+                    failing = true;
+                    set_last_error(&engine, Some(message)).await;
+                    tokio::time::sleep(backoff).await;
+                    backoff = backoff.saturating_mul(2).min(WEBHOOK_BACKOFF_MAX);
+                }
+            }
+        }
+    }
+}
+
+async fn post_payload(
+    http: &reqwest::Client,
+    url: &str,
+    secret: Option<&str>,
+    payload: &WebhookPayload,
+) -> Result<(), String> {
+    let body = serde_json::to_vec(payload).map_err(|err| err.to_string())?;
+    let mut request = http
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(WEBHOOK_EVENT_HEADER, payload.event.as_str());
+    if let Some(secret) = secret {
+        request = request.header(WEBHOOK_SIGNATURE_HEADER, signature_header(secret, &body));
+    }
+    let res = request
+        .body(body)
+        .send()
+        .await
+        .map_err(|err| format!("webhook request failed: {err}"))?;
+    if !res.status().is_success() {
+        return Err(format!("webhook returned {}", res.status()));
+    }
+    Ok(())
+}
+
+async fn set_last_error(engine: &EngineHandle, message: Option<String>) {
+    let _ = engine
+        .dispatch_domain_action(Action::WebhookLastErrorSet { message })
+        .await;
+}
+
+fn signature_header(secret: &str, body: &[u8]) -> String {
+    let mac = hmac_sha256(secret.as_bytes(), body);
+    let hex = mac.iter().map(|b| format!("{b:02x}")).collect::<String>();
+    format!("sha256={hex}")
+}
+
+/// HMAC-SHA256 as specified in RFC 2104.
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    const BLOCK_LEN: usize = 64;
+    let mut block = [0u8; BLOCK_LEN];
+    if key.len() > BLOCK_LEN {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let mut inner = Sha256::new();
+    inner.update(block.map(|b| b ^ 0x36));
+    inner.update(message);
+    let inner = inner.finalize();
+
+    let mut outer = Sha256::new();
+    outer.update(block.map(|b| b ^ 0x5c));
+    outer.update(inner);
+    outer.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(thread_id: u64, turn_status: TurnStatus) -> TaskSummarySnapshot {
+        serde_json::from_value(serde_json::json!({
+            "project_id": "/tmp/project",
+            "workdir_id": 1,
+            "task_id": thread_id,
+            "title": format!("Task {thread_id}"),
+            "updated_at_unix_seconds": 0,
+            "branch_name": "luban/w1",
+            "workdir_name": "w1",
+            "agent_run_status": "idle",
+            "has_unread_completion": false,
+            "turn_status": turn_status,
+            "last_turn_result": "completed",
+        }))
+        .expect("task summary")
+    }
+
+    fn app(merge_ready: bool) -> AppSnapshot {
+        serde_json::from_value(serde_json::json!({
+            "rev": 1,
+            "projects": [{
+                "id": "/tmp/project",
+                "name": "project",
+                "slug": "project",
+                "path": "/tmp/project",
+                "expanded": true,
+                "create_workdir_status": "idle",
+                "workdirs": [{
+                    "id": 1,
+                    "short_id": "w1",
+                    "workdir_name": "w1",
+                    "branch_name": "luban/w1",
+                    "workdir_path": "/tmp/w1",
+                    "status": "active",
+                    "archive_status": "idle",
+                    "branch_rename_status": "idle",
+                    "agent_run_status": "idle",
+                    "has_unread_completion": false,
+                    "pull_request": {
+                        "number": 7,
+                        "is_draft": false,
+                        "state": "open",
+                        "ci_state": "success",
+                        "merge_ready": merge_ready,
+                    },
+                }],
+            }],
+            "appearance": {
+                "theme": "system",
+                "fonts": {
+                    "ui_font": "Inter",
+                    "chat_font": "Inter",
+                    "code_font": "Geist Mono",
+                    "terminal_font": "Geist Mono",
+                },
+            },
+        }))
+        .expect("app snapshot")
+    }
+
+    #[test]
+    fn watcher_reports_finished_turns_and_newly_ready_pull_requests() {
+        let mut watcher = WebhookWatcher::default();
+        assert!(
+            watcher
+                .task_summaries_changed(&[task(1, TurnStatus::Running)])
+                .is_empty(),
+            "the first snapshot only seeds state"
+        );
+        assert!(
+            watcher
+                .task_summaries_changed(&[task(1, TurnStatus::Running), task(2, TurnStatus::Idle)])
+                .is_empty()
+        );
+        let done = watcher.task_summaries_changed(&[task(1, TurnStatus::Idle)]);
+        assert_eq!(done.len(), 1);
+        assert_eq!(done[0].event, WebhookEventKind::TurnCompleted);
+        assert_eq!(done[0].task_id, Some(1));
+        assert_eq!(done[0].title, "Task 1");
+        assert!(
+            watcher
+                .task_summaries_changed(&[task(1, TurnStatus::Idle)])
+                .is_empty()
+        );
+
+        assert!(watcher.app_changed(&app(false)).is_empty());
+        let ready = watcher.app_changed(&app(true));
+        assert_eq!(ready.len(), 1);
+        assert_eq!(ready[0].event, WebhookEventKind::PrReady);
+        assert_eq!(ready[0].pr_number, Some(7));
+        assert!(watcher.app_changed(&app(true)).is_empty());
+
+        let json = serde_json::to_value(&ready[0]).expect("serialize payload");
+        assert_eq!(json["event"], "pr_ready");
+        assert_eq!(json["workdir_id"], 1);
+        assert!(json["task_id"].is_null());
+    }
+
+    #[test]
+    fn hmac_sha256_matches_rfc_4231() {
+        let mac = hmac_sha256(b"Jefe", b"what do ya want for nothing?");
+        let hex = mac.iter().map(|b| format!("{b:02x}")).collect::<String>();
+        assert_eq!(
+            hex,
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+
+        let long_key = [0xaau8; 131];
+        let mac = hmac_sha256(
+            &long_key,
+            b"Test Using Larger Than Block-Size Key - Hash Key First",
+        );
+        let hex = mac.iter().map(|b| format!("{b:02x}")).collect::<String>();
+        assert_eq!(
+            hex,
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+        );
+        assert!(signature_header("Jefe", b"x").starts_with("sha256="));
+    }
+}
@@ -0,0 +1,321 @@
+use luban_api::{ServerEvent, WorkspaceId, WorkspaceThreadId, WsServerMessage};
+use std::collections::{HashMap, VecDeque};
+use tokio::sync::broadcast;
+
+/// Upper bound on how many already-queued messages are pulled into one send batch.
+const MAX_DRAIN: usize = 256;
+
+/// Upper bound on how many conversations a lagged subscriber is resent.
+const MAX_RESYNC_CONVERSATIONS: usize = 16;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+enum SnapshotKey {
+    App,
+    Conversation(WorkspaceId, WorkspaceThreadId),
+    WorkspaceThreads(WorkspaceId),
+    TaskSummaries(WorkspaceId),
+}
+
+fn snapshot_key(msg: &WsServerMessage) -> Option<SnapshotKey> {
+    let WsServerMessage::Event { event, .. } = msg else {
+        return None;
+    };
+    match event.as_ref() {
+        ServerEvent::AppChanged { .. } => Some(SnapshotKey::App),
+        ServerEvent::ConversationChanged { snapshot, .. } => Some(SnapshotKey::Conversation(
+            snapshot.workspace_id,
+            snapshot.thread_id,
+        )),
+        ServerEvent::WorkspaceThreadsChanged { workspace_id, .. } => {
+            Some(SnapshotKey::WorkspaceThreads(*workspace_id))
+        }
+        ServerEvent::TaskSummariesChanged { workspace_id, .. } => {
+            Some(SnapshotKey::TaskSummaries(*workspace_id))
+        }
+        _ => None,
+    }
+}
+
+/// Returns the conversation a `ConversationChanged` message refers to.
+pub(crate) fn conversation_key(msg: &WsServerMessage) -> Option<(WorkspaceId, WorkspaceThreadId)> {
+    match snapshot_key(msg)? {
+        SnapshotKey::Conversation(workspace_id, thread_id) => Some((workspace_id, thread_id)),
+        _ => None,
+    }
+}
+
+/// Returns true for messages that a lag resync replaces with freshly fetched snapshots.
+pub(crate) fn is_resync_snapshot(msg: &WsServerMessage) -> bool {
+    matches!(
+        snapshot_key(msg),
+        Some(SnapshotKey::App | SnapshotKey::Conversation(..))
+    )
+}
+
+/// Compacts a batch of queued messages before they are written to a socket.
+///
+/// Snapshot events (`AppChanged`, `ConversationChanged`, `WorkspaceThreadsChanged`,
+/// `TaskSummariesChanged`) carry full state, so only the last one per app/workdir/task is kept,
+/// at the position of that last occurrence. A dropped `ConversationChanged` with `follow: true`
+/// carries over to the kept one so clients still auto-follow. Every other message (acks, errors,
+/// request replies, toasts) is passed through in order.
+pub(crate) fn coalesce_ws_events(queued: Vec<WsServerMessage>) -> Vec<WsServerMessage> {
+    let mut last_index = HashMap::<SnapshotKey, usize>::new();
+    let mut followed = HashMap::<SnapshotKey, bool>::new();
+    for (index, msg) in queued.iter().enumerate() {
+        let Some(key) = snapshot_key(msg) else {
+            continue;
+        };
+        last_index.insert(key, index);
+        if let WsServerMessage::Event { event, .. } = msg
+            && let ServerEvent::ConversationChanged {
+                follow: Some(true), ..
+            } = event.as_ref()
+        {
+            followed.insert(key, true);
+        }
+    }
+
+    queued
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, mut msg)| {
+            let Some(key) = snapshot_key(&msg) else {
+                return Some(msg);
+            };
+            if last_index.get(&key) != Some(&index) {
+                return None;
+            }
+            if followed.contains_key(&key)
+                && let WsServerMessage::Event { event, .. } = &mut msg
+                && let ServerEvent::ConversationChanged { follow, .. } = event.as_mut()
+            {
+                *follow = Some(true);
+            }
+            Some(msg)
+        })
+        .collect()
+}
+
+/// Conversations a subscriber was sent most recently, oldest first. Every conversation change is
+/// broadcast to every subscriber, so the set is capped: a lag resync refreshes the conversations
+/// the client most likely has open instead of every conversation that changed since it connected.
+#[derive(Debug, Default)]
+pub(crate) struct RecentConversations {
+    keys: VecDeque<(WorkspaceId, WorkspaceThreadId)>,
+}
+
+impl RecentConversations {
+    /// Marks the conversations of `batch` as the most recently sent ones.
+    pub(crate) fn record(&mut self, batch: &[WsServerMessage]) {
+        for key in batch.iter().filter_map(conversation_key) {
+            self.keys.retain(|existing| *existing != key);
+            self.keys.push_back(key);
+        }
+        while self.keys.len() > MAX_RESYNC_CONVERSATIONS {
+            self.keys.pop_front();
+        }
+    }
+
+    pub(crate) fn remove(&mut self, key: (WorkspaceId, WorkspaceThreadId)) {
+        self.keys.retain(|existing| *existing != key);
+    }
+
+    pub(crate) fn keys(&self) -> Vec<(WorkspaceId, WorkspaceThreadId)> {
+        self.keys.iter().copied().collect()
+    }
+}
+
+/// Pulls every message already queued behind `first` without waiting.
+///
+/// Returns the batch and whether the receiver lagged (dropped messages) while draining.
+pub(crate) fn drain_queued(
+    first: Option<WsServerMessage>,
+    rx: &mut broadcast::Receiver<WsServerMessage>,
+) -> (Vec<WsServerMessage>, bool) {
+    let mut batch = first.into_iter().collect::<Vec<_>>();
+    let mut lagged = false;
+    while batch.len() < MAX_DRAIN {
+        match rx.try_recv() {
+            Ok(msg) => batch.push(msg),
+            Err(broadcast::error::TryRecvError::Lagged(_)) => lagged = true,
+            Err(_) => break,
+        }
+    }
+    (batch, lagged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use luban_api::{
+        AgentRunnerKind, AppSnapshot, ConversationSnapshot, ThinkingEffort, WorkspaceTabsSnapshot,
+    };
+
+    fn app_changed(rev: u64) -> WsServerMessage {
+        WsServerMessage::Event {
+            rev,
+            event: Box::new(ServerEvent::AppChanged {
+                rev,
+                snapshot: Box::new(
+                    serde_json::from_value::<AppSnapshot>(serde_json::json!({
+                        "rev": rev,
+                        "projects": [],
+                        "appearance": {
+                            "theme": "system",
+                            "fonts": {
+                                "ui_font": "Inter",
+                                "chat_font": "Inter",
+                                "code_font": "Geist Mono",
+                                "terminal_font": "Geist Mono",
+                            },
+                        },
+                    }))
+                    .expect("app snapshot"),
+                ),
+            }),
+        }
+    }
+
+    fn conversation_changed(rev: u64, thread_id: u64, follow: Option<bool>) -> WsServerMessage {
+        let snapshot: ConversationSnapshot = serde_json::from_value(serde_json::json!({
+            "rev": rev,
+            "workdir_id": 1,
+            "task_id": thread_id,
+            "agent_runner": AgentRunnerKind::Codex,
+            "agent_model_id": "gpt-5.2-codex",
+            "thinking_effort": ThinkingEffort::Medium,
+            "run_status": "running",
+            "entries": [],
+            "remote_thread_id": null,
+            "title": "Task",
+        }))
+        .expect("conversation snapshot");
+        WsServerMessage::Event {
+            rev,
+            event: Box::new(ServerEvent::ConversationChanged {
+                snapshot: Box::new(snapshot),
+                follow,
+            }),
+        }
+    }
+
+    fn event_rev_and_kind(msg: &WsServerMessage) -> (u64, &'static str) {
+        match msg {
+            WsServerMessage::Event { rev, event } => match event.as_ref() {
+                ServerEvent::AppChanged { .. } => (*rev, "app"),
+                ServerEvent::ConversationChanged { .. } => (*rev, "conversation"),
+                ServerEvent::WorkspaceThreadsChanged { .. } => (*rev, "threads"),
+                _ => (*rev, "other"),
+            },
+            WsServerMessage::Ack { rev, .. } => (*rev, "ack"),
+            _ => (0, "other"),
+        }
+    }
+
+    #[test]
+    fn coalesce_keeps_latest_snapshot_per_key_and_preserves_other_messages() {
+        let queued = vec![
+            conversation_changed(1, 7, Some(true)),
+            app_changed(1),
+            WsServerMessage::Ack {
+                request_id: "r1".to_owned(),
+                rev: 2,
+            },
+            conversation_changed(2, 8, None),
+            conversation_changed(3, 7, Some(false)),
+            WsServerMessage::Event {
+                rev: 3,
+                event: Box::new(ServerEvent::WorkspaceThreadsChanged {
+                    workspace_id: WorkspaceId(1),
+                    tabs: WorkspaceTabsSnapshot::default(),
+                    threads: Vec::new(),
+                }),
+            },
+            app_changed(4),
+        ];
+
+        let compacted = coalesce_ws_events(queued);
+        let kinds = compacted.iter().map(event_rev_and_kind).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                (2, "ack"),
+                (2, "conversation"),
+                (3, "conversation"),
+                (3, "threads"),
+                (4, "app"),
+            ]
+        );
+
+        let WsServerMessage::Event { event, .. } = &compacted[2] else {
+            panic!("expected event");
+        };
+        let ServerEvent::ConversationChanged { snapshot, follow } = event.as_ref() else {
+            panic!("expected conversation changed");
+        };
+        assert_eq!(snapshot.thread_id, WorkspaceThreadId(7));
+        assert_eq!(*follow, Some(true), "dropped follow=true must carry over");
+        assert_eq!(
+            conversation_key(&compacted[1]),
+            Some((WorkspaceId(1), WorkspaceThreadId(8)))
+        );
+        assert!(is_resync_snapshot(&compacted[4]));
+        assert!(!is_resync_snapshot(&compacted[3]));
+    }
+
+    #[test]
+    fn drain_queued_collects_pending_messages_and_reports_lag() {
+        let ack = |rev| WsServerMessage::Ack {
+            request_id: format!("r{rev}"),
+            rev,
+        };
+        let (tx, mut rx) = broadcast::channel::<WsServerMessage>(2);
+        for rev in 1..=4 {
+            tx.send(ack(rev)).expect("send");
+        }
+
+        let (batch, lagged) = drain_queued(None, &mut rx);
+        assert!(lagged);
+        let revs = batch
+            .iter()
+            .map(|msg| event_rev_and_kind(msg).0)
+            .collect::<Vec<_>>();
+        assert_eq!(revs, vec![3, 4]);
+
+        let (batch, lagged) = drain_queued(Some(ack(5)), &mut rx);
+        assert!(!lagged);
+        assert_eq!(batch.len(), 1);
+    }
+
+    #[test]
+    fn recent_conversations_keep_the_latest_ones_up_to_the_cap() {
+        let mut recent = RecentConversations::default();
+        let batch = (1..=(MAX_RESYNC_CONVERSATIONS as u64 + 2))
+            .map(|thread_id| conversation_changed(thread_id, thread_id, None))
+            .collect::<Vec<_>>();
+        recent.record(&batch);
+        recent.record(&[conversation_changed(20, 3, None), app_changed(20)]);
+
+        let thread_ids = recent
+            .keys()
+            .into_iter()
+            .map(|(_, thread_id)| thread_id.0)
+            .collect::<Vec<_>>();
+        assert_eq!(thread_ids.len(), MAX_RESYNC_CONVERSATIONS);
+        assert_eq!(thread_ids.first(), Some(&4));
+        assert_eq!(
+            thread_ids.last(),
+            Some(&3),
+            "a resent conversation moves to the back"
+        );
+        assert!(!thread_ids[..thread_ids.len() - 1].contains(&3));
+
+        recent.remove((WorkspaceId(1), WorkspaceThreadId(3)));
+        assert!(
+            !recent
+                .keys()
+                .contains(&(WorkspaceId(1), WorkspaceThreadId(3)))
+        );
+    }
+}
@@ -121,6 +121,32 @@ impl Drop for StartedTestServer {
     }
 }
 
+/// Reads the next SSE event from `res` and decodes its `data:` payload.
+async fn recv_sse_msg(res: &mut reqwest::Response, buf: &mut String) -> luban_api::WsServerMessage {
+    loop {
+        while let Some(end) = buf.find("\n\n") {
+            let block = buf[..end].to_owned();
+            buf.drain(..end + 2);
+            let data = block
+                .lines()
+                .filter_map(|line| line.strip_prefix("data:"))
+                .map(|line| line.strip_prefix(' ').unwrap_or(line))
+                .collect::<Vec<_>>()
+                .join("\n");
+            if data.is_empty() {
+                continue;
+            }
+            return serde_json::from_str(&data).expect("parse sse data");
+        }
+        let chunk = tokio::time::timeout(Duration::from_secs(5), res.chunk())
+            .await
+            .expect("timeout waiting for sse event")
+            .expect("read sse chunk")
+            .expect("sse stream ended");
+        buf.push_str(&String::from_utf8_lossy(&chunk));
+    }
+}
+
 async fn start_avatar_upstream(counter: Arc<AtomicUsize>) -> StartedTestServer {
     const PNG_1X1: &[u8] = &[
         0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44,
@@ -224,10 +250,10 @@ async fn create_workdir_via_ws(server_addr: SocketAddr, project_path: &str) -> (
     for _ in 0..120 {
         let msg = recv_ws_msg(&mut socket, Duration::from_secs(2)).await;
         match msg {
-            luban_api::WsServerMessage::Ack { request_id, .. } => {
-                if request_id == "req-add-project-and-open" {
-                    saw_ack = true;
-                }
+            luban_api::WsServerMessage::Ack { request_id, .. }
+                if request_id == "req-add-project-and-open" =>
+            {
+                saw_ack = true;
             }
             luban_api::WsServerMessage::Event { event, .. } => {
                 if let luban_api::ServerEvent::AddProjectAndOpenReady {
@@ -308,11 +334,11 @@ async fn ensure_task_via_ws(server_addr: SocketAddr, workdir_id: u64) {
     for _ in 0..60 {
         let msg = recv_ws_msg(&mut socket, Duration::from_secs(2)).await;
         match msg {
-            luban_api::WsServerMessage::Ack { request_id, .. } => {
-                if request_id == "req-create-task" {
-                    saw_ack = true;
-                    break;
-                }
+            luban_api::WsServerMessage::Ack { request_id, .. }
+                if request_id == "req-create-task" =>
+            {
+                saw_ack = true;
+                break;
             }
             luban_api::WsServerMessage::Error { message, .. } => {
                 panic!("create_task error: {message}");
@@ -387,10 +413,8 @@ async fn set_task_star_via_ws(
     for _ in 0..60 {
         let msg = recv_ws_msg(&mut socket, Duration::from_secs(2)).await;
         match msg {
-            luban_api::WsServerMessage::Ack { request_id, .. } => {
-                if request_id == "req-task-star" {
-                    saw_ack = true;
-                }
+            luban_api::WsServerMessage::Ack { request_id, .. } if request_id == "req-task-star" => {
+                saw_ack = true;
             }
             luban_api::WsServerMessage::Event { event, .. } => {
                 if let luban_api::ServerEvent::TaskSummariesChanged {
@@ -555,11 +579,11 @@ async fn archive_workdir_via_ws(server_addr: SocketAddr, workdir_id: u64) {
     for _ in 0..120 {
         let msg = recv_ws_msg(&mut socket, Duration::from_secs(2)).await;
         match msg {
-            luban_api::WsServerMessage::Ack { request_id, .. } => {
-                if request_id == "req-archive-workdir" {
-                    saw_ack = true;
-                    break;
-                }
+            luban_api::WsServerMessage::Ack { request_id, .. }
+                if request_id == "req-archive-workdir" =>
+            {
+                saw_ack = true;
+                break;
             }
             luban_api::WsServerMessage::Error { message, .. } => {
                 panic!("archive_workdir error: {message}");
@@ -611,6 +635,7 @@ async fn create_workdir_for_project_via_ws(server_addr: SocketAddr, project_id:
         request_id: "req-create-workdir".to_owned(),
         action: Box::new(luban_api::ClientAction::CreateWorkspace {
             project_id: luban_api::ProjectId(project_id.to_owned()),
+            branch_name_hint: None,
         }),
     };
     socket
@@ -626,11 +651,11 @@ async fn create_workdir_for_project_via_ws(server_addr: SocketAddr, project_id:
     for _ in 0..120 {
         let msg = recv_ws_msg(&mut socket, Duration::from_secs(2)).await;
         match msg {
-            luban_api::WsServerMessage::Ack { request_id, .. } => {
-                if request_id == "req-create-workdir" {
-                    saw_ack = true;
-                    break;
-                }
+            luban_api::WsServerMessage::Ack { request_id, .. }
+                if request_id == "req-create-workdir" =>
+            {
+                saw_ack = true;
+                break;
             }
             luban_api::WsServerMessage::Error { message, .. } => {
                 panic!("create_workdir error: {message}");
@@ -1255,6 +1280,31 @@ async fn http_contracts_smoke() {
         }
     }
 
+    // C-HTTP-CONVERSATION-TRANSCRIPT
+    {
+        let res = client
+            .get(format!(
+                "{base}/api/workdirs/{workdir_id}/conversations/{task_id}/transcript.md"
+            ))
+            .send()
+            .await
+            .expect("GET /transcript.md")
+            .error_for_status()
+            .expect("transcript status");
+        let content_type = res
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_owned();
+        assert!(content_type.starts_with("text/markdown"), "{content_type}");
+        let markdown = res.text().await.expect("transcript body");
+        assert!(
+            markdown.starts_with("# "),
+            "expected an H1 title: {markdown}"
+        );
+    }
+
     // C-HTTP-CHANGES / C-HTTP-DIFF
     {
         let _changes: luban_api::WorkspaceChangesSnapshot = client
@@ -1292,6 +1342,83 @@ async fn http_contracts_smoke() {
         let _items: Vec<luban_api::MentionItemSnapshot> = res.json().await.expect("mentions json");
     }
 
+    // C-WS-EVENTS (Server-Sent Events fallback)
+    {
+        let mut res = client
+            .get(format!("{base}/api/events"))
+            .header("accept", "text/event-stream")
+            .send()
+            .await
+            .expect("GET /events")
+            .error_for_status()
+            .expect("events status");
+        let content_type = res
+            .headers()
+            .get("content-type")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_owned();
+        assert!(
+            content_type.starts_with("text/event-stream"),
+            "{content_type}"
+        );
+
+        let mut buf = String::new();
+        let first = recv_sse_msg(&mut res, &mut buf).await;
+        assert!(
+            matches!(
+                &first,
+                luban_api::WsServerMessage::Event { event, .. }
+                    if matches!(**event, luban_api::ServerEvent::AppChanged { .. })
+            ),
+            "unexpected first sse message: {first:?}"
+        );
+
+        let ack: luban_api::WsServerMessage = client
+            .post(format!("{base}/api/actions"))
+            .json(&serde_json::json!({
+                "request_id": "sse-theme",
+                "action": { "type": "appearance_theme_changed", "theme": "dark" },
+            }))
+            .send()
+            .await
+            .expect("POST /actions")
+            .error_for_status()
+            .expect("actions status")
+            .json()
+            .await
+            .expect("actions json");
+        let luban_api::WsServerMessage::Ack { request_id, rev } = ack else {
+            panic!("unexpected action reply: {ack:?}");
+        };
+        assert_eq!(request_id, "sse-theme");
+
+        loop {
+            let msg = recv_sse_msg(&mut res, &mut buf).await;
+            if let luban_api::WsServerMessage::Event {
+                rev: event_rev,
+                event,
+            } = msg
+                && let luban_api::ServerEvent::AppChanged { snapshot, .. } = *event
+                && event_rev >= rev
+            {
+                assert_eq!(snapshot.appearance.theme, luban_api::AppearanceTheme::Dark);
+                break;
+            }
+        }
+
+        let res = client
+            .post(format!("{base}/api/actions"))
+            .json(&serde_json::json!({
+                "request_id": "sse-terminal",
+                "action": { "type": "list_terminal_history", "workdir_id": workdir_id },
+            }))
+            .send()
+            .await
+            .expect("POST /actions terminal");
+        assert_eq!(res.status(), reqwest::StatusCode::BAD_REQUEST);
+    }
+
     // C-HTTP-ATTACHMENTS-UPLOAD / C-HTTP-ATTACHMENTS-DOWNLOAD / C-HTTP-CONTEXT / C-HTTP-CONTEXT-DELETE
     {
         let bytes = b"hello contracts\n".to_vec();
@@ -11,6 +11,9 @@ fn repo_root_from_manifest_dir(manifest_dir: &Path) -> PathBuf {
         .expect("failed to locate repo root from CARGO_MANIFEST_DIR")
 }
 
+/// Routes registered on the root router instead of under `/api`.
+const ROOT_ROUTES: &[&str] = &["/healthz"];
+
 fn extract_server_routes(server_src: &str) -> BTreeSet<String> {
     let mut out = BTreeSet::new();
     let mut cursor = 0;
@@ -26,7 +29,9 @@ fn extract_server_routes(server_src: &str) -> BTreeSet<String> {
         };
         let end = start + end_rel;
         let path = &server_src[start..end];
-        if path.starts_with('/') {
+        if ROOT_ROUTES.contains(&path) {
+            out.insert(path.to_string());
+        } else if path.starts_with('/') {
             out.insert(format!("/api{path}"));
         }
         cursor = end + 1;
@@ -54,7 +59,7 @@ fn extract_contract_paths(progress_md: &str) -> BTreeSet<String> {
         let Some(path) = path else {
             continue;
         };
-        if path.starts_with("/api/") {
+        if path.starts_with("/api/") || ROOT_ROUTES.contains(&path) {
             out.insert(path.to_string());
         }
     }
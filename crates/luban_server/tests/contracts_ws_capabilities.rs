@@ -0,0 +1,95 @@
+use futures::StreamExt as _;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest as _;
+
+async fn hello_capabilities(addr: SocketAddr, cookie: Option<&str>) -> Vec<String> {
+    let mut request = format!("ws://{addr}/api/events")
+        .into_client_request()
+        .expect("build websocket request");
+    if let Some(cookie) = cookie {
+        request.headers_mut().insert(
+            reqwest::header::COOKIE,
+            cookie.parse().expect("cookie header value"),
+        );
+    }
+    let (mut socket, _) = tokio_tungstenite::connect_async(request)
+        .await
+        .expect("connect websocket");
+
+    let next = tokio::time::timeout(Duration::from_secs(2), socket.next())
+        .await
+        .expect("timed out waiting for ws message")
+        .expect("websocket stream ended")
+        .expect("websocket recv failed");
+    let Message::Text(text) = next else {
+        panic!("expected text ws message");
+    };
+    let msg: luban_api::WsServerMessage =
+        serde_json::from_str(&text).expect("failed to parse ws server message");
+    let luban_api::WsServerMessage::Hello { capabilities, .. } = msg else {
+        panic!("expected hello as first ws message");
+    };
+    capabilities
+}
+
+#[tokio::test]
+async fn ws_hello_advertises_capabilities_from_server_config() {
+    let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+    let server =
+        luban_server::start_server_with_config(addr, luban_server::ServerConfig::default())
+            .await
+            .unwrap();
+    let capabilities = hello_capabilities(server.addr, None).await;
+    assert!(
+        !capabilities.iter().any(|c| c == "auth"),
+        "auth should not be advertised when disabled: {capabilities:?}"
+    );
+    assert!(
+        capabilities.iter().any(|c| c == "sse_events"),
+        "the SSE feed should always be advertised: {capabilities:?}"
+    );
+    assert!(
+        !capabilities.iter().any(|c| c.starts_with("denied_action:")),
+        "no actions are denied by default: {capabilities:?}"
+    );
+
+    let token = "test_capabilities_token";
+    let server = luban_server::start_server_with_config(
+        addr,
+        luban_server::ServerConfig {
+            auth: luban_server::AuthConfig {
+                mode: luban_server::AuthMode::SingleUser,
+                bootstrap_token: Some(token.to_owned()),
+            },
+            denied_client_actions: std::collections::HashSet::from(["delete_project".to_owned()]),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    let bootstrap = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+        .get(format!("http://{}/auth?token={token}", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(bootstrap.status(), reqwest::StatusCode::OK);
+
+    let capabilities =
+        hello_capabilities(server.addr, Some(&format!("luban_session={token}"))).await;
+    assert!(
+        capabilities.iter().any(|c| c == "auth"),
+        "auth should be advertised when enabled: {capabilities:?}"
+    );
+    assert!(
+        capabilities
+            .iter()
+            .any(|c| c == "denied_action:delete_project"),
+        "denied actions should be advertised: {capabilities:?}"
+    );
+}
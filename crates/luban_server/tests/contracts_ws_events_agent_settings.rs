@@ -82,10 +82,10 @@ async fn ws_events_claude_enabled_changed_emits_app_changed() {
     for _ in 0..80 {
         let msg = recv_ws_msg(&mut socket, Duration::from_secs(2)).await;
         match msg {
-            luban_api::WsServerMessage::Ack { request_id, .. } => {
-                if request_id == "req-claude-disable" {
-                    saw_ack = true;
-                }
+            luban_api::WsServerMessage::Ack { request_id, .. }
+                if request_id == "req-claude-disable" =>
+            {
+                saw_ack = true;
             }
             luban_api::WsServerMessage::Event { event, .. } => {
                 if let luban_api::ServerEvent::AppChanged { snapshot, .. } = *event
@@ -190,10 +190,10 @@ async fn ws_events_telegram_pair_start_emits_pair_ready() {
     for _ in 0..80 {
         let msg = recv_ws_msg(&mut socket, Duration::from_secs(2)).await;
         match msg {
-            luban_api::WsServerMessage::Ack { request_id, .. } => {
-                if request_id == "req-telegram-pair" {
-                    saw_ack = true;
-                }
+            luban_api::WsServerMessage::Ack { request_id, .. }
+                if request_id == "req-telegram-pair" =>
+            {
+                saw_ack = true;
             }
             luban_api::WsServerMessage::Event { event, .. } => {
                 if let luban_api::ServerEvent::TelegramPairReady { request_id, url } = *event
@@ -156,13 +156,11 @@ async fn ws_events_terminal_command_start_emits_conversation_events_with_output(
         match msg {
             luban_api::WsServerMessage::Ack {
                 request_id: rid, ..
-            } => {
-                if rid == request_id {
-                    saw_ack = true;
-                }
+            } if rid == request_id => {
+                saw_ack = true;
             }
             luban_api::WsServerMessage::Event { event, .. } => {
-                let luban_api::ServerEvent::ConversationChanged { snapshot } = *event else {
+                let luban_api::ServerEvent::ConversationChanged { snapshot, .. } = *event else {
                     continue;
                 };
                 for entry in snapshot.entries {
@@ -174,17 +172,13 @@ async fn ws_events_terminal_command_start_emits_conversation_events_with_output(
                         "expected created_at_unix_ms to be present on user event entries"
                     );
                     match user.event {
-                        luban_api::UserEvent::TerminalCommandStarted(ev) => {
-                            if ev.command == cmd {
-                                started = Some(ev);
-                                started_created_at_unix_ms = Some(user.created_at_unix_ms);
-                            }
+                        luban_api::UserEvent::TerminalCommandStarted(ev) if ev.command == cmd => {
+                            started = Some(ev);
+                            started_created_at_unix_ms = Some(user.created_at_unix_ms);
                         }
-                        luban_api::UserEvent::TerminalCommandFinished(ev) => {
-                            if ev.command == cmd {
-                                finished = Some(ev);
-                                finished_created_at_unix_ms = Some(user.created_at_unix_ms);
-                            }
+                        luban_api::UserEvent::TerminalCommandFinished(ev) if ev.command == cmd => {
+                            finished = Some(ev);
+                            finished_created_at_unix_ms = Some(user.created_at_unix_ms);
                         }
                         _ => {}
                     }
@@ -0,0 +1,58 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+#[tokio::test]
+async fn healthz_reports_engine_status_without_auth() {
+    let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+    let server = luban_server::start_server_with_config(
+        addr,
+        luban_server::ServerConfig {
+            auth: luban_server::AuthConfig {
+                mode: luban_server::AuthMode::SingleUser,
+                bootstrap_token: Some("test_healthz_token".to_owned()),
+            },
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(10);
+    let body = loop {
+        let res = client
+            .get(format!("http://{}/healthz", server.addr))
+            .send()
+            .await
+            .unwrap();
+        match res.status() {
+            reqwest::StatusCode::OK => break res.json::<serde_json::Value>().await.unwrap(),
+            reqwest::StatusCode::SERVICE_UNAVAILABLE => {
+                assert!(
+                    tokio::time::Instant::now() < deadline,
+                    "engine did not finish bootstrapping"
+                );
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+            other => panic!("unexpected /healthz status: {other}"),
+        }
+    };
+
+    for key in ["rev", "projects", "active_turns", "uptime_secs"] {
+        assert!(body[key].is_u64(), "missing numeric `{key}` in {body}");
+    }
+    assert_eq!(body["active_turns"], 0);
+
+    let unauthorized = client
+        .get(format!("http://{}/api/app", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(unauthorized.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    server.shutdown().await.unwrap();
+}
@@ -0,0 +1,68 @@
+use futures::StreamExt as _;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::Message;
+
+type Socket =
+    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+
+async fn connect_after_hello(addr: SocketAddr) -> Socket {
+    let url = format!("ws://{addr}/api/events");
+    let (mut socket, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+    let first = tokio::time::timeout(Duration::from_secs(1), socket.next())
+        .await
+        .expect("timed out waiting for hello")
+        .expect("websocket stream ended")
+        .expect("websocket recv failed");
+    assert!(matches!(first, Message::Text(_)), "expected hello text");
+    socket
+}
+
+#[tokio::test]
+async fn ws_connection_that_stops_ponging_is_dropped_after_timeout() {
+    let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+    let server = luban_server::start_server_with_config(
+        addr,
+        luban_server::ServerConfig {
+            ws_keepalive: luban_server::WsKeepaliveConfig {
+                ping_interval: Duration::from_millis(100),
+                pong_timeout: Duration::from_millis(300),
+            },
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    // Polling the stream lets tungstenite answer server pings, so this connection stays up.
+    let mut responsive = connect_after_hello(server.addr).await;
+    let mut pings = 0;
+    let deadline = tokio::time::Instant::now() + Duration::from_millis(800);
+    while let Ok(next) = tokio::time::timeout_at(deadline, responsive.next()).await {
+        match next {
+            Some(Ok(Message::Ping(_))) => pings += 1,
+            Some(Ok(Message::Close(_))) | Some(Err(_)) | None => {
+                panic!("responsive connection should not be dropped")
+            }
+            Some(Ok(_)) => {}
+        }
+    }
+    assert!(pings >= 2, "expected periodic server pings, got {pings}");
+
+    // Not polling means pings go unanswered; the server should give up on the connection.
+    let mut silent = connect_after_hello(server.addr).await;
+    tokio::time::sleep(Duration::from_millis(800)).await;
+    let closed = tokio::time::timeout(Duration::from_secs(2), async {
+        loop {
+            match silent.next().await {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => {}
+            }
+        }
+    })
+    .await;
+    assert!(
+        closed.is_ok(),
+        "silent connection should be dropped after pong timeout"
+    );
+}
@@ -13,6 +13,21 @@ static UPDATE_CHECK_IN_PROGRESS: AtomicBool = AtomicBool::new(false);
 
 const MENU_ID_CHECK_FOR_UPDATES: &str = "check_for_updates";
 
+/// The embedded server, taken when the main window goes away so it can shut down cleanly.
+struct ServerSlot(std::sync::Mutex<Option<luban_server::StartedServer>>);
+
+fn shutdown_server<R: tauri::Runtime>(app: &tauri::AppHandle<R>) {
+    let Some(slot) = app.try_state::<ServerSlot>() else {
+        return;
+    };
+    let Some(server) = slot.0.lock().ok().and_then(|mut guard| guard.take()) else {
+        return;
+    };
+    if let Err(err) = tauri::async_runtime::block_on(server.shutdown()) {
+        eprintln!("server: shutdown failed: {err:#}");
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 enum BuildChannel {
     Dev,
@@ -307,6 +322,11 @@ fn main() -> anyhow::Result<()> {
                 });
             }
         })
+        .on_window_event(|window, event| {
+            if window.label() == "main" && matches!(event, tauri::WindowEvent::Destroyed) {
+                shutdown_server(window.app_handle());
+            }
+        })
         .invoke_handler(tauri::generate_handler![open_external])
         .setup(|app| {
             let _ = path_env::fix_path_env();
@@ -331,7 +351,7 @@ fn main() -> anyhow::Result<()> {
                 .parse()
                 .context("invalid server url")?;
 
-            app.manage(server);
+            app.manage(ServerSlot(std::sync::Mutex::new(Some(server))));
 
             WebviewWindowBuilder::new(app, "main", WebviewUrl::External(url))
                 .title("Luban")
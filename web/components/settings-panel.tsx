@@ -129,6 +129,18 @@ const systemTaskTypes: TaskTypeConfig[] = [
     icon: CheckCircle2,
     description: "Suggest task status based on the latest agent progress (manual apply)",
   },
+  {
+    id: "pr-description",
+    label: "PR Description",
+    icon: GitPullRequest,
+    description: "Summarize workdir changes into a pull request body",
+  },
+  {
+    id: "conversation-summary",
+    label: "Conversation Summary",
+    icon: FileText,
+    description: "Summarize earlier conversation entries when compacting history",
+  },
 ]
 
 const taskTypes: TaskTypeConfig[] = [
@@ -162,6 +174,8 @@ const variablesByTaskType: Record<string, string[]> = {
   "rename-branch": ["task_input", "context_json"],
   "auto-title-thread": ["task_input", "context_json"],
   "auto-update-task-status": ["task_input", "context_json"],
+  "pr-description": ["task_input", "context_json"],
+  "conversation-summary": ["task_input", "context_json"],
   fix: ["repo", "issue", "task_input", "intent_label", "known_context"],
   implement: ["repo", "issue", "task_input", "intent_label", "known_context"],
   review: ["repo", "pr", "task_input", "intent_label", "known_context"],
@@ -260,7 +274,9 @@ function TaskPromptEditor({
     taskType === "infer-type" ||
     taskType === "rename-branch" ||
     taskType === "auto-title-thread" ||
-    taskType === "auto-update-task-status"
+    taskType === "auto-update-task-status" ||
+    taskType === "pr-description" ||
+    taskType === "conversation-summary"
 
   const [selectedType, setSelectedType] = useState<TaskType>("infer-type")
   const [typePrompts, setTypePrompts] = useState<Record<string, string>>(() => {
@@ -1636,7 +1652,7 @@ function TelegramIntegrationPanel() {
 
   const enabled = telegram?.enabled ?? false
   const hasToken = telegram?.has_token ?? false
-  const pairedChatId = telegram?.paired_chat_id ?? null
+  const pairedChatIds = telegram?.paired_chat_ids ?? []
   const botUsername = telegram?.bot_username ?? null
   const lastError = telegram?.last_error ?? null
 
@@ -1650,7 +1666,9 @@ function TelegramIntegrationPanel() {
             </div>
             <div className="text-[12px] mt-1" style={{ color: "#6b6b6b" }}>
               {enabled ? "Enabled" : "Disabled"} · {hasToken ? "Token set" : "No token"} ·{" "}
-              {pairedChatId != null ? `Paired (${pairedChatId})` : "Not paired"}
+              {pairedChatIds.length > 0
+                ? `Paired (${pairedChatIds.length} ${pairedChatIds.length === 1 ? "chat" : "chats"})`
+                : "Not paired"}
               {botUsername ? ` · @${botUsername}` : ""}
             </div>
           </div>
@@ -1770,9 +1788,24 @@ function TelegramIntegrationPanel() {
           </div>
         ) : null}
 
+        {pairedChatIds.map((chatId) => (
+          <div key={chatId} className="flex items-center justify-between" data-testid="telegram-paired-chat">
+            <div className="text-[12px]" style={{ color: "#6b6b6b" }}>
+              Paired chat_id: {chatId}
+            </div>
+            <button
+              onClick={() => unpairTelegram(chatId)}
+              className="px-3 py-1 rounded text-[12px] transition-colors"
+              style={{ backgroundColor: "#eeeeee", color: "#1b1b1b" }}
+            >
+              Unpair
+            </button>
+          </div>
+        ))}
+
         <div className="flex items-center justify-between">
           <div className="text-[12px]" style={{ color: "#6b6b6b" }}>
-            {pairedChatId != null ? `Paired chat_id: ${pairedChatId}` : "Not paired"}
+            {pairedChatIds.length > 0 ? "Each generated link pairs one more chat." : "Not paired"}
           </div>
           <button
             data-testid="telegram-unpair"
@@ -1780,11 +1813,11 @@ function TelegramIntegrationPanel() {
               unpairTelegram()
               setPairUrl(null)
             }}
-            disabled={pairedChatId == null}
+            disabled={pairedChatIds.length === 0}
             className="px-3 py-2 rounded text-[13px] transition-colors disabled:opacity-50"
             style={{ backgroundColor: "#eeeeee", color: "#1b1b1b" }}
           >
-            Unpair
+            Unpair all
           </button>
         </div>
       </div>
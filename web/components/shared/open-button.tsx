@@ -3,7 +3,7 @@
 import type { ComponentType } from "react"
 
 import { useEffect, useMemo, useState } from "react"
-import { Check, ChevronDown, Copy, FolderOpen } from "lucide-react"
+import { Check, ChevronDown, Code, Copy, FileCode, FolderOpen, SquareTerminal } from "lucide-react"
 import Image from "next/image"
 
 import type { OpenTarget } from "@/lib/luban-api"
@@ -23,13 +23,13 @@ function BrandIcon({ src, alt, className }: { src: string; alt: string; classNam
   )
 }
 
-export type EditorType = "vscode" | "cursor" | "zed"
+export type EditorType = "vscode" | "cursor" | "zed" | "idea" | "sublime" | "neovim"
 export type ActionType = "copy-path" | "ghostty" | "finder"
 
 type EditorConfig = {
   id: EditorType
   name: string
-  icon: string
+  icon: string | ComponentType<{ className?: string }>
 }
 
 type ActionConfig = {
@@ -42,6 +42,9 @@ const editors: EditorConfig[] = [
   { id: "vscode", name: "VS Code", icon: "/icons/vscode.svg" },
   { id: "cursor", name: "Cursor", icon: "/icons/cursor.svg" },
   { id: "zed", name: "Zed", icon: "/icons/zed.svg" },
+  { id: "idea", name: "IntelliJ IDEA", icon: Code },
+  { id: "sublime", name: "Sublime Text", icon: FileCode },
+  { id: "neovim", name: "Neovim", icon: SquareTerminal },
 ]
 
 const actions: ActionConfig[] = [
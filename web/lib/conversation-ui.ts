@@ -307,11 +307,16 @@ export function activityFromAgentItemLike(args: {
   }
 
   if (kind === "web_search") {
+    const results = Array.isArray(payload?.results) ? payload.results : []
+    const detail =
+      results.length > 0
+        ? results.map((r: any) => `${r.title || r.url}\n${r.url}${r.snippet ? `\n${r.snippet}` : ""}`).join("\n\n")
+        : safeStringify(payload ?? null)
     return {
       id: args.id,
       type: "search",
       title: payload?.query ?? "Web search",
-      detail: safeStringify(payload ?? null),
+      detail,
       status: forcedStatus ?? "done",
     }
   }
@@ -359,7 +364,7 @@ export function activityFromAgentItem(entry: Extract<AgentEvent, { type: "item"
 
 function inferActivityStatusFromPayload(payload: unknown): ActivityStatus {
   const status = (payload as any)?.status
-  if (status === "in_progress") return "running"
+  if (status === "in_progress" || status === "awaiting_approval") return "running"
   return "done"
 }
 
@@ -15,12 +15,15 @@ import type {
   FeedbackSubmitResult,
   FeedbackType,
   OpenTarget,
+  ProjectAgentEnvVar,
   ProjectId,
   SystemTaskKind,
+  TabDirection,
   TaskIntentKind,
   TaskExecuteMode,
   TaskExecuteResult,
   TaskStatus,
+  TerminalHistoryEntry,
   ThinkingEffort,
   WorkspaceId,
   WorkspaceThreadId,
@@ -40,12 +43,18 @@ export type LubanActions = {
   deleteProject: (projectId: ProjectId) => void
   createWorkdir: (projectId: ProjectId) => void
   ensureMainWorkdir: (projectId: ProjectId) => void
+  openProjectMain: (projectId: ProjectId) => Promise<{ workdirId: WorkspaceId; taskId: WorkspaceThreadId }>
   openWorkdirInIde: (workdirId: WorkspaceId) => void
   openWorkdirWith: (workdirId: WorkspaceId, target: OpenTarget) => void
   openWorkdirPullRequest: (workdirId: WorkspaceId) => void
   openWorkdirPullRequestFailedAction: (workdirId: WorkspaceId) => void
   archiveWorkdir: (workdirId: number) => void
   toggleProjectExpanded: (projectId: ProjectId) => void
+  addProjectSavedCommand: (projectId: ProjectId, label: string, command: string) => void
+  removeProjectSavedCommand: (projectId: ProjectId, id: number) => void
+  setProjectBranchNameTemplate: (projectId: ProjectId, template: string | null) => void
+  setProjectPullRequestTarget: (projectId: ProjectId, remote: string | null, baseBranch: string | null) => void
+  setProjectAgentEnv: (projectId: ProjectId, env: ProjectAgentEnvVar[]) => void
   setCodexEnabled: (enabled: boolean) => void
   setAmpEnabled: (enabled: boolean) => void
   setClaudeEnabled: (enabled: boolean) => void
@@ -55,7 +64,9 @@ export type LubanActions = {
   setTelegramBotToken: (token: string) => void
   clearTelegramBotToken: () => void
   startTelegramPairing: () => Promise<string>
-  unpairTelegram: () => void
+  unpairTelegram: (chatId?: number) => void
+  setWebhookConfig: (url: string, secret?: string | null) => void
+  clearWebhookConfig: () => void
   setTaskPromptTemplate: (intentKind: TaskIntentKind, template: string) => void
   setSystemPromptTemplate: (kind: SystemTaskKind, template: string) => void
   checkCodex: () => Promise<{ ok: boolean; message: string | null }>
@@ -87,6 +98,7 @@ export type LubanActions = {
   ) => Promise<TaskExecuteResult>
   setTaskStarred: (workdirId: WorkspaceId, taskId: WorkspaceThreadId, starred: boolean) => void
   setTaskStatus: (workdirId: WorkspaceId, taskId: WorkspaceThreadId, taskStatus: TaskStatus) => void
+  renameTask: (workdirId: WorkspaceId, taskId: WorkspaceThreadId, title: string) => void
   submitFeedback: (args: {
     title: string
     body: string
@@ -96,12 +108,16 @@ export type LubanActions = {
   }) => Promise<FeedbackSubmitResult>
 
   openWorkdir: (workdirId: WorkspaceId) => Promise<void>
+  markWorkdirRead: (workdirId: WorkspaceId) => void
+  markAllRead: () => void
   activateTask: (taskId: number) => Promise<void>
   loadConversationBefore: (workdirId: WorkspaceId, taskId: WorkspaceThreadId, before: number) => Promise<void>
   createTask: () => void
   closeTaskTab: (taskId: number) => Promise<void>
   deleteTask: (taskId: number) => Promise<void>
+  moveTask: (taskId: number, toWorkdirId: WorkspaceId) => Promise<void>
   restoreTaskTab: (taskId: number) => Promise<void>
+  activateAdjacentTask: (direction: TabDirection) => Promise<void>
 
   sendAgentMessage: (
     text: string,
@@ -121,6 +137,7 @@ export type LubanActions = {
     runConfig?: { runner?: AgentRunnerKind | null; amp_mode?: string | null },
   ) => void
   runTerminalCommand: (command: string) => void
+  listTerminalHistory: (workdirId: WorkspaceId, limit?: number) => Promise<TerminalHistoryEntry[]>
   removeQueuedPrompt: (workspaceId: WorkspaceId, taskId: WorkspaceThreadId, promptId: number) => void
   reorderQueuedPrompt: (
     workspaceId: WorkspaceId,
@@ -135,6 +152,7 @@ export type LubanActions = {
     args: { text: string; attachments: AttachmentRef[]; runConfig: AgentRunConfigSnapshot },
   ) => void
   cancelAgentTurn: () => void
+  respondToToolApproval: (callId: string, approved: boolean) => void
   cancelAndSendAgentMessage: (
     text: string,
     attachments?: AttachmentRef[],
@@ -143,11 +161,20 @@ export type LubanActions = {
 
   renameWorkdirBranch: (workdirId: WorkspaceId, branchName: string) => void
   aiRenameWorkdirBranch: (workdirId: WorkspaceId, taskId: WorkspaceThreadId) => void
+  stashWorkdirChanges: (workdirId: WorkspaceId) => void
+  popWorkdirStash: (workdirId: WorkspaceId) => void
+  commitWorkdirChanges: (workdirId: WorkspaceId, message: string, paths?: string[]) => void
 
   setChatModel: (workdirId: WorkspaceId, taskId: WorkspaceThreadId, modelId: string) => void
   setThinkingEffort: (workdirId: WorkspaceId, taskId: WorkspaceThreadId, effort: ThinkingEffort) => void
   setChatRunner: (workdirId: WorkspaceId, taskId: WorkspaceThreadId, runner: AgentRunnerKind) => void
   setChatAmpMode: (workdirId: WorkspaceId, taskId: WorkspaceThreadId, ampMode: string) => void
+  setChatClaudeOptions: (
+    workdirId: WorkspaceId,
+    taskId: WorkspaceThreadId,
+    options: { maxOutputTokens: number | null; systemPrompt: string | null },
+  ) => void
+  setChatAutoCommit: (workdirId: WorkspaceId, taskId: WorkspaceThreadId, enabled: boolean) => void
   setAppearanceTheme: (theme: AppearanceTheme) => void
   setAppearanceFonts: (fonts: AppearanceFontsSnapshot) => void
   setGlobalZoom: (zoom: number) => void
@@ -190,6 +217,13 @@ export function createLubanActions(args: {
     args.sendAction({ type: "ensure_main_workdir", project_id: projectId })
   }
 
+  function openProjectMain(projectId: ProjectId): Promise<{ workdirId: WorkspaceId; taskId: WorkspaceThreadId }> {
+    return args.request<{ workdirId: WorkspaceId; taskId: WorkspaceThreadId }>({
+      type: "open_project_main",
+      project_id: projectId,
+    })
+  }
+
   function openWorkdirInIde(workdirId: WorkspaceId) {
     args.sendAction({ type: "open_workdir_in_ide", workdir_id: workdirId })
   }
@@ -221,6 +255,31 @@ export function createLubanActions(args: {
     args.sendAction({ type: "toggle_project_expanded", project_id: projectId })
   }
 
+  function addProjectSavedCommand(projectId: ProjectId, label: string, command: string) {
+    args.sendAction({ type: "add_project_saved_command", project_id: projectId, label, command })
+  }
+
+  function removeProjectSavedCommand(projectId: ProjectId, id: number) {
+    args.sendAction({ type: "remove_project_saved_command", project_id: projectId, id })
+  }
+
+  function setProjectBranchNameTemplate(projectId: ProjectId, template: string | null) {
+    args.sendAction({ type: "set_project_branch_name_template", project_id: projectId, template })
+  }
+
+  function setProjectPullRequestTarget(projectId: ProjectId, remote: string | null, baseBranch: string | null) {
+    args.sendAction({
+      type: "set_project_pull_request_target",
+      project_id: projectId,
+      remote,
+      base_branch: baseBranch,
+    })
+  }
+
+  function setProjectAgentEnv(projectId: ProjectId, env: ProjectAgentEnvVar[]) {
+    args.sendAction({ type: "set_project_agent_env", project_id: projectId, env })
+  }
+
   function setCodexEnabled(enabled: boolean) {
     args.sendAction({ type: "codex_enabled_changed", enabled })
   }
@@ -259,8 +318,18 @@ export function createLubanActions(args: {
     return args.request<string>({ type: "telegram_pair_start" })
   }
 
-  function unpairTelegram() {
-    args.sendAction({ type: "telegram_unpair" })
+  function unpairTelegram(chatId?: number) {
+    args.sendAction({ type: "telegram_unpair", chat_id: chatId ?? null })
+  }
+
+  function setWebhookConfig(url: string, secret?: string | null) {
+    const trimmed = url.trim()
+    if (!trimmed) return
+    args.sendAction({ type: "webhook_config_set", url: trimmed, secret: secret ?? null })
+  }
+
+  function clearWebhookConfig() {
+    args.sendAction({ type: "webhook_config_clear" })
   }
 
   function setTaskPromptTemplate(intentKind: TaskIntentKind, template: string) {
@@ -436,6 +505,10 @@ export function createLubanActions(args: {
     args.sendAction({ type: "task_status_set", workdir_id: workdirId, task_id: taskId, task_status: taskStatus })
   }
 
+  function renameTask(workdirId: WorkspaceId, taskId: WorkspaceThreadId, title: string) {
+    args.sendAction({ type: "rename_thread", workdir_id: workdirId, task_id: taskId, title })
+  }
+
   function submitFeedback(args2: {
     title: string
     body: string
@@ -530,6 +603,14 @@ export function createLubanActions(args: {
     return true
   }
 
+  function markWorkdirRead(workdirId: WorkspaceId) {
+    args.sendAction({ type: "mark_workdir_read", workdir_id: workdirId })
+  }
+
+  function markAllRead() {
+    args.sendAction({ type: "mark_all_read" })
+  }
+
   async function openWorkdir(workdirId: WorkspaceId) {
     store.setActiveWorkspaceId(workdirId)
 
@@ -653,6 +734,17 @@ export function createLubanActions(args: {
     }
   }
 
+  async function moveTask(taskId: number, toWorkdirId: WorkspaceId) {
+    const wid = store.refs.activeWorkspaceIdRef.current
+    if (wid == null) return
+    args.sendAction({ type: "move_thread", from_workdir_id: wid, task_id: taskId, to_workdir_id: toWorkdirId })
+    try {
+      await refreshThreads(wid)
+    } catch (err) {
+      console.warn("fetchThreads failed after move", err)
+    }
+  }
+
   async function restoreTaskTab(taskId: number) {
     const wid = store.refs.activeWorkspaceIdRef.current
     if (wid == null) return
@@ -664,6 +756,17 @@ export function createLubanActions(args: {
     }
   }
 
+  async function activateAdjacentTask(direction: TabDirection) {
+    const wid = store.refs.activeWorkspaceIdRef.current
+    if (wid == null) return
+    args.sendAction({ type: "activate_adjacent_task", workdir_id: wid, direction })
+    try {
+      await refreshThreads(wid)
+    } catch (err) {
+      console.warn("fetchThreads failed", err)
+    }
+  }
+
   function activeWorkspaceThread(): { workspaceId: WorkspaceId; threadId: number } | null {
     const wid = store.refs.activeWorkspaceIdRef.current
     const tid = store.refs.activeThreadIdRef.current
@@ -736,6 +839,10 @@ export function createLubanActions(args: {
     })
   }
 
+  function listTerminalHistory(workdirId: WorkspaceId, limit?: number): Promise<TerminalHistoryEntry[]> {
+    return args.request<TerminalHistoryEntry[]>({ type: "list_terminal_history", workdir_id: workdirId, limit })
+  }
+
   function removeQueuedPrompt(workspaceId: WorkspaceId, threadId: WorkspaceThreadId, promptId: number) {
     store.setConversation((prev) => {
       if (!prev) return prev
@@ -798,6 +905,8 @@ export function createLubanActions(args: {
       attachments,
       model_id: runConfig.model_id,
       thinking_effort: runConfig.thinking_effort,
+      runner: runConfig.runner,
+      amp_mode: runConfig.runner === "amp" ? (runConfig.amp_mode ?? null) : null,
     })
   }
 
@@ -807,6 +916,18 @@ export function createLubanActions(args: {
     args.sendAction({ type: "cancel_agent_turn", workdir_id: ids.workspaceId, task_id: ids.threadId })
   }
 
+  function respondToToolApproval(callId: string, approved: boolean) {
+    const ids = activeWorkspaceThread()
+    if (!ids) return
+    args.sendAction({
+      type: "respond_to_tool_approval",
+      workdir_id: ids.workspaceId,
+      task_id: ids.threadId,
+      call_id: callId,
+      approved,
+    })
+  }
+
   function cancelAndSendAgentMessage(
     text: string,
     attachments: AttachmentRef[] = [],
@@ -833,6 +954,20 @@ export function createLubanActions(args: {
     args.sendAction({ type: "workdir_ai_rename_branch", workdir_id: workdirId, task_id: taskId })
   }
 
+  function stashWorkdirChanges(workdirId: WorkspaceId) {
+    args.sendAction({ type: "workdir_stash_changes", workdir_id: workdirId })
+  }
+
+  function popWorkdirStash(workdirId: WorkspaceId) {
+    args.sendAction({ type: "workdir_stash_pop", workdir_id: workdirId })
+  }
+
+  function commitWorkdirChanges(workdirId: WorkspaceId, message: string, paths?: string[]) {
+    const trimmed = message.trim()
+    if (!trimmed) return
+    args.sendAction({ type: "workdir_commit", workdir_id: workdirId, message: trimmed, paths: paths ?? null })
+  }
+
   function setChatModel(workdirId: WorkspaceId, taskId: WorkspaceThreadId, modelId: string) {
     args.sendAction({
       type: "chat_model_changed",
@@ -891,6 +1026,37 @@ export function createLubanActions(args: {
     args.sendAction({ type: "chat_amp_mode_changed", workdir_id: workdirId, task_id: taskId, amp_mode: trimmed })
   }
 
+  function setChatClaudeOptions(
+    workdirId: WorkspaceId,
+    taskId: WorkspaceThreadId,
+    options: { maxOutputTokens: number | null; systemPrompt: string | null },
+  ) {
+    const maxOutputTokens = options.maxOutputTokens && options.maxOutputTokens > 0 ? options.maxOutputTokens : null
+    const systemPrompt = options.systemPrompt?.trim() || null
+    store.setConversation((prev) => {
+      if (!prev) return prev
+      if (prev.workdir_id !== workdirId || prev.task_id !== taskId) return prev
+      if (prev.agent_runner !== "claude") return prev
+      return { ...prev, claude_max_output_tokens: maxOutputTokens, claude_system_prompt: systemPrompt }
+    })
+    args.sendAction({
+      type: "chat_claude_options_changed",
+      workdir_id: workdirId,
+      task_id: taskId,
+      max_output_tokens: maxOutputTokens,
+      system_prompt: systemPrompt,
+    })
+  }
+
+  function setChatAutoCommit(workdirId: WorkspaceId, taskId: WorkspaceThreadId, enabled: boolean) {
+    store.setConversation((prev) => {
+      if (!prev) return prev
+      if (prev.workdir_id !== workdirId || prev.task_id !== taskId) return prev
+      return { ...prev, auto_commit: enabled }
+    })
+    args.sendAction({ type: "chat_auto_commit_changed", workdir_id: workdirId, task_id: taskId, enabled })
+  }
+
   function setAppearanceTheme(theme: AppearanceTheme) {
     args.sendAction({ type: "appearance_theme_changed", theme })
   }
@@ -928,12 +1094,18 @@ export function createLubanActions(args: {
     deleteProject,
     createWorkdir,
     ensureMainWorkdir,
+    openProjectMain,
     openWorkdirInIde,
     openWorkdirWith,
     openWorkdirPullRequest,
     openWorkdirPullRequestFailedAction,
     archiveWorkdir,
     toggleProjectExpanded,
+    addProjectSavedCommand,
+    removeProjectSavedCommand,
+    setProjectBranchNameTemplate,
+    setProjectPullRequestTarget,
+    setProjectAgentEnv,
     setCodexEnabled,
     setAmpEnabled,
     setClaudeEnabled,
@@ -944,6 +1116,8 @@ export function createLubanActions(args: {
     clearTelegramBotToken,
     startTelegramPairing,
     unpairTelegram,
+    setWebhookConfig,
+    clearWebhookConfig,
     setTaskPromptTemplate,
     setSystemPromptTemplate,
     checkCodex,
@@ -969,30 +1143,42 @@ export function createLubanActions(args: {
     executeTask,
     setTaskStarred,
     setTaskStatus,
+    renameTask,
     submitFeedback,
     openWorkdir,
+    markWorkdirRead,
+    markAllRead,
     activateTask,
     loadConversationBefore,
     selectThreadInWorkspace,
     createTask,
     closeTaskTab,
     deleteTask,
+    moveTask,
     restoreTaskTab,
+    activateAdjacentTask,
     sendAgentMessage,
     queueAgentMessage,
     sendAgentMessageTo,
     runTerminalCommand,
+    listTerminalHistory,
     removeQueuedPrompt,
     reorderQueuedPrompt,
     updateQueuedPrompt,
     cancelAgentTurn,
+    respondToToolApproval,
     cancelAndSendAgentMessage,
     renameWorkdirBranch,
     aiRenameWorkdirBranch,
+    stashWorkdirChanges,
+    popWorkdirStash,
+    commitWorkdirChanges,
     setChatModel,
     setThinkingEffort,
     setChatRunner,
     setChatAmpMode,
+    setChatClaudeOptions,
+    setChatAutoCommit,
     setAppearanceTheme,
     setAppearanceFonts,
     setGlobalZoom,
@@ -1,8 +1,10 @@
 export type ProjectId = string
+export type ProjectGroupId = number
 export type WorkspaceId = number
 export type WorkspaceThreadId = number
 
 export type WorkspaceStatus = "active" | "archived"
+export type ProjectStatus = "active" | "archived"
 
 export type AppearanceTheme = "light" | "dark" | "system"
 
@@ -21,6 +23,9 @@ export type AppearanceSnapshot = {
 
 export type AgentRunnerKind = "codex" | "amp" | "claude" | "droid"
 
+// Unknown strings round-trip, but the server refuses to start an Amp turn with one.
+export type AmpMode = "smart" | "rush" | (string & {})
+
 export type AgentSettingsSnapshot = {
   codex_enabled: boolean
   amp_enabled: boolean
@@ -28,9 +33,17 @@ export type AgentSettingsSnapshot = {
   droid_enabled: boolean
   default_model_id?: string
   runner_default_models?: Record<string, string>
+  runner_prompt_prefixes?: Record<string, string>
+  runner_prompt_suffixes?: Record<string, string>
+  model_thinking_effort_caps?: Record<string, ThinkingEffort>
   default_thinking_effort?: ThinkingEffort
   default_runner?: AgentRunnerKind
-  amp_mode?: string
+  amp_mode?: AmpMode
+  merge_reasoning_entries?: boolean
+  pause_queue_on_patch_failure?: boolean
+  item_dedup_window?: number
+  auto_retry_on_failure?: number
+  command_output_max_bytes?: number
 }
 
 export type TaskPromptTemplateSnapshot = {
@@ -43,6 +56,8 @@ export type SystemTaskKind =
   | "rename-branch"
   | "auto-title-thread"
   | "auto-update-task-status"
+  | "pr-description"
+  | "conversation-summary"
 
 export type SystemPromptTemplateSnapshot = {
   kind: SystemTaskKind
@@ -60,25 +75,65 @@ export type TelegramIntegrationSnapshot = {
   enabled: boolean
   has_token: boolean
   bot_username?: string
-  paired_chat_id?: number
+  paired_chat_ids?: number[]
+  config_rev: number
+  last_error?: string
+}
+
+export type WebhookIntegrationSnapshot = {
+  enabled: boolean
+  url?: string
+  has_secret: boolean
   config_rev: number
   last_error?: string
 }
 
 export type IntegrationsSnapshot = {
   telegram: TelegramIntegrationSnapshot
+  webhook?: WebhookIntegrationSnapshot
 }
 
 export type AppSnapshot = {
   rev: number
   projects: ProjectSnapshot[]
+  project_groups?: ProjectGroupSnapshot[]
   appearance: AppearanceSnapshot
   agent: AgentSettingsSnapshot
   task: TaskSettingsSnapshot
   ui: UiSnapshot
   integrations: IntegrationsSnapshot
+  running_turns?: number
+  waiting_turns?: number
+  max_concurrent_turns?: number
+  conversation_retention?: ConversationRetentionSnapshot
+  auto_archive_after_days?: number | null
+  queued_prompts_total?: number
+  main_pane?: MainPaneSnapshot
+  errors?: ErrorLogEntrySnapshot[]
+}
+
+export type ConversationRetentionSnapshot = {
+  keep_last_entries?: number | null
+  max_age_days?: number | null
 }
 
+export type ProjectGroupSnapshot = {
+  id: ProjectGroupId
+  name: string
+  project_ids: ProjectId[]
+}
+
+export type ErrorLogEntrySnapshot = {
+  message: string
+  created_at_unix_ms: number
+}
+
+export type MainPaneSnapshot =
+  | { type: "none" }
+  | { type: "dashboard" }
+  | { type: "project_settings"; project_id: ProjectId }
+  | { type: "workdir"; workdir_id: WorkspaceId }
+
 export type UiSnapshot = {
   active_workdir_id?: WorkspaceId
   active_task_id?: WorkspaceThreadId
@@ -93,22 +148,90 @@ export type ProjectSnapshot = {
   path: string
   is_git: boolean
   expanded: boolean
+  system_prompt?: string | null
+  worktree_root?: string | null
+  branch_name_template?: string | null
+  pr_remote?: string | null
+  pr_base_branch?: string | null
+  status?: ProjectStatus
+  agent_defaults?: ProjectAgentDefaultsSnapshot
+  saved_commands?: ProjectSavedCommandSnapshot[]
+  agent_env?: ProjectAgentEnvVarSnapshot[]
   create_workdir_status: OperationStatus
   workdirs: WorkspaceSnapshot[]
 }
 
+export type WorktreeAuditSnapshot = {
+  orphaned: string[]
+  dangling: string[]
+}
+
+export type ProjectAgentDefaultsSnapshot = {
+  runner?: AgentRunnerKind | null
+  model_id?: string | null
+  thinking_effort?: ThinkingEffort | null
+  amp_mode?: string | null
+}
+
+export type ProjectSavedCommandSnapshot = {
+  id: number
+  label: string
+  command: string
+}
+
+export type ProjectAgentEnvVarSnapshot = {
+  key: string
+  has_value: boolean
+}
+
+export type ProjectAgentEnvVar = {
+  key: string
+  value: string | null
+}
+
+export type TaskStatusSetResult = {
+  workdir_id: WorkspaceId
+  task_id: WorkspaceThreadId
+  ok: boolean
+  message?: string | null
+}
+
+export type ConversationSearchMatch = {
+  task_id: WorkspaceThreadId
+  entry_id: string
+  entry_index: number
+  snippet: string
+}
+
+export type JsonlImportLineError = {
+  line: number
+  message: string
+}
+
 export type WorkspaceSnapshot = {
   id: WorkspaceId
   short_id: string
   workdir_name: string
   branch_name: string
+  detached_head?: boolean
   workdir_path: string
   status: WorkspaceStatus
   archive_status: OperationStatus
   branch_rename_status: OperationStatus
+  stash_status?: OperationStatus
+  commit_status?: OperationStatus
   agent_run_status: OperationStatus
   has_unread_completion: boolean
   pull_request: PullRequestSnapshot | null
+  pull_request_error?: string | null
+  last_commit?: LastCommitSnapshot | null
+}
+
+export type LastCommitSnapshot = {
+  sha: string
+  subject: string
+  author: string
+  unix_time: number
 }
 
 export type FileChangeStatus = "modified" | "added" | "deleted" | "renamed"
@@ -140,6 +263,16 @@ export type WorkspaceDiffFileSnapshot = {
   file: ChangedFileSnapshot
   old_file: DiffFileContents
   new_file: DiffFileContents
+  hunks?: DiffHunkSnapshot[]
+  binary?: boolean
+}
+
+export type DiffHunkSnapshot = {
+  old_start: number
+  old_lines: number
+  new_start: number
+  new_lines: number
+  lines: string[]
 }
 
 export type WorkspaceDiffSnapshot = {
@@ -194,6 +327,8 @@ export type WorkspaceTabsSnapshot = {
   active_tab: WorkspaceThreadId
 }
 
+export type TabDirection = "next" | "prev"
+
 export type ThreadMeta = {
   task_id: WorkspaceThreadId
   remote_thread_id: string | null
@@ -203,6 +338,7 @@ export type ThreadMeta = {
   task_status: TaskStatus
   turn_status: TurnStatus
   last_turn_result: TurnResult | null
+  total_tokens?: number
 }
 
 export type AttachmentKind = "image" | "text" | "file"
@@ -214,6 +350,7 @@ export type AttachmentRef = {
   extension: string
   mime: string | null
   byte_len: number
+  thumbnail_base64?: string | null
 }
 
 export type ConversationSnapshot = {
@@ -224,8 +361,13 @@ export type ConversationSnapshot = {
   agent_runner: AgentRunnerKind
   agent_model_id: string
   thinking_effort: ThinkingEffort
-  amp_mode?: string | null
+  run_thinking_effort?: ThinkingEffort | null
+  amp_mode?: AmpMode | null
+  claude_max_output_tokens?: number | null
+  claude_system_prompt?: string | null
+  auto_commit?: boolean
   run_status: OperationStatus
+  turn_status?: TurnStatus
   run_started_at_unix_ms?: number | null
   run_finished_at_unix_ms?: number | null
   entries: ConversationEntry[]
@@ -236,6 +378,40 @@ export type ConversationSnapshot = {
   queue_paused: boolean
   remote_thread_id: string | null
   title: string
+  notes?: string
+  cwd_subpath?: string | null
+  draft?: string
+  hunk_comments?: HunkCommentSnapshot[]
+  collapsed_ranges?: CollapsedRangeSnapshot[]
+  patch_apply_failed_entry_id?: string | null
+  had_failed_commands?: boolean
+  failed_command_ids?: string[]
+  worktree_dirty?: boolean
+  total_usage?: TokenUsageSnapshot
+}
+
+export type TokenUsageSnapshot = {
+  input_tokens: number
+  cached_input_tokens: number
+  output_tokens: number
+}
+
+export type HunkLineSide = "old" | "new"
+
+export type HunkCommentSnapshot = {
+  entry_id: string
+  path: string
+  side: HunkLineSide
+  line: number
+  text: string
+  created_at_unix_ms: number
+}
+
+export type CollapsedRangeSnapshot = {
+  start_entry_id: string
+  end_entry_id: string
+  entry_count: number
+  summary_entry_id: string
 }
 
 export type ConversationSystemEvent =
@@ -249,6 +425,9 @@ export type ConversationSystemEvent =
       title: string
       explanation_markdown: string
     }
+  | { event_type: "turn_retried_with_runner"; runner: AgentRunnerKind; model_id: string }
+  | { event_type: "history_summarized"; summary_markdown: string; collapsed_entries: number }
+  | { event_type: "history_pruned"; pruned_entries: number }
 
 export type ConversationSystemEventEntry = {
   entry_id: string
@@ -261,6 +440,8 @@ export type AgentRunConfigSnapshot = {
   model_id: string
   thinking_effort: ThinkingEffort
   amp_mode?: string | null
+  claude_max_output_tokens?: number | null
+  claude_system_prompt?: string | null
 }
 
 export type QueuedPromptSnapshot = {
@@ -286,7 +467,7 @@ export type TurnResult = "completed" | "failed"
 
 export type ThinkingEffort = "minimal" | "low" | "medium" | "high" | "xhigh"
 
-export type OpenTarget = "vscode" | "cursor" | "zed" | "ghostty" | "finder"
+export type OpenTarget = "vscode" | "cursor" | "zed" | "idea" | "sublime" | "neovim" | "ghostty" | "finder"
 
 export type TaskIntentKind =
   | "fix"
@@ -414,13 +595,20 @@ export type UserEvent =
       output_byte_len: number
     }
 
+export type TerminalHistoryEntry = {
+  command: string
+  exit_code?: number
+  started_at_unix_ms: number
+}
+
 export type AgentEvent =
   | { type: "message"; id: string; text: string }
   | { type: "item"; id: string; kind: AgentItemKind; payload: unknown }
   | { type: "turn_usage"; usage_json: unknown | null }
+  | { type: "turn_started"; at_unix_ms: number }
   | { type: "turn_duration"; duration_ms: number }
   | { type: "turn_canceled" }
-  | { type: "turn_error"; message: string }
+  | { type: "turn_error"; message: string; code?: string }
 
 export type ClientAction =
   | { type: "pick_project_path" }
@@ -436,9 +624,14 @@ export type ClientAction =
   | { type: "telegram_bot_token_set"; token: string }
   | { type: "telegram_bot_token_clear" }
   | { type: "telegram_pair_start" }
-  | { type: "telegram_unpair" }
+  | { type: "telegram_unpair"; chat_id?: number | null }
+  | { type: "webhook_config_set"; url: string; secret?: string | null }
+  | { type: "webhook_config_clear" }
   | { type: "task_star_set"; workdir_id: WorkspaceId; task_id: WorkspaceThreadId; starred: boolean }
+  | { type: "list_starred_tasks" }
   | { type: "task_status_set"; workdir_id: WorkspaceId; task_id: WorkspaceThreadId; task_status: TaskStatus }
+  | { type: "rename_thread"; workdir_id: WorkspaceId; task_id: WorkspaceThreadId; title: string }
+  | { type: "bulk_set_task_status"; targets: [WorkspaceId, WorkspaceThreadId][]; task_status: TaskStatus }
   | {
       type: "feedback_submit"
       title: string
@@ -448,10 +641,51 @@ export type ClientAction =
       action: FeedbackSubmitAction
     }
   | { type: "delete_project"; project_id: ProjectId }
+  | { type: "archive_project"; project_id: ProjectId }
+  | { type: "restore_project"; project_id: ProjectId }
   | { type: "toggle_project_expanded"; project_id: ProjectId }
-  | { type: "create_workdir"; project_id: ProjectId }
+  | { type: "project_system_prompt_changed"; project_id: ProjectId; system_prompt: string | null }
+  | { type: "set_project_worktree_root"; project_id: ProjectId; path: string | null }
+  | { type: "set_project_branch_name_template"; project_id: ProjectId; template: string | null }
+  | {
+      type: "set_project_pull_request_target"
+      project_id: ProjectId
+      remote: string | null
+      base_branch: string | null
+    }
+  | { type: "set_project_agent_env"; project_id: ProjectId; env: ProjectAgentEnvVar[] }
+  | {
+      type: "set_project_agent_defaults"
+      project_id: ProjectId
+      runner: AgentRunnerKind | null
+      model_id: string | null
+      thinking_effort: ThinkingEffort | null
+      amp_mode: string | null
+    }
+  | { type: "add_project_saved_command"; project_id: ProjectId; label: string; command: string }
+  | { type: "remove_project_saved_command"; project_id: ProjectId; id: number }
+  | {
+      type: "get_workspace_diff_page"
+      workdir_id: WorkspaceId
+      offset: number
+      limit: number
+      context_lines?: number | null
+    }
+  | { type: "audit_worktrees"; project_id: ProjectId }
+  | { type: "prune_orphaned_worktrees"; project_id: ProjectId; dry_run?: boolean }
+  | { type: "import_thread_jsonl"; workdir_id: WorkspaceId; jsonl: string }
+  | { type: "generate_pr_description"; workdir_id: WorkspaceId; task_id: WorkspaceThreadId }
+  | { type: "compact_conversation"; workdir_id: WorkspaceId; task_id: WorkspaceThreadId; before_entry_id: string }
+  | { type: "search_conversations"; workdir_id: WorkspaceId; query: string; task_id?: WorkspaceThreadId | null }
+  | { type: "create_workdir"; project_id: ProjectId; branch_name_hint?: string | null }
+  | { type: "preview_workdir"; project_id: ProjectId; branch_name_hint?: string | null }
+  | { type: "create_workdir_from_branch"; project_id: ProjectId; branch_name: string }
   | { type: "ensure_main_workdir"; project_id: ProjectId }
+  | { type: "open_project_main"; project_id: ProjectId }
   | { type: "open_workdir"; workdir_id: WorkspaceId }
+  | { type: "mark_workdir_read"; workdir_id: WorkspaceId }
+  | { type: "mark_all_read" }
+  | { type: "set_main_pane"; pane: MainPaneSnapshot }
   | { type: "open_workdir_in_ide"; workdir_id: WorkspaceId }
   | { type: "open_workdir_with"; workdir_id: WorkspaceId; target: OpenTarget }
   | { type: "open_workdir_pull_request"; workdir_id: WorkspaceId }
@@ -459,14 +693,41 @@ export type ClientAction =
   | { type: "archive_workdir"; workdir_id: WorkspaceId }
   | { type: "chat_model_changed"; workdir_id: WorkspaceId; task_id: WorkspaceThreadId; model_id: string }
   | { type: "chat_runner_changed"; workdir_id: WorkspaceId; task_id: WorkspaceThreadId; runner: AgentRunnerKind }
-  | { type: "chat_amp_mode_changed"; workdir_id: WorkspaceId; task_id: WorkspaceThreadId; amp_mode: string }
+  | { type: "chat_amp_mode_changed"; workdir_id: WorkspaceId; task_id: WorkspaceThreadId; amp_mode: AmpMode }
+  | {
+      type: "chat_claude_options_changed"
+      workdir_id: WorkspaceId
+      task_id: WorkspaceThreadId
+      max_output_tokens: number | null
+      system_prompt: string | null
+    }
+  | { type: "chat_auto_commit_changed"; workdir_id: WorkspaceId; task_id: WorkspaceThreadId; enabled: boolean }
   | {
       type: "thinking_effort_changed"
       workdir_id: WorkspaceId
       task_id: WorkspaceThreadId
       thinking_effort: ThinkingEffort
     }
+  | { type: "chat_draft_changed"; workdir_id: WorkspaceId; task_id: WorkspaceThreadId; text: string }
+  | { type: "thread_notes_changed"; workdir_id: WorkspaceId; task_id: WorkspaceThreadId; notes: string }
+  | {
+      type: "thread_cwd_subpath_changed"
+      workdir_id: WorkspaceId
+      task_id: WorkspaceThreadId
+      cwd_subpath: string | null
+    }
+  | {
+      type: "add_hunk_comment"
+      workdir_id: WorkspaceId
+      task_id: WorkspaceThreadId
+      entry_id: string
+      path: string
+      side: HunkLineSide
+      line: number
+      text: string
+    }
   | { type: "terminal_command_start"; workdir_id: WorkspaceId; task_id: WorkspaceThreadId; command: string }
+  | { type: "list_terminal_history"; workdir_id: WorkspaceId; limit?: number }
   | {
       type: "send_agent_message"
       workdir_id: WorkspaceId
@@ -475,6 +736,7 @@ export type ClientAction =
       attachments: AttachmentRef[]
       runner?: AgentRunnerKind
       amp_mode?: string
+      expected_rev?: number
     }
   | {
       type: "cancel_and_send_agent_message"
@@ -484,6 +746,7 @@ export type ClientAction =
       attachments: AttachmentRef[]
       runner?: AgentRunnerKind
       amp_mode?: string
+      expected_rev?: number
     }
   | {
       type: "queue_agent_message"
@@ -493,9 +756,23 @@ export type ClientAction =
       attachments: AttachmentRef[]
       runner?: AgentRunnerKind
       amp_mode?: string
+      expected_rev?: number
+    }
+  | {
+      type: "remove_queued_prompt"
+      workdir_id: WorkspaceId
+      task_id: WorkspaceThreadId
+      prompt_id: number
+      expected_rev?: number
+    }
+  | {
+      type: "reorder_queued_prompt"
+      workdir_id: WorkspaceId
+      task_id: WorkspaceThreadId
+      active_id: number
+      over_id: number
+      expected_rev?: number
     }
-  | { type: "remove_queued_prompt"; workdir_id: WorkspaceId; task_id: WorkspaceThreadId; prompt_id: number }
-  | { type: "reorder_queued_prompt"; workdir_id: WorkspaceId; task_id: WorkspaceThreadId; active_id: number; over_id: number }
   | {
       type: "update_queued_prompt"
       workdir_id: WorkspaceId
@@ -505,14 +782,39 @@ export type ClientAction =
       attachments: AttachmentRef[]
       model_id: string
       thinking_effort: ThinkingEffort
+      runner?: AgentRunnerKind | null
+      amp_mode?: string | null
+      expected_rev?: number
     }
   | { type: "workdir_rename_branch"; workdir_id: WorkspaceId; branch_name: string }
+  | { type: "workdir_stash_changes"; workdir_id: WorkspaceId }
+  | { type: "workdir_stash_pop"; workdir_id: WorkspaceId }
+  | { type: "workdir_commit"; workdir_id: WorkspaceId; message: string; paths?: string[] | null }
   | { type: "workdir_ai_rename_branch"; workdir_id: WorkspaceId; task_id: WorkspaceThreadId }
   | { type: "cancel_agent_turn"; workdir_id: WorkspaceId; task_id: WorkspaceThreadId }
+  | {
+      type: "respond_to_tool_approval"
+      workdir_id: WorkspaceId
+      task_id: WorkspaceThreadId
+      call_id: string
+      approved: boolean
+    }
+  | { type: "cancel_and_clear_queue"; workdir_id: WorkspaceId; task_id: WorkspaceThreadId; expected_rev?: number }
+  | {
+      type: "retry_last_turn_with_runner"
+      workdir_id: WorkspaceId
+      task_id: WorkspaceThreadId
+      runner: AgentRunnerKind
+      model_id: string
+      amp_mode?: string
+      cancel_first?: boolean
+    }
   | { type: "create_task"; workdir_id: WorkspaceId }
+  | { type: "duplicate_task"; workdir_id: WorkspaceId; task_id: WorkspaceThreadId; up_to_entry_id?: string | null }
   | { type: "activate_task"; workdir_id: WorkspaceId; task_id: WorkspaceThreadId }
   | { type: "close_task_tab"; workdir_id: WorkspaceId; task_id: WorkspaceThreadId }
   | { type: "delete_task"; workdir_id: WorkspaceId; task_id: WorkspaceThreadId }
+  | { type: "move_thread"; from_workdir_id: WorkspaceId; task_id: WorkspaceThreadId; to_workdir_id: WorkspaceId }
   | { type: "restore_task_tab"; workdir_id: WorkspaceId; task_id: WorkspaceThreadId }
   | {
       type: "reorder_task_tab"
@@ -520,8 +822,13 @@ export type ClientAction =
       task_id: WorkspaceThreadId
       to_index: number
     }
+  | { type: "activate_adjacent_task"; workdir_id: WorkspaceId; direction: TabDirection }
   | { type: "open_button_selection_changed"; selection: string }
   | { type: "sidebar_project_order_changed"; project_ids: ProjectId[] }
+  | { type: "create_project_group"; name: string }
+  | { type: "rename_project_group"; group_id: ProjectGroupId; name: string }
+  | { type: "delete_project_group"; group_id: ProjectGroupId }
+  | { type: "assign_project_to_group"; project_id: ProjectId; group_id: ProjectGroupId | null }
   | { type: "appearance_theme_changed"; theme: AppearanceTheme }
   | { type: "appearance_fonts_changed"; fonts: AppearanceFontsSnapshot }
   | { type: "appearance_global_zoom_changed"; zoom: number }
@@ -529,10 +836,21 @@ export type ClientAction =
   | { type: "amp_enabled_changed"; enabled: boolean }
   | { type: "claude_enabled_changed"; enabled: boolean }
   | { type: "droid_enabled_changed"; enabled: boolean }
+  | { type: "merge_reasoning_entries_changed"; enabled: boolean }
+  | { type: "pause_queue_on_patch_failure_changed"; enabled: boolean }
+  | { type: "item_dedup_window_changed"; window: number }
+  | { type: "auto_retry_on_failure_changed"; retries: number }
+  | { type: "command_output_max_bytes_changed"; max_bytes: number }
+  | { type: "auto_archive_config_changed"; days: number | null }
+  | { type: "runner_prompt_prefix_changed"; runner: AgentRunnerKind; prefix: string }
+  | { type: "runner_prompt_suffix_changed"; runner: AgentRunnerKind; suffix: string }
+  | { type: "model_thinking_effort_cap_changed"; model_id: string; cap?: ThinkingEffort | null }
   | { type: "agent_runner_changed"; runner: AgentRunnerKind }
-  | { type: "agent_amp_mode_changed"; mode: string }
+  | { type: "agent_amp_mode_changed"; mode: AmpMode }
   | { type: "task_prompt_template_changed"; intent_kind: TaskIntentKind; template: string }
   | { type: "system_prompt_template_changed"; kind: SystemTaskKind; template: string }
+  | { type: "export_prompt_templates"; dir: string }
+  | { type: "import_prompt_templates_from_dir"; dir: string; force?: boolean }
   | { type: "codex_check" }
   | { type: "codex_config_tree" }
   | { type: "codex_config_list_dir"; path: string }
@@ -553,19 +871,84 @@ export type ClientAction =
   | { type: "droid_config_list_dir"; path: string }
   | { type: "droid_config_read_file"; path: string }
   | { type: "droid_config_write_file"; path: string; contents: string }
+  | { type: "cancel_request"; request_id: string }
+  | { type: "dismiss_error"; index: number }
 
 export type ServerEvent =
   | { type: "app_changed"; rev: number; snapshot: AppSnapshot }
   | { type: "telegram_pair_ready"; request_id: string; url: string }
   | { type: "task_summaries_changed"; project_id: ProjectId; workdir_id: WorkspaceId; tasks: TaskSummarySnapshot[] }
   | { type: "workdir_tasks_changed"; workdir_id: WorkspaceId; tabs: WorkspaceTabsSnapshot; tasks: ThreadMeta[] }
-  | { type: "conversation_changed"; snapshot: ConversationSnapshot }
+  | { type: "conversation_changed"; snapshot: ConversationSnapshot; follow?: boolean }
   | { type: "toast"; message: string }
   | { type: "project_path_picked"; request_id: string; path: string | null }
   | { type: "add_project_and_open_ready"; request_id: string; project_id: ProjectId; workdir_id: WorkspaceId }
+  | {
+      type: "project_main_opened"
+      request_id: string
+      project_id: ProjectId
+      workdir_id: WorkspaceId
+      task_id: WorkspaceThreadId
+    }
+  | {
+      type: "terminal_history"
+      request_id: string
+      workdir_id: WorkspaceId
+      commands: TerminalHistoryEntry[]
+    }
   | { type: "task_executed"; request_id: string; result: TaskExecuteResult }
   | { type: "feedback_submitted"; request_id: string; result: FeedbackSubmitResult }
   | { type: "codex_check_ready"; request_id: string; ok: boolean; message: string | null }
+  | {
+      type: "workspace_diff_page_ready"
+      request_id: string
+      offset: number
+      total: number
+      diff: WorkspaceDiffSnapshot
+    }
+  | { type: "worktree_audit_ready"; request_id: string; project_id: ProjectId; audit: WorktreeAuditSnapshot }
+  | {
+      type: "orphaned_worktrees_pruned"
+      request_id: string
+      project_id: ProjectId
+      dry_run: boolean
+      pruned: string[]
+    }
+  | {
+      type: "thread_jsonl_imported"
+      request_id: string
+      workdir_id: WorkspaceId
+      task_id: WorkspaceThreadId
+      imported: number
+      errors: JsonlImportLineError[]
+    }
+  | { type: "starred_tasks_listed"; request_id: string; tasks: TaskSummarySnapshot[] }
+  | { type: "task_status_bulk_set"; request_id: string; results: TaskStatusSetResult[] }
+  | {
+      type: "pr_description_ready"
+      request_id: string
+      workdir_id: WorkspaceId
+      task_id: WorkspaceThreadId
+      body: string
+      has_changes: boolean
+    }
+  | {
+      type: "conversation_compacted"
+      request_id: string
+      workdir_id: WorkspaceId
+      task_id: WorkspaceThreadId
+      collapsed_entries: number
+    }
+  | { type: "conversation_search_results"; request_id: string; matches: ConversationSearchMatch[] }
+  | {
+      type: "workdir_preview"
+      request_id: string
+      workdir_name: string
+      branch_name: string
+      worktree_path: string
+    }
+  | { type: "prompt_templates_exported"; request_id: string; dir: string; written: string[] }
+  | { type: "prompt_templates_imported"; request_id: string; imported: string[]; conflicts: string[] }
   | { type: "codex_config_tree_ready"; request_id: string; tree: CodexConfigEntrySnapshot[] }
   | {
       type: "codex_config_list_dir_ready"
@@ -597,7 +980,7 @@ export type WsClientMessage =
   | { type: "ping" }
 
 export type WsServerMessage =
-  | { type: "hello"; protocol_version: number; current_rev: number }
+  | { type: "hello"; protocol_version: number; current_rev: number; capabilities?: string[] }
   | { type: "ack"; request_id: string; rev: number }
   | { type: "event"; rev: number; event: ServerEvent }
   | { type: "error"; request_id: string | null; message: string }
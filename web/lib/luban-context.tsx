@@ -20,13 +20,16 @@ import type {
   FeedbackSubmitAction,
   FeedbackSubmitResult,
   FeedbackType,
+  ProjectAgentEnvVar,
   ProjectId,
   ServerEvent,
   SystemTaskKind,
   TaskExecuteMode,
   TaskExecuteResult,
   TaskIntentKind,
+  TabDirection,
   TaskStatus,
+  TerminalHistoryEntry,
   ThreadMeta,
   ThinkingEffort,
   OpenTarget,
@@ -66,12 +69,18 @@ type LubanContextValue = {
   deleteProject: (projectId: ProjectId) => void
   createWorkdir: (projectId: ProjectId) => void
   ensureMainWorkdir: (projectId: ProjectId) => void
+  openProjectMain: (projectId: ProjectId) => Promise<{ workdirId: WorkspaceId; taskId: WorkspaceThreadId }>
   openWorkdirInIde: (workdirId: WorkspaceId) => void
   openWorkdirWith: (workdirId: WorkspaceId, target: OpenTarget) => void
   openWorkdirPullRequest: (workdirId: WorkspaceId) => void
   openWorkdirPullRequestFailedAction: (workdirId: WorkspaceId) => void
   archiveWorkdir: (workdirId: number) => void
   toggleProjectExpanded: (projectId: ProjectId) => void
+  addProjectSavedCommand: (projectId: ProjectId, label: string, command: string) => void
+  removeProjectSavedCommand: (projectId: ProjectId, id: number) => void
+  setProjectBranchNameTemplate: (projectId: ProjectId, template: string | null) => void
+  setProjectPullRequestTarget: (projectId: ProjectId, remote: string | null, baseBranch: string | null) => void
+  setProjectAgentEnv: (projectId: ProjectId, env: ProjectAgentEnvVar[]) => void
 
   executeTask: (
     prompt: string,
@@ -81,6 +90,7 @@ type LubanContextValue = {
   ) => Promise<TaskExecuteResult>
   setTaskStarred: (workdirId: WorkspaceId, taskId: WorkspaceThreadId, starred: boolean) => void
   setTaskStatus: (workdirId: WorkspaceId, taskId: WorkspaceThreadId, taskStatus: TaskStatus) => void
+  renameTask: (workdirId: WorkspaceId, taskId: WorkspaceThreadId, title: string) => void
   submitFeedback: (args: {
     title: string
     body: string
@@ -90,12 +100,16 @@ type LubanContextValue = {
   }) => Promise<FeedbackSubmitResult>
 
   openWorkdir: (workdirId: WorkspaceId) => Promise<void>
+  markWorkdirRead: (workdirId: WorkspaceId) => void
+  markAllRead: () => void
   activateTask: (taskId: number) => Promise<void>
   loadConversationBefore: (workdirId: WorkspaceId, taskId: WorkspaceThreadId, before: number) => Promise<void>
   createTask: () => void
   closeTaskTab: (taskId: number) => Promise<void>
   deleteTask: (taskId: number) => Promise<void>
+  moveTask: (taskId: number, toWorkdirId: WorkspaceId) => Promise<void>
   restoreTaskTab: (taskId: number) => Promise<void>
+  activateAdjacentTask: (direction: TabDirection) => Promise<void>
 
   sendAgentMessage: (
     text: string,
@@ -115,6 +129,7 @@ type LubanContextValue = {
     runConfig?: { runner?: AgentRunnerKind | null; amp_mode?: string | null },
   ) => void
   runTerminalCommand: (command: string) => void
+  listTerminalHistory: (workdirId: WorkspaceId, limit?: number) => Promise<TerminalHistoryEntry[]>
   removeQueuedPrompt: (workspaceId: WorkspaceId, threadId: WorkspaceThreadId, promptId: number) => void
   reorderQueuedPrompt: (
     workspaceId: WorkspaceId,
@@ -129,6 +144,7 @@ type LubanContextValue = {
     args: { text: string; attachments: AttachmentRef[]; runConfig: AgentRunConfigSnapshot },
   ) => void
   cancelAgentTurn: () => void
+  respondToToolApproval: (callId: string, approved: boolean) => void
   cancelAndSendAgentMessage: (
     text: string,
     attachments?: AttachmentRef[],
@@ -137,11 +153,20 @@ type LubanContextValue = {
 
   renameWorkdirBranch: (workdirId: WorkspaceId, branchName: string) => void
   aiRenameWorkdirBranch: (workdirId: WorkspaceId, taskId: WorkspaceThreadId) => void
+  stashWorkdirChanges: (workdirId: WorkspaceId) => void
+  popWorkdirStash: (workdirId: WorkspaceId) => void
+  commitWorkdirChanges: (workdirId: WorkspaceId, message: string, paths?: string[]) => void
 
   setChatModel: (workspaceId: WorkspaceId, threadId: WorkspaceThreadId, modelId: string) => void
   setThinkingEffort: (workspaceId: WorkspaceId, threadId: WorkspaceThreadId, effort: ThinkingEffort) => void
   setChatRunner: (workspaceId: WorkspaceId, threadId: WorkspaceThreadId, runner: AgentRunnerKind) => void
   setChatAmpMode: (workspaceId: WorkspaceId, threadId: WorkspaceThreadId, ampMode: string) => void
+  setChatClaudeOptions: (
+    workspaceId: WorkspaceId,
+    threadId: WorkspaceThreadId,
+    options: { maxOutputTokens: number | null; systemPrompt: string | null },
+  ) => void
+  setChatAutoCommit: (workspaceId: WorkspaceId, threadId: WorkspaceThreadId, enabled: boolean) => void
   setAppearanceTheme: (theme: AppearanceTheme) => void
   setAppearanceFonts: (fonts: AppearanceFontsSnapshot) => void
   setGlobalZoom: (zoom: number) => void
@@ -157,7 +182,9 @@ type LubanContextValue = {
   setTelegramBotToken: (token: string) => void
   clearTelegramBotToken: () => void
   startTelegramPairing: () => Promise<string>
-  unpairTelegram: () => void
+  unpairTelegram: (chatId?: number) => void
+  setWebhookConfig: (url: string, secret?: string | null) => void
+  clearWebhookConfig: () => void
   setTaskPromptTemplate: (intentKind: TaskIntentKind, template: string) => void
   setSystemPromptTemplate: (kind: SystemTaskKind, template: string) => void
   checkCodex: () => Promise<{ ok: boolean; message: string | null }>
@@ -407,38 +434,56 @@ export function LubanProvider({ children }: { children: React.ReactNode }) {
     deleteProject: actions.deleteProject,
     createWorkdir: actions.createWorkdir,
     ensureMainWorkdir: actions.ensureMainWorkdir,
+    openProjectMain: actions.openProjectMain,
     openWorkdirInIde: actions.openWorkdirInIde,
     openWorkdirWith: actions.openWorkdirWith,
     openWorkdirPullRequest: actions.openWorkdirPullRequest,
     openWorkdirPullRequestFailedAction: actions.openWorkdirPullRequestFailedAction,
     archiveWorkdir: actions.archiveWorkdir,
     toggleProjectExpanded: actions.toggleProjectExpanded,
+    addProjectSavedCommand: actions.addProjectSavedCommand,
+    removeProjectSavedCommand: actions.removeProjectSavedCommand,
+    setProjectBranchNameTemplate: actions.setProjectBranchNameTemplate,
+    setProjectPullRequestTarget: actions.setProjectPullRequestTarget,
+    setProjectAgentEnv: actions.setProjectAgentEnv,
     executeTask: actions.executeTask,
     setTaskStarred: actions.setTaskStarred,
     setTaskStatus: actions.setTaskStatus,
+    renameTask: actions.renameTask,
     submitFeedback: actions.submitFeedback,
     openWorkdir: actions.openWorkdir,
+    markWorkdirRead: actions.markWorkdirRead,
+    markAllRead: actions.markAllRead,
     activateTask: actions.activateTask,
     loadConversationBefore: actions.loadConversationBefore,
     createTask: actions.createTask,
     closeTaskTab: actions.closeTaskTab,
     deleteTask: actions.deleteTask,
+    moveTask: actions.moveTask,
     restoreTaskTab: actions.restoreTaskTab,
+    activateAdjacentTask: actions.activateAdjacentTask,
     sendAgentMessage: actions.sendAgentMessage,
     queueAgentMessage: actions.queueAgentMessage,
     sendAgentMessageTo: actions.sendAgentMessageTo,
     runTerminalCommand: actions.runTerminalCommand,
+    listTerminalHistory: actions.listTerminalHistory,
     removeQueuedPrompt: actions.removeQueuedPrompt,
     reorderQueuedPrompt: actions.reorderQueuedPrompt,
     updateQueuedPrompt: actions.updateQueuedPrompt,
     cancelAgentTurn: actions.cancelAgentTurn,
+    respondToToolApproval: actions.respondToToolApproval,
     cancelAndSendAgentMessage: actions.cancelAndSendAgentMessage,
     renameWorkdirBranch: actions.renameWorkdirBranch,
     aiRenameWorkdirBranch: actions.aiRenameWorkdirBranch,
+    stashWorkdirChanges: actions.stashWorkdirChanges,
+    popWorkdirStash: actions.popWorkdirStash,
+    commitWorkdirChanges: actions.commitWorkdirChanges,
     setChatModel: actions.setChatModel,
     setThinkingEffort: actions.setThinkingEffort,
     setChatRunner: actions.setChatRunner,
     setChatAmpMode: actions.setChatAmpMode,
+    setChatClaudeOptions: actions.setChatClaudeOptions,
+    setChatAutoCommit: actions.setChatAutoCommit,
     setAppearanceTheme: actions.setAppearanceTheme,
     setAppearanceFonts: actions.setAppearanceFonts,
     setGlobalZoom: actions.setGlobalZoom,
@@ -454,6 +499,8 @@ export function LubanProvider({ children }: { children: React.ReactNode }) {
     clearTelegramBotToken: actions.clearTelegramBotToken,
     startTelegramPairing: actions.startTelegramPairing,
     unpairTelegram: actions.unpairTelegram,
+    setWebhookConfig: actions.setWebhookConfig,
+    clearWebhookConfig: actions.clearWebhookConfig,
     setTaskPromptTemplate: actions.setTaskPromptTemplate,
     setSystemPromptTemplate: actions.setSystemPromptTemplate,
     checkCodex: actions.checkCodex,
@@ -1,4 +1,5 @@
 import type {
+  AgentItemKind,
   AppSnapshot,
   AttachmentKind,
   AttachmentRef,
@@ -49,12 +50,14 @@ export async function fetchThreads(workspaceId: number): Promise<ThreadsSnapshot
 export async function fetchConversation(
   workspaceId: number,
   threadId: number,
-  args: { before?: number; limit?: number } = {},
+  args: { before?: number; limit?: number; kinds?: AgentItemKind[]; includeUserMessages?: boolean } = {},
 ): Promise<ConversationSnapshot> {
   if (isMockMode()) return await mockFetchConversation(workspaceId, threadId, args)
   const limit = args.limit ?? 2000
   const params = new URLSearchParams({ limit: String(limit) })
   if (args.before != null) params.set("before", String(args.before))
+  if (args.kinds != null) params.set("kinds", args.kinds.join(","))
+  if (args.includeUserMessages) params.set("include_user_messages", "true")
   const res = await fetch(`/api/workdirs/${workspaceId}/conversations/${threadId}?${params.toString()}`)
   if (!res.ok)
     throw new Error(
@@ -63,6 +66,10 @@ export async function fetchConversation(
   return (await res.json()) as ConversationSnapshot
 }
 
+export function conversationTranscriptUrl(workspaceId: number, threadId: number): string {
+  return `/api/workdirs/${workspaceId}/conversations/${threadId}/transcript.md`
+}
+
 export async function uploadAttachment(args: {
   workspaceId: number
   file: File
@@ -192,6 +192,8 @@ export function useLubanTransport(args: {
           if (
             event.type === "project_path_picked" ||
             event.type === "add_project_and_open_ready" ||
+            event.type === "project_main_opened" ||
+            event.type === "terminal_history" ||
             event.type === "task_executed" ||
             event.type === "feedback_submitted" ||
             event.type === "telegram_pair_ready" ||
@@ -214,7 +216,19 @@ export function useLubanTransport(args: {
             event.type === "droid_config_tree_ready" ||
             event.type === "droid_config_list_dir_ready" ||
             event.type === "droid_config_file_ready" ||
-            event.type === "droid_config_file_saved"
+            event.type === "droid_config_file_saved" ||
+            event.type === "workspace_diff_page_ready" ||
+            event.type === "worktree_audit_ready" ||
+            event.type === "orphaned_worktrees_pruned" ||
+            event.type === "thread_jsonl_imported" ||
+            event.type === "starred_tasks_listed" ||
+            event.type === "task_status_bulk_set" ||
+            event.type === "pr_description_ready" ||
+            event.type === "conversation_compacted" ||
+            event.type === "prompt_templates_exported" ||
+            event.type === "prompt_templates_imported" ||
+            event.type === "conversation_search_results" ||
+            event.type === "workdir_preview"
           ) {
             const pending = pendingResponsesRef.current.get(event.request_id)
             if (pending) {
@@ -222,6 +236,9 @@ export function useLubanTransport(args: {
               if (event.type === "project_path_picked") pending.resolve(event.path)
               if (event.type === "add_project_and_open_ready")
                 pending.resolve({ projectId: event.project_id, workdirId: event.workdir_id })
+              if (event.type === "project_main_opened")
+                pending.resolve({ workdirId: event.workdir_id, taskId: event.task_id })
+              if (event.type === "terminal_history") pending.resolve(event.commands)
               if (event.type === "task_executed") pending.resolve(event.result)
               if (event.type === "feedback_submitted") pending.resolve(event.result)
               if (event.type === "telegram_pair_ready") pending.resolve(event.url)
@@ -248,6 +265,23 @@ export function useLubanTransport(args: {
               if (event.type === "droid_config_list_dir_ready")
                 pending.resolve({ path: event.path, entries: event.entries })
               if (event.type === "droid_config_file_ready") pending.resolve(event.contents)
+              if (event.type === "workspace_diff_page_ready")
+                pending.resolve({ offset: event.offset, total: event.total, diff: event.diff })
+              if (event.type === "worktree_audit_ready") pending.resolve(event.audit)
+              if (event.type === "orphaned_worktrees_pruned") pending.resolve(event.pruned)
+              if (event.type === "thread_jsonl_imported")
+                pending.resolve({ taskId: event.task_id, imported: event.imported, errors: event.errors })
+              if (event.type === "starred_tasks_listed") pending.resolve(event.tasks)
+              if (event.type === "task_status_bulk_set") pending.resolve(event.results)
+              if (event.type === "pr_description_ready")
+                pending.resolve({ body: event.body, hasChanges: event.has_changes })
+              if (event.type === "conversation_compacted") pending.resolve(event.collapsed_entries)
+              if (event.type === "prompt_templates_exported") pending.resolve(event.written)
+              if (event.type === "prompt_templates_imported")
+                pending.resolve({ imported: event.imported, conflicts: event.conflicts })
+              if (event.type === "conversation_search_results") pending.resolve(event.matches)
+              if (event.type === "workdir_preview")
+                pending.resolve({ branchName: event.branch_name, worktreePath: event.worktree_path })
               if (event.type === "droid_config_file_saved") pending.resolve(null)
             }
             return
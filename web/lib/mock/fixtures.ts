@@ -442,6 +442,11 @@ export function defaultMockFixtures(): MockFixtures {
         has_token: false,
         config_rev: 0,
       },
+      webhook: {
+        enabled: false,
+        has_secret: false,
+        config_rev: 0,
+      },
     },
   }
 
@@ -24,6 +24,7 @@ import type {
   TaskExecuteMode,
   TaskExecuteResult,
   TasksSnapshot,
+  TerminalHistoryEntry,
   TaskSummarySnapshot,
   ThreadsSnapshot,
   WorkspaceChangesSnapshot,
@@ -514,7 +515,7 @@ export function mockDispatchAction(args: { action: ClientAction; onEvent: (event
         enabled: false,
         has_token: false,
         bot_username: undefined,
-        paired_chat_id: undefined,
+        paired_chat_ids: [],
         config_rev: (prev.config_rev ?? 0) + 1,
         last_error: undefined,
       },
@@ -529,7 +530,39 @@ export function mockDispatchAction(args: { action: ClientAction; onEvent: (event
       ...(state.app.integrations ?? { telegram: prev }),
       telegram: {
         ...prev,
-        paired_chat_id: undefined,
+        paired_chat_ids: a.chat_id == null ? [] : (prev.paired_chat_ids ?? []).filter((id) => id !== a.chat_id),
+        config_rev: (prev.config_rev ?? 0) + 1,
+      },
+    }
+    emitAppChanged({ state, onEvent: args.onEvent })
+    return
+  }
+
+  if (a.type === "webhook_config_set") {
+    const prev = state.app.integrations?.webhook ?? { enabled: false, has_secret: false, config_rev: 0 }
+    const hasSecret = a.secret == null ? prev.has_secret : a.secret.trim().length > 0
+    state.app.integrations = {
+      ...state.app.integrations,
+      webhook: {
+        ...prev,
+        enabled: true,
+        url: a.url.trim(),
+        has_secret: hasSecret,
+        config_rev: (prev.config_rev ?? 0) + 1,
+        last_error: undefined,
+      },
+    }
+    emitAppChanged({ state, onEvent: args.onEvent })
+    return
+  }
+
+  if (a.type === "webhook_config_clear") {
+    const prev = state.app.integrations?.webhook ?? { enabled: false, has_secret: false, config_rev: 0 }
+    state.app.integrations = {
+      ...state.app.integrations,
+      webhook: {
+        enabled: false,
+        has_secret: false,
         config_rev: (prev.config_rev ?? 0) + 1,
       },
     }
@@ -565,6 +598,60 @@ export function mockDispatchAction(args: { action: ClientAction; onEvent: (event
     return
   }
 
+  if (a.type === "add_project_saved_command" || a.type === "remove_project_saved_command") {
+    const found = findProject(state.app, a.project_id)
+    if (!found) return
+    const saved = found.project.saved_commands ?? []
+    if (a.type === "remove_project_saved_command") {
+      found.project.saved_commands = saved.filter((c) => c.id !== a.id)
+    } else {
+      const label = a.label.trim()
+      const command = a.command.trim()
+      if (!label || !command) return
+      let unique = label
+      for (let i = 2; saved.some((c) => c.label === unique); i += 1) unique = `${label}-${i}`
+      const id = saved.reduce((max, c) => Math.max(max, c.id), 0) + 1
+      found.project.saved_commands = [...saved, { id, label: unique, command }]
+    }
+    emitAppChanged({ state, onEvent: args.onEvent })
+    return
+  }
+
+  if (a.type === "set_project_branch_name_template") {
+    const found = findProject(state.app, a.project_id)
+    if (!found) return
+    found.project.branch_name_template = a.template?.trim() || null
+    emitAppChanged({ state, onEvent: args.onEvent })
+    return
+  }
+
+  if (a.type === "set_project_pull_request_target") {
+    const found = findProject(state.app, a.project_id)
+    if (!found) return
+    found.project.pr_remote = a.remote?.trim() || null
+    found.project.pr_base_branch = a.base_branch?.trim() || null
+    emitAppChanged({ state, onEvent: args.onEvent })
+    return
+  }
+
+  if (a.type === "set_project_agent_env") {
+    const found = findProject(state.app, a.project_id)
+    if (!found) return
+    const previous = found.project.agent_env ?? []
+    let next: { key: string; has_value: boolean }[] = []
+    for (const v of a.env) {
+      const key = v.key.trim()
+      if (!key || key.includes("=")) continue
+      const has_value =
+        v.value != null ? v.value.length > 0 : previous.find((p) => p.key === key)?.has_value
+      if (has_value == null) continue
+      next = [...next.filter((p) => p.key !== key), { key, has_value }]
+    }
+    found.project.agent_env = next
+    emitAppChanged({ state, onEvent: args.onEvent })
+    return
+  }
+
   if (a.type === "create_workdir") {
     const found = findProject(state.app, a.project_id)
     if (!found) return
@@ -627,6 +714,19 @@ export function mockDispatchAction(args: { action: ClientAction; onEvent: (event
     return
   }
 
+  if (a.type === "mark_workdir_read" || a.type === "mark_all_read") {
+    for (const project of state.app.projects) {
+      for (const workdir of project.workdirs) {
+        if (!workdir.has_unread_completion) continue
+        if (a.type === "mark_workdir_read" && workdir.id !== a.workdir_id) continue
+        workdir.has_unread_completion = false
+        emitTaskSummariesChanged({ state, workdirId: workdir.id, onEvent: args.onEvent })
+      }
+    }
+    emitAppChanged({ state, onEvent: args.onEvent })
+    return
+  }
+
   if (a.type === "open_workdir") {
     setActiveWorkdirTask(state, { workdirId: a.workdir_id, taskId: null })
     const threads = state.threadsByWorkdir.get(a.workdir_id) ?? null
@@ -659,6 +759,26 @@ export function mockDispatchAction(args: { action: ClientAction; onEvent: (event
     return
   }
 
+  if (a.type === "activate_adjacent_task") {
+    const snap = ensureThreadsSnapshot(state, a.workdir_id)
+    const open = snap.tabs.open_tabs
+    if (open.length < 2) return
+    const idx = open.indexOf(snap.tabs.active_tab)
+    const next =
+      idx < 0
+        ? a.direction === "next"
+          ? 0
+          : open.length - 1
+        : (idx + (a.direction === "next" ? 1 : open.length - 1)) % open.length
+    const taskId = open[next]!
+    snap.tabs.active_tab = taskId
+    setActiveWorkdirTask(state, { workdirId: a.workdir_id, taskId })
+    emitWorkdirTasksChanged({ state, workdirId: a.workdir_id, onEvent: args.onEvent })
+    emitAppChanged({ state, onEvent: args.onEvent })
+    emitConversationChanged({ state, workdirId: a.workdir_id, taskId, onEvent: args.onEvent })
+    return
+  }
+
   if (a.type === "delete_task") {
     const snap = ensureThreadsSnapshot(state, a.workdir_id)
     const id = a.task_id
@@ -677,6 +797,33 @@ export function mockDispatchAction(args: { action: ClientAction; onEvent: (event
     return
   }
 
+  if (a.type === "move_thread") {
+    const from = ensureThreadsSnapshot(state, a.from_workdir_id)
+    const to = ensureThreadsSnapshot(state, a.to_workdir_id)
+    const id = a.task_id
+    const task = from.tasks.find((t) => t.task_id === id) ?? null
+    if (!task || a.from_workdir_id === a.to_workdir_id) return
+    from.tabs.open_tabs = from.tabs.open_tabs.filter((x) => x !== id)
+    from.tabs.archived_tabs = from.tabs.archived_tabs.filter((x) => x !== id)
+    from.tasks = from.tasks.filter((t) => t.task_id !== id)
+    if (from.tabs.active_tab === id) {
+      from.tabs.active_tab = from.tabs.open_tabs[0] ?? 0
+    }
+    to.tasks = [task, ...to.tasks]
+    if (!to.tabs.open_tabs.includes(id)) to.tabs.open_tabs = [...to.tabs.open_tabs, id]
+    const convo = state.conversationsByWorkdirTask.get(workdirTaskKey(a.from_workdir_id, id)) ?? null
+    if (convo) {
+      state.conversationsByWorkdirTask.delete(workdirTaskKey(a.from_workdir_id, id))
+      state.conversationsByWorkdirTask.set(workdirTaskKey(a.to_workdir_id, id), { ...convo, workdir_id: a.to_workdir_id })
+    }
+    for (const workdirId of [a.from_workdir_id, a.to_workdir_id]) {
+      emitWorkdirTasksChanged({ state, workdirId, onEvent: args.onEvent })
+      emitTaskSummariesChanged({ state, workdirId, onEvent: args.onEvent })
+    }
+    emitAppChanged({ state, onEvent: args.onEvent })
+    return
+  }
+
   if (a.type === "close_task_tab" || a.type === "restore_task_tab") {
     const snap = ensureThreadsSnapshot(state, a.workdir_id)
     const id = a.task_id
@@ -898,6 +1045,7 @@ export function mockDispatchAction(args: { action: ClientAction; onEvent: (event
     if (!convo) return
     const pending = (convo.pending_prompts ?? []).map((p) => {
       if (p.id !== a.prompt_id) return p
+      const runner = a.runner ?? p.run_config.runner
       return {
         ...p,
         text: a.text,
@@ -906,6 +1054,8 @@ export function mockDispatchAction(args: { action: ClientAction; onEvent: (event
           ...p.run_config,
           model_id: a.model_id ?? p.run_config.model_id,
           thinking_effort: a.thinking_effort ?? p.run_config.thinking_effort,
+          runner,
+          amp_mode: runner === "amp" ? (a.amp_mode ?? p.run_config.amp_mode ?? null) : null,
         },
       }
     })
@@ -1010,6 +1160,25 @@ export function mockDispatchAction(args: { action: ClientAction; onEvent: (event
     return
   }
 
+  if (a.type === "rename_thread") {
+    const title = a.title.trim()
+    if (!title) {
+      args.onEvent({ type: "toast", message: "Thread title cannot be empty" })
+      return
+    }
+    const snap = state.threadsByWorkdir.get(a.workdir_id) ?? null
+    if (snap) {
+      snap.tasks = snap.tasks.map((t) => (t.task_id === a.task_id ? { ...t, title } : t))
+    }
+    const key = workdirTaskKey(a.workdir_id, a.task_id)
+    const convo = state.conversationsByWorkdirTask.get(key) ?? null
+    if (convo) state.conversationsByWorkdirTask.set(key, { ...convo, title })
+    emitWorkdirTasksChanged({ state, workdirId: a.workdir_id, onEvent: args.onEvent })
+    emitTaskSummariesChanged({ state, workdirId: a.workdir_id, onEvent: args.onEvent })
+    emitConversationChanged({ state, workdirId: a.workdir_id, taskId: a.task_id, onEvent: args.onEvent })
+    return
+  }
+
   if (a.type === "task_star_set") {
     const key = workdirTaskKey(a.workdir_id, a.task_id)
     if (a.starred) {
@@ -1093,6 +1262,37 @@ export async function mockRequest<T>(action: ClientAction): Promise<T> {
     return { projectId, workdirId } as unknown as T
   }
 
+  if (action.type === "open_project_main") {
+    const workdirId = ensureMainWorkdir(state, action.project_id)
+    const snap = ensureThreadsSnapshot(state, workdirId)
+    const taskId =
+      snap.tabs.open_tabs.find((id) => id === snap.tabs.active_tab) ??
+      snap.tabs.open_tabs[0] ??
+      createTaskInWorkdir(state, workdirId, "New task")
+    setActiveWorkdirTask(state, { workdirId, taskId })
+    return { workdirId, taskId } as unknown as T
+  }
+
+  if (action.type === "list_terminal_history") {
+    const prefix = `${action.workdir_id}:`
+    const started: TerminalHistoryEntry[] = []
+    for (const [key, convo] of state.conversationsByWorkdirTask) {
+      if (!key.startsWith(prefix)) continue
+      for (const entry of convo.entries) {
+        if (entry.type !== "user_event" || entry.event.type !== "terminal_command_started") continue
+        started.push({ command: entry.event.command, started_at_unix_ms: entry.created_at_unix_ms })
+      }
+    }
+    started.sort((a, b) => b.started_at_unix_ms - a.started_at_unix_ms)
+    const commands: TerminalHistoryEntry[] = []
+    for (const entry of started) {
+      if (commands.length >= (action.limit ?? 50)) break
+      if (commands[commands.length - 1]?.command === entry.command) continue
+      commands.push(entry)
+    }
+    return commands as unknown as T
+  }
+
   if (action.type === "task_execute") {
     if (action.workdir_id == null) throw new Error("mock: task_execute requires workdir_id")
     const workdirId = action.workdir_id